@@ -0,0 +1,226 @@
+//! `/api/chat` requests, responses and stream events.
+
+use serde::{Deserialize, Serialize};
+
+/// Chat message in conversation history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// How each search hit is turned into context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ContextMode {
+    /// The hit text itself, truncated.
+    #[default]
+    Excerpt,
+    /// The parent section chunk of the hit.
+    Section,
+    /// The hit plus surrounding paragraph chunks.
+    Window,
+}
+
+/// How a chat answer is produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ChatMode {
+    /// Generate with the configured LLM; extractive when none is configured.
+    #[default]
+    Auto,
+    /// Quote the best-matching sentences of the retrieved context, without
+    /// an LLM.
+    Extractive,
+}
+
+/// Incoming chat request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChatRequest {
+    pub message: String,
+    #[serde(default, rename = "conversationHistory")]
+    pub conversation_history: Vec<ChatMessage>,
+    pub model: Option<String>,
+    #[serde(default = "default_use_rag", rename = "useRAG")]
+    pub use_rag: bool,
+    #[serde(default = "default_top_k", rename = "topK")]
+    pub top_k: usize,
+    #[serde(default = "default_min_score", rename = "minScore")]
+    pub min_score: f64,
+    pub temperature: Option<f64>,
+    #[serde(rename = "maxTokens")]
+    pub max_tokens: Option<usize>,
+    #[serde(rename = "consentSessionId")]
+    pub consent_session_id: Option<String>,
+    /// How search hits are expanded into context (excerpt | section | window).
+    #[serde(default, rename = "contextMode", alias = "context_mode")]
+    pub context_mode: ContextMode,
+    /// `auto` (default) or `extractive`.
+    #[serde(default)]
+    pub mode: ChatMode,
+    /// Only documents in this collection are used as context.
+    #[serde(default, rename = "collectionId", alias = "collection_id", skip_serializing_if = "Option::is_none")]
+    pub collection_id: Option<i64>,
+    /// Search with a tool while answering (`true`) or send the context up
+    /// front (`false`); unset follows the server's `toolRetrieval`.
+    #[serde(default, rename = "useTools", skip_serializing_if = "Option::is_none")]
+    pub use_tools: Option<bool>,
+    /// Conversation this turn belongs to; PII keeps its token across the
+    /// turns of one session.
+    #[serde(default, rename = "sessionId", skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Replace PII with tokens before anything is sent to the provider.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub anonymize: bool,
+}
+
+fn default_use_rag() -> bool {
+    true
+}
+fn default_top_k() -> usize {
+    5
+}
+fn default_min_score() -> f64 {
+    0.01
+}
+
+impl ChatRequest {
+    /// A request for `message` with the server's defaults.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            conversation_history: Vec::new(),
+            model: None,
+            use_rag: default_use_rag(),
+            top_k: default_top_k(),
+            min_score: default_min_score(),
+            temperature: None,
+            max_tokens: None,
+            consent_session_id: None,
+            context_mode: ContextMode::default(),
+            mode: ChatMode::default(),
+            collection_id: None,
+            use_tools: None,
+            session_id: None,
+            anonymize: false,
+        }
+    }
+}
+
+/// Non-streaming chat response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChatResponse {
+    pub message: String,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<Vec<ChatContext>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "tokensUsed")]
+    pub tokens_used: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+    /// The message quotes the context (marked `[n]`) instead of being
+    /// generated; `model` is then `extractive`.
+    #[serde(default)]
+    pub extractive: bool,
+    /// Token usage as the provider reported it, when it did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ProviderUsage>,
+}
+
+/// Token usage reported by an LLM provider for one request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderUsage {
+    /// All prompt tokens, cached ones included.
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    /// Prompt tokens read from the provider's prompt cache.
+    pub cached_tokens: usize,
+    /// Prompt tokens written to the prompt cache (Anthropic only).
+    pub cache_write_tokens: usize,
+}
+
+/// RAG context entry (search result excerpt).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChatContext {
+    pub id: i64,
+    pub excerpt: String,
+    pub score: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    /// The context mode that produced this entry.
+    #[serde(default)]
+    pub mode: ContextMode,
+    /// Hit chunks collapsed into this entry (more than one when hits overlapped).
+    #[serde(default, rename = "chunkIds", skip_serializing_if = "Vec::is_empty")]
+    pub chunk_ids: Vec<i64>,
+    /// Characters of the expanded context before compression and budgeting.
+    #[serde(default, rename = "originalChars")]
+    pub original_chars: usize,
+    /// Characters of `excerpt`, as sent to the LLM.
+    #[serde(default, rename = "sentChars")]
+    pub sent_chars: usize,
+}
+
+/// SSE stream event types. Each is sent as the JSON `data` of one event;
+/// the stream ends with `data: [DONE]` after `done`, or after `error`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum StreamEvent {
+    #[serde(rename = "context")]
+    Context { context: Vec<ChatContext> },
+    #[serde(rename = "token")]
+    Token { content: String },
+    /// The model called the search tool; its results follow as a
+    /// `context` event.
+    #[serde(rename = "tool_call")]
+    ToolCall {
+        query: String,
+        #[serde(rename = "topK")]
+        top_k: usize,
+        /// 1-based tool round.
+        round: usize,
+    },
+    #[serde(rename = "done")]
+    Done {
+        model: String,
+        #[serde(rename = "tokensUsed")]
+        tokens_used: usize,
+        duration: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        usage: Option<ProviderUsage>,
+    },
+    #[serde(rename = "error")]
+    Error { error: String },
+}
+
+/// Chat status response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChatStatus {
+    #[serde(rename = "llmAvailable")]
+    pub llm_available: bool,
+    #[serde(rename = "llmProvider")]
+    pub llm_provider: Option<String>,
+    #[serde(rename = "vectorStoreAvailable")]
+    pub vector_store_available: bool,
+    #[serde(rename = "defaultModel")]
+    pub default_model: Option<String>,
+    #[serde(rename = "availableModels")]
+    pub available_models: Vec<String>,
+    #[serde(rename = "gpuAvailable")]
+    pub gpu_available: bool,
+    #[serde(rename = "gpuStatus")]
+    pub gpu_status: String,
+    #[serde(rename = "ollamaAvailable")]
+    pub ollama_available: bool,
+}
@@ -0,0 +1,112 @@
+//! Connector sync and browser connector status.
+
+use serde::{Deserialize, Serialize};
+
+/// Sync run status.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RunStatus {
+    pub running: bool,
+    pub output: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "lastRun")]
+    pub last_run: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "exitCode")]
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "connectorId")]
+    pub connector_id: Option<String>,
+    /// What a feed sync did with each feed, for `rss` connectors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feeds: Vec<FeedSyncCounts>,
+}
+
+/// What one sync did with one feed's entries.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct FeedSyncCounts {
+    pub url: String,
+    /// Entries added as new documents.
+    pub new: usize,
+    /// Entries that changed, their documents replaced.
+    pub updated: usize,
+    /// Entries already stored as they are.
+    pub unchanged: usize,
+    /// The feed answered 304 Not Modified; nothing was read.
+    #[serde(default)]
+    pub not_modified: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Browser runtime status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BrowserStatus {
+    pub running: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "activeUrl")]
+    pub active_url: Option<String>,
+    #[serde(rename = "connectedSites")]
+    pub connected_sites: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "launchedAt")]
+    pub launched_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "memoryUsageMB")]
+    pub memory_usage_mb: Option<f64>,
+    #[serde(rename = "captureStats")]
+    pub capture_stats: CaptureStats,
+    pub vnc: VncInfo,
+    /// The companion extension's relay socket.
+    #[serde(default)]
+    pub extension: ExtensionStatus,
+}
+
+/// Whether the companion extension holds `/api/browser-connector/ws` open.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionStatus {
+    pub connected: bool,
+    /// Version the extension sent in its `hello`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// RFC 3339.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connected_at: Option<String>,
+    /// RFC 3339 time of the last frame from the extension.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen_at: Option<String>,
+}
+
+/// Capture statistics for the current session.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CaptureStats {
+    #[serde(rename = "totalCaptures")]
+    pub total_captures: u64,
+    #[serde(rename = "conversationsTracked")]
+    pub conversations_tracked: usize,
+    /// Resent messages skipped because their role and content were
+    /// already captured under another ID.
+    #[serde(rename = "duplicatesSkipped")]
+    pub duplicates_skipped: u64,
+    /// Bytes freed by retention cleanups, over all sessions.
+    #[serde(rename = "reclaimedBytes", default)]
+    pub reclaimed_bytes: u64,
+    /// RFC 3339 time of the last retention cleanup.
+    #[serde(rename = "lastCleanupAt", default, skip_serializing_if = "Option::is_none")]
+    pub last_cleanup_at: Option<String>,
+}
+
+/// VNC connection info.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VncInfo {
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "wsPort")]
+    pub ws_port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "vncPort")]
+    pub vnc_port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
@@ -0,0 +1,395 @@
+//! Documents, chunks and the `/api/vector-store` document endpoints.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A document row from the database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Document {
+    pub id: i64,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    pub created_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<i64>,
+}
+
+impl Document {
+    /// Parse metadata JSON into a map.
+    pub fn metadata_map(&self) -> HashMap<String, serde_json::Value> {
+        match &self.metadata {
+            Some(v) => serde_json::from_value(v.clone()).unwrap_or_default(),
+            None => HashMap::new(),
+        }
+    }
+}
+
+/// A chunk row from the database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Chunk {
+    pub id: i64,
+    pub doc_id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_chunk_id: Option<i64>,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enriched_text: Option<String>,
+    pub chunk_index: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub char_start: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub char_end: Option<i32>,
+    pub level: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: i64,
+}
+
+/// `GET /api/vector-store/status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StatusResponse {
+    #[cfg_attr(feature = "openapi", schema(example = "healthy"))]
+    pub status: String,
+    pub service: String,
+    pub documents: i64,
+    pub chunks: i64,
+    pub embeddings: i64,
+}
+
+/// `POST /api/vector-store/documents`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AddDocumentRequest {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    /// Defaults to the SHA-256 of `text`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// What to do when a document with the same content hash exists. Only
+    /// used by the single-document endpoint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_duplicate: Option<OnDuplicate>,
+}
+
+impl AddDocumentRequest {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            metadata: None,
+            content_hash: None,
+            on_duplicate: None,
+        }
+    }
+}
+
+/// Duplicate policy of `POST /api/vector-store/documents`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum OnDuplicate {
+    /// Answer 409.
+    #[default]
+    Error,
+    /// Answer 200 with the existing document's id and status `exists`.
+    ReturnExisting,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AddDocumentResponse {
+    pub id: i64,
+    pub content_hash: String,
+    /// `added`, or `exists` when an existing document was returned.
+    #[cfg_attr(feature = "openapi", schema(example = "added"))]
+    pub status: String,
+}
+
+/// `GET /api/vector-store/documents`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentListResponse {
+    pub documents: Vec<Document>,
+    pub total: i64,
+    pub page: usize,
+    pub page_size: usize,
+    pub total_pages: i64,
+    /// Pass as `cursor` for the page after this one; absent on the last
+    /// page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// One line of `GET /api/vector-store/export.ndjson`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExportedDocument {
+    pub document: Document,
+    /// The document's chunks, without embeddings.
+    pub chunks: Vec<Chunk>,
+    /// The document's tags, alphabetically.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Number of documents created in one calendar month.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DateFacet {
+    /// `YYYY-MM` in the requested time zone.
+    pub month: String,
+    pub count: i64,
+}
+
+/// `GET /api/vector-store/documents/facets/date`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DateFacetsResponse {
+    /// Months with at least one document, oldest first.
+    pub facets: Vec<DateFacet>,
+    pub total: i64,
+    /// Offset from UTC the months were computed in.
+    pub tz_offset_minutes: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChunkSummary {
+    pub total: i64,
+    /// Chunk count per level (`"0"` sections, `"1"` paragraphs).
+    #[serde(rename = "byLevel")]
+    pub by_level: BTreeMap<String, i64>,
+}
+
+/// `GET /api/vector-store/documents/{id}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentResponse {
+    pub document: Document,
+    pub chunk_summary: ChunkSummary,
+    /// Every chunk has char offsets, so hits can be located in the text.
+    /// Chunks stored by older versions may lack them until
+    /// `POST /api/indexing/backfill-offsets` has run.
+    #[serde(default)]
+    pub offsets_complete: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<Vec<Chunk>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks_truncated: Option<bool>,
+}
+
+/// The document a chunk belongs to, with what a UI needs to title it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkDocument {
+    pub id: i64,
+    /// `metadata.title`, else the file name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub created_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// `GET /api/vector-store/chunks/{id}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChunkResponse {
+    pub chunk: Chunk,
+    pub document: ChunkDocument,
+}
+
+/// `GET /api/vector-store/chunks/{id}/context`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkContextResponse {
+    pub chunk_id: i64,
+    pub window: usize,
+    /// The chunk and its neighbours of the same level, in document order.
+    pub chunks: Vec<Chunk>,
+    pub document: ChunkDocument,
+}
+
+/// `GET /api/vector-store/chunks/{id}/parent`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkParentResponse {
+    pub chunk_id: i64,
+    /// The enclosing section; `None` for sections and unsectioned documents.
+    pub parent: Option<Chunk>,
+    pub document: ChunkDocument,
+}
+
+/// A section (level 0 chunk) in a document outline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct OutlineSection {
+    pub chunk_id: i64,
+    pub chunk_index: i32,
+    /// The section's Markdown heading; `None` when it has none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Heading level 1–6; `None` without a heading.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heading_level: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_start: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_end: Option<i32>,
+    /// Paragraph chunks in the section.
+    pub paragraphs: usize,
+    /// Sections under deeper headings, and sections without a heading
+    /// that follow this one.
+    #[cfg_attr(feature = "openapi", schema(no_recursion))]
+    pub children: Vec<OutlineSection>,
+}
+
+/// `GET /api/vector-store/documents/{id}/outline`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentOutlineResponse {
+    pub document: ChunkDocument,
+    /// Top-level sections; empty for documents stored without sections.
+    pub sections: Vec<OutlineSection>,
+    pub total_sections: usize,
+}
+
+/// `DELETE /api/vector-store/documents/{id}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteDocumentResponse {
+    pub deleted: bool,
+    pub id: i64,
+}
+
+/// A stored content hash and its document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentHash {
+    pub content_hash: String,
+    pub id: i64,
+    /// Last change of the document (update time, else creation time).
+    pub changed_at: i64,
+}
+
+/// `GET /api/vector-store/documents/hashes`: either the full list or, for
+/// large stores, a Bloom filter of the hashes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentHashesResponse {
+    /// Number of hashes matching the request.
+    pub total: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hashes: Option<Vec<DocumentHash>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bloom: Option<BloomDigest>,
+}
+
+impl DocumentHashesResponse {
+    /// Whether the server may have `content_hash`: exact for a list, with
+    /// false positives (never false negatives) for a Bloom filter.
+    pub fn may_contain(&self, content_hash: &str) -> bool {
+        match (&self.hashes, &self.bloom) {
+            (Some(hashes), _) => hashes.iter().any(|h| h.content_hash == content_hash),
+            (None, Some(bloom)) => bloom.contains(content_hash),
+            (None, None) => false,
+        }
+    }
+}
+
+/// Bloom filter over content hashes.
+///
+/// Bit positions of a hash `h` come from `d = SHA-256(h)`: with `a` and `b`
+/// the little-endian u64s of bytes 0..8 and 8..16 (`b` with its low bit
+/// set), probe `i` sets bit `(a + i·b) mod num_bits` (wrapping u64
+/// arithmetic) for `i` in `0..num_hashes`. Bit `n` is bit `n % 8`
+/// (least significant first) of byte `n / 8` of `bits`, hex-encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BloomDigest {
+    pub num_bits: u64,
+    pub num_hashes: u32,
+    pub bits: String,
+}
+
+impl BloomDigest {
+    /// Filter over `hashes` with about `false_positive_rate` false positives.
+    pub fn build<'a>(hashes: impl ExactSizeIterator<Item = &'a str>, false_positive_rate: f64) -> Self {
+        let n = hashes.len().max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = ((-n * false_positive_rate.ln() / (ln2 * ln2)).ceil() as u64).max(64).next_multiple_of(8);
+        let num_hashes = ((num_bits as f64 / n * ln2).round() as u32).clamp(1, 16);
+        let mut bytes = vec![0u8; (num_bits / 8) as usize];
+        for hash in hashes {
+            for bit in probes(hash, num_bits, num_hashes) {
+                bytes[(bit / 8) as usize] |= 1 << (bit % 8);
+            }
+        }
+        Self {
+            num_bits,
+            num_hashes,
+            bits: hex::encode(bytes),
+        }
+    }
+
+    /// Whether `content_hash` may be in the set. False for a malformed filter.
+    pub fn contains(&self, content_hash: &str) -> bool {
+        let Ok(bytes) = hex::decode(&self.bits) else {
+            return false;
+        };
+        if self.num_bits == 0 || bytes.len() as u64 * 8 < self.num_bits {
+            return false;
+        }
+        probes(content_hash, self.num_bits, self.num_hashes).all(|bit| bytes[(bit / 8) as usize] & (1 << (bit % 8)) != 0)
+    }
+}
+
+/// Bit positions of `content_hash` (see `BloomDigest`).
+fn probes(content_hash: &str, num_bits: u64, num_hashes: u32) -> impl Iterator<Item = u64> {
+    let digest = Sha256::digest(content_hash.as_bytes());
+    let a = u64::from_le_bytes(digest[0..8].try_into().unwrap());
+    let b = u64::from_le_bytes(digest[8..16].try_into().unwrap()) | 1;
+    (0..num_hashes as u64).map(move |i| a.wrapping_add(i.wrapping_mul(b)) % num_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_digest_has_no_false_negatives() {
+        let hashes: Vec<String> = (0..2000).map(|i| format!("{:064x}", i)).collect();
+        let bloom = BloomDigest::build(hashes.iter().map(String::as_str), 0.01);
+        assert!(hashes.iter().all(|h| bloom.contains(h)));
+
+        let false_positives = (2000..12000).filter(|i| bloom.contains(&format!("{:064x}", i))).count();
+        assert!(false_positives < 300, "{} false positives in 10000", false_positives);
+
+        // A round trip through JSON keeps the filter usable
+        let json = serde_json::to_string(&bloom).unwrap();
+        let decoded: BloomDigest = serde_json::from_str(&json).unwrap();
+        assert!(decoded.contains(&hashes[7]));
+    }
+}
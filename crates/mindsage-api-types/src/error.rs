@@ -0,0 +1,19 @@
+//! The error envelope.
+
+use serde::{Deserialize, Serialize};
+
+/// The JSON envelope of every error response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorBody {
+    /// Machine-readable error code (`not_found`, `bad_request`, ...).
+    #[cfg_attr(feature = "openapi", schema(example = "not_found"))]
+    pub code: String,
+    pub message: String,
+    /// HTTP status code.
+    pub status: u16,
+    /// Same as `message`.
+    pub error: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
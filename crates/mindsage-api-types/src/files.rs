@@ -0,0 +1,84 @@
+//! `/api/files` uploads.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UploadedFile {
+    pub filename: String,
+    pub size: usize,
+    /// Indexing job of the file.
+    #[serde(rename = "jobId")]
+    pub job_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FileError {
+    pub filename: String,
+    pub error: String,
+}
+
+/// `POST /api/files/upload`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UploadResponse {
+    pub uploaded: usize,
+    pub errors: usize,
+    pub files: Vec<UploadedFile>,
+    #[serde(rename = "errorDetails")]
+    pub error_details: Vec<FileError>,
+}
+
+/// `POST /api/files/upload/init`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct UploadInitRequest {
+    pub filename: String,
+    /// Total size in bytes.
+    pub size: u64,
+    /// Hex SHA-256 of the whole file, checked when the upload completes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Preferred chunk size; the server clamps it to its limits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<u64>,
+}
+
+/// Half-open byte range `[start, end)` of an upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// A resumable upload and the chunks received so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct UploadSession {
+    pub upload_id: String,
+    pub filename: String,
+    pub size: u64,
+    /// Every chunk but the last is exactly this long.
+    pub chunk_size: u64,
+    pub total_chunks: u64,
+    pub received_bytes: u64,
+    /// Received bytes as merged ranges, in order.
+    pub received_ranges: Vec<ByteRange>,
+    /// Numbers of the chunks still to send, in order.
+    pub missing_chunks: Vec<u64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// When the session is removed unless another chunk arrives (ms).
+    pub expires_at: i64,
+}
+
+impl UploadSession {
+    /// Whether every chunk has been received.
+    pub fn is_complete(&self) -> bool {
+        self.missing_chunks.is_empty()
+    }
+}
@@ -0,0 +1,133 @@
+//! Indexing jobs and the imports folder watcher.
+
+use serde::{Deserialize, Serialize};
+
+/// Indexing job status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct IndexingJob {
+    pub id: String,
+    pub filename: String,
+    pub file_path: String,
+    pub status: IndexingStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub queued_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<i64>,
+    /// Times the file was handed to the ingester, retries included.
+    #[serde(default)]
+    pub attempts: u32,
+    /// When a queued job waiting after a transient failure is tried again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum IndexingStatus {
+    Queued,
+    Processing,
+    Completed,
+    Failed,
+}
+
+impl IndexingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Processing => "processing",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+
+    /// Completed or failed.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed)
+    }
+}
+
+/// How the imports folder is being watched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum WatchMode {
+    #[default]
+    Off,
+    /// OS filesystem events.
+    Events,
+    /// Periodic rescans.
+    Polling,
+}
+
+/// Watcher state reported on `/api/indexing/status`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct WatcherStatus {
+    pub mode: WatchMode,
+    pub delete_on_remove: bool,
+    /// Files queued for indexing by the watcher.
+    pub files_queued: usize,
+    /// Documents deleted because their file was removed.
+    pub files_removed: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_scan_at: Option<i64>,
+    /// Why the watcher fell back to polling.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// Progress of a catch-up pass over the ingest journal: the embedding
+/// or extraction steps left over from earlier sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CatchupProgress {
+    /// `embed` or `enrich`.
+    pub step: String,
+    pub running: bool,
+    /// Documents gone through, including those of the session the pass
+    /// resumed from.
+    pub done: usize,
+    pub total: usize,
+    /// Documents per second over the last minute.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eta_secs: Option<u64>,
+    pub started_at: i64,
+    /// Set when the pass reached the end of the journal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<i64>,
+}
+
+/// `GET /api/indexing/status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct IndexingStatusResponse {
+    pub queued: usize,
+    pub processing: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub total: usize,
+    pub watcher: WatcherStatus,
+    /// Catch-up passes run this session.
+    #[serde(default)]
+    pub catchup: Vec<CatchupProgress>,
+}
+
+/// `GET /api/indexing/jobs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct IndexingJobsResponse {
+    /// Newest first.
+    pub jobs: Vec<IndexingJob>,
+    pub total: usize,
+}
@@ -0,0 +1,23 @@
+//! Request and response types of the MindSage HTTP API.
+//!
+//! The server serializes these and `mindsage-client` deserializes them, so
+//! both sides share one definition of every wire shape. Store, chat,
+//! browser and connector crates re-export the types they also use
+//! internally. With the `openapi` feature every type derives
+//! `utoipa::ToSchema`.
+
+pub mod chat;
+pub mod connectors;
+pub mod documents;
+pub mod error;
+pub mod files;
+pub mod indexing;
+pub mod search;
+
+pub use chat::*;
+pub use connectors::*;
+pub use documents::*;
+pub use error::ErrorBody;
+pub use files::*;
+pub use indexing::*;
+pub use search::*;
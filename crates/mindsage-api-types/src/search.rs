@@ -0,0 +1,362 @@
+//! Search requests, results and budget diagnostics.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// A timed stage of a search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SearchStage {
+    Bm25,
+    Vector,
+    Fusion,
+    EntityBoost,
+}
+
+impl SearchStage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SearchStage::Bm25 => "bm25",
+            SearchStage::Vector => "vector",
+            SearchStage::Fusion => "fusion",
+            SearchStage::EntityBoost => "entity_boost",
+        }
+    }
+}
+
+/// Work dropped to stay within a search latency budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Degradation {
+    /// No time was left for vector search; results are BM25 only.
+    VectorSkipped,
+    /// Vector search scanned only part of the embeddings (the newest rows).
+    VectorTruncated,
+    /// Entity boosting was skipped; results keep their BM25 order.
+    EntityBoostSkipped,
+    /// The query embedding does not have the store's dimension; results
+    /// are BM25 only.
+    VectorDimensionMismatch,
+}
+
+/// Time spent in one search stage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StageTiming {
+    pub stage: SearchStage,
+    pub ms: f64,
+}
+
+/// How a budgeted search spent its time and what it dropped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SearchDiagnostics {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_ms: Option<u64>,
+    pub elapsed_ms: f64,
+    pub stages: Vec<StageTiming>,
+    pub degradations: Vec<Degradation>,
+    /// Embedding rows scored by the vector stage.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector_rows_scanned: Option<usize>,
+    /// True when the search finished after its budget despite degrading.
+    pub over_budget: bool,
+    /// Problems with the query, such as query syntax that could not be
+    /// parsed and was searched as plain text.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+fn default_top_k() -> usize {
+    10
+}
+
+/// Chunk metadata filter pushed into search queries; every entry must
+/// match. Keys are paths into the chunk metadata such as `speaker`, `page`
+/// or `heading_path`. A value matches the field itself or any element of an
+/// array field, so `{"heading_path": "Setup"}` matches every chunk under a
+/// "Setup" heading.
+pub type ChunkFilter = BTreeMap<String, serde_json::Value>;
+
+/// How the `query` of a search is read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSyntax {
+    /// Plain text.
+    #[default]
+    Simple,
+    /// Field scopes (`source:`, `topic:`, `tag:`, `filename:`, `lang:`,
+    /// `before:`, `after:`), `-negated` terms and `"quoted phrases"` around plain text.
+    Query,
+}
+
+/// `POST /api/vector-store/search`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SearchRequest {
+    pub query: String,
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    /// Latency budget override; defaults to the tier's search budget.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_ms: Option<u64>,
+    /// Only chunks whose metadata matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub chunk_filter: Option<ChunkFilter>,
+    /// Relevance/diversity trade-off of the result selection (Maximal
+    /// Marginal Relevance), 0 to 1: 1 ranks by relevance alone, lower
+    /// values skip hits similar to ones already picked. Defaults to the
+    /// search settings (`/api/vector-store/search/settings`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mmr_lambda: Option<f64>,
+    /// Most hits from one document; 1 gives one hit per document. Defaults
+    /// to the search settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_per_doc: Option<usize>,
+    /// `simple` (default) or `query` for the query language.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syntax: Option<SearchSyntax>,
+    /// Only chunks of this level: 0 for sections, 1 for paragraphs. Every
+    /// level by default; a section and its own paragraph are not both
+    /// returned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<i32>,
+    /// Only documents in this collection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection_id: Option<i64>,
+}
+
+impl SearchRequest {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            top_k: default_top_k(),
+            budget_ms: None,
+            chunk_filter: None,
+            mmr_lambda: None,
+            max_per_doc: None,
+            syntax: None,
+            level: None,
+            collection_id: None,
+        }
+    }
+}
+
+/// `POST /api/vector-store/search/enhanced`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EnhancedSearchRequest {
+    pub query: String,
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    /// Defaults to true.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_passages: Option<bool>,
+    /// Characters kept on each side of the query terms in a passage
+    /// (default 200, at most 2000).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passage_window: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_ms: Option<u64>,
+    /// Only chunks whose metadata matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub chunk_filter: Option<ChunkFilter>,
+    /// Relevance/diversity trade-off of the result selection (Maximal
+    /// Marginal Relevance), 0 to 1: 1 ranks by relevance alone, lower
+    /// values skip hits similar to ones already picked. Defaults to the
+    /// search settings (`/api/vector-store/search/settings`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mmr_lambda: Option<f64>,
+    /// Most hits from one document; 1 gives one hit per document. Defaults
+    /// to the search settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_per_doc: Option<usize>,
+    /// Only chunks of this level: 0 for sections, 1 for paragraphs. Every
+    /// level by default; a section and its own paragraph are not both
+    /// returned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<i32>,
+    /// Only documents in this collection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection_id: Option<i64>,
+}
+
+impl EnhancedSearchRequest {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            top_k: default_top_k(),
+            include_passages: None,
+            passage_window: None,
+            budget_ms: None,
+            chunk_filter: None,
+            mmr_lambda: None,
+            max_per_doc: None,
+            level: None,
+            collection_id: None,
+        }
+    }
+}
+
+/// One search hit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SearchResult {
+    pub chunk_id: i64,
+    pub doc_id: i64,
+    pub text: String,
+    pub score: f64,
+    /// Chunk metadata: `heading_path`, `page` and `speaker` when detected.
+    pub metadata: Option<serde_json::Value>,
+    /// Enhanced search: the chunk's heading path joined with ` › `.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breadcrumb: Option<String>,
+    /// Transcript chunks: the part of the recording they cover.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_range: Option<TimeRange>,
+    /// Enhanced search with `include_passages`: the part of `text` around the query terms.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passage: Option<Passage>,
+    /// Enhanced search: extracted entities and topics of the chunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enriched_text: Option<String>,
+    /// Enhanced search: the section containing the chunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_context: Option<ParentContext>,
+    /// Enhanced search: chunk ids of lower-ranked hits inside this one,
+    /// left out of the results.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merged_from: Vec<i64>,
+}
+
+/// Offsets into a recording, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TimeRange {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+impl TimeRange {
+    /// Range stored in a transcript chunk's metadata.
+    pub fn from_metadata(metadata: &serde_json::Value) -> Option<Self> {
+        Some(Self {
+            start_ms: metadata.get("start_ms")?.as_u64()?,
+            end_ms: metadata.get("end_ms")?.as_u64()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Passage {
+    pub text: String,
+    #[cfg_attr(feature = "openapi", schema(example = "heuristic"))]
+    pub method: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ParentContext {
+    pub text: String,
+    pub chunk_id: i64,
+}
+
+/// Results of `/search` and `/search/enhanced`. With `?trace=true` the
+/// response also has a `trace` object with span timings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+    pub total: usize,
+    pub query: String,
+    /// `hybrid` or `bm25`, prefixed with `enhanced_` for enhanced search.
+    pub search_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<SearchDiagnostics>,
+}
+
+/// What a universal search result points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum UniversalResultType {
+    /// A chunk of an indexed document (hybrid search).
+    Document,
+    /// A captured browser conversation.
+    Conversation,
+    /// A file in `uploads/` or `imports/`, by name.
+    File,
+}
+
+/// One result of `GET /api/search/universal`. Which ids are set depends on
+/// `type`: `doc_id` and `chunk_id` for documents, `conversation_id` and
+/// `site` for conversations, `filename` and `location` for files (with
+/// `doc_id` once the file is indexed).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UniversalSearchResult {
+    #[serde(rename = "type")]
+    pub result_type: UniversalResultType,
+    /// Min-max normalized within the group, times the group's weight; comparable across groups.
+    pub score: f64,
+    /// Score from the group's own search.
+    pub raw_score: f64,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+    /// Conversations: the first matching message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    /// Files: `uploads` or `imports`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+}
+
+/// How one group of a universal search went.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UniversalSearchGroup {
+    #[serde(rename = "type")]
+    pub result_type: UniversalResultType,
+    pub weight: f64,
+    /// Results the group found, before the total budget.
+    pub count: usize,
+    /// Results of the group in `results`.
+    pub returned: usize,
+    pub took_ms: u64,
+    /// Set when the group failed or ran out of time; it then has no results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// `GET /api/search/universal`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UniversalSearchResponse {
+    pub query: String,
+    /// Every group's results, best first.
+    pub results: Vec<UniversalSearchResult>,
+    pub groups: Vec<UniversalSearchGroup>,
+    /// Whether any group failed.
+    pub partial: bool,
+}
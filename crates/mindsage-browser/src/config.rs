@@ -5,6 +5,8 @@ use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
+use crate::retention::{RetentionPolicy, TrimmedDocuments};
+use crate::sites::{SiteCaptureSettings, SiteDefinition, SiteRegistry};
 use crate::types::SiteAuthConfig;
 
 /// Persisted browser connector configuration.
@@ -22,6 +24,12 @@ pub struct BrowserConnectorConfig {
     pub memory_limit: usize,
     #[serde(default)]
     pub sites: HashMap<String, SiteAuthConfig>,
+    /// Sites added by the user, after the built-in ones.
+    #[serde(default)]
+    pub custom_sites: Vec<SiteDefinition>,
+    /// Capture settings changed for built-in sites, by site name.
+    #[serde(default)]
+    pub site_capture: HashMap<String, SiteCaptureSettings>,
     #[serde(default = "default_false")]
     pub auto_sync_enabled: bool,
     #[serde(default = "default_interval")]
@@ -30,6 +38,19 @@ pub struct BrowserConnectorConfig {
     pub last_sync_at: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_sync_result: Option<crate::types::SyncResult>,
+    /// Retention limits by site name; `"*"` covers sites without an entry.
+    #[serde(default)]
+    pub retention: HashMap<String, RetentionPolicy>,
+    /// What cleanup does with the documents of conversations it trims.
+    #[serde(default)]
+    pub retention_documents: TrimmedDocuments,
+    #[serde(default = "default_cleanup_interval")]
+    pub cleanup_interval_hours: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_cleanup_at: Option<String>,
+    /// Bytes freed by all cleanups so far.
+    #[serde(default)]
+    pub reclaimed_bytes: u64,
     /// Path to config file (not serialized).
     #[serde(skip)]
     pub config_path: PathBuf,
@@ -50,6 +71,9 @@ fn default_memory_limit() -> usize {
 fn default_interval() -> f64 {
     6.0
 }
+fn default_cleanup_interval() -> f64 {
+    24.0
+}
 
 impl Default for BrowserConnectorConfig {
     fn default() -> Self {
@@ -60,10 +84,17 @@ impl Default for BrowserConnectorConfig {
             vnc_port: 5900,
             memory_limit: 512,
             sites: HashMap::new(),
+            custom_sites: Vec::new(),
+            site_capture: HashMap::new(),
             auto_sync_enabled: false,
             auto_sync_interval_hours: 6.0,
             last_sync_at: None,
             last_sync_result: None,
+            retention: HashMap::new(),
+            retention_documents: TrimmedDocuments::default(),
+            cleanup_interval_hours: 24.0,
+            last_cleanup_at: None,
+            reclaimed_bytes: 0,
             config_path: PathBuf::new(),
         }
     }
@@ -87,15 +118,28 @@ impl BrowserConnectorConfig {
             std::fs::create_dir_all(parent)?;
         }
         let json = serde_json::to_string_pretty(self)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            .map_err(std::io::Error::other)?;
         std::fs::write(&self.config_path, json)
     }
 
+    /// Built-in and custom sites with their capture settings.
+    pub fn site_registry(&self) -> SiteRegistry {
+        SiteRegistry::new(&self.custom_sites, &self.site_capture)
+    }
+
     /// Get auth config for a site, creating an entry if missing.
     pub fn get_site_auth(&self, site: &str) -> SiteAuthConfig {
         self.sites.get(site).cloned().unwrap_or_default()
     }
 
+    /// The retention policy for `site`: its own, else the `"*"` one.
+    pub fn retention_for(&self, site: &str) -> Option<&RetentionPolicy> {
+        self.retention
+            .get(site)
+            .or_else(|| self.retention.get(crate::retention::ALL_SITES))
+            .filter(|policy| !policy.is_empty())
+    }
+
     /// Update auth config for a site.
     pub fn set_site_auth(&mut self, site: &str, auth: SiteAuthConfig) {
         self.sites.insert(site.to_string(), auth);
@@ -1,12 +1,23 @@
 //! Browser connector — Chrome lifecycle, CDP cookie injection, extension relay.
 //!
 //! Manages a Chromium instance for capturing AI conversations from
-//! ChatGPT, Claude, and Gemini via a companion Chrome extension.
+//! ChatGPT, Claude, Gemini, Copilot and user-added sites via a companion
+//! Chrome extension.
 
 pub mod config;
 pub mod manager;
+pub mod relay;
+pub mod retention;
+pub mod search;
+pub mod sites;
+pub mod storage;
 pub mod types;
 
 pub use config::BrowserConnectorConfig;
 pub use manager::BrowserManager;
+pub use relay::ExtensionConnection;
+pub use retention::{RetentionPolicy, RetentionReport, TrimmedDocuments};
+pub use search::{ConversationSearch, ConversationSearchResults, SearchSort};
+pub use sites::{SiteCaptureSettings, SiteDefinition, SiteRegistry, SiteUpdate};
+pub use storage::ConversationStore;
 pub use types::*;
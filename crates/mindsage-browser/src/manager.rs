@@ -1,37 +1,55 @@
 //! Browser manager — Chrome lifecycle, capture state, cookie storage, sync orchestration.
 
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
+use mindsage_core::{Event, EventBus};
 use parking_lot::RwLock;
 use tracing::{info, warn};
 
 use crate::config::BrowserConnectorConfig;
+use crate::relay::{ExtensionConnection, ExtensionRelay};
+use crate::retention::{self, RemovalReason, RemovedConversation, RetentionReport, TrimmedConversation, TrimmedDocuments};
+use crate::search::{ConversationSearch, ConversationSearchResults};
+use crate::sites::{SiteDefinition, SiteUpdate};
+use crate::storage::ConversationStore;
 use crate::types::*;
 
 /// Central browser connector manager.
 pub struct BrowserManager {
     pub config: RwLock<BrowserConnectorConfig>,
-    data_dir: PathBuf,
     /// Chrome process PID if running.
     chrome_pid: RwLock<Option<u32>>,
     /// Session capture stats.
     capture_stats: RwLock<CaptureStats>,
-    /// Captured conversations by ID.
-    conversations: RwLock<HashMap<String, CapturedConversation>>,
+    /// Captured conversations, one file each; only summaries in memory.
+    conversations: ConversationStore,
     /// Pending cookies per site (from companion extension).
     pending_cookies: RwLock<HashMap<String, Vec<ImportedCookie>>>,
     /// Auto-sync interval handle (None if disabled).
     auto_sync_active: RwLock<bool>,
     /// When browser was launched.
     launched_at: RwLock<Option<String>>,
+    /// Event bus for capture notifications.
+    events: Option<EventBus>,
+    /// The companion extension's WebSocket connection.
+    relay: ExtensionRelay,
 }
 
 impl BrowserManager {
     /// Create a new browser manager with the given data directory.
     pub fn new(data_dir: &Path) -> Self {
+        Self::with_conversations(data_dir, ConversationStore::open(data_dir))
+    }
+
+    /// A browser manager that reads `data_dir` but never writes captured
+    /// conversations to it.
+    pub fn open_read_only(data_dir: &Path) -> Self {
+        Self::with_conversations(data_dir, ConversationStore::open_read_only(data_dir))
+    }
+
+    fn with_conversations(data_dir: &Path, conversations: ConversationStore) -> Self {
         let config = BrowserConnectorConfig::load(data_dir);
-        let conversations = Self::load_conversations(data_dir);
 
         info!(
             "BrowserManager initialized: {} conversations loaded",
@@ -40,16 +58,23 @@ impl BrowserManager {
 
         Self {
             config: RwLock::new(config),
-            data_dir: data_dir.to_path_buf(),
             chrome_pid: RwLock::new(None),
             capture_stats: RwLock::new(CaptureStats::default()),
-            conversations: RwLock::new(conversations),
+            conversations,
             pending_cookies: RwLock::new(HashMap::new()),
             auto_sync_active: RwLock::new(false),
             launched_at: RwLock::new(None),
+            events: None,
+            relay: ExtensionRelay::default(),
         }
     }
 
+    /// Publish capture events to the given bus.
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
     // ---------------------------------------------------------------
     // Status
     // ---------------------------------------------------------------
@@ -59,22 +84,22 @@ impl BrowserManager {
         let config = self.config.read();
         let pid = *self.chrome_pid.read();
         let stats = self.capture_stats.read().clone();
-        let conversations = self.conversations.read();
         let launched_at = self.launched_at.read().clone();
 
-        let connected_sites: Vec<String> = SupportedSite::all()
+        let connected_sites: Vec<String> = config
+            .site_registry()
             .iter()
             .filter(|s| {
                 config
-                    .get_site_auth(s.name())
+                    .get_site_auth(&s.name)
                     .authenticated_at
                     .is_some()
             })
-            .map(|s| s.name().to_string())
+            .map(|s| s.name.clone())
             .collect();
 
         let mut stats_out = stats;
-        stats_out.conversations_tracked = conversations.len();
+        stats_out.conversations_tracked = self.conversations.len();
 
         BrowserStatus {
             running: pid.is_some(),
@@ -90,6 +115,7 @@ impl BrowserManager {
                 vnc_port: None,
                 display: None,
             },
+            extension: self.relay.status(),
         }
     }
 
@@ -114,9 +140,10 @@ impl BrowserManager {
             }
         } else {
             // Check if any site is authenticated
-            let any_auth = SupportedSite::all()
+            let any_auth = config
+                .site_registry()
                 .iter()
-                .any(|s| config.get_site_auth(s.name()).authenticated_at.is_some());
+                .any(|s| config.get_site_auth(&s.name).authenticated_at.is_some());
             AuthStatus {
                 authenticated: any_auth,
                 authenticated_at: None,
@@ -149,13 +176,17 @@ impl BrowserManager {
     /// Get list of supported sites with their auth status.
     pub fn get_sites_info(&self) -> Vec<SiteInfo> {
         let config = self.config.read();
-        SupportedSite::all()
+        config
+            .site_registry()
             .iter()
             .map(|site| {
-                let auth = config.get_site_auth(site.name());
+                let auth = config.get_site_auth(&site.name);
                 SiteInfo {
-                    name: site.name().to_string(),
-                    url: site.base_url().to_string(),
+                    name: site.name.clone(),
+                    url: site.base_url.clone(),
+                    cookie_domains: site.cookie_domains.clone(),
+                    capture: site.capture.clone(),
+                    builtin: site.builtin,
                     authenticated: auth.authenticated_at.is_some(),
                     authenticated_at: auth.authenticated_at.clone(),
                     last_sync_at: auth.last_sync_at.clone(),
@@ -164,18 +195,113 @@ impl BrowserManager {
             .collect()
     }
 
+    // ---------------------------------------------------------------
+    // Sites
+    // ---------------------------------------------------------------
+
+    /// Look up a built-in or custom site by name, ignoring case.
+    pub fn find_site(&self, name: &str) -> Option<SiteDefinition> {
+        self.config.read().site_registry().get(name).cloned()
+    }
+
+    /// Add a custom site. Fails when the definition is invalid or the name
+    /// is taken.
+    pub fn add_site(&self, site: SiteDefinition) -> Result<SiteDefinition, String> {
+        let site = site.normalized()?;
+        let mut config = self.config.write();
+        if config.site_registry().get(&site.name).is_some() {
+            return Err(format!("Site already exists: {}", site.name));
+        }
+        config.custom_sites.push(site.clone());
+        let _ = config.save();
+        info!("Added custom site: {}", site.name);
+        Ok(site)
+    }
+
+    /// Change a site. Built-in sites only accept capture settings. Returns
+    /// `Ok(None)` when the site does not exist.
+    pub fn update_site(&self, name: &str, update: SiteUpdate) -> Result<Option<SiteDefinition>, String> {
+        let mut config = self.config.write();
+        let Some(current) = config.site_registry().get(name).cloned() else {
+            return Ok(None);
+        };
+        if current.builtin {
+            if update.base_url.is_some() || update.cookie_domains.is_some() {
+                return Err(format!(
+                    "Built-in site {}: only capture settings can be changed",
+                    current.name
+                ));
+            }
+            if let Some(capture) = update.capture {
+                config.site_capture.insert(current.name.clone(), capture.normalized()?);
+            }
+        } else {
+            let site = SiteDefinition {
+                base_url: update.base_url.unwrap_or(current.base_url),
+                cookie_domains: update.cookie_domains.unwrap_or(current.cookie_domains),
+                capture: update.capture.unwrap_or(current.capture),
+                ..current
+            }
+            .normalized()?;
+            if let Some(entry) = config.custom_sites.iter_mut().find(|s| s.name == site.name) {
+                *entry = site;
+            }
+        }
+        let _ = config.save();
+        Ok(config.site_registry().get(name).cloned())
+    }
+
+    /// Remove a custom site and its stored auth. Built-in sites cannot be
+    /// removed. Returns `Ok(false)` when the site does not exist.
+    pub fn remove_site(&self, name: &str) -> Result<bool, String> {
+        let mut config = self.config.write();
+        let Some(site) = config.site_registry().get(name).cloned() else {
+            return Ok(false);
+        };
+        if site.builtin {
+            return Err(format!("Built-in site {} cannot be removed", site.name));
+        }
+        config.custom_sites.retain(|s| s.name != site.name);
+        config.sites.remove(&site.name);
+        let _ = config.save();
+        self.pending_cookies.write().remove(&site.name);
+        info!("Removed custom site: {}", site.name);
+        Ok(true)
+    }
+
     // ---------------------------------------------------------------
     // Capture Management
     // ---------------------------------------------------------------
 
-    /// Process a capture payload from the extension.
-    pub fn process_capture(&self, payload: CapturePayload) -> usize {
+    /// Process a capture payload from the extension. A `previous_id` that
+    /// names another stored conversation of the same site is merged into
+    /// the captured one first.
+    pub fn process_capture(&self, payload: CapturePayload) -> CaptureOutcome {
+        let merged = payload
+            .previous_id
+            .as_deref()
+            .filter(|previous| *previous != payload.conversation_id)
+            .filter(|previous| {
+                self.conversations
+                    .get(previous)
+                    .is_some_and(|c| c.site == payload.site)
+            })
+            .and_then(|previous| match self.merge_conversations(previous, &payload.conversation_id) {
+                Ok(merged) => Some(merged),
+                Err(e) => {
+                    warn!("Could not merge conversation {} into {}: {}", previous, payload.conversation_id, e);
+                    None
+                }
+            });
+
         let now = chrono::Utc::now().to_rfc3339();
-        let mut conversations = self.conversations.write();
+        let conversation_id = payload.conversation_id.clone();
+        let site = payload.site.clone();
+
+        let full_conversation = payload.full_conversation.unwrap_or(false);
 
-        let entry = conversations
-            .entry(payload.conversation_id.clone())
-            .or_insert_with(|| CapturedConversation {
+        let merge = self.conversations.update(&payload.conversation_id, |existing| {
+            let mut entry = existing.unwrap_or_else(|| CapturedConversation {
                 id: payload.conversation_id.clone(),
                 site: payload.site.clone(),
                 title: payload.title.clone(),
@@ -185,89 +311,283 @@ impl BrowserManager {
                 updated_at: now.clone(),
                 indexed: false,
                 message_count: 0,
+                document_id: None,
             });
 
-        // Update title if provided
-        if payload.title.is_some() {
-            entry.title = payload.title;
-        }
-
-        // Add new messages (dedup by ID)
-        let existing_ids: std::collections::HashSet<String> =
-            entry.messages.iter().map(|m| m.id.clone()).collect();
-
-        let mut new_count = 0;
-        for msg in payload.messages {
-            if !existing_ids.contains(&msg.id) {
-                entry.messages.push(msg);
-                new_count += 1;
+            // Update title if provided
+            if payload.title.is_some() {
+                entry.title = payload.title;
             }
-        }
 
-        entry.message_count = entry.messages.len();
-        entry.updated_at = chrono::Utc::now().to_rfc3339();
+            let merge = merge_messages(&mut entry.messages, payload.messages, full_conversation);
 
-        drop(conversations);
+            if merge.added + merge.replaced > 0 {
+                entry.indexed = false;
+            }
+            entry.message_count = entry.messages.len();
+            entry.updated_at = chrono::Utc::now().to_rfc3339();
+            (entry, merge)
+        });
 
         // Update stats
         {
             let mut stats = self.capture_stats.write();
             stats.total_captures += 1;
+            stats.duplicates_skipped += merge.duplicates as u64;
+        }
+
+        if let Some(events) = &self.events {
+            events.publish(Event::CaptureReceived {
+                conversation_id,
+                site,
+                new_messages: merge.added,
+            });
+        }
+
+        CaptureOutcome {
+            new_messages: merge.added,
+            merged,
+        }
+    }
+
+    /// Merge conversation `source_id` into `target_id` and delete it.
+    /// Messages the target already has (by ID or fingerprint) are skipped;
+    /// the rest keep their place before the target's own messages. A
+    /// missing target is created from the source. Both must be of the same
+    /// site.
+    pub fn merge_conversations(&self, source_id: &str, target_id: &str) -> Result<MergedConversation, String> {
+        if source_id == target_id {
+            return Err("Cannot merge a conversation into itself".to_string());
         }
+        let source = self
+            .conversations
+            .get(source_id)
+            .ok_or_else(|| format!("Conversation not found: {}", source_id))?;
+        let site = source.site.clone();
+        let source_document_id = source.document_id;
+
+        let added_messages = self.conversations.try_update(target_id, |existing| {
+            if let Some(target) = &existing {
+                if target.site != source.site {
+                    return Err(format!(
+                        "Cannot merge a {} conversation into a {} conversation",
+                        source.site, target.site
+                    ));
+                }
+            }
+            Ok(merge_conversation(target_id, source, existing))
+        })?;
+        self.conversations.remove(source_id);
+        info!(
+            "Merged conversation {} into {} ({} new messages)",
+            source_id, target_id, added_messages
+        );
 
-        // Persist conversations
-        self.save_conversations();
+        Ok(MergedConversation {
+            source_id: source_id.to_string(),
+            target_id: target_id.to_string(),
+            site,
+            source_document_id,
+            added_messages,
+        })
+    }
 
-        new_count
+    /// Record that a conversation is indexed as vector-store `document_id`.
+    pub fn mark_indexed(&self, id: &str, document_id: i64) -> bool {
+        self.conversations.modify(id, |c| {
+            c.indexed = true;
+            c.document_id = Some(document_id);
+        })
     }
 
-    /// Get all conversations (paginated).
+    /// Conversation summaries, most recently updated first (paginated).
+    pub fn get_conversation_summaries(
+        &self,
+        page: usize,
+        page_size: usize,
+        site: Option<&str>,
+    ) -> (Vec<ConversationSummary>, usize) {
+        self.conversations.summaries(page, page_size, site)
+    }
+
+    /// Search captured conversations by title and message content.
+    pub fn search_conversations(&self, search: &ConversationSearch) -> ConversationSearchResults {
+        self.conversations.search(search)
+    }
+
+    /// Conversations with their messages (paginated). Only the requested
+    /// page is read from disk.
     pub fn get_conversations(
         &self,
         page: usize,
         page_size: usize,
         site: Option<&str>,
     ) -> (Vec<CapturedConversation>, usize) {
-        let conversations = self.conversations.read();
-        let mut filtered: Vec<&CapturedConversation> = conversations
-            .values()
-            .filter(|c| site.map_or(true, |s| c.site == s))
-            .collect();
-
-        filtered.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
-        let total = filtered.len();
-        let start = (page.saturating_sub(1)) * page_size;
-        let paged: Vec<CapturedConversation> = filtered
-            .into_iter()
-            .skip(start)
-            .take(page_size)
-            .cloned()
+        let (summaries, total) = self.conversations.summaries(page, page_size, site);
+        let paged = summaries
+            .iter()
+            .filter_map(|s| self.conversations.get(&s.id))
             .collect();
-
         (paged, total)
     }
 
     /// Get a single conversation by ID.
     pub fn get_conversation(&self, id: &str) -> Option<CapturedConversation> {
-        self.conversations.read().get(id).cloned()
+        self.conversations.get(id)
     }
 
     /// Delete a conversation.
     pub fn delete_conversation(&self, id: &str) -> bool {
-        let removed = self.conversations.write().remove(id).is_some();
-        if removed {
-            self.save_conversations();
+        self.conversations.remove(id)
+    }
+
+    /// Remove every captured message mentioning `term`, ignoring case. A
+    /// conversation left without messages is deleted; one whose title
+    /// mentions the term loses its title. Touched conversations are marked
+    /// not indexed, since their document held the term. With `dry_run`
+    /// nothing changes. Every conversation is read, not only search hits.
+    pub fn forget_mentions(&self, term: &str, dry_run: bool) -> ForgottenMessages {
+        let needle = term.trim().to_lowercase();
+        let mut forgotten = ForgottenMessages::default();
+        if needle.is_empty() {
+            return forgotten;
         }
-        removed
+        let mentions = |text: &str| text.to_lowercase().contains(&needle);
+        let (summaries, _) = self.conversations.summaries(1, usize::MAX, None);
+        for summary in summaries {
+            let Some(conversation) = self.conversations.get(&summary.id) else {
+                continue;
+            };
+            let removed = conversation.messages.iter().filter(|m| mentions(&m.content)).count();
+            let titled = conversation.title.as_deref().is_some_and(mentions);
+            if removed == 0 && !titled {
+                continue;
+            }
+            forgotten.conversations += 1;
+            forgotten.messages += removed;
+            forgotten.document_ids.extend(conversation.document_id);
+            if removed == conversation.messages.len() {
+                forgotten.conversations_deleted += 1;
+                if !dry_run {
+                    self.conversations.remove(&summary.id);
+                }
+            } else if !dry_run {
+                self.conversations.modify(&summary.id, |c| {
+                    c.messages.retain(|m| !mentions(&m.content));
+                    c.message_count = c.messages.len();
+                    if titled {
+                        c.title = None;
+                    }
+                    c.indexed = false;
+                    c.document_id = None;
+                });
+            }
+        }
+        forgotten
+    }
+
+    /// Apply each site's retention policy as of `now`: delete conversations
+    /// past the age limit, then those beyond the count limit (least recently
+    /// updated first), then trim the rest to the message limit. Trimmed
+    /// conversations keep their document; with `TrimmedDocuments::Refresh`
+    /// they are marked not indexed so the caller re-indexes them. Deleted
+    /// conversations' documents are left in the vector store.
+    pub fn apply_retention(&self, now: chrono::DateTime<chrono::Utc>) -> RetentionReport {
+        let config = self.config.read().clone();
+        let mut report = RetentionReport::default();
+        if config.retention.values().all(|policy| policy.is_empty()) {
+            return report;
+        }
+
+        let (mut summaries, _) = self.conversations.summaries(1, usize::MAX, None);
+        summaries.sort_by(|a, b| (&a.site, &b.updated_at).cmp(&(&b.site, &a.updated_at)));
+        let mut kept_per_site: HashMap<String, usize> = HashMap::new();
+        for summary in summaries {
+            let Some(policy) = config.retention_for(&summary.site) else {
+                continue;
+            };
+            let age_days = chrono::DateTime::parse_from_rfc3339(&summary.updated_at)
+                .map(|updated| (now - updated.with_timezone(&chrono::Utc)).num_days())
+                .unwrap_or(0);
+            let kept = kept_per_site.entry(summary.site.clone()).or_default();
+            let reason = if policy.max_age_days.is_some_and(|days| age_days >= i64::from(days)) {
+                Some(RemovalReason::MaxAge)
+            } else if policy.max_conversations.is_some_and(|max| *kept >= max) {
+                Some(RemovalReason::MaxConversations)
+            } else {
+                None
+            };
+
+            if let Some(reason) = reason {
+                let bytes = self.conversations.file_size(&summary.id);
+                let document_id = self.conversations.get(&summary.id).and_then(|c| c.document_id);
+                if self.conversations.remove(&summary.id) {
+                    report.bytes_reclaimed += bytes;
+                    report.deleted.push(RemovedConversation {
+                        id: summary.id,
+                        site: summary.site,
+                        reason,
+                        document_id,
+                    });
+                }
+                continue;
+            }
+            *kept += 1;
+
+            let Some(max_messages) = policy.max_messages else {
+                continue;
+            };
+            if summary.message_count <= max_messages {
+                continue;
+            }
+            let before = self.conversations.file_size(&summary.id);
+            let mut removed = 0;
+            let mut document_id = None;
+            self.conversations.modify(&summary.id, |c| {
+                removed = retention::trim_messages(c, max_messages);
+                document_id = c.document_id;
+                if removed > 0 && config.retention_documents == TrimmedDocuments::Refresh {
+                    c.indexed = false;
+                }
+            });
+            if removed == 0 {
+                continue;
+            }
+            report.bytes_reclaimed += before.saturating_sub(self.conversations.file_size(&summary.id));
+            report.messages_removed += removed;
+            report.trimmed.push(TrimmedConversation {
+                id: summary.id,
+                site: summary.site,
+                messages_removed: removed,
+                document_id,
+            });
+        }
+
+        let mut config = self.config.write();
+        config.last_cleanup_at = Some(now.to_rfc3339());
+        config.reclaimed_bytes += report.bytes_reclaimed;
+        let _ = config.save();
+        if !report.is_empty() {
+            info!(
+                "Retention cleanup deleted {} conversations and trimmed {}, reclaiming {} bytes",
+                report.deleted.len(),
+                report.trimmed.len(),
+                report.bytes_reclaimed
+            );
+        }
+        report
     }
 
     /// Get capture statistics.
     pub fn get_capture_stats(&self) -> CaptureStats {
         let stats = self.capture_stats.read();
-        let conversations = self.conversations.read();
+        let config = self.config.read();
         CaptureStats {
             total_captures: stats.total_captures,
-            conversations_tracked: conversations.len(),
+            duplicates_skipped: stats.duplicates_skipped,
+            conversations_tracked: self.conversations.len(),
+            reclaimed_bytes: config.reclaimed_bytes,
+            last_cleanup_at: config.last_cleanup_at.clone(),
         }
     }
 
@@ -301,6 +621,99 @@ impl BrowserManager {
             .collect()
     }
 
+    // ---------------------------------------------------------------
+    // Extension relay
+    // ---------------------------------------------------------------
+
+    /// Register the extension's socket, replacing any previous one.
+    pub fn connect_extension(&self) -> ExtensionConnection {
+        info!("Browser extension connected");
+        self.relay.connect()
+    }
+
+    /// Forget connection `id` when its socket closes.
+    pub fn disconnect_extension(&self, id: u64) {
+        self.relay.disconnect(id);
+    }
+
+    pub fn extension_connected(&self) -> bool {
+        self.relay.is_connected()
+    }
+
+    /// Push `message` to the extension. False when it is not connected, so
+    /// the caller can fall back to passive capture.
+    pub fn send_to_extension(&self, message: ServerMessage) -> bool {
+        self.relay.send(message)
+    }
+
+    /// The last conversation list the extension sent for `site`.
+    pub fn extension_conversation_list(&self, site: &str) -> Option<ConversationListing> {
+        self.find_site(site).and_then(|s| self.relay.listing(&s.name))
+    }
+
+    /// Handle a frame from the extension. Captures, auth reports and sync
+    /// results go through the same paths as their HTTP endpoints; an unknown
+    /// site is an error for the extension.
+    pub fn handle_extension_message(&self, message: ExtensionMessage) -> Result<RelayOutcome, String> {
+        let version = match &message {
+            ExtensionMessage::Hello { version } => Some(version.clone()),
+            _ => None,
+        };
+        self.relay.seen(version);
+        let known_site = |name: &str| self.find_site(name).ok_or_else(|| format!("Unsupported site: {}", name));
+
+        match message {
+            ExtensionMessage::Hello { version } => {
+                info!("Browser extension {} said hello", version);
+            }
+            ExtensionMessage::Capture(mut payload) => {
+                let site = known_site(&payload.site)?;
+                payload.site = site.name.clone();
+                let conversation_id = payload.conversation_id.clone();
+                let outcome = self.process_capture(payload);
+                return Ok(RelayOutcome::Captured {
+                    conversation_id,
+                    new_messages: outcome.new_messages,
+                    auto_index: site.capture.auto_index,
+                    merged: outcome.merged,
+                });
+            }
+            ExtensionMessage::AuthStatus { site, authenticated } => {
+                let site = known_site(&site)?;
+                if authenticated {
+                    self.set_authenticated(&site.name);
+                } else {
+                    self.clear_auth(Some(&site.name));
+                }
+            }
+            ExtensionMessage::ConversationList { site, conversations } => {
+                let site = known_site(&site)?;
+                let uncaptured = conversations
+                    .iter()
+                    .filter(|c| self.conversations.get(&c.id).is_none())
+                    .count();
+                self.relay.store_listing(ConversationListing {
+                    site: site.name,
+                    conversations,
+                    uncaptured,
+                    received_at: chrono::Utc::now().to_rfc3339(),
+                });
+            }
+            ExtensionMessage::SyncComplete(result) => self.record_sync_result(result),
+            ExtensionMessage::Pong => {}
+        }
+        Ok(RelayOutcome::Handled)
+    }
+
+    /// Record the result of a sync the extension ran.
+    pub fn record_sync_result(&self, result: SyncResult) {
+        info!("Sync complete: success={}", result.success);
+        let mut config = self.config.write();
+        config.last_sync_at = Some(chrono::Utc::now().to_rfc3339());
+        config.last_sync_result = Some(result);
+        let _ = config.save();
+    }
+
     // ---------------------------------------------------------------
     // Auto-Sync
     // ---------------------------------------------------------------
@@ -339,7 +752,7 @@ impl BrowserManager {
     /// Update auto-sync interval.
     pub fn set_auto_sync_interval(&self, hours: f64) {
         let mut config = self.config.write();
-        config.auto_sync_interval_hours = hours.max(0.5).min(24.0);
+        config.auto_sync_interval_hours = hours.clamp(0.5, 24.0);
         let _ = config.save();
     }
 
@@ -364,31 +777,510 @@ impl BrowserManager {
         if let Some(headed) = updates.get("headed").and_then(|v| v.as_bool()) {
             config.headed = headed;
         }
+        if let Some(retention) = updates.get("retention").and_then(|v| serde_json::from_value(v.clone()).ok()) {
+            config.retention = retention;
+        }
+        if let Some(documents) = updates
+            .get("retentionDocuments")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+        {
+            config.retention_documents = documents;
+        }
+        if let Some(hours) = updates.get("cleanupIntervalHours").and_then(|v| v.as_f64()).filter(|h| *h > 0.0) {
+            config.cleanup_interval_hours = hours;
+        }
         let _ = config.save();
     }
 
-    // ---------------------------------------------------------------
-    // Persistence
-    // ---------------------------------------------------------------
-
-    fn conversations_path(&self) -> PathBuf {
-        self.data_dir.join("conversations.json")
+    /// Write the configuration and conversation index to disk, for shutdown.
+    pub fn flush(&self) {
+        if let Err(e) = self.config.read().save() {
+            warn!("Failed to save browser connector config: {}", e);
+        }
+        self.conversations.flush();
     }
+}
 
-    fn load_conversations(data_dir: &Path) -> HashMap<String, CapturedConversation> {
-        let path = data_dir.join("conversations.json");
-        match std::fs::read_to_string(&path) {
-            Ok(data) => serde_json::from_str(&data).unwrap_or_default(),
-            Err(_) => HashMap::new(),
+/// What merging a capture into a conversation did.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct MergeCounts {
+    added: usize,
+    /// Existing messages whose content was replaced by an edit.
+    replaced: usize,
+    /// Resent messages whose role and content were already captured.
+    duplicates: usize,
+}
+
+/// `source` merged into `target` (created from the source when missing)
+/// under `target_id`, with the number of source messages the target did
+/// not have. The target keeps its URL and document; the earlier of the
+/// two creation times is kept.
+fn merge_conversation(
+    target_id: &str,
+    source: CapturedConversation,
+    target: Option<CapturedConversation>,
+) -> (CapturedConversation, usize) {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut messages = source.messages;
+    let (mut merged, added) = match target {
+        Some(mut target) => {
+            let target_messages = std::mem::take(&mut target.messages);
+            let target_count = target_messages.len();
+            // Source messages first: the source is the earlier capture
+            merge_messages(&mut messages, target_messages, false);
+            let added = messages.len().saturating_sub(target_count);
+            if target.title.is_none() {
+                target.title = source.title;
+            }
+            if source.created_at < target.created_at {
+                target.created_at = source.created_at;
+            }
+            if added > 0 {
+                target.indexed = false;
+            }
+            (target, added)
         }
+        None => {
+            let added = messages.len();
+            let created = CapturedConversation {
+                id: target_id.to_string(),
+                site: source.site,
+                title: source.title,
+                url: source.url,
+                messages: Vec::new(),
+                created_at: source.created_at,
+                updated_at: now.clone(),
+                indexed: false,
+                message_count: 0,
+                document_id: None,
+            };
+            (created, added)
+        }
+    };
+    for message in &mut messages {
+        message.conversation_id = target_id.to_string();
     }
+    merged.messages = messages;
+    merged.message_count = merged.messages.len();
+    merged.updated_at = now;
+    (merged, added)
+}
 
-    fn save_conversations(&self) {
-        let conversations = self.conversations.read();
-        if let Ok(data) = serde_json::to_string_pretty(&*conversations) {
-            if let Err(e) = std::fs::write(self.conversations_path(), data) {
-                warn!("Failed to save conversations: {}", e);
+/// Merge captured messages into a conversation. A message with a known ID
+/// replaces the stored one if its content changed. A message with a new ID
+/// is skipped when its fingerprint (role + normalized content) is already
+/// present, since sites regenerate IDs on reload. In a full-conversation
+/// capture, a new message at a position already holding a message of the
+/// same role is an edit and replaces it; otherwise it is appended.
+fn merge_messages(
+    messages: &mut Vec<CapturedMessage>,
+    incoming: Vec<CapturedMessage>,
+    full_conversation: bool,
+) -> MergeCounts {
+    let mut counts = MergeCounts::default();
+    let mut fingerprints: HashSet<String> = messages.iter().map(|m| m.fingerprint()).collect();
+    let positions: HashMap<String, usize> = messages
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (m.id.clone(), i))
+        .collect();
+
+    for (i, msg) in incoming.into_iter().enumerate() {
+        let fingerprint = msg.fingerprint();
+        if let Some(&pos) = positions.get(&msg.id) {
+            if messages[pos].fingerprint() != fingerprint {
+                fingerprints.insert(fingerprint);
+                messages[pos] = msg;
+                counts.replaced += 1;
             }
+            continue;
+        }
+        if fingerprints.contains(&fingerprint) {
+            counts.duplicates += 1;
+            continue;
+        }
+        fingerprints.insert(fingerprint);
+        if full_conversation && messages.get(i).is_some_and(|m| m.role == msg.role) {
+            messages[i] = msg;
+            counts.replaced += 1;
+        } else {
+            messages.push(msg);
+            counts.added += 1;
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn message(id: &str, role: &str, content: &str) -> CapturedMessage {
+        CapturedMessage {
+            id: id.to_string(),
+            conversation_id: "conv".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+            site: "chatgpt".to_string(),
+            metadata: None,
+        }
+    }
+
+    fn capture(messages: Vec<CapturedMessage>, full_conversation: bool) -> CapturePayload {
+        CapturePayload {
+            site: "chatgpt".to_string(),
+            conversation_id: "conv".to_string(),
+            conversation_url: "https://chatgpt.com/c/conv".to_string(),
+            title: Some("Trip planning".to_string()),
+            messages,
+            full_conversation: Some(full_conversation),
+            previous_id: None,
+        }
+    }
+
+    fn frame(json: serde_json::Value) -> ExtensionMessage {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_extension_messages_are_routed() {
+        let dir = TempDir::new().unwrap();
+        let manager = BrowserManager::new(dir.path());
+        let connection = manager.connect_extension();
+
+        let hello = frame(serde_json::json!({ "type": "hello", "version": "2.1.0" }));
+        assert_eq!(manager.handle_extension_message(hello), Ok(RelayOutcome::Handled));
+        let status = manager.get_status().extension;
+        assert!(status.connected);
+        assert_eq!(status.version.as_deref(), Some("2.1.0"));
+
+        let auth = frame(serde_json::json!({ "type": "auth-status", "site": "ChatGPT", "authenticated": true }));
+        manager.handle_extension_message(auth).unwrap();
+        assert!(manager.get_auth_status(Some("chatgpt")).authenticated);
+
+        let capture = frame(serde_json::json!({
+            "type": "capture",
+            "site": "CHATGPT",
+            "conversationId": "conv",
+            "conversationUrl": "https://chatgpt.com/c/conv",
+            "title": "Trip planning",
+            "messages": [{
+                "id": "u1", "conversationId": "conv", "role": "user", "content": "Where to in May?",
+                "timestamp": "2025-01-01T00:00:00Z", "site": "chatgpt",
+            }],
+        }));
+        assert_eq!(
+            manager.handle_extension_message(capture),
+            Ok(RelayOutcome::Captured {
+                conversation_id: "conv".to_string(),
+                new_messages: 1,
+                auto_index: false,
+                merged: None,
+            })
+        );
+        assert_eq!(manager.get_conversation("conv").unwrap().site, "chatgpt");
+
+        let list = frame(serde_json::json!({
+            "type": "conversation-list",
+            "site": "chatgpt",
+            "conversations": [
+                { "id": "conv", "url": "https://chatgpt.com/c/conv" },
+                { "id": "other", "url": "https://chatgpt.com/c/other", "title": "Recipes" },
+            ],
+        }));
+        manager.handle_extension_message(list).unwrap();
+        let listing = manager.extension_conversation_list("ChatGPT").unwrap();
+        assert_eq!((listing.conversations.len(), listing.uncaptured), (2, 1));
+
+        let unknown = frame(serde_json::json!({ "type": "auth-status", "site": "nope", "authenticated": true }));
+        assert_eq!(manager.handle_extension_message(unknown), Err("Unsupported site: nope".to_string()));
+        let logout = frame(serde_json::json!({ "type": "auth-status", "site": "chatgpt", "authenticated": false }));
+        manager.handle_extension_message(logout).unwrap();
+        assert!(!manager.get_auth_status(Some("chatgpt")).authenticated);
+
+        manager.disconnect_extension(connection.id);
+        assert_eq!(manager.get_status().extension, ExtensionStatus::default());
+    }
+
+    #[test]
+    fn test_resent_messages_with_fresh_ids_are_skipped() {
+        let dir = TempDir::new().unwrap();
+        let manager = BrowserManager::new(dir.path());
+        let first = vec![
+            message("u1", "user", "Where should we go in May?"),
+            message("a1", "assistant", "Lisbon is  mild\nin May."),
+        ];
+        assert_eq!(manager.process_capture(capture(first, true)).new_messages, 2);
+        manager.mark_indexed("conv", 7);
+
+        // The page reloaded: same content, new IDs, whitespace reflowed
+        let resent = vec![
+            message("u1-reload", "user", "Where should we go in May?"),
+            message("a1-reload", "assistant", "Lisbon is mild in May."),
+            message("u2", "user", "And in June?"),
+        ];
+        assert_eq!(manager.process_capture(capture(resent, true)).new_messages, 1);
+
+        let conv = manager.get_conversation("conv").unwrap();
+        assert_eq!(conv.messages.len(), 3);
+        assert_eq!(conv.message_count, 3);
+        assert!(!conv.indexed);
+        assert_eq!(conv.document_id, Some(7));
+        assert_eq!(manager.get_capture_stats().duplicates_skipped, 2);
+    }
+
+    #[test]
+    fn test_edited_message_replaces_instead_of_appending() {
+        let dir = TempDir::new().unwrap();
+        let manager = BrowserManager::new(dir.path());
+        let first = vec![
+            message("u1", "user", "Summarize chapter one"),
+            message("a1", "assistant", "Chapter one introduces"),
+        ];
+        manager.process_capture(capture(first, true));
+
+        // Streaming update under the same ID
+        manager.process_capture(capture(
+            vec![message("a1", "assistant", "Chapter one introduces the narrator.")],
+            false,
+        ));
+        // The user edited their prompt; the site assigned new IDs to both turns
+        let edited = vec![
+            message("u1-v2", "user", "Summarize chapter two"),
+            message("a1-v2", "assistant", "Chapter two moves to Paris."),
+        ];
+        assert_eq!(manager.process_capture(capture(edited, true)).new_messages, 0);
+
+        let conv = manager.get_conversation("conv").unwrap();
+        let contents: Vec<&str> = conv.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["Summarize chapter two", "Chapter two moves to Paris."]);
+        assert_eq!(manager.get_capture_stats().duplicates_skipped, 0);
+    }
+
+    #[test]
+    fn test_temporary_id_is_merged_into_permanent_id() {
+        let dir = TempDir::new().unwrap();
+        let manager = BrowserManager::new(dir.path());
+
+        // Before the first response the site shows a temporary id
+        let mut first = capture(vec![message("u1", "user", "Plan a week in Japan")], true);
+        first.conversation_id = "tmp-1".to_string();
+        manager.process_capture(first);
+        manager.mark_indexed("tmp-1", 3);
+
+        // The id changed mid-session; the page resends the prompt under new IDs
+        let mut second = capture(
+            vec![
+                message("u1-perm", "user", "Plan a week in  Japan"),
+                message("a1", "assistant", "Start in Tokyo."),
+            ],
+            true,
+        );
+        second.previous_id = Some("tmp-1".to_string());
+        let outcome = manager.process_capture(second);
+        assert_eq!(outcome.new_messages, 1);
+        assert_eq!(
+            outcome.merged,
+            Some(MergedConversation {
+                source_id: "tmp-1".to_string(),
+                target_id: "conv".to_string(),
+                site: "chatgpt".to_string(),
+                source_document_id: Some(3),
+                added_messages: 1,
+            })
+        );
+
+        assert!(manager.get_conversation("tmp-1").is_none());
+        let conv = manager.get_conversation("conv").unwrap();
+        let ids: Vec<&str> = conv.messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["u1", "a1"]);
+        assert!(conv.messages.iter().all(|m| m.conversation_id == "conv"));
+        assert_eq!(conv.document_id, None);
+        assert_eq!(manager.get_status().capture_stats.conversations_tracked, 1);
+
+        // A stale hint is ignored
+        let mut third = capture(vec![message("u2", "user", "And Kyoto?")], false);
+        third.previous_id = Some("tmp-1".to_string());
+        assert_eq!(manager.process_capture(third).merged, None);
+    }
+
+    #[test]
+    fn test_forget_mentions_scrubs_messages() {
+        let dir = TempDir::new().unwrap();
+        let manager = BrowserManager::new(dir.path());
+        manager.process_capture(capture(
+            vec![
+                message("u1", "user", "Draft a card for Marta Kowalski"),
+                message("a1", "assistant", "Happy birthday, Marta Kowalski!"),
+                message("u2", "user", "Now one for the neighbours"),
+            ],
+            true,
+        ));
+        manager.mark_indexed("conv", 4);
+        let mut only = capture(vec![message("x1", "user", "marta kowalski's address?")], true);
+        only.conversation_id = "only".to_string();
+        manager.process_capture(only);
+
+        let preview = manager.forget_mentions("Marta Kowalski", true);
+        assert_eq!((preview.conversations, preview.conversations_deleted, preview.messages), (2, 1, 3));
+        assert_eq!(preview.document_ids, vec![4]);
+        assert_eq!(manager.get_conversation("conv").unwrap().message_count, 3);
+
+        assert_eq!(manager.forget_mentions("Marta Kowalski", false), preview);
+        let conv = manager.get_conversation("conv").unwrap();
+        assert_eq!(conv.messages.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["u2"]);
+        assert_eq!((conv.message_count, conv.indexed, conv.document_id), (1, false, None));
+        assert!(manager.get_conversation("only").is_none());
+        assert_eq!(manager.forget_mentions("Marta Kowalski", true), ForgottenMessages::default());
+    }
+
+    /// A stored `site` conversation of `messages` messages last updated
+    /// `days_ago` days before `now`.
+    fn aged(manager: &BrowserManager, id: &str, site: &str, messages: usize, days_ago: i64, now: chrono::DateTime<chrono::Utc>) {
+        let updated = (now - chrono::Duration::days(days_ago)).to_rfc3339();
+        let conversation = CapturedConversation {
+            id: id.to_string(),
+            site: site.to_string(),
+            title: Some(id.to_string()),
+            url: format!("https://{}.example/{}", site, id),
+            messages: (0..messages)
+                .map(|i| CapturedMessage {
+                    site: site.to_string(),
+                    conversation_id: id.to_string(),
+                    ..message(&format!("{}-{}", id, i), ["user", "assistant"][i % 2], &format!("{} message {}", id, i))
+                })
+                .collect(),
+            created_at: updated.clone(),
+            updated_at: updated,
+            indexed: true,
+            message_count: messages,
+            document_id: Some(7),
+        };
+        manager.conversations.update(id, |_| (conversation, ()));
+    }
+
+    fn set_retention(manager: &BrowserManager, site: &str, policy: retention::RetentionPolicy) {
+        manager.config.write().retention.insert(site.to_string(), policy);
+    }
+
+    #[test]
+    fn test_retention_deletes_conversations_past_max_age() {
+        let dir = TempDir::new().unwrap();
+        let manager = BrowserManager::new(dir.path());
+        let now = chrono::Utc::now();
+        aged(&manager, "old", "claude", 2, 120, now);
+        aged(&manager, "recent", "claude", 2, 10, now);
+        aged(&manager, "other-site", "chatgpt", 2, 400, now);
+        set_retention(&manager, "claude", retention::RetentionPolicy { max_age_days: Some(90), ..Default::default() });
+
+        let report = manager.apply_retention(now);
+        assert_eq!(report.deleted.len(), 1);
+        assert_eq!((report.deleted[0].id.as_str(), report.deleted[0].reason), ("old", RemovalReason::MaxAge));
+        assert_eq!(report.deleted[0].document_id, Some(7));
+        assert!(report.bytes_reclaimed > 0);
+        assert!(manager.get_conversation("old").is_none());
+        assert!(manager.get_conversation("recent").is_some());
+        assert!(manager.get_conversation("other-site").is_some());
+
+        let stats = manager.get_capture_stats();
+        assert_eq!(stats.reclaimed_bytes, report.bytes_reclaimed);
+        assert!(stats.last_cleanup_at.is_some());
+        assert!(manager.apply_retention(now).is_empty());
+    }
+
+    #[test]
+    fn test_retention_keeps_most_recent_conversations() {
+        let dir = TempDir::new().unwrap();
+        let manager = BrowserManager::new(dir.path());
+        let now = chrono::Utc::now();
+        for (id, days) in [("a", 1), ("b", 5), ("c", 3), ("d", 9)] {
+            aged(&manager, id, "gemini", 1, days, now);
         }
+        // The "*" policy covers sites without their own
+        set_retention(&manager, "*", retention::RetentionPolicy { max_conversations: Some(2), ..Default::default() });
+
+        let report = manager.apply_retention(now);
+        let mut deleted: Vec<_> = report.deleted.iter().map(|d| (d.id.as_str(), d.reason)).collect();
+        deleted.sort_by_key(|(id, _)| *id);
+        assert_eq!(deleted, vec![("b", RemovalReason::MaxConversations), ("d", RemovalReason::MaxConversations)]);
+        assert_eq!(manager.get_conversation_summaries(1, 10, None).1, 2);
+        assert!(report.trimmed.is_empty());
+    }
+
+    #[test]
+    fn test_retention_trims_messages_behind_a_marker() {
+        let dir = TempDir::new().unwrap();
+        let manager = BrowserManager::new(dir.path());
+        let now = chrono::Utc::now();
+        aged(&manager, "long", "claude", 10, 1, now);
+        aged(&manager, "short", "claude", 3, 1, now);
+        set_retention(&manager, "claude", retention::RetentionPolicy { max_messages: Some(4), ..Default::default() });
+
+        let report = manager.apply_retention(now);
+        assert!(report.deleted.is_empty());
+        assert_eq!(report.trimmed.len(), 1);
+        assert_eq!((report.trimmed[0].messages_removed, report.messages_removed), (6, 6));
+        assert!(report.bytes_reclaimed > 0);
+        let long = manager.get_conversation("long").unwrap();
+        assert_eq!(long.message_count, 5);
+        assert_eq!(long.messages[0].content, "[6 earlier messages removed by the retention policy]");
+        assert_eq!(long.messages[1].id, "long-6");
+        // Refreshing (the default) marks the conversation for re-indexing
+        assert_eq!((long.indexed, long.document_id), (false, Some(7)));
+        assert_eq!(manager.get_conversation("short").unwrap().message_count, 3);
+
+        // Later messages trim again into the same marker
+        manager.conversations.modify("long", |c| {
+            c.messages.push(message("long-10", "user", "one more"));
+            c.messages.push(message("long-11", "assistant", "and a reply"));
+            c.message_count = c.messages.len();
+            c.indexed = true;
+        });
+        manager.config.write().retention_documents = TrimmedDocuments::Keep;
+        let report = manager.apply_retention(now);
+        assert_eq!(report.messages_removed, 2);
+        let long = manager.get_conversation("long").unwrap();
+        assert_eq!(long.message_count, 5);
+        assert_eq!(long.messages[0].content, "[8 earlier messages removed by the retention policy]");
+        assert_eq!(long.messages[1].id, "long-8");
+        // Kept documents stay marked indexed
+        assert!(long.indexed);
+    }
+
+    #[test]
+    fn test_merge_existing_duplicates() {
+        let dir = TempDir::new().unwrap();
+        let manager = BrowserManager::new(dir.path());
+        let mut duplicate = capture(
+            vec![
+                message("u1", "user", "Where should we go in May?"),
+                message("a1", "assistant", "Lisbon."),
+            ],
+            true,
+        );
+        duplicate.conversation_id = "dup".to_string();
+        manager.process_capture(duplicate);
+        manager.process_capture(capture(
+            vec![
+                message("u1-b", "user", "Where should we go in May?"),
+                message("a1-b", "assistant", "Lisbon."),
+                message("u2", "user", "And in June?"),
+            ],
+            true,
+        ));
+        manager.mark_indexed("conv", 9);
+
+        let merged = manager.merge_conversations("dup", "conv").unwrap();
+        assert_eq!(merged.added_messages, 0);
+        let conv = manager.get_conversation("conv").unwrap();
+        assert_eq!(conv.message_count, 3);
+        assert!(conv.indexed);
+        assert_eq!(conv.document_id, Some(9));
+        assert!(manager.get_conversation("dup").is_none());
+
+        assert!(manager.merge_conversations("conv", "conv").is_err());
+        assert!(manager.merge_conversations("dup", "conv").is_err());
     }
 }
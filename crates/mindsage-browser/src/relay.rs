@@ -0,0 +1,136 @@
+//! Extension relay — the companion extension's WebSocket connection.
+//!
+//! The extension keeps one socket open; the server pushes `ServerMessage`s
+//! through the sender registered here, and the socket task hands the
+//! extension's frames to `BrowserManager::handle_extension_message`. A new
+//! connection replaces the previous one, whose command stream then ends.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::{Mutex, RwLock};
+use tokio::sync::mpsc;
+
+use crate::types::{ConversationListing, ExtensionStatus, ServerMessage};
+
+struct Connection {
+    id: u64,
+    version: Option<String>,
+    connected_at: String,
+    last_seen_at: String,
+    commands: mpsc::UnboundedSender<ServerMessage>,
+}
+
+/// Commands for the socket of one extension connection.
+pub struct ExtensionConnection {
+    pub id: u64,
+    pub commands: mpsc::UnboundedReceiver<ServerMessage>,
+}
+
+/// The extension connection, if any, and what it last reported.
+#[derive(Default)]
+pub struct ExtensionRelay {
+    connection: Mutex<Option<Connection>>,
+    next_id: AtomicU64,
+    listings: RwLock<HashMap<String, ConversationListing>>,
+}
+
+impl ExtensionRelay {
+    /// Register a new connection, replacing the current one.
+    pub fn connect(&self) -> ExtensionConnection {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let (sender, commands) = mpsc::unbounded_channel();
+        let now = chrono::Utc::now().to_rfc3339();
+        *self.connection.lock() = Some(Connection {
+            id,
+            version: None,
+            connected_at: now.clone(),
+            last_seen_at: now,
+            commands: sender,
+        });
+        ExtensionConnection { id, commands }
+    }
+
+    /// Forget connection `id`, unless it was already replaced.
+    pub fn disconnect(&self, id: u64) {
+        let mut connection = self.connection.lock();
+        if connection.as_ref().is_some_and(|c| c.id == id) {
+            *connection = None;
+        }
+    }
+
+    /// Queue `message` for the extension. False when none is connected.
+    pub fn send(&self, message: ServerMessage) -> bool {
+        self.connection
+            .lock()
+            .as_ref()
+            .is_some_and(|c| c.commands.send(message).is_ok())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.lock().is_some()
+    }
+
+    pub fn status(&self) -> ExtensionStatus {
+        match self.connection.lock().as_ref() {
+            Some(c) => ExtensionStatus {
+                connected: true,
+                version: c.version.clone(),
+                connected_at: Some(c.connected_at.clone()),
+                last_seen_at: Some(c.last_seen_at.clone()),
+            },
+            None => ExtensionStatus::default(),
+        }
+    }
+
+    /// Record a frame from the extension, and its version if it sent one.
+    pub(crate) fn seen(&self, version: Option<String>) {
+        if let Some(c) = self.connection.lock().as_mut() {
+            c.last_seen_at = chrono::Utc::now().to_rfc3339();
+            if version.is_some() {
+                c.version = version;
+            }
+        }
+    }
+
+    pub(crate) fn store_listing(&self, listing: ConversationListing) {
+        self.listings.write().insert(listing.site.clone(), listing);
+    }
+
+    pub fn listing(&self, site: &str) -> Option<ConversationListing> {
+        self.listings.read().get(site).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_connection_replaces_the_old_one() {
+        let relay = ExtensionRelay::default();
+        assert!(!relay.send(ServerMessage::Ping));
+        assert!(!relay.status().connected);
+
+        let mut first = relay.connect();
+        relay.seen(Some("1.4.0".to_string()));
+        assert!(relay.send(ServerMessage::Ping));
+        assert_eq!(first.commands.try_recv().unwrap(), ServerMessage::Ping);
+        assert_eq!(relay.status().version.as_deref(), Some("1.4.0"));
+
+        let mut second = relay.connect();
+        // The replaced socket's command stream ends
+        assert!(first.commands.try_recv().is_err());
+        assert!(first.commands.is_closed());
+        // and its late disconnect leaves the new connection alone
+        relay.disconnect(first.id);
+        assert!(relay.is_connected());
+        assert_eq!(relay.status().version, None);
+        assert!(relay.send(ServerMessage::Ping));
+        assert_eq!(second.commands.try_recv().unwrap(), ServerMessage::Ping);
+
+        relay.disconnect(second.id);
+        assert!(!relay.is_connected());
+        assert!(!relay.send(ServerMessage::Ping));
+    }
+}
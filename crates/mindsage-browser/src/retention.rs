@@ -0,0 +1,141 @@
+//! Retention limits for captured conversations.
+//!
+//! Policies are set per site in the connector config (`retention`), with
+//! `"*"` for sites that have no entry of their own. A cleanup deletes
+//! conversations older than the age limit or beyond the count limit (the
+//! least recently updated go first), and trims long conversations to their
+//! latest messages behind a single marker message that counts what was
+//! removed.
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{CapturedConversation, CapturedMessage};
+
+/// Key of the policy applied to sites without their own.
+pub const ALL_SITES: &str = "*";
+/// Metadata key on the trim marker: messages removed before it so far.
+pub const TRIMMED_KEY: &str = "retentionTrimmed";
+
+/// Limits for one site's conversations; unset limits do not apply.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    /// Conversations not updated for this many days are deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u32>,
+    /// Most recently updated conversations kept; older ones are deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_conversations: Option<usize>,
+    /// Latest messages kept per conversation; earlier ones are replaced by
+    /// a marker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_messages: Option<usize>,
+}
+
+impl RetentionPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.max_age_days.is_none() && self.max_conversations.is_none() && self.max_messages.is_none()
+    }
+}
+
+/// What a cleanup does with the vector-store document of a conversation it
+/// trims.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrimmedDocuments {
+    /// Re-index the trimmed conversation, replacing the document's text.
+    #[default]
+    Refresh,
+    /// Leave the document with the full conversation.
+    Keep,
+}
+
+/// Why a cleanup deleted a conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalReason {
+    MaxAge,
+    MaxConversations,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovedConversation {
+    pub id: String,
+    pub site: String,
+    pub reason: RemovalReason,
+    /// The conversation's document, which is left in the vector store.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrimmedConversation {
+    pub id: String,
+    pub site: String,
+    pub messages_removed: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document_id: Option<i64>,
+}
+
+/// What a cleanup removed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionReport {
+    pub deleted: Vec<RemovedConversation>,
+    pub trimmed: Vec<TrimmedConversation>,
+    /// Messages removed by trimming, not counting deleted conversations.
+    pub messages_removed: usize,
+    /// Size of the deleted conversation files plus what trimming saved.
+    pub bytes_reclaimed: u64,
+    /// Documents of trimmed conversations re-indexed (`TrimmedDocuments::Refresh`).
+    pub documents_refreshed: usize,
+    pub documents_failed: usize,
+}
+
+impl RetentionReport {
+    pub fn is_empty(&self) -> bool {
+        self.deleted.is_empty() && self.trimmed.is_empty()
+    }
+}
+
+/// Keep the latest `max_messages` messages of `conversation` behind one
+/// marker message. A marker from an earlier trim is replaced, adding up
+/// both counts, and is not counted as a message. Returns how many messages
+/// were removed.
+pub fn trim_messages(conversation: &mut CapturedConversation, max_messages: usize) -> usize {
+    let previous = conversation.messages.first().and_then(trimmed_count);
+    let skip = usize::from(previous.is_some());
+    let messages = conversation.messages.len() - skip;
+    if messages <= max_messages {
+        return 0;
+    }
+    let removed = messages - max_messages;
+    let kept = conversation.messages.split_off(skip + removed);
+    let total = previous.unwrap_or(0) + removed;
+    let timestamp = kept
+        .first()
+        .map(|m| m.timestamp.clone())
+        .unwrap_or_else(|| conversation.updated_at.clone());
+    let marker = CapturedMessage {
+        id: format!("{}-retention-trimmed", conversation.id),
+        conversation_id: conversation.id.clone(),
+        role: "system".to_string(),
+        content: format!("[{} earlier messages removed by the retention policy]", total),
+        timestamp,
+        site: conversation.site.clone(),
+        metadata: Some(serde_json::json!({ TRIMMED_KEY: total })),
+    };
+    conversation.messages = std::iter::once(marker).chain(kept).collect();
+    conversation.message_count = conversation.messages.len();
+    removed
+}
+
+/// Messages a trim marker stands for, or `None` for other messages.
+fn trimmed_count(message: &CapturedMessage) -> Option<usize> {
+    if message.role != "system" {
+        return None;
+    }
+    message.metadata.as_ref()?.get(TRIMMED_KEY)?.as_u64().map(|n| n as usize)
+}
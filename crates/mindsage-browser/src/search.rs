@@ -0,0 +1,350 @@
+//! Search over captured conversations, indexed or not.
+//!
+//! Conversations live one file each, so search is a scan: the summary index
+//! narrows candidates by site and `updated_at` range, then each candidate is
+//! read from disk and matched case-insensitively against every query term.
+//! Sorted by recency, the scan stops once `limit` conversations match;
+//! sorted by relevance, it reads at most `MAX_SCANNED` conversations, most
+//! recent first, and says when it stopped short.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::ConversationStore;
+use crate::types::{CapturedConversation, ConversationSummary};
+
+/// Conversations read per search at most.
+pub const MAX_SCANNED: usize = 5000;
+/// Snippets returned per conversation.
+const MAX_SNIPPETS: usize = 3;
+/// Characters of context kept before and after the first hit in a snippet.
+const SNIPPET_BEFORE: usize = 60;
+const SNIPPET_AFTER: usize = 100;
+/// A title hit counts this many message hits.
+const TITLE_WEIGHT: usize = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchSort {
+    #[default]
+    Relevance,
+    Recency,
+}
+
+/// A conversation search request.
+#[derive(Debug, Clone)]
+pub struct ConversationSearch {
+    pub query: String,
+    pub site: Option<String>,
+    /// Only conversations updated at or after this time.
+    pub updated_after: Option<DateTime<Utc>>,
+    /// Only conversations updated before this time.
+    pub updated_before: Option<DateTime<Utc>>,
+    pub sort: SearchSort,
+    pub limit: usize,
+}
+
+/// A message that matched, with the text around the first hit.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageSnippet {
+    #[serde(rename = "messageId")]
+    pub message_id: String,
+    pub role: String,
+    pub snippet: String,
+}
+
+/// A matching conversation.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationHit {
+    pub conversation: ConversationSummary,
+    /// Messages containing at least one query term.
+    #[serde(rename = "matchCount")]
+    pub match_count: usize,
+    pub score: usize,
+    pub snippets: Vec<MessageSnippet>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationSearchResults {
+    pub results: Vec<ConversationHit>,
+    /// Conversations read from disk.
+    pub scanned: usize,
+    /// Whether the scan stopped before reading every candidate.
+    pub truncated: bool,
+}
+
+impl ConversationStore {
+    /// Search titles and message contents. Every query term must appear in
+    /// the title or in some message.
+    pub fn search(&self, search: &ConversationSearch) -> ConversationSearchResults {
+        let terms: Vec<String> = search.query.split_whitespace().map(|t| t.to_lowercase()).collect();
+        let (candidates, _) = self.summaries(1, usize::MAX, search.site.as_deref());
+        let candidates: Vec<ConversationSummary> = candidates
+            .into_iter()
+            .filter(|c| in_range(&c.updated_at, search.updated_after, search.updated_before))
+            .collect();
+
+        let mut results = Vec::new();
+        let mut scanned = 0;
+        let mut truncated = false;
+        for summary in &candidates {
+            let done = match search.sort {
+                SearchSort::Recency => results.len() >= search.limit,
+                SearchSort::Relevance => scanned >= MAX_SCANNED,
+            };
+            if done {
+                truncated = true;
+                break;
+            }
+            scanned += 1;
+            let Some(conv) = self.get(&summary.id) else {
+                continue;
+            };
+            results.extend(match_conversation(&conv, &terms));
+        }
+
+        if search.sort == SearchSort::Relevance {
+            // Stable sort keeps recency order among equal scores
+            results.sort_by_key(|h| std::cmp::Reverse(h.score));
+        }
+        results.truncate(search.limit);
+        ConversationSearchResults {
+            results,
+            scanned,
+            truncated,
+        }
+    }
+}
+
+fn in_range(updated_at: &str, after: Option<DateTime<Utc>>, before: Option<DateTime<Utc>>) -> bool {
+    if after.is_none() && before.is_none() {
+        return true;
+    }
+    let Ok(updated) = DateTime::parse_from_rfc3339(updated_at) else {
+        return false;
+    };
+    let updated = updated.with_timezone(&Utc);
+    after.map_or(true, |a| updated >= a) && before.map_or(true, |b| updated < b)
+}
+
+/// Score a conversation against lowercased `terms`; `None` unless every
+/// term occurs in the title or a message.
+fn match_conversation(conv: &CapturedConversation, terms: &[String]) -> Option<ConversationHit> {
+    if terms.is_empty() {
+        return None;
+    }
+    let title = conv.title.as_deref().unwrap_or_default().to_lowercase();
+    let mut found = vec![false; terms.len()];
+    let mut score = 0;
+    for (i, term) in terms.iter().enumerate() {
+        let count = title.matches(term.as_str()).count();
+        found[i] |= count > 0;
+        score += count * TITLE_WEIGHT;
+    }
+
+    let mut match_count = 0;
+    let mut snippets = Vec::new();
+    for message in &conv.messages {
+        let content = message.content.to_lowercase();
+        let mut first_term = None;
+        for (i, term) in terms.iter().enumerate() {
+            let count = content.matches(term.as_str()).count();
+            if count > 0 {
+                found[i] = true;
+                score += count;
+                first_term.get_or_insert(term);
+            }
+        }
+        let Some(term) = first_term else {
+            continue;
+        };
+        match_count += 1;
+        if snippets.len() < MAX_SNIPPETS {
+            snippets.push(MessageSnippet {
+                message_id: message.id.clone(),
+                role: message.role.clone(),
+                snippet: snippet(&message.content, term),
+            });
+        }
+    }
+
+    found.iter().all(|f| *f).then(|| ConversationHit {
+        conversation: ConversationSummary::from(conv),
+        match_count,
+        score,
+        snippets,
+    })
+}
+
+/// Text around the first case-insensitive occurrence of `term` (already
+/// lowercased), with whitespace collapsed and elided ends marked.
+fn snippet(content: &str, term: &str) -> String {
+    let term_chars = term.chars().count();
+    let chars: Vec<char> = content.chars().collect();
+    let hit = (0..chars.len())
+        .find(|&i| {
+            chars[i..]
+                .iter()
+                .flat_map(|c| c.to_lowercase())
+                .take(term_chars)
+                .eq(term.chars())
+        })
+        .unwrap_or(0);
+    let start = hit.saturating_sub(SNIPPET_BEFORE);
+    let end = (hit + term_chars + SNIPPET_AFTER).min(chars.len());
+    let text: String = chars[start..end].iter().collect();
+    let mut out = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if start > 0 {
+        out.insert(0, '…');
+    }
+    if end < chars.len() {
+        out.push('…');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CapturedMessage;
+    use tempfile::TempDir;
+
+    fn conversation(id: &str, site: &str, title: &str, updated_at: &str, messages: &[&str]) -> CapturedConversation {
+        CapturedConversation {
+            id: id.to_string(),
+            site: site.to_string(),
+            title: Some(title.to_string()),
+            url: format!("https://{}.example/{}", site, id),
+            messages: messages
+                .iter()
+                .enumerate()
+                .map(|(i, content)| CapturedMessage {
+                    id: format!("{}-{}", id, i),
+                    conversation_id: id.to_string(),
+                    role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                    content: content.to_string(),
+                    timestamp: updated_at.to_string(),
+                    site: site.to_string(),
+                    metadata: None,
+                })
+                .collect(),
+            created_at: updated_at.to_string(),
+            updated_at: updated_at.to_string(),
+            indexed: false,
+            message_count: messages.len(),
+            document_id: None,
+        }
+    }
+
+    fn fixture(dir: &TempDir) -> ConversationStore {
+        let store = ConversationStore::open(dir.path());
+        for conv in [
+            conversation(
+                "sourdough",
+                "chatgpt",
+                "Sourdough starter",
+                "2025-03-01T10:00:00Z",
+                &["My starter smells like acetone", "Feed the starter twice a day; acetone means it is hungry."],
+            ),
+            conversation(
+                "rust",
+                "claude",
+                "Borrow checker",
+                "2025-03-05T10:00:00Z",
+                &["Why does the borrow checker reject this?", "The starter value is moved into the closure."],
+            ),
+            conversation(
+                "bread",
+                "claude",
+                "Baking schedule",
+                "2025-02-10T10:00:00Z",
+                &["Plan a SOURDOUGH bake for Saturday", "Mix on Friday night and bake Saturday morning."],
+            ),
+        ] {
+            store.update(&conv.id.clone(), |_| (conv, ()));
+        }
+        store
+    }
+
+    fn search(query: &str) -> ConversationSearch {
+        ConversationSearch {
+            query: query.to_string(),
+            site: None,
+            updated_after: None,
+            updated_before: None,
+            sort: SearchSort::Relevance,
+            limit: 10,
+        }
+    }
+
+    fn ids(results: &ConversationSearchResults) -> Vec<&str> {
+        results.results.iter().map(|h| h.conversation.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_relevance_and_recency_order() {
+        let dir = TempDir::new().unwrap();
+        let store = fixture(&dir);
+
+        let results = store.search(&search("starter"));
+        // The title hit outweighs a single message hit in a newer conversation
+        assert_eq!(ids(&results), ["sourdough", "rust"]);
+        let top = &results.results[0];
+        assert_eq!(top.match_count, 2);
+        assert_eq!(top.snippets[0].snippet, "My starter smells like acetone");
+
+        let recency = store.search(&ConversationSearch {
+            sort: SearchSort::Recency,
+            ..search("starter")
+        });
+        assert_eq!(ids(&recency), ["rust", "sourdough"]);
+
+        // Case-insensitive; every term must appear somewhere
+        assert_eq!(ids(&store.search(&search("sourdough saturday"))), ["bread"]);
+        assert!(store.search(&search("sourdough closure")).results.is_empty());
+        assert!(store.search(&search("   ")).results.is_empty());
+    }
+
+    #[test]
+    fn test_filters_and_early_termination() {
+        let dir = TempDir::new().unwrap();
+        let store = fixture(&dir);
+
+        let by_site = store.search(&ConversationSearch {
+            site: Some("claude".to_string()),
+            ..search("starter")
+        });
+        assert_eq!(ids(&by_site), ["rust"]);
+
+        let march: DateTime<Utc> = "2025-03-01T00:00:00Z".parse().unwrap();
+        let in_range = store.search(&ConversationSearch {
+            updated_after: Some(march),
+            ..search("sourdough")
+        });
+        assert_eq!(ids(&in_range), ["sourdough"]);
+        let before = store.search(&ConversationSearch {
+            updated_before: Some(march),
+            ..search("sourdough")
+        });
+        assert_eq!(ids(&before), ["bread"]);
+
+        // Recency stops reading once the limit is reached
+        let first = store.search(&ConversationSearch {
+            sort: SearchSort::Recency,
+            limit: 1,
+            ..search("the")
+        });
+        assert_eq!(ids(&first), ["rust"]);
+        assert_eq!((first.scanned, first.truncated), (1, true));
+    }
+
+    #[test]
+    fn test_snippet_window() {
+        let long = format!("{} needle {}", "a ".repeat(100), "b ".repeat(100));
+        let s = snippet(&long, "needle");
+        assert!(s.starts_with('…') && s.ends_with('…'));
+        assert!(s.contains("needle"));
+        // Case-insensitive match on non-ASCII text keeps char boundaries
+        assert_eq!(snippet("Über ÄRGER heute", "ärger"), "Über ÄRGER heute");
+    }
+}
@@ -0,0 +1,258 @@
+//! Site registry — the AI chat sites the connector captures from.
+//!
+//! Built-in sites are listed here; users add others through the browser
+//! connector config (`custom_sites`). Every place that validates a site name
+//! (capture, cookie import, navigation, sync) looks it up in the registry,
+//! so adding a site needs no code change.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of title selector hints per site.
+const MAX_TITLE_SELECTORS: usize = 8;
+/// Maximum length of one title selector hint.
+const MAX_SELECTOR_LEN: usize = 200;
+
+/// Per-site capture settings, passed to the extension with the site list.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteCaptureSettings {
+    /// Index captured conversations into the vector store as they arrive,
+    /// instead of waiting for `POST /browser-connector/reindex`.
+    #[serde(default)]
+    pub auto_index: bool,
+    /// CSS selectors the extension tries, in order, to read the title.
+    #[serde(default)]
+    pub title_selectors: Vec<String>,
+}
+
+impl SiteCaptureSettings {
+    /// Drop blank title selectors and check the rest are within bounds.
+    pub fn normalized(mut self) -> Result<Self, String> {
+        self.title_selectors.retain(|s| !s.trim().is_empty());
+        if self.title_selectors.len() > MAX_TITLE_SELECTORS
+            || self.title_selectors.iter().any(|s| s.len() > MAX_SELECTOR_LEN)
+        {
+            return Err(format!(
+                "At most {} title selectors of up to {} characters",
+                MAX_TITLE_SELECTORS, MAX_SELECTOR_LEN
+            ));
+        }
+        Ok(self)
+    }
+}
+
+/// Partial change to a site; omitted fields are kept.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteUpdate {
+    pub base_url: Option<String>,
+    pub cookie_domains: Option<Vec<String>>,
+    pub capture: Option<SiteCaptureSettings>,
+}
+
+/// A site the connector can capture from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteDefinition {
+    pub name: String,
+    pub base_url: String,
+    /// Domains whose cookies are accepted for the site, with a leading dot.
+    pub cookie_domains: Vec<String>,
+    #[serde(default)]
+    pub capture: SiteCaptureSettings,
+    /// Set for the built-in sites; never read from config.
+    #[serde(default, skip_deserializing)]
+    pub builtin: bool,
+}
+
+impl SiteDefinition {
+    fn builtin(name: &str, base_url: &str, cookie_domains: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            base_url: base_url.to_string(),
+            cookie_domains: cookie_domains.iter().map(|d| d.to_string()).collect(),
+            capture: SiteCaptureSettings::default(),
+            builtin: true,
+        }
+    }
+
+    /// Whether a cookie for `domain` belongs to this site: the domain is one
+    /// of the cookie domains or a subdomain of one.
+    pub fn allows_cookie_domain(&self, domain: &str) -> bool {
+        let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+        self.cookie_domains.iter().any(|d| {
+            let d = d.trim_start_matches('.');
+            domain == d || domain.strip_suffix(d).is_some_and(|rest| rest.ends_with('.'))
+        })
+    }
+
+    /// Check and normalize a user-supplied site: lowercase name and domains,
+    /// HTTPS base URL whose host is covered by a cookie domain, and bounded
+    /// title selector hints.
+    pub fn normalized(mut self) -> Result<Self, String> {
+        self.name = self.name.trim().to_ascii_lowercase();
+        if self.name.is_empty()
+            || self.name.len() > 32
+            || self.name.starts_with('-')
+            || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err("Site name must be 1-32 characters of a-z, 0-9 and '-'".into());
+        }
+
+        let base_url = self.base_url.trim().trim_end_matches('/');
+        let host = base_url
+            .strip_prefix("https://")
+            .map(|rest| rest.split('/').next().unwrap_or_default().to_ascii_lowercase())
+            .ok_or("Base URL must start with https://")?;
+        if !is_valid_domain(&host) {
+            return Err(format!("Invalid host in base URL: {}", host));
+        }
+        self.base_url = format!("https://{}{}", host, &base_url["https://".len() + host.len()..]);
+
+        if self.cookie_domains.is_empty() {
+            return Err("At least one cookie domain is required".into());
+        }
+        let mut domains = Vec::with_capacity(self.cookie_domains.len());
+        for domain in &self.cookie_domains {
+            let bare = domain.trim().trim_start_matches('.').to_ascii_lowercase();
+            if !is_valid_domain(&bare) {
+                return Err(format!("Invalid cookie domain: {}", domain));
+            }
+            domains.push(format!(".{}", bare));
+        }
+        self.cookie_domains = domains;
+        if !self.allows_cookie_domain(&host) {
+            return Err(format!("No cookie domain covers {}", host));
+        }
+
+        self.capture = self.capture.normalized()?;
+        self.builtin = false;
+        Ok(self)
+    }
+}
+
+/// A hostname of at least two dot-separated labels of letters, digits and
+/// inner hyphens.
+fn is_valid_domain(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    domain.len() <= 253
+        && labels.len() >= 2
+        && labels.iter().all(|l| {
+            !l.is_empty()
+                && l.len() <= 63
+                && !l.starts_with('-')
+                && !l.ends_with('-')
+                && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// The sites shipped with the connector.
+pub fn builtin_sites() -> Vec<SiteDefinition> {
+    vec![
+        SiteDefinition::builtin("chatgpt", "https://chatgpt.com", &[".chatgpt.com", ".openai.com"]),
+        SiteDefinition::builtin("claude", "https://claude.ai", &[".claude.ai", ".anthropic.com"]),
+        SiteDefinition::builtin("gemini", "https://gemini.google.com", &[".google.com", ".gemini.google.com"]),
+        SiteDefinition::builtin("copilot", "https://github.com/copilot", &[".github.com"]),
+    ]
+}
+
+/// Built-in sites (with any capture settings the user changed) followed by
+/// the user's custom sites.
+#[derive(Debug, Clone)]
+pub struct SiteRegistry {
+    sites: Vec<SiteDefinition>,
+}
+
+impl SiteRegistry {
+    pub fn new(custom: &[SiteDefinition], capture_overrides: &HashMap<String, SiteCaptureSettings>) -> Self {
+        let mut sites = builtin_sites();
+        for site in &mut sites {
+            if let Some(capture) = capture_overrides.get(&site.name) {
+                site.capture = capture.clone();
+            }
+        }
+        sites.extend(
+            custom
+                .iter()
+                .filter(|c| !sites.iter().any(|b| b.name == c.name))
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        Self { sites }
+    }
+
+    /// Look up a site by name, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&SiteDefinition> {
+        self.sites.iter().find(|s| s.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SiteDefinition> {
+        self.sites.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom(name: &str, base_url: &str, domains: &[&str]) -> SiteDefinition {
+        SiteDefinition {
+            name: name.to_string(),
+            base_url: base_url.to_string(),
+            cookie_domains: domains.iter().map(|d| d.to_string()).collect(),
+            capture: SiteCaptureSettings::default(),
+            builtin: false,
+        }
+    }
+
+    #[test]
+    fn test_normalizes_and_validates_custom_sites() {
+        let site = custom(" Perplexity ", "https://WWW.Perplexity.ai/", &["perplexity.ai"])
+            .normalized()
+            .unwrap();
+        assert_eq!(site.name, "perplexity");
+        assert_eq!(site.base_url, "https://www.perplexity.ai");
+        assert_eq!(site.cookie_domains, [".perplexity.ai"]);
+
+        assert!(custom("bad name", "https://a.example", &["a.example"]).normalized().is_err());
+        assert!(custom("plain", "http://a.example", &["a.example"]).normalized().is_err());
+        assert!(custom("nohost", "https://localhost", &["localhost"]).normalized().is_err());
+        assert!(custom("bad-domain", "https://a.example", &["-a.example"]).normalized().is_err());
+        // The base URL host must be covered by a cookie domain
+        assert!(custom("mismatch", "https://chat.example.org", &["example.com"]).normalized().is_err());
+        assert!(custom("none", "https://a.example", &[]).normalized().is_err());
+    }
+
+    #[test]
+    fn test_cookie_domain_matching() {
+        let site = custom("perplexity", "https://www.perplexity.ai", &[".perplexity.ai"]);
+        assert!(site.allows_cookie_domain(".perplexity.ai"));
+        assert!(site.allows_cookie_domain("www.perplexity.ai"));
+        assert!(!site.allows_cookie_domain("notperplexity.ai"));
+        assert!(!site.allows_cookie_domain("perplexity.ai.evil.com"));
+    }
+
+    #[test]
+    fn test_registry_merges_custom_sites_and_overrides() {
+        let overrides = HashMap::from([(
+            "gemini".to_string(),
+            SiteCaptureSettings {
+                auto_index: true,
+                title_selectors: vec![],
+            },
+        )]);
+        let customs = [
+            custom("perplexity", "https://www.perplexity.ai", &[".perplexity.ai"]),
+            // A custom site cannot shadow a built-in one
+            custom("claude", "https://evil.example", &[".evil.example"]),
+        ];
+        let registry = SiteRegistry::new(&customs, &overrides);
+        assert!(registry.get("Perplexity").is_some());
+        assert_eq!(registry.get("claude").unwrap().base_url, "https://claude.ai");
+        assert!(registry.get("gemini").unwrap().capture.auto_index);
+        assert!(registry.get("copilot").unwrap().builtin);
+        assert_eq!(registry.iter().count(), builtin_sites().len() + 1);
+    }
+}
@@ -0,0 +1,496 @@
+//! Captured conversation persistence — one JSON file per conversation.
+//!
+//! Layout under the browser-connector directory:
+//! - `conversations/<id>.json` — one conversation with its messages
+//! - `conversations-index.json` — summaries of every conversation
+//!
+//! Only the index is held in memory; messages are read from disk when a
+//! conversation is requested. Every file is written to a temporary file
+//! and renamed over the old one, so a crash leaves either the old or the
+//! new version. The index is written after the conversation file; on
+//! startup, conversation files newer than the index or missing from it
+//! are re-read, and index entries whose file is gone are dropped.
+//!
+//! A legacy monolithic `conversations.json` is split into this layout on
+//! first open and renamed to `conversations.json.migrated`.
+//!
+//! A store opened with `open_read_only` loads the index the same way but
+//! never writes: no migration, no index repair, and updates are dropped.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use parking_lot::{Mutex, RwLock};
+use tracing::{info, warn};
+
+use crate::types::{CapturedConversation, ConversationSummary};
+
+const CONVERSATIONS_DIR: &str = "conversations";
+const INDEX_FILE: &str = "conversations-index.json";
+const LEGACY_FILE: &str = "conversations.json";
+const LEGACY_MIGRATED_FILE: &str = "conversations.json.migrated";
+const TMP_SUFFIX: &str = ".tmp";
+
+/// Sharded conversation storage with an in-memory summary index.
+pub struct ConversationStore {
+    dir: PathBuf,
+    index_path: PathBuf,
+    index: RwLock<HashMap<String, ConversationSummary>>,
+    /// Serializes read-modify-write of conversations and index writes.
+    write_lock: Mutex<()>,
+    read_only: bool,
+}
+
+impl ConversationStore {
+    /// Open the store under `data_dir`, migrating a legacy
+    /// `conversations.json` and reconciling the index with the files.
+    pub fn open(data_dir: &Path) -> Self {
+        let store = Self::at(data_dir, false);
+        if let Err(e) = std::fs::create_dir_all(&store.dir) {
+            warn!("Failed to create conversations directory: {}", e);
+        }
+        store.migrate_legacy(data_dir);
+        store.load_index();
+        store
+    }
+
+    /// Open the store under `data_dir` without writing anything there.
+    pub fn open_read_only(data_dir: &Path) -> Self {
+        let store = Self::at(data_dir, true);
+        store.load_index();
+        store
+    }
+
+    fn at(data_dir: &Path, read_only: bool) -> Self {
+        Self {
+            dir: data_dir.join(CONVERSATIONS_DIR),
+            index_path: data_dir.join(INDEX_FILE),
+            index: RwLock::new(HashMap::new()),
+            write_lock: Mutex::new(()),
+            read_only,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.read().is_empty()
+    }
+
+    /// Summaries matching `site`, most recently updated first, paged
+    /// (1-based), with the total match count.
+    pub fn summaries(&self, page: usize, page_size: usize, site: Option<&str>) -> (Vec<ConversationSummary>, usize) {
+        let index = self.index.read();
+        let mut filtered: Vec<&ConversationSummary> = index
+            .values()
+            .filter(|c| site.map_or(true, |s| c.site == s))
+            .collect();
+        filtered.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        let total = filtered.len();
+        let start = page.saturating_sub(1) * page_size;
+        let paged = filtered.into_iter().skip(start).take(page_size).cloned().collect();
+        (paged, total)
+    }
+
+    /// Read one conversation with its messages.
+    pub fn get(&self, id: &str) -> Option<CapturedConversation> {
+        if !self.index.read().contains_key(id) {
+            return None;
+        }
+        read_conversation(&self.conversation_path(id))
+    }
+
+    /// Apply `f` to the stored conversation (or `None`) and persist the
+    /// result. Returns what `f` returned alongside the conversation.
+    pub fn update<T>(&self, id: &str, f: impl FnOnce(Option<CapturedConversation>) -> (CapturedConversation, T)) -> T {
+        let Ok(result) = self.try_update(id, |existing| Ok::<_, std::convert::Infallible>(f(existing)));
+        result
+    }
+
+    /// Like `update`, but `f` may refuse; its error is returned and nothing
+    /// is written.
+    pub fn try_update<T, E>(
+        &self,
+        id: &str,
+        f: impl FnOnce(Option<CapturedConversation>) -> Result<(CapturedConversation, T), E>,
+    ) -> Result<T, E> {
+        let _guard = self.write_lock.lock();
+        let existing = self.get(id);
+        let (conversation, result) = f(existing)?;
+        if self.read_only {
+            warn!("Read-only mode: conversation not saved");
+            return Ok(result);
+        }
+        if let Err(e) = write_atomic(&self.conversation_path(&conversation.id), &conversation) {
+            warn!("Failed to save conversation: {}", e);
+            return Ok(result);
+        }
+        self.index
+            .write()
+            .insert(conversation.id.clone(), ConversationSummary::from(&conversation));
+        self.save_index();
+        Ok(result)
+    }
+
+    /// Apply `f` to a stored conversation and persist it. Returns false when
+    /// the conversation does not exist.
+    pub fn modify(&self, id: &str, f: impl FnOnce(&mut CapturedConversation)) -> bool {
+        if self.read_only {
+            return false;
+        }
+        let _guard = self.write_lock.lock();
+        let Some(mut conversation) = self.get(id) else {
+            return false;
+        };
+        f(&mut conversation);
+        if let Err(e) = write_atomic(&self.conversation_path(id), &conversation) {
+            warn!("Failed to save conversation: {}", e);
+            return false;
+        }
+        self.index
+            .write()
+            .insert(conversation.id.clone(), ConversationSummary::from(&conversation));
+        self.save_index();
+        true
+    }
+
+    /// Delete a conversation. Returns whether it existed.
+    pub fn remove(&self, id: &str) -> bool {
+        if self.read_only {
+            return false;
+        }
+        let _guard = self.write_lock.lock();
+        if self.index.write().remove(id).is_none() {
+            return false;
+        }
+        if let Err(e) = std::fs::remove_file(self.conversation_path(id)) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to delete conversation file: {}", e);
+            }
+        }
+        self.save_index();
+        true
+    }
+
+    /// Size of a conversation's file in bytes, 0 when it has none.
+    pub fn file_size(&self, id: &str) -> u64 {
+        std::fs::metadata(self.conversation_path(id)).map(|m| m.len()).unwrap_or(0)
+    }
+
+    fn conversation_path(&self, id: &str) -> PathBuf {
+        self.dir.join(file_name(id))
+    }
+
+    /// Rewrite the index file from memory.
+    pub fn flush(&self) {
+        let _guard = self.write_lock.lock();
+        self.save_index();
+    }
+
+    fn save_index(&self) {
+        if self.read_only {
+            return;
+        }
+        let index = self.index.read();
+        if let Err(e) = write_atomic(&self.index_path, &*index) {
+            warn!("Failed to save conversation index: {}", e);
+        }
+    }
+
+    /// Split a legacy `conversations.json` into per-conversation files.
+    /// The legacy file is renamed only after every file and the index have
+    /// been written; an unparsable file is left in place.
+    fn migrate_legacy(&self, data_dir: &Path) {
+        let legacy = data_dir.join(LEGACY_FILE);
+        let Ok(data) = std::fs::read_to_string(&legacy) else {
+            return;
+        };
+        let conversations: HashMap<String, CapturedConversation> = match serde_json::from_str(&data) {
+            Ok(c) => c,
+            Err(e) => {
+                warn!("Could not parse {}; leaving it in place: {}", legacy.display(), e);
+                return;
+            }
+        };
+
+        let mut index = HashMap::with_capacity(conversations.len());
+        for conversation in conversations.values() {
+            if let Err(e) = write_atomic(&self.conversation_path(&conversation.id), conversation) {
+                warn!("Conversation migration failed, will retry on next start: {}", e);
+                return;
+            }
+            index.insert(conversation.id.clone(), ConversationSummary::from(conversation));
+        }
+        if let Err(e) = write_atomic(&self.index_path, &index) {
+            warn!("Conversation migration failed, will retry on next start: {}", e);
+            return;
+        }
+        if let Err(e) = std::fs::rename(&legacy, data_dir.join(LEGACY_MIGRATED_FILE)) {
+            warn!("Failed to rename migrated conversations file: {}", e);
+        }
+        info!("Migrated {} conversations to per-conversation files", index.len());
+    }
+
+    /// Load the index and bring it in line with the conversation files.
+    fn load_index(&self) {
+        let index_file: Option<(HashMap<String, ConversationSummary>, SystemTime)> =
+            std::fs::read_to_string(&self.index_path)
+                .ok()
+                .and_then(|data| serde_json::from_str(&data).ok())
+                .zip(std::fs::metadata(&self.index_path).and_then(|m| m.modified()).ok());
+        if index_file.is_none() && self.index_path.exists() {
+            warn!("Conversation index is unreadable; rebuilding it from conversation files");
+        }
+        let (mut loaded, index_mtime) = index_file.unwrap_or((HashMap::new(), SystemTime::UNIX_EPOCH));
+
+        let mut on_disk: HashMap<String, PathBuf> = HashMap::new();
+        if let Ok(entries) = std::fs::read_dir(&self.dir) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.ends_with(TMP_SUFFIX) {
+                    // Interrupted write: the previous version is intact
+                    if !self.read_only {
+                        let _ = std::fs::remove_file(entry.path());
+                    }
+                } else if name.ends_with(".json") {
+                    on_disk.insert(name, entry.path());
+                }
+            }
+        }
+
+        let mut changed = false;
+        let before = loaded.len();
+        loaded.retain(|id, _| on_disk.contains_key(&file_name(id)));
+        changed |= loaded.len() != before;
+
+        let indexed_names: HashSet<String> = loaded.keys().map(|id| file_name(id)).collect();
+        for (name, path) in &on_disk {
+            let indexed = indexed_names.contains(name);
+            // Written after the index: the summary may be stale
+            let newer = std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|mtime| mtime >= index_mtime);
+            if indexed && !newer {
+                continue;
+            }
+            match read_conversation(path) {
+                Some(conversation) => {
+                    loaded.insert(conversation.id.clone(), ConversationSummary::from(&conversation));
+                    changed = true;
+                }
+                None => warn!("Skipping unreadable conversation file {}", path.display()),
+            }
+        }
+
+        *self.index.write() = loaded;
+        if changed {
+            self.save_index();
+        }
+    }
+}
+
+/// Directory holding the conversation files under `data_dir`.
+pub fn conversations_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(CONVERSATIONS_DIR)
+}
+
+/// File a conversation is kept in under `data_dir`.
+pub fn conversation_file(data_dir: &Path, id: &str) -> PathBuf {
+    conversations_dir(data_dir).join(file_name(id))
+}
+
+/// The conversation id a file in the conversations directory is named
+/// after, or `None` for other files.
+pub fn conversation_id(file_name: &str) -> Option<String> {
+    let encoded = file_name.strip_suffix(".json")?.as_bytes();
+    let mut id = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        if encoded[i] == b'%' {
+            let hex = std::str::from_utf8(encoded.get(i + 1..i + 3)?).ok()?;
+            id.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            id.push(encoded[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(id).ok()
+}
+
+/// File name for a conversation id: ASCII letters, digits, `-` and `_`
+/// are kept and every other byte is written as `%XX`.
+fn file_name(id: &str) -> String {
+    let mut name = String::with_capacity(id.len() + 5);
+    for byte in id.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            name.push(byte as char);
+        } else {
+            name.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    name.push_str(".json");
+    name
+}
+
+fn read_conversation(path: &Path) -> Option<CapturedConversation> {
+    let data = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+/// Serialize `value` to a temporary file next to `path`, sync it, and
+/// rename it over `path`.
+fn write_atomic<T: serde::Serialize + ?Sized>(path: &Path, value: &T) -> std::io::Result<()> {
+    let data = serde_json::to_vec(value)?;
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(TMP_SUFFIX);
+    let tmp = PathBuf::from(tmp_name);
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(&data)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CapturedMessage;
+    use tempfile::TempDir;
+
+    fn conversation(id: &str, messages: usize) -> CapturedConversation {
+        CapturedConversation {
+            id: id.to_string(),
+            site: "claude".to_string(),
+            title: Some(format!("Conversation {}", id)),
+            url: format!("https://claude.ai/chat/{}", id),
+            messages: (0..messages)
+                .map(|i| CapturedMessage {
+                    id: format!("{}-{}", id, i),
+                    conversation_id: id.to_string(),
+                    role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                    content: format!("message {}", i),
+                    timestamp: "2025-01-01T00:00:00Z".to_string(),
+                    site: "claude".to_string(),
+                    metadata: None,
+                })
+                .collect(),
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            updated_at: format!("2025-01-0{}T00:00:00Z", messages),
+            indexed: false,
+            message_count: messages,
+            document_id: None,
+        }
+    }
+
+    fn put(store: &ConversationStore, conv: CapturedConversation) {
+        store.update(&conv.id.clone(), |_| (conv, ()));
+    }
+
+    #[test]
+    fn test_migrates_legacy_file() {
+        let dir = TempDir::new().unwrap();
+        let legacy: HashMap<String, CapturedConversation> = [conversation("a", 2), conversation("b/c", 3)]
+            .into_iter()
+            .map(|c| (c.id.clone(), c))
+            .collect();
+        std::fs::write(dir.path().join(LEGACY_FILE), serde_json::to_string(&legacy).unwrap()).unwrap();
+
+        let store = ConversationStore::open(dir.path());
+        assert_eq!(store.len(), 2);
+        assert!(!dir.path().join(LEGACY_FILE).exists());
+        assert!(dir.path().join(LEGACY_MIGRATED_FILE).exists());
+        assert!(dir.path().join(CONVERSATIONS_DIR).join("b%2Fc.json").exists());
+        assert_eq!(conversation_id("b%2Fc.json").as_deref(), Some("b/c"));
+        assert_eq!(conversation_file(dir.path(), "b/c"), dir.path().join(CONVERSATIONS_DIR).join("b%2Fc.json"));
+
+        // Summaries page without reading messages; messages load on demand
+        let (page, total) = store.summaries(1, 1, Some("claude"));
+        assert_eq!((page.len(), total), (1, 2));
+        assert_eq!(page[0].id, "b/c");
+        assert_eq!(store.get("b/c").unwrap().messages.len(), 3);
+
+        // Reopening reads the sharded layout
+        drop(store);
+        let store = ConversationStore::open(dir.path());
+        assert_eq!(store.len(), 2);
+        assert!(store.remove("a"));
+        assert!(store.get("a").is_none());
+        assert_eq!(ConversationStore::open(dir.path()).len(), 1);
+    }
+
+    #[test]
+    fn test_unparsable_legacy_file_is_left_in_place() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join(LEGACY_FILE), "{\"a\": {\"id\": ").unwrap();
+        let store = ConversationStore::open(dir.path());
+        assert!(store.is_empty());
+        assert!(dir.path().join(LEGACY_FILE).exists());
+    }
+
+    #[test]
+    fn test_recovers_from_interrupted_writes() {
+        let dir = TempDir::new().unwrap();
+        {
+            let store = ConversationStore::open(dir.path());
+            put(&store, conversation("a", 1));
+            put(&store, conversation("b", 1));
+        }
+        let conversations = dir.path().join(CONVERSATIONS_DIR);
+
+        // Crash while rewriting "a": a partial temp file next to the intact original
+        std::fs::write(conversations.join("a.json.tmp"), "{\"id\": \"a\", \"mess").unwrap();
+        // Crash after writing "c" and a new version of "b" but before the index
+        let index_before = std::fs::read(dir.path().join(INDEX_FILE)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(conversations.join("c.json"), serde_json::to_string(&conversation("c", 1)).unwrap()).unwrap();
+        std::fs::write(conversations.join("b.json"), serde_json::to_string(&conversation("b", 4)).unwrap()).unwrap();
+        std::fs::write(dir.path().join(INDEX_FILE), index_before).unwrap();
+        // Make the index older than the conversation files it is missing
+        std::fs::File::options()
+            .write(true)
+            .open(dir.path().join(INDEX_FILE))
+            .unwrap()
+            .set_modified(SystemTime::now() - std::time::Duration::from_secs(60))
+            .unwrap();
+
+        let store = ConversationStore::open(dir.path());
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.get("a").unwrap().messages.len(), 1);
+        assert!(!conversations.join("a.json.tmp").exists());
+        let (page, _) = store.summaries(1, 10, None);
+        assert_eq!(page.iter().find(|s| s.id == "b").unwrap().message_count, 4);
+
+        // A corrupt index is rebuilt from the files
+        std::fs::write(dir.path().join(INDEX_FILE), "not json").unwrap();
+        assert_eq!(ConversationStore::open(dir.path()).len(), 3);
+    }
+
+    #[test]
+    fn test_read_only_store_reads_but_never_writes() {
+        let dir = TempDir::new().unwrap();
+        {
+            let store = ConversationStore::open(dir.path());
+            put(&store, conversation("a", 2));
+        }
+        let conversations = dir.path().join(CONVERSATIONS_DIR);
+        std::fs::write(conversations.join("a.json.tmp"), "{\"id\": \"a\", \"mess").unwrap();
+        let index_before = std::fs::read(dir.path().join(INDEX_FILE)).unwrap();
+
+        let store = ConversationStore::open_read_only(dir.path());
+        assert_eq!(store.get("a").unwrap().messages.len(), 2);
+        put(&store, conversation("b", 1));
+        assert!(!store.modify("a", |c| c.indexed = true));
+        assert!(!store.remove("a"));
+        store.flush();
+
+        assert_eq!(store.len(), 1);
+        assert!(!conversations.join("b.json").exists());
+        assert!(conversations.join("a.json.tmp").exists());
+        assert_eq!(std::fs::read(dir.path().join(INDEX_FILE)).unwrap(), index_before);
+    }
+}
@@ -1,101 +1,10 @@
 //! Browser connector types — matching the TypeScript API surface.
 
+use mindsage_core::{redact, Secret};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-/// Supported AI chat sites.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum SupportedSite {
-    #[serde(rename = "chatgpt")]
-    ChatGPT,
-    Claude,
-    Gemini,
-}
-
-impl SupportedSite {
-    pub fn all() -> &'static [SupportedSite] {
-        &[Self::ChatGPT, Self::Claude, Self::Gemini]
-    }
-
-    pub fn base_url(&self) -> &'static str {
-        match self {
-            Self::ChatGPT => "https://chatgpt.com",
-            Self::Claude => "https://claude.ai",
-            Self::Gemini => "https://gemini.google.com",
-        }
-    }
-
-    pub fn cookie_domains(&self) -> &'static [&'static str] {
-        match self {
-            Self::ChatGPT => &[".chatgpt.com", ".openai.com"],
-            Self::Claude => &[".claude.ai", ".anthropic.com"],
-            Self::Gemini => &[".google.com", ".gemini.google.com"],
-        }
-    }
-
-    pub fn name(&self) -> &'static str {
-        match self {
-            Self::ChatGPT => "chatgpt",
-            Self::Claude => "claude",
-            Self::Gemini => "gemini",
-        }
-    }
-
-    pub fn from_name(name: &str) -> Option<Self> {
-        match name.to_lowercase().as_str() {
-            "chatgpt" => Some(Self::ChatGPT),
-            "claude" => Some(Self::Claude),
-            "gemini" => Some(Self::Gemini),
-            _ => None,
-        }
-    }
-}
-
-impl std::fmt::Display for SupportedSite {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.name())
-    }
-}
-
-/// Browser runtime status.
-#[derive(Debug, Clone, Serialize)]
-pub struct BrowserStatus {
-    pub running: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub pid: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none", rename = "activeUrl")]
-    pub active_url: Option<String>,
-    #[serde(rename = "connectedSites")]
-    pub connected_sites: Vec<String>,
-    #[serde(skip_serializing_if = "Option::is_none", rename = "launchedAt")]
-    pub launched_at: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none", rename = "memoryUsageMB")]
-    pub memory_usage_mb: Option<f64>,
-    #[serde(rename = "captureStats")]
-    pub capture_stats: CaptureStats,
-    pub vnc: VncInfo,
-}
-
-/// Capture statistics for the current session.
-#[derive(Debug, Clone, Default, Serialize)]
-pub struct CaptureStats {
-    #[serde(rename = "totalCaptures")]
-    pub total_captures: u64,
-    #[serde(rename = "conversationsTracked")]
-    pub conversations_tracked: usize,
-}
-
-/// VNC connection info.
-#[derive(Debug, Clone, Serialize)]
-pub struct VncInfo {
-    pub enabled: bool,
-    #[serde(skip_serializing_if = "Option::is_none", rename = "wsPort")]
-    pub ws_port: Option<u16>,
-    #[serde(skip_serializing_if = "Option::is_none", rename = "vncPort")]
-    pub vnc_port: Option<u16>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub display: Option<String>,
-}
+pub use mindsage_api_types::{BrowserStatus, CaptureStats, ExtensionStatus, VncInfo};
 
 /// Authentication status for a site.
 #[derive(Debug, Clone, Serialize)]
@@ -123,6 +32,41 @@ pub struct CapturedConversation {
     pub indexed: bool,
     #[serde(rename = "messageCount")]
     pub message_count: usize,
+    /// Vector-store document holding the conversation, once indexed.
+    #[serde(rename = "documentId", default, skip_serializing_if = "Option::is_none")]
+    pub document_id: Option<i64>,
+}
+
+/// A captured conversation without its messages, as kept in the index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub id: String,
+    pub site: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub url: String,
+    #[serde(rename = "messageCount")]
+    pub message_count: usize,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+    pub indexed: bool,
+}
+
+impl From<&CapturedConversation> for ConversationSummary {
+    fn from(c: &CapturedConversation) -> Self {
+        Self {
+            id: c.id.clone(),
+            site: c.site.clone(),
+            title: c.title.clone(),
+            url: c.url.clone(),
+            message_count: c.message_count,
+            created_at: c.created_at.clone(),
+            updated_at: c.updated_at.clone(),
+            indexed: c.indexed,
+        }
+    }
 }
 
 /// A single message in a captured conversation.
@@ -139,6 +83,23 @@ pub struct CapturedMessage {
     pub metadata: Option<serde_json::Value>,
 }
 
+impl CapturedMessage {
+    /// Identity of the message independent of the ID the site assigned:
+    /// SHA-256 (hex) of the role and the whitespace-normalized content.
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.role.as_bytes());
+        hasher.update([0]);
+        for (i, word) in self.content.split_whitespace().enumerate() {
+            if i > 0 {
+                hasher.update(b" ");
+            }
+            hasher.update(word.as_bytes());
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
 /// Capture payload from the extension.
 #[derive(Debug, Clone, Deserialize)]
 pub struct CapturePayload {
@@ -151,13 +112,146 @@ pub struct CapturePayload {
     pub messages: Vec<CapturedMessage>,
     #[serde(rename = "fullConversation")]
     pub full_conversation: Option<bool>,
+    /// The id this conversation was captured under before the site
+    /// assigned `conversation_id` (ChatGPT's temporary ids before the first
+    /// response). That conversation is merged into this one.
+    #[serde(rename = "previousId", default)]
+    pub previous_id: Option<String>,
+}
+
+/// What processing a capture did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureOutcome {
+    pub new_messages: usize,
+    /// The `previous_id` conversation, merged into the captured one.
+    pub merged: Option<MergedConversation>,
+}
+
+/// A conversation merged into another and deleted.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MergedConversation {
+    #[serde(rename = "sourceId")]
+    pub source_id: String,
+    #[serde(rename = "targetId")]
+    pub target_id: String,
+    pub site: String,
+    /// Vector-store document the source was indexed as.
+    #[serde(rename = "sourceDocumentId", skip_serializing_if = "Option::is_none")]
+    pub source_document_id: Option<i64>,
+    /// Source messages the target did not already have.
+    #[serde(rename = "addedMessages")]
+    pub added_messages: usize,
+}
+
+/// Captured messages removed because they mention a forgotten term.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ForgottenMessages {
+    /// Conversations that lost messages or their title.
+    pub conversations: usize,
+    /// Conversations deleted because no message was left.
+    #[serde(rename = "conversationsDeleted")]
+    pub conversations_deleted: usize,
+    pub messages: usize,
+    /// Vector-store documents the touched conversations were indexed as.
+    #[serde(rename = "documentIds")]
+    pub document_ids: Vec<i64>,
+}
+
+/// Frames the server sends the companion extension over the relay socket
+/// (`/api/browser-connector/ws`), tagged by `type`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ServerMessage {
+    /// Open `url` and capture the site's conversations.
+    SyncStart { site: String, url: String },
+    /// Answer with a `conversation-list` for `site`.
+    RequestConversationList { site: String },
+    /// Keepalive; answered with `pong`.
+    Ping,
+    /// The extension's frame of type `of` was handled.
+    Ack {
+        of: String,
+        #[serde(rename = "newMessages", default, skip_serializing_if = "Option::is_none")]
+        new_messages: Option<usize>,
+    },
+    /// The extension's last frame was rejected.
+    Error { message: String },
+}
+
+/// Frames the companion extension sends over the relay socket, tagged by
+/// `type`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ExtensionMessage {
+    /// First frame after connecting.
+    Hello { version: String },
+    /// Same body as `POST /api/browser-connector/capture`.
+    Capture(CapturePayload),
+    /// Same as `POST /api/browser-connector/report-auth`; `false` clears the
+    /// site's authentication.
+    AuthStatus { site: String, authenticated: bool },
+    /// Conversations listed on a site, after `request-conversation-list`.
+    ConversationList {
+        site: String,
+        conversations: Vec<ListedConversation>,
+    },
+    /// Same body as `POST /api/browser-connector/sync-complete`.
+    SyncComplete(SyncResult),
+    Pong,
+}
+
+impl ExtensionMessage {
+    /// The frame's `type`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ExtensionMessage::Hello { .. } => "hello",
+            ExtensionMessage::Capture(_) => "capture",
+            ExtensionMessage::AuthStatus { .. } => "auth-status",
+            ExtensionMessage::ConversationList { .. } => "conversation-list",
+            ExtensionMessage::SyncComplete(_) => "sync-complete",
+            ExtensionMessage::Pong => "pong",
+        }
+    }
+}
+
+/// A conversation in a site's list, as the extension saw it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListedConversation {
+    pub id: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// The last conversation list the extension sent for a site.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationListing {
+    pub site: String,
+    pub conversations: Vec<ListedConversation>,
+    /// Listed conversations not captured yet.
+    pub uncaptured: usize,
+    #[serde(rename = "receivedAt")]
+    pub received_at: String,
+}
+
+/// What handling an extension frame did.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelayOutcome {
+    Handled,
+    /// A capture was merged; `auto_index` is the site's capture setting.
+    Captured {
+        conversation_id: String,
+        new_messages: usize,
+        auto_index: bool,
+        merged: Option<MergedConversation>,
+    },
 }
 
 /// Cookie from the companion extension.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImportedCookie {
     pub name: String,
-    pub value: String,
+    pub value: Secret,
     pub domain: String,
     pub path: String,
     pub secure: bool,
@@ -176,8 +270,9 @@ pub struct CookieImportPayload {
     pub cookies: Vec<ImportedCookie>,
 }
 
-/// Sync result from headless sync operation.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Sync result from headless sync operation. `error` can quote page
+/// content, so `Debug` redacts it.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SyncResult {
     pub success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -190,6 +285,18 @@ pub struct SyncResult {
     pub error: Option<String>,
 }
 
+impl std::fmt::Debug for SyncResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncResult")
+            .field("success", &self.success)
+            .field("synced", &self.synced)
+            .field("failed", &self.failed)
+            .field("total", &self.total)
+            .field("error", &self.error.as_ref().map(redact))
+            .finish()
+    }
+}
+
 /// Auto-sync schedule status.
 #[derive(Debug, Clone, Serialize)]
 pub struct AutoSyncStatus {
@@ -220,6 +327,10 @@ pub struct SiteAuthConfig {
 pub struct SiteInfo {
     pub name: String,
     pub url: String,
+    #[serde(rename = "cookieDomains")]
+    pub cookie_domains: Vec<String>,
+    pub capture: crate::sites::SiteCaptureSettings,
+    pub builtin: bool,
     pub authenticated: bool,
     #[serde(skip_serializing_if = "Option::is_none", rename = "authenticatedAt")]
     pub authenticated_at: Option<String>,
@@ -5,6 +5,8 @@ use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
+pub use mindsage_core::Secret;
+
 use crate::types::{LLMConfigResponse, LLMConfigUpdate, LLMProvider};
 
 pub const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
@@ -17,6 +19,9 @@ pub const ANTHROPIC_MODELS: &[&str] = &[
     "claude-3-5-sonnet-20241022",
     "claude-3-5-haiku-20241022",
 ];
+/// Default number of documents per day that may use LLM topic generation.
+pub const DEFAULT_TOPIC_LLM_DAILY_CAP: u32 = 200;
+
 pub const GROQ_MODELS: &[&str] = &[
     "llama-3.3-70b-versatile",
     "llama-3.1-8b-instant",
@@ -24,24 +29,37 @@ pub const GROQ_MODELS: &[&str] = &[
     "gemma2-9b-it",
 ];
 
-/// Stored LLM configuration (persisted to llm-config.json).
+/// Stored LLM configuration (persisted to llm-config.json). API keys are
+/// written to the file in full but masked in `Debug` output.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LLMConfig {
     #[serde(default = "default_preferred")]
     pub preferred_provider: String,
-    #[serde(default)]
-    pub openai_api_key: Option<String>,
-    #[serde(default)]
-    pub anthropic_api_key: Option<String>,
-    #[serde(default)]
-    pub groq_api_key: Option<String>,
+    #[serde(default, serialize_with = "Secret::serialize_exposed")]
+    pub openai_api_key: Option<Secret>,
+    #[serde(default, serialize_with = "Secret::serialize_exposed")]
+    pub anthropic_api_key: Option<Secret>,
+    #[serde(default, serialize_with = "Secret::serialize_exposed")]
+    pub groq_api_key: Option<Secret>,
     #[serde(default = "default_openai_model")]
     pub openai_model: String,
     #[serde(default = "default_anthropic_model")]
     pub anthropic_model: String,
     #[serde(default = "default_groq_model")]
     pub groq_model: String,
+    /// Documents per day that may use LLM topic generation (0 disables it).
+    #[serde(default = "default_topic_llm_daily_cap")]
+    pub topic_llm_daily_cap: u32,
+    /// OpenAI-compatible API that OpenAI requests go to instead of
+    /// `api.openai.com/v1`, e.g. a local llama.cpp or vLLM server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub openai_base_url: Option<String>,
+    /// Let the model search the knowledge base with a tool while it
+    /// answers, instead of receiving the context up front. Requests may
+    /// override it with `useTools`.
+    #[serde(default)]
+    pub tool_retrieval: bool,
     /// Path to config file for saving.
     #[serde(skip)]
     pub config_path: PathBuf,
@@ -59,6 +77,9 @@ fn default_anthropic_model() -> String {
 fn default_groq_model() -> String {
     DEFAULT_GROQ_MODEL.into()
 }
+fn default_topic_llm_daily_cap() -> u32 {
+    DEFAULT_TOPIC_LLM_DAILY_CAP
+}
 
 impl Default for LLMConfig {
     fn default() -> Self {
@@ -70,6 +91,9 @@ impl Default for LLMConfig {
             openai_model: DEFAULT_OPENAI_MODEL.into(),
             anthropic_model: DEFAULT_ANTHROPIC_MODEL.into(),
             groq_model: DEFAULT_GROQ_MODEL.into(),
+            topic_llm_daily_cap: DEFAULT_TOPIC_LLM_DAILY_CAP,
+            openai_base_url: None,
+            tool_retrieval: false,
             config_path: PathBuf::new(),
         }
     }
@@ -87,13 +111,16 @@ impl LLMConfig {
 
         // Env vars as fallback for API keys
         if config.openai_api_key.is_none() {
-            config.openai_api_key = std::env::var("OPENAI_API_KEY").ok();
+            config.openai_api_key = std::env::var("OPENAI_API_KEY").ok().map(Secret::from);
         }
         if config.anthropic_api_key.is_none() {
-            config.anthropic_api_key = std::env::var("ANTHROPIC_API_KEY").ok();
+            config.anthropic_api_key = std::env::var("ANTHROPIC_API_KEY").ok().map(Secret::from);
         }
         if config.groq_api_key.is_none() {
-            config.groq_api_key = std::env::var("GROQ_API_KEY").ok();
+            config.groq_api_key = std::env::var("GROQ_API_KEY").ok().map(Secret::from);
+        }
+        if config.openai_base_url.is_none() {
+            config.openai_base_url = std::env::var("OPENAI_BASE_URL").ok().filter(|u| !u.trim().is_empty());
         }
 
         config
@@ -105,7 +132,7 @@ impl LLMConfig {
             std::fs::create_dir_all(parent)?;
         }
         let json = serde_json::to_string_pretty(self).map_err(|e| {
-            std::io::Error::new(std::io::ErrorKind::Other, e)
+            std::io::Error::other(e)
         })?;
         std::fs::write(&self.config_path, json)?;
         info!("Saved LLM config to {}", self.config_path.display());
@@ -118,13 +145,13 @@ impl LLMConfig {
             self.preferred_provider = p.clone();
         }
         if let Some(k) = &update.openai_api_key {
-            self.openai_api_key = Some(k.clone());
+            self.openai_api_key = Some(Secret::new(k.expose().to_string()));
         }
         if let Some(k) = &update.anthropic_api_key {
-            self.anthropic_api_key = Some(k.clone());
+            self.anthropic_api_key = Some(Secret::new(k.expose().to_string()));
         }
         if let Some(k) = &update.groq_api_key {
-            self.groq_api_key = Some(k.clone());
+            self.groq_api_key = Some(Secret::new(k.expose().to_string()));
         }
         if let Some(m) = &update.openai_model {
             self.openai_model = m.clone();
@@ -135,6 +162,12 @@ impl LLMConfig {
         if let Some(m) = &update.groq_model {
             self.groq_model = m.clone();
         }
+        if let Some(cap) = update.topic_llm_daily_cap {
+            self.topic_llm_daily_cap = cap;
+        }
+        if let Some(enabled) = update.tool_retrieval {
+            self.tool_retrieval = enabled;
+        }
     }
 
     /// Resolve which provider and model to use.
@@ -145,44 +178,50 @@ impl LLMConfig {
                 "openai" => self
                     .openai_api_key
                     .as_ref()
-                    .map(|k| (LLMProvider::OpenAI, self.openai_model.clone(), k.clone())),
+                    .map(|k| (LLMProvider::OpenAI, self.openai_model.clone(), k.expose().to_string())),
                 "anthropic" => self
                     .anthropic_api_key
                     .as_ref()
-                    .map(|k| (LLMProvider::Anthropic, self.anthropic_model.clone(), k.clone())),
+                    .map(|k| (LLMProvider::Anthropic, self.anthropic_model.clone(), k.expose().to_string())),
                 "groq" => self
                     .groq_api_key
                     .as_ref()
-                    .map(|k| (LLMProvider::Groq, self.groq_model.clone(), k.clone())),
+                    .map(|k| (LLMProvider::Groq, self.groq_model.clone(), k.expose().to_string())),
                 _ => None,
             };
         }
 
         // Auto mode: Anthropic > Groq > OpenAI
         if let Some(k) = &self.anthropic_api_key {
-            return Some((LLMProvider::Anthropic, self.anthropic_model.clone(), k.clone()));
+            return Some((LLMProvider::Anthropic, self.anthropic_model.clone(), k.expose().to_string()));
         }
         if let Some(k) = &self.groq_api_key {
-            return Some((LLMProvider::Groq, self.groq_model.clone(), k.clone()));
+            return Some((LLMProvider::Groq, self.groq_model.clone(), k.expose().to_string()));
         }
         if let Some(k) = &self.openai_api_key {
-            return Some((LLMProvider::OpenAI, self.openai_model.clone(), k.clone()));
+            return Some((LLMProvider::OpenAI, self.openai_model.clone(), k.expose().to_string()));
         }
 
         None
     }
 
-    /// Build the public config response (no API keys exposed).
+    /// Build the public config response: whether each key is configured and
+    /// its masked tail, never the key itself.
     pub fn to_response(&self) -> LLMConfigResponse {
         let resolved = self.resolve_provider();
         LLMConfigResponse {
             preferred_provider: self.preferred_provider.clone(),
             openai_configured: self.openai_api_key.is_some(),
+            openai_key_hint: self.openai_api_key.as_ref().map(Secret::masked),
             anthropic_configured: self.anthropic_api_key.is_some(),
+            anthropic_key_hint: self.anthropic_api_key.as_ref().map(Secret::masked),
             groq_configured: self.groq_api_key.is_some(),
+            groq_key_hint: self.groq_api_key.as_ref().map(Secret::masked),
             openai_model: self.openai_model.clone(),
             anthropic_model: self.anthropic_model.clone(),
             groq_model: self.groq_model.clone(),
+            topic_llm_daily_cap: self.topic_llm_daily_cap,
+            tool_retrieval: self.tool_retrieval,
             active_provider: resolved.map(|(p, _, _)| p.to_string()),
         }
     }
@@ -199,3 +238,40 @@ impl LLMConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "sk-ant-REDACTED";
+
+    #[test]
+    fn test_response_and_debug_hide_keys() {
+        let mut config = LLMConfig::default();
+        config.apply_update(&LLMConfigUpdate {
+            anthropic_api_key: Some(KEY.into()),
+            ..Default::default()
+        });
+
+        let response = serde_json::to_string(&config.to_response()).unwrap();
+        assert!(!response.contains("secretsecret"));
+        assert!(response.contains("\"anthropicConfigured\":true"));
+        assert!(response.contains("\"anthropicKeyHint\":\"****WXYZ\""));
+        assert!(!format!("{:?}", config).contains("secretsecret"));
+        assert!(!format!("Using {:?}", config.anthropic_api_key).contains("secretsecret"));
+
+        assert_eq!(config.resolve_provider().unwrap().2, KEY);
+    }
+
+    #[test]
+    fn test_saved_file_keeps_key() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("llm-config.json");
+        let mut config = LLMConfig::load(&path);
+        config.groq_api_key = Some(Secret::new(KEY));
+        config.save().unwrap();
+
+        let reloaded = LLMConfig::load(&path);
+        assert_eq!(reloaded.groq_api_key.unwrap().expose(), KEY);
+    }
+}
@@ -0,0 +1,570 @@
+//! RAG context assembly — expand search hits into context blocks.
+//!
+//! Each hit is expanded according to the request's [`ContextMode`], then
+//! blocks from the same document whose chunks or character ranges overlap
+//! are merged so the same section is never sent to the LLM twice, and text
+//! a merged chunk repeats from its neighbour (overlap) is sent once. Blocks
+//! are admitted in score order until the context token budget is spent.
+//! With a [`Compression`], each block first keeps only the sentences
+//! relevant to the query, more strictly the more the blocks overrun the
+//! budget.
+
+use std::collections::HashSet;
+
+pub use mindsage_api_types::ContextMode;
+use mindsage_core::text::shared_overlap;
+
+use crate::types::ChatContext;
+
+/// Estimated tokens available for RAG context in the system prompt.
+pub const CONTEXT_TOKEN_BUDGET: usize = 3000;
+
+/// Rough characters-per-token ratio used for budgeting.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Radius (in chunks) of the window pulled around a hit in window mode.
+pub const WINDOW_RADIUS: i32 = 2;
+
+/// Blocks left with fewer characters than this after budgeting are dropped.
+const MIN_BLOCK_CHARS: usize = 200;
+
+/// Relevance a sentence needs to be kept while the context fits the budget.
+const MIN_RELEVANCE: f64 = 0.2;
+
+/// Highest relevance bar, when the blocks far overrun the budget.
+const MAX_RELEVANCE: f64 = 0.6;
+
+/// A compressed block shorter than this is sent uncompressed instead.
+const MIN_COMPRESSED_CHARS: usize = 60;
+
+/// Sentence filter for `assemble_context`.
+pub struct Compression<'a> {
+    /// Relevance of each sentence to the query, 0 to 1.
+    pub relevance: &'a dyn Fn(&[&str]) -> Vec<f64>,
+}
+
+/// Character budget for the context pulled in for a single hit.
+pub fn per_hit_chars(mode: ContextMode) -> usize {
+    match mode {
+        ContextMode::Excerpt => 500,
+        ContextMode::Section => 2000,
+        ContextMode::Window => 1500,
+    }
+}
+
+/// One chunk of text contributing to a context block.
+#[derive(Debug, Clone)]
+pub struct ContextPiece {
+    pub chunk_id: i64,
+    pub chunk_index: i32,
+    pub char_start: Option<i32>,
+    pub char_end: Option<i32>,
+    pub text: String,
+}
+
+/// The expanded context for one search hit, before merging.
+#[derive(Debug, Clone)]
+pub struct ContextSpan {
+    pub doc_id: i64,
+    /// The chunk that matched the query.
+    pub hit_chunk_id: i64,
+    pub score: f64,
+    pub source: Option<String>,
+    pub filename: Option<String>,
+    pub pieces: Vec<ContextPiece>,
+}
+
+impl ContextSpan {
+    fn char_range(&self) -> Option<(i32, i32)> {
+        let start = self.pieces.iter().filter_map(|p| p.char_start).min()?;
+        let end = self.pieces.iter().filter_map(|p| p.char_end).max()?;
+        Some((start, end))
+    }
+
+    fn overlaps(&self, other: &ContextSpan) -> bool {
+        if self.doc_id != other.doc_id {
+            return false;
+        }
+        let shares_chunk = self
+            .pieces
+            .iter()
+            .any(|p| other.pieces.iter().any(|o| o.chunk_id == p.chunk_id));
+        if shares_chunk {
+            return true;
+        }
+        match (self.char_range(), other.char_range()) {
+            (Some((a_start, a_end)), Some((b_start, b_end))) => a_start < b_end && b_start < a_end,
+            _ => false,
+        }
+    }
+
+    /// The pieces in document order. Text a piece repeats from the one
+    /// before it (chunks cut with overlap) is left out, and so is a piece
+    /// lying wholly inside the one before.
+    fn text(&self) -> String {
+        let mut pieces: Vec<&ContextPiece> = self.pieces.iter().collect();
+        pieces.sort_by_key(|p| (p.char_start.unwrap_or(i32::MAX), p.chunk_index, p.chunk_id));
+        let mut parts: Vec<&str> = Vec::new();
+        let mut previous: Option<&ContextPiece> = None;
+        for piece in pieces {
+            let mut text = piece.text.as_str();
+            if let Some(prev) = previous {
+                if let (Some(prev_end), Some(start), Some(end)) = (prev.char_end, piece.char_start, piece.char_end) {
+                    if start < prev_end {
+                        if end <= prev_end && prev.text.contains(text.trim()) {
+                            continue;
+                        }
+                        text = text[shared_overlap(&prev.text, text)..].trim_start();
+                    }
+                }
+            }
+            if !text.is_empty() {
+                parts.push(text);
+            }
+            previous = Some(piece);
+        }
+        parts.join("\n\n")
+    }
+}
+
+/// A merged block: one or more hits sharing the same region of a document.
+struct ContextBlock {
+    span: ContextSpan,
+    hit_chunk_ids: Vec<i64>,
+}
+
+impl ContextBlock {
+    fn absorb(&mut self, other: ContextBlock) {
+        let seen: HashSet<i64> = self.span.pieces.iter().map(|p| p.chunk_id).collect();
+        self.span
+            .pieces
+            .extend(other.span.pieces.into_iter().filter(|p| !seen.contains(&p.chunk_id)));
+        self.span.score = self.span.score.max(other.span.score);
+        for id in other.hit_chunk_ids {
+            if !self.hit_chunk_ids.contains(&id) {
+                self.hit_chunk_ids.push(id);
+            }
+        }
+    }
+}
+
+/// Trim a window of chunks around `center_chunk_id` to at most `budget` characters,
+/// growing outward from the hit one neighbour at a time.
+pub fn trim_window(mut pieces: Vec<ContextPiece>, center_chunk_id: i64, budget: usize) -> Vec<ContextPiece> {
+    pieces.sort_by_key(|p| p.chunk_index);
+    let center = match pieces.iter().position(|p| p.chunk_id == center_chunk_id) {
+        Some(i) => i,
+        None => return pieces,
+    };
+
+    let mut used = pieces[center].text.chars().count();
+    let (mut lo, mut hi) = (center, center);
+    loop {
+        let mut grew = false;
+        if lo > 0 {
+            let len = pieces[lo - 1].text.chars().count();
+            if used + len <= budget {
+                lo -= 1;
+                used += len;
+                grew = true;
+            }
+        }
+        if hi + 1 < pieces.len() {
+            let len = pieces[hi + 1].text.chars().count();
+            if used + len <= budget {
+                hi += 1;
+                used += len;
+                grew = true;
+            }
+        }
+        if !grew {
+            break;
+        }
+    }
+
+    pieces.drain(lo..=hi).collect()
+}
+
+/// Merge overlapping spans, compress them, and fit them into the token
+/// budget.
+///
+/// `spans` should be in rank order. Excerpt spans are never merged with
+/// each other, only deduplicated by hit chunk.
+pub fn assemble_context(
+    spans: Vec<ContextSpan>,
+    mode: ContextMode,
+    token_budget: usize,
+    compression: Option<&Compression>,
+) -> Vec<ChatContext> {
+    let mut blocks: Vec<ContextBlock> = Vec::new();
+
+    for span in spans {
+        let block = ContextBlock {
+            hit_chunk_ids: vec![span.hit_chunk_id],
+            span,
+        };
+
+        if mode == ContextMode::Excerpt {
+            if !blocks.iter().any(|b| b.span.hit_chunk_id == block.span.hit_chunk_id) {
+                blocks.push(block);
+            }
+            continue;
+        }
+
+        // Absorb the new block into the first overlapping one, then keep
+        // merging until no two blocks overlap (a merge can bridge two blocks).
+        match blocks.iter().position(|b| b.span.overlaps(&block.span)) {
+            Some(mut i) => {
+                blocks[i].absorb(block);
+                while let Some(j) =
+                    (0..blocks.len()).find(|&j| j != i && blocks[i].span.overlaps(&blocks[j].span))
+                {
+                    let other = blocks.remove(j);
+                    if j < i {
+                        i -= 1;
+                    }
+                    blocks[i].absorb(other);
+                }
+            }
+            None => blocks.push(block),
+        }
+    }
+
+    blocks.sort_by(|a, b| {
+        b.span
+            .score
+            .partial_cmp(&a.span.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut remaining_chars = token_budget.saturating_mul(CHARS_PER_TOKEN);
+    let mut contexts = Vec::new();
+    let texts: Vec<String> = blocks.iter().map(|b| b.span.text()).collect();
+    let mut pending_chars: usize = texts.iter().map(|t| t.chars().count()).sum();
+
+    for (block, text) in blocks.into_iter().zip(texts) {
+        if remaining_chars < MIN_BLOCK_CHARS {
+            break;
+        }
+        let original_chars = text.chars().count();
+        let text = match compression {
+            Some(compression) => {
+                let pressure = pending_chars as f64 / remaining_chars as f64;
+                let threshold = (MIN_RELEVANCE * pressure).clamp(MIN_RELEVANCE, MAX_RELEVANCE);
+                compress_text(&text, compression, threshold)
+            }
+            None => text,
+        };
+        pending_chars -= original_chars;
+        let block_budget = (per_hit_chars(mode) * block.hit_chunk_ids.len()).min(remaining_chars);
+        let excerpt = truncate_chars(&text, block_budget);
+        let sent_chars = excerpt.chars().count();
+        remaining_chars = remaining_chars.saturating_sub(sent_chars);
+
+        contexts.push(ChatContext {
+            id: block.span.hit_chunk_id,
+            excerpt,
+            score: block.span.score,
+            source: block.span.source,
+            filename: block.span.filename,
+            mode,
+            chunk_ids: block.hit_chunk_ids,
+            original_chars,
+            sent_chars,
+        });
+    }
+
+    contexts
+}
+
+/// Split at sentence ends (`.`, `!` or `?` before whitespace) and line
+/// breaks.
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let end = match c {
+            '\n' => Some(i),
+            '.' | '!' | '?' if chars.peek().is_some_and(|(_, next)| next.is_whitespace()) => Some(i + 1),
+            _ => None,
+        };
+        if let Some(end) = end {
+            let sentence = text[start..end].trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence);
+            }
+            start = end;
+        }
+    }
+    let rest = text[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest);
+    }
+    sentences
+}
+
+/// Keep the sentences of `text` scoring at least `threshold`, and always
+/// the best one. Runs of kept sentences are joined; dropped ones leave
+/// "...". `text` is returned unchanged when no sentence is relevant or
+/// when the result would be shorter than `MIN_COMPRESSED_CHARS`.
+fn compress_text(text: &str, compression: &Compression, threshold: f64) -> String {
+    let sentences = split_sentences(text);
+    if sentences.len() < 2 {
+        return text.to_string();
+    }
+    let scores = (compression.relevance)(&sentences);
+    let best = scores.iter().copied().fold(0.0, f64::max);
+    if best <= 0.0 {
+        return text.to_string();
+    }
+
+    let mut compressed = String::new();
+    let mut dropped = false;
+    for (sentence, &score) in sentences.iter().zip(&scores) {
+        if score < threshold && score < best {
+            dropped = true;
+            continue;
+        }
+        if dropped {
+            compressed.push_str(if compressed.is_empty() { "... " } else { " ... " });
+        } else if !compressed.is_empty() {
+            compressed.push(' ');
+        }
+        compressed.push_str(sentence);
+        dropped = false;
+    }
+    if dropped {
+        compressed.push_str(" ...");
+    }
+
+    if compressed.chars().count() < MIN_COMPRESSED_CHARS {
+        text.to_string()
+    } else {
+        compressed
+    }
+}
+
+/// Truncate to `max_chars` characters, appending "..." when cut.
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((byte_idx, _)) => format!("{}...", &text[..byte_idx]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn piece(chunk_id: i64, chunk_index: i32, start: i32, text: &str) -> ContextPiece {
+        ContextPiece {
+            chunk_id,
+            chunk_index,
+            char_start: Some(start),
+            char_end: Some(start + text.len() as i32),
+            text: text.to_string(),
+        }
+    }
+
+    fn span(doc_id: i64, hit: i64, score: f64, pieces: Vec<ContextPiece>) -> ContextSpan {
+        ContextSpan {
+            doc_id,
+            hit_chunk_id: hit,
+            score,
+            source: None,
+            filename: None,
+            pieces,
+        }
+    }
+
+    #[test]
+    fn test_overlapping_hits_from_one_section_collapse() {
+        // Two paragraph hits (ids 11, 12) share section chunk 10
+        let section = piece(10, 0, 0, "Section: the quarterly numbers table");
+        let spans = vec![
+            span(1, 11, 0.9, vec![section.clone()]),
+            span(1, 12, 0.7, vec![section]),
+            span(2, 21, 0.5, vec![piece(20, 0, 0, "Another document")]),
+        ];
+
+        let contexts = assemble_context(spans, ContextMode::Section, CONTEXT_TOKEN_BUDGET, None);
+        assert_eq!(contexts.len(), 2);
+        assert_eq!(contexts[0].id, 11);
+        assert_eq!(contexts[0].chunk_ids, vec![11, 12]);
+        assert_eq!(contexts[0].mode, ContextMode::Section);
+        assert_eq!(contexts[0].excerpt.matches("quarterly").count(), 1);
+        assert_eq!(contexts[1].chunk_ids, vec![21]);
+    }
+
+    #[test]
+    fn test_overlapping_windows_merge_without_duplicates() {
+        let a = piece(1, 0, 0, "alpha ");
+        let b = piece(2, 1, 6, "beta ");
+        let c = piece(3, 2, 11, "gamma ");
+        let d = piece(4, 3, 17, "delta");
+
+        let spans = vec![
+            span(1, 2, 0.8, vec![a, b.clone(), c.clone()]),
+            span(1, 3, 0.6, vec![b, c, d]),
+        ];
+
+        let contexts = assemble_context(spans, ContextMode::Window, CONTEXT_TOKEN_BUDGET, None);
+        assert_eq!(contexts.len(), 1);
+        assert_eq!(contexts[0].excerpt, "alpha \n\nbeta \n\ngamma \n\ndelta");
+        assert_eq!(contexts[0].score, 0.8);
+    }
+
+    #[test]
+    fn test_overlapping_chunks_send_each_sentence_once() {
+        // Chunks cut with overlap: each repeats the last sentence of the one before
+        let doc = "The harbour froze in January. Ferries stopped for a week. \
+                   The town ran out of flour. Bakers walked the ice to the mainland.";
+        let chunk = |id: i64, start: usize, end: usize| piece(id, id as i32, start as i32, &doc[start..end]);
+        let (a, b, c) = (chunk(1, 0, 57), chunk(2, 30, 84), chunk(3, 58, doc.len()));
+        // A sentence inside chunk 2, as stored by an older chunker
+        let inner = chunk(4, 30, 57);
+
+        let spans = vec![
+            span(1, 2, 0.9, vec![a.clone(), b.clone()]),
+            span(1, 3, 0.8, vec![b, c, inner]),
+        ];
+        let contexts = assemble_context(spans, ContextMode::Window, CONTEXT_TOKEN_BUDGET, None);
+        assert_eq!(contexts.len(), 1);
+        let excerpt = &contexts[0].excerpt;
+        for sentence in ["The harbour froze", "Ferries stopped", "The town ran out", "Bakers walked"] {
+            assert_eq!(excerpt.matches(sentence).count(), 1, "{}", excerpt);
+        }
+        assert_eq!(
+            *excerpt,
+            "The harbour froze in January. Ferries stopped for a week.\n\nThe town ran out of flour.\n\nBakers walked the ice to the mainland."
+        );
+    }
+
+    #[test]
+    fn test_bridging_span_merges_existing_blocks() {
+        let spans = vec![
+            span(1, 1, 0.9, vec![piece(1, 0, 0, "first part")]),
+            span(1, 3, 0.8, vec![piece(3, 2, 100, "third part")]),
+            // Covers both earlier ranges
+            span(1, 2, 0.7, vec![piece(1, 0, 0, "first part"), piece(3, 2, 100, "third part")]),
+        ];
+
+        let contexts = assemble_context(spans, ContextMode::Window, CONTEXT_TOKEN_BUDGET, None);
+        assert_eq!(contexts.len(), 1);
+        assert_eq!(contexts[0].chunk_ids.len(), 3);
+    }
+
+    #[test]
+    fn test_excerpt_mode_does_not_merge() {
+        let shared = piece(10, 0, 0, "shared");
+        let spans = vec![
+            span(1, 11, 0.9, vec![piece(11, 1, 0, "one")]),
+            span(1, 12, 0.8, vec![piece(12, 2, 0, "two")]),
+            span(1, 11, 0.8, vec![shared]),
+        ];
+
+        let contexts = assemble_context(spans, ContextMode::Excerpt, CONTEXT_TOKEN_BUDGET, None);
+        assert_eq!(contexts.len(), 2);
+        assert!(contexts.iter().all(|c| c.mode == ContextMode::Excerpt));
+    }
+
+    #[test]
+    fn test_token_budget_limits_total_context() {
+        let long = "x".repeat(1800);
+        let spans: Vec<ContextSpan> = (0..5)
+            .map(|i| span(i, i, 1.0 - i as f64 * 0.1, vec![piece(i, 0, 0, &long)]))
+            .collect();
+
+        // 1000 tokens ≈ 4000 chars: two full sections, then a partial third
+        let contexts = assemble_context(spans, ContextMode::Section, 1000, None);
+        let total_chars: usize = contexts.iter().map(|c| c.excerpt.trim_end_matches("...").len()).sum();
+        assert_eq!(contexts.len(), 3);
+        assert!(total_chars <= 4000);
+        assert!(contexts[2].excerpt.ends_with("..."));
+    }
+
+    /// Share of `terms` each sentence contains.
+    fn overlap(terms: &'static [&'static str]) -> impl Fn(&[&str]) -> Vec<f64> {
+        move |sentences| {
+            sentences
+                .iter()
+                .map(|s| {
+                    let lower = s.to_lowercase();
+                    terms.iter().filter(|t| lower.contains(*t)).count() as f64 / terms.len() as f64
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_compression_keeps_the_answer_sentence() {
+        let text = "Our cat prefers the window seat in the afternoon. \
+                    The boiler service is booked for the second Tuesday of March every year. \
+                    The garden needs mulch before the spring rains arrive.\n\
+                    Remember to water the ferns twice a week.";
+        let relevance = overlap(&["boiler", "service", "booked"]);
+        let compression = Compression { relevance: &relevance };
+        let spans = vec![span(1, 1, 0.9, vec![piece(1, 0, 0, text)])];
+
+        let contexts = assemble_context(spans, ContextMode::Section, CONTEXT_TOKEN_BUDGET, Some(&compression));
+        assert_eq!(
+            contexts[0].excerpt,
+            "... The boiler service is booked for the second Tuesday of March every year. ..."
+        );
+        assert_eq!(contexts[0].original_chars, text.chars().count());
+        assert_eq!(contexts[0].sent_chars, contexts[0].excerpt.chars().count());
+        assert!(contexts[0].sent_chars < contexts[0].original_chars);
+
+        // Nothing relevant, or too little left: the block is sent whole
+        let none = overlap(&["zebra"]);
+        let short = "Boiler: booked. The garden needs mulch before the spring rains arrive.";
+        for (text, relevance) in [(text, &none as &dyn Fn(&[&str]) -> Vec<f64>), (short, &relevance)] {
+            let compression = Compression { relevance };
+            let spans = vec![span(1, 1, 0.9, vec![piece(1, 0, 0, text)])];
+            let contexts = assemble_context(spans, ContextMode::Section, CONTEXT_TOKEN_BUDGET, Some(&compression));
+            assert_eq!(contexts[0].excerpt, text);
+        }
+    }
+
+    #[test]
+    fn test_compression_tightens_with_the_budget() {
+        // One strong and one weak (1 of 3 terms) sentence per block
+        let text = "The boiler service is booked for the second Tuesday of March every year. \
+                    A service van parks outside on those mornings, according to the neighbours.";
+        let relevance = overlap(&["boiler", "service", "booked"]);
+        let compression = Compression { relevance: &relevance };
+        let spans = |n: i64| -> Vec<ContextSpan> {
+            (0..n).map(|i| span(i, i, 1.0 - i as f64 * 0.01, vec![piece(i, 0, 0, text)])).collect()
+        };
+
+        let roomy = assemble_context(spans(2), ContextMode::Section, CONTEXT_TOKEN_BUDGET, Some(&compression));
+        assert!(roomy[0].excerpt.contains("service van"));
+
+        // Ten blocks overrun a 100-token budget: the weak sentence goes
+        let tight = assemble_context(spans(10), ContextMode::Section, 100, Some(&compression));
+        assert!(!tight[0].excerpt.contains("service van"), "{}", tight[0].excerpt);
+        assert!(tight[0].excerpt.starts_with("The boiler service is booked"));
+    }
+
+    #[test]
+    fn test_trim_window_grows_outward_within_budget() {
+        let pieces = vec![
+            piece(1, 0, 0, "aaaaaaaaaa"),
+            piece(2, 1, 10, "bbbbbbbbbb"),
+            piece(3, 2, 20, "cccccccccc"),
+            piece(4, 3, 30, "dddddddddd"),
+            piece(5, 4, 40, "eeeeeeeeee"),
+        ];
+
+        let trimmed = trim_window(pieces, 3, 30);
+        let ids: Vec<i64> = trimmed.iter().map(|p| p.chunk_id).collect();
+        assert_eq!(ids, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_truncate_chars_is_utf8_safe() {
+        assert_eq!(truncate_chars("héllo wörld", 4), "héll...");
+        assert_eq!(truncate_chars("short", 10), "short");
+    }
+}
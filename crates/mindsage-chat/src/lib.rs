@@ -4,8 +4,12 @@
 //! LLM calls go to external APIs — no local model required.
 
 pub mod config;
+pub mod context;
 pub mod providers;
+pub mod tools;
+pub mod topics;
 pub mod types;
 
 pub use config::LLMConfig;
+pub use context::ContextMode;
 pub use types::*;
@@ -2,6 +2,16 @@
 //!
 //! Each provider streams tokens via SSE from their respective APIs.
 //! OpenAI and Groq use the same format. Anthropic uses a different one.
+//!
+//! Request bodies keep the system prompt (instructions and RAG context)
+//! first, serialized the same way every turn, so providers can reuse their
+//! cached prefix: OpenAI does so automatically, Anthropic when the system
+//! block carries a `cache_control` breakpoint. Cache hits come back in the
+//! usage of the `Done` chunk.
+//!
+//! With a `ToolUse`, OpenAI and Anthropic requests offer the search tool
+//! and replay earlier rounds (see `tools`); the calls a response makes
+//! arrive as one `ToolCalls` chunk just before `Done`.
 
 use std::pin::Pin;
 
@@ -11,7 +21,17 @@ use serde_json::json;
 use tokio_stream::StreamExt;
 use tracing::{debug, error};
 
-use crate::types::{ChatMessage, LLMProvider};
+use crate::tools::{AnthropicCalls, OpenAiCalls, ToolCall, ToolUse};
+use crate::types::{ChatMessage, LLMProvider, ProviderUsage};
+
+/// Smallest prompt Anthropic caches, in tokens; Haiku models need more.
+const ANTHROPIC_MIN_CACHE_TOKENS: usize = 1024;
+const ANTHROPIC_HAIKU_MIN_CACHE_TOKENS: usize = 2048;
+/// Rough characters-per-token ratio for the cacheable-size check.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// OpenAI's API, unless `LLMConfig::openai_base_url` names another.
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
 
 /// Boxed stream type for returning different stream implementations.
 pub type BoxedStream = Pin<Box<dyn Stream<Item = StreamChunk> + Send>>;
@@ -19,11 +39,17 @@ pub type BoxedStream = Pin<Box<dyn Stream<Item = StreamChunk> + Send>>;
 /// A single streamed token or error.
 pub enum StreamChunk {
     Token(String),
-    Done { tokens_used: usize },
+    /// Tools the model called; the answer continues once they have run.
+    ToolCalls(Vec<ToolCall>),
+    /// End of the answer, with the provider's usage when it sent one.
+    Done { tokens_used: usize, usage: Option<ProviderUsage> },
     Error(String),
 }
 
-/// Stream tokens from the appropriate provider.
+/// Stream tokens from the appropriate provider. `openai_base_url`
+/// replaces `OPENAI_BASE_URL` for OpenAI requests. `tools` is ignored by
+/// providers without tool support.
+#[allow(clippy::too_many_arguments)]
 pub fn stream_llm(
     client: &Client,
     provider: LLMProvider,
@@ -32,62 +58,144 @@ pub fn stream_llm(
     api_key: &str,
     temperature: f64,
     max_tokens: usize,
+    openai_base_url: Option<&str>,
+    tools: Option<&ToolUse>,
 ) -> BoxedStream {
     match provider {
-        LLMProvider::OpenAI => Box::pin(stream_openai_compat(
-            client.clone(),
-            "https://api.openai.com/v1/chat/completions",
-            messages,
-            model.to_string(),
-            api_key.to_string(),
-            temperature,
-            max_tokens,
-        )),
+        LLMProvider::OpenAI => {
+            let mut body = openai_request_body(&messages, model, temperature, max_tokens, true);
+            if let Some(tools) = tools {
+                tools.apply_openai(&mut body);
+            }
+            Box::pin(stream_openai_compat(
+                client.clone(),
+                &format!("{}/chat/completions", openai_base_url.unwrap_or(OPENAI_BASE_URL).trim_end_matches('/')),
+                body,
+                model.to_string(),
+                api_key.to_string(),
+            ))
+        }
         LLMProvider::Groq => Box::pin(stream_openai_compat(
             client.clone(),
             "https://api.groq.com/openai/v1/chat/completions",
-            messages,
+            openai_request_body(&messages, model, temperature, max_tokens, false),
             model.to_string(),
             api_key.to_string(),
-            temperature,
-            max_tokens,
-        )),
-        LLMProvider::Anthropic => Box::pin(stream_anthropic(
-            client.clone(),
-            messages,
-            model.to_string(),
-            api_key.to_string(),
-            temperature,
-            max_tokens,
         )),
+        LLMProvider::Anthropic => {
+            let mut body = anthropic_request_body(&messages, model, temperature, max_tokens);
+            if let Some(tools) = tools {
+                tools.apply_anthropic(&mut body);
+            }
+            Box::pin(stream_anthropic(client.clone(), body, model.to_string(), api_key.to_string()))
+        }
+    }
+}
+
+/// Request body for OpenAI-compatible APIs. The messages keep their order,
+/// so the system prompt leads and forms the prefix OpenAI caches on its
+/// own; `include_usage` asks for a final chunk with the token usage.
+pub fn openai_request_body(
+    messages: &[ChatMessage],
+    model: &str,
+    temperature: f64,
+    max_tokens: usize,
+    include_usage: bool,
+) -> serde_json::Value {
+    let msgs: Vec<serde_json::Value> = messages
+        .iter()
+        .map(|m| json!({"role": m.role, "content": m.content}))
+        .collect();
+    let mut body = json!({
+        "model": model,
+        "messages": msgs,
+        "temperature": temperature,
+        "max_tokens": max_tokens,
+        "stream": true,
+    });
+    if include_usage {
+        body["stream_options"] = json!({"include_usage": true});
+    }
+    body
+}
+
+/// Request body for Anthropic's Messages API. The system prompt becomes a
+/// text block, marked as a cache breakpoint once it is long enough for
+/// Anthropic to cache.
+pub fn anthropic_request_body(
+    messages: &[ChatMessage],
+    model: &str,
+    temperature: f64,
+    max_tokens: usize,
+) -> serde_json::Value {
+    let conv_msgs: Vec<serde_json::Value> = messages
+        .iter()
+        .filter(|m| m.role != "system")
+        .map(|m| json!({"role": m.role, "content": m.content}))
+        .collect();
+    let mut body = json!({
+        "model": model,
+        "messages": conv_msgs,
+        "temperature": temperature,
+        "max_tokens": max_tokens,
+        "stream": true,
+    });
+
+    if let Some(system) = messages.iter().find(|m| m.role == "system") {
+        let min_tokens = if model.contains("haiku") {
+            ANTHROPIC_HAIKU_MIN_CACHE_TOKENS
+        } else {
+            ANTHROPIC_MIN_CACHE_TOKENS
+        };
+        let mut block = json!({"type": "text", "text": system.content});
+        if system.content.len() / CHARS_PER_TOKEN >= min_tokens {
+            block["cache_control"] = json!({"type": "ephemeral"});
+        }
+        body["system"] = json!([block]);
     }
+    body
+}
+
+/// Usage from an OpenAI chunk (`usage`) or a Groq one (`x_groq.usage`).
+fn openai_usage(parsed: &serde_json::Value) -> Option<ProviderUsage> {
+    let usage = [&parsed["usage"], &parsed["x_groq"]["usage"]]
+        .into_iter()
+        .find(|u| u.is_object())?;
+    let count = |v: &serde_json::Value| v.as_u64().unwrap_or(0) as usize;
+    Some(ProviderUsage {
+        prompt_tokens: count(&usage["prompt_tokens"]),
+        completion_tokens: count(&usage["completion_tokens"]),
+        cached_tokens: count(&usage["prompt_tokens_details"]["cached_tokens"]),
+        cache_write_tokens: 0,
+    })
+}
+
+/// Fold an Anthropic `usage` object into `into`. `message_start` carries
+/// the input counts, `message_delta` the running output count; Anthropic's
+/// `input_tokens` leaves out the cached tokens.
+fn add_anthropic_usage(usage: &serde_json::Value, into: &mut ProviderUsage) {
+    let count = |key: &str| usage[key].as_u64().map(|n| n as usize);
+    let read = count("cache_read_input_tokens");
+    let written = count("cache_creation_input_tokens");
+    if let Some(input) = count("input_tokens") {
+        into.prompt_tokens = input + read.unwrap_or(0) + written.unwrap_or(0);
+    }
+    into.cached_tokens = read.unwrap_or(into.cached_tokens);
+    into.cache_write_tokens = written.unwrap_or(into.cache_write_tokens);
+    into.completion_tokens = count("output_tokens").unwrap_or(into.completion_tokens);
 }
 
 /// Stream from OpenAI-compatible APIs (OpenAI, Groq).
 fn stream_openai_compat(
     client: Client,
     url: &str,
-    messages: Vec<ChatMessage>,
+    body: serde_json::Value,
     model: String,
     api_key: String,
-    temperature: f64,
-    max_tokens: usize,
 ) -> impl Stream<Item = StreamChunk> + Send + 'static {
     let url = url.to_string();
-    let msgs: Vec<serde_json::Value> = messages
-        .iter()
-        .map(|m| json!({"role": m.role, "content": m.content}))
-        .collect();
 
     async_stream::stream! {
-        let body = json!({
-            "model": model,
-            "messages": msgs,
-            "temperature": temperature,
-            "max_tokens": max_tokens,
-            "stream": true,
-        });
-
         debug!("Streaming from {} with model {}", url, model);
 
         let response = match client
@@ -115,8 +223,10 @@ fn stream_openai_compat(
         let mut stream = response.bytes_stream();
         let mut buffer = String::new();
         let mut token_count = 0usize;
+        let mut usage = None;
+        let mut calls = OpenAiCalls::default();
 
-        while let Some(chunk) = stream.next().await {
+        'read: while let Some(chunk) = stream.next().await {
             let bytes = match chunk {
                 Ok(b) => b,
                 Err(e) => {
@@ -138,11 +248,13 @@ fn stream_openai_compat(
 
                 if let Some(data) = line.strip_prefix("data: ") {
                     if data.trim() == "[DONE]" {
-                        yield StreamChunk::Done { tokens_used: token_count };
-                        return;
+                        break 'read;
                     }
 
                     if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(data) {
+                        // Sent with the last content chunk, or alone after it
+                        usage = openai_usage(&parsed).or(usage);
+                        calls.absorb(&parsed["choices"][0]["delta"]);
                         if let Some(content) = parsed["choices"][0]["delta"]["content"].as_str() {
                             if !content.is_empty() {
                                 token_count += 1;
@@ -154,44 +266,22 @@ fn stream_openai_compat(
             }
         }
 
-        yield StreamChunk::Done { tokens_used: token_count };
+        let calls = calls.finish();
+        if !calls.is_empty() {
+            yield StreamChunk::ToolCalls(calls);
+        }
+        yield StreamChunk::Done { tokens_used: token_count, usage };
     }
 }
 
 /// Stream from Anthropic's Messages API.
 fn stream_anthropic(
     client: Client,
-    messages: Vec<ChatMessage>,
+    body: serde_json::Value,
     model: String,
     api_key: String,
-    temperature: f64,
-    max_tokens: usize,
 ) -> impl Stream<Item = StreamChunk> + Send + 'static {
-    // Separate system message from conversation
-    let system_msg: Option<String> = messages
-        .iter()
-        .find(|m| m.role == "system")
-        .map(|m| m.content.clone());
-
-    let conv_msgs: Vec<serde_json::Value> = messages
-        .iter()
-        .filter(|m| m.role != "system")
-        .map(|m| json!({"role": m.role, "content": m.content}))
-        .collect();
-
     async_stream::stream! {
-        let mut body = json!({
-            "model": model,
-            "messages": conv_msgs,
-            "temperature": temperature,
-            "max_tokens": max_tokens,
-            "stream": true,
-        });
-
-        if let Some(sys) = system_msg {
-            body["system"] = json!(sys);
-        }
-
         debug!("Streaming from Anthropic with model {}", model);
 
         let response = match client
@@ -220,6 +310,8 @@ fn stream_anthropic(
         let mut stream = response.bytes_stream();
         let mut buffer = String::new();
         let mut token_count = 0usize;
+        let mut usage = ProviderUsage::default();
+        let mut calls = AnthropicCalls::default();
 
         while let Some(chunk) = stream.next().await {
             let bytes = match chunk {
@@ -243,7 +335,14 @@ fn stream_anthropic(
                 // Anthropic uses "event: " lines followed by "data: " lines
                 if let Some(data) = line.strip_prefix("data: ") {
                     if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(data) {
+                        calls.absorb(&parsed);
                         match parsed["type"].as_str() {
+                            Some("message_start") => {
+                                add_anthropic_usage(&parsed["message"]["usage"], &mut usage);
+                            }
+                            Some("message_delta") => {
+                                add_anthropic_usage(&parsed["usage"], &mut usage);
+                            }
                             Some("content_block_delta") => {
                                 if let Some(text) = parsed["delta"]["text"].as_str() {
                                     if !text.is_empty() {
@@ -253,7 +352,11 @@ fn stream_anthropic(
                                 }
                             }
                             Some("message_stop") => {
-                                yield StreamChunk::Done { tokens_used: token_count };
+                                let calls = std::mem::take(&mut calls).finish();
+                                if !calls.is_empty() {
+                                    yield StreamChunk::ToolCalls(calls);
+                                }
+                                yield StreamChunk::Done { tokens_used: token_count, usage: Some(usage) };
                                 return;
                             }
                             Some("error") => {
@@ -271,7 +374,12 @@ fn stream_anthropic(
             }
         }
 
-        yield StreamChunk::Done { tokens_used: token_count };
+        let calls = calls.finish();
+        if !calls.is_empty() {
+            yield StreamChunk::ToolCalls(calls);
+        }
+        let usage = (usage != ProviderUsage::default()).then_some(usage);
+        yield StreamChunk::Done { tokens_used: token_count, usage };
     }
 }
 
@@ -330,3 +438,60 @@ pub async fn test_api_key(provider: &str, api_key: &str) -> Result<(), String> {
         _ => Err(format!("Unknown provider: {}", provider)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(system: &str) -> Vec<ChatMessage> {
+        ["system", "user"]
+            .into_iter()
+            .zip([system, "When did the harbour freeze?"])
+            .map(|(role, content)| ChatMessage { role: role.into(), content: content.into() })
+            .collect()
+    }
+
+    #[test]
+    fn test_anthropic_marks_long_system_prompt_for_caching() {
+        let long = "The harbour froze in January. ".repeat(150);
+        let body = anthropic_request_body(&messages(&long), "claude-sonnet-4-5", 0.7, 512);
+        let serialized = serde_json::to_string(&body).unwrap();
+        assert!(serialized.contains(r#""cache_control":{"type":"ephemeral"}"#));
+        assert_eq!(body["system"][0]["text"], long.as_str());
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+
+        // Below the minimum: no breakpoint, Haiku's minimum is higher
+        let body = anthropic_request_body(&messages("Be brief."), "claude-sonnet-4-5", 0.7, 512);
+        assert!(body["system"][0].get("cache_control").is_none());
+        let body = anthropic_request_body(&messages(&long), "claude-3-5-haiku-20241022", 0.7, 512);
+        assert!(body["system"][0].get("cache_control").is_none());
+
+        let body = openai_request_body(&messages(&long), "gpt-4o-mini", 0.7, 512, true);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["stream_options"]["include_usage"], true);
+    }
+
+    #[test]
+    fn test_usage_reports_cache_hits() {
+        let openai = json!({"choices": [], "usage": {
+            "prompt_tokens": 2006, "completion_tokens": 300,
+            "prompt_tokens_details": {"cached_tokens": 1920},
+        }});
+        let usage = openai_usage(&openai).unwrap();
+        assert_eq!((usage.prompt_tokens, usage.cached_tokens, usage.completion_tokens), (2006, 1920, 300));
+        let groq = json!({"choices": [], "x_groq": {"usage": {"prompt_tokens": 80, "completion_tokens": 9}}});
+        assert_eq!(openai_usage(&groq).unwrap().cached_tokens, 0);
+        assert!(openai_usage(&json!({"choices": [{"delta": {"content": "Hi"}}]})).is_none());
+
+        let mut usage = ProviderUsage::default();
+        add_anthropic_usage(
+            &json!({"input_tokens": 21, "cache_read_input_tokens": 1800, "cache_creation_input_tokens": 0, "output_tokens": 1}),
+            &mut usage,
+        );
+        add_anthropic_usage(&json!({"output_tokens": 250}), &mut usage);
+        assert_eq!(
+            usage,
+            ProviderUsage { prompt_tokens: 1821, completion_tokens: 250, cached_tokens: 1800, cache_write_tokens: 0 }
+        );
+    }
+}
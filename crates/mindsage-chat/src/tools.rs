@@ -0,0 +1,335 @@
+//! Retrieval during generation — the `search_knowledge_base` tool.
+//!
+//! Instead of receiving the RAG context up front, the model is offered one
+//! tool and calls it when it needs facts. Each round the provider streams
+//! its text and tool calls (`StreamChunk::ToolCalls`), the caller runs the
+//! searches, and the request is sent again with the calls and their results
+//! appended. After `MAX_TOOL_ROUNDS` rounds the tool stays defined but the
+//! model may no longer call it, so it has to answer.
+//!
+//! Anthropic replays a round as an assistant message of `tool_use` blocks
+//! and a user message of `tool_result` blocks; OpenAI as an assistant
+//! message with `tool_calls` followed by one `tool` message per call.
+
+use serde_json::{json, Value};
+
+use crate::types::LLMProvider;
+
+/// Name of the search tool offered to the model.
+pub const SEARCH_TOOL: &str = "search_knowledge_base";
+
+/// Rounds of tool calls before the model must answer.
+pub const MAX_TOOL_ROUNDS: usize = 3;
+
+/// Largest `top_k` a tool call may ask for.
+pub const MAX_TOOL_TOP_K: usize = 10;
+
+const SEARCH_TOOL_DESCRIPTION: &str = "Search the user's personal knowledge base (their documents, notes and \
+    captured conversations). Call it whenever the answer depends on facts you have not been given yet; \
+    results are numbered excerpts to cite as [n].";
+
+impl LLMProvider {
+    /// Whether tool calling is implemented for this provider. The others
+    /// get the RAG context up front.
+    pub fn supports_tools(self) -> bool {
+        matches!(self, LLMProvider::Anthropic | LLMProvider::OpenAI)
+    }
+}
+
+/// A tool call the model made.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCall {
+    /// Provider-assigned id the result refers back to.
+    pub id: String,
+    pub name: String,
+    /// Parsed JSON arguments; `Null` when the model sent invalid JSON.
+    pub arguments: Value,
+}
+
+impl ToolCall {
+    /// Query and result count of a `search_knowledge_base` call; `None`
+    /// for another tool or a call without a query.
+    pub fn search_args(&self, default_top_k: usize) -> Option<(String, usize)> {
+        if self.name != SEARCH_TOOL {
+            return None;
+        }
+        let query = self.arguments["query"].as_str()?.trim();
+        if query.is_empty() {
+            return None;
+        }
+        let top_k = self.arguments["top_k"].as_u64().map_or(default_top_k, |k| k as usize);
+        Some((query.to_string(), top_k.clamp(1, MAX_TOOL_TOP_K)))
+    }
+}
+
+/// A call and the text sent back as its result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolResult {
+    pub call: ToolCall,
+    pub content: String,
+}
+
+/// One round: the text the model wrote before calling, and its calls with
+/// their results.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolRound {
+    pub text: String,
+    pub results: Vec<ToolResult>,
+}
+
+/// Tool use for one request: the rounds so far, and whether the model may
+/// call again.
+#[derive(Debug, Clone, Default)]
+pub struct ToolUse {
+    pub rounds: Vec<ToolRound>,
+    pub allow_calls: bool,
+}
+
+impl ToolUse {
+    /// The first request of a tool-using answer.
+    pub fn new() -> Self {
+        Self { rounds: Vec::new(), allow_calls: true }
+    }
+
+    /// Append a finished round; calls stop being allowed after
+    /// `MAX_TOOL_ROUNDS`.
+    pub fn push(&mut self, round: ToolRound) {
+        self.rounds.push(round);
+        self.allow_calls = self.rounds.len() < MAX_TOOL_ROUNDS;
+    }
+
+    /// Add the tool definition and the rounds to an Anthropic request body.
+    pub fn apply_anthropic(&self, body: &mut Value) {
+        body["tools"] = json!([{
+            "name": SEARCH_TOOL,
+            "description": SEARCH_TOOL_DESCRIPTION,
+            "input_schema": search_schema(),
+        }]);
+        if !self.allow_calls {
+            body["tool_choice"] = json!({"type": "none"});
+        }
+        let messages = body["messages"].as_array_mut().expect("request body has messages");
+        for round in &self.rounds {
+            let mut content = Vec::new();
+            if !round.text.is_empty() {
+                content.push(json!({"type": "text", "text": round.text}));
+            }
+            content.extend(round.results.iter().map(|r| {
+                json!({"type": "tool_use", "id": r.call.id, "name": r.call.name, "input": r.call.arguments})
+            }));
+            messages.push(json!({"role": "assistant", "content": content}));
+            let results: Vec<Value> = round
+                .results
+                .iter()
+                .map(|r| json!({"type": "tool_result", "tool_use_id": r.call.id, "content": r.content}))
+                .collect();
+            messages.push(json!({"role": "user", "content": results}));
+        }
+    }
+
+    /// Add the tool definition and the rounds to an OpenAI request body.
+    pub fn apply_openai(&self, body: &mut Value) {
+        body["tools"] = json!([{
+            "type": "function",
+            "function": {
+                "name": SEARCH_TOOL,
+                "description": SEARCH_TOOL_DESCRIPTION,
+                "parameters": search_schema(),
+            },
+        }]);
+        if !self.allow_calls {
+            body["tool_choice"] = json!("none");
+        }
+        let messages = body["messages"].as_array_mut().expect("request body has messages");
+        for round in &self.rounds {
+            let calls: Vec<Value> = round
+                .results
+                .iter()
+                .map(|r| {
+                    json!({
+                        "id": r.call.id,
+                        "type": "function",
+                        "function": {"name": r.call.name, "arguments": r.call.arguments.to_string()},
+                    })
+                })
+                .collect();
+            let text = (!round.text.is_empty()).then_some(round.text.as_str());
+            messages.push(json!({"role": "assistant", "content": text, "tool_calls": calls}));
+            for r in &round.results {
+                messages.push(json!({"role": "tool", "tool_call_id": r.call.id, "content": r.content}));
+            }
+        }
+    }
+}
+
+fn search_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "query": {"type": "string", "description": "What to search for, in a few words"},
+            "top_k": {"type": "integer", "description": "How many excerpts to return", "minimum": 1, "maximum": MAX_TOOL_TOP_K},
+        },
+        "required": ["query"],
+    })
+}
+
+/// Tool calls assembled from an OpenAI stream: `delta.tool_calls` entries
+/// name a call by `index` and send its arguments in pieces.
+#[derive(Debug, Default)]
+pub(crate) struct OpenAiCalls {
+    calls: Vec<(String, String, String)>,
+}
+
+impl OpenAiCalls {
+    pub(crate) fn absorb(&mut self, delta: &Value) {
+        let Some(parts) = delta["tool_calls"].as_array() else { return };
+        for part in parts {
+            let index = part["index"].as_u64().unwrap_or(0) as usize;
+            if self.calls.len() <= index {
+                self.calls.resize(index + 1, Default::default());
+            }
+            let call = &mut self.calls[index];
+            if let Some(id) = part["id"].as_str() {
+                call.0 = id.to_string();
+            }
+            if let Some(name) = part["function"]["name"].as_str() {
+                call.1.push_str(name);
+            }
+            if let Some(arguments) = part["function"]["arguments"].as_str() {
+                call.2.push_str(arguments);
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<ToolCall> {
+        self.calls.into_iter().filter(|c| !c.1.is_empty()).map(|(id, name, args)| to_call(id, name, &args)).collect()
+    }
+}
+
+/// Tool calls assembled from an Anthropic stream: a `tool_use` content
+/// block starts a call, `input_json_delta`s send its input in pieces.
+#[derive(Debug, Default)]
+pub(crate) struct AnthropicCalls {
+    /// Content block index, id, name and input so far.
+    calls: Vec<(u64, String, String, String)>,
+}
+
+impl AnthropicCalls {
+    pub(crate) fn absorb(&mut self, event: &Value) {
+        let index = event["index"].as_u64().unwrap_or(0);
+        match event["type"].as_str() {
+            Some("content_block_start") if event["content_block"]["type"] == "tool_use" => {
+                let block = &event["content_block"];
+                let id = block["id"].as_str().unwrap_or_default().to_string();
+                let name = block["name"].as_str().unwrap_or_default().to_string();
+                self.calls.push((index, id, name, String::new()));
+            }
+            Some("content_block_delta") => {
+                if let Some(json) = event["delta"]["partial_json"].as_str() {
+                    if let Some(call) = self.calls.iter_mut().find(|c| c.0 == index) {
+                        call.3.push_str(json);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<ToolCall> {
+        self.calls.into_iter().map(|(_, id, name, input)| to_call(id, name, &input)).collect()
+    }
+}
+
+fn to_call(id: String, name: String, arguments: &str) -> ToolCall {
+    let arguments = if arguments.trim().is_empty() {
+        json!({})
+    } else {
+        serde_json::from_str(arguments).unwrap_or(Value::Null)
+    };
+    ToolCall { id, name, arguments }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round(id: &str, query: &str) -> ToolRound {
+        ToolRound {
+            text: "Let me look that up.".into(),
+            results: vec![ToolResult {
+                call: ToolCall { id: id.into(), name: SEARCH_TOOL.into(), arguments: json!({"query": query}) },
+                content: "[1]: The ferry leaves at ten.".into(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_streamed_calls_are_assembled() {
+        let mut openai = OpenAiCalls::default();
+        for delta in [
+            json!({"tool_calls": [{"index": 0, "id": "call_1", "function": {"name": "search_knowledge_base", "arguments": ""}}]}),
+            json!({"tool_calls": [{"index": 0, "function": {"arguments": "{\"query\": \"ferry"}}]}),
+            json!({"tool_calls": [{"index": 0, "function": {"arguments": " times\", \"top_k\": 50}"}}]}),
+        ] {
+            openai.absorb(&delta);
+        }
+        let calls = openai.finish();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].search_args(5), Some(("ferry times".to_string(), MAX_TOOL_TOP_K)));
+
+        let mut anthropic = AnthropicCalls::default();
+        for event in [
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "search_knowledge_base", "input": {}}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"query\":"}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": " \"boiler\"}"}}),
+        ] {
+            anthropic.absorb(&event);
+        }
+        let calls = anthropic.finish();
+        assert_eq!(calls.len(), 1);
+        assert_eq!((calls[0].id.as_str(), calls[0].search_args(5)), ("toolu_1", Some(("boiler".to_string(), 5))));
+
+        // Another tool, or no query, is not a search
+        let other = ToolCall { id: "x".into(), name: "weather".into(), arguments: json!({"query": "rain"}) };
+        assert!(other.search_args(5).is_none());
+        assert!(to_call("y".into(), SEARCH_TOOL.into(), "{not json").search_args(5).is_none());
+    }
+
+    #[test]
+    fn test_rounds_are_replayed_in_provider_format() {
+        let mut tools = ToolUse::new();
+        tools.push(round("toolu_1", "ferry"));
+
+        let mut body = json!({"messages": [{"role": "user", "content": "When is the ferry?"}]});
+        tools.apply_anthropic(&mut body);
+        assert_eq!(body["tools"][0]["name"], SEARCH_TOOL);
+        assert!(body.get("tool_choice").is_none());
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["content"][1]["type"], "tool_use");
+        assert_eq!(messages[1]["content"][1]["input"]["query"], "ferry");
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(messages[2]["content"][0]["tool_use_id"], "toolu_1");
+
+        let mut body = json!({"messages": [{"role": "user", "content": "When is the ferry?"}]});
+        tools.apply_openai(&mut body);
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages[1]["tool_calls"][0]["function"]["arguments"], r#"{"query":"ferry"}"#);
+        assert_eq!((messages[2]["role"].as_str(), messages[2]["tool_call_id"].as_str()), (Some("tool"), Some("toolu_1")));
+
+        // The last allowed round turns calls off
+        for i in 1..MAX_TOOL_ROUNDS {
+            assert!(tools.allow_calls);
+            tools.push(round(&format!("toolu_{}", i + 1), "ferry"));
+        }
+        assert!(!tools.allow_calls);
+        let mut body = json!({"messages": []});
+        tools.apply_anthropic(&mut body);
+        assert_eq!(body["tool_choice"]["type"], "none");
+        let mut body = json!({"messages": []});
+        tools.apply_openai(&mut body);
+        assert_eq!(body["tool_choice"], "none");
+    }
+}
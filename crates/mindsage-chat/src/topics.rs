@@ -0,0 +1,250 @@
+//! LLM topic generation — an alternative to heuristic extraction.
+//!
+//! A condensed copy of the document is sent to the configured provider with
+//! a prompt asking for strict JSON. The reply is validated and normalized
+//! here; callers fall back to heuristics when it cannot be used.
+
+use std::future::Future;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio_stream::StreamExt;
+
+use crate::providers::{BoxedStream, StreamChunk};
+use crate::types::ChatMessage;
+
+/// Fewest topics accepted from the model.
+pub const MIN_LLM_TOPICS: usize = 3;
+/// Most topics kept from the model (extras are dropped).
+pub const MAX_LLM_TOPICS: usize = 7;
+/// How long to wait for a topic completion.
+pub const LLM_TOPIC_TIMEOUT: Duration = Duration::from_secs(30);
+/// Token budget for the JSON reply.
+pub const LLM_TOPIC_MAX_TOKENS: usize = 256;
+/// Characters of document text sent to the model.
+const CONDENSED_CHARS: usize = 6000;
+/// Longest topic label kept after normalization.
+const MAX_TOPIC_CHARS: usize = 40;
+
+const TOPIC_SYSTEM_PROMPT: &str = "You label documents for a personal knowledge base. \
+Reply with JSON only, no prose and no code fences, in exactly this shape: \
+{\"topics\": [\"...\"], \"primary_topic\": \"...\"}. \
+Give 3 to 7 short topics (one to three words, lowercase). \
+primary_topic must be one of the topics and describe the document as a whole.";
+
+/// Topics produced by the model after validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlmTopics {
+    pub topics: Vec<String>,
+    pub primary_topic: String,
+}
+
+#[derive(Deserialize)]
+struct RawTopics {
+    #[serde(default)]
+    topics: Vec<serde_json::Value>,
+    #[serde(default, alias = "primaryTopic")]
+    primary_topic: Option<String>,
+}
+
+/// Shorten a document for the prompt: the opening of the text plus its
+/// ending, split on char boundaries.
+pub fn condense_document(text: &str, max_chars: usize) -> String {
+    let text = text.trim();
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let head_chars = max_chars * 3 / 4;
+    let tail_chars = max_chars - head_chars;
+    let head: String = text.chars().take(head_chars).collect();
+    let tail_start = text
+        .char_indices()
+        .rev()
+        .nth(tail_chars.saturating_sub(1))
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    format!("{}\n[...]\n{}", head, &text[tail_start..])
+}
+
+/// Build the constrained prompt for a document.
+pub fn topic_messages(text: &str) -> Vec<ChatMessage> {
+    vec![
+        ChatMessage {
+            role: "system".into(),
+            content: TOPIC_SYSTEM_PROMPT.into(),
+        },
+        ChatMessage {
+            role: "user".into(),
+            content: format!("Document:\n{}", condense_document(text, CONDENSED_CHARS)),
+        },
+    ]
+}
+
+/// Normalize a topic label: lowercase, whitespace collapsed, punctuation
+/// other than `-`, `&`, `+` and `.` stripped. Returns `None` when nothing
+/// usable remains.
+pub fn normalize_topic(raw: &str) -> Option<String> {
+    let cleaned: String = raw
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '&' | '+' | '.') {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    let topic = cleaned
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let topic = topic.trim_matches(|c: char| c == '.' || c == '-').to_string();
+    if topic.is_empty() || topic.chars().count() > MAX_TOPIC_CHARS {
+        return None;
+    }
+    Some(topic)
+}
+
+/// Parse and validate a model reply. Tolerates code fences and prose
+/// around the JSON object; requires at least [`MIN_LLM_TOPICS`] usable
+/// topics. The primary topic is listed first (added when the model left
+/// it out) and defaults to the first topic when missing.
+pub fn parse_topic_response(raw: &str) -> Result<LlmTopics, String> {
+    let start = raw.find('{').ok_or("Response contains no JSON object")?;
+    let end = raw.rfind('}').filter(|&e| e > start).ok_or("Response contains no JSON object")?;
+    let parsed: RawTopics = serde_json::from_str(&raw[start..=end])
+        .map_err(|e| format!("Invalid topic JSON: {}", e))?;
+
+    let mut topics: Vec<String> = Vec::new();
+    for value in &parsed.topics {
+        if let Some(topic) = value.as_str().and_then(normalize_topic) {
+            if !topics.contains(&topic) {
+                topics.push(topic);
+            }
+        }
+    }
+    if topics.len() < MIN_LLM_TOPICS {
+        return Err(format!(
+            "Expected at least {} topics, got {}",
+            MIN_LLM_TOPICS,
+            topics.len()
+        ));
+    }
+
+    let primary_topic = parsed
+        .primary_topic
+        .as_deref()
+        .and_then(normalize_topic)
+        .unwrap_or_else(|| topics[0].clone());
+
+    // Primary topic first, so trimming to the limit never drops it
+    topics.retain(|t| *t != primary_topic);
+    topics.insert(0, primary_topic.clone());
+    topics.truncate(MAX_LLM_TOPICS);
+
+    Ok(LlmTopics {
+        topics,
+        primary_topic,
+    })
+}
+
+/// Collect a provider stream into the full reply text.
+pub async fn collect_completion(stream: BoxedStream) -> Result<String, String> {
+    tokio::pin!(stream);
+    let mut text = String::new();
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            StreamChunk::Token(t) => text.push_str(&t),
+            StreamChunk::ToolCalls(_) => {}
+            StreamChunk::Done { .. } => break,
+            StreamChunk::Error(e) => return Err(e),
+        }
+    }
+    Ok(text)
+}
+
+/// Ask the model for topics. `complete` sends the prompt to a provider and
+/// returns its reply; it is a parameter so the provider can be swapped out.
+pub async fn generate_llm_topics<F, Fut>(
+    text: &str,
+    complete: F,
+    timeout: Duration,
+) -> Result<LlmTopics, String>
+where
+    F: FnOnce(Vec<ChatMessage>) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    let reply = tokio::time::timeout(timeout, complete(topic_messages(text)))
+        .await
+        .map_err(|_| format!("LLM did not respond within {}s", timeout.as_secs()))??;
+    parse_topic_response(&reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mocked_provider_valid_json() {
+        let reply = "```json\n{\"topics\": [\"Rust\", \"Async Runtimes\", \"rust\", \"Tokio!\", \"\"], \
+                     \"primary_topic\": \"Async runtimes\"}\n```";
+        let topics = generate_llm_topics(
+            "Tokio is an async runtime for Rust.",
+            |messages| async move {
+                assert_eq!(messages[0].role, "system");
+                assert!(messages[1].content.contains("Tokio"));
+                Ok(reply.to_string())
+            },
+            LLM_TOPIC_TIMEOUT,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(topics.topics, vec!["async runtimes", "rust", "tokio"]);
+        assert_eq!(topics.primary_topic, "async runtimes");
+    }
+
+    #[tokio::test]
+    async fn test_mocked_provider_malformed_json() {
+        for reply in [
+            "Sure! Here are some topics: rust, tokio",
+            "{\"topics\": \"rust\"}",
+            "{\"topics\": [\"rust\", \"tokio\"], \"primary_topic\": \"rust\"}",
+        ] {
+            let result = generate_llm_topics(
+                "Tokio is an async runtime for Rust.",
+                |_| async move { Ok(reply.to_string()) },
+                LLM_TOPIC_TIMEOUT,
+            )
+            .await;
+            assert!(result.is_err(), "accepted {:?}", reply);
+        }
+
+        let result = generate_llm_topics(
+            "text",
+            |_| async { Err::<String, _>("Request failed".to_string()) },
+            LLM_TOPIC_TIMEOUT,
+        )
+        .await;
+        assert_eq!(result.unwrap_err(), "Request failed");
+    }
+
+    #[test]
+    fn test_primary_topic_survives_trimming() {
+        let reply = r#"{"topics": ["a1", "b2", "c3", "d4", "e5", "f6", "g7", "h8"], "primary_topic": "zeta"}"#;
+        let topics = parse_topic_response(reply).unwrap();
+        assert_eq!(topics.topics.len(), MAX_LLM_TOPICS);
+        assert_eq!(topics.topics[0], "zeta");
+        assert_eq!(topics.primary_topic, "zeta");
+    }
+
+    #[test]
+    fn test_condense_document_is_char_safe() {
+        let text = "é".repeat(100);
+        let condensed = condense_document(&text, 20);
+        assert!(condensed.starts_with(&"é".repeat(15)));
+        assert!(condensed.ends_with(&"é".repeat(5)));
+        assert_eq!(condense_document("short", 20), "short");
+    }
+}
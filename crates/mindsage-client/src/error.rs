@@ -0,0 +1,55 @@
+//! Client errors.
+
+use mindsage_api_types::ErrorBody;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The server answered with a non-2xx status.
+    #[error("{} ({}): {}", body.status, body.code, body.message)]
+    Api { body: ErrorBody },
+
+    /// The request could not be sent or the response body not read.
+    #[error("transport error: {0}")]
+    Transport(String),
+
+    /// The response body did not have the expected shape.
+    #[error("invalid response: {0}")]
+    Decode(String),
+
+    /// An indexing job did not finish within the wait timeout.
+    #[error("timed out waiting for indexing job {0}")]
+    Timeout(String),
+}
+
+impl Error {
+    /// HTTP status of an API error.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Api { body } => Some(body.status),
+            _ => None,
+        }
+    }
+
+    /// An API error from a non-2xx response. Bodies that are not the
+    /// error envelope (e.g. from a proxy) become its message.
+    pub(crate) fn from_response(status: http::StatusCode, body: &[u8]) -> Self {
+        let body = serde_json::from_slice(body).unwrap_or_else(|_| {
+            let text = String::from_utf8_lossy(body).trim().to_string();
+            let message = if text.is_empty() {
+                status.canonical_reason().unwrap_or("error").to_string()
+            } else {
+                text
+            };
+            ErrorBody {
+                code: "http_error".to_string(),
+                message: message.clone(),
+                status: status.as_u16(),
+                error: message,
+                details: None,
+            }
+        });
+        Error::Api { body }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
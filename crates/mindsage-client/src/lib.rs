@@ -0,0 +1,386 @@
+//! Async client for the MindSage HTTP API.
+//!
+//! Methods mirror the main endpoints and use the request and response
+//! types of `mindsage-api-types`, the same ones the server serializes.
+//!
+//! ```no_run
+//! # async fn run() -> mindsage_client::Result<()> {
+//! use mindsage_client::{Client, SearchRequest};
+//!
+//! let client = Client::new("http://localhost:3003");
+//! let results = client.search(&SearchRequest::new("tokio runtime")).await?;
+//! for hit in results.results {
+//!     println!("{:.3} {}", hit.score, hit.text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod error;
+mod sse;
+mod transport;
+
+use std::pin::Pin;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{Stream, TryStreamExt};
+use http::{header, Method};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub use error::{Error, Result};
+#[cfg(feature = "tower")]
+pub use transport::ServiceTransport;
+pub use transport::{BodyStream, HttpTransport, Transport};
+
+pub use mindsage_api_types::{chat::*, connectors::*, documents::*, files::*, indexing::*, search::*, ErrorBody};
+
+/// Chat events as they arrive.
+pub type ChatStream = Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>;
+
+/// Header selecting the profile a request is served from.
+const PROFILE_HEADER: &str = "X-Profile";
+
+/// Header carrying the SHA-256 of a resumable upload chunk.
+const CHUNK_SHA256_HEADER: &str = "X-Chunk-Sha256";
+
+#[derive(Debug, Clone)]
+pub struct Client<T = HttpTransport> {
+    transport: T,
+    base_url: String,
+    profile: Option<String>,
+}
+
+impl Client {
+    /// Client for a server at `base_url`, e.g. `http://localhost:3003`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_transport(HttpTransport::default(), base_url)
+    }
+}
+
+impl<T: Transport> Client<T> {
+    pub fn with_transport(transport: T, base_url: impl Into<String>) -> Self {
+        Self {
+            transport,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            profile: None,
+        }
+    }
+
+    /// Send every request to profile `name` instead of the default one.
+    pub fn with_profile(mut self, name: impl Into<String>) -> Self {
+        self.profile = Some(name.into());
+        self
+    }
+
+    // -----------------------------------------------------------
+    // Documents
+    // -----------------------------------------------------------
+
+    /// `GET /api/vector-store/status`
+    pub async fn status(&self) -> Result<StatusResponse> {
+        self.get("/api/vector-store/status").await
+    }
+
+    /// `POST /api/vector-store/documents`. A document with the same
+    /// content hash is a 409 `Error::Api`.
+    pub async fn add_document(&self, request: &AddDocumentRequest) -> Result<AddDocumentResponse> {
+        self.post("/api/vector-store/documents", request).await
+    }
+
+    /// `GET /api/vector-store/documents`, newest first; `page` is 1-based.
+    pub async fn list_documents(&self, page: usize, page_size: usize) -> Result<DocumentListResponse> {
+        self.get(&format!("/api/vector-store/documents?page={}&page_size={}", page, page_size))
+            .await
+    }
+
+    /// `GET /api/vector-store/documents/{id}`, with up to 500 chunks inlined
+    /// when `include_chunks` is set.
+    pub async fn get_document(&self, id: i64, include_chunks: bool) -> Result<DocumentResponse> {
+        self.get(&format!("/api/vector-store/documents/{}?include_chunks={}", id, include_chunks))
+            .await
+    }
+
+    /// `GET /api/vector-store/documents/hashes`: content hashes of documents
+    /// changed since `since` (ms), as a list or a Bloom filter for large
+    /// stores. Check candidates with `DocumentHashesResponse::may_contain`.
+    pub async fn document_hashes(&self, since: Option<i64>) -> Result<DocumentHashesResponse> {
+        match since {
+            Some(since) => self.get(&format!("/api/vector-store/documents/hashes?since={}", since)).await,
+            None => self.get("/api/vector-store/documents/hashes").await,
+        }
+    }
+
+    /// `GET /api/vector-store/documents/by-hash/{hash}`; `None` when no
+    /// document has this content hash.
+    pub async fn document_by_hash(&self, content_hash: &str) -> Result<Option<Document>> {
+        let path = format!("/api/vector-store/documents/by-hash/{}", encode_segment(content_hash));
+        match self.get(&path).await {
+            Ok(doc) => Ok(Some(doc)),
+            Err(e) if e.status() == Some(404) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// `DELETE /api/vector-store/documents/{id}`
+    pub async fn delete_document(&self, id: i64) -> Result<DeleteDocumentResponse> {
+        let request = self.request(Method::DELETE, &format!("/api/vector-store/documents/{}", id), None, Bytes::new());
+        self.json(request).await
+    }
+
+    // -----------------------------------------------------------
+    // Search
+    // -----------------------------------------------------------
+
+    /// `POST /api/vector-store/search`
+    pub async fn search(&self, request: &SearchRequest) -> Result<SearchResponse> {
+        self.post("/api/vector-store/search", request).await
+    }
+
+    /// `POST /api/vector-store/search/enhanced`
+    pub async fn enhanced_search(&self, request: &EnhancedSearchRequest) -> Result<SearchResponse> {
+        self.post("/api/vector-store/search/enhanced", request).await
+    }
+
+    /// `GET /api/search/universal`: documents, captured conversations and
+    /// files matching `query`, at most `limit` per group.
+    pub async fn universal_search(&self, query: &str, limit: usize) -> Result<UniversalSearchResponse> {
+        self.get(&format!("/api/search/universal?q={}&limit={}", encode_segment(query), limit))
+            .await
+    }
+
+    // -----------------------------------------------------------
+    // Files and indexing
+    // -----------------------------------------------------------
+
+    /// `POST /api/files/upload` with one file. The file is queued for
+    /// indexing; poll its job with [`Client::wait_for_job`].
+    pub async fn upload_file(&self, filename: &str, content: impl Into<Bytes>) -> Result<UploadResponse> {
+        let (content_type, body) = multipart_body(filename, &content.into());
+        let request = self.request(Method::POST, "/api/files/upload", Some(&content_type), body);
+        self.json(request).await
+    }
+
+    /// `POST /api/files/upload/init`: start a resumable upload, then send
+    /// each `chunkSize` slice with [`Client::upload_chunk`].
+    pub async fn init_upload(&self, request: &UploadInitRequest) -> Result<UploadSession> {
+        self.post("/api/files/upload/init", request).await
+    }
+
+    /// `PUT /api/files/upload/{id}/chunk/{index}`, with the chunk's SHA-256.
+    /// A 422 `Error::Api` means the chunk was damaged on the way; send it again.
+    pub async fn upload_chunk(&self, upload_id: &str, index: u64, chunk: impl Into<Bytes>) -> Result<UploadSession> {
+        use sha2::Digest;
+
+        let chunk = chunk.into();
+        let path = format!("/api/files/upload/{}/chunk/{}", encode_segment(upload_id), index);
+        let mut request = self.request(Method::PUT, &path, Some("application/octet-stream"), Bytes::new());
+        let sha256 = hex::encode(sha2::Sha256::digest(&chunk));
+        request
+            .headers_mut()
+            .insert(CHUNK_SHA256_HEADER, header::HeaderValue::from_str(&sha256).expect("hex is a valid header"));
+        *request.body_mut() = chunk;
+        self.json(request).await
+    }
+
+    /// `GET /api/files/upload/{id}`: the chunks received so far, to resume from.
+    pub async fn upload_status(&self, upload_id: &str) -> Result<UploadSession> {
+        self.get(&format!("/api/files/upload/{}", encode_segment(upload_id))).await
+    }
+
+    /// `POST /api/files/upload/{id}/complete`: assemble the file and queue it
+    /// for indexing. A 409 `Error::Api` lists the chunks still missing.
+    pub async fn complete_upload(&self, upload_id: &str) -> Result<UploadedFile> {
+        let path = format!("/api/files/upload/{}/complete", encode_segment(upload_id));
+        self.json(self.request(Method::POST, &path, None, Bytes::new())).await
+    }
+
+    /// `GET /api/indexing/status`
+    pub async fn indexing_status(&self) -> Result<IndexingStatusResponse> {
+        self.get("/api/indexing/status").await
+    }
+
+    /// `GET /api/indexing/jobs`
+    pub async fn indexing_jobs(&self) -> Result<IndexingJobsResponse> {
+        self.get("/api/indexing/jobs").await
+    }
+
+    /// `GET /api/indexing/jobs/{id}`
+    pub async fn indexing_job(&self, job_id: &str) -> Result<IndexingJob> {
+        self.get(&format!("/api/indexing/jobs/{}", encode_segment(job_id))).await
+    }
+
+    /// `POST /api/indexing/jobs/{id}/retry`: queue a failed job again.
+    pub async fn retry_indexing_job(&self, job_id: &str) -> Result<IndexingJob> {
+        let path = format!("/api/indexing/jobs/{}/retry", encode_segment(job_id));
+        self.json(self.request(Method::POST, &path, None, Bytes::new())).await
+    }
+
+    /// Poll a job every `interval` until it completes or fails. The
+    /// finished job is returned either way; check its `status`.
+    pub async fn wait_for_job(&self, job_id: &str, interval: Duration, timeout: Duration) -> Result<IndexingJob> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let job = self.indexing_job(job_id).await?;
+            if job.status.is_finished() {
+                return Ok(job);
+            }
+            if tokio::time::Instant::now() + interval > deadline {
+                return Err(Error::Timeout(job_id.to_string()));
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    // -----------------------------------------------------------
+    // Chat
+    // -----------------------------------------------------------
+
+    /// `GET /api/chat/status`
+    pub async fn chat_status(&self) -> Result<ChatStatus> {
+        self.get("/api/chat/status").await
+    }
+
+    /// `POST /api/chat` — the whole answer at once.
+    pub async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        self.post("/api/chat", request).await
+    }
+
+    /// `POST /api/chat/stream` — context, tokens, then `Done` (or `Error`)
+    /// as the server sends them.
+    pub async fn chat_stream(&self, request: &ChatRequest) -> Result<ChatStream> {
+        let body = serde_json::to_vec(request).map_err(|e| Error::Decode(e.to_string()))?;
+        let request = self.request(Method::POST, "/api/chat/stream", Some("application/json"), body.into());
+        let response = self.send(request).await?;
+        Ok(Box::pin(sse::events(response.into_body())))
+    }
+
+    // -----------------------------------------------------------
+    // Connectors
+    // -----------------------------------------------------------
+
+    /// `GET /api/connectors/{id}/status` — the connector's last sync run.
+    pub async fn connector_status(&self, connector_id: &str) -> Result<RunStatus> {
+        self.get(&format!("/api/connectors/{}/status", encode_segment(connector_id)))
+            .await
+    }
+
+    /// `GET /api/browser-connector/status`
+    pub async fn browser_status(&self) -> Result<BrowserStatus> {
+        self.get("/api/browser-connector/status").await
+    }
+
+    // -----------------------------------------------------------
+    // Plumbing
+    // -----------------------------------------------------------
+
+    async fn get<R: DeserializeOwned>(&self, path: &str) -> Result<R> {
+        self.json(self.request(Method::GET, path, None, Bytes::new())).await
+    }
+
+    async fn post<B: Serialize, R: DeserializeOwned>(&self, path: &str, body: &B) -> Result<R> {
+        let body = serde_json::to_vec(body).map_err(|e| Error::Decode(e.to_string()))?;
+        self.json(self.request(Method::POST, path, Some("application/json"), body.into()))
+            .await
+    }
+
+    fn request(&self, method: Method, path: &str, content_type: Option<&str>, body: Bytes) -> http::Request<Bytes> {
+        let mut request = http::Request::new(body);
+        *request.method_mut() = method;
+        // Paths are built from constants and encoded segments
+        *request.uri_mut() = format!("{}{}", self.base_url, path).parse().expect("valid request URI");
+        let headers = request.headers_mut();
+        headers.insert(header::ACCEPT, header::HeaderValue::from_static("application/json"));
+        if let Some(content_type) = content_type.and_then(|c| header::HeaderValue::from_str(c).ok()) {
+            headers.insert(header::CONTENT_TYPE, content_type);
+        }
+        if let Some(profile) = self.profile.as_deref().and_then(|p| header::HeaderValue::from_str(p).ok()) {
+            headers.insert(PROFILE_HEADER, profile);
+        }
+        request
+    }
+
+    /// Send a request; non-2xx responses become `Error::Api`.
+    async fn send(&self, request: http::Request<Bytes>) -> Result<http::Response<BodyStream>> {
+        let response = self.transport.send(request).await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = read_body(response.into_body()).await?;
+        Err(Error::from_response(status, &body))
+    }
+
+    async fn json<R: DeserializeOwned>(&self, request: http::Request<Bytes>) -> Result<R> {
+        let response = self.send(request).await?;
+        let body = read_body(response.into_body()).await?;
+        serde_json::from_slice(&body).map_err(|e| Error::Decode(e.to_string()))
+    }
+}
+
+async fn read_body(body: BodyStream) -> Result<Vec<u8>> {
+    body.try_fold(Vec::new(), |mut all, chunk| async move {
+        all.extend_from_slice(&chunk);
+        Ok(all)
+    })
+    .await
+}
+
+/// Percent-encode one path segment.
+fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// A `multipart/form-data` body with a single `file` field. Returns the
+/// content type (with its boundary) and the body.
+fn multipart_body(filename: &str, content: &[u8]) -> (String, Bytes) {
+    let mut boundary = String::from("mindsage-client-boundary");
+    while content.windows(boundary.len()).any(|w| w == boundary.as_bytes()) {
+        boundary.push('x');
+    }
+    let filename: String = filename
+        .chars()
+        .map(|c| if matches!(c, '"' | '\r' | '\n') { '_' } else { c })
+        .collect();
+
+    let mut body = Vec::with_capacity(content.len() + 256);
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+            boundary, filename
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(content);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    (format!("multipart/form-data; boundary={}", boundary), body.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_segment() {
+        assert_eq!(encode_segment("job-1_a.b~"), "job-1_a.b~");
+        assert_eq!(encode_segment("a/b c"), "a%2Fb%20c");
+    }
+
+    #[test]
+    fn test_multipart_boundary_avoids_content() {
+        let (content_type, body) = multipart_body("we\"ird.txt", b"x mindsage-client-boundary y");
+        assert_eq!(content_type, "multipart/form-data; boundary=mindsage-client-boundaryx");
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.starts_with("--mindsage-client-boundaryx\r\n"));
+        assert!(body.contains("filename=\"we_ird.txt\""));
+        assert!(body.ends_with("\r\n--mindsage-client-boundaryx--\r\n"));
+    }
+}
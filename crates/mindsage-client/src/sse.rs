@@ -0,0 +1,123 @@
+//! Reads the chat stream: server-sent events whose `data` is a JSON
+//! `StreamEvent`, ended by `data: [DONE]`.
+
+use bytes::{Buf, BytesMut};
+use futures::{Stream, StreamExt};
+use mindsage_api_types::StreamEvent;
+
+use crate::error::{Error, Result};
+use crate::transport::BodyStream;
+
+/// Marker the server sends after the last event.
+const DONE: &str = "[DONE]";
+
+struct State {
+    body: BodyStream,
+    buffer: BytesMut,
+    finished: bool,
+}
+
+/// Parse a response body into chat events. The stream ends at `[DONE]` or
+/// when the body ends.
+pub fn events(body: BodyStream) -> impl Stream<Item = Result<StreamEvent>> + Send {
+    let state = State {
+        body,
+        buffer: BytesMut::new(),
+        finished: false,
+    };
+    futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if state.finished {
+                return None;
+            }
+            if let Some(frame) = next_frame(&mut state.buffer) {
+                match frame_data(&frame) {
+                    Some(data) if data == DONE => return None,
+                    Some(data) => {
+                        let event = serde_json::from_str(&data).map_err(|e| Error::Decode(e.to_string()));
+                        return Some((event, state));
+                    }
+                    // Comments and keep-alives
+                    None => continue,
+                }
+            }
+            match state.body.next().await {
+                Some(Ok(chunk)) => state.buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    state.finished = true;
+                    return Some((Err(e), state));
+                }
+                None => {
+                    // A final frame without the trailing blank line
+                    state.finished = true;
+                    let rest = std::mem::take(&mut state.buffer);
+                    let data = frame_data(&String::from_utf8_lossy(&rest)).filter(|d| d != DONE)?;
+                    let event = serde_json::from_str(&data).map_err(|e| Error::Decode(e.to_string()));
+                    return Some((event, state));
+                }
+            }
+        }
+    })
+}
+
+/// Remove and return the first complete frame (terminated by a blank line).
+fn next_frame(buffer: &mut BytesMut) -> Option<String> {
+    let text = String::from_utf8_lossy(buffer);
+    let (end, separator) = [("\r\n\r\n", 4), ("\n\n", 2)]
+        .iter()
+        .filter_map(|(sep, len)| text.find(sep).map(|i| (i, *len)))
+        .min()?;
+    let frame = text[..end].to_string();
+    buffer.advance(end + separator);
+    Some(frame)
+}
+
+/// The `data` lines of a frame, joined with newlines.
+fn frame_data(frame: &str) -> Option<String> {
+    let lines: Vec<&str> = frame
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+
+    fn body(chunks: &[&str]) -> BodyStream {
+        let chunks: Vec<Result<Bytes>> = chunks.iter().map(|c| Ok(Bytes::from(c.to_string()))).collect();
+        Box::pin(futures::stream::iter(chunks))
+    }
+
+    #[tokio::test]
+    async fn test_events_split_across_chunks() {
+        let stream = events(body(&[
+            "data: {\"type\":\"token\",\"con",
+            "tent\":\"Hel\"}\n\n: keep-alive\n\ndata: {\"type\":\"token\",\"content\":\"lo\"}\r\n\r\n",
+            "data: {\"type\":\"done\",\"model\":\"m\",\"tokensUsed\":2,\"duration\":5}\n\ndata: [DONE]\n\n",
+            "data: {\"type\":\"token\",\"content\":\"ignored\"}\n\n",
+        ]));
+        let events: Vec<StreamEvent> = stream.map(|e| e.unwrap()).collect().await;
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0], StreamEvent::Token { content } if content == "Hel"));
+        assert!(matches!(&events[1], StreamEvent::Token { content } if content == "lo"));
+        assert!(matches!(&events[2], StreamEvent::Done { tokens_used: 2, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_unterminated_last_frame_and_bad_json() {
+        let stream = events(body(&["data: not json\n\ndata: {\"type\":\"error\",\"error\":\"boom\"}"]));
+        let events: Vec<Result<StreamEvent>> = stream.collect().await;
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], Err(Error::Decode(_))));
+        assert!(matches!(&events[1], Ok(StreamEvent::Error { error }) if error == "boom"));
+    }
+}
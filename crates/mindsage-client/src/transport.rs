@@ -0,0 +1,97 @@
+//! How requests reach the server: over HTTP with reqwest, or (with the
+//! `tower` feature) by calling an in-process service such as the server's
+//! router directly.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use bytes::Bytes;
+use futures::{Stream, TryStreamExt};
+
+use crate::error::{Error, Result};
+
+/// A response body as a stream of chunks, so SSE responses can be read as
+/// they arrive.
+pub type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>;
+
+/// Sends one request and returns the response with a streaming body.
+pub trait Transport: Send + Sync {
+    fn send(&self, request: http::Request<Bytes>) -> impl Future<Output = Result<http::Response<BodyStream>>> + Send;
+}
+
+/// HTTP transport backed by a `reqwest::Client`.
+#[derive(Debug, Clone, Default)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+}
+
+impl HttpTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Transport for HttpTransport {
+    async fn send(&self, request: http::Request<Bytes>) -> Result<http::Response<BodyStream>> {
+        let request = reqwest::Request::try_from(request).map_err(|e| Error::Transport(e.to_string()))?;
+        let response = self
+            .client
+            .execute(request)
+            .await
+            .map_err(|e| Error::Transport(e.to_string()))?;
+
+        let mut builder = http::Response::builder().status(response.status());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = response.headers().clone();
+        }
+        let body: BodyStream = Box::pin(response.bytes_stream().map_err(|e| Error::Transport(e.to_string())));
+        builder.body(body).map_err(|e| Error::Transport(e.to_string()))
+    }
+}
+
+/// In-process transport: calls a tower service (an axum `Router`, say)
+/// without a socket.
+#[cfg(feature = "tower")]
+#[derive(Debug, Clone)]
+pub struct ServiceTransport<S> {
+    service: S,
+}
+
+#[cfg(feature = "tower")]
+impl<S> ServiceTransport<S> {
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<S, B> Transport for ServiceTransport<S>
+where
+    S: tower::Service<http::Request<http_body_util::Full<Bytes>>, Response = http::Response<B>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Future: Send,
+    S::Error: std::fmt::Display,
+    B: http_body::Body<Data = Bytes> + Send + 'static,
+    B::Error: std::fmt::Display,
+{
+    async fn send(&self, request: http::Request<Bytes>) -> Result<http::Response<BodyStream>> {
+        use http_body_util::BodyExt;
+        use tower::ServiceExt;
+
+        let (parts, body) = request.into_parts();
+        let request = http::Request::from_parts(parts, http_body_util::Full::new(body));
+        let response = self
+            .service
+            .clone()
+            .oneshot(request)
+            .await
+            .map_err(|e| Error::Transport(e.to_string()))?;
+
+        let (parts, body) = response.into_parts();
+        let body: BodyStream = Box::pin(body.into_data_stream().map_err(|e| Error::Transport(e.to_string())));
+        Ok(http::Response::from_parts(parts, body))
+    }
+}
@@ -3,17 +3,23 @@
 use std::collections::HashMap;
 use std::io::Read;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use serde_json::Value;
-use tracing::{info, warn};
+use tracing::info;
 
-use crate::types::ImportResult;
+use crate::import::{self, EntryErrors};
+use crate::types::{ImportProgress, ImportResult, IndexDocument};
 
 /// Process a ChatGPT export ZIP file.
 /// Extracts conversations.json, saves individual conversation files to exports_dir.
+/// `on_progress` is called after each archive entry and each conversation;
+/// setting `cancel` stops the import before the next one.
 pub fn process_chatgpt_export(
     zip_path: &Path,
     exports_dir: &Path,
+    mut on_progress: impl FnMut(&ImportProgress),
+    cancel: &AtomicBool,
 ) -> ImportResult {
     std::fs::create_dir_all(exports_dir).ok();
 
@@ -43,100 +49,133 @@ pub fn process_chatgpt_export(
 
     let mut conversation_count = 0;
     let mut total_messages = 0;
-
-    // Find and extract conversations.json
-    let conversations_data = {
-        let mut data = None;
-        for i in 0..archive.len() {
-            if let Ok(mut entry) = archive.by_index(i) {
-                let name = entry.name().to_string();
-                if name == "conversations.json" || name.ends_with("/conversations.json") {
-                    let mut buf = String::new();
-                    if entry.read_to_string(&mut buf).is_ok() {
-                        data = Some(buf);
-                    }
-                    break;
-                }
-            }
+    let mut errors = EntryErrors::default();
+    let mut cancelled = false;
+    let mut conversations_seen = false;
+    let mut user_seen = false;
+
+    // conversations.json holds every conversation; user.json the profile
+    let total_entries = archive.len();
+    for i in 0..total_entries {
+        if cancel.load(Ordering::Relaxed) {
+            cancelled = true;
+            break;
         }
-        data
-    };
 
-    if let Some(raw) = conversations_data {
-        if let Ok(conversations) = serde_json::from_str::<Vec<Value>>(&raw) {
-            for conv in &conversations {
-                let conv_id = conv
-                    .get("id")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("unknown");
-                let title = conv
-                    .get("title")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("Untitled");
-
-                // Extract messages from mapping tree
-                let messages = extract_messages(conv);
-                if messages.is_empty() {
-                    continue;
+        let name = archive.name_for_index(i).unwrap_or_default().to_string();
+        let is_conversations = !conversations_seen && is_conversations_entry(&name);
+        let is_user = !user_seen && (name == "user.json" || name.ends_with("/user.json"));
+        if is_conversations || is_user {
+            let mut buf = String::new();
+            let read = match archive.by_index(i) {
+                Ok(mut entry) => entry.read_to_string(&mut buf).map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            match read {
+                Err(e) => errors.record(&name, e),
+                Ok(_) if is_user => {
+                    user_seen = true;
+                    if let Err(e) = std::fs::write(exports_dir.join("user_profile.json"), buf) {
+                        errors.record(&name, format!("writing user_profile.json: {}", e));
+                    }
                 }
-
-                total_messages += messages.len();
-                conversation_count += 1;
-
-                // Build output document
-                let doc = serde_json::json!({
-                    "id": conv_id,
-                    "title": title,
-                    "create_time": conv.get("create_time"),
-                    "update_time": conv.get("update_time"),
-                    "messages": messages,
-                });
-
-                // Sanitize title for filename
-                let safe_title: String = title
-                    .chars()
-                    .map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' { c } else { '_' })
-                    .take(50)
-                    .collect();
-                let filename = format!("chatgpt_{}_{}.json", conv_id, safe_title.trim());
-                let out_path = exports_dir.join(&filename);
-
-                if let Ok(json) = serde_json::to_string_pretty(&doc) {
-                    if let Err(e) = std::fs::write(&out_path, json) {
-                        warn!("Failed to write {}: {}", filename, e);
+                Ok(_) => {
+                    conversations_seen = true;
+                    let conversations = match serde_json::from_str::<Vec<Value>>(&buf) {
+                        Ok(conversations) => conversations,
+                        Err(e) => {
+                            errors.record(&name, format!("invalid JSON: {}", e));
+                            Vec::new()
+                        }
+                    };
+                    for conv in &conversations {
+                        if cancel.load(Ordering::Relaxed) {
+                            cancelled = true;
+                            break;
+                        }
+                        if let Some(messages) = write_conversation(conv, exports_dir, &name, &mut errors) {
+                            total_messages += messages;
+                            conversation_count += 1;
+                        }
+                        // The one big entry reports each conversation
+                        on_progress(&ImportProgress {
+                            entries_scanned: i,
+                            total_entries,
+                            current_entry: name.clone(),
+                            items_emitted: conversation_count,
+                        });
                     }
                 }
             }
         }
-    }
 
-    // Also extract user.json if present
-    for i in 0..archive.len() {
-        if let Ok(mut entry) = archive.by_index(i) {
-            let name = entry.name().to_string();
-            if name == "user.json" || name.ends_with("/user.json") {
-                let mut buf = String::new();
-                if entry.read_to_string(&mut buf).is_ok() {
-                    let _ = std::fs::write(exports_dir.join("user_profile.json"), buf);
-                }
-                break;
-            }
-        }
+        on_progress(&ImportProgress {
+            entries_scanned: i + 1,
+            total_entries,
+            current_entry: name,
+            items_emitted: conversation_count,
+        });
     }
 
     info!(
-        "ChatGPT import: {} conversations, {} messages",
-        conversation_count, total_messages
+        "ChatGPT import{}: {} conversations, {} messages",
+        if cancelled { " cancelled" } else { "" },
+        conversation_count,
+        total_messages
     );
 
-    ImportResult {
-        success: true,
-        item_count: conversation_count,
-        error: None,
-        details: Some(serde_json::json!({
-            "conversationCount": conversation_count,
-            "messageCount": total_messages,
-        })),
+    let details = serde_json::json!({
+        "conversationCount": conversation_count,
+        "messageCount": total_messages,
+    });
+    import::finish(conversation_count, details, errors, cancelled)
+}
+
+/// Write one conversation to its export file. Returns its message count, or
+/// `None` when it has no messages or could not be written.
+fn write_conversation(conv: &Value, exports_dir: &Path, entry: &str, errors: &mut EntryErrors) -> Option<usize> {
+    let conv_id = conv
+        .get("id")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+    let title = conv
+        .get("title")
+        .and_then(|v| v.as_str())
+        .unwrap_or("Untitled");
+
+    // Extract messages from mapping tree
+    let messages = extract_messages(conv);
+    if messages.is_empty() {
+        return None;
+    }
+    let message_count = messages.len();
+
+    // Build output document
+    let doc = serde_json::json!({
+        "id": conv_id,
+        "title": title,
+        "create_time": conv.get("create_time"),
+        "update_time": conv.get("update_time"),
+        "messages": messages,
+    });
+
+    // Sanitize title for filename
+    let safe_title: String = title
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' { c } else { '_' })
+        .take(50)
+        .collect();
+    let filename = format!("chatgpt_{}_{}.json", conv_id, safe_title.trim());
+
+    let written = serde_json::to_string_pretty(&doc)
+        .map_err(std::io::Error::from)
+        .and_then(|json| std::fs::write(exports_dir.join(&filename), json));
+    match written {
+        Ok(()) => Some(message_count),
+        Err(e) => {
+            errors.record(entry, format!("writing {}: {}", filename, e));
+            None
+        }
     }
 }
 
@@ -224,6 +263,11 @@ fn extract_content_text(content: Option<&Value>) -> String {
         .to_string()
 }
 
+/// Whether an archive entry is the export's `conversations.json`.
+pub(crate) fn is_conversations_entry(name: &str) -> bool {
+    name == "conversations.json" || name.ends_with("/conversations.json")
+}
+
 /// Build a minimal ChatGPT-format ZIP in memory for testing.
 #[cfg(test)]
 fn build_test_zip(conversations: &serde_json::Value) -> Vec<u8> {
@@ -237,11 +281,9 @@ fn build_test_zip(conversations: &serde_json::Value) -> Vec<u8> {
     zip.finish().unwrap().into_inner()
 }
 
-/// Build indexable documents from ChatGPT export files.
-/// Returns (text, metadata) pairs ready for vector store indexing.
-pub fn build_index_documents(
-    exports_dir: &Path,
-) -> Vec<(String, serde_json::Value)> {
+/// Build indexable documents from ChatGPT export files, dated by the
+/// conversation's `create_time`.
+pub fn build_index_documents(exports_dir: &Path) -> Vec<IndexDocument> {
     let mut documents = Vec::new();
 
     let entries = match std::fs::read_dir(exports_dir) {
@@ -303,7 +345,18 @@ pub fn build_index_documents(
                 "exportFile": name,
             });
 
-            documents.push((text, metadata));
+            // create_time is fractional seconds since the epoch
+            let created_at = conv
+                .get("create_time")
+                .and_then(|t| t.as_f64())
+                .map(|secs| (secs * 1000.0) as i64);
+
+            documents.push(IndexDocument {
+                text,
+                metadata,
+                created_at,
+                external_id: conv.get("id").and_then(|v| v.as_str()).map(str::to_string),
+            });
         }
     }
 
@@ -348,7 +401,7 @@ mod tests {
         let zip_path = dir.path().join("export.zip");
         std::fs::write(&zip_path, zip_data).unwrap();
 
-        let result = process_chatgpt_export(&zip_path, &exports_dir);
+        let result = process_chatgpt_export(&zip_path, &exports_dir, |_| {}, &AtomicBool::new(false));
         assert!(result.success);
         assert_eq!(result.item_count, 1);
 
@@ -369,7 +422,7 @@ mod tests {
         let zip_path = dir.path().join("empty.zip");
         std::fs::write(&zip_path, data).unwrap();
 
-        let result = process_chatgpt_export(&zip_path, &exports_dir);
+        let result = process_chatgpt_export(&zip_path, &exports_dir, |_| {}, &AtomicBool::new(false));
         assert!(result.success);
         assert_eq!(result.item_count, 0);
     }
@@ -380,7 +433,7 @@ mod tests {
         let zip_path = dir.path().join("bad.zip");
         std::fs::write(&zip_path, b"not a zip").unwrap();
 
-        let result = process_chatgpt_export(&zip_path, &dir.path().join("exports"));
+        let result = process_chatgpt_export(&zip_path, &dir.path().join("exports"), |_| {}, &AtomicBool::new(false));
         assert!(!result.success);
         assert!(result.error.is_some());
     }
@@ -395,6 +448,7 @@ mod tests {
         let doc = serde_json::json!({
             "id": "conv-1",
             "title": "My Chat",
+            "create_time": 1700000000.5,
             "messages": [
                 { "role": "user", "content": "What is Rust?" },
                 { "role": "assistant", "content": "A systems programming language." }
@@ -408,8 +462,9 @@ mod tests {
 
         let docs = build_index_documents(&exports_dir);
         assert_eq!(docs.len(), 1);
-        assert!(docs[0].0.contains("What is Rust?"));
-        assert_eq!(docs[0].1["source"], "chatgpt");
+        assert!(docs[0].text.contains("What is Rust?"));
+        assert_eq!(docs[0].metadata["source"], "chatgpt");
+        assert_eq!(docs[0].created_at, Some(1_700_000_000_500));
     }
 
     #[test]
@@ -447,8 +502,51 @@ mod tests {
         let zip_path = dir.path().join("sys.zip");
         std::fs::write(&zip_path, zip_data).unwrap();
 
-        let result = process_chatgpt_export(&zip_path, &dir.path().join("exports"));
+        let result = process_chatgpt_export(&zip_path, &dir.path().join("exports"), |_| {}, &AtomicBool::new(false));
         assert!(result.success);
         assert_eq!(result.item_count, 0); // system messages filtered
     }
+
+    #[test]
+    fn test_progress_per_conversation_and_cancel() {
+        let conversation = |id: &str| {
+            serde_json::json!({
+                "id": id,
+                "title": id,
+                "mapping": {
+                    "node": {
+                        "message": {
+                            "author": { "role": "user" },
+                            "content": { "parts": [format!("Question {}", id)] },
+                            "create_time": 1.0
+                        }
+                    }
+                }
+            })
+        };
+        let conversations = serde_json::json!([conversation("a"), conversation("b"), conversation("c")]);
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("export.zip");
+        std::fs::write(&zip_path, build_test_zip(&conversations)).unwrap();
+
+        let mut reports = Vec::new();
+        let result = process_chatgpt_export(&zip_path, &dir.path().join("all"), |p| reports.push(p.clone()), &AtomicBool::new(false));
+        assert!(result.success);
+        assert_eq!(result.details.unwrap()["errorCount"], 0);
+        let emitted: Vec<usize> = reports.iter().map(|p| p.items_emitted).collect();
+        assert_eq!(emitted, vec![1, 2, 3, 3]);
+        let last = reports.last().unwrap();
+        assert_eq!((last.entries_scanned, last.total_entries), (1, 1));
+        assert_eq!(last.current_entry, "conversations.json");
+
+        // Cancelled after the first conversation: the rest is not written
+        let cancel = AtomicBool::new(false);
+        let exports_dir = dir.path().join("cancelled");
+        let result = process_chatgpt_export(&zip_path, &exports_dir, |_| cancel.store(true, Ordering::Relaxed), &cancel);
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("Import cancelled"));
+        assert_eq!(result.item_count, 1);
+        assert_eq!(result.details.unwrap()["cancelled"], true);
+        assert_eq!(build_index_documents(&exports_dir).len(), 1);
+    }
 }
@@ -1,12 +1,18 @@
 //! Facebook export ZIP processor.
 
+use std::collections::HashMap;
 use std::io::Read;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use serde_json::Value;
 use tracing::info;
 
-use crate::types::{ImportResult, MediaCounts, PendingMediaFile, PendingMediaRegistry};
+use crate::import::{self, EntryErrors};
+use crate::media::{self, ExifData};
+use crate::types::{
+    ImportProgress, ImportResult, IndexDocument, MediaCounts, PendingMediaFile, PendingMediaRegistry,
+};
 
 /// Media file extensions.
 const PHOTO_EXTS: &[&str] = &[
@@ -15,13 +21,18 @@ const PHOTO_EXTS: &[&str] = &[
 const VIDEO_EXTS: &[&str] = &["mp4", "mov", "avi", "mkv", "webm", "m4v"];
 const AUDIO_EXTS: &[&str] = &["mp3", "m4a", "wav", "aac", "ogg", "flac"];
 
-/// Process a Facebook export ZIP file.
+/// Process a Facebook export ZIP file. `on_progress` is called after each
+/// archive entry; setting `cancel` stops the import before the next one.
+/// Entries that cannot be read, parsed or written are listed in the result's
+/// details and skipped.
 pub fn process_facebook_export(
     zip_path: &Path,
     exports_dir: &Path,
+    mut on_progress: impl FnMut(&ImportProgress),
+    cancel: &AtomicBool,
 ) -> ImportResult {
     std::fs::create_dir_all(exports_dir).ok();
-    let media_dir = exports_dir.join("pending-media");
+    let media_dir = exports_dir.join(media::MEDIA_DIR);
     std::fs::create_dir_all(&media_dir).ok();
 
     let file = match std::fs::File::open(zip_path) {
@@ -52,87 +63,95 @@ pub fn process_facebook_export(
     let mut comment_count = 0;
     let mut message_count = 0;
     let mut media_files: Vec<PendingMediaFile> = Vec::new();
+    let mut media_exif: Vec<Option<ExifData>> = Vec::new();
+    let mut sidecars = HashMap::new();
+    let mut errors = EntryErrors::default();
+    let mut cancelled = false;
+
+    // One pass: JSON entries are handled as they are read, media is
+    // extracted; sidecar descriptions are matched to media at the end
+    let total_entries = archive.len();
+    for i in 0..total_entries {
+        if cancel.load(Ordering::Relaxed) {
+            cancelled = true;
+            break;
+        }
 
-    // Collect all entries (we need to process them in multiple passes)
-    let mut json_entries: Vec<(String, String)> = Vec::new();
-
-    for i in 0..archive.len() {
-        if let Ok(mut entry) = archive.by_index(i) {
-            let name = entry.name().to_string();
+        let name = archive.name_for_index(i).unwrap_or_default().to_string();
+        let mut entry = match archive.by_index(i) {
+            Ok(entry) => entry,
+            Err(e) => {
+                errors.record(&name, e);
+                continue;
+            }
+        };
 
-            if name.ends_with(".json") {
-                let mut buf = String::new();
-                if entry.read_to_string(&mut buf).is_ok() {
+        if name.ends_with(".json") {
+            let mut buf = String::new();
+            match entry.read_to_string(&mut buf) {
+                Ok(_) => {
                     // Fix Facebook's unicode encoding (UTF-8 encoded as Latin-1)
-                    let fixed = fix_facebook_unicode(&buf);
-                    json_entries.push((name, fixed));
-                }
-            } else if is_media_file(&name) {
-                // Extract media to pending-media directory
-                let media_filename = Path::new(&name)
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("unknown")
-                    .to_string();
-                let dest = media_dir.join(&media_filename);
-
-                let mut data = Vec::new();
-                if entry.read_to_end(&mut data).is_ok() {
-                    let size = data.len() as u64;
-                    if std::fs::write(&dest, &data).is_ok() {
-                        let ext = Path::new(&name)
-                            .extension()
-                            .and_then(|e| e.to_str())
-                            .unwrap_or("")
-                            .to_lowercase();
-                        let media_type = classify_media_type(&ext);
-
-                        media_files.push(PendingMediaFile {
-                            original_path: name.clone(),
-                            filename: media_filename,
-                            media_type,
-                            extension: ext,
-                            size,
-                            context: None,
-                            stored_at: chrono::Utc::now().to_rfc3339(),
-                            stored_path: dest.to_string_lossy().to_string(),
-                        });
-                    }
+                    let data = fix_facebook_unicode(&buf);
+                    let counts = process_json_entry(&name, &data, exports_dir, &mut sidecars, &mut errors);
+                    post_count += counts.0;
+                    comment_count += counts.1;
+                    message_count += counts.2;
                 }
+                Err(e) => errors.record(&name, e),
             }
-        }
-    }
-
-    // Process JSON entries
-    for (name, data) in &json_entries {
-        let lower = name.to_lowercase();
-
-        // Posts
-        if lower.contains("posts/your_posts") {
-            if let Ok(val) = serde_json::from_str::<Value>(data) {
-                let count = process_posts(&val, exports_dir);
-                post_count += count;
+        } else if is_media_file(&name) {
+            // Extract media to pending-media directory
+            let media_filename = Path::new(&name)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown")
+                .to_string();
+            let dest = media_dir.join(&media_filename);
+
+            let mut data = Vec::new();
+            let written = entry
+                .read_to_end(&mut data)
+                .and_then(|_| std::fs::write(&dest, &data));
+            match written {
+                Ok(()) => {
+                    let ext = Path::new(&name)
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .unwrap_or("")
+                        .to_lowercase();
+                    let media_type = classify_media_type(&ext);
+                    media_exif.push(media::read_exif(&data));
+
+                    media_files.push(PendingMediaFile {
+                        original_path: name.clone(),
+                        filename: media_filename,
+                        media_type,
+                        extension: ext,
+                        size: data.len() as u64,
+                        context: None,
+                        stored_at: chrono::Utc::now().to_rfc3339(),
+                        stored_path: dest.to_string_lossy().to_string(),
+                        info: None,
+                        document_id: None,
+                    });
+                }
+                Err(e) => errors.record(&name, e),
             }
         }
 
-        // Comments
-        if lower.contains("comments/") && lower.ends_with(".json") {
-            if let Ok(val) = serde_json::from_str::<Value>(data) {
-                let count = process_comments(&val, exports_dir);
-                comment_count += count;
-            }
-        }
+        on_progress(&ImportProgress {
+            entries_scanned: i + 1,
+            total_entries,
+            current_entry: name,
+            items_emitted: post_count + comment_count + message_count,
+        });
+    }
 
-        // Messages
-        if lower.contains("messages/inbox/") && lower.contains("message_") {
-            if let Ok(val) = serde_json::from_str::<Value>(data) {
-                let count = process_messages(&val, &name, exports_dir);
-                message_count += count;
-            }
-        }
+    for (file, exif) in media_files.iter_mut().zip(&media_exif) {
+        file.info = media::media_info(exif.as_ref(), media::find_sidecar(&sidecars, &file.original_path));
     }
 
-    // Save media registry
+    // Save media registry, also after a cancel so extracted files are known
     if !media_files.is_empty() {
         let registry = PendingMediaRegistry {
             files: media_files.clone(),
@@ -154,34 +173,109 @@ pub fn process_facebook_export(
             },
         };
 
-        if let Ok(json) = serde_json::to_string_pretty(&registry) {
-            let _ = std::fs::write(media_dir.join(".registry.json"), json);
+        if let Err(e) = media::save_registry(exports_dir, &registry) {
+            errors.record("media registry", e);
         }
     }
 
     let item_count = post_count + comment_count + message_count;
     info!(
-        "Facebook import: {} posts, {} comments, {} message threads, {} media files",
+        "Facebook import{}: {} posts, {} comments, {} message threads, {} media files",
+        if cancelled { " cancelled" } else { "" },
         post_count,
         comment_count,
         message_count,
         media_files.len()
     );
 
-    ImportResult {
-        success: true,
-        item_count,
-        error: None,
-        details: Some(serde_json::json!({
-            "postCount": post_count,
-            "commentCount": comment_count,
-            "messageCount": message_count,
-            "mediaCount": media_files.len(),
-        })),
+    let details = serde_json::json!({
+        "postCount": post_count,
+        "commentCount": comment_count,
+        "messageCount": message_count,
+        "mediaCount": media_files.len(),
+    });
+    import::finish(item_count, details, errors, cancelled)
+}
+
+/// Handle one JSON entry of the export: collect media sidecars and write
+/// its posts, comments or message thread. Returns the (posts, comments,
+/// threads) written.
+fn process_json_entry(
+    name: &str,
+    data: &str,
+    exports_dir: &Path,
+    sidecars: &mut HashMap<String, media::Sidecar>,
+    errors: &mut EntryErrors,
+) -> (usize, usize, usize) {
+    let JsonEntryKind {
+        posts: is_posts,
+        comments: is_comments,
+        messages: is_messages,
+    } = json_entry_kind(name);
+    // Descriptions and albums of media files, from any JSON naming them
+    let has_media = data.contains("\"uri\"");
+    if !(is_posts || is_comments || is_messages || has_media) {
+        return (0, 0, 0);
+    }
+
+    let val = match serde_json::from_str::<Value>(data) {
+        Ok(val) => val,
+        Err(e) => {
+            errors.record(name, format!("invalid JSON: {}", e));
+            return (0, 0, 0);
+        }
+    };
+
+    if has_media {
+        media::collect_sidecars(&val, sidecars);
+    }
+
+    let mut counts = (0, 0, 0);
+    if is_posts {
+        counts.0 = process_posts(&val, name, exports_dir, errors);
+    }
+    if is_comments {
+        counts.1 = process_comments(&val, name, exports_dir, errors);
+    }
+    if is_messages {
+        counts.2 = process_messages(&val, name, exports_dir, errors);
+    }
+    counts
+}
+
+/// What a JSON entry of the export holds, judged by its path.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct JsonEntryKind {
+    pub posts: bool,
+    pub comments: bool,
+    /// One message thread.
+    pub messages: bool,
+}
+
+pub(crate) fn json_entry_kind(name: &str) -> JsonEntryKind {
+    let lower = name.to_lowercase();
+    JsonEntryKind {
+        posts: lower.contains("posts/your_posts"),
+        comments: lower.contains("comments/"),
+        messages: lower.contains("messages/inbox/") && lower.contains("message_"),
+    }
+}
+
+/// Write an export document, recording a failure against its entry.
+fn write_export(exports_dir: &Path, filename: &str, doc: &Value, entry: &str, errors: &mut EntryErrors) -> bool {
+    let written = serde_json::to_string_pretty(doc)
+        .map_err(std::io::Error::from)
+        .and_then(|json| std::fs::write(exports_dir.join(filename), json));
+    match written {
+        Ok(()) => true,
+        Err(e) => {
+            errors.record(entry, format!("writing {}: {}", filename, e));
+            false
+        }
     }
 }
 
-fn process_posts(val: &Value, exports_dir: &Path) -> usize {
+fn process_posts(val: &Value, entry: &str, exports_dir: &Path, errors: &mut EntryErrors) -> usize {
     let mut count = 0;
     if let Some(items) = val.as_array() {
         for item in items {
@@ -209,16 +303,15 @@ fn process_posts(val: &Value, exports_dir: &Path) -> usize {
             });
 
             let filename = format!("facebook_post_{}.json", timestamp);
-            if let Ok(json) = serde_json::to_string_pretty(&doc) {
-                let _ = std::fs::write(exports_dir.join(&filename), json);
+            if write_export(exports_dir, &filename, &doc, entry, errors) {
+                count += 1;
             }
-            count += 1;
         }
     }
     count
 }
 
-fn process_comments(val: &Value, exports_dir: &Path) -> usize {
+fn process_comments(val: &Value, entry: &str, exports_dir: &Path, errors: &mut EntryErrors) -> usize {
     let mut count = 0;
     if let Some(comments) = val.get("comments_v2").and_then(|c| c.as_array()) {
         for comment in comments {
@@ -248,16 +341,15 @@ fn process_comments(val: &Value, exports_dir: &Path) -> usize {
             });
 
             let filename = format!("facebook_comment_{}.json", timestamp);
-            if let Ok(json) = serde_json::to_string_pretty(&doc) {
-                let _ = std::fs::write(exports_dir.join(&filename), json);
+            if write_export(exports_dir, &filename, &doc, entry, errors) {
+                count += 1;
             }
-            count += 1;
         }
     }
     count
 }
 
-fn process_messages(val: &Value, source_name: &str, exports_dir: &Path) -> usize {
+fn process_messages(val: &Value, source_name: &str, exports_dir: &Path, errors: &mut EntryErrors) -> usize {
     let title = val
         .get("title")
         .and_then(|t| t.as_str())
@@ -313,11 +405,8 @@ fn process_messages(val: &Value, source_name: &str, exports_dir: &Path) -> usize
     });
 
     let filename = format!("facebook_messages_{}_{}.json", thread_name, timestamp);
-    if let Ok(json) = serde_json::to_string_pretty(&doc) {
-        let _ = std::fs::write(exports_dir.join(&filename), json);
-    }
-
-    1 // One thread = one document
+    // One thread = one document
+    usize::from(write_export(exports_dir, &filename, &doc, source_name, errors))
 }
 
 /// Fix Facebook's broken Unicode encoding (UTF-8 bytes stored as Latin-1 escapes).
@@ -327,7 +416,7 @@ fn fix_facebook_unicode(text: &str) -> String {
     text.to_string()
 }
 
-fn is_media_file(name: &str) -> bool {
+pub(crate) fn is_media_file(name: &str) -> bool {
     let ext = Path::new(name)
         .extension()
         .and_then(|e| e.to_str())
@@ -352,7 +441,234 @@ fn classify_media_type(ext: &str) -> String {
 
 /// Load pending media registry for a connector.
 pub fn load_media_registry(exports_dir: &Path) -> Option<PendingMediaRegistry> {
-    let registry_path = exports_dir.join("pending-media").join(".registry.json");
-    let data = std::fs::read_to_string(registry_path).ok()?;
-    serde_json::from_str(&data).ok()
+    media::load_registry(exports_dir)
+}
+
+/// Build indexable documents from Facebook export files, dated by the
+/// original post, comment or first-message time.
+pub fn build_index_documents(exports_dir: &Path) -> Vec<IndexDocument> {
+    let mut documents = Vec::new();
+
+    let entries = match std::fs::read_dir(exports_dir) {
+        Ok(e) => e,
+        Err(_) => return documents,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("")
+            .to_string();
+
+        if !name.starts_with("facebook_") || !name.ends_with(".json") {
+            continue;
+        }
+
+        let doc: Value = match std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|d| serde_json::from_str(&d).ok())
+        {
+            Some(d) => d,
+            None => continue,
+        };
+
+        let kind = doc.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        let (text, created_at) = match kind {
+            // Posts and comments carry timestamps in seconds
+            "post" | "comment" => {
+                let text = doc.get("content").and_then(|c| c.as_str()).unwrap_or("").to_string();
+                let secs = doc.get("timestamp").and_then(|t| t.as_i64()).filter(|&t| t > 0);
+                (text, secs.map(|s| s * 1000))
+            }
+            // Message timestamps are already in milliseconds
+            "message_thread" => {
+                let messages = doc.get("messages").and_then(|m| m.as_array());
+                let text = messages
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|m| {
+                        let sender = m.get("sender").and_then(|s| s.as_str()).unwrap_or("");
+                        let content = m.get("content").and_then(|c| c.as_str())?;
+                        (!content.is_empty()).then(|| format!("{}: {}", sender, content))
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let first = messages
+                    .and_then(|m| m.first())
+                    .and_then(|m| m.get("timestamp"))
+                    .and_then(|t| t.as_i64())
+                    .filter(|&t| t > 0);
+                (text, first)
+            }
+            _ => continue,
+        };
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let mut metadata = serde_json::json!({
+            "source": "facebook",
+            "type": kind,
+            "exportFile": name,
+        });
+        if let Some(title) = doc.get("title").and_then(|t| t.as_str()) {
+            metadata["title"] = Value::String(title.to_string());
+        }
+
+        documents.push(IndexDocument {
+            text,
+            metadata,
+            created_at,
+            external_id: None,
+        });
+    }
+
+    documents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_index_documents_keeps_original_times() {
+        let dir = tempfile::tempdir().unwrap();
+        // Posts and comments are written with second timestamps, threads
+        // with millisecond ones
+        let post = serde_json::json!({
+            "type": "post",
+            "timestamp": 1_560_000_000,
+            "content": "Summer trip to the coast",
+        });
+        let thread = serde_json::json!({
+            "type": "message_thread",
+            "title": "Family",
+            "messages": [
+                { "sender": "Ana", "timestamp": 1_600_000_000_123i64, "content": "Dinner on Sunday?" },
+                { "sender": "Ben", "timestamp": 1_600_000_100_000i64, "content": "" },
+            ],
+        });
+        std::fs::write(dir.path().join("facebook_post_1560000000.json"), post.to_string()).unwrap();
+        std::fs::write(
+            dir.path().join("facebook_messages_family_1600000000123.json"),
+            thread.to_string(),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("chatgpt_other.json"), "{}").unwrap();
+
+        let mut docs = build_index_documents(dir.path());
+        docs.sort_by_key(|d| d.created_at);
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[0].created_at, Some(1_560_000_000_000));
+        assert_eq!(docs[0].metadata["type"], "post");
+        assert_eq!(docs[1].created_at, Some(1_600_000_000_123));
+        assert_eq!(docs[1].text, "Ana: Dinner on Sunday?");
+        assert_eq!(docs[1].metadata["title"], "Family");
+    }
+
+    #[test]
+    fn test_media_gets_sidecar_descriptions() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("facebook.zip");
+        let album = serde_json::json!({
+            "name": "Lisbon 2018",
+            "photos": [{
+                "uri": "photos_and_videos/Lisbon2018_abc/123.jpg",
+                "creation_timestamp": 1_531_600_000,
+                "description": "Sunset over the Tagus",
+            }],
+        });
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("facebook-sam/photos_and_videos/album/0.json", options).unwrap();
+        zip.write_all(album.to_string().as_bytes()).unwrap();
+        for name in ["123.jpg", "456.jpg"] {
+            zip.start_file(format!("facebook-sam/photos_and_videos/Lisbon2018_abc/{}", name), options).unwrap();
+            zip.write_all(b"not really a jpeg").unwrap();
+        }
+        zip.finish().unwrap();
+
+        let exports_dir = dir.path().join("exports");
+        let result = process_facebook_export(&zip_path, &exports_dir, |_| {}, &AtomicBool::new(false));
+        assert!(result.success);
+
+        let registry = load_media_registry(&exports_dir).unwrap();
+        let described = registry.files.iter().find(|f| f.filename == "123.jpg").unwrap();
+        let info = described.info.as_ref().unwrap();
+        assert_eq!(info.description.as_deref(), Some("Sunset over the Tagus"));
+        assert_eq!(info.album.as_deref(), Some("Lisbon 2018"));
+        assert_eq!(info.taken_at, Some(1_531_600_000_000));
+        let bare = registry.files.iter().find(|f| f.filename == "456.jpg").unwrap();
+        assert_eq!(bare.info, None);
+    }
+
+    /// A stored (uncompressed) archive whose `broken.jpg` entry fails its
+    /// checksum when read, between two readable post files.
+    fn build_zip_with_unreadable_entry(zip_path: &Path) {
+        use std::io::Write;
+
+        let posts = |text: &str, timestamp: i64| {
+            serde_json::json!([{ "timestamp": timestamp, "data": [{ "post": text }] }]).to_string()
+        };
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("posts/your_posts_1.json", options).unwrap();
+        zip.write_all(posts("First post", 1_560_000_000).as_bytes()).unwrap();
+        zip.start_file("photos_and_videos/broken.jpg", options).unwrap();
+        zip.write_all(b"CORRUPTED-PHOTO-BYTES").unwrap();
+        zip.start_file("posts/your_posts_2.json", options).unwrap();
+        zip.write_all(posts("Second post", 1_560_000_100).as_bytes()).unwrap();
+        let mut data = zip.finish().unwrap().into_inner();
+
+        let at = data.windows(9).position(|w| w == b"CORRUPTED").unwrap();
+        data[at] = b'X';
+        std::fs::write(zip_path, data).unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_entry_is_listed_and_import_continues() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("facebook.zip");
+        build_zip_with_unreadable_entry(&zip_path);
+
+        let exports_dir = dir.path().join("exports");
+        let mut reports = Vec::new();
+        let result = process_facebook_export(&zip_path, &exports_dir, |p| reports.push(p.clone()), &AtomicBool::new(false));
+        assert!(result.success);
+        assert_eq!(result.item_count, 2);
+
+        let details = result.details.unwrap();
+        assert_eq!(details["errorCount"], 1);
+        assert_eq!(details["errors"][0]["entry"], "photos_and_videos/broken.jpg");
+        assert!(!details["errors"][0]["error"].as_str().unwrap().is_empty());
+        assert_eq!(details["mediaCount"], 0);
+        assert_eq!(details["cancelled"], false);
+        assert_eq!(build_index_documents(&exports_dir).len(), 2);
+
+        let scanned: Vec<(usize, usize)> = reports.iter().map(|p| (p.entries_scanned, p.items_emitted)).collect();
+        assert_eq!(scanned, vec![(1, 1), (2, 1), (3, 2)]);
+        assert!(reports.iter().all(|p| p.total_entries == 3));
+    }
+
+    #[test]
+    fn test_cancel_stops_before_the_next_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("facebook.zip");
+        build_zip_with_unreadable_entry(&zip_path);
+
+        let cancel = AtomicBool::new(false);
+        let exports_dir = dir.path().join("exports");
+        let result = process_facebook_export(&zip_path, &exports_dir, |_| cancel.store(true, Ordering::Relaxed), &cancel);
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("Import cancelled"));
+        assert_eq!(result.item_count, 1);
+        let details = result.details.unwrap();
+        assert_eq!(details["cancelled"], true);
+        assert_eq!(details["errorCount"], 0);
+    }
 }
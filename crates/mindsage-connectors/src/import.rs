@@ -0,0 +1,50 @@
+//! Bookkeeping shared by the ZIP import processors: errors of single
+//! archive entries and the result of a finished or cancelled import.
+
+use std::fmt::Display;
+
+use serde_json::Value;
+use tracing::warn;
+
+use crate::types::{EntryError, ImportResult};
+use mindsage_core::redact;
+
+/// Entry errors listed in an import result; later ones are only counted.
+pub(crate) const MAX_ENTRY_ERRORS: usize = 100;
+
+/// Entries that could not be read, parsed or written. An import carries on
+/// past them.
+#[derive(Debug, Default)]
+pub(crate) struct EntryErrors {
+    listed: Vec<EntryError>,
+    count: usize,
+}
+
+impl EntryErrors {
+    pub fn record(&mut self, entry: &str, error: impl Display) {
+        let error = error.to_string();
+        warn!("Import entry {} failed: {}", redact(entry), error);
+        self.count += 1;
+        if self.listed.len() < MAX_ENTRY_ERRORS {
+            self.listed.push(EntryError {
+                entry: entry.to_string(),
+                error,
+            });
+        }
+    }
+}
+
+/// The result of an import that ran to the end or was cancelled. Both carry
+/// the counts so far plus `errors`, `errorCount` and `cancelled` in their
+/// details; a cancelled import is not a success.
+pub(crate) fn finish(item_count: usize, mut details: Value, errors: EntryErrors, cancelled: bool) -> ImportResult {
+    details["errors"] = serde_json::to_value(&errors.listed).unwrap_or_default();
+    details["errorCount"] = errors.count.into();
+    details["cancelled"] = cancelled.into();
+    ImportResult {
+        success: !cancelled,
+        item_count,
+        error: cancelled.then(|| "Import cancelled".to_string()),
+        details: Some(details),
+    }
+}
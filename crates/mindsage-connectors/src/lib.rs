@@ -1,13 +1,18 @@
-//! Data connectors: Notion API, Facebook ZIP, ChatGPT import.
+//! Data connectors: Notion API, Facebook ZIP, ChatGPT import, RSS feeds.
 //!
 //! Manages data source connections, file-based imports (ChatGPT ZIP,
-//! Facebook ZIP), and API-based syncs (Notion). Persists connector
+//! Facebook ZIP), and API-based syncs (Notion, RSS and Atom feeds). Persists connector
 //! configuration to `data/connectors.json`.
 
 pub mod chatgpt;
 pub mod facebook;
+mod import;
 pub mod manager;
+pub mod media;
+pub mod preflight;
+pub mod rss;
 pub mod types;
+mod xml;
 
 pub use manager::ConnectorManager;
 pub use types::*;
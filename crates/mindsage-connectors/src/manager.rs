@@ -2,18 +2,25 @@
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use parking_lot::RwLock;
 use tracing::{info, warn};
 
 use crate::types::*;
 
+/// Output lines kept in a run status; older progress lines are dropped.
+const MAX_OUTPUT_LINES: usize = 200;
+
 /// Manages connector configurations and sync state.
 pub struct ConnectorManager {
     connectors_file: PathBuf,
     exports_dir: PathBuf,
     connectors: RwLock<Vec<ConnectorConfig>>,
     run_statuses: RwLock<HashMap<String, RunStatus>>,
+    /// Cancel flags of the imports running now, by connector.
+    active_runs: RwLock<HashMap<String, Arc<AtomicBool>>>,
 }
 
 impl ConnectorManager {
@@ -27,6 +34,7 @@ impl ConnectorManager {
             exports_dir: exports_dir.to_path_buf(),
             connectors: RwLock::new(connectors),
             run_statuses: RwLock::new(HashMap::new()),
+            active_runs: RwLock::new(HashMap::new()),
         }
     }
 
@@ -129,7 +137,7 @@ impl ConnectorManager {
         self.save();
     }
 
-    /// Mark a connector as errored.
+    /// Mark a connector as errored, ending its run with the error.
     pub fn mark_error(&self, id: &str, error: &str) {
         let mut connectors = self.connectors.write();
         if let Some(connector) = connectors.iter_mut().find(|c| c.id == id) {
@@ -138,16 +146,80 @@ impl ConnectorManager {
         drop(connectors);
         self.save();
 
+        self.finish_run(id, format!("Error: {}", error), 1);
+    }
+
+    /// Start an import run, shown as running until `finish_run`. Returns the
+    /// flag that cancels it, or `None` while the connector already has one.
+    pub fn start_run(&self, id: &str) -> Option<Arc<AtomicBool>> {
+        let mut active = self.active_runs.write();
+        if active.contains_key(id) {
+            return None;
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        active.insert(id.to_string(), cancel.clone());
+        drop(active);
+
         self.run_statuses.write().insert(
             id.to_string(),
             RunStatus {
-                running: false,
-                output: vec![format!("Error: {}", error)],
+                running: true,
+                output: Vec::new(),
                 last_run: Some(chrono::Utc::now().to_rfc3339()),
-                exit_code: Some(1),
+                exit_code: None,
                 connector_id: Some(id.to_string()),
+                feeds: Vec::new(),
             },
         );
+        Some(cancel)
+    }
+
+    /// Add a progress line to a connector's run output.
+    pub fn report_progress(&self, id: &str, line: String) {
+        let mut statuses = self.run_statuses.write();
+        let status = statuses.entry(id.to_string()).or_default();
+        status.output.push(line);
+        let excess = status.output.len().saturating_sub(MAX_OUTPUT_LINES);
+        status.output.drain(..excess);
+    }
+
+    /// Record what a feed sync did with one feed, with a progress line.
+    pub fn report_feed(&self, id: &str, counts: FeedSyncCounts) {
+        let line = match (&counts.error, counts.not_modified) {
+            (Some(error), _) => format!("{}: {}", counts.url, error),
+            (None, true) => format!("{}: not modified", counts.url),
+            (None, false) => format!(
+                "{}: {} new, {} updated, {} unchanged",
+                counts.url, counts.new, counts.updated, counts.unchanged
+            ),
+        };
+        self.report_progress(id, line);
+        self.run_statuses.write().entry(id.to_string()).or_default().feeds.push(counts);
+    }
+
+    /// Ask a connector's running import to stop after its current entry.
+    /// Returns false when none is running.
+    pub fn cancel_run(&self, id: &str) -> bool {
+        match self.active_runs.read().get(id) {
+            Some(cancel) => {
+                cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// End a connector's run with a last output line and its exit code.
+    pub fn finish_run(&self, id: &str, line: String, exit_code: i32) {
+        self.active_runs.write().remove(id);
+        self.report_progress(id, line);
+        let mut statuses = self.run_statuses.write();
+        if let Some(status) = statuses.get_mut(id) {
+            status.running = false;
+            status.last_run = Some(chrono::Utc::now().to_rfc3339());
+            status.exit_code = Some(exit_code);
+            status.connector_id = Some(id.to_string());
+        }
     }
 
     // ---------------------------------------------------------------
@@ -187,6 +259,12 @@ impl ConnectorManager {
         serde_json::from_str(&data).ok()
     }
 
+    /// A registered media file of a connector, by its path in the media
+    /// directory; `None` for anything else.
+    pub fn media_file(&self, id: &str, path: &str) -> Option<PathBuf> {
+        crate::media::locate_media(&self.exports_dir.join(id), path)
+    }
+
     /// Load pending media registry for a connector.
     pub fn get_pending_media(&self, id: &str) -> Option<crate::types::PendingMediaRegistry> {
         let exports_dir = self.exports_dir.join(id);
@@ -197,6 +275,11 @@ impl ConnectorManager {
     // Persistence
     // ---------------------------------------------------------------
 
+    /// Write the connector configurations to disk, for shutdown.
+    pub fn flush(&self) {
+        self.save();
+    }
+
     fn save(&self) {
         let connectors = self.connectors.read();
         if let Ok(data) = serde_json::to_string_pretty(&*connectors) {
@@ -339,4 +422,34 @@ mod tests {
         assert!(!status.running);
         assert_eq!(status.exit_code, Some(1));
     }
+
+    #[test]
+    fn test_run_can_be_cancelled_once_started() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = test_manager(dir.path());
+        assert!(!mgr.cancel_run("fb"));
+
+        let cancel = mgr.start_run("fb").unwrap();
+        assert!(mgr.start_run("fb").is_none());
+        for i in 0..MAX_OUTPUT_LINES + 5 {
+            mgr.report_progress("fb", format!("entry {}", i));
+        }
+        let status = mgr.get_run_status("fb");
+        assert!(status.running);
+        assert_eq!(status.output.len(), MAX_OUTPUT_LINES);
+        assert_eq!(status.output[0], "entry 5");
+
+        assert!(mgr.cancel_run("fb"));
+        assert!(cancel.load(Ordering::Relaxed));
+        mgr.finish_run("fb", "Cancelled".to_string(), 1);
+        let status = mgr.get_run_status("fb");
+        assert!(!status.running);
+        assert_eq!(status.output.last().unwrap(), "Cancelled");
+        assert_eq!(status.exit_code, Some(1));
+
+        // The next import starts with a fresh flag and output
+        let cancel = mgr.start_run("fb").unwrap();
+        assert!(!cancel.load(Ordering::Relaxed));
+        assert!(mgr.get_run_status("fb").output.is_empty());
+    }
 }
@@ -0,0 +1,700 @@
+//! Media metadata for imported photos and videos.
+//!
+//! Nothing here looks at pixels: a media document is built from the JPEG's
+//! EXIF fields (date taken, GPS position, camera), the description and
+//! album that Facebook's JSON sidecars give for the file's `uri`, and a
+//! place name from a local geocoding table when one is present. The
+//! document's text is what makes "photos from Lisbon 2018" find the file.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::types::{IndexDocument, MediaInfo, PendingMediaFile, PendingMediaRegistry};
+
+/// Directory of a connector's extracted media, under its exports.
+pub const MEDIA_DIR: &str = "pending-media";
+const REGISTRY_FILE: &str = ".registry.json";
+
+/// A photo is placed at the nearest table entry within this distance.
+const MAX_PLACE_DISTANCE_KM: f64 = 50.0;
+/// GPS positions are kept to two decimals (about 1 km).
+const COORDINATE_PRECISION: f64 = 100.0;
+
+// ---------------------------------------------------------------
+// EXIF
+// ---------------------------------------------------------------
+
+const TAG_MAKE: u16 = 0x010F;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_GPS_LATITUDE_REF: u16 = 0x0001;
+const TAG_GPS_LATITUDE: u16 = 0x0002;
+const TAG_GPS_LONGITUDE_REF: u16 = 0x0003;
+const TAG_GPS_LONGITUDE: u16 = 0x0004;
+
+/// The EXIF fields a media document uses.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExifData {
+    /// Date taken (ms). EXIF times have no zone; they are read as UTC.
+    pub taken_at: Option<i64>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Make and model, e.g. "Apple iPhone 8".
+    pub camera: Option<String>,
+}
+
+/// Read the EXIF block of a JPEG. `None` for other formats or a JPEG
+/// without EXIF.
+pub fn read_exif(bytes: &[u8]) -> Option<ExifData> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() {
+        if bytes[pos] != 0xFF {
+            return None;
+        }
+        let marker = bytes[pos + 1];
+        // Start of scan or end of image: no more metadata segments
+        if marker == 0xDA || marker == 0xD9 {
+            return None;
+        }
+        let len = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let segment = bytes.get(pos + 4..pos + 2 + len)?;
+        if marker == 0xE1 {
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                return Tiff::new(tiff).map(|t| t.read());
+            }
+        }
+        pos += 2 + len;
+    }
+    None
+}
+
+/// The TIFF structure inside an EXIF segment.
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+/// An IFD entry: its tag, type, count and the position of its 4-byte
+/// value or offset field.
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    value_pos: usize,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(0..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        let tiff = Self { data, little_endian };
+        (tiff.u16_at(2)? == 42).then_some(tiff)
+    }
+
+    fn read(&self) -> ExifData {
+        let mut exif = ExifData::default();
+        let Some(ifd0) = self.u32_at(4).and_then(|o| self.entries(o as usize)) else {
+            return exif;
+        };
+        let ascii = |entries: &[Entry], tag| entries.iter().find(|e| e.tag == tag).and_then(|e| self.ascii(e));
+        let offset = |entries: &[Entry], tag| {
+            entries
+                .iter()
+                .find(|e| e.tag == tag)
+                .and_then(|e| self.u32_at(e.value_pos))
+                .map(|o| o as usize)
+        };
+
+        exif.camera = match (ascii(&ifd0, TAG_MAKE), ascii(&ifd0, TAG_MODEL)) {
+            // Models often repeat the make ("Canon" + "Canon EOS 80D")
+            (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => Some(model),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.or(model),
+        };
+
+        let original = offset(&ifd0, TAG_EXIF_IFD)
+            .and_then(|o| self.entries(o))
+            .and_then(|exif_ifd| ascii(&exif_ifd, TAG_DATE_TIME_ORIGINAL));
+        exif.taken_at = original
+            .or_else(|| ascii(&ifd0, TAG_DATE_TIME))
+            .and_then(|s| parse_exif_date(&s));
+
+        if let Some(gps) = offset(&ifd0, TAG_GPS_IFD).and_then(|o| self.entries(o)) {
+            let coordinate = |ref_tag, tag, negative: &str| {
+                let value = self.degrees(gps.iter().find(|e| e.tag == tag)?)?;
+                let sign = if ascii(&gps, ref_tag)?.eq_ignore_ascii_case(negative) { -1.0 } else { 1.0 };
+                Some(sign * value)
+            };
+            let latitude = coordinate(TAG_GPS_LATITUDE_REF, TAG_GPS_LATITUDE, "S");
+            let longitude = coordinate(TAG_GPS_LONGITUDE_REF, TAG_GPS_LONGITUDE, "W");
+            // 0,0 is what some cameras write without a fix
+            if let (Some(lat), Some(lon)) = (latitude, longitude) {
+                if (lat, lon) != (0.0, 0.0) && lat.abs() <= 90.0 && lon.abs() <= 180.0 {
+                    exif.latitude = Some(lat);
+                    exif.longitude = Some(lon);
+                }
+            }
+        }
+        exif
+    }
+
+    fn entries(&self, offset: usize) -> Option<Vec<Entry>> {
+        let count = self.u16_at(offset)? as usize;
+        (0..count)
+            .map(|i| {
+                let pos = offset + 2 + i * 12;
+                Some(Entry {
+                    tag: self.u16_at(pos)?,
+                    kind: self.u16_at(pos + 2)?,
+                    count: self.u32_at(pos + 4)?,
+                    value_pos: pos + 8,
+                })
+            })
+            .collect()
+    }
+
+    /// The bytes of an entry's value, inline or at its offset.
+    fn value(&self, entry: &Entry) -> Option<&'a [u8]> {
+        let unit = match entry.kind {
+            1 | 2 | 7 => 1,
+            3 => 2,
+            4 | 9 => 4,
+            5 | 10 => 8,
+            _ => return None,
+        };
+        let len = unit * entry.count as usize;
+        let start = if len <= 4 {
+            entry.value_pos
+        } else {
+            self.u32_at(entry.value_pos)? as usize
+        };
+        self.data.get(start..start.checked_add(len)?)
+    }
+
+    fn ascii(&self, entry: &Entry) -> Option<String> {
+        if entry.kind != 2 {
+            return None;
+        }
+        let bytes = self.value(entry)?;
+        let text = String::from_utf8_lossy(bytes.split(|&b| b == 0).next()?);
+        let text = text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    /// Degrees, minutes and seconds (three rationals) as decimal degrees.
+    fn degrees(&self, entry: &Entry) -> Option<f64> {
+        if entry.kind != 5 || entry.count != 3 {
+            return None;
+        }
+        let bytes = self.value(entry)?;
+        let mut parts = [0.0; 3];
+        for (i, part) in parts.iter_mut().enumerate() {
+            let num = self.u32_in(bytes, i * 8)? as f64;
+            let den = self.u32_in(bytes, i * 8 + 4)? as f64;
+            *part = if den == 0.0 { 0.0 } else { num / den };
+        }
+        Some(parts[0] + parts[1] / 60.0 + parts[2] / 3600.0)
+    }
+
+    fn u16_at(&self, pos: usize) -> Option<u16> {
+        let b: [u8; 2] = self.data.get(pos..pos + 2)?.try_into().ok()?;
+        Some(if self.little_endian { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) })
+    }
+
+    fn u32_at(&self, pos: usize) -> Option<u32> {
+        self.u32_in(self.data, pos)
+    }
+
+    fn u32_in(&self, bytes: &[u8], pos: usize) -> Option<u32> {
+        let b: [u8; 4] = bytes.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if self.little_endian { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    }
+}
+
+/// `2018:07:14 18:30:05` in epoch ms.
+fn parse_exif_date(value: &str) -> Option<i64> {
+    let date = chrono::NaiveDateTime::parse_from_str(value, "%Y:%m:%d %H:%M:%S").ok()?;
+    Some(date.and_utc().timestamp_millis())
+}
+
+// ---------------------------------------------------------------
+// Sidecars
+// ---------------------------------------------------------------
+
+/// What the export's JSON says about one media `uri`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sidecar {
+    pub description: Option<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    /// `creation_timestamp`, or the EXIF copy's `taken_timestamp` (ms).
+    pub taken_at: Option<i64>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// Collect every object with a `uri` in a Facebook JSON file, keyed by that
+/// `uri`. Objects listed under an album's `photos` get the album's `name`.
+/// The first value seen for a field is kept.
+pub fn collect_sidecars(json: &Value, sidecars: &mut HashMap<String, Sidecar>) {
+    walk_sidecars(json, None, sidecars);
+}
+
+fn walk_sidecars(value: &Value, album: Option<&str>, sidecars: &mut HashMap<String, Sidecar>) {
+    match value {
+        Value::Array(items) => items.iter().for_each(|v| walk_sidecars(v, album, sidecars)),
+        Value::Object(obj) => {
+            let text = |key: &str| {
+                obj.get(key)
+                    .and_then(|v| v.as_str())
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+            };
+            if let Some(uri) = text("uri") {
+                let exif = obj
+                    .get("media_metadata")
+                    .and_then(|m| m.get("photo_metadata"))
+                    .and_then(|m| m.get("exif_data"))
+                    .and_then(|e| e.as_array())
+                    .and_then(|e| e.first());
+                let exif_number = |key: &str| exif.and_then(|e| e.get(key)).and_then(|v| v.as_f64());
+                let found = Sidecar {
+                    description: text("description"),
+                    title: text("title"),
+                    album: album.map(str::to_string),
+                    taken_at: obj
+                        .get("creation_timestamp")
+                        .and_then(|t| t.as_i64())
+                        .or_else(|| exif_number("taken_timestamp").map(|t| t as i64))
+                        .filter(|&t| t > 0)
+                        .map(|s| s * 1000),
+                    latitude: exif_number("latitude"),
+                    longitude: exif_number("longitude"),
+                };
+                let entry = sidecars.entry(uri).or_default();
+                entry.description = entry.description.take().or(found.description);
+                entry.title = entry.title.take().or(found.title);
+                entry.album = entry.album.take().or(found.album);
+                entry.taken_at = entry.taken_at.or(found.taken_at);
+                if entry.latitude.is_none() {
+                    entry.latitude = found.latitude;
+                    entry.longitude = found.longitude;
+                }
+            }
+            let album = match (text("name"), obj.get("photos").is_some_and(|p| p.is_array())) {
+                (Some(name), true) => Some(name),
+                _ => album.map(str::to_string),
+            };
+            for v in obj.values() {
+                walk_sidecars(v, album.as_deref(), sidecars);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The sidecar for a ZIP entry. Sidecar `uri`s are relative to the export
+/// root, which the ZIP may nest in a folder, so the entry's path suffixes
+/// are tried too.
+pub fn find_sidecar<'a>(sidecars: &'a HashMap<String, Sidecar>, zip_path: &str) -> Option<&'a Sidecar> {
+    let mut path = zip_path;
+    loop {
+        if let Some(sidecar) = sidecars.get(path) {
+            return Some(sidecar);
+        }
+        path = &path[path.find('/')? + 1..];
+    }
+}
+
+/// Media info from EXIF and the sidecar; the sidecar wins where both have
+/// a value. `None` when neither says anything.
+pub fn media_info(exif: Option<&ExifData>, sidecar: Option<&Sidecar>) -> Option<MediaInfo> {
+    let (latitude, longitude) = match (sidecar.and_then(|s| s.latitude.zip(s.longitude)), exif) {
+        (Some((lat, lon)), _) => (Some(lat), Some(lon)),
+        (None, Some(exif)) => (exif.latitude, exif.longitude),
+        (None, None) => (None, None),
+    };
+    let info = MediaInfo {
+        description: sidecar.and_then(|s| s.description.clone()),
+        title: sidecar.and_then(|s| s.title.clone()),
+        album: sidecar.and_then(|s| s.album.clone()),
+        taken_at: exif.and_then(|e| e.taken_at).or(sidecar.and_then(|s| s.taken_at)),
+        latitude: latitude.map(coarse),
+        longitude: longitude.map(coarse),
+        place: None,
+        camera: exif.and_then(|e| e.camera.clone()),
+    };
+    (info != MediaInfo::default()).then_some(info)
+}
+
+fn coarse(coordinate: f64) -> f64 {
+    (coordinate * COORDINATE_PRECISION).round() / COORDINATE_PRECISION
+}
+
+// ---------------------------------------------------------------
+// Places
+// ---------------------------------------------------------------
+
+/// A local place table (`data/geocoding.csv`): one `name,latitude,longitude`
+/// per line. Positions are never sent anywhere.
+#[derive(Debug, Clone, Default)]
+pub struct Geocoder {
+    places: Vec<(String, f64, f64)>,
+}
+
+impl Geocoder {
+    /// The table at `path`; `None` when there is none or it has no places.
+    pub fn load(path: &Path) -> Option<Self> {
+        let geocoder = Self::from_csv(&std::fs::read_to_string(path).ok()?);
+        (!geocoder.places.is_empty()).then_some(geocoder)
+    }
+
+    /// Parse `name,latitude,longitude` lines. Names may contain commas;
+    /// `#` comments, a header and unreadable lines are skipped.
+    pub fn from_csv(text: &str) -> Self {
+        let places = text
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.rsplitn(3, ',');
+                let lon: f64 = fields.next()?.trim().parse().ok()?;
+                let lat: f64 = fields.next()?.trim().parse().ok()?;
+                let name = fields.next()?.trim().trim_matches('"').to_string();
+                (!name.is_empty()).then_some((name, lat, lon))
+            })
+            .collect();
+        Self { places }
+    }
+
+    /// The nearest place within `MAX_PLACE_DISTANCE_KM`.
+    pub fn place(&self, latitude: f64, longitude: f64) -> Option<&str> {
+        self.places
+            .iter()
+            .map(|(name, lat, lon)| (name, distance_km(latitude, longitude, *lat, *lon)))
+            .filter(|(_, d)| *d <= MAX_PLACE_DISTANCE_KM)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Great-circle distance.
+fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (dlat, dlon) = ((lat2 - lat1).to_radians(), (lon2 - lon1).to_radians());
+    let a = (dlat / 2.0).sin().powi(2) + lat1.to_radians().cos() * lat2.to_radians().cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * 6371.0 * a.sqrt().asin()
+}
+
+// ---------------------------------------------------------------
+// Documents
+// ---------------------------------------------------------------
+
+/// Load the media registry in `exports_dir`.
+pub fn load_registry(exports_dir: &Path) -> Option<PendingMediaRegistry> {
+    let data = std::fs::read_to_string(exports_dir.join(MEDIA_DIR).join(REGISTRY_FILE)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+/// Save the media registry in `exports_dir`.
+pub fn save_registry(exports_dir: &Path, registry: &PendingMediaRegistry) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(registry)?;
+    std::fs::write(exports_dir.join(MEDIA_DIR).join(REGISTRY_FILE), json)
+}
+
+/// The document describing `file`: its description, album, date, place
+/// and camera, with `metadata.media = true` and the stored file's path.
+pub fn media_document(file: &PendingMediaFile) -> IndexDocument {
+    let info = file.info.clone().unwrap_or_default();
+    let kind = match file.media_type.as_str() {
+        "photo" => "Photo",
+        "video" => "Video",
+        "audio" => "Audio recording",
+        _ => "Media file",
+    };
+    let mut lines = vec![format!("{} {}", kind, file.filename)];
+    lines.extend(info.title.clone().filter(|t| Some(t) != info.description.as_ref()));
+    lines.extend(info.description.clone());
+    if let Some(album) = &info.album {
+        lines.push(format!("Album: {}", album));
+    }
+    let taken = info
+        .taken_at
+        .and_then(chrono::DateTime::from_timestamp_millis)
+        .map(|t| t.format("%-d %B %Y").to_string());
+    if let Some(taken) = &taken {
+        lines.push(format!("Taken: {}", taken));
+    }
+    if let Some(place) = &info.place {
+        lines.push(format!("Place: {}", place));
+    }
+    if let Some(camera) = &info.camera {
+        lines.push(format!("Camera: {}", camera));
+    }
+    lines.push(format!("Path: {}", file.original_path));
+
+    let mut metadata = serde_json::json!({
+        "source": "facebook",
+        "type": "media",
+        "media": true,
+        "mediaType": file.media_type,
+        "filename": file.filename,
+        "title": info.description.as_ref().or(info.title.as_ref()).unwrap_or(&file.filename),
+        "originalPath": file.original_path,
+        "storedPath": file.stored_path,
+    });
+    let fields = [
+        ("album", info.album.clone().map(Value::from)),
+        ("place", info.place.clone().map(Value::from)),
+        ("camera", info.camera.clone().map(Value::from)),
+        ("latitude", info.latitude.map(Value::from)),
+        ("longitude", info.longitude.map(Value::from)),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            metadata[key] = value;
+        }
+    }
+
+    IndexDocument {
+        text: lines.join("\n"),
+        metadata,
+        created_at: info.taken_at,
+        external_id: None,
+    }
+}
+
+/// Index the registered media of `exports_dir` that have no document yet.
+/// `index` stores a document and returns its id; the registry records it,
+/// with the place found in `geocoder`. Returns how many were linked.
+pub fn index_media(
+    exports_dir: &Path,
+    geocoder: Option<&Geocoder>,
+    mut index: impl FnMut(&IndexDocument) -> Option<i64>,
+) -> std::io::Result<usize> {
+    let Some(mut registry) = load_registry(exports_dir) else {
+        return Ok(0);
+    };
+    let mut linked = 0;
+    for file in registry.files.iter_mut().filter(|f| f.document_id.is_none()) {
+        if let (Some(geocoder), Some(info)) = (geocoder, file.info.as_mut()) {
+            if let (None, Some(lat), Some(lon)) = (&info.place, info.latitude, info.longitude) {
+                info.place = geocoder.place(lat, lon).map(str::to_string);
+            }
+        }
+        if let Some(id) = index(&media_document(file)) {
+            file.document_id = Some(id);
+            linked += 1;
+        }
+    }
+    if linked > 0 {
+        save_registry(exports_dir, &registry)?;
+    }
+    Ok(linked)
+}
+
+/// The stored media file at `path`, relative to `exports_dir`'s media
+/// directory. Only files in the registry are served; anything else, or a
+/// path that leaves the directory, is `None`.
+pub fn locate_media(exports_dir: &Path, path: &str) -> Option<PathBuf> {
+    let relative = Path::new(path);
+    if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    let media_dir = exports_dir.join(MEDIA_DIR).canonicalize().ok()?;
+    let file = media_dir.join(relative).canonicalize().ok()?;
+    if !file.starts_with(&media_dir) || !file.is_file() {
+        return None;
+    }
+    let registry = load_registry(exports_dir)?;
+    registry
+        .files
+        .iter()
+        .any(|f| Path::new(&f.stored_path).canonicalize().is_ok_and(|p| p == file))
+        .then_some(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A big-endian JPEG with Make, Model, DateTimeOriginal and GPS.
+    fn jpeg_with_exif(lat: [u32; 6], lon: [u32; 6], lat_ref: u8, lon_ref: u8) -> Vec<u8> {
+        let mut t: Vec<u8> = b"MM\0\x2a\0\0\0\x08".to_vec();
+        let entry = |t: &mut Vec<u8>, tag: u16, kind: u16, count: u32, value: u32| {
+            t.extend(tag.to_be_bytes());
+            t.extend(kind.to_be_bytes());
+            t.extend(count.to_be_bytes());
+            t.extend(value.to_be_bytes());
+        };
+        // IFD0 at 8: 4 entries (2 + 48 + 4 = 54 bytes) → data from 62
+        let (make, model, date) = (b"Apple\0".as_slice(), b"iPhone 8\0".as_slice(), b"2018:07:14 18:30:05\0".as_slice());
+        let make_at = 62;
+        let model_at = make_at + make.len() as u32;
+        let exif_at = model_at + model.len() as u32;
+        let date_at = exif_at + 18;
+        let gps_at = date_at + date.len() as u32;
+        let rationals_at = gps_at + 54;
+        t.extend(4u16.to_be_bytes());
+        entry(&mut t, TAG_MAKE, 2, make.len() as u32, make_at);
+        entry(&mut t, TAG_MODEL, 2, model.len() as u32, model_at);
+        entry(&mut t, TAG_EXIF_IFD, 4, 1, exif_at);
+        entry(&mut t, TAG_GPS_IFD, 4, 1, gps_at);
+        t.extend(0u32.to_be_bytes());
+        t.extend(make);
+        t.extend(model);
+        // Exif IFD: 1 entry
+        t.extend(1u16.to_be_bytes());
+        entry(&mut t, TAG_DATE_TIME_ORIGINAL, 2, date.len() as u32, date_at);
+        t.extend(0u32.to_be_bytes());
+        t.extend(date);
+        // GPS IFD: 4 entries; one-letter refs are inline
+        t.extend(4u16.to_be_bytes());
+        entry(&mut t, TAG_GPS_LATITUDE_REF, 2, 2, u32::from_be_bytes([lat_ref, 0, 0, 0]));
+        entry(&mut t, TAG_GPS_LATITUDE, 5, 3, rationals_at);
+        entry(&mut t, TAG_GPS_LONGITUDE_REF, 2, 2, u32::from_be_bytes([lon_ref, 0, 0, 0]));
+        entry(&mut t, TAG_GPS_LONGITUDE, 5, 3, rationals_at + 24);
+        t.extend(0u32.to_be_bytes());
+        for value in lat.iter().chain(&lon) {
+            t.extend(value.to_be_bytes());
+        }
+
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 4, 0, 0];
+        jpeg.extend([0xFF, 0xE1]);
+        jpeg.extend(((t.len() + 8) as u16).to_be_bytes());
+        jpeg.extend(b"Exif\0\0");
+        jpeg.extend(t);
+        jpeg.extend([0xFF, 0xDA, 0, 2, 0xFF, 0xD9]);
+        jpeg
+    }
+
+    #[test]
+    fn test_read_exif() {
+        // Lisbon: 38° 43' 1.2" N, 9° 8' 21.6" W
+        let jpeg = jpeg_with_exif([38, 1, 43, 1, 12, 10], [9, 1, 8, 1, 216, 10], b'N', b'W');
+        let exif = read_exif(&jpeg).unwrap();
+        assert_eq!(exif.camera.as_deref(), Some("Apple iPhone 8"));
+        assert_eq!(exif.taken_at, Some(1_531_593_005_000));
+        assert!((exif.latitude.unwrap() - 38.717).abs() < 1e-6);
+        assert!((exif.longitude.unwrap() + 9.139_333).abs() < 1e-6);
+
+        assert_eq!(read_exif(b"\x89PNG\r\n"), None);
+        assert_eq!(read_exif(&[0xFF, 0xD8, 0xFF, 0xDA, 0, 2]), None);
+        // Truncated blocks are not read past their end
+        assert_eq!(read_exif(&jpeg[..40]), None);
+        let mut damaged = jpeg.clone();
+        damaged.truncate(jpeg.len() - 60);
+        let _ = read_exif(&damaged);
+    }
+
+    #[test]
+    fn test_sidecars_by_uri_and_album() {
+        let album = serde_json::json!({
+            "name": "Lisbon 2018",
+            "photos": [{
+                "uri": "photos_and_videos/Lisbon2018_abc/123.jpg",
+                "creation_timestamp": 1_531_600_000,
+                "description": "Sunset over the Tagus",
+                "media_metadata": { "photo_metadata": { "exif_data": [{ "latitude": 38.7071, "longitude": -9.1355 }] } },
+            }],
+        });
+        let post = serde_json::json!([{
+            "attachments": [{ "data": [{ "media": { "uri": "photos_and_videos/Lisbon2018_abc/123.jpg", "description": "Later caption" } }] }],
+        }]);
+        let mut sidecars = HashMap::new();
+        collect_sidecars(&album, &mut sidecars);
+        collect_sidecars(&post, &mut sidecars);
+
+        let sidecar = find_sidecar(&sidecars, "facebook-sam/photos_and_videos/Lisbon2018_abc/123.jpg").unwrap();
+        assert_eq!(sidecar.description.as_deref(), Some("Sunset over the Tagus"));
+        assert_eq!(sidecar.album.as_deref(), Some("Lisbon 2018"));
+        assert_eq!(sidecar.taken_at, Some(1_531_600_000_000));
+        assert!(find_sidecar(&sidecars, "photos_and_videos/other.jpg").is_none());
+
+        let info = media_info(None, Some(sidecar)).unwrap();
+        assert_eq!((info.latitude, info.longitude), (Some(38.71), Some(-9.14)));
+        assert_eq!(media_info(None, None), None);
+    }
+
+    #[test]
+    fn test_geocoder_nearest_place() {
+        let geocoder = Geocoder::from_csv("name,latitude,longitude\n# comment\nLisbon,38.7223,-9.1393\nPorto,41.1579,-8.6291\n\"Washington, D.C.\",38.9072,-77.0369\nbad line\n");
+        assert_eq!(geocoder.place(38.71, -9.14), Some("Lisbon"));
+        assert_eq!(geocoder.place(41.0, -8.6), Some("Porto"));
+        assert_eq!(geocoder.place(38.9, -77.0), Some("Washington, D.C."));
+        assert_eq!(geocoder.place(0.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_index_media_links_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let media_dir = dir.path().join(MEDIA_DIR);
+        std::fs::create_dir_all(&media_dir).unwrap();
+        let stored = media_dir.join("123.jpg");
+        std::fs::write(&stored, b"jpeg").unwrap();
+        std::fs::write(dir.path().join("secret.json"), b"{}").unwrap();
+        let file = PendingMediaFile {
+            original_path: "photos_and_videos/Lisbon2018_abc/123.jpg".to_string(),
+            filename: "123.jpg".to_string(),
+            media_type: "photo".to_string(),
+            extension: "jpg".to_string(),
+            size: 4,
+            context: None,
+            stored_at: String::new(),
+            stored_path: stored.to_string_lossy().to_string(),
+            info: Some(MediaInfo {
+                description: Some("Sunset over the Tagus".to_string()),
+                album: Some("Lisbon 2018".to_string()),
+                taken_at: Some(1_531_593_005_000),
+                latitude: Some(38.71),
+                longitude: Some(-9.14),
+                camera: Some("Apple iPhone 8".to_string()),
+                ..Default::default()
+            }),
+            document_id: None,
+        };
+        let registry = PendingMediaRegistry {
+            files: vec![file],
+            ..Default::default()
+        };
+        save_registry(dir.path(), &registry).unwrap();
+
+        let geocoder = Geocoder::from_csv("Lisbon,38.7223,-9.1393");
+        let mut indexed = Vec::new();
+        let linked = index_media(dir.path(), Some(&geocoder), |doc| {
+            indexed.push(doc.clone());
+            Some(7)
+        })
+        .unwrap();
+        assert_eq!(linked, 1);
+        let doc = &indexed[0];
+        assert_eq!(
+            doc.text,
+            "Photo 123.jpg\nSunset over the Tagus\nAlbum: Lisbon 2018\nTaken: 14 July 2018\nPlace: Lisbon\nCamera: Apple iPhone 8\nPath: photos_and_videos/Lisbon2018_abc/123.jpg"
+        );
+        assert_eq!(doc.metadata["media"], true);
+        assert_eq!(doc.metadata["place"], "Lisbon");
+        assert_eq!(doc.created_at, Some(1_531_593_005_000));
+
+        let registry = load_registry(dir.path()).unwrap();
+        assert_eq!(registry.files[0].document_id, Some(7));
+        assert_eq!(registry.files[0].info.as_ref().unwrap().place.as_deref(), Some("Lisbon"));
+        // Linked files are not indexed again
+        assert_eq!(index_media(dir.path(), None, |_| Some(8)).unwrap(), 0);
+
+        assert!(locate_media(dir.path(), "123.jpg").is_some());
+        for bad in ["../secret.json", ".registry.json", "/etc/passwd", "missing.jpg", ""] {
+            assert!(locate_media(dir.path(), bad).is_none(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,465 @@
+//! Import preflight — what a ChatGPT or Facebook export ZIP would add to the
+//! store, estimated from the archive's table of contents without extracting
+//! anything, and whether the device has room for it.
+//!
+//! The estimates are averages over typical exports: item counts come from
+//! entry counts and uncompressed sizes, chunk counts from the text they
+//! carry. They are meant to tell a 20-minute import from a two-day one, not
+//! to predict exact numbers.
+
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{chatgpt, facebook};
+
+/// Bytes of `conversations.json` per conversation.
+pub const CHATGPT_CONVERSATION_BYTES: u64 = 24 * 1024;
+/// Bytes of `your_posts_*.json` per post.
+pub const FACEBOOK_POST_BYTES: u64 = 1024;
+/// Bytes of a comments file per comment.
+pub const FACEBOOK_COMMENT_BYTES: u64 = 512;
+/// Share of an export's JSON that is message text rather than structure
+/// (ids, timestamps, the ChatGPT message tree).
+pub const CHATGPT_TEXT_RATIO: f64 = 0.35;
+pub const FACEBOOK_TEXT_RATIO: f64 = 0.5;
+/// Characters of the document indexed for one media file.
+pub const MEDIA_DOCUMENT_CHARS: u64 = 200;
+/// New text per paragraph chunk: the ingester's 512-character chunks less
+/// their 100-character overlap.
+pub const PARAGRAPH_CHUNK_CHARS: u64 = 412;
+/// Database bytes per character of imported text: the document, its chunks
+/// (with overlap) and the full-text index.
+pub const DB_BYTES_PER_TEXT_CHAR: f64 = 3.5;
+/// Database bytes per chunk row and its index entries, text excluded.
+pub const DB_BYTES_PER_CHUNK: u64 = 256;
+/// Bytes of the export files written per character of text.
+pub const EXPORT_BYTES_PER_TEXT_CHAR: f64 = 1.5;
+/// Extra disk kept free on top of the estimate, for SQLite's journal and
+/// estimation error.
+pub const DISK_HEADROOM: f64 = 1.2;
+/// Embedding throughput assumed before the embedder has run.
+pub const DEFAULT_EMBED_TEXTS_PER_SEC: f64 = 10.0;
+/// Embedding time from which it, rather than disk space, is reported as the
+/// limiting factor. A long embedding run does not block an import; search
+/// falls back to BM25 for chunks not embedded yet.
+pub const LONG_EMBEDDING_SECS: f64 = 8.0 * 3600.0;
+
+/// One file in an archive's table of contents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveEntry {
+    pub name: String,
+    /// Uncompressed size, as the archive declares it.
+    pub size: u64,
+}
+
+/// An archive's table of contents; directories are left out.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveManifest {
+    pub entries: Vec<ArchiveEntry>,
+    /// Size of the archive itself.
+    pub archive_bytes: u64,
+}
+
+impl ArchiveManifest {
+    /// Read the central directory of a ZIP. No entry is decompressed.
+    pub fn read(reader: impl Read + Seek, archive_bytes: u64) -> Result<Self, String> {
+        let mut archive = zip::ZipArchive::new(reader).map_err(|e| format!("Invalid ZIP file: {}", e))?;
+        let mut entries = Vec::with_capacity(archive.len());
+        for i in 0..archive.len() {
+            let entry = archive.by_index_raw(i).map_err(|e| format!("Invalid ZIP file: {}", e))?;
+            if !entry.is_dir() {
+                entries.push(ArchiveEntry {
+                    name: entry.name().to_string(),
+                    size: entry.size(),
+                });
+            }
+        }
+        Ok(Self { entries, archive_bytes })
+    }
+
+    pub fn read_file(zip_path: &Path) -> Result<Self, String> {
+        let file = std::fs::File::open(zip_path).map_err(|e| format!("Failed to open ZIP: {}", e))?;
+        let archive_bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Self::read(std::io::BufReader::new(file), archive_bytes)
+    }
+
+    pub fn uncompressed_bytes(&self) -> u64 {
+        self.entries.iter().map(|e| e.size).sum()
+    }
+}
+
+/// Which processor an export is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportKind {
+    #[serde(rename = "chatgpt")]
+    ChatGpt,
+    Facebook,
+}
+
+impl ExportKind {
+    /// The kind of a connector's `config.script`.
+    pub fn from_script(script: &str) -> Option<Self> {
+        match script {
+            "chatgpt-import" => Some(Self::ChatGpt),
+            "facebook-import" => Some(Self::Facebook),
+            _ => None,
+        }
+    }
+
+    /// Recognize an export by its entries: a `conversations.json` is
+    /// ChatGPT's, posts, comments or message threads are Facebook's.
+    pub fn detect(manifest: &ArchiveManifest) -> Option<Self> {
+        let names = || manifest.entries.iter().map(|e| e.name.as_str());
+        if names().any(chatgpt::is_conversations_entry) {
+            return Some(Self::ChatGpt);
+        }
+        names()
+            .filter(|name| name.ends_with(".json"))
+            .map(facebook::json_entry_kind)
+            .any(|kind| kind.posts || kind.comments || kind.messages)
+            .then_some(Self::Facebook)
+    }
+}
+
+/// Estimated items an import writes, by kind.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemEstimate {
+    pub conversations: u64,
+    pub posts: u64,
+    pub comments: u64,
+    pub threads: u64,
+    pub media: u64,
+    pub total: u64,
+}
+
+/// What besides the archive an estimate depends on.
+#[derive(Debug, Clone, Default)]
+pub struct PreflightResources {
+    /// Free space on the data directory's disk, when it could be read.
+    pub free_disk_bytes: Option<u64>,
+    /// Whether an embedder is loaded; without one nothing is embedded.
+    pub embedder_available: bool,
+    /// Texts per second the embedder has managed so far, when it has run.
+    pub measured_texts_per_sec: Option<f64>,
+    pub embedding_dim: usize,
+}
+
+/// The resource an import is closest to running out of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LimitingFactor {
+    DiskSpace,
+    EmbeddingTime,
+}
+
+/// The preflight of one export.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportEstimate {
+    pub kind: ExportKind,
+    pub total_entries: usize,
+    pub archive_bytes: u64,
+    pub uncompressed_bytes: u64,
+    pub items: ItemEstimate,
+    /// Indexed documents: one per item.
+    pub documents: u64,
+    pub text_chars: u64,
+    /// Paragraph chunks, which are the ones embedded.
+    pub paragraph_chunks: u64,
+    /// Section chunks: one per document.
+    pub section_chunks: u64,
+    pub db_growth_bytes: u64,
+    /// Database growth, export files, extracted media and the staged
+    /// upload, with `DISK_HEADROOM`.
+    pub disk_required_bytes: u64,
+    pub free_disk_bytes: Option<u64>,
+    /// Texts per second the time estimate assumes; absent without an
+    /// embedder.
+    pub embed_texts_per_sec: Option<f64>,
+    /// Whether that rate was measured or is `DEFAULT_EMBED_TEXTS_PER_SEC`.
+    pub throughput_measured: bool,
+    pub embedding_secs: Option<f64>,
+    /// False when the import would not fit on the disk.
+    pub go: bool,
+    pub limiting_factor: Option<LimitingFactor>,
+    pub reason: String,
+}
+
+/// Estimate the import of `manifest` as a `kind` export.
+pub fn estimate_import(kind: ExportKind, manifest: &ArchiveManifest, resources: &PreflightResources) -> ImportEstimate {
+    let mut items = ItemEstimate::default();
+    let mut json_bytes = 0;
+    let mut media_bytes = 0;
+    for entry in &manifest.entries {
+        match kind {
+            ExportKind::ChatGpt => {
+                if chatgpt::is_conversations_entry(&entry.name) {
+                    items.conversations += entry.size.div_ceil(CHATGPT_CONVERSATION_BYTES);
+                    json_bytes += entry.size;
+                }
+            }
+            ExportKind::Facebook => {
+                if entry.name.ends_with(".json") {
+                    let entry_kind = facebook::json_entry_kind(&entry.name);
+                    if entry_kind.posts {
+                        items.posts += entry.size.div_ceil(FACEBOOK_POST_BYTES);
+                    }
+                    if entry_kind.comments {
+                        items.comments += entry.size.div_ceil(FACEBOOK_COMMENT_BYTES);
+                    }
+                    if entry_kind.messages {
+                        items.threads += 1;
+                    }
+                    if entry_kind.posts || entry_kind.comments || entry_kind.messages {
+                        json_bytes += entry.size;
+                    }
+                } else if facebook::is_media_file(&entry.name) {
+                    items.media += 1;
+                    media_bytes += entry.size;
+                }
+            }
+        }
+    }
+    items.total = items.conversations + items.posts + items.comments + items.threads + items.media;
+
+    let text_ratio = match kind {
+        ExportKind::ChatGpt => CHATGPT_TEXT_RATIO,
+        ExportKind::Facebook => FACEBOOK_TEXT_RATIO,
+    };
+    let text_chars = (json_bytes as f64 * text_ratio) as u64 + items.media * MEDIA_DOCUMENT_CHARS;
+    let documents = items.total;
+    let paragraph_chunks = text_chars.div_ceil(PARAGRAPH_CHUNK_CHARS).max(documents);
+    let section_chunks = documents;
+
+    // Embeddings are stored as int8 plus a 4-byte scale
+    let embedding_bytes = paragraph_chunks * (resources.embedding_dim as u64 + 4);
+    let db_growth_bytes = (text_chars as f64 * DB_BYTES_PER_TEXT_CHAR) as u64
+        + (paragraph_chunks + section_chunks) * DB_BYTES_PER_CHUNK
+        + embedding_bytes;
+    let export_bytes = (text_chars as f64 * EXPORT_BYTES_PER_TEXT_CHAR) as u64;
+    let disk_required_bytes =
+        ((db_growth_bytes + export_bytes + media_bytes + manifest.archive_bytes) as f64 * DISK_HEADROOM) as u64;
+
+    let embed_texts_per_sec = resources
+        .embedder_available
+        .then(|| resources.measured_texts_per_sec.filter(|rate| *rate > 0.0).unwrap_or(DEFAULT_EMBED_TEXTS_PER_SEC));
+    let embedding_secs = embed_texts_per_sec.map(|rate| paragraph_chunks as f64 / rate);
+
+    let disk_use = resources
+        .free_disk_bytes
+        .map(|free| disk_required_bytes as f64 / free.max(1) as f64);
+    let time_use = embedding_secs.map(|secs| secs / LONG_EMBEDDING_SECS);
+    let go = disk_use.map_or(true, |used| used <= 1.0);
+    let limiting_factor = match (disk_use, time_use) {
+        (Some(disk), Some(time)) if time > disk => Some(LimitingFactor::EmbeddingTime),
+        (Some(_), _) => Some(LimitingFactor::DiskSpace),
+        (None, Some(_)) => Some(LimitingFactor::EmbeddingTime),
+        (None, None) => None,
+    };
+    let reason = match (go, limiting_factor) {
+        (false, _) => format!(
+            "Needs about {} of disk space, {} free",
+            format_bytes(disk_required_bytes),
+            format_bytes(resources.free_disk_bytes.unwrap_or(0))
+        ),
+        (true, Some(LimitingFactor::EmbeddingTime)) => format!(
+            "Fits; embedding takes about {}",
+            format_duration(embedding_secs.unwrap_or(0.0))
+        ),
+        (true, Some(LimitingFactor::DiskSpace)) => format!(
+            "Fits; uses about {} of {} free disk space",
+            format_bytes(disk_required_bytes),
+            format_bytes(resources.free_disk_bytes.unwrap_or(0))
+        ),
+        (true, None) => "Free disk space unknown; nothing to embed".to_string(),
+    };
+
+    ImportEstimate {
+        kind,
+        total_entries: manifest.entries.len(),
+        archive_bytes: manifest.archive_bytes,
+        uncompressed_bytes: manifest.uncompressed_bytes(),
+        items,
+        documents,
+        text_chars,
+        paragraph_chunks,
+        section_chunks,
+        db_growth_bytes,
+        disk_required_bytes,
+        free_disk_bytes: resources.free_disk_bytes,
+        embed_texts_per_sec,
+        throughput_measured: resources.embedder_available && resources.measured_texts_per_sec.is_some_and(|rate| rate > 0.0),
+        embedding_secs,
+        go,
+        limiting_factor,
+        reason,
+    }
+}
+
+/// Space available to this process on the disk holding `path`.
+pub fn available_disk_space(path: &Path) -> Option<u64> {
+    fs4::available_space(path).ok()
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn format_duration(secs: f64) -> String {
+    if secs >= 3600.0 {
+        format!("{:.1} h", secs / 3600.0)
+    } else {
+        format!("{:.0} min", (secs / 60.0).ceil())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(entries: &[(&str, u64)], archive_bytes: u64) -> ArchiveManifest {
+        ArchiveManifest {
+            entries: entries
+                .iter()
+                .map(|(name, size)| ArchiveEntry {
+                    name: name.to_string(),
+                    size: *size,
+                })
+                .collect(),
+            archive_bytes,
+        }
+    }
+
+    fn resources(free_disk_bytes: Option<u64>, measured: Option<f64>) -> PreflightResources {
+        PreflightResources {
+            free_disk_bytes,
+            embedder_available: true,
+            measured_texts_per_sec: measured,
+            embedding_dim: 384,
+        }
+    }
+
+    #[test]
+    fn test_detects_export_kind_from_entries() {
+        let chatgpt = manifest(&[("export/conversations.json", 10), ("export/user.json", 1)], 0);
+        assert_eq!(ExportKind::detect(&chatgpt), Some(ExportKind::ChatGpt));
+        let facebook = manifest(&[("messages/inbox/sam_1/message_1.json", 10), ("photos/a.jpg", 5)], 0);
+        assert_eq!(ExportKind::detect(&facebook), Some(ExportKind::Facebook));
+        assert_eq!(ExportKind::detect(&manifest(&[("notes.txt", 10)], 0)), None);
+    }
+
+    #[test]
+    fn test_facebook_estimate_arithmetic() {
+        let manifest = manifest(
+            &[
+                ("your_facebook_activity/posts/your_posts_1.json", 10 * 1024),
+                ("comments/comments.json", 2048),
+                ("messages/inbox/sam_1/message_1.json", 4096),
+                ("messages/inbox/kim_2/message_1.json", 2048),
+                ("photos/album/1.jpg", 1_000_000),
+                ("profile_information/profile.json", 50_000),
+            ],
+            900_000,
+        );
+        let estimate = estimate_import(ExportKind::Facebook, &manifest, &resources(Some(u64::MAX), Some(20.0)));
+
+        assert_eq!(
+            estimate.items,
+            ItemEstimate {
+                posts: 10,
+                comments: 4,
+                threads: 2,
+                media: 1,
+                total: 17,
+                ..Default::default()
+            }
+        );
+        assert_eq!(estimate.total_entries, 6);
+        assert_eq!(estimate.uncompressed_bytes, 1_068_432);
+        // Half of the 18 KB of posts, comments and threads, plus one media document
+        assert_eq!(estimate.text_chars, 9216 + 200);
+        // 9416 / 412 rounds up to 23, more than the 17 documents
+        assert_eq!((estimate.paragraph_chunks, estimate.section_chunks), (23, 17));
+        let db = (9416.0 * DB_BYTES_PER_TEXT_CHAR) as u64 + 40 * DB_BYTES_PER_CHUNK + 23 * 388;
+        assert_eq!(estimate.db_growth_bytes, db);
+        let disk = ((db + (9416.0 * EXPORT_BYTES_PER_TEXT_CHAR) as u64 + 1_000_000 + 900_000) as f64 * DISK_HEADROOM) as u64;
+        assert_eq!(estimate.disk_required_bytes, disk);
+        assert_eq!(estimate.embedding_secs, Some(23.0 / 20.0));
+        assert!(estimate.throughput_measured);
+        assert!(estimate.go);
+    }
+
+    #[test]
+    fn test_chatgpt_estimate_without_embedder() {
+        let manifest = manifest(&[("conversations.json", 240 * 1024), ("user.json", 100)], 60 * 1024);
+        let mut resources = resources(None, None);
+        resources.embedder_available = false;
+        let estimate = estimate_import(ExportKind::ChatGpt, &manifest, &resources);
+        assert_eq!(estimate.items.conversations, 10);
+        assert_eq!(estimate.documents, 10);
+        assert_eq!(estimate.text_chars, (240.0 * 1024.0 * CHATGPT_TEXT_RATIO) as u64);
+        assert_eq!((estimate.embed_texts_per_sec, estimate.embedding_secs), (None, None));
+        assert!(estimate.go);
+        assert_eq!(estimate.limiting_factor, None);
+    }
+
+    #[test]
+    fn test_disk_space_guard() {
+        // A 2 GB export of message threads
+        let threads: Vec<(String, u64)> =
+            (0..2000).map(|i| (format!("messages/inbox/t_{}/message_1.json", i), 1 << 20)).collect();
+        let entries: Vec<(&str, u64)> = threads.iter().map(|(name, size)| (name.as_str(), *size)).collect();
+        let manifest = manifest(&entries, 1 << 30);
+        let estimate = |free| estimate_import(ExportKind::Facebook, &manifest, &resources(free, None));
+
+        let tight = estimate(Some(1 << 30));
+        assert!(!tight.go);
+        assert_eq!(tight.limiting_factor, Some(LimitingFactor::DiskSpace));
+        assert!(tight.reason.starts_with("Needs about"));
+
+        let roomy = estimate(Some(tight.disk_required_bytes));
+        assert!(roomy.go);
+        // 2.5 million paragraphs at the default rate take days, which now
+        // limits more than the disk
+        assert!(!roomy.throughput_measured);
+        assert_eq!(roomy.embedding_secs, Some(roomy.paragraph_chunks as f64 / DEFAULT_EMBED_TEXTS_PER_SEC));
+        assert_eq!(roomy.limiting_factor, Some(LimitingFactor::EmbeddingTime));
+
+        // Unknown free space never blocks
+        assert!(estimate(None).go);
+    }
+
+    #[test]
+    fn test_manifest_is_read_without_extracting() {
+        use std::io::Write;
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("export/", options).unwrap();
+        zip.start_file("export/conversations.json", options).unwrap();
+        zip.write_all(&[b' '; 5000]).unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let len = bytes.len() as u64;
+        let manifest = ArchiveManifest::read(std::io::Cursor::new(bytes), len).unwrap();
+        assert_eq!(
+            manifest.entries,
+            [ArchiveEntry {
+                name: "export/conversations.json".into(),
+                size: 5000
+            }]
+        );
+        assert_eq!(manifest.archive_bytes, len);
+        assert!(ArchiveManifest::read(std::io::Cursor::new(b"not a zip".to_vec()), 9).is_err());
+    }
+}
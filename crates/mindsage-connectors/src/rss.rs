@@ -0,0 +1,289 @@
+//! RSS and Atom feeds — the `rss` connector.
+//!
+//! The connector's config lists the feeds to follow (`feeds`), filled in
+//! by hand or from an uploaded OPML file. A sync reads each feed's newest
+//! entries; every entry becomes a document keyed by its GUID (Atom `id`)
+//! within the feed, so an entry edited since the last sync replaces its
+//! document. The ETag and Last-Modified of each feed's last response are
+//! kept next to the connector's exports for conditional requests.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{ConnectorConfig, IndexDocument};
+use crate::xml::{self, Element};
+
+/// Entries read from one feed per sync when the config sets no cap.
+pub const DEFAULT_MAX_ITEMS_PER_FEED: usize = 50;
+/// Wait between two feed requests when the config sets none.
+pub const DEFAULT_POLITENESS_DELAY_MS: u64 = 1000;
+
+/// Conditional-request validators of the feeds, in the exports directory.
+const FEED_STATE_FILE: &str = ".feed-state.json";
+
+/// Settings of an `rss` connector, from its `config`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FeedSettings {
+    /// Feed URLs.
+    pub feeds: Vec<String>,
+    /// Newest entries read from each feed per sync.
+    pub max_items_per_feed: usize,
+    /// Wait between two feed requests of a sync.
+    pub politeness_delay_ms: u64,
+    /// Minutes between automatic syncs; 0 syncs only when asked.
+    pub poll_minutes: u64,
+}
+
+impl Default for FeedSettings {
+    fn default() -> Self {
+        Self {
+            feeds: Vec::new(),
+            max_items_per_feed: DEFAULT_MAX_ITEMS_PER_FEED,
+            politeness_delay_ms: DEFAULT_POLITENESS_DELAY_MS,
+            poll_minutes: 0,
+        }
+    }
+}
+
+impl ConnectorConfig {
+    /// The connector's feed settings; defaults for what its config lacks.
+    pub fn feed_settings(&self) -> FeedSettings {
+        serde_json::from_value(self.config.clone()).unwrap_or_default()
+    }
+}
+
+/// A parsed RSS or Atom feed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Feed {
+    pub title: Option<String>,
+    /// Entries in feed order, usually newest first.
+    pub entries: Vec<FeedEntry>,
+}
+
+/// One feed entry.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeedEntry {
+    /// RSS `guid`, Atom `id`, or the link when the feed has neither.
+    pub guid: String,
+    pub title: Option<String>,
+    pub link: Option<String>,
+    /// HTML body: `content:encoded` (Atom `content`), else the summary.
+    pub content: Option<String>,
+    /// Publication date (ms).
+    pub published_at: Option<i64>,
+}
+
+/// Parse an RSS 2.0, RSS 1.0 (RDF) or Atom feed. Entries with neither an
+/// id nor a link are left out.
+pub fn parse_feed(data: &str) -> Result<Feed, String> {
+    let root = xml::parse(data)?;
+    match root.name.as_str() {
+        "rss" => {
+            let channel = root.child("channel").ok_or("RSS feed without a channel")?;
+            Ok(Feed {
+                title: channel.child_text("title"),
+                entries: channel.children_named("item").filter_map(rss_entry).collect(),
+            })
+        }
+        "rdf:RDF" => Ok(Feed {
+            title: root.child("channel").and_then(|c| c.child_text("title")),
+            entries: root.children_named("item").filter_map(rss_entry).collect(),
+        }),
+        "feed" => Ok(Feed {
+            title: root.child_text("title"),
+            entries: root.children_named("entry").filter_map(atom_entry).collect(),
+        }),
+        other => Err(format!("Not an RSS or Atom feed (root element <{}>)", other)),
+    }
+}
+
+fn rss_entry(item: &Element) -> Option<FeedEntry> {
+    let link = item.child_text("link");
+    Some(FeedEntry {
+        guid: item.child_text("guid").or_else(|| link.clone())?,
+        title: item.child_text("title"),
+        content: item.child_text("content:encoded").or_else(|| item.child_text("description")),
+        published_at: item
+            .child_text("pubDate")
+            .and_then(|d| chrono::DateTime::parse_from_rfc2822(&d).ok())
+            .or_else(|| item.child_text("dc:date").and_then(|d| chrono::DateTime::parse_from_rfc3339(&d).ok()))
+            .map(|d| d.timestamp_millis()),
+        link,
+    })
+}
+
+fn atom_entry(entry: &Element) -> Option<FeedEntry> {
+    let link = entry
+        .children_named("link")
+        .find(|l| l.attr("rel").map_or(true, |rel| rel == "alternate"))
+        .and_then(|l| l.attr("href"))
+        .map(str::to_string);
+    Some(FeedEntry {
+        guid: entry.child_text("id").or_else(|| link.clone())?,
+        title: entry.child_text("title"),
+        content: entry.child_text("content").or_else(|| entry.child_text("summary")),
+        published_at: entry
+            .child_text("published")
+            .or_else(|| entry.child_text("updated"))
+            .and_then(|d| chrono::DateTime::parse_from_rfc3339(&d).ok())
+            .map(|d| d.timestamp_millis()),
+        link,
+    })
+}
+
+/// Feed URLs of an OPML file's outlines (`xmlUrl`), each once, in order.
+pub fn parse_opml(data: &str) -> Result<Vec<String>, String> {
+    let root = xml::parse(data)?;
+    if root.name != "opml" {
+        return Err(format!("Not an OPML file (root element <{}>)", root.name));
+    }
+    let mut outlines = Vec::new();
+    root.descendants_named("outline", &mut outlines);
+    let mut feeds: Vec<String> = Vec::new();
+    for url in outlines.iter().filter_map(|o| o.attr("xmlUrl")).map(str::trim) {
+        if !url.is_empty() && !feeds.iter().any(|f| f == url) {
+            feeds.push(url.to_string());
+        }
+    }
+    Ok(feeds)
+}
+
+/// Validators of a feed's last response, sent back as `If-None-Match` and
+/// `If-Modified-Since`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedValidators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+/// The validators saved in a connector's exports directory, by feed URL.
+pub fn load_feed_state(exports_dir: &Path) -> HashMap<String, FeedValidators> {
+    std::fs::read_to_string(exports_dir.join(FEED_STATE_FILE))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_feed_state(exports_dir: &Path, state: &HashMap<String, FeedValidators>) -> std::io::Result<()> {
+    let data = serde_json::to_string_pretty(state)?;
+    std::fs::write(exports_dir.join(FEED_STATE_FILE), data)
+}
+
+/// The document of `entry` from the feed at `feed_url`, with `text` (the
+/// entry's readable content) under its title.
+pub fn entry_document(feed_url: &str, feed: &Feed, entry: &FeedEntry, text: &str) -> IndexDocument {
+    let text = match &entry.title {
+        Some(title) if !text.starts_with(title.as_str()) => format!("{}\n\n{}", title, text),
+        _ => text.to_string(),
+    };
+    let mut metadata = serde_json::json!({
+        "source": "rss",
+        "type": "feed_entry",
+        "feedUrl": feed_url,
+    });
+    for (key, value) in [("title", &entry.title), ("url", &entry.link), ("feedTitle", &feed.title)] {
+        if let Some(value) = value {
+            metadata[key] = value.clone().into();
+        }
+    }
+    IndexDocument {
+        text: text.trim().to_string(),
+        metadata,
+        created_at: entry.published_at,
+        external_id: Some(format!("{}#{}", feed_url, entry.guid)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/">
+<channel><title>Garden Notes</title>
+<item><title>Pruning</title><link>https://garden.example/pruning</link>
+<guid isPermaLink="false">post-2</guid><pubDate>Tue, 05 Mar 2024 08:00:00 +0000</pubDate>
+<description>Short summary</description>
+<content:encoded><![CDATA[<p>Cut above an outward bud.</p>]]></content:encoded></item>
+<item><title>Seeds</title><link>https://garden.example/seeds</link>
+<description>&lt;p&gt;Sow thinly.&lt;/p&gt;</description></item>
+<item><title>No id</title></item>
+</channel></rss>"#;
+
+    const ATOM: &str = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Dev Log</title>
+<entry><id>tag:dev.example,2024:1</id><title>Release</title>
+<link rel="edit" href="https://dev.example/edit/1"/><link href="https://dev.example/1"/>
+<updated>2024-04-01T10:00:00Z</updated><summary type="html">Version 1 is out</summary></entry>
+</feed>"#;
+
+    #[test]
+    fn test_rss_entries_prefer_full_content() {
+        let feed = parse_feed(RSS).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Garden Notes"));
+        assert_eq!(feed.entries.len(), 2);
+        let first = &feed.entries[0];
+        assert_eq!(first.guid, "post-2");
+        assert_eq!(first.content.as_deref(), Some("<p>Cut above an outward bud.</p>"));
+        assert_eq!(first.published_at, Some(1_709_625_600_000));
+        // Without a guid the link identifies the entry
+        assert_eq!(feed.entries[1].guid, "https://garden.example/seeds");
+        assert_eq!(feed.entries[1].content.as_deref(), Some("<p>Sow thinly.</p>"));
+
+        let doc = entry_document("https://garden.example/feed", &feed, first, "Cut above an outward bud.");
+        assert_eq!(doc.text, "Pruning\n\nCut above an outward bud.");
+        assert_eq!(doc.external_id.as_deref(), Some("https://garden.example/feed#post-2"));
+        assert_eq!(doc.metadata["url"], "https://garden.example/pruning");
+        assert_eq!(doc.created_at, Some(1_709_625_600_000));
+    }
+
+    #[test]
+    fn test_atom_entries_and_opml() {
+        let feed = parse_feed(ATOM).unwrap();
+        let entry = &feed.entries[0];
+        assert_eq!(entry.guid, "tag:dev.example,2024:1");
+        assert_eq!(entry.link.as_deref(), Some("https://dev.example/1"));
+        assert_eq!(entry.content.as_deref(), Some("Version 1 is out"));
+        assert_eq!(entry.published_at, Some(1_711_965_600_000));
+        assert!(parse_feed("<html><body/></html>").is_err());
+
+        let opml = r#"<opml version="2.0"><head><title>Subscriptions</title></head><body>
+            <outline text="Blogs"><outline text="Garden" type="rss" xmlUrl="https://garden.example/feed"/>
+            <outline text="Dev" xmlUrl="https://dev.example/atom.xml"/></outline>
+            <outline text="Again" xmlUrl="https://garden.example/feed"/></body></opml>"#;
+        assert_eq!(
+            parse_opml(opml).unwrap(),
+            vec!["https://garden.example/feed", "https://dev.example/atom.xml"]
+        );
+        assert!(parse_opml(RSS).is_err());
+    }
+
+    #[test]
+    fn test_settings_and_validators_round_trip() {
+        let connector: ConnectorConfig = serde_json::from_value(serde_json::json!({
+            "id": "1", "name": "Feeds", "type": "rss", "status": "connected",
+            "config": { "feeds": ["https://garden.example/feed"], "maxItemsPerFeed": 5 }
+        }))
+        .unwrap();
+        let settings = connector.feed_settings();
+        assert_eq!(settings.max_items_per_feed, 5);
+        assert_eq!(settings.politeness_delay_ms, DEFAULT_POLITENESS_DELAY_MS);
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load_feed_state(dir.path()).is_empty());
+        let state = HashMap::from([(
+            "https://garden.example/feed".to_string(),
+            FeedValidators {
+                etag: Some("\"v1\"".into()),
+                last_modified: None,
+            },
+        )]);
+        save_feed_state(dir.path(), &state).unwrap();
+        assert_eq!(load_feed_state(dir.path()), state);
+    }
+}
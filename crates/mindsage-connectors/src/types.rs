@@ -2,6 +2,8 @@
 
 use serde::{Deserialize, Serialize};
 
+pub use mindsage_api_types::{FeedSyncCounts, RunStatus};
+
 /// Connector configuration persisted to connectors.json.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectorConfig {
@@ -18,6 +20,29 @@ pub struct ConnectorConfig {
     pub item_count: usize,
 }
 
+impl ConnectorConfig {
+    /// Where processed export items go, from `config.importMode`
+    /// (`direct` when unset or unknown).
+    pub fn import_mode(&self) -> ImportMode {
+        self.config
+            .get("importMode")
+            .cloned()
+            .and_then(|mode| serde_json::from_value(mode).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Where a connector's processed export items go.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Straight into the store as documents.
+    #[default]
+    Direct,
+    /// Into the staging table, to be approved or rejected item by item.
+    Staged,
+}
+
 /// Type of data connector.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -26,6 +51,8 @@ pub enum ConnectorType {
     Webhook,
     File,
     Custom,
+    /// RSS and Atom feeds, polled (see `rss`).
+    Rss,
 }
 
 /// Connector status.
@@ -48,31 +75,6 @@ pub struct CreateConnectorRequest {
     pub config: serde_json::Value,
 }
 
-/// Sync run status.
-#[derive(Debug, Clone, Serialize)]
-pub struct RunStatus {
-    pub running: bool,
-    pub output: Vec<String>,
-    #[serde(skip_serializing_if = "Option::is_none", rename = "lastRun")]
-    pub last_run: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none", rename = "exitCode")]
-    pub exit_code: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none", rename = "connectorId")]
-    pub connector_id: Option<String>,
-}
-
-impl Default for RunStatus {
-    fn default() -> Self {
-        Self {
-            running: false,
-            output: Vec::new(),
-            last_run: None,
-            exit_code: None,
-            connector_id: None,
-        }
-    }
-}
-
 /// Result of processing an import (ChatGPT or Facebook).
 #[derive(Debug, Clone, Serialize)]
 pub struct ImportResult {
@@ -85,6 +87,39 @@ pub struct ImportResult {
     pub details: Option<serde_json::Value>,
 }
 
+/// How far a ZIP import has got, reported after each archive entry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProgress {
+    pub entries_scanned: usize,
+    pub total_entries: usize,
+    /// Name of the entry just processed.
+    pub current_entry: String,
+    /// Items (posts, comments, threads or conversations) written so far.
+    pub items_emitted: usize,
+}
+
+/// An archive entry that could not be read, parsed or written, listed in
+/// `ImportResult.details.errors`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntryError {
+    pub entry: String,
+    pub error: String,
+}
+
+/// A document built from connector exports, ready for indexing.
+#[derive(Debug, Clone)]
+pub struct IndexDocument {
+    pub text: String,
+    pub metadata: serde_json::Value,
+    /// When the item was originally created at the source (ms), if known.
+    pub created_at: Option<i64>,
+    /// Stable id of the item within `metadata.source` (a ChatGPT
+    /// conversation id), when the source has one. Importing the item again
+    /// after it changed replaces its document instead of adding another.
+    pub external_id: Option<String>,
+}
+
 /// Pending media file info (Facebook import).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PendingMediaFile {
@@ -101,6 +136,37 @@ pub struct PendingMediaFile {
     pub stored_at: String,
     #[serde(rename = "storedPath")]
     pub stored_path: String,
+    /// EXIF and sidecar metadata, when the file has any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<MediaInfo>,
+    /// The document describing the file, once indexed.
+    #[serde(default, rename = "documentId", skip_serializing_if = "Option::is_none")]
+    pub document_id: Option<i64>,
+}
+
+/// What is known about a media file without looking at it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    /// Date taken (ms).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub taken_at: Option<i64>,
+    /// Coarse GPS position, to two decimals.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// Nearest place in the local geocoding table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub place: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<String>,
 }
 
 /// Pending media registry.
@@ -0,0 +1,274 @@
+//! A small, lenient XML reader for feeds and OPML files.
+//!
+//! Builds a tree of elements and text. Comments, processing instructions
+//! and the doctype are skipped, CDATA sections become text as they are, and
+//! the predefined and numeric entities are decoded. Mismatched end tags
+//! close the elements left open inside them; unknown ones are ignored.
+//! Namespaces are not resolved: `content:encoded` is just a name.
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Element {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Child elements named `name`, in order.
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter_map(move |node| match node {
+            Node::Element(e) if e.name == name => Some(e),
+            _ => None,
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find_map(|node| match node {
+            Node::Element(e) if e.name == name => Some(e),
+            _ => None,
+        })
+    }
+
+    /// All elements named `name` below this one, depth first.
+    pub fn descendants_named<'a>(&'a self, name: &str, found: &mut Vec<&'a Element>) {
+        for node in &self.children {
+            if let Node::Element(e) = node {
+                if e.name == name {
+                    found.push(e);
+                }
+                e.descendants_named(name, found);
+            }
+        }
+    }
+
+    /// The text inside this element, trimmed.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.collect_text(&mut text);
+        text.trim().to_string()
+    }
+
+    /// Trimmed text of the child `name`; `None` when missing or empty.
+    pub fn child_text(&self, name: &str) -> Option<String> {
+        self.child(name).map(Element::text).filter(|t| !t.is_empty())
+    }
+
+    fn collect_text(&self, out: &mut String) {
+        for node in &self.children {
+            match node {
+                Node::Text(t) => out.push_str(t),
+                Node::Element(e) => e.collect_text(out),
+            }
+        }
+    }
+}
+
+/// Parse `xml` into its root element.
+pub(crate) fn parse(xml: &str) -> Result<Element, String> {
+    // The bottom of the stack collects the document's top-level nodes
+    let mut stack: Vec<Element> = vec![Element::default()];
+    let mut rest = xml.trim_start_matches('\u{feff}');
+
+    while let Some(lt) = rest.find('<') {
+        push_text(&mut stack, &rest[..lt]);
+        rest = &rest[lt..];
+
+        if let Some(body) = rest.strip_prefix("<!--") {
+            rest = body.find("-->").map_or("", |end| &body[end + 3..]);
+        } else if let Some(body) = rest.strip_prefix("<![CDATA[") {
+            let end = body.find("]]>").unwrap_or(body.len());
+            top(&mut stack).children.push(Node::Text(body[..end].to_string()));
+            rest = body.get(end + 3..).unwrap_or("");
+        } else if let Some(body) = rest.strip_prefix("<?") {
+            rest = body.find("?>").map_or("", |end| &body[end + 2..]);
+        } else if rest.starts_with("<!") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+        } else if let Some(body) = rest.strip_prefix("</") {
+            let end = body.find('>').ok_or("unterminated end tag")?;
+            let name = body[..end].trim();
+            rest = &body[end + 1..];
+            if let Some(depth) = stack.iter().rposition(|e| e.name == name).filter(|&d| d > 0) {
+                while stack.len() > depth {
+                    close(&mut stack);
+                }
+            }
+        } else {
+            let end = tag_end(rest).ok_or("unterminated tag")?;
+            let tag = &rest[1..end];
+            rest = &rest[end + 1..];
+            let (tag, self_closing) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let element = start_tag(tag)?;
+            if self_closing {
+                top(&mut stack).children.push(Node::Element(element));
+            } else {
+                stack.push(element);
+            }
+        }
+    }
+    push_text(&mut stack, rest);
+    while stack.len() > 1 {
+        close(&mut stack);
+    }
+
+    let document = stack.pop().unwrap_or_default();
+    document
+        .children
+        .into_iter()
+        .find_map(|node| match node {
+            Node::Element(e) => Some(e),
+            Node::Text(_) => None,
+        })
+        .ok_or_else(|| "no root element".to_string())
+}
+
+fn top(stack: &mut [Element]) -> &mut Element {
+    stack.last_mut().expect("the document element is never popped")
+}
+
+fn close(stack: &mut Vec<Element>) {
+    if let Some(element) = stack.pop() {
+        top(stack).children.push(Node::Element(element));
+    }
+}
+
+fn push_text(stack: &mut [Element], text: &str) {
+    if !text.is_empty() {
+        top(stack).children.push(Node::Text(decode_entities(text)));
+    }
+}
+
+/// Index of the `>` ending the tag at the start of `rest`, outside quotes.
+fn tag_end(rest: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in rest.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Name and attributes of a start tag, without its `<` and `>`.
+fn start_tag(tag: &str) -> Result<Element, String> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = &tag[..name_end];
+    if name.is_empty() {
+        return Err("empty tag name".to_string());
+    }
+    let mut attrs = Vec::new();
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let key_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = rest[key_end..].trim_start();
+        let Some(value) = rest.strip_prefix('=') else {
+            attrs.push((key.to_string(), String::new()));
+            continue;
+        };
+        let value = value.trim_start();
+        let (raw, after) = match value.chars().next() {
+            Some(q @ ('"' | '\'')) => {
+                let end = value[1..].find(q).map_or(value.len(), |e| e + 1);
+                (&value[1..end], value.get(end + 1..).unwrap_or(""))
+            }
+            _ => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                (&value[..end], &value[end..])
+            }
+        };
+        attrs.push((key.to_string(), decode_entities(raw)));
+        rest = after.trim_start();
+    }
+    Ok(Element {
+        name: name.to_string(),
+        attrs,
+        children: Vec::new(),
+    })
+}
+
+/// Decode the five predefined entities and character references; anything
+/// else is kept as it is.
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').filter(|&semi| semi <= 10).and_then(|semi| {
+            let c = match &rest[1..semi] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                entity => {
+                    let number = entity.strip_prefix('#')?;
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => number.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, semi + 1))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_text_attributes_and_cdata() {
+        let root = parse(
+            r#"<?xml version="1.0"?><!-- feed --><rss version='2.0'><channel>
+            <title>Tom &amp; Jerry&#39;s</title><link href="a?b=1&amp;c=2"/>
+            <item><description><![CDATA[<p>Hi & bye</p>]]></description></item>
+            <unclosed><b>bold</channel></rss>"#,
+        )
+        .unwrap();
+        assert_eq!(root.name, "rss");
+        assert_eq!(root.attr("version"), Some("2.0"));
+        let channel = root.child("channel").unwrap();
+        assert_eq!(channel.child_text("title").unwrap(), "Tom & Jerry's");
+        assert_eq!(channel.child("link").unwrap().attr("href"), Some("a?b=1&c=2"));
+        let item = channel.child("item").unwrap();
+        assert_eq!(item.child_text("description").unwrap(), "<p>Hi & bye</p>");
+        // Elements left open are closed by their parent's end tag
+        assert_eq!(channel.child("unclosed").unwrap().text(), "bold");
+        assert!(parse("just text").is_err());
+    }
+}
@@ -24,16 +24,32 @@ impl ConsolidationPipeline {
         // Stage 2: Deduplicate content
         report.duplicates_removed = Self::deduplicate(store);
 
-        // Stage 3: Evict if over capacity
+        // Stage 3: Give documents with only section chunks paragraph chunks
+        report.documents_reconciled = Self::reconcile_levels(store);
+
+        // Stage 4: Evict if over capacity
         report.documents_evicted = Self::evict(store, &thresholds);
 
+        // Stage 5: Rebuild stale document centroids for similarity search
+        report.centroids_refreshed = Self::refresh_centroids(store);
+
+        // Stage 6: Rewrite embeddings stored in an older quantization format
+        report.embeddings_requantized = Self::requantize(store);
+
+        // Stage 7: Build the ANN index, or rebuild it once deletions have thinned it out
+        report.ann_index_rebuilt = Self::maintain_ann_index(store);
+
         report.duration_ms = start.elapsed().as_millis() as u64;
 
         info!(
-            "Consolidation complete: pruned={}, deduped={}, evicted={}, duration={}ms",
+            "Consolidation complete: pruned={}, deduped={}, reconciled={}, evicted={}, centroids={}, requantized={}, ann_rebuilt={}, duration={}ms",
             report.orphans_pruned,
             report.duplicates_removed,
+            report.documents_reconciled,
             report.documents_evicted,
+            report.centroids_refreshed,
+            report.embeddings_requantized,
+            report.ann_index_rebuilt,
             report.duration_ms
         );
 
@@ -72,6 +88,71 @@ impl ConsolidationPipeline {
         }
     }
 
+    /// Add paragraph chunks to documents imported with sections only, so
+    /// they are embedded and found like any other document.
+    fn reconcile_levels(store: &SqliteStore) -> usize {
+        match store.add_missing_paragraph_chunks() {
+            Ok(count) => {
+                if count > 0 {
+                    info!("Added paragraph chunks to {} documents with sections only", count);
+                }
+                count
+            }
+            Err(e) => {
+                tracing::warn!("Failed to reconcile chunk levels: {}", e);
+                0
+            }
+        }
+    }
+
+    /// Recompute document centroids invalidated by new embeddings or deletions.
+    fn refresh_centroids(store: &SqliteStore) -> usize {
+        match store.refresh_doc_centroids() {
+            Ok(count) => {
+                if count > 0 {
+                    info!("Refreshed {} document centroids", count);
+                }
+                count
+            }
+            Err(e) => {
+                tracing::warn!("Failed to refresh centroids: {}", e);
+                0
+            }
+        }
+    }
+
+    /// Upgrade embeddings to the store's active quantization format.
+    fn requantize(store: &SqliteStore) -> usize {
+        match store.requantize_embeddings() {
+            Ok(count) => {
+                if count > 0 {
+                    info!("Requantized {} embeddings", count);
+                }
+                count
+            }
+            Err(e) => {
+                tracing::warn!("Failed to requantize embeddings: {}", e);
+                0
+            }
+        }
+    }
+
+    /// Build, rebuild or drop the ANN index as the store changes.
+    fn maintain_ann_index(store: &SqliteStore) -> bool {
+        match store.maintain_ann_index() {
+            Ok(rebuilt) => {
+                if rebuilt {
+                    info!("Built ANN index");
+                }
+                rebuilt
+            }
+            Err(e) => {
+                tracing::warn!("Failed to maintain ANN index: {}", e);
+                false
+            }
+        }
+    }
+
     /// Evict oldest documents if storage exceeds tier capacity.
     #[allow(clippy::cast_possible_truncation)]
     fn evict(store: &SqliteStore, thresholds: &ConsolidationThresholds) -> usize {
@@ -165,10 +246,31 @@ mod tests {
         assert_eq!(stats.total_documents, 2);
     }
 
+    #[test]
+    fn test_pipeline_adds_paragraphs_to_section_only_documents() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Sailing log. Wind from the west.", AddDocumentOptions::default()).unwrap();
+        let section = store
+            .add_chunk(doc_id, "Sailing log. Wind from the west.", 0, 0, None, Some(0), Some(32), None, None, None)
+            .unwrap();
+        assert!(store.bm25_search("wind", Some(1), 10).unwrap().is_empty());
+
+        let report = ConsolidationPipeline::run(&store, CapabilityTier::Base);
+        assert_eq!(report.documents_reconciled, 1);
+        let hits = store.bm25_search("wind", Some(1), 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].parent_chunk_id, hits[0].char_end), (Some(section), Some(32)));
+        assert_eq!(store.get_chunks_without_embedding(10).unwrap().len(), 1);
+
+        // Once reconciled there is nothing left to do
+        let report = ConsolidationPipeline::run(&store, CapabilityTier::Base);
+        assert_eq!(report.documents_reconciled, 0);
+    }
+
     #[test]
     fn test_consolidation_stages() {
         let stages = ConsolidationStage::all();
-        assert_eq!(stages.len(), 4);
+        assert_eq!(stages.len(), 8);
         assert!(stages.contains(&ConsolidationStage::PruneOrphans));
         assert!(stages.contains(&ConsolidationStage::Evict));
     }
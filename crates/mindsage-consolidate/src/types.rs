@@ -8,8 +8,12 @@ use serde::Serialize;
 pub enum ConsolidationStage {
     PruneOrphans,
     Deduplicate,
+    ReconcileLevels,
     Compress,
     Evict,
+    RefreshCentroids,
+    RequantizeEmbeddings,
+    MaintainAnnIndex,
 }
 
 impl ConsolidationStage {
@@ -17,8 +21,12 @@ impl ConsolidationStage {
         &[
             Self::PruneOrphans,
             Self::Deduplicate,
+            Self::ReconcileLevels,
             Self::Compress,
             Self::Evict,
+            Self::RefreshCentroids,
+            Self::RequantizeEmbeddings,
+            Self::MaintainAnnIndex,
         ]
     }
 }
@@ -30,10 +38,20 @@ pub struct ConsolidationReport {
     pub orphans_pruned: usize,
     #[serde(rename = "duplicatesRemoved")]
     pub duplicates_removed: usize,
+    /// Documents that had only section chunks and were given paragraph
+    /// chunks.
+    #[serde(rename = "documentsReconciled")]
+    pub documents_reconciled: usize,
     #[serde(rename = "chunksCompressed")]
     pub chunks_compressed: usize,
     #[serde(rename = "documentsEvicted")]
     pub documents_evicted: usize,
+    #[serde(rename = "centroidsRefreshed")]
+    pub centroids_refreshed: usize,
+    #[serde(rename = "embeddingsRequantized")]
+    pub embeddings_requantized: usize,
+    #[serde(rename = "annIndexRebuilt")]
+    pub ann_index_rebuilt: bool,
     #[serde(rename = "durationMs")]
     pub duration_ms: u64,
 }
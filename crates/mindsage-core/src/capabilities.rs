@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 
 /// Hardware capability tier that determines available features.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum CapabilityTier {
     /// FTS5 + graph only, no embeddings or local LLM.
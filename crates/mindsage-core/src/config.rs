@@ -1,8 +1,13 @@
 //! Configuration and data directory management.
 
 use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::{Path, PathBuf};
 
+use crate::capabilities::{CapabilityTier, DeviceCapabilities};
+use crate::events::EventCategory;
+use crate::redact::Secret;
+
 /// Paths to all MindSage data directories.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DataPaths {
@@ -22,15 +27,47 @@ pub struct DataPaths {
     pub browser_connector: PathBuf,
     /// LLM configuration (`data/llm-config.json`).
     pub llm_config_file: PathBuf,
-    /// Indexed files tracking (`data/.indexed-files.json`).
+    /// Legacy indexed files tracking (`data/.indexed-files.json`), imported
+    /// into the store on startup.
     pub indexed_files: PathBuf,
+    /// Append-only log of outbound LLM requests (`data/audit.log`).
+    pub audit_log: PathBuf,
+    /// LocalSend TLS certificate and key (`data/localsend/`).
+    pub localsend: PathBuf,
+    /// Known LocalSend devices and transfer history
+    /// (`data/localsend-devices.json`).
+    pub localsend_devices_file: PathBuf,
+    /// Indexing jobs left queued at shutdown, re-queued on the next start
+    /// (`data/indexing-queue.json`).
+    pub indexing_queue_file: PathBuf,
+    /// Data directories of the non-default profiles (`data/profiles/<name>/`).
+    pub profiles: PathBuf,
+    /// Outbound webhook endpoints (`data/webhooks.json`).
+    pub webhooks_file: PathBuf,
+    /// Webhook deliveries that failed every attempt
+    /// (`data/webhooks-dead-letter.jsonl`).
+    pub webhooks_dead_letter: PathBuf,
+    /// Resumable uploads in progress (`data/upload-sessions/<id>/`).
+    pub upload_sessions: PathBuf,
+    /// Optional place table for photo locations (`data/geocoding.csv`,
+    /// `name,latitude,longitude` per line).
+    pub geocoding_file: PathBuf,
+    /// Search post-processing settings changed at runtime.
+    pub search_settings_file: PathBuf,
 }
 
 impl DataPaths {
     /// Create data paths from a root directory. Creates directories if needed.
     pub fn new(root: impl AsRef<Path>) -> std::io::Result<Self> {
+        let paths = Self::existing(root);
+        paths.ensure_dirs()?;
+        Ok(paths)
+    }
+
+    /// Data paths under a root directory, creating nothing; for read-only mode.
+    pub fn existing(root: impl AsRef<Path>) -> Self {
         let root = root.as_ref().to_path_buf();
-        let paths = Self {
+        Self {
             vectordb: root.join("vectordb"),
             uploads: root.join("uploads"),
             imports: root.join("imports"),
@@ -39,10 +76,18 @@ impl DataPaths {
             browser_connector: root.join("browser-connector"),
             llm_config_file: root.join("llm-config.json"),
             indexed_files: root.join(".indexed-files.json"),
+            audit_log: root.join("audit.log"),
+            localsend: root.join("localsend"),
+            localsend_devices_file: root.join("localsend-devices.json"),
+            indexing_queue_file: root.join("indexing-queue.json"),
+            profiles: root.join("profiles"),
+            webhooks_file: root.join("webhooks.json"),
+            webhooks_dead_letter: root.join("webhooks-dead-letter.jsonl"),
+            upload_sessions: root.join("upload-sessions"),
+            geocoding_file: root.join("geocoding.csv"),
+            search_settings_file: root.join("search-settings.json"),
             root,
-        };
-        paths.ensure_dirs()?;
-        Ok(paths)
+        }
     }
 
     /// Create all required directories.
@@ -52,19 +97,244 @@ impl DataPaths {
         std::fs::create_dir_all(&self.imports)?;
         std::fs::create_dir_all(&self.exports)?;
         std::fs::create_dir_all(&self.browser_connector)?;
+        std::fs::create_dir_all(&self.upload_sessions)?;
         Ok(())
     }
 }
 
+/// An outbound webhook: events are POSTed to `url` as the same JSON frames
+/// the `/api/events` WebSocket sends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookEndpoint {
+    pub id: String,
+    pub url: String,
+    /// Key for the `X-MindSage-Signature` HMAC-SHA256 header; requests are
+    /// unsigned without one.
+    #[serde(default, serialize_with = "Secret::serialize_exposed", skip_serializing_if = "Option::is_none")]
+    pub secret: Option<Secret>,
+    /// Categories delivered to this endpoint; empty means all.
+    #[serde(default)]
+    pub events: Vec<EventCategory>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl WebhookEndpoint {
+    /// Whether events of `category` go to this endpoint.
+    pub fn accepts(&self, category: EventCategory) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.contains(&category))
+    }
+}
+
+/// Read the webhook endpoints saved in `path`; none if the file is missing
+/// or unreadable.
+pub fn load_webhooks(path: &Path) -> Vec<WebhookEndpoint> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// How search hits are re-ranked after retrieval, the same way for the
+/// search routes, chat context and the resolver: hits containing the
+/// query's words are boosted, then picked by MMR with a cap per document.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(default)]
+pub struct SearchPostProcessing {
+    /// Share of the best hit's score added to a hit containing every query
+    /// term, pro rata for fewer; 0 turns the boost off.
+    pub entity_boost: f64,
+    /// Query words shorter than this (in characters) do not count for the
+    /// boost.
+    pub min_term_length: usize,
+    /// Most hits kept from one document; `None` for no cap.
+    pub max_chunks_per_doc: Option<usize>,
+    /// Relevance weight of the MMR selection, 0 to 1; lower values favour
+    /// variety.
+    pub mmr_lambda: f64,
+}
+
+impl Default for SearchPostProcessing {
+    fn default() -> Self {
+        Self {
+            entity_boost: 0.15,
+            min_term_length: 3,
+            max_chunks_per_doc: Some(3),
+            mmr_lambda: 0.7,
+        }
+    }
+}
+
+impl SearchPostProcessing {
+    /// Why the settings are unusable, if they are.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if !(self.entity_boost.is_finite() && (0.0..=1.0).contains(&self.entity_boost)) {
+            return Err("entity_boost must be between 0 and 1".to_string());
+        }
+        if self.min_term_length == 0 {
+            return Err("min_term_length must be at least 1".to_string());
+        }
+        if self.max_chunks_per_doc == Some(0) {
+            return Err("max_chunks_per_doc must be at least 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.mmr_lambda) {
+            return Err("mmr_lambda must be between 0 and 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Read the settings saved in `path`; the defaults if the file is
+    /// missing, unreadable or invalid.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|data| serde_json::from_str::<Self>(&data).ok())
+            .filter(|settings| settings.validate().is_ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let data = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, data)
+    }
+}
+
+/// Name of the profile that uses the data directory itself. Requests that
+/// select no profile use it.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Whether `name` can be used as a profile name: 1–32 lowercase letters,
+/// digits, `-` or `_`, so it is safe as a directory name.
+pub fn is_valid_profile_name(name: &str) -> bool {
+    (1..=32).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+}
+
 /// Top-level MindSage configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MindSageConfig {
     /// HTTP server port.
     pub port: u16,
+    /// Address the HTTP API binds to (`MINDSAGE_BIND`, default `127.0.0.1`;
+    /// `0.0.0.0` serves every network interface).
+    pub bind_address: IpAddr,
+    /// Browser origins allowed to call the API, as exact origins such as
+    /// `http://localhost:5173`, or `*` for any (`MINDSAGE_CORS_ORIGINS`,
+    /// comma-separated). By default only the API's own loopback origins.
+    pub cors_origins: Vec<String>,
     /// Data directory paths.
     pub data_paths: DataPaths,
     /// Embedding dimension (384 for all-MiniLM-L6-v2).
     pub embedding_dim: usize,
+    /// Per-client request rate limits.
+    pub rate_limit: RateLimitConfig,
+    /// Record successful search queries for autocomplete suggestions.
+    pub query_log: bool,
+    /// Per-search records for relevance tuning (`MINDSAGE_QUERY_STATS`,
+    /// off by default).
+    pub query_stats: QueryStatsConfig,
+    /// Store new embeddings with per-block int8 scales instead of one
+    /// scale per vector (`MINDSAGE_QUANTIZATION=block`).
+    pub block_quantization: bool,
+    /// BM25 weights of a chunk's text and of its extracted enriched text
+    /// in full-text search (`MINDSAGE_FTS_WEIGHTS=<text>,<enriched>`,
+    /// default `1,0.25`).
+    pub fts_weights: (f64, f64),
+    /// Factor (0 to 1) applied to the vector score of embeddings whose
+    /// chunk text was edited after embedding, until they are re-embedded
+    /// (`MINDSAGE_STALE_EMBEDDING_WEIGHT`, default 1: not down-weighted).
+    pub text_stale_weight: f32,
+    /// Normalization applied to passages and queries before embedding:
+    /// comma-separated `nfc`, `casefold`, `accents`
+    /// (`MINDSAGE_EMBED_NORMALIZE`, default none).
+    pub embed_normalization: String,
+    /// Keep the full prompt text in privacy audit entries, for debugging
+    /// (`MINDSAGE_AUDIT_PROMPTS=on`). Off by default: entries hold a hash.
+    pub audit_prompts: bool,
+    /// Print file names, titles and captured content in log lines instead
+    /// of redacting them, for local debugging (`MINDSAGE_LOG_UNREDACTED=on`).
+    pub log_unredacted: bool,
+    /// Index files dropped into `data/imports/` without an explicit import
+    /// call (`MINDSAGE_WATCH_IMPORTS=on`).
+    pub watch_imports: bool,
+    /// With the imports watcher on, delete a file's document when the file
+    /// is removed from `data/imports/` (`MINDSAGE_WATCH_IMPORTS_DELETE=on`).
+    pub watch_imports_delete: bool,
+    /// Store text shared over LocalSend as a note document instead of a
+    /// file in `data/uploads/` (on by default; `MINDSAGE_LOCALSEND_TEXT_NOTES=off`).
+    pub localsend_text_notes: bool,
+    /// Port of the LocalSend protocol listener (`MINDSAGE_LOCALSEND_PORT`,
+    /// default 53317; `0` disables the listener).
+    pub localsend_port: u16,
+    /// Address of the LocalSend protocol listener (`MINDSAGE_LOCALSEND_BIND`,
+    /// default `0.0.0.0` so phones on the network can reach it).
+    pub localsend_bind: IpAddr,
+    /// Serve the LocalSend protocol over HTTPS with a self-signed
+    /// certificate (default; `MINDSAGE_LOCALSEND_PROTOCOL=http` for plain HTTP).
+    pub localsend_https: bool,
+    /// Trust level for LocalSend devices seen for the first time: `ask`
+    /// (default), `trusted` or `blocked` (`MINDSAGE_LOCALSEND_DEFAULT_TRUST`).
+    pub localsend_default_trust: String,
+    /// Seconds allowed for a graceful shutdown (finishing the current
+    /// indexing job, saving the queue, checkpointing the database) before
+    /// the process exits anyway (`MINDSAGE_SHUTDOWN_TIMEOUT`, default 30).
+    pub shutdown_timeout_secs: u64,
+    /// Seconds of recording per chunk of a WebVTT/SRT transcript
+    /// (`MINDSAGE_TRANSCRIPT_WINDOW_SECS`, default 120).
+    pub transcript_window_secs: u64,
+    /// Automatic retries of an indexing job that failed with a transient
+    /// error (`MINDSAGE_INDEXING_MAX_RETRIES`, default 3; 0 disables).
+    pub indexing_max_retries: u32,
+    /// Delay before the first automatic retry, doubled for each further
+    /// one (`MINDSAGE_INDEXING_RETRY_BASE_MS`, default 2000).
+    pub indexing_retry_base_ms: u64,
+    /// Days a staged connector item waits for approval before it is
+    /// discarded (`MINDSAGE_STAGED_TTL_DAYS`, default 30; 0 keeps items
+    /// until they are approved or rejected).
+    pub staged_ttl_days: u64,
+    /// Days between scheduled digests (`MINDSAGE_DIGEST_INTERVAL_DAYS`,
+    /// default 0 = off). Each scheduled run consolidates the store, then
+    /// stores a digest of the days since the last one.
+    pub digest_interval_days: u64,
+    /// Byte caps of the data areas, checked before files are written
+    /// there (`MINDSAGE_QUOTA_<AREA>=<megabytes>[:reject|evict]`).
+    pub disk_quotas: DiskQuotas,
+    /// Serve Swagger UI for the OpenAPI document at `/api/docs`
+    /// (`MINDSAGE_SWAGGER_UI=on`). `/api/openapi.json` is always served.
+    pub swagger_ui: bool,
+    /// Open the data directory without changing anything in it
+    /// (`MINDSAGE_READ_ONLY=on`): the store is opened read-only, background
+    /// writers are not started, and mutating routes answer 403. Also turns
+    /// off the query log and the imports watcher.
+    pub read_only: bool,
+    /// Outbound webhooks, loaded from `data/webhooks.json` and edited
+    /// through `PUT /api/config/webhooks`.
+    pub webhooks: Vec<WebhookEndpoint>,
+    /// How search hits are re-ranked, loaded from
+    /// `data/search-settings.json` and edited through
+    /// `PUT /api/vector-store/search/settings`.
+    pub search_post_processing: SearchPostProcessing,
+}
+
+/// Full-text weights of chunk text and enriched text when
+/// `MINDSAGE_FTS_WEIGHTS` is unset or invalid.
+pub const DEFAULT_FTS_WEIGHTS: (f64, f64) = (1.0, 0.25);
+
+/// Parse `<text>,<enriched>` weights: finite, non-negative, not both zero.
+fn parse_fts_weights(value: &str) -> Option<(f64, f64)> {
+    let (text, enriched) = value.split_once(',')?;
+    let text: f64 = text.trim().parse().ok()?;
+    let enriched: f64 = enriched.trim().parse().ok()?;
+    let valid = |w: f64| w.is_finite() && w >= 0.0;
+    (valid(text) && valid(enriched) && text + enriched > 0.0).then_some((text, enriched))
 }
 
 impl MindSageConfig {
@@ -75,12 +345,365 @@ impl MindSageConfig {
             .and_then(|p| p.parse().ok())
             .unwrap_or(3003);
 
-        let data_paths = DataPaths::new(data_dir)?;
+        let bind_address = std::env::var("MINDSAGE_BIND")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        let cors_origins = std::env::var("MINDSAGE_CORS_ORIGINS")
+            .ok()
+            .map(|v| {
+                v.split(',')
+                    .map(|o| o.trim().trim_end_matches('/').to_string())
+                    .filter(|o| !o.is_empty())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|origins| !origins.is_empty())
+            .unwrap_or_else(|| vec![format!("http://localhost:{}", port), format!("http://127.0.0.1:{}", port)]);
+
+        let read_only = std::env::var("MINDSAGE_READ_ONLY")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "on" | "true" | "enabled"))
+            .unwrap_or(false);
+
+        let data_paths = if read_only {
+            DataPaths::existing(data_dir)
+        } else {
+            DataPaths::new(data_dir)?
+        };
+
+        let mut rate_limit = RateLimitConfig::for_tier(DeviceCapabilities::discover().tier);
+        if let Ok(value) = std::env::var("MINDSAGE_RATE_LIMIT") {
+            if matches!(value.to_lowercase().as_str(), "0" | "off" | "false" | "disabled") {
+                rate_limit.enabled = false;
+            }
+        }
+
+        let query_log = !read_only
+            && !std::env::var("MINDSAGE_QUERY_LOG")
+                .map(|v| matches!(v.to_lowercase().as_str(), "0" | "off" | "false" | "disabled"))
+                .unwrap_or(false);
+
+        let mut query_stats = QueryStatsConfig::default();
+        if !read_only {
+            query_stats.mode = match std::env::var("MINDSAGE_QUERY_STATS").map(|v| v.trim().to_lowercase()).as_deref() {
+                Ok("on" | "plain" | "true" | "1") => QueryStatsMode::Plain,
+                Ok("hashed") => QueryStatsMode::Hashed,
+                _ => QueryStatsMode::Off,
+            };
+        }
+        query_stats.capture = std::env::var("MINDSAGE_QUERY_STATS_CAPTURE")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "on" | "true" | "enabled"))
+            .unwrap_or(false);
+        if let Some(ms) = std::env::var("MINDSAGE_QUERY_STATS_SLOW_MS").ok().and_then(|s| s.trim().parse().ok()) {
+            query_stats.slow_ms = ms;
+        }
+        if let Some(days) = std::env::var("MINDSAGE_QUERY_STATS_RETENTION_DAYS")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .filter(|&days: &u32| days > 0)
+        {
+            query_stats.retention_days = days;
+        }
+
+        let block_quantization = std::env::var("MINDSAGE_QUANTIZATION")
+            .map(|v| v.eq_ignore_ascii_case("block"))
+            .unwrap_or(false);
+
+        let fts_weights = std::env::var("MINDSAGE_FTS_WEIGHTS")
+            .ok()
+            .and_then(|v| parse_fts_weights(&v))
+            .unwrap_or(DEFAULT_FTS_WEIGHTS);
+
+        let text_stale_weight = std::env::var("MINDSAGE_STALE_EMBEDDING_WEIGHT")
+            .ok()
+            .and_then(|v| v.trim().parse::<f32>().ok())
+            .filter(|w| (0.0..=1.0).contains(w))
+            .unwrap_or(1.0);
+
+        let embed_normalization = std::env::var("MINDSAGE_EMBED_NORMALIZE").unwrap_or_default();
+
+        let audit_prompts = std::env::var("MINDSAGE_AUDIT_PROMPTS")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "on" | "true" | "enabled"))
+            .unwrap_or(false);
+
+        let log_unredacted = std::env::var("MINDSAGE_LOG_UNREDACTED")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "on" | "true" | "enabled"))
+            .unwrap_or(false);
+
+        let watch_imports = !read_only
+            && std::env::var("MINDSAGE_WATCH_IMPORTS")
+                .map(|v| matches!(v.to_lowercase().as_str(), "1" | "on" | "true" | "enabled"))
+                .unwrap_or(false);
+
+        let watch_imports_delete = std::env::var("MINDSAGE_WATCH_IMPORTS_DELETE")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "on" | "true" | "enabled"))
+            .unwrap_or(false);
+
+        let localsend_text_notes = std::env::var("MINDSAGE_LOCALSEND_TEXT_NOTES")
+            .map(|v| !matches!(v.to_lowercase().as_str(), "0" | "off" | "false" | "disabled"))
+            .unwrap_or(true);
+
+        let localsend_port = std::env::var("MINDSAGE_LOCALSEND_PORT")
+            .ok()
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(53317);
+
+        let localsend_bind = std::env::var("MINDSAGE_LOCALSEND_BIND")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        let localsend_https = std::env::var("MINDSAGE_LOCALSEND_PROTOCOL")
+            .map(|v| !v.eq_ignore_ascii_case("http"))
+            .unwrap_or(true);
+
+        let localsend_default_trust = std::env::var("MINDSAGE_LOCALSEND_DEFAULT_TRUST")
+            .map(|v| v.to_lowercase())
+            .unwrap_or_else(|_| "ask".to_string());
+
+        let shutdown_timeout_secs = std::env::var("MINDSAGE_SHUTDOWN_TIMEOUT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(30);
+
+        let transcript_window_secs = std::env::var("MINDSAGE_TRANSCRIPT_WINDOW_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&secs: &u64| secs > 0)
+            .unwrap_or(120);
+
+        let indexing_max_retries = std::env::var("MINDSAGE_INDEXING_MAX_RETRIES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3);
+
+        let indexing_retry_base_ms = std::env::var("MINDSAGE_INDEXING_RETRY_BASE_MS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(2000);
+
+        let staged_ttl_days = std::env::var("MINDSAGE_STAGED_TTL_DAYS")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(30);
+
+        let digest_interval_days = std::env::var("MINDSAGE_DIGEST_INTERVAL_DAYS")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(0);
+
+        let quota = |var: &str| std::env::var(var).ok().and_then(|v| AreaQuota::parse(&v));
+        let disk_quotas = DiskQuotas {
+            uploads: quota("MINDSAGE_QUOTA_UPLOADS"),
+            imports: quota("MINDSAGE_QUOTA_IMPORTS"),
+            exports: quota("MINDSAGE_QUOTA_EXPORTS"),
+            browser: quota("MINDSAGE_QUOTA_BROWSER"),
+        };
+
+        let swagger_ui = std::env::var("MINDSAGE_SWAGGER_UI")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "on" | "true" | "enabled"))
+            .unwrap_or(false);
+
+        let webhooks = load_webhooks(&data_paths.webhooks_file);
+        let search_post_processing = SearchPostProcessing::load(&data_paths.search_settings_file);
 
         Ok(Self {
             port,
+            bind_address,
+            cors_origins,
             data_paths,
             embedding_dim: 384,
+            rate_limit,
+            query_log,
+            query_stats,
+            block_quantization,
+            fts_weights,
+            text_stale_weight,
+            embed_normalization,
+            audit_prompts,
+            log_unredacted,
+            watch_imports,
+            watch_imports_delete,
+            localsend_text_notes,
+            localsend_port,
+            localsend_bind,
+            localsend_https,
+            localsend_default_trust,
+            shutdown_timeout_secs,
+            transcript_window_secs,
+            indexing_max_retries,
+            indexing_retry_base_ms,
+            staged_ttl_days,
+            digest_interval_days,
+            disk_quotas,
+            swagger_ui,
+            read_only,
+            webhooks,
+            search_post_processing,
+        })
+    }
+
+    /// Configuration for a non-default profile: the same settings with data
+    /// paths under `data/profiles/<name>/`, creating them if needed unless
+    /// read-only, and the profile's own webhooks and search settings. The LLM configuration file
+    /// stays shared with the default profile.
+    pub fn for_profile(&self, name: &str) -> std::io::Result<Self> {
+        let mut config = self.clone();
+        let root = self.data_paths.profiles.join(name);
+        config.data_paths = if self.read_only { DataPaths::existing(root) } else { DataPaths::new(root)? };
+        config.data_paths.llm_config_file = self.data_paths.llm_config_file.clone();
+        config.webhooks = load_webhooks(&config.data_paths.webhooks_file);
+        config.search_post_processing = SearchPostProcessing::load(&config.data_paths.search_settings_file);
+        Ok(config)
+    }
+}
+
+/// How searches are recorded in the query stats log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum QueryStatsMode {
+    /// Nothing is recorded.
+    #[default]
+    Off,
+    /// The normalized query text is kept.
+    Plain,
+    /// Only the SHA-256 of the normalized query is kept.
+    Hashed,
+}
+
+/// Settings of the query stats log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct QueryStatsConfig {
+    pub mode: QueryStatsMode,
+    /// Keep full diagnostics of slow and zero-hit searches
+    /// (`MINDSAGE_QUERY_STATS_CAPTURE=on`).
+    pub capture: bool,
+    /// Latency from which a search is slow (`MINDSAGE_QUERY_STATS_SLOW_MS`,
+    /// default 500).
+    pub slow_ms: u64,
+    /// Days a record is kept (`MINDSAGE_QUERY_STATS_RETENTION_DAYS`,
+    /// default 30).
+    pub retention_days: u32,
+}
+
+impl Default for QueryStatsConfig {
+    fn default() -> Self {
+        Self {
+            mode: QueryStatsMode::Off,
+            capture: false,
+            slow_ms: 500,
+            retention_days: 30,
+        }
+    }
+}
+
+/// What happens to a write that would take an area past its quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaPolicy {
+    /// Refuse the write.
+    #[default]
+    Reject,
+    /// Delete the area's oldest files until the write fits.
+    Evict,
+}
+
+/// Byte cap of one data area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AreaQuota {
+    pub max_bytes: u64,
+    pub policy: QuotaPolicy,
+}
+
+impl AreaQuota {
+    /// Parse `<megabytes>[:reject|evict]`, e.g. `500` or `2048:evict`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (size, policy) = match value.trim().split_once(':') {
+            Some((size, policy)) => (size, policy.trim()),
+            None => (value.trim(), "reject"),
+        };
+        let megabytes: u64 = size.trim().parse().ok().filter(|&mb| mb > 0)?;
+        let policy = match policy.to_lowercase().as_str() {
+            "reject" => QuotaPolicy::Reject,
+            "evict" => QuotaPolicy::Evict,
+            _ => return None,
+        };
+        Some(Self {
+            max_bytes: megabytes * 1024 * 1024,
+            policy,
         })
     }
 }
+
+/// Disk quotas of the data areas; an area without one is unbounded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskQuotas {
+    /// `data/uploads/`, where LocalSend saves received files.
+    pub uploads: Option<AreaQuota>,
+    /// `data/imports/`, where uploads land before indexing.
+    pub imports: Option<AreaQuota>,
+    /// Each connector's directory in `data/exports/`, separately.
+    pub exports: Option<AreaQuota>,
+    /// Captured conversations in `data/browser-connector/`.
+    pub browser: Option<AreaQuota>,
+}
+
+/// Token bucket budget for one route class.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitBudget {
+    /// Maximum requests allowed in a burst (bucket capacity).
+    pub burst: u32,
+    /// Sustained requests per minute (refill rate).
+    pub per_minute: u32,
+}
+
+impl RateLimitBudget {
+    pub const fn new(burst: u32, per_minute: u32) -> Self {
+        Self { burst, per_minute }
+    }
+}
+
+/// Rate limits per route class, keyed by client IP.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Disable to skip throttling entirely (`MINDSAGE_RATE_LIMIT=off`).
+    pub enabled: bool,
+    /// Cheap reads: listings, status, stats.
+    pub read: RateLimitBudget,
+    /// Search endpoints (BM25 + vector).
+    pub search: RateLimitBudget,
+    /// Uploads and imports (hit the SQLite writer).
+    pub upload: RateLimitBudget,
+    /// Chat completions (external LLM cost).
+    pub chat: RateLimitBudget,
+}
+
+impl RateLimitConfig {
+    /// Defaults scaled to the device tier. Chat is not scaled — its cost
+    /// is the LLM provider's, not local hardware.
+    pub fn for_tier(tier: CapabilityTier) -> Self {
+        let scale = match tier {
+            CapabilityTier::Base => 1,
+            CapabilityTier::Enhanced => 2,
+            CapabilityTier::Advanced => 4,
+            CapabilityTier::Full => 8,
+        };
+        Self {
+            enabled: true,
+            read: RateLimitBudget::new(60 * scale, 300 * scale),
+            search: RateLimitBudget::new(10 * scale, 60 * scale),
+            upload: RateLimitBudget::new(10 * scale, 30 * scale),
+            chat: RateLimitBudget::new(5, 20),
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self::for_tier(CapabilityTier::Base)
+    }
+}
@@ -10,6 +10,15 @@ pub enum Error {
     #[error("Database error: {0}")]
     Database(String),
 
+    /// The database was locked by another connection; trying again later
+    /// may succeed.
+    #[error("Database busy: {0}")]
+    Busy(String),
+
+    /// A write was refused because the data directory is open read-only.
+    #[error("Read-only mode: {0}")]
+    ReadOnly(String),
+
     #[error("Not found: {0}")]
     NotFound(String),
 
@@ -31,9 +40,19 @@ pub enum Error {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
+    #[error("Embedding dimension mismatch: expected {expected}, got {actual}")]
+    DimensionMismatch { expected: usize, actual: usize },
+
     #[error("Configuration error: {0}")]
     Config(String),
 
+    /// Document metadata a caller may not write, such as a reserved key.
+    #[error("Invalid metadata: {0}")]
+    InvalidMetadata(String),
+
     #[error("HTTP error: {0}")]
     Http(String),
 
@@ -41,4 +60,19 @@ pub enum Error {
     Internal(String),
 }
 
+impl Error {
+    /// Whether the operation may succeed if tried again: a busy database
+    /// or an I/O timeout.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Busy(_) => true,
+            Self::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock
+            ),
+            _ => false,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
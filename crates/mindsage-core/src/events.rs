@@ -0,0 +1,334 @@
+//! In-process event bus — typed notifications pushed to `/api/events` clients.
+//!
+//! Producers (indexing worker, saved-search checks, browser capture,
+//! LocalSend, orchestrator, disk quotas) publish into a tokio broadcast channel.
+//! Publishing never blocks: slow subscribers lag and drop events instead
+//! of applying backpressure.
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Default number of buffered events per subscriber before it starts lagging.
+pub const DEFAULT_EVENT_CAPACITY: usize = 256;
+
+/// Event categories clients can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventCategory {
+    Indexing,
+    Capture,
+    LocalSend,
+    Consolidation,
+    Distill,
+    Search,
+    Connector,
+    Storage,
+}
+
+impl EventCategory {
+    pub fn all() -> &'static [EventCategory] {
+        &[
+            Self::Indexing,
+            Self::Capture,
+            Self::LocalSend,
+            Self::Consolidation,
+            Self::Distill,
+            Self::Search,
+            Self::Connector,
+            Self::Storage,
+        ]
+    }
+}
+
+/// A typed event broadcast to subscribers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum Event {
+    /// An indexing job changed state (queued → processing → completed/failed).
+    #[serde(rename = "indexing.job")]
+    IndexingJob {
+        #[serde(rename = "jobId")]
+        job_id: String,
+        filename: String,
+        status: String,
+        #[serde(skip_serializing_if = "Option::is_none", rename = "documentId")]
+        document_id: Option<i64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// A catch-up pass over the ingest journal made progress or finished.
+    #[serde(rename = "indexing.catchup")]
+    IndexingCatchup {
+        step: String,
+        done: usize,
+        total: usize,
+        #[serde(skip_serializing_if = "Option::is_none", rename = "etaSecs")]
+        eta_secs: Option<u64>,
+        finished: bool,
+    },
+    /// The browser extension delivered a capture.
+    #[serde(rename = "capture.received")]
+    CaptureReceived {
+        #[serde(rename = "conversationId")]
+        conversation_id: String,
+        site: String,
+        #[serde(rename = "newMessages")]
+        new_messages: usize,
+    },
+    /// A LocalSend transfer session changed state.
+    #[serde(rename = "localsend.session")]
+    LocalSendSession {
+        #[serde(rename = "sessionId")]
+        session_id: String,
+        state: String,
+        #[serde(rename = "fileCount")]
+        file_count: usize,
+    },
+    /// The consolidation pipeline finished a run.
+    #[serde(rename = "consolidation.complete")]
+    ConsolidationComplete {
+        #[serde(rename = "orphansPruned")]
+        orphans_pruned: usize,
+        #[serde(rename = "duplicatesRemoved")]
+        duplicates_removed: usize,
+        #[serde(rename = "documentsEvicted")]
+        documents_evicted: usize,
+        #[serde(rename = "durationMs")]
+        duration_ms: u64,
+    },
+    /// Distill made progress on pending chunks.
+    #[serde(rename = "distill.progress")]
+    DistillProgress {
+        enriched: usize,
+        embedded: usize,
+        done: bool,
+    },
+    /// A saved search found new matches after indexing.
+    #[serde(rename = "search.match")]
+    SavedSearchMatch {
+        #[serde(rename = "searchId")]
+        search_id: i64,
+        name: String,
+        #[serde(rename = "newMatches")]
+        new_matches: usize,
+        #[serde(rename = "chunkIds")]
+        chunk_ids: Vec<i64>,
+    },
+    /// A connector import finished (`completed` or `failed`).
+    #[serde(rename = "connector.sync")]
+    ConnectorSync {
+        #[serde(rename = "connectorId")]
+        connector_id: String,
+        name: String,
+        status: String,
+        #[serde(rename = "itemCount")]
+        item_count: usize,
+        indexed: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// A running connector import got through more of its archive.
+    #[serde(rename = "connector.progress")]
+    ConnectorProgress {
+        #[serde(rename = "connectorId")]
+        connector_id: String,
+        #[serde(rename = "entriesScanned")]
+        entries_scanned: usize,
+        #[serde(rename = "totalEntries")]
+        total_entries: usize,
+        #[serde(rename = "currentEntry")]
+        current_entry: String,
+        #[serde(rename = "itemsEmitted")]
+        items_emitted: usize,
+    },
+    /// The capped data areas passed 80 or 95% of their combined quotas.
+    #[serde(rename = "storage.warning")]
+    StorageWarning {
+        /// The threshold passed, in percent.
+        level: u8,
+        #[serde(rename = "usedBytes")]
+        used_bytes: u64,
+        #[serde(rename = "budgetBytes")]
+        budget_bytes: u64,
+    },
+}
+
+impl Event {
+    /// Category used for subscription filtering.
+    pub fn category(&self) -> EventCategory {
+        match self {
+            Self::IndexingJob { .. } | Self::IndexingCatchup { .. } => EventCategory::Indexing,
+            Self::CaptureReceived { .. } => EventCategory::Capture,
+            Self::LocalSendSession { .. } => EventCategory::LocalSend,
+            Self::ConsolidationComplete { .. } => EventCategory::Consolidation,
+            Self::DistillProgress { .. } => EventCategory::Distill,
+            Self::SavedSearchMatch { .. } => EventCategory::Search,
+            Self::ConnectorSync { .. } | Self::ConnectorProgress { .. } => EventCategory::Connector,
+            Self::StorageWarning { .. } => EventCategory::Storage,
+        }
+    }
+
+    /// Serialize to a JSON text frame.
+    pub fn to_frame(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Message sent by a client to choose which categories it receives.
+///
+/// `{"type": "subscribe", "categories": ["indexing", "capture"]}`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    #[serde(rename = "subscribe")]
+    Subscribe { categories: Vec<EventCategory> },
+}
+
+/// Per-connection category filter. Defaults to all categories.
+#[derive(Debug, Clone)]
+pub struct EventFilter {
+    categories: Vec<EventCategory>,
+}
+
+impl EventFilter {
+    /// Replace the subscribed categories.
+    pub fn set(&mut self, categories: Vec<EventCategory>) {
+        self.categories = categories;
+    }
+
+    /// Whether an event passes the filter.
+    pub fn matches(&self, event: &Event) -> bool {
+        self.categories.contains(&event.category())
+    }
+}
+
+impl Default for EventFilter {
+    fn default() -> Self {
+        Self {
+            categories: EventCategory::all().to_vec(),
+        }
+    }
+}
+
+/// Cloneable handle to the broadcast channel.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self { tx }
+    }
+
+    /// Publish an event. A bus without subscribers silently drops it.
+    pub fn publish(&self, event: Event) {
+        let _ = self.tx.send(event);
+    }
+
+    /// Subscribe to all future events.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+
+    /// Number of currently connected subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_and_serialize() {
+        let bus = EventBus::default();
+        let mut rx = bus.subscribe();
+
+        bus.publish(Event::IndexingJob {
+            job_id: "job-1".into(),
+            filename: "notes.md".into(),
+            status: "completed".into(),
+            document_id: Some(7),
+            error: None,
+        });
+
+        let event = rx.try_recv().unwrap();
+        let frame: serde_json::Value = serde_json::from_str(&event.to_frame()).unwrap();
+        assert_eq!(frame["type"], "indexing.job");
+        assert_eq!(frame["jobId"], "job-1");
+        assert_eq!(frame["status"], "completed");
+        assert_eq!(frame["documentId"], 7);
+        assert!(frame.get("error").is_none());
+    }
+
+    #[test]
+    fn test_publish_without_subscribers() {
+        let bus = EventBus::default();
+        bus.publish(Event::DistillProgress {
+            enriched: 1,
+            embedded: 0,
+            done: true,
+        });
+        assert_eq!(bus.subscriber_count(), 0);
+    }
+
+    #[test]
+    fn test_slow_subscriber_lags_without_blocking() {
+        let bus = EventBus::new(2);
+        let mut rx = bus.subscribe();
+
+        for i in 0..5 {
+            bus.publish(Event::DistillProgress {
+                enriched: i,
+                embedded: 0,
+                done: false,
+            });
+        }
+
+        assert!(matches!(
+            rx.try_recv(),
+            Err(broadcast::error::TryRecvError::Lagged(3))
+        ));
+        let event = rx.try_recv().unwrap();
+        assert_eq!(
+            serde_json::to_value(&event).unwrap()["enriched"],
+            3
+        );
+    }
+
+    #[test]
+    fn test_filter_from_subscribe_message() {
+        let msg: ClientMessage = serde_json::from_str(
+            r#"{"type": "subscribe", "categories": ["capture", "localsend"]}"#,
+        )
+        .unwrap();
+
+        let mut filter = EventFilter::default();
+        let ClientMessage::Subscribe { categories } = msg;
+        filter.set(categories);
+
+        let capture = Event::CaptureReceived {
+            conversation_id: "c1".into(),
+            site: "claude".into(),
+            new_messages: 2,
+        };
+        let consolidation = Event::ConsolidationComplete {
+            orphans_pruned: 0,
+            duplicates_removed: 0,
+            documents_evicted: 0,
+            duration_ms: 5,
+        };
+        assert!(filter.matches(&capture));
+        assert!(!filter.matches(&consolidation));
+        assert!(EventFilter::default().matches(&consolidation));
+    }
+}
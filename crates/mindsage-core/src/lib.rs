@@ -3,7 +3,16 @@
 pub mod capabilities;
 pub mod config;
 pub mod error;
+pub mod events;
+pub mod paths;
+pub mod redact;
+pub mod text;
 
 pub use capabilities::{CapabilityTier, DeviceCapabilities};
-pub use config::{DataPaths, MindSageConfig};
+pub use config::{
+    is_valid_profile_name, load_webhooks, AreaQuota, DataPaths, DiskQuotas, MindSageConfig, QueryStatsConfig, QueryStatsMode, QuotaPolicy, RateLimitBudget,
+    RateLimitConfig, SearchPostProcessing, WebhookEndpoint, DEFAULT_PROFILE,
+};
 pub use error::{Error, Result};
+pub use events::{Event, EventBus, EventCategory};
+pub use redact::{redact, Secret};
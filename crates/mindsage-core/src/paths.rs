@@ -0,0 +1,214 @@
+//! File names and paths that work on both Windows and Unix.
+//!
+//! Uploads keep the name the client sent, so a name has to be made safe
+//! for the host's filesystem: Windows refuses device names (`CON`,
+//! `aux.txt`) and silently drops trailing dots and spaces, leaving files
+//! that cannot be opened or deleted by their recorded name. Stored paths
+//! may have been written with either separator (a Python install on
+//! Windows, a data directory copied between machines), so they are
+//! compared by component and kept in one canonical form.
+//!
+//! Names are sanitized for an explicit [`PathStyle`] rather than the
+//! host's, and paths are split on either separator, so both styles can be
+//! exercised on any platform.
+
+use crate::text::truncate_text;
+
+/// Longest file name, in bytes, accepted by common filesystems.
+pub const MAX_FILENAME_BYTES: usize = 255;
+/// Longest full path Windows accepts without the `\\?\` prefix.
+const WINDOWS_MAX_PATH: usize = 259;
+
+/// Names Windows reserves for devices, with or without an extension.
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+    "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Which filesystem rules a name or path follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathStyle {
+    Unix,
+    Windows,
+}
+
+impl PathStyle {
+    /// The style of the platform this binary was built for.
+    pub const HOST: PathStyle = if cfg!(windows) { PathStyle::Windows } else { PathStyle::Unix };
+}
+
+/// Make a client-supplied file name safe to create in `dir`: directory
+/// components and `..` are removed, and the name is cut so that it (and,
+/// on Windows, the whole path) stays within the filesystem's limits,
+/// keeping its extension. On Windows, characters the filesystem refuses
+/// become `_`, trailing dots and spaces are dropped, and device names get
+/// a leading `_`. An empty result is `unnamed`.
+pub fn sanitize_filename(name: &str, dir: &str, style: PathStyle) -> String {
+    let mut name = name.replace(['/', '\\'], "").replace("..", "");
+    name.retain(|c| !c.is_control());
+
+    if style == PathStyle::Windows {
+        name = name
+            .chars()
+            .map(|c| if matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*') { '_' } else { c })
+            .collect();
+        name.truncate(name.trim_end_matches(['.', ' ']).len());
+        if is_windows_reserved(&name) {
+            name.insert(0, '_');
+        }
+    }
+    if name.is_empty() {
+        return "unnamed".to_string();
+    }
+
+    let mut max_len = MAX_FILENAME_BYTES;
+    if style == PathStyle::Windows {
+        // The directory, a separator, then the name
+        max_len = max_len.min(WINDOWS_MAX_PATH.saturating_sub(dir.len() + 1));
+    }
+    let name = cap_filename(&name, max_len);
+    match style {
+        // Cutting may expose a trailing dot or space again
+        PathStyle::Windows => name.trim_end_matches(['.', ' ']).to_string(),
+        PathStyle::Unix => name,
+    }
+}
+
+/// Whether Windows treats `name` as a device: a reserved name on its own
+/// or before the first dot, in any case, ignoring trailing spaces.
+fn is_windows_reserved(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or("").trim_end();
+    WINDOWS_RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved))
+}
+
+/// `name` cut to `max_len` bytes at a char boundary, shortening the stem
+/// so a short extension survives.
+fn cap_filename(name: &str, max_len: usize) -> String {
+    if name.len() <= max_len {
+        return name.to_string();
+    }
+    match name.rfind('.') {
+        Some(dot) if dot > 0 && name.len() - dot < max_len / 2 => {
+            let ext = &name[dot..];
+            format!("{}{}", truncate_text(&name[..dot], max_len - ext.len()), ext)
+        }
+        _ => truncate_text(name, max_len).to_string(),
+    }
+}
+
+/// Canonical form of a stored path: `/` as the only separator, repeated
+/// separators collapsed (a leading `//` of a UNC path is kept) and the
+/// drive letter upper-cased, so `c:\data\a.txt` and `C:/data/a.txt` are
+/// the same key.
+pub fn normalize_path(path: &str) -> String {
+    let unified = path.replace('\\', "/");
+    let (lead, rest) = match unified.strip_prefix("//") {
+        Some(rest) => ("//", rest),
+        None => ("", unified.as_str()),
+    };
+    let mut normalized = String::with_capacity(unified.len());
+    normalized.push_str(lead);
+    let mut previous_slash = false;
+    for c in rest.chars() {
+        if c == '/' && previous_slash {
+            continue;
+        }
+        previous_slash = c == '/';
+        normalized.push(c);
+    }
+    if is_drive_prefixed(&normalized) {
+        normalized[..1].make_ascii_uppercase();
+    }
+    normalized
+}
+
+fn is_drive_prefixed(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// The components of `path` after `prefix`, splitting on either separator.
+/// Drive letters compare without case. `None` when `path` is not under
+/// `prefix`.
+pub fn strip_path_prefix<'a>(path: &'a str, prefix: &str) -> Option<Vec<&'a str>> {
+    let mut rest = components(path);
+    for (i, expected) in components(prefix).enumerate() {
+        let actual = rest.next()?;
+        let same = if i == 0 && is_drive_prefixed(expected) {
+            actual.eq_ignore_ascii_case(expected)
+        } else {
+            actual == expected
+        };
+        if !same {
+            return None;
+        }
+    }
+    Some(rest.collect())
+}
+
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split(['/', '\\']).filter(|c| !c.is_empty() && *c != ".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_removes_directories_on_both_styles() {
+        for style in [PathStyle::Unix, PathStyle::Windows] {
+            assert_eq!(sanitize_filename("../notes.txt", "/data/uploads", style), "notes.txt");
+            assert_eq!(sanitize_filename("..\\..\\secret.txt", "C:\\data\\uploads", style), "secret.txt");
+            assert_eq!(sanitize_filename("", "/data/uploads", style), "unnamed");
+        }
+    }
+
+    #[test]
+    fn test_sanitize_windows_reserved_names_and_trailing_dots() {
+        let dir = "C:\\mindsage\\data\\uploads";
+        assert_eq!(sanitize_filename("CON", dir, PathStyle::Windows), "_CON");
+        assert_eq!(sanitize_filename("aux.txt", dir, PathStyle::Windows), "_aux.txt");
+        assert_eq!(sanitize_filename("Lpt1 .tar.gz", dir, PathStyle::Windows), "_Lpt1 .tar.gz");
+        assert_eq!(sanitize_filename("console.txt", dir, PathStyle::Windows), "console.txt");
+        assert_eq!(sanitize_filename("report. . ", dir, PathStyle::Windows), "report");
+        assert_eq!(sanitize_filename("a:b?.txt", dir, PathStyle::Windows), "a_b_.txt");
+        assert_eq!(sanitize_filename("...", dir, PathStyle::Windows), "unnamed");
+
+        // Unix keeps them as they are
+        assert_eq!(sanitize_filename("aux.txt", "/data/uploads", PathStyle::Unix), "aux.txt");
+        assert_eq!(sanitize_filename("report.", "/data/uploads", PathStyle::Unix), "report.");
+    }
+
+    #[test]
+    fn test_sanitize_caps_length_with_the_directory() {
+        let long = format!("{}.pdf", "é".repeat(200));
+        let unix = sanitize_filename(&long, "/data/uploads", PathStyle::Unix);
+        assert!(unix.len() <= MAX_FILENAME_BYTES);
+        assert!(unix.ends_with(".pdf"));
+
+        let dir = format!("C:\\{}", "d".repeat(150));
+        let windows = sanitize_filename(&long, &dir, PathStyle::Windows);
+        assert!(dir.len() + 1 + windows.len() <= WINDOWS_MAX_PATH);
+        assert!(windows.ends_with(".pdf"));
+        assert!(windows.starts_with('é'));
+    }
+
+    #[test]
+    fn test_normalize_path_either_separator() {
+        assert_eq!(normalize_path("c:\\data\\imports\\a.txt"), "C:/data/imports/a.txt");
+        assert_eq!(normalize_path("C:/data//imports/a.txt"), "C:/data/imports/a.txt");
+        assert_eq!(normalize_path("\\\\nas\\share\\a.txt"), "//nas/share/a.txt");
+        assert_eq!(normalize_path("/app/data/imports/"), "/app/data/imports/");
+    }
+
+    #[test]
+    fn test_strip_path_prefix_by_component() {
+        let windows = "C:\\Users\\me\\mindsage\\data\\imports\\a.txt";
+        assert_eq!(strip_path_prefix(windows, "c:/Users/me/mindsage/data"), Some(vec!["imports", "a.txt"]));
+        assert_eq!(strip_path_prefix(windows, "C:\\Users\\me\\mindsage\\data\\"), Some(vec!["imports", "a.txt"]));
+        assert_eq!(strip_path_prefix("/app/data/imports/a.txt", "/app/data"), Some(vec!["imports", "a.txt"]));
+        // A sibling directory sharing the prefix's text is not under it
+        assert_eq!(strip_path_prefix("/app/data2/imports/a.txt", "/app/data"), None);
+        assert_eq!(strip_path_prefix("/app/data", "/app/data/imports"), None);
+    }
+}
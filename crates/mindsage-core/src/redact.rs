@@ -0,0 +1,156 @@
+//! Redaction of secrets and user content in logs and API responses.
+//!
+//! Log lines should not carry what the user stored: file names, titles,
+//! URLs and captured page text are wrapped in [`redact`] at the call site,
+//! which prints only their length unless unredacted logging has been turned
+//! on for local debugging (`MINDSAGE_LOG_UNREDACTED=on`). Credentials are
+//! held in [`Secret`], which never prints or serializes more than its last
+//! four characters.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+static UNREDACTED_LOGGING: AtomicBool = AtomicBool::new(false);
+
+/// Characters of a secret left visible at the end of its mask.
+const SECRET_VISIBLE_TAIL: usize = 4;
+/// Secrets shorter than this are masked completely.
+const SECRET_MIN_LEN_FOR_TAIL: usize = 12;
+
+/// Print user content in full in log lines (local debugging only).
+pub fn set_unredacted_logging(enabled: bool) {
+    UNREDACTED_LOGGING.store(enabled, Ordering::Relaxed);
+}
+
+pub fn unredacted_logging() -> bool {
+    UNREDACTED_LOGGING.load(Ordering::Relaxed)
+}
+
+/// Wrap user content for a log line.
+pub fn redact<T: fmt::Display>(value: T) -> Redacted<T> {
+    Redacted(value)
+}
+
+/// User content that displays as `<redacted N chars>` unless unredacted
+/// logging is enabled.
+pub struct Redacted<T>(T);
+
+impl<T: fmt::Display> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if unredacted_logging() {
+            self.0.fmt(f)
+        } else {
+            write!(f, "<redacted {} chars>", self.0.to_string().chars().count())
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// `****` followed by the last four characters, or only `****` for short values.
+pub fn mask_secret(value: &str) -> String {
+    let len = value.chars().count();
+    if len < SECRET_MIN_LEN_FOR_TAIL {
+        return "****".to_string();
+    }
+    let tail: String = value.chars().skip(len - SECRET_VISIBLE_TAIL).collect();
+    format!("****{}", tail)
+}
+
+/// A credential (API key, cookie value). `Debug`, `Display` and `Serialize`
+/// show only the masked tail; [`Secret::expose`] returns the value itself.
+/// Deserializes from a plain string.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// The raw value, for sending to the service it authenticates with.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn masked(&self) -> String {
+        mask_secret(&self.0)
+    }
+
+    /// Serialize the raw value, for files that must keep the credential.
+    pub fn serialize_exposed<S: Serializer>(secret: &Option<Secret>, serializer: S) -> Result<S::Ok, S::Error> {
+        secret.as_ref().map(Secret::expose).serialize(serializer)
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", self.masked())
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.masked())
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.masked())
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_never_prints_key_material() {
+        let key = Secret::new("sk-live-0123456789abcdef");
+        assert_eq!(key.masked(), "****cdef");
+        assert_eq!(format!("{:?}", key), "Secret(****cdef)");
+        assert_eq!(key.to_string(), "****cdef");
+        assert_eq!(serde_json::to_string(&key).unwrap(), "\"****cdef\"");
+        assert_eq!(key.expose(), "sk-live-0123456789abcdef");
+        assert_eq!(mask_secret("short"), "****");
+
+        let parsed: Secret = serde_json::from_str("\"sk-live-0123456789abcdef\"").unwrap();
+        assert_eq!(parsed, key);
+    }
+
+    #[test]
+    fn test_redact_hides_content_unless_enabled() {
+        let line = format!("Indexed {} → document 3", redact("Divorce lawyer notes.pdf"));
+        assert_eq!(line, "Indexed <redacted 24 chars> → document 3");
+
+        set_unredacted_logging(true);
+        let line = format!("Indexed {}", redact("notes.pdf"));
+        set_unredacted_logging(false);
+        assert_eq!(line, "Indexed notes.pdf");
+    }
+}
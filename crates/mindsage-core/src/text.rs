@@ -0,0 +1,83 @@
+//! UTF-8 safe text cutting.
+//!
+//! Byte offsets computed from lengths or search positions can land inside
+//! a multi-byte character, and slicing there panics. These helpers move a
+//! cut to the previous character boundary instead. `shared_overlap` finds
+//! the text two overlapping chunks repeat, so it can be cut from one.
+
+/// The largest index `<= index` that is a char boundary of `text`.
+pub fn floor_char_boundary(text: &str, index: usize) -> usize {
+    if index >= text.len() {
+        return text.len();
+    }
+    (0..=index).rev().find(|&i| text.is_char_boundary(i)).unwrap_or(0)
+}
+
+/// The smallest index `>= index` that is a char boundary of `text`.
+pub fn ceil_char_boundary(text: &str, index: usize) -> usize {
+    (index..text.len()).find(|&i| text.is_char_boundary(i)).unwrap_or(text.len())
+}
+
+/// At most `max_bytes` of `text`, cut at a char boundary.
+pub fn truncate_text(text: &str, max_bytes: usize) -> &str {
+    &text[..floor_char_boundary(text, max_bytes)]
+}
+
+/// Shortest run counted as text two chunks share; a shorter match is more
+/// likely a coincidence (a repeated word) than chunk overlap.
+pub const MIN_SHARED_BYTES: usize = 16;
+
+/// Bytes at the start of `later` that repeat the end of `earlier`: the
+/// longest suffix of `earlier` that is also a prefix of `later`, ending on
+/// char boundaries. 0 when that is shorter than `MIN_SHARED_BYTES`.
+pub fn shared_overlap(earlier: &str, later: &str) -> usize {
+    let longest = earlier.len().min(later.len());
+    (MIN_SHARED_BYTES..=longest)
+        .rev()
+        .find(|&n| {
+            let from = earlier.len() - n;
+            later.is_char_boundary(n)
+                && earlier.is_char_boundary(from)
+                && earlier.as_bytes()[from..] == later.as_bytes()[..n]
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_text_at_multibyte_cuts() {
+        // "Grüße": ü and ß are two bytes each
+        assert_eq!(truncate_text("Grüße", 3), "Gr");
+        assert_eq!(truncate_text("Grüße", 4), "Grü");
+        // Emoji are four bytes; every cut inside one drops it
+        for cut in 2..=4 {
+            assert_eq!(truncate_text("ok🙂!", cut), "ok");
+        }
+        assert_eq!(truncate_text("ok🙂!", 6), "ok🙂");
+        // CJK characters are three bytes
+        assert_eq!(truncate_text("日本語", 4), "日");
+        assert_eq!(truncate_text("日本語", 6), "日本");
+        // A combining mark is its own char: "e" + U+0301 keeps the base letter
+        assert_eq!(truncate_text("cafe\u{301}", 5), "cafe");
+        assert_eq!(truncate_text("short", 100), "short");
+
+        assert_eq!(floor_char_boundary("日本", 2), 0);
+        assert_eq!(ceil_char_boundary("日本", 2), 3);
+        assert_eq!(ceil_char_boundary("日本", 9), 6);
+    }
+
+    #[test]
+    fn test_shared_overlap_of_adjacent_chunks() {
+        let earlier = "The harbour froze in January. Ferries stopped for a week.";
+        let later = "Ferries stopped for a week. The town ran out of flour.";
+        assert_eq!(&later[..shared_overlap(earlier, later)], "Ferries stopped for a week.");
+        // A repeated word is not an overlap, and neither is the reverse order
+        assert_eq!(shared_overlap("It was cold. The", "The town"), 0);
+        assert_eq!(shared_overlap(later, earlier), 0);
+        // Never cuts inside a character
+        assert_eq!(shared_overlap("Über die Brücke gehen wir", "Brücke gehen wir heute"), "Brücke gehen wir".len());
+    }
+}
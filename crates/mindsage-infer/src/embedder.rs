@@ -7,12 +7,20 @@
 
 use ndarray::Array1;
 
+use crate::stats::EmbedderStats;
+
 /// Result of an embedding operation.
 pub struct EmbeddingResult {
     /// Float32 embedding vector (384-dim for all-MiniLM-L6-v2).
     pub embedding: Array1<f32>,
     /// Whether this was served from cache.
     pub cached: bool,
+    /// Tokens the text produced, before truncation, when the backend
+    /// tokenizes.
+    pub input_token_count: Option<usize>,
+    /// Whether the text was longer than the model reads, so its end is not
+    /// reflected in the embedding.
+    pub truncated: bool,
 }
 
 /// Trait for embedding backends.
@@ -26,21 +34,57 @@ pub trait EmbedderBackend: Send + Sync {
         texts.iter().map(|t| self.embed(t)).collect()
     }
 
+    /// Generate an embedding for a search query. Same as `embed` unless
+    /// queries have a model of their own (`EmbedderPair`).
+    fn embed_query(&self, text: &str) -> Option<EmbeddingResult> {
+        self.embed(text)
+    }
+
     /// Get the embedding dimension.
     fn dimension(&self) -> usize;
 
     /// Check if the embedder is available (model loaded).
     fn is_available(&self) -> bool;
+
+    /// Identifier of the model producing embeddings. Stored with each
+    /// embedding so vectors from different models are never compared.
+    fn model_id(&self) -> &str;
+
+    /// Batch latency and truncation totals since the embedder was loaded.
+    fn stats(&self) -> EmbedderStats {
+        EmbedderStats::default()
+    }
+
+    /// The model `embed_query` uses, when it is not this one.
+    fn query_embedder(&self) -> Option<&dyn EmbedderBackend> {
+        None
+    }
+
+    /// Estimated bytes the loaded model and its caches hold, for memory
+    /// accounting. 0 for backends without a model.
+    fn memory_bytes(&self) -> usize {
+        0
+    }
 }
 
 /// Placeholder embedder that always returns None (BM25-only mode).
 pub struct NoopEmbedder {
     dim: usize,
+    model_id: String,
 }
 
 impl NoopEmbedder {
     pub fn new(dim: usize) -> Self {
-        Self { dim }
+        Self {
+            dim,
+            model_id: "noop".into(),
+        }
+    }
+
+    /// Use a different model identifier (e.g. to simulate a model switch).
+    pub fn with_model_id(mut self, model_id: impl Into<String>) -> Self {
+        self.model_id = model_id.into();
+        self
     }
 }
 
@@ -56,4 +100,8 @@ impl EmbedderBackend for NoopEmbedder {
     fn is_available(&self) -> bool {
         false
     }
+
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
 }
@@ -4,13 +4,23 @@
 //! When the `onnx` feature is enabled and model files are present,
 //! `OnnxEmbedder` loads all-MiniLM-L6-v2 for 384-dim embeddings.
 //! Without it, `NoopEmbedder` is used and search falls back to BM25-only.
+//! A `models.json` in the model directory can name a passage and a query
+//! model instead, which are paired in an `EmbedderPair`.
 
 pub mod cache;
 pub mod embedder;
+pub mod normalize;
 pub mod onnx_embedder;
+pub mod pair;
+pub mod stats;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 
 pub use cache::QueryCache;
 pub use embedder::{EmbedderBackend, EmbeddingResult, NoopEmbedder};
+pub use normalize::{NormalizingEmbedder, TextNormalization};
+pub use pair::{EmbedderPair, ModelsManifest};
+pub use stats::{EmbedderStats, EmbedderStatsRecorder};
 
 #[cfg(feature = "onnx")]
 pub use onnx_embedder::OnnxEmbedder;
@@ -21,10 +31,23 @@ use std::sync::Arc;
 /// Create the best available embedder for the given model directory.
 ///
 /// Tries ONNX first (if feature enabled and model files present),
-/// falls back to NoopEmbedder.
+/// falls back to NoopEmbedder. With a `models.json`, loads the passage
+/// and query models it names from their subdirectories.
 pub fn create_embedder(model_dir: &Path) -> Arc<dyn EmbedderBackend> {
     #[cfg(feature = "onnx")]
     {
+        let load = |dir: &Path| OnnxEmbedder::load(dir).map(|e| Arc::new(e) as Arc<dyn EmbedderBackend>);
+        match ModelsManifest::read(model_dir) {
+            Ok(Some(manifest)) => {
+                if let Some(embedder) = manifest.build(model_dir, load) {
+                    return embedder;
+                }
+                tracing::warn!("No model in models.json could be loaded. Falling back to BM25-only.");
+                return Arc::new(NoopEmbedder::new(384));
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("{}. Loading the model directory as a single model.", e),
+        }
         match OnnxEmbedder::load(model_dir) {
             Ok(embedder) => {
                 tracing::info!("Using ONNX embedder (dim={})", embedder.dimension());
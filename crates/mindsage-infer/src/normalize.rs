@@ -0,0 +1,225 @@
+//! Text normalization applied before embedding.
+//!
+//! The same word can reach the embedder in different forms: `Zürich`
+//! typed with a precomposed `ü`, pasted with a combining diaeresis, or
+//! ASCII-folded by a client to `Zurich`. `NormalizingEmbedder` puts every
+//! passage and every query through the same `TextNormalization`, so only
+//! the chosen differences reach the model. The flags are part of its
+//! `model_id`, which is stored with each embedding: changing them leaves
+//! the existing vectors to the re-embed job instead of mixing the two.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use unicode_normalization_alignments::char::is_combining_mark;
+use unicode_normalization_alignments::UnicodeNormalization;
+
+use crate::embedder::{EmbedderBackend, EmbeddingResult};
+use crate::stats::EmbedderStats;
+
+/// Which normalizations to apply; all off leaves text untouched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextNormalization {
+    /// Compose characters (Unicode NFC).
+    pub nfc: bool,
+    /// Lowercase.
+    pub casefold: bool,
+    /// Drop combining marks after decomposing (`é` → `e`); implies NFC.
+    pub fold_accents: bool,
+}
+
+impl TextNormalization {
+    /// Parse a comma-separated list of `nfc`, `casefold` and `accents`
+    /// (`MINDSAGE_EMBED_NORMALIZE`). Empty or `off` is no normalization;
+    /// unknown names are skipped with a warning.
+    pub fn parse(value: &str) -> Self {
+        let mut normalization = Self::default();
+        for flag in value.split(',').map(|f| f.trim().to_lowercase()) {
+            match flag.as_str() {
+                "" | "off" | "none" => {}
+                "nfc" => normalization.nfc = true,
+                "casefold" | "lowercase" => normalization.casefold = true,
+                "accents" | "fold_accents" => normalization.fold_accents = true,
+                other => tracing::warn!("Unknown embedding normalization '{}', ignored", other),
+            }
+        }
+        normalization
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Suffix recorded in the model id, e.g. `+nfc+casefold`; empty when
+    /// nothing is normalized, so such embeddings keep the bare model id.
+    pub fn tag(&self) -> String {
+        let mut tag = String::new();
+        if self.nfc || self.fold_accents {
+            tag.push_str("+nfc");
+        }
+        if self.casefold {
+            tag.push_str("+casefold");
+        }
+        if self.fold_accents {
+            tag.push_str("+unaccent");
+        }
+        tag
+    }
+
+    /// `text` normalized; borrowed when nothing changes.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        // Composition and combining marks only concern non-ASCII text
+        if !text.is_ascii() {
+            if self.fold_accents {
+                let stripped: String = text.nfd().map(|(c, _)| c).filter(|&c| !is_combining_mark(c)).collect();
+                text = Cow::Owned(stripped.nfc().map(|(c, _)| c).collect());
+            } else if self.nfc {
+                text = Cow::Owned(text.nfc().map(|(c, _)| c).collect());
+            }
+        }
+        if self.casefold && text.chars().any(char::is_uppercase) {
+            text = Cow::Owned(text.to_lowercase());
+        }
+        text
+    }
+}
+
+/// An embedder that normalizes every text before passing it on.
+pub struct NormalizingEmbedder {
+    inner: Arc<dyn EmbedderBackend>,
+    normalization: TextNormalization,
+    model_id: String,
+}
+
+impl NormalizingEmbedder {
+    /// `inner` behind `normalization`, or `inner` itself when there is
+    /// nothing to normalize.
+    pub fn wrap(inner: Arc<dyn EmbedderBackend>, normalization: TextNormalization) -> Arc<dyn EmbedderBackend> {
+        if normalization.is_none() {
+            return inner;
+        }
+        let model_id = format!("{}{}", inner.model_id(), normalization.tag());
+        Arc::new(Self {
+            inner,
+            normalization,
+            model_id,
+        })
+    }
+}
+
+impl EmbedderBackend for NormalizingEmbedder {
+    fn embed(&self, text: &str) -> Option<EmbeddingResult> {
+        self.inner.embed(&self.normalization.apply(text))
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Vec<Option<EmbeddingResult>> {
+        let normalized: Vec<Cow<'_, str>> = texts.iter().map(|t| self.normalization.apply(t)).collect();
+        let texts: Vec<&str> = normalized.iter().map(|t| t.as_ref()).collect();
+        self.inner.embed_batch(&texts)
+    }
+
+    fn embed_query(&self, text: &str) -> Option<EmbeddingResult> {
+        self.inner.embed_query(&self.normalization.apply(text))
+    }
+
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn stats(&self) -> EmbedderStats {
+        self.inner.stats()
+    }
+
+    fn query_embedder(&self) -> Option<&dyn EmbedderBackend> {
+        self.inner.query_embedder()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.inner.memory_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ndarray::Array1;
+
+    /// Embeds each text as its bytes, so different input gives a
+    /// different vector.
+    struct ByteEmbedder;
+
+    impl EmbedderBackend for ByteEmbedder {
+        fn embed(&self, text: &str) -> Option<EmbeddingResult> {
+            let mut embedding = Array1::zeros(16);
+            for (i, b) in text.bytes().enumerate() {
+                embedding[i % 16] += b as f32 * (i + 1) as f32;
+            }
+            Some(EmbeddingResult {
+                embedding,
+                cached: false,
+                input_token_count: None,
+                truncated: false,
+            })
+        }
+
+        fn dimension(&self) -> usize {
+            16
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn model_id(&self) -> &str {
+            "bytes"
+        }
+    }
+
+    fn vector(embedder: &dyn EmbedderBackend, text: &str) -> Vec<f32> {
+        embedder.embed(text).unwrap().embedding.to_vec()
+    }
+
+    #[test]
+    fn test_normalized_inputs_embed_identically() {
+        let composed = "Zürich";
+        let decomposed = "Zu\u{308}rich";
+        let raw: Arc<dyn EmbedderBackend> = Arc::new(ByteEmbedder);
+        assert_ne!(vector(raw.as_ref(), composed), vector(raw.as_ref(), decomposed));
+
+        let nfc = NormalizingEmbedder::wrap(raw.clone(), TextNormalization::parse("nfc"));
+        assert_eq!(vector(nfc.as_ref(), composed), vector(nfc.as_ref(), decomposed));
+        assert_ne!(vector(nfc.as_ref(), composed), vector(nfc.as_ref(), "Zurich"));
+
+        let folded = NormalizingEmbedder::wrap(raw.clone(), TextNormalization::parse("nfc, casefold, accents"));
+        let expected = vector(raw.as_ref(), "zurich");
+        for text in [composed, decomposed, "Zurich", "ZÜRICH"] {
+            assert_eq!(vector(folded.as_ref(), text), expected, "{}", text);
+        }
+        let batch: Vec<Vec<f32>> = folded
+            .embed_batch(&[composed, "zurich"])
+            .into_iter()
+            .map(|r| r.unwrap().embedding.to_vec())
+            .collect();
+        assert_eq!(batch, vec![expected.clone(), expected]);
+    }
+
+    #[test]
+    fn test_flags_are_part_of_the_model_id() {
+        let raw: Arc<dyn EmbedderBackend> = Arc::new(ByteEmbedder);
+        assert_eq!(NormalizingEmbedder::wrap(raw.clone(), TextNormalization::parse("off")).model_id(), "bytes");
+        assert_eq!(NormalizingEmbedder::wrap(raw.clone(), TextNormalization::parse("nfc")).model_id(), "bytes+nfc");
+        let all = TextNormalization::parse("accents,casefold,unknown");
+        assert_eq!(NormalizingEmbedder::wrap(raw, all).model_id(), "bytes+nfc+casefold+unaccent");
+        assert!(matches!(all.apply("plain ascii"), Cow::Borrowed(_)));
+    }
+}
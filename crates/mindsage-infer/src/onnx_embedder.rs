@@ -1,12 +1,14 @@
 //! ONNX-based embedding engine using all-MiniLM-L6-v2.
 //!
 //! Loads a SentenceTransformers ONNX model and tokenizer to generate
-//! 384-dimensional float32 embeddings. Requires the `onnx` feature.
+//! float32 embeddings, 384-dimensional for all-MiniLM-L6-v2. Requires the
+//! `onnx` feature.
 
 #[cfg(feature = "onnx")]
 mod inner {
     use std::path::Path;
     use std::sync::Arc;
+    use std::time::Instant;
 
     use ndarray::Array1;
     use ort::session::Session;
@@ -17,6 +19,7 @@ mod inner {
 
     use crate::cache::QueryCache;
     use crate::embedder::{EmbedderBackend, EmbeddingResult};
+    use crate::stats::{EmbedderStats, EmbedderStatsRecorder};
 
     /// Maximum sequence length for the model.
     const MAX_SEQ_LEN: usize = 512;
@@ -30,6 +33,10 @@ mod inner {
         tokenizer: Tokenizer,
         cache: QueryCache,
         dimension: usize,
+        model_id: String,
+        /// Size of `model.onnx`; the session holds about as much.
+        model_bytes: usize,
+        stats: EmbedderStatsRecorder,
     }
 
     impl OnnxEmbedder {
@@ -63,22 +70,40 @@ mod inner {
             let tokenizer = Tokenizer::from_file(&tokenizer_path)
                 .map_err(|e| format!("Failed to load tokenizer: {}", e))?;
 
-            info!(
-                "ONNX embedder loaded: dim={}, model={}",
-                DEFAULT_DIM,
-                model_path.display()
-            );
+            // Directory name plus file size: swapping model.onnx changes the id
+            let model_size = std::fs::metadata(&model_path).map(|m| m.len()).unwrap_or(0);
+            let model_name = model_dir
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "onnx".into());
+            let model_id = format!("{}:{}", model_name, model_size);
 
-            Ok(Self {
+            let mut embedder = Self {
                 session: Arc::new(Mutex::new(session)),
                 tokenizer,
                 cache: QueryCache::default_cache(),
+                stats: EmbedderStatsRecorder::default(),
                 dimension: DEFAULT_DIM,
-            })
+                model_id,
+                model_bytes: model_size as usize,
+            };
+            // Models other than all-MiniLM-L6-v2 (e.g. the 768-dim passage
+            // model of a pair) report their dimension in their output
+            if let Some(probe) = embedder.infer("dimension") {
+                embedder.dimension = probe.embedding.len();
+            }
+
+            info!(
+                "ONNX embedder loaded: dim={}, model={}, id={}",
+                embedder.dimension,
+                model_path.display(),
+                embedder.model_id
+            );
+            Ok(embedder)
         }
 
         /// Run inference on tokenized input.
-        fn infer(&self, text: &str) -> Option<Array1<f32>> {
+        fn infer(&self, text: &str) -> Option<EmbeddingResult> {
             // Tokenize
             let encoding = self
                 .tokenizer
@@ -92,6 +117,12 @@ mod inner {
             let input_ids = encoding.get_ids();
             let attention_mask = encoding.get_attention_mask();
 
+            // The tokenizer config may truncate on its own, leaving the rest
+            // in overflowing encodings
+            let overflow: usize = encoding.get_overflowing().iter().map(|e| e.len()).sum();
+            let input_token_count = input_ids.len() + overflow;
+            let truncated = overflow > 0 || input_ids.len() > MAX_SEQ_LEN;
+
             // Truncate to max sequence length
             let seq_len = input_ids.len().min(MAX_SEQ_LEN);
             let input_ids = &input_ids[..seq_len];
@@ -164,32 +195,46 @@ mod inner {
                 return None;
             };
 
-            Some(embedding)
+            Some(EmbeddingResult {
+                embedding,
+                cached: false,
+                input_token_count: Some(input_token_count),
+                truncated,
+            })
         }
-    }
 
-    impl EmbedderBackend for OnnxEmbedder {
-        fn embed(&self, text: &str) -> Option<EmbeddingResult> {
-            // Check cache first
+        /// Embed from the cache or the model, without recording stats.
+        fn embed_one(&self, text: &str) -> Option<EmbeddingResult> {
             if let Some(cached) = self.cache.get(text) {
                 return Some(EmbeddingResult {
                     embedding: cached,
                     cached: true,
+                    input_token_count: None,
+                    truncated: false,
                 });
             }
 
-            let embedding = self.infer(text)?;
-            self.cache.put(text.to_string(), embedding.clone());
+            let result = self.infer(text)?;
+            self.cache.put(text.to_string(), result.embedding.clone());
+            Some(result)
+        }
+    }
 
-            Some(EmbeddingResult {
-                embedding,
-                cached: false,
-            })
+    impl EmbedderBackend for OnnxEmbedder {
+        #[tracing::instrument(level = "debug", skip_all)]
+        fn embed(&self, text: &str) -> Option<EmbeddingResult> {
+            let start = Instant::now();
+            let result = self.embed_one(text);
+            self.stats.record_batch(start.elapsed(), std::slice::from_ref(&result));
+            result
         }
 
         fn embed_batch(&self, texts: &[&str]) -> Vec<Option<EmbeddingResult>> {
             // Sequential for now; batch inference can be added later
-            texts.iter().map(|t| self.embed(t)).collect()
+            let start = Instant::now();
+            let results: Vec<_> = texts.iter().map(|t| self.embed_one(t)).collect();
+            self.stats.record_batch(start.elapsed(), &results);
+            results
         }
 
         fn dimension(&self) -> usize {
@@ -199,6 +244,18 @@ mod inner {
         fn is_available(&self) -> bool {
             true
         }
+
+        fn model_id(&self) -> &str {
+            &self.model_id
+        }
+
+        fn stats(&self) -> EmbedderStats {
+            self.stats.snapshot()
+        }
+
+        fn memory_bytes(&self) -> usize {
+            self.model_bytes + self.cache.len() * self.dimension * std::mem::size_of::<f32>()
+        }
     }
 }
 
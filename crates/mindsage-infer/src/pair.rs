@@ -0,0 +1,269 @@
+//! Asymmetric embedding — a large model for passages, a small one for
+//! queries.
+//!
+//! Indexing can afford a slow, better model; a search cannot wait for it.
+//! Passage/query model pairs where the small model is distilled to match
+//! the large one's space are common. `EmbedderPair` embeds documents with
+//! the passage model and routes `embed_query` to the query model. Both must
+//! produce vectors of the same dimension. Stored embeddings come from the
+//! passage model, so the pair keeps its model id: swapping the query model
+//! does not make them stale.
+//!
+//! `create_embedder` builds a pair from `models.json` in the model
+//! directory:
+//!
+//! ```json
+//! { "passage_model": "e5-base", "query_model": "e5-small-distilled" }
+//! ```
+//!
+//! Each name is a subdirectory holding `model.onnx` and `tokenizer.json`.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::embedder::{EmbedderBackend, EmbeddingResult};
+use crate::stats::EmbedderStats;
+
+/// File in the model directory naming the passage and query models.
+pub const MODELS_MANIFEST: &str = "models.json";
+
+/// Contents of `models.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ModelsManifest {
+    /// Subdirectory of the model that embeds documents.
+    pub passage_model: String,
+    /// Subdirectory of the model that embeds queries; without it, queries
+    /// use the passage model.
+    #[serde(default)]
+    pub query_model: Option<String>,
+}
+
+impl ModelsManifest {
+    /// Read `models.json` from `model_dir`. `None` when there is no such
+    /// file; an unreadable one is an error.
+    pub fn read(model_dir: &Path) -> Result<Option<Self>, String> {
+        let path = model_dir.join(MODELS_MANIFEST);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+
+    /// Build the embedder the manifest describes, loading each model from
+    /// its subdirectory with `load`. A model that fails to load is skipped
+    /// with a warning, so one model alone is used as a single embedder.
+    /// `None` when neither loads.
+    pub fn build<F>(&self, model_dir: &Path, load: F) -> Option<Arc<dyn EmbedderBackend>>
+    where
+        F: Fn(&Path) -> Result<Arc<dyn EmbedderBackend>, String>,
+    {
+        let load = |name: &str| match load(&model_dir.join(name)) {
+            Ok(embedder) => Some(embedder),
+            Err(e) => {
+                tracing::warn!("Embedding model '{}' unavailable: {}", name, e);
+                None
+            }
+        };
+        let passage = load(&self.passage_model);
+        let query = self.query_model.as_deref().filter(|q| *q != self.passage_model).and_then(load);
+        match (passage, query) {
+            (Some(passage), Some(query)) => match EmbedderPair::new(passage.clone(), query) {
+                Ok(pair) => {
+                    tracing::info!(
+                        "Using embedder pair: passages with {}, queries with {}",
+                        self.passage_model,
+                        self.query_model.as_deref().unwrap_or_default()
+                    );
+                    Some(Arc::new(pair))
+                }
+                Err(e) => {
+                    tracing::warn!("{}; using the passage model for queries too", e);
+                    Some(passage)
+                }
+            },
+            (Some(passage), None) => Some(passage),
+            (None, Some(query)) => {
+                tracing::warn!("Passage model unavailable; using the query model for everything");
+                Some(query)
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+/// Passage model for documents, query model for `embed_query`.
+pub struct EmbedderPair {
+    passage: Arc<dyn EmbedderBackend>,
+    query: Arc<dyn EmbedderBackend>,
+}
+
+impl EmbedderPair {
+    /// Pair two models; fails unless their dimensions match.
+    pub fn new(passage: Arc<dyn EmbedderBackend>, query: Arc<dyn EmbedderBackend>) -> Result<Self, String> {
+        if passage.dimension() != query.dimension() {
+            return Err(format!(
+                "Query model {} has dimension {}, passage model {} has {}",
+                query.model_id(),
+                query.dimension(),
+                passage.model_id(),
+                passage.dimension()
+            ));
+        }
+        Ok(Self { passage, query })
+    }
+}
+
+impl EmbedderBackend for EmbedderPair {
+    fn embed(&self, text: &str) -> Option<EmbeddingResult> {
+        self.passage.embed(text)
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Vec<Option<EmbeddingResult>> {
+        self.passage.embed_batch(texts)
+    }
+
+    fn embed_query(&self, text: &str) -> Option<EmbeddingResult> {
+        self.query.embed_query(text)
+    }
+
+    fn dimension(&self) -> usize {
+        self.passage.dimension()
+    }
+
+    fn is_available(&self) -> bool {
+        self.passage.is_available()
+    }
+
+    fn model_id(&self) -> &str {
+        self.passage.model_id()
+    }
+
+    fn stats(&self) -> EmbedderStats {
+        self.passage.stats()
+    }
+
+    fn query_embedder(&self) -> Option<&dyn EmbedderBackend> {
+        Some(self.query.as_ref())
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.passage.memory_bytes() + self.query.memory_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ndarray::Array1;
+    use parking_lot::Mutex;
+    use tempfile::TempDir;
+
+    use crate::embedder::NoopEmbedder;
+
+    /// Records the texts each model was asked to embed.
+    struct Recording {
+        model_id: &'static str,
+        seen: Mutex<Vec<String>>,
+    }
+
+    impl Recording {
+        fn new(model_id: &'static str) -> Arc<Self> {
+            Arc::new(Self {
+                model_id,
+                seen: Mutex::new(Vec::new()),
+            })
+        }
+    }
+
+    impl EmbedderBackend for Recording {
+        fn embed(&self, text: &str) -> Option<EmbeddingResult> {
+            self.seen.lock().push(text.to_string());
+            Some(EmbeddingResult {
+                embedding: Array1::zeros(8),
+                cached: false,
+                input_token_count: None,
+                truncated: false,
+            })
+        }
+
+        fn dimension(&self) -> usize {
+            8
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn model_id(&self) -> &str {
+            self.model_id
+        }
+    }
+
+    #[test]
+    fn test_queries_go_to_the_query_model() {
+        let (large, small) = (Recording::new("large"), Recording::new("small"));
+        let pair = EmbedderPair::new(large.clone(), small.clone()).unwrap();
+        pair.embed("a passage");
+        pair.embed_batch(&["another", "and another"]);
+        pair.embed_query("a query");
+
+        assert_eq!(*large.seen.lock(), ["a passage", "another", "and another"]);
+        assert_eq!(*small.seen.lock(), ["a query"]);
+        assert_eq!(pair.model_id(), "large");
+        assert_eq!(pair.query_embedder().map(|q| q.model_id()), Some("small"));
+        // A single model embeds its own queries
+        assert!(large.query_embedder().is_none());
+        large.embed_query("alone");
+        assert_eq!(large.seen.lock().last().map(String::as_str), Some("alone"));
+
+        let wide: Arc<dyn EmbedderBackend> = Arc::new(NoopEmbedder::new(768));
+        let err = EmbedderPair::new(wide, Arc::new(NoopEmbedder::new(384))).err().unwrap();
+        assert!(err.contains("dimension 384"), "{}", err);
+    }
+
+    #[test]
+    fn test_manifest_builds_a_pair_or_falls_back() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(ModelsManifest::read(dir.path()).unwrap(), None);
+        std::fs::write(
+            dir.path().join(MODELS_MANIFEST),
+            r#"{"passage_model": "large", "query_model": "small"}"#,
+        )
+        .unwrap();
+        let manifest = ModelsManifest::read(dir.path()).unwrap().unwrap();
+
+        // Each model is a NoopEmbedder named after its directory; `missing`
+        // lists the ones that fail to load
+        let build = |missing: &[&str], dims: (usize, usize)| {
+            manifest.build(dir.path(), |path| {
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                if missing.contains(&name.as_str()) {
+                    return Err("not found".into());
+                }
+                let dim = if name == "large" { dims.0 } else { dims.1 };
+                Ok(Arc::new(NoopEmbedder::new(dim).with_model_id(name)) as Arc<dyn EmbedderBackend>)
+            })
+        };
+        let ids = |embedder: Option<Arc<dyn EmbedderBackend>>| {
+            let embedder = embedder.unwrap();
+            let query = embedder.query_embedder().map(|q| q.model_id().to_string());
+            (embedder.model_id().to_string(), query)
+        };
+
+        assert_eq!(ids(build(&[], (768, 768))), ("large".into(), Some("small".into())));
+        assert_eq!(ids(build(&[], (768, 384))), ("large".into(), None));
+        assert_eq!(ids(build(&["small"], (768, 768))), ("large".into(), None));
+        assert_eq!(ids(build(&["large"], (768, 768))), ("small".into(), None));
+        assert!(build(&["large", "small"], (768, 768)).is_none());
+
+        std::fs::write(dir.path().join(MODELS_MANIFEST), "{").unwrap();
+        assert!(ModelsManifest::read(dir.path()).is_err());
+    }
+}
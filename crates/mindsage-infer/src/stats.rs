@@ -0,0 +1,162 @@
+//! Embedder statistics — per-session batch latency and input truncation.
+//!
+//! Models read a bounded number of tokens, and text past the limit is cut
+//! off without an error. Embedders record each batch they run in an
+//! `EmbedderStatsRecorder`; `EmbedderBackend::stats` returns the totals.
+
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::embedder::EmbeddingResult;
+
+/// Totals since the embedder was loaded. Cache hits are not counted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbedderStats {
+    /// Batches that ran the model (a single `embed` is a batch of one).
+    pub batches: u64,
+    /// Texts embedded by the model.
+    pub texts: u64,
+    /// Texts cut off at the model's input limit.
+    pub truncated: u64,
+    /// `truncated / texts` (0 before the first text).
+    pub truncation_rate: f64,
+    /// Input tokens of the embedded texts, before truncation.
+    pub input_tokens: u64,
+    pub mean_latency_ms: f64,
+    pub max_latency_ms: f64,
+}
+
+#[derive(Default)]
+struct Totals {
+    batches: u64,
+    texts: u64,
+    truncated: u64,
+    input_tokens: u64,
+    latency: Duration,
+    max_latency: Duration,
+}
+
+/// Accumulates `EmbedderStats` for one embedder.
+#[derive(Default)]
+pub struct EmbedderStatsRecorder {
+    totals: Mutex<Totals>,
+}
+
+impl EmbedderStatsRecorder {
+    /// Record a batch that took `latency` and produced `results`. Cached
+    /// and failed results are skipped; a batch without any other result
+    /// did not run the model and is not counted.
+    pub fn record_batch(&self, latency: Duration, results: &[Option<EmbeddingResult>]) {
+        let computed: Vec<&EmbeddingResult> =
+            results.iter().flatten().filter(|r| !r.cached).collect();
+        if computed.is_empty() {
+            return;
+        }
+        let mut totals = self.totals.lock();
+        totals.batches += 1;
+        totals.texts += computed.len() as u64;
+        totals.truncated += computed.iter().filter(|r| r.truncated).count() as u64;
+        totals.input_tokens += computed
+            .iter()
+            .filter_map(|r| r.input_token_count)
+            .sum::<usize>() as u64;
+        totals.latency += latency;
+        totals.max_latency = totals.max_latency.max(latency);
+    }
+
+    pub fn snapshot(&self) -> EmbedderStats {
+        let totals = self.totals.lock();
+        let ratio = |n: f64, d: u64| if d == 0 { 0.0 } else { n / d as f64 };
+        EmbedderStats {
+            batches: totals.batches,
+            texts: totals.texts,
+            truncated: totals.truncated,
+            truncation_rate: ratio(totals.truncated as f64, totals.texts),
+            input_tokens: totals.input_tokens,
+            mean_latency_ms: ratio(totals.latency.as_secs_f64() * 1000.0, totals.batches),
+            max_latency_ms: totals.max_latency.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::EmbedderBackend;
+    use ndarray::Array1;
+
+    /// Reads at most `limit` words, one token each.
+    struct WordLimitEmbedder {
+        limit: usize,
+        stats: EmbedderStatsRecorder,
+    }
+
+    impl WordLimitEmbedder {
+        fn result(&self, text: &str) -> Option<EmbeddingResult> {
+            let tokens = text.split_whitespace().count();
+            Some(EmbeddingResult {
+                embedding: Array1::zeros(4),
+                cached: text.starts_with("cached"),
+                input_token_count: Some(tokens),
+                truncated: tokens > self.limit,
+            })
+        }
+
+        /// Run a batch as if it took `ms`.
+        fn run(&self, texts: &[&str], ms: u64) -> Vec<Option<EmbeddingResult>> {
+            let results: Vec<_> = texts.iter().map(|t| self.result(t)).collect();
+            self.stats.record_batch(Duration::from_millis(ms), &results);
+            results
+        }
+    }
+
+    impl EmbedderBackend for WordLimitEmbedder {
+        fn embed(&self, text: &str) -> Option<EmbeddingResult> {
+            self.run(&[text], 0).pop().flatten()
+        }
+
+        fn dimension(&self) -> usize {
+            4
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn model_id(&self) -> &str {
+            "words"
+        }
+
+        fn stats(&self) -> EmbedderStats {
+            self.stats.snapshot()
+        }
+    }
+
+    #[test]
+    fn test_stats_aggregate_batches() {
+        let embedder = WordLimitEmbedder {
+            limit: 3,
+            stats: EmbedderStatsRecorder::default(),
+        };
+        assert_eq!(embedder.stats(), EmbedderStats::default());
+
+        embedder.run(&["one two", "one two three four five"], 10);
+        embedder.run(&["a b c d", "x", "y z"], 40);
+        // Only cache hits: the model did not run
+        embedder.run(&["cached query"], 500);
+        // The cache hit in a mixed batch is not a text
+        embedder.run(&["cached query", "p q r s t u"], 25);
+
+        let stats = embedder.stats();
+        assert_eq!(stats.batches, 3);
+        assert_eq!(stats.texts, 6);
+        assert_eq!(stats.truncated, 3);
+        assert!((stats.truncation_rate - 0.5).abs() < 1e-9);
+        assert_eq!(stats.input_tokens, 2 + 5 + 4 + 1 + 2 + 6);
+        assert!((stats.mean_latency_ms - 25.0).abs() < 1e-9);
+        assert!((stats.max_latency_ms - 40.0).abs() < 1e-9);
+    }
+}
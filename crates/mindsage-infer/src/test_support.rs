@@ -0,0 +1,56 @@
+//! A deterministic embedder for tests here and in dependent crates, which
+//! enable the `test-support` feature as a dev-dependency.
+
+use ndarray::Array1;
+
+use crate::embedder::{EmbedderBackend, EmbeddingResult};
+
+/// Bag-of-words embedder: each lowercased word adds to one of `dim`
+/// buckets chosen by a stable hash, and the vector is unit length. Texts
+/// sharing words are close, and a text always embeds the same way.
+pub struct WordEmbedder {
+    dim: usize,
+}
+
+impl WordEmbedder {
+    pub fn new(dim: usize) -> Self {
+        Self { dim }
+    }
+}
+
+impl EmbedderBackend for WordEmbedder {
+    fn embed(&self, text: &str) -> Option<EmbeddingResult> {
+        let mut embedding = Array1::<f32>::zeros(self.dim);
+        let words = text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty());
+        for word in words {
+            // FNV-1a, which unlike `DefaultHasher` is stable across runs
+            let hash = word
+                .to_lowercase()
+                .bytes()
+                .fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+            embedding[(hash % self.dim as u64) as usize] += 1.0;
+        }
+        let norm = embedding.dot(&embedding).sqrt();
+        if norm > 0.0 {
+            embedding /= norm;
+        }
+        Some(EmbeddingResult {
+            embedding,
+            cached: false,
+            input_token_count: None,
+            truncated: false,
+        })
+    }
+
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn model_id(&self) -> &str {
+        "words"
+    }
+}
@@ -4,7 +4,16 @@
 //! Only level=1 paragraphs get embeddings and are searched.
 //! Default chunk size 512 chars aligned with all-MiniLM-L6-v2 (256 tokens).
 
+use mindsage_store::NewChunk;
+use once_cell::sync::Lazy;
 use regex::Regex;
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::time::Duration;
+
+use crate::conversation::{self, ConversationFormat, CONVERSATION_CHUNK_SIZE};
+use crate::file::transcript::{self, TranscriptFormat, DEFAULT_TRANSCRIPT_WINDOW};
+use crate::outline::DocumentOutline;
 
 /// Default chunk size aligned with embedding model (all-MiniLM-L6-v2: 256 tokens ≈ 512 chars).
 pub const DEFAULT_CHUNK_SIZE: usize = 512;
@@ -38,6 +47,7 @@ pub struct HierarchicalChunk {
 }
 
 /// Recursive chunker that respects document structure.
+#[derive(Clone)]
 pub struct RecursiveChunker {
     pub chunk_size: usize,
     pub chunk_overlap: usize,
@@ -127,6 +137,9 @@ impl RecursiveChunker {
     }
 }
 
+/// Section breaks: a markdown header line, or two or more blank lines.
+static SECTION_BREAK: Lazy<Regex> = Lazy::new(|| Regex::new(r"(\n#{1,6}\s)|(\n\n\n+)").unwrap());
+
 /// Three-level hierarchical chunker: section → paragraph.
 pub struct HierarchicalChunker {
     paragraph_chunker: RecursiveChunker,
@@ -142,71 +155,158 @@ impl HierarchicalChunker {
     /// Split text into a hierarchical chunk list.
     /// Returns level=0 (sections) and level=1 (paragraphs) interleaved.
     pub fn chunk(&self, text: &str) -> Vec<HierarchicalChunk> {
-        let sections = self.split_sections(text);
-        let mut all_chunks = Vec::new();
-
-        for (sec_text, sec_start) in &sections {
-            let section_idx = all_chunks.len();
-            all_chunks.push(HierarchicalChunk {
-                text: sec_text.clone(),
-                level: 0,
-                chunk_index: section_idx,
-                char_start: *sec_start,
-                char_end: sec_start + sec_text.len(),
-                parent_index: None,
-            });
+        // Reading from a string cannot fail
+        self.chunk_reader(text.as_bytes()).map_while(Result::ok).collect()
+    }
 
-            // Split section into paragraph-level chunks
-            let para_chunks = self.paragraph_chunker.chunk(sec_text);
-            for pc in para_chunks {
-                let para_idx = all_chunks.len();
-                all_chunks.push(HierarchicalChunk {
-                    text: pc.text,
-                    level: 1,
-                    chunk_index: para_idx,
-                    char_start: sec_start + pc.start_char,
-                    char_end: sec_start + pc.end_char,
-                    parent_index: Some(section_idx),
-                });
-            }
+    /// Streaming `chunk`: yields the chunks of the text read from `reader`
+    /// section by section, holding only the current section (and the lines
+    /// read past it) in memory. Offsets are byte offsets into the whole
+    /// text and indexes count from its first chunk, as with `chunk`.
+    pub fn chunk_reader<R: BufRead>(&self, reader: R) -> ChunkReader<R> {
+        ChunkReader {
+            reader,
+            paragraph_chunker: self.paragraph_chunker.clone(),
+            buf: String::new(),
+            buf_start: 0,
+            section_start: 0,
+            scan_from: 0,
+            found_break: false,
+            yielded_section: false,
+            ready: VecDeque::new(),
+            next_index: 0,
+            done: false,
+            max_buffered: 0,
         }
-
-        all_chunks
     }
+}
+
+/// Chunks of a text being read, from `HierarchicalChunker::chunk_reader`.
+///
+/// Sections end at the same breaks as in `chunk`: a break is only taken
+/// once the lines after it are read, since a blank line may be the start
+/// of a longer gap. A text without breaks is one section, held whole.
+pub struct ChunkReader<R> {
+    reader: R,
+    paragraph_chunker: RecursiveChunker,
+    /// Text read from byte `buf_start` of the whole text on.
+    buf: String,
+    buf_start: usize,
+    /// Start of the current section in `buf`.
+    section_start: usize,
+    /// Where the next search for a break starts in `buf`; no break
+    /// starts before it.
+    scan_from: usize,
+    found_break: bool,
+    /// Until a section is yielded, the whole text is kept: a text whose
+    /// sections are all blank becomes one untrimmed section.
+    yielded_section: bool,
+    ready: VecDeque<HierarchicalChunk>,
+    next_index: usize,
+    done: bool,
+    max_buffered: usize,
+}
 
-    /// Split text into sections by headers or large gaps.
-    fn split_sections(&self, text: &str) -> Vec<(String, usize)> {
-        let re = Regex::new(r"(\n#{1,6}\s)|(\n\n\n+)").unwrap();
-        let matches: Vec<_> = re.find_iter(text).collect();
+impl<R: BufRead> ChunkReader<R> {
+    /// Most text held at once, in bytes.
+    pub fn max_buffered(&self) -> usize {
+        self.max_buffered
+    }
 
-        if matches.is_empty() {
-            return vec![(text.to_string(), 0)];
+    /// Read one line and yield the sections it ends.
+    fn read_line(&mut self) -> std::io::Result<()> {
+        let read = self.reader.read_line(&mut self.buf)?;
+        self.max_buffered = self.max_buffered.max(self.buf.len());
+        let eof = read == 0;
+        // After a blank line the gap may go on: look again after the next
+        // line. Otherwise every break before the last newline is settled.
+        if !eof && &self.buf[self.buf.len() - read..] == "\n" {
+            return Ok(());
         }
+        let limit = if eof { self.buf.len() + 1 } else { self.buf.len() - 1 };
 
-        let mut sections = Vec::new();
-        let mut prev_end = 0;
+        while let Some(m) = SECTION_BREAK.find_at(&self.buf, self.scan_from) {
+            let (start, end) = (m.start(), m.end());
+            if start >= limit {
+                break;
+            }
+            self.found_break = true;
+            if start > self.section_start {
+                let text = self.buf[self.section_start..start].trim().to_string();
+                self.push_section(text, self.buf_start + self.section_start);
+            }
+            self.section_start = start;
+            self.scan_from = end;
+        }
 
-        for m in &matches {
-            if m.start() > prev_end {
-                let section_text = text[prev_end..m.start()].trim().to_string();
-                if !section_text.is_empty() {
-                    sections.push((section_text, prev_end));
-                }
+        if eof {
+            self.done = true;
+            if !self.found_break {
+                let text = std::mem::take(&mut self.buf);
+                self.push_section(text, 0);
+                return Ok(());
             }
-            prev_end = m.start();
+            let text = self.buf[self.section_start..].trim().to_string();
+            self.push_section(text, self.buf_start + self.section_start);
+            if !self.yielded_section {
+                let text = std::mem::take(&mut self.buf);
+                self.push_section(text, 0);
+            }
+            return Ok(());
         }
 
-        // Last section
-        let remaining = text[prev_end..].trim().to_string();
-        if !remaining.is_empty() {
-            sections.push((remaining, prev_end));
+        self.scan_from = self.scan_from.max(limit);
+        if self.yielded_section && self.section_start > 0 {
+            self.buf.drain(..self.section_start);
+            self.buf_start += self.section_start;
+            self.scan_from -= self.section_start;
+            self.section_start = 0;
         }
+        Ok(())
+    }
 
-        if sections.is_empty() {
-            return vec![(text.to_string(), 0)];
+    /// Queue a section starting at byte `start` and its paragraphs.
+    /// Blank sections are skipped, except for the whole text.
+    fn push_section(&mut self, text: String, start: usize) {
+        if text.is_empty() && self.found_break {
+            return;
         }
+        let section_idx = self.next_index;
+        let paragraphs = self.paragraph_chunker.chunk(&text);
+        self.ready.push_back(HierarchicalChunk {
+            level: 0,
+            chunk_index: section_idx,
+            char_start: start,
+            char_end: start + text.len(),
+            parent_index: None,
+            text,
+        });
+        self.next_index = section_idx + 1 + paragraphs.len();
+        for (i, pc) in paragraphs.into_iter().enumerate() {
+            self.ready.push_back(HierarchicalChunk {
+                text: pc.text,
+                level: 1,
+                chunk_index: section_idx + 1 + i,
+                char_start: start + pc.start_char,
+                char_end: start + pc.end_char,
+                parent_index: Some(section_idx),
+            });
+        }
+        self.yielded_section = true;
+    }
+}
+
+impl<R: BufRead> Iterator for ChunkReader<R> {
+    type Item = std::io::Result<HierarchicalChunk>;
 
-        sections
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.ready.is_empty() && !self.done {
+            if let Err(e) = self.read_line() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        self.ready.pop_front().map(Ok)
     }
 }
 
@@ -216,8 +316,119 @@ impl Default for HierarchicalChunker {
     }
 }
 
+/// Chunks to store for a document: hierarchical sections and paragraphs for
+/// long text, a single paragraph chunk otherwise. Each chunk carries the
+/// heading path, page and speaker at its start (see `outline`). Chat
+/// transcripts, recognized by the document's `metadata`, are chunked by
+/// exchange instead (see `conversation`).
+pub fn plan_chunks(text: &str, metadata: Option<&serde_json::Value>, file_extension: Option<&str>) -> Vec<NewChunk> {
+    plan_chunks_with_window(text, metadata, file_extension, DEFAULT_TRANSCRIPT_WINDOW)
+}
+
+/// `plan_chunks` with the time window of transcripts (`.vtt`, `.srt`), which
+/// are chunked by time rather than length. Transcript text that is not in
+/// the extracted turn format is chunked like any other text.
+pub fn plan_chunks_with_window(
+    text: &str,
+    metadata: Option<&serde_json::Value>,
+    file_extension: Option<&str>,
+    transcript_window: Duration,
+) -> Vec<NewChunk> {
+    plan_chunk_stream(text, metadata, file_extension, transcript_window).collect()
+}
+
+/// `plan_chunks_with_window` one chunk at a time, so the chunks of a long
+/// document can be stored as they are made instead of all at once.
+pub fn plan_chunk_stream<'a>(
+    text: &'a str,
+    metadata: Option<&serde_json::Value>,
+    file_extension: Option<&str>,
+    transcript_window: Duration,
+) -> Box<dyn Iterator<Item = NewChunk> + 'a> {
+    if let Some(format) = metadata.and_then(ConversationFormat::from_metadata) {
+        let chunks = plan_conversation_chunks(text, format);
+        if !chunks.is_empty() {
+            return Box::new(chunks.into_iter());
+        }
+    }
+    if file_extension.and_then(TranscriptFormat::from_extension).is_some() {
+        let chunks = plan_transcript_chunks(text, transcript_window);
+        if !chunks.is_empty() {
+            return Box::new(chunks.into_iter());
+        }
+    }
+
+    let outline = DocumentOutline::parse(text, file_extension);
+    if should_chunk(text, file_extension) {
+        let (chunk_size, chunk_overlap) = calculate_chunk_size(file_extension);
+        let chunks = HierarchicalChunker::new(chunk_size, chunk_overlap)
+            .chunk_reader(text.as_bytes())
+            .map_while(Result::ok);
+        Box::new(chunks.map(move |chunk| NewChunk {
+            metadata: outline.metadata_at(text, chunk.char_start).to_value(),
+            text: chunk.text,
+            chunk_index: chunk.chunk_index as i32,
+            level: chunk.level,
+            parent_index: chunk.parent_index.map(|pi| pi as i32),
+            char_start: Some(chunk.char_start as i32),
+            char_end: Some(chunk.char_end as i32),
+        }))
+    } else {
+        Box::new(std::iter::once(NewChunk {
+            text: text.to_string(),
+            chunk_index: 0,
+            level: 1,
+            parent_index: None,
+            char_start: Some(0),
+            char_end: Some(text.len() as i32),
+            metadata: outline.metadata_at(text, 0).to_value(),
+        }))
+    }
+}
+
+/// Paragraph chunks of a transcript, one per time window, with the window's
+/// time range and speakers as metadata.
+pub fn plan_transcript_chunks(text: &str, window: Duration) -> Vec<NewChunk> {
+    transcript::transcript_chunks(text, window)
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| NewChunk {
+            metadata: Some(chunk.metadata()),
+            text: chunk.text,
+            chunk_index: i as i32,
+            level: 1,
+            parent_index: None,
+            char_start: Some(chunk.char_start as i32),
+            char_end: Some(chunk.char_end as i32),
+        })
+        .collect()
+}
+
+/// Paragraph chunks of a chat transcript, whole exchanges where they fit,
+/// with the roles and message indices they cover as metadata.
+pub fn plan_conversation_chunks(text: &str, format: ConversationFormat) -> Vec<NewChunk> {
+    conversation::conversation_chunks(text, format, CONVERSATION_CHUNK_SIZE)
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| NewChunk {
+            metadata: Some(chunk.metadata()),
+            text: chunk.text,
+            chunk_index: i as i32,
+            level: 1,
+            parent_index: None,
+            char_start: Some(chunk.char_start as i32),
+            char_end: Some(chunk.char_end as i32),
+        })
+        .collect()
+}
+
 /// Determine if text should be chunked based on size and content.
+/// Transcripts always are, by time window.
 pub fn should_chunk(text: &str, file_extension: Option<&str>) -> bool {
+    if file_extension.and_then(TranscriptFormat::from_extension).is_some() {
+        return true;
+    }
+
     let text_length = text.len();
     if text_length < 2000 {
         return false;
@@ -290,10 +501,188 @@ mod tests {
         assert!(chunks.iter().any(|c| c.level == 1));
     }
 
+    /// Chunks as `HierarchicalChunker::chunk` made them from the whole
+    /// text before it was streaming.
+    fn whole_text_chunks(chunker: &HierarchicalChunker, text: &str) -> Vec<(String, i32, usize, usize, usize, Option<usize>)> {
+        let matches: Vec<_> = SECTION_BREAK.find_iter(text).collect();
+        let mut sections = Vec::new();
+        let mut prev_end = 0;
+        for m in &matches {
+            if m.start() > prev_end && !text[prev_end..m.start()].trim().is_empty() {
+                sections.push((text[prev_end..m.start()].trim(), prev_end));
+            }
+            prev_end = m.start();
+        }
+        if !matches.is_empty() && !text[prev_end..].trim().is_empty() {
+            sections.push((text[prev_end..].trim(), prev_end));
+        }
+        if sections.is_empty() {
+            sections.push((text, 0));
+        }
+
+        let mut chunks = Vec::new();
+        for (sec_text, sec_start) in sections {
+            let section_idx = chunks.len();
+            chunks.push((sec_text.to_string(), 0, section_idx, sec_start, sec_start + sec_text.len(), None));
+            for pc in chunker.paragraph_chunker.chunk(sec_text) {
+                let idx = chunks.len();
+                chunks.push((pc.text, 1, idx, sec_start + pc.start_char, sec_start + pc.end_char, Some(section_idx)));
+            }
+        }
+        chunks
+    }
+
+    #[test]
+    fn test_streaming_matches_whole_text_chunking() {
+        let body = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(15);
+        let mut long = String::from("Preface line\n");
+        for i in 0..40 {
+            let gap = ["\n", "\n\n", "\n\n\n", "\n\n\n\n\n"][i % 4];
+            let header = ["# ", "## ", "###### ", "####### ", "##\n", "#\u{3000}", "#x "][i % 7];
+            long.push_str(&format!("{}{}Heading {}\n\n{}\n{}", gap, header, i, body, if i % 5 == 0 { "\n\n\n" } else { "" }));
+        }
+        let fixtures = [
+            long.clone(),
+            long.replace('\n', "\r\n"),
+            format!("\n\n\n\n{}\n\n\n   ", long),
+            body.clone(),
+            String::new(),
+            "   ".into(),
+            "\n\n\n".into(),
+            "\n\n\n\n# a".into(),
+            "text\n##\n\n\nmore".into(),
+            "\n \n\n\n \n# \n".into(),
+        ];
+
+        let chunker = HierarchicalChunker::default();
+        for text in &fixtures {
+            let expected = whole_text_chunks(&chunker, text);
+            let streamed: Vec<_> = chunker
+                .chunk_reader(std::io::BufReader::with_capacity(7, text.as_bytes()))
+                .map(|c| {
+                    let c = c.unwrap();
+                    (c.text, c.level, c.chunk_index, c.char_start, c.char_end, c.parent_index)
+                })
+                .collect();
+            assert_eq!(streamed, expected, "{:?}", text);
+            assert_eq!(chunker.chunk(text).len(), expected.len());
+        }
+        assert!(whole_text_chunks(&chunker, &long).iter().filter(|c| c.1 == 0).count() > 30);
+    }
+
+    /// Sections of generated text, made as they are read.
+    struct GeneratedSections {
+        next: usize,
+        count: usize,
+        pending: Vec<u8>,
+        pos: usize,
+    }
+
+    impl std::io::Read for GeneratedSections {
+        fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+            if self.pos == self.pending.len() {
+                if self.next == self.count {
+                    return Ok(0);
+                }
+                let body = "Generated words for a long mailbox export. ".repeat(8);
+                self.pending = format!("# Message {}\n\n{}\n\n{}\n\n\n", self.next, body, body).into_bytes();
+                self.pos = 0;
+                self.next += 1;
+            }
+            let n = out.len().min(self.pending.len() - self.pos);
+            out[..n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_streaming_buffers_one_section() {
+        let count = 20_000;
+        let input = GeneratedSections { next: 0, count, pending: Vec::new(), pos: 0 };
+        let mut reader = HierarchicalChunker::default().chunk_reader(std::io::BufReader::new(input));
+        let (mut sections, mut last_end) = (0, 0);
+        for chunk in reader.by_ref() {
+            let chunk = chunk.unwrap();
+            if chunk.level == 0 {
+                sections += 1;
+                assert!(chunk.text.starts_with(&format!("# Message {}", sections - 1)));
+            }
+            last_end = chunk.char_end;
+        }
+        assert_eq!(sections, count);
+        assert!(last_end > 14_000_000, "{}", last_end);
+        assert!(reader.max_buffered() < 2_000, "{}", reader.max_buffered());
+    }
+
+    #[test]
+    fn test_plan_chunks_carries_heading_path() {
+        let body = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(20);
+        let text = format!("# Manual\n\n## Setup\n\n{}\n\n## Troubleshooting\n\n{}", body, body);
+        let chunks = plan_chunks(&text, None, Some(".md"));
+
+        let paths: Vec<serde_json::Value> = chunks
+            .iter()
+            .filter(|c| c.level == 1)
+            .map(|c| c.metadata.as_ref().unwrap()["heading_path"].clone())
+            .collect();
+        assert!(paths.contains(&serde_json::json!(["Manual", "Setup"])));
+        assert!(paths.contains(&serde_json::json!(["Manual", "Troubleshooting"])));
+
+        let single = plan_chunks("plain short note", None, None);
+        assert_eq!(single.len(), 1);
+        assert!(single[0].metadata.is_none());
+    }
+
+    #[test]
+    fn test_plan_chunks_windows_transcripts() {
+        let text = (0..10)
+            .map(|i| {
+                let start = transcript::format_timestamp(i * 30_000);
+                let end = transcript::format_timestamp(i * 30_000 + 25_000);
+                let speaker = if i % 2 == 0 { "Ana" } else { "Ben" };
+                format!("[{} --> {}] {}: point {}", start, end, speaker, i)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let chunks = plan_chunks(&text, None, Some(".vtt"));
+        // 0-90s, 120-210s, 240-270s
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.level == 1 && c.parent_index.is_none()));
+        assert_eq!(chunks[1].text, "Ana: point 4\nBen: point 5\nAna: point 6\nBen: point 7");
+        let metadata = chunks[1].metadata.as_ref().unwrap();
+        assert_eq!((metadata["start_ms"].as_u64(), metadata["end_ms"].as_u64()), (Some(120_000), Some(235_000)));
+        assert_eq!(metadata["speakers"], serde_json::json!(["Ana", "Ben"]));
+
+        assert_eq!(plan_chunks_with_window(&text, None, Some(".vtt"), Duration::from_secs(60)).len(), 5);
+        // Text that is not in turn format falls back to regular chunking
+        assert_eq!(plan_chunks("no cues", None, Some(".srt")).len(), 2);
+    }
+
+    #[test]
+    fn test_plan_chunks_by_exchange_for_chat_sources() {
+        let answer = "Mulch keeps the soil moist and the weeds down. ".repeat(30);
+        let text = format!("user: Why mulch?\n\nassistant: {}\n\nuser: Thanks", answer.trim_end());
+        let metadata = serde_json::json!({"source": "chatgpt", "title": "Mulch"});
+        let chunks = plan_chunks(&text, Some(&metadata), None);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.level == 1));
+        assert!(chunks[0].text.starts_with("user: Why mulch?\n\nassistant: Mulch"));
+        assert_eq!(chunks[0].metadata.as_ref().unwrap()["roles"], serde_json::json!(["user", "assistant"]));
+        assert_eq!(chunks[1].metadata.as_ref().unwrap()["continuation"], true);
+
+        // Other sources keep the generic chunker, which leaves text this
+        // short in one chunk
+        let generic = plan_chunks(&text, Some(&serde_json::json!({"source": "file"})), None);
+        assert_eq!(generic.len(), 1);
+    }
+
     #[test]
     fn test_should_chunk() {
         assert!(!should_chunk("short text", None));
         assert!(should_chunk(&"x".repeat(5001), Some(".py")));
         assert!(should_chunk(&"x".repeat(2001), Some(".md")));
+        assert!(should_chunk("[00:00:01.000 --> 00:00:02.000] Hi", Some(".VTT")));
+        assert!(should_chunk("short", Some(".srt")));
     }
 }
@@ -0,0 +1,330 @@
+//! Chat transcripts — documents built from conversations, one
+//! `role: content` message after another.
+//!
+//! ChatGPT exports and browser captures join `user: …` and `assistant: …`
+//! messages with blank lines; Facebook message threads put one
+//! `Sender: text` message per line. Such documents are chunked on message
+//! boundaries by exchange: a user message with the replies up to the next
+//! user message. In a message thread, where no sender is the user, one
+//! sender's messages and the other's answer make an exchange. Short
+//! exchanges share a chunk; one too long for a chunk is split, its first
+//! chunk still holding the question with the start of the answer and the
+//! rest marked as continuations.
+
+use mindsage_core::text::floor_char_boundary;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Exchanges are packed into chunks up to this many bytes.
+pub const CONVERSATION_CHUNK_SIZE: usize = 1024;
+
+/// Bytes of the reply the first chunk of a long exchange always keeps.
+const REPLY_LEAD: usize = 200;
+
+/// Roles that start a message in ChatGPT and browser transcripts.
+const CHAT_ROLES: &[&str] = &["user", "assistant", "system", "tool"];
+
+static SENDER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\p{Lu}[^\n:]{0,59}): ").unwrap());
+
+/// How a transcript's messages are laid out, by document source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversationFormat {
+    /// `role: content` messages separated by blank lines (`chatgpt`,
+    /// `browser-connector-*`).
+    Chat,
+    /// `Sender: text` messages, one per line (Facebook `message_thread`).
+    MessageThread,
+}
+
+impl ConversationFormat {
+    /// Format of a document with `metadata`; `None` for other documents.
+    pub fn from_metadata(metadata: &serde_json::Value) -> Option<Self> {
+        let source = metadata.get("source")?.as_str()?;
+        match source {
+            "chatgpt" => Some(Self::Chat),
+            "facebook" if metadata.get("type").and_then(|t| t.as_str()) == Some("message_thread") => {
+                Some(Self::MessageThread)
+            }
+            s if s.starts_with("browser-connector-") => Some(Self::Chat),
+            _ => None,
+        }
+    }
+}
+
+/// One message: its role (or sender) and byte range in the document,
+/// `role: ` prefix included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub role: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A run of messages, as stored in a chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversationChunk {
+    pub text: String,
+    /// Byte range in the document text.
+    pub char_start: usize,
+    pub char_end: usize,
+    /// Roles of the chunk's messages, in order of first appearance.
+    pub roles: Vec<String>,
+    /// Indices of the first and last message the chunk overlaps.
+    pub message_start: usize,
+    pub message_end: usize,
+    /// The chunk starts inside an exchange rather than at its first message.
+    pub continuation: bool,
+}
+
+impl ConversationChunk {
+    pub fn metadata(&self) -> serde_json::Value {
+        let mut metadata = serde_json::json!({
+            "roles": self.roles,
+            "message_start": self.message_start,
+            "message_end": self.message_end,
+        });
+        if self.continuation {
+            metadata["continuation"] = true.into();
+        }
+        metadata
+    }
+}
+
+/// The messages of `text`. Text before the first message belongs to it.
+pub fn parse_messages(text: &str, format: ConversationFormat) -> Vec<Message> {
+    let separator = match format {
+        ConversationFormat::Chat => "\n\n",
+        ConversationFormat::MessageThread => "\n",
+    };
+    let line_starts = std::iter::once(0).chain(text.match_indices(separator).map(|(i, _)| i + separator.len()));
+    let mut messages: Vec<Message> = Vec::new();
+    for start in line_starts {
+        let rest = &text[start..];
+        let role = match format {
+            ConversationFormat::Chat => CHAT_ROLES
+                .iter()
+                .find(|role| rest.strip_prefix(**role).is_some_and(|r| r.starts_with(": ")))
+                .map(|role| role.to_string()),
+            ConversationFormat::MessageThread => SENDER_RE.captures(rest).map(|c| c[1].to_string()),
+        };
+        if let Some(role) = role {
+            if let Some(last) = messages.last_mut() {
+                last.end = start;
+            }
+            messages.push(Message {
+                role,
+                start,
+                end: text.len(),
+            });
+        }
+    }
+    if let Some(first) = messages.first_mut() {
+        first.start = 0;
+    }
+    for message in &mut messages {
+        message.end = message.start + text[message.start..message.end].trim_end().len();
+    }
+    messages
+}
+
+/// Index of the message that starts each exchange.
+fn exchange_starts(messages: &[Message], format: ConversationFormat) -> Vec<usize> {
+    match format {
+        ConversationFormat::Chat => (0..messages.len())
+            .filter(|&i| i == 0 || messages[i].role == "user")
+            .collect(),
+        // Runs of one sender alternate between prompt and answer
+        ConversationFormat::MessageThread => {
+            let mut starts = Vec::new();
+            let mut run = 0;
+            for i in 0..messages.len() {
+                if i > 0 && messages[i].role != messages[i - 1].role {
+                    run += 1;
+                }
+                if (i == 0 || messages[i].role != messages[i - 1].role) && run % 2 == 0 {
+                    starts.push(i);
+                }
+            }
+            starts
+        }
+    }
+}
+
+/// Chunks of a transcript, or none when `text` has fewer than two messages.
+pub fn conversation_chunks(text: &str, format: ConversationFormat, max_size: usize) -> Vec<ConversationChunk> {
+    let messages = parse_messages(text, format);
+    if messages.len() < 2 {
+        return Vec::new();
+    }
+    let starts = exchange_starts(&messages, format);
+    // Message ranges [first, last) of the exchanges
+    let exchanges: Vec<(usize, usize)> = starts
+        .iter()
+        .zip(starts.iter().skip(1).copied().chain(std::iter::once(messages.len())))
+        .map(|(&first, last)| (first, last))
+        .collect();
+
+    let mut chunks = Vec::new();
+    // Whole exchanges waiting to be stored together
+    let mut pending: Option<(usize, usize)> = None;
+    for &(first, last) in &exchanges {
+        let (start, end) = (messages[first].start, messages[last - 1].end);
+        if let Some((p_first, p_last)) = pending {
+            if end - messages[p_first].start <= max_size {
+                pending = Some((p_first, last));
+                continue;
+            }
+            chunks.push(chunk(text, &messages, messages[p_first].start, messages[p_last - 1].end, false));
+        }
+        if end - start <= max_size {
+            pending = Some((first, last));
+        } else {
+            pending = None;
+            split_exchange(text, &messages[..last], first, max_size, &mut chunks);
+        }
+    }
+    if let Some((first, last)) = pending {
+        chunks.push(chunk(text, &messages, messages[first].start, messages[last - 1].end, false));
+    }
+    chunks
+}
+
+/// Split the exchange starting at message `first` and running to the end of
+/// `messages`. The first piece reaches into the reply.
+fn split_exchange(text: &str, messages: &[Message], first: usize, max_size: usize, chunks: &mut Vec<ConversationChunk>) {
+    let end = messages[messages.len() - 1].end;
+    let mut start = messages[first].start;
+    let mut continuation = false;
+    while start < end {
+        let mut lo = start;
+        let mut hi = (start + max_size).min(end);
+        if !continuation && first + 1 < messages.len() {
+            let reply = messages[first + 1].start;
+            lo = reply;
+            hi = hi.max((reply + REPLY_LEAD).min(messages[first + 1].end)).min(end);
+        }
+        let (piece_end, next) = if hi >= end { (end, end) } else { split_point(text, lo, hi) };
+        chunks.push(chunk(text, messages, start, piece_end, continuation));
+        start = next + (text[next..end].len() - text[next..end].trim_start().len());
+        continuation = true;
+    }
+}
+
+/// Where to end a piece within `(lo, hi]`: after the last paragraph break,
+/// else line break, sentence or space, else at `hi`. Returns the piece's
+/// end and the next piece's start.
+fn split_point(text: &str, lo: usize, hi: usize) -> (usize, usize) {
+    let lo = floor_char_boundary(text, lo);
+    let hi = floor_char_boundary(text, hi);
+    let window = &text[lo..hi];
+    for separator in ["\n\n", "\n", ". ", " "] {
+        if let Some(i) = window.rfind(separator).filter(|&i| i > 0) {
+            let keep = if separator == ". " { 1 } else { 0 };
+            return (lo + i + keep, lo + i + separator.len());
+        }
+    }
+    if hi > lo {
+        (hi, hi)
+    } else {
+        let next = text[hi..].chars().next().map_or(hi, |c| hi + c.len_utf8());
+        (next, next)
+    }
+}
+
+fn chunk(text: &str, messages: &[Message], start: usize, end: usize, continuation: bool) -> ConversationChunk {
+    let overlapping: Vec<usize> = (0..messages.len())
+        .filter(|&i| messages[i].start < end && messages[i].end > start)
+        .collect();
+    let mut roles: Vec<String> = Vec::new();
+    for &i in &overlapping {
+        if !roles.contains(&messages[i].role) {
+            roles.push(messages[i].role.clone());
+        }
+    }
+    let trimmed = text[start..end].trim_end();
+    ConversationChunk {
+        text: trimmed.to_string(),
+        char_start: start,
+        char_end: start + trimmed.len(),
+        roles,
+        message_start: overlapping.first().copied().unwrap_or(0),
+        message_end: overlapping.last().copied().unwrap_or(0),
+        continuation,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ChatGPT-style transcript: a system preamble, short exchanges, a
+    /// long answer and a user message with two replies.
+    fn fixture() -> String {
+        let long_answer = (1..=12)
+            .map(|i| format!("Step {}: water the seedlings and check the soil moisture before noon.", i))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        [
+            "system: You are a gardening assistant.".to_string(),
+            "user: When should I prune roses?".to_string(),
+            "assistant: In late winter, just as the buds swell.".to_string(),
+            "user: And tomatoes?".to_string(),
+            "assistant: Pinch out side shoots weekly.".to_string(),
+            format!("user: {}", "How do I raise seedlings indoors without them going leggy? ".repeat(4).trim_end()),
+            format!("assistant: {}", long_answer),
+            "user: Thanks!".to_string(),
+            "assistant: You're welcome.".to_string(),
+            "tool: logged".to_string(),
+        ]
+        .join("\n\n")
+    }
+
+    #[test]
+    fn test_chunks_start_at_exchanges() {
+        let text = fixture();
+        let messages = parse_messages(&text, ConversationFormat::Chat);
+        assert_eq!(messages.len(), 10);
+        let chunks = conversation_chunks(&text, ConversationFormat::Chat, 400);
+        assert!(chunks.len() > 3);
+
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.char_start..chunk.char_end], chunk.text);
+            let starts_message = messages.iter().find(|m| m.start == chunk.char_start);
+            // Only continuations start with a reply or inside a message
+            if !chunk.continuation {
+                let role = &starts_message.expect("a chunk starts at a message").role;
+                assert!(role == "user" || chunk.char_start == 0, "chunk starts with {}", role);
+            }
+            assert!(chunk.roles.contains(&messages[chunk.message_start].role));
+        }
+        // The long question keeps the start of its answer
+        let question = chunks.iter().find(|c| c.text.starts_with("user: How do I")).unwrap();
+        assert_eq!(question.roles, vec!["user", "assistant"]);
+        assert!(question.text.contains("assistant: Step 1"));
+        assert!(chunks.iter().any(|c| c.continuation && c.text.starts_with("Step")));
+        // Short exchanges share a chunk
+        assert_eq!(chunks[0].message_start, 0);
+        assert!(chunks[0].text.contains("And tomatoes?"));
+        let last = chunks.last().unwrap();
+        assert_eq!((last.message_end, last.continuation), (9, false));
+        assert_eq!(last.metadata()["roles"], serde_json::json!(["user", "assistant", "tool"]));
+    }
+
+    #[test]
+    fn test_format_from_metadata_and_message_threads() {
+        let format = |m| ConversationFormat::from_metadata(&m);
+        assert_eq!(format(serde_json::json!({"source": "browser-connector-claude"})), Some(ConversationFormat::Chat));
+        assert_eq!(format(serde_json::json!({"source": "facebook", "type": "post"})), None);
+        assert_eq!(format(serde_json::json!({"source": "file"})), None);
+
+        let thread = "Ana: Are you coming?\nAna: We start at 8\nBo: Yes\nBo: Bringing cake\nAna: Great\nCy: Me too";
+        let messages = parse_messages(thread, ConversationFormat::MessageThread);
+        assert_eq!(messages.len(), 6);
+        assert_eq!(exchange_starts(&messages, ConversationFormat::MessageThread), vec![0, 4]);
+        let chunks = conversation_chunks(thread, ConversationFormat::MessageThread, 80);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].roles, vec!["Ana", "Bo"]);
+        assert!(chunks[1].text.starts_with("Ana: Great"));
+        assert!(conversation_chunks("Just a note", ConversationFormat::Chat, 40).is_empty());
+    }
+}
@@ -11,8 +11,13 @@ pub mod passages;
 pub mod stemmer;
 pub mod topics;
 
+use mindsage_core::text::truncate_text;
 use serde::{Deserialize, Serialize};
 
+/// Version of the extraction heuristics. Bump it when `extract_all` or
+/// `build_enriched_text` changes output, so existing chunks can be re-extracted.
+pub const CURRENT_EXTRACTION_VERSION: u32 = 1;
+
 /// Combined extraction result for a document.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExtractionResult {
@@ -87,8 +92,7 @@ pub fn build_enriched_text(result: &ExtractionResult) -> String {
     }
     if !result.key_passages.is_empty() {
         let joined: String = result.key_passages.join(" ");
-        let truncated = if joined.len() > 500 { &joined[..500] } else { &joined };
-        parts.push(format!("passages: {}", truncated));
+        parts.push(format!("passages: {}", truncate_text(&joined, 500)));
     }
 
     let sm = &result.structured_metadata;
@@ -1,8 +1,34 @@
 //! Heuristic key sentence extraction — port of Python's _extract_key_sentences_heuristic().
 //!
 //! Scores sentences by position, length, indicator words, and information density.
+//! `extract_passage` cuts the part of a search hit around the query terms;
+//! `rank_sentences` orders a hit's sentences by how many query terms they
+//! contain.
 
+use mindsage_core::text::truncate_text;
 use regex::Regex;
+use std::collections::HashSet;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Characters kept on each side of a query match by default.
+pub const DEFAULT_PASSAGE_WINDOW: usize = 200;
+
+/// Words too common to tell whether a sentence answers a query.
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "did", "do", "does", "for", "from", "how",
+    "i", "in", "is", "it", "my", "of", "on", "or", "the", "to", "was", "what", "when", "where",
+    "which", "who", "why", "with",
+];
+
+/// Distinct lowercased query terms, without surrounding punctuation.
+fn query_terms(query: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    query
+        .split_whitespace()
+        .map(|t| t.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|t| !t.is_empty() && seen.insert(t.clone()))
+        .collect()
+}
 
 /// Split text into sentences (no lookbehind — Rust regex doesn't support it).
 fn split_sentences(text: &str) -> Vec<&str> {
@@ -37,8 +63,7 @@ pub fn extract_key_sentences(text: &str, max_sentences: usize) -> Vec<String> {
         .collect();
 
     if sentences.is_empty() {
-        let truncated = if text.len() > 500 { &text[..500] } else { text };
-        return vec![truncated.to_string()];
+        return vec![truncate_text(text, 500).to_string()];
     }
 
     let total = sentences.len();
@@ -105,7 +130,7 @@ pub fn extract_key_sentences(text: &str, max_sentences: usize) -> Vec<String> {
         })
         .collect();
 
-    scored.sort_by(|a, b| b.0.cmp(&a.0));
+    scored.sort_by_key(|s| std::cmp::Reverse(s.0));
 
     // For large documents, ensure position diversity
     if total > 10 && max_sentences >= 3 {
@@ -146,6 +171,120 @@ pub fn extract_key_sentences(text: &str, max_sentences: usize) -> Vec<String> {
     }
 }
 
+/// The part of `text` around the query terms, about `2 * window`
+/// characters long. The span is placed to cover as many distinct query
+/// terms as possible (the earliest such span on ties), then widened to word
+/// boundaries, which never split a grapheme. Elided ends are marked with
+/// "...". Without a match the passage is the start of the text.
+pub fn extract_passage(text: &str, query: &str, window: usize) -> String {
+    let span = window.max(1) * 2;
+    let byte_at: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+    let char_count = byte_at.len();
+
+    // Lowercased text, with the index in `text` of the char each came from
+    let mut lower: Vec<char> = Vec::with_capacity(char_count);
+    let mut origin: Vec<usize> = Vec::with_capacity(char_count);
+    for (ci, c) in text.chars().enumerate() {
+        for lc in c.to_lowercase() {
+            lower.push(lc);
+            origin.push(ci);
+        }
+    }
+
+    let terms: Vec<Vec<char>> = query_terms(query).iter().map(|t| t.chars().collect()).collect();
+
+    // (start char, end char, term) of every match
+    let mut matches: Vec<(usize, usize, usize)> = Vec::new();
+    for (t, term) in terms.iter().enumerate() {
+        for i in 0..(lower.len() + 1).saturating_sub(term.len()) {
+            if lower[i..i + term.len()] == term[..] {
+                matches.push((origin[i], origin[i + term.len() - 1] + 1, t));
+            }
+        }
+    }
+    matches.sort_unstable();
+
+    // The span starting at a match that covers the most distinct terms
+    let mut best: Option<(usize, usize, usize)> = None; // (terms, start, covered end)
+    for &(start, _, _) in &matches {
+        let covered: Vec<&(usize, usize, usize)> = matches
+            .iter()
+            .filter(|(s, e, _)| *s >= start && *e <= start + span)
+            .collect();
+        let distinct = covered.iter().map(|(_, _, t)| *t).collect::<HashSet<_>>().len();
+        let end = covered.iter().map(|(_, e, _)| *e).max().unwrap_or(start);
+        if best.map_or(true, |(n, _, _)| distinct > n) {
+            best = Some((distinct, start, end));
+        }
+    }
+    let (start, end) = match best {
+        Some((_, first, last)) => {
+            // Center the covered terms in the span
+            let slack = span.saturating_sub(last - first);
+            let start = first.saturating_sub(slack / 2);
+            let end = (start + span).min(char_count);
+            (end.saturating_sub(span).min(start), end)
+        }
+        None => (0, span.min(char_count)),
+    };
+
+    let to_byte = |ci: usize| byte_at.get(ci).copied().unwrap_or(text.len());
+    let (start, end) = (to_byte(start), to_byte(end));
+    let bounds: Vec<usize> = text
+        .split_word_bound_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(text.len()))
+        .collect();
+    let start = bounds.iter().rev().find(|&&b| b <= start).copied().unwrap_or(0);
+    let end = bounds.iter().find(|&&b| b >= end).copied().unwrap_or(text.len());
+
+    let mut passage = text[start..end].trim().to_string();
+    if start > 0 {
+        passage = format!("...{}", passage);
+    }
+    if end < text.len() {
+        passage = format!("{}...", passage);
+    }
+    passage
+}
+
+/// Query terms that say what a sentence must contain: stop words aside,
+/// unless the query has nothing else.
+fn content_terms(query: &str) -> Vec<String> {
+    let all = query_terms(query);
+    if all.iter().all(|t| STOP_WORDS.contains(&t.as_str())) {
+        return all;
+    }
+    all.into_iter().filter(|t| !STOP_WORDS.contains(&t.as_str())).collect()
+}
+
+fn coverage(text: &str, terms: &[String]) -> f64 {
+    if terms.is_empty() {
+        return 0.0;
+    }
+    let lower = text.to_lowercase();
+    terms.iter().filter(|t| lower.contains(t.as_str())).count() as f64 / terms.len() as f64
+}
+
+/// Share of the distinct query terms, stop words aside, found in `text`
+/// (0 to 1). A query of only stop words counts them all.
+pub fn query_coverage(text: &str, query: &str) -> f64 {
+    coverage(text, &content_terms(query))
+}
+
+/// The sentences of `text` that contain query terms, best first, with
+/// their `query_coverage`. Ties keep text order.
+pub fn rank_sentences<'a>(text: &'a str, query: &str) -> Vec<(f64, &'a str)> {
+    let terms = content_terms(query);
+    let mut ranked: Vec<(f64, &str)> = split_sentences(text)
+        .into_iter()
+        .map(|sentence| (coverage(sentence, &terms), sentence))
+        .filter(|(c, _)| *c > 0.0)
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+    ranked
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -167,4 +306,60 @@ mod tests {
         assert_eq!(result.len(), 1);
         assert_eq!(result[0], "Hello world");
     }
+
+    #[test]
+    fn test_extract_passage_covers_most_query_terms() {
+        let filler = "lorem ipsum dolor sit amet ".repeat(10);
+        let text = format!("Boiler notes. {}Check the boiler pressure and bleed each radiator. {}", filler, filler);
+        let passage = extract_passage(&text, "boiler pressure radiator", 40);
+        assert!(passage.contains("pressure") && passage.contains("radiator"), "{}", passage);
+        assert!(passage.starts_with("...") && passage.ends_with("..."));
+
+        let unmatched = extract_passage(&text, "zebra", 10);
+        assert!(unmatched.starts_with("Boiler notes."));
+        assert_eq!(extract_passage("short", "short", 200), "short");
+    }
+
+    #[test]
+    fn test_rank_sentences_by_query_coverage() {
+        let text = "The ferry leaves at noon. Tickets for the ferry cost five euros at the harbour. \
+                    The harbour closes in winter. Nothing else here.";
+        let ranked = rank_sentences(text, "What do ferry tickets cost?");
+        let sentences: Vec<&str> = ranked.iter().map(|(_, s)| *s).collect();
+        assert_eq!(
+            sentences,
+            ["Tickets for the ferry cost five euros at the harbour.", "The ferry leaves at noon."]
+        );
+        assert!((ranked[0].0 - 1.0).abs() < 1e-9);
+        assert!((ranked[1].0 - 1.0 / 3.0).abs() < 1e-9);
+
+        // Stop words alone still match when nothing else is asked
+        assert_eq!(rank_sentences(text, "the").len(), 3);
+        assert!(rank_sentences(text, "zebra").is_empty());
+        assert!(rank_sentences(text, "  ").is_empty());
+        assert!((query_coverage("Ferry tickets", "what do ferry tickets cost") - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_extract_passage_multibyte_cuts() {
+        let texts = [
+            "🙂🙂 party 🎉🎉🎉 with 👩‍👩‍👧 family 🙂🙂🙂 tonight 🎉".to_string(),
+            "日本語のテキストでボイラーの圧力を確認します。東京都の天気は晴れです。".to_string(),
+            "cafe\u{301} cre\u{300}me bru\u{302}le\u{301}e Grüße aus Köln über Straße".to_string(),
+        ];
+        for text in &texts {
+            // Every window size puts the cut at a different byte position
+            for window in 1..40 {
+                for query in ["family", "圧力", "Köln", "creme", "nothing"] {
+                    let passage = extract_passage(text, query, window);
+                    let inner = passage.trim_start_matches("...").trim_end_matches("...");
+                    assert!(text.contains(inner), "{:?}", passage);
+                    let first = inner.chars().next();
+                    assert!(!matches!(first, Some('\u{300}'..='\u{36f}' | '\u{200d}')), "{:?}", passage);
+                }
+            }
+        }
+        let passage = extract_passage(&texts[1], "圧力", 3);
+        assert!(passage.contains("圧力"), "{}", passage);
+    }
 }
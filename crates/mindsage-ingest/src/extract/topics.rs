@@ -172,7 +172,7 @@ pub fn classify_by_keywords(text: &str) -> TopicResult {
 
     if !topic_counts.is_empty() {
         let mut sorted: Vec<(&str, usize)> = topic_counts.into_iter().collect();
-        sorted.sort_by(|a, b| b.1.cmp(&a.1));
+        sorted.sort_by_key(|s| std::cmp::Reverse(s.1));
         let topics: Vec<String> = sorted.iter().take(3).map(|(t, _)| t.to_string()).collect();
         let primary = topics[0].clone();
         TopicResult {
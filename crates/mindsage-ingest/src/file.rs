@@ -1,8 +1,13 @@
 //! File text extraction for various formats.
 
-use mindsage_core::Result;
+use mindsage_core::{redact, Result};
 use std::path::Path;
 
+pub mod html;
+pub mod transcript;
+
+use transcript::TranscriptFormat;
+
 /// Supported file types for text extraction.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileType {
@@ -11,6 +16,8 @@ pub enum FileType {
     Code,
     Json,
     Pdf,
+    /// WebVTT or SRT captions.
+    Transcript,
     Unknown,
 }
 
@@ -26,6 +33,7 @@ impl FileType {
             | "scss" | "sql" => Self::Code,
             "json" => Self::Json,
             "pdf" => Self::Pdf,
+            "vtt" | "srt" => Self::Transcript,
             _ => Self::Unknown,
         }
     }
@@ -34,7 +42,7 @@ impl FileType {
     pub fn is_text(&self) -> bool {
         matches!(
             self,
-            Self::PlainText | Self::Markdown | Self::Code | Self::Json
+            Self::PlainText | Self::Markdown | Self::Code | Self::Json | Self::Transcript
         )
     }
 }
@@ -50,13 +58,18 @@ pub fn extract_text(path: &Path) -> Result<Option<String>> {
     match file_type {
         FileType::PlainText | FileType::Markdown | FileType::Code => {
             let content = std::fs::read_to_string(path)
-                .map_err(|e| mindsage_core::Error::Io(e))?;
+                .map_err(mindsage_core::Error::Io)?;
             Ok(Some(content))
         }
         FileType::Json => extract_json(path),
+        FileType::Transcript => {
+            let content = std::fs::read_to_string(path)
+                .map_err(mindsage_core::Error::Io)?;
+            Ok(TranscriptFormat::from_extension(ext).and_then(|format| transcript::extract(&content, format)))
+        }
         FileType::Pdf => {
             // PDF extraction — placeholder for pdf-extract crate integration
-            tracing::warn!("PDF extraction not yet implemented: {}", path.display());
+            tracing::warn!("PDF extraction not yet implemented: {}", redact(path.display()));
             Ok(None)
         }
         FileType::Unknown => {
@@ -81,7 +94,7 @@ pub fn extract_text(path: &Path) -> Result<Option<String>> {
 /// Extract text from a JSON file. Handles ChatGPT export format.
 fn extract_json(path: &Path) -> Result<Option<String>> {
     let content = std::fs::read_to_string(path)
-        .map_err(|e| mindsage_core::Error::Io(e))?;
+        .map_err(mindsage_core::Error::Io)?;
 
     // Try ChatGPT export format: array of conversations
     if let Ok(conversations) = serde_json::from_str::<Vec<serde_json::Value>>(&content) {
@@ -0,0 +1,226 @@
+//! HTML to text — the readable content of a page or feed entry, without
+//! the chrome around it.
+//!
+//! When the page has an `<article>` (or else a `<main>`), only that is
+//! kept. Scripts, styles, navigation, headers, footers, asides and forms
+//! are dropped with their content. Block elements become paragraph breaks
+//! and `<br>` a line break; entities are decoded and runs of whitespace
+//! collapsed.
+
+/// Elements dropped with everything inside them.
+const BOILERPLATE_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "header", "footer", "aside", "form", "iframe", "svg", "button",
+];
+
+/// Elements whose content is raw text, skipped to their end tag unparsed.
+const RAW_TEXT_TAGS: &[&str] = &["script", "style"];
+
+/// Elements that start and end a paragraph.
+const BLOCK_TAGS: &[&str] = &[
+    "p", "div", "section", "article", "main", "h1", "h2", "h3", "h4", "h5", "h6", "li", "ul", "ol", "dl", "dt", "dd",
+    "blockquote", "pre", "table", "tr", "figure", "figcaption", "hr",
+];
+
+/// The readable text of `html`, paragraphs separated by blank lines.
+pub fn html_to_text(html: &str) -> String {
+    let html = main_content(html);
+    let mut out = String::new();
+    // Depth inside boilerplate elements
+    let mut skipping = 0usize;
+    let mut rest = html;
+
+    while let Some(lt) = rest.find('<') {
+        if skipping == 0 {
+            push_text(&mut out, &rest[..lt]);
+        }
+        rest = &rest[lt..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(gt) = rest.find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[1..gt];
+        rest = &rest[gt + 1..];
+
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if name.is_empty() {
+            continue;
+        }
+        let self_closing = tag.ends_with('/');
+
+        if BOILERPLATE_TAGS.contains(&name.as_str()) {
+            if closing {
+                skipping = skipping.saturating_sub(1);
+            } else if !self_closing {
+                if RAW_TEXT_TAGS.contains(&name.as_str()) {
+                    rest = skip_past_end_tag(rest, &name);
+                } else {
+                    skipping += 1;
+                }
+            }
+            continue;
+        }
+        if skipping > 0 {
+            continue;
+        }
+        if name == "br" {
+            out.push('\n');
+        } else if BLOCK_TAGS.contains(&name.as_str()) {
+            out.push_str("\n\n");
+        }
+    }
+    if skipping == 0 {
+        push_text(&mut out, rest);
+    }
+    tidy(&out)
+}
+
+/// Append decoded text; its line breaks are just whitespace.
+fn push_text(out: &mut String, text: &str) {
+    out.push_str(&decode_entities(&text.replace(['\n', '\r'], " ")));
+}
+
+/// The inside of the first `<article>` to the last `</article>`, else of
+/// `<main>`, else the whole document.
+fn main_content(html: &str) -> &str {
+    // ASCII lowercasing keeps byte offsets
+    let lower = html.to_ascii_lowercase();
+    for tag in ["article", "main"] {
+        let open = format!("<{}", tag);
+        let close = format!("</{}", tag);
+        let start = lower
+            .match_indices(&open)
+            .map(|(i, _)| i)
+            .find(|&i| lower[i + open.len()..].starts_with(|c: char| c == '>' || c.is_ascii_whitespace()));
+        if let (Some(start), Some(end)) = (start, lower.rfind(&close)) {
+            if let Some(gt) = lower[start..].find('>').map(|gt| start + gt + 1) {
+                if gt <= end {
+                    return &html[gt..end];
+                }
+            }
+        }
+    }
+    html
+}
+
+/// `rest` after the end tag of the raw-text element `name`.
+fn skip_past_end_tag<'a>(rest: &'a str, name: &str) -> &'a str {
+    let lower = rest.to_ascii_lowercase();
+    match lower.find(&format!("</{}", name)) {
+        Some(end) => rest[end..].find('>').map_or("", |gt| &rest[end + gt + 1..]),
+        None => "",
+    }
+}
+
+/// Collapse whitespace within paragraphs; one blank line between them.
+fn tidy(text: &str) -> String {
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(current.join("\n"));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current.join("\n"));
+    }
+    paragraphs.join("\n\n")
+}
+
+/// Decode character references and the common named entities; unknown
+/// ones are left as they are.
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&semi| semi <= 10)
+            .and_then(|semi| Some((decode_entity(&rest[1..semi + 1])?, semi + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(number) = entity.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "copy" => '©',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_article_text_without_chrome() {
+        let html = r#"<html><head><title>Blog</title><style>p { color: red }</style></head>
+            <body><nav><a href="/">Home</a> | <a href="/about">About</a></nav>
+            <article><h1>Pruning roses</h1><p>Cut above an outward&nbsp;bud,
+            at an angle.</p><script>track("<p>")</script><p>Feed in spring &amp; summer.<br>Water well.</p>
+            <aside>Related: tomatoes</aside></article>
+            <footer>&copy; 2024</footer></body></html>"#;
+        assert_eq!(
+            html_to_text(html),
+            "Pruning roses\n\nCut above an outward bud, at an angle.\n\nFeed in spring & summer.\nWater well."
+        );
+    }
+
+    #[test]
+    fn test_fragment_and_entities() {
+        assert_eq!(html_to_text("Plain <b>bold</b> &lt;tag&gt; &#8217; &#x2014; &bogus; & more"), "Plain bold <tag> ’ — &bogus; & more");
+        // Without an article or main, the whole document is kept
+        assert_eq!(html_to_text("<div>One</div><div>Two</div><header>Site</header>"), "One\n\nTwo");
+    }
+}
@@ -0,0 +1,383 @@
+//! WebVTT and SRT transcripts.
+//!
+//! Cues are merged into speaker turns and stored as one line per turn,
+//! `[00:01:02.000 --> 00:01:09.500] Alice: text`, so the document reads as
+//! a transcript and keeps its timing. `transcript_chunks` groups those
+//! lines into time windows; each chunk holds the turns without their
+//! timestamps and carries the window's time range and speakers as metadata.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::time::Duration;
+
+/// Default length of a transcript chunk.
+pub const DEFAULT_TRANSCRIPT_WINDOW: Duration = Duration::from_secs(120);
+
+/// Cues of the same speaker at most this far apart join one turn.
+const MAX_TURN_GAP_MS: u64 = 2_000;
+/// A turn stops absorbing cues once it spans this long.
+const MAX_TURN_MS: u64 = 60_000;
+
+static TIMING_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*((?:\d+:)?\d{1,2}:\d{2}[.,]\d{1,3})\s+-->\s+((?:\d+:)?\d{1,2}:\d{2}[.,]\d{1,3})").unwrap()
+});
+static VOICE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"<v(?:\.[\w.-]+)?\s+([^>]+)>").unwrap());
+static TAG_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+static SPEAKER_PREFIX_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^-?\s*([A-Z][A-Za-z0-9 .'\-]{0,39}):\s+(.+)$").unwrap());
+static TURN_LINE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\[(\d+:\d{2}:\d{2}\.\d{3}) --> (\d+:\d{2}:\d{2}\.\d{3})\] (?:([A-Z][A-Za-z0-9 .'\-]{0,39}): )?(.*)$")
+        .unwrap()
+});
+
+/// Transcript formats, by file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    WebVtt,
+    Srt,
+}
+
+impl TranscriptFormat {
+    /// Format for an extension, with or without the leading dot.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.trim_start_matches('.').to_lowercase().as_str() {
+            "vtt" => Some(Self::WebVtt),
+            "srt" => Some(Self::Srt),
+            _ => None,
+        }
+    }
+}
+
+/// One timed caption.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cue {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub speaker: Option<String>,
+    pub text: String,
+}
+
+/// Consecutive cues of one speaker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Turn {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub speaker: Option<String>,
+    pub text: String,
+}
+
+/// A time window of turns, as stored in a chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptChunk {
+    /// Turns as `Speaker: text` lines.
+    pub text: String,
+    /// Byte range of the window's lines in the document text.
+    pub char_start: usize,
+    pub char_end: usize,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// Speakers in order of first appearance.
+    pub speakers: Vec<String>,
+}
+
+impl TranscriptChunk {
+    /// Chunk metadata: time range (milliseconds and `HH:MM:SS.mmm`), the
+    /// speaker at the start of the window and every speaker in it.
+    pub fn metadata(&self) -> serde_json::Value {
+        let mut metadata = serde_json::json!({
+            "start_ms": self.start_ms,
+            "end_ms": self.end_ms,
+            "start": format_timestamp(self.start_ms),
+            "end": format_timestamp(self.end_ms),
+        });
+        if let Some(first) = self.speakers.first() {
+            metadata["speaker"] = first.clone().into();
+            metadata["speakers"] = self.speakers.clone().into();
+        }
+        metadata
+    }
+}
+
+/// Parse a transcript into cues.
+pub fn parse(input: &str, format: TranscriptFormat) -> Vec<Cue> {
+    let input = input.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut cues = Vec::new();
+    for block in input.split("\n\n") {
+        let lines: Vec<&str> = block.lines().filter(|l| !l.trim().is_empty()).collect();
+        if format == TranscriptFormat::WebVtt {
+            let first = lines.first().map(|l| l.trim_start()).unwrap_or("");
+            if ["WEBVTT", "NOTE", "STYLE", "REGION"].iter().any(|kw| first.starts_with(kw)) {
+                continue;
+            }
+        }
+        // An identifier (or SRT counter) may precede the timing line
+        let Some(timing_at) = lines.iter().position(|l| TIMING_RE.is_match(l)) else {
+            continue;
+        };
+        let caps = TIMING_RE.captures(lines[timing_at]).unwrap();
+        let (Some(start_ms), Some(end_ms)) = (parse_timestamp(&caps[1]), parse_timestamp(&caps[2])) else {
+            continue;
+        };
+        let raw = lines[timing_at + 1..].join(" ");
+        let mut speaker = VOICE_RE.captures(&raw).map(|c| c[1].trim().to_string());
+        let mut text = decode_entities(&TAG_RE.replace_all(&raw, ""));
+        text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if speaker.is_none() {
+            if let Some(caps) = SPEAKER_PREFIX_RE.captures(&text) {
+                speaker = Some(caps[1].trim().to_string());
+                text = caps[2].to_string();
+            }
+        }
+        if !text.is_empty() {
+            cues.push(Cue {
+                start_ms,
+                end_ms: end_ms.max(start_ms),
+                speaker,
+                text,
+            });
+        }
+    }
+    cues
+}
+
+/// Merge cues into turns. Cues are ordered by start time; a cue joins the
+/// previous turn when it has the same speaker and starts at most two seconds
+/// after the turn ends (or overlaps it). Repeated text from rolling captions
+/// is dropped, and a cue that extends the previous one replaces it. Cues of
+/// different speakers that overlap stay separate turns.
+pub fn merge_turns(mut cues: Vec<Cue>) -> Vec<Turn> {
+    cues.sort_by_key(|c| (c.start_ms, c.end_ms));
+    let mut turns: Vec<Turn> = Vec::new();
+    let mut last_cue_text = String::new();
+    for cue in cues {
+        if let Some(turn) = turns.last_mut() {
+            let joins = turn.speaker == cue.speaker
+                && cue.start_ms <= turn.end_ms + MAX_TURN_GAP_MS
+                && cue.end_ms.saturating_sub(turn.start_ms) <= MAX_TURN_MS;
+            if joins {
+                turn.end_ms = turn.end_ms.max(cue.end_ms);
+                if cue.text == last_cue_text || turn.text.ends_with(&cue.text) {
+                    continue;
+                }
+                if !last_cue_text.is_empty() && cue.text.starts_with(&last_cue_text) {
+                    let kept = turn.text.len() - last_cue_text.len();
+                    turn.text.truncate(kept);
+                }
+                if !turn.text.is_empty() && !turn.text.ends_with(' ') {
+                    turn.text.push(' ');
+                }
+                turn.text.push_str(&cue.text);
+                last_cue_text = cue.text;
+                continue;
+            }
+        }
+        last_cue_text = cue.text.clone();
+        turns.push(Turn {
+            start_ms: cue.start_ms,
+            end_ms: cue.end_ms,
+            speaker: cue.speaker,
+            text: cue.text,
+        });
+    }
+    turns
+}
+
+/// Document text for turns: one `[start --> end] Speaker: text` line each.
+pub fn render(turns: &[Turn]) -> String {
+    turns
+        .iter()
+        .map(|turn| {
+            let timing = format!("[{} --> {}]", format_timestamp(turn.start_ms), format_timestamp(turn.end_ms));
+            match &turn.speaker {
+                Some(speaker) => format!("{} {}: {}", timing, speaker, turn.text),
+                None => format!("{} {}", timing, turn.text),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse, merge and render a transcript file's content. `None` when it has
+/// no cues.
+pub fn extract(input: &str, format: TranscriptFormat) -> Option<String> {
+    let turns = merge_turns(parse(input, format));
+    (!turns.is_empty()).then(|| render(&turns))
+}
+
+/// Group the turn lines of a rendered transcript into windows of `window`:
+/// a new chunk starts with the first turn beginning `window` or more after
+/// the current chunk's first turn. Lines that are not turn lines are kept
+/// with the chunk they follow.
+pub fn transcript_chunks(text: &str, window: Duration) -> Vec<TranscriptChunk> {
+    let window_ms = (window.as_millis() as u64).max(1);
+    let mut chunks: Vec<TranscriptChunk> = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let content = line.trim_end_matches(['\n', '\r']);
+        let line_end = line_start + content.len();
+        if content.trim().is_empty() {
+            continue;
+        }
+
+        let Some(caps) = TURN_LINE_RE.captures(content) else {
+            if let Some(chunk) = chunks.last_mut() {
+                chunk.text.push('\n');
+                chunk.text.push_str(content.trim());
+                chunk.char_end = line_end;
+            }
+            continue;
+        };
+        let (Some(start_ms), Some(end_ms)) = (parse_timestamp(&caps[1]), parse_timestamp(&caps[2])) else {
+            continue;
+        };
+        let speaker = caps.get(3).map(|m| m.as_str().to_string());
+        let turn_text = match &speaker {
+            Some(speaker) => format!("{}: {}", speaker, &caps[4]),
+            None => caps[4].to_string(),
+        };
+
+        match chunks.last_mut() {
+            Some(chunk) if start_ms < chunk.start_ms + window_ms => {
+                chunk.text.push('\n');
+                chunk.text.push_str(&turn_text);
+                chunk.char_end = line_end;
+                chunk.end_ms = chunk.end_ms.max(end_ms);
+                if let Some(speaker) = speaker.filter(|s| !chunk.speakers.contains(s)) {
+                    chunk.speakers.push(speaker);
+                }
+            }
+            _ => chunks.push(TranscriptChunk {
+                text: turn_text,
+                char_start: line_start,
+                char_end: line_end,
+                start_ms,
+                end_ms,
+                speakers: speaker.into_iter().collect(),
+            }),
+        }
+    }
+    chunks
+}
+
+/// `HH:MM:SS.mmm`
+pub fn format_timestamp(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// `[HH:]MM:SS.mmm` or `HH:MM:SS,mmm` in milliseconds.
+fn parse_timestamp(s: &str) -> Option<u64> {
+    let (clock, fraction) = s.split_once(['.', ','])?;
+    let parts: Vec<u64> = clock.split(':').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    let (h, m, sec) = match parts.as_slice() {
+        [m, s] => (0, *m, *s),
+        [h, m, s] => (*h, *m, *s),
+        _ => return None,
+    };
+    // "5" after the separator is 500 ms
+    let millis: u64 = format!("{:0<3}", fraction).get(..3)?.parse().ok()?;
+    Some(((h * 60 + m) * 60 + sec) * 1000 + millis)
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEETING_VTT: &str = "WEBVTT - weekly sync
+
+NOTE exported from the recorder
+
+1
+00:00:01.000 --> 00:00:04.000
+<v Alice>Morning everyone, let's start with the roadmap.</v>
+
+2
+00:00:04.500 --> 00:00:07.000
+<v Alice>The beta ships next week.</v>
+
+3
+00:00:06.500 --> 00:00:09.000 align:start
+<v.loud Bob>Can we move it?</v>
+
+00:00:08.800 --> 00:00:11.000
+<v Alice>We could &amp; should discuss.</v>
+
+00:02:05.000 --> 00:02:09.000
+<v Carol>Budget next: we are <i>under</i> plan.</v>
+
+00:02:08.000 --> 00:02:09.000
+<v Carol>Budget next: we are under plan.</v>
+";
+
+    #[test]
+    fn test_vtt_voice_tags_and_overlapping_cues() {
+        let cues = parse(MEETING_VTT, TranscriptFormat::WebVtt);
+        assert_eq!(cues.len(), 6);
+        assert_eq!(cues[2].speaker.as_deref(), Some("Bob"));
+        assert_eq!(cues[3].text, "We could & should discuss.");
+        assert_eq!(cues[4].text, "Budget next: we are under plan.");
+
+        let turns = merge_turns(cues);
+        let summary: Vec<(u64, u64, Option<&str>)> =
+            turns.iter().map(|t| (t.start_ms, t.end_ms, t.speaker.as_deref())).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1_000, 7_000, Some("Alice")),
+                // Bob overlaps Alice's turn but stays his own turn
+                (6_500, 9_000, Some("Bob")),
+                (8_800, 11_000, Some("Alice")),
+                // The repeated rolling caption is dropped
+                (125_000, 129_000, Some("Carol")),
+            ]
+        );
+        assert_eq!(turns[0].text, "Morning everyone, let's start with the roadmap. The beta ships next week.");
+        assert_eq!(turns[3].text, "Budget next: we are under plan.");
+
+        let text = render(&turns);
+        assert!(text.starts_with("[00:00:01.000 --> 00:00:07.000] Alice: Morning everyone"));
+
+        let chunks = transcript_chunks(&text, DEFAULT_TRANSCRIPT_WINDOW);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[0].start_ms, chunks[0].end_ms), (1_000, 11_000));
+        assert_eq!(chunks[0].speakers, vec!["Alice", "Bob"]);
+        assert!(chunks[0].text.starts_with("Alice: Morning everyone"));
+        assert!(!chunks[0].text.contains("-->"));
+        assert_eq!(&text[chunks[1].char_start..chunks[1].char_end], text.lines().last().unwrap());
+        let metadata = chunks[1].metadata();
+        assert_eq!(metadata["start"], "00:02:05.000");
+        assert_eq!(metadata["speaker"], "Carol");
+
+        // A shorter window splits the first minute per turn start
+        assert_eq!(transcript_chunks(&text, Duration::from_secs(5)).len(), 3);
+    }
+
+    #[test]
+    fn test_srt_with_speaker_prefixes() {
+        let srt = "1\r\n00:00:00,500 --> 00:00:02,000\r\nDANA: Are we recording?\r\n\r\n\
+                   2\r\n00:00:02,100 --> 00:00:04,250\r\nEli: Yes,\r\nit is on.\r\n";
+        let cues = parse(srt, TranscriptFormat::Srt);
+        assert_eq!(cues.len(), 2);
+        assert_eq!((cues[0].start_ms, cues[0].speaker.as_deref()), (500, Some("DANA")));
+        assert_eq!((cues[1].end_ms, cues[1].text.as_str()), (4_250, "Yes, it is on."));
+        assert_eq!(parse_timestamp("01:02:03.5"), Some(3_723_500));
+        assert_eq!(format_timestamp(3_723_500), "01:02:03.500");
+        assert!(extract("WEBVTT\n\nno cues here", TranscriptFormat::WebVtt).is_none());
+    }
+}
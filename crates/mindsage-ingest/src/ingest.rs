@@ -1,23 +1,35 @@
 //! Document ingestion pipeline: file → text → chunk → store.
 
 use std::path::Path;
+use std::time::Duration;
 
 use sha2::{Digest, Sha256};
 use tracing::{debug, info};
 
-use crate::chunking::{calculate_chunk_size, should_chunk, HierarchicalChunker};
+use crate::chunking::{plan_chunk_stream, plan_chunks_with_window};
+use crate::file::transcript::DEFAULT_TRANSCRIPT_WINDOW;
 use crate::file;
-use mindsage_core::{Error, Result};
-use mindsage_store::{AddDocumentOptions, SqliteStore};
+use mindsage_core::{redact, Error, Result};
+use mindsage_store::{AddDocumentOptions, NewChunk, NewDocument, SqliteStore, UpsertOutcome};
 
 /// Handles document ingestion: text extraction, chunking, and storage.
 pub struct Ingester<'a> {
     store: &'a SqliteStore,
+    transcript_window: Duration,
 }
 
 impl<'a> Ingester<'a> {
     pub fn new(store: &'a SqliteStore) -> Self {
-        Self { store }
+        Self {
+            store,
+            transcript_window: DEFAULT_TRANSCRIPT_WINDOW,
+        }
+    }
+
+    /// Length of the time windows transcripts (`.vtt`, `.srt`) are chunked by.
+    pub fn with_transcript_window(mut self, window: Duration) -> Self {
+        self.transcript_window = window;
+        self
     }
 
     /// Ingest a file: extract text, chunk, and store.
@@ -26,7 +38,7 @@ impl<'a> Ingester<'a> {
         let text = match file::extract_text(path)? {
             Some(t) if !t.trim().is_empty() => t,
             _ => {
-                debug!("No text extracted from {}", path.display());
+                debug!("No text extracted from {}", redact(path.display()));
                 return Ok(None);
             }
         };
@@ -52,14 +64,21 @@ impl<'a> Ingester<'a> {
         let ext_ref = ext.as_deref();
 
         // Build metadata
+        let file_meta = std::fs::metadata(path).ok();
         let metadata = serde_json::json!({
             "source": "file",
             "filename": filename,
             "file_extension": ext_ref.unwrap_or(""),
-            "file_size": std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            "file_size": file_meta.as_ref().map(|m| m.len()).unwrap_or(0),
         });
 
-        self.ingest_text(&text, &content_hash, &metadata, ext_ref)
+        // Date the document by the file's modification time
+        let modified_ms = file_meta
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as i64);
+
+        self.ingest_text_at(&text, &content_hash, &metadata, ext_ref, modified_ms)
     }
 
     /// Ingest raw text with metadata.
@@ -69,79 +88,165 @@ impl<'a> Ingester<'a> {
         content_hash: &str,
         metadata: &serde_json::Value,
         file_extension: Option<&str>,
+    ) -> Result<Option<i64>> {
+        self.ingest_text_at(text, content_hash, metadata, file_extension, None)
+    }
+
+    /// Ingest raw text with metadata, created at `created_at` (ms; now
+    /// when `None`).
+    pub fn ingest_text_at(
+        &self,
+        text: &str,
+        content_hash: &str,
+        metadata: &serde_json::Value,
+        file_extension: Option<&str>,
+        created_at: Option<i64>,
     ) -> Result<Option<i64>> {
         // Check for duplicate
         if self.store.find_document_by_hash(content_hash)?.is_some() {
             return Err(Error::DuplicateContent(content_hash.to_string()));
         }
 
-        // Store document
-        let doc_id = self.store.add_document(
-            text,
-            AddDocumentOptions {
-                metadata: Some(metadata.clone()),
-                content_hash: Some(content_hash.to_string()),
-                ..Default::default()
+        // Store the document with its chunks in one transaction, inserting
+        // each chunk as the chunker makes it
+        let options = AddDocumentOptions {
+            metadata: Some(metadata.clone()),
+            content_hash: Some(content_hash.to_string()),
+            created_at,
+            ..Default::default()
+        };
+        let (mut chunk_count, mut para_count) = (0, 0);
+        let chunks = plan_chunk_stream(text, Some(metadata), file_extension, self.transcript_window).inspect(|chunk| {
+            chunk_count += 1;
+            para_count += usize::from(chunk.level == 1);
+        });
+        let doc_id = self.store.add_document_with_chunk_stream(text, &options, chunks.map(Ok))?;
+        log_chunks(doc_id, chunk_count, para_count);
+        Ok(Some(doc_id))
+    }
+
+    /// Replace an existing document's text and chunk it again, by the
+    /// strategy its metadata calls for. The old chunks and their embeddings
+    /// are deleted; metadata is kept. Returns false when the document does
+    /// not exist.
+    pub fn replace_text(
+        &self,
+        doc_id: i64,
+        text: &str,
+        content_hash: &str,
+        file_extension: Option<&str>,
+    ) -> Result<bool> {
+        if let Some(existing) = self.store.find_document_by_hash(content_hash)? {
+            if existing.id != doc_id {
+                return Err(Error::DuplicateContent(content_hash.to_string()));
+            }
+        }
+        let Some(document) = self.store.get_document(doc_id)? else {
+            return Ok(false);
+        };
+        let chunks = plan_chunks_with_window(text, document.metadata.as_ref(), file_extension, self.transcript_window);
+        if !self.store.replace_document_chunks(doc_id, text, Some(content_hash), &chunks)? {
+            return Ok(false);
+        }
+        log_planned_chunks(doc_id, &chunks);
+        Ok(true)
+    }
+
+    /// Store `text` as the document with `external_id` in `source`: a new
+    /// document the first time, afterwards the same document with its text
+    /// replaced and chunked again when the content changed. `metadata` is
+    /// merged into the stored metadata; `created_at` (ms) dates a new
+    /// document only.
+    pub fn upsert_text(
+        &self,
+        source: &str,
+        external_id: &str,
+        text: &str,
+        metadata: &serde_json::Value,
+        created_at: Option<i64>,
+    ) -> Result<UpsertOutcome> {
+        let hash = content_hash(text);
+        if let Some(existing) = self.store.find_document_by_external_id(source, external_id)? {
+            if existing.content_hash.as_deref() == Some(hash.as_str()) {
+                return Ok(UpsertOutcome::Unchanged(existing.id));
+            }
+        }
+        let chunks = plan_chunks_with_window(text, Some(metadata), None, self.transcript_window);
+        let (chunk_count, para_count) = (chunks.len(), chunks.iter().filter(|c| c.level == 1).count());
+        let outcome = self.store.upsert_document_by_external_id(
+            source,
+            external_id,
+            &NewDocument {
+                text: text.to_string(),
+                options: AddDocumentOptions {
+                    metadata: Some(metadata.clone()),
+                    content_hash: Some(hash),
+                    created_at,
+                    ..Default::default()
+                },
+                chunks,
             },
         )?;
+        if !matches!(outcome, UpsertOutcome::Unchanged(_)) {
+            log_chunks(outcome.doc_id(), chunk_count, para_count);
+        }
+        Ok(outcome)
+    }
 
-        // Chunk the document
-        if should_chunk(text, file_extension) {
-            let (chunk_size, chunk_overlap) = calculate_chunk_size(file_extension);
-            let chunker = HierarchicalChunker::new(chunk_size, chunk_overlap);
-            let chunks = chunker.chunk(text);
-
-            let mut section_db_ids: std::collections::HashMap<usize, i64> =
-                std::collections::HashMap::new();
-
-            for chunk in &chunks {
-                let parent_db_id = chunk
-                    .parent_index
-                    .and_then(|pi| section_db_ids.get(&pi).copied());
-
-                let chunk_id = self.store.add_chunk(
-                    doc_id,
-                    &chunk.text,
-                    chunk.chunk_index as i32,
-                    chunk.level,
-                    parent_db_id,
-                    Some(chunk.char_start as i32),
-                    Some(chunk.char_end as i32),
-                    None, // enriched_text added later by extraction
-                    None, // chunk metadata
-                    None, // created_at
-                )?;
-
-                if chunk.level == 0 {
-                    section_db_ids.insert(chunk.chunk_index, chunk_id);
+    /// Finish ingests torn by a crash in versions that wrote a document and
+    /// its chunks separately: a document left without chunks is chunked
+    /// from its stored text, or deleted when it has no text.
+    pub fn repair_torn_documents(&self) -> Result<TornRepair> {
+        let mut repair = TornRepair::default();
+        let mut after_id = 0;
+        loop {
+            let docs = self.store.get_documents_without_chunks(after_id, 100)?;
+            let Some(last) = docs.last() else {
+                return Ok(repair);
+            };
+            after_id = last.id;
+            for doc in docs {
+                if doc.text.trim().is_empty() {
+                    self.store.delete_document(doc.id)?;
+                    repair.rolled_back += 1;
+                    continue;
                 }
+                let ext = doc
+                    .metadata
+                    .as_ref()
+                    .and_then(|m| m.get("file_extension"))
+                    .and_then(|e| e.as_str())
+                    .filter(|e| !e.is_empty())
+                    .map(str::to_string);
+                let chunks = plan_chunks_with_window(&doc.text, doc.metadata.as_ref(), ext.as_deref(), self.transcript_window);
+                self.store.add_document_chunks(doc.id, &chunks)?;
+                repair.completed += 1;
             }
-
-            let para_count = chunks.iter().filter(|c| c.level == 1).count();
-            info!(
-                "Ingested document {} with {} chunks ({} paragraphs)",
-                doc_id,
-                chunks.len(),
-                para_count
-            );
-        } else {
-            // Small text — store as a single level=1 chunk
-            self.store.add_chunk(
-                doc_id,
-                text,
-                0,     // chunk_index
-                1,     // level (paragraph, searchable)
-                None,  // parent_chunk_id
-                Some(0),
-                Some(text.len() as i32),
-                None, // enriched_text
-                None, // metadata
-                None, // created_at
-            )?;
-            info!("Ingested document {} as single chunk", doc_id);
         }
+    }
+}
 
-        Ok(Some(doc_id))
+/// What `Ingester::repair_torn_documents` did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TornRepair {
+    /// Documents chunked from their stored text.
+    pub completed: usize,
+    /// Empty documents deleted.
+    pub rolled_back: usize,
+}
+
+fn log_planned_chunks(doc_id: i64, chunks: &[NewChunk]) {
+    log_chunks(doc_id, chunks.len(), chunks.iter().filter(|c| c.level == 1).count());
+}
+
+fn log_chunks(doc_id: i64, chunk_count: usize, para_count: usize) {
+    if chunk_count > 1 {
+        info!(
+            "Ingested document {} with {} chunks ({} paragraphs)",
+            doc_id, chunk_count, para_count
+        );
+    } else {
+        info!("Ingested document {} as single chunk", doc_id);
     }
 }
 
@@ -1,10 +1,17 @@
 //! MindSage Ingest — text chunking, file processing, document ingestion, metadata extraction.
 
 pub mod chunking;
+pub mod conversation;
 pub mod extract;
 pub mod file;
 pub mod ingest;
+pub mod outline;
 
-pub use chunking::{HierarchicalChunk, HierarchicalChunker, TextChunk};
-pub use extract::{ExtractionResult, build_enriched_text, extract_all};
-pub use ingest::Ingester;
+pub use chunking::{
+    plan_chunk_stream, plan_chunks, plan_chunks_with_window, plan_conversation_chunks, ChunkReader, HierarchicalChunk, HierarchicalChunker, TextChunk,
+};
+pub use extract::{
+    ExtractionResult, CURRENT_EXTRACTION_VERSION, build_enriched_text, extract_all,
+};
+pub use ingest::{Ingester, TornRepair};
+pub use outline::{ChunkMetadata, DocumentOutline};
@@ -0,0 +1,158 @@
+//! Document outline — headings, page breaks and speaker turns by offset,
+//! so each chunk can carry the metadata of its position in the document.
+//!
+//! - Headings: Markdown ATX headings (`#` … `######`) outside code fences;
+//!   not looked for in code files, where `#` starts comments.
+//! - Pages: form feeds (`\x0c`), which PDF text extractors put between
+//!   pages. Documents without one have no page numbers.
+//! - Speakers: transcript lines of the form `Name: text` (optionally after a
+//!   `[00:01:02]` timestamp), once at least three such lines from two or
+//!   more names show the document is a transcript.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::file::FileType;
+
+static HEADING_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(#{1,6})\s+(.+?)\s*#*\s*$").unwrap());
+static SPEAKER_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*(?:\[[\d:.,]+\]\s*)?([A-Z][A-Za-z0-9 .'\-]{0,39}):\s+\S").unwrap());
+
+/// Fewest speaker lines before a document counts as a transcript.
+const MIN_SPEAKER_LINES: usize = 3;
+
+/// Metadata of a chunk, stored in `chunks.metadata_json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ChunkMetadata {
+    /// Titles of the enclosing headings, outermost first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub heading_path: Vec<String>,
+    /// 1-based page number.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+}
+
+impl ChunkMetadata {
+    /// JSON object to store, or `None` when nothing was detected.
+    pub fn to_value(&self) -> Option<serde_json::Value> {
+        if self.heading_path.is_empty() && self.page.is_none() && self.speaker.is_none() {
+            return None;
+        }
+        serde_json::to_value(self).ok()
+    }
+}
+
+/// Positions of headings, page breaks and speaker turns in a text.
+#[derive(Debug, Default)]
+pub struct DocumentOutline {
+    /// (offset, level, title) in text order.
+    headings: Vec<(usize, usize, String)>,
+    page_breaks: Vec<usize>,
+    /// (offset, name) in text order.
+    speakers: Vec<(usize, String)>,
+}
+
+impl DocumentOutline {
+    pub fn parse(text: &str, file_extension: Option<&str>) -> Self {
+        let is_code = file_extension
+            .map(|ext| FileType::from_extension(ext.trim_start_matches('.')) == FileType::Code)
+            .unwrap_or(false);
+
+        let mut outline = Self {
+            page_breaks: text.match_indices('\x0c').map(|(i, _)| i).collect(),
+            ..Default::default()
+        };
+        let mut in_fence = false;
+        let mut offset = 0;
+        for line in text.split_inclusive('\n') {
+            let trimmed = line.trim_end_matches(['\n', '\r']).trim_start_matches('\x0c');
+            if trimmed.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+            } else if !in_fence {
+                if let Some(caps) = HEADING_RE.captures(trimmed).filter(|_| !is_code) {
+                    outline.headings.push((offset, caps[1].len(), caps[2].to_string()));
+                } else if let Some(caps) = SPEAKER_RE.captures(trimmed) {
+                    outline.speakers.push((offset, caps[1].trim().to_string()));
+                }
+            }
+            offset += line.len();
+        }
+
+        let distinct: std::collections::HashSet<&str> = outline.speakers.iter().map(|(_, s)| s.as_str()).collect();
+        if outline.speakers.len() < MIN_SPEAKER_LINES || distinct.len() < 2 {
+            outline.speakers.clear();
+        }
+        outline
+    }
+
+    /// Metadata of a chunk starting at `text[offset..]`. Leading whitespace
+    /// is skipped, so a chunk starting at the newline before a heading is
+    /// inside that heading.
+    pub fn metadata_at(&self, text: &str, offset: usize) -> ChunkMetadata {
+        let offset = offset.min(text.len());
+        let anchor = text
+            .get(offset..)
+            .map(|rest| offset + (rest.len() - rest.trim_start().len()))
+            .unwrap_or(offset);
+
+        let mut path: Vec<(usize, &str)> = Vec::new();
+        for (_, level, title) in self.headings.iter().take_while(|(pos, _, _)| *pos <= anchor) {
+            while path.last().is_some_and(|(l, _)| l >= level) {
+                path.pop();
+            }
+            path.push((*level, title));
+        }
+
+        let page = (!self.page_breaks.is_empty())
+            .then(|| 1 + self.page_breaks.iter().take_while(|&&pos| pos < anchor).count() as u32);
+        let speaker = self
+            .speakers
+            .iter()
+            .take_while(|(pos, _)| *pos <= anchor)
+            .last()
+            .map(|(_, name)| name.clone());
+
+        ChunkMetadata {
+            heading_path: path.into_iter().map(|(_, title)| title.to_string()).collect(),
+            page,
+            speaker,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heading_path_follows_nesting() {
+        let text = "# Guide\nIntro\n## Install\nSteps\n```\n# not a heading\n```\n### Linux\napt\n## Usage\nRun it";
+        let outline = DocumentOutline::parse(text, Some(".md"));
+        let at = |needle: &str| outline.metadata_at(text, text.find(needle).unwrap()).heading_path;
+        assert_eq!(at("Intro"), vec!["Guide"]);
+        assert_eq!(at("apt"), vec!["Guide", "Install", "Linux"]);
+        assert_eq!(at("Run it"), vec!["Guide", "Usage"]);
+        // A chunk starting at the newline before a heading belongs to it
+        assert_eq!(outline.metadata_at(text, text.find("\n## Usage").unwrap()).heading_path, vec!["Guide", "Usage"]);
+
+        let code = DocumentOutline::parse("# comment\nx = 1", Some(".py"));
+        assert_eq!(code.metadata_at("# comment\nx = 1", 10), ChunkMetadata::default());
+    }
+
+    #[test]
+    fn test_pages_and_speakers() {
+        let text = "Alice: Hello there.\nBob: Hi Alice.\n\x0cAlice: Shall we start?\nIt is late.";
+        let outline = DocumentOutline::parse(text, None);
+        let late = outline.metadata_at(text, text.find("It is late").unwrap());
+        assert_eq!((late.page, late.speaker.as_deref()), (Some(2), Some("Alice")));
+        let bob = outline.metadata_at(text, text.find("Bob").unwrap());
+        assert_eq!((bob.page, bob.speaker.as_deref()), (Some(1), Some("Bob")));
+
+        // A couple of "Label:" lines are not a transcript
+        let note = "Note: remember milk.\nTodo: call Bob.";
+        assert_eq!(DocumentOutline::parse(note, None).metadata_at(note, 0).to_value(), None);
+    }
+}
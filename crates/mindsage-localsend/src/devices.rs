@@ -0,0 +1,321 @@
+//! Device registry — known LocalSend senders, their trust level, and the
+//! history of transfers received from them.
+//!
+//! Every sender that registers or prepares an upload is recorded by
+//! fingerprint. New senders get the default trust level; `trusted` devices
+//! send without approval, `ask` devices wait for the user to accept each
+//! transfer, and `blocked` devices are refused. The registry and history are
+//! kept in one JSON file (`data/localsend-devices.json`), rewritten
+//! atomically on every change.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Transfers kept in the history; the oldest are dropped first.
+pub const MAX_HISTORY: usize = 1000;
+
+/// How transfers from a device are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrustLevel {
+    /// Every transfer is refused.
+    Blocked,
+    /// Each transfer waits for the user to accept it.
+    #[default]
+    Ask,
+    /// Transfers are accepted without asking.
+    Trusted,
+}
+
+impl TrustLevel {
+    /// Parse `blocked`, `ask` or `trusted`, ignoring case.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "blocked" => Some(Self::Blocked),
+            "ask" => Some(Self::Ask),
+            "trusted" => Some(Self::Trusted),
+            _ => None,
+        }
+    }
+}
+
+/// A sender seen on `register` or `prepare-upload`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownDevice {
+    pub fingerprint: String,
+    pub alias: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_type: Option<String>,
+    pub trust: TrustLevel,
+    pub first_seen: String,
+    pub last_seen: String,
+    /// Completed transfers from this device.
+    #[serde(default)]
+    pub transfers: usize,
+}
+
+/// One file or text received in a transfer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferredFile {
+    /// Name the sender gave the file.
+    pub file_name: String,
+    pub size: u64,
+    /// Stored as a note rather than saved to the uploads directory.
+    #[serde(default)]
+    pub note: bool,
+}
+
+/// A completed transfer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferRecord {
+    pub session_id: String,
+    pub fingerprint: String,
+    /// The sender's alias at the time of the transfer.
+    pub alias: String,
+    pub files: Vec<TransferredFile>,
+    pub total_bytes: u64,
+    pub started_at: String,
+    pub finished_at: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RegistryFile {
+    #[serde(default)]
+    devices: HashMap<String, KnownDevice>,
+    /// Oldest first.
+    #[serde(default)]
+    history: Vec<TransferRecord>,
+}
+
+/// Known devices and transfer history, optionally persisted to a file.
+pub struct DeviceRegistry {
+    path: Option<PathBuf>,
+    default_trust: TrustLevel,
+    data: RwLock<RegistryFile>,
+}
+
+impl DeviceRegistry {
+    /// Load the registry from `path`, starting empty if the file is missing
+    /// or unreadable. Devices seen for the first time get `default_trust`.
+    pub fn open(path: &Path, default_trust: TrustLevel) -> Self {
+        let data = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable LocalSend device registry: {}", e);
+                RegistryFile::default()
+            }),
+            Err(_) => RegistryFile::default(),
+        };
+        Self {
+            path: Some(path.to_path_buf()),
+            default_trust,
+            data: RwLock::new(data),
+        }
+    }
+
+    /// A registry that is never written to disk.
+    pub fn in_memory(default_trust: TrustLevel) -> Self {
+        Self {
+            path: None,
+            default_trust,
+            data: RwLock::new(RegistryFile::default()),
+        }
+    }
+
+    /// Record that a device was seen, updating its alias and model, and
+    /// return its trust level.
+    pub fn observe(
+        &self,
+        fingerprint: &str,
+        alias: &str,
+        device_model: Option<&str>,
+        device_type: Option<&str>,
+    ) -> TrustLevel {
+        let now = Utc::now().to_rfc3339();
+        let mut data = self.data.write();
+        let device = data
+            .devices
+            .entry(fingerprint.to_string())
+            .or_insert_with(|| KnownDevice {
+                fingerprint: fingerprint.to_string(),
+                alias: alias.to_string(),
+                device_model: None,
+                device_type: None,
+                trust: self.default_trust,
+                first_seen: now.clone(),
+                last_seen: now.clone(),
+                transfers: 0,
+            });
+        device.alias = alias.to_string();
+        device.device_model = device_model.map(str::to_string).or(device.device_model.take());
+        device.device_type = device_type.map(str::to_string).or(device.device_type.take());
+        device.last_seen = now;
+        let trust = device.trust;
+        self.save(&data);
+        trust
+    }
+
+    pub fn get(&self, fingerprint: &str) -> Option<KnownDevice> {
+        self.data.read().devices.get(fingerprint).cloned()
+    }
+
+    /// All known devices, most recently seen first.
+    pub fn list(&self) -> Vec<KnownDevice> {
+        let mut devices: Vec<KnownDevice> = self.data.read().devices.values().cloned().collect();
+        devices.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+        devices
+    }
+
+    /// Change a known device's trust level. `None` if the device is unknown.
+    pub fn set_trust(&self, fingerprint: &str, trust: TrustLevel) -> Option<KnownDevice> {
+        let mut data = self.data.write();
+        let device = data.devices.get_mut(fingerprint)?;
+        device.trust = trust;
+        let device = device.clone();
+        self.save(&data);
+        Some(device)
+    }
+
+    /// Remove a device; it gets the default trust level if it comes back.
+    /// Its transfers stay in the history.
+    pub fn forget(&self, fingerprint: &str) -> bool {
+        let mut data = self.data.write();
+        let removed = data.devices.remove(fingerprint).is_some();
+        if removed {
+            self.save(&data);
+        }
+        removed
+    }
+
+    /// Append a completed transfer to the history.
+    pub fn record_transfer(&self, record: TransferRecord) {
+        let mut data = self.data.write();
+        if let Some(device) = data.devices.get_mut(&record.fingerprint) {
+            device.transfers += 1;
+        }
+        data.history.push(record);
+        if data.history.len() > MAX_HISTORY {
+            let excess = data.history.len() - MAX_HISTORY;
+            data.history.drain(..excess);
+        }
+        self.save(&data);
+    }
+
+    /// One page of the history, newest first, optionally for one device.
+    /// Returns the page and the total number of matching transfers.
+    pub fn history(&self, page: usize, page_size: usize, fingerprint: Option<&str>) -> (Vec<TransferRecord>, usize) {
+        let data = self.data.read();
+        let matching: Vec<&TransferRecord> = data
+            .history
+            .iter()
+            .rev()
+            .filter(|r| fingerprint.map_or(true, |f| r.fingerprint == f))
+            .collect();
+        let total = matching.len();
+        let records = matching
+            .into_iter()
+            .skip(page.saturating_sub(1).saturating_mul(page_size))
+            .take(page_size)
+            .cloned()
+            .collect();
+        (records, total)
+    }
+
+    fn save(&self, data: &RegistryFile) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(e) = write_atomic(path, data) {
+            warn!("Failed to save LocalSend device registry: {}", e);
+        }
+    }
+}
+
+/// Write `value` as JSON to a temporary file next to `path` and rename it
+/// over `path`.
+fn write_atomic<T: Serialize>(path: &Path, value: &T) -> std::io::Result<()> {
+    let data = serde_json::to_vec_pretty(value)?;
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp = PathBuf::from(tmp_name);
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(&data)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(session_id: &str, fingerprint: &str, size: u64) -> TransferRecord {
+        TransferRecord {
+            session_id: session_id.to_string(),
+            fingerprint: fingerprint.to_string(),
+            alias: "Phone".to_string(),
+            files: vec![TransferredFile {
+                file_name: format!("{}.jpg", session_id),
+                size,
+                note: false,
+            }],
+            total_bytes: size,
+            started_at: Utc::now().to_rfc3339(),
+            finished_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    #[test]
+    fn test_registry_persists_devices_and_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("localsend-devices.json");
+        let registry = DeviceRegistry::open(&path, TrustLevel::Ask);
+
+        assert_eq!(registry.observe("fp-1", "Pixel", Some("Pixel 8"), Some("mobile")), TrustLevel::Ask);
+        assert_eq!(registry.set_trust("fp-1", TrustLevel::Trusted).unwrap().trust, TrustLevel::Trusted);
+        assert!(registry.set_trust("unknown", TrustLevel::Trusted).is_none());
+        // A later sighting updates the alias but keeps the trust level
+        assert_eq!(registry.observe("fp-1", "Work phone", None, None), TrustLevel::Trusted);
+        registry.record_transfer(transfer("s1", "fp-1", 10));
+        registry.record_transfer(transfer("s2", "fp-2", 20));
+
+        let reopened = DeviceRegistry::open(&path, TrustLevel::Ask);
+        let device = reopened.get("fp-1").unwrap();
+        assert_eq!((device.alias.as_str(), device.device_model.as_deref()), ("Work phone", Some("Pixel 8")));
+        assert_eq!((device.trust, device.transfers), (TrustLevel::Trusted, 1));
+
+        let (page, total) = reopened.history(1, 1, None);
+        assert_eq!((page[0].session_id.as_str(), total), ("s2", 2));
+        let (page, _) = reopened.history(2, 1, None);
+        assert_eq!(page[0].session_id, "s1");
+        let (page, total) = reopened.history(1, 10, Some("fp-1"));
+        assert_eq!((page.len(), total), (1, 1));
+
+        // Forgetting a device keeps its history
+        assert!(reopened.forget("fp-1"));
+        assert!(!reopened.forget("fp-1"));
+        assert_eq!(reopened.observe("fp-1", "Pixel", None, None), TrustLevel::Ask);
+        assert_eq!(reopened.history(1, 10, Some("fp-1")).1, 1);
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let registry = DeviceRegistry::in_memory(TrustLevel::Trusted);
+        for i in 0..MAX_HISTORY + 5 {
+            registry.record_transfer(transfer(&format!("s{}", i), "fp", 1));
+        }
+        let (page, total) = registry.history(1, 1, None);
+        assert_eq!(total, MAX_HISTORY);
+        assert_eq!(page[0].session_id, format!("s{}", MAX_HISTORY + 4));
+    }
+}
@@ -2,10 +2,14 @@
 //!
 //! Implements the LocalSend v2 protocol for receiving files from mobile
 //! devices on the local network. Supports multicast discovery and
-//! HTTP-based file transfer with session management.
+//! HTTP(S)-based file transfer with session management.
 
+pub mod devices;
 pub mod server;
+pub mod tls;
 pub mod types;
 
-pub use server::LocalSendServer;
+pub use devices::{DeviceRegistry, KnownDevice, TransferRecord, TransferredFile, TrustLevel};
+pub use server::{LocalSendServer, PrepareOutcome};
+pub use tls::TlsIdentity;
 pub use types::*;
@@ -2,12 +2,16 @@
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use sha2::{Digest, Sha256};
+use tokio::sync::oneshot;
 use tracing::{info, warn};
 
+use crate::devices::{DeviceRegistry, KnownDevice, TransferRecord, TransferredFile, TrustLevel};
+use crate::tls::TlsIdentity;
 use crate::types::*;
 
 /// Maximum session age before auto-cleanup.
@@ -15,11 +19,37 @@ const SESSION_TTL: Duration = Duration::from_secs(3600);
 
 /// LocalSend server managing sessions, discovery, and file reception.
 pub struct LocalSendServer {
-    pub device_info: DeviceInfo,
+    device_info: RwLock<DeviceInfo>,
     uploads_dir: PathBuf,
     sessions: RwLock<HashMap<String, TransferSession>>,
     discovered_devices: RwLock<HashMap<String, String>>,
     running: RwLock<bool>,
+    /// Capture text shares as notes instead of files.
+    text_notes: bool,
+    /// Directory holding the TLS certificate, when serving HTTPS.
+    cert_dir: Option<PathBuf>,
+    tls: RwLock<Option<TlsIdentity>>,
+    /// Known senders and transfer history.
+    devices: DeviceRegistry,
+    /// Sessions from `ask` devices waiting for the user, with the channel
+    /// that releases the sender's prepare-upload request.
+    pending: Mutex<HashMap<String, (TransferSession, oneshot::Sender<bool>)>>,
+}
+
+/// Result of a prepare-upload request.
+#[derive(Debug)]
+pub enum PrepareOutcome {
+    /// The session is open for uploads.
+    Accepted(PrepareUploadResponse),
+    /// The sender's device needs approval. `approval` resolves to `true`
+    /// once the user accepts (the session is then open with `response`),
+    /// or `false` when they decline.
+    Pending {
+        response: PrepareUploadResponse,
+        approval: oneshot::Receiver<bool>,
+    },
+    /// The sender's device is blocked.
+    Blocked,
 }
 
 impl LocalSendServer {
@@ -40,20 +70,77 @@ impl LocalSendServer {
         };
 
         Self {
-            device_info,
+            device_info: RwLock::new(device_info),
             uploads_dir: uploads_dir.to_path_buf(),
             sessions: RwLock::new(HashMap::new()),
             discovered_devices: RwLock::new(HashMap::new()),
             running: RwLock::new(false),
+            text_notes: false,
+            cert_dir: None,
+            tls: RwLock::new(None),
+            devices: DeviceRegistry::in_memory(TrustLevel::Trusted),
+            pending: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Track senders in `registry`. Without one, every sender is trusted
+    /// and nothing is persisted.
+    pub fn with_device_registry(mut self, registry: DeviceRegistry) -> Self {
+        self.devices = registry;
+        self
+    }
+
+    /// Serve the protocol over HTTPS with the certificate kept in
+    /// `cert_dir` (generated on first use). The device fingerprint becomes
+    /// the certificate's SHA-256 and announcements advertise `https`.
+    pub fn enable_tls(&mut self, cert_dir: &Path) -> std::io::Result<()> {
+        let identity = TlsIdentity::load_or_generate(cert_dir)?;
+        {
+            let info = self.device_info.get_mut();
+            info.protocol = "https".to_string();
+            info.fingerprint = identity.fingerprint();
+        }
+        self.cert_dir = Some(cert_dir.to_path_buf());
+        *self.tls.get_mut() = Some(identity);
+        Ok(())
+    }
+
+    /// Advertise `port` as the protocol port.
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.device_info.get_mut().port = port;
+        self
+    }
+
+    /// TLS configuration for the protocol listener; `None` when serving
+    /// plain HTTP.
+    pub fn tls_config(&self) -> Option<Arc<rustls::ServerConfig>> {
+        self.tls.read().as_ref().map(|t| t.server_config())
+    }
+
+    /// Replace the certificate with a new one and return the new
+    /// fingerprint. Paired devices see a new fingerprint afterwards.
+    pub fn regenerate_certificate(&self) -> Result<String, String> {
+        let dir = self.cert_dir.as_ref().ok_or("HTTPS is not enabled")?;
+        let identity = TlsIdentity::regenerate(dir).map_err(|e| e.to_string())?;
+        let fingerprint = identity.fingerprint();
+        self.device_info.write().fingerprint = fingerprint.clone();
+        *self.tls.write() = Some(identity);
+        Ok(fingerprint)
+    }
+
+    /// Capture text shares in memory for note creation instead of saving
+    /// them as files.
+    pub fn with_text_notes(mut self, enabled: bool) -> Self {
+        self.text_notes = enabled;
+        self
+    }
+
     /// Mark server as running.
     pub fn start(&self) {
         *self.running.write() = true;
         info!(
             "LocalSend server started (fingerprint: {})",
-            self.device_info.fingerprint
+            self.device_info.read().fingerprint
         );
     }
 
@@ -70,47 +157,167 @@ impl LocalSendServer {
 
     /// Get server status.
     pub fn get_status(&self) -> LocalSendStatus {
+        let info = self.device_info.read();
         LocalSendStatus {
             running: self.is_running(),
-            port: LOCALSEND_PORT,
-            device_name: self.device_info.alias.clone(),
-            fingerprint: self.device_info.fingerprint.clone(),
+            port: info.port,
+            protocol: info.protocol.clone(),
+            device_name: info.alias.clone(),
+            fingerprint: info.fingerprint.clone(),
             discovered_devices: self.discovered_devices.read().len(),
             active_sessions: self.sessions.read().len(),
         }
     }
 
     /// Get device info (for /api/localsend/v2/info and / endpoints).
-    pub fn get_device_info(&self) -> &DeviceInfo {
-        &self.device_info
+    pub fn get_device_info(&self) -> DeviceInfo {
+        self.device_info.read().clone()
     }
 
     /// Handle device registration (POST /api/localsend/v2/register).
-    pub fn register_device(&self, info: &DeviceInfo) {
+    /// Returns the device's trust level; blocked devices are not recorded
+    /// as discovered.
+    pub fn register_device(&self, info: &DeviceInfo) -> TrustLevel {
+        let trust = self.devices.observe(
+            &info.fingerprint,
+            &info.alias,
+            info.device_model.as_deref(),
+            Some(&info.device_type),
+        );
+        if trust == TrustLevel::Blocked {
+            return trust;
+        }
         if let Some(addr) = &info.address {
             self.discovered_devices
                 .write()
                 .insert(info.fingerprint.clone(), addr.clone());
         }
+        trust
+    }
+
+    // ---------------------------------------------------------------
+    // Devices
+    // ---------------------------------------------------------------
+
+    /// Known senders, most recently seen first.
+    pub fn known_devices(&self) -> Vec<KnownDevice> {
+        self.devices.list()
+    }
+
+    /// Change a device's trust level. Its pending transfers are accepted
+    /// when it becomes trusted and declined when it is blocked.
+    pub fn set_device_trust(&self, fingerprint: &str, trust: TrustLevel) -> Option<KnownDevice> {
+        let device = self.devices.set_trust(fingerprint, trust)?;
+        if trust != TrustLevel::Ask {
+            let waiting: Vec<String> = self
+                .pending
+                .lock()
+                .iter()
+                .filter(|(_, (session, _))| session.sender_info.fingerprint == fingerprint)
+                .map(|(id, _)| id.clone())
+                .collect();
+            for session_id in waiting {
+                self.resolve_pending(&session_id, trust == TrustLevel::Trusted);
+            }
+        }
+        Some(device)
+    }
+
+    /// Forget a device; it is treated as new if it sends again.
+    pub fn forget_device(&self, fingerprint: &str) -> bool {
+        self.discovered_devices.write().remove(fingerprint);
+        self.devices.forget(fingerprint)
+    }
+
+    /// One page of the transfer history, newest first.
+    pub fn transfer_history(
+        &self,
+        page: usize,
+        page_size: usize,
+        fingerprint: Option<&str>,
+    ) -> (Vec<TransferRecord>, usize) {
+        self.devices.history(page, page_size, fingerprint)
+    }
+
+    /// Transfers waiting for approval, oldest first.
+    pub fn pending_transfers(&self) -> Vec<PendingTransfer> {
+        let mut pending: Vec<PendingTransfer> = self
+            .pending
+            .lock()
+            .values()
+            .map(|(session, _)| PendingTransfer {
+                session_id: session.id.clone(),
+                fingerprint: session.sender_info.fingerprint.clone(),
+                alias: session.sender_info.alias.clone(),
+                file_count: session.files.len(),
+                total_bytes: session.files.values().map(|f| f.size).sum(),
+                requested_at: session.started_at.to_rfc3339(),
+            })
+            .collect();
+        pending.sort_by(|a, b| a.requested_at.cmp(&b.requested_at));
+        pending
+    }
+
+    /// Accept or decline a pending transfer. Returns `false` if no
+    /// transfer with this id is waiting.
+    pub fn resolve_pending(&self, session_id: &str, accept: bool) -> bool {
+        let Some((session, approval)) = self.pending.lock().remove(session_id) else {
+            return false;
+        };
+        if accept {
+            self.sessions.write().insert(session_id.to_string(), session);
+        }
+        info!(
+            "Transfer {} {}",
+            session_id,
+            if accept { "accepted" } else { "declined" }
+        );
+        let _ = approval.send(accept);
+        true
     }
 
     // ---------------------------------------------------------------
     // Session Management
     // ---------------------------------------------------------------
 
-    /// Prepare a new upload session. Returns session ID and file tokens.
-    pub fn prepare_upload(&self, req: PrepareUploadRequest) -> PrepareUploadResponse {
+    /// Prepare a new upload session. Returns session ID and file tokens,
+    /// unless the sender is blocked or has to be approved first.
+    pub fn prepare_upload(&self, req: PrepareUploadRequest) -> PrepareOutcome {
         // Cleanup stale sessions
         self.cleanup_stale_sessions();
 
+        let trust = self.devices.observe(
+            &req.info.fingerprint,
+            &req.info.alias,
+            req.info.device_model.as_deref(),
+            Some(&req.info.device_type),
+        );
+        if trust == TrustLevel::Blocked {
+            warn!("Refused transfer from blocked device {}", req.info.fingerprint);
+            return PrepareOutcome::Blocked;
+        }
+
         let session_id = uuid::Uuid::new_v4().to_string();
         let mut file_tokens = HashMap::new();
 
-        for (file_id, _file_info) in &req.files {
+        for file_id in req.files.keys() {
             let token = uuid::Uuid::new_v4().to_string();
             file_tokens.insert(file_id.clone(), token);
         }
 
+        let mut text_files = std::collections::HashSet::new();
+        let mut received_texts = HashMap::new();
+        if self.text_notes {
+            for (file_id, file) in req.files.iter().filter(|(_, f)| f.is_text_share()) {
+                text_files.insert(file_id.clone());
+                // Senders put the whole message in the preview; use it in
+                // case the upload never comes
+                if let Some(preview) = file.preview.as_ref().filter(|p| p.len() as u64 == file.size) {
+                    received_texts.insert(file_id.clone(), preview.clone());
+                }
+            }
+        }
+
         let session = TransferSession {
             id: session_id.clone(),
             sender_info: req.info,
@@ -118,21 +325,36 @@ impl LocalSendServer {
             file_tokens: file_tokens.clone(),
             received_files: std::collections::HashSet::new(),
             saved_filenames: Vec::new(),
+            text_files,
+            received_texts,
             created_at: std::time::Instant::now(),
+            started_at: chrono::Utc::now(),
+        };
+        let response = PrepareUploadResponse {
+            session_id: session_id.clone(),
+            files: file_tokens,
         };
 
+        if trust == TrustLevel::Ask {
+            let (approve, approval) = oneshot::channel();
+            self.pending.lock().insert(session_id.clone(), (session, approve));
+            info!(
+                "Transfer session {} waiting for approval ({} files)",
+                session_id,
+                response.files.len()
+            );
+            return PrepareOutcome::Pending { response, approval };
+        }
+
         self.sessions.write().insert(session_id.clone(), session);
 
         info!(
             "Transfer session created: {} ({} files)",
             session_id,
-            file_tokens.len()
+            response.files.len()
         );
 
-        PrepareUploadResponse {
-            session_id,
-            files: file_tokens,
-        }
+        PrepareOutcome::Accepted(response)
     }
 
     /// Validate upload parameters. Returns Ok(file_name) or Err(error_msg).
@@ -175,6 +397,23 @@ impl LocalSendServer {
         }
     }
 
+    /// Whether an upload is a text share to capture as a note.
+    pub fn is_text_share(&self, session_id: &str, file_id: &str) -> bool {
+        self.sessions
+            .read()
+            .get(session_id)
+            .is_some_and(|s| s.text_files.contains(file_id))
+    }
+
+    /// Record the content of an uploaded text share.
+    pub fn record_text(&self, session_id: &str, file_id: &str, text: String) {
+        let mut sessions = self.sessions.write();
+        if let Some(session) = sessions.get_mut(session_id) {
+            session.received_files.insert(file_id.to_string());
+            session.received_texts.insert(file_id.to_string(), text);
+        }
+    }
+
     /// Resolve a unique filename in the uploads directory.
     pub fn resolve_filename(&self, original_name: &str) -> PathBuf {
         let path = self.uploads_dir.join(original_name);
@@ -204,16 +443,24 @@ impl LocalSendServer {
         }
     }
 
-    /// Finish a session, returning saved filenames for auto-import.
-    pub fn finish_session(&self, session_id: &str) -> Option<Vec<String>> {
-        let mut sessions = self.sessions.write();
-        let session = sessions.remove(session_id)?;
+    /// Finish a session, recording it in the transfer history and returning
+    /// saved filenames for auto-import and captured texts for note creation.
+    pub fn finish_session(&self, session_id: &str) -> Option<FinishedSession> {
+        let session = self.sessions.write().remove(session_id)?;
+        self.record_transfer(&session);
         info!(
-            "Session {} finished: {} files received",
+            "Session {} finished: {} files, {} texts received",
             session_id,
-            session.saved_filenames.len()
+            session.saved_filenames.len(),
+            session.received_texts.len()
         );
-        Some(session.saved_filenames)
+        let mut texts: Vec<(String, String)> = session.received_texts.into_iter().collect();
+        texts.sort();
+        Some(FinishedSession {
+            sender_alias: session.sender_info.alias,
+            saved_filenames: session.saved_filenames,
+            texts: texts.into_iter().map(|(_, text)| text).collect(),
+        })
     }
 
     /// Cancel a session.
@@ -236,7 +483,7 @@ impl LocalSendServer {
 
     /// Build the multicast announcement payload.
     pub fn announcement_payload(&self) -> serde_json::Value {
-        serde_json::to_value(&self.device_info).unwrap_or_default()
+        serde_json::to_value(&*self.device_info.read()).unwrap_or_default()
     }
 
     /// Record a discovered device.
@@ -250,6 +497,34 @@ impl LocalSendServer {
     // Internal
     // ---------------------------------------------------------------
 
+    fn record_transfer(&self, session: &TransferSession) {
+        let mut ids: Vec<&String> = session
+            .received_files
+            .iter()
+            .chain(session.received_texts.keys())
+            .collect();
+        ids.sort();
+        ids.dedup();
+        let files: Vec<TransferredFile> = ids
+            .into_iter()
+            .filter_map(|id| session.files.get(id).map(|f| (id, f)))
+            .map(|(id, f)| TransferredFile {
+                file_name: f.file_name.clone(),
+                size: f.size,
+                note: session.received_texts.contains_key(id),
+            })
+            .collect();
+        self.devices.record_transfer(TransferRecord {
+            session_id: session.id.clone(),
+            fingerprint: session.sender_info.fingerprint.clone(),
+            alias: session.sender_info.alias.clone(),
+            total_bytes: files.iter().map(|f| f.size).sum(),
+            files,
+            started_at: session.started_at.to_rfc3339(),
+            finished_at: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+
     fn cleanup_stale_sessions(&self) {
         let mut sessions = self.sessions.write();
         let stale: Vec<String> = sessions
@@ -265,10 +540,40 @@ impl LocalSendServer {
     }
 }
 
+/// Generate a consistent device fingerprint.
+fn generate_fingerprint(device_name: &str) -> String {
+    let hostname = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("HOST"))
+        .unwrap_or_else(|_| "mindsage".to_string());
+
+    let mut hasher = Sha256::new();
+    hasher.update(device_name.as_bytes());
+    hasher.update(hostname.as_bytes());
+    let result = hasher.finalize();
+    hex::encode(&result[..16])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn accepted(outcome: PrepareOutcome) -> PrepareUploadResponse {
+        match outcome {
+            PrepareOutcome::Accepted(response) => response,
+            other => panic!("transfer not accepted: {:?}", other),
+        }
+    }
+
+    fn sender(fingerprint: &str) -> SenderInfo {
+        SenderInfo {
+            alias: "Phone".to_string(),
+            version: "2.0".to_string(),
+            device_model: None,
+            device_type: "mobile".to_string(),
+            fingerprint: fingerprint.to_string(),
+        }
+    }
+
     fn test_server() -> (LocalSendServer, tempfile::TempDir) {
         let dir = tempfile::tempdir().unwrap();
         let uploads_dir = dir.path().join("uploads");
@@ -337,7 +642,7 @@ mod tests {
             files,
         };
 
-        let resp = server.prepare_upload(req);
+        let resp = accepted(server.prepare_upload(req));
         assert!(!resp.session_id.is_empty());
         assert_eq!(resp.files.len(), 1);
         assert!(resp.files.contains_key("file-1"));
@@ -348,7 +653,8 @@ mod tests {
         // Record upload and finish
         server.record_upload(&resp.session_id, "file-1", "test.txt");
         let saved = server.finish_session(&resp.session_id).unwrap();
-        assert_eq!(saved, vec!["test.txt"]);
+        assert_eq!(saved.saved_filenames, vec!["test.txt"]);
+        assert!(saved.texts.is_empty());
 
         // Session removed
         assert_eq!(server.get_status().active_sessions, 0);
@@ -371,7 +677,7 @@ mod tests {
             },
         );
 
-        let resp = server.prepare_upload(PrepareUploadRequest {
+        let resp = accepted(server.prepare_upload(PrepareUploadRequest {
             info: SenderInfo {
                 alias: "Sender".to_string(),
                 version: "2.0".to_string(),
@@ -380,7 +686,7 @@ mod tests {
                 fingerprint: "xyz".to_string(),
             },
             files,
-        });
+        }));
 
         let token = resp.files.get("f1").unwrap();
 
@@ -413,7 +719,7 @@ mod tests {
     fn test_cancel_session() {
         let (server, _dir) = test_server();
 
-        let resp = server.prepare_upload(PrepareUploadRequest {
+        let resp = accepted(server.prepare_upload(PrepareUploadRequest {
             info: SenderInfo {
                 alias: "S".to_string(),
                 version: "2.0".to_string(),
@@ -422,7 +728,7 @@ mod tests {
                 fingerprint: "f".to_string(),
             },
             files: HashMap::new(),
-        });
+        }));
 
         assert!(server.cancel_session(&resp.session_id));
         assert!(!server.cancel_session(&resp.session_id)); // already cancelled
@@ -472,17 +778,150 @@ mod tests {
         let fp3 = generate_fingerprint("OtherDevice");
         assert_ne!(fp1, fp3);
     }
-}
 
-/// Generate a consistent device fingerprint.
-fn generate_fingerprint(device_name: &str) -> String {
-    let hostname = std::env::var("HOSTNAME")
-        .or_else(|_| std::env::var("HOST"))
-        .unwrap_or_else(|_| "mindsage".to_string());
+    #[test]
+    fn test_text_shares_are_captured() {
+        let (server, _dir) = test_server();
+        let server = server.with_text_notes(true);
+        let text = |id: &str, size: u64, preview: Option<&str>| FileInfo {
+            id: id.to_string(),
+            file_name: format!("{}.txt", id),
+            size,
+            file_type: "text/plain".to_string(),
+            sha256: None,
+            preview: preview.map(str::to_string),
+        };
+        let files = HashMap::from([
+            ("a".to_string(), text("a", 19, Some("https://example.org"))),
+            ("b".to_string(), text("b", 11, Some("Hello"))),
+            ("big".to_string(), text("big", MAX_TEXT_SHARE_BYTES + 1, None)),
+        ]);
+        let resp = accepted(server.prepare_upload(PrepareUploadRequest {
+            info: SenderInfo {
+                alias: "Pixel".to_string(),
+                version: "2.0".to_string(),
+                device_model: None,
+                device_type: "mobile".to_string(),
+                fingerprint: "p".to_string(),
+            },
+            files,
+        }));
+        assert!(server.is_text_share(&resp.session_id, "b"));
+        assert!(!server.is_text_share(&resp.session_id, "big"));
+
+        // "a" is complete in its preview; "b" needs its upload
+        server.record_text(&resp.session_id, "b", "Hello world".to_string());
+        let finished = server.finish_session(&resp.session_id).unwrap();
+        assert_eq!(finished.sender_alias, "Pixel");
+        assert_eq!(finished.texts, ["https://example.org", "Hello world"]);
+    }
 
-    let mut hasher = Sha256::new();
-    hasher.update(device_name.as_bytes());
-    hasher.update(hostname.as_bytes());
-    let result = hasher.finalize();
-    hex::encode(&result[..16])
+    #[test]
+    fn test_https_uses_certificate_fingerprint() {
+        let (server, dir) = test_server();
+        let http_fingerprint = server.get_device_info().fingerprint;
+        assert!(server.regenerate_certificate().is_err());
+
+        let mut server = server;
+        server.enable_tls(&dir.path().join("localsend")).unwrap();
+        let info = server.get_device_info();
+        assert_eq!(info.protocol, "https");
+        assert_ne!(info.fingerprint, http_fingerprint);
+        assert_eq!(server.announcement_payload()["protocol"], "https");
+        assert!(server.tls_config().is_some());
+
+        let fingerprint = server.regenerate_certificate().unwrap();
+        assert_ne!(fingerprint, info.fingerprint);
+        assert_eq!(server.get_status().fingerprint, fingerprint);
+    }
+    fn photo_request(fingerprint: &str) -> PrepareUploadRequest {
+        let photo = FileInfo {
+            id: "photo".to_string(),
+            file_name: "IMG_0001.jpg".to_string(),
+            size: 2048,
+            file_type: "image/jpeg".to_string(),
+            sha256: None,
+            preview: None,
+        };
+        PrepareUploadRequest {
+            info: sender(fingerprint),
+            files: HashMap::from([("photo".to_string(), photo)]),
+        }
+    }
+
+    #[test]
+    fn test_blocked_and_trusted_devices() {
+        let (server, dir) = test_server();
+        let registry = DeviceRegistry::open(&dir.path().join("localsend-devices.json"), TrustLevel::Ask);
+        let server = server.with_device_registry(registry);
+
+        // Unknown devices get the default (ask) and wait for approval
+        let PrepareOutcome::Pending { response, mut approval } = server.prepare_upload(photo_request("fp-1")) else {
+            panic!("expected a pending transfer");
+        };
+        assert_eq!(server.pending_transfers()[0].total_bytes, 2048);
+        assert!(server.validate_upload(&response.session_id, "photo", &response.files["photo"]).is_err());
+        // Trusting the device releases its pending transfer
+        server.set_device_trust("fp-1", TrustLevel::Trusted).unwrap();
+        assert_eq!(approval.try_recv(), Ok(true));
+        assert!(server.pending_transfers().is_empty());
+        assert!(server.validate_upload(&response.session_id, "photo", &response.files["photo"]).is_ok());
+
+        // Trusted devices are accepted straight away
+        accepted(server.prepare_upload(photo_request("fp-1")));
+
+        // Blocked devices are refused, also on register
+        server.prepare_upload(photo_request("fp-2"));
+        server.set_device_trust("fp-2", TrustLevel::Blocked).unwrap();
+        assert!(matches!(server.prepare_upload(photo_request("fp-2")), PrepareOutcome::Blocked));
+        let info = DeviceInfo {
+            fingerprint: "fp-2".to_string(),
+            address: Some("192.168.1.60".to_string()),
+            ..server.get_device_info()
+        };
+        assert_eq!(server.register_device(&info), TrustLevel::Blocked);
+        assert_eq!(server.get_status().discovered_devices, 0);
+
+        // Declining drops the session
+        server.set_device_trust("fp-2", TrustLevel::Ask).unwrap();
+        let PrepareOutcome::Pending { response, mut approval } = server.prepare_upload(photo_request("fp-2")) else {
+            panic!("expected a pending transfer");
+        };
+        assert!(server.resolve_pending(&response.session_id, false));
+        assert_eq!(approval.try_recv(), Ok(false));
+        assert!(!server.resolve_pending(&response.session_id, true));
+    }
+
+    #[test]
+    fn test_finished_transfers_are_recorded() {
+        let (server, _dir) = test_server();
+        let server = server.with_text_notes(true);
+        let mut request = photo_request("fp-1");
+        request.files.insert(
+            "note".to_string(),
+            FileInfo {
+                id: "note".to_string(),
+                file_name: "note.txt".to_string(),
+                size: 5,
+                file_type: "text/plain".to_string(),
+                sha256: None,
+                preview: Some("Hello".to_string()),
+            },
+        );
+        let resp = accepted(server.prepare_upload(request));
+        server.record_upload(&resp.session_id, "photo", "IMG_0001.jpg");
+        server.finish_session(&resp.session_id).unwrap();
+        // Cancelled sessions are not history
+        let cancelled = accepted(server.prepare_upload(photo_request("fp-1")));
+        server.cancel_session(&cancelled.session_id);
+
+        let (history, total) = server.transfer_history(1, 10, None);
+        assert_eq!(total, 1);
+        let record = &history[0];
+        assert_eq!((record.session_id.as_str(), record.alias.as_str()), (resp.session_id.as_str(), "Phone"));
+        assert_eq!(record.total_bytes, 2053);
+        let files: Vec<(&str, bool)> = record.files.iter().map(|f| (f.file_name.as_str(), f.note)).collect();
+        assert_eq!(files, [("note.txt", true), ("IMG_0001.jpg", false)]);
+        assert_eq!(server.known_devices()[0].transfers, 1);
+    }
 }
@@ -0,0 +1,131 @@
+//! TLS identity for the HTTPS protocol variant.
+//!
+//! LocalSend clients do not verify certificates against a CA; they pin the
+//! device fingerprint, which the spec defines as the SHA-256 of the
+//! certificate (DER, lowercase hex). The self-signed certificate and its
+//! key are generated once and kept in the data directory so the fingerprint
+//! stays stable across restarts.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+const CERT_FILE: &str = "localsend-cert.pem";
+const KEY_FILE: &str = "localsend-key.pem";
+
+/// Self-signed certificate for the protocol listener, with the rustls
+/// configuration holding its key.
+#[derive(Clone)]
+pub struct TlsIdentity {
+    cert: CertificateDer<'static>,
+    config: Arc<rustls::ServerConfig>,
+}
+
+impl TlsIdentity {
+    /// Load the certificate from `dir`, generating one on first use.
+    pub fn load_or_generate(dir: &Path) -> std::io::Result<Self> {
+        match (std::fs::read(dir.join(CERT_FILE)), std::fs::read_to_string(dir.join(KEY_FILE))) {
+            (Ok(cert_pem), Ok(key_pem)) => {
+                let cert = CertificateDer::from_pem_slice(&cert_pem).map_err(std::io::Error::other)?;
+                Self::new(cert, key_pem)
+            }
+            _ => Self::regenerate(dir),
+        }
+    }
+
+    /// Generate a new certificate and key, replacing any in `dir`.
+    pub fn regenerate(dir: &Path) -> std::io::Result<Self> {
+        let rcgen::CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec!["mindsage.local".to_string()]).map_err(std::io::Error::other)?;
+        std::fs::create_dir_all(dir)?;
+        write_private(&dir.join(KEY_FILE), &key_pair.serialize_pem())?;
+        std::fs::write(dir.join(CERT_FILE), cert.pem())?;
+        let identity = Self::new(cert.der().clone(), key_pair.serialize_pem())?;
+        info!("Generated LocalSend certificate (fingerprint: {})", identity.fingerprint());
+        Ok(identity)
+    }
+
+    fn new(cert: CertificateDer<'static>, key_pem: String) -> std::io::Result<Self> {
+        let key = PrivateKeyDer::from_pem_slice(key_pem.as_bytes()).map_err(std::io::Error::other)?;
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .and_then(|b| b.with_no_client_auth().with_single_cert(vec![cert.clone()], key))
+            .map_err(std::io::Error::other)?;
+        Ok(Self {
+            cert,
+            config: Arc::new(config),
+        })
+    }
+
+    /// SHA-256 of the DER certificate, lowercase hex.
+    pub fn fingerprint(&self) -> String {
+        hex::encode(Sha256::digest(self.cert.as_ref()))
+    }
+
+    /// rustls server configuration presenting this certificate.
+    pub fn server_config(&self) -> Arc<rustls::ServerConfig> {
+        self.config.clone()
+    }
+
+    pub fn certificate_der(&self) -> &[u8] {
+        self.cert.as_ref()
+    }
+}
+
+impl std::fmt::Debug for TlsIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsIdentity")
+            .field("fingerprint", &self.fingerprint())
+            .finish()
+    }
+}
+
+/// Write a file readable only by the owner where the platform allows it.
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(contents.as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::write(path, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_sha256_of_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let identity = TlsIdentity::load_or_generate(dir.path()).unwrap();
+        let fingerprint = identity.fingerprint();
+        assert_eq!(fingerprint.len(), 64);
+        assert_eq!(fingerprint, hex::encode(Sha256::digest(identity.certificate_der())));
+
+        // Persisted: loading again keeps the fingerprint
+        let reloaded = TlsIdentity::load_or_generate(dir.path()).unwrap();
+        assert_eq!(reloaded.fingerprint(), fingerprint);
+
+        // Regenerating replaces the files and the fingerprint
+        let regenerated = TlsIdentity::regenerate(dir.path()).unwrap();
+        assert_ne!(regenerated.fingerprint(), fingerprint);
+        assert_eq!(
+            TlsIdentity::load_or_generate(dir.path()).unwrap().fingerprint(),
+            regenerated.fingerprint()
+        );
+    }
+}
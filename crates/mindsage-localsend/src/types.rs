@@ -44,6 +44,18 @@ pub struct FileInfo {
     pub preview: Option<String>,
 }
 
+/// Largest text share captured as a note; bigger texts are saved as files.
+pub const MAX_TEXT_SHARE_BYTES: u64 = 64 * 1024;
+
+impl FileInfo {
+    /// Whether this is a text snippet (a shared message, URL or clipboard)
+    /// rather than a document: a `text/plain` type within the size limit.
+    pub fn is_text_share(&self) -> bool {
+        (self.file_type == "text" || self.file_type.starts_with("text/plain"))
+            && self.size <= MAX_TEXT_SHARE_BYTES
+    }
+}
+
 /// Sender info in prepare-upload request.
 #[derive(Debug, Clone, Deserialize)]
 pub struct SenderInfo {
@@ -79,11 +91,40 @@ pub struct TransferSession {
     pub file_tokens: HashMap<String, String>,
     pub received_files: HashSet<String>,
     pub saved_filenames: Vec<String>,
+    /// File ids of text shares to capture as notes.
+    pub text_files: HashSet<String>,
+    /// Captured text by file id, from the upload or a complete preview.
+    pub received_texts: HashMap<String, String>,
     pub created_at: std::time::Instant,
+    /// When the sender prepared the upload, for the transfer history.
+    pub started_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A transfer waiting for the user to accept it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingTransfer {
+    pub session_id: String,
+    pub fingerprint: String,
+    pub alias: String,
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub requested_at: String,
+}
+
+/// What a finished session received.
+#[derive(Debug, Clone, Default)]
+pub struct FinishedSession {
+    pub sender_alias: String,
+    /// Files saved under the uploads directory.
+    pub saved_filenames: Vec<String>,
+    /// Text shares, in file id order.
+    pub texts: Vec<String>,
 }
 
 /// Upload query parameters.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
 pub struct UploadQuery {
     #[serde(rename = "sessionId")]
     pub session_id: String,
@@ -94,6 +135,7 @@ pub struct UploadQuery {
 
 /// Cancel/finish query parameters.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
 pub struct SessionQuery {
     #[serde(rename = "sessionId")]
     pub session_id: String,
@@ -104,6 +146,8 @@ pub struct SessionQuery {
 pub struct LocalSendStatus {
     pub running: bool,
     pub port: u16,
+    /// `https` or `http`.
+    pub protocol: String,
     #[serde(rename = "deviceName")]
     pub device_name: String,
     pub fingerprint: String,
@@ -0,0 +1,229 @@
+//! Session-stable anonymization — one value → token map per chat session.
+//!
+//! Detection re-runs on every request, so tokens are assigned by the
+//! session rather than per call: a value keeps its token across turns and
+//! across retrieved chunks, and new values extend the map with the next
+//! number of their type. Maps stay on the device; nothing here is
+//! serializable.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::pii::{AnonymizationResult, PiiDetector, PiiType};
+
+/// Idle time after which a session's map is dropped.
+pub const SESSION_TTL: Duration = Duration::from_secs(3600);
+
+/// Sessions kept at most; the least recently used one is evicted.
+pub const MAX_SESSIONS: usize = 100;
+
+/// Longest placeholder a provider can stream (`<PII:CREDIT_CARD:` plus a
+/// number and `>`), bounding how much text a stream holds back.
+const MAX_PLACEHOLDER_CHARS: usize = 32;
+
+const PLACEHOLDER_PREFIX: &str = "<PII:";
+
+/// The value → token map of one chat session.
+#[derive(Default)]
+pub struct AnonymizationSession {
+    /// Original value → placeholder.
+    tokens: HashMap<String, String>,
+    /// Placeholder → original value.
+    values: HashMap<String, String>,
+    /// Placeholders assigned so far, per type.
+    counters: HashMap<PiiType, usize>,
+}
+
+impl AnonymizationSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace PII in `text` with this session's placeholders
+    /// (`<PII:TYPE:n>`), assigning the next number to values not seen yet.
+    pub fn anonymize(&mut self, detector: &PiiDetector, text: &str) -> AnonymizationResult {
+        let entities = detector.detect(text);
+        let mut result = String::with_capacity(text.len());
+        let mut last_end = 0;
+        for entity in &entities {
+            result.push_str(&text[last_end..entity.start]);
+            result.push_str(&self.token_for(&entity.text, entity.pii_type));
+            last_end = entity.end;
+        }
+        result.push_str(&text[last_end..]);
+
+        AnonymizationResult {
+            text: result,
+            token_count: entities.len(),
+            entities,
+        }
+    }
+
+    fn token_for(&mut self, value: &str, pii_type: PiiType) -> String {
+        if let Some(token) = self.tokens.get(value) {
+            return token.clone();
+        }
+        let counter = self.counters.entry(pii_type).or_insert(0);
+        *counter += 1;
+        let token = format!("{}{}:{}>", PLACEHOLDER_PREFIX, pii_type.label(), counter);
+        self.tokens.insert(value.to_string(), token.clone());
+        self.values.insert(token.clone(), value.to_string());
+        token
+    }
+
+    /// Restore this session's placeholders in `text`. Unknown ones are
+    /// left as they are.
+    pub fn deanonymize(&self, text: &str) -> String {
+        if !text.contains(PLACEHOLDER_PREFIX) {
+            return text.to_string();
+        }
+        let mut result = text.to_string();
+        for (token, value) in &self.values {
+            result = result.replace(token.as_str(), value);
+        }
+        result
+    }
+
+    /// Number of distinct values mapped.
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
+
+/// Bytes at the end of `text` that may be the start of a placeholder cut
+/// off mid-stream, and so cannot be restored yet.
+pub fn pending_placeholder_len(text: &str) -> usize {
+    let Some(start) = text.rfind('<') else {
+        return 0;
+    };
+    let tail = &text[start..];
+    let open = !tail.contains('>') && tail.len() < MAX_PLACEHOLDER_CHARS;
+    let prefix_matches = if tail.len() <= PLACEHOLDER_PREFIX.len() {
+        PLACEHOLDER_PREFIX.starts_with(tail)
+    } else {
+        tail.starts_with(PLACEHOLDER_PREFIX)
+    };
+    if open && prefix_matches {
+        tail.len()
+    } else {
+        0
+    }
+}
+
+/// A session's map, shared by the requests of its turns.
+pub type SharedSession = Arc<Mutex<AnonymizationSession>>;
+
+/// Anonymization sessions keyed by chat session id, with a sliding TTL.
+pub struct AnonymizationSessions {
+    sessions: Mutex<HashMap<String, (Instant, SharedSession)>>,
+    ttl: Duration,
+}
+
+impl AnonymizationSessions {
+    pub fn new() -> Self {
+        Self::with_ttl(SESSION_TTL)
+    }
+
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    /// The map of session `id`, created when it is new or has expired.
+    /// Each call restarts the session's TTL.
+    pub fn session(&self, id: &str) -> SharedSession {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock();
+        sessions.retain(|_, (used, _)| now.duration_since(*used) < self.ttl);
+
+        if !sessions.contains_key(id) && sessions.len() >= MAX_SESSIONS {
+            if let Some(oldest) = sessions.iter().min_by_key(|(_, (used, _))| *used).map(|(id, _)| id.clone()) {
+                sessions.remove(&oldest);
+            }
+        }
+        let entry = sessions
+            .entry(id.to_string())
+            .or_insert_with(|| (now, Arc::new(Mutex::new(AnonymizationSession::new()))));
+        entry.0 = now;
+        entry.1.clone()
+    }
+
+    /// Drop the map of session `id`.
+    pub fn remove(&self, id: &str) -> bool {
+        self.sessions.lock().remove(id).is_some()
+    }
+
+    /// Sessions that have not expired.
+    pub fn len(&self) -> usize {
+        let now = Instant::now();
+        self.sessions
+            .lock()
+            .values()
+            .filter(|(used, _)| now.duration_since(*used) < self.ttl)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for AnonymizationSessions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_are_stable_across_calls() {
+        let detector = PiiDetector::new();
+        let mut session = AnonymizationSession::new();
+        let first = session.anonymize(&detector, "Mail alice@example.com or bob@example.com.");
+        assert_eq!(first.text, "Mail <PII:EMAIL:1> or <PII:EMAIL:2>.");
+
+        // Repeated values keep their token; new ones extend the map
+        let second = session.anonymize(&detector, "carol@example.com wrote to alice@example.com");
+        assert_eq!(second.text, "<PII:EMAIL:3> wrote to <PII:EMAIL:1>");
+        assert_eq!(session.len(), 3);
+        assert_eq!(session.deanonymize(&second.text), "carol@example.com wrote to alice@example.com");
+        assert_eq!(session.deanonymize("<PII:EMAIL:9>"), "<PII:EMAIL:9>");
+    }
+
+    #[test]
+    fn test_pending_placeholder_is_held_back() {
+        assert_eq!(pending_placeholder_len("Write to <PII:EM"), 7);
+        assert_eq!(pending_placeholder_len("Write to <"), 1);
+        assert_eq!(pending_placeholder_len("Write to <PII:EMAIL:1>"), 0);
+        assert_eq!(pending_placeholder_len("if a < b"), 0);
+        assert_eq!(pending_placeholder_len("<div"), 0);
+    }
+
+    #[test]
+    fn test_sessions_expire_and_are_shared_by_id() {
+        let detector = PiiDetector::new();
+        let sessions = AnonymizationSessions::new();
+        sessions.session("chat-1").lock().anonymize(&detector, "alice@example.com");
+        assert_eq!(sessions.session("chat-1").lock().len(), 1);
+        assert!(sessions.session("chat-2").lock().is_empty());
+        assert!(sessions.remove("chat-1"));
+        assert!(sessions.session("chat-1").lock().is_empty());
+
+        let expiring = AnonymizationSessions::with_ttl(Duration::ZERO);
+        expiring.session("chat-1").lock().anonymize(&detector, "alice@example.com");
+        assert!(expiring.session("chat-1").lock().is_empty());
+        assert!(expiring.is_empty());
+    }
+}
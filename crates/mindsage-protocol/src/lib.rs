@@ -2,10 +2,13 @@
 //!
 //! Provides regex-based PII detection covering structured PII types
 //! (email, phone, SSN, credit card, IP, URL), token-based anonymization
-//! with session-scoped de-anonymization, and consent session management.
+//! with session-scoped de-anonymization, session-stable token maps for chat,
+//! and consent session management.
 
+pub mod anonymization;
 pub mod consent;
 pub mod pii;
 
+pub use anonymization::{AnonymizationSession, AnonymizationSessions};
 pub use consent::{ConsentSession, ConsentManager};
 pub use pii::{PiiDetector, PiiEntity, PiiType, AnonymizationResult};
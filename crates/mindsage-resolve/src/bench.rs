@@ -0,0 +1,410 @@
+//! Search evaluation — graded relevance fixtures, ranking metrics and a
+//! runner comparing search configurations on the same store.
+//!
+//! A fixture lists queries with the documents or chunks that should come
+//! back, each with a grade (higher is more relevant):
+//!
+//! ```json
+//! {
+//!   "documents": [{"key": "manual", "text": "Boiler user manual. ..."}],
+//!   "queries": [
+//!     {"query": "boiler pressure", "relevant": [{"key": "manual", "grade": 2}, {"docId": 41}]}
+//!   ]
+//! }
+//! ```
+//!
+//! `documents` is optional: with it the fixture brings its own corpus
+//! (`populate` adds it and judgments name documents by `key`); without it
+//! judgments name existing rows by `docId` or `chunkId`. A document judgment
+//! is credited once, to the first of its chunks in the ranking.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Instant;
+
+use mindsage_core::{CapabilityTier, Error, Result};
+use mindsage_store::{AddDocumentOptions, SearchSyntax, SqliteStore};
+use serde::{Deserialize, Serialize};
+
+use crate::hybrid::HybridResolver;
+use crate::types::{ResolveQuery, ResolverKind};
+
+/// The fixture shipped with the crate: eight short notes and six queries.
+pub const BUILTIN_FIXTURE: &str = include_str!("../fixtures/search.json");
+
+/// Queries with graded judgments, and optionally the corpus they refer to.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RelevanceFixture {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub documents: Vec<FixtureDocument>,
+    pub queries: Vec<FixtureQuery>,
+}
+
+/// A corpus document, referred to by `key` in judgments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureDocument {
+    pub key: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureQuery {
+    pub query: String,
+    pub relevant: Vec<Judgment>,
+}
+
+/// A relevant document or chunk; exactly one of `key`, `doc_id` and
+/// `chunk_id` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Judgment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_id: Option<i64>,
+    #[serde(default = "default_grade")]
+    pub grade: u32,
+}
+
+fn default_grade() -> u32 {
+    1
+}
+
+impl RelevanceFixture {
+    /// Parse and validate a fixture.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let fixture: Self = serde_json::from_str(json)?;
+        fixture.validate()?;
+        Ok(fixture)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_FIXTURE).expect("built-in fixture is valid")
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.queries.is_empty() {
+            return Err(Error::Config("fixture has no queries".to_string()));
+        }
+        for query in &self.queries {
+            for judgment in &query.relevant {
+                let targets = [judgment.key.is_some(), judgment.doc_id.is_some(), judgment.chunk_id.is_some()];
+                if targets.iter().filter(|&&set| set).count() != 1 {
+                    return Err(Error::Config(format!(
+                        "judgment for {:?} must set exactly one of key, docId and chunkId",
+                        query.query
+                    )));
+                }
+                if let Some(key) = &judgment.key {
+                    if !self.documents.iter().any(|d| &d.key == key) {
+                        return Err(Error::Config(format!("unknown document key {:?}", key)));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the fixture's documents to `store`, one level-1 chunk each, and
+    /// return their ids by key. Embeddings are left to the caller.
+    pub fn populate(&self, store: &SqliteStore) -> Result<HashMap<String, i64>> {
+        let mut ids = HashMap::new();
+        for doc in &self.documents {
+            let metadata = serde_json::json!({ "source": "bench", "benchKey": doc.key });
+            let options = AddDocumentOptions { metadata: Some(metadata), ..Default::default() };
+            let doc_id = store.add_document(&doc.text, options)?;
+            let end = doc.text.len() as i32;
+            store.add_chunk(doc_id, &doc.text, 0, 1, None, Some(0), Some(end), None, None, None)?;
+            ids.insert(doc.key.clone(), doc_id);
+        }
+        Ok(ids)
+    }
+}
+
+/// One ranked result: the chunk and the document it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RankedHit {
+    pub chunk_id: i64,
+    pub doc_id: i64,
+}
+
+/// Quality of one ranking against its judgments, each in `0.0..=1.0`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QueryMetrics {
+    pub ndcg: f64,
+    pub reciprocal_rank: f64,
+    pub recall: f64,
+}
+
+/// Score the first `k` hits against `judgments`, whose keys must already be
+/// resolved to `doc_id`s.
+pub fn evaluate(hits: &[RankedHit], judgments: &[Judgment], k: usize) -> QueryMetrics {
+    let mut credited = vec![false; judgments.len()];
+    let mut dcg = 0.0;
+    let mut reciprocal_rank = 0.0;
+    for (rank, hit) in hits.iter().take(k).enumerate() {
+        let judged = judgments.iter().enumerate().position(|(i, j)| {
+            !credited[i] && (j.chunk_id == Some(hit.chunk_id) || j.doc_id == Some(hit.doc_id))
+        });
+        let Some(index) = judged else { continue };
+        credited[index] = true;
+        let grade = judgments[index].grade;
+        if grade == 0 {
+            continue;
+        }
+        dcg += gain(grade) / (rank as f64 + 2.0).log2();
+        if reciprocal_rank == 0.0 {
+            reciprocal_rank = 1.0 / (rank as f64 + 1.0);
+        }
+    }
+
+    let mut grades: Vec<u32> = judgments.iter().map(|j| j.grade).filter(|&g| g > 0).collect();
+    grades.sort_unstable_by(|a, b| b.cmp(a));
+    let ideal: f64 = grades
+        .iter()
+        .take(k)
+        .enumerate()
+        .map(|(rank, &grade)| gain(grade) / (rank as f64 + 2.0).log2())
+        .sum();
+    let found = judgments
+        .iter()
+        .zip(&credited)
+        .filter(|(j, &credited)| credited && j.grade > 0)
+        .count();
+    QueryMetrics {
+        ndcg: if ideal > 0.0 { dcg / ideal } else { 0.0 },
+        reciprocal_rank,
+        recall: if grades.is_empty() { 0.0 } else { found as f64 / grades.len() as f64 },
+    }
+}
+
+fn gain(grade: u32) -> f64 {
+    2f64.powi(grade as i32) - 1.0
+}
+
+type SearchFn<'a> = Box<dyn Fn(&str, usize) -> Vec<RankedHit> + 'a>;
+
+/// A named search configuration: a query and `k` in, ranked hits out.
+pub struct Candidate<'a> {
+    pub name: String,
+    search: SearchFn<'a>,
+}
+
+impl<'a> Candidate<'a> {
+    pub fn new(name: impl Into<String>, search: impl Fn(&str, usize) -> Vec<RankedHit> + 'a) -> Self {
+        Self {
+            name: name.into(),
+            search: Box::new(search),
+        }
+    }
+
+    /// `HybridResolver` with a fixed resolver.
+    pub fn resolver(store: &'a SqliteStore, kind: ResolverKind) -> Self {
+        let name = serde_json::to_value(kind)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        Self::new(name, move |query, k| {
+            let query = ResolveQuery {
+                query: query.to_string(),
+                syntax: SearchSyntax::Simple,
+                resolver: Some(kind),
+                limit: k,
+                filters: None,
+                budget_ms: None,
+            };
+            HybridResolver::resolve(store, &query, CapabilityTier::Full)
+                .items
+                .iter()
+                .filter_map(|item| {
+                    let chunk = store.get_chunk(item.id).ok()??;
+                    Some(RankedHit { chunk_id: item.id, doc_id: chunk.doc_id })
+                })
+                .collect()
+        })
+    }
+}
+
+/// Mean metrics of one candidate over a fixture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CandidateReport {
+    pub name: String,
+    pub ndcg: f64,
+    pub mrr: f64,
+    pub recall: f64,
+    pub mean_latency_ms: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    pub k: usize,
+    pub queries: usize,
+    pub candidates: Vec<CandidateReport>,
+}
+
+impl BenchReport {
+    /// The comparison as a plain-text table, one candidate per row.
+    pub fn table(&self) -> String {
+        let width = self.candidates.iter().map(|c| c.name.len()).max().unwrap_or(0).max(9);
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{:<width$}  {:>8}  {:>8}  {:>9}  {:>8}",
+            "candidate",
+            format!("nDCG@{}", self.k),
+            format!("MRR@{}", self.k),
+            format!("recall@{}", self.k),
+            "ms/query",
+        );
+        for c in &self.candidates {
+            let _ = writeln!(
+                out,
+                "{:<width$}  {:>8.3}  {:>8.3}  {:>9.3}  {:>8.1}",
+                c.name, c.ndcg, c.mrr, c.recall, c.mean_latency_ms
+            );
+        }
+        let _ = writeln!(out, "{} queries", self.queries);
+        out
+    }
+}
+
+/// Run every query of `fixture` through each candidate and average the
+/// metrics at `k`. `keys` maps fixture document keys to their ids, as
+/// returned by `populate`.
+pub fn run(fixture: &RelevanceFixture, keys: &HashMap<String, i64>, candidates: &[Candidate<'_>], k: usize) -> BenchReport {
+    let judgments: Vec<Vec<Judgment>> = fixture
+        .queries
+        .iter()
+        .map(|q| {
+            q.relevant
+                .iter()
+                .map(|j| Judgment {
+                    doc_id: j.doc_id.or_else(|| j.key.as_ref().and_then(|key| keys.get(key).copied())),
+                    ..j.clone()
+                })
+                .collect()
+        })
+        .collect();
+    let queries = fixture.queries.len().max(1) as f64;
+
+    let candidates = candidates
+        .iter()
+        .map(|candidate| {
+            let mut total = QueryMetrics::default();
+            let mut elapsed_ms = 0.0;
+            for (query, judged) in fixture.queries.iter().zip(&judgments) {
+                let start = Instant::now();
+                let hits = (candidate.search)(&query.query, k);
+                elapsed_ms += start.elapsed().as_secs_f64() * 1000.0;
+                let metrics = evaluate(&hits, judged, k);
+                total.ndcg += metrics.ndcg;
+                total.reciprocal_rank += metrics.reciprocal_rank;
+                total.recall += metrics.recall;
+            }
+            CandidateReport {
+                name: candidate.name.clone(),
+                ndcg: total.ndcg / queries,
+                mrr: total.reciprocal_rank / queries,
+                recall: total.recall / queries,
+                mean_latency_ms: elapsed_ms / queries,
+            }
+        })
+        .collect();
+    BenchReport {
+        k,
+        queries: fixture.queries.len(),
+        candidates,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(chunk_id: i64, doc_id: i64) -> RankedHit {
+        RankedHit { chunk_id, doc_id }
+    }
+
+    fn doc(doc_id: i64, grade: u32) -> Judgment {
+        Judgment { key: None, doc_id: Some(doc_id), chunk_id: None, grade }
+    }
+
+    #[test]
+    fn test_metrics_match_hand_computed_values() {
+        // Relevant: doc 1 (grade 2) and doc 2 (grade 1), ranked 2nd and 3rd
+        let judgments = [doc(1, 2), doc(2, 1)];
+        let hits = [hit(10, 9), hit(11, 1), hit(12, 2)];
+        let m = evaluate(&hits, &judgments, 10);
+        // DCG = 3/log2(3) + 1/log2(4); IDCG = 3/log2(2) + 1/log2(3)
+        let dcg = 3.0 / 3f64.log2() + 0.5;
+        let ideal = 3.0 + 1.0 / 3f64.log2();
+        assert!((m.ndcg - dcg / ideal).abs() < 1e-12, "{}", m.ndcg);
+        assert_eq!(m.reciprocal_rank, 0.5);
+        assert_eq!(m.recall, 1.0);
+
+        // Only the top hit counts at k = 1, and it is irrelevant
+        assert_eq!(evaluate(&hits, &judgments, 1), QueryMetrics::default());
+        // At k = 2 half of the judgments are found
+        assert_eq!(evaluate(&hits, &judgments, 2).recall, 0.5);
+
+        // The ideal ranking scores 1.0
+        let m = evaluate(&[hit(11, 1), hit(12, 2)], &judgments, 10);
+        assert!((m.ndcg - 1.0).abs() < 1e-12);
+        assert_eq!(m.reciprocal_rank, 1.0);
+    }
+
+    #[test]
+    fn test_documents_are_credited_once_and_chunks_directly() {
+        // Two chunks of doc 1: the second adds nothing
+        let m = evaluate(&[hit(10, 1), hit(11, 1)], &[doc(1, 1)], 10);
+        assert_eq!(m.ndcg, 1.0);
+        let chunk = Judgment { key: None, doc_id: None, chunk_id: Some(11), grade: 1 };
+        let m = evaluate(&[hit(10, 1), hit(11, 1)], &[chunk], 10);
+        assert_eq!(m.reciprocal_rank, 0.5);
+        // No judgments, or only grade 0, scores 0 rather than NaN
+        assert_eq!(evaluate(&[hit(10, 1)], &[], 10), QueryMetrics::default());
+        assert_eq!(evaluate(&[hit(10, 1)], &[doc(1, 0)], 10), QueryMetrics::default());
+    }
+
+    #[test]
+    fn test_fixture_validation() {
+        assert!(RelevanceFixture::from_json(r#"{"queries": []}"#).is_err());
+        let unknown = r#"{"queries": [{"query": "q", "relevant": [{"key": "missing"}]}]}"#;
+        assert!(matches!(RelevanceFixture::from_json(unknown), Err(Error::Config(_))));
+        let ambiguous = r#"{"queries": [{"query": "q", "relevant": [{"docId": 1, "chunkId": 2}]}]}"#;
+        assert!(RelevanceFixture::from_json(ambiguous).is_err());
+        let fixture = RelevanceFixture::from_json(r#"{"queries": [{"query": "q", "relevant": [{"docId": 4}]}]}"#).unwrap();
+        assert_eq!(fixture.queries[0].relevant[0].grade, 1);
+    }
+
+    #[test]
+    fn test_builtin_fixture_runs_against_keyword_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open(dir.path(), 384).unwrap();
+        let fixture = RelevanceFixture::builtin();
+        let keys = fixture.populate(&store).unwrap();
+        assert_eq!(keys.len(), fixture.documents.len());
+
+        let candidates = [
+            Candidate::resolver(&store, ResolverKind::Keyword),
+            Candidate::new("nothing", |_, _| Vec::new()),
+        ];
+        let report = run(&fixture, &keys, &candidates, 5);
+        assert_eq!(report.queries, 6);
+        let keyword = &report.candidates[0];
+        assert_eq!(keyword.name, "keyword");
+        assert!(keyword.mrr > 0.8 && keyword.recall > 0.8, "{:?}", keyword);
+        assert_eq!((report.candidates[1].ndcg, report.candidates[1].mrr), (0.0, 0.0));
+
+        let table = report.table();
+        assert!(table.starts_with("candidate"));
+        assert!(table.contains("nDCG@5") && table.contains("nothing"));
+    }
+}
@@ -1,7 +1,13 @@
 //! Hybrid resolver — BM25 + vector search with RRF fusion.
 
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
 use mindsage_core::CapabilityTier;
-use mindsage_store::SqliteStore;
+use mindsage_store::{
+    post_process, timed_stage, Degradation, QuerySample, SearchDiagnostics, SearchHit, SearchStage, SqliteStore,
+};
+use crate::query::read_query;
 use crate::types::*;
 
 /// Hybrid resolver combining BM25 and vector search.
@@ -15,13 +21,43 @@ impl HybridResolver {
         tier: CapabilityTier,
     ) -> ResolveResult {
         let resolver_kind = query.resolver.unwrap_or_else(|| Self::select_resolver(tier));
+        let start = Instant::now();
+        let mut diagnostics = SearchDiagnostics {
+            budget_ms: query.budget_ms,
+            ..Default::default()
+        };
 
-        match resolver_kind {
-            ResolverKind::Keyword => Self::keyword_resolve(store, query),
-            ResolverKind::Entity => Self::entity_resolve(store, query),
+        let mut result = match resolver_kind {
+            ResolverKind::Keyword => Self::keyword_resolve(store, query, &mut diagnostics),
+            ResolverKind::Entity => Self::entity_resolve(store, query, start, &mut diagnostics),
             // Vector, Hybrid, Timeline, Answer all use BM25 for now (vector needs embeddings)
-            _ => Self::keyword_resolve(store, query),
+            _ => Self::keyword_resolve(store, query, &mut diagnostics),
+        };
+
+        let elapsed = start.elapsed();
+        diagnostics.elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        diagnostics.over_budget = query
+            .budget_ms
+            .is_some_and(|ms| elapsed > Duration::from_millis(ms));
+
+        let mut sample_diagnostics = serde_json::to_value(&diagnostics).unwrap_or_default();
+        if let Some(object) = sample_diagnostics.as_object_mut() {
+            object.insert("limit".to_string(), query.limit.into());
+        }
+        let sample = QuerySample {
+            query: query.query.clone(),
+            search_type: format!("resolve_{:?}", result.resolver_used).to_lowercase(),
+            result_count: result.items.len(),
+            top_score: result.items.iter().map(|item| item.score).reduce(f64::max).map(|s| s as f32),
+            latency_ms: diagnostics.elapsed_ms,
+            diagnostics: Some(sample_diagnostics),
+        };
+        if let Err(e) = store.record_search(sample) {
+            tracing::debug!("Failed to record query stats: {}", e);
         }
+
+        result.diagnostics = Some(diagnostics);
+        result
     }
 
     /// Select the best resolver for the given capability tier.
@@ -35,60 +71,79 @@ impl HybridResolver {
     }
 
     /// BM25-only keyword search.
-    fn keyword_resolve(store: &SqliteStore, query: &ResolveQuery) -> ResolveResult {
-        let results = store.bm25_search(&query.query, 1, query.limit).unwrap_or_default();
-        let items: Vec<ResolvedItem> = results
-            .into_iter()
-            .map(|r| ResolvedItem {
-                id: r.chunk_id,
-                text: r.text,
-                score: r.score,
-                source: String::new(),
-                metadata: r.metadata,
-                passage: None,
-            })
-            .collect();
-
-        let total = items.len();
-        ResolveResult {
-            items,
-            resolver_used: ResolverKind::Keyword,
-            total_found: total,
-            answer: None,
-        }
+    fn keyword_resolve(
+        store: &SqliteStore,
+        query: &ResolveQuery,
+        diagnostics: &mut SearchDiagnostics,
+    ) -> ResolveResult {
+        resolved(Self::bm25_hits(store, query, diagnostics), ResolverKind::Keyword)
     }
 
-    /// Entity-focused search — boost results with matching entities.
-    fn entity_resolve(store: &SqliteStore, query: &ResolveQuery) -> ResolveResult {
-        let mut result = Self::keyword_resolve(store, query);
-        result.resolver_used = ResolverKind::Entity;
+    fn bm25_hits(store: &SqliteStore, query: &ResolveQuery, diagnostics: &mut SearchDiagnostics) -> Vec<SearchHit> {
+        let parsed = read_query(&query.query, query.syntax);
+        diagnostics.warnings.extend(parsed.warning.clone());
+        timed_stage(SearchStage::Bm25, diagnostics, || {
+            store
+                .bm25_search_filtered(&parsed.text, Some(1), query.limit, None, parsed.filters())
+                .unwrap_or_default()
+        })
+    }
 
-        // Boost items whose text contains query terms
-        let query_lower = query.query.to_lowercase();
-        let terms: Vec<&str> = query_lower.split_whitespace().collect();
+    /// Entity-focused search — the store's search post-processing (query
+    /// term boost, per-document cap), as for the search routes and chat.
+    /// It is skipped once the BM25 stage has used up the budget.
+    fn entity_resolve(
+        store: &SqliteStore,
+        query: &ResolveQuery,
+        start: Instant,
+        diagnostics: &mut SearchDiagnostics,
+    ) -> ResolveResult {
+        let hits = Self::bm25_hits(store, query, diagnostics);
 
-        for item in &mut result.items {
-            let text_lower = item.text.to_lowercase();
-            let match_count = terms.iter().filter(|t| text_lower.contains(*t)).count();
-            if match_count > 0 {
-                item.score += 0.15 * (match_count as f64 / terms.len() as f64);
-            }
+        if query
+            .budget_ms
+            .is_some_and(|ms| start.elapsed() >= Duration::from_millis(ms))
+        {
+            diagnostics.degradations.push(Degradation::EntityBoostSkipped);
+            return resolved(hits, ResolverKind::Entity);
         }
 
-        result.items.sort_by(|a, b| {
-            b.score
-                .partial_cmp(&a.score)
-                .unwrap_or(std::cmp::Ordering::Equal)
+        let hits = timed_stage(SearchStage::EntityBoost, diagnostics, || {
+            let text = read_query(&query.query, query.syntax).text;
+            post_process(hits, &text, &store.search_post_processing(), &HashMap::new(), query.limit)
         });
+        resolved(hits, ResolverKind::Entity)
+    }
+}
 
-        result
+/// A result of `resolver_used` with `hits` as its items, in order.
+fn resolved(hits: Vec<SearchHit>, resolver_used: ResolverKind) -> ResolveResult {
+    let items: Vec<ResolvedItem> = hits
+        .into_iter()
+        .map(|r| ResolvedItem {
+            id: r.chunk_id,
+            text: r.text,
+            score: r.score,
+            source: String::new(),
+            metadata: r.metadata,
+            passage: None,
+        })
+        .collect();
+
+    let total = items.len();
+    ResolveResult {
+        items,
+        resolver_used,
+        total_found: total,
+        answer: None,
+        diagnostics: None,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mindsage_store::{AddDocumentOptions, SqliteStore};
+    use mindsage_store::{AddDocumentOptions, SearchSyntax, SqliteStore};
 
     fn test_store() -> (SqliteStore, tempfile::TempDir) {
         let dir = tempfile::tempdir().unwrap();
@@ -101,9 +156,11 @@ mod tests {
         let (store, _dir) = test_store();
         let query = ResolveQuery {
             query: "test".into(),
+            syntax: Default::default(),
             resolver: Some(ResolverKind::Keyword),
             limit: 10,
             filters: None,
+            budget_ms: None,
         };
         let result = HybridResolver::resolve(&store, &query, CapabilityTier::Base);
         assert_eq!(result.items.len(), 0);
@@ -129,15 +186,81 @@ mod tests {
 
         let query = ResolveQuery {
             query: "Rust programming".into(),
+            syntax: Default::default(),
             resolver: Some(ResolverKind::Keyword),
             limit: 10,
             filters: None,
+            budget_ms: None,
         };
         let result = HybridResolver::resolve(&store, &query, CapabilityTier::Base);
         assert!(result.total_found > 0);
         assert!(result.items[0].text.contains("Rust"));
     }
 
+    #[test]
+    fn test_resolve_records_query_stats() {
+        let (store, _dir) = test_store();
+        add_searchable_doc(&store, "Rust is a systems programming language focused on safety");
+        store.set_query_stats_config(mindsage_core::QueryStatsConfig {
+            mode: mindsage_core::QueryStatsMode::Plain,
+            ..Default::default()
+        });
+
+        let query = ResolveQuery {
+            query: "Rust".into(),
+            syntax: Default::default(),
+            resolver: Some(ResolverKind::Keyword),
+            limit: 10,
+            filters: None,
+            budget_ms: None,
+        };
+        HybridResolver::resolve(&store, &query, CapabilityTier::Base);
+        let stats = store.query_stats(0, 10).unwrap();
+        assert_eq!(stats.by_search_type.get("resolve_keyword"), Some(&1));
+        assert_eq!((stats.top_queries[0].query.as_str(), stats.zero_results), ("rust", 0));
+    }
+
+    #[test]
+    fn test_keyword_resolve_with_query_syntax() {
+        let (store, _dir) = test_store();
+        let add = |text: &str, source: &str, topics: &[&str]| {
+            let metadata = serde_json::json!({ "source": source, "topics": topics });
+            let doc_id = store
+                .add_document(text, AddDocumentOptions { metadata: Some(metadata), ..Default::default() })
+                .unwrap();
+            store.add_chunk(doc_id, text, 0, 1, None, None, None, None, None, None).unwrap();
+        };
+        add("Final quarterly report on the budget", "notes", &["finance"]);
+        add("Draft quarterly report on the budget", "notes", &["finance"]);
+        add("Quarterly budget report from the web", "browser", &["finance"]);
+        add("Report on the quarterly garden budget", "notes", &["garden"]);
+
+        let resolve = |text: &str, syntax: SearchSyntax| {
+            let query = ResolveQuery {
+                query: text.into(),
+                syntax,
+                resolver: Some(ResolverKind::Keyword),
+                limit: 10,
+                filters: None,
+                budget_ms: None,
+            };
+            HybridResolver::resolve(&store, &query, CapabilityTier::Base)
+        };
+
+        let result = resolve(r#"source:notes topic:Finance -draft "quarterly report""#, SearchSyntax::Query);
+        let texts: Vec<&str> = result.items.iter().map(|i| i.text.as_str()).collect();
+        assert_eq!(texts, ["Final quarterly report on the budget"]);
+        assert!(result.diagnostics.unwrap().warnings.is_empty());
+
+        // Simple syntax searches the same string as words
+        assert_eq!(resolve("quarterly budget", SearchSyntax::Simple).items.len(), 4);
+
+        // A query that does not parse is searched as plain text
+        let result = resolve(r#"quarterly "budget"#, SearchSyntax::Query);
+        assert_eq!(result.items.len(), 4);
+        assert_eq!(result.diagnostics.unwrap().warnings.len(), 1);
+    }
+
     #[test]
     fn test_entity_resolve_boost() {
         let (store, _dir) = test_store();
@@ -146,9 +269,11 @@ mod tests {
 
         let query = ResolveQuery {
             query: "Rust".into(),
+            syntax: Default::default(),
             resolver: Some(ResolverKind::Entity),
             limit: 10,
             filters: None,
+            budget_ms: None,
         };
         let result = HybridResolver::resolve(&store, &query, CapabilityTier::Enhanced);
         assert_eq!(result.resolver_used, ResolverKind::Entity);
@@ -167,9 +292,11 @@ mod tests {
         // Base tier → Keyword
         let query = ResolveQuery {
             query: "test".into(),
+            syntax: Default::default(),
             resolver: None,
             limit: 10,
             filters: None,
+            budget_ms: None,
         };
         let result = HybridResolver::resolve(&store, &query, CapabilityTier::Base);
         assert_eq!(result.resolver_used, ResolverKind::Keyword);
@@ -182,11 +309,42 @@ mod tests {
 
         let query = ResolveQuery {
             query: "search".into(),
+            syntax: Default::default(),
             resolver: Some(ResolverKind::Entity),
             limit: 5,
             filters: None,
+            budget_ms: None,
         };
         let result = HybridResolver::resolve(&store, &query, CapabilityTier::Base);
         assert_eq!(result.resolver_used, ResolverKind::Entity);
     }
+
+    #[test]
+    fn test_exhausted_budget_skips_entity_boost() {
+        let (store, _dir) = test_store();
+        add_searchable_doc(&store, "Rust programming language is memory safe");
+
+        let mut query = ResolveQuery {
+            query: "Rust".into(),
+            syntax: Default::default(),
+            resolver: Some(ResolverKind::Entity),
+            limit: 10,
+            filters: None,
+            budget_ms: None,
+        };
+        let unlimited = HybridResolver::resolve(&store, &query, CapabilityTier::Enhanced);
+        let diagnostics = unlimited.diagnostics.unwrap();
+        assert!(diagnostics.degradations.is_empty());
+        assert_eq!(diagnostics.stages.len(), 2);
+        assert!(!diagnostics.over_budget);
+
+        query.budget_ms = Some(0);
+        let result = HybridResolver::resolve(&store, &query, CapabilityTier::Enhanced);
+        let diagnostics = result.diagnostics.unwrap();
+        assert_eq!(diagnostics.degradations, vec![Degradation::EntityBoostSkipped]);
+        assert_eq!(diagnostics.stages.len(), 1);
+        // BM25 results are still returned, without the boost
+        assert_eq!(result.total_found, 1);
+        assert!(result.items[0].score < unlimited.items[0].score);
+    }
 }
@@ -3,8 +3,14 @@
 //! Each resolver implements a different search strategy. The tier system
 //! selects which resolvers are available based on device capabilities.
 
+#[cfg(feature = "bench")]
+pub mod bench;
 pub mod hybrid;
+pub mod overlap;
+pub mod query;
 pub mod types;
 
 pub use hybrid::HybridResolver;
+pub use overlap::{merge_overlapping_hits, MergedHit};
+pub use query::{parse_query, read_query, try_parse_query, ParsedQuery, QueryError};
 pub use types::*;
@@ -0,0 +1,154 @@
+//! Overlap removal between hits of the same document.
+//!
+//! Chunks cut with overlap repeat the end of their predecessor, so two
+//! adjacent chunks that both rank show the same sentences twice. Run after
+//! the diversity selection, `merge_overlapping_hits` finds such pairs by
+//! their `char_start`/`char_end`: a hit inside a better-ranked one is
+//! merged into it, and text a lower-ranked hit shares with a better one is
+//! cut from the lower-ranked hit. Hits without offsets are left alone.
+
+use mindsage_core::text::shared_overlap;
+use mindsage_store::SearchHit;
+
+/// A hit after overlap removal, with the hits merged into it.
+#[derive(Debug, Clone)]
+pub struct MergedHit {
+    pub hit: SearchHit,
+    /// Chunk ids of hits contained in this one, in rank order.
+    pub merged_from: Vec<i64>,
+}
+
+/// Remove overlap between `hits` of the same document, keeping rank order.
+///
+/// A hit whose text lies within another hit's text and range is merged
+/// into the other: the containing hit stays, at the better of the two
+/// ranks and scores. Otherwise, when the ranges of two hits overlap and
+/// one's text ends with the start of the other's, the shared text is cut
+/// from the lower-ranked hit and its range shrunk to match.
+pub fn merge_overlapping_hits(hits: Vec<SearchHit>) -> Vec<MergedHit> {
+    let mut kept: Vec<MergedHit> = Vec::with_capacity(hits.len());
+    'hits: for mut hit in hits {
+        for slot in kept.iter_mut() {
+            let better = &slot.hit;
+            let (Some((start, end)), Some((b_start, b_end))) = (range(&hit), range(better)) else {
+                continue;
+            };
+            if better.doc_id != hit.doc_id || start >= b_end || b_start >= end {
+                continue;
+            }
+
+            if b_start <= start && end <= b_end && better.text.contains(hit.text.trim()) {
+                slot.merged_from.push(hit.chunk_id);
+                continue 'hits;
+            }
+            if start <= b_start && b_end <= end && hit.text.contains(better.text.trim()) {
+                // The lower-ranked hit holds the better one: it takes the
+                // better one's place and score
+                let better = std::mem::replace(slot, MergedHit { hit, merged_from: Vec::new() });
+                slot.hit.score = slot.hit.score.max(better.hit.score);
+                slot.merged_from.push(better.hit.chunk_id);
+                slot.merged_from.extend(better.merged_from);
+                continue 'hits;
+            }
+
+            if start > b_start {
+                // Starts inside the better hit: cut the repeated beginning
+                let shared = shared_overlap(&better.text, &hit.text);
+                if shared > 0 {
+                    let rest = &hit.text[shared..];
+                    let cut = hit.text.len() - rest.trim_start().len();
+                    hit.text = rest.trim_start().to_string();
+                    hit.char_start = Some(start + cut as i32);
+                }
+            } else {
+                // Ends inside the better hit: cut the repeated end
+                let shared = shared_overlap(&hit.text, &better.text);
+                if shared > 0 {
+                    let rest = &hit.text[..hit.text.len() - shared];
+                    let cut = hit.text.len() - rest.trim_end().len();
+                    hit.text = rest.trim_end().to_string();
+                    hit.char_end = Some(end - cut as i32);
+                }
+            }
+        }
+        kept.push(MergedHit { hit, merged_from: Vec::new() });
+    }
+    kept
+}
+
+fn range(hit: &SearchHit) -> Option<(i32, i32)> {
+    Some((hit.char_start?, hit.char_end?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = "The harbour froze in January. Ferries stopped for a week. \
+                       The town ran out of flour. Bakers walked the ice to the mainland.";
+
+    /// A hit on `DOC[start..end]`.
+    fn hit(chunk_id: i64, doc_id: i64, start: usize, end: usize) -> SearchHit {
+        SearchHit {
+            chunk_id,
+            doc_id,
+            text: DOC[start..end].to_string(),
+            score: 1.0 / chunk_id as f64,
+            level: 1,
+            metadata: None,
+            enriched_text: None,
+            parent_chunk_id: None,
+            chunk_index: chunk_id as i32,
+            char_start: Some(start as i32),
+            char_end: Some(end as i32),
+        }
+    }
+
+    fn texts(merged: &[MergedHit]) -> Vec<&str> {
+        merged.iter().map(|m| m.hit.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_lower_ranked_hit_loses_the_shared_text() {
+        // Chunks 0..57 and 30..84 share "Ferries stopped for a week."
+        let merged = merge_overlapping_hits(vec![hit(1, 1, 30, 84), hit(2, 1, 0, 57)]);
+        assert_eq!(texts(&merged), ["Ferries stopped for a week. The town ran out of flour.", "The harbour froze in January."]);
+        assert_eq!((merged[1].hit.char_start, merged[1].hit.char_end), (Some(0), Some(29)));
+
+        let merged = merge_overlapping_hits(vec![hit(1, 1, 0, 57), hit(2, 1, 30, 84)]);
+        assert_eq!(merged[1].hit.text, "The town ran out of flour.");
+        assert_eq!((merged[1].hit.char_start, merged[1].hit.char_end), (Some(58), Some(84)));
+        assert!(merged.iter().all(|m| m.merged_from.is_empty()));
+    }
+
+    #[test]
+    fn test_contained_hits_are_merged() {
+        // A sentence inside a better-ranked chunk disappears into it
+        let merged = merge_overlapping_hits(vec![hit(1, 1, 0, 84), hit(2, 1, 30, 57), hit(3, 2, 30, 57)]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].merged_from, [2]);
+        // Another document's hit is untouched
+        assert_eq!(merged[1].hit.text, "Ferries stopped for a week.");
+
+        // A better-ranked sentence inside a lower-ranked chunk: the chunk
+        // takes its place and score
+        let merged = merge_overlapping_hits(vec![hit(2, 1, 30, 57), hit(5, 1, 0, 84)]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!((merged[0].hit.chunk_id, merged[0].hit.score), (5, 0.5));
+        assert_eq!(merged[0].merged_from, [2]);
+    }
+
+    #[test]
+    fn test_hits_without_offsets_or_shared_text_are_kept() {
+        let mut no_offsets = hit(2, 1, 30, 84);
+        no_offsets.char_start = None;
+        let merged = merge_overlapping_hits(vec![hit(1, 1, 0, 57), no_offsets]);
+        assert_eq!(merged[1].hit.text, DOC[30..84]);
+
+        // Ranges that overlap on paper but whose texts do not repeat
+        let mut edited = hit(2, 1, 30, 84);
+        edited.text = "A different text, edited since it was chunked.".to_string();
+        let merged = merge_overlapping_hits(vec![hit(1, 1, 0, 57), edited.clone()]);
+        assert_eq!(merged[1].hit.text, edited.text);
+    }
+}
@@ -0,0 +1,342 @@
+//! Query language for recall and search.
+//!
+//! `source:notes topic:finance -draft "quarterly report"` reads as: plain
+//! terms and quoted phrases are ranked by BM25 and the embedding, a quoted
+//! phrase must also appear in every hit, `-term` and `-"phrase"` exclude
+//! hits containing them, and `field:value` scopes the documents searched:
+//!
+//! | Field | Matches |
+//! |-------|---------|
+//! | `source:` | `metadata.source` |
+//! | `topic:` | one of the document's topics |
+//! | `tag:` | one of the document's tags |
+//! | `filename:` | part of `metadata.filename` |
+//! | `lang:` | `metadata.lang` |
+//! | `after:` | created on or after the date (`2024`, `2024-03`, `2024-03-05`, UTC) |
+//! | `before:` | created before the date |
+//!
+//! Values may be quoted (`filename:"q3 report"`) and are matched ignoring
+//! case; typographic quotes (`“…”`, `«…»`) count as `"`. Repeating a field
+//! gives alternatives. Unknown fields such as `http:` or `10:30` are plain
+//! text. A query that cannot be parsed is searched as plain text, with a
+//! warning.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use mindsage_store::{SearchFilters, SearchSyntax};
+
+/// A query split into ranked text and scopes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedQuery {
+    /// Plain terms and phrase words, for BM25 and the embedding.
+    pub text: String,
+    pub filters: SearchFilters,
+    /// Why the query was searched as plain text, if it was.
+    pub warning: Option<String>,
+}
+
+impl ParsedQuery {
+    /// The scopes, or `None` when the query has none.
+    pub fn filters(&self) -> Option<&SearchFilters> {
+        (!self.filters.is_empty()).then_some(&self.filters)
+    }
+}
+
+/// Why a query could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    /// A `"` without its closing quote.
+    UnclosedQuote,
+    /// `field:` without a value.
+    EmptyValue(&'static str),
+    /// `before:` or `after:` with something other than a date.
+    InvalidDate { field: &'static str, value: String },
+    /// `-field:value`; only terms and phrases can be negated.
+    NegatedField(&'static str),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::UnclosedQuote => write!(f, "unclosed quote"),
+            QueryError::EmptyValue(field) => write!(f, "{}: has no value", field),
+            QueryError::InvalidDate { field, value } => {
+                write!(f, "{}:{} is not a date (YYYY, YYYY-MM or YYYY-MM-DD)", field, value)
+            }
+            QueryError::NegatedField(field) => write!(f, "-{}: cannot be negated", field),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Read `query` with `syntax`: as plain text, or with the query language,
+/// falling back to plain text when it does not parse.
+pub fn read_query(query: &str, syntax: SearchSyntax) -> ParsedQuery {
+    match syntax {
+        SearchSyntax::Simple => ParsedQuery {
+            text: query.to_string(),
+            ..Default::default()
+        },
+        SearchSyntax::Query => parse_query(query),
+    }
+}
+
+/// Parse `query` with the query language; a query that does not parse is
+/// returned as plain text with a warning.
+pub fn parse_query(query: &str) -> ParsedQuery {
+    try_parse_query(query).unwrap_or_else(|e| {
+        tracing::debug!("Query syntax not applied to {:?}: {}", query, e);
+        ParsedQuery {
+            text: query.to_string(),
+            filters: SearchFilters::default(),
+            warning: Some(format!("Query syntax not applied ({}); searched as plain text", e)),
+        }
+    })
+}
+
+/// Parse `query` with the query language.
+pub fn try_parse_query(query: &str) -> Result<ParsedQuery, QueryError> {
+    let mut words: Vec<String> = Vec::new();
+    let mut filters = SearchFilters::default();
+    let query = normalize_quotes(query);
+    let mut chars = query.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        let negated = chars.next_if_eq(&'-').is_some();
+
+        if chars.next_if_eq(&'"').is_some() {
+            let phrase = read_quoted(&mut chars)?;
+            if phrase.trim().is_empty() {
+                continue;
+            }
+            if negated {
+                filters.excluded.push(phrase);
+            } else {
+                words.push(phrase.clone());
+                filters.phrases.push(phrase);
+            }
+            continue;
+        }
+
+        let head = read_until(&mut chars, |c| c.is_whitespace() || c == ':');
+        let field = if chars.peek() == Some(&':') { field_name(&head) } else { None };
+        let Some(field) = field else {
+            // A plain word, including unknown `name:rest` words
+            let mut word = head;
+            if chars.next_if_eq(&':').is_some() {
+                word.push(':');
+                word.push_str(&read_until(&mut chars, char::is_whitespace));
+            }
+            if word.is_empty() {
+                continue;
+            }
+            if negated {
+                filters.excluded.push(word);
+            } else {
+                words.push(word);
+            }
+            continue;
+        };
+
+        chars.next();
+        if negated {
+            return Err(QueryError::NegatedField(field));
+        }
+        let value = if chars.next_if_eq(&'"').is_some() {
+            read_quoted(&mut chars)?
+        } else {
+            read_until(&mut chars, char::is_whitespace)
+        };
+        let value = value.trim().to_string();
+        if value.is_empty() {
+            return Err(QueryError::EmptyValue(field));
+        }
+        match field {
+            "source" => filters.sources.push(value),
+            "topic" => filters.topics.push(value),
+            "tag" => filters.tags.push(value),
+            "filename" => filters.filenames.push(value),
+            "lang" => filters.langs.push(value),
+            "after" => {
+                let start = date_millis(&value).ok_or(QueryError::InvalidDate { field, value })?;
+                filters.created_after = Some(filters.created_after.map_or(start, |a| a.max(start)));
+            }
+            _ => {
+                let start = date_millis(&value).ok_or(QueryError::InvalidDate { field, value })?;
+                filters.created_before = Some(filters.created_before.map_or(start, |b| b.min(start)));
+            }
+        }
+    }
+
+    Ok(ParsedQuery {
+        text: words.join(" "),
+        filters,
+        warning: None,
+    })
+}
+
+/// The known field called `name`, ignoring case.
+fn field_name(name: &str) -> Option<&'static str> {
+    ["source", "topic", "tag", "filename", "lang", "after", "before"]
+        .into_iter()
+        .find(|field| field.eq_ignore_ascii_case(name))
+}
+
+fn read_until(chars: &mut Peekable<Chars<'_>>, stop: impl Fn(char) -> bool) -> String {
+    let mut out = String::new();
+    while let Some(c) = chars.next_if(|&c| !stop(c)) {
+        out.push(c);
+    }
+    out
+}
+
+/// The rest of a quoted string, after its opening quote.
+/// Typographic double quotes (as phones and word processors type them)
+/// replaced by `"`, so `“quarterly report”` is a phrase too.
+fn normalize_quotes(query: &str) -> String {
+    query
+        .chars()
+        .map(|c| match c {
+            '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' | '\u{00ab}' | '\u{00bb}' | '\u{ff02}' => '"',
+            c => c,
+        })
+        .collect()
+}
+
+fn read_quoted(chars: &mut Peekable<Chars<'_>>) -> Result<String, QueryError> {
+    let text = read_until(chars, |c| c == '"');
+    chars.next().ok_or(QueryError::UnclosedQuote)?;
+    Ok(text)
+}
+
+/// Start of `YYYY`, `YYYY-MM` or `YYYY-MM-DD` in epoch ms (UTC).
+fn date_millis(value: &str) -> Option<i64> {
+    let parts: Vec<&str> = value.split('-').collect();
+    if parts.len() > 3 || parts[0].len() != 4 || parts.iter().any(|p| p.is_empty() || !p.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    let year = parts[0].parse().ok()?;
+    let month = parts.get(1).map_or(Some(1), |m| m.parse().ok())?;
+    let day = parts.get(2).map_or(Some(1), |d| d.parse().ok())?;
+    let date = chrono::NaiveDate::from_ymd_opt(year, month, day)?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> ParsedQuery {
+        try_parse_query(query).unwrap()
+    }
+
+    #[test]
+    fn test_fields_negation_and_phrases() {
+        let parsed = parse(r#"source:notes topic:finance -draft "quarterly report""#);
+        assert_eq!(parsed.text, "quarterly report");
+        assert_eq!(parsed.filters.sources, ["notes"]);
+        assert_eq!(parsed.filters.topics, ["finance"]);
+        assert_eq!(parsed.filters.excluded, ["draft"]);
+        assert_eq!(parsed.filters.phrases, ["quarterly report"]);
+        assert_eq!(parsed.warning, None);
+    }
+
+    #[test]
+    fn test_plain_text_is_kept_in_order() {
+        let parsed = parse("  budget   review\tplan ");
+        assert_eq!(parsed.text, "budget review plan");
+        assert!(parsed.filters.is_empty());
+        assert_eq!(parsed.filters(), None);
+        assert_eq!(parse(""), ParsedQuery::default());
+    }
+
+    #[test]
+    fn test_unknown_fields_are_plain_text() {
+        let parsed = parse("meet at 10:30 see https://example.com/a author:sam");
+        assert_eq!(parsed.text, "meet at 10:30 see https://example.com/a author:sam");
+        assert!(parsed.filters.is_empty());
+        let parsed = parse("-author:sam");
+        assert_eq!(parsed.filters.excluded, ["author:sam"]);
+    }
+
+    #[test]
+    fn test_field_names_ignore_case_and_values_may_be_quoted() {
+        let parsed = parse(r#"Source:Notes FILENAME:"q3 report.pdf" lang:pt"#);
+        assert_eq!(parsed.filters.sources, ["Notes"]);
+        assert_eq!(parsed.filters.filenames, ["q3 report.pdf"]);
+        assert_eq!(parsed.filters.langs, ["pt"]);
+        assert_eq!(parsed.text, "");
+    }
+
+    #[test]
+    fn test_repeated_fields_are_alternatives() {
+        let parsed = parse("source:notes source:browser rust");
+        assert_eq!(parsed.filters.sources, ["notes", "browser"]);
+        assert_eq!(parsed.text, "rust");
+        let parsed = parse("boiler TAG:receipts tag:2024");
+        assert_eq!(parsed.filters.tags, ["receipts", "2024"]);
+        assert_eq!(parsed.text, "boiler");
+    }
+
+    #[test]
+    fn test_negated_phrases_and_hyphenated_words() {
+        let parsed = parse(r#"e-mail -"out of office" -"" - x"#);
+        assert_eq!(parsed.text, "e-mail x");
+        assert_eq!(parsed.filters.excluded, ["out of office"]);
+        assert!(parsed.filters.phrases.is_empty());
+    }
+
+    #[test]
+    fn test_typographic_quotes_are_phrases() {
+        let parsed = parse("\u{201c}quarterly report\u{201d} -\u{00ab}draft copy\u{00bb}");
+        assert_eq!(parsed.filters.phrases, ["quarterly report"]);
+        assert_eq!(parsed.filters.excluded, ["draft copy"]);
+        assert_eq!(parse("filename:\u{201e}q3 report\u{201c}").filters.filenames, ["q3 report"]);
+    }
+
+    #[test]
+    fn test_dates() {
+        let parsed = parse("after:2024-03 before:2024-03-05");
+        assert_eq!(parsed.filters.created_after, Some(1_709_251_200_000));
+        assert_eq!(parsed.filters.created_before, Some(1_709_596_800_000));
+        assert_eq!(parse("after:2024").filters.created_after, Some(1_704_067_200_000));
+        // Repeated bounds keep the narrowest range
+        let parsed = parse("after:2023 after:2024 before:2025 before:2024-06");
+        assert_eq!(parsed.filters.created_after, Some(1_704_067_200_000));
+        assert_eq!(parsed.filters.created_before, date_millis("2024-06"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(try_parse_query(r#"report "quarterly"#), Err(QueryError::UnclosedQuote));
+        assert_eq!(try_parse_query(r#"filename:"q3"#), Err(QueryError::UnclosedQuote));
+        assert_eq!(try_parse_query("source: notes"), Err(QueryError::EmptyValue("source")));
+        assert_eq!(try_parse_query("-topic:finance"), Err(QueryError::NegatedField("topic")));
+        for bad in ["after:yesterday", "before:2024-13", "after:2024-02-30", "after:24", "before:2024-1-"] {
+            assert!(matches!(try_parse_query(bad), Err(QueryError::InvalidDate { .. })), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_errors_degrade_to_plain_text() {
+        let parsed = parse_query(r#"source:notes "unclosed"#);
+        assert_eq!(parsed.text, r#"source:notes "unclosed"#);
+        assert!(parsed.filters.is_empty());
+        assert!(parsed.warning.unwrap().contains("unclosed quote"));
+    }
+
+    #[test]
+    fn test_simple_syntax_is_plain_text() {
+        let parsed = read_query("source:notes -draft", SearchSyntax::Simple);
+        assert_eq!(parsed.text, "source:notes -draft");
+        assert_eq!(parsed.filters(), None);
+        assert_eq!(read_query("source:notes -draft", SearchSyntax::Query).filters.sources, ["notes"]);
+    }
+}
@@ -1,5 +1,6 @@
 //! Resolver types.
 
+use mindsage_store::{SearchDiagnostics, SearchSyntax};
 use serde::{Deserialize, Serialize};
 
 /// Available resolver strategies.
@@ -24,12 +25,19 @@ pub enum ResolverKind {
 #[derive(Debug, Clone, Deserialize)]
 pub struct ResolveQuery {
     pub query: String,
+    /// `query` syntax: plain text (default) or the query language.
+    #[serde(default)]
+    pub syntax: SearchSyntax,
     #[serde(default)]
     pub resolver: Option<ResolverKind>,
     #[serde(default = "default_limit")]
     pub limit: usize,
     #[serde(default)]
     pub filters: Option<ResolveFilters>,
+    /// Latency budget in milliseconds; stages that would overrun it are
+    /// skipped. `None` means unlimited.
+    #[serde(default)]
+    pub budget_ms: Option<u64>,
 }
 
 fn default_limit() -> usize {
@@ -66,4 +74,7 @@ pub struct ResolveResult {
     pub total_found: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub answer: Option<String>,
+    /// Per-stage timings and any degradations applied to meet the budget.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<SearchDiagnostics>,
 }
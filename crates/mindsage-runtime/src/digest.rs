@@ -0,0 +1,557 @@
+//! Digests — what was captured over a period: new documents by source,
+//! the topics and entities they brought, and a few representative chunks
+//! per topic. The caller may add a narrative written by an LLM from
+//! `Digest::narrative_prompt`; the digest is then stored as a document with
+//! `source = "digest"` so it can be searched like anything else.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{TimeZone, Utc};
+use mindsage_core::Result;
+use mindsage_store::{DocumentSelector, EntityCount, SqliteStore};
+use serde::{Deserialize, Serialize};
+
+/// `metadata.source` of stored digests. Digests never count themselves.
+pub const DIGEST_SOURCE: &str = "digest";
+
+/// Days covered when no period is given.
+pub const DEFAULT_DIGEST_DAYS: i64 = 7;
+
+/// Token budget for the narrative.
+pub const NARRATIVE_MAX_TOKENS: usize = 400;
+
+/// Characters of prompt sent for the narrative; excerpts that do not fit
+/// are left out.
+pub const NARRATIVE_PROMPT_CHARS: usize = 6000;
+
+const TOP_TOPICS: usize = 10;
+const TOP_ENTITIES: usize = 15;
+/// Topics given representative chunks, and chunks per topic.
+const REPRESENTED_TOPICS: usize = 5;
+const REPRESENTATIVES_PER_TOPIC: usize = 2;
+/// Characters of a representative chunk kept in the digest.
+const EXCERPT_CHARS: usize = 400;
+
+const DAY_MS: i64 = 86_400_000;
+
+/// A window of document creation times, `[start, end)` in epoch ms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestPeriod {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl DigestPeriod {
+    /// The `days` days before `end`.
+    pub fn days_before(end: i64, days: i64) -> Self {
+        Self {
+            start: end - days * DAY_MS,
+            end,
+        }
+    }
+
+    /// Whole days covered, rounded up.
+    pub fn days(&self) -> i64 {
+        (self.end - self.start + DAY_MS - 1) / DAY_MS
+    }
+
+    /// "2026-10-10 to 2026-10-16" (UTC, end inclusive).
+    pub fn label(&self) -> String {
+        let day = |ms: i64| {
+            Utc.timestamp_millis_opt(ms)
+                .single()
+                .map(|t| t.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| ms.to_string())
+        };
+        format!("{} to {}", day(self.start), day((self.end - 1).max(self.start)))
+    }
+}
+
+/// Documents from one source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceCount {
+    pub source: String,
+    pub documents: usize,
+}
+
+/// How a representative chunk was chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Selection {
+    /// Nearest to the mean embedding of the topic's chunks.
+    Centrality,
+    /// Longest chunk; used when the chunks have no embeddings.
+    Length,
+}
+
+/// A chunk that stands for its topic.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Representative {
+    pub chunk_id: i64,
+    pub doc_id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub excerpt: String,
+    pub selected_by: Selection,
+}
+
+/// A topic of the period's documents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestTopic {
+    pub topic: String,
+    /// Documents of the period carrying the topic.
+    pub documents: usize,
+    /// No document before the period carries it.
+    pub new: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub representatives: Vec<Representative>,
+}
+
+/// Summary of the documents created in `period`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Digest {
+    pub period: DigestPeriod,
+    pub documents: usize,
+    /// Most documents first.
+    pub sources: Vec<SourceCount>,
+    /// Most documents first; new topics first among equals.
+    pub topics: Vec<DigestTopic>,
+    /// Most chunks first.
+    pub entities: Vec<EntityCount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub narrative: Option<String>,
+    pub generated_at: i64,
+    /// The stored digest document, once stored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc_id: Option<i64>,
+}
+
+impl Digest {
+    /// Chunks shown in the digest, in topic order.
+    pub fn representative_chunk_ids(&self) -> Vec<i64> {
+        self.topics
+            .iter()
+            .flat_map(|t| t.representatives.iter().map(|r| r.chunk_id))
+            .collect()
+    }
+
+    /// Prompt asking for a short narrative of the period, at most
+    /// `NARRATIVE_PROMPT_CHARS` long. Excerpts are added in topic order
+    /// until the next one would not fit.
+    pub fn narrative_prompt(&self) -> String {
+        let mut prompt = format!(
+            "Write a short narrative summary (one or two paragraphs) of what was captured in a personal \
+             knowledge base from {}. Mention the main themes and anything new. Use only the facts below.\n\n",
+            self.period.label()
+        );
+        prompt.push_str(&self.outline());
+        prompt.push_str("\nExcerpts:\n");
+        for topic in &self.topics {
+            for rep in &topic.representatives {
+                let excerpt = format!("[{}] {}\n", topic.topic, rep.excerpt);
+                if prompt.len() + excerpt.len() > NARRATIVE_PROMPT_CHARS {
+                    return prompt;
+                }
+                prompt.push_str(&excerpt);
+            }
+        }
+        prompt
+    }
+
+    /// The digest as the text of its document.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Digest for {}\n\n", self.period.label());
+        if let Some(narrative) = &self.narrative {
+            text.push_str(narrative.trim());
+            text.push_str("\n\n");
+        }
+        text.push_str(&self.outline());
+        for topic in self.topics.iter().filter(|t| !t.representatives.is_empty()) {
+            text.push_str(&format!("\nAbout {}:\n", topic.topic));
+            for rep in &topic.representatives {
+                text.push_str(&format!("- {}\n", rep.excerpt));
+            }
+        }
+        text
+    }
+
+    /// Counts, topics and entities, one section per line.
+    fn outline(&self) -> String {
+        let sources: Vec<String> = self.sources.iter().map(|s| format!("{} {}", s.documents, s.source)).collect();
+        let mut text = format!("New documents: {} ({})\n", self.documents, sources.join(", "));
+        if !self.topics.is_empty() {
+            let topics: Vec<String> = self
+                .topics
+                .iter()
+                .map(|t| format!("{} ({}{})", t.topic, t.documents, if t.new { ", new" } else { "" }))
+                .collect();
+            text.push_str(&format!("Topics: {}\n", topics.join(", ")));
+        }
+        if !self.entities.is_empty() {
+            let entities: Vec<&str> = self.entities.iter().map(|e| e.entity.as_str()).collect();
+            text.push_str(&format!("Entities: {}\n", entities.join(", ")));
+        }
+        text
+    }
+}
+
+/// Aggregate the documents created in `period`, leaving out digests.
+pub fn generate_digest(store: &SqliteStore, period: DigestPeriod, now: i64) -> Result<Digest> {
+    let ids = store.select_documents(&DocumentSelector {
+        created_after: Some(period.start),
+        created_before: Some(period.end),
+        ..Default::default()
+    })?;
+
+    let mut sources: BTreeMap<String, usize> = BTreeMap::new();
+    let mut topic_docs: BTreeMap<String, Vec<i64>> = BTreeMap::new();
+    let mut titles: HashMap<i64, String> = HashMap::new();
+    let mut counted = Vec::new();
+    for id in ids {
+        let Some(doc) = store.get_document(id)? else {
+            continue;
+        };
+        let metadata = doc.metadata.unwrap_or_default();
+        let source = metadata.get("source").and_then(|s| s.as_str()).unwrap_or("unknown");
+        if source == DIGEST_SOURCE {
+            continue;
+        }
+        counted.push(id);
+        *sources.entry(source.to_string()).or_insert(0) += 1;
+        if let Some(title) = metadata
+            .get("title")
+            .or_else(|| metadata.get("filename"))
+            .and_then(|t| t.as_str())
+        {
+            titles.insert(id, title.to_string());
+        }
+        let topics = metadata.get("topics").and_then(|t| t.as_array());
+        for topic in topics.into_iter().flatten().filter_map(|t| t.as_str()) {
+            let docs = topic_docs.entry(topic.to_string()).or_default();
+            if !docs.contains(&id) {
+                docs.push(id);
+            }
+        }
+    }
+
+    // Chunks of every counted document, for entities and representatives
+    let mut chunks = HashMap::new();
+    let mut entity_counts: HashMap<String, i64> = HashMap::new();
+    for &doc_id in &counted {
+        let doc_chunks = store.get_chunks_for_document(doc_id)?;
+        for chunk in &doc_chunks {
+            let mut seen: Vec<&str> = Vec::new();
+            for entity in chunk_entities(chunk.enriched_text.as_deref()) {
+                if !seen.contains(&entity) {
+                    seen.push(entity);
+                    *entity_counts.entry(entity.to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+        chunks.insert(doc_id, doc_chunks);
+    }
+
+    let mut topics = Vec::with_capacity(topic_docs.len());
+    for (topic, docs) in &topic_docs {
+        let first_seen = store.topic_stats(topic, 0)?.and_then(|s| s.first_created_at);
+        topics.push(DigestTopic {
+            topic: topic.clone(),
+            documents: docs.len(),
+            new: first_seen.map_or(true, |first| first >= period.start),
+            representatives: Vec::new(),
+        });
+    }
+    topics.sort_by(|a, b| {
+        b.documents
+            .cmp(&a.documents)
+            .then_with(|| b.new.cmp(&a.new))
+            .then_with(|| a.topic.cmp(&b.topic))
+    });
+    topics.truncate(TOP_TOPICS);
+
+    for topic in topics.iter_mut().take(REPRESENTED_TOPICS) {
+        let candidates: Vec<&mindsage_store::Chunk> = topic_docs[&topic.topic]
+            .iter()
+            .filter_map(|id| chunks.get(id))
+            .flatten()
+            .filter(|c| c.level == 1 && !c.text.trim().is_empty())
+            .collect();
+        topic.representatives = representatives(store, &candidates, &titles)?;
+    }
+
+    let mut entities: Vec<EntityCount> = entity_counts
+        .into_iter()
+        .map(|(entity, count)| EntityCount { entity, count })
+        .collect();
+    entities.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.entity.cmp(&b.entity)));
+    entities.truncate(TOP_ENTITIES);
+
+    let mut sources: Vec<SourceCount> = sources
+        .into_iter()
+        .map(|(source, documents)| SourceCount { source, documents })
+        .collect();
+    sources.sort_by(|a, b| b.documents.cmp(&a.documents).then_with(|| a.source.cmp(&b.source)));
+
+    Ok(Digest {
+        period,
+        documents: counted.len(),
+        sources,
+        topics,
+        entities,
+        narrative: None,
+        generated_at: now,
+        doc_id: None,
+    })
+}
+
+/// Stored digests, newest first.
+pub fn list_digests(store: &SqliteStore, limit: usize) -> Result<Vec<Digest>> {
+    let ids = store.select_documents(&DocumentSelector {
+        source: Some(DIGEST_SOURCE.to_string()),
+        ..Default::default()
+    })?;
+    let mut digests = Vec::new();
+    for id in ids.into_iter().rev() {
+        if digests.len() == limit {
+            break;
+        }
+        let Some(doc) = store.get_document(id)? else {
+            continue;
+        };
+        let stored = doc.metadata.and_then(|m| m.get("digest").cloned());
+        if let Some(mut digest) = stored.and_then(|d| serde_json::from_value::<Digest>(d).ok()) {
+            digest.doc_id = Some(id);
+            digests.push(digest);
+        }
+    }
+    Ok(digests)
+}
+
+/// Metadata of a stored digest: the digest itself under `digest`.
+pub(crate) fn digest_metadata(digest: &Digest) -> serde_json::Value {
+    serde_json::json!({
+        "source": DIGEST_SOURCE,
+        "title": format!("Digest for {}", digest.period.label()),
+        "digest": digest,
+    })
+}
+
+/// The words of the `entities:` section of a chunk's enriched text.
+fn chunk_entities(enriched: Option<&str>) -> impl Iterator<Item = &str> {
+    enriched
+        .and_then(|e| e.split(" | ").find_map(|part| part.strip_prefix("entities: ")))
+        .into_iter()
+        .flat_map(str::split_whitespace)
+}
+
+/// The `REPRESENTATIVES_PER_TOPIC` chunks nearest the mean embedding of
+/// `candidates`, or the longest when fewer than two have embeddings.
+fn representatives(
+    store: &SqliteStore,
+    candidates: &[&mindsage_store::Chunk],
+    titles: &HashMap<i64, String>,
+) -> Result<Vec<Representative>> {
+    let ids: Vec<i64> = candidates.iter().map(|c| c.id).collect();
+    let embeddings = store.get_chunk_embeddings(&ids)?;
+
+    let mut scored: Vec<(f32, &mindsage_store::Chunk)>;
+    let selected_by = if embeddings.len() >= 2 {
+        let dim = embeddings.values().next().map_or(0, |e| e.len());
+        let mut mean = vec![0f32; dim];
+        for embedding in embeddings.values() {
+            for (m, v) in mean.iter_mut().zip(embedding.iter()) {
+                *m += v;
+            }
+        }
+        scored = candidates
+            .iter()
+            .filter_map(|c| embeddings.get(&c.id).map(|e| (cosine(&mean, e.as_slice().unwrap_or(&[])), *c)))
+            .collect();
+        Selection::Centrality
+    } else {
+        scored = candidates.iter().map(|c| (c.text.len() as f32, *c)).collect();
+        Selection::Length
+    };
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.id.cmp(&b.1.id)));
+
+    Ok(scored
+        .into_iter()
+        .take(REPRESENTATIVES_PER_TOPIC)
+        .map(|(_, chunk)| Representative {
+            chunk_id: chunk.id,
+            doc_id: chunk.doc_id,
+            title: titles.get(&chunk.doc_id).cloned(),
+            excerpt: excerpt(&chunk.text),
+            selected_by,
+        })
+        .collect())
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    dot / (norm(a) * norm(b)).max(1e-6)
+}
+
+/// `text` on one line, cut to `EXCERPT_CHARS` at a word boundary.
+fn excerpt(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= EXCERPT_CHARS {
+        return text;
+    }
+    let cut: String = text.chars().take(EXCERPT_CHARS).collect();
+    let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(head, _)| head);
+    format!("{}…", cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mindsage_store::AddDocumentOptions;
+
+    const NOW: i64 = 1_790_000_000_000;
+
+    fn test_store() -> (SqliteStore, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open(dir.path(), 384).unwrap();
+        (store, dir)
+    }
+
+    /// A document created `days_ago` with one paragraph chunk per entry of
+    /// `chunks` (text, entities).
+    fn add(store: &SqliteStore, days_ago: i64, metadata: serde_json::Value, chunks: &[(&str, &str)]) -> i64 {
+        let text: Vec<&str> = chunks.iter().map(|(t, _)| *t).collect();
+        let doc = store
+            .add_document(
+                &text.join("\n\n"),
+                AddDocumentOptions {
+                    metadata: Some(metadata),
+                    created_at: Some(NOW - days_ago * DAY_MS),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        for (i, (text, entities)) in chunks.iter().enumerate() {
+            let enriched = format!("topics: x | entities: {}", entities);
+            store
+                .add_chunk(doc, text, i as i32, 1, None, None, None, Some(enriched.as_str()), None, None)
+                .unwrap();
+        }
+        doc
+    }
+
+    fn week() -> DigestPeriod {
+        DigestPeriod::days_before(NOW, 7)
+    }
+
+    #[test]
+    fn test_aggregates_the_period() {
+        let (store, _dir) = test_store();
+        add(&store, 30, serde_json::json!({"source": "upload", "topics": ["rust"]}), &[("Old notes on Rust lifetimes.", "Rust")]);
+        add(
+            &store,
+            2,
+            serde_json::json!({"source": "browser", "topics": ["rust", "tokio"], "title": "Async Rust"}),
+            &[("Tokio runs async tasks on a work-stealing scheduler.", "Tokio Rust")],
+        );
+        add(
+            &store,
+            3,
+            serde_json::json!({"source": "browser", "topics": ["tokio"]}),
+            &[("Tokio channels connect tasks.", "Tokio")],
+        );
+        add(&store, 1, serde_json::json!({"source": "upload", "topics": ["gardening"]}), &[("Tomatoes need sun.", "Tomatoes")]);
+        add(&store, 1, serde_json::json!({"source": DIGEST_SOURCE, "topics": ["tokio"]}), &[("An earlier digest.", "Tokio")]);
+
+        let digest = generate_digest(&store, week(), NOW).unwrap();
+        assert_eq!(digest.documents, 3);
+        let sources: Vec<(&str, usize)> = digest.sources.iter().map(|s| (s.source.as_str(), s.documents)).collect();
+        assert_eq!(sources, vec![("browser", 2), ("upload", 1)]);
+
+        let topics: Vec<(&str, usize, bool)> =
+            digest.topics.iter().map(|t| (t.topic.as_str(), t.documents, t.new)).collect();
+        // rust was seen before the period; the digest itself is not counted
+        assert_eq!(topics, vec![("tokio", 2, true), ("gardening", 1, true), ("rust", 1, false)]);
+
+        assert_eq!(digest.entities[0].entity, "Tokio");
+        assert_eq!(digest.entities[0].count, 2);
+        assert!(digest.entities.iter().all(|e| e.entity != "Rust" || e.count == 1));
+        assert_eq!(digest.generated_at, NOW);
+    }
+
+    #[test]
+    fn test_representatives_fall_back_to_length() {
+        let (store, _dir) = test_store();
+        add(
+            &store,
+            1,
+            serde_json::json!({"source": "upload", "topics": ["tokio"], "title": "Runtime notes"}),
+            &[
+                ("Short.", "Tokio"),
+                ("The Tokio runtime schedules tasks across worker threads and parks idle ones.", "Tokio"),
+                ("A medium length note about Tokio.", "Tokio"),
+            ],
+        );
+
+        let digest = generate_digest(&store, week(), NOW).unwrap();
+        let reps = &digest.topics[0].representatives;
+        assert_eq!(reps.len(), REPRESENTATIVES_PER_TOPIC);
+        assert!(reps[0].excerpt.starts_with("The Tokio runtime"));
+        assert_eq!(reps[0].selected_by, Selection::Length);
+        assert_eq!(reps[0].title.as_deref(), Some("Runtime notes"));
+        assert_eq!(digest.representative_chunk_ids().len(), 2);
+    }
+
+    #[test]
+    fn test_representatives_nearest_the_centre() {
+        let (store, _dir) = test_store();
+        let doc = add(
+            &store,
+            1,
+            serde_json::json!({"source": "upload", "topics": ["tokio"]}),
+            &[("north", ""), ("north by east", ""), ("south", "")],
+        );
+        let chunks = store.get_chunks_for_document(doc).unwrap();
+        let vector = |values: [f32; 2]| {
+            let mut v = ndarray::Array1::<f32>::zeros(384);
+            v[0] = values[0];
+            v[1] = values[1];
+            v
+        };
+        let embeddings = [vector([1.0, 0.0]), vector([0.9, 0.1]), vector([0.0, 1.0])];
+        for (chunk, embedding) in chunks.iter().zip(embeddings) {
+            store.add_chunk_embedding(chunk.id, &embedding).unwrap();
+        }
+
+        let digest = generate_digest(&store, week(), NOW).unwrap();
+        let reps = &digest.topics[0].representatives;
+        assert_eq!(reps[0].selected_by, Selection::Centrality);
+        assert_eq!(reps[0].excerpt, "north by east");
+    }
+
+    #[test]
+    fn test_text_and_prompt() {
+        let (store, _dir) = test_store();
+        let long = "word ".repeat(2000);
+        add(&store, 1, serde_json::json!({"source": "upload", "topics": ["tokio"]}), &[(long.as_str(), "Tokio")]);
+
+        let mut digest = generate_digest(&store, week(), NOW).unwrap();
+        assert!(digest.topics[0].representatives[0].excerpt.chars().count() <= EXCERPT_CHARS + 1);
+        let prompt = digest.narrative_prompt();
+        assert!(prompt.len() <= NARRATIVE_PROMPT_CHARS);
+        assert!(prompt.contains("Topics: tokio (1, new)"));
+        assert!(prompt.contains(&week().label()));
+
+        digest.narrative = Some("A quiet week about async Rust.".into());
+        let text = digest.to_text();
+        assert!(text.starts_with(&format!("Digest for {}\n\nA quiet week", week().label())));
+        assert!(text.contains("New documents: 1 (1 upload)"));
+        assert!(text.contains("About tokio:"));
+        assert_eq!(DigestPeriod::days_before(NOW, 7).days(), 7);
+    }
+}
@@ -1,10 +1,14 @@
 //! Runtime orchestrator — coordinates SDK verbs, budget tracking, scheduling.
 //!
-//! Provides the high-level SDK verbs (ingest, distill, recall, consolidate)
-//! and manages resource budgets and power-aware scheduling.
+//! Provides the high-level SDK verbs (ingest, distill, recall, consolidate,
+//! forget, digest) and manages resource budgets and power-aware scheduling.
 
+pub mod digest;
+pub mod memory;
 pub mod orchestrator;
 pub mod types;
 
+pub use digest::{Digest, DigestPeriod, DIGEST_SOURCE};
+pub use memory::{MemoryAccountant, MemoryReservation, MemoryUsage};
 pub use orchestrator::Orchestrator;
 pub use types::*;
@@ -0,0 +1,253 @@
+//! Memory accounting against the tier's `max_memory_mb`.
+//!
+//! Nothing here asks the allocator. The accountant adds up what the big
+//! known consumers report: resident ones (the store's embedding matrix,
+//! the embedder's model) are sampled with `set_resident`, and transient
+//! ones (a batch of texts about to be embedded) reserve their estimate
+//! before allocating and give it back when the reservation drops. Work
+//! whose reservation would take the total past the budget is refused, or
+//! shrunk by `reserve_batch` to the part that fits, so a Base-tier device
+//! slows down instead of meeting the OOM killer.
+
+use std::collections::BTreeMap;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::warn;
+
+/// Bytes in a megabyte, as `max_memory_mb` counts them.
+const MB: usize = 1024 * 1024;
+
+/// Resident consumer: the store's in-memory embedding matrix.
+pub const EMBEDDING_MATRIX: &str = "embedding_matrix";
+/// Resident consumer: the embedder's model and query cache.
+pub const EMBEDDER_MODEL: &str = "embedder_model";
+/// Transient consumer: texts and vectors of an embedding batch.
+pub const EMBED_BATCH: &str = "embed_batch";
+
+/// Working memory the model needs per text of a batch at its longest
+/// input: token ids, attention mask and the activations of one sequence.
+const EMBED_WORKING_BYTES_PER_TEXT: usize = MB;
+
+/// Estimated bytes to embed one text of `text_len` bytes into a vector of
+/// `dimension` floats.
+pub fn embed_item_bytes(text_len: usize, dimension: usize) -> usize {
+    text_len + dimension * std::mem::size_of::<f32>() + EMBED_WORKING_BYTES_PER_TEXT
+}
+
+/// Current use against the budget, as reported on `RuntimeStatus`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryUsage {
+    pub budget_bytes: usize,
+    /// Resident plus in-flight bytes.
+    pub used_bytes: usize,
+    pub available_bytes: usize,
+    /// Last sampled size of each resident consumer.
+    pub resident: BTreeMap<String, usize>,
+    /// Bytes reserved by each transient consumer right now.
+    pub in_flight: BTreeMap<String, usize>,
+    /// Reservations refused since start.
+    pub refused: u64,
+    /// Batches cut down to fit since start.
+    pub shrunk: u64,
+}
+
+#[derive(Default)]
+struct Ledger {
+    resident: BTreeMap<&'static str, usize>,
+    in_flight: BTreeMap<&'static str, usize>,
+    refused: u64,
+    shrunk: u64,
+}
+
+impl Ledger {
+    fn used(&self) -> usize {
+        self.resident.values().chain(self.in_flight.values()).sum()
+    }
+}
+
+/// Tracks estimated memory use of the known consumers against a budget.
+pub struct MemoryAccountant {
+    budget_bytes: usize,
+    ledger: Mutex<Ledger>,
+}
+
+impl MemoryAccountant {
+    pub fn new(budget_bytes: usize) -> Self {
+        Self {
+            budget_bytes,
+            ledger: Mutex::new(Ledger::default()),
+        }
+    }
+
+    /// An accountant for a budget of `max_memory_mb`.
+    pub fn with_budget_mb(max_memory_mb: usize) -> Self {
+        Self::new(max_memory_mb.saturating_mul(MB))
+    }
+
+    pub fn budget_bytes(&self) -> usize {
+        self.budget_bytes
+    }
+
+    /// Record the current size of a resident consumer, replacing its last
+    /// sample.
+    pub fn set_resident(&self, consumer: &'static str, bytes: usize) {
+        self.ledger.lock().resident.insert(consumer, bytes);
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.ledger.lock().used()
+    }
+
+    pub fn available_bytes(&self) -> usize {
+        self.budget_bytes.saturating_sub(self.used_bytes())
+    }
+
+    /// Reserve `bytes` for `consumer`, or `None` when they do not fit in
+    /// what the budget has left.
+    pub fn reserve(&self, consumer: &'static str, bytes: usize) -> Option<MemoryReservation<'_>> {
+        let mut ledger = self.ledger.lock();
+        if ledger.used().saturating_add(bytes) > self.budget_bytes {
+            ledger.refused += 1;
+            warn!(
+                "Memory budget refused {} bytes for {} ({} of {} in use)",
+                bytes,
+                consumer,
+                ledger.used(),
+                self.budget_bytes
+            );
+            return None;
+        }
+        *ledger.in_flight.entry(consumer).or_insert(0) += bytes;
+        Some(MemoryReservation {
+            accountant: self,
+            consumer,
+            bytes,
+        })
+    }
+
+    /// Reserve room for up to `items` items of `item_bytes` each. Returns
+    /// the reservation with the number of items it covers, fewer than
+    /// asked when only that many fit; `None` when not even one does.
+    pub fn reserve_batch(
+        &self,
+        consumer: &'static str,
+        item_bytes: usize,
+        items: usize,
+    ) -> Option<(MemoryReservation<'_>, usize)> {
+        if items == 0 {
+            return None;
+        }
+        let fitting = match item_bytes {
+            0 => items,
+            _ => (self.available_bytes() / item_bytes).min(items),
+        };
+        let count = fitting.max(1);
+        let reservation = self.reserve(consumer, count * item_bytes)?;
+        if count < items {
+            self.ledger.lock().shrunk += 1;
+        }
+        Some((reservation, count))
+    }
+
+    pub fn usage(&self) -> MemoryUsage {
+        let ledger = self.ledger.lock();
+        let used = ledger.used();
+        let named = |map: &BTreeMap<&'static str, usize>| map.iter().map(|(k, v)| (k.to_string(), *v)).collect();
+        MemoryUsage {
+            budget_bytes: self.budget_bytes,
+            used_bytes: used,
+            available_bytes: self.budget_bytes.saturating_sub(used),
+            resident: named(&ledger.resident),
+            in_flight: named(&ledger.in_flight),
+            refused: ledger.refused,
+            shrunk: ledger.shrunk,
+        }
+    }
+
+    fn release(&self, consumer: &'static str, bytes: usize) {
+        let mut ledger = self.ledger.lock();
+        if let Some(reserved) = ledger.in_flight.get_mut(consumer) {
+            *reserved = reserved.saturating_sub(bytes);
+            if *reserved == 0 {
+                ledger.in_flight.remove(consumer);
+            }
+        }
+    }
+}
+
+/// Bytes held for a transient consumer; released when dropped.
+#[must_use = "the reservation is released as soon as it is dropped"]
+pub struct MemoryReservation<'a> {
+    accountant: &'a MemoryAccountant,
+    consumer: &'static str,
+    bytes: usize,
+}
+
+impl MemoryReservation<'_> {
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for MemoryReservation<'_> {
+    fn drop(&mut self) {
+        self.accountant.release(self.consumer, self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_refuses_past_the_budget_and_releases_on_drop() {
+        let memory = MemoryAccountant::new(1000);
+        memory.set_resident(EMBEDDING_MATRIX, 600);
+
+        let first = memory.reserve(EMBED_BATCH, 300).unwrap();
+        assert_eq!(memory.used_bytes(), 900);
+        assert!(memory.reserve(EMBED_BATCH, 200).is_none());
+
+        let second = memory.reserve("other", 100).unwrap();
+        assert_eq!(memory.available_bytes(), 0);
+        drop(first);
+        drop(second);
+        assert_eq!(memory.used_bytes(), 600);
+
+        let usage = memory.usage();
+        assert_eq!(usage.refused, 1);
+        assert!(usage.in_flight.is_empty());
+        assert_eq!(usage.resident.get(EMBEDDING_MATRIX), Some(&600));
+
+        // A resident consumer that grows leaves less for batches
+        memory.set_resident(EMBEDDING_MATRIX, 950);
+        assert!(memory.reserve(EMBED_BATCH, 100).is_none());
+    }
+
+    #[test]
+    fn test_reserve_batch_shrinks_to_what_fits() {
+        let memory = MemoryAccountant::new(1000);
+        memory.set_resident(EMBEDDER_MODEL, 400);
+
+        let (all, count) = memory.reserve_batch(EMBED_BATCH, 50, 8).unwrap();
+        assert_eq!((count, all.bytes()), (8, 400));
+
+        // 200 bytes left: 2 of 5 items fit
+        let (part, count) = memory.reserve_batch(EMBED_BATCH, 100, 5).unwrap();
+        assert_eq!((count, part.bytes()), (2, 200));
+        assert_eq!(memory.usage().in_flight.get(EMBED_BATCH), Some(&600));
+
+        // Nothing left: not even one item
+        assert!(memory.reserve_batch(EMBED_BATCH, 100, 5).is_none());
+        drop(all);
+        drop(part);
+
+        let usage = memory.usage();
+        assert_eq!((usage.shrunk, usage.refused), (1, 1));
+        assert_eq!(usage.used_bytes, 400);
+        assert!(memory.reserve_batch(EMBED_BATCH, 100, 0).is_none());
+    }
+}
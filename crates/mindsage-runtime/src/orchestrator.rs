@@ -1,21 +1,44 @@
 //! Orchestrator — coordinates SDK verbs with resource budgets.
 
+use std::collections::{BTreeMap, VecDeque};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use mindsage_consolidate::ConsolidationPipeline;
-use mindsage_core::{CapabilityTier, DeviceCapabilities};
+use mindsage_core::{CapabilityTier, DeviceCapabilities, Event, EventBus};
 use mindsage_infer::EmbedderBackend;
 use mindsage_ingest::Ingester;
 use mindsage_resolve::HybridResolver;
 use mindsage_store::SqliteStore;
-use tracing::{debug, error, info};
+use parking_lot::Mutex;
+use tracing::{debug, error, info, warn};
 
+use crate::digest::{self, Digest, DigestPeriod};
+use crate::memory::{self, MemoryAccountant, MemoryReservation};
 use crate::types::*;
 
+/// Paragraphs embedded per batch during ingest; the embedding budget is
+/// checked between batches.
+const INGEST_EMBED_BATCH: usize = 16;
+
+/// Consolidation runs kept in the history.
+const CONSOLIDATION_HISTORY_LEN: usize = 20;
+
+/// Full-text hits checked for the words of a term being forgotten.
+const FORGET_SEARCH_LIMIT: usize = 500;
+
+/// Characters of a document's text shown in a forget preview.
+const FORGET_SNIPPET_CHARS: usize = 160;
+
 /// Top-level orchestrator that coordinates all SDK verbs.
 pub struct Orchestrator {
     tier: CapabilityTier,
     budget: ResourceBudget,
+    /// Estimated use of `budget.max_memory_mb`.
+    memory: MemoryAccountant,
+    events: Option<EventBus>,
+    /// Most recent consolidation runs, oldest first.
+    consolidations: Mutex<VecDeque<ConsolidationRun>>,
 }
 
 impl Orchestrator {
@@ -30,13 +53,37 @@ impl Orchestrator {
             tier, budget.max_memory_mb
         );
 
-        Self { tier, budget }
+        Self {
+            tier,
+            memory: MemoryAccountant::with_budget_mb(budget.max_memory_mb),
+            budget,
+            events: None,
+            consolidations: Mutex::new(VecDeque::new()),
+        }
     }
 
     /// Create with explicit tier (for testing).
     pub fn with_tier(tier: CapabilityTier) -> Self {
         let budget = ResourceBudget::for_tier(tier);
-        Self { tier, budget }
+        Self {
+            tier,
+            memory: MemoryAccountant::with_budget_mb(budget.max_memory_mb),
+            budget,
+            events: None,
+            consolidations: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Publish distill progress and consolidation results to the given bus.
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn publish(&self, event: Event) {
+        if let Some(events) = &self.events {
+            events.publish(event);
+        }
     }
 
     /// Get current capability tier.
@@ -49,9 +96,37 @@ impl Orchestrator {
         &self.budget
     }
 
+    /// Memory accounting against the budget.
+    pub fn memory(&self) -> &MemoryAccountant {
+        &self.memory
+    }
+
+    /// Update the resident consumers: the store's embedding matrix and the
+    /// embedder's model.
+    pub fn sample_memory(&self, store: &SqliteStore, embedder: &dyn EmbedderBackend) {
+        self.memory.set_resident(memory::EMBEDDING_MATRIX, store.matrix_memory_bytes());
+        self.memory.set_resident(memory::EMBEDDER_MODEL, embedder.memory_bytes());
+    }
+
+    /// Reserve memory to embed `texts`, after sampling the resident
+    /// consumers. Returns how many of the leading texts the reservation
+    /// covers; `None` when not even one fits in the budget.
+    pub fn reserve_embedding(
+        &self,
+        store: &SqliteStore,
+        embedder: &dyn EmbedderBackend,
+        texts: &[&str],
+    ) -> Option<(MemoryReservation<'_>, usize)> {
+        self.sample_memory(store, embedder);
+        let longest = texts.iter().map(|t| t.len()).max().unwrap_or(0);
+        let item_bytes = memory::embed_item_bytes(longest, embedder.dimension());
+        self.memory.reserve_batch(memory::EMBED_BATCH, item_bytes, texts.len())
+    }
+
     /// SDK verb: ingest — text → chunk → embed → store.
     ///
-    /// Returns the document ID if successful.
+    /// Text whose content hash is already stored is not an error: the
+    /// report names the existing document in `duplicate`.
     pub fn ingest(
         &self,
         store: &SqliteStore,
@@ -60,79 +135,196 @@ impl Orchestrator {
         content_hash: &str,
         metadata: &serde_json::Value,
         file_extension: Option<&str>,
-    ) -> mindsage_core::Result<Option<i64>> {
+    ) -> mindsage_core::Result<IngestReport> {
+        self.ingest_within(store, embedder, text, content_hash, metadata, file_extension, None)
+    }
+
+    /// `ingest` with a time budget for embedding. Paragraphs not embedded
+    /// when the budget runs out are left for background catch-up; the
+    /// budget is checked between batches. Extraction always runs in full.
+    #[allow(clippy::too_many_arguments)]
+    pub fn ingest_within(
+        &self,
+        store: &SqliteStore,
+        embedder: &Arc<dyn EmbedderBackend>,
+        text: &str,
+        content_hash: &str,
+        metadata: &serde_json::Value,
+        file_extension: Option<&str>,
+        embed_budget: Option<Duration>,
+    ) -> mindsage_core::Result<IngestReport> {
+        let started = Instant::now();
         let ingester = Ingester::new(store);
-        let doc_id = ingester.ingest_text(text, content_hash, metadata, file_extension)?;
+        let doc_id = match ingester.ingest_text(text, content_hash, metadata, file_extension) {
+            Ok(Some(doc_id)) => doc_id,
+            Ok(None) => return Err(mindsage_core::Error::Ingest("Text was not stored".into())),
+            Err(mindsage_core::Error::DuplicateContent(hash)) => {
+                return match store.find_document_by_hash(&hash)? {
+                    Some(existing) => Ok(IngestReport::duplicate_of(existing.id)),
+                    // Deleted since the check
+                    None => Err(mindsage_core::Error::DuplicateContent(hash)),
+                };
+            }
+            Err(e) => return Err(e),
+        };
+        let store_ms = elapsed_ms(started);
+        let mut report = self.index_document(store, embedder, doc_id, metadata, embed_budget, false)?;
+        report.timings.store_ms = store_ms;
+        Ok(report)
+    }
+
+    /// Replace a document's text, then chunk, embed and enrich it again as
+    /// `ingest_within` does. Returns `None` when the document does not
+    /// exist; text already stored as another document is
+    /// `Error::DuplicateContent`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn reindex_within(
+        &self,
+        store: &SqliteStore,
+        embedder: &Arc<dyn EmbedderBackend>,
+        doc_id: i64,
+        text: &str,
+        content_hash: &str,
+        metadata: &serde_json::Value,
+        file_extension: Option<&str>,
+        embed_budget: Option<Duration>,
+    ) -> mindsage_core::Result<Option<IngestReport>> {
+        let started = Instant::now();
+        let ingester = Ingester::new(store);
+        if !ingester.replace_text(doc_id, text, content_hash, file_extension)? {
+            return Ok(None);
+        }
+        let store_ms = elapsed_ms(started);
+        let mut report = self.index_document(store, embedder, doc_id, metadata, embed_budget, true)?;
+        report.timings.store_ms = store_ms;
+        Ok(Some(report))
+    }
+
+    /// Embed a freshly chunked document's paragraphs until the budget runs
+    /// out, then run heuristic extraction on all its chunks. With
+    /// `replace_topics`, the extracted topics are written even when empty so
+    /// topics of the previous text do not linger.
+    fn index_document(
+        &self,
+        store: &SqliteStore,
+        embedder: &Arc<dyn EmbedderBackend>,
+        doc_id: i64,
+        metadata: &serde_json::Value,
+        embed_budget: Option<Duration>,
+        replace_topics: bool,
+    ) -> mindsage_core::Result<IngestReport> {
+        let started = Instant::now();
+        let chunks = store.get_chunks_for_document(doc_id)?;
+        let mut chunk_counts = BTreeMap::new();
+        for chunk in &chunks {
+            *chunk_counts.entry(chunk.level).or_insert(0) += 1;
+        }
 
         // Embed level=1 chunks
-        if let Some(doc_id) = doc_id {
-            if embedder.is_available() {
-                let chunks = store.get_chunks_for_document(doc_id)?;
-                let paragraphs: Vec<_> = chunks.iter().filter(|c| c.level == 1).collect();
-                if !paragraphs.is_empty() {
-                    let texts: Vec<&str> = paragraphs.iter().map(|c| c.text.as_str()).collect();
-                    let embeddings = embedder.embed_batch(&texts);
-                    let mut count = 0;
-                    for (chunk, emb) in paragraphs.iter().zip(embeddings.iter()) {
-                        if let Some(result) = emb {
-                            let _ = store.add_chunk_embedding(chunk.id, &result.embedding);
-                            let _ = store.append_to_matrix(chunk.id, &result.embedding);
-                            count += 1;
-                        }
+        let paragraphs: Vec<_> = chunks.iter().filter(|c| c.level == 1).collect();
+        let mut embedded = 0;
+        let mut embedding_deferred = 0;
+        if embedder.is_available() {
+            let mut attempted = 0;
+            while attempted < paragraphs.len() {
+                if embed_budget.is_some_and(|budget| started.elapsed() >= budget) {
+                    break;
+                }
+                let batch = &paragraphs[attempted..paragraphs.len().min(attempted + INGEST_EMBED_BATCH)];
+                let texts: Vec<&str> = batch.iter().map(|c| c.text.as_str()).collect();
+                // Out of memory budget: the rest waits for background catch-up
+                let Some((_reservation, count)) = self.reserve_embedding(store, embedder.as_ref(), &texts) else {
+                    break;
+                };
+                let embeddings = embedder.embed_batch(&texts[..count]);
+                for (chunk, emb) in batch.iter().zip(embeddings.iter()) {
+                    if let Some(result) = emb {
+                        let _ = store.add_chunk_embedding(chunk.id, &result.embedding);
+                        let _ = store.append_to_matrix(chunk.id, chunk.level, &result.embedding);
+                        embedded += 1;
                     }
-                    debug!("Embedded {} chunks for document {}", count, doc_id);
                 }
+                attempted += count;
             }
+            embedding_deferred = paragraphs.len() - attempted;
+            debug!(
+                "Embedded {} chunks for document {} ({} deferred)",
+                embedded, doc_id, embedding_deferred
+            );
+        }
 
-            // Run heuristic extraction
-            let chunks = store.get_chunks_for_document(doc_id)?;
-            let mut doc_topics: Vec<String> = Vec::new();
-            for chunk in &chunks {
-                if chunk.enriched_text.is_some() {
-                    continue;
-                }
-                let source = metadata.get("source").and_then(|s| s.as_str());
-                let filename = metadata.get("filename").and_then(|s| s.as_str());
-                let result = mindsage_ingest::extract_all(&chunk.text, source, filename);
-                let enriched = mindsage_ingest::build_enriched_text(&result);
-                if !enriched.is_empty() {
-                    let _ = store.update_chunk_enriched_text(chunk.id, &enriched);
-                }
-                for topic in &result.topics {
-                    if !doc_topics.contains(topic) {
-                        doc_topics.push(topic.clone());
-                    }
-                }
+        let embed_ms = elapsed_ms(started);
+
+        // Run heuristic extraction
+        let extract_started = Instant::now();
+        let mut enriched_count = 0;
+        let mut doc_topics: Vec<String> = Vec::new();
+        for chunk in &chunks {
+            if chunk.enriched_text.is_some() {
+                continue;
             }
-            if !doc_topics.is_empty() {
-                let updates = serde_json::json!({
-                    "topics": doc_topics,
-                    "extraction_method": "heuristic",
-                });
-                let _ = store.update_document_metadata(doc_id, &updates);
+            let source = metadata.get("source").and_then(|s| s.as_str());
+            let filename = metadata.get("filename").and_then(|s| s.as_str());
+            let result = mindsage_ingest::extract_all(&chunk.text, source, filename);
+            let enriched = mindsage_ingest::build_enriched_text(&result);
+            if !enriched.is_empty()
+                && store
+                    .update_chunk_enriched_text(chunk.id, &enriched, mindsage_ingest::CURRENT_EXTRACTION_VERSION)
+                    .is_ok()
+            {
+                enriched_count += 1;
+            }
+            for topic in &result.topics {
+                if !doc_topics.contains(topic) {
+                    doc_topics.push(topic.clone());
+                }
             }
         }
+        if !doc_topics.is_empty() || replace_topics {
+            let updates = serde_json::json!({
+                "topics": doc_topics,
+                "extraction_method": "heuristic",
+            });
+            let _ = store.update_document_metadata(doc_id, &updates);
+        }
 
-        Ok(doc_id)
+        Ok(IngestReport {
+            doc_id,
+            duplicate: None,
+            chunks: chunks.len(),
+            chunk_counts,
+            embedded,
+            embedding_deferred,
+            enriched: enriched_count,
+            topics: doc_topics,
+            timings: IngestTimings {
+                store_ms: 0,
+                embed_ms,
+                extract_ms: elapsed_ms(extract_started),
+            },
+        })
     }
 
     /// SDK verb: distill — run extraction on all pending chunks.
     ///
-    /// Processes chunks that haven't been enriched or embedded yet.
-    /// Returns (enriched_count, embedded_count).
+    /// Processes chunks that haven't been enriched or embedded yet, and
+    /// re-embeds chunks whose text was edited after embedding before any
+    /// others; with `include_outdated`, also re-extracts chunks enriched by
+    /// an older extractor version. Returns (enriched_count, embedded_count).
     pub fn distill(
         &self,
         store: &SqliteStore,
         embedder: &Arc<dyn EmbedderBackend>,
+        include_outdated: bool,
     ) -> (usize, usize) {
         let batch_size = 50;
         let mut enriched_total = 0;
         let mut embedded_total = 0;
 
-        // Embed unembedded chunks
+        // Embed edited, then unembedded chunks, in parts the memory budget allows
         if embedder.is_available() {
-            loop {
-                let chunks = match store.get_chunks_without_embedding(batch_size) {
+            'embed: loop {
+                let chunks = match store.get_chunks_to_embed(batch_size) {
                     Ok(c) => c,
                     Err(e) => {
                         error!("Failed to get chunks for embedding: {}", e);
@@ -142,15 +334,28 @@ impl Orchestrator {
                 if chunks.is_empty() {
                     break;
                 }
-                let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
-                let embeddings = embedder.embed_batch(&texts);
-                for (chunk, emb) in chunks.iter().zip(embeddings.iter()) {
-                    if let Some(result) = emb {
-                        let _ = store.add_chunk_embedding(chunk.id, &result.embedding);
-                        let _ = store.append_to_matrix(chunk.id, &result.embedding);
-                        embedded_total += 1;
+                let mut rest = &chunks[..];
+                while !rest.is_empty() {
+                    let texts: Vec<&str> = rest.iter().map(|c| c.text.as_str()).collect();
+                    let Some((_reservation, count)) = self.reserve_embedding(store, embedder.as_ref(), &texts) else {
+                        warn!("Distill stopped embedding: memory budget exhausted");
+                        break 'embed;
+                    };
+                    let embeddings = embedder.embed_batch(&texts[..count]);
+                    for (chunk, emb) in rest.iter().zip(embeddings.iter()) {
+                        if let Some(result) = emb {
+                            let _ = store.add_chunk_embedding(chunk.id, &result.embedding);
+                            let _ = store.append_to_matrix(chunk.id, chunk.level, &result.embedding);
+                            embedded_total += 1;
+                        }
                     }
+                    rest = &rest[count..];
                 }
+                self.publish(Event::DistillProgress {
+                    enriched: enriched_total,
+                    embedded: embedded_total,
+                    done: false,
+                });
             }
         }
 
@@ -170,12 +375,64 @@ impl Orchestrator {
                 let result = mindsage_ingest::extract_all(&chunk.text, None, None);
                 let enriched = mindsage_ingest::build_enriched_text(&result);
                 if !enriched.is_empty() {
-                    let _ = store.update_chunk_enriched_text(chunk.id, &enriched);
+                    let _ = store.update_chunk_enriched_text(
+                        chunk.id,
+                        &enriched,
+                        mindsage_ingest::CURRENT_EXTRACTION_VERSION,
+                    );
                 }
                 enriched_total += 1;
             }
+            self.publish(Event::DistillProgress {
+                enriched: enriched_total,
+                embedded: embedded_total,
+                done: false,
+            });
+        }
+
+        // Re-extract chunks enriched by an older extractor version
+        if include_outdated {
+            let mut after_id = 0;
+            loop {
+                let chunks = match store.get_chunks_with_outdated_extraction(
+                    mindsage_ingest::CURRENT_EXTRACTION_VERSION,
+                    after_id,
+                    batch_size,
+                ) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        error!("Failed to get outdated chunks for extraction: {}", e);
+                        break;
+                    }
+                };
+                let Some(last) = chunks.last() else {
+                    break;
+                };
+                after_id = last.id;
+                for chunk in &chunks {
+                    let result = mindsage_ingest::extract_all(&chunk.text, None, None);
+                    let enriched = mindsage_ingest::build_enriched_text(&result);
+                    let _ = store.update_chunk_enriched_text(
+                        chunk.id,
+                        &enriched,
+                        mindsage_ingest::CURRENT_EXTRACTION_VERSION,
+                    );
+                    enriched_total += 1;
+                }
+                self.publish(Event::DistillProgress {
+                    enriched: enriched_total,
+                    embedded: embedded_total,
+                    done: false,
+                });
+            }
         }
 
+        self.publish(Event::DistillProgress {
+            enriched: enriched_total,
+            embedded: embedded_total,
+            done: true,
+        });
+
         if enriched_total > 0 || embedded_total > 0 {
             info!(
                 "Distill complete: {} enriched, {} embedded",
@@ -186,12 +443,14 @@ impl Orchestrator {
         (enriched_total, embedded_total)
     }
 
-    /// SDK verb: recall — query with tier-aware resolver selection.
+    /// SDK verb: recall — query with tier-aware resolver selection. Queries
+    /// without a latency budget get the tier's `search_budget_ms`.
     pub fn recall(
         &self,
         store: &SqliteStore,
-        query: mindsage_resolve::ResolveQuery,
+        mut query: mindsage_resolve::ResolveQuery,
     ) -> mindsage_resolve::ResolveResult {
+        query.budget_ms.get_or_insert(self.budget.search_budget_ms);
         HybridResolver::resolve(store, &query, self.tier)
     }
 
@@ -200,7 +459,145 @@ impl Orchestrator {
         &self,
         store: &SqliteStore,
     ) -> mindsage_consolidate::ConsolidationReport {
-        ConsolidationPipeline::run(store, self.tier)
+        let report = ConsolidationPipeline::run(store, self.tier);
+        self.publish(Event::ConsolidationComplete {
+            orphans_pruned: report.orphans_pruned,
+            duplicates_removed: report.duplicates_removed,
+            documents_evicted: report.documents_evicted,
+            duration_ms: report.duration_ms,
+        });
+        let finished_at = now_ms();
+        let mut history = self.consolidations.lock();
+        if history.len() == CONSOLIDATION_HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(ConsolidationRun {
+            finished_at,
+            report: report.clone(),
+        });
+        report
+    }
+
+    /// SDK verb: forget, preview — find everything that mentions `query`:
+    /// documents whose text, metadata or enriched chunk text contains it
+    /// (ignoring case), full-text hits containing all of its words in any
+    /// order, and the chunks, embeddings, topic links, logged queries,
+    /// saved searches and staged items that would go with them. Vector
+    /// similarity is not used: nearest neighbours always exist, mentions
+    /// may not.
+    pub fn plan_forget(&self, store: &SqliteStore, query: &str) -> mindsage_core::Result<ForgetPlan> {
+        let query = query.trim();
+        let mut reasons: BTreeMap<i64, ForgetReason> = store
+            .find_documents_mentioning(query)?
+            .into_iter()
+            .map(|id| (id, ForgetReason::Mention))
+            .collect();
+
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        if !words.is_empty() {
+            for hit in store.bm25_search(query, None, FORGET_SEARCH_LIMIT)? {
+                let text = format!("{}\n{}", hit.text, hit.enriched_text.as_deref().unwrap_or("")).to_lowercase();
+                if words.iter().all(|w| text.contains(w.as_str())) {
+                    reasons.entry(hit.doc_id).or_insert(ForgetReason::Search);
+                }
+            }
+        }
+
+        let mut documents = Vec::with_capacity(reasons.len());
+        for (doc_id, reason) in reasons {
+            let Some(doc) = store.get_document(doc_id)? else {
+                continue;
+            };
+            let title = doc.metadata.as_ref().and_then(|m| {
+                m.get("title")
+                    .or_else(|| m.get("filename"))
+                    .and_then(|t| t.as_str())
+                    .map(str::to_string)
+            });
+            documents.push(ForgetMatch {
+                doc_id,
+                title,
+                snippet: doc.text.chars().take(FORGET_SNIPPET_CHARS).collect(),
+                reason,
+            });
+        }
+
+        let doc_ids: Vec<i64> = documents.iter().map(|d| d.doc_id).collect();
+        let footprint = store.document_footprint(&doc_ids)?;
+        Ok(ForgetPlan {
+            query: query.to_string(),
+            store: ForgetStoreCounts {
+                documents: documents.len(),
+                chunks: footprint.chunk_ids.len(),
+                embeddings: footprint.embeddings,
+            },
+            graph: ForgetGraphCounts {
+                topic_links: footprint.topic_links,
+            },
+            cache: store.forget_mentions(query, true)?,
+            chunk_ids: footprint.chunk_ids,
+            documents,
+        })
+    }
+
+    /// SDK verb: forget — delete what `plan` lists from the store. The
+    /// documents go with their chunks, embeddings and topic links; logged
+    /// queries, saved searches and staged items mentioning the term are
+    /// deleted too. Returns the plan with the counts actually deleted.
+    pub fn forget(&self, store: &SqliteStore, plan: &ForgetPlan) -> mindsage_core::Result<ForgetPlan> {
+        let doc_ids = plan.doc_ids();
+        let footprint = store.document_footprint(&doc_ids)?;
+        let documents = store.bulk_delete_documents(&doc_ids, |_| {})?;
+        let cache = store.forget_mentions(&plan.query, false)?;
+        info!(
+            documents,
+            chunks = footprint.chunk_ids.len(),
+            "Forgot {} documents and {} other mentions",
+            documents,
+            cache.search_queries + cache.saved_searches + cache.staged_items
+        );
+        Ok(ForgetPlan {
+            store: ForgetStoreCounts {
+                documents,
+                chunks: footprint.chunk_ids.len(),
+                embeddings: footprint.embeddings,
+            },
+            graph: ForgetGraphCounts {
+                topic_links: footprint.topic_links,
+            },
+            cache,
+            chunk_ids: footprint.chunk_ids,
+            ..plan.clone()
+        })
+    }
+
+    /// SDK verb: digest — summarize the documents created in `period`.
+    /// The digest is not stored; see `store_digest`.
+    pub fn digest(&self, store: &SqliteStore, period: DigestPeriod) -> mindsage_core::Result<Digest> {
+        digest::generate_digest(store, period, now_ms())
+    }
+
+    /// Store `digest` as a searchable document with `source = "digest"`,
+    /// setting its `doc_id`. Storing the same digest twice keeps one
+    /// document.
+    pub fn store_digest(
+        &self,
+        store: &SqliteStore,
+        embedder: &Arc<dyn EmbedderBackend>,
+        digest: &mut Digest,
+    ) -> mindsage_core::Result<i64> {
+        digest.doc_id = None;
+        let text = digest.to_text();
+        let hash = mindsage_ingest::ingest::content_hash(&text);
+        let report = self.ingest(store, embedder, &text, &hash, &digest::digest_metadata(digest), None)?;
+        digest.doc_id = Some(report.doc_id);
+        Ok(report.doc_id)
+    }
+
+    /// The last consolidation runs (at most 20), newest first.
+    pub fn consolidation_history(&self) -> Vec<ConsolidationRun> {
+        let history = self.consolidations.lock();
+        history.iter().rev().cloned().collect()
     }
 
     /// Get runtime status.
@@ -210,6 +607,7 @@ impl Orchestrator {
             budget: self.budget.clone(),
             active_verbs: Vec::new(),
             pending_distill: 0,
+            memory: self.memory.usage(),
         }
     }
 }
@@ -220,9 +618,21 @@ impl Default for Orchestrator {
     }
 }
 
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+fn elapsed_ms(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mindsage_infer::test_support::WordEmbedder;
     use mindsage_store::AddDocumentOptions;
 
     fn test_store() -> (SqliteStore, tempfile::TempDir) {
@@ -265,9 +675,11 @@ mod tests {
             &store,
             mindsage_resolve::ResolveQuery {
                 query: "Tokio async".into(),
+                syntax: Default::default(),
                 resolver: None,
                 limit: 5,
                 filters: None,
+                budget_ms: None,
             },
         );
         assert!(result.total_found > 0);
@@ -283,6 +695,33 @@ mod tests {
         assert_eq!(report.duplicates_removed, 0);
     }
 
+    #[test]
+    fn test_consolidation_history_is_bounded() {
+        let (store, _dir) = test_store();
+        let orch = Orchestrator::with_tier(CapabilityTier::Base);
+        assert!(orch.consolidation_history().is_empty());
+        for _ in 0..CONSOLIDATION_HISTORY_LEN + 3 {
+            orch.consolidate(&store);
+        }
+        let history = orch.consolidation_history();
+        assert_eq!(history.len(), CONSOLIDATION_HISTORY_LEN);
+        assert!(history.windows(2).all(|w| w[0].finished_at >= w[1].finished_at));
+        assert!(history[0].finished_at > 0);
+    }
+
+    #[test]
+    fn test_consolidate_publishes_event() {
+        let (store, _dir) = test_store();
+        let bus = EventBus::default();
+        let mut rx = bus.subscribe();
+        let orch = Orchestrator::with_tier(CapabilityTier::Base).with_events(bus);
+        orch.consolidate(&store);
+
+        let frame = serde_json::to_value(rx.try_recv().unwrap()).unwrap();
+        assert_eq!(frame["type"], "consolidation.complete");
+        assert_eq!(frame["orphansPruned"], 0);
+    }
+
     #[test]
     fn test_status() {
         let orch = Orchestrator::with_tier(CapabilityTier::Advanced);
@@ -292,6 +731,78 @@ mod tests {
         assert!(status.active_verbs.is_empty());
     }
 
+    #[test]
+    fn test_forget_removes_every_trace() {
+        let (store, _dir) = test_store();
+        let orch = Orchestrator::with_tier(CapabilityTier::Base);
+        let embedder: Arc<dyn EmbedderBackend> = Arc::new(WordEmbedder::new(384));
+        store.set_embedding_model(embedder.model_id());
+
+        let ingest = |text: &str, metadata: serde_json::Value| {
+            orch.ingest(&store, &embedder, text, text, &metadata, None).unwrap().doc_id
+        };
+        let mention = ingest("Lunch with Marta Kowalski about the allotment plans.", serde_json::json!({}));
+        let titled = ingest("Notes on compost ratios.", serde_json::json!({"title": "From marta kowalski"}));
+        let reordered = ingest("Kowalski, Marta: seed swap on Saturday.", serde_json::json!({}));
+        let unrelated = ingest("The allotment water tap is fixed.", serde_json::json!({}));
+        store.record_query("marta kowalski birthday", 2).unwrap();
+        store.record_query("allotment", 2).unwrap();
+        store.create_saved_search("Marta Kowalski", "seed swap", None, 5).unwrap();
+
+        let plan = orch.plan_forget(&store, "Marta Kowalski").unwrap();
+        let reasons: Vec<(i64, ForgetReason)> = plan.documents.iter().map(|d| (d.doc_id, d.reason)).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (mention, ForgetReason::Mention),
+                (titled, ForgetReason::Mention),
+                (reordered, ForgetReason::Search)
+            ]
+        );
+        assert_eq!(plan.documents[1].title.as_deref(), Some("From marta kowalski"));
+        assert!(plan.store.chunks >= 3 && plan.store.embeddings >= 3);
+        assert_eq!((plan.cache.search_queries, plan.cache.saved_searches), (1, 1));
+        // A preview deletes nothing
+        assert_eq!(store.count_documents().unwrap(), 4);
+
+        let done = orch.forget(&store, &plan).unwrap();
+        assert_eq!(done.store.documents, 3);
+        assert_eq!(done.store.embeddings, plan.store.embeddings);
+        assert_eq!(store.count_documents().unwrap(), 1);
+        assert!(store.bm25_search("Kowalski", None, 10).unwrap().is_empty());
+        let query = embedder.embed("Marta Kowalski").unwrap().embedding;
+        let hits = store.vector_search(&query, None, 10).unwrap();
+        assert!(hits.iter().all(|h| h.doc_id == unrelated), "{:?}", hits);
+        assert!(store.suggest("mar", 10).unwrap().iter().all(|s| !s.text.contains("marta")));
+        assert!(store.list_saved_searches().unwrap().is_empty());
+        assert!(orch.plan_forget(&store, "marta kowalski").unwrap().documents.is_empty());
+    }
+
+    #[test]
+    fn test_stored_digest_is_searchable_but_not_counted() {
+        let (store, _dir) = test_store();
+        let orch = Orchestrator::with_tier(CapabilityTier::Base);
+        let embedder: Arc<dyn EmbedderBackend> = Arc::new(WordEmbedder::new(384));
+        let metadata = serde_json::json!({"source": "upload"});
+        orch.ingest(&store, &embedder, "Sourdough starter needs feeding twice a day.", "h1", &metadata, None)
+            .unwrap();
+
+        let period = DigestPeriod::days_before(now_ms() + 1000, 7);
+        let mut digest = orch.digest(&store, period).unwrap();
+        assert_eq!(digest.documents, 1);
+        let doc_id = orch.store_digest(&store, &embedder, &mut digest).unwrap();
+        assert_eq!(digest.doc_id, Some(doc_id));
+        assert_eq!(orch.store_digest(&store, &embedder, &mut digest).unwrap(), doc_id);
+
+        let hits = store.bm25_search("digest sourdough", None, 10).unwrap();
+        assert!(hits.iter().any(|h| h.doc_id == doc_id));
+        let listed = digest::list_digests(&store, 10).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].doc_id, Some(doc_id));
+        assert_eq!(listed[0].period, period);
+        assert_eq!(orch.digest(&store, period).unwrap().documents, 1);
+    }
+
     #[test]
     fn test_ingest() {
         let (store, _dir) = test_store();
@@ -306,7 +817,7 @@ mod tests {
         let doc_id = orch
             .ingest(&store, &embedder, text, hash, &metadata, None)
             .unwrap()
-            .unwrap();
+            .doc_id;
         assert!(doc_id > 0);
 
         // Document should exist
@@ -330,12 +841,105 @@ mod tests {
         let metadata = serde_json::json!({});
 
         // First ingest succeeds
-        let result = orch.ingest(&store, &embedder, text, hash, &metadata, None);
-        assert!(result.is_ok());
+        let first = orch.ingest(&store, &embedder, text, hash, &metadata, None).unwrap();
+        assert_eq!(first.duplicate, None);
+
+        // Second ingest with same hash reports the existing document
+        let second = orch.ingest(&store, &embedder, text, hash, &metadata, None).unwrap();
+        assert!(second.is_duplicate());
+        assert_eq!(second.duplicate, Some(first.doc_id));
+        assert_eq!(second.doc_id, first.doc_id);
+        assert_eq!((second.chunks, second.embedded, second.enriched), (0, 0, 0));
+        assert_eq!(store.get_stats().unwrap().total_documents, 1);
+    }
+
+    #[test]
+    fn test_ingest_report() {
+        let (store, _dir) = test_store();
+        let orch = Orchestrator::with_tier(CapabilityTier::Base);
+        let embedder: Arc<dyn EmbedderBackend> = Arc::new(WordEmbedder::new(384));
+        store.set_embedding_model(embedder.model_id());
+
+        let text = "# Garden\n\nPython scripts water the tomatoes every morning at six.\n\n\
+                    The Raspberry Pi logs soil moisture to a database in Berlin.";
+        let report = orch
+            .ingest(&store, &embedder, text, "garden", &serde_json::json!({"source": "note"}), Some("md"))
+            .unwrap();
+
+        let chunks = store.get_chunks_for_document(report.doc_id).unwrap();
+        assert_eq!(report.duplicate, None);
+        assert_eq!(report.chunks, chunks.len());
+        assert_eq!(report.chunk_counts.values().sum::<usize>(), chunks.len());
+        let paragraphs = chunks.iter().filter(|c| c.level == 1).count();
+        assert_eq!(report.chunk_counts.get(&1), Some(&paragraphs));
+        assert_eq!((report.embedded, report.embedding_deferred), (paragraphs, 0));
+        let enriched = chunks.iter().filter(|c| c.enriched_text.is_some()).count();
+        assert!(enriched > 0);
+        assert_eq!(report.enriched, enriched);
+        assert!(!report.topics.is_empty());
+        let doc = store.get_document(report.doc_id).unwrap().unwrap();
+        assert_eq!(doc.metadata.unwrap()["topics"], serde_json::json!(report.topics));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert!(json["chunkCounts"]["1"].is_number());
+        assert!(json["timings"]["embedMs"].is_number());
+    }
+
+    /// `WordEmbedder` reporting a model of the given size.
+    struct ResidentEmbedder(usize);
+
+    impl EmbedderBackend for ResidentEmbedder {
+        fn embed(&self, text: &str) -> Option<mindsage_infer::EmbeddingResult> {
+            WordEmbedder::new(384).embed(text)
+        }
+
+        fn dimension(&self) -> usize {
+            384
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn model_id(&self) -> &str {
+            "words"
+        }
 
-        // Second ingest with same hash fails
-        let result = orch.ingest(&store, &embedder, text, hash, &metadata, None);
-        assert!(result.is_err());
+        fn memory_bytes(&self) -> usize {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_ingest_embeds_within_the_memory_budget() {
+        let paragraphs: Vec<String> = (0..6)
+            .map(|i| format!("Paragraph {} about the garden. {}", i, "The tomatoes need water every morning. ".repeat(30)))
+            .collect();
+        let text = paragraphs.join("\n\n");
+        let orch = Orchestrator::with_tier(CapabilityTier::Base);
+        let budget = orch.memory().budget_bytes();
+
+        // Room for two texts per batch: every paragraph still gets embedded
+        let (store, _dir) = test_store();
+        let embedder: Arc<dyn EmbedderBackend> = Arc::new(ResidentEmbedder(budget - 5 * 1024 * 1024 / 2));
+        store.set_embedding_model(embedder.model_id());
+        let report = orch.ingest(&store, &embedder, &text, "roomy", &serde_json::json!({}), None).unwrap();
+        let paragraph_count = report.chunk_counts[&1];
+        assert!(paragraph_count > 2);
+        assert_eq!((report.embedded, report.embedding_deferred), (paragraph_count, 0));
+        let usage = orch.memory().usage();
+        assert!(usage.shrunk > 0);
+        assert_eq!(usage.refused, 0);
+        assert!(usage.in_flight.is_empty());
+
+        // A model filling the budget leaves embedding to catch-up
+        let (store, _dir) = test_store();
+        let embedder: Arc<dyn EmbedderBackend> = Arc::new(ResidentEmbedder(budget));
+        let report = orch.ingest(&store, &embedder, &text, "full", &serde_json::json!({}), None).unwrap();
+        assert_eq!((report.embedded, report.embedding_deferred), (0, paragraph_count));
+        assert_eq!(orch.memory().usage().refused, 1);
+        assert_eq!(orch.distill(&store, &embedder, false).1, 0);
+        assert_eq!(orch.status().memory.resident[memory::EMBEDDER_MODEL], budget);
     }
 
     #[test]
@@ -354,8 +958,126 @@ mod tests {
             .unwrap();
 
         // Distill should enrich the chunk
-        let (enriched, embedded) = orch.distill(&store, &embedder);
+        let (enriched, embedded) = orch.distill(&store, &embedder, false);
         assert!(enriched > 0);
         assert_eq!(embedded, 0); // NoopEmbedder returns None
     }
+
+    #[test]
+    fn test_distill_includes_outdated_extractions() {
+        let (store, _dir) = test_store();
+        let orch = Orchestrator::with_tier(CapabilityTier::Base);
+        let embedder: Arc<dyn EmbedderBackend> =
+            Arc::new(mindsage_infer::NoopEmbedder::new(384));
+        let doc_id = store
+            .add_document("Test doc", AddDocumentOptions::default())
+            .unwrap();
+        let chunk_id = store
+            .add_chunk(doc_id, "Python is a programming language used for data science", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+        // Enriched by an extractor older than the current version
+        store
+            .update_chunk_enriched_text(chunk_id, "topics: old", mindsage_ingest::CURRENT_EXTRACTION_VERSION - 1)
+            .unwrap();
+
+        assert_eq!(orch.distill(&store, &embedder, false), (0, 0));
+        assert_eq!(orch.distill(&store, &embedder, true), (1, 0));
+        assert_eq!(store.count_outdated_extractions(mindsage_ingest::CURRENT_EXTRACTION_VERSION).unwrap(), 0);
+        let chunk = store.get_chunk(chunk_id).unwrap().unwrap();
+        assert_ne!(chunk.enriched_text.as_deref(), Some("topics: old"));
+    }
+
+    #[test]
+    fn test_distill_reembeds_edited_chunks_first() {
+        let (store, _dir) = test_store();
+        let orch = Orchestrator::with_tier(CapabilityTier::Base);
+        let embedder: Arc<dyn EmbedderBackend> = Arc::new(WordEmbedder::new(384));
+        let doc_id = store.add_document("Ferry notes", AddDocumentOptions::default()).unwrap();
+        let chunk_id = store
+            .add_chunk(doc_id, "The ferry leaves at nine", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+        assert_eq!(orch.distill(&store, &embedder, false).1, 1);
+        let rows = store.get_stats().unwrap().matrix_rows;
+
+        store.update_chunk_text(chunk_id, "The ferry leaves at ten").unwrap();
+        let later = store
+            .add_chunk(doc_id, "Bikes ride free", 1, 1, None, None, None, None, None, None)
+            .unwrap();
+        assert_eq!(
+            store.get_chunks_to_embed(10).unwrap().iter().map(|c| c.id).collect::<Vec<_>>(),
+            vec![chunk_id, later]
+        );
+        assert_eq!(orch.distill(&store, &embedder, false).1, 2);
+
+        let stats = store.get_stats().unwrap();
+        assert_eq!((stats.text_stale_embeddings, stats.matrix_rows), (0, rows + 1));
+        let query = embedder.embed("The ferry leaves at ten").unwrap().embedding;
+        let hits = store.vector_search(&query, None, 5).unwrap();
+        assert_eq!(hits[0].chunk_id, chunk_id);
+        assert!(hits[0].score > 0.99);
+    }
+
+    #[test]
+    fn test_ingest_within_is_searchable_immediately() {
+        let (store, _dir) = test_store();
+        let orch = Orchestrator::with_tier(CapabilityTier::Base);
+        let embedder: Arc<dyn EmbedderBackend> = Arc::new(WordEmbedder::new(384));
+        store.set_embedding_model(embedder.model_id());
+        store.add_document("Unrelated grocery list", AddDocumentOptions::default()).unwrap();
+
+        let text = "Call the plumber about the leaking kitchen sink";
+        let outcome = orch
+            .ingest_within(&store, &embedder, text, "note-1", &serde_json::json!({}), None, Some(Duration::from_secs(5)))
+            .unwrap();
+        assert_eq!((outcome.embedded, outcome.embedding_deferred), (1, 0));
+
+        let query = embedder.embed("plumber sink").unwrap().embedding;
+        let hits = store.vector_search(&query, Some(1), 1).unwrap();
+        assert_eq!(hits[0].doc_id, outcome.doc_id);
+
+        // A spent budget leaves embedding to background catch-up
+        let outcome = orch
+            .ingest_within(&store, &embedder, "Second note", "note-2", &serde_json::json!({}), None, Some(Duration::ZERO))
+            .unwrap();
+        assert_eq!((outcome.embedded, outcome.embedding_deferred), (0, 1));
+        assert_eq!(store.get_chunks_without_embedding(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_reindex_replaces_chunks() {
+        let (store, _dir) = test_store();
+        let orch = Orchestrator::with_tier(CapabilityTier::Base);
+        let embedder: Arc<dyn EmbedderBackend> = Arc::new(WordEmbedder::new(384));
+        store.set_embedding_model(embedder.model_id());
+        let metadata = serde_json::json!({"source": "note"});
+
+        let doc_id = orch
+            .ingest(&store, &embedder, "Draft agenda for the offsite", "v1", &metadata, None)
+            .unwrap()
+            .doc_id;
+        let old_chunks = store.get_chunks_for_document(doc_id).unwrap();
+
+        let outcome = orch
+            .reindex_within(&store, &embedder, doc_id, "Final budget for the retreat", "v2", &metadata, None, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(outcome.doc_id, doc_id);
+        assert_eq!(outcome.embedded, 1);
+        assert!(store.get_chunk(old_chunks[0].id).unwrap().is_none());
+        assert_eq!(store.get_document(doc_id).unwrap().unwrap().content_hash.as_deref(), Some("v2"));
+        assert!(store.bm25_search("agenda", Some(1), 10).unwrap().is_empty());
+        assert_eq!(store.bm25_search("retreat", Some(1), 10).unwrap()[0].doc_id, doc_id);
+
+        let query = embedder.embed("retreat budget").unwrap().embedding;
+        assert_eq!(store.vector_search(&query, Some(1), 1).unwrap()[0].doc_id, doc_id);
+
+        // Another document's text is a duplicate; a missing document is None
+        let other = orch.ingest(&store, &embedder, "Other note", "v3", &metadata, None).unwrap().doc_id;
+        let dup = orch.reindex_within(&store, &embedder, other, "Final budget for the retreat", "v2", &metadata, None, None);
+        assert!(matches!(dup, Err(mindsage_core::Error::DuplicateContent(_))));
+        assert!(orch
+            .reindex_within(&store, &embedder, 9999, "text", "v4", &metadata, None, None)
+            .unwrap()
+            .is_none());
+    }
 }
@@ -1,9 +1,12 @@
 //! Runtime types.
 
+use std::collections::BTreeMap;
+
 use serde::Serialize;
 
 /// SDK verb that can be executed.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum Verb {
     /// Ingest text → chunk → embed → store → queue extraction.
@@ -14,10 +17,15 @@ pub enum Verb {
     Recall,
     /// Run consolidation pipeline.
     Consolidate,
+    /// Delete everything that mentions a term.
+    Forget,
+    /// Summarize what was captured over a period.
+    Digest,
 }
 
 /// Resource budget for operation scheduling.
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ResourceBudget {
     /// Maximum memory usage in MB.
     #[serde(rename = "maxMemoryMb")]
@@ -28,6 +36,13 @@ pub struct ResourceBudget {
     /// Maximum concurrent operations.
     #[serde(rename = "maxConcurrency")]
     pub max_concurrency: usize,
+    /// Default latency budget for one search, in milliseconds.
+    #[serde(rename = "searchBudgetMs")]
+    pub search_budget_ms: u64,
+    /// Default time allowed for embedding a note inline, in milliseconds;
+    /// the rest is left to background catch-up.
+    #[serde(rename = "embedBudgetMs")]
+    pub embed_budget_ms: u64,
 }
 
 impl ResourceBudget {
@@ -37,28 +52,153 @@ impl ResourceBudget {
                 max_memory_mb: 256,
                 max_gpu_memory_mb: 0,
                 max_concurrency: 1,
+                search_budget_ms: 200,
+                embed_budget_ms: 500,
             },
             mindsage_core::CapabilityTier::Enhanced => Self {
                 max_memory_mb: 512,
                 max_gpu_memory_mb: 2048,
                 max_concurrency: 2,
+                search_budget_ms: 150,
+                embed_budget_ms: 1000,
             },
             mindsage_core::CapabilityTier::Advanced => Self {
                 max_memory_mb: 1024,
                 max_gpu_memory_mb: 4096,
                 max_concurrency: 4,
+                search_budget_ms: 120,
+                embed_budget_ms: 1500,
             },
             mindsage_core::CapabilityTier::Full => Self {
                 max_memory_mb: 2048,
                 max_gpu_memory_mb: 8192,
                 max_concurrency: 8,
+                search_budget_ms: 100,
+                embed_budget_ms: 2000,
             },
         }
     }
 }
 
+/// What an ingest did. A duplicate writes nothing: `doc_id` is then the
+/// existing document and every count is zero.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestReport {
+    pub doc_id: i64,
+    /// Existing document with the same content hash.
+    pub duplicate: Option<i64>,
+    pub chunks: usize,
+    /// Chunks per level (0 = section, 1 = paragraph, ...).
+    pub chunk_counts: BTreeMap<i32, usize>,
+    /// Paragraph chunks embedded before the budget ran out.
+    pub embedded: usize,
+    /// Paragraph chunks left for background embedding.
+    pub embedding_deferred: usize,
+    /// Chunks given enriched text by extraction.
+    pub enriched: usize,
+    /// Topics extracted from the chunks, in first-seen order.
+    pub topics: Vec<String>,
+    pub timings: IngestTimings,
+}
+
+impl IngestReport {
+    pub(crate) fn duplicate_of(doc_id: i64) -> Self {
+        Self {
+            doc_id,
+            duplicate: Some(doc_id),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_duplicate(&self) -> bool {
+        self.duplicate.is_some()
+    }
+}
+
+/// Time spent in each ingest stage (ms).
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestTimings {
+    /// Chunking and storing the document.
+    pub store_ms: u64,
+    pub embed_ms: u64,
+    pub extract_ms: u64,
+}
+
+/// One finished consolidation run.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsolidationRun {
+    /// When the run finished (ms since the epoch).
+    pub finished_at: i64,
+    pub report: mindsage_consolidate::ConsolidationReport,
+}
+
+/// What forgetting a term deletes: the documents that mention it, with
+/// counts per subsystem. A plan from `Orchestrator::plan_forget` is a
+/// preview; the one `Orchestrator::forget` returns counts what was deleted.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgetPlan {
+    pub query: String,
+    pub documents: Vec<ForgetMatch>,
+    pub store: ForgetStoreCounts,
+    pub graph: ForgetGraphCounts,
+    /// Logged queries, saved searches and staged items mentioning the term.
+    pub cache: mindsage_store::MentionCounts,
+    /// Chunks of the matched documents, for subsystems that reference them.
+    #[serde(skip)]
+    pub chunk_ids: Vec<i64>,
+}
+
+impl ForgetPlan {
+    pub fn doc_ids(&self) -> Vec<i64> {
+        self.documents.iter().map(|d| d.doc_id).collect()
+    }
+}
+
+/// A document a forget would delete.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgetMatch {
+    pub doc_id: i64,
+    /// `metadata.title`, else `metadata.filename`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Start of the document's text.
+    pub snippet: String,
+    pub reason: ForgetReason,
+}
+
+/// Why a document matched a forget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgetReason {
+    /// The term appears in its text, metadata or a chunk's enriched text.
+    Mention,
+    /// A full-text hit containing every word of the term, in any order.
+    Search,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgetStoreCounts {
+    pub documents: usize,
+    pub chunks: usize,
+    pub embeddings: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgetGraphCounts {
+    /// Document-topic edges.
+    pub topic_links: usize,
+}
+
 /// Runtime status information.
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct RuntimeStatus {
     pub tier: mindsage_core::CapabilityTier,
     pub budget: ResourceBudget,
@@ -66,4 +206,6 @@ pub struct RuntimeStatus {
     pub active_verbs: Vec<Verb>,
     #[serde(rename = "pendingDistill")]
     pub pending_distill: usize,
+    /// Estimated memory use against `budget.max_memory_mb`.
+    pub memory: crate::memory::MemoryUsage,
 }
@@ -0,0 +1,517 @@
+//! Privacy audit log — a record of every request sent to an external LLM.
+//!
+//! Entries are appended to `data/audit.log` as JSON lines and synced to
+//! disk before the request goes out, so a crash cannot lose one. Each entry
+//! holds a SHA-256 of the full prompt rather than the prompt itself unless
+//! `MINDSAGE_AUDIT_PROMPTS=on`. The file rotates to `audit.log.1` ..
+//! `audit.log.N` once it passes `AUDIT_LOG_MAX_BYTES`.
+//!
+//! In read-only mode entries are kept in memory instead, so the data
+//! directory is left untouched; they are listed the same way.
+//!
+//! When the provider reports token usage, including prompt cache hits, a
+//! usage line naming the entry is appended after the answer; listing folds
+//! it into the entry.
+//!
+//! Forgetting a term removes the entries that sent its chunks or, when
+//! prompts are stored, mention it, and appends a tombstone that records the
+//! forget without naming the term.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use mindsage_chat::{ChatMessage, LLMProvider, ProviderUsage};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::{IntoParams, ToSchema};
+
+/// Size at which the active log is rotated.
+pub const AUDIT_LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+/// Rotated files kept besides the active log.
+pub const AUDIT_LOG_ROTATIONS: usize = 3;
+/// Rough characters-per-token ratio for prompt token estimates.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// What an outbound LLM request was for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditPurpose {
+    Chat,
+    ChatStream,
+    Topics,
+    Digest,
+}
+
+/// One outbound LLM request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub id: String,
+    /// Unix milliseconds when the request was about to be sent.
+    pub timestamp: i64,
+    pub purpose: AuditPurpose,
+    pub provider: String,
+    pub model: String,
+    /// SHA-256 (hex) of the JSON-encoded messages sent.
+    pub prompt_hash: String,
+    /// Chunks whose text was included as context.
+    pub chunk_ids: Vec<i64>,
+    /// Estimated prompt tokens (characters / 4).
+    pub prompt_tokens: usize,
+    /// Completion token limit requested.
+    pub max_tokens: usize,
+    /// Whether PII in the prompt was replaced with tokens before sending.
+    pub anonymized: bool,
+    /// Full prompt, only when prompt storage is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<Vec<ChatMessage>>,
+    /// Usage the provider reported once it answered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ProviderUsage>,
+}
+
+/// Usage reported for an earlier entry, appended after the answer.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageLine {
+    entry_id: String,
+    usage: ProviderUsage,
+}
+
+/// Record of a forget, appended after the entries it removed. Holds counts
+/// only: neither the term nor the documents.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgetTombstone {
+    pub forget_id: String,
+    /// Unix milliseconds.
+    pub timestamp: i64,
+    /// What was deleted, per subsystem.
+    #[schema(value_type = Object)]
+    pub counts: serde_json::Value,
+}
+
+/// An outbound request about to be recorded.
+pub struct AuditRequest<'a> {
+    pub purpose: AuditPurpose,
+    pub provider: LLMProvider,
+    pub model: &'a str,
+    pub messages: &'a [ChatMessage],
+    pub chunk_ids: Vec<i64>,
+    pub max_tokens: usize,
+    pub anonymized: bool,
+}
+
+/// Filters and paging for listing entries, newest first.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    pub page: Option<usize>,
+    pub page_size: Option<usize>,
+    pub provider: Option<String>,
+    pub purpose: Option<AuditPurpose>,
+    /// Unix milliseconds, inclusive.
+    pub since: Option<i64>,
+    /// Unix milliseconds, exclusive.
+    pub until: Option<i64>,
+}
+
+impl AuditQuery {
+    /// 1-based page number.
+    pub fn page(&self) -> usize {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size.unwrap_or(50).clamp(1, 500)
+    }
+}
+
+/// Append-only JSONL audit log with size-based rotation.
+pub struct AuditLog {
+    path: PathBuf,
+    store_prompts: bool,
+    max_bytes: u64,
+    /// Serializes appends and rotation.
+    write_lock: Mutex<()>,
+    /// Lines not written to disk, in read-only mode.
+    unsaved: Option<Mutex<Vec<String>>>,
+}
+
+impl AuditLog {
+    pub fn new(path: impl AsRef<Path>, store_prompts: bool) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            store_prompts,
+            max_bytes: AUDIT_LOG_MAX_BYTES,
+            write_lock: Mutex::new(()),
+            unsaved: None,
+        }
+    }
+
+    /// Keep new entries in memory rather than appending them to the file.
+    pub fn unsaved(mut self) -> Self {
+        self.unsaved = Some(Mutex::new(Vec::new()));
+        self
+    }
+
+    /// Append an entry for `request` and sync it to disk.
+    pub fn record(&self, request: AuditRequest<'_>) -> std::io::Result<AuditEntry> {
+        let encoded = serde_json::to_string(request.messages)?;
+        let prompt_chars: usize = request.messages.iter().map(|m| m.content.chars().count()).sum();
+        let entry = AuditEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            purpose: request.purpose,
+            provider: request.provider.to_string(),
+            model: request.model.to_string(),
+            prompt_hash: hex::encode(Sha256::digest(encoded.as_bytes())),
+            chunk_ids: request.chunk_ids,
+            prompt_tokens: prompt_chars.div_ceil(CHARS_PER_TOKEN),
+            max_tokens: request.max_tokens,
+            anonymized: request.anonymized,
+            prompt: self.store_prompts.then(|| request.messages.to_vec()),
+            usage: None,
+        };
+        self.append(&entry)?;
+        Ok(entry)
+    }
+
+    /// Append the usage the provider reported for entry `entry_id`.
+    pub fn record_usage(&self, entry_id: &str, usage: ProviderUsage) -> std::io::Result<()> {
+        self.append(&UsageLine {
+            entry_id: entry_id.to_string(),
+            usage,
+        })
+    }
+
+    /// Entries whose prompt included one of `chunk_ids`, or whose stored
+    /// prompt mentions `term` (ignoring case), with their usage lines. With
+    /// `dry_run` they are only counted. Returns the number of entries.
+    pub fn forget(&self, chunk_ids: &HashSet<i64>, term: &str, dry_run: bool) -> std::io::Result<usize> {
+        let needle = term.trim().to_lowercase();
+        let matches = |entry: &AuditEntry| {
+            entry.chunk_ids.iter().any(|id| chunk_ids.contains(id))
+                || (!needle.is_empty()
+                    && entry.prompt.iter().flatten().any(|m| m.content.to_lowercase().contains(&needle)))
+        };
+        let _guard = self.write_lock.lock();
+        let paths: Vec<PathBuf> = (1..=AUDIT_LOG_ROTATIONS)
+            .rev()
+            .map(|n| self.rotated_path(n))
+            .chain([self.path.clone()])
+            .collect();
+
+        let mut forgotten = HashSet::new();
+        let mut files = Vec::new();
+        for path in paths {
+            let content = match std::fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            for line in content.lines() {
+                if let Ok(entry) = serde_json::from_str::<AuditEntry>(line) {
+                    if matches(&entry) {
+                        forgotten.insert(entry.id);
+                    }
+                }
+            }
+            files.push((path, content));
+        }
+        if let Some(unsaved) = &self.unsaved {
+            for line in unsaved.lock().iter() {
+                if let Ok(entry) = serde_json::from_str::<AuditEntry>(line) {
+                    if matches(&entry) {
+                        forgotten.insert(entry.id);
+                    }
+                }
+            }
+        }
+        if dry_run || forgotten.is_empty() {
+            return Ok(forgotten.len());
+        }
+
+        let keep = |line: &str| {
+            let id = serde_json::from_str::<AuditEntry>(line)
+                .map(|e| e.id)
+                .or_else(|_| serde_json::from_str::<UsageLine>(line).map(|u| u.entry_id));
+            !id.is_ok_and(|id| forgotten.contains(&id))
+        };
+        if let Some(unsaved) = &self.unsaved {
+            unsaved.lock().retain(|line| keep(line));
+            return Ok(forgotten.len());
+        }
+        for (path, content) in files {
+            let kept: String = content.lines().filter(|l| keep(l)).map(|l| format!("{}\n", l)).collect();
+            if kept.len() == content.len() {
+                continue;
+            }
+            let mut temp = path.clone().into_os_string();
+            temp.push(".tmp");
+            let mut file = File::create(&temp)?;
+            file.write_all(kept.as_bytes())?;
+            file.sync_data()?;
+            std::fs::rename(&temp, &path)?;
+        }
+        Ok(forgotten.len())
+    }
+
+    /// Append a tombstone for a forget.
+    pub fn record_forget(&self, tombstone: &ForgetTombstone) -> std::io::Result<()> {
+        self.append(tombstone)
+    }
+
+    /// Tombstones of past forgets, newest first.
+    pub fn forget_tombstones(&self) -> std::io::Result<Vec<ForgetTombstone>> {
+        let mut tombstones = Vec::new();
+        for path in (1..=AUDIT_LOG_ROTATIONS).rev().map(|n| self.rotated_path(n)).chain([self.path.clone()]) {
+            let file = match File::open(&path) {
+                Ok(f) => f,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            for line in BufReader::new(file).lines() {
+                tombstones.extend(serde_json::from_str::<ForgetTombstone>(&line?).ok());
+            }
+        }
+        if let Some(unsaved) = &self.unsaved {
+            tombstones.extend(unsaved.lock().iter().filter_map(|l| serde_json::from_str(l).ok()));
+        }
+        tombstones.reverse();
+        Ok(tombstones)
+    }
+
+    fn append(&self, value: &impl Serialize) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(value)?;
+        if let Some(unsaved) = &self.unsaved {
+            unsaved.lock().push(line);
+            return Ok(());
+        }
+        line.push('\n');
+
+        let _guard = self.write_lock.lock();
+        self.rotate_if_full()?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        file.sync_data()
+    }
+
+    /// Entries matching `query`, newest first, and the total match count.
+    pub fn list(&self, query: &AuditQuery) -> std::io::Result<(Vec<AuditEntry>, usize)> {
+        let mut matching = Vec::new();
+        let mut usage = HashMap::new();
+        let mut consider = |line: &str| {
+            let Ok(entry) = serde_json::from_str::<AuditEntry>(line) else {
+                if let Ok(reported) = serde_json::from_str::<UsageLine>(line) {
+                    usage.insert(reported.entry_id, reported.usage);
+                }
+                return;
+            };
+            if query.provider.as_ref().is_some_and(|p| !p.eq_ignore_ascii_case(&entry.provider))
+                || query.purpose.is_some_and(|p| p != entry.purpose)
+                || query.since.is_some_and(|t| entry.timestamp < t)
+                || query.until.is_some_and(|t| entry.timestamp >= t)
+            {
+                return;
+            }
+            matching.push(entry);
+        };
+        // Oldest rotation first so the combined list is chronological
+        for path in (1..=AUDIT_LOG_ROTATIONS).rev().map(|n| self.rotated_path(n)).chain([self.path.clone()]) {
+            let file = match File::open(&path) {
+                Ok(f) => f,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            for line in BufReader::new(file).lines() {
+                consider(&line?);
+            }
+        }
+        if let Some(unsaved) = &self.unsaved {
+            unsaved.lock().iter().for_each(|line| consider(line));
+        }
+        matching.reverse();
+        for entry in &mut matching {
+            entry.usage = usage.remove(&entry.id);
+        }
+
+        let total = matching.len();
+        let page_size = query.page_size();
+        let entries = matching
+            .into_iter()
+            .skip((query.page() - 1) * page_size)
+            .take(page_size)
+            .collect();
+        Ok((entries, total))
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    /// Shift `audit.log` → `.1` → `.2` … once it reaches the size limit;
+    /// the oldest rotation is dropped.
+    fn rotate_if_full(&self) -> std::io::Result<()> {
+        let size = match std::fs::metadata(&self.path) {
+            Ok(m) => m.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if size < self.max_bytes {
+            return Ok(());
+        }
+        for n in (1..AUDIT_LOG_ROTATIONS).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated_path(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated_path(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn messages(text: &str) -> Vec<ChatMessage> {
+        vec![ChatMessage {
+            role: "user".into(),
+            content: text.into(),
+        }]
+    }
+
+    fn request<'a>(messages: &'a [ChatMessage], provider: LLMProvider) -> AuditRequest<'a> {
+        AuditRequest {
+            purpose: AuditPurpose::Chat,
+            provider,
+            model: "m",
+            messages,
+            chunk_ids: vec![1, 2],
+            max_tokens: 100,
+            anonymized: false,
+        }
+    }
+
+    #[test]
+    fn test_hash_only_unless_prompts_enabled() {
+        let dir = TempDir::new().unwrap();
+        let msgs = messages("what did I write about rust?");
+
+        let log = AuditLog::new(dir.path().join("audit.log"), false);
+        let entry = log.record(request(&msgs, LLMProvider::OpenAI)).unwrap();
+        assert_eq!(entry.prompt_hash.len(), 64);
+        assert_eq!(entry.prompt_tokens, 7);
+        let raw = std::fs::read_to_string(dir.path().join("audit.log")).unwrap();
+        assert!(!raw.contains("rust"));
+
+        let debug_log = AuditLog::new(dir.path().join("debug.log"), true);
+        let entry = debug_log.record(request(&msgs, LLMProvider::OpenAI)).unwrap();
+        assert_eq!(entry.prompt.unwrap()[0].content, msgs[0].content);
+    }
+
+    #[test]
+    fn test_reported_usage_is_folded_into_its_entry() {
+        let dir = TempDir::new().unwrap();
+        let log = AuditLog::new(dir.path().join("audit.log"), false);
+        let msgs = messages("hello");
+        let first = log.record(request(&msgs, LLMProvider::Anthropic)).unwrap();
+        let second = log.record(request(&msgs, LLMProvider::Anthropic)).unwrap();
+        let usage = ProviderUsage {
+            prompt_tokens: 1500,
+            completion_tokens: 40,
+            cached_tokens: 1400,
+            cache_write_tokens: 0,
+        };
+        log.record_usage(&second.id, usage).unwrap();
+
+        let (entries, total) = log.list(&AuditQuery::default()).unwrap();
+        assert_eq!(total, 2);
+        assert_eq!((entries[0].id.as_str(), entries[0].usage), (second.id.as_str(), Some(usage)));
+        assert_eq!((entries[1].id.as_str(), entries[1].usage), (first.id.as_str(), None));
+
+        // Read-only: listed after the file's entries, never written
+        let before = std::fs::read(dir.path().join("audit.log")).unwrap();
+        let unsaved = AuditLog::new(dir.path().join("audit.log"), false).unsaved();
+        let third = unsaved.record(request(&msgs, LLMProvider::Groq)).unwrap();
+        unsaved.record_usage(&third.id, usage).unwrap();
+        let (entries, total) = unsaved.list(&AuditQuery::default()).unwrap();
+        assert_eq!(total, 3);
+        assert_eq!((entries[0].id.as_str(), entries[0].usage), (third.id.as_str(), Some(usage)));
+        assert_eq!(std::fs::read(dir.path().join("audit.log")).unwrap(), before);
+    }
+
+    #[test]
+    fn test_rotation_and_filtered_listing() {
+        let dir = TempDir::new().unwrap();
+        let mut log = AuditLog::new(dir.path().join("audit.log"), false);
+        log.max_bytes = 600;
+        let msgs = messages("hello");
+        for i in 0..12 {
+            let provider = if i % 3 == 0 { LLMProvider::Anthropic } else { LLMProvider::Groq };
+            log.record(request(&msgs, provider)).unwrap();
+        }
+        assert!(dir.path().join("audit.log.1").exists());
+        assert!(!dir.path().join("audit.log.4").exists());
+
+        let (all, total) = log.list(&AuditQuery::default()).unwrap();
+        assert_eq!(total, all.len());
+        assert!(all.windows(2).all(|w| w[0].timestamp >= w[1].timestamp));
+
+        let anthropic = AuditQuery {
+            provider: Some("anthropic".into()),
+            ..Default::default()
+        };
+        let (entries, total) = log.list(&anthropic).unwrap();
+        assert!(total > 0 && entries.iter().all(|e| e.provider == "anthropic"));
+
+        let paged = AuditQuery {
+            page: Some(2),
+            page_size: Some(2),
+            ..Default::default()
+        };
+        let (page, _) = log.list(&paged).unwrap();
+        assert_eq!(page.iter().map(|e| &e.id).collect::<Vec<_>>(), all[2..4].iter().map(|e| &e.id).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_forget_removes_entries_and_keeps_a_tombstone() {
+        let dir = TempDir::new().unwrap();
+        let log = AuditLog::new(dir.path().join("audit.log"), true);
+        let about = messages("tell me about Marguerite");
+        let other = messages("hello");
+        let by_chunk = log.record(request(&other, LLMProvider::Groq)).unwrap();
+        let by_prompt = AuditRequest { chunk_ids: vec![9], ..request(&about, LLMProvider::Groq) };
+        let by_prompt = log.record(by_prompt).unwrap();
+        let kept = log.record(AuditRequest { chunk_ids: vec![9], ..request(&other, LLMProvider::Groq) }).unwrap();
+        let usage = ProviderUsage { prompt_tokens: 10, completion_tokens: 2, cached_tokens: 0, cache_write_tokens: 0 };
+        log.record_usage(&by_prompt.id, usage).unwrap();
+
+        let chunks = HashSet::from([2]);
+        assert_eq!(log.forget(&chunks, "marguerite", true).unwrap(), 2);
+        assert_eq!(log.list(&AuditQuery::default()).unwrap().1, 3);
+        assert_eq!(log.forget(&chunks, "marguerite", false).unwrap(), 2);
+        let (entries, _) = log.list(&AuditQuery::default()).unwrap();
+        assert_eq!(entries.iter().map(|e| &e.id).collect::<Vec<_>>(), [&kept.id]);
+        let raw = std::fs::read_to_string(dir.path().join("audit.log")).unwrap();
+        assert!(!raw.contains("Marguerite") && !raw.contains(&by_chunk.id) && !raw.contains(&by_prompt.id));
+
+        let tombstone = ForgetTombstone {
+            forget_id: "f1".into(),
+            timestamp: 1,
+            counts: serde_json::json!({"audit": 2}),
+        };
+        log.record_forget(&tombstone).unwrap();
+        let tombstones = log.forget_tombstones().unwrap();
+        assert_eq!(tombstones.len(), 1);
+        assert_eq!(tombstones[0].counts["audit"], 2);
+        assert_eq!(log.list(&AuditQuery::default()).unwrap().1, 1);
+    }
+}
@@ -0,0 +1,318 @@
+//! Char offsets for chunks stored before offsets were recorded.
+//!
+//! Older versions stored chunks with NULL `char_start`/`char_end`, so a
+//! hit in such a chunk cannot be highlighted in its document. This job
+//! finds each chunk's text in its document again and writes the offsets:
+//! an exact match first, then a match of the same words with any
+//! whitespace between them, then the window of the document sharing the
+//! most words with the chunk, for documents whose text was edited a
+//! little since. Chunks found nowhere keep NULL offsets and are listed on
+//! the job. Batches are time-boxed and paused between, like re-extraction.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::jobs::{Job, JobStatus, JOB_BATCH_PAUSE};
+use crate::state::AppState;
+
+/// Chunks fetched per batch.
+const BACKFILL_BATCH_SIZE: usize = 50;
+/// Matching time allowed per batch before yielding.
+const BACKFILL_BATCH_BUDGET: Duration = Duration::from_millis(200);
+/// Unlocatable chunk ids kept on the job; the count covers the rest.
+const MAX_LISTED_UNLOCATABLE: usize = 100;
+/// Share of a chunk's words a document window must contain to count as
+/// the chunk's place.
+const FUZZY_MIN_OVERLAP: f64 = 0.8;
+
+/// Fields of the offset backfill job beyond the common ones.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillProgress {
+    /// Chunks whose text appears verbatim in the document.
+    pub exact: usize,
+    /// Chunks placed by whitespace-tolerant or word-overlap matching.
+    pub fuzzy: usize,
+    pub unlocatable: usize,
+    /// Ids of the first unlocatable chunks.
+    pub unlocatable_chunks: Vec<i64>,
+}
+
+/// Progress of the offset backfill job.
+pub type BackfillJob = Job<BackfillProgress>;
+
+/// Where a chunk's text was found, as a byte range of the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Located {
+    Exact(usize, usize),
+    Fuzzy(usize, usize),
+}
+
+impl Located {
+    fn range(self) -> (usize, usize) {
+        match self {
+            Located::Exact(start, end) | Located::Fuzzy(start, end) => (start, end),
+        }
+    }
+}
+
+/// Find `chunk` in `document`, preferring a place at or after `hint` (the
+/// previous chunk's start, since chunks may overlap).
+pub fn locate_chunk(document: &str, chunk: &str, hint: usize) -> Option<Located> {
+    if chunk.is_empty() {
+        return None;
+    }
+    let after_hint = document
+        .get(hint..)
+        .and_then(|rest| rest.find(chunk))
+        .map(|pos| hint + pos);
+    if let Some(start) = after_hint.or_else(|| document.find(chunk)) {
+        return Some(Located::Exact(start, start + chunk.len()));
+    }
+
+    let doc_words = word_spans(document);
+    let chunk_words: Vec<&str> = chunk.split_whitespace().collect();
+    if chunk_words.is_empty() || doc_words.len() < chunk_words.len() {
+        return None;
+    }
+    let word = |i: usize| &document[doc_words[i].0..doc_words[i].1];
+    let n = chunk_words.len();
+    let span = |first: usize| Located::Fuzzy(doc_words[first].0, doc_words[first + n - 1].1);
+
+    // The same words with other whitespace between them
+    if let Some(first) = (0..=doc_words.len() - n).find(|&i| (0..n).all(|j| word(i + j) == chunk_words[j])) {
+        return Some(span(first));
+    }
+
+    // The window of the chunk's length sharing the most words with it
+    let mut needed: HashMap<&str, usize> = HashMap::new();
+    for w in &chunk_words {
+        *needed.entry(w).or_insert(0) += 1;
+    }
+    let mut in_window: HashMap<&str, usize> = HashMap::new();
+    let mut matched = 0;
+    let mut best = (0, 0);
+    for i in 0..doc_words.len() {
+        let added = word(i);
+        let count = in_window.entry(added).or_insert(0);
+        if *count < needed.get(added).copied().unwrap_or(0) {
+            matched += 1;
+        }
+        *count += 1;
+        if i >= n {
+            let removed = word(i - n);
+            let count = in_window.get_mut(removed).unwrap();
+            *count -= 1;
+            if *count < needed.get(removed).copied().unwrap_or(0) {
+                matched -= 1;
+            }
+        }
+        if i + 1 >= n && matched > best.0 {
+            best = (matched, i + 1 - n);
+        }
+    }
+    if (best.0 as f64) < FUZZY_MIN_OVERLAP * n as f64 {
+        return None;
+    }
+    // Drop words at either edge the chunk does not have, such as the end
+    // of the previous sentence when the document lost a word since
+    let window = best.1..best.1 + n;
+    let first = window.clone().find(|&i| needed.contains_key(word(i)))?;
+    let last = window.rev().find(|&i| needed.contains_key(word(i)))?;
+    Some(Located::Fuzzy(doc_words[first].0, doc_words[last].1))
+}
+
+/// Byte ranges of the whitespace-separated words of `text`.
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+/// Locate up to the started job's `total` chunks without offsets and
+/// record what is found. Blocking; run it on a blocking thread.
+pub fn run_backfill(state: &AppState) {
+    let Some(job) = state.offset_backfill.current() else {
+        return;
+    };
+    let mut after_id = 0;
+    let mut processed = 0;
+    // Previous chunk start per document and level, as the search hint
+    let mut hints: HashMap<(i64, i32), usize> = HashMap::new();
+
+    let result = loop {
+        let limit = BACKFILL_BATCH_SIZE.min(job.total - processed);
+        if limit == 0 {
+            break Ok(());
+        }
+        let chunks = match state.store.get_chunks_without_offsets(after_id, limit) {
+            Ok(c) if c.is_empty() => break Ok(()),
+            Ok(c) => c,
+            Err(e) => break Err(e.to_string()),
+        };
+
+        let started = Instant::now();
+        let mut documents: HashMap<i64, Option<String>> = HashMap::new();
+        let (mut batch, mut exact, mut fuzzy) = (0, 0, 0);
+        let mut unlocatable = Vec::new();
+        for chunk in &chunks {
+            after_id = chunk.id;
+            batch += 1;
+            let document = documents
+                .entry(chunk.doc_id)
+                .or_insert_with(|| state.store.get_document(chunk.doc_id).ok().flatten().map(|d| d.text));
+            let hint = hints.get(&(chunk.doc_id, chunk.level)).copied().unwrap_or(0);
+            let located = document.as_deref().and_then(|text| locate_chunk(text, &chunk.text, hint));
+            let offsets = located.and_then(|l| {
+                let (start, end) = l.range();
+                Some((l, i32::try_from(start).ok()?, i32::try_from(end).ok()?))
+            });
+            match offsets {
+                Some((located, start, end)) => match state.store.set_chunk_offsets(chunk.id, start, end) {
+                    Ok(_) => {
+                        hints.insert((chunk.doc_id, chunk.level), start as usize);
+                        match located {
+                            Located::Exact(..) => exact += 1,
+                            Located::Fuzzy(..) => fuzzy += 1,
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to store offsets of chunk {}: {}", chunk.id, e);
+                        unlocatable.push(chunk.id);
+                    }
+                },
+                None => unlocatable.push(chunk.id),
+            }
+            if started.elapsed() >= BACKFILL_BATCH_BUDGET {
+                break;
+            }
+        }
+
+        processed += batch;
+        state.offset_backfill.update(|j| {
+            j.processed = processed;
+            let progress = &mut j.detail;
+            progress.exact += exact;
+            progress.fuzzy += fuzzy;
+            progress.unlocatable += unlocatable.len();
+            let room = MAX_LISTED_UNLOCATABLE.saturating_sub(progress.unlocatable_chunks.len());
+            progress.unlocatable_chunks.extend(unlocatable.into_iter().take(room));
+        });
+        std::thread::sleep(JOB_BATCH_PAUSE);
+    };
+
+    if let Err(e) = &result {
+        error!("Offset backfill failed: {}", e);
+    }
+    if let Some(j) = state.offset_backfill.finish(result).filter(|j| j.status == JobStatus::Completed) {
+        info!(
+            "Backfilled offsets of {} chunks ({} exact, {} fuzzy, {} not found)",
+            j.processed, j.detail.exact, j.detail.fuzzy, j.detail.unlocatable
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    #[test]
+    fn test_locate_chunk_exact_whitespace_and_edited() {
+        let document = "The garden plan.\n\nWater the tomatoes every morning at six.\nThe Raspberry Pi logs soil moisture.";
+        let chunk = "Water the tomatoes every morning at six.";
+        let start = document.find(chunk).unwrap();
+        assert_eq!(locate_chunk(document, chunk, 0), Some(Located::Exact(start, start + chunk.len())));
+
+        // Chunked with single spaces where the document has a line break
+        let collapsed = "morning at six. The Raspberry Pi";
+        let expected_start = document.find("morning").unwrap();
+        let expected_end = document.find(" logs").unwrap();
+        assert_eq!(locate_chunk(document, collapsed, 0), Some(Located::Fuzzy(expected_start, expected_end)));
+
+        // The document was edited after chunking: one word of nine changed
+        let edited = document.replace("tomatoes", "peppers");
+        let Some(Located::Fuzzy(start, end)) = locate_chunk(&edited, chunk, 0) else {
+            panic!("edited text not located");
+        };
+        assert_eq!(&edited[start..end], "Water the peppers every morning at six.");
+
+        assert_eq!(locate_chunk(document, "Nothing like this is in the document at all", 0), None);
+        assert_eq!(locate_chunk(document, "   ", 0), None);
+    }
+
+    #[test]
+    fn test_locate_chunk_prefers_the_hint() {
+        let document = "same words here. other text. same words here.";
+        let second = document.rfind("same").unwrap();
+        assert_eq!(locate_chunk(document, "same words here.", 0), Some(Located::Exact(0, 16)));
+        assert_eq!(locate_chunk(document, "same words here.", 1), Some(Located::Exact(second, second + 16)));
+        // A hint inside a multi-byte char is ignored
+        assert_eq!(locate_chunk("é x", "x", 1), Some(Located::Exact(3, 4)));
+    }
+
+    #[test]
+    fn test_backfill_writes_offsets_and_lists_the_rest() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+
+        // Chunks from an older version, without offsets; the document was
+        // edited slightly after chunking
+        let text = "Trip notes.\n\nWe hiked to the  lake on Saturday morning.\n\nThe cabin had no signal at all.";
+        let doc_id = state.store.add_document(text, Default::default()).unwrap();
+        let chunks = [
+            "Trip notes.",
+            "We hiked to the lake on Saturday morning.",
+            "The cabin had no phone signal at all.",
+            "Completely unrelated words that are nowhere.",
+        ];
+        let ids: Vec<i64> = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                state.store.add_chunk(doc_id, chunk, i as i32, 1, None, None, None, None, None, None).unwrap()
+            })
+            .collect();
+        assert!(!state.store.document_offsets_complete(doc_id).unwrap());
+
+        state.offset_backfill.start(10, BackfillProgress::default()).unwrap();
+        run_backfill(&state);
+        let job = state.offset_backfill.current().unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+        let progress = &job.detail;
+        assert_eq!((job.processed, progress.exact, progress.fuzzy, progress.unlocatable), (4, 1, 2, 1));
+        assert_eq!(progress.unlocatable_chunks, vec![ids[3]]);
+
+        let offsets = |id: i64| {
+            let chunk = state.store.get_chunk(id).unwrap().unwrap();
+            (chunk.char_start, chunk.char_end)
+        };
+        assert_eq!(offsets(ids[0]), (Some(0), Some(11)));
+        let (start, end) = offsets(ids[1]);
+        assert_eq!(&text[start.unwrap() as usize..end.unwrap() as usize], "We hiked to the  lake on Saturday morning.");
+        let (start, end) = offsets(ids[2]);
+        assert_eq!(&text[start.unwrap() as usize..end.unwrap() as usize], "The cabin had no signal at all.");
+        assert_eq!(offsets(ids[3]), (None, None));
+        assert_eq!(state.store.count_chunks_without_offsets().unwrap(), 1);
+    }
+}
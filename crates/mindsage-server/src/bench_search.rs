@@ -0,0 +1,156 @@
+//! `mindsage bench-search` — rank a relevance fixture with each search
+//! configuration and print nDCG, MRR and recall side by side.
+//!
+//! A fixture that brings its own `documents` is searched in a scratch store
+//! under the system temp directory, removed afterwards; one that names
+//! existing `docId`s or `chunkId`s is searched in the profile's store, which
+//! is only read.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use mindsage_infer::EmbedderBackend;
+use mindsage_resolve::bench::{Candidate, RankedHit, RelevanceFixture};
+use mindsage_resolve::ResolverKind;
+use mindsage_store::{SearchHit, SqliteStore};
+
+/// Hits scored per query when `-k` is not given.
+const DEFAULT_K: usize = 10;
+/// RRF constant of the `hybrid` candidate, as in `POST /vector-store/search`.
+const RRF_K: usize = 60;
+
+const USAGE: &str = "Usage: mindsage bench-search [fixtures.json] [-k <n>] [--data-dir <dir>]";
+
+/// Run the command with the arguments after `bench-search`.
+pub fn run(args: &[String], default_data_dir: PathBuf) -> anyhow::Result<()> {
+    let mut fixture_path = None;
+    let mut k = DEFAULT_K;
+    let mut data_dir = default_data_dir;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "-k" => {
+                k = rest
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .filter(|&k| k > 0)
+                    .ok_or_else(|| anyhow::anyhow!("-k needs a positive number\n{}", USAGE))?;
+            }
+            "--data-dir" => {
+                data_dir = rest.next().map(PathBuf::from).ok_or_else(|| anyhow::anyhow!(USAGE))?;
+            }
+            _ if fixture_path.is_none() && !arg.starts_with('-') => fixture_path = Some(PathBuf::from(arg)),
+            _ => anyhow::bail!("Unexpected argument {}\n{}", arg, USAGE),
+        }
+    }
+
+    let fixture = match &fixture_path {
+        Some(path) => RelevanceFixture::load(path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?,
+        None => RelevanceFixture::builtin(),
+    };
+    let config = mindsage_core::MindSageConfig::from_env(&data_dir)?;
+    let embedder = mindsage_infer::NormalizingEmbedder::wrap(
+        mindsage_infer::create_embedder(&data_dir.join("models")),
+        mindsage_infer::TextNormalization::parse(&config.embed_normalization),
+    );
+
+    if fixture.documents.is_empty() {
+        let store = SqliteStore::open_with_encryption(
+            &config.data_paths.vectordb,
+            config.embedding_dim,
+            mindsage_store::crypto::EncryptionConfig::from_env()?,
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to open store: {}", e))?;
+        store.set_embedding_model(embedder.model_id());
+        println!("Searching {} with {} queries", config.data_paths.vectordb.display(), fixture.queries.len());
+        bench(&store, &fixture, &embedder, k, false)
+    } else {
+        let scratch = std::env::temp_dir().join(format!("mindsage-bench-{}", uuid::Uuid::new_v4()));
+        let result = SqliteStore::open(&scratch, config.embedding_dim)
+            .map_err(|e| anyhow::anyhow!("Failed to open scratch store: {}", e))
+            .and_then(|store| {
+                store.set_embedding_model(embedder.model_id());
+                println!(
+                    "Searching {} fixture documents with {} queries",
+                    fixture.documents.len(),
+                    fixture.queries.len()
+                );
+                bench(&store, &fixture, &embedder, k, true)
+            });
+        let _ = std::fs::remove_dir_all(&scratch);
+        result
+    }
+}
+
+fn bench(
+    store: &SqliteStore,
+    fixture: &RelevanceFixture,
+    embedder: &Arc<dyn EmbedderBackend>,
+    k: usize,
+    populate: bool,
+) -> anyhow::Result<()> {
+    let keys = if populate {
+        let keys = fixture.populate(store)?;
+        if embedder.is_available() {
+            embed_chunks(store, embedder.as_ref())?;
+        }
+        keys
+    } else {
+        Default::default()
+    };
+
+    let mut candidates = vec![
+        Candidate::resolver(store, ResolverKind::Keyword),
+        Candidate::resolver(store, ResolverKind::Entity),
+    ];
+    if embedder.is_available() {
+        candidates.push(Candidate::new("vector", |query, k| {
+            embedder
+                .embed_query(query)
+                .and_then(|e| store.vector_search(&e.embedding, Some(1), k).ok())
+                .map(ranked)
+                .unwrap_or_default()
+        }));
+        candidates.push(Candidate::new("hybrid", |query, k| {
+            let Some(e) = embedder.embed_query(query) else {
+                return Vec::new();
+            };
+            let mut hits = ranked(store.hybrid_search(query, &e.embedding, Some(1), k * 3, k * 3, RRF_K).unwrap_or_default());
+            hits.truncate(k);
+            hits
+        }));
+    } else {
+        println!("No embedding model loaded: comparing keyword resolvers only");
+    }
+
+    print!("{}", mindsage_resolve::bench::run(fixture, &keys, &candidates, k).table());
+    Ok(())
+}
+
+fn ranked(hits: Vec<SearchHit>) -> Vec<RankedHit> {
+    hits.into_iter()
+        .map(|h| RankedHit { chunk_id: h.chunk_id, doc_id: h.doc_id })
+        .collect()
+}
+
+/// Embed every chunk of the scratch store.
+fn embed_chunks(store: &SqliteStore, embedder: &dyn EmbedderBackend) -> anyhow::Result<()> {
+    loop {
+        let chunks = store.get_chunks_without_embedding(64)?;
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        let results = embedder.embed_batch(&texts);
+        let mut embedded = 0;
+        for (chunk, result) in chunks.iter().zip(results) {
+            if let Some(result) = result {
+                store.add_chunk_embedding(chunk.id, &result.embedding)?;
+                embedded += 1;
+            }
+        }
+        if embedded == 0 {
+            anyhow::bail!("The embedding model produced no embeddings");
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Retention cleanup of captured browser conversations.
+//!
+//! `BrowserManager::apply_retention` deletes and trims conversations by
+//! each site's policy; this module re-indexes the trimmed conversations
+//! whose documents are to be refreshed, and runs the cleanup every
+//! `cleanup_interval_hours` while a policy is set.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use mindsage_browser::{RetentionReport, TrimmedDocuments};
+use mindsage_ingest::Ingester;
+use tracing::warn;
+
+use crate::health;
+use crate::routes::browser::index_conversation;
+use crate::state::AppState;
+
+/// How often the schedule checks whether a cleanup is due.
+const CLEANUP_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Apply the retention policies now and refresh the documents of trimmed
+/// conversations. Blocking.
+pub fn run_browser_cleanup(state: &AppState) -> RetentionReport {
+    let mut report = state.browser_manager.apply_retention(chrono::Utc::now());
+    if state.browser_manager.get_config().retention_documents != TrimmedDocuments::Refresh {
+        return report;
+    }
+    let ingester = Ingester::new(&state.store);
+    for trimmed in report.trimmed.iter().filter(|t| t.document_id.is_some()) {
+        let Some(conversation) = state.browser_manager.get_conversation(&trimmed.id) else {
+            continue;
+        };
+        match index_conversation(state, &ingester, &conversation) {
+            Ok(_) => report.documents_refreshed += 1,
+            Err(e) => {
+                warn!("Failed to refresh trimmed conversation {}: {}", trimmed.id, e);
+                report.documents_failed += 1;
+            }
+        }
+    }
+    if report.documents_refreshed > 0 {
+        crate::indexing::embed_pending_chunks(state);
+    }
+    report
+}
+
+/// Whether a scheduled cleanup should run: a policy is set and the last
+/// cleanup is older than the interval (or there was none).
+fn cleanup_due(state: &AppState, now: chrono::DateTime<chrono::Utc>) -> bool {
+    let config = state.browser_manager.get_config();
+    if config.retention.values().all(|policy| policy.is_empty()) {
+        return false;
+    }
+    let interval = chrono::Duration::seconds((config.cleanup_interval_hours * 3600.0) as i64);
+    config
+        .last_cleanup_at
+        .and_then(|at| chrono::DateTime::parse_from_rfc3339(&at).ok())
+        .map_or(true, |at| now - at.with_timezone(&chrono::Utc) >= interval)
+}
+
+/// Run the retention cleanup on its schedule until shutdown.
+pub fn start_browser_cleanup(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = state.shutdown.wait() => break,
+                _ = interval.tick() => {}
+            }
+            if !cleanup_due(&state, chrono::Utc::now()) {
+                continue;
+            }
+            let report = state.blocking(run_browser_cleanup).await;
+            if report.documents_failed > 0 {
+                state.health.record_failure(
+                    health::BROWSER_CLEANUP,
+                    format!("{} trimmed conversations could not be re-indexed", report.documents_failed),
+                );
+            } else {
+                state.health.record_success(health::BROWSER_CLEANUP);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mindsage_browser::{CapturePayload, CapturedMessage};
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    fn capture(messages: usize) -> CapturePayload {
+        CapturePayload {
+            site: "claude".to_string(),
+            conversation_id: "conv".to_string(),
+            conversation_url: "https://claude.ai/chat/conv".to_string(),
+            title: Some("Sourdough".to_string()),
+            messages: (0..messages)
+                .map(|i| CapturedMessage {
+                    id: format!("m{}", i),
+                    conversation_id: "conv".to_string(),
+                    role: ["user", "assistant"][i % 2].to_string(),
+                    content: format!("step {} of the sourdough starter", i),
+                    timestamp: "2026-01-01T00:00:00Z".to_string(),
+                    site: "claude".to_string(),
+                    metadata: None,
+                })
+                .collect(),
+            full_conversation: Some(true),
+            previous_id: None,
+        }
+    }
+
+    #[test]
+    fn test_trimmed_conversation_document_is_refreshed() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+        let manager = &state.browser_manager;
+        manager.process_capture(capture(6));
+        let conversation = manager.get_conversation("conv").unwrap();
+        index_conversation(&state, &Ingester::new(&state.store), &conversation).unwrap();
+        let doc_id = manager.get_conversation("conv").unwrap().document_id.unwrap();
+
+        let now = chrono::Utc::now();
+        assert!(!cleanup_due(&state, now));
+        manager.update_config(serde_json::json!({"retention": {"claude": {"maxMessages": 2}}}));
+        assert!(cleanup_due(&state, now));
+
+        let report = run_browser_cleanup(&state);
+        assert_eq!((report.trimmed.len(), report.messages_removed, report.documents_refreshed), (1, 4, 1));
+        let text = state.store.get_document(doc_id).unwrap().unwrap().text;
+        assert!(text.starts_with("system: [4 earlier messages removed by the retention policy]"));
+        assert!(!text.contains("step 0 "));
+        assert!(text.contains("step 5 "));
+        let conversation = manager.get_conversation("conv").unwrap();
+        assert_eq!((conversation.indexed, conversation.document_id), (true, Some(doc_id)));
+        assert_eq!(manager.get_capture_stats().reclaimed_bytes, report.bytes_reclaimed);
+        assert!(!cleanup_due(&state, now + chrono::Duration::hours(1)));
+        assert!(cleanup_due(&state, now + chrono::Duration::hours(25)));
+    }
+}
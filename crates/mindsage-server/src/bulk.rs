@@ -0,0 +1,208 @@
+//! Bulk document operations — dry-run previews redeemed by confirm tokens,
+//! and trackable background jobs for large selections.
+//!
+//! A destructive bulk call always starts with a preview: the selection is
+//! resolved to document IDs and parked under a single-use token. The
+//! confirm call acts on exactly those IDs, so documents added between the
+//! preview and the confirm are never touched.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+
+/// How long a preview's confirm token stays valid.
+pub const PREVIEW_TTL: Duration = Duration::from_secs(600);
+/// Selections larger than this run as background jobs.
+pub const BULK_JOB_THRESHOLD: usize = 200;
+/// Keep at most this many finished jobs.
+const MAX_FINISHED_JOBS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkOperation {
+    Delete,
+    UpdateMetadata,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkJobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// A previewed selection awaiting confirmation.
+#[derive(Debug, Clone)]
+pub struct BulkPreview {
+    pub operation: BulkOperation,
+    pub ids: Vec<i64>,
+    pub patch: Option<serde_json::Value>,
+    created: Instant,
+}
+
+/// Progress of a bulk operation running in the background.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkJob {
+    pub id: String,
+    pub operation: BulkOperation,
+    pub status: BulkJobStatus,
+    pub total: usize,
+    pub processed: usize,
+    pub affected: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub started_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<i64>,
+}
+
+/// Pending previews and bulk jobs.
+#[derive(Default)]
+pub struct BulkOps {
+    previews: Mutex<HashMap<String, BulkPreview>>,
+    jobs: RwLock<HashMap<String, BulkJob>>,
+}
+
+impl BulkOps {
+    /// Park a resolved selection and return its confirm token.
+    pub fn create_preview(
+        &self,
+        operation: BulkOperation,
+        ids: Vec<i64>,
+        patch: Option<serde_json::Value>,
+        now: Instant,
+    ) -> String {
+        let token = uuid::Uuid::new_v4().to_string();
+        let mut previews = self.previews.lock();
+        previews.retain(|_, p| now.saturating_duration_since(p.created) < PREVIEW_TTL);
+        previews.insert(
+            token.clone(),
+            BulkPreview {
+                operation,
+                ids,
+                patch,
+                created: now,
+            },
+        );
+        token
+    }
+
+    /// Redeem a confirm token. Tokens are single-use; expired tokens and
+    /// tokens issued for a different operation are rejected (and consumed).
+    pub fn take_preview(&self, token: &str, operation: BulkOperation, now: Instant) -> Option<BulkPreview> {
+        let preview = self.previews.lock().remove(token)?;
+        let fresh = now.saturating_duration_since(preview.created) < PREVIEW_TTL;
+        (fresh && preview.operation == operation).then_some(preview)
+    }
+
+    /// Register a running job and return its ID.
+    pub fn start_job(&self, operation: BulkOperation, total: usize) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut jobs = self.jobs.write();
+
+        let mut finished: Vec<(String, i64)> = jobs
+            .values()
+            .filter(|j| j.status != BulkJobStatus::Running)
+            .map(|j| (j.id.clone(), j.completed_at.unwrap_or(0)))
+            .collect();
+        if finished.len() >= MAX_FINISHED_JOBS {
+            finished.sort_by_key(|(_, t)| *t);
+            for (old, _) in finished.iter().take(finished.len() + 1 - MAX_FINISHED_JOBS) {
+                jobs.remove(old);
+            }
+        }
+
+        jobs.insert(
+            id.clone(),
+            BulkJob {
+                id: id.clone(),
+                operation,
+                status: BulkJobStatus::Running,
+                total,
+                processed: 0,
+                affected: 0,
+                error: None,
+                started_at: chrono::Utc::now().timestamp_millis(),
+                completed_at: None,
+            },
+        );
+        id
+    }
+
+    /// Add processed IDs to a running job.
+    pub fn record_progress(&self, job_id: &str, processed: usize) {
+        if let Some(job) = self.jobs.write().get_mut(job_id) {
+            job.processed += processed;
+        }
+    }
+
+    /// Mark a job finished with its outcome.
+    pub fn finish_job(&self, job_id: &str, result: Result<usize, String>) {
+        if let Some(job) = self.jobs.write().get_mut(job_id) {
+            match result {
+                Ok(affected) => {
+                    job.status = BulkJobStatus::Completed;
+                    job.affected = affected;
+                    job.processed = job.total;
+                }
+                Err(e) => {
+                    job.status = BulkJobStatus::Failed;
+                    job.error = Some(e);
+                }
+            }
+            job.completed_at = Some(chrono::Utc::now().timestamp_millis());
+        }
+    }
+
+    pub fn get_job(&self, job_id: &str) -> Option<BulkJob> {
+        self.jobs.read().get(job_id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirm_token_is_single_use_and_bound_to_operation() {
+        let ops = BulkOps::default();
+        let now = Instant::now();
+
+        let token = ops.create_preview(BulkOperation::Delete, vec![1, 2, 3], None, now);
+        let preview = ops.take_preview(&token, BulkOperation::Delete, now).unwrap();
+        assert_eq!(preview.ids, vec![1, 2, 3]);
+        assert!(ops.take_preview(&token, BulkOperation::Delete, now).is_none());
+
+        let token = ops.create_preview(BulkOperation::Delete, vec![1], None, now);
+        assert!(ops.take_preview(&token, BulkOperation::UpdateMetadata, now).is_none());
+        assert!(ops.take_preview("not-a-token", BulkOperation::Delete, now).is_none());
+    }
+
+    #[test]
+    fn test_confirm_token_expires() {
+        let ops = BulkOps::default();
+        let now = Instant::now();
+        let token = ops.create_preview(BulkOperation::UpdateMetadata, vec![7], None, now);
+        let later = now + PREVIEW_TTL + Duration::from_secs(1);
+        assert!(ops.take_preview(&token, BulkOperation::UpdateMetadata, later).is_none());
+    }
+
+    #[test]
+    fn test_job_lifecycle() {
+        let ops = BulkOps::default();
+        let id = ops.start_job(BulkOperation::Delete, 1000);
+        ops.record_progress(&id, 500);
+        assert_eq!(ops.get_job(&id).unwrap().processed, 500);
+
+        ops.finish_job(&id, Ok(998));
+        let job = ops.get_job(&id).unwrap();
+        assert_eq!(job.status, BulkJobStatus::Completed);
+        assert_eq!(job.processed, 1000);
+        assert_eq!(job.affected, 998);
+        assert!(job.completed_at.is_some());
+    }
+}
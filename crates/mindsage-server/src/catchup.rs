@@ -0,0 +1,191 @@
+//! Progress of the catch-up passes over the ingest journal.
+//!
+//! One pass per step runs at a time. A pass asked for while another runs
+//! is folded into it: the running pass looks for newly journaled documents
+//! once more before it ends, so they are not left for the next trigger.
+//! The ETA is based on the throughput of the last minute.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use mindsage_core::Event;
+use mindsage_store::PendingStep;
+use parking_lot::Mutex;
+
+pub use mindsage_api_types::CatchupProgress;
+
+/// Span of the throughput the ETA is based on.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(60);
+/// Least time between progress events of a pass.
+const EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Pass {
+    progress: CatchupProgress,
+    /// `(when, done)` after each batch, over about `THROUGHPUT_WINDOW`.
+    samples: VecDeque<(Instant, usize)>,
+    /// Another pass was asked for while this one ran.
+    requested: bool,
+    last_event: Option<Instant>,
+}
+
+/// The current (or most recent) catch-up pass of each step.
+#[derive(Default)]
+pub struct CatchupTracker {
+    passes: Mutex<HashMap<PendingStep, Pass>>,
+}
+
+impl CatchupTracker {
+    /// Start a pass over `step` at `done` of `total` documents. `None`
+    /// while another pass over it runs; that one then looks again before
+    /// it ends.
+    pub fn begin(&self, step: PendingStep, done: usize, total: usize, started_at: i64) -> Option<CatchupPass<'_>> {
+        let mut passes = self.passes.lock();
+        if let Some(pass) = passes.get_mut(&step).filter(|p| p.progress.running) {
+            pass.requested = true;
+            return None;
+        }
+        let progress = CatchupProgress {
+            step: step.as_str().to_string(),
+            running: true,
+            done,
+            total,
+            rate: None,
+            eta_secs: None,
+            started_at,
+            finished_at: None,
+        };
+        passes.insert(
+            step,
+            Pass {
+                progress,
+                samples: VecDeque::from([(Instant::now(), done)]),
+                requested: false,
+                last_event: None,
+            },
+        );
+        Some(CatchupPass { tracker: self, step })
+    }
+
+    /// The passes run this session, in step order.
+    pub fn progress(&self) -> Vec<CatchupProgress> {
+        let passes = self.passes.lock();
+        PendingStep::ALL
+            .iter()
+            .filter_map(|step| passes.get(step))
+            .map(|pass| pass.progress.clone())
+            .collect()
+    }
+}
+
+/// A running pass. Dropping it before it finishes (shutdown, an error)
+/// marks it stopped.
+pub struct CatchupPass<'a> {
+    tracker: &'a CatchupTracker,
+    step: PendingStep,
+}
+
+impl CatchupPass<'_> {
+    /// Record that the pass went through `done` of `total` documents.
+    /// Returns the progress event when one is due.
+    pub fn advance(&self, done: usize, total: usize) -> Option<Event> {
+        self.advance_at(done, total, Instant::now())
+    }
+
+    fn advance_at(&self, done: usize, total: usize, now: Instant) -> Option<Event> {
+        let mut passes = self.tracker.passes.lock();
+        let pass = passes.get_mut(&self.step)?;
+        pass.samples.push_back((now, done));
+        while pass.samples.len() > 2 && now.duration_since(pass.samples[1].0) >= THROUGHPUT_WINDOW {
+            pass.samples.pop_front();
+        }
+        let (since, done_since) = pass.samples[0];
+        let secs = now.duration_since(since).as_secs_f64();
+        let rate = (secs > 0.0).then(|| done.saturating_sub(done_since) as f64 / secs);
+
+        let progress = &mut pass.progress;
+        progress.done = done;
+        progress.total = total.max(done);
+        progress.rate = rate;
+        progress.eta_secs = rate
+            .filter(|r| *r > 0.0)
+            .map(|r| ((progress.total - done) as f64 / r).ceil() as u64);
+
+        if pass.last_event.is_some_and(|at| now.duration_since(at) < EVENT_INTERVAL) {
+            return None;
+        }
+        pass.last_event = Some(now);
+        Some(catchup_event(progress, false))
+    }
+
+    /// End the pass at the end of the journal, returning its final
+    /// progress; `None` when another pass was asked for meanwhile, so the
+    /// caller should look for newly journaled documents first.
+    pub fn try_finish(&self) -> Option<CatchupProgress> {
+        let mut passes = self.tracker.passes.lock();
+        let pass = passes.get_mut(&self.step)?;
+        if std::mem::take(&mut pass.requested) {
+            return None;
+        }
+        let progress = &mut pass.progress;
+        progress.running = false;
+        progress.total = progress.done;
+        progress.eta_secs = None;
+        progress.finished_at = Some(chrono::Utc::now().timestamp_millis());
+        Some(progress.clone())
+    }
+}
+
+impl Drop for CatchupPass<'_> {
+    fn drop(&mut self) {
+        if let Some(pass) = self.tracker.passes.lock().get_mut(&self.step) {
+            pass.progress.running = false;
+            pass.progress.eta_secs = None;
+        }
+    }
+}
+
+/// The `indexing.catchup` event for `progress`.
+pub fn catchup_event(progress: &CatchupProgress, finished: bool) -> Event {
+    Event::IndexingCatchup {
+        step: progress.step.clone(),
+        done: progress.done,
+        total: progress.total,
+        eta_secs: progress.eta_secs,
+        finished,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passes_are_folded_and_eta_follows_recent_throughput() {
+        let tracker = CatchupTracker::default();
+        let pass = tracker.begin(PendingStep::Embed, 100, 1000, 0).unwrap();
+        assert!(tracker.begin(PendingStep::Embed, 0, 0, 0).is_none());
+        assert!(tracker.begin(PendingStep::Enrich, 0, 10, 0).is_some());
+
+        // 10 documents a second for a while, then 50 a second
+        let start = Instant::now();
+        for i in 1..=10u64 {
+            pass.advance_at(100 + 100 * i as usize, 1000, start + Duration::from_secs(10 * i));
+        }
+        let progress = &tracker.progress()[0];
+        assert_eq!((progress.done, progress.total), (1100, 1100));
+        for i in 1..=6u64 {
+            pass.advance_at(1100 + 500 * i as usize, 5000, start + Duration::from_secs(100 + 10 * i));
+        }
+        let progress = &tracker.progress()[0];
+        assert_eq!(progress.done, 4100);
+        assert!((progress.rate.unwrap() - 50.0).abs() < 1e-9, "{:?}", progress.rate);
+        assert_eq!(progress.eta_secs, Some(18));
+
+        // The folded request makes the pass look once more
+        assert!(pass.try_finish().is_none());
+        let finished = pass.try_finish().unwrap();
+        assert!(!finished.running && finished.finished_at.is_some());
+        drop(pass);
+        assert!(tracker.begin(PendingStep::Embed, 0, 0, 0).is_some());
+    }
+}
@@ -0,0 +1,160 @@
+//! CORS policy and network exposure of the API.
+//!
+//! Browsers may only call the API from the origins in
+//! `MindSageConfig.cors_origins`: exact origins, or `*` given explicitly.
+//! Methods and headers are the ones the web UI uses. The LocalSend
+//! protocol listener has no CORS layer; phones reach it directly.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use mindsage_core::MindSageConfig;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::warn;
+
+/// How long browsers may cache a preflight response.
+const PREFLIGHT_MAX_AGE: Duration = Duration::from_secs(3600);
+
+const ALLOWED_METHODS: [Method; 6] = [Method::GET, Method::HEAD, Method::POST, Method::PUT, Method::PATCH, Method::DELETE];
+
+/// Build the CORS layer for the API from `config.cors_origins`. Origins
+/// that are not valid header values are skipped with a warning.
+pub fn cors_layer(config: &MindSageConfig) -> CorsLayer {
+    let allow_origin = if config.cors_origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins: Vec<HeaderValue> = config
+            .cors_origins
+            .iter()
+            .filter_map(|origin| match HeaderValue::from_str(origin) {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    warn!("Ignoring invalid CORS origin {:?}", origin);
+                    None
+                }
+            })
+            .collect();
+        AllowOrigin::list(origins)
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(ALLOWED_METHODS)
+        .allow_headers([
+            header::ACCEPT,
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            HeaderName::from_static("x-profile"),
+            HeaderName::from_static("x-request-id"),
+            HeaderName::from_static(crate::routes::files::CHUNK_SHA256_HEADER),
+        ])
+        .expose_headers([HeaderName::from_static("x-request-id"), header::RETRY_AFTER])
+        .max_age(PREFLIGHT_MAX_AGE)
+}
+
+/// Who can reach a listener bound to `addr`, for the startup log.
+pub fn describe_exposure(addr: IpAddr) -> String {
+    if addr.is_loopback() {
+        "this machine only".to_string()
+    } else if addr.is_unspecified() {
+        "all network interfaces".to_string()
+    } else {
+        format!("interface {}", addr)
+    }
+}
+
+/// The CORS policy, for the startup log.
+pub fn describe_cors(config: &MindSageConfig) -> String {
+    if config.cors_origins.iter().any(|o| o == "*") {
+        "any origin".to_string()
+    } else if config.cors_origins.is_empty() {
+        "no cross-origin requests".to_string()
+    } else {
+        config.cors_origins.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::profiles::Profiles;
+    use crate::test_support::TestState;
+
+    fn app(dir: &tempfile::TempDir, origins: &[&str]) -> axum::Router {
+        let cors_origins = origins.iter().map(|o| o.to_string()).collect();
+        let state = Arc::new(TestState::new(dir).config(|c| c.cors_origins = cors_origins).build());
+        crate::routes::build_app(Arc::new(Profiles::start(state)))
+    }
+
+    fn preflight(origin: &str) -> Request<Body> {
+        Request::builder()
+            .method("OPTIONS")
+            .uri("/api/vector-store/search")
+            .header("origin", origin)
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "content-type,x-profile")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn get(origin: &str) -> Request<Body> {
+        Request::builder()
+            .uri("/api/stats")
+            .header("origin", origin)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_listed_origins_are_allowed_and_others_rejected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let app = app(&dir, &["http://localhost:5173"]);
+
+        let response = app.clone().oneshot(preflight("http://localhost:5173")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers["access-control-allow-origin"], "http://localhost:5173");
+        assert!(headers["access-control-allow-methods"].to_str().unwrap().contains("POST"));
+        assert!(headers["access-control-allow-headers"].to_str().unwrap().contains("x-profile"));
+
+        let response = app.clone().oneshot(get("http://localhost:5173")).await.unwrap();
+        assert_eq!(response.headers()["access-control-allow-origin"], "http://localhost:5173");
+        assert!(response.headers()["access-control-expose-headers"].to_str().unwrap().contains("x-request-id"));
+
+        // An unlisted origin gets no CORS headers, so the browser blocks it
+        for request in [preflight("http://evil.example"), get("http://evil.example")] {
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert!(response.headers().get("access-control-allow-origin").is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wildcard_must_be_explicit() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let response = app(&dir, &["*"]).oneshot(get("http://anything.example")).await.unwrap();
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
+
+        // The default allows only the API's own loopback origins
+        let config = MindSageConfig::from_env(dir.path()).unwrap();
+        let default_origin = format!("http://localhost:{}", config.port);
+        assert!(config.cors_origins.contains(&default_origin));
+        assert!(config.bind_address.is_loopback());
+        let response = app(&dir, &[&default_origin]).oneshot(get("http://192.168.1.20:3000")).await.unwrap();
+        assert!(response.headers().get("access-control-allow-origin").is_none());
+    }
+
+    #[test]
+    fn test_exposure_descriptions() {
+        assert_eq!(describe_exposure("127.0.0.1".parse().unwrap()), "this machine only");
+        assert_eq!(describe_exposure("0.0.0.0".parse().unwrap()), "all network interfaces");
+        assert_eq!(describe_exposure("192.168.1.5".parse().unwrap()), "interface 192.168.1.5");
+    }
+}
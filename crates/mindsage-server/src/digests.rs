@@ -0,0 +1,219 @@
+//! Digests of recently captured content — the runtime's aggregation with a
+//! narrative from the configured LLM, and the optional schedule that
+//! consolidates the store and stores a digest every
+//! `MINDSAGE_DIGEST_INTERVAL_DAYS` days.
+//!
+//! Without a provider, or when the call fails, the digest is stored
+//! without a narrative. Periods without documents are not stored.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use mindsage_chat::{providers, topics, ChatMessage};
+use mindsage_core::Result;
+use mindsage_runtime::digest::{self, NARRATIVE_MAX_TOKENS};
+use mindsage_runtime::{Digest, DigestPeriod};
+use tracing::{info, warn};
+
+use crate::audit::{AuditPurpose, AuditRequest};
+use crate::health;
+use crate::state::AppState;
+
+/// Time allowed for the narrative.
+const NARRATIVE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often the schedule checks whether a digest is due.
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
+const DAY_MS: i64 = 86_400_000;
+
+const NARRATIVE_SYSTEM_PROMPT: &str =
+    "You summarize a personal knowledge base for its owner. Be brief, concrete and neutral. \
+     Do not invent anything that is not in the notes you are given.";
+
+/// Generate a digest of `period`, add a narrative when `narrative` is set
+/// and a provider is configured, and store it.
+pub async fn create_digest(state: &Arc<AppState>, period: DigestPeriod, narrative: bool) -> Result<Digest> {
+    let resolved = if narrative {
+        state.llm_config.read().resolve_provider()
+    } else {
+        None
+    };
+    let openai_base_url = state.llm_config.read().openai_base_url.clone();
+    let complete = resolved.map(|(provider, model, api_key)| {
+        move |messages: Vec<ChatMessage>, chunk_ids: Vec<i64>| async move {
+            state
+                .audit
+                .record(AuditRequest {
+                    purpose: AuditPurpose::Digest,
+                    provider,
+                    model: &model,
+                    messages: &messages,
+                    chunk_ids,
+                    max_tokens: NARRATIVE_MAX_TOKENS,
+                    anonymized: false,
+                })
+                .map_err(|e| format!("Failed to write audit entry: {}", e))?;
+            let client = reqwest::Client::new();
+            let stream = providers::stream_llm(
+                &client, provider, messages,
+                &model, &api_key,
+                0.3, NARRATIVE_MAX_TOKENS,
+                openai_base_url.as_deref(), None,
+            );
+            topics::collect_completion(stream).await
+        }
+    });
+    create_with(state, period, complete).await
+}
+
+/// Digest creation with the provider call supplied by the caller;
+/// `complete` is `None` when no narrative is wanted or possible.
+async fn create_with<F, Fut>(state: &Arc<AppState>, period: DigestPeriod, complete: Option<F>) -> Result<Digest>
+where
+    F: FnOnce(Vec<ChatMessage>, Vec<i64>) -> Fut,
+    Fut: Future<Output = std::result::Result<String, String>>,
+{
+    let mut digest = state
+        .blocking(move |state| state.orchestrator.digest(&state.store, period))
+        .await?;
+    if digest.documents == 0 {
+        return Ok(digest);
+    }
+
+    if let Some(complete) = complete {
+        let messages = vec![
+            ChatMessage {
+                role: "system".into(),
+                content: NARRATIVE_SYSTEM_PROMPT.into(),
+            },
+            ChatMessage {
+                role: "user".into(),
+                content: digest.narrative_prompt(),
+            },
+        ];
+        let chunk_ids = digest.representative_chunk_ids();
+        match tokio::time::timeout(NARRATIVE_TIMEOUT, complete(messages, chunk_ids)).await {
+            Ok(Ok(text)) if !text.trim().is_empty() => digest.narrative = Some(text.trim().to_string()),
+            Ok(Ok(_)) => warn!("Digest narrative was empty"),
+            Ok(Err(e)) => warn!("Digest narrative failed: {}", e),
+            Err(_) => warn!("Digest narrative timed out after {:?}", NARRATIVE_TIMEOUT),
+        }
+    }
+
+    state
+        .blocking(move |state| {
+            state.orchestrator.store_digest(&state.store, &state.embedder, &mut digest)?;
+            Ok(digest)
+        })
+        .await
+}
+
+/// Check hourly for a due digest while `digest_interval_days` is set.
+pub fn start_digest_schedule(state: Arc<AppState>) {
+    let days = state.config.digest_interval_days;
+    if days == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+        let mut last_run = None;
+        loop {
+            tokio::select! {
+                _ = state.shutdown.wait() => break,
+                _ = interval.tick() => {}
+            }
+            let now = chrono::Utc::now().timestamp_millis();
+            match run_scheduled(&state, days as i64, now, last_run).await {
+                Ok(None) => {}
+                Ok(Some(digest)) => {
+                    last_run = Some(now);
+                    state.health.record_success(health::DIGEST_SCHEDULE);
+                    info!("Scheduled digest covered {} documents", digest.documents);
+                }
+                Err(e) => state.health.record_failure(health::DIGEST_SCHEDULE, e),
+            }
+        }
+    });
+}
+
+/// Consolidate and create a digest when the last one ended at least `days`
+/// days before `now`. The digest covers the time since the last stored
+/// digest or scheduled run (`last_run`), or `days` days when there is none.
+/// Returns `None` when no digest was due.
+async fn run_scheduled(state: &Arc<AppState>, days: i64, now: i64, last_run: Option<i64>) -> Result<Option<Digest>> {
+    let stored = state.db(|store| digest::list_digests(store, 1)).await?;
+    let last_end = stored.first().map(|d| d.period.end).max(last_run);
+    if last_end.is_some_and(|end| now - end < days * DAY_MS) {
+        return Ok(None);
+    }
+    state.blocking(|state| state.orchestrator.consolidate(&state.store)).await;
+    let period = DigestPeriod {
+        start: last_end.unwrap_or(now - days * DAY_MS),
+        end: now,
+    };
+    create_digest(state, period, true).await.map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use parking_lot::Mutex;
+    use tempfile::TempDir;
+
+    use crate::test_support::test_state;
+
+    fn ingest(state: &AppState, text: &str) {
+        let metadata = serde_json::json!({"source": "upload"});
+        state.orchestrator.ingest(&state.store, &state.embedder, text, text, &metadata, None).unwrap();
+    }
+
+    fn now() -> i64 {
+        chrono::Utc::now().timestamp_millis()
+    }
+
+    #[tokio::test]
+    async fn test_narrative_is_stored_with_the_digest() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        ingest(&state, "The sourdough starter doubled overnight after feeding it rye flour.");
+
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let recorded = sent.clone();
+        let complete = move |messages: Vec<ChatMessage>, _: Vec<i64>| {
+            recorded.lock().extend(messages);
+            std::future::ready(Ok::<_, String>(" A week of bread baking. ".to_string()))
+        };
+        let period = DigestPeriod::days_before(now() + 1000, 7);
+        let digest = create_with(&state, period, Some(complete)).await.unwrap();
+        assert_eq!(digest.narrative.as_deref(), Some("A week of bread baking."));
+        assert!(sent.lock()[1].content.contains("New documents: 1"));
+
+        let stored = state.store.get_document(digest.doc_id.unwrap()).unwrap().unwrap();
+        assert!(stored.text.contains("A week of bread baking."));
+        assert_eq!(stored.metadata.unwrap()["source"], "digest");
+    }
+
+    #[tokio::test]
+    async fn test_schedule_runs_when_due() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        ingest(&state, "Notes from the quarterly planning meeting about the billing migration.");
+
+        let first = run_scheduled(&state, 7, now() + 1000, None).await.unwrap().unwrap();
+        assert!(first.doc_id.is_some());
+        assert_eq!(first.period.days(), 7);
+        // The stored digest is recent
+        assert!(run_scheduled(&state, 7, now() + 1000, None).await.unwrap().is_none());
+
+        // A week later the next digest starts where the last one ended
+        let later = first.period.end + 7 * DAY_MS;
+        let second = run_scheduled(&state, 7, later, None).await.unwrap().unwrap();
+        assert_eq!(second.period.start, first.period.end);
+        assert_eq!(second.documents, 0);
+        assert!(second.doc_id.is_none());
+        assert!(run_scheduled(&state, 7, later, Some(later)).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,163 @@
+//! Shared API error type — one JSON envelope and proper status codes for every route.
+//!
+//! Body shape:
+//! `{"code": "not_found", "message": "...", "status": 404, "error": "...", "details"?: {...}}`
+//!
+//! `error` duplicates `message` so clients that used to check for an
+//! `error` key on 200 responses keep working; `status` mirrors the HTTP
+//! status for clients migrating off that pattern.
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use tracing::error;
+
+/// An API error rendered as a JSON envelope with a matching status code.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+}
+
+pub type ApiResult<T> = std::result::Result<T, ApiError>;
+
+pub use mindsage_api_types::ErrorBody;
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized", message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "forbidden", message)
+    }
+
+    /// A mutating request refused because the server runs read-only.
+    pub fn read_only(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "read_only", message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", message)
+    }
+
+    pub fn service_unavailable(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::SERVICE_UNAVAILABLE, "service_unavailable", message)
+    }
+
+    /// Attach structured details (e.g. the conflicting content hash).
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// The JSON envelope for this error.
+    pub fn body(&self) -> serde_json::Value {
+        serde_json::to_value(ErrorBody {
+            code: self.code.to_string(),
+            message: self.message.clone(),
+            status: self.status.as_u16(),
+            error: self.message.clone(),
+            details: self.details.clone(),
+        })
+        .unwrap_or_default()
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if self.status.is_server_error() {
+            error!("{} {}: {}", self.status.as_u16(), self.code, self.message);
+        }
+        (self.status, Json(self.body())).into_response()
+    }
+}
+
+impl From<mindsage_core::Error> for ApiError {
+    fn from(err: mindsage_core::Error) -> Self {
+        use mindsage_core::Error;
+
+        let message = err.to_string();
+        match err {
+            Error::NotFound(_) => Self::new(StatusCode::NOT_FOUND, "not_found", message),
+            Error::DuplicateContent(hash) => {
+                Self::new(StatusCode::CONFLICT, "duplicate_content", "Duplicate content")
+                    .with_details(serde_json::json!({ "content_hash": hash }))
+            }
+            Error::Ingest(_) => Self::new(StatusCode::UNPROCESSABLE_ENTITY, "ingest_failed", message),
+            Error::Json(_) => Self::new(StatusCode::BAD_REQUEST, "invalid_json", message),
+            Error::Config(_) => Self::new(StatusCode::BAD_REQUEST, "invalid_config", message),
+            Error::InvalidMetadata(_) => Self::new(StatusCode::BAD_REQUEST, "invalid_metadata", message),
+            Error::Search(_) => Self::new(StatusCode::INTERNAL_SERVER_ERROR, "search_failed", message),
+            Error::Inference(_) => {
+                Self::new(StatusCode::SERVICE_UNAVAILABLE, "inference_unavailable", message)
+            }
+            Error::Http(_) => Self::new(StatusCode::BAD_GATEWAY, "upstream_error", message),
+            Error::Encryption(_) => Self::new(StatusCode::INTERNAL_SERVER_ERROR, "encryption_error", message),
+            Error::DimensionMismatch { expected, actual } => {
+                Self::new(StatusCode::INTERNAL_SERVER_ERROR, "dimension_mismatch", message)
+                    .with_details(serde_json::json!({ "expected": expected, "actual": actual }))
+            }
+            Error::Busy(_) => Self::new(StatusCode::SERVICE_UNAVAILABLE, "database_busy", message),
+            Error::ReadOnly(_) => Self::read_only(message),
+            Error::Storage(_) | Error::Database(_) | Error::Io(_) | Error::Internal(_) => {
+                Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", message)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_shape() {
+        let err = ApiError::not_found("Document not found");
+        let body = err.body();
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["message"], "Document not found");
+        assert_eq!(body["error"], "Document not found");
+        assert_eq!(body["status"], 404);
+        assert!(body.get("details").is_none());
+    }
+
+    #[test]
+    fn test_core_error_mapping() {
+        let dup: ApiError = mindsage_core::Error::DuplicateContent("abc".into()).into();
+        assert_eq!(dup.status, StatusCode::CONFLICT);
+        assert_eq!(dup.code, "duplicate_content");
+        assert_eq!(dup.body()["details"]["content_hash"], "abc");
+
+        let db: ApiError = mindsage_core::Error::Database("locked".into()).into();
+        assert_eq!(db.status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        let missing: ApiError = mindsage_core::Error::NotFound("chunk 1".into()).into();
+        assert_eq!(missing.status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_into_response_status() {
+        let resp = ApiError::bad_request("bad").into_response();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,428 @@
+//! Feed syncs of `rss` connectors, on request and on their poll schedule.
+//!
+//! A sync requests the connector's feeds one after another, waiting
+//! `politenessDelayMs` between two requests, with the validators of each
+//! feed's last response so an unchanged feed answers 304. The newest
+//! `maxItemsPerFeed` entries of a changed feed go through the HTML
+//! boilerplate extractor and are upserted like connector exports. The run
+//! status lists what happened to each feed.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use mindsage_connectors::rss::{self, Feed, FeedValidators};
+use mindsage_connectors::{ConnectorConfig, ConnectorStatus, ConnectorType, FeedSyncCounts};
+use mindsage_core::Event;
+use mindsage_ingest::file::html::html_to_text;
+use mindsage_ingest::Ingester;
+use mindsage_store::UpsertOutcome;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use tracing::{info, warn};
+
+use crate::health;
+use crate::routes::connectors::index_export_document;
+use crate::state::AppState;
+
+/// Longest wait for one feed.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// Largest feed body read; a longer feed fails its sync.
+const MAX_FEED_BYTES: usize = 10 * 1024 * 1024;
+/// How often connectors are checked for a due poll.
+const POLL_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sync `connector`'s feeds in its started run (`start_run`), then finish
+/// the run. Fails when every feed failed.
+pub async fn run_feed_sync(
+    state: Arc<AppState>,
+    connector: ConnectorConfig,
+    cancel: Arc<AtomicBool>,
+) -> Result<(), String> {
+    let id = connector.id.clone();
+    let settings = connector.feed_settings();
+    let exports_dir = state.connector_manager.exports_dir_for(&id);
+    let mut validators = rss::load_feed_state(&exports_dir);
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+
+    let mut results: Vec<FeedSyncCounts> = Vec::new();
+    for (i, url) in settings.feeds.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        if i > 0 && settings.politeness_delay_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(settings.politeness_delay_ms)).await;
+        }
+        let counts = sync_feed(&state, &client, &id, url, settings.max_items_per_feed, &mut validators).await;
+        state.connector_manager.report_feed(&id, counts.clone());
+        results.push(counts);
+    }
+    if let Err(e) = rss::save_feed_state(&exports_dir, &validators) {
+        warn!("Failed to save the feed state of connector {}: {}", id, e);
+    }
+
+    let sum = |count: fn(&FeedSyncCounts) -> usize| results.iter().map(count).sum::<usize>();
+    let (new, updated, unchanged) = (sum(|c| c.new), sum(|c| c.updated), sum(|c| c.unchanged));
+    let failed = results.iter().filter(|c| c.error.is_some()).count();
+
+    if cancel.load(Ordering::Relaxed) {
+        state.connector_manager.finish_run(&id, "Sync cancelled".to_string(), 1);
+        return Ok(());
+    }
+    if failed > 0 && failed == results.len() {
+        let message = format!("All {} feeds failed", failed);
+        state.connector_manager.mark_error(&id, &message);
+        publish_sync(&state, &connector, "failed", 0, 0, Some(message.clone()));
+        return Err(message);
+    }
+
+    state.connector_manager.mark_import_complete(&id, connector.item_count + new);
+    state.connector_manager.finish_run(
+        &id,
+        format!(
+            "Synced {} feeds: {} new, {} updated, {} unchanged, {} failed",
+            results.len(),
+            new,
+            updated,
+            unchanged,
+            failed
+        ),
+        0,
+    );
+    info!(
+        "Feed sync of connector {}: {} new, {} updated, {} unchanged entries",
+        id, new, updated, unchanged
+    );
+    publish_sync(&state, &connector, "completed", new + updated + unchanged, new + updated, None);
+    Ok(())
+}
+
+fn publish_sync(
+    state: &AppState,
+    connector: &ConnectorConfig,
+    status: &str,
+    item_count: usize,
+    indexed: usize,
+    error: Option<String>,
+) {
+    state.events.publish(Event::ConnectorSync {
+        connector_id: connector.id.clone(),
+        name: connector.name.clone(),
+        status: status.to_string(),
+        item_count,
+        indexed,
+        error,
+    });
+}
+
+/// Request one feed and store its entries. The feed's validators are
+/// replaced once its entries are stored.
+async fn sync_feed(
+    state: &Arc<AppState>,
+    client: &reqwest::Client,
+    connector_id: &str,
+    url: &str,
+    max_items: usize,
+    validators: &mut HashMap<String, FeedValidators>,
+) -> FeedSyncCounts {
+    let mut counts = FeedSyncCounts {
+        url: url.to_string(),
+        ..Default::default()
+    };
+    match fetch_feed(client, url, validators.get(url)).await {
+        Ok(None) => counts.not_modified = true,
+        Ok(Some((body, fresh))) => match rss::parse_feed(&body) {
+            Ok(feed) => {
+                let (connector_id, feed_url) = (connector_id.to_string(), url.to_string());
+                counts = state
+                    .blocking(move |state| index_feed(state, &connector_id, &feed_url, &feed, max_items))
+                    .await;
+                if counts.error.is_none() {
+                    validators.insert(url.to_string(), fresh);
+                }
+            }
+            Err(e) => counts.error = Some(e),
+        },
+        Err(e) => counts.error = Some(e),
+    }
+    counts
+}
+
+/// The feed's body and validators; `None` when it answered 304.
+async fn fetch_feed(
+    client: &reqwest::Client,
+    url: &str,
+    validators: Option<&FeedValidators>,
+) -> Result<Option<(String, FeedValidators)>, String> {
+    let mut request = client.get(url);
+    if let Some(validators) = validators {
+        if let Some(etag) = &validators.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let mut response = request.send().await.map_err(|e| e.to_string())?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let fresh = FeedValidators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+    let too_large = || format!("Feed is larger than {} bytes", MAX_FEED_BYTES);
+    if response.content_length().is_some_and(|len| len > MAX_FEED_BYTES as u64) {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        if body.len() + chunk.len() > MAX_FEED_BYTES {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(Some((String::from_utf8_lossy(&body).into_owned(), fresh)))
+}
+
+/// Upsert the first `max_items` entries of `feed`, counting what changed.
+/// The last indexing error, if any, is reported.
+fn index_feed(state: &AppState, connector_id: &str, url: &str, feed: &Feed, max_items: usize) -> FeedSyncCounts {
+    let ingester = Ingester::new(&state.store);
+    let mut counts = FeedSyncCounts {
+        url: url.to_string(),
+        ..Default::default()
+    };
+    for entry in feed.entries.iter().take(max_items) {
+        let text = entry.content.as_deref().map(html_to_text).unwrap_or_default();
+        if text.is_empty() && entry.title.is_none() {
+            continue;
+        }
+        let doc = rss::entry_document(url, feed, entry, &text);
+        match index_export_document(&ingester, connector_id, &doc) {
+            Ok(Some(UpsertOutcome::Inserted(_))) => counts.new += 1,
+            Ok(Some(UpsertOutcome::Updated(_))) => counts.updated += 1,
+            Ok(_) | Err(mindsage_core::Error::DuplicateContent(_)) => counts.unchanged += 1,
+            Err(e) => {
+                warn!("Failed to index feed entry {} of {}: {}", entry.guid, url, e);
+                counts.error = Some(e.to_string());
+            }
+        }
+    }
+    counts
+}
+
+/// Whether `connector` is an `rss` connector due for its scheduled sync.
+fn poll_due(connector: &ConnectorConfig, now: chrono::DateTime<chrono::Utc>) -> bool {
+    let settings = connector.feed_settings();
+    if connector.connector_type != ConnectorType::Rss
+        || connector.status == ConnectorStatus::Paused
+        || settings.poll_minutes == 0
+        || settings.feeds.is_empty()
+    {
+        return false;
+    }
+    connector
+        .last_sync
+        .as_deref()
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        .map_or(true, |last| now.signed_duration_since(last) >= chrono::Duration::minutes(settings.poll_minutes as i64))
+}
+
+/// Check every minute for `rss` connectors due for a sync.
+pub fn start_feed_polling(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = state.shutdown.wait() => break,
+                _ = interval.tick() => {}
+            }
+            let now = chrono::Utc::now();
+            let due: Vec<ConnectorConfig> = state
+                .connector_manager
+                .list()
+                .into_iter()
+                .filter(|c| poll_due(c, now))
+                .collect();
+            for connector in due {
+                // A sync asked for by hand is already running
+                let Some(cancel) = state.connector_manager.start_run(&connector.id) else {
+                    continue;
+                };
+                match run_feed_sync(state.clone(), connector, cancel).await {
+                    Ok(()) => state.health.record_success(health::FEED_POLLING),
+                    Err(e) => state.health.record_failure(health::FEED_POLLING, e),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode as HttpStatus};
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::Router;
+    use mindsage_connectors::CreateConnectorRequest;
+    use parking_lot::Mutex;
+    use tempfile::TempDir;
+
+    use crate::test_support::test_state;
+
+    fn rss_feed(items: &[(&str, &str, &str)]) -> String {
+        let items: String = items
+            .iter()
+            .map(|(guid, title, body)| {
+                format!(
+                    "<item><guid>{}</guid><title>{}</title><pubDate>Tue, 05 Mar 2024 08:00:00 GMT</pubDate>\
+                     <description>Teaser</description><content:encoded><![CDATA[<nav>Menu</nav><p>{}</p>]]></content:encoded></item>",
+                    guid, title, body
+                )
+            })
+            .collect();
+        format!(
+            r#"<?xml version="1.0"?><rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/"><channel><title>Garden</title>{}</channel></rss>"#,
+            items
+        )
+    }
+
+    const ATOM: &str = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Dev Log</title>
+        <entry><id>tag:dev,1</id><title>Release notes</title><updated>2024-04-01T10:00:00Z</updated>
+        <summary type="html">&lt;p&gt;Version one ships the importer.&lt;/p&gt;</summary></entry></feed>"#;
+
+    /// Serves `/rss` (honouring `If-None-Match` against its ETag), `/atom`
+    /// (no validators), `/gone` (404) and `/huge` (past the size limit).
+    /// Returns the base URL and the number of full `/rss` responses.
+    async fn feed_server(rss: Arc<Mutex<String>>) -> (String, Arc<Mutex<usize>>) {
+        let served = Arc::new(Mutex::new(0));
+        let counter = served.clone();
+        let app = Router::new()
+            .route(
+                "/rss",
+                get(move |State(body): State<Arc<Mutex<String>>>, headers: HeaderMap| {
+                    let counter = counter.clone();
+                    async move {
+                        let body = body.lock().clone();
+                        let etag = format!("\"{}\"", body.len());
+                        if headers.get("if-none-match").and_then(|v| v.to_str().ok()) == Some(etag.as_str()) {
+                            return HttpStatus::NOT_MODIFIED.into_response();
+                        }
+                        *counter.lock() += 1;
+                        ([("etag", etag)], body).into_response()
+                    }
+                }),
+            )
+            .route("/atom", get(|| async { ATOM }))
+            .route("/gone", get(|| async { HttpStatus::NOT_FOUND }))
+            .route("/huge", get(|| async { "x".repeat(MAX_FEED_BYTES + 1) }))
+            .with_state(rss);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{}", addr), served)
+    }
+
+    async fn sync(state: &Arc<AppState>, id: &str) -> Vec<FeedSyncCounts> {
+        let connector = state.connector_manager.get(id).unwrap();
+        let cancel = state.connector_manager.start_run(id).unwrap();
+        run_feed_sync(state.clone(), connector, cancel).await.unwrap();
+        let status = state.connector_manager.get_run_status(id);
+        assert_eq!(status.exit_code, Some(0));
+        status.feeds
+    }
+
+    #[tokio::test]
+    async fn test_feed_sync_is_conditional_and_upserts_by_guid() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let rss = Arc::new(Mutex::new(rss_feed(&[
+            ("p3", "Mulching", "Mulch after rain."),
+            ("p2", "Pruning", "Cut above an outward bud."),
+            ("p1", "Seeds", "Sow thinly."),
+        ])));
+        let (base, served) = feed_server(rss.clone()).await;
+        let connector = state.connector_manager.create(CreateConnectorRequest {
+            name: "Feeds".into(),
+            connector_type: ConnectorType::Rss,
+            config: serde_json::json!({
+                "feeds": [
+                    format!("{}/rss", base),
+                    format!("{}/atom", base),
+                    format!("{}/gone", base),
+                    format!("{}/huge", base),
+                ],
+                "maxItemsPerFeed": 2,
+                "politenessDelayMs": 0,
+            }),
+        });
+
+        let feeds = sync(&state, &connector.id).await;
+        assert_eq!(feeds.len(), 4);
+        assert_eq!((feeds[0].new, feeds[0].updated, feeds[0].unchanged), (2, 0, 0));
+        assert_eq!(feeds[1].new, 1);
+        assert_eq!(feeds[2].error.as_deref(), Some("HTTP 404 Not Found"));
+        assert!(feeds[3].error.as_deref().unwrap().starts_with("Feed is larger than"));
+
+        let rss_url = format!("{}/rss", base);
+        let doc = state
+            .store
+            .find_document_by_external_id("rss", &format!("{}#p2", rss_url))
+            .unwrap()
+            .unwrap();
+        assert_eq!(doc.text, "Pruning\n\nCut above an outward bud.");
+        assert_eq!(doc.created_at, 1_709_625_600_000);
+        assert!(state.store.find_document_by_external_id("rss", &format!("{}#p1", rss_url)).unwrap().is_none());
+        assert_eq!(state.connector_manager.get(&connector.id).unwrap().item_count, 3);
+
+        // Unchanged: the RSS feed answers 304, the Atom feed is read again
+        let feeds = sync(&state, &connector.id).await;
+        assert!(feeds[0].not_modified);
+        assert_eq!(*served.lock(), 1);
+        assert_eq!((feeds[1].new, feeds[1].unchanged), (0, 1));
+
+        // An edited entry replaces its document
+        *rss.lock() = rss_feed(&[
+            ("p3", "Mulching", "Mulch after rain."),
+            ("p2", "Pruning", "Cut above an outward bud, at an angle."),
+        ]);
+        let feeds = sync(&state, &connector.id).await;
+        assert_eq!((feeds[0].new, feeds[0].updated, feeds[0].unchanged), (0, 1, 1));
+        let updated = state.store.get_document(doc.id).unwrap().unwrap();
+        assert!(updated.text.ends_with("at an angle."));
+    }
+
+    #[test]
+    fn test_poll_due_follows_the_schedule() {
+        let mut connector: ConnectorConfig = serde_json::from_value(serde_json::json!({
+            "id": "1", "name": "Feeds", "type": "rss", "status": "connected",
+            "config": { "feeds": ["https://garden.example/feed"], "pollMinutes": 30 }
+        }))
+        .unwrap();
+        let now = chrono::Utc::now();
+        assert!(poll_due(&connector, now));
+        connector.last_sync = Some((now - chrono::Duration::minutes(10)).to_rfc3339());
+        assert!(!poll_due(&connector, now));
+        assert!(poll_due(&connector, now + chrono::Duration::minutes(25)));
+        connector.status = ConnectorStatus::Paused;
+        assert!(!poll_due(&connector, now + chrono::Duration::minutes(25)));
+    }
+}
@@ -0,0 +1,213 @@
+//! Health of background subsystems.
+//!
+//! Each background task records its runs here: when it last ran, when it
+//! last succeeded, and its last error with the number of failures in a row.
+//! `GET /api/stats/background` lists every subsystem that has run, and
+//! `GET /api/health/ready` names those whose last run failed as degraded.
+//! Catch-up tasks that fail are restarted with exponential backoff instead
+//! of giving up until the next server start.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use serde::Serialize;
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::state::AppState;
+
+/// The queue worker that indexes uploaded and imported files.
+pub const INDEXING_WORKER: &str = "indexing_worker";
+/// Embedding of the documents the ingest journal lists for `embed`.
+pub const EMBED_CATCHUP: &str = "embed_catchup";
+/// Extraction of the documents the ingest journal lists for `enrich`.
+pub const EXTRACTION_CATCHUP: &str = "extraction_catchup";
+/// The LocalSend protocol listener.
+pub const LOCALSEND_LISTENER: &str = "localsend_listener";
+/// Scheduled consolidation and digest (`MINDSAGE_DIGEST_INTERVAL_DAYS`).
+pub const DIGEST_SCHEDULE: &str = "digest_schedule";
+/// Scheduled syncs of `rss` connectors (`pollMinutes`).
+pub const FEED_POLLING: &str = "feed_polling";
+/// Scheduled retention cleanup of captured browser conversations.
+pub const BROWSER_CLEANUP: &str = "browser_cleanup";
+
+/// Wait before the first restart of a failed task.
+const RESTART_BASE_DELAY: Duration = Duration::from_secs(5);
+/// Longest wait between restarts.
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(600);
+
+/// Last known state of one background subsystem. Times are Unix millis.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SubsystemHealth {
+    pub last_run_at: Option<i64>,
+    pub last_success_at: Option<i64>,
+    /// Error of the last failed run; kept after a later success.
+    pub last_error: Option<String>,
+    /// Failed runs since the last success.
+    pub consecutive_failures: u32,
+}
+
+impl SubsystemHealth {
+    /// Whether the last run failed.
+    pub fn is_degraded(&self) -> bool {
+        self.consecutive_failures > 0
+    }
+}
+
+/// Registry of background subsystem health, one per profile.
+#[derive(Default)]
+pub struct BackgroundHealth {
+    subsystems: RwLock<BTreeMap<&'static str, SubsystemHealth>>,
+}
+
+impl BackgroundHealth {
+    pub fn record_success(&self, name: &'static str) {
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut subsystems = self.subsystems.write();
+        let entry = subsystems.entry(name).or_default();
+        entry.last_run_at = Some(now);
+        entry.last_success_at = Some(now);
+        entry.consecutive_failures = 0;
+    }
+
+    pub fn record_failure(&self, name: &'static str, error: impl Display) {
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut subsystems = self.subsystems.write();
+        let entry = subsystems.entry(name).or_default();
+        entry.last_run_at = Some(now);
+        entry.last_error = Some(error.to_string());
+        entry.consecutive_failures += 1;
+    }
+
+    pub fn get(&self, name: &str) -> Option<SubsystemHealth> {
+        self.subsystems.read().get(name).cloned()
+    }
+
+    /// Every subsystem that has run, by name.
+    pub fn snapshot(&self) -> BTreeMap<String, SubsystemHealth> {
+        self.subsystems
+            .read()
+            .iter()
+            .map(|(name, health)| (name.to_string(), health.clone()))
+            .collect()
+    }
+
+    /// Names of the subsystems whose last run failed.
+    pub fn degraded(&self) -> Vec<String> {
+        self.subsystems
+            .read()
+            .iter()
+            .filter(|(_, health)| health.is_degraded())
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+}
+
+/// Wait before restart number `failures` of a failed task: `base` doubled
+/// per earlier failure, capped at `MAX_RESTART_DELAY`.
+fn restart_delay(base: Duration, failures: u32) -> Duration {
+    base.saturating_mul(1u32 << failures.saturating_sub(1).min(16)).min(MAX_RESTART_DELAY)
+}
+
+/// Run `task` on a blocking thread until it succeeds, recording each run
+/// under `name` and restarting it with backoff after a failure. Stops early
+/// when shutdown is triggered.
+pub async fn run_until_success<E: Display + Send + 'static>(
+    state: Arc<AppState>,
+    name: &'static str,
+    task: impl Fn(&AppState) -> Result<(), E> + Send + Sync + 'static,
+) {
+    run_with_backoff(state, name, RESTART_BASE_DELAY, task).await
+}
+
+async fn run_with_backoff<E: Display + Send + 'static>(
+    state: Arc<AppState>,
+    name: &'static str,
+    base_delay: Duration,
+    task: impl Fn(&AppState) -> Result<(), E> + Send + Sync + 'static,
+) {
+    let task = Arc::new(task);
+    while !state.shutdown.is_triggered() {
+        let run_state = state.clone();
+        let run = task.clone();
+        let result = tokio::task::spawn_blocking(move || run(&run_state).map_err(|e| e.to_string()))
+            .await
+            .unwrap_or_else(|e| Err(format!("task panicked: {}", e)));
+        match result {
+            Ok(()) => {
+                state.health.record_success(name);
+                return;
+            }
+            Err(e) => state.health.record_failure(name, &e),
+        }
+        let failures = state.health.get(name).map(|h| h.consecutive_failures).unwrap_or(1);
+        let delay = restart_delay(base_delay, failures);
+        warn!("Background task {} failed ({} in a row), restarting in {:?}", name, failures, delay);
+        tokio::select! {
+            _ = state.shutdown.wait() => return,
+            _ = tokio::time::sleep(delay) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use tempfile::TempDir;
+
+    use crate::test_support::test_state;
+
+    #[tokio::test]
+    async fn test_failing_task_is_restarted_until_it_recovers() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+
+        // Fails twice, then succeeds; the registry tracks every run
+        let runs = Arc::new(AtomicU32::new(0));
+        let task_runs = runs.clone();
+        let observed = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let task_observed = observed.clone();
+        run_with_backoff(state.clone(), EMBED_CATCHUP, Duration::from_millis(5), move |state| {
+            task_observed.lock().push(state.health.get(EMBED_CATCHUP));
+            match task_runs.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err("database is locked"),
+                _ => Ok(()),
+            }
+        })
+        .await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+
+        let observed = observed.lock();
+        assert!(observed[0].is_none());
+        let after_second = observed[2].clone().unwrap();
+        assert_eq!(after_second.consecutive_failures, 2);
+        assert_eq!(after_second.last_error.as_deref(), Some("database is locked"));
+        assert!(after_second.last_success_at.is_none());
+        assert_eq!(state.health.degraded(), Vec::<String>::new());
+
+        let recovered = state.health.get(EMBED_CATCHUP).unwrap();
+        assert_eq!(recovered.consecutive_failures, 0);
+        assert!(recovered.last_success_at.is_some());
+        assert_eq!(recovered.last_error.as_deref(), Some("database is locked"));
+
+        state.health.record_failure(INDEXING_WORKER, "worker panicked");
+        assert_eq!(state.health.degraded(), vec![INDEXING_WORKER.to_string()]);
+        let json = serde_json::to_value(state.health.snapshot()).unwrap();
+        assert_eq!(json[INDEXING_WORKER]["consecutiveFailures"], 1);
+        assert_eq!(json[EMBED_CATCHUP]["lastError"], "database is locked");
+    }
+
+    #[test]
+    fn test_restart_delay_doubles_up_to_the_cap() {
+        assert_eq!(restart_delay(RESTART_BASE_DELAY, 1), Duration::from_secs(5));
+        assert_eq!(restart_delay(RESTART_BASE_DELAY, 3), Duration::from_secs(20));
+        assert_eq!(restart_delay(RESTART_BASE_DELAY, 40), MAX_RESTART_DELAY);
+    }
+}
@@ -1,61 +1,177 @@
 //! Background indexing queue — processes files asynchronously.
 //! Also runs heuristic extraction on newly indexed chunks.
 
+use std::io::Write;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
-use tracing::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
 
-use crate::state::{AppState, IndexingStatus};
-use mindsage_ingest::Ingester;
+use crate::catchup;
+use crate::health;
+use crate::saved_searches;
+use crate::state::{AppState, IndexingRequest, IndexingStatus};
+use mindsage_core::redact;
+use mindsage_ingest::{Ingester, TornRepair};
+use mindsage_store::{CatchupCheckpoint, Chunk, PendingStep};
 
-/// Start the background indexing worker task.
-pub fn start_indexing_worker(state: Arc<AppState>) {
+/// Start the background indexing worker task. Jobs saved by the last
+/// shutdown are queued first. The worker stops, after finishing the current
+/// file, once shutdown is triggered.
+pub fn start_indexing_worker(state: Arc<AppState>) -> JoinHandle<()> {
     let mut rx = match state.take_indexing_rx() {
         Some(rx) => rx,
         None => {
             error!("Indexing worker already started");
-            return;
+            return tokio::spawn(async {});
         }
     };
 
-    // Run embedding + extraction on any unprocessed chunks from prior sessions
+    match restore_pending_queue(&state) {
+        Ok(0) => {}
+        Ok(restored) => info!("Re-queued {} indexing jobs from the last shutdown", restored),
+        Err(e) => warn!("Failed to restore the indexing queue: {}", e),
+    }
+
+    // Finish ingests interrupted in prior sessions: torn documents first,
+    // then the embedding and extraction steps left in the journal. A
+    // catch-up that fails is restarted with backoff until it succeeds.
     let catchup_state = state.clone();
     tokio::spawn(async move {
-        tokio::task::spawn_blocking(move || {
-            embed_pending_chunks(&catchup_state);
-            run_pending_extractions(&catchup_state);
-        })
-        .await
-        .ok();
+        let repair_state = catchup_state.clone();
+        tokio::task::spawn_blocking(move || repair_torn_documents(&repair_state))
+            .await
+            .ok();
+        health::run_until_success(catchup_state.clone(), health::EMBED_CATCHUP, catch_up_embeddings).await;
+        health::run_until_success(catchup_state.clone(), health::EXTRACTION_CATCHUP, run_pending_extractions).await;
+        maintain_ann_index(&catchup_state);
     });
 
     tokio::spawn(async move {
         info!("Background indexing worker started");
-        while let Some(request) = rx.recv().await {
-            process_indexing_job(&state, &request.job_id, &request.file_path, &request.filename);
+        loop {
+            let request = tokio::select! {
+                biased;
+                _ = state.shutdown.wait() => break,
+                request = rx.recv() => match request {
+                    Some(request) => request,
+                    None => break,
+                },
+            };
+            let job_state = state.clone();
+            let retry = tokio::task::spawn_blocking(move || {
+                let ingester = Ingester::new(&job_state.store)
+                    .with_transcript_window(Duration::from_secs(job_state.config.transcript_window_secs));
+                let delay = process_indexing_job(&job_state, &request, |path| ingester.ingest_file(path));
+                delay.map(|delay| (request, delay))
+            })
+            .await;
+            match retry {
+                Ok(retry) => {
+                    state.health.record_success(health::INDEXING_WORKER);
+                    match retry {
+                        Some((request, delay)) => schedule_retry(&state, request, delay),
+                        None => maintain_ann_index(&state),
+                    }
+                }
+                Err(e) => {
+                    error!("Indexing job panicked: {}", e);
+                    state.health.record_failure(health::INDEXING_WORKER, format!("indexing job panicked: {}", e));
+                }
+            }
+        }
+        info!("Background indexing worker stopped");
+    })
+}
+
+/// Build the ANN index once the store has grown past the threshold (or
+/// rebuild it after heavy deletions) on a blocking thread, so no search
+/// waits for it. Overlapping calls return at once while a build runs.
+fn maintain_ann_index(state: &Arc<AppState>) {
+    let state = state.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = state.store.maintain_ann_index() {
+            warn!("Failed to maintain ANN index: {}", e);
+        }
+    });
+}
+
+/// Longest wait between automatic retries.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// Error of a job whose file was deleted or moved before it was indexed.
+pub(crate) const FILE_MISSING_ERROR: &str = "File no longer exists";
+
+/// Wait before automatic retry number `retries + 1`: the configured base
+/// delay, doubled per earlier retry, capped at `MAX_RETRY_DELAY`.
+fn retry_delay(base_ms: u64, retries: u32) -> Duration {
+    Duration::from_millis(base_ms.saturating_mul(1u64 << retries.min(20))).min(MAX_RETRY_DELAY)
+}
+
+/// Send `request` back to the worker after `delay`, unless shutdown comes
+/// first. The job stays queued meanwhile, so shutdown saves it.
+fn schedule_retry(state: &Arc<AppState>, request: IndexingRequest, delay: Duration) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = state.shutdown.wait() => {}
+            _ = tokio::time::sleep(delay) => {
+                let _ = state.indexing_tx.send(IndexingRequest {
+                    retries: request.retries + 1,
+                    ..request
+                });
+            }
         }
     });
 }
 
-fn process_indexing_job(state: &AppState, job_id: &str, file_path: &str, filename: &str) {
+/// Index one queued file with `ingest`. Returns how long to wait before
+/// trying again when it failed with a retryable error and retries remain.
+fn process_indexing_job(
+    state: &AppState,
+    request: &IndexingRequest,
+    ingest: impl FnOnce(&Path) -> mindsage_core::Result<Option<i64>>,
+) -> Option<Duration> {
+    let IndexingRequest { job_id, file_path, filename, retries } = request;
+    let job_id = job_id.as_str();
     let now = now_millis();
 
+    // A file removed since it was queued can never succeed
+    let path = Path::new(file_path);
+    if !path.exists() {
+        {
+            let mut jobs = state.indexing_jobs.write();
+            if let Some(job) = jobs.get_mut(job_id) {
+                job.status = IndexingStatus::Failed;
+                job.error = Some(FILE_MISSING_ERROR.to_string());
+                job.completed_at = Some(now);
+                job.next_attempt_at = None;
+            }
+        }
+        state.publish_job(job_id);
+        warn!("Not indexing {}: {}", redact(filename), FILE_MISSING_ERROR);
+        return None;
+    }
+
     // Update job status to processing
     {
         let mut jobs = state.indexing_jobs.write();
         if let Some(job) = jobs.get_mut(job_id) {
             job.status = IndexingStatus::Processing;
             job.started_at = Some(now);
+            job.attempts += 1;
+            job.next_attempt_at = None;
         }
     }
+    state.publish_job(job_id);
 
-    info!("Processing indexing job {}: {}", job_id, filename);
-
-    let path = Path::new(file_path);
-    let ingester = Ingester::new(&state.store);
+    info!("Processing indexing job {}: {}", job_id, redact(filename));
 
-    match ingester.ingest_file(path) {
+    let mut retry = None;
+    match ingest(path) {
         Ok(Some(doc_id)) => {
             let completed_at = now_millis();
             {
@@ -63,17 +179,26 @@ fn process_indexing_job(state: &AppState, job_id: &str, file_path: &str, filenam
                 if let Some(job) = jobs.get_mut(job_id) {
                     job.status = IndexingStatus::Completed;
                     job.document_id = Some(doc_id);
+                    job.error = None;
                     job.completed_at = Some(completed_at);
                 }
             }
+            state.publish_job(job_id);
             state.mark_file_indexed(file_path, Some(doc_id));
-            info!("Indexed {} → document {}", filename, doc_id);
+            info!("Indexed {} → document {}", redact(filename), doc_id);
 
             // Embed level=1 chunks if embedder is available
-            embed_document_chunks(state, doc_id);
+            if embed_document_chunks(state, doc_id) {
+                complete_step(state, doc_id, PendingStep::Embed);
+            }
 
             // Run heuristic extraction on the new document's chunks
-            run_extraction_for_document(state, doc_id);
+            if run_extraction_for_document(state, doc_id) {
+                complete_step(state, doc_id, PendingStep::Enrich);
+            }
+
+            // Re-run saved searches against the new content
+            saved_searches::check_saved_searches(state);
         }
         Ok(None) => {
             let completed_at = now_millis();
@@ -85,11 +210,36 @@ fn process_indexing_job(state: &AppState, job_id: &str, file_path: &str, filenam
                     job.error = Some("No text extracted".to_string());
                 }
             }
-            info!("No text extracted from {}", filename);
+            state.publish_job(job_id);
+            info!("No text extracted from {}", redact(filename));
+        }
+        Err(e) if e.is_retryable() && *retries < state.config.indexing_max_retries => {
+            let delay = retry_delay(state.config.indexing_retry_base_ms, *retries);
+            {
+                let mut jobs = state.indexing_jobs.write();
+                if let Some(job) = jobs.get_mut(job_id) {
+                    job.status = IndexingStatus::Queued;
+                    job.error = Some(e.to_string());
+                    job.next_attempt_at = Some(now_millis() + delay.as_millis() as i64);
+                }
+            }
+            state.publish_job(job_id);
+            warn!(
+                "Indexing {} failed ({}), retrying in {:?}",
+                redact(filename),
+                e,
+                delay
+            );
+            retry = Some(delay);
         }
         Err(e) => {
             let completed_at = now_millis();
-            let err_msg = e.to_string();
+            let err_msg = match e {
+                mindsage_core::Error::Io(ref io) if io.kind() == std::io::ErrorKind::NotFound => {
+                    FILE_MISSING_ERROR.to_string()
+                }
+                _ => e.to_string(),
+            };
             {
                 let mut jobs = state.indexing_jobs.write();
                 if let Some(job) = jobs.get_mut(job_id) {
@@ -103,16 +253,18 @@ fn process_indexing_job(state: &AppState, job_id: &str, file_path: &str, filenam
                     job.completed_at = Some(completed_at);
                 }
             }
+            state.publish_job(job_id);
             if err_msg.contains("Duplicate content") {
-                info!("Skipped duplicate: {}", filename);
+                info!("Skipped duplicate: {}", redact(filename));
             } else {
-                error!("Failed to index {}: {}", filename, err_msg);
+                error!("Failed to index {}: {}", redact(filename), err_msg);
             }
         }
     }
 
     // Cleanup old completed jobs (keep last 100)
     cleanup_old_jobs(state);
+    retry
 }
 
 fn cleanup_old_jobs(state: &AppState) {
@@ -142,6 +294,83 @@ fn cleanup_old_jobs(state: &AppState) {
     }
 }
 
+// ---------------------------------------------------------------
+// Queue persistence
+// ---------------------------------------------------------------
+
+/// A job saved at shutdown (`data/indexing-queue.json`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedJob {
+    file_path: String,
+    filename: String,
+    queued_at: i64,
+}
+
+/// Save the jobs that have not finished, oldest first, so the next start
+/// can queue them again. A job still processing is included: whatever it
+/// wrote is replaced when the file is indexed again. Removes the file when
+/// nothing is pending. Returns the number of jobs saved.
+pub(crate) fn save_pending_queue(state: &AppState) -> std::io::Result<usize> {
+    let path = &state.config.data_paths.indexing_queue_file;
+    let mut pending: Vec<SavedJob> = state
+        .indexing_jobs
+        .read()
+        .values()
+        .filter(|j| matches!(j.status, IndexingStatus::Queued | IndexingStatus::Processing))
+        .map(|j| SavedJob {
+            file_path: j.file_path.clone(),
+            filename: j.filename.clone(),
+            queued_at: j.queued_at,
+        })
+        .collect();
+    if pending.is_empty() {
+        return match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(0),
+        };
+    }
+    pending.sort_by_key(|j| j.queued_at);
+
+    let data = serde_json::to_vec_pretty(&pending)?;
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp = std::path::PathBuf::from(tmp_name);
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(&data)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)?;
+    Ok(pending.len())
+}
+
+/// Queue the jobs saved by the last shutdown and remove the file. Files
+/// that no longer exist, are already indexed, or are already queued are
+/// skipped. Returns the number of jobs queued.
+pub(crate) fn restore_pending_queue(state: &AppState) -> std::io::Result<usize> {
+    let path = &state.config.data_paths.indexing_queue_file;
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let saved: Vec<SavedJob> = serde_json::from_str(&data)?;
+
+    let mut restored = 0;
+    for job in saved {
+        if !Path::new(&job.file_path).exists()
+            || state.is_file_indexed(&job.file_path)
+            || state.has_pending_job(&job.file_path)
+        {
+            continue;
+        }
+        state.queue_indexing(job.file_path, job.filename);
+        restored += 1;
+    }
+    std::fs::remove_file(path)?;
+    Ok(restored)
+}
+
 fn now_millis() -> i64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -153,90 +382,264 @@ fn now_millis() -> i64 {
 // Embedding
 // ---------------------------------------------------------------
 
-/// Embed all level=1 (paragraph) chunks for a document.
-fn embed_document_chunks(state: &AppState, doc_id: i64) {
+/// Share of a document's chunks cut off by the embedder above which the
+/// document is flagged with `metadata.embedding_truncation`.
+const TRUNCATION_WARN_RATE: f64 = 0.1;
+
+/// Embed the level=1 (paragraph) chunks of a document that have no
+/// embedding yet. True when every paragraph chunk has one afterwards, so
+/// the journal's embed step is done.
+fn embed_document_chunks(state: &AppState, doc_id: i64) -> bool {
     if !state.embedder.is_available() {
-        return;
+        return false;
     }
 
     let chunks = match state.store.get_chunks_for_document(doc_id) {
         Ok(c) => c,
         Err(e) => {
             error!("Failed to get chunks for embedding (doc {}): {}", doc_id, e);
-            return;
+            return false;
         }
     };
 
-    let paragraph_chunks: Vec<_> = chunks.iter().filter(|c| c.level == 1).collect();
-    if paragraph_chunks.is_empty() {
-        return;
-    }
-
-    let texts: Vec<&str> = paragraph_chunks.iter().map(|c| c.text.as_str()).collect();
-    let embeddings = state.embedder.embed_batch(&texts);
-
-    let mut embedded_count = 0;
-    for (chunk, emb_result) in paragraph_chunks.iter().zip(embeddings.iter()) {
-        if let Some(result) = emb_result {
-            if let Err(e) = state.store.add_chunk_embedding(chunk.id, &result.embedding) {
-                error!("Failed to store embedding for chunk {}: {}", chunk.id, e);
-                continue;
-            }
-            // Also update in-memory matrix for fast vector search
-            if let Err(e) = state.store.append_to_matrix(chunk.id, &result.embedding) {
-                debug!("Matrix append deferred for chunk {}: {}", chunk.id, e);
-            }
-            embedded_count += 1;
+    let paragraph_ids: Vec<i64> = chunks.iter().filter(|c| c.level == 1).map(|c| c.id).collect();
+    let embedded = match state.store.get_chunk_embeddings(&paragraph_ids) {
+        Ok(embedded) => embedded,
+        Err(e) => {
+            error!("Failed to read embeddings (doc {}): {}", doc_id, e);
+            return false;
         }
+    };
+    let paragraph_chunks: Vec<_> = chunks
+        .iter()
+        .filter(|c| c.level == 1 && !embedded.contains_key(&c.id))
+        .collect();
+    if paragraph_chunks.is_empty() {
+        return true;
     }
 
+    let (embedded_count, truncated_count) = embed_chunks(state, &paragraph_chunks);
     if embedded_count > 0 {
+        record_truncation(state, doc_id, embedded_count, truncated_count);
+        // Keep the document's centroid ready for similarity lookups
+        if let Err(e) = state.store.refresh_doc_centroid(doc_id) {
+            debug!("Failed to refresh centroid of document {}: {}", doc_id, e);
+        }
         debug!(
             "Embedded {} paragraph chunks for document {}",
             embedded_count, doc_id
         );
     }
+    embedded_count == paragraph_chunks.len()
 }
 
-/// Embed any level=1 chunks from prior sessions that don't have embeddings yet.
-fn embed_pending_chunks(state: &AppState) {
-    if !state.embedder.is_available() {
-        return;
+/// Embed `chunks` and store their embeddings, replacing any they had, in
+/// parts the memory budget allows; what it refuses is retried on the next
+/// catch-up. Returns how many were embedded and how many of those the
+/// embedder truncated.
+fn embed_chunks(state: &AppState, chunks: &[&Chunk]) -> (usize, usize) {
+    let mut embedded_count = 0;
+    let mut truncated_count = 0;
+    let mut rest = chunks;
+    while !rest.is_empty() {
+        let texts: Vec<&str> = rest.iter().map(|c| c.text.as_str()).collect();
+        let Some((_reservation, count)) = state.orchestrator.reserve_embedding(&state.store, state.embedder.as_ref(), &texts)
+        else {
+            break;
+        };
+        let embeddings = state.embedder.embed_batch(&texts[..count]);
+        for (chunk, emb_result) in rest.iter().zip(embeddings.iter()) {
+            if let Some(result) = emb_result {
+                if let Err(e) = state.store.add_chunk_embedding(chunk.id, &result.embedding) {
+                    error!("Failed to store embedding for chunk {}: {}", chunk.id, e);
+                    continue;
+                }
+                // Also update in-memory matrix for fast vector search
+                if let Err(e) = state.store.append_to_matrix(chunk.id, chunk.level, &result.embedding) {
+                    debug!("Matrix append deferred for chunk {}: {}", chunk.id, e);
+                }
+                embedded_count += 1;
+                if result.truncated {
+                    truncated_count += 1;
+                }
+            }
+        }
+        rest = &rest[count..];
     }
+    (embedded_count, truncated_count)
+}
 
+/// Re-embed the chunks whose text was edited after they were embedded.
+/// Runs before the journal's never-embedded documents, so an edited chunk
+/// is not searched by its old text for longer than necessary. Returns how
+/// many were re-embedded.
+fn reembed_text_stale_chunks(state: &AppState) -> mindsage_core::Result<usize> {
     let batch_size = 50;
+    let mut after_id = 0;
     let mut total = 0;
-
-    loop {
-        let chunks = match state.store.get_chunks_without_embedding(batch_size) {
-            Ok(c) => c,
-            Err(e) => {
-                error!("Failed to get pending chunks for embedding: {}", e);
-                break;
-            }
+    while !state.shutdown.is_triggered() {
+        let chunks = state.store.get_chunks_with_text_stale_embedding(after_id, batch_size)?;
+        let Some(last) = chunks.last() else {
+            break;
         };
-
-        if chunks.is_empty() {
+        after_id = last.id;
+        let chunks: Vec<&Chunk> = chunks.iter().collect();
+        let (embedded, _) = embed_chunks(state, &chunks);
+        total += embedded;
+        // Out of memory budget: the rest waits for the next catch-up
+        if embedded < chunks.len() {
             break;
         }
+    }
+    Ok(total)
+}
 
-        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
-        let embeddings = state.embedder.embed_batch(&texts);
+/// Flag a document whose chunks the embedder cut off too often, so its
+/// chunker settings can be revisited and the affected documents found
+/// with the `has_metadata` document filter.
+fn record_truncation(state: &AppState, doc_id: i64, embedded: usize, truncated: usize) {
+    let rate = truncated as f64 / embedded as f64;
+    if rate <= TRUNCATION_WARN_RATE {
+        return;
+    }
+    warn!(
+        "Embedder truncated {} of {} chunks of document {} ({:.0}%)",
+        truncated,
+        embedded,
+        doc_id,
+        rate * 100.0
+    );
+    let flag = serde_json::json!({
+        "embedding_truncation": {
+            "chunks": embedded,
+            "truncated": truncated,
+            "rate": rate,
+            "model": state.embedder.model_id(),
+        }
+    });
+    if let Err(e) = state.store.update_document_metadata(doc_id, &flag) {
+        warn!("Failed to record truncation of document {}: {}", doc_id, e);
+    }
+}
 
-        for (chunk, emb_result) in chunks.iter().zip(embeddings.iter()) {
-            if let Some(result) = emb_result {
-                if let Err(e) = state.store.add_chunk_embedding(chunk.id, &result.embedding) {
-                    error!("Failed to store embedding for chunk {}: {}", chunk.id, e);
-                    continue;
+/// Embed the documents the ingest journal still lists for embedding, from
+/// prior sessions or from writers that leave embedding to the background.
+/// The outcome is recorded as the embedding catch-up's health.
+pub fn embed_pending_chunks(state: &AppState) {
+    match catch_up_embeddings(state) {
+        Ok(()) => state.health.record_success(health::EMBED_CATCHUP),
+        Err(e) => {
+            error!("Embedding catch-up failed: {}", e);
+            state.health.record_failure(health::EMBED_CATCHUP, e);
+        }
+    }
+}
+
+fn catch_up_embeddings(state: &AppState) -> mindsage_core::Result<()> {
+    if !state.embedder.is_available() {
+        return Ok(());
+    }
+    let reembedded = reembed_text_stale_chunks(state)?;
+    if reembedded > 0 {
+        info!("Re-embedded {} chunks whose text was edited", reembedded);
+    }
+    let total = for_each_pending(state, PendingStep::Embed, |doc_id| embed_document_chunks(state, doc_id))?;
+    if total > 0 {
+        info!("Embedded pending chunks of {} documents", total);
+    }
+    Ok(())
+}
+
+/// Journal entries fetched per catch-up batch.
+const CATCHUP_BATCH_SIZE: usize = 50;
+
+/// Run `step` on each document the journal lists for it, oldest first,
+/// clearing the entries it completes. The pass resumes after the
+/// checkpoint an interrupted one left and saves its own after each batch,
+/// so a restart does not go over the same documents again; documents whose
+/// step failed wait for the next pass. Returns how many it completed, or
+/// the error that stopped it reading the journal.
+fn for_each_pending(
+    state: &AppState,
+    step: PendingStep,
+    mut run: impl FnMut(i64) -> bool,
+) -> mindsage_core::Result<usize> {
+    let mut checkpoint = state.store.get_catchup_checkpoint(step)?.unwrap_or(CatchupCheckpoint {
+        step,
+        after_doc_id: 0,
+        done: 0,
+        started_at: now_millis(),
+    });
+    let remaining = state.store.count_pending_work_after(step, checkpoint.after_doc_id)? as usize;
+    let Some(pass) = state.catchup.begin(step, checkpoint.done, checkpoint.done + remaining, checkpoint.started_at)
+    else {
+        return Ok(0);
+    };
+    if checkpoint.done > 0 {
+        info!(
+            "Resuming the {} catch-up after document {} ({} of {} documents done)",
+            step.as_str(),
+            checkpoint.after_doc_id,
+            checkpoint.done,
+            checkpoint.done + remaining
+        );
+    }
+    let mut completed = 0;
+
+    while !state.shutdown.is_triggered() {
+        let doc_ids = state.store.get_pending_work(step, checkpoint.after_doc_id, CATCHUP_BATCH_SIZE)?;
+        if doc_ids.is_empty() {
+            state.store.clear_catchup_checkpoint(step)?;
+            match pass.try_finish() {
+                Some(progress) => {
+                    if progress.done > 0 {
+                        state.events.publish(catchup::catchup_event(&progress, true));
+                    }
+                    break;
                 }
-                let _ = state.store.append_to_matrix(chunk.id, &result.embedding);
-                total += 1;
+                None => continue,
+            }
+        }
+
+        for doc_id in doc_ids {
+            if state.shutdown.is_triggered() {
+                break;
+            }
+            if run(doc_id) {
+                complete_step(state, doc_id, step);
+                completed += 1;
             }
+            checkpoint.after_doc_id = doc_id;
+            checkpoint.done += 1;
+        }
+        state.store.save_catchup_checkpoint(&checkpoint)?;
+        let total = checkpoint.done + state.store.count_pending_work_after(step, checkpoint.after_doc_id)? as usize;
+        if let Some(event) = pass.advance(checkpoint.done, total) {
+            state.events.publish(event);
         }
     }
+    Ok(completed)
+}
 
-    if total > 0 {
-        info!("Embedded {} pending chunks from prior sessions", total);
+fn complete_step(state: &AppState, doc_id: i64, step: PendingStep) {
+    if let Err(e) = state.store.complete_pending_work(doc_id, step) {
+        warn!("Failed to clear {} journal entry of document {}: {}", step.as_str(), doc_id, e);
+    }
+}
+
+/// Chunk the documents a crash left without chunks, or delete them when
+/// they have no text.
+fn repair_torn_documents(state: &AppState) {
+    match Ingester::new(&state.store)
+        .with_transcript_window(Duration::from_secs(state.config.transcript_window_secs))
+        .repair_torn_documents()
+    {
+        Ok(TornRepair { completed: 0, rolled_back: 0 }) => {}
+        Ok(repair) => info!(
+            "Repaired torn ingests: {} documents chunked, {} empty documents removed",
+            repair.completed, repair.rolled_back
+        ),
+        Err(e) => warn!("Failed to repair torn ingests: {}", e),
     }
 }
 
@@ -244,13 +647,14 @@ fn embed_pending_chunks(state: &AppState) {
 // Heuristic Extraction
 // ---------------------------------------------------------------
 
-/// Run heuristic extraction on all chunks of a newly indexed document.
-fn run_extraction_for_document(state: &AppState, doc_id: i64) {
+/// Run heuristic extraction on the chunks of a document that have none
+/// yet. True when no chunk failed, so the journal's enrich step is done.
+fn run_extraction_for_document(state: &AppState, doc_id: i64) -> bool {
     let chunks = match state.store.get_chunks_for_document(doc_id) {
         Ok(c) => c,
         Err(e) => {
             error!("Failed to get chunks for extraction (doc {}): {}", doc_id, e);
-            return;
+            return false;
         }
     };
 
@@ -270,6 +674,7 @@ fn run_extraction_for_document(state: &AppState, doc_id: i64) {
         .map(|s| s.to_string());
 
     let mut extracted_count = 0;
+    let mut failed = false;
     let mut doc_topics: Vec<String> = Vec::new();
 
     for chunk in &chunks {
@@ -285,8 +690,13 @@ fn run_extraction_for_document(state: &AppState, doc_id: i64) {
 
         let enriched = mindsage_ingest::build_enriched_text(&result);
         if !enriched.is_empty() {
-            if let Err(e) = state.store.update_chunk_enriched_text(chunk.id, &enriched) {
+            if let Err(e) = state.store.update_chunk_enriched_text(
+                chunk.id,
+                &enriched,
+                mindsage_ingest::CURRENT_EXTRACTION_VERSION,
+            ) {
                 error!("Failed to update enriched_text for chunk {}: {}", chunk.id, e);
+                failed = true;
                 continue;
             }
         }
@@ -316,37 +726,373 @@ fn run_extraction_for_document(state: &AppState, doc_id: i64) {
             extracted_count, doc_id
         );
     }
+    !failed
 }
 
-/// Process any chunks that don't have enriched_text yet (from prior sessions).
-fn run_pending_extractions(state: &AppState) {
-    let batch_size = 50;
-    let mut total = 0;
+/// Run extraction on the documents the ingest journal still lists for it.
+fn run_pending_extractions(state: &AppState) -> mindsage_core::Result<()> {
+    let total = for_each_pending(state, PendingStep::Enrich, |doc_id| run_extraction_for_document(state, doc_id))?;
+    if total > 0 {
+        info!("Completed pending extraction for {} documents", total);
+    }
+    Ok(())
+}
 
-    loop {
-        let chunks = match state.store.get_chunks_without_enrichment(batch_size) {
-            Ok(c) => c,
-            Err(e) => {
-                error!("Failed to get pending chunks for extraction: {}", e);
-                break;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    fn write_import(state: &AppState, name: &str) -> String {
+        let path = state.config.data_paths.imports.join(name);
+        std::fs::write(&path, format!("Contents of {}", name)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_pending_queue_survives_restart() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+        let first = write_import(&state, "first.txt");
+        let second = write_import(&state, "second.txt");
+        let gone = write_import(&state, "gone.txt");
+        let done = write_import(&state, "done.txt");
+
+        let ids = [
+            state.queue_indexing(second.clone(), "second.txt".into()),
+            state.queue_indexing(first.clone(), "first.txt".into()),
+            state.queue_indexing(gone.clone(), "gone.txt".into()),
+            state.queue_indexing(done, "done.txt".into()),
+        ];
+        {
+            let mut jobs = state.indexing_jobs.write();
+            for (queued_at, id) in [2, 1, 3, 4].into_iter().zip(&ids) {
+                jobs.get_mut(id).unwrap().queued_at = queued_at;
             }
+            jobs.get_mut(&ids[1]).unwrap().status = IndexingStatus::Processing;
+            jobs.get_mut(&ids[3]).unwrap().status = IndexingStatus::Completed;
+        }
+
+        assert_eq!(save_pending_queue(&state).unwrap(), 3);
+        std::fs::remove_file(&gone).unwrap();
+        drop(state);
+
+        // The next start queues the unfinished files, oldest first
+        let state = TestState::new(&dir).build();
+        let mut rx = state.take_indexing_rx().unwrap();
+        assert_eq!(restore_pending_queue(&state).unwrap(), 2);
+        assert_eq!(rx.try_recv().unwrap().file_path, first);
+        assert_eq!(rx.try_recv().unwrap().file_path, second);
+        assert!(rx.try_recv().is_err());
+        assert!(state.has_pending_job(&first));
+        assert!(!state.config.data_paths.indexing_queue_file.exists());
+
+        // Nothing saved means nothing to restore
+        assert_eq!(restore_pending_queue(&state).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_empty_queue_removes_saved_file() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+        let path = write_import(&state, "a.txt");
+        state.queue_indexing(path, "a.txt".into());
+        assert_eq!(save_pending_queue(&state).unwrap(), 1);
+
+        state.indexing_jobs.write().clear();
+        assert_eq!(save_pending_queue(&state).unwrap(), 0);
+        assert!(!state.config.data_paths.indexing_queue_file.exists());
+    }
+
+    fn take_request(rx: &mut tokio::sync::mpsc::UnboundedReceiver<IndexingRequest>) -> IndexingRequest {
+        rx.try_recv().unwrap()
+    }
+
+    fn job(state: &AppState, id: &str) -> mindsage_api_types::IndexingJob {
+        state.indexing_jobs.read()[id].clone()
+    }
+
+    #[test]
+    fn test_transient_failure_is_retried_until_it_succeeds() {
+        let dir = TempDir::new().unwrap();
+        let mut state = TestState::new(&dir).build();
+        state.config.indexing_retry_base_ms = 100;
+        let path = write_import(&state, "notes.txt");
+        let mut rx = state.take_indexing_rx().unwrap();
+        let id = state.queue_indexing(path, "notes.txt".into());
+
+        // The ingester hook reports a locked database twice, then works
+        let failures = std::cell::Cell::new(2);
+        let ingest = |path: &Path| {
+            if failures.get() > 0 {
+                failures.set(failures.get() - 1);
+                return Err(mindsage_core::Error::Busy("database is locked".into()));
+            }
+            Ingester::new(&state.store).ingest_file(path)
         };
 
-        if chunks.is_empty() {
-            break;
+        let mut request = take_request(&mut rx);
+        for (retries, expected_delay) in [(0, 100), (1, 200)] {
+            assert_eq!(request.retries, retries);
+            let delay = process_indexing_job(&state, &request, ingest).unwrap();
+            assert_eq!(delay, Duration::from_millis(expected_delay));
+            let waiting = job(&state, &id);
+            assert_eq!(waiting.status, IndexingStatus::Queued);
+            assert_eq!(waiting.attempts, retries + 1);
+            assert!(waiting.error.unwrap().contains("busy"));
+            assert!(waiting.next_attempt_at.is_some());
+            request = IndexingRequest { retries: request.retries + 1, ..request };
         }
 
-        for chunk in &chunks {
-            let result = mindsage_ingest::extract_all(&chunk.text, None, None);
-            let enriched = mindsage_ingest::build_enriched_text(&result);
-            if !enriched.is_empty() {
-                let _ = state.store.update_chunk_enriched_text(chunk.id, &enriched);
-            }
-            total += 1;
+        assert_eq!(process_indexing_job(&state, &request, ingest), None);
+        let done = job(&state, &id);
+        assert_eq!(done.status, IndexingStatus::Completed);
+        assert_eq!(done.attempts, 3);
+        assert!(done.document_id.is_some());
+        assert_eq!(done.error, None);
+        assert_eq!(done.next_attempt_at, None);
+    }
+
+    #[test]
+    fn test_retries_stop_at_the_configured_max() {
+        let dir = TempDir::new().unwrap();
+        let mut state = TestState::new(&dir).build();
+        state.config.indexing_max_retries = 1;
+        let path = write_import(&state, "a.txt");
+        let mut rx = state.take_indexing_rx().unwrap();
+        let id = state.queue_indexing(path.clone(), "a.txt".into());
+        let busy = |_: &Path| Err(mindsage_core::Error::Busy("database is locked".into()));
+
+        let request = take_request(&mut rx);
+        assert!(process_indexing_job(&state, &request, busy).is_some());
+        let request = IndexingRequest { retries: 1, ..request };
+        assert_eq!(process_indexing_job(&state, &request, busy), None);
+        assert_eq!(job(&state, &id).status, IndexingStatus::Failed);
+        assert_eq!(job(&state, &id).attempts, 2);
+
+        // Errors that would fail again are not retried at all
+        let id = state.queue_indexing(path, "a.txt".into());
+        let request = take_request(&mut rx);
+        let broken = |_: &Path| Err(mindsage_core::Error::Ingest("extractor panicked".into()));
+        assert_eq!(process_indexing_job(&state, &request, broken), None);
+        assert_eq!(job(&state, &id).status, IndexingStatus::Failed);
+        assert_eq!(job(&state, &id).attempts, 1);
+
+        assert_eq!(retry_delay(2000, 0), Duration::from_secs(2));
+        assert_eq!(retry_delay(2000, 3), Duration::from_secs(16));
+        assert_eq!(retry_delay(2000, 30), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_missing_file_fails_terminally() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+        let path = write_import(&state, "gone.txt");
+        let mut rx = state.take_indexing_rx().unwrap();
+        let id = state.queue_indexing(path.clone(), "gone.txt".into());
+        std::fs::remove_file(&path).unwrap();
+
+        let request = take_request(&mut rx);
+        let retry = process_indexing_job(&state, &request, |_| panic!("the ingester must not run"));
+        assert_eq!(retry, None);
+        let failed = job(&state, &id);
+        assert_eq!(failed.status, IndexingStatus::Failed);
+        assert_eq!(failed.error.as_deref(), Some(FILE_MISSING_ERROR));
+        assert_eq!(failed.attempts, 0);
+    }
+
+    /// Embeds every text as the same unit vector.
+    struct UnitEmbedder;
+
+    impl mindsage_infer::EmbedderBackend for UnitEmbedder {
+        fn embed(&self, _text: &str) -> Option<mindsage_infer::EmbeddingResult> {
+            let mut embedding = ndarray::Array1::zeros(384);
+            embedding[0] = 1.0;
+            Some(mindsage_infer::EmbeddingResult {
+                embedding,
+                cached: false,
+                input_token_count: None,
+                truncated: false,
+            })
+        }
+
+        fn dimension(&self) -> usize {
+            384
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn model_id(&self) -> &str {
+            "unit"
         }
     }
 
-    if total > 0 {
-        info!("Completed pending extraction for {} chunks", total);
+    /// Reports texts longer than 60 bytes as truncated.
+    struct ShortWindowEmbedder;
+
+    impl mindsage_infer::EmbedderBackend for ShortWindowEmbedder {
+        fn embed(&self, text: &str) -> Option<mindsage_infer::EmbeddingResult> {
+            let mut result = UnitEmbedder.embed(text)?;
+            result.truncated = text.len() > 60;
+            Some(result)
+        }
+
+        fn dimension(&self) -> usize {
+            384
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn model_id(&self) -> &str {
+            "short-window"
+        }
+    }
+
+    #[test]
+    fn test_truncated_documents_are_flagged() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).embedder(ShortWindowEmbedder).build();
+        let ingester = Ingester::new(&state.store);
+        let ingest = |text: &str| {
+            ingester
+                .ingest_text(text, &mindsage_ingest::ingest::content_hash(text), &serde_json::json!({}), None)
+                .unwrap()
+                .unwrap()
+        };
+        let long = ingest("The harbour master logs every ferry that leaves after the evening tide turns.");
+        let short = ingest("Short note about tea.\n\nAnother short note.");
+
+        assert!(embed_document_chunks(&state, long));
+        assert!(embed_document_chunks(&state, short));
+
+        let metadata = |id: i64| state.store.get_document(id).unwrap().unwrap().metadata.unwrap();
+        let flag = &metadata(long)["embedding_truncation"];
+        assert_eq!(flag["chunks"], 1);
+        assert_eq!(flag["truncated"], 1);
+        assert_eq!(flag["model"], "short-window");
+        assert!(metadata(short).get("embedding_truncation").is_none());
+
+        let flagged = state
+            .store
+            .select_documents(&mindsage_store::DocumentSelector {
+                has_metadata: Some("embedding_truncation".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(flagged, vec![long]);
+    }
+
+    #[test]
+    fn test_startup_recovers_interrupted_ingests() {
+        let dir = TempDir::new().unwrap();
+        let text = "Call alice@example.com on 2024-03-05 about the #budget review.\n\n\
+                    The second paragraph mentions https://example.com/report and Lisbon.";
+
+        // Killed after the document was written, before embedding and
+        // extraction; plus two documents torn by an older version
+        let (doc_id, torn, empty) = {
+            let state = TestState::new(&dir).build();
+            let ingester = Ingester::new(&state.store);
+            let doc_id = ingester
+                .ingest_text(text, &mindsage_ingest::ingest::content_hash(text), &serde_json::json!({}), None)
+                .unwrap()
+                .unwrap();
+            let torn = state.store.add_document(text, mindsage_store::AddDocumentOptions::default()).unwrap();
+            let empty = state.store.add_document("  ", mindsage_store::AddDocumentOptions::default()).unwrap();
+            (doc_id, torn, empty)
+        };
+
+        let state = TestState::new(&dir).embedder(UnitEmbedder).build();
+        for step in PendingStep::ALL {
+            assert_eq!(state.store.get_pending_work(step, 0, 10).unwrap(), vec![doc_id]);
+        }
+
+        repair_torn_documents(&state);
+        embed_pending_chunks(&state);
+        run_pending_extractions(&state).unwrap();
+
+        assert!(state.store.get_document(empty).unwrap().is_none());
+        for id in [doc_id, torn] {
+            let chunks = state.store.get_chunks_for_document(id).unwrap();
+            let paragraphs: Vec<i64> = chunks.iter().filter(|c| c.level == 1).map(|c| c.id).collect();
+            assert!(!paragraphs.is_empty());
+            assert_eq!(state.store.get_chunk_embeddings(&paragraphs).unwrap().len(), paragraphs.len());
+            assert!(chunks.iter().any(|c| c.enriched_text.is_some()));
+        }
+        for step in PendingStep::ALL {
+            assert_eq!(state.store.count_pending_work(step).unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn test_interrupted_catch_up_resumes_from_checkpoint() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+        let ingester = Ingester::new(&state.store);
+        let doc_ids: Vec<i64> = (0..120)
+            .map(|i| {
+                let text = format!("Journaled note number {}", i);
+                let hash = mindsage_ingest::ingest::content_hash(&text);
+                ingester.ingest_text(&text, &hash, &serde_json::json!({}), None).unwrap().unwrap()
+            })
+            .collect();
+
+        // The first document fails; the server stops after the 70th
+        let mut seen = Vec::new();
+        let completed = for_each_pending(&state, PendingStep::Enrich, |doc_id| {
+            seen.push(doc_id);
+            if seen.len() == 70 {
+                state.shutdown.trigger();
+            }
+            seen.len() > 1
+        })
+        .unwrap();
+        assert_eq!((seen.as_slice(), completed), (&doc_ids[..70], 69));
+        let checkpoint = state.store.get_catchup_checkpoint(PendingStep::Enrich).unwrap().unwrap();
+        assert_eq!((checkpoint.after_doc_id, checkpoint.done), (doc_ids[69], 70));
+        let progress = &state.catchup.progress()[0];
+        assert_eq!((progress.running, progress.done, progress.total), (false, 70, 120));
+        assert!(progress.finished_at.is_none());
+        drop(state);
+
+        // After the restart the pass goes on from the checkpoint
+        let state = TestState::new(&dir).build();
+        let mut events = state.events.subscribe();
+        let mut seen = Vec::new();
+        let completed = for_each_pending(&state, PendingStep::Enrich, |doc_id| {
+            seen.push(doc_id);
+            true
+        })
+        .unwrap();
+        assert_eq!((seen.as_slice(), completed), (&doc_ids[70..], 50));
+        assert!(state.store.get_catchup_checkpoint(PendingStep::Enrich).unwrap().is_none());
+        let progress = &state.catchup.progress()[0];
+        assert_eq!((progress.done, progress.total, progress.started_at), (120, 120, checkpoint.started_at));
+        assert!(progress.finished_at.is_some());
+        let mut last = None;
+        while let Ok(event) = events.try_recv() {
+            last = Some(event);
+        }
+        assert!(matches!(
+            last,
+            Some(mindsage_core::Event::IndexingCatchup { done: 120, total: 120, finished: true, .. })
+        ));
+
+        // The document that failed is left for the next pass
+        assert_eq!(state.store.get_pending_work(PendingStep::Enrich, 0, 10).unwrap(), vec![doc_ids[0]]);
+        let mut seen = Vec::new();
+        for_each_pending(&state, PendingStep::Enrich, |doc_id| {
+            seen.push(doc_id);
+            true
+        })
+        .unwrap();
+        assert_eq!(seen, vec![doc_ids[0]]);
     }
 }
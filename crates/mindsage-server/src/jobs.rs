@@ -0,0 +1,119 @@
+//! Tracking shared by the maintenance jobs (re-embed, re-extract, offset
+//! backfill, metadata normalization).
+//!
+//! Each kind runs one job at a time in batches on a blocking thread. The
+//! id, status, progress counts and timestamps are common; the fields a job
+//! reports beyond those live in its own module, as the `J` of `Job<J>`.
+
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Pause between a job's batches, so searches and indexing are not starved.
+pub const JOB_BATCH_PAUSE: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Progress of a maintenance job. `detail` is serialized inline, next to
+/// the common fields.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Job<J> {
+    pub id: String,
+    pub status: JobStatus,
+    pub total: usize,
+    pub processed: usize,
+    #[serde(flatten)]
+    pub detail: J,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub started_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<i64>,
+}
+
+/// The current (or most recent) job of one kind.
+pub struct JobTracker<J> {
+    job: RwLock<Option<Job<J>>>,
+}
+
+impl<J> Default for JobTracker<J> {
+    fn default() -> Self {
+        Self { job: RwLock::new(None) }
+    }
+}
+
+impl<J: Clone> JobTracker<J> {
+    /// Register a new job over `total` items; returns `None` while another
+    /// job is running.
+    pub fn start(&self, total: usize, detail: J) -> Option<Job<J>> {
+        let mut current = self.job.write();
+        if current.as_ref().is_some_and(|j| j.status == JobStatus::Running) {
+            return None;
+        }
+        let job = Job {
+            id: uuid::Uuid::new_v4().to_string(),
+            status: JobStatus::Running,
+            total,
+            processed: 0,
+            detail,
+            error: None,
+            started_at: chrono::Utc::now().timestamp_millis(),
+            completed_at: None,
+        };
+        *current = Some(job.clone());
+        Some(job)
+    }
+
+    pub fn current(&self) -> Option<Job<J>> {
+        self.job.read().clone()
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut Job<J>)) {
+        if let Some(job) = self.job.write().as_mut() {
+            f(job);
+        }
+    }
+
+    /// End the job: completed, or failed with the error of `result`.
+    /// Returns the job as it ended.
+    pub fn finish(&self, result: Result<(), String>) -> Option<Job<J>> {
+        let mut current = self.job.write();
+        let job = current.as_mut()?;
+        job.completed_at = Some(chrono::Utc::now().timestamp_millis());
+        match result {
+            Ok(()) => job.status = JobStatus::Completed,
+            Err(e) => {
+                job.status = JobStatus::Failed;
+                job.error = Some(e);
+            }
+        }
+        Some(job.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_one_job_runs_at_a_time() {
+        let tracker = JobTracker::<()>::default();
+        assert!(tracker.start(5, ()).is_some());
+        assert!(tracker.start(5, ()).is_none());
+
+        let job = tracker.finish(Err("store closed".into())).unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.error.as_deref(), Some("store closed"));
+        assert!(job.completed_at.is_some());
+        assert_eq!(tracker.start(5, ()).unwrap().processed, 0);
+    }
+}
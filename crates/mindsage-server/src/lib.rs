@@ -0,0 +1,39 @@
+//! MindSage server — the HTTP API, background workers and CLI commands
+//! behind the `mindsage` binary. Built as a library too, so the scenario
+//! tests in `tests/` can run the full router in-process.
+
+pub mod audit;
+pub mod backfill_offsets;
+pub mod bench_search;
+pub mod browser_cleanup;
+pub mod bulk;
+pub mod catchup;
+pub mod cors;
+pub mod digests;
+pub mod error;
+pub mod feeds;
+pub mod health;
+pub mod indexing;
+pub mod jobs;
+pub mod localsend_listener;
+pub mod logging;
+pub mod ndjson;
+pub mod normalize_metadata;
+pub mod migrate;
+pub mod rate_limit;
+pub mod read_only;
+pub mod reembed;
+pub mod reextract;
+pub mod profiles;
+pub mod quota;
+pub mod request_trace;
+pub mod routes;
+pub mod saved_searches;
+pub mod shutdown;
+pub mod state;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod topic_generation;
+pub mod uploads;
+pub mod watcher;
+pub mod webhooks;
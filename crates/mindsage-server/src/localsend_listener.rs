@@ -0,0 +1,184 @@
+//! LocalSend protocol listener.
+//!
+//! LocalSend peers talk to the protocol port (53317 by default), not the main
+//! API port. This listener serves only the v2 protocol routes there, over
+//! HTTPS with the device's self-signed certificate unless the protocol is
+//! set to plain HTTP. TLS handshakes run in their own tasks so a slow or
+//! stalled client cannot hold up the accept loop, and each handshake uses
+//! the current certificate so a regenerated one takes effect immediately.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+
+use crate::health;
+use crate::routes;
+use crate::state::AppState;
+
+/// Time allowed for a client to finish the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Completed handshakes waiting for the server to pick them up.
+const ACCEPT_BACKLOG: usize = 64;
+
+/// Bind the protocol listener on `addr` and serve it in the background.
+/// Returns the bound address.
+pub async fn start_protocol_listener(state: Arc<AppState>, addr: SocketAddr) -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+    let app = routes::build_localsend_router(state.clone());
+    state.health.record_success(health::LOCALSEND_LISTENER);
+    let serve_state = state.clone();
+
+    if state.localsend_server.tls_config().is_some() {
+        info!("LocalSend protocol listening on https://{}", local_addr);
+        let tls = TlsListener::new(listener, state, local_addr);
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(tls, app).await {
+                warn!("LocalSend protocol listener stopped: {}", e);
+                serve_state.health.record_failure(health::LOCALSEND_LISTENER, format!("listener stopped: {}", e));
+            }
+        });
+    } else {
+        info!("LocalSend protocol listening on http://{}", local_addr);
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                warn!("LocalSend protocol listener stopped: {}", e);
+                serve_state.health.record_failure(health::LOCALSEND_LISTENER, format!("listener stopped: {}", e));
+            }
+        });
+    }
+    Ok(local_addr)
+}
+
+/// A TCP listener yielding connections that completed a TLS handshake.
+struct TlsListener {
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    fn new(listener: TcpListener, state: Arc<AppState>, local_addr: SocketAddr) -> Self {
+        let (tx, incoming) = mpsc::channel(ACCEPT_BACKLOG);
+        tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        debug!("LocalSend accept failed: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                if tx.is_closed() {
+                    break;
+                }
+                let Some(config) = state.localsend_server.tls_config() else {
+                    continue;
+                };
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let handshake = TlsAcceptor::from(config).accept(stream);
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake).await {
+                        Ok(Ok(tls)) => {
+                            let _ = tx.send((tls, peer)).await;
+                        }
+                        Ok(Err(e)) => debug!("LocalSend TLS handshake with {} failed: {}", peer, e),
+                        Err(_) => debug!("LocalSend TLS handshake with {} timed out", peer),
+                    }
+                });
+            }
+        });
+        Self { incoming, local_addr }
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(conn) => conn,
+            // The accept task only exits once the server is gone
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    async fn start(state: &Arc<AppState>) -> SocketAddr {
+        start_protocol_listener(state.clone(), "127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_plain_http_when_configured() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).https(false).build());
+        let addr = start(&state).await;
+
+        let info: serde_json::Value = reqwest::get(format!("http://{}/api/localsend/v2/info", addr))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(info["protocol"], "http");
+        assert!(state.localsend_server.tls_config().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_https_serves_certificate_with_advertised_fingerprint() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).https(true).build());
+        let addr = start(&state).await;
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+
+        let info: serde_json::Value = client
+            .get(format!("https://{}/api/localsend/v2/info", addr))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(info["protocol"], "https");
+        assert_eq!(info["fingerprint"], state.localsend_server.get_status().fingerprint);
+
+        // Plain HTTP is refused on the HTTPS port
+        assert!(reqwest::get(format!("http://{}/api/localsend/v2/info", addr)).await.is_err());
+
+        // A regenerated certificate is served to new connections
+        let fingerprint = state.localsend_server.regenerate_certificate().unwrap();
+        let info: serde_json::Value = client
+            .get(format!("https://{}/api/localsend/v2/info", addr))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(info["fingerprint"], fingerprint);
+    }
+}
@@ -0,0 +1,179 @@
+//! Log output: readable text by default, or one JSON object per line with
+//! `MINDSAGE_LOG_FORMAT=json` for log shippers such as Loki.
+//!
+//! A JSON line carries the timestamp, level, target, message, the event's
+//! other fields, and the enclosing spans with their fields. The request ID
+//! of the enclosing `request` span is copied to the top level so lines can
+//! be filtered by request without unpacking spans.
+
+use std::fmt;
+
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::request_trace::{self, FieldMap};
+
+/// Install the global subscriber: the log output filtered by `RUST_LOG`
+/// (default `info`), plus the span collector behind `?trace=true`.
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let output = if json_format() {
+        tracing_subscriber::fmt::layer()
+            .fmt_fields(JsonFields)
+            .event_format(JsonFormat)
+            .boxed()
+    } else {
+        tracing_subscriber::fmt::layer().boxed()
+    };
+    tracing_subscriber::registry()
+        .with(output.with_filter(filter))
+        .with(request_trace::layer())
+        .init();
+}
+
+fn json_format() -> bool {
+    std::env::var("MINDSAGE_LOG_FORMAT").is_ok_and(|v| v.eq_ignore_ascii_case("json"))
+}
+
+/// Formats span fields as a JSON object so `JsonFormat` can embed them.
+pub struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: tracing_subscriber::field::RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut map = FieldMap::default();
+        fields.record(&mut map);
+        write!(writer, "{}", serde_json::Value::Object(map.0))
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &tracing::span::Record<'_>,
+    ) -> fmt::Result {
+        let existing = match serde_json::from_str(&current.fields) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        let mut map = FieldMap(existing);
+        fields.record(&mut map);
+        current.fields = serde_json::Value::Object(map.0).to_string();
+        Ok(())
+    }
+}
+
+/// One JSON object per event.
+pub struct JsonFormat;
+
+impl<S> FormatEvent<S, JsonFields> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, JsonFields>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut fields = FieldMap::default();
+        event.record(&mut fields);
+
+        let mut line = serde_json::Map::new();
+        line.insert(
+            "timestamp".into(),
+            chrono::Utc::now()
+                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+                .into(),
+        );
+        line.insert("level".into(), metadata.level().to_string().into());
+        line.insert("target".into(), metadata.target().into());
+        if let Some(message) = fields.0.remove("message") {
+            line.insert("message".into(), message);
+        }
+        if !fields.0.is_empty() {
+            line.insert("fields".into(), serde_json::Value::Object(fields.0));
+        }
+
+        if let Some(scope) = ctx.event_scope() {
+            let mut spans = Vec::new();
+            for span in scope.from_root() {
+                let mut entry = serde_json::Map::new();
+                entry.insert("name".into(), span.name().into());
+                if let Some(formatted) = span.extensions().get::<FormattedFields<JsonFields>>() {
+                    if let Ok(serde_json::Value::Object(span_fields)) = serde_json::from_str(&formatted.fields) {
+                        if let Some(id) = span_fields.get("request_id") {
+                            line.insert("request_id".into(), id.clone());
+                        }
+                        entry.extend(span_fields);
+                    }
+                }
+                spans.push(serde_json::Value::Object(entry));
+            }
+            if !spans.is_empty() {
+                line.insert("spans".into(), spans.into());
+            }
+        }
+
+        writeln!(writer, "{}", serde_json::Value::Object(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_lines_carry_request_id_and_fields() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .fmt_fields(JsonFields)
+                .event_format(JsonFormat)
+                .with_writer(move || writer.clone()),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", request_id = "req-1", path = "/api/search");
+            span.in_scope(|| {
+                let stage = tracing::info_span!("search_stage", stage = "bm25", elapsed_ms = tracing::field::Empty);
+                stage.record("elapsed_ms", 1.5);
+                stage.in_scope(|| tracing::info!(hits = 3, "search done"));
+            });
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "search done");
+        assert_eq!(line["fields"]["hits"], 3);
+        assert_eq!(line["request_id"], "req-1");
+        assert_eq!(line["spans"][0]["path"], "/api/search");
+        assert_eq!(line["spans"][1]["stage"], "bm25");
+        assert_eq!(line["spans"][1]["elapsed_ms"], 1.5);
+    }
+}
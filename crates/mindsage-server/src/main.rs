@@ -1,17 +1,16 @@
 //! MindSage — single-binary privacy-first data aggregation server.
 
+use std::future::IntoFuture;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use tracing::info;
-use tracing_subscriber::EnvFilter;
+use tracing::{info, warn};
 
-mod indexing;
-pub mod migrate;
-mod routes;
-mod state;
-
-use state::AppState;
+use mindsage_server::state::AppState;
+use mindsage_server::{
+    bench_search, cors, health, localsend_listener, logging, migrate, profiles, routes, shutdown, watcher,
+};
 
 fn resolve_data_dir() -> PathBuf {
     std::env::var("MINDSAGE_DATA_DIR")
@@ -32,12 +31,8 @@ fn resolve_data_dir() -> PathBuf {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .init();
+    // Initialize tracing (MINDSAGE_LOG_FORMAT=json for JSON lines)
+    logging::init();
 
     let args: Vec<String> = std::env::args().collect();
 
@@ -55,20 +50,98 @@ async fn main() -> anyhow::Result<()> {
                 std::process::exit(if report.db_valid { 0 } else { 1 });
             }
             "--migrate" | "migrate" => {
-                if args.len() < 3 {
-                    eprintln!("Usage: mindsage migrate <source-data-dir> [target-data-dir]");
+                // `--verify <target>` checks a migration against its journal
+                if args.get(2).map(String::as_str) == Some("--verify") {
+                    let target = match args.get(3) {
+                        Some(dir) => PathBuf::from(dir),
+                        None => resolve_data_dir(),
+                    };
+                    let report = migrate::verify_migration(&target);
+                    migrate::print_verify_report(&report);
+                    std::process::exit(if report.is_ok() { 0 } else { 1 });
+                }
+                // `--profile <name>` migrates into data/profiles/<name>
+                let mut profile = None;
+                let mut positional = Vec::new();
+                let mut rest = args[2..].iter();
+                while let Some(arg) = rest.next() {
+                    if arg == "--profile" {
+                        profile = rest.next().cloned();
+                    } else {
+                        positional.push(arg);
+                    }
+                }
+                if positional.is_empty() {
+                    eprintln!("Usage: mindsage migrate <source-data-dir> [target-data-dir] [--profile <name>]");
+                    eprintln!("       mindsage migrate --verify [target-data-dir]");
                     std::process::exit(1);
                 }
-                let source = PathBuf::from(&args[2]);
-                let target = if args.len() > 3 {
-                    PathBuf::from(&args[3])
-                } else {
-                    resolve_data_dir()
+                let source = PathBuf::from(positional[0]);
+                let mut target = match positional.get(1) {
+                    Some(dir) => PathBuf::from(dir),
+                    None => resolve_data_dir(),
                 };
+                if let Some(name) = profile.filter(|n| n != mindsage_core::DEFAULT_PROFILE) {
+                    if !mindsage_core::is_valid_profile_name(&name) {
+                        eprintln!("Invalid profile name: {}", name);
+                        std::process::exit(1);
+                    }
+                    target = target.join("profiles").join(name);
+                }
                 let report = migrate::run_migration(&source, &target);
                 migrate::print_report(&report);
                 std::process::exit(if report.errors.is_empty() { 0 } else { 1 });
             }
+            "--reencrypt" | "reencrypt" => {
+                let data_dir = if args.len() > 2 {
+                    PathBuf::from(&args[2])
+                } else {
+                    resolve_data_dir()
+                };
+                let Some(encryption) = mindsage_store::crypto::EncryptionConfig::from_env()? else {
+                    eprintln!("Set {} to the new key first", mindsage_store::crypto::ENCRYPTION_KEY_ENV);
+                    std::process::exit(1);
+                };
+                let config = mindsage_core::MindSageConfig::from_env(&data_dir)?;
+                let store = mindsage_store::SqliteStore::open_with_encryption(
+                    &config.data_paths.vectordb,
+                    config.embedding_dim,
+                    Some(encryption),
+                )
+                .map_err(|e| anyhow::anyhow!("Failed to open store: {}", e))?;
+                let mut done = 0;
+                let rewritten = store
+                    .reencrypt(|batch| {
+                        done += batch;
+                        println!("  {} rows re-encrypted", done);
+                    })
+                    .map_err(|e| anyhow::anyhow!("Re-encryption failed: {}", e))?;
+                println!("Re-encrypted {} rows", rewritten);
+                return Ok(());
+            }
+            "store-key" => {
+                // Read from stdin so the key stays out of the shell history
+                let mut key = String::new();
+                std::io::stdin().read_line(&mut key)?;
+                let key = key.trim();
+                if key.is_empty() {
+                    eprintln!("Pipe the key to store on stdin");
+                    std::process::exit(1);
+                }
+                mindsage_store::crypto::store_key_in_keyring(key)?;
+                println!(
+                    "Stored the encryption key in the OS keyring; set {}=keyring to use it",
+                    mindsage_store::crypto::KEY_SOURCE_ENV
+                );
+                return Ok(());
+            }
+            "bench-search" => {
+                if let Err(e) = bench_search::run(&args[2..], resolve_data_dir()) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
             "--help" | "-h" | "help" => {
                 println!("MindSage — privacy-first data aggregation server");
                 println!();
@@ -78,6 +151,13 @@ async fn main() -> anyhow::Result<()> {
                 println!("  (none)                   Start the server");
                 println!("  validate [data-dir]      Validate existing database");
                 println!("  migrate <src> [dst]      Migrate data from Python installation");
+                println!("    --profile <name>       Migrate into a profile instead of the default");
+                println!("  migrate --verify [dst]   Check a migration target against its journal");
+                println!("  reencrypt [data-dir]     Encrypt stored text with MINDSAGE_ENCRYPTION_KEY");
+                println!("  store-key                Store the key read from stdin in the OS keyring");
+                println!("  bench-search [fixtures]  Compare search configurations on a relevance fixture");
+                println!("    -k <n>                 Hits scored per query (default 10)");
+                println!("    --data-dir <dir>       Store searched by fixtures without documents");
                 println!("  help                     Show this help message");
                 return Ok(());
             }
@@ -96,30 +176,89 @@ async fn main() -> anyhow::Result<()> {
     // Initialize configuration
     let config = mindsage_core::MindSageConfig::from_env(&data_dir)?;
     let port = config.port;
+    mindsage_core::redact::set_unredacted_logging(config.log_unredacted);
+    if config.log_unredacted {
+        warn!("Unredacted logging is on: file names and captured content will appear in logs");
+    }
 
-    // Initialize store
-    let store = mindsage_store::SqliteStore::open(&config.data_paths.vectordb, config.embedding_dim)
-        .map_err(|e| anyhow::anyhow!("Failed to open store: {}", e))?;
+    if config.read_only {
+        warn!("Read-only mode: nothing in {} will be changed; mutating requests are refused", data_dir.display());
+    }
+
+    // Initialize store (read-only with MINDSAGE_READ_ONLY=on)
+    let store = AppState::open_store(&config).map_err(|e| anyhow::anyhow!("Failed to open store: {}", e))?;
 
-    // Initialize embedder (ONNX if available, otherwise BM25-only)
+    // Initialize embedder (ONNX if available, otherwise BM25-only), with
+    // the configured text normalization in front of it
     let model_dir = data_dir.join("models");
-    let embedder = mindsage_infer::create_embedder(&model_dir);
+    let embedder = mindsage_infer::NormalizingEmbedder::wrap(
+        mindsage_infer::create_embedder(&model_dir),
+        mindsage_infer::TextNormalization::parse(&config.embed_normalization),
+    );
 
     // Build application state
     let state = Arc::new(AppState::new(config, store, embedder));
 
-    // Start background indexing queue
-    indexing::start_indexing_worker(state.clone());
+    // Stop gracefully on SIGTERM/SIGINT
+    shutdown::listen_for_signals(state.clone());
+
+    // Start the default profile's indexing queue (re-queues jobs saved at
+    // the last shutdown); other profiles start theirs when first used
+    let profiles = Arc::new(profiles::Profiles::start(state.clone()));
+
+    // Index files dropped into data/imports (MINDSAGE_WATCH_IMPORTS=on)
+    watcher::start_import_watcher(state.clone());
+
+    // Serve the LocalSend protocol on its own port (MINDSAGE_LOCALSEND_PORT=0
+    // disables); it only receives files, so not in read-only mode
+    let localsend_port = state.config.localsend_port;
+    if localsend_port != 0 && !state.config.read_only {
+        let addr = SocketAddr::new(state.config.localsend_bind, localsend_port);
+        match localsend_listener::start_protocol_listener(state.clone(), addr).await {
+            Ok(_) => info!("LocalSend protocol reachable from {}", cors::describe_exposure(addr.ip())),
+            Err(e) => {
+                warn!("LocalSend protocol listener not started on {}: {}", addr, e);
+                state.health.record_failure(health::LOCALSEND_LISTENER, format!("not started on {}: {}", addr, e));
+            }
+        }
+    }
 
     // Build router
-    let app = routes::build_router(state.clone());
+    let app = routes::build_app(profiles.clone());
 
     // Start server
-    let addr = format!("0.0.0.0:{}", port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    info!("MindSage server listening on {}", addr);
+    let addr = SocketAddr::new(state.config.bind_address, port);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!(
+        "MindSage server listening on {} (reachable from {}; CORS: {})",
+        addr,
+        cors::describe_exposure(addr.ip()),
+        cors::describe_cors(&state.config)
+    );
+
+    let shutdown_state = state.clone();
+    let mut server = tokio::spawn(
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(async move { shutdown_state.shutdown.wait().await })
+        .into_future(),
+    );
 
-    axum::serve(listener, app).await?;
+    tokio::select! {
+        result = &mut server => {
+            // The server only returns on its own if it failed
+            profiles.trigger_shutdown();
+            for state in profiles.loaded_states() {
+                shutdown::finish(&state);
+            }
+            result??;
+        }
+        _ = state.shutdown.wait() => {
+            shutdown::drain(&profiles, server).await;
+        }
+    }
 
     Ok(())
 }
@@ -6,11 +6,21 @@
 //! - Path adjustment in `.indexed-files.json`
 //! - LLM config migration
 //! - Browser connector state migration
+//!
+//! A migration records each finished step in `.migration-state.json` in the
+//! target, with fingerprints of the files it read and wrote, so an
+//! interrupted run resumes where it stopped and `verify_migration` can
+//! cross-check the target afterwards.
 
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
+use mindsage_core::paths;
 use rusqlite::Connection;
-use tracing::{error, info};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{error, info, warn};
 
 /// Result of a migration check or operation.
 #[derive(Debug)]
@@ -19,8 +29,17 @@ pub struct MigrationReport {
     pub documents: i64,
     pub chunks: i64,
     pub embeddings: i64,
+    /// Embeddings whose chunk no longer exists.
+    pub orphan_embeddings: i64,
     pub indexed_files_migrated: usize,
     pub llm_config_migrated: bool,
+    /// A journal from an earlier run was found and its finished steps
+    /// were checked rather than copied again.
+    pub resumed: bool,
+    /// Steps that copied or rewrote files in this run.
+    pub steps_run: Vec<String>,
+    /// Steps the journal already recorded whose files were unchanged.
+    pub steps_skipped: Vec<String>,
     pub warnings: Vec<String>,
     pub errors: Vec<String>,
 }
@@ -32,8 +51,12 @@ pub fn validate(data_dir: &Path) -> MigrationReport {
         documents: 0,
         chunks: 0,
         embeddings: 0,
+        orphan_embeddings: 0,
         indexed_files_migrated: 0,
         llm_config_migrated: false,
+        resumed: false,
+        steps_run: Vec::new(),
+        steps_skipped: Vec::new(),
         warnings: Vec::new(),
         errors: Vec::new(),
     };
@@ -154,6 +177,29 @@ pub fn validate(data_dir: &Path) -> MigrationReport {
         }
     }
 
+    // Check for embeddings left behind by deleted chunks
+    if let Ok(orphans) = conn.query_row(
+        "SELECT COUNT(*) FROM chunk_embeddings WHERE chunk_id NOT IN (SELECT id FROM chunks)",
+        [],
+        |row| row.get::<_, i64>(0),
+    ) {
+        report.orphan_embeddings = orphans;
+        if orphans > 0 {
+            report.warnings.push(format!("{} embeddings without chunks found", orphans));
+        }
+    }
+    if let Ok(0) = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_foreign_key_list('chunk_embeddings') \
+         WHERE \"table\" = 'chunks' AND on_delete = 'CASCADE'",
+        [],
+        |row| row.get::<_, i64>(0),
+    ) {
+        report.warnings.push(
+            "chunk_embeddings does not cascade chunk deletes; it is rebuilt when the server first opens the database"
+                .to_string(),
+        );
+    }
+
     // Check ancillary files
     let llm_config = data_dir.join("llm-config.json");
     if llm_config.exists() {
@@ -204,19 +250,18 @@ pub fn migrate_indexed_files(data_dir: &Path, new_data_dir: &Path) -> Result<usi
         .map_err(|e| format!("Invalid .indexed-files.json: {}", e))?;
 
     let old_prefix = data_dir.to_string_lossy();
-    let new_prefix = new_data_dir.to_string_lossy();
 
     let mut new_map = serde_json::Map::new();
     let mut count = 0;
 
     for (key, mut value) in map {
         // Update the key (file path)
-        let new_key = key.replace(old_prefix.as_ref(), new_prefix.as_ref());
+        let new_key = rebase_path(&key, &old_prefix, new_data_dir);
 
         // Update filePath inside the value
         if let Some(obj) = value.as_object_mut() {
             if let Some(fp) = obj.get("filePath").and_then(|v| v.as_str()) {
-                let new_fp = fp.replace(old_prefix.as_ref(), new_prefix.as_ref());
+                let new_fp = rebase_path(fp, &old_prefix, new_data_dir);
                 obj.insert("filePath".to_string(), serde_json::json!(new_fp));
             }
         }
@@ -234,8 +279,128 @@ pub fn migrate_indexed_files(data_dir: &Path, new_data_dir: &Path) -> Result<usi
     Ok(count)
 }
 
+/// `path` moved from under `old_data_dir` to under `new_data_dir`, or
+/// unchanged when it is elsewhere. Both are compared by component, so
+/// either separator matches whatever the host uses.
+fn rebase_path(path: &str, old_data_dir: &str, new_data_dir: &Path) -> String {
+    match paths::strip_path_prefix(path, old_data_dir) {
+        Some(rest) => rest
+            .into_iter()
+            .fold(new_data_dir.to_path_buf(), |dir, component| dir.join(component))
+            .to_string_lossy()
+            .to_string(),
+        None => path.to_string(),
+    }
+}
+
+/// Journal written in the target directory as a migration progresses.
+pub const MIGRATION_STATE_FILE: &str = ".migration-state.json";
+
+/// One unit of a migration, journaled once all of its files are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationStep {
+    Database,
+    LlmConfig,
+    IndexedFiles,
+    BrowserCaptures,
+    Imports,
+}
+
+impl MigrationStep {
+    /// Steps in the order they run; the database comes first.
+    pub const ALL: [MigrationStep; 5] = [
+        MigrationStep::Database,
+        MigrationStep::LlmConfig,
+        MigrationStep::IndexedFiles,
+        MigrationStep::BrowserCaptures,
+        MigrationStep::Imports,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MigrationStep::Database => "database",
+            MigrationStep::LlmConfig => "llm_config",
+            MigrationStep::IndexedFiles => "indexed_files",
+            MigrationStep::BrowserCaptures => "browser_captures",
+            MigrationStep::Imports => "imports",
+        }
+    }
+}
+
+/// Size and SHA-256 of a file, with its path relative to the data directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// A file a step read from the source and the file it wrote in the target.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigratedFile {
+    pub source: Fingerprint,
+    pub target: Fingerprint,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepRecord {
+    pub completed_at: String,
+    pub files: Vec<MigratedFile>,
+}
+
+/// Contents of `.migration-state.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationJournal {
+    pub source: String,
+    pub started_at: String,
+    #[serde(default)]
+    pub completed_at: Option<String>,
+    /// Finished steps by `MigrationStep::name`.
+    #[serde(default)]
+    pub steps: BTreeMap<String, StepRecord>,
+}
+
+impl MigrationJournal {
+    pub fn load(target_dir: &Path) -> Result<Option<Self>, String> {
+        let path = target_dir.join(MIGRATION_STATE_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", MIGRATION_STATE_FILE, e))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Invalid {}: {}", MIGRATION_STATE_FILE, e))
+    }
+
+    /// Write through a temporary file so a crash leaves the previous journal.
+    fn save(&self, target_dir: &Path) -> Result<(), String> {
+        let path = target_dir.join(MIGRATION_STATE_FILE);
+        let tmp = path.with_extension("json.tmp");
+        let output = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize: {}", e))?;
+        std::fs::write(&tmp, output)
+            .and_then(|_| std::fs::rename(&tmp, &path))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
 /// Run the full migration: validate source, copy DB and state files.
+///
+/// Resumes from `.migration-state.json` when the target holds one.
 pub fn run_migration(source_dir: &Path, target_dir: &Path) -> MigrationReport {
+    migrate_with_hook(source_dir, target_dir, &mut |_| Ok(()))
+}
+
+/// `run_migration`, calling `after_step` once each step is journaled.
+/// An error from the hook stops the migration there, as a crash would.
+fn migrate_with_hook(
+    source_dir: &Path,
+    target_dir: &Path,
+    after_step: &mut dyn FnMut(MigrationStep) -> Result<(), String>,
+) -> MigrationReport {
     info!("Starting migration: {} → {}", source_dir.display(), target_dir.display());
 
     let mut report = validate(source_dir);
@@ -243,6 +408,9 @@ pub fn run_migration(source_dir: &Path, target_dir: &Path) -> MigrationReport {
         error!("Source database validation failed");
         return report;
     }
+    // validate() counts what the source has; these count what was migrated
+    report.llm_config_migrated = false;
+    report.indexed_files_migrated = 0;
 
     info!(
         "Source validated: {} documents, {} chunks, {} embeddings",
@@ -250,84 +418,323 @@ pub fn run_migration(source_dir: &Path, target_dir: &Path) -> MigrationReport {
     );
 
     // Ensure target directories exist
-    let target_vectordb = target_dir.join("vectordb");
-    let target_uploads = target_dir.join("uploads");
-    let target_imports = target_dir.join("imports");
-    let target_exports = target_dir.join("exports");
-    let target_browser = target_dir.join("browser-connector");
-
-    for dir in [&target_vectordb, &target_uploads, &target_imports, &target_exports, &target_browser] {
-        if let Err(e) = std::fs::create_dir_all(dir) {
+    for dir in ["vectordb", "uploads", "imports", "exports", "browser-connector"] {
+        let dir = target_dir.join(dir);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
             report.errors.push(format!("Failed to create {}: {}", dir.display(), e));
             return report;
         }
     }
 
-    // Copy database file (not WAL — let SQLite rebuild it)
-    let src_db = source_dir.join("vectordb/mindsage.db");
-    let dst_db = target_vectordb.join("mindsage.db");
-    if src_db != dst_db {
-        if let Err(e) = std::fs::copy(&src_db, &dst_db) {
-            report.errors.push(format!("Failed to copy database: {}", e));
+    let source_name = std::fs::canonicalize(source_dir)
+        .unwrap_or_else(|_| source_dir.to_path_buf())
+        .to_string_lossy()
+        .to_string();
+    let mut journal = match MigrationJournal::load(target_dir) {
+        Ok(Some(journal)) if journal.source != source_name => {
+            report.errors.push(format!(
+                "{} holds a migration from {}; remove it to migrate from {}",
+                target_dir.display(),
+                journal.source,
+                source_name
+            ));
+            return report;
+        }
+        Ok(Some(journal)) => {
+            info!("Resuming migration: {} steps already done", journal.steps.len());
+            report.resumed = true;
+            journal
+        }
+        Ok(None) => MigrationJournal {
+            source: source_name,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            completed_at: None,
+            steps: BTreeMap::new(),
+        },
+        Err(e) => {
+            report.errors.push(e);
+            return report;
+        }
+    };
+
+    for step in MigrationStep::ALL {
+        let previous = journal.steps.get(step.name()).cloned();
+        let files = match run_step(step, source_dir, target_dir, previous.as_ref(), &mut report) {
+            Ok(files) => files,
+            Err(e) => {
+                report.errors.push(e);
+                return report;
+            }
+        };
+        let copied = files.iter().filter(|(_, written)| *written).count();
+        let files: Vec<MigratedFile> = files.into_iter().map(|(file, _)| file).collect();
+
+        match step {
+            MigrationStep::LlmConfig => report.llm_config_migrated = !files.is_empty(),
+            MigrationStep::IndexedFiles => {
+                report.indexed_files_migrated =
+                    count_indexed_files(&target_dir.join(".indexed-files.json"));
+            }
+            _ => {}
+        }
+        if previous.is_some() && copied == 0 {
+            report.steps_skipped.push(step.name().to_string());
+            continue;
+        }
+        report.steps_run.push(step.name().to_string());
+
+        journal.steps.insert(
+            step.name().to_string(),
+            StepRecord { completed_at: chrono::Utc::now().to_rfc3339(), files },
+        );
+        if let Err(e) = journal.save(target_dir) {
+            report.errors.push(e);
+            return report;
+        }
+        if let Err(e) = after_step(step) {
+            report.errors.push(format!("Migration stopped after {}: {}", step.name(), e));
             return report;
         }
-        info!("Copied database to {}", dst_db.display());
     }
 
-    // Copy LLM config
-    let src_llm = source_dir.join("llm-config.json");
-    let dst_llm = target_dir.join("llm-config.json");
-    if src_llm.exists() && src_llm != dst_llm {
-        if let Err(e) = std::fs::copy(&src_llm, &dst_llm) {
-            report.warnings.push(format!("Failed to copy llm-config.json: {}", e));
-        } else {
-            report.llm_config_migrated = true;
-            info!("Copied llm-config.json");
+    if journal.completed_at.is_none() || !report.steps_run.is_empty() {
+        journal.completed_at = Some(chrono::Utc::now().to_rfc3339());
+        if let Err(e) = journal.save(target_dir) {
+            report.errors.push(e);
+            return report;
         }
     }
 
-    // Migrate indexed files with path adjustment
-    match migrate_indexed_files(source_dir, target_dir) {
-        Ok(count) => {
-            report.indexed_files_migrated = count;
-            if count > 0 {
-                info!("Migrated {} indexed file records", count);
+    info!("Migration complete");
+    report
+}
+
+/// Source and target paths, relative to their data directories, of the
+/// files `step` migrates.
+fn step_files(step: MigrationStep, source_dir: &Path) -> Vec<String> {
+    match step {
+        MigrationStep::Database => vec!["vectordb/mindsage.db".to_string()],
+        MigrationStep::LlmConfig => vec!["llm-config.json".to_string()],
+        MigrationStep::IndexedFiles => vec![".indexed-files.json".to_string()],
+        MigrationStep::BrowserCaptures => list_files(source_dir, "browser-connector/captures"),
+        MigrationStep::Imports => list_files(source_dir, "imports"),
+    }
+    .into_iter()
+    .filter(|rel| source_dir.join(rel).is_file())
+    .collect()
+}
+
+fn list_files(source_dir: &Path, dir: &str) -> Vec<String> {
+    let mut files: Vec<String> = std::fs::read_dir(source_dir.join(dir))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.file_type().map(|t| t.is_file()).unwrap_or(false))
+                .map(|entry| format!("{}/{}", dir, entry.file_name().to_string_lossy()))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Migrate the files of one step, each paired with whether it was written
+/// in this run. Files the journal already records are left alone when the
+/// target still matches; a target changed since it was migrated is kept
+/// and reported rather than copied over. Errors only for failures that
+/// stop the migration (the database); the rest become warnings.
+fn run_step(
+    step: MigrationStep,
+    source_dir: &Path,
+    target_dir: &Path,
+    previous: Option<&StepRecord>,
+    report: &mut MigrationReport,
+) -> Result<Vec<(MigratedFile, bool)>, String> {
+    let mut files = Vec::new();
+    for rel in step_files(step, source_dir) {
+        let src = source_dir.join(&rel);
+        let dst = target_dir.join(&rel);
+        let source = match fingerprint(&src, &rel) {
+            Ok(fp) => fp,
+            Err(e) if step == MigrationStep::Database => return Err(e),
+            Err(e) => {
+                report.warnings.push(e);
+                continue;
+            }
+        };
+
+        let recorded = previous.and_then(|record| record.files.iter().find(|f| f.target.path == rel));
+        if let Some(recorded) = recorded {
+            match fingerprint(&dst, &rel) {
+                Ok(current) if current == recorded.target => {
+                    if recorded.source == source {
+                        files.push((recorded.clone(), false));
+                        continue;
+                    }
+                    info!("{} changed in the source since it was migrated; copying again", rel);
+                }
+                Ok(_) => {
+                    warn!("{} was changed in the target after it was migrated", rel);
+                    report.warnings.push(format!(
+                        "{} was changed in the target after it was migrated; left as is",
+                        rel
+                    ));
+                    files.push((recorded.clone(), false));
+                    continue;
+                }
+                // Missing from the target: write it again
+                Err(_) => {}
             }
         }
-        Err(e) => {
-            report.warnings.push(format!("Failed to migrate indexed files: {}", e));
+
+        let written = if src == dst {
+            Ok(())
+        } else if step == MigrationStep::IndexedFiles {
+            migrate_indexed_files(source_dir, target_dir).map(|_| ())
+        } else {
+            if let Some(parent) = dst.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            std::fs::copy(&src, &dst)
+                .map(|_| ())
+                .map_err(|e| format!("Failed to copy {}: {}", rel, e))
+        };
+        match written {
+            Ok(()) => {}
+            Err(e) if step == MigrationStep::Database => return Err(e),
+            Err(e) => {
+                report.warnings.push(e);
+                continue;
+            }
         }
+        let target = fingerprint(&dst, &rel)?;
+        info!("Migrated {}", rel);
+        files.push((MigratedFile { source, target }, true));
     }
+    Ok(files)
+}
 
-    // Copy browser connector state
-    let src_captures = source_dir.join("browser-connector/captures");
-    let dst_captures = target_browser.join("captures");
-    if src_captures.exists() {
-        if let Err(e) = std::fs::create_dir_all(&dst_captures) {
-            report.warnings.push(format!("Failed to create captures dir: {}", e));
-        } else if let Ok(entries) = std::fs::read_dir(&src_captures) {
-            for entry in entries.flatten() {
-                let dst = dst_captures.join(entry.file_name());
-                let _ = std::fs::copy(entry.path(), dst);
+fn fingerprint(path: &Path, rel: &str) -> Result<Fingerprint, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Cannot read {}: {}", rel, e))?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).map_err(|e| format!("Cannot read {}: {}", rel, e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok(Fingerprint { path: rel.to_string(), size, sha256: hex::encode(hasher.finalize()) })
+}
+
+fn count_indexed_files(path: &Path) -> usize {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| {
+            serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&content).ok()
+        })
+        .map(|map| map.len())
+        .unwrap_or(0)
+}
+
+/// Result of `verify_migration`.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub source: Option<String>,
+    pub steps_done: Vec<String>,
+    pub steps_pending: Vec<String>,
+    pub files_checked: usize,
+    /// Files changed in the target since they were migrated. Expected for
+    /// the database once a server has opened it.
+    pub modified: Vec<String>,
+    pub missing: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && self.steps_pending.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Cross-check a migration target against its `.migration-state.json`:
+/// every step finished, and every migrated file present and unchanged.
+pub fn verify_migration(target_dir: &Path) -> VerifyReport {
+    let mut report = VerifyReport::default();
+    let journal = match MigrationJournal::load(target_dir) {
+        Ok(Some(journal)) => journal,
+        Ok(None) => {
+            report.errors.push(format!(
+                "No {} in {}",
+                MIGRATION_STATE_FILE,
+                target_dir.display()
+            ));
+            return report;
+        }
+        Err(e) => {
+            report.errors.push(e);
+            return report;
+        }
+    };
+    report.source = Some(journal.source.clone());
+
+    for step in MigrationStep::ALL {
+        let Some(record) = journal.steps.get(step.name()) else {
+            report.steps_pending.push(step.name().to_string());
+            continue;
+        };
+        report.steps_done.push(step.name().to_string());
+        for file in &record.files {
+            report.files_checked += 1;
+            let path: PathBuf = target_dir.join(&file.target.path);
+            match fingerprint(&path, &file.target.path) {
+                Ok(current) if current == file.target => {}
+                Ok(_) => report.modified.push(file.target.path.clone()),
+                Err(_) => report.missing.push(file.target.path.clone()),
             }
         }
     }
+    report
+}
 
-    // Copy import files
-    let src_imports = source_dir.join("imports");
-    if src_imports.exists() {
-        if let Ok(entries) = std::fs::read_dir(&src_imports) {
-            for entry in entries.flatten() {
-                if entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
-                    let dst = target_imports.join(entry.file_name());
-                    let _ = std::fs::copy(entry.path(), dst);
-                }
+/// Print a verification report to stdout.
+pub fn print_verify_report(report: &VerifyReport) {
+    println!("=== MindSage Migration Verification ===");
+    println!();
+    if let Some(source) = &report.source {
+        println!("Source:             {}", source);
+    }
+    println!("Steps done:         {}", report.steps_done.join(", "));
+    if !report.steps_pending.is_empty() {
+        println!("Steps pending:      {}", report.steps_pending.join(", "));
+    }
+    println!("Files checked:      {}", report.files_checked);
+
+    for (title, paths) in [
+        ("Missing from target:", &report.missing),
+        ("Changed since migration:", &report.modified),
+        ("Errors:", &report.errors),
+    ] {
+        if !paths.is_empty() {
+            println!();
+            println!("{}", title);
+            for path in paths {
+                println!("  - {}", path);
             }
         }
     }
 
-    info!("Migration complete");
-    report
+    println!();
+    if report.is_ok() {
+        println!("Status: COMPLETE");
+    } else if !report.steps_pending.is_empty() {
+        println!("Status: INCOMPLETE (run `mindsage migrate` again with the same source to resume)");
+    } else {
+        println!("Status: FAILED");
+    }
 }
 
 /// Print a migration report to stdout.
@@ -338,8 +745,20 @@ pub fn print_report(report: &MigrationReport) {
     println!("Documents:          {}", report.documents);
     println!("Chunks:             {}", report.chunks);
     println!("Embeddings:         {}", report.embeddings);
+    println!("Orphan embeddings:  {}", report.orphan_embeddings);
     println!("Indexed files:      {}", report.indexed_files_migrated);
     println!("LLM config:         {}", if report.llm_config_migrated { "migrated" } else { "not found" });
+    if report.resumed {
+        println!("Run:                resumed");
+        if !report.steps_skipped.is_empty() {
+            println!("Steps skipped:      {}", report.steps_skipped.join(", "));
+        }
+    } else if !report.steps_run.is_empty() {
+        println!("Run:                fresh");
+    }
+    if !report.steps_run.is_empty() {
+        println!("Steps run:          {}", report.steps_run.join(", "));
+    }
 
     if !report.warnings.is_empty() {
         println!();
@@ -452,6 +871,28 @@ mod tests {
         assert_eq!(report.documents, 1);
         assert_eq!(report.chunks, 1);
         assert!(report.errors.is_empty());
+        assert_eq!(report.orphan_embeddings, 0);
+        assert!(report.warnings.iter().any(|w| w.contains("does not cascade")));
+    }
+
+    #[test]
+    fn test_validate_counts_embeddings_without_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        setup_test_db(dir.path());
+        let conn = Connection::open(dir.path().join("vectordb/mindsage.db")).unwrap();
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+             INSERT INTO chunk_embeddings VALUES (1, zeroblob(384), 1.0, 0.0);
+             INSERT INTO chunk_embeddings VALUES (7, zeroblob(384), 1.0, 0.0);
+             INSERT INTO chunk_embeddings VALUES (8, zeroblob(384), 1.0, 0.0);",
+        )
+        .unwrap();
+
+        let report = validate(dir.path());
+        assert!(report.db_valid);
+        assert_eq!(report.embeddings, 3);
+        assert_eq!(report.orphan_embeddings, 2);
+        assert!(report.warnings.contains(&"2 embeddings without chunks found".to_string()));
     }
 
     #[test]
@@ -487,6 +928,24 @@ mod tests {
         assert!(dst_path.exists());
     }
 
+    #[test]
+    fn test_rebase_path_either_separator() {
+        let new_dir = Path::new("/srv/mindsage/data");
+        let moved = new_dir.join("imports").join("notes.txt").to_string_lossy().to_string();
+
+        // Windows install paths, with the old data dir given either way
+        let windows = "C:\\Users\\me\\mindsage\\data\\imports\\notes.txt";
+        assert_eq!(rebase_path(windows, "c:/Users/me/mindsage/data", new_dir), moved);
+        assert_eq!(rebase_path(windows, "C:\\Users\\me\\mindsage\\data\\", new_dir), moved);
+        let forward = "C:/Users/me/mindsage/data/imports/notes.txt";
+        assert_eq!(rebase_path(forward, "C:\\Users\\me\\mindsage\\data", new_dir), moved);
+        assert_eq!(rebase_path("/app/data/imports/notes.txt", "/app/data", new_dir), moved);
+
+        // A sibling directory sharing the text of the prefix is left alone
+        let sibling = "/app/data2/imports/notes.txt";
+        assert_eq!(rebase_path(sibling, "/app/data", new_dir), sibling);
+    }
+
     #[test]
     fn test_run_migration() {
         let src = tempfile::tempdir().unwrap();
@@ -510,4 +969,86 @@ mod tests {
         assert!(dst.path().join("vectordb/mindsage.db").exists());
         assert!(dst.path().join("llm-config.json").exists());
     }
+
+    fn setup_source(dir: &Path) {
+        setup_test_db(dir);
+        std::fs::write(dir.join("llm-config.json"), r#"{"preferredProvider":"auto"}"#).unwrap();
+        std::fs::create_dir_all(dir.join("imports")).unwrap();
+        std::fs::write(dir.join("imports/notes.txt"), "notes").unwrap();
+        std::fs::create_dir_all(dir.join("browser-connector/captures")).unwrap();
+        std::fs::write(dir.join("browser-connector/captures/c1.json"), "{}").unwrap();
+    }
+
+    #[test]
+    fn test_interrupted_migration_resumes_remaining_steps() {
+        let src = tempfile::tempdir().unwrap();
+        let dst = tempfile::tempdir().unwrap();
+        setup_source(src.path());
+
+        let report = migrate_with_hook(src.path(), dst.path(), &mut |step| match step {
+            MigrationStep::Database => Err("killed".to_string()),
+            _ => Ok(()),
+        });
+        assert!(!report.resumed);
+        assert_eq!(report.steps_run, vec!["database"]);
+        assert!(report.errors[0].contains("after database"));
+        assert!(dst.path().join("vectordb/mindsage.db").exists());
+        assert!(!dst.path().join("llm-config.json").exists());
+
+        let partial = verify_migration(dst.path());
+        assert!(!partial.is_ok());
+        assert_eq!(partial.steps_done, vec!["database"]);
+        assert_eq!(partial.steps_pending.len(), 4);
+
+        // The database copy is checked, not repeated
+        let mut ran = Vec::new();
+        let report = migrate_with_hook(src.path(), dst.path(), &mut |step| {
+            ran.push(step);
+            Ok(())
+        });
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert!(report.resumed);
+        assert_eq!(report.steps_skipped, vec!["database"]);
+        assert_eq!(ran, &MigrationStep::ALL[1..]);
+        assert!(report.llm_config_migrated);
+        assert_eq!(std::fs::read_to_string(dst.path().join("imports/notes.txt")).unwrap(), "notes");
+        assert!(dst.path().join("browser-connector/captures/c1.json").exists());
+
+        let verified = verify_migration(dst.path());
+        assert!(verified.is_ok(), "{:?}", verified);
+        assert_eq!(verified.files_checked, 4);
+        let journal = MigrationJournal::load(dst.path()).unwrap().unwrap();
+        assert!(journal.completed_at.is_some());
+    }
+
+    #[test]
+    fn test_rerun_keeps_target_changes_and_copies_new_files() {
+        let src = tempfile::tempdir().unwrap();
+        let dst = tempfile::tempdir().unwrap();
+        setup_source(src.path());
+        assert!(run_migration(src.path(), dst.path()).errors.is_empty());
+
+        std::fs::write(dst.path().join("imports/notes.txt"), "edited").unwrap();
+        std::fs::remove_file(dst.path().join("llm-config.json")).unwrap();
+        std::fs::write(src.path().join("imports/later.txt"), "later").unwrap();
+
+        let verified = verify_migration(dst.path());
+        assert_eq!(verified.modified, vec!["imports/notes.txt"]);
+        assert_eq!(verified.missing, vec!["llm-config.json"]);
+        assert!(!verified.is_ok());
+
+        let report = run_migration(src.path(), dst.path());
+        assert!(report.resumed);
+        assert_eq!(report.steps_run, vec!["llm_config", "imports"]);
+        assert!(report.warnings.iter().any(|w| w.contains("imports/notes.txt was changed in the target")));
+        assert_eq!(std::fs::read_to_string(dst.path().join("imports/notes.txt")).unwrap(), "edited");
+        assert!(dst.path().join("imports/later.txt").exists());
+        assert!(dst.path().join("llm-config.json").exists());
+
+        // Another source cannot continue this target's migration
+        let other = tempfile::tempdir().unwrap();
+        setup_test_db(other.path());
+        let report = run_migration(other.path(), dst.path());
+        assert!(report.errors[0].contains("holds a migration from"));
+    }
 }
@@ -0,0 +1,86 @@
+//! NDJSON (`application/x-ndjson`) responses: one JSON value per line,
+//! written on a blocking thread and sent while it is produced, so large
+//! listings and exports never sit in memory whole.
+//!
+//! The status is sent before the first line, so an error partway through
+//! is reported as a final `{"error": "..."}` line.
+
+use std::convert::Infallible;
+
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::warn;
+
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Lines buffered ahead of a slow client.
+const BUFFERED_LINES: usize = 64;
+
+/// Whether the request's `Accept` header asks for NDJSON.
+pub fn wants_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|media| media.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case(NDJSON_CONTENT_TYPE))
+}
+
+/// Writes lines to a streaming response.
+pub struct LineWriter {
+    tx: mpsc::Sender<Result<Bytes, Infallible>>,
+}
+
+impl LineWriter {
+    /// Send `value` as one line. False once the client has gone away; the
+    /// producer should stop then.
+    pub fn send<T: Serialize>(&self, value: &T) -> bool {
+        let mut line = match serde_json::to_vec(value) {
+            Ok(line) => line,
+            Err(e) => return self.error(&e.to_string()),
+        };
+        line.push(b'\n');
+        self.tx.blocking_send(Ok(Bytes::from(line))).is_ok()
+    }
+
+    /// Send the final `{"error": message}` line.
+    pub fn error(&self, message: &str) -> bool {
+        warn!("NDJSON stream ended early: {}", message);
+        self.send(&serde_json::json!({ "error": message }))
+    }
+}
+
+/// A response whose body is the lines `produce` writes on a blocking
+/// thread.
+pub fn stream(produce: impl FnOnce(&LineWriter) + Send + 'static) -> Response {
+    let (tx, rx) = mpsc::channel(BUFFERED_LINES);
+    tokio::task::spawn_blocking(move || produce(&LineWriter { tx }));
+    (
+        [(header::CONTENT_TYPE, HeaderValue::from_static(NDJSON_CONTENT_TYPE))],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_negotiation() {
+        let accept = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_str(value).unwrap());
+            wants_ndjson(&headers)
+        };
+        assert!(accept("application/x-ndjson"));
+        assert!(accept("application/json;q=0.5, Application/X-NDJSON; q=1"));
+        assert!(!accept("application/json"));
+        assert!(!accept("*/*"));
+        assert!(!wants_ndjson(&HeaderMap::new()));
+    }
+}
@@ -0,0 +1,160 @@
+//! Metadata normalization for documents written before it existed.
+//!
+//! New writes are normalized by the store (see `mindsage_store::metadata`);
+//! this job brings older rows to the current `METADATA_SCHEMA_VERSION` in
+//! batches, counting the changes per rule and listing the first documents
+//! changed. Metadata that is not a JSON object is left alone and listed.
+//! Batches are paused between, like the offset backfill.
+
+use std::collections::BTreeMap;
+
+use mindsage_store::metadata::MetadataChange;
+use serde::Serialize;
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::jobs::{Job, JobStatus, JOB_BATCH_PAUSE};
+use crate::state::AppState;
+
+/// Documents normalized per batch, in one transaction.
+const NORMALIZE_BATCH_SIZE: usize = 200;
+/// Changed and unreadable documents kept on the job; the counts cover the
+/// rest.
+const MAX_LISTED_DOCUMENTS: usize = 100;
+
+/// The changes made to one document's metadata.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentMetadataChanges {
+    pub doc_id: i64,
+    pub changes: Vec<MetadataChange>,
+}
+
+/// Report of the metadata normalization job, beyond the common fields.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizeProgress {
+    /// Documents whose metadata was rewritten.
+    pub changed: usize,
+    /// Documents whose metadata is not a JSON object.
+    pub unreadable: usize,
+    /// Changes made, per rule (`renamed_key`, `topics_to_array`, ...).
+    pub rules: BTreeMap<String, usize>,
+    /// The first documents changed, with their changes.
+    pub changed_documents: Vec<DocumentMetadataChanges>,
+    /// Ids of the first unreadable documents.
+    pub unreadable_documents: Vec<i64>,
+}
+
+/// Progress and report of the metadata normalization job.
+pub type NormalizeJob = Job<NormalizeProgress>;
+
+/// Normalize up to the started job's `total` documents with stale
+/// metadata. Blocking; run it on a blocking thread.
+pub fn run_normalization(state: &AppState) {
+    let Some(job) = state.metadata_normalization.current() else {
+        return;
+    };
+    let mut after_id = 0;
+    let mut processed = 0;
+
+    let result = loop {
+        let limit = NORMALIZE_BATCH_SIZE.min(job.total - processed);
+        if limit == 0 {
+            break Ok(());
+        }
+        let batch = match state.store.normalize_stale_metadata(after_id, limit) {
+            Ok(b) => b,
+            Err(e) => break Err(e.to_string()),
+        };
+        let Some(last_id) = batch.last_id else {
+            break Ok(());
+        };
+        after_id = last_id;
+        processed += batch.scanned;
+
+        state.metadata_normalization.update(|j| {
+            j.processed = processed;
+            let progress = &mut j.detail;
+            progress.changed += batch.changed.len();
+            progress.unreadable += batch.unreadable.len();
+            for change in batch.changed.iter().flat_map(|(_, changes)| changes) {
+                *progress.rules.entry(change.rule().to_string()).or_insert(0) += 1;
+            }
+            let room = MAX_LISTED_DOCUMENTS.saturating_sub(progress.changed_documents.len());
+            progress.changed_documents.extend(
+                batch
+                    .changed
+                    .into_iter()
+                    .take(room)
+                    .map(|(doc_id, changes)| DocumentMetadataChanges { doc_id, changes }),
+            );
+            let room = MAX_LISTED_DOCUMENTS.saturating_sub(progress.unreadable_documents.len());
+            progress.unreadable_documents.extend(batch.unreadable.into_iter().take(room));
+        });
+        std::thread::sleep(JOB_BATCH_PAUSE);
+    };
+
+    if let Err(e) = &result {
+        error!("Metadata normalization failed: {}", e);
+    }
+    if let Some(j) = state.metadata_normalization.finish(result).filter(|j| j.status == JobStatus::Completed) {
+        info!(
+            "Normalized the metadata of {} documents ({} changed, {} unreadable)",
+            j.processed, j.detail.changed, j.detail.unreadable
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    #[test]
+    fn test_job_reports_changes_per_rule() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+
+        // Rows from before normalization, written straight to the database
+        let db = state.config.data_paths.vectordb.join("mindsage.db");
+        let conn = rusqlite::Connection::open(&db).unwrap();
+        for (i, metadata) in [
+            r#"{"Source":"upload","topics":"rust"}"#,
+            r#"{"source":"file","timestamp":"1700000000"}"#,
+            r#"{"source":"file"}"#,
+            "not json",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            conn.execute(
+                "INSERT INTO documents (text, metadata_json, created_at) VALUES (?1, ?2, 0)",
+                rusqlite::params![format!("doc {}", i), metadata],
+            )
+            .unwrap();
+        }
+        drop(conn);
+        assert_eq!(state.store.count_documents_with_stale_metadata().unwrap(), 4);
+
+        state.metadata_normalization.start(4, NormalizeProgress::default()).unwrap();
+        assert!(state.metadata_normalization.start(4, NormalizeProgress::default()).is_none());
+        run_normalization(&state);
+        let job = state.metadata_normalization.current().unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+        let report = &job.detail;
+        assert_eq!((job.processed, report.changed, report.unreadable), (4, 2, 1));
+        let rules: Vec<(&str, usize)> = report.rules.iter().map(|(r, n)| (r.as_str(), *n)).collect();
+        assert_eq!(rules, vec![("numeric_timestamp", 1), ("renamed_key", 1), ("topics_to_array", 1)]);
+        assert_eq!(report.changed_documents[0].changes.len(), 2);
+        assert_eq!(report.unreadable_documents.len(), 1);
+        assert_eq!(state.store.count_documents_with_stale_metadata().unwrap(), 0);
+
+        let doc = state.store.get_document(report.changed_documents[0].doc_id).unwrap().unwrap();
+        assert_eq!(doc.metadata.unwrap(), serde_json::json!({"source": "upload", "topics": ["rust"]}));
+        assert!(state.metadata_normalization.start(1, NormalizeProgress::default()).is_some());
+    }
+}
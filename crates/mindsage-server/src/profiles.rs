@@ -0,0 +1,247 @@
+//! Profiles — separate data directories for people sharing one server.
+//!
+//! The `default` profile is the data directory itself, so existing installs
+//! keep working unchanged. Every other profile lives under
+//! `data/profiles/<name>/` with its own store, uploads, browser and
+//! connector state, webhooks, and indexing worker. The embedder and LLM configuration
+//! are shared. A profile is opened the first time a request selects it and
+//! stays open until shutdown or deletion.
+//!
+//! Requests select a profile with the `X-Profile` header or a
+//! `/profiles/<name>` path prefix (`/profiles/alice/api/stats`); the prefix
+//! wins when both are given.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::Router;
+use mindsage_core::{is_valid_profile_name, Error, Result, DEFAULT_PROFILE};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::browser_cleanup;
+use crate::digests;
+use crate::feeds;
+use crate::indexing;
+use crate::routes;
+use crate::state::AppState;
+use crate::uploads;
+use crate::webhooks;
+
+/// Header selecting the profile of a request.
+pub const PROFILE_HEADER: &str = "x-profile";
+
+/// Time a deleted profile's indexing worker gets to finish its current file.
+const DELETE_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A profile as listed by `GET /api/profiles`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileInfo {
+    pub name: String,
+    pub default: bool,
+    /// Opened since the server started.
+    pub loaded: bool,
+    pub data_dir: String,
+}
+
+struct LoadedProfile {
+    state: Arc<AppState>,
+    router: Router,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// Open profiles by name, including the default one.
+pub struct Profiles {
+    default: Arc<AppState>,
+    loaded: Mutex<HashMap<String, LoadedProfile>>,
+}
+
+impl Profiles {
+    /// Register the default profile and start its indexing worker.
+    pub fn start(default: Arc<AppState>) -> Self {
+        let worker = start_background_tasks(&default);
+        let mut loaded = HashMap::new();
+        loaded.insert(
+            DEFAULT_PROFILE.to_string(),
+            LoadedProfile {
+                state: default.clone(),
+                router: routes::build_profile_router(default.clone()),
+                worker,
+            },
+        );
+        Self {
+            default,
+            loaded: Mutex::new(loaded),
+        }
+    }
+
+    pub fn default_state(&self) -> &Arc<AppState> {
+        &self.default
+    }
+
+    /// Whether a profile with this name exists on disk.
+    pub fn exists(&self, name: &str) -> bool {
+        name == DEFAULT_PROFILE
+            || (is_valid_profile_name(name) && self.default.config.data_paths.profiles.join(name).is_dir())
+    }
+
+    /// The state of an existing profile, opening it if needed. `None` if
+    /// there is no such profile.
+    pub fn state(&self, name: &str) -> Result<Option<Arc<AppState>>> {
+        self.with_loaded(name, |p| p.state.clone())
+    }
+
+    /// The API router of an existing profile, opening it if needed.
+    pub fn router(&self, name: &str) -> Result<Option<Router>> {
+        self.with_loaded(name, |p| p.router.clone())
+    }
+
+    fn with_loaded<T>(&self, name: &str, f: impl FnOnce(&LoadedProfile) -> T) -> Result<Option<T>> {
+        let mut loaded = self.loaded.lock();
+        if let Some(profile) = loaded.get(name) {
+            return Ok(Some(f(profile)));
+        }
+        if !self.exists(name) {
+            return Ok(None);
+        }
+        let profile = self.open(name)?;
+        let value = f(&profile);
+        loaded.insert(name.to_string(), profile);
+        Ok(Some(value))
+    }
+
+    fn open(&self, name: &str) -> Result<LoadedProfile> {
+        let config = self.default.config.for_profile(name)?;
+        let store = AppState::open_store(&config)?;
+        let state = Arc::new(AppState::for_profile(name, config, store, &self.default));
+        let worker = start_background_tasks(&state);
+        info!("Opened profile '{}'", name);
+        Ok(LoadedProfile {
+            router: routes::build_profile_router(state.clone()),
+            state,
+            worker,
+        })
+    }
+
+    /// Create a profile and open it. Fails if the name is invalid or taken.
+    pub fn create(&self, name: &str) -> Result<Arc<AppState>> {
+        if !is_valid_profile_name(name) {
+            return Err(Error::Config(format!(
+                "Invalid profile name '{}': use 1-32 lowercase letters, digits, '-' or '_'",
+                name
+            )));
+        }
+        if self.exists(name) {
+            return Err(Error::Config(format!("Profile '{}' already exists", name)));
+        }
+        std::fs::create_dir_all(self.default.config.data_paths.profiles.join(name))?;
+        self.state(name)?
+            .ok_or_else(|| Error::Internal(format!("Profile '{}' vanished after creation", name)))
+    }
+
+    /// Stop a profile's worker and delete its data directory. The default
+    /// profile cannot be deleted. Returns false if there is no such profile.
+    pub async fn delete(&self, name: &str) -> Result<bool> {
+        if name == DEFAULT_PROFILE {
+            return Err(Error::Config("The default profile cannot be deleted".to_string()));
+        }
+        if !self.exists(name) {
+            return Ok(false);
+        }
+        let removed = self.loaded.lock().remove(name);
+        if let Some(mut profile) = removed {
+            profile.state.shutdown.trigger();
+            if let Some(worker) = profile.worker.take() {
+                if tokio::time::timeout(DELETE_DRAIN_TIMEOUT, worker).await.is_err() {
+                    warn!("Indexing in profile '{}' did not stop before deletion", name);
+                }
+            }
+        }
+        std::fs::remove_dir_all(self.default.config.data_paths.profiles.join(name))?;
+        info!("Deleted profile '{}'", name);
+        Ok(true)
+    }
+
+    /// All profiles on disk, the default first, then by name.
+    pub fn list(&self) -> Vec<ProfileInfo> {
+        let loaded = self.loaded.lock();
+        let mut names: Vec<String> = std::fs::read_dir(&self.default.config.data_paths.profiles)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().to_str().map(str::to_string))
+            .filter(|n| is_valid_profile_name(n) && n != DEFAULT_PROFILE)
+            .collect();
+        names.sort();
+        names.insert(0, DEFAULT_PROFILE.to_string());
+        names
+            .into_iter()
+            .map(|name| ProfileInfo {
+                default: name == DEFAULT_PROFILE,
+                loaded: loaded.contains_key(&name),
+                data_dir: match loaded.get(&name) {
+                    Some(p) => p.state.config.data_paths.root.to_string_lossy().to_string(),
+                    None => self
+                        .default
+                        .config
+                        .data_paths
+                        .profiles
+                        .join(&name)
+                        .to_string_lossy()
+                        .to_string(),
+                },
+                name,
+            })
+            .collect()
+    }
+
+    /// Open profiles, the default first.
+    pub fn loaded_states(&self) -> Vec<Arc<AppState>> {
+        let loaded = self.loaded.lock();
+        let mut states: Vec<Arc<AppState>> = loaded.values().map(|p| p.state.clone()).collect();
+        states.sort_by_key(|s| (s.profile != DEFAULT_PROFILE, s.profile.clone()));
+        states
+    }
+
+    /// Trigger shutdown in every open profile.
+    pub fn trigger_shutdown(&self) {
+        for profile in self.loaded.lock().values() {
+            profile.state.shutdown.trigger();
+        }
+    }
+
+    /// Take the indexing worker handles of every open profile.
+    pub fn take_workers(&self) -> Vec<JoinHandle<()>> {
+        self.loaded
+            .lock()
+            .values_mut()
+            .filter_map(|p| p.worker.take())
+            .collect()
+    }
+}
+
+/// Start a profile's indexing worker, webhook dispatcher, upload cleanup,
+/// digest schedule and feed polling, returning the worker. None of them
+/// runs in read-only mode, as each writes to the data directory.
+fn start_background_tasks(state: &Arc<AppState>) -> Option<JoinHandle<()>> {
+    if state.config.read_only {
+        info!(
+            "Read-only mode: background indexing, webhooks, upload cleanup, digests, feed polling and browser cleanup are off for profile '{}'",
+            state.profile
+        );
+        return None;
+    }
+    let worker = indexing::start_indexing_worker(state.clone());
+    webhooks::start_webhook_dispatcher(state.clone());
+    uploads::start_upload_gc(state.clone());
+    digests::start_digest_schedule(state.clone());
+    feeds::start_feed_polling(state.clone());
+    browser_cleanup::start_browser_cleanup(state.clone());
+    Some(worker)
+}
@@ -0,0 +1,490 @@
+//! Disk quotas of the data areas — caps from `MindSageConfig::disk_quotas`
+//! checked before files are written, per-area usage for
+//! `GET /api/stats/disk`, and `storage.warning` events when the capped
+//! areas pass 80 or 95% of their combined quotas.
+//!
+//! Sizes come from recursive directory scans, reused for
+//! `SIZE_CACHE_TTL` and bumped by each admitted write in between. Under
+//! the `evict` policy the area's oldest files are deleted to make room;
+//! files in uploads and imports that are not indexed yet are kept.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use axum::http::StatusCode;
+use mindsage_browser::storage;
+use mindsage_core::{AreaQuota, DataPaths, DiskQuotas, Event, QuotaPolicy};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+use crate::state::AppState;
+
+/// How long a directory scan is reused.
+const SIZE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Shares of the budget, in percent, at which a warning is published.
+const WARNING_LEVELS: [u8; 2] = [95, 80];
+
+/// A part of the data directory with its own quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaArea {
+    Uploads,
+    Imports,
+    /// Capped per connector directory.
+    Exports,
+    Browser,
+}
+
+impl QuotaArea {
+    pub const ALL: [QuotaArea; 4] = [Self::Uploads, Self::Imports, Self::Exports, Self::Browser];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Uploads => "uploads",
+            Self::Imports => "imports",
+            Self::Exports => "exports",
+            Self::Browser => "browser",
+        }
+    }
+
+    pub fn quota(self, quotas: &DiskQuotas) -> Option<AreaQuota> {
+        match self {
+            Self::Uploads => quotas.uploads,
+            Self::Imports => quotas.imports,
+            Self::Exports => quotas.exports,
+            Self::Browser => quotas.browser,
+        }
+    }
+
+    /// The area's directory; for exports, the one holding every connector's.
+    pub fn dir(self, paths: &DataPaths) -> &Path {
+        match self {
+            Self::Uploads => &paths.uploads,
+            Self::Imports => &paths.imports,
+            Self::Exports => &paths.exports,
+            Self::Browser => &paths.browser_connector,
+        }
+    }
+}
+
+/// A write refused because it does not fit its area's quota.
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    pub area: QuotaArea,
+    pub used: u64,
+    pub limit: u64,
+    pub incoming: u64,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Disk quota of {} exceeded: {} bytes used of {}, {} more requested",
+            self.area.label(),
+            self.used,
+            self.limit,
+            self.incoming
+        )
+    }
+}
+
+impl From<QuotaExceeded> for ApiError {
+    fn from(e: QuotaExceeded) -> Self {
+        ApiError::new(StatusCode::INSUFFICIENT_STORAGE, "quota_exceeded", e.to_string()).with_details(
+            serde_json::json!({
+                "area": e.area,
+                "usedBytes": e.used,
+                "limitBytes": e.limit,
+                "incomingBytes": e.incoming,
+            }),
+        )
+    }
+}
+
+/// Cached directory sizes and the last warning published.
+#[derive(Default)]
+pub struct DiskUsage {
+    sizes: Mutex<HashMap<PathBuf, (Instant, u64)>>,
+    /// Highest level published since usage last fell below it; 0 for none.
+    warned: Mutex<u8>,
+}
+
+impl DiskUsage {
+    /// Bytes of all files under `dir`, from a scan at most
+    /// `SIZE_CACHE_TTL` old.
+    pub fn size(&self, dir: &Path) -> u64 {
+        if let Some((scanned, size)) = self.sizes.lock().get(dir) {
+            if scanned.elapsed() < SIZE_CACHE_TTL {
+                return *size;
+            }
+        }
+        let size = dir_size(dir);
+        self.sizes.lock().insert(dir.to_path_buf(), (Instant::now(), size));
+        size
+    }
+
+    /// Count `bytes` written to `dir` until its next scan, and drop the
+    /// cached sizes of directories containing it or inside it.
+    fn add(&self, dir: &Path, bytes: u64) {
+        let mut sizes = self.sizes.lock();
+        sizes.retain(|cached, _| cached == dir || !(dir.starts_with(cached) || cached.starts_with(dir)));
+        if let Some((_, size)) = sizes.get_mut(dir) {
+            *size += bytes;
+        }
+    }
+
+    /// Forget every cached size under or above `dir`.
+    fn invalidate(&self, dir: &Path) {
+        self.sizes.lock().retain(|cached, _| !(dir.starts_with(cached) || cached.starts_with(dir)));
+    }
+}
+
+/// Bytes of all files under `dir`, following no symlinks.
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| Some((e.path(), e.file_type().ok()?)))
+        .map(|(path, kind)| {
+            if kind.is_dir() {
+                dir_size(&path)
+            } else if kind.is_file() {
+                path.metadata().map(|m| m.len()).unwrap_or(0)
+            } else {
+                0
+            }
+        })
+        .sum()
+}
+
+/// Files under `dir` with their modification time and size, oldest first.
+fn files_oldest_first(dir: &Path) -> Vec<(PathBuf, u64)> {
+    fn collect(dir: &Path, files: &mut Vec<(SystemTime, PathBuf, u64)>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let Ok(kind) = entry.file_type() else { continue };
+            if kind.is_dir() {
+                collect(&entry.path(), files);
+            } else if let Some(meta) = entry.metadata().ok().filter(|_| kind.is_file()) {
+                files.push((meta.modified().unwrap_or(SystemTime::UNIX_EPOCH), entry.path(), meta.len()));
+            }
+        }
+    }
+    let mut files = Vec::new();
+    collect(dir, &mut files);
+    files.sort();
+    files.into_iter().map(|(_, path, size)| (path, size)).collect()
+}
+
+/// Refuse a write of `incoming` bytes to `dir` (an area's directory, or a
+/// connector's for exports) that could not fit the area's quota, without
+/// evicting anything. Under `evict` only a write larger than the whole
+/// quota is refused.
+pub fn check_room(state: &AppState, area: QuotaArea, dir: &Path, incoming: u64) -> Result<(), QuotaExceeded> {
+    let Some(quota) = area.quota(&state.config.disk_quotas) else {
+        return Ok(());
+    };
+    let used = state.disk.size(dir);
+    let fits = match quota.policy {
+        QuotaPolicy::Reject => used + incoming <= quota.max_bytes,
+        QuotaPolicy::Evict => incoming <= quota.max_bytes,
+    };
+    if fits {
+        Ok(())
+    } else {
+        Err(QuotaExceeded { area, used, limit: quota.max_bytes, incoming })
+    }
+}
+
+/// Admit a write of `incoming` bytes to `dir`, first evicting the area's
+/// oldest files under the `evict` policy; `keep` is never evicted. The
+/// write is counted until the next scan, and the budget warning checked.
+pub fn make_room(
+    state: &AppState,
+    area: QuotaArea,
+    dir: &Path,
+    incoming: u64,
+    keep: Option<&Path>,
+) -> Result<(), QuotaExceeded> {
+    if let Some(quota) = area.quota(&state.config.disk_quotas) {
+        let mut used = state.disk.size(dir);
+        if used + incoming > quota.max_bytes && quota.policy == QuotaPolicy::Evict && incoming <= quota.max_bytes {
+            used = evict(state, area, dir, used + incoming - quota.max_bytes, keep);
+        }
+        if used + incoming > quota.max_bytes {
+            return Err(QuotaExceeded { area, used, limit: quota.max_bytes, incoming });
+        }
+    }
+    state.disk.add(dir, incoming);
+    check_budget(state);
+    Ok(())
+}
+
+/// Delete the oldest files of `dir` until `needed` bytes are freed or
+/// nothing more may go. Returns the directory's size after.
+fn evict(state: &AppState, area: QuotaArea, dir: &Path, needed: u64, keep: Option<&Path>) -> u64 {
+    let scan_dir = match area {
+        QuotaArea::Browser => storage::conversations_dir(dir),
+        _ => dir.to_path_buf(),
+    };
+    let mut freed = 0;
+    for (path, size) in files_oldest_first(&scan_dir) {
+        if freed >= needed {
+            break;
+        }
+        if keep == Some(path.as_path()) {
+            continue;
+        }
+        let removed = match area {
+            // Through the store, so its index forgets the conversation too
+            QuotaArea::Browser => path
+                .file_name()
+                .and_then(|name| storage::conversation_id(&name.to_string_lossy()))
+                .is_some_and(|id| state.browser_manager.delete_conversation(&id)),
+            QuotaArea::Uploads | QuotaArea::Imports if !state.is_file_indexed(&path.to_string_lossy()) => false,
+            _ => std::fs::remove_file(&path).is_ok(),
+        };
+        if removed {
+            freed += size;
+        }
+    }
+    if freed > 0 {
+        info!("Evicted {} bytes from {} to stay within its quota", freed, area.label());
+    } else {
+        warn!("Nothing could be evicted from {} to stay within its quota", area.label());
+    }
+    state.disk.invalidate(dir);
+    state.disk.size(dir)
+}
+
+/// Usage of one area for `GET /api/stats/disk`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AreaUsage {
+    pub area: QuotaArea,
+    pub used_bytes: u64,
+    /// The area's quota; per connector for exports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub policy: Option<QuotaPolicy>,
+    /// Exports only: usage per connector directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connectors: Option<HashMap<String, u64>>,
+}
+
+/// Disk usage of every area, and of the capped ones against their budget.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskReport {
+    pub areas: Vec<AreaUsage>,
+    /// Bytes used by the areas that have a quota.
+    pub used_bytes: u64,
+    /// Sum of the quotas: the exports quota counts once per connector
+    /// directory. 0 when no area has one.
+    pub budget_bytes: u64,
+}
+
+/// Per-area usage from the cached scans.
+pub fn disk_report(state: &AppState) -> DiskReport {
+    let paths = &state.config.data_paths;
+    let quotas = &state.config.disk_quotas;
+    let mut areas = Vec::new();
+    let (mut used_bytes, mut budget_bytes) = (0, 0);
+    for area in QuotaArea::ALL {
+        let dir = area.dir(paths);
+        let quota = area.quota(quotas);
+        let connectors = (area == QuotaArea::Exports).then(|| connector_usage(state, dir));
+        let used = match &connectors {
+            Some(connectors) => connectors.values().sum::<u64>() + files_directly_in(dir),
+            None => state.disk.size(dir),
+        };
+        if let Some(quota) = quota {
+            used_bytes += used;
+            budget_bytes += match &connectors {
+                Some(connectors) => quota.max_bytes * connectors.len().max(1) as u64,
+                None => quota.max_bytes,
+            };
+        }
+        areas.push(AreaUsage {
+            area,
+            used_bytes: used,
+            limit_bytes: quota.map(|q| q.max_bytes),
+            policy: quota.map(|q| q.policy),
+            connectors,
+        });
+    }
+    DiskReport {
+        areas,
+        used_bytes,
+        budget_bytes,
+    }
+}
+
+/// Usage of each connector's directory in `exports`.
+fn connector_usage(state: &AppState, exports: &Path) -> HashMap<String, u64> {
+    let Ok(entries) = std::fs::read_dir(exports) else {
+        return HashMap::new();
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| (e.file_name().to_string_lossy().to_string(), state.disk.size(&e.path())))
+        .collect()
+}
+
+/// Bytes of the files directly in `dir`, not in its subdirectories.
+fn files_directly_in(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| e.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Publish `storage.warning` when usage first passes 80 or 95% of the
+/// budget; the warning re-arms once usage falls below its level.
+fn check_budget(state: &AppState) {
+    let report = disk_report(state);
+    if report.budget_bytes == 0 {
+        return;
+    }
+    let percent = report.used_bytes.saturating_mul(100) / report.budget_bytes;
+    let level = WARNING_LEVELS.into_iter().find(|&l| percent >= l as u64).unwrap_or(0);
+    let mut warned = state.disk.warned.lock();
+    if level > *warned {
+        warn!("Data areas use {}% of their disk quotas", percent);
+        state.events.publish(Event::StorageWarning {
+            level,
+            used_bytes: report.used_bytes,
+            budget_bytes: report.budget_bytes,
+        });
+    }
+    *warned = level;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    fn quota(max_bytes: u64, policy: QuotaPolicy) -> Option<AreaQuota> {
+        Some(AreaQuota { max_bytes, policy })
+    }
+
+    /// Write `bytes` bytes to `path`, dated `age_secs` seconds ago.
+    fn write_aged(path: &Path, bytes: usize, age_secs: u64) {
+        std::fs::write(path, vec![b'x'; bytes]).unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(age_secs);
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn test_reject_policy_refuses_writes_past_the_cap() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir)
+            .quotas(DiskQuotas {
+                uploads: quota(100, QuotaPolicy::Reject),
+                ..Default::default()
+            })
+            .build();
+        let uploads = state.config.data_paths.uploads.clone();
+        write_aged(&uploads.join("a.bin"), 60, 10);
+
+        assert!(make_room(&state, QuotaArea::Uploads, &uploads, 40, None).is_ok());
+        // The admitted write counts before the directory is scanned again
+        let refused = make_room(&state, QuotaArea::Uploads, &uploads, 1, None).unwrap_err();
+        assert_eq!((refused.used, refused.limit, refused.incoming), (100, 100, 1));
+        assert!(check_room(&state, QuotaArea::Uploads, &uploads, 1).is_err());
+        assert!(uploads.join("a.bin").exists());
+
+        let error = ApiError::from(refused);
+        assert_eq!(error.status, StatusCode::INSUFFICIENT_STORAGE);
+        assert_eq!(error.details.unwrap()["area"], "uploads");
+        // Areas without a quota take anything
+        assert!(make_room(&state, QuotaArea::Imports, &state.config.data_paths.imports, 1 << 30, None).is_ok());
+    }
+
+    #[test]
+    fn test_evict_policy_deletes_oldest_indexed_files() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir)
+            .quotas(DiskQuotas {
+                imports: quota(100, QuotaPolicy::Evict),
+                ..Default::default()
+            })
+            .build();
+        let imports = state.config.data_paths.imports.clone();
+        let (oldest, pending, newer) = (imports.join("oldest.txt"), imports.join("pending.txt"), imports.join("newer.txt"));
+        write_aged(&oldest, 40, 300);
+        write_aged(&pending, 40, 200);
+        write_aged(&newer, 20, 100);
+        for path in [&oldest, &newer] {
+            state.mark_file_indexed(&path.to_string_lossy(), None);
+        }
+
+        // 100 used: the oldest indexed file goes; the one awaiting indexing stays
+        assert!(check_room(&state, QuotaArea::Imports, &imports, 30).is_ok());
+        make_room(&state, QuotaArea::Imports, &imports, 30, None).unwrap();
+        assert!(!oldest.exists());
+        assert!(pending.exists() && newer.exists());
+
+        // Larger than the whole quota: refused without evicting anything
+        assert!(check_room(&state, QuotaArea::Imports, &imports, 101).is_err());
+        assert!(make_room(&state, QuotaArea::Imports, &imports, 101, None).is_err());
+        assert!(newer.exists());
+    }
+
+    #[test]
+    fn test_exports_are_capped_per_connector_and_warn_near_budget() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir)
+            .quotas(DiskQuotas {
+                exports: quota(100, QuotaPolicy::Reject),
+                ..Default::default()
+            })
+            .build();
+        let mut events = state.events.subscribe();
+        let exports = &state.config.data_paths.exports;
+        for id in ["chatgpt", "facebook"] {
+            std::fs::create_dir_all(exports.join(id)).unwrap();
+        }
+        write_aged(&exports.join("chatgpt").join("a.json"), 90, 10);
+
+        // Each connector has the whole quota; the budget counts both
+        let facebook = exports.join("facebook");
+        make_room(&state, QuotaArea::Exports, &facebook, 80, None).unwrap();
+        write_aged(&facebook.join("b.json"), 80, 0);
+        assert!(make_room(&state, QuotaArea::Exports, &exports.join("chatgpt"), 20, None).is_err());
+
+        let report = disk_report(&state);
+        let area = report.areas.iter().find(|a| a.area == QuotaArea::Exports).unwrap();
+        assert_eq!(area.connectors.as_ref().unwrap()["chatgpt"], 90);
+        assert_eq!((report.used_bytes, report.budget_bytes), (170, 200));
+
+        // 170 of 200 passed 80%, once
+        let event = events.try_recv().unwrap();
+        assert!(matches!(event, Event::StorageWarning { level: 80, .. }), "{:?}", event);
+        make_room(&state, QuotaArea::Exports, &facebook, 5, None).unwrap();
+        assert!(events.try_recv().is_err());
+    }
+}
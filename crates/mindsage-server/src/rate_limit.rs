@@ -0,0 +1,342 @@
+//! Per-client rate limiting — token buckets keyed by client IP and route class.
+//!
+//! Protects the single SQLite writer and the LLM budget from runaway
+//! clients. LocalSend protocol routes, the event stream, and health
+//! checks are exempt.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use mindsage_core::{RateLimitBudget, RateLimitConfig};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::error::ApiError;
+use crate::state::AppState;
+
+/// Buckets idle longer than this are dropped when the table is pruned.
+const BUCKET_IDLE_TTL: Duration = Duration::from_secs(600);
+/// Prune the bucket table once it grows past this many entries.
+const MAX_BUCKETS: usize = 4096;
+
+/// Route prefixes per class, matched on whole path segments; `*` matches
+/// any one segment. Everything else is a read.
+const EXEMPT_ROUTES: &[&str] = &["/api/localsend/v2", "/api/server-info", "/api/events", "/api/health"];
+const CHAT_ROUTES: &[&str] = &["/api/chat"];
+const SEARCH_ROUTES: &[&str] = &[
+    "/api/search",
+    "/api/vector-store/search",
+    "/api/browser-connector/conversations/search",
+];
+/// Settings under a search prefix; they do not run a search.
+const SEARCH_SETTINGS_ROUTES: &[&str] = &["/api/vector-store/search/settings"];
+const UPLOAD_ROUTES: &[&str] = &["/api/files/upload", "/api/files/*/import", "/api/connectors/*/upload"];
+
+/// Route classes with independent budgets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteClass {
+    Read,
+    Search,
+    Upload,
+    Chat,
+    /// Never throttled.
+    Exempt,
+}
+
+impl RouteClass {
+    /// Classify a request by method and URI path, after dropping a
+    /// `/profiles/{name}` prefix. Chat and upload classes only apply to POST.
+    pub fn classify(method: &Method, path: &str) -> Self {
+        let segments: Vec<&str> = strip_profile_prefix(path).split('/').filter(|s| !s.is_empty()).collect();
+        let under = |routes: &[&str]| routes.iter().any(|route| has_route_prefix(&segments, route));
+        let post = *method == Method::POST;
+        if under(EXEMPT_ROUTES) {
+            Self::Exempt
+        } else if post && under(CHAT_ROUTES) {
+            Self::Chat
+        } else if under(SEARCH_ROUTES) && !under(SEARCH_SETTINGS_ROUTES) {
+            Self::Search
+        } else if post && under(UPLOAD_ROUTES) {
+            Self::Upload
+        } else {
+            Self::Read
+        }
+    }
+}
+
+/// `path` without a leading `/profiles/{name}`.
+fn strip_profile_prefix(path: &str) -> &str {
+    match path.strip_prefix("/profiles/") {
+        Some(rest) => rest.find('/').map_or("/", |i| &rest[i..]),
+        None => path,
+    }
+}
+
+/// Whether `segments` start with the segments of `route`.
+fn has_route_prefix(segments: &[&str], route: &str) -> bool {
+    let route: Vec<&str> = route.split('/').filter(|s| !s.is_empty()).collect();
+    route.len() <= segments.len() && route.iter().zip(segments).all(|(r, s)| *r == "*" || r == s)
+}
+
+/// A single token bucket.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket.
+    pub fn new(budget: &RateLimitBudget, now: Instant) -> Self {
+        Self {
+            tokens: budget.burst as f64,
+            last_refill: now,
+        }
+    }
+
+    /// Refill for elapsed time, then take one token.
+    /// Returns the wait until a token is available if the bucket is empty.
+    pub fn try_acquire(&mut self, budget: &RateLimitBudget, now: Instant) -> Result<(), Duration> {
+        let rate = budget.per_minute as f64 / 60.0;
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(budget.burst as f64);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else if rate <= 0.0 {
+            Err(Duration::from_secs(60))
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+}
+
+/// Shared limiter state plus throttle counters for the stats endpoint.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<(IpAddr, RouteClass), TokenBucket>>,
+    throttled_read: AtomicU64,
+    throttled_search: AtomicU64,
+    throttled_upload: AtomicU64,
+    throttled_chat: AtomicU64,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            throttled_read: AtomicU64::new(0),
+            throttled_search: AtomicU64::new(0),
+            throttled_upload: AtomicU64::new(0),
+            throttled_chat: AtomicU64::new(0),
+        }
+    }
+
+    fn budget(&self, class: RouteClass) -> Option<&RateLimitBudget> {
+        match class {
+            RouteClass::Read => Some(&self.config.read),
+            RouteClass::Search => Some(&self.config.search),
+            RouteClass::Upload => Some(&self.config.upload),
+            RouteClass::Chat => Some(&self.config.chat),
+            RouteClass::Exempt => None,
+        }
+    }
+
+    fn counter(&self, class: RouteClass) -> Option<&AtomicU64> {
+        match class {
+            RouteClass::Read => Some(&self.throttled_read),
+            RouteClass::Search => Some(&self.throttled_search),
+            RouteClass::Upload => Some(&self.throttled_upload),
+            RouteClass::Chat => Some(&self.throttled_chat),
+            RouteClass::Exempt => None,
+        }
+    }
+
+    /// Check (and consume) one request for a client. Err carries Retry-After.
+    pub fn check(&self, ip: IpAddr, class: RouteClass, now: Instant) -> Result<(), Duration> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let budget = match self.budget(class) {
+            Some(b) => b,
+            None => return Ok(()),
+        };
+
+        let mut buckets = self.buckets.lock();
+        if buckets.len() > MAX_BUCKETS {
+            buckets.retain(|_, b| now.saturating_duration_since(b.last_refill) < BUCKET_IDLE_TTL);
+        }
+        let result = buckets
+            .entry((ip, class))
+            .or_insert_with(|| TokenBucket::new(budget, now))
+            .try_acquire(budget, now);
+        drop(buckets);
+
+        if result.is_err() {
+            if let Some(counter) = self.counter(class) {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        result
+    }
+
+    /// Throttle counters and configuration for `/api/stats`.
+    pub fn stats(&self) -> serde_json::Value {
+        serde_json::json!({
+            "enabled": self.config.enabled,
+            "throttled": {
+                "read": self.throttled_read.load(Ordering::Relaxed),
+                "search": self.throttled_search.load(Ordering::Relaxed),
+                "upload": self.throttled_upload.load(Ordering::Relaxed),
+                "chat": self.throttled_chat.load(Ordering::Relaxed),
+            },
+        })
+    }
+}
+
+/// Axum middleware enforcing the limiter. Responds 429 with Retry-After.
+pub async fn rate_limit(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    let class = RouteClass::classify(req.method(), req.uri().path());
+    let ip = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ci| ci.0.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+    match state.rate_limiter.check(ip, class, Instant::now()) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            let err = ApiError::new(StatusCode::TOO_MANY_REQUESTS, "rate_limited", "Too many requests")
+                .with_details(serde_json::json!({ "retryAfter": retry_after }));
+            let mut body = err.body();
+            body["retryAfter"] = serde_json::json!(retry_after);
+            (
+                err.status,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(body),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_burst_then_empty() {
+        let budget = RateLimitBudget::new(3, 60);
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(&budget, now);
+
+        assert!(bucket.try_acquire(&budget, now).is_ok());
+        assert!(bucket.try_acquire(&budget, now).is_ok());
+        assert!(bucket.try_acquire(&budget, now).is_ok());
+
+        // 60/min = 1 token/sec, so an empty bucket waits one second
+        let wait = bucket.try_acquire(&budget, now).unwrap_err();
+        assert!((wait.as_secs_f64() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_bucket_refill_math() {
+        let budget = RateLimitBudget::new(2, 30); // 0.5 tokens/sec
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(&budget, start);
+        bucket.try_acquire(&budget, start).unwrap();
+        bucket.try_acquire(&budget, start).unwrap();
+
+        // After 1s only half a token has refilled: wait the remaining 1s
+        let t1 = start + Duration::from_secs(1);
+        let wait = bucket.try_acquire(&budget, t1).unwrap_err();
+        assert!((wait.as_secs_f64() - 1.0).abs() < 1e-6);
+
+        // After 2s a full token is available
+        let t2 = start + Duration::from_secs(2);
+        assert!(bucket.try_acquire(&budget, t2).is_ok());
+    }
+
+    #[test]
+    fn test_bucket_refill_capped_at_burst() {
+        let budget = RateLimitBudget::new(2, 600);
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(&budget, start);
+
+        let later = start + Duration::from_secs(3600);
+        assert!(bucket.try_acquire(&budget, later).is_ok());
+        assert!(bucket.try_acquire(&budget, later).is_ok());
+        assert!(bucket.try_acquire(&budget, later).is_err());
+    }
+
+    #[test]
+    fn test_classify_routes() {
+        assert_eq!(RouteClass::classify(&Method::POST, "/api/vector-store/search"), RouteClass::Search);
+        assert_eq!(RouteClass::classify(&Method::POST, "/api/chat/stream"), RouteClass::Chat);
+        assert_eq!(RouteClass::classify(&Method::GET, "/api/chat/status"), RouteClass::Read);
+        assert_eq!(RouteClass::classify(&Method::POST, "/api/files/upload"), RouteClass::Upload);
+        assert_eq!(RouteClass::classify(&Method::POST, "/api/localsend/v2/upload"), RouteClass::Exempt);
+        assert_eq!(RouteClass::classify(&Method::GET, "/api/server-info"), RouteClass::Exempt);
+        assert_eq!(RouteClass::classify(&Method::GET, "/api/stats"), RouteClass::Read);
+        assert_eq!(RouteClass::classify(&Method::GET, "/api/health/ready"), RouteClass::Exempt);
+    }
+
+    #[test]
+    fn test_classify_matches_whole_route_prefixes() {
+        let classify = RouteClass::classify;
+        assert_eq!(classify(&Method::GET, "/api/search/universal"), RouteClass::Search);
+        assert_eq!(classify(&Method::POST, "/api/vector-store/search/enhanced"), RouteClass::Search);
+        assert_eq!(classify(&Method::GET, "/api/browser-connector/conversations/search"), RouteClass::Search);
+        // Names that merely contain "search", "upload" or "import"
+        assert_eq!(classify(&Method::GET, "/api/vector-store/saved-searches"), RouteClass::Read);
+        assert_eq!(classify(&Method::POST, "/api/vector-store/saved-searches/3/matches"), RouteClass::Read);
+        assert_eq!(classify(&Method::PUT, "/api/vector-store/search/settings"), RouteClass::Read);
+        assert_eq!(classify(&Method::POST, "/api/browser-connector/import-cookies"), RouteClass::Read);
+        assert_eq!(classify(&Method::POST, "/api/chatter"), RouteClass::Read);
+        // Uploads, by method
+        assert_eq!(classify(&Method::POST, "/api/files/upload/abc/chunk/0"), RouteClass::Upload);
+        assert_eq!(classify(&Method::POST, "/api/files/notes.txt/import"), RouteClass::Upload);
+        assert_eq!(classify(&Method::POST, "/api/connectors/7/upload"), RouteClass::Upload);
+        assert_eq!(classify(&Method::GET, "/api/files/upload/abc"), RouteClass::Read);
+        // A profile prefix is dropped first
+        assert_eq!(classify(&Method::POST, "/profiles/alice/api/vector-store/search"), RouteClass::Search);
+        assert_eq!(classify(&Method::POST, "/profiles/alice/api/chat/stream"), RouteClass::Chat);
+        assert_eq!(classify(&Method::POST, "/profiles/alice/api/localsend/v2/upload"), RouteClass::Exempt);
+    }
+
+    #[test]
+    fn test_limiter_counts_throttled_and_can_be_disabled() {
+        let config = RateLimitConfig {
+            chat: RateLimitBudget::new(1, 1),
+            ..RateLimitConfig::default()
+        };
+        let limiter = RateLimiter::new(config.clone());
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let now = Instant::now();
+
+        assert!(limiter.check(ip, RouteClass::Chat, now).is_ok());
+        assert!(limiter.check(ip, RouteClass::Chat, now).is_err());
+        assert!(limiter.check(ip, RouteClass::Exempt, now).is_ok());
+        assert_eq!(limiter.stats()["throttled"]["chat"], 1);
+
+        let disabled = RateLimiter::new(RateLimitConfig {
+            enabled: false,
+            ..config
+        });
+        assert!(disabled.check(ip, RouteClass::Chat, now).is_ok());
+        assert!(disabled.check(ip, RouteClass::Chat, now).is_ok());
+    }
+}
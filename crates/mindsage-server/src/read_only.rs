@@ -0,0 +1,184 @@
+//! Read-only mode (`MINDSAGE_READ_ONLY=on`) — explore a data directory
+//! without changing it.
+//!
+//! The store is opened with `SQLITE_OPEN_READ_ONLY`, so a write that gets
+//! past this middleware still fails (`Error::ReadOnly`, answered 403). The
+//! middleware turns mutating requests away before they reach a handler:
+//! everything but GET, HEAD and OPTIONS is refused, except the POST routes
+//! that only read (search, chat, PII detection, import preflight) and the
+//! consent routes, whose sessions live in memory.
+
+use axum::extract::{Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::error::ApiError;
+
+/// POST routes that read the data directory without changing it.
+const READING_POSTS: &[&str] = &[
+    "/api/vector-store/search",
+    "/api/vector-store/search/enhanced",
+    "/api/vector-store/search/with-topic",
+    "/api/vector-store/graph",
+    "/api/chat",
+    "/api/chat/stream",
+    "/api/chat/config/test",
+    "/api/connectors/preflight",
+    "/api/pii/detect",
+    "/api/pii/anonymize",
+    "/api/pii/deanonymize",
+];
+
+/// Routes under this prefix only change in-memory state.
+const IN_MEMORY_PREFIX: &str = "/api/consent/";
+
+/// Whether a request may run in read-only mode. `path` is the full URI
+/// path, with or without a `/profiles/<name>` prefix.
+pub fn is_allowed(method: &Method, path: &str) -> bool {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return true;
+    }
+    let path = api_path(path).trim_end_matches('/');
+    path.starts_with(IN_MEMORY_PREFIX) || (*method == Method::POST && READING_POSTS.contains(&path))
+}
+
+/// `path` without its `/profiles/<name>` prefix.
+fn api_path(path: &str) -> &str {
+    path.strip_prefix("/profiles/")
+        .and_then(|rest| rest.find('/').map(|i| &rest[i..]))
+        .unwrap_or(path)
+}
+
+/// Middleware: refuse mutating requests with 403 `read_only` when
+/// `read_only` is set.
+pub async fn reject_writes(State(read_only): State<bool>, req: Request, next: Next) -> Response {
+    if !read_only || is_allowed(req.method(), req.uri().path()) {
+        return next.run(req).await;
+    }
+    ApiError::read_only(format!(
+        "The server is in read-only mode (MINDSAGE_READ_ONLY); {} {} would change the data directory",
+        req.method(),
+        req.uri().path()
+    ))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::profiles::Profiles;
+    use crate::state::AppState;
+    use crate::test_support::TestState;
+
+    /// A data directory with one document, reopened read-only.
+    fn read_only_state(dir: &TempDir) -> Arc<AppState> {
+        {
+            let state = TestState::new(dir).build();
+            let store = &state.store;
+            let text = "Tide tables for the harbor";
+            let doc_id = store.add_document(text, Default::default()).unwrap();
+            let chunk_id = store
+                .add_chunk(doc_id, text, 0, 1, None, Some(0), Some(text.len() as i32), None, None, None)
+                .unwrap();
+            store.add_chunk_embedding(chunk_id, &ndarray::Array1::ones(384)).unwrap();
+        }
+        Arc::new(TestState::new(dir).config(|c| c.read_only = true).build())
+    }
+
+    fn request(method: Method, uri: &str) -> axum::http::Request<Body> {
+        axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from("{}"))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_mutating_routes_are_refused() {
+        let dir = TempDir::new().unwrap();
+        let state = read_only_state(&dir);
+        let app = crate::routes::build_app(Arc::new(Profiles::start(state.clone())));
+
+        let mutating = [
+            (Method::POST, "/api/vector-store/documents"),
+            (Method::DELETE, "/api/vector-store/documents/1"),
+            (Method::POST, "/api/vector-store/collections"),
+            (Method::POST, "/api/vector-store/documents/bulk-delete"),
+            (Method::POST, "/api/vector-store/saved-searches"),
+            (Method::PUT, "/api/chunks/1"),
+            (Method::POST, "/api/files/upload"),
+            (Method::POST, "/api/notes"),
+            (Method::POST, "/api/indexing/reembed"),
+            (Method::PUT, "/api/chat/config"),
+            (Method::POST, "/api/browser-connector/capture"),
+            (Method::POST, "/api/localsend/start"),
+            (Method::POST, "/api/connectors/some-id/sync"),
+            (Method::PUT, "/api/config/webhooks"),
+            (Method::POST, "/api/profiles"),
+            (Method::POST, "/profiles/default/api/notes"),
+        ];
+        for (method, uri) in mutating {
+            let response = app.clone().oneshot(request(method.clone(), uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{} {}", method, uri);
+            let body = axum::body::to_bytes(response.into_body(), 1 << 16).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["code"], "read_only");
+        }
+
+        // Reads, searches and chat still work
+        let response = app.clone().oneshot(request(Method::GET, "/api/stats")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), 1 << 16).await.unwrap();
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!((stats["documents"].as_u64(), stats["readOnly"].as_bool()), (Some(1), Some(true)));
+
+        // The document's centroid is computed without being cached
+        let response = app
+            .clone()
+            .oneshot(request(Method::GET, "/api/vector-store/documents/1/similar"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let search = axum::http::Request::post("/api/vector-store/search")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"query": "harbor tide"}"#))
+            .unwrap();
+        let response = app.clone().oneshot(search).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let chat = axum::http::Request::post("/api/chat")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"message": "harbor tide tables", "mode": "extractive"}"#))
+            .unwrap();
+        let response = app.oneshot(chat).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_store_refuses_writes_that_get_past_the_middleware() {
+        let dir = TempDir::new().unwrap();
+        let state = read_only_state(&dir);
+        let err = state.store.add_document("Sneaked in", Default::default()).unwrap_err();
+        assert!(matches!(err, mindsage_core::Error::ReadOnly(_)), "{}", err);
+        assert_eq!(ApiError::from(err).status, StatusCode::FORBIDDEN);
+        assert_eq!(state.store.count_documents().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_reading_posts_and_profile_prefix() {
+        assert!(is_allowed(&Method::POST, "/api/vector-store/search/enhanced"));
+        assert!(is_allowed(&Method::POST, "/profiles/alice/api/chat/stream"));
+        assert!(is_allowed(&Method::DELETE, "/api/consent/session/abc"));
+        assert!(!is_allowed(&Method::PUT, "/api/vector-store/search"));
+        assert!(!is_allowed(&Method::POST, "/profiles/alice/api/notes"));
+    }
+}
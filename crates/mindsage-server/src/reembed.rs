@@ -0,0 +1,180 @@
+//! Re-embedding after an embedder model switch.
+//!
+//! Each embedding records the model that produced it, and vector search only
+//! uses the active model's embeddings. This job walks the chunks embedded by
+//! other models in small batches and replaces their embeddings, pausing
+//! between batches so searches and indexing are not starved.
+
+use serde::Serialize;
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::jobs::{Job, JobStatus, JOB_BATCH_PAUSE};
+use crate::state::AppState;
+
+/// Chunks embedded per batch.
+pub const REEMBED_BATCH_SIZE: usize = 32;
+
+/// Fields of the re-embed job beyond the common ones.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReembedProgress {
+    /// Model the chunks are re-embedded with.
+    pub model_id: String,
+    pub reembedded: usize,
+    pub failed: usize,
+}
+
+impl ReembedProgress {
+    pub fn new(model_id: &str) -> Self {
+        Self {
+            model_id: model_id.to_string(),
+            reembedded: 0,
+            failed: 0,
+        }
+    }
+}
+
+/// Progress of the re-embed job.
+pub type ReembedJob = Job<ReembedProgress>;
+
+/// Re-embed up to the started job's `total` chunks whose embedding came
+/// from another model. Blocking; run it on a blocking thread.
+pub fn run_reembed(state: &AppState) {
+    let Some(job) = state.reembed.current() else {
+        return;
+    };
+    let mut after_id = 0;
+    let mut processed = 0;
+
+    let result = loop {
+        let limit = REEMBED_BATCH_SIZE.min(job.total - processed);
+        if limit == 0 {
+            break Ok(());
+        }
+        let chunks = match state.store.get_chunks_with_stale_embedding(after_id, limit) {
+            Ok(c) if c.is_empty() => break Ok(()),
+            Ok(c) => c,
+            Err(e) => break Err(e.to_string()),
+        };
+        after_id = chunks.last().map(|c| c.id).unwrap_or(after_id);
+
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        let embeddings = state.embedder.embed_batch(&texts);
+
+        let mut reembedded = 0;
+        for (chunk, emb_result) in chunks.iter().zip(embeddings.iter()) {
+            let Some(result) = emb_result else { continue };
+            match state.store.add_chunk_embedding(chunk.id, &result.embedding) {
+                Ok(()) => reembedded += 1,
+                Err(e) => error!("Failed to store embedding for chunk {}: {}", chunk.id, e),
+            }
+        }
+
+        processed += chunks.len();
+        state.reembed.update(|j| {
+            j.processed = processed;
+            j.detail.reembedded += reembedded;
+            j.detail.failed += chunks.len() - reembedded;
+        });
+        std::thread::sleep(JOB_BATCH_PAUSE);
+    };
+
+    if let Err(e) = &result {
+        error!("Re-embed job failed: {}", e);
+    }
+    if let Some(j) = state.reembed.finish(result).filter(|j| j.status == JobStatus::Completed) {
+        info!(
+            "Re-embedded {} chunks with model {} ({} failed)",
+            j.detail.reembedded, j.detail.model_id, j.detail.failed
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mindsage_infer::{EmbedderBackend, EmbeddingResult, NoopEmbedder};
+    use ndarray::Array1;
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    /// Deterministic embedder keyed on the text's first byte.
+    struct FixedEmbedder(&'static str);
+
+    impl EmbedderBackend for FixedEmbedder {
+        fn embed(&self, text: &str) -> Option<EmbeddingResult> {
+            let mut embedding = Array1::zeros(384);
+            embedding[text.as_bytes()[0] as usize % 384] = 1.0;
+            Some(EmbeddingResult {
+                embedding,
+                cached: false,
+                input_token_count: None,
+                truncated: false,
+            })
+        }
+
+        fn dimension(&self) -> usize {
+            384
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn model_id(&self) -> &str {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_model_switch_then_reembed() {
+        let dir = TempDir::new().unwrap();
+
+        // Embeddings written while "model-a" is active
+        let chunk_ids: Vec<i64> = {
+            let state = TestState::new(&dir).embedder(NoopEmbedder::new(384).with_model_id("model-a")).build();
+            let doc_id = state.store.add_document("three chunks", Default::default()).unwrap();
+            (0..3)
+                .map(|i| {
+                    let text = format!("{} chunk", ["alpha", "beta", "gamma"][i]);
+                    let id = state
+                        .store
+                        .add_chunk(doc_id, &text, i as i32, 1, None, None, None, None, None, None)
+                        .unwrap();
+                    let mut emb = Array1::zeros(384);
+                    emb[i] = 1.0;
+                    state.store.add_chunk_embedding(id, &emb).unwrap();
+                    id
+                })
+                .collect()
+        };
+
+        // Restart with a different model: old embeddings are not searched
+        let state = TestState::new(&dir).embedder(FixedEmbedder("model-b")).build();
+        let query = state.embedder.embed("alpha").unwrap().embedding;
+        assert!(state.store.vector_search(&query, Some(1), 5).unwrap().is_empty());
+        assert_eq!(state.store.count_stale_embeddings().unwrap(), 3);
+
+        // A budget of two chunks leaves one stale
+        state.reembed.start(2, ReembedProgress::new("model-b")).unwrap();
+        run_reembed(&state);
+        let job = state.reembed.current().unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!((job.processed, job.detail.reembedded, job.detail.failed), (2, 2, 0));
+        assert_eq!(state.store.count_stale_embeddings().unwrap(), 1);
+
+        state.reembed.start(10, ReembedProgress::new("model-b")).unwrap();
+        run_reembed(&state);
+        assert_eq!(state.reembed.current().unwrap().detail.reembedded, 1);
+        assert_eq!(state.store.count_stale_embeddings().unwrap(), 0);
+
+        let hits = state.store.vector_search(&query, Some(1), 1).unwrap();
+        assert_eq!(hits[0].chunk_id, chunk_ids[0]);
+        let stats = state.store.get_stats().unwrap();
+        assert_eq!(stats.embeddings_by_model.get("model-b"), Some(&3));
+        assert!(!stats.embeddings_by_model.contains_key("model-a"));
+    }
+}
@@ -0,0 +1,160 @@
+//! Re-extraction after the heuristic extractor improves.
+//!
+//! Each chunk records the extractor version that produced its
+//! `enriched_text`. This job walks the chunks enriched by a version older
+//! than the requested one and rewrites their enriched text with the current
+//! extractor. Each batch stops once it has used its time budget, and the job
+//! pauses between batches so searches and indexing are not starved. The FTS
+//! index follows through the chunk update trigger.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::jobs::{Job, JobStatus, JOB_BATCH_PAUSE};
+use crate::state::AppState;
+
+/// Chunks fetched per batch.
+pub const REEXTRACT_BATCH_SIZE: usize = 50;
+/// Extraction time allowed per batch before yielding.
+const REEXTRACT_BATCH_BUDGET: Duration = Duration::from_millis(200);
+
+/// Fields of the re-extract job beyond the common ones.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReextractProgress {
+    /// Chunks extracted by a version below this one are re-extracted.
+    pub min_version: u32,
+    /// Extractor version written to re-extracted chunks.
+    pub extraction_version: u32,
+    pub failed: usize,
+}
+
+impl ReextractProgress {
+    pub fn new(min_version: u32) -> Self {
+        Self {
+            min_version,
+            extraction_version: mindsage_ingest::CURRENT_EXTRACTION_VERSION,
+            failed: 0,
+        }
+    }
+}
+
+/// Progress of the re-extract job.
+pub type ReextractJob = Job<ReextractProgress>;
+
+/// Re-extract up to the started job's `total` chunks whose extraction
+/// version is below its `min_version`. Blocking; run it on a blocking thread.
+pub fn run_reextract(state: &AppState) {
+    let Some(job) = state.reextract.current() else {
+        return;
+    };
+    let mut after_id = 0;
+    let mut processed = 0;
+
+    let result = loop {
+        let limit = REEXTRACT_BATCH_SIZE.min(job.total - processed);
+        if limit == 0 {
+            break Ok(());
+        }
+        let chunks = match state
+            .store
+            .get_chunks_with_outdated_extraction(job.detail.min_version, after_id, limit)
+        {
+            Ok(c) if c.is_empty() => break Ok(()),
+            Ok(c) => c,
+            Err(e) => break Err(e.to_string()),
+        };
+
+        let started = Instant::now();
+        let mut batch = 0;
+        let mut failed = 0;
+        for chunk in &chunks {
+            let result = mindsage_ingest::extract_all(&chunk.text, None, None);
+            let enriched = mindsage_ingest::build_enriched_text(&result);
+            if let Err(e) =
+                state
+                    .store
+                    .update_chunk_enriched_text(chunk.id, &enriched, job.detail.extraction_version)
+            {
+                error!("Failed to update enriched_text for chunk {}: {}", chunk.id, e);
+                failed += 1;
+            }
+            after_id = chunk.id;
+            batch += 1;
+            if started.elapsed() >= REEXTRACT_BATCH_BUDGET {
+                break;
+            }
+        }
+
+        processed += batch;
+        state.reextract.update(|j| {
+            j.processed = processed;
+            j.detail.failed += failed;
+        });
+        std::thread::sleep(JOB_BATCH_PAUSE);
+    };
+
+    if let Err(e) = &result {
+        error!("Re-extract job failed: {}", e);
+    }
+    if let Some(j) = state.reextract.finish(result).filter(|j| j.status == JobStatus::Completed) {
+        info!(
+            "Re-extracted {} chunks below version {} ({} failed)",
+            j.processed, j.detail.min_version, j.detail.failed
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mindsage_ingest::CURRENT_EXTRACTION_VERSION;
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    #[test]
+    fn test_reextract_picks_up_older_versions() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+
+        let doc_id = state.store.add_document("notes", Default::default()).unwrap();
+        let ids: Vec<i64> = (0..3)
+            .map(|i| {
+                let id = state
+                    .store
+                    .add_chunk(doc_id, "Deployed the Rust service with Docker", i, 1, None, None, None, None, None, None)
+                    .unwrap();
+                // Two chunks from an older extractor, one current
+                let version = if i < 2 { CURRENT_EXTRACTION_VERSION - 1 } else { CURRENT_EXTRACTION_VERSION };
+                state.store.update_chunk_enriched_text(id, "stale", version).unwrap();
+                id
+            })
+            .collect();
+        assert_eq!(state.store.count_outdated_extractions(CURRENT_EXTRACTION_VERSION).unwrap(), 2);
+
+        // A budget of one chunk leaves one outdated
+        state.reextract.start(1, ReextractProgress::new(CURRENT_EXTRACTION_VERSION)).unwrap();
+        run_reextract(&state);
+        let job = state.reextract.current().unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!((job.processed, job.detail.failed), (1, 0));
+        assert_eq!(state.store.count_outdated_extractions(CURRENT_EXTRACTION_VERSION).unwrap(), 1);
+
+        state.reextract.start(10, ReextractProgress::new(CURRENT_EXTRACTION_VERSION)).unwrap();
+        run_reextract(&state);
+        assert_eq!(state.reextract.current().unwrap().processed, 1);
+        assert_eq!(state.store.count_outdated_extractions(CURRENT_EXTRACTION_VERSION).unwrap(), 0);
+
+        let rewritten = state.store.get_chunk(ids[1]).unwrap().unwrap();
+        assert_ne!(rewritten.enriched_text.as_deref(), Some("stale"));
+        let untouched = state.store.get_chunk(ids[2]).unwrap().unwrap();
+        assert_eq!(untouched.enriched_text.as_deref(), Some("stale"));
+        // The FTS index follows the new enriched text
+        assert!(state.store.bm25_search("stale", Some(1), 10).unwrap().iter().all(|h| h.chunk_id == ids[2]));
+    }
+}
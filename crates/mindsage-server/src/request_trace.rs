@@ -0,0 +1,419 @@
+//! Request IDs and per-request span timings.
+//!
+//! Every API request runs inside a `request` span carrying its ID, so log
+//! lines from the route, embedder and store for one request can be grouped.
+//! The ID comes from the client's `X-Request-Id` header when it is usable and
+//! is generated otherwise; either way it is echoed in the response.
+//!
+//! Search endpoints called with `?trace=true` also return the timing of every
+//! span opened while they ran (store calls, search stages, embedding). The
+//! spans are collected by [`layer`], which is part of the global subscriber
+//! but only builds spans while a trace is running.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Json;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Instrument, Span, Subscriber};
+use tracing_subscriber::filter::dynamic_filter_fn;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+use utoipa::IntoParams;
+
+use crate::error::ApiResult;
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest client-supplied request ID that is kept.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Middleware: assign the request ID, run the request inside its span, and
+/// return the ID in `X-Request-Id`.
+pub async fn request_id(req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| is_valid_request_id(v))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+    let start = Instant::now();
+    let mut response = next.run(req).instrument(span.clone()).await;
+    span.in_scope(|| {
+        tracing::debug!(
+            status = response.status().as_u16(),
+            elapsed_ms = start.elapsed().as_secs_f64() * 1000.0,
+            "request finished"
+        )
+    });
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER.clone(), value);
+    }
+    response
+}
+
+/// Client IDs are kept only if they are short and plain enough to log.
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+// ---------------------------------------------------------------
+// Span timing collection
+// ---------------------------------------------------------------
+
+/// `?trace=true` on the search endpoints.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TraceQuery {
+    /// Add span timings to the response as `trace`.
+    #[serde(default)]
+    pub trace: bool,
+}
+
+/// One span closed during a trace.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanTiming {
+    pub name: &'static str,
+    /// Module that opened the span, e.g. `mindsage_store::sqlite`.
+    pub target: &'static str,
+    /// Nesting below the traced handler; 1 for its direct children.
+    pub depth: usize,
+    /// Start relative to the start of the trace.
+    pub offset_us: u64,
+    pub duration_us: u64,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Span timings returned in the `trace` field of a response.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceReport {
+    pub total_us: u64,
+    /// In start order.
+    pub spans: Vec<SpanTiming>,
+}
+
+struct Collector {
+    origin: Instant,
+    spans: Mutex<Vec<SpanTiming>>,
+}
+
+/// Traces running now; while zero the layer builds no spans.
+static ACTIVE_TRACES: AtomicUsize = AtomicUsize::new(0);
+static NEXT_TRACE_ID: AtomicU64 = AtomicU64::new(1);
+static COLLECTORS: Lazy<Mutex<HashMap<u64, Arc<Collector>>>> = Lazy::new(Default::default);
+
+/// The collecting layer, filtered to do nothing while no trace runs.
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    TraceLayer.with_filter(dynamic_filter_fn(|_, _| ACTIVE_TRACES.load(Ordering::Relaxed) > 0))
+}
+
+/// Records spans opened under a [`RequestTrace`] into its collector.
+pub struct TraceLayer;
+
+/// Per-span state for spans inside a trace.
+struct Traced {
+    collector: Arc<Collector>,
+    start: Instant,
+    depth: usize,
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl<S> Layer<S> for TraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = FieldMap::default();
+        attrs.record(&mut fields);
+        let inherited = span.parent().and_then(|parent| {
+            parent
+                .extensions()
+                .get::<Traced>()
+                .map(|t| (t.collector.clone(), t.depth + 1))
+        });
+        let (collector, depth) = match inherited {
+            Some(found) => found,
+            None => {
+                // The root span of a trace names its collector
+                let Some(trace_id) = fields.0.get("trace_id").and_then(|v| v.as_u64()) else {
+                    return;
+                };
+                let Some(collector) = COLLECTORS.lock().get(&trace_id).cloned() else {
+                    return;
+                };
+                (collector, 0)
+            }
+        };
+        span.extensions_mut().insert(Traced {
+            collector,
+            start: Instant::now(),
+            depth,
+            fields: fields.0,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(traced) = extensions.get_mut::<Traced>() {
+            let mut fields = FieldMap(std::mem::take(&mut traced.fields));
+            values.record(&mut fields);
+            traced.fields = fields.0;
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let Some(traced) = extensions.get::<Traced>().filter(|t| t.depth > 0) else {
+            return;
+        };
+        traced.collector.spans.lock().push(SpanTiming {
+            name: span.name(),
+            target: span.metadata().target(),
+            depth: traced.depth,
+            offset_us: traced.start.duration_since(traced.collector.origin).as_micros() as u64,
+            duration_us: traced.start.elapsed().as_micros() as u64,
+            fields: traced.fields.clone(),
+        });
+    }
+}
+
+/// Collects the spans opened inside [`RequestTrace::in_scope`].
+pub struct RequestTrace {
+    id: u64,
+    collector: Arc<Collector>,
+    span: Span,
+}
+
+impl RequestTrace {
+    pub fn start() -> Self {
+        let id = NEXT_TRACE_ID.fetch_add(1, Ordering::Relaxed);
+        let collector = Arc::new(Collector {
+            origin: Instant::now(),
+            spans: Mutex::new(Vec::new()),
+        });
+        COLLECTORS.lock().insert(id, collector.clone());
+        ACTIVE_TRACES.fetch_add(1, Ordering::Relaxed);
+        let span = tracing::info_span!("trace", trace_id = id);
+        Self { id, collector, span }
+    }
+
+    pub fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.span.in_scope(f)
+    }
+
+    /// Close the trace and return what it collected.
+    pub fn finish(self) -> TraceReport {
+        let mut spans = std::mem::take(&mut *self.collector.spans.lock());
+        spans.sort_by_key(|s| s.offset_us);
+        TraceReport {
+            total_us: self.collector.origin.elapsed().as_micros() as u64,
+            spans,
+        }
+    }
+}
+
+impl Drop for RequestTrace {
+    fn drop(&mut self) {
+        COLLECTORS.lock().remove(&self.id);
+        ACTIVE_TRACES.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Run a JSON handler body, traced when `enabled`, adding the span timings
+/// to the response as `trace`.
+pub fn traced<T: Serialize>(
+    enabled: bool,
+    f: impl FnOnce() -> ApiResult<Json<T>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let to_value = |Json(body): Json<T>| Json(serde_json::to_value(body).unwrap_or_default());
+    if !enabled {
+        return f().map(to_value);
+    }
+    let trace = RequestTrace::start();
+    let result = trace.in_scope(f);
+    let report = trace.finish();
+    result.map(to_value).map(|Json(mut body)| {
+        body["trace"] = serde_json::json!(report);
+        Json(body)
+    })
+}
+
+/// Span and event fields as JSON values.
+#[derive(Default)]
+pub(crate) struct FieldMap(pub(crate) serde_json::Map<String, serde_json::Value>);
+
+impl Visit for FieldMap {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), serde_json::json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), serde_json::json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), serde_json::json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), serde_json::json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), serde_json::json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), serde_json::json!(format!("{:?}", value)));
+    }
+}
+
+/// Install a subscriber with only the collecting layer, once per test run.
+#[cfg(test)]
+pub(crate) fn install_test_subscriber() {
+    use tracing_subscriber::layer::SubscriberExt;
+    static INSTALL: std::sync::Once = std::sync::Once::new();
+    INSTALL.call_once(|| {
+        let subscriber = tracing_subscriber::registry().with(layer());
+        let _ = tracing::subscriber::set_global_default(subscriber);
+    });
+    // Callsites first hit by other tests while the subscriber was being
+    // installed may have cached "never"
+    tracing::callsite::rebuild_interest_cache();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_request_id_round_trips() {
+        let app = Router::new()
+            .route("/echo", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn(request_id));
+
+        let request = Request::builder()
+            .uri("/echo")
+            .header("x-request-id", "client-42")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()["x-request-id"], "client-42");
+
+        // Missing or unusable IDs are replaced with a UUID
+        for header in [None, Some("bad id\twith spaces")] {
+            let mut request = Request::builder().uri("/echo");
+            if let Some(value) = header {
+                request = request.header("x-request-id", value);
+            }
+            let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+            let id = response.headers()["x-request-id"].to_str().unwrap();
+            assert!(uuid::Uuid::parse_str(id).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_trace_collects_nested_spans() {
+        install_test_subscriber();
+        // Spans outside a trace are not collected
+        tracing::debug_span!("outside").in_scope(|| {});
+
+        let trace = RequestTrace::start();
+        trace.in_scope(|| {
+            let outer = tracing::debug_span!("outer", top_k = 5);
+            outer.in_scope(|| {
+                tracing::debug_span!("inner", stage = tracing::field::Empty).in_scope(|| {
+                    Span::current().record("stage", "bm25");
+                });
+            });
+        });
+        let report = trace.finish();
+        let names: Vec<(&str, usize)> = report.spans.iter().map(|s| (s.name, s.depth)).collect();
+        assert_eq!(names, [("outer", 1), ("inner", 2)]);
+        assert_eq!(report.spans[0].fields["top_k"], 5);
+        assert_eq!(report.spans[1].fields["stage"], "bm25");
+        assert!(report.total_us >= report.spans[0].duration_us);
+    }
+
+    #[tokio::test]
+    async fn test_search_trace_includes_store_timings() {
+        install_test_subscriber();
+        let dir = tempfile::TempDir::new().unwrap();
+        let state = crate::test_support::test_state(&dir);
+        let doc = state.store.add_document("Lisbon travel notes", Default::default()).unwrap();
+        state
+            .store
+            .add_chunk(doc, "Lisbon travel notes", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+        let app = crate::routes::build_app(Arc::new(crate::profiles::Profiles::start(state)));
+
+        let search = |uri: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .header("x-request-id", "search-1")
+                .body(Body::from(r#"{"query": "lisbon"}"#))
+                .unwrap()
+        };
+        let response = app.clone().oneshot(search("/api/vector-store/search?trace=true")).await.unwrap();
+        assert_eq!(response.headers()["x-request-id"], "search-1");
+        let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["total"], 1);
+        let spans = body["trace"]["spans"].as_array().unwrap();
+        let bm25 = spans
+            .iter()
+            .find(|s| s["name"] == "bm25_search")
+            .unwrap_or_else(|| panic!("store call in trace: {:#}", body["trace"]));
+        assert_eq!(bm25["target"], "mindsage_store::sqlite");
+        assert_eq!(bm25["fields"]["top_k"], 20);
+        assert!(bm25["durationUs"].is_u64());
+
+        // Without ?trace=true the response has no trace
+        let response = app.oneshot(search("/api/vector-store/search")).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(body.get("trace").is_none());
+    }
+}
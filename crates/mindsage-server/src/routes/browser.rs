@@ -1,16 +1,27 @@
 //! Browser connector routes — Chrome lifecycle, capture, auth, sync, cookies.
 
+use std::collections::HashSet;
 use std::sync::Arc;
 
+use std::time::Duration;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
 use axum::routing::{delete, get, post, put};
 use axum::{Json, Router};
 use serde::{Deserialize, Serialize};
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
+use utoipa::{IntoParams, OpenApi};
 
+use crate::error::{ApiError, ApiResult, ErrorBody};
+use crate::quota::{self, QuotaArea};
 use crate::state::AppState;
 use mindsage_browser::*;
-use mindsage_store::AddDocumentOptions;
+use mindsage_core::redact;
+use mindsage_ingest::Ingester;
+use mindsage_store::UpsertOutcome;
 
 // ---------------------------------------------------------------
 // Route builder
@@ -26,6 +37,14 @@ pub fn routes() -> Router<Arc<AppState>> {
         // Capture & Conversations
         .route("/browser-connector/capture", post(capture))
         .route("/browser-connector/conversations", get(list_conversations))
+        .route(
+            "/browser-connector/conversations/search",
+            get(search_conversations),
+        )
+        .route(
+            "/browser-connector/conversations/merge",
+            post(merge_conversations),
+        )
         .route(
             "/browser-connector/conversations/{id}",
             get(get_conversation).delete(delete_conversation),
@@ -33,6 +52,7 @@ pub fn routes() -> Router<Arc<AppState>> {
         // Indexing & Stats
         .route("/browser-connector/reindex", post(reindex))
         .route("/browser-connector/stats", get(get_stats))
+        .route("/browser-connector/cleanup", post(cleanup))
         // Config
         .route(
             "/browser-connector/config",
@@ -46,7 +66,11 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/browser-connector/report-auth", post(report_auth))
         .route("/browser-connector/auth", delete(clear_auth))
         // Sites
-        .route("/browser-connector/sites", get(get_sites))
+        .route("/browser-connector/sites", get(get_sites).post(add_site))
+        .route(
+            "/browser-connector/sites/{name}",
+            put(update_site).delete(remove_site),
+        )
         // Sync
         .route("/browser-connector/sync", post(start_sync))
         .route(
@@ -54,6 +78,12 @@ pub fn routes() -> Router<Arc<AppState>> {
             post(navigate_to_site),
         )
         .route("/browser-connector/sync-complete", post(sync_complete))
+        // Extension relay
+        .route("/browser-connector/ws", get(extension_ws))
+        .route(
+            "/browser-connector/extension/conversations",
+            get(extension_conversations).post(request_extension_conversations),
+        )
         // Auto-sync
         .route("/browser-connector/auto-sync", get(auto_sync_status))
         .route("/browser-connector/auto-sync/start", post(auto_sync_start))
@@ -75,38 +105,99 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/browser-connector/debug", post(debug_endpoint))
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_status,
+    launch_browser,
+    close_browser,
+    navigate,
+    capture,
+    list_conversations,
+    search_conversations,
+    merge_conversations,
+    get_conversation,
+    delete_conversation,
+    reindex,
+    get_stats,
+    cleanup,
+    get_config,
+    update_config,
+    vnc_status,
+    vnc_check,
+    auth_status,
+    report_auth,
+    clear_auth,
+    get_sites,
+    add_site,
+    update_site,
+    remove_site,
+    start_sync,
+    navigate_to_site,
+    sync_complete,
+    extension_ws,
+    extension_conversations,
+    request_extension_conversations,
+    auto_sync_status,
+    auto_sync_start,
+    auto_sync_stop,
+    auto_sync_interval,
+    import_cookies,
+    pending_cookies,
+    debug_endpoint,
+))]
+pub struct BrowserApi;
+
 // ---------------------------------------------------------------
 // Query / Body types
 // ---------------------------------------------------------------
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct ConversationQuery {
     site: Option<String>,
     limit: Option<usize>,
     offset: Option<usize>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ConversationSearchQuery {
+    q: String,
+    site: Option<String>,
+    /// RFC 3339 time or YYYY-MM-DD; conversations updated at or after it.
+    from: Option<String>,
+    /// RFC 3339 time or YYYY-MM-DD (inclusive day); updated before it.
+    to: Option<String>,
+    /// `relevance` (default) or `recency`.
+    #[param(value_type = Option<String>)]
+    sort: Option<SearchSort>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct SiteQuery {
     site: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct MergeBody {
+    #[serde(rename = "sourceId", alias = "source_id")]
+    source_id: String,
+    #[serde(rename = "targetId", alias = "target_id")]
+    target_id: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct NavigateBody {
     url: String,
 }
 
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
 struct LaunchBody {
     headed: Option<bool>,
     #[serde(rename = "startUrl")]
     start_url: Option<String>,
-    vnc: Option<bool>,
-    #[serde(rename = "vncPort")]
-    vnc_port: Option<u16>,
-    #[serde(rename = "wsPort")]
-    ws_port: Option<u16>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -121,11 +212,8 @@ struct SyncBody {
 }
 
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
 struct NavigateToSiteBody {
     site: String,
-    #[serde(rename = "forSync")]
-    for_sync: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -174,20 +262,12 @@ struct ConversationListResponse {
     page_size: usize,
 }
 
-#[derive(Serialize)]
-struct ConversationSummary {
-    id: String,
-    site: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    title: Option<String>,
-    url: String,
-    #[serde(rename = "messageCount")]
-    message_count: usize,
-    #[serde(rename = "createdAt")]
-    created_at: String,
-    #[serde(rename = "updatedAt")]
-    updated_at: String,
-    indexed: bool,
+fn browser_not_running() -> ApiError {
+    ApiError::new(StatusCode::CONFLICT, "browser_not_running", "Browser is not running")
+}
+
+fn unknown_site(site: &str) -> ApiError {
+    ApiError::bad_request(format!("Unknown site: {}", site))
 }
 
 #[derive(Serialize)]
@@ -202,10 +282,18 @@ struct VncCheckResponse {
 // Handlers
 // ---------------------------------------------------------------
 
+#[utoipa::path(get, path = "/browser-connector/status", tag = "browser-connector", responses((status = 200, body = BrowserStatus)))]
 async fn get_status(State(state): State<Arc<AppState>>) -> Json<BrowserStatus> {
     Json(state.browser_manager.get_status())
 }
 
+#[utoipa::path(
+    post,
+    path = "/browser-connector/launch",
+    tag = "browser-connector",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn launch_browser(
     State(state): State<Arc<AppState>>,
     Json(body): Json<LaunchBody>,
@@ -229,6 +317,12 @@ async fn launch_browser(
     }))
 }
 
+#[utoipa::path(
+    post,
+    path = "/browser-connector/close",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn close_browser(State(state): State<Arc<AppState>>) -> Json<SuccessResponse> {
     if !state.browser_manager.is_running() {
         return Json(SuccessResponse::with_message("Browser is not running"));
@@ -238,36 +332,148 @@ async fn close_browser(State(state): State<Arc<AppState>>) -> Json<SuccessRespon
     Json(SuccessResponse::with_message("Browser close requested"))
 }
 
+#[utoipa::path(
+    post,
+    path = "/browser-connector/navigate",
+    tag = "browser-connector",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn navigate(
     State(state): State<Arc<AppState>>,
     Json(body): Json<NavigateBody>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     if !state.browser_manager.is_running() {
-        return Json(serde_json::json!({ "error": "Browser is not running" }));
+        return Err(browser_not_running());
     }
-    info!("Navigate to: {}", body.url);
+    info!("Navigate to: {}", redact(&body.url));
     // Stub: actual CDP navigation in Phase 4
-    Json(serde_json::json!({ "success": true, "url": body.url }))
+    Ok(Json(serde_json::json!({ "success": true, "url": body.url })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/browser-connector/capture",
+    tag = "browser-connector",
+    request_body = Object,
+    responses(
+        (status = 200, body = Object),
+        (status = 507, description = "The capture does not fit the browser quota", body = ErrorBody),
+    )
+)]
 async fn capture(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<CapturePayload>,
-) -> Json<serde_json::Value> {
+    Json(mut payload): Json<CapturePayload>,
+) -> ApiResult<Json<serde_json::Value>> {
     // Validate site
-    if SupportedSite::from_name(&payload.site).is_none() {
-        return Json(serde_json::json!({
-            "error": format!("Unsupported site: {}", payload.site)
-        }));
-    }
+    let Some(site) = state.browser_manager.find_site(&payload.site) else {
+        return Err(ApiError::bad_request(format!(
+            "Unsupported site: {}",
+            payload.site
+        )));
+    };
 
-    let new_messages = state.browser_manager.process_capture(payload);
-    Json(serde_json::json!({
+    payload.site = site.name.clone();
+    let conversation_id = payload.conversation_id.clone();
+
+    // The text of messages not captured before stands in for what the
+    // conversation file grows by; the conversation itself is never evicted
+    // to make room for it
+    let browser_dir = &state.config.data_paths.browser_connector;
+    let known: HashSet<String> = state
+        .browser_manager
+        .get_conversation(&conversation_id)
+        .map(|c| c.messages.into_iter().map(|m| m.id).collect())
+        .unwrap_or_default();
+    let incoming = payload
+        .messages
+        .iter()
+        .filter(|m| !known.contains(&m.id))
+        .map(|m| m.content.len() as u64)
+        .sum();
+    let own_file = storage::conversation_file(browser_dir, &conversation_id);
+    quota::make_room(&state, QuotaArea::Browser, browser_dir, incoming, Some(&own_file))?;
+
+    let outcome = state.browser_manager.process_capture(payload);
+    index_after_capture(&state, conversation_id, outcome.merged, site.capture.auto_index);
+    Ok(Json(serde_json::json!({
         "success": true,
-        "newMessages": new_messages
-    }))
+        "newMessages": outcome.new_messages
+    })))
+}
+
+/// Bring the vector store up to date after a capture, in the background:
+/// the document of a conversation merged away by the capture is removed,
+/// and the captured conversation is indexed when its site has
+/// `auto_index` set or the merge left an indexed conversation stale.
+fn index_after_capture(
+    state: &Arc<AppState>,
+    conversation_id: String,
+    merged: Option<MergedConversation>,
+    auto_index: bool,
+) {
+    if merged.is_none() && !auto_index {
+        return;
+    }
+    let task_state = state.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut reindex = auto_index;
+        if let Some(merged) = &merged {
+            match retire_merged_document(&task_state, merged) {
+                Ok(was_indexed) => reindex |= was_indexed,
+                Err(e) => warn!("Failed to remove document of merged conversation {}: {}", merged.source_id, e),
+            }
+        }
+        if !reindex {
+            return;
+        }
+        let Some(conv) = task_state.browser_manager.get_conversation(&conversation_id) else {
+            return;
+        };
+        if conv.indexed {
+            return;
+        }
+        let ingester = Ingester::new(&task_state.store);
+        match index_conversation(&task_state, &ingester, &conv) {
+            Ok(ConversationIndexed::Indexed) => crate::indexing::embed_pending_chunks(&task_state),
+            Ok(_) => {}
+            Err(e) => warn!("Failed to auto-index conversation {}: {}", conv.id, e),
+        }
+    });
+}
+
+/// Delete the vector-store document of a merged-away conversation, unless
+/// the target shares it. Returns whether either conversation was indexed,
+/// in which case the target's document must be rebuilt from the merged
+/// messages.
+fn retire_merged_document(state: &AppState, merged: &MergedConversation) -> mindsage_core::Result<bool> {
+    let source = format!("browser-connector-{}", merged.site);
+    let source_doc = match merged.source_document_id {
+        Some(doc_id) => Some(doc_id),
+        None => state
+            .store
+            .find_document_by_external_id(&source, &merged.source_id)?
+            .map(|d| d.id),
+    };
+    let target_doc = match state.browser_manager.get_conversation(&merged.target_id) {
+        Some(conv) if conv.document_id.is_some() => conv.document_id,
+        _ => state
+            .store
+            .find_document_by_external_id(&source, &merged.target_id)?
+            .map(|d| d.id),
+    };
+    if let Some(doc_id) = source_doc.filter(|id| Some(*id) != target_doc) {
+        state.store.delete_document(doc_id)?;
+    }
+    Ok(source_doc.is_some() || target_doc.is_some())
 }
 
+#[utoipa::path(
+    get,
+    path = "/browser-connector/conversations",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn list_conversations(
     State(state): State<Arc<AppState>>,
     Query(query): Query<ConversationQuery>,
@@ -276,24 +482,10 @@ async fn list_conversations(
     let offset = query.offset.unwrap_or(0);
     let page = (offset / limit) + 1;
 
-    let (conversations, total) =
+    let (summaries, total) =
         state
             .browser_manager
-            .get_conversations(page, limit, query.site.as_deref());
-
-    let summaries: Vec<ConversationSummary> = conversations
-        .into_iter()
-        .map(|c| ConversationSummary {
-            id: c.id,
-            site: c.site,
-            title: c.title,
-            url: c.url,
-            message_count: c.message_count,
-            created_at: c.created_at,
-            updated_at: c.updated_at,
-            indexed: c.indexed,
-        })
-        .collect();
+            .get_conversation_summaries(page, limit, query.site.as_deref());
 
     Json(ConversationListResponse {
         conversations: summaries,
@@ -303,89 +495,295 @@ async fn list_conversations(
     })
 }
 
+/// GET /browser-connector/conversations/search — search captured
+/// conversations by title and message content, indexed or not.
+#[utoipa::path(
+    get,
+    path = "/browser-connector/conversations/search",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
+async fn search_conversations(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ConversationSearchQuery>,
+) -> ApiResult<Json<ConversationSearchResults>> {
+    if query.q.trim().is_empty() {
+        return Err(ApiError::bad_request("Query is empty"));
+    }
+    let search = ConversationSearch {
+        query: query.q,
+        site: query.site,
+        updated_after: query.from.as_deref().map(|d| parse_time_bound(d, false)).transpose()?,
+        updated_before: query.to.as_deref().map(|d| parse_time_bound(d, true)).transpose()?,
+        sort: query.sort.unwrap_or_default(),
+        limit: query.limit.unwrap_or(20).clamp(1, 100),
+    };
+    let results = tokio::task::spawn_blocking(move || state.browser_manager.search_conversations(&search))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    Ok(Json(results))
+}
+
+/// POST /browser-connector/conversations/merge — merge a duplicate
+/// conversation into another and delete it. The duplicate's vector-store
+/// document is removed and, if either was indexed, the merged conversation
+/// is indexed again.
+#[utoipa::path(
+    post,
+    path = "/browser-connector/conversations/merge",
+    tag = "browser-connector",
+    request_body = Object,
+    responses(
+        (status = 200, body = Object),
+        (status = 400, description = "Same conversation or different sites", body = ErrorBody),
+        (status = 404, description = "Source conversation not found", body = ErrorBody),
+    )
+)]
+async fn merge_conversations(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<MergeBody>,
+) -> ApiResult<Json<serde_json::Value>> {
+    if state.browser_manager.get_conversation(&body.source_id).is_none() {
+        return Err(ApiError::not_found("Conversation not found"));
+    }
+    let (merged, reindexed) = state
+        .blocking(move |state| -> ApiResult<_> {
+            let merged = state
+                .browser_manager
+                .merge_conversations(&body.source_id, &body.target_id)
+                .map_err(ApiError::bad_request)?;
+            let reindexed = merge_documents(state, &merged)?;
+            Ok((merged, reindexed))
+        })
+        .await?;
+
+    if reindexed {
+        let task_state = state.clone();
+        tokio::task::spawn_blocking(move || crate::indexing::embed_pending_chunks(&task_state));
+    }
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "merged": merged,
+        "reindexed": reindexed
+    })))
+}
+
+/// Fix the vector store after a merge: the source's document is removed
+/// and the target is indexed again if either was indexed. Returns whether
+/// the target's document changed.
+fn merge_documents(state: &AppState, merged: &MergedConversation) -> mindsage_core::Result<bool> {
+    if !retire_merged_document(state, merged)? {
+        return Ok(false);
+    }
+    let Some(conv) = state.browser_manager.get_conversation(&merged.target_id) else {
+        return Ok(false);
+    };
+    let indexed = index_conversation(state, &Ingester::new(&state.store), &conv)?;
+    Ok(matches!(indexed, ConversationIndexed::Indexed))
+}
+
+/// Parse an RFC 3339 time or a YYYY-MM-DD date. A date used as an upper
+/// bound covers the whole day.
+fn parse_time_bound(value: &str, end_of_day: bool) -> ApiResult<chrono::DateTime<chrono::Utc>> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| ApiError::bad_request(format!("Invalid date: {}", value)))?;
+    let date = if end_of_day { date + chrono::Days::new(1) } else { date };
+    Ok(date.and_time(chrono::NaiveTime::MIN).and_utc())
+}
+
+#[utoipa::path(
+    get,
+    path = "/browser-connector/conversations/{id}",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn get_conversation(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     match state.browser_manager.get_conversation(&id) {
-        Some(conv) => Json(serde_json::to_value(conv).unwrap_or_default()),
-        None => Json(serde_json::json!({ "error": "Conversation not found" })),
+        Some(conv) => Ok(Json(serde_json::to_value(conv).unwrap_or_default())),
+        None => Err(ApiError::not_found("Conversation not found")),
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/browser-connector/conversations/{id}",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn delete_conversation(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Json<SuccessResponse> {
+) -> ApiResult<Json<SuccessResponse>> {
     if state.browser_manager.delete_conversation(&id) {
-        Json(SuccessResponse::ok())
+        Ok(Json(SuccessResponse::ok()))
     } else {
-        Json(SuccessResponse::with_message("Conversation not found"))
+        Err(ApiError::not_found("Conversation not found"))
     }
 }
 
-async fn reindex(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
-    let (conversations, total) = state.browser_manager.get_conversations(1, 10000, None);
+#[utoipa::path(
+    post,
+    path = "/browser-connector/reindex",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
+async fn reindex(State(state): State<Arc<AppState>>) -> ApiResult<Json<serde_json::Value>> {
+    let (total, indexed, unchanged) = state.blocking(reindex_conversations).await;
+
+    if indexed > 0 {
+        let task_state = state.clone();
+        tokio::task::spawn_blocking(move || crate::indexing::embed_pending_chunks(&task_state));
+    }
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "total": total,
+        "indexed": indexed,
+        "unchanged": unchanged
+    })))
+}
+
+/// Index every captured conversation, reading one at a time. Returns
+/// `(total, indexed, unchanged)`.
+fn reindex_conversations(state: &AppState) -> (usize, usize, usize) {
+    let (summaries, total) = state.browser_manager.get_conversation_summaries(1, usize::MAX, None);
     info!("Reindex requested for {} conversations", total);
 
-    // Queue each conversation for indexing into the vector store
+    let ingester = Ingester::new(&state.store);
     let mut indexed = 0;
-    for conv in &conversations {
-        // Build document content from messages
-        let content = conv
-            .messages
-            .iter()
-            .map(|m| format!("{}: {}", m.role, m.content))
-            .collect::<Vec<_>>()
-            .join("\n\n");
-
-        if content.is_empty() {
+    let mut unchanged = 0;
+    for summary in &summaries {
+        let Some(conv) = state.browser_manager.get_conversation(&summary.id) else {
             continue;
+        };
+        match index_conversation(state, &ingester, &conv) {
+            Ok(ConversationIndexed::Indexed) => indexed += 1,
+            Ok(ConversationIndexed::Unchanged) => unchanged += 1,
+            Ok(ConversationIndexed::Empty) => {}
+            Err(e) => warn!("Failed to index conversation {}: {}", conv.id, e),
         }
+    }
+    (total, indexed, unchanged)
+}
 
-        let title = conv
-            .title
-            .as_deref()
-            .unwrap_or("Untitled conversation");
-
-        let metadata = serde_json::json!({
-            "title": title,
-            "source": format!("browser-connector-{}", conv.site),
-            "url": conv.url,
-            "conversationId": conv.id,
-        });
-
-        match state.store.add_document(
-            &content,
-            AddDocumentOptions {
-                metadata: Some(metadata),
-                ..Default::default()
-            },
-        ) {
-            Ok(_doc_id) => {
-                indexed += 1;
+pub(crate) enum ConversationIndexed {
+    Indexed,
+    Unchanged,
+    Empty,
+}
+
+/// Index one conversation into the vector store, upserted by its
+/// conversation id within `browser-connector-<site>`: a conversation whose
+/// messages did not change keeps its document, and a changed one replaces
+/// its document's text instead of adding another.
+pub(crate) fn index_conversation(
+    state: &AppState,
+    ingester: &Ingester<'_>,
+    conv: &CapturedConversation,
+) -> mindsage_core::Result<ConversationIndexed> {
+    // Build document content from messages
+    let content = conv
+        .messages
+        .iter()
+        .map(|m| format!("{}: {}", m.role, m.content))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    if content.is_empty() {
+        return Ok(ConversationIndexed::Empty);
+    }
+
+    let source = format!("browser-connector-{}", conv.site);
+    // Documents indexed before external ids were recorded
+    if let Some(doc_id) = conv.document_id {
+        if state.store.find_document_by_external_id(&source, &conv.id)?.is_none() {
+            state.store.set_external_id(doc_id, &source, &conv.id)?;
+        }
+    }
+
+    let title = conv
+        .title
+        .as_deref()
+        .unwrap_or("Untitled conversation");
+
+    let metadata = serde_json::json!({
+        "title": title,
+        "source": source,
+        "url": conv.url,
+        "conversationId": conv.id,
+    });
+
+    // Date the document by when the conversation was first captured
+    let created_at = chrono::DateTime::parse_from_rfc3339(&conv.created_at)
+        .ok()
+        .map(|t| t.timestamp_millis());
+    match ingester.upsert_text(&source, &conv.id, &content, &metadata, created_at) {
+        Ok(UpsertOutcome::Unchanged(doc_id)) => {
+            if !conv.indexed || conv.document_id != Some(doc_id) {
+                state.browser_manager.mark_indexed(&conv.id, doc_id);
             }
-            Err(e) => {
-                warn!("Failed to index conversation {}: {}", conv.id, e);
+            Ok(ConversationIndexed::Unchanged)
+        }
+        Ok(outcome) => {
+            state.browser_manager.mark_indexed(&conv.id, outcome.doc_id());
+            Ok(ConversationIndexed::Indexed)
+        }
+        // Same messages as another document (e.g. indexed before
+        // document ids were recorded): adopt it
+        Err(mindsage_core::Error::DuplicateContent(hash)) => {
+            if let Some(doc) = state.store.find_document_by_hash(&hash)? {
+                state.store.set_external_id(doc.id, &source, &conv.id)?;
+                state.browser_manager.mark_indexed(&conv.id, doc.id);
             }
+            Ok(ConversationIndexed::Unchanged)
         }
+        Err(e) => Err(e),
     }
-
-    Json(serde_json::json!({
-        "success": true,
-        "total": total,
-        "indexed": indexed
-    }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/browser-connector/stats",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn get_stats(State(state): State<Arc<AppState>>) -> Json<CaptureStats> {
     Json(state.browser_manager.get_capture_stats())
 }
 
+/// Apply the retention policies now; answers what was deleted and trimmed.
+#[utoipa::path(
+    post,
+    path = "/browser-connector/cleanup",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
+async fn cleanup(State(state): State<Arc<AppState>>) -> Json<RetentionReport> {
+    Json(state.blocking(crate::browser_cleanup::run_browser_cleanup).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/browser-connector/config",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn get_config(State(state): State<Arc<AppState>>) -> Json<BrowserConnectorConfig> {
     Json(state.browser_manager.get_config())
 }
 
+#[utoipa::path(
+    put,
+    path = "/browser-connector/config",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn update_config(
     State(state): State<Arc<AppState>>,
     Json(updates): Json<serde_json::Value>,
@@ -394,11 +792,23 @@ async fn update_config(
     Json(SuccessResponse::ok())
 }
 
+#[utoipa::path(
+    get,
+    path = "/browser-connector/vnc/status",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn vnc_status(State(state): State<Arc<AppState>>) -> Json<VncInfo> {
     let status = state.browser_manager.get_status();
     Json(status.vnc)
 }
 
+#[utoipa::path(
+    get,
+    path = "/browser-connector/vnc/check",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn vnc_check() -> Json<VncCheckResponse> {
     // Check for VNC dependencies (Xvfb, x11vnc, websockify)
     let deps = ["Xvfb", "x11vnc", "websockify"];
@@ -441,6 +851,12 @@ fn which_exists(cmd: &str) -> bool {
         .unwrap_or(false)
 }
 
+#[utoipa::path(
+    get,
+    path = "/browser-connector/auth-status",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn auth_status(
     State(state): State<Arc<AppState>>,
     Query(query): Query<SiteQuery>,
@@ -448,6 +864,13 @@ async fn auth_status(
     Json(state.browser_manager.get_auth_status(query.site.as_deref()))
 }
 
+#[utoipa::path(
+    post,
+    path = "/browser-connector/report-auth",
+    tag = "browser-connector",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn report_auth(
     State(state): State<Arc<AppState>>,
     Json(body): Json<ReportAuthBody>,
@@ -458,6 +881,12 @@ async fn report_auth(
     Json(SuccessResponse::ok())
 }
 
+#[utoipa::path(
+    delete,
+    path = "/browser-connector/auth",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn clear_auth(
     State(state): State<Arc<AppState>>,
     Query(query): Query<SiteQuery>,
@@ -466,6 +895,12 @@ async fn clear_auth(
     Json(SuccessResponse::ok())
 }
 
+#[utoipa::path(
+    get,
+    path = "/browser-connector/sites",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn get_sites(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
     let sites = state.browser_manager.get_sites_info();
     let sites_with_id: Vec<serde_json::Value> = sites
@@ -475,6 +910,9 @@ async fn get_sites(State(state): State<Arc<AppState>>) -> Json<serde_json::Value
                 "id": s.name,
                 "name": s.name,
                 "url": s.url,
+                "cookieDomains": s.cookie_domains,
+                "capture": s.capture,
+                "builtin": s.builtin,
                 "authenticated": s.authenticated,
             })
         })
@@ -482,82 +920,314 @@ async fn get_sites(State(state): State<Arc<AppState>>) -> Json<serde_json::Value
     Json(serde_json::json!({ "sites": sites_with_id }))
 }
 
+/// POST /browser-connector/sites — add a custom site.
+#[utoipa::path(
+    post,
+    path = "/browser-connector/sites",
+    tag = "browser-connector",
+    request_body = Object,
+    responses((status = 201, body = Object))
+)]
+async fn add_site(
+    State(state): State<Arc<AppState>>,
+    Json(site): Json<SiteDefinition>,
+) -> ApiResult<(StatusCode, Json<SiteDefinition>)> {
+    let site = state.browser_manager.add_site(site).map_err(ApiError::bad_request)?;
+    Ok((StatusCode::CREATED, Json(site)))
+}
+
+/// PUT /browser-connector/sites/:name — change a custom site, or the capture
+/// settings of a built-in one.
+#[utoipa::path(
+    put,
+    path = "/browser-connector/sites/{name}",
+    tag = "browser-connector",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
+async fn update_site(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(update): Json<SiteUpdate>,
+) -> ApiResult<Json<SiteDefinition>> {
+    state
+        .browser_manager
+        .update_site(&name, update)
+        .map_err(ApiError::bad_request)?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("Unknown site: {}", name)))
+}
+
+/// DELETE /browser-connector/sites/:name — remove a custom site.
+#[utoipa::path(
+    delete,
+    path = "/browser-connector/sites/{name}",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
+async fn remove_site(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> ApiResult<Json<SuccessResponse>> {
+    if !state.browser_manager.remove_site(&name).map_err(ApiError::bad_request)? {
+        return Err(ApiError::not_found(format!("Unknown site: {}", name)));
+    }
+    Ok(Json(SuccessResponse::ok()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/browser-connector/sync",
+    tag = "browser-connector",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn start_sync(
     State(state): State<Arc<AppState>>,
     Json(body): Json<SyncBody>,
-) -> Json<serde_json::Value> {
-    let site = body.site.as_deref().unwrap_or("chatgpt");
+) -> ApiResult<Json<serde_json::Value>> {
+    let name = body.site.as_deref().unwrap_or("chatgpt");
+    let site = state.browser_manager.find_site(name).ok_or_else(|| unknown_site(name))?;
+    let base_url = site.base_url.clone();
+    let site = site.name.as_str();
 
     // Check auth
     let auth = state.browser_manager.get_auth_status(Some(site));
     if !auth.authenticated {
-        return Json(serde_json::json!({
-            "error": format!("Not authenticated for {}. Please authenticate first.", site),
-            "status": 401
-        }));
+        return Err(ApiError::unauthorized(format!(
+            "Not authenticated for {}. Please authenticate first.",
+            site
+        )));
     }
 
-    // Stub: actual sync (CDP navigation + extension interaction) in Phase 4
-    info!("Sync requested for {} (stub)", site);
-    Json(serde_json::json!({
+    // The connected extension runs the sync; otherwise captures arrive
+    // passively as the user browses
+    let command = ServerMessage::SyncStart {
+        site: site.to_string(),
+        url: base_url,
+    };
+    if state.browser_manager.send_to_extension(command) {
+        info!("Sync requested for {} via the extension", site);
+        return Ok(Json(serde_json::json!({
+            "success": true,
+            "via": "extension",
+            "message": format!("Sync started for {} in the extension", site)
+        })));
+    }
+    info!("Sync requested for {} without the extension (passive capture)", site);
+    Ok(Json(serde_json::json!({
         "success": true,
-        "message": format!("Sync started for {} (headless sync pending)", site)
-    }))
+        "via": "passive",
+        "message": format!("Extension not connected; {} conversations are captured as they are opened", site)
+    })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/browser-connector/navigate-to-site",
+    tag = "browser-connector",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn navigate_to_site(
     State(state): State<Arc<AppState>>,
     Json(body): Json<NavigateToSiteBody>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     if !state.browser_manager.is_running() {
-        return Json(serde_json::json!({ "error": "Browser is not running" }));
+        return Err(browser_not_running());
     }
 
-    let site = match SupportedSite::from_name(&body.site) {
-        Some(s) => s,
-        None => {
-            return Json(serde_json::json!({
-                "error": format!("Unknown site: {}", body.site)
-            }))
-        }
-    };
+    let site = state
+        .browser_manager
+        .find_site(&body.site)
+        .ok_or_else(|| unknown_site(&body.site))?;
 
-    info!("Navigate to site: {} (url: {})", site, site.base_url());
+    info!("Navigate to site: {} (url: {})", site.name, site.base_url);
     // Stub: actual navigation via CDP in Phase 4
-    Json(serde_json::json!({
+    Ok(Json(serde_json::json!({
         "success": true,
-        "url": site.base_url()
-    }))
+        "url": site.base_url
+    })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/browser-connector/sync-complete",
+    tag = "browser-connector",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn sync_complete(
     State(state): State<Arc<AppState>>,
     Json(result): Json<SyncResult>,
 ) -> Json<SuccessResponse> {
-    info!("Sync complete: success={}", result.success);
-    // Store sync result in config
-    let mut config = state.browser_manager.config.write();
-    config.last_sync_at = Some(chrono::Utc::now().to_rfc3339());
-    config.last_sync_result = Some(result);
-    let _ = config.save();
+    state.browser_manager.record_sync_result(result);
     Json(SuccessResponse::ok())
 }
 
+/// How often the relay socket is pinged.
+const EXTENSION_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// GET /api/browser-connector/ws — the companion extension's relay socket.
+///
+/// Frames are JSON objects tagged by `type`. The server sends `sync-start`,
+/// `request-conversation-list`, `ping`, and an `ack` or `error` for each
+/// frame it receives; the extension sends `hello`, `capture`,
+/// `auth-status`, `conversation-list`, `sync-complete` and `pong`.
+#[utoipa::path(
+    get,
+    path = "/browser-connector/ws",
+    tag = "browser-connector",
+    responses((status = 101, description = "Switched to the relay WebSocket"))
+)]
+async fn extension_ws(State(state): State<Arc<AppState>>, ws: WebSocketUpgrade) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_extension_socket(state, socket))
+}
+
+async fn handle_extension_socket(state: Arc<AppState>, mut socket: WebSocket) {
+    let mut connection = state.browser_manager.connect_extension();
+    let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + EXTENSION_PING_INTERVAL, EXTENSION_PING_INTERVAL);
+
+    loop {
+        let outgoing = tokio::select! {
+            command = connection.commands.recv() => match command {
+                Some(command) => Some(command),
+                // Replaced by a newer connection
+                None => break,
+            },
+            frame = socket.recv() => match frame {
+                Some(Ok(Message::Text(text))) => handle_extension_frame(&state, text.as_str()),
+                Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                Some(Ok(_)) => None,
+            },
+            _ = ping.tick() => Some(ServerMessage::Ping),
+            _ = state.shutdown.wait() => {
+                let _ = socket.send(Message::Close(None)).await;
+                break;
+            }
+        };
+        if let Some(message) = outgoing {
+            let Ok(json) = serde_json::to_string(&message) else {
+                continue;
+            };
+            if socket.send(Message::Text(json.into())).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    state.browser_manager.disconnect_extension(connection.id);
+    info!("Browser extension disconnected");
+}
+
+/// Handle one frame from the extension; the reply to send, if any.
+fn handle_extension_frame(state: &Arc<AppState>, text: &str) -> Option<ServerMessage> {
+    let message: ExtensionMessage = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(e) => {
+            debug!("Invalid extension frame: {}", e);
+            return Some(ServerMessage::Error {
+                message: format!("Invalid message: {}", e),
+            });
+        }
+    };
+    let kind = message.kind();
+    match state.browser_manager.handle_extension_message(message) {
+        Ok(RelayOutcome::Captured {
+            conversation_id,
+            new_messages,
+            auto_index,
+            merged,
+        }) => {
+            index_after_capture(state, conversation_id, merged, auto_index);
+            Some(ServerMessage::Ack {
+                of: kind.to_string(),
+                new_messages: Some(new_messages),
+            })
+        }
+        Ok(RelayOutcome::Handled) if kind == "pong" => None,
+        Ok(RelayOutcome::Handled) => Some(ServerMessage::Ack {
+            of: kind.to_string(),
+            new_messages: None,
+        }),
+        Err(message) => Some(ServerMessage::Error { message }),
+    }
+}
+
+/// GET /api/browser-connector/extension/conversations?site= — the last
+/// conversation list the extension sent for a site.
+#[utoipa::path(
+    get,
+    path = "/browser-connector/extension/conversations",
+    tag = "browser-connector",
+    params(SiteQuery),
+    responses(
+        (status = 200, body = Object),
+        (status = 404, description = "No list received for the site", body = ErrorBody),
+    )
+)]
+async fn extension_conversations(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SiteQuery>,
+) -> ApiResult<Json<ConversationListing>> {
+    let name = query.site.as_deref().unwrap_or("chatgpt");
+    state
+        .browser_manager
+        .extension_conversation_list(name)
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("No conversation list received for {}", name)))
+}
+
+/// POST /api/browser-connector/extension/conversations?site= — ask the
+/// connected extension for a site's conversation list.
+#[utoipa::path(
+    post,
+    path = "/browser-connector/extension/conversations",
+    tag = "browser-connector",
+    params(SiteQuery),
+    responses(
+        (status = 202, description = "Requested; the list arrives over the relay socket", body = Object),
+        (status = 503, description = "The extension is not connected", body = ErrorBody),
+    )
+)]
+async fn request_extension_conversations(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SiteQuery>,
+) -> ApiResult<(StatusCode, Json<SuccessResponse>)> {
+    let name = query.site.as_deref().unwrap_or("chatgpt");
+    let site = state.browser_manager.find_site(name).ok_or_else(|| unknown_site(name))?;
+    let command = ServerMessage::RequestConversationList { site: site.name.clone() };
+    if !state.browser_manager.send_to_extension(command) {
+        return Err(ApiError::service_unavailable("The browser extension is not connected"));
+    }
+    Ok((StatusCode::ACCEPTED, Json(SuccessResponse::ok())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/browser-connector/auto-sync",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn auto_sync_status(State(state): State<Arc<AppState>>) -> Json<AutoSyncStatus> {
     Json(state.browser_manager.get_auto_sync_status())
 }
 
+#[utoipa::path(
+    post,
+    path = "/browser-connector/auto-sync/start",
+    tag = "browser-connector",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn auto_sync_start(
     State(state): State<Arc<AppState>>,
     Json(body): Json<AutoSyncStartBody>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     // Check if at least one site is authenticated
     let auth = state.browser_manager.get_auth_status(None);
     if !auth.authenticated {
-        return Json(serde_json::json!({
-            "error": "Not authenticated for any site",
-            "status": 401
-        }));
+        return Err(ApiError::unauthorized("Not authenticated for any site"));
     }
 
     if let Some(hours) = body.interval_hours {
@@ -565,90 +1235,458 @@ async fn auto_sync_start(
     }
 
     state.browser_manager.start_auto_sync();
-    Json(serde_json::json!({
+    Ok(Json(serde_json::json!({
         "success": true,
         "message": "Auto-sync enabled"
-    }))
+    })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/browser-connector/auto-sync/stop",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn auto_sync_stop(State(state): State<Arc<AppState>>) -> Json<SuccessResponse> {
     state.browser_manager.stop_auto_sync();
     Json(SuccessResponse::with_message("Auto-sync disabled"))
 }
 
+#[utoipa::path(
+    put,
+    path = "/browser-connector/auto-sync/interval",
+    tag = "browser-connector",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn auto_sync_interval(
     State(state): State<Arc<AppState>>,
     Json(body): Json<AutoSyncIntervalBody>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     if body.hours < 0.5 || body.hours > 24.0 {
-        return Json(serde_json::json!({
-            "error": "Interval must be between 0.5 and 24 hours"
-        }));
+        return Err(ApiError::bad_request(
+            "Interval must be between 0.5 and 24 hours",
+        ));
     }
     state.browser_manager.set_auto_sync_interval(body.hours);
-    Json(serde_json::json!({
+    Ok(Json(serde_json::json!({
         "success": true,
         "intervalHours": body.hours
-    }))
+    })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/browser-connector/import-cookies",
+    tag = "browser-connector",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn import_cookies(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<CookieImportPayload>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     // Validate site
-    let site = match SupportedSite::from_name(&payload.site) {
-        Some(s) => s,
-        None => {
-            return Json(serde_json::json!({
-                "error": format!("Unknown site: {}", payload.site)
-            }))
-        }
-    };
+    let site = state
+        .browser_manager
+        .find_site(&payload.site)
+        .ok_or_else(|| unknown_site(&payload.site))?;
 
     if payload.cookies.is_empty() {
-        return Json(serde_json::json!({ "error": "No cookies provided" }));
+        return Err(ApiError::bad_request("No cookies provided"));
     }
 
     // Filter cookies to allowed domains
-    let allowed_domains = site.cookie_domains();
     let filtered: Vec<ImportedCookie> = payload
         .cookies
         .into_iter()
-        .filter(|c| {
-            allowed_domains
-                .iter()
-                .any(|d| c.domain.ends_with(d.trim_start_matches('.')))
-        })
+        .filter(|c| site.allows_cookie_domain(&c.domain))
         .collect();
 
     let count = filtered.len();
     info!(
         "Importing {} cookies for {} (filtered from request)",
         count,
-        site.name()
+        site.name
     );
 
     state
         .browser_manager
-        .store_pending_cookies(site.name(), filtered);
+        .store_pending_cookies(&site.name, filtered);
 
     // TODO: trigger headless sync asynchronously (Phase 4)
 
-    Json(serde_json::json!({
+    Ok(Json(serde_json::json!({
         "success": true,
         "imported": count,
-        "site": site.name()
-    }))
+        "site": site.name
+    })))
 }
 
+#[utoipa::path(
+    get,
+    path = "/browser-connector/pending-cookies",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn pending_cookies(
     State(state): State<Arc<AppState>>,
 ) -> Json<std::collections::HashMap<String, usize>> {
     Json(state.browser_manager.get_pending_cookies_counts())
 }
 
+#[utoipa::path(
+    post,
+    path = "/browser-connector/debug",
+    tag = "browser-connector",
+    responses((status = 200, body = Object))
+)]
 async fn debug_endpoint(Json(body): Json<serde_json::Value>) -> Json<SuccessResponse> {
-    info!("Browser debug: {:?}", body);
+    info!("Browser debug: {}", redact(&body));
     Json(SuccessResponse::ok())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::test_support::test_state;
+
+    fn capture_payload(messages: &[(&str, &str, &str)]) -> CapturePayload {
+        CapturePayload {
+            site: "claude".to_string(),
+            conversation_id: "conv".to_string(),
+            conversation_url: "https://claude.ai/chat/conv".to_string(),
+            title: Some("Garden".to_string()),
+            messages: messages
+                .iter()
+                .map(|(id, role, content)| CapturedMessage {
+                    id: id.to_string(),
+                    conversation_id: "conv".to_string(),
+                    role: role.to_string(),
+                    content: content.to_string(),
+                    timestamp: "2025-01-01T00:00:00Z".to_string(),
+                    site: "claude".to_string(),
+                    metadata: None,
+                })
+                .collect(),
+            full_conversation: Some(true),
+            previous_id: None,
+        }
+    }
+
+    #[test]
+    fn test_reindex_keeps_one_document_per_conversation() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+
+        state
+            .browser_manager
+            .process_capture(capture_payload(&[("u1", "user", "When to plant tomatoes?")]));
+        assert_eq!(reindex_conversations(&state), (1, 1, 0));
+        let doc_id = state.browser_manager.get_conversation("conv").unwrap().document_id.unwrap();
+
+        // Resent with fresh IDs: nothing to index again
+        state
+            .browser_manager
+            .process_capture(capture_payload(&[("u1-reload", "user", "When to plant tomatoes?")]));
+        assert_eq!(reindex_conversations(&state), (1, 0, 1));
+
+        // A new reply replaces the document's text
+        state.browser_manager.process_capture(capture_payload(&[
+            ("u1-reload", "user", "When to plant tomatoes?"),
+            ("a1", "assistant", "After the last frost."),
+        ]));
+        assert_eq!(reindex_conversations(&state), (1, 1, 0));
+        assert_eq!(state.store.count_documents().unwrap(), 1);
+        let doc = state.store.get_document(doc_id).unwrap().unwrap();
+        assert!(doc.text.contains("After the last frost."));
+        assert_eq!(doc.metadata.unwrap()["conversationId"], "conv");
+    }
+
+    #[test]
+    fn test_id_transition_moves_the_document() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let mut temporary = capture_payload(&[("u1", "user", "How deep to plant garlic?")]);
+        temporary.conversation_id = "tmp".to_string();
+        state.browser_manager.process_capture(temporary);
+        reindex_conversations(&state);
+        let temporary_doc = state.browser_manager.get_conversation("tmp").unwrap().document_id.unwrap();
+
+        let mut permanent = capture_payload(&[
+            ("u1-perm", "user", "How deep to plant garlic?"),
+            ("a1", "assistant", "About two inches."),
+        ]);
+        permanent.previous_id = Some("tmp".to_string());
+        let outcome = state.browser_manager.process_capture(permanent);
+        let merged = outcome.merged.unwrap();
+        assert_eq!(merged.source_document_id, Some(temporary_doc));
+        assert!(merge_documents(&state, &merged).unwrap());
+
+        assert!(state.store.get_document(temporary_doc).unwrap().is_none());
+        assert_eq!(state.store.count_documents().unwrap(), 1);
+        let doc = state
+            .store
+            .find_document_by_external_id("browser-connector-claude", "conv")
+            .unwrap()
+            .unwrap();
+        assert!(doc.text.contains("About two inches."));
+        assert_eq!(state.browser_manager.get_conversation("conv").unwrap().document_id, Some(doc.id));
+    }
+
+    #[tokio::test]
+    async fn test_merge_route_fixes_documents() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let mut duplicate = capture_payload(&[("u1", "user", "Best soil for blueberries?")]);
+        duplicate.conversation_id = "dup".to_string();
+        state.browser_manager.process_capture(duplicate);
+        state.browser_manager.process_capture(capture_payload(&[
+            ("u1-b", "user", "Best soil for blueberries?"),
+            ("a1", "assistant", "Acidic, well drained."),
+        ]));
+        assert_eq!(reindex_conversations(&state), (2, 2, 0));
+        let target_doc = state.browser_manager.get_conversation("conv").unwrap().document_id.unwrap();
+
+        let body = |source: &str, target: &str| -> MergeBody {
+            serde_json::from_value(serde_json::json!({ "source_id": source, "targetId": target })).unwrap()
+        };
+        let err = merge_conversations(State(state.clone()), Json(body("missing", "conv")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+        let err = merge_conversations(State(state.clone()), Json(body("conv", "conv")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        let Json(merged) = merge_conversations(State(state.clone()), Json(body("dup", "conv")))
+            .await
+            .unwrap();
+        assert_eq!(merged["merged"]["addedMessages"], 0);
+        assert!(state.browser_manager.get_conversation("dup").is_none());
+        assert_eq!(state.store.count_documents().unwrap(), 1);
+        assert!(state.store.get_document(target_doc).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_custom_site_can_be_captured() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let mut payload = capture_payload(&[("u1", "user", "Compare heat pumps")]);
+        payload.site = "Perplexity".to_string();
+
+        let err = capture(State(state.clone()), Json(payload.clone())).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        let site: SiteDefinition = serde_json::from_value(serde_json::json!({
+            "name": "perplexity",
+            "baseUrl": "https://www.perplexity.ai",
+            "cookieDomains": ["perplexity.ai"],
+            "capture": {"titleSelectors": ["h1.thread-title"]},
+        }))
+        .unwrap();
+        let (status, _) = add_site(State(state.clone()), Json(site.clone())).await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        let err = add_site(State(state.clone()), Json(site)).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        let Json(body) = capture(State(state.clone()), Json(payload)).await.unwrap();
+        assert_eq!(body["newMessages"], 1);
+        assert_eq!(state.browser_manager.get_conversation("conv").unwrap().site, "perplexity");
+
+        let Json(sites) = get_sites(State(state.clone())).await;
+        let listed = sites["sites"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["name"] == "perplexity")
+            .unwrap()
+            .clone();
+        assert_eq!(listed["builtin"], false);
+        assert_eq!(listed["capture"]["titleSelectors"][0], "h1.thread-title");
+
+        // Cookies outside the site's domains are dropped
+        let cookies: CookieImportPayload = serde_json::from_value(serde_json::json!({
+            "site": "perplexity",
+            "cookies": [
+                {"name": "a", "value": "1", "domain": ".perplexity.ai", "path": "/", "secure": true, "httpOnly": true},
+                {"name": "b", "value": "2", "domain": "evilperplexity.ai", "path": "/", "secure": true, "httpOnly": true},
+            ],
+        }))
+        .unwrap();
+        let Json(imported) = import_cookies(State(state.clone()), Json(cookies)).await.unwrap();
+        assert_eq!(imported["imported"], 1);
+
+        let Json(updated) = update_site(
+            State(state.clone()),
+            Path("perplexity".to_string()),
+            Json(serde_json::from_value(serde_json::json!({"capture": {"autoIndex": true}})).unwrap()),
+        )
+        .await
+        .unwrap();
+        assert!(updated.capture.auto_index);
+        // Built-in sites keep their URL and domains
+        let err = update_site(
+            State(state.clone()),
+            Path("claude".to_string()),
+            Json(serde_json::from_value(serde_json::json!({"baseUrl": "https://evil.example"})).unwrap()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        assert!(remove_site(State(state.clone()), Path("perplexity".to_string())).await.is_ok());
+        assert!(state.browser_manager.find_site("perplexity").is_none());
+        let result = remove_site(State(state), Path("claude".to_string())).await;
+        assert!(result.is_err_and(|e| e.status == StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_search_conversations_route() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        state.browser_manager.process_capture(capture_payload(&[
+            ("u1", "user", "When to plant tomatoes?"),
+            ("a1", "assistant", "Plant tomatoes after the last frost."),
+        ]));
+
+        let query = |q: &str| {
+            Query(serde_json::from_value::<ConversationSearchQuery>(serde_json::json!({ "q": q })).unwrap())
+        };
+        let Json(results) = search_conversations(State(state.clone()), query("TOMATOES")).await.unwrap();
+        assert_eq!(results.results.len(), 1);
+        assert_eq!(results.results[0].match_count, 2);
+        assert!(!results.results[0].conversation.indexed);
+
+        let future: ConversationSearchQuery =
+            serde_json::from_value(serde_json::json!({"q": "tomatoes", "from": "2999-01-01", "sort": "recency"})).unwrap();
+        let Json(results) = search_conversations(State(state.clone()), Query(future)).await.unwrap();
+        assert!(results.results.is_empty());
+
+        let err = search_conversations(State(state.clone()), query(" ")).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        let bad_date: ConversationSearchQuery =
+            serde_json::from_value(serde_json::json!({"q": "tomatoes", "to": "last week"})).unwrap();
+        assert!(search_conversations(State(state), Query(bad_date)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_extension_relay_socket() {
+        use futures::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let app = Router::new().nest("/api", routes()).with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/api/browser-connector/ws", addr))
+            .await
+            .unwrap();
+        async fn exchange<S>(ws: &mut S, frame: serde_json::Value) -> serde_json::Value
+        where
+            S: SinkExt<WsMessage> + StreamExt<Item = Result<WsMessage, tokio_tungstenite::tungstenite::Error>> + Unpin,
+            <S as futures::Sink<WsMessage>>::Error: std::fmt::Debug,
+        {
+            ws.send(WsMessage::Text(frame.to_string().into())).await.unwrap();
+            next_frame(ws).await
+        }
+        async fn next_frame<S>(ws: &mut S) -> serde_json::Value
+        where
+            S: StreamExt<Item = Result<WsMessage, tokio_tungstenite::tungstenite::Error>> + Unpin,
+        {
+            loop {
+                if let WsMessage::Text(text) = ws.next().await.unwrap().unwrap() {
+                    return serde_json::from_str(text.as_str()).unwrap();
+                }
+            }
+        }
+
+        let ack = exchange(&mut ws, serde_json::json!({"type": "hello", "version": "1.4.0"})).await;
+        assert_eq!(ack, serde_json::json!({"type": "ack", "of": "hello"}));
+        let Json(status) = get_status(State(state.clone())).await;
+        assert!(status.extension.connected);
+        assert_eq!(status.extension.version.as_deref(), Some("1.4.0"));
+
+        let rejected = exchange(&mut ws, serde_json::json!({"type": "teleport"})).await;
+        assert_eq!(rejected["type"], "error");
+
+        let ack = exchange(
+            &mut ws,
+            serde_json::json!({
+                "type": "capture",
+                "site": "claude",
+                "conversationId": "conv",
+                "conversationUrl": "https://claude.ai/chat/conv",
+                "title": "Garden",
+                "messages": [{
+                    "id": "u1", "conversationId": "conv", "role": "user",
+                    "content": "When to plant tomatoes?", "timestamp": "2025-01-01T00:00:00Z", "site": "claude",
+                }],
+            }),
+        )
+        .await;
+        assert_eq!(ack, serde_json::json!({"type": "ack", "of": "capture", "newMessages": 1}));
+        assert_eq!(state.browser_manager.get_conversation("conv").unwrap().message_count, 1);
+
+        // Sync goes through the socket once the site is authenticated
+        let ack = exchange(&mut ws, serde_json::json!({"type": "auth-status", "site": "claude", "authenticated": true})).await;
+        assert_eq!(ack["of"], "auth-status");
+        let body = SyncBody {
+            site: Some("claude".to_string()),
+        };
+        let Json(sync) = start_sync(State(state.clone()), Json(body)).await.unwrap();
+        assert_eq!(sync["via"], "extension");
+        let command = next_frame(&mut ws).await;
+        assert_eq!(command["type"], "sync-start");
+        assert_eq!(command["site"], "claude");
+
+        let query = || SiteQuery {
+            site: Some("claude".to_string()),
+        };
+        let (accepted, _) = request_extension_conversations(State(state.clone()), Query(query())).await.unwrap();
+        assert_eq!(accepted, StatusCode::ACCEPTED);
+        assert_eq!(next_frame(&mut ws).await["type"], "request-conversation-list");
+        let ack = exchange(
+            &mut ws,
+            serde_json::json!({
+                "type": "conversation-list",
+                "site": "claude",
+                "conversations": [
+                    {"id": "conv", "url": "https://claude.ai/chat/conv"},
+                    {"id": "other", "url": "https://claude.ai/chat/other", "title": "Bread"},
+                ],
+            }),
+        )
+        .await;
+        assert_eq!(ack["of"], "conversation-list");
+        let Json(listing) = extension_conversations(State(state.clone()), Query(query())).await.unwrap();
+        assert_eq!(listing.conversations.len(), 2);
+        assert_eq!(listing.uncaptured, 1);
+
+        // Closing the socket disconnects; sync falls back to passive capture
+        ws.close(None).await.unwrap();
+        for _ in 0..100 {
+            if !state.browser_manager.extension_connected() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!state.browser_manager.extension_connected());
+        let body = SyncBody {
+            site: Some("claude".to_string()),
+        };
+        let Json(sync) = start_sync(State(state.clone()), Json(body)).await.unwrap();
+        assert_eq!(sync["via"], "passive");
+        let Err(err) = request_extension_conversations(State(state), Query(query())).await else {
+            panic!("requested a list without the extension");
+        };
+        assert_eq!(err.status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,251 @@
+//! Bulk document routes — delete or patch metadata for a selection.
+//!
+//! Both endpoints are two-step: a dry run (the default) resolves the
+//! selection and returns counts plus a confirm token; a second call with
+//! `dry_run: false` and that token performs the operation on the
+//! previewed documents. Large selections run as background jobs.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use utoipa::OpenApi;
+
+use crate::bulk::{BulkOperation, BULK_JOB_THRESHOLD, PREVIEW_TTL};
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+use mindsage_store::metadata;
+use mindsage_store::DocumentSelector;
+
+/// IDs echoed back by a dry run.
+const PREVIEW_SAMPLE_SIZE: usize = 20;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/vector-store/documents/bulk-delete", post(bulk_delete))
+        .route(
+            "/vector-store/documents/bulk-update-metadata",
+            post(bulk_update_metadata),
+        )
+        .route("/vector-store/bulk-jobs/{id}", get(get_bulk_job))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    bulk_delete,
+    bulk_update_metadata,
+    get_bulk_job,
+))]
+pub struct BulkApi;
+
+#[derive(Deserialize)]
+struct BulkRequest {
+    /// Explicit document IDs (ANDed with `filter` when both are given).
+    #[serde(default)]
+    ids: Vec<i64>,
+    #[serde(default)]
+    filter: Option<DocumentSelector>,
+    /// Defaults to true: nothing is changed without a confirm token.
+    #[serde(default)]
+    dry_run: Option<bool>,
+    #[serde(default)]
+    confirm_token: Option<String>,
+    /// Metadata patch (bulk-update-metadata only).
+    #[serde(default)]
+    patch: Option<serde_json::Value>,
+}
+
+/// POST /api/vector-store/documents/bulk-delete
+#[utoipa::path(
+    post,
+    path = "/vector-store/documents/bulk-delete",
+    tag = "vector-store",
+    request_body = Object,
+    responses(
+        (status = 200, description = "Dry-run preview or completed operation", body = Object),
+        (status = 202, description = "Background job started", body = Object),
+    )
+)]
+async fn bulk_delete(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BulkRequest>,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    run_bulk(state, BulkOperation::Delete, req).await
+}
+
+/// POST /api/vector-store/documents/bulk-update-metadata
+#[utoipa::path(
+    post,
+    path = "/vector-store/documents/bulk-update-metadata",
+    tag = "vector-store",
+    request_body = Object,
+    responses(
+        (status = 200, description = "Dry-run preview or completed operation", body = Object),
+        (status = 202, description = "Background job started", body = Object),
+    )
+)]
+async fn bulk_update_metadata(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BulkRequest>,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    run_bulk(state, BulkOperation::UpdateMetadata, req).await
+}
+
+/// GET /api/vector-store/bulk-jobs/:id — progress of a background bulk job.
+#[utoipa::path(
+    get,
+    path = "/vector-store/bulk-jobs/{id}",
+    tag = "vector-store",
+    responses((status = 200, body = Object))
+)]
+async fn get_bulk_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let job = state
+        .bulk
+        .get_job(&id)
+        .ok_or_else(|| ApiError::not_found("Bulk job not found"))?;
+    Ok(Json(serde_json::json!({ "job": job })))
+}
+
+async fn run_bulk(
+    state: Arc<AppState>,
+    operation: BulkOperation,
+    req: BulkRequest,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    if operation == BulkOperation::UpdateMetadata {
+        if let Some(patch) = &req.patch {
+            if !patch.is_object() {
+                return Err(ApiError::bad_request("patch must be an object"));
+            }
+            metadata::validate_metadata(patch)?;
+        }
+    }
+
+    match (req.dry_run.unwrap_or(true), req.confirm_token.clone()) {
+        (true, _) => preview(&state, operation, req).await,
+        (false, Some(token)) => confirm(state, operation, &token, req.patch),
+        (false, None) => Err(ApiError::bad_request(
+            "Run a dry_run preview first and pass its confirm_token",
+        )),
+    }
+}
+
+/// Resolve the selection and park it under a confirm token.
+async fn preview(
+    state: &Arc<AppState>,
+    operation: BulkOperation,
+    req: BulkRequest,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    if operation == BulkOperation::UpdateMetadata && req.patch.is_none() {
+        return Err(ApiError::bad_request("patch is required"));
+    }
+
+    let mut selector = req.filter.unwrap_or_default();
+    selector.ids.extend(req.ids);
+    if selector.is_empty() {
+        return Err(ApiError::bad_request("Provide ids or a filter"));
+    }
+
+    let ids = state.db(move |store| store.select_documents(&selector)).await?;
+    let matched = ids.len();
+    let sample: Vec<i64> = ids.iter().take(PREVIEW_SAMPLE_SIZE).copied().collect();
+    let token = (matched > 0).then(|| {
+        state
+            .bulk
+            .create_preview(operation, ids, req.patch, Instant::now())
+    });
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "dryRun": true,
+            "operation": operation,
+            "matched": matched,
+            "sampleIds": sample,
+            "confirmToken": token,
+            "expiresInSecs": PREVIEW_TTL.as_secs(),
+            "runsAsJob": matched > BULK_JOB_THRESHOLD,
+        })),
+    ))
+}
+
+/// Apply the operation to a previewed selection.
+fn confirm(
+    state: Arc<AppState>,
+    operation: BulkOperation,
+    token: &str,
+    patch: Option<serde_json::Value>,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    let preview = state
+        .bulk
+        .take_preview(token, operation, Instant::now())
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_confirm_token",
+                "Confirm token is invalid, expired, or already used",
+            )
+        })?;
+    if patch.is_some() && patch != preview.patch {
+        return Err(ApiError::bad_request("patch differs from the previewed patch"));
+    }
+
+    let total = preview.ids.len();
+    if total > BULK_JOB_THRESHOLD {
+        let job_id = state.bulk.start_job(operation, total);
+        let job_state = state.clone();
+        let job = job_id.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = execute(&job_state, operation, &preview.ids, preview.patch.as_ref(), |n| {
+                job_state.bulk.record_progress(&job, n)
+            });
+            job_state.bulk.finish_job(&job, result.map_err(|e| e.to_string()));
+        });
+
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({
+                "dryRun": false,
+                "operation": operation,
+                "jobId": job_id,
+                "status": "running",
+                "total": total,
+            })),
+        ));
+    }
+
+    let affected = execute(&state, operation, &preview.ids, preview.patch.as_ref(), |_| {})?;
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "dryRun": false,
+            "operation": operation,
+            "matched": total,
+            "affected": affected,
+        })),
+    ))
+}
+
+fn execute(
+    state: &AppState,
+    operation: BulkOperation,
+    ids: &[i64],
+    patch: Option<&serde_json::Value>,
+    on_batch: impl FnMut(usize),
+) -> mindsage_core::Result<usize> {
+    match operation {
+        BulkOperation::Delete => state.store.bulk_delete_documents(ids, on_batch),
+        BulkOperation::UpdateMetadata => {
+            let empty = serde_json::json!({});
+            state
+                .store
+                .bulk_update_document_metadata(ids, patch.unwrap_or(&empty), on_batch)
+        }
+    }
+}
@@ -1,6 +1,10 @@
 //! Chat routes — RAG chat with external LLM streaming.
-//! Matches /api/chat/* endpoints from the Express server.
+//! Matches /api/chat/* endpoints from the Express server. Without an LLM
+//! (or with `mode: extractive`) the answer quotes the retrieved context.
+//! With tool retrieval the model searches while it answers instead (see
+//! `mindsage_chat::tools`).
 
+use std::collections::HashSet;
 use std::convert::Infallible;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -9,15 +13,28 @@ use std::time::Instant;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::sse::{Event, Sse};
-use axum::response::IntoResponse;
 use axum::routing::{get, post};
 use axum::{Json, Router};
 use futures::Stream;
 use tokio_stream::StreamExt;
+use tracing::{instrument, warn, Instrument};
+use utoipa::OpenApi;
 
+use crate::audit::{AuditPurpose, AuditRequest};
+use crate::error::{ApiError, ApiResult, ErrorBody};
 use crate::state::AppState;
-use mindsage_chat::providers::{self, StreamChunk};
+use mindsage_chat::context::{self, ContextPiece, ContextSpan};
+use mindsage_chat::providers::{self, BoxedStream, StreamChunk};
+use mindsage_chat::tools::{ToolResult, ToolRound, ToolUse, SEARCH_TOOL};
 use mindsage_chat::types::*;
+use mindsage_chat::ContextMode;
+use mindsage_ingest::extract::passages::{query_coverage, rank_sentences};
+use mindsage_protocol::anonymization::{pending_placeholder_len, SharedSession};
+use mindsage_protocol::pii::PiiDetector;
+use mindsage_resolve::merge_overlapping_hits;
+use mindsage_store::{Chunk, SearchFilters, SearchHit};
+
+use super::vector_store::post_process_hits;
 
 type SseStream = Pin<Box<dyn Stream<Item = Result<Event, Infallible>> + Send>>;
 
@@ -30,162 +47,505 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/chat/config/test", post(test_key))
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(get_status, chat, stream_chat, get_config, update_config, test_key))]
+pub struct ChatApi;
+
 // ---------------------------------------------------------------
 // Status
 // ---------------------------------------------------------------
 
-async fn get_status(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+#[utoipa::path(get, path = "/chat/status", tag = "chat", responses((status = 200, body = ChatStatus)))]
+async fn get_status(State(state): State<Arc<AppState>>) -> Json<ChatStatus> {
+    let store_stats = state.db(|store| store.get_stats()).await.ok();
     let config = state.llm_config.read();
     let resolved = config.resolve_provider();
-    let store_stats = state.store.get_stats().ok();
-
-    Json(serde_json::json!({
-        "llmAvailable": resolved.is_some(),
-        "llmProvider": resolved.as_ref().map(|(p, _, _)| p.to_string()),
-        "vectorStoreAvailable": store_stats.is_some(),
-        "defaultModel": resolved.as_ref().map(|(_, m, _)| m.clone()),
-        "availableModels": config.available_models(),
-        "gpuAvailable": false,
-        "gpuStatus": "not_applicable",
-        "ollamaAvailable": false,
-    }))
+
+    Json(ChatStatus {
+        llm_available: resolved.is_some(),
+        llm_provider: resolved.as_ref().map(|(p, _, _)| p.to_string()),
+        vector_store_available: store_stats.is_some(),
+        default_model: resolved.as_ref().map(|(_, m, _)| m.clone()),
+        available_models: config.available_models(),
+        gpu_available: false,
+        gpu_status: "not_applicable".to_string(),
+        ollama_available: false,
+    })
 }
 
 // ---------------------------------------------------------------
-// Non-streaming chat
+// Outbound requests
 // ---------------------------------------------------------------
 
-async fn chat(
-    State(state): State<Arc<AppState>>,
-    Json(req): Json<ChatRequest>,
-) -> impl IntoResponse {
-    let start = Instant::now();
+/// A chat request ready to send to the provider.
+#[derive(Clone)]
+struct LlmRequest {
+    provider: LLMProvider,
+    model: String,
+    api_key: String,
+    messages: Vec<ChatMessage>,
+    temperature: f64,
+    max_tokens: usize,
+    /// `LLMConfig::openai_base_url`.
+    openai_base_url: Option<String>,
+    /// Set when the model searches with the tool instead of getting the
+    /// context up front.
+    tools: Option<ToolUse>,
+    /// Audit entry the provider's reported usage is recorded against.
+    audit_id: String,
+    /// Set when PII in the messages was replaced with tokens; the answer
+    /// is restored and tool results are tokenized with the same map.
+    anonymizer: Option<SharedSession>,
+}
 
-    let (provider, model, api_key) = {
-        let config = state.llm_config.read();
-        match config.resolve_provider() {
-            Some(resolved) => resolved,
-            None => {
-                return (
-                    StatusCode::SERVICE_UNAVAILABLE,
-                    Json(serde_json::json!({
-                        "error": "No LLM provider configured",
-                    })),
-                );
-            }
+/// Open the provider stream for a prepared request.
+fn send_to_provider(request: LlmRequest) -> BoxedStream {
+    let client = reqwest::Client::new();
+    providers::stream_llm(
+        &client, request.provider, request.messages,
+        &request.model, &request.api_key,
+        request.temperature, request.max_tokens,
+        request.openai_base_url.as_deref(),
+        request.tools.as_ref(),
+    )
+}
+
+/// How a request will be answered, with its RAG context.
+enum Prepared {
+    Llm(LlmRequest, Vec<ChatContext>),
+    /// No LLM configured or `mode: extractive`; nothing leaves the device,
+    /// so no audit entry is written.
+    Extractive(Vec<ChatContext>),
+}
+
+/// Resolve the provider, build the RAG prompt, and write the privacy audit
+/// entry. Nothing is sent if the entry cannot be written.
+#[instrument(level = "debug", skip_all)]
+fn prepare_request(state: &AppState, req: &ChatRequest, purpose: AuditPurpose) -> ApiResult<Prepared> {
+    if let Some(id) = req.collection_id {
+        if state.store.get_collection(id)?.is_none() {
+            return Err(ApiError::not_found("Collection not found"));
         }
+    }
+    let resolved = match req.mode {
+        ChatMode::Auto => state.llm_config.read().resolve_provider(),
+        ChatMode::Extractive => None,
+    };
+    let Some((provider, model, api_key)) = resolved else {
+        // Quoting needs the context even when the request turned RAG off
+        let context = build_rag_context(state, &req.message, req.top_k, req.min_score, req.context_mode, req.collection_id);
+        return Ok(Prepared::Extractive(context));
     };
 
+    // With tool retrieval the context is searched for during the answer;
+    // providers without tool support get it up front
+    let use_tools = req.use_rag
+        && provider.supports_tools()
+        && req.use_tools.unwrap_or_else(|| state.llm_config.read().tool_retrieval);
+
     // Build RAG context
-    let context = if req.use_rag {
-        build_rag_context(&state, &req.message, req.top_k, req.min_score)
+    let context = if req.use_rag && !use_tools {
+        build_rag_context(state, &req.message, req.top_k, req.min_score, req.context_mode, req.collection_id)
     } else {
         Vec::new()
     };
 
     // Build messages
-    let messages = build_messages(&context, &req.conversation_history, &req.message);
+    let mut messages = if use_tools {
+        build_tool_messages(&req.conversation_history, &req.message)
+    } else {
+        build_messages(&context, &req.conversation_history, &req.message)
+    };
 
-    let temperature = req.temperature.unwrap_or(0.7);
+    // Tokenize with the session's map so a value keeps its token across
+    // turns and chunks; the map itself never leaves the device
+    let anonymizer = req.anonymize.then(|| anonymization_session(state, req.session_id.as_deref()));
+    let anonymized = anonymize_messages(&state.pii_detector, anonymizer.as_ref(), &mut messages);
     let max_tokens = req.max_tokens.unwrap_or(2048);
 
-    // Collect all tokens (non-streaming)
-    let client = reqwest::Client::new();
-    let stream = providers::stream_llm(
-        &client, provider, messages,
-        &model, &api_key,
-        temperature, max_tokens,
-    );
+    let chunk_ids = context_chunk_ids(&context);
+    let entry = state
+        .audit
+        .record(AuditRequest {
+            purpose,
+            provider,
+            model: &model,
+            messages: &messages,
+            chunk_ids,
+            max_tokens,
+            anonymized,
+        })
+        .map_err(|e| ApiError::internal(format!("Failed to write audit entry: {}", e)))?;
+
+    let request = LlmRequest {
+        provider,
+        model,
+        api_key,
+        messages,
+        temperature: req.temperature.unwrap_or(0.7),
+        max_tokens,
+        openai_base_url: state.llm_config.read().openai_base_url.clone(),
+        tools: use_tools.then(ToolUse::new),
+        audit_id: entry.id,
+        anonymizer,
+    };
+    Ok(Prepared::Llm(request, context))
+}
+
+/// The token map of chat session `session_id`, or one for this request
+/// alone without a session.
+fn anonymization_session(state: &AppState, session_id: Option<&str>) -> SharedSession {
+    match session_id {
+        Some(id) => state.anonymization.session(id),
+        None => Arc::default(),
+    }
+}
+
+/// Replace PII in `messages` with tokens from `session`, when there is
+/// one. Returns whether anonymization ran, as recorded in the audit log.
+pub(crate) fn anonymize_messages(
+    detector: &PiiDetector,
+    session: Option<&SharedSession>,
+    messages: &mut [ChatMessage],
+) -> bool {
+    let Some(session) = session else {
+        return false;
+    };
+    let mut session = session.lock();
+    for message in messages {
+        message.content = session.anonymize(detector, &message.content).text;
+    }
+    true
+}
 
-    tokio::pin!(stream);
+/// Chunk ids behind context entries, as recorded in the audit log.
+fn context_chunk_ids(context: &[ChatContext]) -> Vec<i64> {
+    context
+        .iter()
+        .flat_map(|c| if c.chunk_ids.is_empty() { vec![c.id] } else { c.chunk_ids.clone() })
+        .collect()
+}
+
+// ---------------------------------------------------------------
+// Answering, with tool rounds
+// ---------------------------------------------------------------
+
+/// What answering a request produces: the provider's chunks, and the
+/// searches run for its tool calls. `ToolCalls` chunks are not passed on.
+enum AnswerEvent {
+    Chunk(StreamChunk),
+    Searched {
+        query: String,
+        top_k: usize,
+        round: usize,
+        /// Entries not sent in an earlier round, numbered on from them.
+        context: Vec<ChatContext>,
+    },
+}
+
+type AnswerStream = Pin<Box<dyn Stream<Item = AnswerEvent> + Send>>;
+
+/// Send `request` and stream the answer. When the model calls the search
+/// tool, run the searches against the store, audit the follow-up request
+/// and send it, until the model answers or its rounds run out. Usage is
+/// recorded against each round's audit entry; `Done` carries the totals.
+fn answer(
+    state: Arc<AppState>,
+    mut request: LlmRequest,
+    req: ChatRequest,
+    purpose: AuditPurpose,
+    mut send: impl FnMut(LlmRequest) -> BoxedStream + Send + 'static,
+) -> AnswerStream {
+    // The body is polled after the handler returns; keep provider logs in
+    // the request's span
+    let span = tracing::Span::current();
+
+    Box::pin(async_stream::stream! {
+        let mut tokens_used = 0;
+        let mut usage: Option<ProviderUsage> = None;
+        // Context ids already sent as tool results, so a repeated search
+        // only sends what is new
+        let mut sent = HashSet::new();
+        let mut numbered = 0;
+        let anonymizer = request.anonymizer.clone();
+        // Answer text that may end in a placeholder cut off mid-stream
+        let mut held = String::new();
+
+        loop {
+            let mut stream = send(request.clone());
+            let mut text = String::new();
+            let mut calls = Vec::new();
+            while let Some(chunk) = stream.next().instrument(span.clone()).await {
+                match chunk {
+                    StreamChunk::Token(content) => {
+                        text.push_str(&content);
+                        let Some(session) = &anonymizer else {
+                            yield AnswerEvent::Chunk(StreamChunk::Token(content));
+                            continue;
+                        };
+                        held.push_str(&content);
+                        let ready = held.len() - pending_placeholder_len(&held);
+                        if ready > 0 {
+                            let restored = session.lock().deanonymize(&held[..ready]);
+                            held.drain(..ready);
+                            yield AnswerEvent::Chunk(StreamChunk::Token(restored));
+                        }
+                    }
+                    StreamChunk::ToolCalls(c) => calls = c,
+                    StreamChunk::Done { tokens_used: t, usage: u } => {
+                        tokens_used += t;
+                        if let Some(u) = u {
+                            let audit_id = request.audit_id.clone();
+                            state
+                                .blocking(move |state| record_usage(state, &audit_id, u))
+                                .instrument(span.clone())
+                                .await;
+                            usage = Some(add_usage(usage, u));
+                        }
+                        break;
+                    }
+                    StreamChunk::Error(e) => {
+                        yield AnswerEvent::Chunk(StreamChunk::Error(e));
+                        return;
+                    }
+                }
+            }
+            if !held.is_empty() {
+                yield AnswerEvent::Chunk(StreamChunk::Token(std::mem::take(&mut held)));
+            }
+
+            // Calls made after the last round are ignored
+            let Some(tools) = request.tools.as_mut().filter(|t| t.allow_calls && !calls.is_empty()) else {
+                yield AnswerEvent::Chunk(StreamChunk::Done { tokens_used, usage });
+                return;
+            };
+            let round = tools.rounds.len() + 1;
+            let mut results = Vec::new();
+            let mut chunk_ids = Vec::new();
+            for call in calls {
+                let Some((query, top_k)) = call.search_args(req.top_k) else {
+                    let content = format!("Unknown tool or missing query; only {} with a query is available.", SEARCH_TOOL);
+                    results.push(ToolResult { call, content });
+                    continue;
+                };
+                let (search, q) = (req.clone(), query.clone());
+                let found = state
+                    .blocking(move |state| {
+                        build_rag_context(state, &q, top_k, search.min_score, search.context_mode, search.collection_id)
+                    })
+                    .instrument(span.clone())
+                    .await;
+                let context: Vec<ChatContext> = found.into_iter().filter(|c| sent.insert(c.id)).collect();
+                let content = match (&anonymizer, context.is_empty()) {
+                    (_, true) => "No new passages match this search.".to_string(),
+                    (Some(session), false) => session.lock().anonymize(&state.pii_detector, &format_context(&context, numbered)).text,
+                    (None, false) => format_context(&context, numbered),
+                };
+                numbered += context.len();
+                chunk_ids.extend(context_chunk_ids(&context));
+                yield AnswerEvent::Searched { query, top_k, round, context };
+                results.push(ToolResult { call, content });
+            }
+            tools.push(ToolRound { text, results });
+
+            // The follow-up sends the results out; audit it like the first
+            let follow_up = request.clone();
+            let recorded = state
+                .blocking(move |state| audit_follow_up(state, &follow_up, purpose, chunk_ids))
+                .instrument(span.clone())
+                .await;
+            match recorded {
+                Ok(audit_id) => request.audit_id = audit_id,
+                Err(e) => {
+                    yield AnswerEvent::Chunk(StreamChunk::Error(e));
+                    return;
+                }
+            }
+        }
+    })
+}
+
+/// Write the audit entry of a tool round's follow-up request. The prompt
+/// is the request's messages with each round appended as an assistant
+/// message naming the searches and one `tool` message per result.
+fn audit_follow_up(state: &AppState, request: &LlmRequest, purpose: AuditPurpose, chunk_ids: Vec<i64>) -> Result<String, String> {
+    let mut messages = request.messages.clone();
+    for round in request.tools.iter().flat_map(|t| &t.rounds) {
+        let calls: Vec<String> = round.results.iter().map(|r| format!("{}({})", r.call.name, r.call.arguments)).collect();
+        messages.push(ChatMessage {
+            role: "assistant".into(),
+            content: format!("{}\n{}", round.text, calls.join("\n")).trim().to_string(),
+        });
+        messages.extend(round.results.iter().map(|r| ChatMessage { role: "tool".into(), content: r.content.clone() }));
+    }
+    state
+        .audit
+        .record(AuditRequest {
+            purpose,
+            provider: request.provider,
+            model: &request.model,
+            messages: &messages,
+            chunk_ids,
+            max_tokens: request.max_tokens,
+            anonymized: request.anonymizer.is_some(),
+        })
+        .map(|entry| entry.id)
+        .map_err(|e| format!("Failed to write audit entry: {}", e))
+}
+
+/// Usage of two rounds of one answer, added up.
+fn add_usage(total: Option<ProviderUsage>, usage: ProviderUsage) -> ProviderUsage {
+    let total = total.unwrap_or_default();
+    ProviderUsage {
+        prompt_tokens: total.prompt_tokens + usage.prompt_tokens,
+        completion_tokens: total.completion_tokens + usage.completion_tokens,
+        cached_tokens: total.cached_tokens + usage.cached_tokens,
+        cache_write_tokens: total.cache_write_tokens + usage.cache_write_tokens,
+    }
+}
+
+// ---------------------------------------------------------------
+// Non-streaming chat
+// ---------------------------------------------------------------
+
+#[utoipa::path(
+    post,
+    path = "/chat",
+    tag = "chat",
+    responses(
+        (status = 200, description = "Generated, or extractive without an LLM", body = ChatResponse),
+        (status = 502, description = "The LLM provider failed", body = ErrorBody),
+    )
+)]
+async fn chat(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ChatRequest>,
+) -> ApiResult<Json<ChatResponse>> {
+    chat_with(&state, req, send_to_provider).await
+}
+
+async fn chat_with(
+    state: &Arc<AppState>,
+    req: ChatRequest,
+    send: impl FnMut(LlmRequest) -> BoxedStream + Send + 'static,
+) -> ApiResult<Json<ChatResponse>> {
+    let start = Instant::now();
+
+    let prepare = req.clone();
+    let prepared = state
+        .blocking(move |state| prepare_request(state, &prepare, AuditPurpose::Chat))
+        .await?;
+    let (request, mut context) = match prepared {
+        Prepared::Llm(request, context) => (request, context),
+        Prepared::Extractive(context) => {
+            return Ok(Json(ChatResponse {
+                message: extractive_answer(&req.message, &context).concat(),
+                model: EXTRACTIVE_MODEL.to_string(),
+                context: if context.is_empty() { None } else { Some(context) },
+                tokens_used: Some(0),
+                duration: Some(start.elapsed().as_millis() as u64),
+                extractive: true,
+                usage: None,
+            }));
+        }
+    };
+    let model = request.model.clone();
+
+    // Collect all tokens (non-streaming)
+    let mut stream = answer(state.clone(), request, req, AuditPurpose::Chat, send);
 
     let mut full_response = String::new();
     let mut tokens_used = 0;
+    let mut usage = None;
 
-    while let Some(chunk) = stream.next().await {
-        match chunk {
-            StreamChunk::Token(text) => {
+    while let Some(event) = stream.next().await {
+        match event {
+            AnswerEvent::Chunk(StreamChunk::Token(text)) => {
                 full_response.push_str(&text);
             }
-            StreamChunk::Done { tokens_used: t } => {
+            AnswerEvent::Chunk(StreamChunk::Done { tokens_used: t, usage: u }) => {
                 tokens_used = t;
+                usage = u;
             }
-            StreamChunk::Error(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({ "error": e })),
-                );
+            AnswerEvent::Chunk(StreamChunk::Error(e)) => {
+                return Err(ApiError::new(StatusCode::BAD_GATEWAY, "upstream_error", e));
             }
+            AnswerEvent::Chunk(StreamChunk::ToolCalls(_)) => {}
+            AnswerEvent::Searched { context: found, .. } => context.extend(found),
         }
     }
 
     let duration = start.elapsed().as_millis() as u64;
 
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({
-            "message": full_response,
-            "model": model,
-            "context": if context.is_empty() { None } else { Some(&context) },
-            "tokensUsed": tokens_used,
-            "duration": duration,
-        })),
-    )
+    Ok(Json(ChatResponse {
+        message: full_response,
+        model,
+        context: if context.is_empty() { None } else { Some(context) },
+        tokens_used: Some(tokens_used),
+        duration: Some(duration),
+        extractive: false,
+        usage,
+    }))
+}
+
+/// Append reported usage to the audit log. The answer has already been
+/// received, so a failed write is logged rather than returned.
+fn record_usage(state: &AppState, audit_id: &str, usage: ProviderUsage) {
+    if let Err(e) = state.audit.record_usage(audit_id, usage) {
+        warn!("Failed to record usage for audit entry {}: {}", audit_id, e);
+    }
 }
 
 // ---------------------------------------------------------------
 // Streaming chat (SSE)
 // ---------------------------------------------------------------
 
+/// Server-sent events whose data is a JSON `StreamEvent` (context, tokens,
+/// then done or error), followed by a `[DONE]` marker. With tool retrieval
+/// each search is a `tool_call` event followed by a `context` event with
+/// its new results.
+#[utoipa::path(
+    post,
+    path = "/chat/stream",
+    tag = "chat",
+    responses((status = 200, description = "Event stream", content_type = "text/event-stream", body = String))
+)]
 async fn stream_chat(
     State(state): State<Arc<AppState>>,
     Json(req): Json<ChatRequest>,
 ) -> Sse<SseStream> {
-    let start = Instant::now();
+    Sse::new(stream_chat_with(&state, req, send_to_provider).await)
+}
 
-    let resolved = {
-        let config = state.llm_config.read();
-        config.resolve_provider()
-    };
+async fn stream_chat_with(
+    state: &Arc<AppState>,
+    req: ChatRequest,
+    send: impl FnMut(LlmRequest) -> BoxedStream + Send + 'static,
+) -> SseStream {
+    let start = Instant::now();
 
-    let (provider, model, api_key) = match resolved {
-        Some(r) => r,
-        None => {
+    let prepare = req.clone();
+    let prepared = state
+        .blocking(move |state| prepare_request(state, &prepare, AuditPurpose::ChatStream))
+        .await;
+    let (request, context) = match prepared {
+        Ok(Prepared::Llm(request, context)) => (request, context),
+        Ok(Prepared::Extractive(context)) => return extractive_stream(&req.message, context, start),
+        Err(e) => {
             let error_stream: SseStream = Box::pin(async_stream::stream! {
-                let event = StreamEvent::Error {
-                    error: "No LLM provider configured".into(),
-                };
+                let event = StreamEvent::Error { error: e.message };
                 yield Ok::<_, Infallible>(Event::default().data(
                     serde_json::to_string(&event).unwrap()
                 ));
             });
-            return Sse::new(error_stream);
+            return error_stream;
         }
     };
 
-    // Build RAG context
-    let context = if req.use_rag {
-        build_rag_context(&state, &req.message, req.top_k, req.min_score)
-    } else {
-        Vec::new()
-    };
+    let model_clone = request.model.clone();
+    let llm_stream = answer(state.clone(), request, req, AuditPurpose::ChatStream, send);
 
-    // Build messages
-    let messages = build_messages(&context, &req.conversation_history, &req.message);
-
-    let temperature = req.temperature.unwrap_or(0.7);
-    let max_tokens = req.max_tokens.unwrap_or(2048);
-
-    let client = reqwest::Client::new();
-    let llm_stream = providers::stream_llm(
-        &client, provider, messages,
-        &model, &api_key,
-        temperature, max_tokens,
-    );
-
-    let model_clone = model.clone();
-
-    let sse_stream: SseStream = Box::pin(async_stream::stream! {
+    Box::pin(async_stream::stream! {
         // First: emit context event
         if !context.is_empty() {
             let event = StreamEvent::Context { context };
@@ -196,7 +556,19 @@ async fn stream_chat(
 
         // Stream tokens from LLM
         tokio::pin!(llm_stream);
-        while let Some(chunk) = llm_stream.next().await {
+        while let Some(event) = llm_stream.next().await {
+            let chunk = match event {
+                AnswerEvent::Chunk(chunk) => chunk,
+                AnswerEvent::Searched { query, top_k, round, context } => {
+                    let event = StreamEvent::ToolCall { query, top_k, round };
+                    yield Ok(Event::default().data(serde_json::to_string(&event).unwrap()));
+                    if !context.is_empty() {
+                        let event = StreamEvent::Context { context };
+                        yield Ok(Event::default().data(serde_json::to_string(&event).unwrap()));
+                    }
+                    continue;
+                }
+            };
             match chunk {
                 StreamChunk::Token(text) => {
                     let event = StreamEvent::Token { content: text };
@@ -204,18 +576,20 @@ async fn stream_chat(
                         serde_json::to_string(&event).unwrap()
                     ));
                 }
-                StreamChunk::Done { tokens_used } => {
+                StreamChunk::ToolCalls(_) => {}
+                StreamChunk::Done { tokens_used, usage } => {
                     let duration = start.elapsed().as_millis() as u64;
                     let event = StreamEvent::Done {
                         model: model_clone.clone(),
                         tokens_used,
                         duration,
+                        usage,
                     };
                     yield Ok(Event::default().data(
                         serde_json::to_string(&event).unwrap()
                     ));
                     // Final [DONE] marker
-                    yield Ok(Event::default().data("[DONE]".to_string()));
+                    yield Ok(Event::default().data("[DONE]"));
                     return;
                 }
                 StreamChunk::Error(e) => {
@@ -227,103 +601,261 @@ async fn stream_chat(
                 }
             }
         }
-    });
+    })
+}
+
+/// An extractive answer as the events of an LLM stream, ending at `[DONE]`.
+fn extractive_stream(query: &str, context: Vec<ChatContext>, start: Instant) -> SseStream {
+    let data = extractive_events(query, context, start)
+        .iter()
+        .map(|event| serde_json::to_string(event).unwrap())
+        .chain(std::iter::once("[DONE]".to_string()))
+        .map(|data| Ok::<_, Infallible>(Event::default().data(data)))
+        .collect::<Vec<_>>();
+    Box::pin(tokio_stream::iter(data))
+}
 
-    Sse::new(sse_stream)
+/// Context, one token per piece of the extractive answer, then `done` with
+/// model `extractive`.
+fn extractive_events(query: &str, context: Vec<ChatContext>, start: Instant) -> Vec<StreamEvent> {
+    let tokens = extractive_answer(query, &context);
+    let mut events = Vec::new();
+    if !context.is_empty() {
+        events.push(StreamEvent::Context { context });
+    }
+    events.extend(tokens.into_iter().map(|content| StreamEvent::Token { content }));
+    events.push(StreamEvent::Done {
+        model: EXTRACTIVE_MODEL.to_string(),
+        tokens_used: 0,
+        duration: start.elapsed().as_millis() as u64,
+        usage: None,
+    });
+    events
 }
 
 // ---------------------------------------------------------------
 // Config
 // ---------------------------------------------------------------
 
-async fn get_config(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
-    let config = state.llm_config.read();
-    Json(serde_json::to_value(config.to_response()).unwrap())
+#[utoipa::path(get, path = "/chat/config", tag = "chat", responses((status = 200, body = LLMConfigResponse)))]
+async fn get_config(State(state): State<Arc<AppState>>) -> Json<LLMConfigResponse> {
+    Json(state.llm_config.read().to_response())
 }
 
+#[utoipa::path(put, path = "/chat/config", tag = "chat", responses((status = 200, body = LLMConfigResponse)))]
 async fn update_config(
     State(state): State<Arc<AppState>>,
     Json(update): Json<LLMConfigUpdate>,
-) -> impl IntoResponse {
+) -> ApiResult<Json<LLMConfigResponse>> {
     let mut config = state.llm_config.write();
     config.apply_update(&update);
 
-    if let Err(e) = config.save() {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": format!("Failed to save config: {}", e) })),
-        );
-    }
+    config
+        .save()
+        .map_err(|e| ApiError::internal(format!("Failed to save config: {}", e)))?;
 
-    (
-        StatusCode::OK,
-        Json(serde_json::to_value(config.to_response()).unwrap()),
-    )
+    Ok(Json(config.to_response()))
 }
 
+#[utoipa::path(
+    post,
+    path = "/chat/config/test",
+    tag = "chat",
+    responses(
+        (status = 200, body = Object, example = json!({"success": true})),
+        (status = 400, description = "The provider rejected the key", body = ErrorBody),
+    )
+)]
 async fn test_key(
     Json(req): Json<TestKeyRequest>,
-) -> impl IntoResponse {
-    match providers::test_api_key(&req.provider, &req.api_key).await {
-        Ok(()) => (
-            StatusCode::OK,
-            Json(serde_json::json!({ "success": true })),
-        ),
-        Err(e) => (
-            StatusCode::OK,
-            Json(serde_json::json!({ "success": false, "error": e })),
-        ),
-    }
+) -> ApiResult<Json<serde_json::Value>> {
+    providers::test_api_key(&req.provider, req.api_key.expose())
+        .await
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, "invalid_api_key", e))?;
+    Ok(Json(serde_json::json!({ "success": true })))
 }
 
 // ---------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------
 
-/// Build RAG context from vector store search.
+/// `model` of an answer quoted from the context.
+const EXTRACTIVE_MODEL: &str = "extractive";
+
+/// Most sentences an extractive answer quotes.
+const EXTRACTIVE_SENTENCES: usize = 3;
+
+/// Quote the context sentences that cover the most query terms, each cited
+/// as `[n]` (its context entry, as in the LLM prompt). Returned as the
+/// pieces of the message, one per sentence.
+fn extractive_answer(query: &str, context: &[ChatContext]) -> Vec<String> {
+    // Best sentence of each entry first, so one long entry cannot crowd
+    // out the others; ties keep the retrieval order
+    let mut candidates: Vec<(f64, usize, usize, &str)> = context
+        .iter()
+        .enumerate()
+        .flat_map(|(i, c)| {
+            rank_sentences(&c.excerpt, query)
+                .into_iter()
+                .enumerate()
+                .map(move |(rank, (coverage, sentence))| (coverage, rank, i, sentence))
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let mut seen = std::collections::HashSet::new();
+    let quotes: Vec<String> = candidates
+        .into_iter()
+        .filter(|(_, _, _, sentence)| seen.insert(*sentence))
+        .take(EXTRACTIVE_SENTENCES)
+        .map(|(_, _, i, sentence)| format!("\n\n> {} [{}]", sentence.trim_end_matches("..."), i + 1))
+        .collect();
+    if quotes.is_empty() {
+        return vec!["No passages in your documents match the question.".to_string()];
+    }
+
+    let mut pieces =
+        vec!["No language model was used. These passages from your documents match the question:".to_string()];
+    pieces.extend(quotes);
+    pieces
+}
+
+/// Build RAG context from vector store search, expanding each hit per `mode`.
+/// With a `collection_id`, only documents of that collection are searched.
+#[instrument(level = "debug", skip_all, fields(top_k = top_k, collection_id = ?collection_id))]
 fn build_rag_context(
     state: &AppState,
     query: &str,
     top_k: usize,
     min_score: f64,
+    mode: ContextMode,
+    collection_id: Option<i64>,
 ) -> Vec<ChatContext> {
-    // Use hybrid search when embedder is available, else BM25
-    let results = if state.embedder.is_available() {
-        if let Some(emb_result) = state.embedder.embed(query) {
-            match state.store.hybrid_search(query, &emb_result.embedding, 1, top_k, top_k, 60) {
-                Ok(r) => r,
-                Err(_) => match state.store.bm25_search(query, 1, top_k) {
-                    Ok(r) => r,
-                    Err(_) => return Vec::new(),
-                },
-            }
-        } else {
-            match state.store.bm25_search(query, 1, top_k) {
+    // Use hybrid search when embedder is available, else BM25; fetch extra
+    // candidates for the post-processing
+    let fetch_k = top_k * 2;
+    let query_embedding = if state.embedder.is_available() {
+        state.embedder.embed_query(query).map(|r| r.embedding)
+    } else {
+        None
+    };
+    let scope = collection_id.map(|id| SearchFilters { collections: vec![id], ..Default::default() });
+    let candidates = if let Some(embedding) = &query_embedding {
+        match state.store.hybrid_search_filtered(query, embedding, None, fetch_k, fetch_k, 60, scope.as_ref()) {
+            Ok(r) => r,
+            Err(_) => match state.store.bm25_search_filtered(query, None, fetch_k, None, scope.as_ref()) {
                 Ok(r) => r,
                 Err(_) => return Vec::new(),
-            }
+            },
         }
     } else {
-        match state.store.bm25_search(query, 1, top_k) {
+        match state.store.bm25_search_filtered(query, None, fetch_k, None, scope.as_ref()) {
             Ok(r) => r,
             Err(_) => return Vec::new(),
         }
     };
+    // Overlap between the selected hits is cut before expansion, so an
+    // excerpt never repeats the end of a better-ranked one
+    let settings = state.store.search_post_processing();
+    let results = merge_overlapping_hits(post_process_hits(state, candidates, query, &settings, top_k));
 
-    results
+    let spans: Vec<ContextSpan> = results
         .iter()
+        .map(|merged| &merged.hit)
         .filter(|hit| hit.score >= min_score)
         .map(|hit| {
             let (source, filename) = extract_source_filename(&hit.metadata);
-            ChatContext {
-                id: hit.chunk_id,
-                excerpt: truncate_text(&hit.text, 500),
+            ContextSpan {
+                doc_id: hit.doc_id,
+                hit_chunk_id: hit.chunk_id,
                 score: hit.score,
                 source,
                 filename,
+                pieces: expand_hit(state, hit, mode),
             }
         })
-        .collect()
+        .collect();
+
+    let relevance = |sentences: &[&str]| sentence_relevance(state, query, query_embedding.as_ref(), sentences);
+    let compression = context::Compression { relevance: &relevance };
+    context::assemble_context(spans, mode, context::CONTEXT_TOKEN_BUDGET, Some(&compression))
+}
+
+/// Relevance of each sentence to the query for context compression: its
+/// query-term coverage, or its embedding's cosine similarity to the query
+/// when that is higher.
+fn sentence_relevance(
+    state: &AppState,
+    query: &str,
+    query_embedding: Option<&ndarray::Array1<f32>>,
+    sentences: &[&str],
+) -> Vec<f64> {
+    let mut scores: Vec<f64> = sentences.iter().map(|s| query_coverage(s, query)).collect();
+    if let Some(query_embedding) = query_embedding {
+        let query_norm = query_embedding.dot(query_embedding).sqrt();
+        for (score, result) in scores.iter_mut().zip(state.embedder.embed_batch(sentences)) {
+            let Some(result) = result else { continue };
+            let norm = query_norm * result.embedding.dot(&result.embedding).sqrt();
+            if norm > 0.0 {
+                *score = score.max((query_embedding.dot(&result.embedding) / norm) as f64);
+            }
+        }
+    }
+    scores
+}
+
+/// Pull the chunks that make up one hit's context. Falls back to the hit
+/// itself when the parent section or neighbours can't be loaded.
+fn expand_hit(state: &AppState, hit: &SearchHit, mode: ContextMode) -> Vec<ContextPiece> {
+    let expanded = match mode {
+        // The hit as found, less any overlap with a better-ranked hit
+        ContextMode::Excerpt => vec![ContextPiece {
+            chunk_id: hit.chunk_id,
+            chunk_index: hit.chunk_index,
+            char_start: hit.char_start,
+            char_end: hit.char_end,
+            text: hit.text.clone(),
+        }],
+        ContextMode::Section => state
+            .store
+            .get_parent_chunk(hit.chunk_id)
+            .ok()
+            .flatten()
+            .map(|parent| vec![to_piece(&parent)])
+            .unwrap_or_default(),
+        ContextMode::Window => state
+            .store
+            .get_surrounding_chunks(hit.chunk_id, context::WINDOW_RADIUS)
+            .map(|chunks| {
+                let pieces = chunks.iter().map(to_piece).collect();
+                context::trim_window(pieces, hit.chunk_id, context::per_hit_chars(mode))
+            })
+            .unwrap_or_default(),
+    };
+    if !expanded.is_empty() {
+        return expanded;
+    }
+
+    match state.store.get_chunk(hit.chunk_id) {
+        Ok(Some(chunk)) => vec![to_piece(&chunk)],
+        _ => vec![ContextPiece {
+            chunk_id: hit.chunk_id,
+            chunk_index: 0,
+            char_start: None,
+            char_end: None,
+            text: hit.text.clone(),
+        }],
+    }
+}
+
+fn to_piece(chunk: &Chunk) -> ContextPiece {
+    ContextPiece {
+        chunk_id: chunk.id,
+        chunk_index: chunk.chunk_index,
+        char_start: chunk.char_start,
+        char_end: chunk.char_end,
+        text: chunk.text.clone(),
+    }
 }
 
 fn extract_source_filename(
@@ -342,15 +874,12 @@ fn extract_source_filename(
     (source, filename)
 }
 
-fn truncate_text(text: &str, max_len: usize) -> String {
-    if text.len() <= max_len {
-        text.to_string()
-    } else {
-        format!("{}...", &text[..max_len])
-    }
-}
-
 /// Build the message array for the LLM, including system prompt with RAG context.
+///
+/// The system prompt and context come first and history after, so a session's
+/// turns share a prefix the provider can serve from its prompt cache. Message
+/// text is trimmed, so a reply echoed back with different surrounding
+/// whitespace does not break that prefix.
 fn build_messages(
     context: &[ChatContext],
     conversation_history: &[ChatMessage],
@@ -364,19 +893,7 @@ fn build_messages(
          Answer questions based on your knowledge."
             .to_string()
     } else {
-        let context_str: String = context
-            .iter()
-            .enumerate()
-            .map(|(i, c)| {
-                let source_info = c
-                    .source
-                    .as_ref()
-                    .map(|s| format!(" (source: {})", s))
-                    .unwrap_or_default();
-                format!("[{}]{}: {}", i + 1, source_info, c.excerpt)
-            })
-            .collect::<Vec<_>>()
-            .join("\n\n");
+        let context_str = format_context(context, 0);
 
         format!(
             "You are a helpful assistant with access to the user's personal knowledge base. \
@@ -395,14 +912,574 @@ fn build_messages(
 
     // Conversation history
     for msg in conversation_history {
-        messages.push(msg.clone());
+        messages.push(ChatMessage {
+            role: msg.role.clone(),
+            content: msg.content.trim().to_string(),
+        });
     }
 
     // Current user message
     messages.push(ChatMessage {
         role: "user".into(),
-        content: user_message.to_string(),
+        content: user_message.trim().to_string(),
     });
 
     messages
 }
+
+/// Messages for an answer that searches with the tool: no context, and
+/// a system prompt asking the model to search before answering.
+fn build_tool_messages(conversation_history: &[ChatMessage], user_message: &str) -> Vec<ChatMessage> {
+    let mut messages = build_messages(&[], conversation_history, user_message);
+    messages[0].content = format!(
+        "You are a helpful assistant with access to the user's personal knowledge base. \
+         Use the {} tool to look up facts in it before answering, and cite the \
+         excerpts you use as [n]. If the searches find nothing relevant, say so.\n\n\
+         Note: Some values in the results may have been modified for privacy protection.",
+        SEARCH_TOOL
+    );
+    messages
+}
+
+/// Context entries as numbered lines, `[n] (source: ...): excerpt`,
+/// counting on from `numbered` entries sent before.
+fn format_context(context: &[ChatContext], numbered: usize) -> String {
+    context
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let source_info = c
+                .source
+                .as_ref()
+                .map(|s| format!(" (source: {})", s))
+                .unwrap_or_default();
+            format!("[{}]{}: {}", numbered + i + 1, source_info, c.excerpt)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use crate::audit::AuditQuery;
+    use crate::routes::vector_store::run_search;
+    use crate::test_support::TestState;
+    use mindsage_api_types::SearchRequest;
+    use mindsage_chat::tools::{ToolCall, MAX_TOOL_ROUNDS};
+    use mindsage_core::SearchPostProcessing;
+    use tempfile::TempDir;
+
+    /// State with a configured provider and three indexed documents.
+    fn seeded_state() -> (Arc<AppState>, TempDir) {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+        {
+            let mut llm = state.llm_config.write();
+            llm.preferred_provider = "groq".into();
+            llm.groq_api_key = Some("test-key".into());
+        }
+        for text in ["Tokio is an async runtime for Rust", "Sourdough needs a long cold proof", "Tide tables for the harbor"] {
+            let doc_id = state.store.add_document(text, Default::default()).unwrap();
+            state
+                .store
+                .add_chunk(doc_id, text, 0, 1, None, Some(0), Some(text.len() as i32), None, None, None)
+                .unwrap();
+        }
+        (Arc::new(state), dir)
+    }
+
+    fn request(message: &str) -> ChatRequest {
+        serde_json::from_value(serde_json::json!({ "message": message, "contextMode": "excerpt" })).unwrap()
+    }
+
+    /// A mocked provider that checks the audit entry already exists.
+    fn mock_send(state: Arc<AppState>) -> impl FnMut(LlmRequest) -> BoxedStream + Send + 'static {
+        move |request| {
+            let (entries, _) = state.audit.list(&AuditQuery::default()).unwrap();
+            assert_eq!(entries.len(), 1, "audit entry must be written before sending");
+            assert_eq!(entries[0].model, request.model);
+            Box::pin(tokio_stream::iter(vec![
+                StreamChunk::Token("Tokio runs async tasks.".into()),
+                StreamChunk::Done { tokens_used: 5, usage: None },
+            ]))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_writes_audit_entry_before_sending() {
+        let (state, _dir) = seeded_state();
+        let Json(response) = chat_with(&state, request("tokio async runtime"), mock_send(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.message, "Tokio runs async tasks.");
+
+        let (entries, total) = state.audit.list(&AuditQuery::default()).unwrap();
+        assert_eq!(total, 1);
+        let entry = &entries[0];
+        assert_eq!(entry.purpose, AuditPurpose::Chat);
+        assert_eq!(entry.provider, "groq");
+        assert_eq!(entry.chunk_ids.len(), 1);
+        assert_eq!(entry.prompt_hash.len(), 64);
+        assert!(entry.prompt.is_none());
+        assert!(!entry.anonymized);
+    }
+
+    #[tokio::test]
+    async fn test_stream_chat_writes_audit_entry_before_sending() {
+        let (state, _dir) = seeded_state();
+        let events: Vec<_> = stream_chat_with(&state, request("tokio async runtime"), mock_send(state.clone()))
+            .await
+            .collect()
+            .await;
+        assert!(events.len() >= 3);
+
+        let stream_only = AuditQuery {
+            purpose: Some(AuditPurpose::ChatStream),
+            ..Default::default()
+        };
+        let (entries, total) = state.audit.list(&stream_only).unwrap();
+        assert_eq!(total, 1);
+        assert!(entries[0].prompt_tokens > 0);
+    }
+
+    #[tokio::test]
+    async fn test_reported_usage_reaches_done_event_and_audit_log() {
+        let (state, _dir) = seeded_state();
+        let usage = ProviderUsage {
+            prompt_tokens: 1300,
+            completion_tokens: 6,
+            cached_tokens: 1024,
+            cache_write_tokens: 0,
+        };
+        let send = move |_: LlmRequest| -> BoxedStream {
+            Box::pin(tokio_stream::iter(vec![
+                StreamChunk::Token("Tokio runs async tasks.".into()),
+                StreamChunk::Done { tokens_used: 1, usage: Some(usage) },
+            ]))
+        };
+        let events: Vec<_> = stream_chat_with(&state, request("tokio async runtime"), send)
+            .await
+            .collect()
+            .await;
+        assert_eq!(events.len(), 4);
+
+        let Json(response) = chat_with(&state, request("tokio async runtime"), send).await.unwrap();
+        assert_eq!(response.usage, Some(usage));
+
+        let (entries, total) = state.audit.list(&AuditQuery::default()).unwrap();
+        assert_eq!(total, 2);
+        assert!(entries.iter().all(|e| e.usage == Some(usage)));
+    }
+
+    #[test]
+    fn test_request_prefix_is_stable_across_turns() {
+        let context = vec![ChatContext {
+            id: 1,
+            excerpt: "Tokio is an async runtime for Rust. ".repeat(150),
+            score: 0.9,
+            source: Some("notes".into()),
+            filename: None,
+            mode: ContextMode::Excerpt,
+            chunk_ids: Vec::new(),
+            original_chars: 0,
+            sent_chars: 0,
+        }];
+        let first = build_messages(&context, &[], "What is tokio? ");
+        let history = vec![
+            ChatMessage { role: "user".into(), content: "What is tokio?".into() },
+            ChatMessage { role: "assistant".into(), content: "An async runtime.\n".into() },
+        ];
+        let second = build_messages(&context, &history, "Who maintains it?");
+
+        // Everything the first turn sent, up to its question, opens the second
+        let open_prefix = |messages: &serde_json::Value, keep: usize| {
+            let kept = &messages.as_array().unwrap()[..keep];
+            let encoded = serde_json::to_string(kept).unwrap();
+            encoded[..encoded.len() - 1].to_string()
+        };
+        let bodies = [
+            (providers::openai_request_body(&first, "gpt-4o", 0.7, 512, true), providers::openai_request_body(&second, "gpt-4o", 0.7, 512, true)),
+            (
+                providers::anthropic_request_body(&first, "claude-sonnet-4-5", 0.7, 512),
+                providers::anthropic_request_body(&second, "claude-sonnet-4-5", 0.7, 512),
+            ),
+        ];
+        for (turn1, turn2) in &bodies {
+            let asked = turn1["messages"].as_array().unwrap().len();
+            let prefix = open_prefix(&turn1["messages"], asked);
+            assert!(serde_json::to_string(&turn2["messages"]).unwrap().starts_with(&prefix));
+            assert_eq!(turn1["system"].to_string(), turn2["system"].to_string());
+        }
+
+        let (turn1, turn2) = &bodies[1];
+        for body in [turn1, turn2] {
+            let serialized = serde_json::to_string(body).unwrap();
+            assert!(serialized.contains(r#""cache_control":{"type":"ephemeral"}"#));
+        }
+    }
+
+    #[test]
+    fn test_rag_context_drops_irrelevant_sentences() {
+        let (state, _dir) = seeded_state();
+        let text = "The allotment committee meets on Thursdays. \
+                    Bring your own gloves to every work party. \
+                    The water meter for plot 14 is behind the green shed near the compost bays. \
+                    Tea is served after the meeting.";
+        let doc_id = state.store.add_document(text, Default::default()).unwrap();
+        state
+            .store
+            .add_chunk(doc_id, text, 0, 1, None, Some(0), Some(text.len() as i32), None, None, None)
+            .unwrap();
+
+        let context = build_rag_context(&state, "where is the water meter", 5, 0.0, ContextMode::Excerpt, None);
+        let entry = context.iter().find(|c| c.excerpt.contains("water meter")).unwrap();
+        assert_eq!(
+            entry.excerpt,
+            "... The water meter for plot 14 is behind the green shed near the compost bays. ..."
+        );
+        assert_eq!(entry.original_chars, text.chars().count());
+        assert_eq!(entry.sent_chars, entry.excerpt.chars().count());
+        assert!(entry.sent_chars < entry.original_chars);
+    }
+
+    #[test]
+    fn test_rag_context_sends_overlapping_chunks_once() {
+        let (state, _dir) = seeded_state();
+        let text = "The boiler is in the loft. Its pressure gauge should read 1.5 bar. \
+                    Top the boiler up from the filling loop under the sink. \
+                    Bleed the radiators when the boiler has been topped up.";
+        let doc_id = state.store.add_document(text, Default::default()).unwrap();
+        // Each chunk repeats the last sentence of the one before
+        let sentences: Vec<usize> = text.match_indices(". ").map(|(i, _)| i + 1).collect();
+        let bounds = [(0, sentences[1]), (sentences[0] + 1, sentences[2]), (sentences[1] + 1, text.len())];
+        for (index, (start, end)) in bounds.into_iter().enumerate() {
+            state
+                .store
+                .add_chunk(doc_id, &text[start..end], index as i32, 1, None, Some(start as i32), Some(end as i32), None, None, None)
+                .unwrap();
+        }
+
+        for mode in [ContextMode::Excerpt, ContextMode::Window] {
+            let context = build_rag_context(&state, "boiler", 5, 0.0, mode, None);
+            let sent: String = context.iter().map(|c| c.excerpt.as_str()).collect::<Vec<_>>().join(" ");
+            for sentence in ["The boiler is in the loft.", "pressure gauge", "filling loop", "Bleed the radiators"] {
+                assert!(sent.matches(sentence).count() <= 1, "{:?}: {}", mode, sent);
+            }
+            assert!(sent.contains("filling loop"));
+        }
+    }
+
+    #[test]
+    fn test_rag_context_stays_in_its_collection() {
+        let (state, _dir) = seeded_state();
+        let mut docs = Vec::new();
+        for text in ["Work: the standup moved to 9:30.", "Personal: the dentist moved to 9:30."] {
+            let doc_id = state.store.add_document(text, Default::default()).unwrap();
+            state.store.add_chunk(doc_id, text, 0, 1, None, None, None, None, None, None).unwrap();
+            docs.push(doc_id);
+        }
+        let work = state.store.create_collection("work").unwrap().unwrap();
+        state.store.add_to_collection(work.id, &[docs[0]]).unwrap();
+
+        let everything = build_rag_context(&state, "what moved to 9:30", 5, 0.0, ContextMode::Excerpt, None);
+        assert_eq!(everything.len(), 2);
+        let scoped = build_rag_context(&state, "what moved to 9:30", 5, 0.0, ContextMode::Excerpt, Some(work.id));
+        assert_eq!(scoped.len(), 1);
+        assert!(scoped[0].excerpt.contains("standup"));
+    }
+
+    #[test]
+    fn test_chat_and_search_pick_the_same_hits() {
+        let (state, _dir) = seeded_state();
+        let texts: [(&str, &[&str]); 3] = [
+            ("manual", &["The heat pump defrost cycle runs hourly.", "Heat pump filters need cleaning.", "Heat pump noise at night."]),
+            ("forum", &["Defrost problems with an old heat pump outside."]),
+            ("notes", &["The freezer needs a defrost."]),
+        ];
+        for (name, chunks) in texts {
+            let doc_id = state.store.add_document(&chunks.join(" "), Default::default()).unwrap();
+            for (index, chunk) in chunks.iter().enumerate() {
+                state
+                    .store
+                    .add_chunk(doc_id, &format!("{}: {}", name, chunk), index as i32, 1, None, None, None, None, None, None)
+                    .unwrap();
+            }
+        }
+
+        for settings in [
+            SearchPostProcessing::default(),
+            SearchPostProcessing {
+                entity_boost: 0.0,
+                max_chunks_per_doc: Some(1),
+                mmr_lambda: 1.0,
+                ..Default::default()
+            },
+        ] {
+            state.store.set_search_post_processing(settings);
+            let query = "heat pump defrost";
+            let search = run_search(&state, SearchRequest::new(query)).unwrap().0;
+            let searched: Vec<i64> = search.results.iter().map(|r| r.chunk_id).collect();
+            let context = build_rag_context(&state, query, 10, 0.0, ContextMode::Excerpt, None);
+            let chatted: Vec<i64> = context.iter().flat_map(|c| c.chunk_ids.clone()).collect();
+            assert_eq!(searched, chatted, "{:?}", settings);
+
+            let per_doc = settings.max_chunks_per_doc.unwrap();
+            let mut docs: HashMap<i64, usize> = HashMap::new();
+            for result in &search.results {
+                *docs.entry(result.doc_id).or_default() += 1;
+            }
+            assert!(docs.values().all(|&n| n <= per_doc), "{:?}", docs);
+        }
+    }
+
+    /// A mocked provider that records each request and answers with the
+    /// chunks `reply` gives for it.
+    fn recording_send(
+        reply: impl Fn(&LlmRequest) -> Vec<StreamChunk> + Send + 'static,
+    ) -> (Arc<parking_lot::Mutex<Vec<LlmRequest>>>, impl FnMut(LlmRequest) -> BoxedStream + Send + 'static) {
+        let seen = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let record = seen.clone();
+        let send = move |request: LlmRequest| -> BoxedStream {
+            let chunks = reply(&request);
+            record.lock().push(request);
+            Box::pin(tokio_stream::iter(chunks))
+        };
+        (seen, send)
+    }
+
+    fn search_call(id: &str, query: &str) -> StreamChunk {
+        StreamChunk::ToolCalls(vec![ToolCall {
+            id: id.into(),
+            name: SEARCH_TOOL.into(),
+            arguments: serde_json::json!({"query": query, "top_k": 2}),
+        }])
+    }
+
+    fn use_anthropic(state: &AppState, tool_retrieval: bool) {
+        let mut llm = state.llm_config.write();
+        llm.preferred_provider = "anthropic".into();
+        llm.anthropic_api_key = Some("test-key".into());
+        llm.tool_retrieval = tool_retrieval;
+    }
+
+    /// The JSON events of a stream, as a client reads them.
+    async fn sse_events(stream: SseStream) -> Vec<serde_json::Value> {
+        use axum::response::IntoResponse;
+        let body = axum::body::to_bytes(Sse::new(stream).into_response().into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec())
+            .unwrap()
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str(data).ok())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_searches_and_sends_results_back() {
+        let (state, _dir) = seeded_state();
+        use_anthropic(&state, true);
+        let (seen, send) = recording_send(|request| {
+            let tools = request.tools.as_ref().unwrap();
+            if tools.rounds.is_empty() {
+                vec![
+                    StreamChunk::Token("Let me check. ".into()),
+                    search_call("toolu_1", "tokio async runtime"),
+                    StreamChunk::Done { tokens_used: 3, usage: None },
+                ]
+            } else {
+                vec![
+                    StreamChunk::Token("Tokio is an async runtime [1].".into()),
+                    StreamChunk::Done { tokens_used: 6, usage: None },
+                ]
+            }
+        });
+        let events = sse_events(stream_chat_with(&state, request("what is tokio?"), send).await).await;
+        let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(types, ["token", "tool_call", "context", "token", "done"]);
+        assert_eq!((events[1]["query"].as_str(), events[1]["topK"].as_u64(), events[1]["round"].as_u64()), (Some("tokio async runtime"), Some(2), Some(1)));
+        assert!(events[2]["context"][0]["excerpt"].as_str().unwrap().contains("Tokio"));
+        assert_eq!(events[4]["tokensUsed"], 9);
+
+        // Nothing was prefetched; the second request carries the results
+        let seen = seen.lock();
+        assert_eq!(seen.len(), 2);
+        assert!(!seen[0].messages[0].content.contains("Tokio is an async runtime"));
+        let round = &seen[1].tools.as_ref().unwrap().rounds[0];
+        assert_eq!(round.text, "Let me check. ");
+        assert!(round.results[0].content.starts_with("[1]: Tokio is an async runtime"), "{}", round.results[0].content);
+
+        // Each request is audited; the follow-up lists the chunks it sent
+        let (entries, total) = state.audit.list(&AuditQuery::default()).unwrap();
+        assert_eq!(total, 2);
+        assert_eq!((entries[0].chunk_ids.len(), entries[1].chunk_ids.len()), (1, 0));
+        assert_ne!(entries[0].prompt_hash, entries[1].prompt_hash);
+    }
+
+    #[tokio::test]
+    async fn test_tool_rounds_are_bounded() {
+        let (state, _dir) = seeded_state();
+        use_anthropic(&state, false);
+        // A model that never stops searching
+        let (seen, send) = recording_send(|request| {
+            let round = request.tools.as_ref().unwrap().rounds.len();
+            vec![
+                search_call(&format!("toolu_{}", round), if round == 0 { "tokio" } else { "harbor tide" }),
+                StreamChunk::Done { tokens_used: 1, usage: None },
+            ]
+        });
+        let mut req = request("what is tokio?");
+        req.use_tools = Some(true);
+        let Json(response) = chat_with(&state, req, send).await.unwrap();
+
+        let seen = seen.lock();
+        assert_eq!(seen.len(), MAX_TOOL_ROUNDS + 1);
+        let last = seen.last().unwrap().tools.as_ref().unwrap();
+        assert_eq!((last.rounds.len(), last.allow_calls), (MAX_TOOL_ROUNDS, false));
+        assert_eq!(response.tokens_used, Some(MAX_TOOL_ROUNDS + 1));
+        // A repeated search sends only what is new
+        let context: Vec<_> = response.context.unwrap().into_iter().map(|c| c.excerpt).collect();
+        assert_eq!(context, ["Tokio is an async runtime for Rust", "Tide tables for the harbor"]);
+        assert_eq!(last.rounds[2].results[0].content, "No new passages match this search.");
+        assert_eq!(state.audit.list(&AuditQuery::default()).unwrap().1, MAX_TOOL_ROUNDS + 1);
+    }
+
+    #[tokio::test]
+    async fn test_tools_fall_back_to_prefetched_context() {
+        let (state, _dir) = seeded_state();
+        state.llm_config.write().tool_retrieval = true;
+        // Groq has no tool support here; `useTools: false` turns them off
+        let (seen, send) = recording_send(|_| vec![StreamChunk::Done { tokens_used: 0, usage: None }]);
+        let Json(groq) = chat_with(&state, request("tokio async runtime"), send).await.unwrap();
+        assert_eq!(groq.context.map(|c| c.len()), Some(1));
+        use_anthropic(&state, true);
+        let mut req = request("tokio async runtime");
+        req.use_tools = Some(false);
+        let (seen_anthropic, send) = recording_send(|_| vec![StreamChunk::Done { tokens_used: 0, usage: None }]);
+        let Json(anthropic) = chat_with(&state, req, send).await.unwrap();
+        assert_eq!(anthropic.context.map(|c| c.len()), Some(1));
+
+        for request in [&seen.lock()[0], &seen_anthropic.lock()[0]] {
+            assert!(request.tools.is_none());
+            assert!(request.messages[0].content.contains("Tokio is an async runtime for Rust"));
+        }
+    }
+
+    fn no_send(_: LlmRequest) -> BoxedStream {
+        panic!("nothing should be sent")
+    }
+
+    #[tokio::test]
+    async fn test_anonymized_session_keeps_tokens_across_turns() {
+        let (state, _dir) = seeded_state();
+        let seen = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        // Answers with a placeholder split across tokens
+        let send = || {
+            let record = seen.clone();
+            move |request: LlmRequest| -> BoxedStream {
+                record.lock().push(request);
+                Box::pin(tokio_stream::iter(vec![
+                    StreamChunk::Token("I will write to <PII:EM".into()),
+                    StreamChunk::Token("AIL:1> today.".into()),
+                    StreamChunk::Done { tokens_used: 4, usage: None },
+                ]))
+            }
+        };
+        let turn = |message: &str, history: serde_json::Value| -> ChatRequest {
+            serde_json::from_value(serde_json::json!({
+                "message": message,
+                "conversationHistory": history,
+                "sessionId": "chat-1",
+                "anonymize": true,
+            }))
+            .unwrap()
+        };
+
+        let first = turn("Please email alice@example.com about tokio", serde_json::json!([]));
+        let Json(response) = chat_with(&state, first, send()).await.unwrap();
+        assert_eq!(response.message, "I will write to alice@example.com today.");
+
+        let history = serde_json::json!([
+            {"role": "user", "content": "Please email alice@example.com about tokio"},
+            {"role": "assistant", "content": response.message},
+        ]);
+        let second = turn("Also cc bob@example.com and alice@example.com", history);
+        let events = sse_events(stream_chat_with(&state, second, send()).await).await;
+        let streamed: String = events.iter().filter_map(|e| e["content"].as_str()).collect();
+        assert_eq!(streamed, "I will write to alice@example.com today.");
+
+        // Both outbound prompts carry the same token, never the address
+        let seen = seen.lock();
+        let prompts: Vec<String> = seen.iter().map(|r| serde_json::to_string(&r.messages).unwrap()).collect();
+        assert!(prompts.iter().all(|p| !p.contains("alice@example.com")));
+        assert!(prompts[0].contains("Please email <PII:EMAIL:1> about tokio"));
+        assert!(prompts[1].contains("Please email <PII:EMAIL:1> about tokio"));
+        assert!(prompts[1].contains("Also cc <PII:EMAIL:2> and <PII:EMAIL:1>"));
+
+        let (entries, _) = state.audit.list(&AuditQuery::default()).unwrap();
+        assert!(entries.iter().all(|e| e.anonymized));
+    }
+
+    #[tokio::test]
+    async fn test_no_provider_sends_nothing() {
+        let (state, _dir) = seeded_state();
+        state.llm_config.write().groq_api_key = None;
+        let Json(response) = chat_with(&state, request("hi"), no_send).await.unwrap();
+        assert!(response.extractive);
+        assert_eq!(state.audit.list(&AuditQuery::default()).unwrap().1, 0);
+    }
+
+    #[tokio::test]
+    async fn test_extractive_answer_quotes_context() {
+        let (state, _dir) = seeded_state();
+        let extractive = |message: &str| {
+            let mut req = request(message);
+            req.mode = ChatMode::Extractive;
+            req
+        };
+
+        // Requested while an LLM is configured
+        let Json(requested) = chat_with(&state, extractive("Is tokio an async runtime?"), no_send).await.unwrap();
+        assert!(requested.extractive);
+        assert_eq!(requested.model, "extractive");
+        assert_eq!(requested.tokens_used, Some(0));
+        let context = requested.context.clone().unwrap();
+        assert_eq!(context[0].excerpt, "Tokio is an async runtime for Rust");
+        assert!(
+            requested.message.ends_with("\n\n> Tokio is an async runtime for Rust [1]"),
+            "{}",
+            requested.message
+        );
+
+        // Same answer as the fallback without an LLM
+        state.llm_config.write().groq_api_key = None;
+        let Json(fallback) = chat_with(&state, request("Is tokio an async runtime?"), no_send).await.unwrap();
+        assert_eq!(fallback.message, requested.message);
+        assert_eq!(fallback.model, requested.model);
+        let ids = |r: &ChatResponse| r.context.iter().flatten().map(|c| c.id).collect::<Vec<_>>();
+        assert_eq!(ids(&fallback), ids(&requested));
+        assert_eq!(state.audit.list(&AuditQuery::default()).unwrap().1, 0);
+
+        // The stream carries the same answer in the LLM event shapes
+        let events = extractive_events("Is tokio an async runtime?", context, Instant::now());
+        assert!(matches!(&events[0], StreamEvent::Context { context } if context.len() == 1));
+        let streamed: String = events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::Token { content } => Some(content.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(streamed, requested.message);
+        assert!(matches!(events.last(), Some(StreamEvent::Done { model, tokens_used: 0, .. }) if model == "extractive"));
+        let sse: Vec<_> = stream_chat_with(&state, request("Is tokio an async runtime?"), no_send).await.collect().await;
+        assert_eq!(sse.len(), events.len() + 1);
+
+        let Json(unmatched) = chat_with(&state, extractive("zebra migration"), no_send).await.unwrap();
+        assert_eq!(unmatched.message, "No passages in your documents match the question.");
+        assert!(unmatched.context.is_none());
+    }
+}
@@ -0,0 +1,355 @@
+//! Chunk reading routes — a chunk, its neighbours, its section, and the
+//! section outline of a document, for readers that expand around a hit.
+//!
+//! Every response carries the chunk's document (`ChunkDocument`) so a UI
+//! can title it without a second request.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi};
+
+use crate::error::{ApiError, ApiResult, ErrorBody};
+use crate::state::AppState;
+use mindsage_api_types::{
+    ChunkContextResponse, ChunkDocument, ChunkParentResponse, ChunkResponse, DocumentOutlineResponse, OutlineSection,
+};
+use mindsage_store::{Chunk, Document, SqliteStore};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/vector-store/chunks/{id}", get(get_chunk))
+        .route("/vector-store/chunks/{id}/context", get(get_chunk_context))
+        .route("/vector-store/chunks/{id}/parent", get(get_chunk_parent))
+        .route("/vector-store/documents/{id}/outline", get(get_document_outline))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_chunk, get_chunk_context, get_chunk_parent, get_document_outline))]
+pub struct ChunksApi;
+
+/// Default and largest `window` of the context endpoint.
+const DEFAULT_WINDOW: usize = 2;
+const MAX_WINDOW: usize = 20;
+
+/// GET /api/vector-store/chunks/:id — one chunk with its metadata.
+#[utoipa::path(
+    get,
+    path = "/vector-store/chunks/{id}",
+    tag = "vector-store",
+    responses(
+        (status = 200, body = ChunkResponse),
+        (status = 404, description = "Chunk not found", body = ErrorBody),
+    )
+)]
+async fn get_chunk(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> ApiResult<Json<ChunkResponse>> {
+    state
+        .db(move |store| {
+            let chunk = find_chunk(store, id)?;
+            let document = chunk_document(store, chunk.doc_id)?;
+            Ok(Json(ChunkResponse { chunk, document }))
+        })
+        .await
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ContextQuery {
+    /// Chunks of the same level on each side (default 2, at most 20).
+    window: Option<usize>,
+}
+
+/// GET /api/vector-store/chunks/:id/context — the chunk with its
+/// neighbouring chunks of the same level, in document order.
+#[utoipa::path(
+    get,
+    path = "/vector-store/chunks/{id}/context",
+    tag = "vector-store",
+    params(ContextQuery),
+    responses(
+        (status = 200, body = ChunkContextResponse),
+        (status = 404, description = "Chunk not found", body = ErrorBody),
+    )
+)]
+async fn get_chunk_context(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(query): Query<ContextQuery>,
+) -> ApiResult<Json<ChunkContextResponse>> {
+    let window = query.window.unwrap_or(DEFAULT_WINDOW).min(MAX_WINDOW);
+    state
+        .db(move |store| {
+            let chunk = find_chunk(store, id)?;
+            let chunks = store.get_surrounding_chunks(id, window as i32)?;
+            let document = chunk_document(store, chunk.doc_id)?;
+            Ok(Json(ChunkContextResponse {
+                chunk_id: id,
+                window,
+                chunks,
+                document,
+            }))
+        })
+        .await
+}
+
+/// GET /api/vector-store/chunks/:id/parent — the section containing a
+/// paragraph chunk; `parent` is null for sections and for documents
+/// stored without sections.
+#[utoipa::path(
+    get,
+    path = "/vector-store/chunks/{id}/parent",
+    tag = "vector-store",
+    responses(
+        (status = 200, body = ChunkParentResponse),
+        (status = 404, description = "Chunk not found", body = ErrorBody),
+    )
+)]
+async fn get_chunk_parent(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<ChunkParentResponse>> {
+    state
+        .db(move |store| {
+            let chunk = find_chunk(store, id)?;
+            let parent = match chunk.parent_chunk_id {
+                Some(parent_id) => store.get_chunk(parent_id)?,
+                None => None,
+            };
+            let document = chunk_document(store, chunk.doc_id)?;
+            Ok(Json(ChunkParentResponse {
+                chunk_id: id,
+                parent,
+                document,
+            }))
+        })
+        .await
+}
+
+/// GET /api/vector-store/documents/:id/outline — the document's sections
+/// as a tree by heading level, with character offsets.
+#[utoipa::path(
+    get,
+    path = "/vector-store/documents/{id}/outline",
+    tag = "vector-store",
+    responses(
+        (status = 200, body = DocumentOutlineResponse),
+        (status = 404, description = "Document not found", body = ErrorBody),
+    )
+)]
+async fn get_document_outline(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<DocumentOutlineResponse>> {
+    let (document, chunks) = state
+        .db(move |store| -> ApiResult<_> { Ok((chunk_document(store, id)?, store.get_chunks_for_document(id)?)) })
+        .await?;
+    let sections = build_outline(&chunks);
+    Ok(Json(DocumentOutlineResponse {
+        document,
+        total_sections: chunks.iter().filter(|c| c.level == 0).count(),
+        sections,
+    }))
+}
+
+fn find_chunk(store: &SqliteStore, id: i64) -> ApiResult<Chunk> {
+    store
+        .get_chunk(id)?
+        .ok_or_else(|| ApiError::not_found("Chunk not found"))
+}
+
+fn chunk_document(store: &SqliteStore, doc_id: i64) -> ApiResult<ChunkDocument> {
+    let doc = store
+        .get_document(doc_id)?
+        .ok_or_else(|| ApiError::not_found("Document not found"))?;
+    Ok(to_chunk_document(doc))
+}
+
+fn to_chunk_document(doc: Document) -> ChunkDocument {
+    let field = |key: &str| {
+        doc.metadata
+            .as_ref()
+            .and_then(|m| m.get(key))
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+    };
+    let filename = field("filename");
+    ChunkDocument {
+        id: doc.id,
+        title: field("title").or_else(|| filename.clone()),
+        filename,
+        source: field("source"),
+        created_at: doc.created_at,
+        metadata: doc.metadata,
+    }
+}
+
+/// The Markdown heading a section starts with: (level, title).
+fn section_heading(text: &str) -> Option<(u8, String)> {
+    let line = text.lines().next()?.trim();
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let title = line[level..].trim().trim_end_matches('#').trim();
+    (!title.is_empty() && line[level..].starts_with(char::is_whitespace)).then(|| (level as u8, title.to_string()))
+}
+
+/// Nest the level 0 chunks of a document by heading level: a section goes
+/// under the closest earlier section with a shallower heading. Sections
+/// without a heading go under the section before them.
+fn build_outline(chunks: &[Chunk]) -> Vec<OutlineSection> {
+    let mut paragraphs: HashMap<i64, usize> = HashMap::new();
+    for parent in chunks.iter().filter(|c| c.level == 1).filter_map(|c| c.parent_chunk_id) {
+        *paragraphs.entry(parent).or_default() += 1;
+    }
+
+    let mut sections: Vec<&Chunk> = chunks.iter().filter(|c| c.level == 0).collect();
+    sections.sort_by_key(|c| c.chunk_index);
+
+    // Open sections with their depth; a headingless section is deeper than
+    // any heading so it never takes children of its own
+    let mut roots = Vec::new();
+    let mut stack: Vec<(u8, OutlineSection)> = Vec::new();
+    for chunk in sections {
+        let heading = section_heading(&chunk.text);
+        let depth = heading.as_ref().map_or(u8::MAX, |(level, _)| *level);
+        let section = OutlineSection {
+            chunk_id: chunk.id,
+            chunk_index: chunk.chunk_index,
+            heading_level: heading.as_ref().map(|(level, _)| *level),
+            title: heading.map(|(_, title)| title),
+            char_start: chunk.char_start,
+            char_end: chunk.char_end,
+            paragraphs: paragraphs.get(&chunk.id).copied().unwrap_or(0),
+            children: Vec::new(),
+        };
+        while stack.last().is_some_and(|(open, _)| *open >= depth) {
+            close_section(&mut stack, &mut roots);
+        }
+        stack.push((depth, section));
+    }
+    while !stack.is_empty() {
+        close_section(&mut stack, &mut roots);
+    }
+    roots
+}
+
+fn close_section(stack: &mut Vec<(u8, OutlineSection)>, roots: &mut Vec<OutlineSection>) {
+    let Some((_, section)) = stack.pop() else {
+        return;
+    };
+    match stack.last_mut() {
+        Some((_, parent)) => parent.children.push(section),
+        None => roots.push(section),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use mindsage_ingest::Ingester;
+    use tower::ServiceExt;
+
+    use crate::profiles::Profiles;
+    use crate::test_support::test_state;
+
+    /// A Markdown guide long enough to be chunked into sections.
+    fn guide() -> String {
+        let para = |topic: &str, n: usize| {
+            format!(
+                "{} note {}: the sourdough starter needs feeding with equal parts flour and water every twelve hours, \
+                 kept somewhere warm, and discarded down to a spoonful before each feed so it stays lively.",
+                topic, n
+            )
+        };
+        let body = |topic: &str| (1..=4).map(|n| para(topic, n)).collect::<Vec<_>>().join("\n\n");
+        format!(
+            "# Baking\n\n{}\n\n## Starter\n\n{}\n\n### Feeding\n\n{}\n\n## Loaves\n\n{}\n",
+            body("Intro"),
+            body("Starter"),
+            body("Feeding"),
+            body("Loaves")
+        )
+    }
+
+    async fn get_json(app: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_chunk_context_parent_and_outline() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let path = state.config.data_paths.uploads.join("bread.md");
+        std::fs::write(&path, guide()).unwrap();
+        let doc_id = Ingester::new(&state.store).ingest_file(&path).unwrap().unwrap();
+        let chunks = state.store.get_chunks_for_document(doc_id).unwrap();
+        let app = crate::routes::build_app(Arc::new(Profiles::start(state.clone())));
+
+        // The outline nests sections by heading level
+        let (status, outline) = get_json(&app, &format!("/api/vector-store/documents/{}/outline", doc_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(outline["document"]["title"], "bread.md");
+        assert_eq!(outline["totalSections"], 4);
+        let root = &outline["sections"][0];
+        assert_eq!((root["title"].as_str(), root["headingLevel"].as_u64()), (Some("Baking"), Some(1)));
+        let titles = |sections: &serde_json::Value| {
+            sections.as_array().unwrap().iter().map(|s| s["title"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(titles(&root["children"]), ["Starter", "Loaves"]);
+        assert_eq!(titles(&root["children"][0]["children"]), ["Feeding"]);
+        assert!(root["paragraphs"].as_u64().unwrap() > 0);
+        let starter = &root["children"][0];
+        let start = starter["charStart"].as_i64().unwrap() as usize;
+        assert!(guide()[start..].trim_start().starts_with("## Starter"));
+
+        // A paragraph in "Feeding": the chunk, its section and its neighbours
+        let feeding = starter["children"][0]["chunkId"].as_i64().unwrap();
+        let paragraph = chunks.iter().find(|c| c.parent_chunk_id == Some(feeding)).unwrap();
+
+        let (status, body) = get_json(&app, &format!("/api/vector-store/chunks/{}", paragraph.id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["chunk"]["id"], paragraph.id);
+        assert_eq!(body["document"]["id"], doc_id);
+
+        let (_, body) = get_json(&app, &format!("/api/vector-store/chunks/{}/parent", paragraph.id)).await;
+        assert_eq!(body["parent"]["id"], feeding);
+        assert!(body["parent"]["text"].as_str().unwrap().starts_with("### Feeding"));
+        let (_, body) = get_json(&app, &format!("/api/vector-store/chunks/{}/parent", feeding)).await;
+        assert!(body["parent"].is_null());
+
+        let (_, body) = get_json(&app, &format!("/api/vector-store/chunks/{}/context?window=1", paragraph.id)).await;
+        let context = body["chunks"].as_array().unwrap();
+        assert_eq!(body["window"], 1);
+        assert!(context.iter().all(|c| c["level"] == 1));
+        let position = context.iter().position(|c| c["id"] == paragraph.id).unwrap();
+        assert!(context.len() <= 3 && position <= 1);
+        let indices: Vec<i64> = context.iter().map(|c| c["chunk_index"].as_i64().unwrap()).collect();
+        assert!(indices.windows(2).all(|w| w[0] < w[1]));
+
+        for uri in ["/api/vector-store/chunks/999999", "/api/vector-store/documents/999999/outline"] {
+            let (status, _) = get_json(&app, uri).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[test]
+    fn test_section_heading() {
+        assert_eq!(section_heading("## Starter\n\ntext"), Some((2, "Starter".to_string())));
+        assert_eq!(section_heading("# Title #"), Some((1, "Title".to_string())));
+        assert_eq!(section_heading("#hashtag"), None);
+        assert_eq!(section_heading("####### seven"), None);
+        assert_eq!(section_heading("Plain text"), None);
+    }
+}
@@ -0,0 +1,192 @@
+//! `mindsage-client` against the full router, called in-process through
+//! `ServiceTransport` (no socket).
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::Router;
+use futures::StreamExt;
+use mindsage_client::{
+    AddDocumentRequest, ChatRequest, Client, EnhancedSearchRequest, IndexingStatus, OnDuplicate, SearchRequest, ServiceTransport,
+    StreamEvent, UniversalResultType, UploadInitRequest,
+};
+use tempfile::TempDir;
+
+use crate::profiles::Profiles;
+use crate::test_support::test_state;
+
+fn client() -> (Client<ServiceTransport<Router>>, TempDir) {
+    let dir = TempDir::new().unwrap();
+    let state = test_state(&dir);
+    {
+        // Keys from the environment must not send test chats anywhere
+        let mut llm = state.llm_config.write();
+        llm.openai_api_key = None;
+        llm.anthropic_api_key = None;
+        llm.groq_api_key = None;
+    }
+    let app = super::build_app(Arc::new(Profiles::start(state)));
+    (Client::with_transport(ServiceTransport::new(app), "http://localhost"), dir)
+}
+
+#[tokio::test]
+async fn test_documents_and_search() {
+    let (client, _dir) = client();
+
+    let tokio_doc = client
+        .add_document(&AddDocumentRequest::new("Tokio is an async runtime for Rust with a work-stealing scheduler."))
+        .await
+        .unwrap();
+    assert_eq!(tokio_doc.status, "added");
+    let mut bread = AddDocumentRequest::new("Sourdough bread needs a long cold proof in the fridge.");
+    bread.metadata = Some(serde_json::json!({"source": "note"}));
+    let bread_doc = client.add_document(&bread).await.unwrap();
+
+    // Duplicate content is a 409 with the error envelope
+    let duplicate = client.add_document(&bread).await.unwrap_err();
+    assert_eq!(duplicate.status(), Some(409));
+
+    // ... or the existing document when asked for it
+    bread.on_duplicate = Some(OnDuplicate::ReturnExisting);
+    let existing = client.add_document(&bread).await.unwrap();
+    assert_eq!((existing.id, existing.status.as_str()), (bread_doc.id, "exists"));
+    let hashes = client.document_hashes(None).await.unwrap();
+    assert!(hashes.may_contain(&bread_doc.content_hash));
+    let by_hash = client.document_by_hash(&bread_doc.content_hash).await.unwrap();
+    assert_eq!(by_hash.map(|d| d.id), Some(bread_doc.id));
+    assert!(client.document_by_hash("unknown").await.unwrap().is_none());
+
+    let status = client.status().await.unwrap();
+    assert_eq!(status.status, "healthy");
+    assert_eq!(status.documents, 2);
+
+    let list = client.list_documents(1, 1).await.unwrap();
+    assert_eq!((list.total, list.total_pages, list.documents.len()), (2, 2, 1));
+    assert_eq!(list.documents[0].id, bread_doc.id);
+
+    let doc = client.get_document(bread_doc.id, true).await.unwrap();
+    assert_eq!(doc.document.metadata_map()["source"], "note");
+    assert_eq!(doc.chunks.unwrap().len() as i64, doc.chunk_summary.total);
+
+    let results = client.search(&SearchRequest::new("async runtime")).await.unwrap();
+    assert_eq!(results.search_type, "bm25");
+    assert_eq!(results.results[0].doc_id, tokio_doc.id);
+
+    let enhanced = client
+        .enhanced_search(&EnhancedSearchRequest::new("cold proof"))
+        .await
+        .unwrap();
+    assert_eq!(enhanced.results[0].doc_id, bread_doc.id);
+    assert!(enhanced.results[0].passage.is_some());
+
+    let universal = client.universal_search("cold proof", 5).await.unwrap();
+    assert_eq!(universal.results[0].result_type, UniversalResultType::Document);
+    assert_eq!(universal.results[0].doc_id, Some(bread_doc.id));
+    assert!(!universal.partial);
+
+    let deleted = client.delete_document(tokio_doc.id).await.unwrap();
+    assert!(deleted.deleted);
+    let missing = client.get_document(tokio_doc.id, false).await.unwrap_err();
+    assert_eq!(missing.status(), Some(404));
+}
+
+#[tokio::test]
+async fn test_upload_and_wait_for_job() {
+    let (client, _dir) = client();
+
+    let upload = client
+        .upload_file("harbor.md", "# Harbor\n\nTide tables for the harbor mouth, updated weekly.")
+        .await
+        .unwrap();
+    assert_eq!((upload.uploaded, upload.errors), (1, 0));
+    let job_id = &upload.files[0].job_id;
+
+    let job = client
+        .wait_for_job(job_id, Duration::from_millis(20), Duration::from_secs(10))
+        .await
+        .unwrap();
+    assert_eq!(job.status, IndexingStatus::Completed);
+    assert!(job.document_id.is_some());
+
+    let jobs = client.indexing_jobs().await.unwrap();
+    assert!(jobs.jobs.iter().any(|j| &j.id == job_id));
+    let status = client.indexing_status().await.unwrap();
+    assert_eq!(status.completed, 1);
+
+    let results = client.search(&SearchRequest::new("tide tables")).await.unwrap();
+    assert_eq!(Some(results.results[0].doc_id), job.document_id);
+
+    let unknown = client.indexing_job("no-such-job").await.unwrap_err();
+    assert_eq!(unknown.status(), Some(404));
+}
+
+#[tokio::test]
+async fn test_resumable_upload_out_of_order() {
+    let (client, _dir) = client();
+    let content: Vec<u8> = "Ferry timetable for the winter crossing.\n".repeat(4000).into_bytes();
+    let session = client
+        .init_upload(&UploadInitRequest {
+            filename: "ferry.md".to_string(),
+            size: content.len() as u64,
+            sha256: None,
+            chunk_size: Some(64 * 1024),
+        })
+        .await
+        .unwrap();
+    assert_eq!(session.total_chunks, 3);
+    let chunks: Vec<&[u8]> = content.chunks(session.chunk_size as usize).collect();
+    let id = &session.upload_id;
+
+    // The last chunk first, then the first one twice (a retried request)
+    client.upload_chunk(id, 2, chunks[2].to_vec()).await.unwrap();
+    client.upload_chunk(id, 0, chunks[0].to_vec()).await.unwrap();
+    client.upload_chunk(id, 0, chunks[0].to_vec()).await.unwrap();
+
+    let incomplete = client.complete_upload(id).await.unwrap_err();
+    assert_eq!(incomplete.status(), Some(409));
+
+    // After a dropped connection the status says what is left to send
+    let status = client.upload_status(id).await.unwrap();
+    assert_eq!(status.missing_chunks, [1]);
+    assert_eq!(status.received_ranges.len(), 2);
+    for index in status.missing_chunks {
+        client.upload_chunk(id, index, chunks[index as usize].to_vec()).await.unwrap();
+    }
+
+    let uploaded = client.complete_upload(id).await.unwrap();
+    assert_eq!((uploaded.filename.as_str(), uploaded.size), ("ferry.md", content.len()));
+    let job = client
+        .wait_for_job(&uploaded.job_id, Duration::from_millis(20), Duration::from_secs(10))
+        .await
+        .unwrap();
+    assert_eq!(job.status, IndexingStatus::Completed);
+    assert_eq!(client.upload_status(id).await.unwrap_err().status(), Some(404));
+}
+
+#[tokio::test]
+async fn test_chat_and_connector_status() {
+    let (client, _dir) = client();
+
+    let status = client.chat_status().await.unwrap();
+    assert!(!status.llm_available);
+
+    // Without a provider the answer is extractive, in the usual events
+    let events: Vec<StreamEvent> = client
+        .chat_stream(&ChatRequest::new("hello"))
+        .await
+        .unwrap()
+        .map(|e| e.unwrap())
+        .collect()
+        .await;
+    assert_eq!(events.len(), 2);
+    assert!(matches!(&events[0], StreamEvent::Token { content } if content.starts_with("No passages")));
+    assert!(matches!(&events[1], StreamEvent::Done { model, .. } if model == "extractive"));
+    let answer = client.chat(&ChatRequest::new("hello")).await.unwrap();
+    assert!(answer.extractive);
+    assert_eq!(answer.model, "extractive");
+
+    let run = client.connector_status("notion").await.unwrap();
+    assert!(!run.running);
+    let browser = client.browser_status().await.unwrap();
+    assert!(!browser.running);
+}
@@ -1,16 +1,30 @@
 //! Connector routes — CRUD, sync, upload, exports.
 
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use axum::body::Bytes;
-use axum::extract::{Path, State};
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::Response;
 use axum::routing::{get, post, put};
 use axum::{Json, Router};
+use serde::Deserialize;
+use tokio_util::io::ReaderStream;
 use tracing::{info, warn};
+use utoipa::{IntoParams, OpenApi};
 
+use crate::error::{ApiError, ApiResult, ErrorBody};
+use crate::feeds;
+use crate::quota::{self, QuotaArea};
 use crate::state::AppState;
+use mindsage_connectors::preflight::{self, ArchiveManifest, ExportKind, ImportEstimate, PreflightResources};
 use mindsage_connectors::*;
-use mindsage_store::AddDocumentOptions;
+use mindsage_core::Event;
+use mindsage_ingest::ingest::content_hash;
+use mindsage_ingest::Ingester;
+use mindsage_store::{NewStagedItem, StagedFilter, StagedItem, UpsertOutcome};
 
 // ---------------------------------------------------------------
 // Route builder
@@ -28,7 +42,9 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/connectors/{id}/sync", post(sync_connector))
         .route("/connectors/{id}/status", get(get_status))
         .route("/connectors/{id}/stop", post(stop_sync))
+        .route("/connectors/{id}/cancel", post(cancel_import))
         // Upload
+        .route("/connectors/preflight", post(preflight_import))
         .route("/connectors/{id}/upload", post(upload_file))
         // Exports
         .route("/connectors/{id}/exports", get(list_exports))
@@ -41,17 +57,57 @@ pub fn routes() -> Router<Arc<AppState>> {
             "/connectors/{id}/pending-media",
             get(get_pending_media),
         )
+        .route("/connectors/{id}/media", get(get_media_file))
         .route("/pending-media", get(get_all_pending_media))
+        // Staged imports
+        .route("/connectors/{id}/staged", get(list_staged))
+        .route("/connectors/{id}/staged/approve", post(approve_staged))
+        .route("/connectors/{id}/staged/reject", post(reject_staged))
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(
+    list_connectors,
+    create_connector,
+    update_connector,
+    delete_connector,
+    sync_connector,
+    get_status,
+    stop_sync,
+    cancel_import,
+    preflight_import,
+    upload_file,
+    list_exports,
+    get_export_file,
+    get_pending_media,
+    get_media_file,
+    get_all_pending_media,
+    list_staged,
+    approve_staged,
+    reject_staged,
+))]
+pub struct ConnectorsApi;
+
 // ---------------------------------------------------------------
 // Handlers
 // ---------------------------------------------------------------
 
+fn connector_not_found() -> ApiError {
+    ApiError::not_found("Connector not found")
+}
+
+#[utoipa::path(get, path = "/connectors", tag = "connectors", responses((status = 200, body = Object)))]
 async fn list_connectors(State(state): State<Arc<AppState>>) -> Json<Vec<ConnectorConfig>> {
     Json(state.connector_manager.list())
 }
 
+#[utoipa::path(
+    post,
+    path = "/connectors",
+    tag = "connectors",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn create_connector(
     State(state): State<Arc<AppState>>,
     Json(req): Json<CreateConnectorRequest>,
@@ -60,47 +116,85 @@ async fn create_connector(
     Json(connector)
 }
 
+#[utoipa::path(put, path = "/connectors/{id}", tag = "connectors", responses((status = 200, body = Object)))]
 async fn update_connector(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
     Json(updates): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     match state.connector_manager.update(&id, updates) {
-        Some(connector) => Json(serde_json::to_value(connector).unwrap_or_default()),
-        None => Json(serde_json::json!({ "error": "Connector not found" })),
+        Some(connector) => Ok(Json(serde_json::to_value(connector).unwrap_or_default())),
+        None => Err(connector_not_found()),
     }
 }
 
+#[utoipa::path(delete, path = "/connectors/{id}", tag = "connectors", responses((status = 200, body = Object)))]
 async fn delete_connector(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     if state.connector_manager.delete(&id) {
-        Json(serde_json::json!({ "success": true }))
+        // Its staged items can no longer be approved
+        state
+            .db(move |store| store.delete_staged_items(&id, &StagedFilter::default()))
+            .await?;
+        Ok(Json(serde_json::json!({ "success": true })))
     } else {
-        Json(serde_json::json!({ "error": "Connector not found" }))
+        Err(connector_not_found())
     }
 }
 
+/// POST /api/connectors/:id/sync — an `rss` connector syncs its feeds in
+/// the background; follow it with `/status`.
+#[utoipa::path(
+    post,
+    path = "/connectors/{id}/sync",
+    tag = "connectors",
+    responses(
+        (status = 200, body = Object),
+        (status = 400, description = "An rss connector without feeds", body = ErrorBody),
+        (status = 409, description = "A sync of this connector is already running", body = ErrorBody),
+    )
+)]
 async fn sync_connector(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Json<serde_json::Value> {
-    let connector = match state.connector_manager.get(&id) {
-        Some(c) => c,
-        None => return Json(serde_json::json!({ "error": "Connector not found" })),
-    };
+) -> ApiResult<Json<serde_json::Value>> {
+    let connector = state
+        .connector_manager
+        .get(&id)
+        .ok_or_else(connector_not_found)?;
 
     info!("Sync requested for connector: {} ({})", connector.name, id);
 
+    if connector.connector_type == ConnectorType::Rss {
+        if connector.feed_settings().feeds.is_empty() {
+            return Err(ApiError::bad_request("The connector has no feeds"));
+        }
+        let cancel = state.connector_manager.start_run(&id).ok_or_else(|| {
+            ApiError::new(
+                StatusCode::CONFLICT,
+                "sync_running",
+                "A sync is already running for this connector",
+            )
+        })?;
+        let name = connector.name.clone();
+        tokio::spawn(feeds::run_feed_sync(state.clone(), connector, cancel));
+        return Ok(Json(serde_json::json!({
+            "success": true,
+            "message": format!("Sync started for {}", name)
+        })));
+    }
+
     // For custom/file connectors, sync is triggered by upload
     // For API connectors (Notion), we'd need the API token
-    Json(serde_json::json!({
+    Ok(Json(serde_json::json!({
         "success": true,
         "message": format!("Sync started for {}", connector.name)
-    }))
+    })))
 }
 
+#[utoipa::path(get, path = "/connectors/{id}/status", tag = "connectors", responses((status = 200, body = RunStatus)))]
 async fn get_status(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -108,6 +202,7 @@ async fn get_status(
     Json(state.connector_manager.get_run_status(&id))
 }
 
+#[utoipa::path(post, path = "/connectors/{id}/stop", tag = "connectors", responses((status = 200, body = Object)))]
 async fn stop_sync(
     State(_state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -116,18 +211,168 @@ async fn stop_sync(
     Json(serde_json::json!({ "success": true }))
 }
 
-async fn upload_file(
+/// POST /api/connectors/:id/cancel — stop a running ZIP import after the
+/// entry it is on. The upload request then fails with `Import cancelled`.
+#[utoipa::path(
+    post,
+    path = "/connectors/{id}/cancel",
+    tag = "connectors",
+    params(("id" = String, Path, description = "Connector id")),
+    responses(
+        (status = 200, description = "The import will stop", body = Object),
+        (status = 404, description = "Unknown connector", body = ErrorBody),
+        (status = 409, description = "No import is running", body = ErrorBody),
+    )
+)]
+async fn cancel_import(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    state.connector_manager.get(&id).ok_or_else(connector_not_found)?;
+    if !state.connector_manager.cancel_run(&id) {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "import_not_running",
+            "No import is running for this connector",
+        ));
+    }
+    info!("Import cancel requested for connector: {}", id);
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+#[derive(Deserialize, IntoParams)]
+struct PreflightQuery {
+    /// A file in `data/uploads/` or `data/imports/` to check instead of the
+    /// request body.
+    file: Option<String>,
+    /// `chatgpt` or `facebook`; recognized from the archive's entries when
+    /// absent.
+    #[param(value_type = Option<String>)]
+    kind: Option<ExportKind>,
+}
+
+/// POST /api/connectors/preflight — estimate what importing a ZIP export
+/// would add (items, documents, chunks, database growth, embedding time)
+/// from its table of contents, and whether it fits on the disk. The ZIP is
+/// the request body or an uploaded `file`; nothing is extracted.
+#[utoipa::path(
+    post,
+    path = "/connectors/preflight",
+    tag = "connectors",
+    params(PreflightQuery),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 200, body = Object),
+        (status = 400, description = "Neither a body nor a file", body = ErrorBody),
+        (status = 404, description = "File not found", body = ErrorBody),
+        (status = 422, description = "Not a ZIP, or not a ChatGPT or Facebook export", body = ErrorBody),
+    )
+)]
+async fn preflight_import(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<PreflightQuery>,
     body: Bytes,
-) -> Json<serde_json::Value> {
-    let connector = match state.connector_manager.get(&id) {
-        Some(c) => c,
-        None => return Json(serde_json::json!({ "error": "Connector not found" })),
+) -> ApiResult<Json<ImportEstimate>> {
+    let zip_path = match &params.file {
+        Some(name) => Some(super::files::locate_file(&state, &super::files::sanitize_filename(&state, name))?),
+        None if body.is_empty() => return Err(ApiError::bad_request("No file data received")),
+        None => None,
     };
 
+    state
+        .blocking(move |state| {
+            let manifest = match zip_path {
+                Some(path) => ArchiveManifest::read_file(&path),
+                None => ArchiveManifest::read(std::io::Cursor::new(&body), body.len() as u64),
+            }
+            .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_archive", e))?;
+            let kind = params.kind.or_else(|| ExportKind::detect(&manifest)).ok_or_else(|| {
+                ApiError::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "unknown_export",
+                    "Not a ChatGPT or Facebook export; pass `kind` to estimate it as one",
+                )
+            })?;
+
+            let stats = state.embedder.stats();
+            let busy_secs = stats.mean_latency_ms * stats.batches as f64 / 1000.0;
+            let resources = PreflightResources {
+                free_disk_bytes: preflight::available_disk_space(&state.config.data_paths.vectordb),
+                embedder_available: state.embedder.is_available(),
+                measured_texts_per_sec: (busy_secs > 0.0).then(|| stats.texts as f64 / busy_secs),
+                embedding_dim: state.config.embedding_dim,
+            };
+            Ok(Json(preflight::estimate_import(kind, &manifest, &resources)))
+        })
+        .await
+}
+
+/// Add the feeds of an uploaded OPML file to an `rss` connector's `feeds`.
+fn import_opml(
+    state: &AppState,
+    connector: &ConnectorConfig,
+    body: &[u8],
+) -> ApiResult<Json<serde_json::Value>> {
+    let invalid = |message: String| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_opml", message);
+    let text = std::str::from_utf8(body).map_err(|_| invalid("The OPML file is not UTF-8".to_string()))?;
+    let urls = rss::parse_opml(text).map_err(invalid)?;
+
+    let mut config = connector.config.clone();
+    if !config.is_object() {
+        config = serde_json::json!({});
+    }
+    let mut feeds = connector.feed_settings().feeds;
+    let before = feeds.len();
+    for url in urls {
+        if !feeds.contains(&url) {
+            feeds.push(url);
+        }
+    }
+    let added = feeds.len() - before;
+    config["feeds"] = serde_json::json!(feeds);
+    state
+        .connector_manager
+        .update(&connector.id, serde_json::json!({ "config": config }))
+        .ok_or_else(connector_not_found)?;
+    info!("Imported {} new feeds into connector {}", added, connector.id);
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "feeds": feeds,
+        "added": added,
+    })))
+}
+
+/// How often a running import reports progress to its run status and the
+/// event bus.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+#[utoipa::path(
+    post,
+    path = "/connectors/{id}/upload",
+    tag = "connectors",
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 200, body = Object),
+        (status = 409, description = "An import of this connector is already running", body = ErrorBody),
+        (status = 422, description = "The import failed or was cancelled, or the OPML file is invalid", body = ErrorBody),
+        (status = 507, description = "The upload does not fit the exports quota", body = ErrorBody),
+    )
+)]
+async fn upload_file(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> ApiResult<Json<serde_json::Value>> {
+    let connector = state
+        .connector_manager
+        .get(&id)
+        .ok_or_else(connector_not_found)?;
+
     if body.is_empty() {
-        return Json(serde_json::json!({ "error": "No file data received" }));
+        return Err(ApiError::bad_request("No file data received"));
+    }
+    if connector.connector_type == ConnectorType::Rss {
+        return import_opml(&state, &connector, &body);
     }
 
     // Determine import type from connector config
@@ -135,86 +380,162 @@ async fn upload_file(
         .config
         .get("script")
         .and_then(|s| s.as_str())
-        .unwrap_or("");
+        .unwrap_or("")
+        .to_string();
+    let import_mode = connector.import_mode();
 
     let exports_dir = state.connector_manager.exports_dir_for(&id);
+    quota::make_room(&state, QuotaArea::Exports, &exports_dir, body.len() as u64, None)?;
+
+    let cancel = state.connector_manager.start_run(&id).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::CONFLICT,
+            "import_running",
+            "An import is already running for this connector",
+        )
+    })?;
 
     // Save the uploaded ZIP to a temp file
     let temp_zip = exports_dir.join("_upload.zip");
     if let Err(e) = std::fs::write(&temp_zip, &body) {
-        return Json(serde_json::json!({
-            "error": format!("Failed to save upload: {}", e)
-        }));
+        let message = format!("Failed to save upload: {}", e);
+        state.connector_manager.mark_error(&id, &message);
+        return Err(ApiError::internal(message));
     }
 
-    let result = match script {
-        "chatgpt-import" => chatgpt::process_chatgpt_export(&temp_zip, &exports_dir),
-        "facebook-import" => facebook::process_facebook_export(&temp_zip, &exports_dir),
-        _ => ImportResult {
-            success: false,
-            item_count: 0,
-            error: Some(format!("Unknown import type: {}", script)),
-            details: None,
-        },
-    };
-
-    // Clean up temp file
-    let _ = std::fs::remove_file(&temp_zip);
+    let connector_id = id.clone();
+    let import_dir = exports_dir.clone();
+    let result = state
+        .blocking(move |state| run_import(state, &connector_id, &script, &temp_zip, &import_dir, &cancel))
+        .await;
 
     if result.success {
         // Update connector status
         state
             .connector_manager
             .mark_import_complete(&id, result.item_count);
+        state
+            .connector_manager
+            .finish_run(&id, format!("Imported {} items", result.item_count), 0);
 
-        // Auto-index exported files to vector store
-        let indexed = auto_index_exports(&state, &id, &exports_dir);
+        // Auto-index exported files to vector store, or stage them for review
+        let connector_id = id.clone();
+        let (indexed, staged) = state
+            .blocking(move |state| match import_mode {
+                ImportMode::Direct => (auto_index_exports(state, &connector_id, &exports_dir), 0),
+                ImportMode::Staged => stage_exports(state, &connector_id, &exports_dir),
+            })
+            .await;
+        state.events.publish(Event::ConnectorSync {
+            connector_id: id.clone(),
+            name: connector.name.clone(),
+            status: "completed".to_string(),
+            item_count: result.item_count,
+            indexed,
+            error: None,
+        });
 
-        Json(serde_json::json!({
+        Ok(Json(serde_json::json!({
             "success": true,
             "itemCount": result.item_count,
             "indexed": indexed,
+            "staged": staged,
             "details": result.details,
-        }))
+        })))
     } else {
-        state
-            .connector_manager
-            .mark_error(&id, result.error.as_deref().unwrap_or("Unknown error"));
+        let message = result.error.unwrap_or_else(|| "Unknown error".to_string());
+        state.connector_manager.mark_error(&id, &message);
+        state.events.publish(Event::ConnectorSync {
+            connector_id: id.clone(),
+            name: connector.name.clone(),
+            status: "failed".to_string(),
+            item_count: 0,
+            indexed: 0,
+            error: Some(message.clone()),
+        });
 
-        Json(serde_json::json!({
-            "success": false,
-            "error": result.error,
-        }))
+        let mut err = ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "import_failed", message);
+        if let Some(details) = result.details {
+            err = err.with_details(details);
+        }
+        Err(err)
     }
 }
 
-/// Auto-index connector exports into the vector store.
+/// Run a connector's ZIP processor, reporting its progress to the run
+/// status and the event bus at most every `PROGRESS_INTERVAL`. Removes the
+/// uploaded ZIP afterwards.
+fn run_import(
+    state: &AppState,
+    connector_id: &str,
+    script: &str,
+    zip_path: &std::path::Path,
+    exports_dir: &std::path::Path,
+    cancel: &AtomicBool,
+) -> ImportResult {
+    let mut last_report: Option<Instant> = None;
+    let on_progress = |progress: &ImportProgress| {
+        let done = progress.entries_scanned == progress.total_entries;
+        if !done && last_report.is_some_and(|at| at.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        last_report = Some(Instant::now());
+        state.connector_manager.report_progress(
+            connector_id,
+            format!(
+                "Scanned {}/{} entries, {} items ({})",
+                progress.entries_scanned, progress.total_entries, progress.items_emitted, progress.current_entry
+            ),
+        );
+        state.events.publish(Event::ConnectorProgress {
+            connector_id: connector_id.to_string(),
+            entries_scanned: progress.entries_scanned,
+            total_entries: progress.total_entries,
+            current_entry: progress.current_entry.clone(),
+            items_emitted: progress.items_emitted,
+        });
+    };
+
+    let result = match script {
+        "chatgpt-import" => chatgpt::process_chatgpt_export(zip_path, exports_dir, on_progress, cancel),
+        "facebook-import" => facebook::process_facebook_export(zip_path, exports_dir, on_progress, cancel),
+        _ => ImportResult {
+            success: false,
+            item_count: 0,
+            error: Some(format!("Unknown import type: {}", script)),
+            details: None,
+        },
+    };
+
+    // Clean up temp file
+    let _ = std::fs::remove_file(zip_path);
+    result
+}
+
+/// Documents built from every export file of a connector.
+fn export_documents(exports_dir: &std::path::Path) -> Vec<IndexDocument> {
+    let mut documents = chatgpt::build_index_documents(exports_dir);
+    documents.extend(facebook::build_index_documents(exports_dir));
+    documents
+}
+
+/// Auto-index connector exports into the vector store, then a document per
+/// imported media file.
 fn auto_index_exports(state: &AppState, connector_id: &str, exports_dir: &std::path::Path) -> usize {
-    let documents = chatgpt::build_index_documents(exports_dir);
+    let documents = export_documents(exports_dir);
+    let ingester = Ingester::new(&state.store);
     let mut indexed = 0;
 
-    for (text, metadata) in documents {
-        let mut meta = metadata;
-        meta.as_object_mut().map(|m| {
-            m.insert(
-                "connectorId".to_string(),
-                serde_json::Value::String(connector_id.to_string()),
-            )
-        });
-
-        match state.store.add_document(
-            &text,
-            AddDocumentOptions {
-                metadata: Some(meta),
-                ..Default::default()
-            },
-        ) {
-            Ok(_) => indexed += 1,
+    for doc in &documents {
+        match index_export_document(&ingester, connector_id, doc) {
+            Ok(Some(UpsertOutcome::Inserted(_) | UpsertOutcome::Updated(_))) => indexed += 1,
+            Ok(_) | Err(mindsage_core::Error::DuplicateContent(_)) => {}
             Err(e) => {
                 warn!("Failed to index connector document: {}", e);
             }
         }
     }
+    indexed += index_export_media(state, &ingester, connector_id, exports_dir);
 
     if indexed > 0 {
         info!(
@@ -226,6 +547,125 @@ fn auto_index_exports(state: &AppState, connector_id: &str, exports_dir: &std::p
     indexed
 }
 
+/// Index a document per imported media file and link it from the media
+/// registry. Returns how many documents were added or changed.
+fn index_export_media(
+    state: &AppState,
+    ingester: &Ingester<'_>,
+    connector_id: &str,
+    exports_dir: &std::path::Path,
+) -> usize {
+    let mut indexed = 0;
+    // Media documents are linked from the registry, duplicates included
+    let geocoder = media::Geocoder::load(&state.config.data_paths.geocoding_file);
+    let linked = media::index_media(exports_dir, geocoder.as_ref(), |doc| {
+        match index_export_document(ingester, connector_id, doc) {
+            Ok(outcome) => {
+                if !matches!(outcome, Some(UpsertOutcome::Unchanged(_))) {
+                    indexed += 1;
+                }
+                outcome.map(|o| o.doc_id())
+            }
+            Err(mindsage_core::Error::DuplicateContent(hash)) => {
+                state.store.find_document_by_hash(&hash).ok().flatten().map(|d| d.id)
+            }
+            Err(e) => {
+                warn!("Failed to index connector media: {}", e);
+                None
+            }
+        }
+    });
+    if let Err(e) = linked {
+        warn!("Failed to save media registry of connector {}: {}", connector_id, e);
+    }
+    indexed
+}
+
+/// Stage connector exports for review instead of indexing them. Items
+/// whose text is already a document are left out. Media files are still
+/// indexed directly. Returns the media documents indexed and the items
+/// staged.
+fn stage_exports(state: &AppState, connector_id: &str, exports_dir: &std::path::Path) -> (usize, usize) {
+    expire_staged_items(state);
+    let items: Vec<NewStagedItem> = export_documents(exports_dir)
+        .into_iter()
+        .filter(|doc| !matches!(state.store.find_document_by_hash(&content_hash(&doc.text)), Ok(Some(_))))
+        .map(staged_item)
+        .collect();
+    let staged = state.store.stage_items(connector_id, &items).unwrap_or_else(|e| {
+        warn!("Failed to stage items of connector {}: {}", connector_id, e);
+        0
+    });
+    if staged > 0 {
+        info!("Staged {} items from connector {} for review", staged, connector_id);
+    }
+
+    let ingester = Ingester::new(&state.store);
+    (index_export_media(state, &ingester, connector_id, exports_dir), staged)
+}
+
+/// A staging row for an export document, with topics proposed by the
+/// keyword classifier.
+fn staged_item(doc: IndexDocument) -> NewStagedItem {
+    let metadata = &doc.metadata;
+    let item_type = metadata["type"].as_str().or(metadata["source"].as_str()).unwrap_or("item");
+    let source_item_id = doc
+        .external_id
+        .clone()
+        .or_else(|| metadata["exportFile"].as_str().map(str::to_string))
+        .unwrap_or_else(|| content_hash(&doc.text));
+    NewStagedItem {
+        source_item_id,
+        item_type: item_type.to_string(),
+        proposed_topics: mindsage_ingest::extract::topics::classify_by_keywords(&doc.text).topics,
+        text: doc.text,
+        metadata: doc.metadata,
+        external_id: doc.external_id,
+        created_at: doc.created_at,
+    }
+}
+
+/// Discard staged items older than `MINDSAGE_STAGED_TTL_DAYS`.
+fn expire_staged_items(state: &AppState) {
+    let ttl_days = state.config.staged_ttl_days;
+    if ttl_days == 0 || state.config.read_only {
+        return;
+    }
+    let cutoff = chrono::Utc::now().timestamp_millis() - (ttl_days as i64) * 86_400_000;
+    if let Err(e) = state.store.expire_staged_items(cutoff) {
+        warn!("Failed to expire staged items: {}", e);
+    }
+}
+
+/// Index one export document, tagged with its connector. Chunked in the
+/// same transaction. A document with an external id is upserted, so an
+/// item that changed since the last import replaces its document; other
+/// exports seen before are `DuplicateContent`.
+pub(crate) fn index_export_document(
+    ingester: &Ingester<'_>,
+    connector_id: &str,
+    doc: &IndexDocument,
+) -> mindsage_core::Result<Option<UpsertOutcome>> {
+    let mut meta = doc.metadata.clone();
+    if let Some(m) = meta.as_object_mut() {
+        m.insert(
+            "connectorId".to_string(),
+            serde_json::Value::String(connector_id.to_string()),
+        );
+    }
+    if let Some(external_id) = &doc.external_id {
+        let source = doc.metadata["source"].as_str().unwrap_or(connector_id);
+        return ingester
+            .upsert_text(source, external_id, &doc.text, &meta, doc.created_at)
+            .map(Some);
+    }
+    let hash = content_hash(&doc.text);
+    Ok(ingester
+        .ingest_text_at(&doc.text, &hash, &meta, None, doc.created_at)?
+        .map(UpsertOutcome::Inserted))
+}
+
+#[utoipa::path(get, path = "/connectors/{id}/exports", tag = "connectors", responses((status = 200, body = Object)))]
 async fn list_exports(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -233,16 +673,29 @@ async fn list_exports(
     Json(state.connector_manager.list_exports(&id))
 }
 
+#[utoipa::path(
+    get,
+    path = "/connectors/{id}/exports/{filename}",
+    tag = "connectors",
+    responses((status = 200, body = Object))
+)]
 async fn get_export_file(
     State(state): State<Arc<AppState>>,
     Path((id, filename)): Path<(String, String)>,
-) -> Json<serde_json::Value> {
-    match state.connector_manager.read_export(&id, &filename) {
-        Some(data) => Json(data),
-        None => Json(serde_json::json!({ "error": "Export file not found" })),
-    }
+) -> ApiResult<Json<serde_json::Value>> {
+    state
+        .connector_manager
+        .read_export(&id, &filename)
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("Export file not found"))
 }
 
+#[utoipa::path(
+    get,
+    path = "/connectors/{id}/pending-media",
+    tag = "connectors",
+    responses((status = 200, body = Object))
+)]
 async fn get_pending_media(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -257,6 +710,49 @@ async fn get_pending_media(
     }
 }
 
+#[derive(Deserialize, IntoParams)]
+struct MediaQuery {
+    /// Path of the file in the connector's media directory, as in its
+    /// registry entry (usually the filename).
+    path: String,
+}
+
+/// GET /api/connectors/:id/media?path= — an imported media file, for
+/// thumbnails and previews. Only files in the connector's media registry
+/// are served.
+#[utoipa::path(
+    get,
+    path = "/connectors/{id}/media",
+    tag = "connectors",
+    params(("id" = String, Path, description = "Connector id"), MediaQuery),
+    responses(
+        (status = 200, description = "The file, with a content type from its extension", content_type = "application/octet-stream"),
+        (status = 404, description = "Unknown connector or file, or a path outside the media directory", body = ErrorBody),
+    )
+)]
+async fn get_media_file(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<MediaQuery>,
+) -> ApiResult<Response> {
+    state.connector_manager.get(&id).ok_or_else(connector_not_found)?;
+    let file_path = state
+        .connector_manager
+        .media_file(&id, &query.path)
+        .ok_or_else(|| ApiError::not_found("Media file not found"))?;
+    let file = tokio::fs::File::open(&file_path).await.map_err(mindsage_core::Error::Io)?;
+    let len = file.metadata().await.map_err(mindsage_core::Error::Io)?.len();
+    let content_type = mime_guess::from_path(&file_path).first_or_octet_stream();
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, content_type.as_ref())
+        .header(header::CONTENT_LENGTH, len)
+        .header(header::CACHE_CONTROL, "private, max-age=86400")
+        .body(Body::from_stream(ReaderStream::new(file)))
+        .map_err(|e| ApiError::internal(e.to_string()))
+}
+
+#[utoipa::path(get, path = "/pending-media", tag = "connectors", responses((status = 200, body = Object)))]
 async fn get_all_pending_media(
     State(state): State<Arc<AppState>>,
 ) -> Json<serde_json::Value> {
@@ -277,3 +773,556 @@ async fn get_all_pending_media(
         "connectors": connectors.len(),
     }))
 }
+
+// ---------------------------------------------------------------
+// Staged imports
+// ---------------------------------------------------------------
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StagedQuery {
+    page: Option<usize>,
+    #[serde(rename = "pageSize")]
+    page_size: Option<usize>,
+    /// Only items with at least this many characters.
+    #[serde(rename = "minLength")]
+    min_length: Option<usize>,
+    /// Only items of this type (`post`, `comment`, `message_thread`,
+    /// `chatgpt`).
+    #[serde(rename = "type")]
+    item_type: Option<String>,
+}
+
+/// GET /api/connectors/:id/staged — items of a staged import waiting for
+/// review, oldest first.
+#[utoipa::path(
+    get,
+    path = "/connectors/{id}/staged",
+    tag = "connectors",
+    params(("id" = String, Path, description = "Connector id"), StagedQuery),
+    responses(
+        (status = 200, body = Object),
+        (status = 404, description = "Connector not found", body = ErrorBody),
+    )
+)]
+async fn list_staged(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<StagedQuery>,
+) -> ApiResult<Json<serde_json::Value>> {
+    state.connector_manager.get(&id).ok_or_else(connector_not_found)?;
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query.page_size.unwrap_or(50).clamp(1, 500);
+    let filter = StagedFilter {
+        min_length: query.min_length,
+        item_type: query.item_type,
+        ids: None,
+    };
+    let (items, total): (Vec<StagedItem>, i64) = state
+        .blocking(move |state| {
+            expire_staged_items(state);
+            state.store.list_staged_items(&id, &filter, (page - 1) * page_size, page_size)
+        })
+        .await?;
+    Ok(Json(serde_json::json!({
+        "items": items,
+        "total": total,
+        "page": page,
+        "pageSize": page_size,
+        "totalPages": (total as usize).div_ceil(page_size),
+    })))
+}
+
+/// Staged items to approve or reject: by id, by filter, or by both (the
+/// ids that match the filter). An empty `filter` selects every item.
+#[derive(Debug, Deserialize)]
+struct StagedSelection {
+    ids: Option<Vec<i64>>,
+    filter: Option<StagedSelectionFilter>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StagedSelectionFilter {
+    min_length: Option<usize>,
+    #[serde(rename = "type")]
+    item_type: Option<String>,
+}
+
+impl StagedSelection {
+    fn into_filter(self) -> ApiResult<StagedFilter> {
+        if self.ids.is_none() && self.filter.is_none() {
+            return Err(ApiError::bad_request("Select staged items with `ids` or `filter`"));
+        }
+        let filter = self.filter.unwrap_or_default();
+        Ok(StagedFilter {
+            min_length: filter.min_length,
+            item_type: filter.item_type,
+            ids: self.ids,
+        })
+    }
+}
+
+/// POST /api/connectors/:id/staged/approve — index the selected staged
+/// items as documents, the same way a direct import would, and remove them
+/// from staging. Items that fail to index stay staged.
+#[utoipa::path(
+    post,
+    path = "/connectors/{id}/staged/approve",
+    tag = "connectors",
+    request_body = Object,
+    responses(
+        (status = 200, body = Object),
+        (status = 400, description = "Neither `ids` nor `filter`", body = ErrorBody),
+        (status = 404, description = "Connector not found", body = ErrorBody),
+    )
+)]
+async fn approve_staged(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(selection): Json<StagedSelection>,
+) -> ApiResult<Json<serde_json::Value>> {
+    state.connector_manager.get(&id).ok_or_else(connector_not_found)?;
+    let filter = selection.into_filter()?;
+    let (approved, indexed, failed) = state
+        .blocking(move |state| -> mindsage_core::Result<_> {
+            expire_staged_items(state);
+            let items = state.store.get_staged_items(&id, &filter)?;
+            let selected = items.len();
+            let ingester = Ingester::new(&state.store);
+            let (mut done, mut indexed) = (Vec::new(), 0);
+            for item in items {
+                let doc = IndexDocument {
+                    text: item.text,
+                    metadata: item.metadata,
+                    created_at: item.created_at,
+                    external_id: item.external_id,
+                };
+                match index_export_document(&ingester, &id, &doc) {
+                    Ok(outcome) => {
+                        if matches!(outcome, Some(UpsertOutcome::Inserted(_) | UpsertOutcome::Updated(_))) {
+                            indexed += 1;
+                        }
+                        done.push(item.id);
+                    }
+                    Err(mindsage_core::Error::DuplicateContent(_)) => done.push(item.id),
+                    Err(e) => warn!("Failed to index staged item {}: {}", item.id, e),
+                }
+            }
+            let failed = selected - done.len();
+            let approved = state.store.delete_staged_items(
+                &id,
+                &StagedFilter {
+                    ids: Some(done),
+                    ..Default::default()
+                },
+            )?;
+            if indexed > 0 {
+                info!("Indexed {} approved items from connector {}", indexed, id);
+            }
+            Ok((approved, indexed, failed))
+        })
+        .await?;
+    Ok(Json(serde_json::json!({
+        "approved": approved,
+        "indexed": indexed,
+        "failed": failed,
+    })))
+}
+
+/// POST /api/connectors/:id/staged/reject — discard the selected staged
+/// items.
+#[utoipa::path(
+    post,
+    path = "/connectors/{id}/staged/reject",
+    tag = "connectors",
+    request_body = Object,
+    responses(
+        (status = 200, body = Object),
+        (status = 400, description = "Neither `ids` nor `filter`", body = ErrorBody),
+        (status = 404, description = "Connector not found", body = ErrorBody),
+    )
+)]
+async fn reject_staged(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(selection): Json<StagedSelection>,
+) -> ApiResult<Json<serde_json::Value>> {
+    state.connector_manager.get(&id).ok_or_else(connector_not_found)?;
+    let filter = selection.into_filter()?;
+    let rejected = state
+        .blocking(move |state| {
+            expire_staged_items(state);
+            state.store.delete_staged_items(&id, &filter)
+        })
+        .await?;
+    Ok(Json(serde_json::json!({ "rejected": rejected })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::test_support::{TestState, test_state};
+
+    #[test]
+    fn test_imported_post_keeps_its_original_date() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+
+        state
+            .store
+            .add_document("A fresh note written today", mindsage_store::AddDocumentOptions::default())
+            .unwrap();
+
+        // 2019-06-08T13:20:00Z, as written by the Facebook import
+        let exports_dir = dir.path().join("exports");
+        std::fs::create_dir_all(&exports_dir).unwrap();
+        let post = serde_json::json!({
+            "type": "post",
+            "timestamp": 1_560_000_000,
+            "content": "Back from the lake house",
+        });
+        std::fs::write(exports_dir.join("facebook_post_1560000000.json"), post.to_string()).unwrap();
+        assert_eq!(auto_index_exports(&state, "fb", &exports_dir), 1);
+
+        let (oldest_first, _) = state.store.get_documents_paginated(1, 10, true).unwrap();
+        let texts: Vec<&str> = oldest_first.iter().map(|d| d.text.as_str()).collect();
+        assert_eq!(texts, vec!["Back from the lake house", "A fresh note written today"]);
+        assert_eq!(oldest_first[0].created_at, 1_560_000_000_000);
+
+        let months = state.store.document_month_counts(0).unwrap();
+        assert_eq!(months[0].month, "2019-06");
+        assert_eq!(months.len(), 2);
+    }
+
+    #[test]
+    fn test_reimported_conversation_replaces_its_document() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+
+        let exports_dir = dir.path().join("exports");
+        std::fs::create_dir_all(&exports_dir).unwrap();
+        let export = |messages: serde_json::Value| {
+            let conv = serde_json::json!({"id": "conv-1", "title": "Sourdough", "create_time": 1_700_000_000.0, "messages": messages});
+            std::fs::write(exports_dir.join("chatgpt_conv-1.json"), conv.to_string()).unwrap();
+        };
+        export(serde_json::json!([{"role": "user", "content": "How long should the starter rest?"}]));
+        assert_eq!(auto_index_exports(&state, "chatgpt", &exports_dir), 1);
+        let doc = state.store.find_document_by_external_id("chatgpt", "conv-1").unwrap().unwrap();
+
+        // Importing the same export again changes nothing
+        assert_eq!(auto_index_exports(&state, "chatgpt", &exports_dir), 0);
+
+        // The conversation grew: its document is replaced, not duplicated
+        export(serde_json::json!([
+            {"role": "user", "content": "How long should the starter rest?"},
+            {"role": "assistant", "content": "Overnight in the fridge, then two hours warm."},
+        ]));
+        assert_eq!(auto_index_exports(&state, "chatgpt", &exports_dir), 1);
+        assert_eq!(state.store.count_documents().unwrap(), 1);
+        let updated = state.store.get_document(doc.id).unwrap().unwrap();
+        assert!(updated.text.contains("Overnight in the fridge"));
+        assert_eq!(updated.created_at, 1_700_000_000_000);
+        let chunks = state.store.get_chunks_for_document(doc.id).unwrap();
+        assert!(chunks.iter().any(|c| c.text.contains("Overnight")));
+    }
+
+    #[tokio::test]
+    async fn test_media_documents_are_searchable_and_served() {
+        use tower::ServiceExt;
+
+        let dir = TempDir::new().unwrap();
+        // The gazetteer is loaded when the state is built
+        let gazetteer = "Lisbon,38.7223,-9.1393\nPorto,41.1579,-8.6291\n";
+        let state = Arc::new(
+            TestState::new(&dir)
+                .config(|c| std::fs::write(&c.data_paths.geocoding_file, gazetteer).unwrap())
+                .build(),
+        );
+        let connector = state.connector_manager.create(CreateConnectorRequest {
+            name: "Facebook".to_string(),
+            connector_type: ConnectorType::File,
+            config: serde_json::json!({ "script": "facebook-import" }),
+        });
+
+        let exports_dir = state.connector_manager.exports_dir_for(&connector.id);
+        let media_dir = exports_dir.join(media::MEDIA_DIR);
+        std::fs::create_dir_all(&media_dir).unwrap();
+        let stored = media_dir.join("123.jpg");
+        std::fs::write(&stored, b"\xFF\xD8 jpeg bytes").unwrap();
+        let registry = PendingMediaRegistry {
+            files: vec![PendingMediaFile {
+                original_path: "photos_and_videos/Lisbon2018_abc/123.jpg".to_string(),
+                filename: "123.jpg".to_string(),
+                media_type: "photo".to_string(),
+                extension: "jpg".to_string(),
+                size: 12,
+                context: None,
+                stored_at: String::new(),
+                stored_path: stored.to_string_lossy().to_string(),
+                info: Some(MediaInfo {
+                    description: Some("Sunset over the river".to_string()),
+                    taken_at: Some(1_531_593_005_000),
+                    latitude: Some(38.71),
+                    longitude: Some(-9.14),
+                    ..Default::default()
+                }),
+                document_id: None,
+            }],
+            ..Default::default()
+        };
+        media::save_registry(&exports_dir, &registry).unwrap();
+
+        assert_eq!(auto_index_exports(&state, &connector.id, &exports_dir), 1);
+        let registry = state.connector_manager.get_pending_media(&connector.id).unwrap();
+        let doc_id = registry.files[0].document_id.unwrap();
+        let hits = state.store.bm25_search("photos from Lisbon 2018", Some(1), 5).unwrap();
+        assert_eq!(hits[0].doc_id, doc_id);
+        let doc = state.store.get_document(doc_id).unwrap().unwrap();
+        let metadata = doc.metadata.unwrap();
+        assert_eq!(metadata["media"], true);
+        assert_eq!(metadata["storedPath"], stored.to_string_lossy().as_ref());
+        assert_eq!(doc.created_at, 1_531_593_005_000);
+        // A second import links nothing new
+        assert_eq!(auto_index_exports(&state, &connector.id, &exports_dir), 0);
+
+        let app = crate::routes::build_app(Arc::new(crate::profiles::Profiles::start(state)));
+        let get = |uri: String| {
+            app.clone()
+                .oneshot(axum::http::Request::get(uri).body(Body::empty()).unwrap())
+        };
+        let response = get(format!("/api/connectors/{}/media?path=123.jpg", connector.id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/jpeg");
+        let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+        assert_eq!(&body[..], b"\xFF\xD8 jpeg bytes");
+
+        for path in ["..%2F..%2Fconnectors.json", ".registry.json", "%2Fetc%2Fpasswd", "missing.jpg"] {
+            let response = get(format!("/api/connectors/{}/media?path={}", connector.id, path)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+        }
+        let response = get("/api/connectors/nope/media?path=123.jpg".to_string()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_staged_import_indexes_only_approved_items() {
+        use std::collections::HashSet;
+        use tower::ServiceExt;
+
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let connector = state.connector_manager.create(CreateConnectorRequest {
+            name: "Facebook".to_string(),
+            connector_type: ConnectorType::File,
+            config: serde_json::json!({ "script": "facebook-import", "importMode": "staged" }),
+        });
+        assert_eq!(connector.import_mode(), ImportMode::Staged);
+
+        let exports_dir = state.connector_manager.exports_dir_for(&connector.id);
+        let items = [
+            ("post", "Moved the sailing club meeting to Thursday evening"),
+            ("post", "Photos from the harbor festival are up"),
+            ("comment", "lol"),
+            ("comment", "Nice"),
+            ("comment", "Count me in, I will bring the spare life jackets"),
+        ];
+        for (i, (kind, content)) in items.iter().enumerate() {
+            let item = serde_json::json!({"type": kind, "timestamp": 1_600_000_000 + i as i64, "content": content});
+            std::fs::write(exports_dir.join(format!("facebook_{}_{}.json", kind, i)), item.to_string()).unwrap();
+        }
+        assert_eq!(stage_exports(&state, &connector.id, &exports_dir), (0, 5));
+        assert_eq!(state.store.count_documents().unwrap(), 0);
+        // Staging the same export again replaces its items
+        assert_eq!(stage_exports(&state, &connector.id, &exports_dir), (0, 5));
+
+        let app = crate::routes::build_app(Arc::new(crate::profiles::Profiles::start(state.clone())));
+        let call = |request: axum::http::Request<Body>| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+        let post = |path: &str, body: serde_json::Value| {
+            axum::http::Request::post(format!("/api/connectors/{}/staged/{}", connector.id, path))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let list = |query: &str| {
+            axum::http::Request::get(format!("/api/connectors/{}/staged?{}", connector.id, query))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let (status, page) = call(list("pageSize=2")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((page["total"].as_i64(), page["totalPages"].as_i64()), (Some(5), Some(3)));
+        assert_eq!(page["items"].as_array().unwrap().len(), 2);
+        let (_, posts) = call(list("type=POST")).await;
+        let export_files: HashSet<&str> = posts["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["metadata"]["exportFile"].as_str().unwrap())
+            .collect();
+        assert_eq!(export_files, HashSet::from(["facebook_post_0.json", "facebook_post_1.json"]));
+
+        // Short comments are the noise
+        let (_, short) = call(list("type=comment")).await;
+        let noise: Vec<i64> = short["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|item| item["textLength"].as_i64().unwrap() < 10)
+            .map(|item| item["id"].as_i64().unwrap())
+            .collect();
+        assert_eq!(noise.len(), 2);
+        let (_, rejected) = call(post("reject", serde_json::json!({ "ids": noise }))).await;
+        assert_eq!(rejected["rejected"], 2);
+
+        // Approve the posts by filter and nothing else
+        let (status, approved) = call(post("approve", serde_json::json!({ "filter": {"type": "post", "minLength": 10} }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((approved["approved"].as_u64(), approved["indexed"].as_u64()), (Some(2), Some(2)));
+        let (docs, total) = state.store.get_documents_paginated(1, 10, true).unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(docs[0].text, "Moved the sailing club meeting to Thursday evening");
+        assert_eq!(docs[0].created_at, 1_600_000_000_000);
+        assert_eq!(docs[0].metadata.as_ref().unwrap()["connectorId"], connector.id.as_str());
+
+        // The long comment waits for review
+        let (_, rest) = call(list("")).await;
+        assert_eq!(rest["total"], 1);
+        assert_eq!(rest["items"][0]["text"], "Count me in, I will bring the spare life jackets");
+        let (status, _) = call(post("approve", serde_json::json!({}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Approved items are not staged again by the next import
+        assert_eq!(stage_exports(&state, &connector.id, &exports_dir), (0, 3));
+        let (status, _) = call(list("")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(state.store.expire_staged_items(i64::MAX).unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_upload_reports_entry_errors_and_cancel() {
+        use std::io::Write;
+        use tower::ServiceExt;
+
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let connector = state.connector_manager.create(CreateConnectorRequest {
+            name: "Facebook".to_string(),
+            connector_type: ConnectorType::File,
+            config: serde_json::json!({ "script": "facebook-import" }),
+        });
+        let mut events = state.events.subscribe();
+
+        // A post, then a JSON entry that is not UTF-8
+        let options = zip::write::SimpleFileOptions::default();
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("posts/your_posts_1.json", options).unwrap();
+        let posts = serde_json::json!([{ "timestamp": 1_560_000_000, "data": [{ "post": "Kayaking at dawn" }] }]);
+        zip.write_all(posts.to_string().as_bytes()).unwrap();
+        zip.start_file("comments/comments.json", options).unwrap();
+        zip.write_all(b"{\"comments_v2\": \"\xFF\xFE\"}").unwrap();
+        let upload = zip.finish().unwrap().into_inner();
+
+        let app = crate::routes::build_app(Arc::new(crate::profiles::Profiles::start(state.clone())));
+        let post = |uri: String, body: Vec<u8>| {
+            app.clone()
+                .oneshot(axum::http::Request::post(uri).body(Body::from(body)).unwrap())
+        };
+        let response = post(format!("/api/connectors/{}/upload", connector.id), upload).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["itemCount"], 1);
+        assert_eq!(body["details"]["errorCount"], 1);
+        assert_eq!(body["details"]["errors"][0]["entry"], "comments/comments.json");
+
+        let status = state.connector_manager.get_run_status(&connector.id);
+        assert!(!status.running);
+        assert_eq!(status.exit_code, Some(0));
+        assert!(status.output.iter().any(|line| line.starts_with("Scanned 2/2 entries, 1 items")));
+        let progress = events.try_recv().unwrap();
+        assert!(matches!(
+            progress,
+            Event::ConnectorProgress { entries_scanned: 1, total_entries: 2, items_emitted: 1, .. }
+        ));
+
+        // Nothing to cancel once the import is over
+        let cancel_uri = format!("/api/connectors/{}/cancel", connector.id);
+        let response = post(cancel_uri.clone(), Vec::new()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        // While one runs, a second upload is refused and cancel reaches it
+        let cancel = state.connector_manager.start_run(&connector.id).unwrap();
+        let response = post(format!("/api/connectors/{}/upload", connector.id), b"zip".to_vec()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = post(cancel_uri, Vec::new()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(cancel.load(std::sync::atomic::Ordering::Relaxed));
+        let response = post("/api/connectors/nope/cancel".to_string(), Vec::new()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_preflight_estimates_body_or_uploaded_file() {
+        use std::io::Write;
+        use tower::ServiceExt;
+
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let app = crate::routes::build_app(Arc::new(crate::profiles::Profiles::start(state.clone())));
+        let post = |uri: &str, body: Vec<u8>| {
+            app.clone()
+                .oneshot(axum::http::Request::post(uri).body(Body::from(body)).unwrap())
+        };
+
+        let options = zip::write::SimpleFileOptions::default();
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("conversations.json", options).unwrap();
+        zip.write_all(&[b' '; 48 * 1024]).unwrap();
+        let export = zip.finish().unwrap().into_inner();
+
+        let response = post("/api/connectors/preflight", export.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+        let estimate: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(estimate["kind"], "chatgpt");
+        assert_eq!(estimate["items"]["conversations"], 2);
+        assert_eq!(estimate["go"], true);
+        assert!(estimate["freeDiskBytes"].as_u64().unwrap() > 0);
+        // The no-op embedder embeds nothing, so there is no time estimate
+        assert!(estimate["embeddingSecs"].is_null());
+
+        // The same archive, uploaded earlier, estimated as the other kind
+        std::fs::create_dir_all(&state.config.data_paths.uploads).unwrap();
+        std::fs::write(state.config.data_paths.uploads.join("export.zip"), &export).unwrap();
+        let response = post("/api/connectors/preflight?file=export.zip&kind=facebook", Vec::new()).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+        let estimate: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(estimate["kind"], "facebook");
+        assert_eq!(estimate["items"]["total"], 0);
+
+        let status = |uri: &'static str, body: &[u8]| {
+            let response = post(uri, body.to_vec());
+            async move { response.await.unwrap().status() }
+        };
+        assert_eq!(status("/api/connectors/preflight", b"").await, StatusCode::BAD_REQUEST);
+        assert_eq!(status("/api/connectors/preflight", b"not a zip").await, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(status("/api/connectors/preflight?file=missing.zip", b"").await, StatusCode::NOT_FOUND);
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("notes.txt", options).unwrap();
+        let other = zip.finish().unwrap().into_inner();
+        assert_eq!(status("/api/connectors/preflight", &other).await, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
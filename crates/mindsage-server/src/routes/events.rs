@@ -0,0 +1,79 @@
+//! Event stream — WebSocket push notifications from the in-process event bus.
+
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use mindsage_core::events::{ClientMessage, EventFilter};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+use utoipa::OpenApi;
+
+use crate::state::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/events", get(events_ws))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    events_ws,
+))]
+pub struct EventsApi;
+
+/// GET /api/events — upgrade to a WebSocket that streams JSON event frames.
+///
+/// Clients receive every category until they send a subscribe message:
+/// `{"type": "subscribe", "categories": ["indexing", "capture"]}`.
+#[utoipa::path(get, path = "/events", tag = "events", responses((status = 200, body = Object)))]
+async fn events_ws(
+    State(state): State<Arc<AppState>>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_socket(state, socket))
+}
+
+async fn handle_socket(state: Arc<AppState>, mut socket: WebSocket) {
+    let mut rx = state.events.subscribe();
+    let mut filter = EventFilter::default();
+
+    debug!("Event subscriber connected ({} total)", state.events.subscriber_count());
+
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Ok(event) => {
+                    if !filter.matches(&event) {
+                        continue;
+                    }
+                    if socket.send(Message::Text(event.to_frame().into())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Event subscriber lagged, dropped {} events", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    match serde_json::from_str::<ClientMessage>(text.as_str()) {
+                        Ok(ClientMessage::Subscribe { categories }) => filter.set(categories),
+                        Err(e) => debug!("Ignoring invalid event client message: {}", e),
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                Some(Ok(_)) => {}
+            },
+            _ = state.shutdown.wait() => {
+                let _ = socket.send(Message::Close(None)).await;
+                break;
+            }
+        }
+    }
+
+    debug!("Event subscriber disconnected");
+}
@@ -1,26 +1,90 @@
-//! File management routes — upload, list, delete, import.
+//! File management routes — upload, list, download, delete, import.
 //! Matches /api/files/* endpoints from Express.
 
+use std::io::SeekFrom;
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use axum::extract::{Multipart, Path, State};
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
-use axum::routing::{delete, get, post};
+use axum::body::Body;
+use axum::extract::{DefaultBodyLimit, Multipart, Path, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
 use axum::{Json, Router};
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use utoipa::{OpenApi, ToSchema};
 
-use crate::state::{AppState, IndexingJob, IndexingRequest, IndexingStatus};
+use crate::error::{ApiError, ApiResult, ErrorBody};
+use crate::quota::{self, QuotaArea};
+use crate::state::AppState;
+use crate::uploads::MAX_CHUNK_SIZE;
+use mindsage_api_types::{FileError, UploadInitRequest, UploadResponse, UploadSession, UploadedFile};
+use mindsage_core::paths::{self, PathStyle};
+
+/// Header carrying the SHA-256 of a resumable upload chunk.
+pub const CHUNK_SHA256_HEADER: &str = "x-chunk-sha256";
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/files", get(list_files))
         .route("/files/upload", post(upload_files))
+        .route("/files/upload/init", post(init_upload))
+        .route(
+            "/files/upload/{id}/chunk/{n}",
+            put(upload_chunk).layer(DefaultBodyLimit::max(MAX_CHUNK_SIZE as usize)),
+        )
+        .route("/files/upload/{id}", get(upload_status).delete(cancel_upload))
+        .route("/files/upload/{id}/complete", post(complete_upload))
         .route("/files/{filename}", delete(delete_file))
+        .route("/files/{filename}/download", get(download_file))
         .route("/files/{filename}/import", post(import_file))
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(
+    list_files,
+    upload_files,
+    init_upload,
+    upload_chunk,
+    upload_status,
+    complete_upload,
+    cancel_upload,
+    download_file,
+    delete_file,
+    import_file
+))]
+pub struct FilesApi;
+
+/// A file in `data/uploads/` or `data/imports/`.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct FileEntry {
+    pub(crate) filename: String,
+    pub(crate) path: String,
+    size: u64,
+    /// RFC 3339 modification time.
+    modified: String,
+    /// "uploads" or "imports".
+    pub(crate) location: &'static str,
+    /// Indexed and unchanged since.
+    indexed: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct FilesResponse {
+    pub(crate) files: Vec<FileEntry>,
+    total: usize,
+}
+
 /// GET /api/files — list uploaded files.
-async fn list_files(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+#[utoipa::path(get, path = "/files", tag = "files", responses((status = 200, body = FilesResponse)))]
+async fn list_files(State(state): State<Arc<AppState>>) -> Json<FilesResponse> {
+    Json(state.blocking(collect_files).await)
+}
+
+/// Files in the uploads and imports directories, newest first.
+pub(crate) fn collect_files(state: &AppState) -> FilesResponse {
     let uploads_dir = &state.config.data_paths.uploads;
     let imports_dir = &state.config.data_paths.imports;
 
@@ -36,17 +100,18 @@ async fn list_files(State(state): State<Arc<AppState>>) -> Json<serde_json::Valu
                         let file_path = entry.path().to_string_lossy().to_string();
                         let indexed = state.is_file_indexed(&file_path);
 
-                        files.push(serde_json::json!({
-                            "filename": filename,
-                            "path": file_path,
-                            "size": meta.len(),
-                            "modified": meta.modified()
+                        files.push(FileEntry {
+                            filename,
+                            path: file_path,
+                            size: meta.len(),
+                            modified: meta
+                                .modified()
                                 .ok()
                                 .map(|m| chrono::DateTime::<chrono::Utc>::from(m).to_rfc3339())
                                 .unwrap_or_default(),
-                            "location": location,
-                            "indexed": indexed,
-                        }));
+                            location,
+                            indexed,
+                        });
                     }
                 }
             }
@@ -54,23 +119,26 @@ async fn list_files(State(state): State<Arc<AppState>>) -> Json<serde_json::Valu
     }
 
     // Sort by modified time, newest first
-    files.sort_by(|a, b| {
-        let a_time = a.get("modified").and_then(|v| v.as_str()).unwrap_or("");
-        let b_time = b.get("modified").and_then(|v| v.as_str()).unwrap_or("");
-        b_time.cmp(a_time)
-    });
-
-    Json(serde_json::json!({
-        "files": files,
-        "total": files.len(),
-    }))
+    files.sort_by(|a, b| b.modified.cmp(&a.modified));
+
+    FilesResponse {
+        total: files.len(),
+        files,
+    }
 }
 
 /// POST /api/files/upload — upload files (multipart).
+#[utoipa::path(
+    post,
+    path = "/files/upload",
+    tag = "files",
+    request_body(content = Object, content_type = "multipart/form-data", description = "One file per field"),
+    responses((status = 200, description = "Files are moved to `data/imports/` and queued for indexing", body = UploadResponse))
+)]
 async fn upload_files(
     State(state): State<Arc<AppState>>,
     mut multipart: Multipart,
-) -> impl IntoResponse {
+) -> Json<UploadResponse> {
     let mut uploaded = Vec::new();
     let mut errors = Vec::new();
 
@@ -81,164 +149,411 @@ async fn upload_files(
         };
 
         // Sanitize filename
-        let safe_filename = sanitize_filename(&filename);
-        let upload_path = state.config.data_paths.uploads.join(&safe_filename);
+        let safe_filename = sanitize_filename(&state, &filename);
 
         match field.bytes().await {
             Ok(bytes) => {
-                // Handle duplicate filenames
-                let final_path = if upload_path.exists() {
-                    let stem = std::path::Path::new(&safe_filename)
-                        .file_stem()
-                        .and_then(|s| s.to_str())
-                        .unwrap_or("file");
-                    let ext = std::path::Path::new(&safe_filename)
-                        .extension()
-                        .and_then(|e| e.to_str())
-                        .unwrap_or("");
-                    let ts = chrono::Utc::now().format("%Y%m%d%H%M%S");
-                    let new_name = if ext.is_empty() {
-                        format!("{}_{}", stem, ts)
-                    } else {
-                        format!("{}_{}.{}", stem, ts, ext)
-                    };
-                    state.config.data_paths.uploads.join(new_name)
-                } else {
-                    upload_path
-                };
-
-                match std::fs::write(&final_path, &bytes) {
-                    Ok(()) => {
-                        let final_filename = final_path
-                            .file_name()
-                            .and_then(|n| n.to_str())
-                            .unwrap_or("")
-                            .to_string();
-
-                        // Auto-import: move to imports and queue indexing
-                        let import_path = state.config.data_paths.imports.join(&final_filename);
-                        if let Err(e) = std::fs::rename(&final_path, &import_path) {
-                            // If rename fails (cross-device), copy+delete
-                            if std::fs::copy(&final_path, &import_path).is_ok() {
-                                let _ = std::fs::remove_file(&final_path);
-                            } else {
-                                errors.push(serde_json::json!({
-                                    "filename": final_filename,
-                                    "error": format!("Failed to move to imports: {}", e),
-                                }));
-                                continue;
-                            }
-                        }
-
-                        // Queue for indexing
-                        let job_id = uuid::Uuid::new_v4().to_string();
-                        let import_path_str = import_path.to_string_lossy().to_string();
-
-                        let job = IndexingJob {
-                            id: job_id.clone(),
-                            filename: final_filename.clone(),
-                            file_path: import_path_str.clone(),
-                            status: IndexingStatus::Queued,
-                            document_id: None,
-                            error: None,
-                            queued_at: now_millis(),
-                            started_at: None,
-                            completed_at: None,
-                        };
-                        state.indexing_jobs.write().insert(job_id.clone(), job);
-
-                        let _ = state.indexing_tx.send(IndexingRequest {
-                            job_id: job_id.clone(),
-                            file_path: import_path_str,
-                            filename: final_filename.clone(),
-                        });
-
-                        uploaded.push(serde_json::json!({
-                            "filename": final_filename,
-                            "size": bytes.len(),
-                            "jobId": job_id,
-                        }));
-                    }
-                    Err(e) => {
-                        errors.push(serde_json::json!({
-                            "filename": safe_filename,
-                            "error": format!("Write failed: {}", e),
-                        }));
-                    }
+                // Uploads pass through to data/imports/, whose quota applies
+                let imports = &state.config.data_paths.imports;
+                if let Err(e) = quota::make_room(&state, QuotaArea::Imports, imports, bytes.len() as u64, None) {
+                    errors.push(FileError {
+                        filename: safe_filename,
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+                let final_path = unique_upload_path(&state, &safe_filename);
+                if let Err(e) = std::fs::write(&final_path, &bytes) {
+                    errors.push(FileError {
+                        filename: safe_filename,
+                        error: format!("Write failed: {}", e),
+                    });
+                    continue;
+                }
+                match import_upload(&state, &final_path, bytes.len()) {
+                    Ok(file) => uploaded.push(file),
+                    Err(error) => errors.push(error),
                 }
             }
             Err(e) => {
-                errors.push(serde_json::json!({
-                    "filename": safe_filename,
-                    "error": format!("Read failed: {}", e),
-                }));
+                errors.push(FileError {
+                    filename: safe_filename,
+                    error: format!("Read failed: {}", e),
+                });
             }
         }
     }
 
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({
-            "uploaded": uploaded.len(),
-            "errors": errors.len(),
-            "files": uploaded,
-            "errorDetails": errors,
-        })),
+    Json(UploadResponse {
+        uploaded: uploaded.len(),
+        errors: errors.len(),
+        files: uploaded,
+        error_details: errors,
+    })
+}
+
+/// A free path for `safe_filename` in `data/uploads/`: the name itself, or
+/// the name with a timestamp when it is taken.
+fn unique_upload_path(state: &AppState, safe_filename: &str) -> PathBuf {
+    let upload_path = state.config.data_paths.uploads.join(safe_filename);
+    if !upload_path.exists() {
+        return upload_path;
+    }
+    let stem = std::path::Path::new(safe_filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("file");
+    let ext = std::path::Path::new(safe_filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+    let ts = chrono::Utc::now().format("%Y%m%d%H%M%S");
+    let new_name = if ext.is_empty() {
+        format!("{}_{}", stem, ts)
+    } else {
+        format!("{}_{}.{}", stem, ts, ext)
+    };
+    state.config.data_paths.uploads.join(new_name)
+}
+
+/// Auto-import an upload: move it from `data/uploads/` to `data/imports/`
+/// and queue it for indexing.
+fn import_upload(state: &AppState, upload_path: &std::path::Path, size: usize) -> Result<UploadedFile, FileError> {
+    let final_filename = upload_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("")
+        .to_string();
+
+    let import_path = state.config.data_paths.imports.join(&final_filename);
+    if let Err(e) = std::fs::rename(upload_path, &import_path) {
+        // If rename fails (cross-device), copy+delete
+        if std::fs::copy(upload_path, &import_path).is_ok() {
+            let _ = std::fs::remove_file(upload_path);
+        } else {
+            return Err(FileError {
+                filename: final_filename,
+                error: format!("Failed to move to imports: {}", e),
+            });
+        }
+    }
+
+    // Queue for indexing
+    let job_id = state.queue_indexing(import_path.to_string_lossy().to_string(), final_filename.clone());
+
+    Ok(UploadedFile {
+        filename: final_filename,
+        size,
+        job_id,
+    })
+}
+
+/// POST /api/files/upload/init — start a resumable upload.
+#[utoipa::path(
+    post,
+    path = "/files/upload/init",
+    tag = "files",
+    request_body = UploadInitRequest,
+    responses(
+        (status = 201, description = "Send the chunks of `chunkSize` bytes next", body = UploadSession),
+        (status = 400, description = "Invalid filename or checksum", body = ErrorBody),
+        (status = 507, description = "The file would not fit the imports quota", body = ErrorBody),
+    )
+)]
+async fn init_upload(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<UploadInitRequest>,
+) -> ApiResult<(StatusCode, Json<UploadSession>)> {
+    let filename = sanitize_filename(&state, &request.filename);
+    quota::check_room(&state, QuotaArea::Imports, &state.config.data_paths.imports, request.size)?;
+    let session = state
+        .uploads
+        .create(&filename, request.size, request.chunk_size, request.sha256)?;
+    Ok((StatusCode::CREATED, Json(session)))
+}
+
+/// PUT /api/files/upload/:id/chunk/:n — store chunk `n`, checked against
+/// its `X-Chunk-Sha256`. Sending a chunk again is harmless.
+#[utoipa::path(
+    put,
+    path = "/files/upload/{id}/chunk/{n}",
+    tag = "files",
+    params(
+        ("id" = String, Path, description = "Upload id"),
+        ("n" = u64, Path, description = "Chunk index, from 0"),
+        ("X-Chunk-Sha256" = String, Header, description = "SHA-256 of the chunk, hex"),
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream", description = "The chunk's bytes"),
+    responses(
+        (status = 200, description = "Chunk stored", body = UploadSession),
+        (status = 400, description = "Chunk out of range, wrong length or no checksum", body = ErrorBody),
+        (status = 404, description = "Upload not found", body = ErrorBody),
+        (status = 422, description = "Checksum mismatch; send the chunk again", body = ErrorBody),
     )
+)]
+async fn upload_chunk(
+    State(state): State<Arc<AppState>>,
+    Path((id, index)): Path<(String, u64)>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> ApiResult<Json<UploadSession>> {
+    let sha256 = headers
+        .get(CHUNK_SHA256_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| ApiError::bad_request(format!("{} header is required", CHUNK_SHA256_HEADER)))?
+        .to_string();
+    let session = tokio::task::spawn_blocking(move || state.uploads.write_chunk(&id, index, &body, &sha256))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))??;
+    Ok(Json(session))
+}
+
+/// GET /api/files/upload/:id — the chunks received so far.
+#[utoipa::path(
+    get,
+    path = "/files/upload/{id}",
+    tag = "files",
+    params(("id" = String, Path, description = "Upload id")),
+    responses(
+        (status = 200, body = UploadSession),
+        (status = 404, description = "Upload not found or expired", body = ErrorBody),
+    )
+)]
+async fn upload_status(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<UploadSession>> {
+    Ok(Json(state.uploads.status(&id)?))
+}
+
+/// POST /api/files/upload/:id/complete — assemble the upload, move it to
+/// `data/imports/` and queue it for indexing, like `/files/upload`.
+#[utoipa::path(
+    post,
+    path = "/files/upload/{id}/complete",
+    tag = "files",
+    params(("id" = String, Path, description = "Upload id")),
+    responses(
+        (status = 200, body = UploadedFile),
+        (status = 404, description = "Upload not found", body = ErrorBody),
+        (status = 409, description = "Chunks are missing; `details.missingChunks` lists them", body = ErrorBody),
+        (status = 422, description = "The file does not match its SHA-256", body = ErrorBody),
+        (status = 507, description = "The file does not fit the imports quota", body = ErrorBody),
+    )
+)]
+async fn complete_upload(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<UploadedFile>> {
+    let uploaded = state
+        .blocking(move |state| -> ApiResult<UploadedFile> {
+            let finished = state.uploads.finish(&id)?;
+            quota::make_room(state, QuotaArea::Imports, &state.config.data_paths.imports, finished.size, None)?;
+            let upload_path = unique_upload_path(state, &finished.filename);
+            std::fs::rename(&finished.data, &upload_path).map_err(mindsage_core::Error::Io)?;
+            let result = import_upload(state, &upload_path, finished.size as usize);
+            state.uploads.remove(&id)?;
+            result.map_err(|e| ApiError::internal(e.error))
+        })
+        .await?;
+    Ok(Json(uploaded))
+}
+
+/// DELETE /api/files/upload/:id — abandon an upload.
+#[utoipa::path(
+    delete,
+    path = "/files/upload/{id}",
+    tag = "files",
+    params(("id" = String, Path, description = "Upload id")),
+    responses(
+        (status = 204, description = "Upload removed"),
+        (status = 404, description = "Upload not found", body = ErrorBody),
+    )
+)]
+async fn cancel_upload(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> ApiResult<StatusCode> {
+    if state.uploads.remove(&id)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("Upload not found"))
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct DeleteFileResponse {
+    deleted: bool,
+    filename: String,
 }
 
 /// DELETE /api/files/:filename — delete a file.
+#[utoipa::path(
+    delete,
+    path = "/files/{filename}",
+    tag = "files",
+    responses(
+        (status = 200, body = DeleteFileResponse),
+        (status = 404, description = "File not found", body = ErrorBody),
+    )
+)]
 async fn delete_file(
     State(state): State<Arc<AppState>>,
     Path(filename): Path<String>,
-) -> impl IntoResponse {
-    let safe_filename = sanitize_filename(&filename);
+) -> ApiResult<Json<DeleteFileResponse>> {
+    let safe_filename = sanitize_filename(&state, &filename);
+    let file_path = locate_file(&state, &safe_filename)?;
+
+    std::fs::remove_file(&file_path).map_err(mindsage_core::Error::Io)?;
+    Ok(Json(DeleteFileResponse {
+        deleted: true,
+        filename: safe_filename,
+    }))
+}
 
-    // Try both directories
+/// Path of `safe_filename` in `data/uploads/` or else `data/imports/`.
+/// A path that resolves outside its directory (through a symlink) is
+/// refused.
+pub(crate) fn locate_file(state: &AppState, safe_filename: &str) -> ApiResult<PathBuf> {
     for dir in [&state.config.data_paths.uploads, &state.config.data_paths.imports] {
-        let file_path = dir.join(&safe_filename);
+        let file_path = dir.join(safe_filename);
         if file_path.exists() {
             // Security: ensure path is within the directory
             if let (Ok(canonical), Ok(dir_canonical)) =
                 (file_path.canonicalize(), dir.canonicalize())
             {
                 if !canonical.starts_with(&dir_canonical) {
-                    return (
-                        StatusCode::FORBIDDEN,
-                        Json(serde_json::json!({ "error": "Path traversal not allowed" })),
-                    );
-                }
-            }
-
-            match std::fs::remove_file(&file_path) {
-                Ok(()) => {
-                    return (
-                        StatusCode::OK,
-                        Json(serde_json::json!({ "deleted": true, "filename": safe_filename })),
-                    );
-                }
-                Err(e) => {
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(serde_json::json!({ "error": e.to_string() })),
-                    );
+                    return Err(ApiError::forbidden("Path traversal not allowed"));
                 }
             }
+            return Ok(file_path);
         }
     }
+    Err(ApiError::not_found("File not found"))
+}
 
-    (
-        StatusCode::NOT_FOUND,
-        Json(serde_json::json!({ "error": "File not found" })),
+/// GET /api/files/:filename/download — the file's bytes, as an attachment.
+/// A `Range: bytes=…` header (one range) answers 206 with that part.
+#[utoipa::path(
+    get,
+    path = "/files/{filename}/download",
+    tag = "files",
+    responses(
+        (status = 200, description = "The whole file", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 206, description = "The requested byte range", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 404, description = "File not found", body = ErrorBody),
+        (status = 416, description = "Range outside the file"),
     )
+)]
+async fn download_file(
+    State(state): State<Arc<AppState>>,
+    Path(filename): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let safe_filename = sanitize_filename(&state, &filename);
+    let file_path = locate_file(&state, &safe_filename)?;
+    let mut file = tokio::fs::File::open(&file_path).await.map_err(mindsage_core::Error::Io)?;
+    let len = file.metadata().await.map_err(mindsage_core::Error::Io)?.len();
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_range(v, len));
+    let (status, start, end) = match range {
+        None => (StatusCode::OK, 0, len),
+        Some(Ok((start, end))) => (StatusCode::PARTIAL_CONTENT, start, end),
+        Some(Err(())) => {
+            let content_range = format!("bytes */{}", len);
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, [(header::CONTENT_RANGE, content_range)]).into_response());
+        }
+    };
+
+    file.seek(SeekFrom::Start(start)).await.map_err(mindsage_core::Error::Io)?;
+    let body = Body::from_stream(ReaderStream::new(file.take(end - start)));
+    let content_type = mime_guess::from_path(&safe_filename).first_or_octet_stream();
+
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type.as_ref())
+        .header(header::CONTENT_LENGTH, end - start)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_DISPOSITION, content_disposition(&safe_filename));
+    if status == StatusCode::PARTIAL_CONTENT {
+        response = response.header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end - 1, len));
+    }
+    response.body(body).map_err(|e| ApiError::internal(e.to_string()))
+}
+
+/// The byte range `[start, end)` asked for by a `Range` header on a body of
+/// `len` bytes. `None` means the header is ignored and the whole body is
+/// sent (not a byte range, or several ranges); `Some(Err)` means the range
+/// cannot be satisfied.
+fn parse_range(value: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (first, last) = spec.split_once('-')?;
+    let (first, last) = (first.trim(), last.trim());
+    let range = if first.is_empty() {
+        // Suffix range: the last N bytes
+        let suffix: u64 = last.parse().ok()?;
+        if suffix == 0 {
+            return Some(Err(()));
+        }
+        (len.saturating_sub(suffix), len)
+    } else {
+        let start: u64 = first.parse().ok()?;
+        let end = match last {
+            "" => len,
+            last => last.parse::<u64>().ok()?.saturating_add(1).min(len),
+        };
+        if start >= len || end <= start {
+            return Some(Err(()));
+        }
+        (start, end)
+    };
+    Some(Ok(range))
+}
+
+/// `Content-Disposition` for downloading `filename`: an ASCII fallback
+/// plus the exact name percent-encoded (RFC 6266).
+pub(crate) fn content_disposition(filename: &str) -> HeaderValue {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' { c } else { '_' })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    HeaderValue::from_str(&format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded))
+        .unwrap_or_else(|_| HeaderValue::from_static("attachment"))
+}
+
+#[derive(Serialize, ToSchema)]
+struct ImportFileResponse {
+    #[schema(example = "queued")]
+    status: &'static str,
+    #[serde(rename = "jobId")]
+    job_id: String,
 }
 
 /// POST /api/files/:filename/import — queue a file for indexing.
+#[utoipa::path(
+    post,
+    path = "/files/{filename}/import",
+    tag = "files",
+    responses(
+        (status = 200, body = ImportFileResponse),
+        (status = 404, description = "File not found", body = ErrorBody),
+    )
+)]
 async fn import_file(
     State(state): State<Arc<AppState>>,
     Path(filename): Path<String>,
-) -> impl IntoResponse {
-    let safe_filename = sanitize_filename(&filename);
+) -> ApiResult<Json<ImportFileResponse>> {
+    let safe_filename = sanitize_filename(&state, &filename);
 
     // Find the file
     let file_path = if state.config.data_paths.imports.join(&safe_filename).exists() {
@@ -246,62 +561,147 @@ async fn import_file(
     } else if state.config.data_paths.uploads.join(&safe_filename).exists() {
         state.config.data_paths.uploads.join(&safe_filename)
     } else {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "File not found" })),
-        );
+        return Err(ApiError::not_found("File not found"));
     };
 
-    let file_path_str = file_path.to_string_lossy().to_string();
-    let job_id = uuid::Uuid::new_v4().to_string();
-
-    let job = IndexingJob {
-        id: job_id.clone(),
-        filename: safe_filename.clone(),
-        file_path: file_path_str.clone(),
-        status: IndexingStatus::Queued,
-        document_id: None,
-        error: None,
-        queued_at: now_millis(),
-        started_at: None,
-        completed_at: None,
-    };
-    state.indexing_jobs.write().insert(job_id.clone(), job);
+    let job_id = state.queue_indexing(file_path.to_string_lossy().to_string(), safe_filename);
 
-    let _ = state.indexing_tx.send(IndexingRequest {
-        job_id: job_id.clone(),
-        file_path: file_path_str,
-        filename: safe_filename,
-    });
-
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({
-            "status": "queued",
-            "jobId": job_id,
-        })),
-    )
+    Ok(Json(ImportFileResponse {
+        status: "queued",
+        job_id,
+    }))
 }
 
-/// Sanitize a filename to prevent path traversal.
-fn sanitize_filename(name: &str) -> String {
-    // Remove directory components
-    let name = name
-        .replace('/', "")
-        .replace('\\', "")
-        .replace("..", "");
-
-    // Take just the filename part
-    std::path::Path::new(&name)
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("unnamed")
-        .to_string()
+/// Sanitize a client-supplied filename for `data/uploads/` on this
+/// platform (`data/imports/` is as long, so the cap holds there too).
+pub(crate) fn sanitize_filename(state: &AppState, name: &str) -> String {
+    paths::sanitize_filename(name, &state.config.data_paths.uploads.to_string_lossy(), PathStyle::HOST)
 }
 
-fn now_millis() -> i64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as i64
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::Request;
+    use mindsage_core::MindSageConfig;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    use crate::test_support::test_state;
+
+    fn app(dir: &TempDir) -> (Router, MindSageConfig) {
+        let state = test_state(dir);
+        let config = state.config.clone();
+        (crate::routes::build_app(Arc::new(crate::profiles::Profiles::start(state))), config)
+    }
+
+    async fn download(app: &Router, uri: &str, range: Option<&str>) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let mut request = Request::builder().uri(uri);
+        if let Some(range) = range {
+            request = request.header("range", range);
+        }
+        let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let bytes = axum::body::to_bytes(body, 1 << 20).await.unwrap();
+        (parts.status, parts.headers, bytes.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_download_with_ranges() {
+        let dir = TempDir::new().unwrap();
+        let (app, config) = app(&dir);
+        let content: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        std::fs::write(config.data_paths.uploads.join("archive.zip"), &content).unwrap();
+        let uri = "/api/files/archive.zip/download";
+
+        let (status, headers, body) = download(&app, uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, content);
+        assert_eq!(headers[header::CONTENT_TYPE], "application/zip");
+        assert_eq!(headers[header::ACCEPT_RANGES], "bytes");
+        assert!(headers[header::CONTENT_DISPOSITION].to_str().unwrap().contains("filename=\"archive.zip\""));
+
+        let (status, headers, body) = download(&app, uri, Some("bytes=100-199")).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body, content[100..200]);
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes 100-199/1000");
+        assert_eq!(headers[header::CONTENT_LENGTH], "100");
+
+        // Open-ended and suffix ranges; an end past the file is clamped
+        let (_, _, body) = download(&app, uri, Some("bytes=900-")).await;
+        assert_eq!(body, content[900..]);
+        let (_, headers, body) = download(&app, uri, Some("bytes=-10")).await;
+        assert_eq!(body, content[990..]);
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes 990-999/1000");
+        let (_, _, body) = download(&app, uri, Some("bytes=995-5000")).await;
+        assert_eq!(body, content[995..]);
+
+        let (status, headers, _) = download(&app, uri, Some("bytes=1000-")).await;
+        assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes */1000");
+        // Several ranges are answered with the whole file
+        let (status, _, body) = download(&app, uri, Some("bytes=0-1,5-6")).await;
+        assert_eq!((status, body.len()), (StatusCode::OK, 1000));
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_traversal() {
+        let dir = TempDir::new().unwrap();
+        let (app, config) = app(&dir);
+        let secret = dir.path().join("secret.txt");
+        std::fs::write(&secret, "do not serve").unwrap();
+
+        // Encoded separators and dot segments are stripped from the name
+        for uri in ["/api/files/..%2Fsecret.txt/download", "/api/files/..%5C..%5Csecret.txt/download"] {
+            let (status, _, body) = download(&app, uri, None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+            assert!(!String::from_utf8_lossy(&body).contains("do not serve"));
+        }
+
+        // A symlink out of the uploads directory is refused
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&secret, config.data_paths.uploads.join("link.txt")).unwrap();
+            let (status, _, _) = download(&app, "/api/files/link.txt/download", None).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+        }
+        let _ = config;
+    }
+
+    #[tokio::test]
+    async fn test_chunk_checksum_is_required_and_checked() {
+        let dir = TempDir::new().unwrap();
+        let (app, _config) = app(&dir);
+        let init = Request::post("/api/files/upload/init")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"filename":"../notes.txt","size":5}"#))
+            .unwrap();
+        let response = app.clone().oneshot(init).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+        let session: UploadSession = serde_json::from_slice(&body).unwrap();
+        assert_eq!(session.filename, "notes.txt");
+
+        let put = |sha256: Option<&str>| {
+            let mut request = Request::put(format!("/api/files/upload/{}/chunk/0", session.upload_id));
+            if let Some(sha256) = sha256 {
+                request = request.header(CHUNK_SHA256_HEADER, sha256);
+            }
+            app.clone().oneshot(request.body(Body::from("hello")).unwrap())
+        };
+        assert_eq!(put(None).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        let wrong = "0".repeat(64);
+        assert_eq!(put(Some(&wrong)).await.unwrap().status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let right = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(put(Some(right)).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_content_disposition_encodes_non_ascii() {
+        let value = content_disposition("Résumé \"final\".pdf");
+        assert_eq!(
+            value.to_str().unwrap(),
+            "attachment; filename=\"R_sum_ _final_.pdf\"; filename*=UTF-8''R%C3%A9sum%C3%A9%20%22final%22.pdf"
+        );
+    }
 }
@@ -2,23 +2,58 @@
 
 use std::sync::Arc;
 
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
-use axum::response::IntoResponse;
-use axum::routing::get;
+use axum::routing::{get, post};
 use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
-use crate::state::{AppState, IndexingStatus};
+use crate::error::{ApiError, ApiResult, ErrorBody};
+use crate::indexing;
+use crate::reembed::{self, ReembedJob, ReembedProgress};
+use crate::backfill_offsets::{self, BackfillJob, BackfillProgress};
+use crate::normalize_metadata::{self, NormalizeJob, NormalizeProgress};
+use crate::reextract::{self, ReextractJob, ReextractProgress};
+use crate::state::{AppState, IndexingJob, IndexingStatus};
+use mindsage_api_types::{IndexingJobsResponse, IndexingStatusResponse};
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/indexing/status", get(get_indexing_status))
         .route("/indexing/jobs", get(get_indexing_jobs))
         .route("/indexing/jobs/{job_id}", get(get_indexing_job))
+        .route("/indexing/jobs/{job_id}/retry", post(retry_indexing_job))
+        .route("/indexing/reembed", get(get_reembed).post(start_reembed))
+        .route("/indexing/re-extract", get(get_reextract).post(start_reextract))
+        .route("/indexing/backfill-offsets", get(get_backfill_offsets).post(start_backfill_offsets))
+        .route("/indexing/normalize-metadata", get(get_normalize_metadata).post(start_normalize_metadata))
 }
 
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        get_indexing_status,
+        get_indexing_jobs,
+        get_indexing_job,
+        retry_indexing_job,
+        get_reembed,
+        start_reembed,
+        get_reextract,
+        start_reextract,
+        get_backfill_offsets,
+        start_backfill_offsets,
+        get_normalize_metadata,
+        start_normalize_metadata,
+    ),
+    // Flattened into `Job<J>`, so not collected from the responses
+    components(schemas(ReembedProgress, ReextractProgress, BackfillProgress, NormalizeProgress))
+)]
+pub struct IndexingApi;
+
 /// GET /api/indexing/status — summary of indexing state.
-async fn get_indexing_status(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+#[utoipa::path(get, path = "/indexing/status", tag = "indexing", responses((status = 200, body = IndexingStatusResponse)))]
+async fn get_indexing_status(State(state): State<Arc<AppState>>) -> Json<IndexingStatusResponse> {
     let jobs = state.indexing_jobs.read();
     let queued = jobs
         .values()
@@ -37,38 +72,597 @@ async fn get_indexing_status(State(state): State<Arc<AppState>>) -> Json<serde_j
         .filter(|j| j.status == IndexingStatus::Failed)
         .count();
 
-    Json(serde_json::json!({
-        "queued": queued,
-        "processing": processing,
-        "completed": completed,
-        "failed": failed,
-        "total": jobs.len(),
-    }))
+    Json(IndexingStatusResponse {
+        queued,
+        processing,
+        completed,
+        failed,
+        total: jobs.len(),
+        watcher: state.import_watcher.status(),
+        catchup: state.catchup.progress(),
+    })
 }
 
-/// GET /api/indexing/jobs — list all jobs.
-async fn get_indexing_jobs(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
-    let jobs = state.indexing_jobs.read();
-    let mut all_jobs: Vec<&crate::state::IndexingJob> = jobs.values().collect();
-    all_jobs.sort_by(|a, b| b.queued_at.cmp(&a.queued_at));
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct JobsQuery {
+    /// Only jobs with this status.
+    #[param(inline)]
+    status: Option<IndexingStatus>,
+}
 
-    Json(serde_json::json!({
-        "jobs": all_jobs,
-        "total": all_jobs.len(),
-    }))
+/// GET /api/indexing/jobs — list all jobs, or those with one status.
+#[utoipa::path(
+    get,
+    path = "/indexing/jobs",
+    tag = "indexing",
+    params(JobsQuery),
+    responses((status = 200, body = IndexingJobsResponse))
+)]
+async fn get_indexing_jobs(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<JobsQuery>,
+) -> Json<IndexingJobsResponse> {
+    let mut jobs: Vec<IndexingJob> = state
+        .indexing_jobs
+        .read()
+        .values()
+        .filter(|j| query.status.as_ref().map_or(true, |status| &j.status == status))
+        .cloned()
+        .collect();
+    jobs.sort_by_key(|j| std::cmp::Reverse(j.queued_at));
+
+    Json(IndexingJobsResponse {
+        total: jobs.len(),
+        jobs,
+    })
 }
 
 /// GET /api/indexing/jobs/:jobId — get a single job.
+#[utoipa::path(
+    get,
+    path = "/indexing/jobs/{job_id}",
+    tag = "indexing",
+    responses(
+        (status = 200, body = IndexingJob),
+        (status = 404, description = "Job not found", body = ErrorBody),
+    )
+)]
 async fn get_indexing_job(
     State(state): State<Arc<AppState>>,
     Path(job_id): Path<String>,
-) -> impl IntoResponse {
+) -> ApiResult<Json<IndexingJob>> {
     let jobs = state.indexing_jobs.read();
     match jobs.get(&job_id) {
-        Some(job) => (StatusCode::OK, Json(serde_json::json!(job))),
-        None => (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Job not found" })),
-        ),
+        Some(job) => Ok(Json(job.clone())),
+        None => Err(ApiError::not_found("Job not found")),
+    }
+}
+
+/// POST /api/indexing/jobs/:jobId/retry — queue a failed job again with
+/// its original file.
+#[utoipa::path(
+    post,
+    path = "/indexing/jobs/{job_id}/retry",
+    tag = "indexing",
+    responses(
+        (status = 202, description = "Job queued again", body = IndexingJob),
+        (status = 404, description = "Job not found", body = ErrorBody),
+        (status = 409, description = "The job has not failed", body = ErrorBody),
+        (status = 410, description = "The job's file no longer exists", body = ErrorBody),
+    )
+)]
+async fn retry_indexing_job(
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<String>,
+) -> ApiResult<(StatusCode, Json<IndexingJob>)> {
+    let (status, file_path) = match state.indexing_jobs.read().get(&job_id) {
+        Some(job) => (job.status.clone(), job.file_path.clone()),
+        None => return Err(ApiError::not_found("Job not found")),
+    };
+    if status != IndexingStatus::Failed {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "job_not_failed",
+            format!("Only failed jobs can be retried; this one is {}", status.as_str()),
+        ));
+    }
+    if !std::path::Path::new(&file_path).exists() {
+        if let Some(job) = state.indexing_jobs.write().get_mut(&job_id) {
+            job.error = Some(indexing::FILE_MISSING_ERROR.to_string());
+        }
+        state.publish_job(&job_id);
+        return Err(ApiError::new(StatusCode::GONE, "file_missing", indexing::FILE_MISSING_ERROR));
+    }
+
+    let job = state
+        .requeue_indexing(&job_id)
+        .ok_or_else(|| ApiError::new(StatusCode::CONFLICT, "job_not_failed", "Only failed jobs can be retried"))?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ReembedParams {
+    /// Re-embed at most this many chunks in this run.
+    max_chunks: Option<usize>,
+}
+
+/// POST /api/indexing/reembed — re-embed chunks whose embedding came from
+/// a model other than the active one, as a background job.
+#[utoipa::path(
+    post,
+    path = "/indexing/reembed",
+    tag = "indexing",
+    responses(
+        (status = 202, description = "Job started", body = Object),
+        (status = 200, description = "Nothing to re-embed", body = Object),
+        (status = 409, description = "A re-embed job is already running", body = ErrorBody),
+        (status = 503, description = "No embedding model loaded", body = ErrorBody),
+    )
+)]
+async fn start_reembed(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ReembedParams>,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    if !state.embedder.is_available() {
+        return Err(ApiError::service_unavailable(
+            "No embedding model loaded; re-embedding needs one",
+        ));
+    }
+
+    let stale = state.db(|store| store.count_stale_embeddings()).await? as usize;
+    let total = params.max_chunks.map_or(stale, |m| m.min(stale));
+    let model_id = state.embedder.model_id().to_string();
+    if total == 0 {
+        return Ok((
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "started": false,
+                "staleEmbeddings": stale,
+                "modelId": model_id,
+            })),
+        ));
+    }
+
+    let job = state.reembed.start(total, ReembedProgress::new(&model_id)).ok_or_else(|| {
+        ApiError::new(StatusCode::CONFLICT, "reembed_running", "A re-embed job is already running")
+    })?;
+    let job_state = state.clone();
+    tokio::task::spawn_blocking(move || reembed::run_reembed(&job_state));
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "started": true,
+            "staleEmbeddings": stale,
+            "job": job,
+        })),
+    ))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ReembedStatusResponse {
+    job: Option<ReembedJob>,
+    /// Embeddings from a model other than the active one.
+    stale_embeddings: i64,
+    model_id: String,
+}
+
+/// GET /api/indexing/reembed — progress of the current or last re-embed job.
+#[utoipa::path(get, path = "/indexing/reembed", tag = "indexing", responses((status = 200, body = ReembedStatusResponse)))]
+async fn get_reembed(State(state): State<Arc<AppState>>) -> ApiResult<Json<ReembedStatusResponse>> {
+    let stale = state.db(|store| store.count_stale_embeddings()).await?;
+    Ok(Json(ReembedStatusResponse {
+        job: state.reembed.current(),
+        stale_embeddings: stale,
+        model_id: state.embedder.model_id().to_string(),
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ReextractParams {
+    /// Re-extract chunks extracted by a version below this one; defaults
+    /// to the current extractor version.
+    min_version: Option<u32>,
+    /// Re-extract at most this many chunks in this run.
+    max_chunks: Option<usize>,
+}
+
+/// POST /api/indexing/re-extract — re-run heuristic extraction for chunks
+/// enriched by an older extractor version, as a background job.
+#[utoipa::path(
+    post,
+    path = "/indexing/re-extract",
+    tag = "indexing",
+    responses(
+        (status = 202, description = "Job started", body = Object),
+        (status = 200, description = "Nothing to re-extract", body = Object),
+        (status = 409, description = "A re-extract job is already running", body = ErrorBody),
+    )
+)]
+async fn start_reextract(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ReextractParams>,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    let current = mindsage_ingest::CURRENT_EXTRACTION_VERSION;
+    let min_version = params.min_version.unwrap_or(current);
+    if min_version > current {
+        return Err(ApiError::bad_request(format!(
+            "min_version {} is above the current extraction version {}",
+            min_version, current
+        )));
+    }
+
+    let outdated = state.db(move |store| store.count_outdated_extractions(min_version)).await? as usize;
+    let total = params.max_chunks.map_or(outdated, |m| m.min(outdated));
+    if total == 0 {
+        return Ok((
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "started": false,
+                "outdatedChunks": outdated,
+                "extractionVersion": current,
+            })),
+        ));
+    }
+
+    let job = state.reextract.start(total, ReextractProgress::new(min_version)).ok_or_else(|| {
+        ApiError::new(StatusCode::CONFLICT, "reextract_running", "A re-extract job is already running")
+    })?;
+    let job_state = state.clone();
+    tokio::task::spawn_blocking(move || reextract::run_reextract(&job_state));
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "started": true,
+            "outdatedChunks": outdated,
+            "job": job,
+        })),
+    ))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ReextractStatusResponse {
+    job: Option<ReextractJob>,
+    /// Chunks enriched by an older extractor version.
+    outdated_chunks: i64,
+    extraction_version: u32,
+}
+
+/// GET /api/indexing/re-extract — progress of the current or last re-extract job.
+#[utoipa::path(get, path = "/indexing/re-extract", tag = "indexing", responses((status = 200, body = ReextractStatusResponse)))]
+async fn get_reextract(State(state): State<Arc<AppState>>) -> ApiResult<Json<ReextractStatusResponse>> {
+    let current = mindsage_ingest::CURRENT_EXTRACTION_VERSION;
+    let outdated = state.db(move |store| store.count_outdated_extractions(current)).await?;
+    Ok(Json(ReextractStatusResponse {
+        job: state.reextract.current(),
+        outdated_chunks: outdated,
+        extraction_version: current,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BackfillOffsetsParams {
+    /// Locate at most this many chunks in this run.
+    max_chunks: Option<usize>,
+}
+
+/// POST /api/indexing/backfill-offsets — find the char offsets of chunks
+/// stored without them in their documents, as a background job.
+#[utoipa::path(
+    post,
+    path = "/indexing/backfill-offsets",
+    tag = "indexing",
+    responses(
+        (status = 202, description = "Job started", body = Object),
+        (status = 200, description = "No chunk is missing offsets", body = Object),
+        (status = 409, description = "A backfill job is already running", body = ErrorBody),
+    )
+)]
+async fn start_backfill_offsets(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<BackfillOffsetsParams>,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    let missing = state.db(|store| store.count_chunks_without_offsets()).await? as usize;
+    let total = params.max_chunks.map_or(missing, |m| m.min(missing));
+    if total == 0 {
+        return Ok((
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "started": false,
+                "missingOffsets": missing,
+            })),
+        ));
+    }
+
+    let job = state.offset_backfill.start(total, BackfillProgress::default()).ok_or_else(|| {
+        ApiError::new(StatusCode::CONFLICT, "backfill_running", "An offset backfill job is already running")
+    })?;
+    let job_state = state.clone();
+    tokio::task::spawn_blocking(move || backfill_offsets::run_backfill(&job_state));
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "started": true,
+            "missingOffsets": missing,
+            "job": job,
+        })),
+    ))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct BackfillOffsetsStatusResponse {
+    job: Option<BackfillJob>,
+    /// Chunks still without char offsets, unlocatable ones included.
+    missing_offsets: i64,
+}
+
+/// GET /api/indexing/backfill-offsets — progress of the current or last offset backfill.
+#[utoipa::path(
+    get,
+    path = "/indexing/backfill-offsets",
+    tag = "indexing",
+    responses((status = 200, body = BackfillOffsetsStatusResponse))
+)]
+async fn get_backfill_offsets(State(state): State<Arc<AppState>>) -> ApiResult<Json<BackfillOffsetsStatusResponse>> {
+    let missing = state.db(|store| store.count_chunks_without_offsets()).await?;
+    Ok(Json(BackfillOffsetsStatusResponse {
+        job: state.offset_backfill.current(),
+        missing_offsets: missing,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct NormalizeMetadataParams {
+    /// Normalize at most this many documents in this run.
+    max_documents: Option<usize>,
+}
+
+/// POST /api/indexing/normalize-metadata — bring the metadata of documents
+/// written before the current normalization rules up to date, as a
+/// background job.
+#[utoipa::path(
+    post,
+    path = "/indexing/normalize-metadata",
+    tag = "indexing",
+    params(NormalizeMetadataParams),
+    responses(
+        (status = 202, description = "Job started", body = Object),
+        (status = 200, description = "All metadata is current", body = Object),
+        (status = 409, description = "A normalization job is already running", body = ErrorBody),
+    )
+)]
+async fn start_normalize_metadata(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<NormalizeMetadataParams>,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    let stale = state.db(|store| store.count_documents_with_stale_metadata()).await? as usize;
+    let total = params.max_documents.map_or(stale, |m| m.min(stale));
+    if total == 0 {
+        return Ok((
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "started": false,
+                "staleDocuments": stale,
+            })),
+        ));
+    }
+
+    let job = state.metadata_normalization.start(total, NormalizeProgress::default()).ok_or_else(|| {
+        ApiError::new(StatusCode::CONFLICT, "normalization_running", "A metadata normalization job is already running")
+    })?;
+    let job_state = state.clone();
+    tokio::task::spawn_blocking(move || normalize_metadata::run_normalization(&job_state));
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "started": true,
+            "staleDocuments": stale,
+            "job": job,
+        })),
+    ))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct NormalizeMetadataStatusResponse {
+    job: Option<NormalizeJob>,
+    /// Documents whose metadata predates the current rules.
+    stale_documents: i64,
+}
+
+/// GET /api/indexing/normalize-metadata — progress and report of the
+/// current or last metadata normalization.
+#[utoipa::path(
+    get,
+    path = "/indexing/normalize-metadata",
+    tag = "indexing",
+    responses((status = 200, body = NormalizeMetadataStatusResponse))
+)]
+async fn get_normalize_metadata(State(state): State<Arc<AppState>>) -> ApiResult<Json<NormalizeMetadataStatusResponse>> {
+    let stale = state.db(|store| store.count_documents_with_stale_metadata()).await?;
+    Ok(Json(NormalizeMetadataStatusResponse {
+        job: state.metadata_normalization.current(),
+        stale_documents: stale,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use crate::profiles::Profiles;
+    use crate::test_support::test_state;
+
+    async fn send(app: &Router, method: &str, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    fn fail(state: &AppState, job_id: &str) {
+        let mut jobs = state.indexing_jobs.write();
+        let job = jobs.get_mut(job_id).unwrap();
+        job.status = IndexingStatus::Failed;
+        job.error = Some("Ingest error: extractor panicked".into());
+        job.attempts = 1;
+    }
+
+    #[tokio::test]
+    async fn test_retry_failed_job_and_filter_by_status() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let mut rx = state.take_indexing_rx().unwrap();
+        let app = crate::routes::build_app(Arc::new(Profiles::start(state.clone())));
+
+        let kept = state.config.data_paths.uploads.join("kept.txt");
+        let gone = state.config.data_paths.uploads.join("gone.txt");
+        std::fs::write(&kept, "kept").unwrap();
+        std::fs::write(&gone, "gone").unwrap();
+        let kept_id = state.queue_indexing(kept.to_string_lossy().to_string(), "kept.txt".into());
+        let gone_id = state.queue_indexing(gone.to_string_lossy().to_string(), "gone.txt".into());
+        let queued_id = state.queue_indexing(kept.to_string_lossy().to_string(), "kept.txt".into());
+        fail(&state, &kept_id);
+        fail(&state, &gone_id);
+        while rx.try_recv().is_ok() {}
+
+        let (status, body) = send(&app, "GET", "/api/indexing/jobs?status=failed").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total"], 2);
+        let (_, body) = send(&app, "GET", "/api/indexing/jobs?status=queued").await;
+        assert_eq!(body["jobs"][0]["id"], queued_id.as_str());
+        let (status, _) = send(&app, "GET", "/api/indexing/jobs?status=stuck").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // A failed job goes back to the worker with its id, file and attempts
+        let (status, body) = send(&app, "POST", &format!("/api/indexing/jobs/{}/retry", kept_id)).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body["status"], "queued");
+        assert_eq!(body["attempts"], 1);
+        assert!(body.get("error").is_none());
+        let request = rx.try_recv().unwrap();
+        assert_eq!(request.job_id, kept_id);
+        assert_eq!(request.file_path, kept.to_string_lossy());
+        assert_eq!(request.retries, 0);
+
+        // Only failed jobs can be retried
+        let (status, body) = send(&app, "POST", &format!("/api/indexing/jobs/{}/retry", queued_id)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "job_not_failed");
+        let (status, _) = send(&app, "POST", "/api/indexing/jobs/nope/retry").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // A job whose file is gone stays failed, with the reason
+        std::fs::remove_file(&gone).unwrap();
+        let (status, body) = send(&app, "POST", &format!("/api/indexing/jobs/{}/retry", gone_id)).await;
+        assert_eq!(status, StatusCode::GONE);
+        assert_eq!(body["code"], "file_missing");
+        let job = state.indexing_jobs.read()[&gone_id].clone();
+        assert_eq!(job.status, IndexingStatus::Failed);
+        assert_eq!(job.error.as_deref(), Some(indexing::FILE_MISSING_ERROR));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_normalize_metadata_route() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let app = crate::routes::build_app(Arc::new(Profiles::start(state.clone())));
+
+        let (status, body) = send(&app, "POST", "/api/indexing/normalize-metadata").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["started"].clone(), body["staleDocuments"].clone()), (false.into(), 0.into()));
+
+        // Metadata written through the store is normalized already
+        let doc_id = state
+            .store
+            .add_document(
+                "Trip notes",
+                mindsage_store::AddDocumentOptions {
+                    metadata: Some(serde_json::json!({"Source": "notes", "topics": "travel"})),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let (_, body) = send(&app, "GET", &format!("/api/vector-store/documents/{}/topics", doc_id)).await;
+        assert_eq!(body["topics"], serde_json::json!(["travel"]));
+        let (_, body) = send(&app, "GET", "/api/indexing/normalize-metadata").await;
+        assert_eq!((body["job"].clone(), body["staleDocuments"].clone()), (serde_json::Value::Null, 0.into()));
+
+        // A row from before normalization; a running job turns a second start away
+        rusqlite::Connection::open(state.config.data_paths.vectordb.join("mindsage.db"))
+            .unwrap()
+            .execute(
+                "UPDATE documents SET metadata_json = '{\"Title\":\"Trip\"}', metadata_schema = 0 WHERE id = ?1",
+                [doc_id],
+            )
+            .unwrap();
+        state.metadata_normalization.start(1, NormalizeProgress::default()).unwrap();
+        let (status, body) = send(&app, "POST", "/api/indexing/normalize-metadata").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "normalization_running");
+        normalize_metadata::run_normalization(&state);
+        let (_, body) = send(&app, "GET", "/api/indexing/normalize-metadata").await;
+        assert_eq!(body["job"]["status"], "completed");
+        assert_eq!(body["job"]["rules"]["renamed_key"], 1);
+        assert_eq!(body["job"]["changedDocuments"][0]["changes"][0]["to"], "title");
+        assert_eq!(body["staleDocuments"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_offsets_route() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let app = crate::routes::build_app(Arc::new(Profiles::start(state.clone())));
+
+        let (status, body) = send(&app, "POST", "/api/indexing/backfill-offsets").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["started"].clone(), body["missingOffsets"].clone()), (false.into(), 0.into()));
+
+        let doc_id = state.store.add_document("Alpha beta gamma. Delta epsilon.", Default::default()).unwrap();
+        state.store.add_chunk(doc_id, "Delta epsilon.", 0, 1, None, None, None, None, None, None).unwrap();
+        let (_, body) = send(&app, "GET", &format!("/api/vector-store/documents/{}", doc_id)).await;
+        assert_eq!(body["offsetsComplete"], false);
+
+        // A running job turns a second start away
+        state.offset_backfill.start(1, BackfillProgress::default()).unwrap();
+        let (status, body) = send(&app, "POST", "/api/indexing/backfill-offsets").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "backfill_running");
+        backfill_offsets::run_backfill(&state);
+        let (_, body) = send(&app, "GET", &format!("/api/vector-store/documents/{}", doc_id)).await;
+        assert_eq!(body["offsetsComplete"], true);
+
+        state.store.add_chunk(doc_id, "Alpha  beta gamma.", 1, 1, None, None, None, None, None, None).unwrap();
+        let (status, body) = send(&app, "POST", "/api/indexing/backfill-offsets").await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body["job"]["total"], 1);
+        for _ in 0..100 {
+            let (_, body) = send(&app, "GET", "/api/indexing/backfill-offsets").await;
+            if body["job"]["status"] == "completed" {
+                assert_eq!((body["job"]["fuzzy"].clone(), body["missingOffsets"].clone()), (1.into(), 0.into()));
+                let (_, body) = send(&app, "GET", &format!("/api/vector-store/documents/{}", doc_id)).await;
+                assert_eq!(body["offsetsComplete"], true);
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("backfill did not complete");
     }
 }
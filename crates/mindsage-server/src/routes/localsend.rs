@@ -1,15 +1,25 @@
 //! LocalSend routes — protocol endpoints + management routes.
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::body::Bytes;
-use axum::extract::{Query, State};
-use axum::routing::{get, post};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post, put};
 use axum::{Json, Router};
 use tracing::{info, warn};
+use utoipa::{IntoParams, OpenApi};
 
+use crate::error::{ApiError, ApiResult};
+use crate::quota::{self, QuotaArea};
 use crate::state::AppState;
+use mindsage_core::{redact, Event};
 use mindsage_localsend::*;
+use serde::Deserialize;
+
+/// How long a sender waits for the user to accept a transfer.
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
 
 // ---------------------------------------------------------------
 // Route builder
@@ -23,7 +33,27 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/localsend/stop", post(stop_server))
         .route("/localsend/setup", post(setup))
         .route("/localsend/configure", post(configure))
+        .route(
+            "/localsend/certificate/regenerate",
+            post(regenerate_certificate),
+        )
+        .route("/localsend/devices", get(list_devices))
+        .route(
+            "/localsend/devices/{fingerprint}",
+            put(set_device_trust).delete(forget_device),
+        )
+        .route("/localsend/history", get(transfer_history))
+        .route("/localsend/pending", get(list_pending))
+        .route("/localsend/pending/{session_id}/accept", post(accept_pending))
+        .route("/localsend/pending/{session_id}/decline", post(decline_pending))
         // Protocol v2 routes (also served on port 3003 for compat)
+        .merge(protocol_routes())
+}
+
+/// The v2 protocol endpoints, served by the main API and by the LocalSend
+/// protocol listener.
+pub fn protocol_routes() -> Router<Arc<AppState>> {
+    Router::new()
         .route("/localsend/v2/info", get(get_info))
         .route("/localsend/v2/register", post(register))
         .route("/localsend/v2/prepare-upload", post(prepare_upload))
@@ -32,14 +62,40 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/localsend/v2/finish", post(finish))
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_status,
+    start_server,
+    stop_server,
+    setup,
+    configure,
+    regenerate_certificate,
+    list_devices,
+    set_device_trust,
+    forget_device,
+    transfer_history,
+    list_pending,
+    accept_pending,
+    decline_pending,
+    get_info,
+    register,
+    prepare_upload,
+    upload_file,
+    cancel,
+    finish,
+))]
+pub struct LocalSendApi;
+
 // ---------------------------------------------------------------
 // Management handlers
 // ---------------------------------------------------------------
 
+#[utoipa::path(get, path = "/localsend/status", tag = "localsend", responses((status = 200, body = Object)))]
 async fn get_status(State(state): State<Arc<AppState>>) -> Json<LocalSendStatus> {
     Json(state.localsend_server.get_status())
 }
 
+#[utoipa::path(post, path = "/localsend/start", tag = "localsend", responses((status = 200, body = Object)))]
 async fn start_server(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
     state.localsend_server.start();
     // Note: actual UDP multicast discovery is started by the runtime
@@ -51,6 +107,7 @@ async fn start_server(State(state): State<Arc<AppState>>) -> Json<serde_json::Va
     }))
 }
 
+#[utoipa::path(post, path = "/localsend/stop", tag = "localsend", responses((status = 200, body = Object)))]
 async fn stop_server(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
     state.localsend_server.stop();
     Json(serde_json::json!({
@@ -59,6 +116,7 @@ async fn stop_server(State(state): State<Arc<AppState>>) -> Json<serde_json::Val
     }))
 }
 
+#[utoipa::path(post, path = "/localsend/setup", tag = "localsend", responses((status = 200, body = Object)))]
 async fn setup() -> Json<serde_json::Value> {
     // No-op — LocalSend is built-in
     Json(serde_json::json!({
@@ -67,6 +125,7 @@ async fn setup() -> Json<serde_json::Value> {
     }))
 }
 
+#[utoipa::path(post, path = "/localsend/configure", tag = "localsend", responses((status = 200, body = Object)))]
 async fn configure() -> Json<serde_json::Value> {
     Json(serde_json::json!({
         "success": true,
@@ -74,51 +133,273 @@ async fn configure() -> Json<serde_json::Value> {
     }))
 }
 
+/// POST /localsend/certificate/regenerate — replace the HTTPS certificate.
+#[utoipa::path(
+    post,
+    path = "/localsend/certificate/regenerate",
+    tag = "localsend",
+    responses((status = 200, body = Object))
+)]
+async fn regenerate_certificate(State(state): State<Arc<AppState>>) -> ApiResult<Json<serde_json::Value>> {
+    let server = state.clone();
+    let fingerprint = tokio::task::spawn_blocking(move || server.localsend_server.regenerate_certificate())
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .map_err(ApiError::bad_request)?;
+    info!("LocalSend certificate regenerated (fingerprint: {})", fingerprint);
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "fingerprint": fingerprint
+    })))
+}
+
+/// GET /localsend/devices — known senders, most recently seen first.
+#[utoipa::path(get, path = "/localsend/devices", tag = "localsend", responses((status = 200, body = Object)))]
+async fn list_devices(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let devices = state.localsend_server.known_devices();
+    Json(serde_json::json!({
+        "devices": devices,
+        "total": devices.len()
+    }))
+}
+
+#[derive(Deserialize)]
+struct TrustRequest {
+    trust: TrustLevel,
+}
+
+/// PUT /localsend/devices/:fingerprint — set a device's trust level.
+#[utoipa::path(
+    put,
+    path = "/localsend/devices/{fingerprint}",
+    tag = "localsend",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
+async fn set_device_trust(
+    State(state): State<Arc<AppState>>,
+    Path(fingerprint): Path<String>,
+    Json(req): Json<TrustRequest>,
+) -> ApiResult<Json<KnownDevice>> {
+    let device = state
+        .localsend_server
+        .set_device_trust(&fingerprint, req.trust)
+        .ok_or_else(|| ApiError::not_found(format!("Device {} not found", fingerprint)))?;
+    info!("LocalSend device {} is now {:?}", fingerprint, req.trust);
+    Ok(Json(device))
+}
+
+/// DELETE /localsend/devices/:fingerprint — forget a device.
+#[utoipa::path(
+    delete,
+    path = "/localsend/devices/{fingerprint}",
+    tag = "localsend",
+    responses((status = 200, body = Object))
+)]
+async fn forget_device(
+    State(state): State<Arc<AppState>>,
+    Path(fingerprint): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    if !state.localsend_server.forget_device(&fingerprint) {
+        return Err(ApiError::not_found(format!("Device {} not found", fingerprint)));
+    }
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct HistoryQuery {
+    page: Option<usize>,
+    #[serde(rename = "pageSize")]
+    page_size: Option<usize>,
+    fingerprint: Option<String>,
+}
+
+/// GET /localsend/history — completed transfers, newest first.
+#[utoipa::path(get, path = "/localsend/history", tag = "localsend", responses((status = 200, body = Object)))]
+async fn transfer_history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HistoryQuery>,
+) -> Json<serde_json::Value> {
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query.page_size.unwrap_or(50).clamp(1, 500);
+    let (transfers, total) = state
+        .localsend_server
+        .transfer_history(page, page_size, query.fingerprint.as_deref());
+    Json(serde_json::json!({
+        "transfers": transfers,
+        "total": total,
+        "page": page,
+        "pageSize": page_size,
+        "totalPages": total.div_ceil(page_size),
+    }))
+}
+
+/// GET /localsend/pending — transfers waiting for approval.
+#[utoipa::path(get, path = "/localsend/pending", tag = "localsend", responses((status = 200, body = Object)))]
+async fn list_pending(State(state): State<Arc<AppState>>) -> Json<Vec<PendingTransfer>> {
+    Json(state.localsend_server.pending_transfers())
+}
+
+/// POST /localsend/pending/:session_id/accept
+#[utoipa::path(
+    post,
+    path = "/localsend/pending/{session_id}/accept",
+    tag = "localsend",
+    responses((status = 200, body = Object))
+)]
+async fn accept_pending(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    resolve_pending(&state, &session_id, true)
+}
+
+/// POST /localsend/pending/:session_id/decline
+#[utoipa::path(
+    post,
+    path = "/localsend/pending/{session_id}/decline",
+    tag = "localsend",
+    responses((status = 200, body = Object))
+)]
+async fn decline_pending(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    resolve_pending(&state, &session_id, false)
+}
+
+fn resolve_pending(state: &AppState, session_id: &str, accept: bool) -> ApiResult<Json<serde_json::Value>> {
+    if !state.localsend_server.resolve_pending(session_id, accept) {
+        return Err(ApiError::not_found("No pending transfer with this id"));
+    }
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
 // ---------------------------------------------------------------
 // Protocol v2 handlers
 // ---------------------------------------------------------------
 
+#[utoipa::path(get, path = "/localsend/v2/info", tag = "localsend", responses((status = 200, body = Object)))]
 async fn get_info(State(state): State<Arc<AppState>>) -> Json<DeviceInfo> {
-    Json(state.localsend_server.get_device_info().clone())
+    Json(state.localsend_server.get_device_info())
 }
 
+#[utoipa::path(
+    post,
+    path = "/localsend/v2/register",
+    tag = "localsend",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn register(
     State(state): State<Arc<AppState>>,
     Json(info): Json<DeviceInfo>,
-) -> Json<DeviceInfo> {
-    state.localsend_server.register_device(&info);
-    Json(state.localsend_server.get_device_info().clone())
+) -> ApiResult<Json<DeviceInfo>> {
+    if state.localsend_server.register_device(&info) == TrustLevel::Blocked {
+        return Err(ApiError::forbidden("Device is blocked"));
+    }
+    Ok(Json(state.localsend_server.get_device_info()))
 }
 
+/// Open a transfer session. Senders from `ask` devices wait here until the
+/// user accepts or declines, for at most `APPROVAL_TIMEOUT`. Sessions whose
+/// declared file sizes would not fit the uploads quota are refused.
+#[utoipa::path(
+    post,
+    path = "/localsend/v2/prepare-upload",
+    tag = "localsend",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn prepare_upload(
     State(state): State<Arc<AppState>>,
     Json(req): Json<PrepareUploadRequest>,
-) -> Json<PrepareUploadResponse> {
-    let response = state.localsend_server.prepare_upload(req);
-    Json(response)
+) -> ApiResult<Json<PrepareUploadResponse>> {
+    let file_count = req.files.len();
+    let declared = req.files.values().map(|f| f.size).sum();
+    quota::check_room(&state, QuotaArea::Uploads, state.localsend_server.uploads_dir(), declared)?;
+    let response = match state.localsend_server.prepare_upload(req) {
+        PrepareOutcome::Accepted(response) => response,
+        PrepareOutcome::Blocked => return Err(ApiError::forbidden("Device is blocked")),
+        PrepareOutcome::Pending { response, mut approval } => {
+            state.events.publish(Event::LocalSendSession {
+                session_id: response.session_id.clone(),
+                state: "pending".to_string(),
+                file_count,
+            });
+            let accepted = match tokio::time::timeout(APPROVAL_TIMEOUT, &mut approval).await {
+                Ok(decision) => decision.unwrap_or(false),
+                // Accepted just as the wait ran out: the session is open
+                Err(_) => {
+                    !state.localsend_server.resolve_pending(&response.session_id, false)
+                        && approval.try_recv().unwrap_or(false)
+                }
+            };
+            if !accepted {
+                state.events.publish(Event::LocalSendSession {
+                    session_id: response.session_id.clone(),
+                    state: "declined".to_string(),
+                    file_count,
+                });
+                return Err(ApiError::forbidden("Transfer was not accepted"));
+            }
+            response
+        }
+    };
+    state.events.publish(Event::LocalSendSession {
+        session_id: response.session_id.clone(),
+        state: "prepared".to_string(),
+        file_count,
+    });
+    Ok(Json(response))
 }
 
+#[utoipa::path(
+    post,
+    path = "/localsend/v2/upload",
+    tag = "localsend",
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses((status = 200, body = Object))
+)]
 async fn upload_file(
     State(state): State<Arc<AppState>>,
     Query(query): Query<UploadQuery>,
     body: Bytes,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     // Validate session and token
-    let file_name = match state
+    let file_name = state
         .localsend_server
         .validate_upload(&query.session_id, &query.file_id, &query.token)
-    {
-        Ok(name) => name,
-        Err((status, msg)) => {
-            return Json(serde_json::json!({ "error": msg, "status": status }));
-        }
-    };
+        .map_err(|(status, msg)| {
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_REQUEST);
+            ApiError::new(status, "upload_rejected", msg)
+        })?;
 
     if body.is_empty() {
-        return Json(serde_json::json!({ "error": "No file data received" }));
+        return Err(ApiError::bad_request("No file data received"));
+    }
+
+    // Text shares become notes at finish; a body that is not UTF-8 is
+    // saved as a file instead
+    if state.localsend_server.is_text_share(&query.session_id, &query.file_id) {
+        if let Ok(text) = std::str::from_utf8(&body) {
+            info!("Text received ({} bytes)", body.len());
+            state
+                .localsend_server
+                .record_text(&query.session_id, &query.file_id, text.to_string());
+            state.events.publish(Event::LocalSendSession {
+                session_id: query.session_id.clone(),
+                state: "text_received".to_string(),
+                file_count: 1,
+            });
+            return Ok(Json(serde_json::json!({ "success": true })));
+        }
     }
 
     // Resolve unique filename and save
+    let uploads = state.localsend_server.uploads_dir();
+    quota::make_room(&state, QuotaArea::Uploads, uploads, body.len() as u64, None)?;
     let dest = state.localsend_server.resolve_filename(&file_name);
     match tokio::fs::write(&dest, &body).await {
         Ok(_) => {
@@ -130,66 +411,282 @@ async fn upload_file(
 
             info!(
                 "File received: {} ({} bytes)",
-                saved_name,
+                redact(&saved_name),
                 body.len()
             );
 
             state
                 .localsend_server
                 .record_upload(&query.session_id, &query.file_id, &saved_name);
+            state.events.publish(Event::LocalSendSession {
+                session_id: query.session_id.clone(),
+                state: "file_received".to_string(),
+                file_count: 1,
+            });
 
-            Json(serde_json::json!({ "success": true }))
+            Ok(Json(serde_json::json!({ "success": true })))
         }
         Err(e) => {
             warn!("Failed to save file {}: {}", file_name, e);
-            Json(serde_json::json!({
-                "error": format!("Failed to save file: {}", e),
-                "status": 500
-            }))
+            Err(ApiError::internal(format!("Failed to save file: {}", e)))
         }
     }
 }
 
+#[utoipa::path(post, path = "/localsend/v2/cancel", tag = "localsend", responses((status = 200, body = Object)))]
 async fn cancel(
     State(state): State<Arc<AppState>>,
     Query(query): Query<SessionQuery>,
 ) -> Json<serde_json::Value> {
     state.localsend_server.cancel_session(&query.session_id);
+    state.events.publish(Event::LocalSendSession {
+        session_id: query.session_id.clone(),
+        state: "cancelled".to_string(),
+        file_count: 0,
+    });
     Json(serde_json::json!({ "success": true }))
 }
 
+#[utoipa::path(post, path = "/localsend/v2/finish", tag = "localsend", responses((status = 200, body = Object)))]
 async fn finish(
     State(state): State<Arc<AppState>>,
     Query(query): Query<SessionQuery>,
-) -> Json<serde_json::Value> {
-    match state.localsend_server.finish_session(&query.session_id) {
-        Some(saved_files) => {
-            // Queue received files for indexing
-            for filename in &saved_files {
-                let file_path = state
-                    .localsend_server
-                    .uploads_dir()
-                    .join(filename)
-                    .to_string_lossy()
-                    .to_string();
-
-                // Queue for indexing via the existing indexing pipeline
-                let job_id = uuid::Uuid::new_v4().to_string();
-                let _ = state.indexing_tx.send(crate::state::IndexingRequest {
-                    job_id,
-                    file_path,
-                    filename: filename.clone(),
-                });
-            }
+) -> ApiResult<Json<serde_json::Value>> {
+    let Some(session) = state.localsend_server.finish_session(&query.session_id) else {
+        return Err(ApiError::not_found("Session not found"));
+    };
+
+    // Queue received files for indexing
+    for filename in &session.saved_filenames {
+        let file_path = state
+            .localsend_server
+            .uploads_dir()
+            .join(filename)
+            .to_string_lossy()
+            .to_string();
+
+        // Queue for indexing via the existing indexing pipeline
+        state.queue_indexing(file_path, filename.clone());
+    }
+
+    // Texts become notes; the ids let the sender confirm what was stored
+    let mut document_ids = Vec::new();
+    for text in session.texts.into_iter().filter(|t| !t.trim().is_empty()) {
+        match create_text_note(&state, text, &session.sender_alias).await {
+            Ok(id) => document_ids.push(id),
+            Err(e) => warn!("Failed to store shared text as a note: {}", e.message),
+        }
+    }
+
+    state.events.publish(Event::LocalSendSession {
+        session_id: query.session_id.clone(),
+        state: "finished".to_string(),
+        file_count: session.saved_filenames.len() + document_ids.len(),
+    });
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "filesReceived": session.saved_filenames.len(),
+        "notesCreated": document_ids.len(),
+        "documentIds": document_ids
+    })))
+}
+
+/// Store shared text as a note (`source: "localsend"`, titled by its first
+/// line). Text already stored returns the existing document's id.
+async fn create_text_note(state: &Arc<AppState>, text: String, sender: &str) -> ApiResult<i64> {
+    let hash = mindsage_ingest::ingest::content_hash(&text);
+    let title: String = text
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or_default()
+        .trim()
+        .chars()
+        .take(80)
+        .collect();
+    let metadata = serde_json::json!({
+        "source": "localsend",
+        "title": title,
+        "sender": sender,
+    });
+    let budget = Duration::from_millis(state.orchestrator.budget().embed_budget_ms);
+    let outcome = super::notes::ingest_note(state, text, hash, metadata, budget).await?;
+    Ok(outcome.doc_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mindsage_core::{AreaQuota, DiskQuotas, QuotaPolicy};
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
 
-            Json(serde_json::json!({
-                "success": true,
-                "filesReceived": saved_files.len()
+    /// Send one text through prepare-upload, upload and finish.
+    async fn send_text(state: &Arc<AppState>, text: &str) -> serde_json::Value {
+        let request: PrepareUploadRequest = serde_json::from_value(serde_json::json!({
+            "info": {"alias": "Pixel 8", "version": "2.0", "deviceType": "mobile", "fingerprint": "phone"},
+            "files": {"msg": {
+                "id": "msg", "fileName": "message.txt", "size": text.len(),
+                "fileType": "text/plain", "preview": text.chars().take(10).collect::<String>(),
+            }},
+        }))
+        .unwrap();
+        let Json(prepared) = prepare_upload(State(state.clone()), Json(request)).await.unwrap();
+        let upload = UploadQuery {
+            session_id: prepared.session_id.clone(),
+            file_id: "msg".to_string(),
+            token: prepared.files["msg"].clone(),
+        };
+        let Json(uploaded) = upload_file(State(state.clone()), Query(upload), Bytes::from(text.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(uploaded["success"], true);
+        let session = SessionQuery {
+            session_id: prepared.session_id,
+        };
+        let Json(body) = finish(State(state.clone()), Query(session)).await.unwrap();
+        body
+    }
+
+    #[tokio::test]
+    async fn test_text_share_becomes_note() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).text_notes(true).default_trust("trusted").build());
+        let text = "https://example.org/recipe\nTry this on Sunday";
+
+        let body = send_text(&state, text).await;
+        assert_eq!((body["filesReceived"].as_u64(), body["notesCreated"].as_u64()), (Some(0), Some(1)));
+        let id = body["documentIds"][0].as_i64().unwrap();
+        let doc = state.store.get_document(id).unwrap().unwrap();
+        assert_eq!(doc.text, text);
+        let metadata = doc.metadata.unwrap();
+        assert_eq!(metadata["source"], "localsend");
+        assert_eq!(metadata["sender"], "Pixel 8");
+        assert_eq!(metadata["title"], "https://example.org/recipe");
+        assert!(std::fs::read_dir(&state.config.data_paths.uploads).unwrap().next().is_none());
+
+        // Sending the same text again confirms the existing note
+        let again = send_text(&state, text).await;
+        assert_eq!(again["documentIds"][0].as_i64(), Some(id));
+        assert_eq!(state.store.count_documents().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_text_share_saved_as_file_when_notes_off() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).text_notes(false).default_trust("trusted").build());
+
+        let body = send_text(&state, "Buy milk").await;
+        assert_eq!(body["filesReceived"], 1);
+        assert_eq!(body["documentIds"], serde_json::json!([]));
+        let saved = std::fs::read_to_string(state.config.data_paths.uploads.join("message.txt")).unwrap();
+        assert_eq!(saved, "Buy milk");
+    }
+    fn photo_request(fingerprint: &str) -> Json<PrepareUploadRequest> {
+        Json(
+            serde_json::from_value(serde_json::json!({
+                "info": {"alias": "Tablet", "version": "2.0", "deviceType": "tablet", "fingerprint": fingerprint},
+                "files": {"img": {"id": "img", "fileName": "photo.jpg", "size": 3, "fileType": "image/jpeg"}},
             }))
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_blocked_device_is_refused() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).text_notes(true).default_trust("trusted").build());
+        let Json(prepared) = prepare_upload(State(state.clone()), photo_request("tablet")).await.unwrap();
+        let session = SessionQuery {
+            session_id: prepared.session_id,
+        };
+        let Json(cancelled) = cancel(State(state.clone()), Query(session)).await;
+        assert_eq!(cancelled["success"], true);
+
+        let Json(device) = set_device_trust(
+            State(state.clone()),
+            Path("tablet".to_string()),
+            Json(TrustRequest { trust: TrustLevel::Blocked }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(device.trust, TrustLevel::Blocked);
+        let err = prepare_upload(State(state.clone()), photo_request("tablet")).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+
+        // The trust level survives a restart
+        let restarted = Arc::new(TestState::new(&dir).text_notes(true).default_trust("trusted").build());
+        let err = prepare_upload(State(restarted.clone()), photo_request("tablet")).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+        assert!(forget_device(State(restarted.clone()), Path("tablet".to_string())).await.is_ok());
+        assert!(prepare_upload(State(restarted), photo_request("tablet")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_session_past_the_uploads_quota_is_refused() {
+        let dir = TempDir::new().unwrap();
+        let quotas = DiskQuotas {
+            uploads: Some(AreaQuota { max_bytes: 2, policy: QuotaPolicy::Reject }),
+            ..Default::default()
+        };
+        let state = Arc::new(TestState::new(&dir).default_trust("trusted").quotas(quotas).build());
+
+        // The declared 3 bytes do not fit; nothing is started
+        let err = prepare_upload(State(state.clone()), photo_request("tablet")).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::INSUFFICIENT_STORAGE);
+        assert_eq!(err.code, "quota_exceeded");
+    }
+
+    #[tokio::test]
+    async fn test_ask_device_waits_for_approval() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).default_trust("ask").build());
+
+        let waiting = tokio::spawn(prepare_upload(State(state.clone()), photo_request("tablet")));
+        let pending = loop {
+            let Json(pending) = list_pending(State(state.clone())).await;
+            if let Some(p) = pending.into_iter().next() {
+                break p;
+            }
+            tokio::task::yield_now().await;
+        };
+        assert_eq!((pending.alias.as_str(), pending.file_count), ("Tablet", 1));
+        assert!(accept_pending(State(state.clone()), Path(pending.session_id.clone())).await.is_ok());
+        let Json(prepared) = waiting.await.unwrap().unwrap();
+        assert_eq!(prepared.session_id, pending.session_id);
+
+        let waiting = tokio::spawn(prepare_upload(State(state.clone()), photo_request("tablet")));
+        while state.localsend_server.pending_transfers().is_empty() {
+            tokio::task::yield_now().await;
         }
-        None => Json(serde_json::json!({
-            "error": "Session not found",
-            "status": 404
-        })),
+        let session_id = state.localsend_server.pending_transfers()[0].session_id.clone();
+        assert!(decline_pending(State(state.clone()), Path(session_id)).await.is_ok());
+        assert_eq!(waiting.await.unwrap().unwrap_err().status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_history_records_finished_transfers() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).text_notes(true).default_trust("trusted").build());
+        send_text(&state, "First").await;
+        send_text(&state, "Second message").await;
+
+        let query = HistoryQuery {
+            page: Some(1),
+            page_size: Some(1),
+            fingerprint: None,
+        };
+        let Json(body) = transfer_history(State(state.clone()), Query(query)).await;
+        assert_eq!((body["total"].as_u64(), body["totalPages"].as_u64()), (Some(2), Some(2)));
+        let transfer = &body["transfers"][0];
+        assert_eq!(transfer["alias"], "Pixel 8");
+        assert_eq!(transfer["totalBytes"], 14);
+        assert_eq!(transfer["files"][0]["note"], true);
+
+        let Json(devices) = list_devices(State(state)).await;
+        assert_eq!(devices["devices"][0]["transfers"], 2);
+        assert_eq!(devices["devices"][0]["trust"], "trusted");
     }
 }
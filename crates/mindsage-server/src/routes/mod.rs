@@ -1,27 +1,72 @@
 //! HTTP route handlers — matches the existing Express API surface.
 
 pub mod browser;
+pub mod bulk;
 pub mod chat;
+pub mod chunks;
+#[cfg(test)]
+mod client_tests;
 pub mod connectors;
+pub mod events;
 pub mod files;
 pub mod indexing;
 pub mod localsend;
+pub mod notes;
+pub mod openapi;
 pub mod privacy;
+pub mod profiles;
+pub mod runtime;
+pub mod saved_searches;
+pub mod search;
 pub mod stats;
 pub mod vector_store;
+pub mod webhooks;
 
 use std::sync::Arc;
 
-use axum::Router;
-use tower_http::cors::CorsLayer;
-
+use axum::routing::any;
+use axum::{middleware, Router};
+use crate::cors;
+use crate::profiles::Profiles;
+use crate::rate_limit;
+use crate::read_only;
+use crate::request_trace;
 use crate::state::AppState;
 
-/// Build the main Axum router with all routes.
-pub fn build_router(state: Arc<AppState>) -> Router {
+/// Build the main Axum router: profile management, and every API route
+/// dispatched to the profile the request selects.
+pub fn build_app(profiles: Arc<Profiles>) -> Router {
+    let config = &profiles.default_state().config;
+    let swagger_ui = config.swagger_ui;
+    let cors = cors::cors_layer(config);
+    let read_only = config.read_only;
+    Router::new()
+        .nest("/api/profiles", profiles::routes())
+        .merge(openapi::routes(swagger_ui))
+        .route("/profiles/{profile}/{*rest}", any(profiles::dispatch_prefixed))
+        .fallback(profiles::dispatch)
+        .layer(middleware::from_fn_with_state(read_only, read_only::reject_writes))
+        .layer(cors)
+        .layer(middleware::from_fn(request_trace::request_id))
+        .with_state(profiles)
+}
+
+/// API routes of one profile; `build_app` adds CORS and request IDs.
+pub fn build_profile_router(state: Arc<AppState>) -> Router {
     Router::new()
         .nest("/api", api_routes())
-        .layer(CorsLayer::permissive())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit,
+        ))
+        .with_state(state)
+}
+
+/// Router for the LocalSend protocol listener: only the v2 endpoints.
+pub fn build_localsend_router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .nest("/api", localsend::protocol_routes())
+        .layer(middleware::from_fn(request_trace::request_id))
         .with_state(state)
 }
 
@@ -29,11 +74,19 @@ fn api_routes() -> Router<Arc<AppState>> {
     Router::new()
         .merge(stats::routes())
         .merge(vector_store::routes())
+        .merge(search::routes())
+        .merge(chunks::routes())
+        .merge(saved_searches::routes())
+        .merge(bulk::routes())
         .merge(files::routes())
+        .merge(notes::routes())
         .merge(indexing::routes())
         .merge(chat::routes())
         .merge(browser::routes())
         .merge(localsend::routes())
         .merge(connectors::routes())
         .merge(privacy::routes())
+        .merge(events::routes())
+        .merge(webhooks::routes())
+        .merge(runtime::routes())
 }
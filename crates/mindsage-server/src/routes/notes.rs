@@ -0,0 +1,297 @@
+//! Note routes — create and edit text documents with immediate indexing.
+//!
+//! Unlike `POST /api/vector-store/documents`, which leaves embedding to the
+//! background catch-up, a note is chunked, embedded and enriched before the
+//! response. Embedding stops at the tier's `embedBudgetMs` (or the request's
+//! `embedBudgetMs`); whatever is left is embedded in the background and the
+//! response says so.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{post, put};
+use axum::{Json, Router};
+use mindsage_runtime::IngestReport;
+use serde::Deserialize;
+use tracing::debug;
+use utoipa::OpenApi;
+
+use crate::error::{ApiError, ApiResult};
+use crate::indexing;
+use crate::saved_searches;
+use crate::state::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/notes", post(create_note))
+        .route("/notes/{id}", put(update_note))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    create_note,
+    update_note,
+))]
+pub struct NotesApi;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NoteRequest {
+    text: String,
+    title: Option<String>,
+    /// Merged into the document metadata.
+    metadata: Option<serde_json::Value>,
+    /// Time allowed for inline embedding; defaults to the tier budget.
+    embed_budget_ms: Option<u64>,
+}
+
+impl NoteRequest {
+    fn validate(&self) -> ApiResult<()> {
+        if self.text.trim().is_empty() {
+            return Err(ApiError::bad_request("Note text is empty"));
+        }
+        if self.metadata.as_ref().is_some_and(|m| !m.is_object()) {
+            return Err(ApiError::bad_request("metadata must be an object"));
+        }
+        Ok(())
+    }
+
+    /// Request metadata with the title applied.
+    fn metadata_updates(&self) -> serde_json::Value {
+        let mut metadata = self.metadata.clone().unwrap_or_else(|| serde_json::json!({}));
+        if let Some(title) = &self.title {
+            metadata["title"] = serde_json::json!(title);
+        }
+        metadata
+    }
+
+    fn embed_budget(&self, state: &AppState) -> Duration {
+        Duration::from_millis(
+            self.embed_budget_ms
+                .unwrap_or(state.orchestrator.budget().embed_budget_ms),
+        )
+    }
+}
+
+/// POST /api/notes — create a note and index it before responding.
+#[utoipa::path(post, path = "/notes", tag = "notes", request_body = Object, responses((status = 201, body = Object)))]
+async fn create_note(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<NoteRequest>,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    req.validate()?;
+    let mut metadata = req.metadata_updates();
+    if metadata.get("source").is_none() {
+        metadata["source"] = serde_json::json!("note");
+    }
+    let hash = mindsage_ingest::ingest::content_hash(&req.text);
+    let budget = req.embed_budget(&state);
+    let outcome = ingest_note(&state, req.text, hash.clone(), metadata, budget).await?;
+    if outcome.is_duplicate() {
+        return Err(mindsage_core::Error::DuplicateContent(hash).into());
+    }
+    Ok((StatusCode::CREATED, Json(note_response(&state, &outcome, &hash, "created"))))
+}
+
+/// Store `text` as a new document and index it, embedding inline for up to
+/// `budget`. Text already stored is reported as a duplicate of its
+/// document. Also used for text shared over LocalSend.
+pub(crate) async fn ingest_note(
+    state: &Arc<AppState>,
+    text: String,
+    hash: String,
+    metadata: serde_json::Value,
+    budget: Duration,
+) -> ApiResult<IngestReport> {
+    let outcome = state
+        .blocking(move |state| {
+            state.orchestrator.ingest_within(
+                &state.store,
+                &state.embedder,
+                &text,
+                &hash,
+                &metadata,
+                None,
+                Some(budget),
+            )
+        })
+        .await?;
+
+    if !outcome.is_duplicate() {
+        debug!(
+            "Indexed note {}: {:?} chunks, {} embedded, {} enriched in {:?}",
+            outcome.doc_id, outcome.chunk_counts, outcome.embedded, outcome.enriched, outcome.timings
+        );
+        after_indexing(state, &outcome);
+    }
+    Ok(outcome)
+}
+
+/// PUT /api/notes/:id — replace a document's text and index it again.
+#[utoipa::path(
+    put,
+    path = "/notes/{id}",
+    tag = "notes",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
+async fn update_note(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(req): Json<NoteRequest>,
+) -> ApiResult<(StatusCode, Json<serde_json::Value>)> {
+    req.validate()?;
+    let hash = mindsage_ingest::ingest::content_hash(&req.text);
+    let budget = req.embed_budget(&state);
+
+    let task_hash = hash.clone();
+    let outcome = state
+        .blocking(move |state| -> ApiResult<_> {
+            if state.store.get_document(id)?.is_none() {
+                return Err(ApiError::not_found(format!("Document {} not found", id)));
+            }
+            let updates = req.metadata_updates();
+            if updates.as_object().is_some_and(|m| !m.is_empty()) {
+                state.store.update_document_metadata(id, &updates)?;
+            }
+            let metadata = state
+                .store
+                .get_document(id)?
+                .and_then(|d| d.metadata)
+                .unwrap_or_else(|| serde_json::json!({}));
+            Ok(state.orchestrator.reindex_within(
+                &state.store,
+                &state.embedder,
+                id,
+                &req.text,
+                &task_hash,
+                &metadata,
+                None,
+                Some(budget),
+            )?)
+        })
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Document {} not found", id)))?;
+
+    after_indexing(&state, &outcome);
+    Ok((StatusCode::OK, Json(note_response(&state, &outcome, &hash, "updated"))))
+}
+
+/// Hand deferred embeddings to the background catch-up and re-run saved
+/// searches, as the indexing worker does for files.
+fn after_indexing(state: &Arc<AppState>, outcome: &IngestReport) {
+    let task_state = state.clone();
+    let deferred = outcome.embedding_deferred > 0;
+    tokio::task::spawn_blocking(move || {
+        if deferred {
+            indexing::embed_pending_chunks(&task_state);
+        }
+        saved_searches::check_saved_searches(&task_state);
+    });
+}
+
+fn note_response(state: &AppState, outcome: &IngestReport, hash: &str, status: &str) -> serde_json::Value {
+    let embedding = if !state.embedder.is_available() {
+        "unavailable"
+    } else if outcome.embedding_deferred > 0 {
+        "deferred"
+    } else {
+        "inline"
+    };
+    serde_json::json!({
+        "id": outcome.doc_id,
+        "contentHash": hash,
+        "status": status,
+        "chunks": outcome.chunks,
+        "embedded": outcome.embedded,
+        "embeddingDeferred": outcome.embedding_deferred,
+        "embedding": embedding,
+        "topics": outcome.topics,
+        "timings": outcome.timings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    fn note(value: serde_json::Value) -> Json<NoteRequest> {
+        Json(serde_json::from_value(value).unwrap())
+    }
+
+    fn top_vector_hit(state: &AppState, query: &str) -> i64 {
+        let embedding = state.embedder.embed(query).unwrap().embedding;
+        state.store.vector_search(&embedding, Some(1), 1).unwrap()[0].doc_id
+    }
+
+    #[tokio::test]
+    async fn test_created_note_is_vector_searchable_immediately() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).word_embedder().build());
+        state.store.add_document("Unrelated", Default::default()).unwrap();
+
+        let (status, Json(body)) = create_note(
+            State(state.clone()),
+            note(serde_json::json!({"text": "Renew the passport before the Lisbon trip", "title": "Passport"})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["embedding"], "inline");
+        assert_eq!(body["embeddingDeferred"], 0);
+        let id = body["id"].as_i64().unwrap();
+        assert_eq!(top_vector_hit(&state, "passport Lisbon"), id);
+
+        let doc = state.store.get_document(id).unwrap().unwrap();
+        let metadata = doc.metadata.unwrap();
+        assert_eq!((metadata["title"].as_str(), metadata["source"].as_str()), (Some("Passport"), Some("note")));
+
+        let err = create_note(
+            State(state.clone()),
+            note(serde_json::json!({"text": "Renew the passport before the Lisbon trip"})),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_update_note_rechunks_and_reembeds() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).word_embedder().build());
+        let (_, Json(body)) = create_note(State(state.clone()), note(serde_json::json!({"text": "Buy oat milk"})))
+            .await
+            .unwrap();
+        let id = body["id"].as_i64().unwrap();
+
+        let (status, Json(body)) = update_note(
+            State(state.clone()),
+            Path(id),
+            note(serde_json::json!({"text": "Book the dentist appointment", "title": "Errands"})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "updated");
+        assert_eq!(top_vector_hit(&state, "dentist appointment"), id);
+        assert!(state.store.bm25_search("oat", Some(1), 10).unwrap().is_empty());
+        let doc = state.store.get_document(id).unwrap().unwrap();
+        assert_eq!(doc.text, "Book the dentist appointment");
+        assert_eq!(doc.metadata.unwrap()["title"], "Errands");
+
+        let missing = update_note(State(state.clone()), Path(9999), note(serde_json::json!({"text": "x"})))
+            .await
+            .unwrap_err();
+        assert_eq!(missing.status, StatusCode::NOT_FOUND);
+        let empty = update_note(State(state), Path(id), note(serde_json::json!({"text": "  "})))
+            .await
+            .unwrap_err();
+        assert_eq!(empty.status, StatusCode::BAD_REQUEST);
+    }
+}
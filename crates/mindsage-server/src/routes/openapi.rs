@@ -0,0 +1,320 @@
+//! OpenAPI document of the HTTP API, served at `/api/openapi.json`, with
+//! Swagger UI at `/api/docs` when `MINDSAGE_SWAGGER_UI` is on.
+//!
+//! Each route module lists its `#[utoipa::path]` handlers in an `OpenApi`
+//! struct next to its router; `ApiDoc` nests them under `/api`. Every
+//! operation also gets a `default` response with the error envelope.
+
+use std::sync::Arc;
+
+use axum::routing::get;
+use axum::{Json, Router};
+use utoipa::openapi::{Content, OpenApi as OpenApiDoc, Ref, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::error::ErrorBody;
+use crate::profiles::Profiles;
+
+use super::{
+    browser, bulk, chat, chunks, connectors, events, files, indexing, localsend, notes, privacy, profiles, runtime, saved_searches,
+    search, stats, vector_store, webhooks,
+};
+
+/// Path of the OpenAPI document.
+const SPEC_PATH: &str = "/api/openapi.json";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "MindSage API",
+        description = "Local-first personal knowledge store. Every path except `/api/profiles` \
+                       serves the profile selected by the `X-Profile` header (default: `default`) \
+                       or a `/profiles/{name}` path prefix."
+    ),
+    paths(openapi_json),
+    nest(
+        (path = "/api", api = stats::StatsApi),
+        (path = "/api", api = vector_store::VectorStoreApi),
+        (path = "/api", api = search::SearchApi),
+        (path = "/api", api = chunks::ChunksApi),
+        (path = "/api", api = saved_searches::SavedSearchesApi),
+        (path = "/api", api = bulk::BulkApi),
+        (path = "/api", api = files::FilesApi),
+        (path = "/api", api = notes::NotesApi),
+        (path = "/api", api = indexing::IndexingApi),
+        (path = "/api", api = chat::ChatApi),
+        (path = "/api", api = browser::BrowserApi),
+        (path = "/api", api = localsend::LocalSendApi),
+        (path = "/api", api = connectors::ConnectorsApi),
+        (path = "/api", api = privacy::PrivacyApi),
+        (path = "/api", api = events::EventsApi),
+        (path = "/api", api = webhooks::WebhooksApi),
+        (path = "/api", api = runtime::RuntimeApi),
+        (path = "/api/profiles", api = profiles::ProfilesApi),
+    ),
+    components(schemas(ErrorBody)),
+    modifiers(&ErrorResponses)
+)]
+pub struct ApiDoc;
+
+/// Adds the error envelope as the `default` response of every operation.
+struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+    fn modify(&self, openapi: &mut OpenApiDoc) {
+        let response = ResponseBuilder::new()
+            .description("Error envelope")
+            .content("application/json", Content::new(Some(Ref::from_schema_name("ErrorBody"))))
+            .build();
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for operation in operations.into_iter().flatten() {
+                operation
+                    .responses
+                    .responses
+                    .entry("default".to_string())
+                    .or_insert_with(|| response.clone().into());
+            }
+        }
+    }
+}
+
+pub fn routes(swagger_ui: bool) -> Router<Arc<Profiles>> {
+    if swagger_ui {
+        Router::new().merge(SwaggerUi::new("/api/docs").url(SPEC_PATH, ApiDoc::openapi()))
+    } else {
+        Router::new().route(SPEC_PATH, get(openapi_json))
+    }
+}
+
+/// This document.
+#[utoipa::path(get, path = "/api/openapi.json", tag = "meta", responses((status = 200, body = Object)))]
+async fn openapi_json() -> Json<OpenApiDoc> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    /// Every path the server routes, kept by hand: a new route goes here
+    /// and in the `OpenApi` struct of its module.
+    const ROUTES: &[&str] = &[
+        "/api/browser-connector/auth",
+        "/api/browser-connector/auth-status",
+        "/api/browser-connector/auto-sync",
+        "/api/browser-connector/auto-sync/interval",
+        "/api/browser-connector/auto-sync/start",
+        "/api/browser-connector/auto-sync/stop",
+        "/api/browser-connector/capture",
+        "/api/browser-connector/cleanup",
+        "/api/browser-connector/close",
+        "/api/browser-connector/config",
+        "/api/browser-connector/conversations",
+        "/api/browser-connector/conversations/merge",
+        "/api/browser-connector/conversations/search",
+        "/api/browser-connector/conversations/{id}",
+        "/api/browser-connector/debug",
+        "/api/browser-connector/extension/conversations",
+        "/api/browser-connector/import-cookies",
+        "/api/browser-connector/launch",
+        "/api/browser-connector/navigate",
+        "/api/browser-connector/navigate-to-site",
+        "/api/browser-connector/pending-cookies",
+        "/api/browser-connector/reindex",
+        "/api/browser-connector/report-auth",
+        "/api/browser-connector/sites",
+        "/api/browser-connector/sites/{name}",
+        "/api/browser-connector/stats",
+        "/api/browser-connector/status",
+        "/api/browser-connector/sync",
+        "/api/browser-connector/sync-complete",
+        "/api/browser-connector/vnc/check",
+        "/api/browser-connector/vnc/status",
+        "/api/browser-connector/ws",
+        "/api/chat",
+        "/api/chat/config",
+        "/api/chat/config/test",
+        "/api/chat/status",
+        "/api/chat/stream",
+        "/api/config/webhooks",
+        "/api/config/webhooks/{id}/test",
+        "/api/connectors",
+        "/api/connectors/preflight",
+        "/api/connectors/{id}",
+        "/api/connectors/{id}/cancel",
+        "/api/connectors/{id}/exports",
+        "/api/connectors/{id}/exports/{filename}",
+        "/api/connectors/{id}/media",
+        "/api/connectors/{id}/pending-media",
+        "/api/connectors/{id}/staged",
+        "/api/connectors/{id}/staged/approve",
+        "/api/connectors/{id}/staged/reject",
+        "/api/connectors/{id}/status",
+        "/api/connectors/{id}/stop",
+        "/api/connectors/{id}/sync",
+        "/api/connectors/{id}/upload",
+        "/api/consent/presets",
+        "/api/consent/session",
+        "/api/consent/session/{id}",
+        "/api/consent/session/{id}/categories",
+        "/api/consent/session/{id}/check",
+        "/api/consent/sessions",
+        "/api/consent/status",
+        "/api/events",
+        "/api/files",
+        "/api/files/upload",
+        "/api/files/upload/init",
+        "/api/files/upload/{id}",
+        "/api/files/upload/{id}/chunk/{n}",
+        "/api/files/upload/{id}/complete",
+        "/api/files/{filename}",
+        "/api/files/{filename}/download",
+        "/api/files/{filename}/import",
+        "/api/health/ready",
+        "/api/indexing/backfill-offsets",
+        "/api/indexing/jobs",
+        "/api/indexing/jobs/{job_id}",
+        "/api/indexing/jobs/{job_id}/retry",
+        "/api/indexing/normalize-metadata",
+        "/api/indexing/re-extract",
+        "/api/indexing/reembed",
+        "/api/indexing/status",
+        "/api/localsend/certificate/regenerate",
+        "/api/localsend/configure",
+        "/api/localsend/devices",
+        "/api/localsend/devices/{fingerprint}",
+        "/api/localsend/history",
+        "/api/localsend/pending",
+        "/api/localsend/pending/{session_id}/accept",
+        "/api/localsend/pending/{session_id}/decline",
+        "/api/localsend/setup",
+        "/api/localsend/start",
+        "/api/localsend/status",
+        "/api/localsend/stop",
+        "/api/localsend/v2/cancel",
+        "/api/localsend/v2/finish",
+        "/api/localsend/v2/info",
+        "/api/localsend/v2/prepare-upload",
+        "/api/localsend/v2/register",
+        "/api/localsend/v2/upload",
+        "/api/notes",
+        "/api/notes/{id}",
+        "/api/openapi.json",
+        "/api/pending-media",
+        "/api/pii/anonymize",
+        "/api/pii/deanonymize",
+        "/api/pii/detect",
+        "/api/pii/status",
+        "/api/privacy/audit",
+        "/api/profiles",
+        "/api/profiles/{name}",
+        "/api/profiles/{name}/stats",
+        "/api/runtime/digest",
+        "/api/runtime/digests",
+        "/api/runtime/forget",
+        "/api/search/universal",
+        "/api/server-info",
+        "/api/stats",
+        "/api/stats/background",
+        "/api/stats/disk",
+        "/api/stats/queries",
+        "/api/stats/queries/slow",
+        "/api/stats/runtime",
+        "/api/stats/sources",
+        "/api/vector-store/bulk-jobs/{id}",
+        "/api/vector-store/chunks/{id}",
+        "/api/vector-store/chunks/{id}/context",
+        "/api/vector-store/chunks/{id}/parent",
+        "/api/vector-store/collections",
+        "/api/vector-store/collections/{id}",
+        "/api/vector-store/collections/{id}/documents",
+        "/api/vector-store/debug",
+        "/api/vector-store/documents",
+        "/api/vector-store/documents/batch",
+        "/api/vector-store/documents/bulk-delete",
+        "/api/vector-store/documents/bulk-update-metadata",
+        "/api/vector-store/documents/by-hash/{hash}",
+        "/api/vector-store/documents/facets/date",
+        "/api/vector-store/documents/hashes",
+        "/api/vector-store/documents/{id}",
+        "/api/vector-store/documents/{id}/chunks",
+        "/api/vector-store/documents/{id}/outline",
+        "/api/vector-store/documents/{id}/raw",
+        "/api/vector-store/documents/{id}/similar",
+        "/api/vector-store/documents/{id}/tags/{tag}",
+        "/api/vector-store/documents/{id}/topics",
+        "/api/vector-store/documents/{id}/topics/generate",
+        "/api/vector-store/export.ndjson",
+        "/api/vector-store/graph",
+        "/api/vector-store/graph/node/{node_id}",
+        "/api/vector-store/saved-searches",
+        "/api/vector-store/saved-searches/{id}",
+        "/api/vector-store/saved-searches/{id}/matches",
+        "/api/vector-store/search",
+        "/api/vector-store/search/enhanced",
+        "/api/vector-store/search/settings",
+        "/api/vector-store/search/with-topic",
+        "/api/vector-store/status",
+        "/api/vector-store/suggest",
+        "/api/vector-store/tags",
+        "/api/vector-store/tags/{tag}/documents",
+        "/api/vector-store/topics",
+        "/api/vector-store/topics/cooccurrence",
+        "/api/vector-store/topics/generate",
+        "/api/vector-store/topics/{topic}/documents",
+        "/api/vector-store/topics/{topic}/stats",
+    ];
+
+    /// Every `$ref` in the document.
+    fn collect_refs(value: &serde_json::Value, refs: &mut BTreeSet<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    match (key.as_str(), value.as_str()) {
+                        ("$ref", Some(target)) => {
+                            refs.insert(target.to_string());
+                        }
+                        _ => collect_refs(value, refs),
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_spec_parses_and_covers_every_route() {
+        let json = serde_json::to_string(&ApiDoc::openapi()).unwrap();
+        let spec: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+        let documented: BTreeSet<String> = spec["paths"].as_object().unwrap().keys().cloned().collect();
+
+        let registered: BTreeSet<String> = ROUTES.iter().map(|p| p.to_string()).collect();
+
+        let undocumented: Vec<&String> = registered.difference(&documented).collect();
+        assert!(undocumented.is_empty(), "routes missing from the spec: {:?}", undocumented);
+        let unknown: Vec<&String> = documented.difference(&registered).collect();
+        assert!(unknown.is_empty(), "spec paths without a route: {:?}", unknown);
+
+        // Every referenced schema is defined, including the error envelope
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        let mut refs = BTreeSet::new();
+        collect_refs(&spec, &mut refs);
+        assert!(refs.contains("#/components/schemas/ErrorBody"));
+        for target in refs {
+            let name = target.strip_prefix("#/components/schemas/").unwrap_or(&target);
+            assert!(schemas.contains_key(name), "unresolved reference {}", target);
+        }
+    }
+}
@@ -2,10 +2,13 @@
 
 use std::sync::Arc;
 
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::routing::{get, post};
 use axum::{Json, Router};
+use utoipa::OpenApi;
 
+use crate::audit::AuditQuery;
+use crate::error::{ApiError, ApiResult};
 use crate::state::AppState;
 use mindsage_protocol::consent::*;
 use mindsage_protocol::pii::*;
@@ -36,8 +39,28 @@ pub fn routes() -> Router<Arc<AppState>> {
         // Status & presets (used by frontend)
         .route("/consent/status", get(consent_status))
         .route("/consent/presets", get(consent_presets))
+        // Audit log of outbound LLM requests
+        .route("/privacy/audit", get(list_audit_entries))
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(
+    detect_pii,
+    anonymize_text,
+    deanonymize_text,
+    pii_status,
+    create_consent_session,
+    list_consent_sessions,
+    get_consent_session,
+    revoke_consent_session,
+    check_consent,
+    update_consent_categories,
+    consent_status,
+    consent_presets,
+    list_audit_entries,
+))]
+pub struct PrivacyApi;
+
 // ---------------------------------------------------------------
 // Request/Response types
 // ---------------------------------------------------------------
@@ -61,6 +84,13 @@ struct UpdateCategoriesBody {
 // PII Handlers
 // ---------------------------------------------------------------
 
+#[utoipa::path(
+    post,
+    path = "/pii/detect",
+    tag = "privacy",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn detect_pii(
     State(state): State<Arc<AppState>>,
     Json(input): Json<TextInput>,
@@ -72,6 +102,13 @@ async fn detect_pii(
     }))
 }
 
+#[utoipa::path(
+    post,
+    path = "/pii/anonymize",
+    tag = "privacy",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn anonymize_text(
     State(state): State<Arc<AppState>>,
     Json(input): Json<TextInput>,
@@ -79,6 +116,13 @@ async fn anonymize_text(
     Json(state.pii_detector.anonymize(&input.text))
 }
 
+#[utoipa::path(
+    post,
+    path = "/pii/deanonymize",
+    tag = "privacy",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn deanonymize_text(
     State(state): State<Arc<AppState>>,
     Json(input): Json<TextInput>,
@@ -87,6 +131,7 @@ async fn deanonymize_text(
     Json(serde_json::json!({ "text": restored }))
 }
 
+#[utoipa::path(get, path = "/pii/status", tag = "privacy", responses((status = 200, body = Object)))]
 async fn pii_status(
     State(state): State<Arc<AppState>>,
 ) -> Json<serde_json::Value> {
@@ -97,10 +142,40 @@ async fn pii_status(
     }))
 }
 
+// ---------------------------------------------------------------
+// Audit Handlers
+// ---------------------------------------------------------------
+
+#[utoipa::path(get, path = "/privacy/audit", tag = "privacy", responses((status = 200, body = Object)))]
+async fn list_audit_entries(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let (entries, total) = state
+        .audit
+        .list(&query)
+        .map_err(|e| ApiError::internal(format!("Failed to read audit log: {}", e)))?;
+    let page_size = query.page_size();
+    Ok(Json(serde_json::json!({
+        "entries": entries,
+        "total": total,
+        "page": query.page(),
+        "pageSize": page_size,
+        "totalPages": total.div_ceil(page_size),
+    })))
+}
+
 // ---------------------------------------------------------------
 // Consent Handlers
 // ---------------------------------------------------------------
 
+#[utoipa::path(
+    post,
+    path = "/consent/session",
+    tag = "privacy",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn create_consent_session(
     State(state): State<Arc<AppState>>,
     Json(req): Json<CreateConsentRequest>,
@@ -108,6 +183,7 @@ async fn create_consent_session(
     Json(state.consent_manager.create_session(req))
 }
 
+#[utoipa::path(get, path = "/consent/sessions", tag = "privacy", responses((status = 200, body = Object)))]
 async fn list_consent_sessions(
     State(state): State<Arc<AppState>>,
 ) -> Json<serde_json::Value> {
@@ -118,27 +194,36 @@ async fn list_consent_sessions(
     }))
 }
 
+#[utoipa::path(get, path = "/consent/session/{id}", tag = "privacy", responses((status = 200, body = Object)))]
 async fn get_consent_session(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     match state.consent_manager.get_session(&id) {
-        Some(session) => Json(serde_json::to_value(session).unwrap_or_default()),
-        None => Json(serde_json::json!({ "error": "Session not found" })),
+        Some(session) => Ok(Json(serde_json::to_value(session).unwrap_or_default())),
+        None => Err(ApiError::not_found("Session not found")),
     }
 }
 
+#[utoipa::path(delete, path = "/consent/session/{id}", tag = "privacy", responses((status = 200, body = Object)))]
 async fn revoke_consent_session(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     if state.consent_manager.revoke_session(&id) {
-        Json(serde_json::json!({ "success": true }))
+        Ok(Json(serde_json::json!({ "success": true })))
     } else {
-        Json(serde_json::json!({ "error": "Session not found" }))
+        Err(ApiError::not_found("Session not found"))
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/consent/session/{id}/check",
+    tag = "privacy",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn check_consent(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -151,17 +236,25 @@ async fn check_consent(
     }))
 }
 
+#[utoipa::path(
+    post,
+    path = "/consent/session/{id}/categories",
+    tag = "privacy",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
 async fn update_consent_categories(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
     Json(body): Json<UpdateCategoriesBody>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
     match state.consent_manager.update_session(&id, body.categories) {
-        Some(session) => Json(serde_json::to_value(session).unwrap_or_default()),
-        None => Json(serde_json::json!({ "error": "Session not found" })),
+        Some(session) => Ok(Json(serde_json::to_value(session).unwrap_or_default())),
+        None => Err(ApiError::not_found("Session not found")),
     }
 }
 
+#[utoipa::path(get, path = "/consent/status", tag = "privacy", responses((status = 200, body = Object)))]
 async fn consent_status(
     State(state): State<Arc<AppState>>,
 ) -> Json<serde_json::Value> {
@@ -177,6 +270,7 @@ async fn consent_status(
     }))
 }
 
+#[utoipa::path(get, path = "/consent/presets", tag = "privacy", responses((status = 200, body = Object)))]
 async fn consent_presets() -> Json<serde_json::Value> {
     Json(serde_json::json!({
         "presets": [
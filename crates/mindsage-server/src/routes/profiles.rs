@@ -0,0 +1,312 @@
+//! Profile routes — create, list and delete profiles, and hand every other
+//! request to the router of the profile it selects (see `profiles.rs`).
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use mindsage_core::DEFAULT_PROFILE;
+use serde::{Deserialize, Serialize};
+use tower::ServiceExt;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::error::{ApiError, ApiResult};
+use crate::profiles::{ProfileInfo, Profiles, PROFILE_HEADER};
+
+pub fn routes() -> Router<Arc<Profiles>> {
+    Router::new()
+        .route("/", get(list_profiles).post(create_profile))
+        .route("/{name}", delete(delete_profile))
+        .route("/{name}/stats", get(profile_stats))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    list_profiles,
+    create_profile,
+    delete_profile,
+    profile_stats,
+))]
+pub struct ProfilesApi;
+
+#[derive(Serialize, ToSchema)]
+struct ProfilesResponse {
+    profiles: Vec<ProfileInfo>,
+}
+
+/// GET /api/profiles — all profiles, the default first.
+#[utoipa::path(get, path = "", tag = "profiles", responses((status = 200, body = ProfilesResponse)))]
+async fn list_profiles(State(profiles): State<Arc<Profiles>>) -> Json<ProfilesResponse> {
+    Json(ProfilesResponse {
+        profiles: profiles.list(),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateProfileRequest {
+    name: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct CreateProfileResponse {
+    success: bool,
+    name: String,
+    data_dir: String,
+}
+
+/// POST /api/profiles — create a profile with an empty data directory.
+#[utoipa::path(
+    post,
+    path = "",
+    tag = "profiles",
+    request_body = CreateProfileRequest,
+    responses((status = 201, body = CreateProfileResponse))
+)]
+async fn create_profile(
+    State(profiles): State<Arc<Profiles>>,
+    Json(req): Json<CreateProfileRequest>,
+) -> ApiResult<(StatusCode, Json<CreateProfileResponse>)> {
+    let name = req.name.trim();
+    if profiles.exists(name) {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "profile_exists",
+            format!("Profile '{}' already exists", name),
+        ));
+    }
+    let state = profiles.create(name).map_err(|e| match e {
+        mindsage_core::Error::Config(message) => ApiError::bad_request(message),
+        e => e.into(),
+    })?;
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateProfileResponse {
+            success: true,
+            name: state.profile.clone(),
+            data_dir: state.config.data_paths.root.to_string_lossy().into_owned(),
+        }),
+    ))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DeleteProfileQuery {
+    /// Must repeat the profile name.
+    #[serde(default)]
+    confirm: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct DeleteProfileResponse {
+    success: bool,
+    name: String,
+}
+
+/// DELETE /api/profiles/{name}?confirm={name} — delete a profile and all of
+/// its data. The name must be repeated in `confirm`.
+#[utoipa::path(delete, path = "/{name}", tag = "profiles", params(DeleteProfileQuery), responses((status = 200, body = DeleteProfileResponse)))]
+async fn delete_profile(
+    State(profiles): State<Arc<Profiles>>,
+    Path(name): Path<String>,
+    Query(query): Query<DeleteProfileQuery>,
+) -> ApiResult<Json<DeleteProfileResponse>> {
+    if name == DEFAULT_PROFILE {
+        return Err(ApiError::bad_request("The default profile cannot be deleted"));
+    }
+    if !profiles.exists(&name) {
+        return Err(ApiError::not_found(format!("Profile '{}' not found", name)));
+    }
+    if query.confirm.as_deref() != Some(name.as_str()) {
+        return Err(ApiError::bad_request(format!(
+            "Deleting a profile removes all of its data; pass confirm={} to proceed",
+            name
+        )));
+    }
+    profiles.delete(&name).await?;
+    Ok(Json(DeleteProfileResponse { success: true, name }))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ProfileStatsResponse {
+    profile: String,
+    documents: i64,
+    chunks: i64,
+    embeddings: i64,
+    db_size_mb: f64,
+    /// Files in the profile's `uploads/`.
+    uploads: usize,
+}
+
+/// GET /api/profiles/{name}/stats — document and storage counts of a profile.
+#[utoipa::path(get, path = "/{name}/stats", tag = "profiles", responses((status = 200, body = ProfileStatsResponse)))]
+async fn profile_stats(
+    State(profiles): State<Arc<Profiles>>,
+    Path(name): Path<String>,
+) -> ApiResult<Json<ProfileStatsResponse>> {
+    let Some(state) = profiles.state(&name)? else {
+        return Err(ApiError::not_found(format!("Profile '{}' not found", name)));
+    };
+    let stats = state.db(|store| store.get_stats()).await?;
+    let uploads = std::fs::read_dir(&state.config.data_paths.uploads)
+        .map(|entries| entries.flatten().filter(|e| e.path().is_file()).count())
+        .unwrap_or(0);
+    Ok(Json(ProfileStatsResponse {
+        profile: state.profile.clone(),
+        documents: stats.total_documents,
+        chunks: stats.total_chunks,
+        embeddings: stats.embeddings_stored,
+        db_size_mb: stats.db_size_mb,
+        uploads,
+    }))
+}
+
+/// Fallback: route the request to the profile named by `X-Profile`, or the
+/// default profile.
+pub async fn dispatch(State(profiles): State<Arc<Profiles>>, req: Request) -> Response {
+    let name = req
+        .headers()
+        .get(PROFILE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+    forward(&profiles, &name, req).await
+}
+
+/// `/profiles/{profile}/...` — strip the prefix and route the rest of the
+/// path to that profile.
+pub async fn dispatch_prefixed(
+    State(profiles): State<Arc<Profiles>>,
+    Path((name, _)): Path<(String, String)>,
+    mut req: Request,
+) -> Response {
+    let prefix_len = "/profiles/".len() + name.len();
+    let rest = req
+        .uri()
+        .path_and_query()
+        .map(|pq| pq.as_str().get(prefix_len..).unwrap_or("/").to_string())
+        .unwrap_or_else(|| "/".to_string());
+    match rest.parse::<Uri>() {
+        Ok(uri) => *req.uri_mut() = uri,
+        Err(_) => return ApiError::bad_request("Invalid path").into_response(),
+    }
+    forward(&profiles, &name, req).await
+}
+
+async fn forward(profiles: &Profiles, name: &str, req: Request<Body>) -> Response {
+    match profiles.router(name) {
+        Ok(Some(router)) => match router.oneshot(req).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        },
+        Ok(None) => ApiError::not_found(format!("Profile '{}' not found", name)).into_response(),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::Request;
+    use tempfile::TempDir;
+
+    use crate::test_support::test_state;
+
+    fn test_app(dir: &TempDir) -> (Arc<Profiles>, Router) {
+        let state = test_state(dir);
+        let profiles = Arc::new(Profiles::start(state));
+        (profiles.clone(), crate::routes::build_app(profiles))
+    }
+
+    async fn call(app: &Router, method: &str, uri: &str, profile: Option<&str>, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        if let Some(profile) = profile {
+            req = req.header(PROFILE_HEADER, profile);
+        }
+        let response = app
+            .clone()
+            .oneshot(req.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    async fn search_hits(app: &Router, uri: &str, profile: Option<&str>, query: &str) -> usize {
+        let (status, body) = call(app, "POST", uri, profile, serde_json::json!({ "query": query })).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["results"].as_array().unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn test_profiles_keep_data_apart() {
+        let dir = TempDir::new().unwrap();
+        let (profiles, app) = test_app(&dir);
+
+        let (status, _) = call(&app, "POST", "/api/profiles", None, serde_json::json!({ "name": "alice" })).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = call(&app, "POST", "/api/profiles", None, serde_json::json!({ "name": "alice" })).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _) = call(&app, "POST", "/api/profiles", None, serde_json::json!({ "name": "../etc" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let note = |text: &str| serde_json::json!({ "text": text });
+        let (status, _) = call(&app, "POST", "/api/notes", Some("alice"), note("Alice's marathon training plan")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = call(&app, "POST", "/api/notes", None, note("Bob's sourdough starter schedule")).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        // Each profile only finds its own notes, by header or by path prefix
+        let search = "/api/vector-store/search";
+        assert_eq!(search_hits(&app, search, Some("alice"), "marathon").await, 1);
+        assert_eq!(search_hits(&app, search, Some("alice"), "sourdough").await, 0);
+        assert_eq!(search_hits(&app, search, None, "marathon").await, 0);
+        assert_eq!(search_hits(&app, search, None, "sourdough").await, 1);
+        assert_eq!(search_hits(&app, "/profiles/alice/api/vector-store/search", None, "marathon").await, 1);
+        assert_eq!(search_hits(&app, "/profiles/default/api/vector-store/search", Some("alice"), "marathon").await, 0);
+
+        let (status, _) = call(&app, "GET", "/api/stats", Some("nobody"), serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, stats) = call(&app, "GET", "/api/profiles/alice/stats", None, serde_json::Value::Null).await;
+        assert_eq!((stats["profile"].as_str(), stats["documents"].as_i64()), (Some("alice"), Some(1)));
+        let (_, list) = call(&app, "GET", "/api/profiles", None, serde_json::Value::Null).await;
+        let names: Vec<&str> = list["profiles"].as_array().unwrap().iter().map(|p| p["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["default", "alice"]);
+
+        // The shared LLM configuration is the same object in both profiles
+        let alice = profiles.state("alice").unwrap().unwrap();
+        assert!(Arc::ptr_eq(&alice.llm_config, &profiles.default_state().llm_config));
+    }
+
+    #[tokio::test]
+    async fn test_delete_requires_confirmation() {
+        let dir = TempDir::new().unwrap();
+        let (profiles, app) = test_app(&dir);
+        call(&app, "POST", "/api/profiles", None, serde_json::json!({ "name": "guest" })).await;
+        let data_dir = dir.path().join("profiles").join("guest");
+        assert!(data_dir.join("vectordb").exists());
+
+        let (status, _) = call(&app, "DELETE", "/api/profiles/guest", None, serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call(&app, "DELETE", "/api/profiles/default?confirm=default", None, serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call(&app, "DELETE", "/api/profiles/guest?confirm=guest", None, serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!data_dir.exists());
+        assert!(!profiles.exists("guest"));
+        let (status, _) = call(&app, "DELETE", "/api/profiles/guest?confirm=guest", None, serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,478 @@
+//! Runtime verb routes. `forget` deletes everything that mentions a term:
+//! the documents (with their chunks, embeddings and topic links), logged
+//! queries, saved searches, staged items, captured chat messages, audit
+//! entries and uploaded source files. A dry run previews the deletion and
+//! returns a confirmation token; the deletion only runs when the token
+//! still matches, so nothing is deleted that the preview did not show.
+//!
+//! `digest` summarizes the documents captured over the last days and stores
+//! the summary as a searchable document; `digests` lists the stored ones.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use mindsage_browser::ForgottenMessages;
+use mindsage_runtime::digest::{self, DEFAULT_DIGEST_DAYS};
+use mindsage_runtime::{Digest, DigestPeriod, ForgetPlan};
+use mindsage_store::IndexedFile;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest as _, Sha256};
+use tracing::{info, warn};
+use utoipa::{IntoParams, OpenApi};
+
+use crate::audit::ForgetTombstone;
+use crate::digests;
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Shortest term a forget accepts; shorter ones match too much.
+const MIN_FORGET_TERM_CHARS: usize = 3;
+
+/// Longest period a digest may cover.
+const MAX_DIGEST_DAYS: i64 = 366;
+
+/// Digests listed when no limit is given, and the most a request may ask for.
+const DEFAULT_DIGEST_LIMIT: usize = 20;
+const MAX_DIGEST_LIMIT: usize = 100;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/runtime/forget", post(forget).get(list_forgets))
+        .route("/runtime/digest", post(create_digest))
+        .route("/runtime/digests", get(list_digests))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(forget, list_forgets, create_digest, list_digests),
+    components(schemas(ForgetTombstone))
+)]
+pub struct RuntimeApi;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ForgetRequest {
+    query: String,
+    #[serde(default = "default_dry_run")]
+    dry_run: bool,
+    #[serde(default)]
+    confirmation_token: Option<String>,
+}
+
+fn default_dry_run() -> bool {
+    true
+}
+
+/// Everything a forget would delete.
+struct ForgetPreview {
+    plan: ForgetPlan,
+    chat: ForgottenMessages,
+    audit_entries: usize,
+    /// Source files under the data directory, removed with their documents.
+    files: Vec<IndexedFile>,
+    /// Source files elsewhere (watched folders), left on disk.
+    kept_files: Vec<IndexedFile>,
+}
+
+impl ForgetPreview {
+    fn counts(&self) -> serde_json::Value {
+        json!({
+            "store": self.plan.store,
+            "graph": self.plan.graph,
+            "cache": self.plan.cache,
+            "chat": {
+                "conversations": self.chat.conversations,
+                "conversationsDeleted": self.chat.conversations_deleted,
+                "messages": self.chat.messages,
+            },
+            "audit": { "entries": self.audit_entries },
+            "files": { "deleted": self.files.len(), "kept": self.kept_files.len() },
+        })
+    }
+
+    /// Hash of what the preview shows; a confirmation must present it.
+    fn token(&self) -> String {
+        let shown = json!({
+            "query": self.plan.query,
+            "documents": self.plan.doc_ids(),
+            "counts": self.counts(),
+        });
+        hex::encode(Sha256::digest(shown.to_string().as_bytes()))
+    }
+}
+
+fn preview(state: &AppState, query: &str) -> ApiResult<ForgetPreview> {
+    let plan = state.orchestrator.plan_forget(&state.store, query)?;
+    let chat = state.browser_manager.forget_mentions(query, true);
+    let chunk_ids: HashSet<i64> = plan.chunk_ids.iter().copied().collect();
+    let audit_entries = state
+        .audit
+        .forget(&chunk_ids, query, true)
+        .map_err(|e| ApiError::internal(format!("Failed to read audit log: {}", e)))?;
+    let (files, kept_files) = state
+        .store
+        .get_indexed_files_of(&plan.doc_ids())?
+        .into_iter()
+        .partition(|f| in_data_dir(state, &f.path));
+    Ok(ForgetPreview {
+        plan,
+        chat,
+        audit_entries,
+        files,
+        kept_files,
+    })
+}
+
+/// Whether `path` is inside the data directory (uploads, imports, exports).
+fn in_data_dir(state: &AppState, path: &str) -> bool {
+    let root = &state.config.data_paths.root;
+    let root = root.canonicalize().unwrap_or_else(|_| root.clone());
+    Path::new(path).canonicalize().is_ok_and(|p| p.starts_with(root))
+}
+
+/// POST /api/runtime/forget — preview (`dryRun`, the default) or delete
+/// everything that mentions `query`. Confirming requires the
+/// `confirmationToken` of a preview whose matches have not changed since.
+#[utoipa::path(
+    post,
+    path = "/runtime/forget",
+    tag = "runtime",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
+async fn forget(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ForgetRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let query = req.query.trim().to_string();
+    if query.chars().count() < MIN_FORGET_TERM_CHARS {
+        return Err(ApiError::bad_request(format!(
+            "query must be at least {} characters",
+            MIN_FORGET_TERM_CHARS
+        )));
+    }
+    if !req.dry_run && req.confirmation_token.is_none() {
+        return Err(ApiError::bad_request(
+            "confirmationToken is required; request a dry run first",
+        ));
+    }
+
+    state
+        .blocking(move |state| {
+            let preview = preview(state, &query)?;
+            let token = preview.token();
+            if req.dry_run {
+                return Ok(Json(json!({
+                    "dryRun": true,
+                    "query": query,
+                    "documents": preview.plan.documents,
+                    "counts": preview.counts(),
+                    "keptFiles": preview.kept_files.iter().map(|f| &f.path).collect::<Vec<_>>(),
+                    "confirmationToken": token,
+                })));
+            }
+            if req.confirmation_token.as_deref() != Some(token.as_str()) {
+                return Err(ApiError::new(
+                    StatusCode::CONFLICT,
+                    "confirmation_mismatch",
+                    "What would be forgotten changed since the dry run; preview again",
+                ));
+            }
+
+            let plan = state.orchestrator.forget(&state.store, &preview.plan)?;
+            let chat = state.browser_manager.forget_mentions(&query, false);
+            let chunk_ids: HashSet<i64> = preview.plan.chunk_ids.iter().copied().collect();
+            let audit_entries = state
+                .audit
+                .forget(&chunk_ids, &query, false)
+                .map_err(|e| ApiError::internal(format!("Failed to rewrite audit log: {}", e)))?;
+            for file in &preview.files {
+                if let Err(e) = std::fs::remove_file(&file.path) {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        warn!("Failed to remove forgotten file {}: {}", file.path, e);
+                    }
+                }
+                state.store.delete_indexed_file(&file.path)?;
+            }
+
+            let done = ForgetPreview {
+                plan,
+                chat,
+                audit_entries,
+                ..preview
+            };
+            let tombstone = ForgetTombstone {
+                forget_id: uuid::Uuid::new_v4().to_string(),
+                timestamp: chrono::Utc::now().timestamp_millis(),
+                counts: done.counts(),
+            };
+            state
+                .audit
+                .record_forget(&tombstone)
+                .map_err(|e| ApiError::internal(format!("Failed to record forget: {}", e)))?;
+            info!(
+                "Forgot {} documents ({})",
+                done.plan.store.documents, tombstone.forget_id
+            );
+            Ok(Json(json!({
+                "dryRun": false,
+                "forgetId": tombstone.forget_id,
+                "counts": tombstone.counts,
+            })))
+        })
+        .await
+}
+
+/// GET /api/runtime/forget — tombstones of past forgets, newest first.
+#[utoipa::path(
+    get,
+    path = "/runtime/forget",
+    tag = "runtime",
+    responses((status = 200, body = Vec<ForgetTombstone>))
+)]
+async fn list_forgets(State(state): State<Arc<AppState>>) -> ApiResult<Json<Vec<ForgetTombstone>>> {
+    let tombstones = state
+        .blocking(|state| state.audit.forget_tombstones())
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read audit log: {}", e)))?;
+    Ok(Json(tombstones))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DigestRequest {
+    /// Days covered, ending at `end` (default 7).
+    #[serde(default)]
+    days: Option<i64>,
+    /// End of the period in epoch ms (default now).
+    #[serde(default)]
+    end: Option<i64>,
+    /// Ask the configured LLM for a narrative (default true).
+    #[serde(default = "default_narrative")]
+    narrative: bool,
+}
+
+fn default_narrative() -> bool {
+    true
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DigestListQuery {
+    /// Most digests returned (default 20, at most 100).
+    limit: Option<usize>,
+}
+
+/// POST /api/runtime/digest — summarize the documents created in the last
+/// `days` days (default 7) before `end`: counts by source, top topics,
+/// notable entities, representative chunks and, when an LLM is configured
+/// and `narrative` is not false, a short narrative. A period with documents
+/// is stored as a document with `source = "digest"` (`docId`).
+#[utoipa::path(
+    post,
+    path = "/runtime/digest",
+    tag = "runtime",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
+async fn create_digest(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<DigestRequest>,
+) -> ApiResult<Json<Digest>> {
+    let days = req.days.unwrap_or(DEFAULT_DIGEST_DAYS);
+    if !(1..=MAX_DIGEST_DAYS).contains(&days) {
+        return Err(ApiError::bad_request(format!(
+            "days must be between 1 and {}",
+            MAX_DIGEST_DAYS
+        )));
+    }
+    let end = req.end.unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+    let period = DigestPeriod::days_before(end, days);
+    Ok(Json(digests::create_digest(&state, period, req.narrative).await?))
+}
+
+/// GET /api/runtime/digests — stored digests, newest first.
+#[utoipa::path(
+    get,
+    path = "/runtime/digests",
+    tag = "runtime",
+    params(DigestListQuery),
+    responses((status = 200, body = Object))
+)]
+async fn list_digests(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DigestListQuery>,
+) -> ApiResult<Json<Vec<Digest>>> {
+    let limit = query.limit.unwrap_or(DEFAULT_DIGEST_LIMIT).clamp(1, MAX_DIGEST_LIMIT);
+    Ok(Json(state.db(move |store| digest::list_digests(store, limit)).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use mindsage_browser::{CapturePayload, CapturedMessage};
+    use mindsage_chat::{ChatMessage, LLMProvider};
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    use crate::audit::{AuditPurpose, AuditQuery, AuditRequest};
+    use crate::test_support::test_state;
+
+    async fn call(app: &Router, method: &str, uri: &str, body: Option<serde_json::Value>) -> (StatusCode, serde_json::Value) {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn ingest(state: &AppState, text: &str, metadata: serde_json::Value) -> i64 {
+        state
+            .orchestrator
+            .ingest(&state.store, &state.embedder, text, text, &metadata, None)
+            .unwrap()
+            .doc_id
+    }
+
+    #[tokio::test]
+    async fn test_forget_leaves_nothing_findable() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let app = crate::routes::build_app(Arc::new(crate::profiles::Profiles::start(state.clone())));
+
+        // An uploaded file, a note, an unrelated note, a logged query, a
+        // captured chat and an audited prompt
+        std::fs::create_dir_all(&state.config.data_paths.uploads).unwrap();
+        let upload = state.config.data_paths.uploads.join("letter.txt");
+        let letter = "Dear Marguerite Okafor, thank you for the quince jam.";
+        std::fs::write(&upload, letter).unwrap();
+        let uploaded = ingest(&state, letter, json!({"filename": "letter.txt"}));
+        state.mark_file_indexed(upload.to_str().unwrap(), Some(uploaded));
+        ingest(&state, "Okafor, Marguerite: recipe swap next week.", json!({}));
+        let kept = ingest(&state, "The quince tree needs pruning in winter.", json!({}));
+        state.store.record_query("marguerite okafor jam", 1).unwrap();
+        state.browser_manager.process_capture(CapturePayload {
+            site: "claude".to_string(),
+            conversation_id: "conv".to_string(),
+            conversation_url: "https://claude.ai/chat/conv".to_string(),
+            title: Some("Thank-you notes".to_string()),
+            messages: ["Write a note to Marguerite Okafor", "Now one for the postman"]
+                .iter()
+                .enumerate()
+                .map(|(i, content)| CapturedMessage {
+                    id: format!("m{}", i),
+                    conversation_id: "conv".to_string(),
+                    role: "user".to_string(),
+                    content: content.to_string(),
+                    timestamp: "2025-01-01T00:00:00Z".to_string(),
+                    site: "claude".to_string(),
+                    metadata: None,
+                })
+                .collect(),
+            full_conversation: Some(true),
+            previous_id: None,
+        });
+        let uploaded_chunks: Vec<i64> = state.store.get_chunks_for_document(uploaded).unwrap().iter().map(|c| c.id).collect();
+        let prompt = vec![ChatMessage {
+            role: "user".into(),
+            content: "what jam did I get?".into(),
+        }];
+        state
+            .audit
+            .record(AuditRequest {
+                purpose: AuditPurpose::Chat,
+                provider: LLMProvider::Groq,
+                model: "m",
+                messages: &prompt,
+                chunk_ids: uploaded_chunks,
+                max_tokens: 100,
+                anonymized: false,
+            })
+            .unwrap();
+
+        let (status, _) = call(&app, "POST", "/api/runtime/forget", Some(json!({"query": "mo"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, preview) = call(&app, "POST", "/api/runtime/forget", Some(json!({"query": "Marguerite Okafor"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(preview["documents"].as_array().unwrap().len(), 2);
+        let counts = &preview["counts"];
+        assert_eq!(counts["store"]["documents"], 2);
+        assert_eq!(counts["cache"]["searchQueries"], 1);
+        assert_eq!(counts["chat"]["messages"], 1);
+        assert_eq!(counts["audit"]["entries"], 1);
+        assert_eq!(counts["files"]["deleted"], 1);
+        assert_eq!(state.store.count_documents().unwrap(), 3);
+
+        let confirm = |token: &str| json!({"query": "Marguerite Okafor", "dryRun": false, "confirmationToken": token});
+        let (status, _) = call(&app, "POST", "/api/runtime/forget", Some(confirm("stale"))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let token = preview["confirmationToken"].as_str().unwrap();
+        let (status, done) = call(&app, "POST", "/api/runtime/forget", Some(confirm(token))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(done["counts"], *counts);
+
+        // No trace left: search, suggestions, files, chats and the audit log
+        let (_, search) = call(&app, "POST", "/api/vector-store/search", Some(json!({"query": "Marguerite Okafor"}))).await;
+        assert!(search["results"].as_array().unwrap().iter().all(|r| r["doc_id"] == kept));
+        let (_, suggest) = call(&app, "GET", "/api/vector-store/suggest?q=margu", None).await;
+        assert!(!suggest.to_string().to_lowercase().contains("marguerite"));
+        assert_eq!(state.store.count_documents().unwrap(), 1);
+        assert!(!upload.exists());
+        assert!(state.store.get_indexed_files_of(&[uploaded]).unwrap().is_empty());
+        let conv = state.browser_manager.get_conversation("conv").unwrap();
+        assert!(conv.messages.iter().all(|m| !m.content.contains("Marguerite")));
+        assert_eq!(state.audit.list(&AuditQuery::default()).unwrap().1, 0);
+
+        let (_, tombstones) = call(&app, "GET", "/api/runtime/forget", None).await;
+        assert_eq!(tombstones[0]["forgetId"], done["forgetId"]);
+        assert!(!tombstones.to_string().contains("Marguerite"));
+        let (_, again) = call(&app, "POST", "/api/runtime/forget", Some(json!({"query": "Marguerite Okafor"}))).await;
+        assert_eq!(again["counts"]["store"]["documents"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_digest_is_stored_and_listed() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let app = crate::routes::build_app(Arc::new(crate::profiles::Profiles::start(state.clone())));
+        ingest(&state, "Pruned the quince tree and planted garlic for next summer.", json!({"source": "upload"}));
+        ingest(&state, "Saved an article on composting kitchen scraps.", json!({"source": "browser"}));
+
+        let (status, _) = call(&app, "POST", "/api/runtime/digest", Some(json!({"days": 0}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, digest) = call(&app, "POST", "/api/runtime/digest", Some(json!({"narrative": false}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(digest["documents"], 2);
+        assert_eq!(digest["sources"].as_array().unwrap().len(), 2);
+        assert!(digest.get("narrative").is_none());
+        let doc_id = digest["docId"].as_i64().unwrap();
+
+        // Searchable, listed, and not counted by the next digest
+        let hits = state.store.bm25_search("digest quince", None, 10).unwrap();
+        assert!(hits.iter().any(|h| h.doc_id == doc_id));
+        let (status, listed) = call(&app, "GET", "/api/runtime/digests?limit=5", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(listed[0]["docId"], doc_id);
+        assert_eq!(listed[0]["period"], digest["period"]);
+        let (_, next) = call(&app, "POST", "/api/runtime/digest", Some(json!({"narrative": false}))).await;
+        assert_eq!(next["documents"], 2);
+
+        // An empty period is reported but not stored
+        let (_, empty) = call(&app, "POST", "/api/runtime/digest", Some(json!({"days": 1, "end": 1_000_000_000_000i64}))).await;
+        assert_eq!(empty["documents"], 0);
+        assert!(empty.get("docId").is_none());
+    }
+}
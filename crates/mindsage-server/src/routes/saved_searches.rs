@@ -0,0 +1,165 @@
+//! Saved search routes — persist queries and read their new matches.
+//! Searches are re-run by the indexing worker (see `saved_searches.rs`).
+
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi};
+
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Largest `top_k` a saved search may request.
+const MAX_SAVED_SEARCH_TOP_K: usize = 100;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/vector-store/saved-searches",
+            get(list_saved_searches).post(create_saved_search),
+        )
+        .route("/vector-store/saved-searches/{id}", delete(delete_saved_search))
+        .route("/vector-store/saved-searches/{id}/matches", get(get_matches))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    list_saved_searches,
+    create_saved_search,
+    delete_saved_search,
+    get_matches,
+))]
+pub struct SavedSearchesApi;
+
+#[derive(Deserialize)]
+struct CreateSavedSearchRequest {
+    query: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    filters: Option<serde_json::Value>,
+    #[serde(default)]
+    top_k: Option<usize>,
+}
+
+/// POST /api/vector-store/saved-searches — save a query, filters, and top_k.
+#[utoipa::path(
+    post,
+    path = "/vector-store/saved-searches",
+    tag = "vector-store",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
+async fn create_saved_search(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateSavedSearchRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let query = req.query.trim();
+    if query.is_empty() {
+        return Err(ApiError::bad_request("query is required"));
+    }
+    if let Some(filters) = &req.filters {
+        if !filters.is_object() && !filters.is_null() {
+            return Err(ApiError::bad_request("filters must be an object"));
+        }
+    }
+
+    let query = query.to_string();
+    let name = req
+        .name
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map_or_else(|| query.clone(), str::to_string);
+    let top_k = req.top_k.unwrap_or(10).clamp(1, MAX_SAVED_SEARCH_TOP_K);
+    let filters = req.filters.filter(|f| !f.is_null());
+
+    let saved = state
+        .db(move |store| store.create_saved_search(&name, &query, filters.as_ref(), top_k))
+        .await?;
+    Ok(Json(serde_json::json!({ "savedSearch": saved })))
+}
+
+/// GET /api/vector-store/saved-searches — all saved searches with new-match counts.
+#[utoipa::path(
+    get,
+    path = "/vector-store/saved-searches",
+    tag = "vector-store",
+    responses((status = 200, body = Object))
+)]
+async fn list_saved_searches(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let searches = state.db(|store| store.list_saved_searches()).await?;
+    Ok(Json(serde_json::json!({
+        "savedSearches": searches,
+        "total": searches.len(),
+    })))
+}
+
+/// DELETE /api/vector-store/saved-searches/:id
+#[utoipa::path(
+    delete,
+    path = "/vector-store/saved-searches/{id}",
+    tag = "vector-store",
+    responses((status = 200, body = Object))
+)]
+async fn delete_saved_search(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<serde_json::Value>> {
+    if !state.db(move |store| store.delete_saved_search(id)).await? {
+        return Err(ApiError::not_found("Saved search not found"));
+    }
+    Ok(Json(serde_json::json!({ "deleted": true, "id": id })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MatchesQuery {
+    /// Include previously acknowledged matches.
+    all: Option<bool>,
+    /// Reset the new-match counter after reading.
+    ack: Option<bool>,
+}
+
+/// GET /api/vector-store/saved-searches/:id/matches — new matches (or all with `all=true`).
+#[utoipa::path(
+    get,
+    path = "/vector-store/saved-searches/{id}/matches",
+    tag = "vector-store",
+    responses((status = 200, body = Object))
+)]
+async fn get_matches(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(params): Query<MatchesQuery>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let new_only = !params.all.unwrap_or(false);
+    let ack = params.ack.unwrap_or(false);
+    let (saved, matches) = state
+        .db(move |store| -> ApiResult<_> {
+            let saved = store
+                .get_saved_search(id)?
+                .ok_or_else(|| ApiError::not_found("Saved search not found"))?;
+            let matches = store.get_saved_search_matches(id, new_only)?;
+            if ack {
+                store.acknowledge_saved_search(id)?;
+            }
+            Ok((saved, matches))
+        })
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "searchId": saved.id,
+        "name": saved.name,
+        "query": saved.query,
+        "newMatches": saved.new_matches,
+        "lastCheckedAt": saved.last_checked_at,
+        "matches": matches,
+        "total": matches.len(),
+    })))
+}
@@ -0,0 +1,415 @@
+//! Universal search — one query over indexed documents, captured
+//! conversations and file names.
+//!
+//! The three searches run concurrently, each with a result limit and the
+//! request's time budget. Scores of different searches mean different
+//! things (fused ranks, term counts, name matches), so each group's scores
+//! are min-max normalized to 0–1 and multiplied by the group's weight
+//! before the groups are merged. A group that fails or runs out of time
+//! contributes no results and a warning; the others are still returned.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::{Query, State};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi};
+
+use crate::error::{ApiError, ApiResult, ErrorBody};
+use crate::routes::files::collect_files;
+use crate::routes::vector_store::run_search;
+use crate::state::AppState;
+use mindsage_api_types::{
+    SearchRequest, UniversalResultType, UniversalSearchGroup, UniversalSearchResponse, UniversalSearchResult,
+};
+use mindsage_browser::{ConversationSearch, SearchSort};
+
+/// Results per group unless `limit` says otherwise, and the most allowed.
+const DEFAULT_GROUP_LIMIT: usize = 10;
+const MAX_GROUP_LIMIT: usize = 50;
+/// Results returned across groups unless `total` says otherwise, and the
+/// most allowed.
+const DEFAULT_TOTAL: usize = 30;
+const MAX_TOTAL: usize = 100;
+/// Time each group may take unless `budget_ms` says otherwise, and the
+/// most allowed.
+const DEFAULT_BUDGET_MS: u64 = 2_000;
+const MAX_BUDGET_MS: u64 = 10_000;
+
+/// Weights of the groups' normalized scores: a document chunk that matches
+/// as well as a file name ranks above it.
+const DOCUMENT_WEIGHT: f64 = 1.0;
+const CONVERSATION_WEIGHT: f64 = 0.9;
+const FILE_WEIGHT: f64 = 0.7;
+
+/// Characters of a result's text kept as its snippet.
+const SNIPPET_CHARS: usize = 200;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/search/universal", get(universal_search))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(universal_search))]
+pub struct SearchApi;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct UniversalSearchQuery {
+    #[serde(default)]
+    q: String,
+    /// Results per group, at most 50 (default 10).
+    limit: Option<usize>,
+    /// Results across groups, at most 100 (default 30).
+    total: Option<usize>,
+    /// Time each group may take, at most 10000 (default 2000).
+    budget_ms: Option<u64>,
+}
+
+/// GET /api/search/universal?q= — documents, captured conversations and
+/// files matching `q`, best first across groups.
+#[utoipa::path(
+    get,
+    path = "/search/universal",
+    tag = "search",
+    params(UniversalSearchQuery),
+    responses(
+        (status = 200, body = UniversalSearchResponse),
+        (status = 400, description = "Empty query", body = ErrorBody),
+    )
+)]
+async fn universal_search(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<UniversalSearchQuery>,
+) -> ApiResult<Json<UniversalSearchResponse>> {
+    let q = query.q.trim().to_string();
+    if q.is_empty() {
+        return Err(ApiError::bad_request("Query is empty"));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_GROUP_LIMIT).clamp(1, MAX_GROUP_LIMIT);
+    let total = query.total.unwrap_or(DEFAULT_TOTAL).clamp(1, MAX_TOTAL);
+    let budget = Duration::from_millis(query.budget_ms.unwrap_or(DEFAULT_BUDGET_MS).clamp(1, MAX_BUDGET_MS));
+
+    let (documents, conversations, files) = {
+        let (q1, q2, q3) = (q.clone(), q.clone(), q.clone());
+        tokio::join!(
+            run_group(UniversalResultType::Document, DOCUMENT_WEIGHT, budget, async {
+                state.blocking(move |state| search_documents(state, &q1, limit)).await
+            }),
+            run_group(UniversalResultType::Conversation, CONVERSATION_WEIGHT, budget, async {
+                state.blocking(move |state| Ok(search_conversations(state, &q2, limit))).await
+            }),
+            run_group(UniversalResultType::File, FILE_WEIGHT, budget, async {
+                state.blocking(move |state| Ok(search_files(state, &q3, limit))).await
+            }),
+        )
+    };
+    Ok(Json(merge_groups(q, vec![documents, conversations, files], total)))
+}
+
+/// A group's results, raw scores, or why it has none.
+struct GroupOutcome {
+    result_type: UniversalResultType,
+    weight: f64,
+    took_ms: u64,
+    results: Result<Vec<UniversalSearchResult>, String>,
+}
+
+async fn run_group(
+    result_type: UniversalResultType,
+    weight: f64,
+    budget: Duration,
+    search: impl Future<Output = Result<Vec<UniversalSearchResult>, String>>,
+) -> GroupOutcome {
+    let started = Instant::now();
+    let results = match tokio::time::timeout(budget, search).await {
+        Ok(results) => results,
+        Err(_) => Err(format!("Timed out after {} ms", budget.as_millis())),
+    };
+    if let Err(e) = &results {
+        tracing::warn!("Universal search: {:?} group failed: {}", result_type, e);
+    }
+    GroupOutcome {
+        result_type,
+        weight,
+        took_ms: started.elapsed().as_millis() as u64,
+        results,
+    }
+}
+
+/// Normalize each group's scores, weight them, and keep the best `total`
+/// results across groups. Ties keep group order.
+fn merge_groups(query: String, outcomes: Vec<GroupOutcome>, total: usize) -> UniversalSearchResponse {
+    let mut groups = Vec::new();
+    let mut results = Vec::new();
+    for outcome in outcomes {
+        let (mut found, warning) = match outcome.results {
+            Ok(found) => (found, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        let min = found.iter().map(|r| r.raw_score).fold(f64::INFINITY, f64::min);
+        let max = found.iter().map(|r| r.raw_score).fold(f64::NEG_INFINITY, f64::max);
+        for result in &mut found {
+            let normalized = if max > min { (result.raw_score - min) / (max - min) } else { 1.0 };
+            result.score = normalized * outcome.weight;
+        }
+        groups.push(UniversalSearchGroup {
+            result_type: outcome.result_type,
+            weight: outcome.weight,
+            count: found.len(),
+            returned: 0,
+            took_ms: outcome.took_ms,
+            warning,
+        });
+        results.extend(found);
+    }
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results.truncate(total);
+    for group in &mut groups {
+        group.returned = results.iter().filter(|r| r.result_type == group.result_type).count();
+    }
+    UniversalSearchResponse {
+        query,
+        partial: groups.iter().any(|g| g.warning.is_some()),
+        results,
+        groups,
+    }
+}
+
+fn result(result_type: UniversalResultType, raw_score: f64, title: String) -> UniversalSearchResult {
+    UniversalSearchResult {
+        result_type,
+        score: 0.0,
+        raw_score,
+        title,
+        snippet: None,
+        doc_id: None,
+        chunk_id: None,
+        conversation_id: None,
+        message_id: None,
+        site: None,
+        url: None,
+        filename: None,
+        location: None,
+    }
+}
+
+/// Hybrid (or BM25) search, as `/vector-store/search` runs it, titled by
+/// each document's title or file name.
+fn search_documents(state: &AppState, query: &str, limit: usize) -> Result<Vec<UniversalSearchResult>, String> {
+    let mut request = SearchRequest::new(query);
+    request.top_k = limit;
+    let Json(response) = run_search(state, request).map_err(|e| e.message)?;
+    Ok(response
+        .results
+        .into_iter()
+        .map(|hit| {
+            let metadata = state.store.get_document(hit.doc_id).ok().flatten().and_then(|d| d.metadata);
+            let title = metadata
+                .as_ref()
+                .and_then(|m| m.get("title").or_else(|| m.get("filename")))
+                .and_then(|t| t.as_str())
+                .unwrap_or("Untitled document")
+                .to_string();
+            let mut found = result(UniversalResultType::Document, hit.score, title);
+            found.snippet = Some(snippet(&hit.text));
+            found.doc_id = Some(hit.doc_id);
+            found.chunk_id = Some(hit.chunk_id);
+            found.url = metadata.as_ref().and_then(|m| m.get("url")).and_then(|u| u.as_str()).map(str::to_string);
+            found
+        })
+        .collect())
+}
+
+/// Captured conversations by relevance, as
+/// `/browser-connector/conversations/search` ranks them.
+fn search_conversations(state: &AppState, query: &str, limit: usize) -> Vec<UniversalSearchResult> {
+    let search = ConversationSearch {
+        query: query.to_string(),
+        site: None,
+        updated_after: None,
+        updated_before: None,
+        sort: SearchSort::Relevance,
+        limit,
+    };
+    state
+        .browser_manager
+        .search_conversations(&search)
+        .results
+        .into_iter()
+        .map(|hit| {
+            let conversation = hit.conversation;
+            let title = conversation.title.unwrap_or_else(|| "Untitled conversation".to_string());
+            let mut found = result(UniversalResultType::Conversation, hit.score as f64, title);
+            let first = hit.snippets.into_iter().next();
+            found.snippet = first.as_ref().map(|s| s.snippet.clone());
+            found.message_id = first.map(|s| s.message_id);
+            found.conversation_id = Some(conversation.id);
+            found.site = Some(conversation.site);
+            found.url = Some(conversation.url);
+            found
+        })
+        .collect()
+}
+
+/// Uploaded and imported files whose names contain query words: the share
+/// of words found, plus one when the name has the whole query. Newest
+/// first among equals.
+fn search_files(state: &AppState, query: &str, limit: usize) -> Vec<UniversalSearchResult> {
+    let phrase = query.to_lowercase();
+    let terms: Vec<&str> = phrase.split_whitespace().collect();
+    let mut found: Vec<UniversalSearchResult> = collect_files(state)
+        .files
+        .into_iter()
+        .filter_map(|file| {
+            let name = file.filename.to_lowercase();
+            let matched = terms.iter().filter(|t| name.contains(**t)).count();
+            if matched == 0 {
+                return None;
+            }
+            let score = matched as f64 / terms.len() as f64 + f64::from(u8::from(name.contains(&phrase)));
+            let mut found = result(UniversalResultType::File, score, file.filename.clone());
+            found.doc_id = state.store.get_indexed_file(&file.path).ok().flatten().and_then(|f| f.doc_id);
+            found.filename = Some(file.filename);
+            found.location = Some(file.location.to_string());
+            Some(found)
+        })
+        .collect();
+    found.sort_by(|a, b| b.raw_score.total_cmp(&a.raw_score));
+    found.truncate(limit);
+    found
+}
+
+fn snippet(text: &str) -> String {
+    match text.char_indices().nth(SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mindsage_browser::{CapturePayload, CapturedMessage};
+    use mindsage_ingest::Ingester;
+    use tempfile::TempDir;
+
+    use crate::test_support::test_state;
+
+    fn capture(state: &AppState, id: &str, title: &str, content: &str) {
+        state.browser_manager.process_capture(CapturePayload {
+            site: "claude".to_string(),
+            conversation_id: id.to_string(),
+            conversation_url: format!("https://claude.ai/chat/{}", id),
+            title: Some(title.to_string()),
+            messages: vec![CapturedMessage {
+                id: format!("{}-m1", id),
+                conversation_id: id.to_string(),
+                role: "user".to_string(),
+                content: content.to_string(),
+                timestamp: "2025-01-01T00:00:00Z".to_string(),
+                site: "claude".to_string(),
+                metadata: None,
+            }],
+            full_conversation: Some(true),
+            previous_id: None,
+        });
+    }
+
+    async fn search(state: &Arc<AppState>, q: &str) -> UniversalSearchResponse {
+        let query = UniversalSearchQuery {
+            q: q.to_string(),
+            limit: None,
+            total: None,
+            budget_ms: None,
+        };
+        universal_search(State(state.clone()), Query(query)).await.unwrap().0
+    }
+
+    #[tokio::test]
+    async fn test_universal_search_covers_every_group() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let ingester = Ingester::new(&state.store);
+        for (text, title) in [
+            ("Prune roses in late winter, above an outward bud.", "Roses"),
+            ("Tomatoes need staking once they flower.", "Tomatoes"),
+        ] {
+            let hash = mindsage_ingest::ingest::content_hash(text);
+            ingester
+                .ingest_text(text, &hash, &serde_json::json!({ "title": title }), None)
+                .unwrap();
+        }
+        capture(&state, "c1", "Rose care", "How hard should I prune climbing roses?");
+        capture(&state, "c2", "Dinner", "What goes with roasted squash?");
+        std::fs::write(state.config.data_paths.uploads.join("roses-pruning.pdf"), b"%PDF").unwrap();
+        std::fs::write(state.config.data_paths.imports.join("tomato-notes.txt"), b"notes").unwrap();
+
+        let response = search(&state, "roses").await;
+        assert!(!response.partial);
+        assert_eq!(response.groups.len(), 3);
+        let of = |t| response.results.iter().filter(|r| r.result_type == t).collect::<Vec<_>>();
+
+        let documents = of(UniversalResultType::Document);
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].title, "Roses");
+        assert!(documents[0].doc_id.is_some() && documents[0].chunk_id.is_some());
+        let conversations = of(UniversalResultType::Conversation);
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].conversation_id.as_deref(), Some("c1"));
+        assert_eq!(conversations[0].message_id.as_deref(), Some("c1-m1"));
+        let files = of(UniversalResultType::File);
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            (files[0].filename.as_deref(), files[0].location.as_deref()),
+            (Some("roses-pruning.pdf"), Some("uploads"))
+        );
+
+        // The best hit of each group scores its weight
+        let scores: Vec<f64> = response.results.iter().map(|r| r.score).collect();
+        assert_eq!(scores, vec![DOCUMENT_WEIGHT, CONVERSATION_WEIGHT, FILE_WEIGHT]);
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["results"][0]["type"], "document");
+    }
+
+    #[test]
+    fn test_failed_group_warns_and_budget_caps_results() {
+        let hit = |t, raw| result(t, raw, "hit".to_string());
+        let outcomes = vec![
+            GroupOutcome {
+                result_type: UniversalResultType::Document,
+                weight: DOCUMENT_WEIGHT,
+                took_ms: 3,
+                results: Ok(vec![
+                    hit(UniversalResultType::Document, 3.0),
+                    hit(UniversalResultType::Document, 2.0),
+                    hit(UniversalResultType::Document, 1.0),
+                ]),
+            },
+            GroupOutcome {
+                result_type: UniversalResultType::Conversation,
+                weight: CONVERSATION_WEIGHT,
+                took_ms: 2_000,
+                results: Err("Timed out after 2000 ms".to_string()),
+            },
+            GroupOutcome {
+                result_type: UniversalResultType::File,
+                weight: FILE_WEIGHT,
+                took_ms: 1,
+                results: Ok(vec![hit(UniversalResultType::File, 2.0), hit(UniversalResultType::File, 1.0)]),
+            },
+        ];
+        let response = merge_groups("roses".to_string(), outcomes, 3);
+        assert!(response.partial);
+        assert_eq!(response.groups[1].warning.as_deref(), Some("Timed out after 2000 ms"));
+        // 1.0 and 0.5 for documents, 0.7 for the best file; the rest is over budget
+        let scores: Vec<f64> = response.results.iter().map(|r| r.score).collect();
+        assert_eq!(scores, vec![1.0, 0.7, 0.5]);
+        let counts: Vec<(usize, usize)> = response.groups.iter().map(|g| (g.count, g.returned)).collect();
+        assert_eq!(counts, vec![(3, 2), (0, 0), (2, 1)]);
+    }
+}
@@ -1,33 +1,102 @@
-//! Stats and server info routes.
+//! Stats, readiness and server info routes.
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
-use axum::extract::State;
+use axum::extract::{Query, State};
 use axum::routing::get;
 use axum::{Json, Router};
+use mindsage_core::QueryStatsConfig;
+use mindsage_runtime::{ConsolidationRun, RuntimeStatus};
+use mindsage_store::matrix::MatrixMode;
+use mindsage_store::{LoggedQuery, QueryStats, SourceStats};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
+use crate::error::ApiResult;
+use crate::health::SubsystemHealth;
+use crate::quota::{self, DiskReport};
 use crate::state::AppState;
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/stats", get(get_stats))
+        .route("/stats/sources", get(get_source_stats))
+        .route("/stats/background", get(get_background_stats))
+        .route("/stats/runtime", get(get_runtime_stats))
+        .route("/stats/disk", get(get_disk_stats))
+        .route("/stats/queries", get(get_query_stats).delete(purge_query_stats))
+        .route("/stats/queries/slow", get(get_slow_queries))
+        .route("/health/ready", get(get_readiness))
         .route("/server-info", get(get_server_info))
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(get_stats, get_source_stats, get_background_stats, get_runtime_stats, get_disk_stats, get_query_stats, get_slow_queries, purge_query_stats, get_readiness, get_server_info))]
+pub struct StatsApi;
+
+#[derive(Serialize, ToSchema)]
+struct IndexingQueueCounts {
+    queued: usize,
+    processing: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct StatsResponse {
+    /// Profile the request was served from.
+    profile: String,
+    documents: i64,
+    chunks: i64,
+    paragraph_chunks: i64,
+    section_chunks: i64,
+    embeddings: i64,
+    /// Stored embeddings per producing model id.
+    embeddings_by_model: BTreeMap<String, i64>,
+    /// Embeddings whose chunk text was edited since, awaiting re-embedding.
+    text_stale_embeddings: i64,
+    embedding_model: String,
+    embedding_dimension: usize,
+    db_size_mb: f64,
+    matrix_loaded: bool,
+    matrix_rows: usize,
+    matrix_mode: MatrixMode,
+    matrix_bytes: usize,
+    /// Files in `data/uploads/`.
+    uploads: usize,
+    /// Files in `data/imports/`.
+    imports: usize,
+    indexing_queue: IndexingQueueCounts,
+    /// Rate limiting state and throttle counters per bucket.
+    #[schema(value_type = Object)]
+    rate_limit: serde_json::Value,
+    /// Whether the server runs in read-only mode (`MINDSAGE_READ_ONLY`).
+    read_only: bool,
+}
+
 /// GET /api/stats — storage statistics.
-async fn get_stats(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
-    let store_stats = state.store.get_stats().unwrap_or_else(|_| {
+#[utoipa::path(get, path = "/stats", tag = "stats", responses((status = 200, body = StatsResponse)))]
+async fn get_stats(State(state): State<Arc<AppState>>) -> Json<StatsResponse> {
+    let store_stats = state.db(|store| store.get_stats()).await.unwrap_or_else(|_| {
         mindsage_store::StoreStats {
             total_documents: 0,
             total_chunks: 0,
             paragraph_chunks: 0,
             section_chunks: 0,
             embeddings_stored: 0,
+            embeddings_by_model: Default::default(),
+            text_stale_embeddings: 0,
+            embedding_model: state.embedder.model_id().to_string(),
             embedding_dimension: state.config.embedding_dim,
             db_path: String::new(),
             db_size_mb: 0.0,
             matrix_loaded: false,
             matrix_rows: 0,
+            matrix_mode: Default::default(),
+            matrix_bytes: 0,
+            encryption: None,
+            corrupt_rows: 0,
+            dimension_mismatches: 0,
         }
     });
 
@@ -39,39 +108,227 @@ async fn get_stats(State(state): State<Arc<AppState>>) -> Json<serde_json::Value
     let queued = jobs.values().filter(|j| j.status == crate::state::IndexingStatus::Queued).count();
     let processing = jobs.values().filter(|j| j.status == crate::state::IndexingStatus::Processing).count();
 
-    Json(serde_json::json!({
-        "documents": store_stats.total_documents,
-        "chunks": store_stats.total_chunks,
-        "paragraphChunks": store_stats.paragraph_chunks,
-        "sectionChunks": store_stats.section_chunks,
-        "embeddings": store_stats.embeddings_stored,
-        "embeddingDimension": store_stats.embedding_dimension,
-        "dbSizeMb": store_stats.db_size_mb,
-        "matrixLoaded": store_stats.matrix_loaded,
-        "matrixRows": store_stats.matrix_rows,
-        "uploads": upload_count,
-        "imports": import_count,
-        "indexingQueue": {
-            "queued": queued,
-            "processing": processing,
-        },
+    Json(StatsResponse {
+        profile: state.profile.clone(),
+        documents: store_stats.total_documents,
+        chunks: store_stats.total_chunks,
+        paragraph_chunks: store_stats.paragraph_chunks,
+        section_chunks: store_stats.section_chunks,
+        embeddings: store_stats.embeddings_stored,
+        embeddings_by_model: store_stats.embeddings_by_model,
+        text_stale_embeddings: store_stats.text_stale_embeddings,
+        embedding_model: store_stats.embedding_model,
+        embedding_dimension: store_stats.embedding_dimension,
+        db_size_mb: store_stats.db_size_mb,
+        matrix_loaded: store_stats.matrix_loaded,
+        matrix_rows: store_stats.matrix_rows,
+        matrix_mode: store_stats.matrix_mode,
+        matrix_bytes: store_stats.matrix_bytes,
+        uploads: upload_count,
+        imports: import_count,
+        indexing_queue: IndexingQueueCounts { queued, processing },
+        rate_limit: state.rate_limiter.stats(),
+        read_only: state.config.read_only,
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct SourceStatsResponse {
+    /// Storage per document source, largest first; documents without a
+    /// source are counted as `unknown`.
+    sources: Vec<SourceStats>,
+    db_size_mb: f64,
+    /// Recent consolidation runs, newest first.
+    #[schema(value_type = Vec<Object>)]
+    consolidations: Vec<ConsolidationRun>,
+}
+
+/// GET /api/stats/sources — how documents, chunks, embeddings and bytes
+/// split across sources (computed at most every 30 seconds).
+#[utoipa::path(get, path = "/stats/sources", tag = "stats", responses((status = 200, body = SourceStatsResponse)))]
+async fn get_source_stats(State(state): State<Arc<AppState>>) -> ApiResult<Json<SourceStatsResponse>> {
+    let sources = state.db(|store| store.get_source_breakdown()).await?;
+    let db_size_mb = std::fs::metadata(&state.config.data_paths.vectordb)
+        .map(|m| m.len() as f64 / (1024.0 * 1024.0))
+        .unwrap_or(0.0);
+
+    Ok(Json(SourceStatsResponse {
+        sources,
+        db_size_mb,
+        consolidations: state.orchestrator.consolidation_history(),
+    }))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct BackgroundStatsResponse {
+    /// Background subsystems that have run, by name.
+    subsystems: BTreeMap<String, SubsystemHealth>,
+}
+
+/// GET /api/stats/background — last run, last success and last error of
+/// each background task.
+#[utoipa::path(get, path = "/stats/background", tag = "stats", responses((status = 200, body = BackgroundStatsResponse)))]
+async fn get_background_stats(State(state): State<Arc<AppState>>) -> Json<BackgroundStatsResponse> {
+    Json(BackgroundStatsResponse {
+        subsystems: state.health.snapshot(),
+    })
+}
+
+/// GET /api/stats/runtime — the orchestrator's tier and resource budget,
+/// with estimated memory use: the resident consumers and the batches in
+/// flight against `maxMemoryMb`.
+#[utoipa::path(get, path = "/stats/runtime", tag = "stats", responses((status = 200, body = RuntimeStatus)))]
+async fn get_runtime_stats(State(state): State<Arc<AppState>>) -> Json<RuntimeStatus> {
+    let status = state
+        .blocking(|state| {
+            state.orchestrator.sample_memory(&state.store, state.embedder.as_ref());
+            state.orchestrator.status()
+        })
+        .await;
+    Json(status)
+}
+
+/// GET /api/stats/disk — bytes used by each data area against its
+/// quota, and the share of the combined budget in use.
+#[utoipa::path(get, path = "/stats/disk", tag = "stats", responses((status = 200, body = DiskReport)))]
+async fn get_disk_stats(State(state): State<Arc<AppState>>) -> Json<DiskReport> {
+    Json(state.blocking(quota::disk_report).await)
+}
+
+/// Default days of searches aggregated by `GET /api/stats/queries`.
+const DEFAULT_QUERY_STATS_DAYS: u32 = 7;
+/// Most top queries listed by `GET /api/stats/queries`.
+const MAX_TOP_QUERIES: usize = 100;
+/// Most searches listed by `GET /api/stats/queries/slow`.
+const MAX_SLOW_QUERIES: usize = 500;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct QueryStatsQuery {
+    /// Searches of the last this many days (7 by default).
+    days: Option<u32>,
+    /// Top queries listed (20 by default, at most 100).
+    top: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SlowQueriesQuery {
+    /// 50 by default, at most 500.
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct QueryStatsResponse {
+    /// How searches are being recorded (`MINDSAGE_QUERY_STATS*`).
+    config: QueryStatsConfig,
+    #[serde(flatten)]
+    stats: QueryStats,
+}
+
+#[derive(Serialize, ToSchema)]
+struct SlowQueriesResponse {
+    queries: Vec<LoggedQuery>,
+    total: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+struct PurgeQueryStatsResponse {
+    deleted: usize,
+}
+
+/// GET /api/stats/queries — searches recorded in the query stats log:
+/// count, zero-result rate, latency percentiles, counts per search type
+/// and the most frequent queries. Empty unless `MINDSAGE_QUERY_STATS` is on.
+#[utoipa::path(get, path = "/stats/queries", tag = "stats", params(QueryStatsQuery), responses((status = 200, body = QueryStatsResponse)))]
+async fn get_query_stats(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<QueryStatsQuery>,
+) -> ApiResult<Json<QueryStatsResponse>> {
+    let days = params.days.unwrap_or(DEFAULT_QUERY_STATS_DAYS);
+    let since = chrono::Utc::now().timestamp_millis() - i64::from(days) * 86_400_000;
+    let top = params.top.unwrap_or(20).min(MAX_TOP_QUERIES);
+    let stats = state.db(move |store| store.query_stats(since, top)).await?;
+    Ok(Json(QueryStatsResponse {
+        config: state.store.query_stats_config(),
+        stats,
+    }))
+}
+
+/// GET /api/stats/queries/slow — the latest searches kept with their
+/// diagnostics: those over `MINDSAGE_QUERY_STATS_SLOW_MS` or without
+/// results, while `MINDSAGE_QUERY_STATS_CAPTURE` is on. Newest first.
+#[utoipa::path(get, path = "/stats/queries/slow", tag = "stats", params(SlowQueriesQuery), responses((status = 200, body = SlowQueriesResponse)))]
+async fn get_slow_queries(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SlowQueriesQuery>,
+) -> ApiResult<Json<SlowQueriesResponse>> {
+    let limit = params.limit.unwrap_or(50).min(MAX_SLOW_QUERIES);
+    let queries = state.db(move |store| store.captured_searches(limit)).await?;
+    Ok(Json(SlowQueriesResponse {
+        total: queries.len(),
+        queries,
     }))
 }
 
+/// DELETE /api/stats/queries — delete everything in the query stats log.
+#[utoipa::path(delete, path = "/stats/queries", tag = "stats", responses((status = 200, body = PurgeQueryStatsResponse)))]
+async fn purge_query_stats(State(state): State<Arc<AppState>>) -> ApiResult<Json<PurgeQueryStatsResponse>> {
+    let deleted = state.db(|store| store.purge_query_stats()).await?;
+    Ok(Json(PurgeQueryStatsResponse { deleted }))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ReadinessResponse {
+    /// `ready`, or `degraded` when a background subsystem's last run failed.
+    status: &'static str,
+    /// Subsystems whose last run failed.
+    degraded: Vec<String>,
+    /// Whether the server refuses writes (`MINDSAGE_READ_ONLY`).
+    read_only: bool,
+}
+
+/// GET /api/health/ready — whether the server and its background tasks are
+/// working. Degraded subsystems do not fail the probe; the server still
+/// answers requests.
+#[utoipa::path(get, path = "/health/ready", tag = "stats", responses((status = 200, body = ReadinessResponse)))]
+async fn get_readiness(State(state): State<Arc<AppState>>) -> Json<ReadinessResponse> {
+    let degraded = state.health.degraded();
+    Json(ReadinessResponse {
+        status: if degraded.is_empty() { "ready" } else { "degraded" },
+        degraded,
+        read_only: state.config.read_only,
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+struct ServerInfoResponse {
+    hostname: String,
+    ip: String,
+    port: u16,
+    url: String,
+    platform: &'static str,
+    arch: &'static str,
+}
+
 /// GET /api/server-info — network info.
-async fn get_server_info(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+#[utoipa::path(get, path = "/server-info", tag = "stats", responses((status = 200, body = ServerInfoResponse)))]
+async fn get_server_info(State(state): State<Arc<AppState>>) -> Json<ServerInfoResponse> {
     let hostname = hostname();
     let ip = local_ip();
     let port = state.config.port;
 
-    Json(serde_json::json!({
-        "hostname": hostname,
-        "ip": ip,
-        "port": port,
-        "url": format!("http://{}:{}", ip, port),
-        "platform": std::env::consts::OS,
-        "arch": std::env::consts::ARCH,
-    }))
+    Json(ServerInfoResponse {
+        hostname,
+        url: format!("http://{}:{}", ip, port),
+        ip,
+        port,
+        platform: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+    })
 }
 
 fn count_files_in_dir(dir: &std::path::Path) -> usize {
@@ -113,3 +370,117 @@ fn local_ip() -> String {
         .map(|addr| addr.ip().to_string())
         .unwrap_or_else(|_| "127.0.0.1".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mindsage_store::AddDocumentOptions;
+    use tempfile::TempDir;
+
+    use crate::test_support::{TestState, test_state};
+
+    #[tokio::test]
+    async fn test_source_stats_split_by_source() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+
+        for (text, source) in [("a post", Some("facebook")), ("a chat", Some("chatgpt")), ("another chat", Some("chatgpt")), ("a note", None)] {
+            let metadata = source.map(|s| serde_json::json!({ "source": s }));
+            state.store.add_document(text, AddDocumentOptions { metadata, ..Default::default() }).unwrap();
+        }
+        state.orchestrator.consolidate(&state.store);
+
+        let Json(stats) = get_source_stats(State(state.clone())).await.unwrap();
+        let counts: Vec<(&str, i64, i64)> =
+            stats.sources.iter().map(|s| (s.source.as_str(), s.documents, s.text_bytes)).collect();
+        assert_eq!(counts, vec![("chatgpt", 2, 18), ("facebook", 1, 6), ("unknown", 1, 6)]);
+        assert_eq!(stats.consolidations.len(), 1);
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["sources"][0]["textBytes"], 18);
+        assert!(json["consolidations"][0]["report"]["durationMs"].is_number());
+    }
+
+    #[tokio::test]
+    async fn test_runtime_stats_report_memory_use() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+
+        let reservation = state.orchestrator.memory().reserve("test_batch", 4096).unwrap();
+        let Json(status) = get_runtime_stats(State(state.clone())).await;
+        let json = serde_json::to_value(&status).unwrap();
+        let memory = &json["memory"];
+        assert_eq!(memory["budgetBytes"], json["budget"]["maxMemoryMb"].as_u64().unwrap() * 1024 * 1024);
+        assert_eq!(memory["inFlight"]["test_batch"], 4096);
+        assert_eq!(memory["resident"]["embedding_matrix"], 0);
+        assert_eq!(memory["usedBytes"], 4096);
+        drop(reservation);
+
+        let Json(status) = get_runtime_stats(State(state)).await;
+        assert_eq!(status.memory.used_bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_query_stats_record_searches() {
+        let dir = TempDir::new().unwrap();
+        let query_stats = QueryStatsConfig {
+            mode: mindsage_core::QueryStatsMode::Hashed,
+            capture: true,
+            ..Default::default()
+        };
+        let state = Arc::new(TestState::new(&dir).config(|c| c.query_stats = query_stats).build());
+        let text = "notes on the sourdough starter";
+        let doc = state.store.add_document(text, AddDocumentOptions::default()).unwrap();
+        state.store.add_chunk(doc, text, 0, 1, None, None, None, None, None, None).unwrap();
+
+        for query in ["sourdough", "Sourdough", "rye flour"] {
+            let request: mindsage_api_types::SearchRequest = serde_json::from_value(serde_json::json!({ "query": query })).unwrap();
+            let _ = crate::routes::vector_store::run_search(&state, request).unwrap();
+        }
+
+        let Json(stats) = get_query_stats(State(state.clone()), Query(QueryStatsQuery { days: None, top: None }))
+            .await
+            .unwrap();
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!((json["searches"].as_u64(), json["zeroResults"].as_u64()), (Some(3), Some(1)));
+        assert_eq!(json["config"]["mode"], "hashed");
+        assert_eq!(json["topQueries"][0]["query"], mindsage_store::hash_query("sourdough"));
+        assert_eq!(json["topQueries"][0]["count"], 2);
+        assert!(!json.to_string().contains("rye"));
+
+        let Json(slow) = get_slow_queries(State(state.clone()), Query(SlowQueriesQuery { limit: None })).await.unwrap();
+        assert_eq!(slow.total, 1);
+        assert_eq!(slow.queries[0].result_count, 0);
+        assert_eq!(slow.queries[0].diagnostics.as_ref().unwrap()["topK"], 10);
+
+        let Json(purged) = purge_query_stats(State(state.clone())).await.unwrap();
+        assert_eq!(purged.deleted, 3);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_flags_degraded_subsystems() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+
+        let Json(ready) = get_readiness(State(state.clone())).await;
+        assert_eq!(ready.status, "ready");
+
+        state.health.record_success(crate::health::INDEXING_WORKER);
+        state.health.record_failure(crate::health::EXTRACTION_CATCHUP, "database disk image is malformed");
+        let Json(ready) = get_readiness(State(state.clone())).await;
+        assert_eq!(ready.status, "degraded");
+        assert_eq!(ready.degraded, vec!["extraction_catchup"]);
+
+        let Json(background) = get_background_stats(State(state.clone())).await;
+        let json = serde_json::to_value(&background).unwrap();
+        assert_eq!(json["subsystems"]["extraction_catchup"]["consecutiveFailures"], 1);
+        assert_eq!(json["subsystems"]["extraction_catchup"]["lastError"], "database disk image is malformed");
+        assert!(json["subsystems"]["indexing_worker"]["lastSuccessAt"].is_number());
+
+        // A successful run clears the flag
+        state.health.record_success(crate::health::EXTRACTION_CATCHUP);
+        let Json(ready) = get_readiness(State(state)).await;
+        assert_eq!(ready.status, "ready");
+    }
+}
@@ -3,17 +3,40 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
-use axum::routing::{get, post};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
 use axum::{Json, Router};
-use serde::Deserialize;
-
+use ndarray::Array1;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::error::{ApiError, ApiResult, ErrorBody};
+use crate::ndjson;
+use crate::request_trace::{self, TraceQuery};
 use crate::state::AppState;
+use crate::topic_generation::{generate_document_topics, TopicMode};
+use crate::topic_generation::TopicGeneration;
+use mindsage_api_types::{
+    AddDocumentRequest, AddDocumentResponse, BloomDigest, ChunkSummary, DeleteDocumentResponse,
+    DateFacetsResponse, DocumentHashesResponse, ExportedDocument, DocumentListResponse, DocumentResponse, EnhancedSearchRequest, OnDuplicate, ParentContext, Passage, SearchRequest, SearchResponse, SearchResult, TimeRange,
+    StatusResponse,
+};
+use mindsage_core::SearchPostProcessing;
+use mindsage_infer::EmbedderBackend;
 use mindsage_ingest::ingest::content_hash;
-use mindsage_store::{AddDocumentOptions, SearchHit};
+use mindsage_ingest::extract::passages::{extract_passage, DEFAULT_PASSAGE_WINDOW};
+use mindsage_ingest::plan_chunks;
+use mindsage_resolve::{merge_overlapping_hits, read_query, MergedHit};
+use mindsage_store::{
+    check_chunk_filter, mmr_select, normalize_tag, AddDocumentOptions, BatchItemOutcome, Chunk, ChunkFilter, Collection, Diversity, Document, DocumentCursor, NewDocument, SearchFilters, SearchHit, SqliteStore,
+    post_process,
+    QuerySample, SearchDiagnostics, Suggestion, TopicPair, TopicStats, MAX_COLLECTION_NAME_CHARS, MAX_TAG_CHARS,
+};
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
@@ -23,45 +46,123 @@ pub fn routes() -> Router<Arc<AppState>> {
         // Documents
         .route("/vector-store/documents", post(add_document).get(list_documents))
         .route("/vector-store/documents/batch", post(batch_add_documents))
+        .route("/vector-store/documents/hashes", get(list_document_hashes))
+        .route("/vector-store/export.ndjson", get(export_ndjson))
+        .route("/vector-store/documents/facets/date", get(get_date_facets))
+        .route("/vector-store/documents/by-hash/{hash}", get(get_document_by_hash))
         .route(
             "/vector-store/documents/{id}",
             get(get_document).delete(delete_document),
         )
+        .route("/vector-store/documents/{id}/raw", get(get_document_raw))
+        .route("/vector-store/documents/{id}/chunks", get(get_document_chunks))
+        .route("/vector-store/documents/{id}/similar", get(get_similar_documents))
         // Search
         .route("/vector-store/search", post(search))
         .route("/vector-store/search/enhanced", post(enhanced_search))
         .route("/vector-store/search/with-topic", post(search_with_topic))
+        .route("/vector-store/search/settings", get(get_search_settings).put(put_search_settings))
+        .route("/vector-store/suggest", get(suggest))
         // Topics
         .route("/vector-store/topics", get(get_topics))
+        .route("/vector-store/topics/cooccurrence", get(get_topic_cooccurrence))
+        .route("/vector-store/topics/generate", post(batch_generate_topics))
         .route("/vector-store/topics/{topic}/documents", get(get_documents_by_topic))
+        .route("/vector-store/topics/{topic}/stats", get(get_topic_stats))
         .route(
             "/vector-store/documents/{id}/topics",
             get(get_document_topics).put(update_document_topics),
         )
         .route("/vector-store/documents/{id}/topics/generate", post(generate_topics))
+        // Tags
+        .route("/vector-store/tags", get(get_tags))
+        .route("/vector-store/tags/{tag}/documents", get(get_documents_by_tag))
+        .route(
+            "/vector-store/documents/{id}/tags/{tag}",
+            put(add_document_tag).delete(remove_document_tag),
+        )
+        // Collections
+        .route("/vector-store/collections", get(list_collections).post(create_collection))
+        .route("/vector-store/collections/{id}", delete(delete_collection))
+        .route(
+            "/vector-store/collections/{id}/documents",
+            get(get_collection_documents)
+                .post(add_collection_documents)
+                .delete(remove_collection_documents),
+        )
         // Knowledge Graph
         .route("/vector-store/graph", post(get_graph))
         .route("/vector-store/graph/node/{node_id}", get(get_graph_node))
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_status,
+    get_debug,
+    add_document,
+    list_documents,
+    batch_add_documents,
+    list_document_hashes,
+    export_ndjson,
+    get_date_facets,
+    get_document_by_hash,
+    get_document,
+    delete_document,
+    get_document_raw,
+    get_document_chunks,
+    get_similar_documents,
+    search,
+    enhanced_search,
+    search_with_topic,
+    get_search_settings,
+    put_search_settings,
+    suggest,
+    get_topics,
+    get_topic_cooccurrence,
+    batch_generate_topics,
+    get_documents_by_topic,
+    get_topic_stats,
+    get_document_topics,
+    update_document_topics,
+    generate_topics,
+    get_tags,
+    get_documents_by_tag,
+    add_document_tag,
+    remove_document_tag,
+    list_collections,
+    create_collection,
+    delete_collection,
+    get_collection_documents,
+    add_collection_documents,
+    remove_collection_documents,
+    get_graph,
+    get_graph_node,
+))]
+pub struct VectorStoreApi;
+
 // ---------------------------------------------------------------
 // Health
 // ---------------------------------------------------------------
 
-async fn get_status(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
-    let stats = state.store.get_stats().ok();
-    Json(serde_json::json!({
-        "status": "healthy",
-        "service": "mindsage-rs",
-        "documents": stats.as_ref().map(|s| s.total_documents).unwrap_or(0),
-        "chunks": stats.as_ref().map(|s| s.total_chunks).unwrap_or(0),
-        "embeddings": stats.as_ref().map(|s| s.embeddings_stored).unwrap_or(0),
-    }))
+/// Health check with document, chunk and embedding counts.
+#[utoipa::path(get, path = "/vector-store/status", tag = "vector-store", responses((status = 200, body = StatusResponse)))]
+async fn get_status(State(state): State<Arc<AppState>>) -> Json<StatusResponse> {
+    let stats = state.db(|store| store.get_stats()).await.ok();
+    Json(StatusResponse {
+        status: "healthy".to_string(),
+        service: "mindsage-rs".to_string(),
+        documents: stats.as_ref().map(|s| s.total_documents).unwrap_or(0),
+        chunks: stats.as_ref().map(|s| s.total_chunks).unwrap_or(0),
+        embeddings: stats.as_ref().map(|s| s.embeddings_stored).unwrap_or(0),
+    })
 }
 
+/// Device capabilities, raw store statistics and embedder batch stats,
+/// per model when queries have their own.
+#[utoipa::path(get, path = "/vector-store/debug", tag = "vector-store", responses((status = 200, body = Object)))]
 async fn get_debug(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
     let caps = mindsage_core::DeviceCapabilities::discover();
-    let stats = state.store.get_stats().ok();
+    let stats = state.db(|store| store.get_stats()).await.ok();
 
     Json(serde_json::json!({
         "device": {
@@ -73,395 +174,1178 @@ async fn get_debug(State(state): State<Arc<AppState>>) -> Json<serde_json::Value
             "isJetson": caps.is_jetson,
         },
         "store": stats,
+        "embedder": state.embedder.stats(),
+        "models": {
+            "passage": model_info(state.embedder.as_ref()),
+            "query": state.embedder.query_embedder().map(model_info),
+        },
     }))
 }
 
+/// Id, dimension and batch stats of one embedding model.
+fn model_info(embedder: &dyn EmbedderBackend) -> serde_json::Value {
+    serde_json::json!({
+        "modelId": embedder.model_id(),
+        "dimension": embedder.dimension(),
+        "available": embedder.is_available(),
+        "stats": embedder.stats(),
+    })
+}
+
 // ---------------------------------------------------------------
 // Documents
 // ---------------------------------------------------------------
 
-#[derive(Deserialize)]
-struct AddDocumentRequest {
-    text: String,
-    metadata: Option<serde_json::Value>,
-    content_hash: Option<String>,
-}
-
+/// Add a document and chunk it for search; its chunks are embedded in the
+/// background. A document whose content hash already exists answers 409,
+/// or 200 with the existing id and status `exists` when `on_duplicate` is
+/// `return_existing`.
+#[utoipa::path(
+    post,
+    path = "/vector-store/documents",
+    tag = "vector-store",
+    responses(
+        (status = 201, body = AddDocumentResponse),
+        (status = 200, description = "Existing document returned (`on_duplicate: return_existing`)", body = AddDocumentResponse),
+        (status = 409, description = "A document with the same content hash exists", body = ErrorBody),
+    )
+)]
 async fn add_document(
     State(state): State<Arc<AppState>>,
     Json(req): Json<AddDocumentRequest>,
-) -> impl IntoResponse {
+) -> ApiResult<impl IntoResponse> {
     let hash = req
         .content_hash
         .unwrap_or_else(|| content_hash(&req.text));
 
-    match state.store.add_document(
-        &req.text,
-        AddDocumentOptions {
+    // The document and its chunks are stored in one transaction
+    let document = NewDocument {
+        chunks: plan_chunks(&req.text, req.metadata.as_ref(), None),
+        text: req.text,
+        options: AddDocumentOptions {
             metadata: req.metadata,
             content_hash: Some(hash.clone()),
             ..Default::default()
         },
-    ) {
-        Ok(doc_id) => {
-            // Also create a single chunk for searchability
-            // Chunk the document for searchability
-            let _ = chunk_document(&state, doc_id, &req.text, None);
-
-            (
-                StatusCode::CREATED,
-                Json(serde_json::json!({
-                    "id": doc_id,
-                    "content_hash": hash,
-                    "status": "added",
-                })),
-            )
-        }
-        Err(mindsage_core::Error::DuplicateContent(_)) => (
-            StatusCode::CONFLICT,
-            Json(serde_json::json!({
-                "error": "Duplicate content",
-                "content_hash": hash,
-            })),
-        ),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        ),
-    }
-}
-
-/// Chunk a document and store chunks in the database.
-fn chunk_document(
-    state: &AppState,
-    doc_id: i64,
-    text: &str,
-    file_extension: Option<&str>,
-) -> Result<(), mindsage_core::Error> {
-    use mindsage_ingest::chunking::{calculate_chunk_size, should_chunk, HierarchicalChunker};
-    use std::collections::HashMap;
-
-    if should_chunk(text, file_extension) {
-        let (chunk_size, chunk_overlap) = calculate_chunk_size(file_extension);
-        let chunker = HierarchicalChunker::new(chunk_size, chunk_overlap);
-        let chunks = chunker.chunk(text);
-
-        let mut section_db_ids: HashMap<usize, i64> = HashMap::new();
-
-        for chunk in &chunks {
-            let parent_db_id = chunk
-                .parent_index
-                .and_then(|pi| section_db_ids.get(&pi).copied());
-
-            let chunk_id = state.store.add_chunk(
-                doc_id,
-                &chunk.text,
-                chunk.chunk_index as i32,
-                chunk.level,
-                parent_db_id,
-                Some(chunk.char_start as i32),
-                Some(chunk.char_end as i32),
-                None,
-                None,
-                None,
-            )?;
-
-            if chunk.level == 0 {
-                section_db_ids.insert(chunk.chunk_index, chunk_id);
+    };
+    let added = state.db(move |store| store.add_document_with_chunks(&document)).await;
+    let doc_id = match added {
+        Ok(doc_id) => doc_id,
+        Err(mindsage_core::Error::DuplicateContent(_))
+            if req.on_duplicate == Some(OnDuplicate::ReturnExisting) =>
+        {
+            let lookup = hash.clone();
+            if let Some(existing) = state.db(move |store| store.find_document_by_hash(&lookup)).await? {
+                return Ok((
+                    StatusCode::OK,
+                    Json(AddDocumentResponse {
+                        id: existing.id,
+                        content_hash: hash,
+                        status: "exists".to_string(),
+                    }),
+                ));
             }
+            // Deleted in the meantime: report the conflict as usual
+            return Err(mindsage_core::Error::DuplicateContent(hash).into());
         }
-    } else {
-        state.store.add_chunk(
-            doc_id,
-            text,
-            0,
-            1,
-            None,
-            Some(0),
-            Some(text.len() as i32),
-            None,
-            None,
-            None,
-        )?;
-    }
-    Ok(())
+        Err(e) => return Err(e.into()),
+    };
+    embed_in_background(&state);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(AddDocumentResponse {
+            id: doc_id,
+            content_hash: hash,
+            status: "added".to_string(),
+        }),
+    ))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct BatchAddRequest {
     documents: Vec<AddDocumentRequest>,
+    /// Add every document (with its chunks) in one transaction, or none.
+    #[serde(default)]
+    atomic: bool,
+    /// With `atomic`, skip documents whose content hash already exists
+    /// instead of rolling the batch back (default true).
+    #[serde(default = "default_skip_duplicates")]
+    skip_duplicates: bool,
+}
+
+fn default_skip_duplicates() -> bool {
+    true
+}
+
+#[derive(Serialize, ToSchema)]
+struct AddedDocument {
+    id: i64,
+    content_hash: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BatchAddError {
+    error: String,
+    content_hash: String,
+}
+
+/// Whether an atomic batch was written.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum TransactionOutcome {
+    Committed,
+    RolledBack,
+}
+
+/// Result of the transaction of an atomic batch.
+#[derive(Serialize, ToSchema)]
+struct BatchTransaction {
+    outcome: TransactionOutcome,
+    /// Position in `documents` of the document that caused the rollback.
+    #[serde(rename = "failedIndex", skip_serializing_if = "Option::is_none")]
+    failed_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BatchAddResponse {
+    added: usize,
+    duplicates: usize,
+    errors: usize,
+    results: Vec<AddedDocument>,
+    #[serde(rename = "errorDetails")]
+    error_details: Vec<BatchAddError>,
+    /// Present for atomic batches. After a rollback nothing was added.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transaction: Option<BatchTransaction>,
 }
 
+/// Add several documents. By default each document is added on its own and
+/// duplicates are counted and skipped. With `atomic` they are added in one
+/// transaction: a rollback answers 409 (duplicate) or 500 with nothing
+/// added. Added chunks are embedded in the background either way.
+#[utoipa::path(
+    post,
+    path = "/vector-store/documents/batch",
+    tag = "vector-store",
+    responses(
+        (status = 200, body = BatchAddResponse),
+        (status = 409, description = "Atomic batch rolled back on a duplicate", body = BatchAddResponse),
+        (status = 500, description = "Atomic batch rolled back on an error", body = BatchAddResponse),
+    )
+)]
 async fn batch_add_documents(
     State(state): State<Arc<AppState>>,
     Json(req): Json<BatchAddRequest>,
-) -> Json<serde_json::Value> {
+) -> (StatusCode, Json<BatchAddResponse>) {
+    if req.atomic {
+        return batch_add_atomic(&state, req.documents, req.skip_duplicates).await;
+    }
+
+    let (added, errors, duplicates) = state.db(move |store| add_each(store, req.documents)).await;
+    if !added.is_empty() {
+        embed_in_background(&state);
+    }
+    (
+        StatusCode::OK,
+        Json(BatchAddResponse {
+            added: added.len(),
+            duplicates,
+            errors: errors.len(),
+            results: added,
+            error_details: errors,
+            transaction: None,
+        }),
+    )
+}
+
+/// Embed the chunks of newly added documents on a blocking thread; the
+/// ingest journal lists them until they are.
+fn embed_in_background(state: &Arc<AppState>) {
+    let task_state = state.clone();
+    tokio::task::spawn_blocking(move || crate::indexing::embed_pending_chunks(&task_state));
+}
+
+/// Add each document on its own. Returns the added documents, the
+/// failures, and the number of duplicates skipped.
+fn add_each(store: &SqliteStore, documents: Vec<AddDocumentRequest>) -> (Vec<AddedDocument>, Vec<BatchAddError>, usize) {
     let mut added = Vec::new();
     let mut errors = Vec::new();
     let mut duplicates = 0;
 
-    for doc in req.documents {
+    for doc in documents {
         let hash = doc
             .content_hash
             .unwrap_or_else(|| content_hash(&doc.text));
 
-        match state.store.add_document(
-            &doc.text,
-            AddDocumentOptions {
+        match store.add_document_with_chunks(&NewDocument {
+            chunks: plan_chunks(&doc.text, doc.metadata.as_ref(), None),
+            text: doc.text,
+            options: AddDocumentOptions {
                 metadata: doc.metadata,
                 content_hash: Some(hash.clone()),
                 ..Default::default()
             },
-        ) {
+        }) {
             Ok(doc_id) => {
-                let _ = chunk_document(&state, doc_id, &doc.text, None);
-                added.push(serde_json::json!({ "id": doc_id, "content_hash": hash }));
+                added.push(AddedDocument {
+                    id: doc_id,
+                    content_hash: hash,
+                });
             }
             Err(mindsage_core::Error::DuplicateContent(_)) => {
                 duplicates += 1;
             }
             Err(e) => {
-                errors.push(serde_json::json!({ "error": e.to_string(), "content_hash": hash }));
+                errors.push(BatchAddError {
+                    error: e.to_string(),
+                    content_hash: hash,
+                });
             }
         }
     }
+    (added, errors, duplicates)
+}
 
-    Json(serde_json::json!({
-        "added": added.len(),
-        "duplicates": duplicates,
-        "errors": errors.len(),
-        "results": added,
-        "errorDetails": errors,
-    }))
+/// All-or-nothing variant of `batch_add_documents`.
+async fn batch_add_atomic(
+    state: &Arc<AppState>,
+    documents: Vec<AddDocumentRequest>,
+    skip_duplicates: bool,
+) -> (StatusCode, Json<BatchAddResponse>) {
+    let docs: Vec<NewDocument> = documents
+        .into_iter()
+        .map(|doc| {
+            let hash = doc.content_hash.unwrap_or_else(|| content_hash(&doc.text));
+            NewDocument {
+                chunks: plan_chunks(&doc.text, doc.metadata.as_ref(), None),
+                text: doc.text,
+                options: AddDocumentOptions {
+                    metadata: doc.metadata,
+                    content_hash: Some(hash),
+                    ..Default::default()
+                },
+            }
+        })
+        .collect();
+    let hashes: Vec<String> = docs
+        .iter()
+        .map(|doc| doc.options.content_hash.clone().unwrap_or_default())
+        .collect();
+    let hash_of = |i: usize| hashes[i].clone();
+
+    match state.db(move |store| store.add_documents_transactional(&docs, skip_duplicates)).await {
+        Ok(outcomes) => {
+            let mut results = Vec::new();
+            let mut duplicates = 0;
+            for (i, outcome) in outcomes.into_iter().enumerate() {
+                match outcome {
+                    BatchItemOutcome::Added { doc_id } => results.push(AddedDocument {
+                        id: doc_id,
+                        content_hash: hash_of(i),
+                    }),
+                    BatchItemOutcome::SkippedDuplicate => duplicates += 1,
+                }
+            }
+            if !results.is_empty() {
+                embed_in_background(state);
+            }
+            (
+                StatusCode::OK,
+                Json(BatchAddResponse {
+                    added: results.len(),
+                    duplicates,
+                    errors: 0,
+                    results,
+                    error_details: Vec::new(),
+                    transaction: Some(BatchTransaction {
+                        outcome: TransactionOutcome::Committed,
+                        failed_index: None,
+                        error: None,
+                    }),
+                }),
+            )
+        }
+        Err(rollback) => {
+            let (status, duplicates) = match rollback.error {
+                mindsage_core::Error::DuplicateContent(_) => (StatusCode::CONFLICT, 1),
+                _ => (StatusCode::INTERNAL_SERVER_ERROR, 0),
+            };
+            let error = rollback.error.to_string();
+            (
+                status,
+                Json(BatchAddResponse {
+                    added: 0,
+                    duplicates,
+                    errors: 1,
+                    results: Vec::new(),
+                    error_details: vec![BatchAddError {
+                        error: error.clone(),
+                        content_hash: rollback.index.map(hash_of).unwrap_or_default(),
+                    }],
+                    transaction: Some(BatchTransaction {
+                        outcome: TransactionOutcome::RolledBack,
+                        failed_index: rollback.index,
+                        error: Some(error),
+                    }),
+                }),
+            )
+        }
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct ListDocumentsQuery {
+    /// 1-based page number (default 1). Ignored with `cursor`.
     page: Option<usize>,
+    /// Documents per page (default 10). With NDJSON, the most documents
+    /// to stream (default all).
     page_size: Option<usize>,
+    /// Oldest first instead of newest first.
     ascending: Option<bool>,
+    /// `nextCursor` of the previous page: list the documents after it
+    /// instead of by page number.
+    cursor: Option<String>,
+    /// Only documents in this collection; cannot be combined with `cursor`.
+    collection_id: Option<i64>,
+}
+
+/// Number of pages of `page_size` items needed for `total` items.
+fn total_pages(total: i64, page_size: usize) -> i64 {
+    (total as f64 / page_size as f64).ceil() as i64
 }
 
+/// Documents read per query while streaming.
+const STREAM_BATCH_SIZE: usize = 200;
+
+/// List documents, newest first. With `Accept: application/x-ndjson` the
+/// documents are streamed one per line instead.
+#[utoipa::path(
+    get,
+    path = "/vector-store/documents",
+    tag = "vector-store",
+    params(ListDocumentsQuery),
+    responses(
+        (status = 200, content(
+            (DocumentListResponse = "application/json"),
+            (Document = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Malformed cursor", body = ErrorBody),
+    )
+)]
 async fn list_documents(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Query(params): Query<ListDocumentsQuery>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Response> {
+    let ascending = params.ascending.unwrap_or(false);
+    let cursor = match params.cursor.as_deref() {
+        Some(value) => Some(
+            DocumentCursor::parse(value).ok_or_else(|| ApiError::bad_request(format!("Malformed cursor '{}'", value)))?,
+        ),
+        None => None,
+    };
+
+    if let Some(collection_id) = params.collection_id {
+        if cursor.is_some() {
+            return Err(ApiError::bad_request("cursor cannot be combined with collection_id"));
+        }
+        state
+            .db(move |store| store.get_collection(collection_id))
+            .await?
+            .ok_or_else(collection_not_found)?;
+    }
+
+    if ndjson::wants_ndjson(&headers) {
+        if let Some(collection_id) = params.collection_id {
+            let limit = params.page_size.unwrap_or(usize::MAX);
+            return Ok(ndjson::stream(move |out| {
+                let mut sent = 0;
+                for page in 1.. {
+                    let batch = state.store.list_documents_in_collection(collection_id, page, STREAM_BATCH_SIZE, ascending);
+                    let docs = match batch {
+                        Ok((docs, _)) => docs,
+                        Err(e) => {
+                            out.error(&e.to_string());
+                            return;
+                        }
+                    };
+                    for doc in docs.iter().take(limit - sent) {
+                        if !out.send(doc) {
+                            return;
+                        }
+                        sent += 1;
+                    }
+                    if docs.len() < STREAM_BATCH_SIZE || sent == limit {
+                        return;
+                    }
+                }
+            }));
+        }
+        let limit = params.page_size.unwrap_or(usize::MAX);
+        return Ok(ndjson::stream(move |out| {
+            let mut cursor = cursor;
+            let mut sent = 0;
+            while sent < limit {
+                let batch = STREAM_BATCH_SIZE.min(limit - sent);
+                let after = state.store.get_documents_after(cursor, batch, ascending);
+                let docs = match after {
+                    Ok(docs) => docs,
+                    Err(e) => {
+                        out.error(&e.to_string());
+                        return;
+                    }
+                };
+                for doc in &docs {
+                    if !out.send(doc) {
+                        return;
+                    }
+                }
+                sent += docs.len();
+                match docs.last() {
+                    Some(last) if docs.len() == batch => cursor = Some(DocumentCursor::after(last)),
+                    _ => return,
+                }
+            }
+        }));
+    }
+
     let page = params.page.unwrap_or(1);
     let page_size = params.page_size.unwrap_or(10);
-    let ascending = params.ascending.unwrap_or(false);
+    let collection_id = params.collection_id;
+    let (docs, total) = state
+        .db(move |store| match (cursor, collection_id) {
+            (Some(cursor), _) => Ok((
+                store.get_documents_after(Some(cursor), page_size, ascending)?,
+                store.count_documents()?,
+            )),
+            (None, Some(collection_id)) => store.list_documents_in_collection(collection_id, page, page_size, ascending),
+            (None, None) => store.get_documents_paginated(page, page_size, ascending),
+        })
+        .await?;
+    let next_cursor = docs
+        .last()
+        .filter(|_| docs.len() == page_size && collection_id.is_none())
+        .map(|last| DocumentCursor::after(last).to_string());
+
+    Ok(Json(DocumentListResponse {
+        documents: docs,
+        total,
+        page,
+        page_size,
+        total_pages: total_pages(total, page_size),
+        next_cursor,
+    })
+    .into_response())
+}
 
-    match state
-        .store
-        .get_documents_paginated(page, page_size, ascending)
-    {
-        Ok((docs, total)) => Json(serde_json::json!({
-            "documents": docs,
-            "total": total,
-            "page": page,
-            "pageSize": page_size,
-            "totalPages": (total as f64 / page_size as f64).ceil() as i64,
-        })),
-        Err(e) => Json(serde_json::json!({ "error": e.to_string() })),
+/// GET /api/vector-store/export.ndjson — every document with its chunks,
+/// one `ExportedDocument` per line, oldest first.
+#[utoipa::path(
+    get,
+    path = "/vector-store/export.ndjson",
+    tag = "vector-store",
+    responses((status = 200, content((ExportedDocument = "application/x-ndjson"))))
+)]
+async fn export_ndjson(State(state): State<Arc<AppState>>) -> Response {
+    let mut response = ndjson::stream(move |out| {
+        for doc in state.store.iter_documents(true, STREAM_BATCH_SIZE) {
+            let line = doc.and_then(|document| {
+                let chunks = state.store.get_chunks_for_document(document.id)?;
+                let tags = state.store.get_tags(document.id)?;
+                Ok(ExportedDocument { document, chunks, tags })
+            });
+            let sent = match line {
+                Ok(line) => out.send(&line),
+                Err(e) => {
+                    out.error(&e.to_string());
+                    false
+                }
+            };
+            if !sent {
+                return;
+            }
+        }
+    });
+    response
+        .headers_mut()
+        .insert(header::CONTENT_DISPOSITION, super::files::content_disposition("mindsage-export.ndjson"));
+    response
+}
+
+/// Largest time zone offset accepted by the date facets, in minutes
+/// (UTC+14:00).
+const MAX_TZ_OFFSET_MINUTES: i32 = 14 * 60;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DateFacetsQuery {
+    /// Minutes east of UTC to compute months in (default 0, at most ±840).
+    tz_offset_minutes: Option<i32>,
+}
+
+/// GET /api/vector-store/documents/facets/date — document counts per
+/// creation month, for the documents list filters.
+#[utoipa::path(
+    get,
+    path = "/vector-store/documents/facets/date",
+    tag = "vector-store",
+    params(DateFacetsQuery),
+    responses(
+        (status = 200, body = DateFacetsResponse),
+        (status = 400, description = "Time zone offset out of range", body = ErrorBody)
+    )
+)]
+async fn get_date_facets(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<DateFacetsQuery>,
+) -> ApiResult<Json<DateFacetsResponse>> {
+    let tz_offset_minutes = params.tz_offset_minutes.unwrap_or(0);
+    if tz_offset_minutes.abs() > MAX_TZ_OFFSET_MINUTES {
+        return Err(ApiError::bad_request(format!(
+            "tz_offset_minutes must be within ±{}",
+            MAX_TZ_OFFSET_MINUTES
+        )));
+    }
+    let facets = state.db(move |store| store.document_month_counts(tz_offset_minutes)).await?;
+    Ok(Json(DateFacetsResponse {
+        total: facets.iter().map(|f| f.count).sum(),
+        facets,
+        tz_offset_minutes,
+    }))
+}
+
+/// Most chunks returned inline by `GET /documents/{id}?include_chunks=true`.
+const MAX_INLINE_CHUNKS: usize = 500;
+/// Largest page accepted by `GET /documents/{id}/chunks`.
+const MAX_CHUNK_PAGE_SIZE: usize = 200;
+
+/// GET /api/vector-store/documents/:id/raw — the document's full text as
+/// a `.txt` attachment named after its file or title.
+#[utoipa::path(
+    get,
+    path = "/vector-store/documents/{id}/raw",
+    tag = "vector-store",
+    responses(
+        (status = 200, description = "Document text", content_type = "text/plain", body = String),
+        (status = 404, description = "Document not found", body = ErrorBody),
+    )
+)]
+async fn get_document_raw(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> ApiResult<impl IntoResponse> {
+    let doc = state
+        .db(move |store| store.get_document(id))
+        .await?
+        .ok_or_else(|| ApiError::not_found("Document not found"))?;
+    let filename = raw_filename(&doc);
+    Ok((
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static("text/plain; charset=utf-8")),
+            (header::CONTENT_DISPOSITION, super::files::content_disposition(&filename)),
+        ],
+        doc.text,
+    ))
+}
+
+/// Download name for a document's text: its file name (or else title)
+/// with a `.txt` extension, or `document-<id>.txt`.
+fn raw_filename(doc: &Document) -> String {
+    let meta = doc.metadata.as_ref();
+    let name = meta
+        .and_then(|m| m.get("filename"))
+        .and_then(|v| v.as_str())
+        .map(|f| std::path::Path::new(f).file_stem().and_then(|s| s.to_str()).unwrap_or(f).to_string())
+        .or_else(|| meta.and_then(|m| m.get("title")).and_then(|v| v.as_str()).map(str::to_string))
+        .map(|name| {
+            name.chars()
+                .map(|c| if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.') { c } else { '_' })
+                .take(100)
+                .collect::<String>()
+                .trim()
+                .to_string()
+        })
+        .filter(|name| !name.is_empty());
+    match name {
+        Some(name) => format!("{}.txt", name),
+        None => format!("document-{}.txt", doc.id),
     }
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetDocumentQuery {
+    /// Inline the document's chunks (at most 500).
+    include_chunks: Option<bool>,
+}
+
+/// Above this many hashes, `GET /documents/hashes` answers with a Bloom
+/// filter unless a list is requested.
+const HASH_LIST_MAX: usize = 50_000;
+
+/// False-positive rate of the hash Bloom filter.
+const HASH_BLOOM_FP_RATE: f64 = 0.01;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum HashFormat {
+    List,
+    Bloom,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DocumentHashesQuery {
+    /// Only documents created or updated at or after this time (ms).
+    since: Option<i64>,
+    /// `list` or `bloom`; by default a list up to 50,000 hashes, a Bloom
+    /// filter beyond.
+    #[param(value_type = Option<String>)]
+    format: Option<HashFormat>,
+}
+
+/// GET /api/vector-store/documents/hashes — content hashes with their
+/// document ids, for client-side deduplication before uploading.
+#[utoipa::path(
+    get,
+    path = "/vector-store/documents/hashes",
+    tag = "vector-store",
+    params(DocumentHashesQuery),
+    responses((status = 200, body = DocumentHashesResponse))
+)]
+async fn list_document_hashes(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<DocumentHashesQuery>,
+) -> ApiResult<Json<DocumentHashesResponse>> {
+    let since = params.since;
+    let hashes = state.db(move |store| store.list_content_hashes(since)).await?;
+    let bloom = match params.format {
+        Some(HashFormat::Bloom) => true,
+        Some(HashFormat::List) => false,
+        None => hashes.len() > HASH_LIST_MAX,
+    };
+    let total = hashes.len();
+    let (hashes, bloom) = if bloom {
+        let digest = BloomDigest::build(hashes.iter().map(|h| h.content_hash.as_str()), HASH_BLOOM_FP_RATE);
+        (None, Some(digest))
+    } else {
+        (Some(hashes), None)
+    };
+    Ok(Json(DocumentHashesResponse {
+        total,
+        since: params.since,
+        hashes,
+        bloom,
+    }))
+}
+
+/// GET (or HEAD) /api/vector-store/documents/by-hash/{hash} — the document
+/// with this content hash.
+#[utoipa::path(
+    get,
+    path = "/vector-store/documents/by-hash/{hash}",
+    tag = "vector-store",
+    responses(
+        (status = 200, body = Document),
+        (status = 404, description = "No document has this hash", body = ErrorBody),
+    )
+)]
+async fn get_document_by_hash(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> ApiResult<Json<Document>> {
+    state
+        .db(move |store| store.find_document_by_hash(&hash))
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("No document with this content hash"))
+}
+
+/// GET /api/vector-store/documents/:id — document plus a per-level chunk summary.
+/// Chunks are only inlined with `include_chunks=true`, capped at `MAX_INLINE_CHUNKS`.
+#[utoipa::path(
+    get,
+    path = "/vector-store/documents/{id}",
+    tag = "vector-store",
+    responses(
+        (status = 200, body = DocumentResponse),
+        (status = 404, description = "Document not found", body = ErrorBody),
+    )
+)]
 async fn get_document(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
-) -> impl IntoResponse {
-    match state.store.get_document(id) {
-        Ok(Some(doc)) => {
-            let chunks = state.store.get_chunks_for_document(id).unwrap_or_default();
-            (
-                StatusCode::OK,
-                Json(serde_json::json!({
-                    "document": doc,
-                    "chunks": chunks,
-                })),
-            )
-        }
-        Ok(None) => (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Document not found" })),
-        ),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        ),
+    Query(params): Query<GetDocumentQuery>,
+) -> ApiResult<Json<DocumentResponse>> {
+    let include_chunks = params.include_chunks.unwrap_or(false);
+    let (doc, by_level, offsets_complete, chunks) = state
+        .db(move |store| -> ApiResult<_> {
+            let doc = store
+                .get_document(id)?
+                .ok_or_else(|| ApiError::not_found("Document not found"))?;
+            let by_level = store.count_chunks_by_level(id)?;
+            let offsets_complete = store.document_offsets_complete(id)?;
+            let chunks = if include_chunks {
+                Some(store.get_chunks_for_document_paginated(id, 1, MAX_INLINE_CHUNKS, None)?.0)
+            } else {
+                None
+            };
+            Ok((doc, by_level, offsets_complete, chunks))
+        })
+        .await?;
+    let total: i64 = by_level.iter().map(|(_, count)| count).sum();
+    let level_counts = by_level
+        .iter()
+        .map(|(level, count)| (level.to_string(), *count))
+        .collect();
+
+    let mut response = DocumentResponse {
+        document: doc,
+        chunk_summary: ChunkSummary {
+            total,
+            by_level: level_counts,
+        },
+        offsets_complete,
+        chunks: None,
+        chunks_truncated: None,
+    };
+
+    if let Some(chunks) = chunks {
+        response.chunks = Some(chunks);
+        response.chunks_truncated = Some(total as usize > MAX_INLINE_CHUNKS);
     }
+
+    Ok(Json(response))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DocumentChunksQuery {
+    /// 1-based page number (default 1).
+    page: Option<usize>,
+    /// Chunks per page (default 50, at most 200).
+    page_size: Option<usize>,
+    /// Only chunks of this level.
+    level: Option<i32>,
+    /// Leave out `text` and `enriched_text`.
+    omit_text: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct DocumentChunksResponse {
+    doc_id: i64,
+    /// Chunks, without `text` and `enriched_text` when `omit_text` is set.
+    #[schema(value_type = Vec<Chunk>)]
+    chunks: Vec<serde_json::Value>,
+    total: i64,
+    page: usize,
+    page_size: usize,
+    total_pages: i64,
+}
+
+/// GET /api/vector-store/documents/:id/chunks — paginated chunks for one document.
+#[utoipa::path(
+    get,
+    path = "/vector-store/documents/{id}/chunks",
+    tag = "vector-store",
+    responses(
+        (status = 200, body = DocumentChunksResponse),
+        (status = 404, description = "Document not found", body = ErrorBody),
+    )
+)]
+async fn get_document_chunks(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(params): Query<DocumentChunksQuery>,
+) -> ApiResult<Json<DocumentChunksResponse>> {
+    let page = params.page.unwrap_or(1).max(1);
+    let page_size = params.page_size.unwrap_or(50).clamp(1, MAX_CHUNK_PAGE_SIZE);
+    let omit_text = params.omit_text.unwrap_or(false);
+
+    let level = params.level;
+    let (chunks, total) = state
+        .db(move |store| -> ApiResult<_> {
+            if store.get_document(id)?.is_none() {
+                return Err(ApiError::not_found("Document not found"));
+            }
+            Ok(store.get_chunks_for_document_paginated(id, page, page_size, level)?)
+        })
+        .await?;
+
+    let chunks: Vec<serde_json::Value> = chunks
+        .iter()
+        .map(|chunk| {
+            let mut value = serde_json::json!(chunk);
+            if omit_text {
+                if let Some(obj) = value.as_object_mut() {
+                    obj.remove("text");
+                    obj.remove("enriched_text");
+                }
+            }
+            value
+        })
+        .collect();
+
+    Ok(Json(DocumentChunksResponse {
+        doc_id: id,
+        chunks,
+        total,
+        page,
+        page_size,
+        total_pages: total_pages(total, page_size),
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SimilarDocumentsQuery {
+    /// Number of documents (default 5, at most 50).
+    top_k: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct SimilarDocumentResult {
+    doc_id: i64,
+    score: f64,
+    /// "centroid" or "bm25".
+    method: String,
+    /// `metadata.title`, or null.
+    title: serde_json::Value,
+    /// `metadata.source`, or null.
+    source: serde_json::Value,
+    metadata: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct SimilarDocumentsResponse {
+    #[serde(rename = "docId")]
+    doc_id: i64,
+    results: Vec<SimilarDocumentResult>,
+    total: usize,
+}
+
+/// GET /api/vector-store/documents/:id/similar — related documents by centroid similarity.
+#[utoipa::path(
+    get,
+    path = "/vector-store/documents/{id}/similar",
+    tag = "vector-store",
+    responses(
+        (status = 200, body = SimilarDocumentsResponse),
+        (status = 404, description = "Document not found", body = ErrorBody),
+    )
+)]
+async fn get_similar_documents(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(params): Query<SimilarDocumentsQuery>,
+) -> ApiResult<Json<SimilarDocumentsResponse>> {
+    let top_k = params.top_k.unwrap_or(5).clamp(1, 50);
+    let similar = state.db(move |store| store.find_similar_documents(id, top_k)).await?;
+
+    let results: Vec<SimilarDocumentResult> = similar
+        .into_iter()
+        .map(|doc| {
+            let field = |key: &str| {
+                doc.metadata
+                    .as_ref()
+                    .and_then(|m| m.get(key))
+                    .cloned()
+                    .unwrap_or(serde_json::Value::Null)
+            };
+            SimilarDocumentResult {
+                title: field("title"),
+                source: field("source"),
+                doc_id: doc.doc_id,
+                score: doc.score,
+                method: doc.method,
+                metadata: doc.metadata,
+            }
+        })
+        .collect();
+
+    Ok(Json(SimilarDocumentsResponse {
+        doc_id: id,
+        total: results.len(),
+        results,
+    }))
 }
 
+/// Delete a document with its chunks and embeddings.
+#[utoipa::path(
+    delete,
+    path = "/vector-store/documents/{id}",
+    tag = "vector-store",
+    responses(
+        (status = 200, body = DeleteDocumentResponse),
+        (status = 404, description = "Document not found", body = ErrorBody),
+    )
+)]
 async fn delete_document(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
-) -> impl IntoResponse {
-    match state.store.delete_document(id) {
-        Ok(true) => (
-            StatusCode::OK,
-            Json(serde_json::json!({ "deleted": true, "id": id })),
-        ),
-        Ok(false) => (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Document not found" })),
-        ),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        ),
+) -> ApiResult<Json<DeleteDocumentResponse>> {
+    if !state.db(move |store| store.delete_document(id)).await? {
+        return Err(ApiError::not_found("Document not found"));
     }
+    Ok(Json(DeleteDocumentResponse { deleted: true, id }))
 }
 
 // ---------------------------------------------------------------
 // Search
 // ---------------------------------------------------------------
 
-#[derive(Deserialize)]
-struct SearchRequest {
-    query: String,
-    #[serde(default = "default_top_k")]
-    top_k: usize,
-}
-
 fn default_top_k() -> usize {
     10
 }
 
+/// A hit as returned, before enhanced search adds its extras.
+fn search_result(hit: &SearchHit) -> SearchResult {
+    SearchResult {
+        chunk_id: hit.chunk_id,
+        doc_id: hit.doc_id,
+        text: hit.text.clone(),
+        score: hit.score,
+        metadata: hit.metadata.clone(),
+        breadcrumb: None,
+        time_range: hit.metadata.as_ref().and_then(TimeRange::from_metadata),
+        passage: None,
+        enriched_text: None,
+        parent_context: None,
+        merged_from: Vec::new(),
+    }
+}
+
+/// A request's chunk filter, rejected with 400 when it cannot be pushed
+/// into the store query. Empty filters are dropped.
+fn checked_chunk_filter(filter: Option<&ChunkFilter>) -> ApiResult<Option<&ChunkFilter>> {
+    let filter = filter.filter(|f| !f.is_empty());
+    if let Some(filter) = filter {
+        check_chunk_filter(filter).map_err(ApiError::bad_request)?;
+    }
+    Ok(filter)
+}
+
+/// Heading path of a chunk as a breadcrumb, e.g. `Guide › Install`.
+fn breadcrumb(metadata: Option<&serde_json::Value>) -> Option<String> {
+    let path: Vec<&str> = metadata?
+        .get("heading_path")?
+        .as_array()?
+        .iter()
+        .filter_map(|h| h.as_str())
+        .collect();
+    (!path.is_empty()).then(|| path.join(" › "))
+}
+
+/// Latency budget for a search: the request's override or the tier default.
+fn search_budget(state: &AppState, requested: Option<u64>) -> Duration {
+    Duration::from_millis(requested.unwrap_or(state.orchestrator.budget().search_budget_ms))
+}
+
+fn collection_not_found() -> ApiError {
+    ApiError::not_found("Collection not found")
+}
+
+/// 404 for a search scoped to a collection that does not exist.
+fn check_collection(state: &AppState, collection_id: Option<i64>) -> ApiResult<()> {
+    match collection_id {
+        Some(id) if state.store.get_collection(id)?.is_none() => Err(collection_not_found()),
+        _ => Ok(()),
+    }
+}
+
+/// Hybrid (BM25 + vector) search, BM25 only without an embedder; hits are
+/// post-processed per the search settings, which `mmr_lambda` and
+/// `max_per_doc` override.
+#[utoipa::path(post, path = "/vector-store/search", tag = "vector-store", params(TraceQuery), responses((status = 200, body = SearchResponse)))]
 async fn search(
     State(state): State<Arc<AppState>>,
+    Query(params): Query<TraceQuery>,
     Json(req): Json<SearchRequest>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
+    let trace = params.trace;
+    state
+        .blocking(move |state| request_trace::traced(trace, || run_search(state, req)))
+        .await
+}
+
+pub(crate) fn run_search(state: &AppState, req: SearchRequest) -> ApiResult<Json<SearchResponse>> {
+    let started = Instant::now();
+    let chunk_filter = checked_chunk_filter(req.chunk_filter.as_ref())?;
+    let settings = checked_post_processing(state, req.mmr_lambda, req.max_per_doc)?;
+    check_collection(state, req.collection_id)?;
+    let parsed = read_query(&req.query, req.syntax.unwrap_or_default());
+    let mut scopes = parsed.filters.clone();
+    scopes.collections.extend(req.collection_id);
+    let (text, filters) = (parsed.text.as_str(), (!scopes.is_empty()).then_some(&scopes));
+
     // Try hybrid search if embedder is available, else fall back to BM25
+    let mut diagnostics = None;
     let (results, search_type) = if state.embedder.is_available() {
-        if let Some(emb_result) = state.embedder.embed(&req.query) {
-            match state.store.hybrid_search(
-                &req.query,
+        if let Some(emb_result) = state.embedder.embed_query(text) {
+            match state.store.hybrid_search_within(
+                text,
                 &emb_result.embedding,
-                1,
+                req.level,
                 req.top_k * 2,
                 req.top_k * 2,
                 60,
+                search_budget(state, req.budget_ms),
+                chunk_filter,
+                filters,
             ) {
-                Ok(hits) => (hits, "hybrid"),
-                Err(_) => match state.store.bm25_search(&req.query, 1, req.top_k * 2) {
+                Ok((hits, search_diagnostics)) => {
+                    diagnostics = Some(search_diagnostics);
+                    (hits, "hybrid")
+                }
+                Err(_) => match state.store.bm25_search_filtered(text, req.level, req.top_k * 2, chunk_filter, filters) {
                     Ok(hits) => (hits, "bm25"),
-                    Err(e) => return Json(serde_json::json!({ "error": e.to_string() })),
+                    Err(e) => return Err(e.into()),
                 },
             }
         } else {
-            match state.store.bm25_search(&req.query, 1, req.top_k * 2) {
+            match state.store.bm25_search_filtered(text, req.level, req.top_k * 2, chunk_filter, filters) {
                 Ok(hits) => (hits, "bm25"),
-                Err(e) => return Json(serde_json::json!({ "error": e.to_string() })),
+                Err(e) => return Err(e.into()),
             }
         }
     } else {
-        match state.store.bm25_search(&req.query, 1, req.top_k * 2) {
+        match state.store.bm25_search_filtered(text, req.level, req.top_k * 2, chunk_filter, filters) {
             Ok(hits) => (hits, "bm25"),
-            Err(e) => return Json(serde_json::json!({ "error": e.to_string() })),
+            Err(e) => return Err(e.into()),
         }
     };
 
-    let boosted = apply_entity_boost(&results, &req.query);
-    let deduped = dedup_by_document(boosted, req.top_k);
+    if let Some(warning) = parsed.warning {
+        diagnostics.get_or_insert_with(Default::default).warnings.push(warning);
+    }
 
-    let formatted: Vec<serde_json::Value> = deduped
-        .iter()
-        .map(|hit| {
-            serde_json::json!({
-                "chunk_id": hit.chunk_id,
-                "doc_id": hit.doc_id,
-                "text": hit.text,
-                "score": hit.score,
-                "metadata": hit.metadata,
-            })
-        })
-        .collect();
+    let deduped = post_process_hits(state, results, text, &settings, req.top_k);
 
-    Json(serde_json::json!({
-        "results": formatted,
-        "total": formatted.len(),
-        "query": req.query,
-        "search_type": search_type,
-    }))
-}
+    let formatted: Vec<SearchResult> = deduped.iter().map(search_result).collect();
 
-#[derive(Deserialize)]
-struct EnhancedSearchRequest {
-    query: String,
-    #[serde(default = "default_top_k")]
-    top_k: usize,
-    #[serde(default)]
-    include_passages: Option<bool>,
+    log_query(
+        state,
+        QuerySample {
+            query: req.query.clone(),
+            search_type: search_type.to_string(),
+            result_count: formatted.len(),
+            top_score: top_score(formatted.iter().map(|r| r.score)),
+            latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+            diagnostics: Some(sample_diagnostics(req.top_k, req.level, diagnostics.as_ref())),
+        },
+    );
+
+    Ok(Json(SearchResponse {
+        total: formatted.len(),
+        results: formatted,
+        query: req.query,
+        search_type: search_type.to_string(),
+        diagnostics,
+    }))
 }
 
+/// Search with passages, enrichment and parent section context per hit.
+#[utoipa::path(post, path = "/vector-store/search/enhanced", tag = "vector-store", params(TraceQuery), responses((status = 200, body = SearchResponse)))]
 async fn enhanced_search(
     State(state): State<Arc<AppState>>,
+    Query(params): Query<TraceQuery>,
     Json(req): Json<EnhancedSearchRequest>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
+    let trace = params.trace;
+    state
+        .blocking(move |state| request_trace::traced(trace, || run_enhanced_search(state, req)))
+        .await
+}
+
+fn run_enhanced_search(state: &AppState, req: EnhancedSearchRequest) -> ApiResult<Json<SearchResponse>> {
+    let started = Instant::now();
     let include_passages = req.include_passages.unwrap_or(true);
+    let passage_window = req
+        .passage_window
+        .unwrap_or(DEFAULT_PASSAGE_WINDOW)
+        .clamp(1, MAX_PASSAGE_WINDOW);
+    let chunk_filter = checked_chunk_filter(req.chunk_filter.as_ref())?;
+    let settings = checked_post_processing(state, req.mmr_lambda, req.max_per_doc)?;
+    check_collection(state, req.collection_id)?;
+    let scope = req.collection_id.map(|id| SearchFilters { collections: vec![id], ..Default::default() });
 
     // Try hybrid search if embedder is available
+    let mut diagnostics = None;
     let (results, search_type) = if state.embedder.is_available() {
-        if let Some(emb_result) = state.embedder.embed(&req.query) {
-            match state.store.hybrid_search(
+        if let Some(emb_result) = state.embedder.embed_query(&req.query) {
+            match state.store.hybrid_search_within(
                 &req.query,
                 &emb_result.embedding,
-                1,
+                req.level,
                 req.top_k * 2,
                 req.top_k * 2,
                 60,
+                search_budget(state, req.budget_ms),
+                chunk_filter,
+                scope.as_ref(),
             ) {
-                Ok(hits) => (hits, "enhanced_hybrid"),
-                Err(_) => match state.store.bm25_search(&req.query, 1, req.top_k * 2) {
+                Ok((hits, search_diagnostics)) => {
+                    diagnostics = Some(search_diagnostics);
+                    (hits, "enhanced_hybrid")
+                }
+                Err(_) => match state.store.bm25_search_filtered(&req.query, req.level, req.top_k * 2, chunk_filter, scope.as_ref()) {
                     Ok(hits) => (hits, "enhanced_bm25"),
-                    Err(e) => return Json(serde_json::json!({ "error": e.to_string() })),
+                    Err(e) => return Err(e.into()),
                 },
             }
         } else {
-            match state.store.bm25_search(&req.query, 1, req.top_k * 2) {
+            match state.store.bm25_search_filtered(&req.query, req.level, req.top_k * 2, chunk_filter, scope.as_ref()) {
                 Ok(hits) => (hits, "enhanced_bm25"),
-                Err(e) => return Json(serde_json::json!({ "error": e.to_string() })),
+                Err(e) => return Err(e.into()),
             }
         }
     } else {
-        match state.store.bm25_search(&req.query, 1, req.top_k * 2) {
+        match state.store.bm25_search_filtered(&req.query, req.level, req.top_k * 2, chunk_filter, scope.as_ref()) {
             Ok(hits) => (hits, "enhanced_bm25"),
-            Err(e) => return Json(serde_json::json!({ "error": e.to_string() })),
+            Err(e) => return Err(e.into()),
         }
     };
 
-    let boosted = apply_entity_boost(&results, &req.query);
-    let deduped = dedup_by_document(boosted, req.top_k);
+    let deduped = merge_overlapping_hits(post_process_hits(state, results, &req.query, &settings, req.top_k));
 
-    let formatted: Vec<serde_json::Value> = deduped
-        .iter()
-        .map(|hit| {
-            let mut result = serde_json::json!({
-                "chunk_id": hit.chunk_id,
-                "doc_id": hit.doc_id,
-                "text": hit.text,
-                "score": hit.score,
-                "metadata": hit.metadata,
-            });
+    // Parent context for all hits in one query
+    let parent_ids: Vec<i64> = deduped.iter().filter_map(|merged| merged.hit.parent_chunk_id).collect();
+    let parents: HashMap<i64, mindsage_store::Chunk> = state
+        .store
+        .get_chunks_by_ids(&parent_ids)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| (c.id, c))
+        .collect();
+
+    let formatted: Vec<SearchResult> = deduped
+        .into_iter()
+        .map(|MergedHit { hit, merged_from }| {
+            let mut result = search_result(&hit);
+            result.merged_from = merged_from;
 
             if include_passages {
-                let passage = extract_passage(&hit.text, &req.query);
-                result["passage"] = serde_json::json!({
-                    "text": passage,
-                    "method": "heuristic",
+                result.passage = Some(Passage {
+                    text: extract_passage(&hit.text, &req.query, passage_window),
+                    method: "heuristic".to_string(),
                 });
             }
 
             // Include enriched metadata if available
-            if let Some(enriched) = &hit.enriched_text {
-                result["enriched_text"] = serde_json::json!(enriched);
-            }
+            result.enriched_text = hit.enriched_text.clone();
+            result.breadcrumb = breadcrumb(hit.metadata.as_ref());
 
             // Add parent context if available
             if let Some(parent_id) = hit.parent_chunk_id {
-                if let Ok(Some(parent)) = state.store.get_chunk(parent_id) {
-                    result["parent_context"] = serde_json::json!({
-                        "text": parent.text,
-                        "chunk_id": parent.id,
+                if let Some(parent) = parents.get(&parent_id) {
+                    result.parent_context = Some(ParentContext {
+                        text: parent.text.clone(),
+                        chunk_id: parent.id,
                     });
                 }
             }
@@ -470,276 +1354,482 @@ async fn enhanced_search(
         })
         .collect();
 
-    Json(serde_json::json!({
-        "results": formatted,
-        "total": formatted.len(),
-        "query": req.query,
-        "search_type": search_type,
+    log_query(
+        state,
+        QuerySample {
+            query: req.query.clone(),
+            search_type: search_type.to_string(),
+            result_count: formatted.len(),
+            top_score: top_score(formatted.iter().map(|r| r.score)),
+            latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+            diagnostics: Some(sample_diagnostics(req.top_k, req.level, diagnostics.as_ref())),
+        },
+    );
+
+    Ok(Json(SearchResponse {
+        total: formatted.len(),
+        results: formatted,
+        query: req.query,
+        search_type: search_type.to_string(),
+        diagnostics,
     }))
 }
 
-/// Apply entity boost to search results: +0.15 if query entities match enriched_text.
-fn apply_entity_boost(results: &[SearchHit], query: &str) -> Vec<SearchHit> {
-    let query_lower = query.to_lowercase();
-    let query_terms: Vec<&str> = query_lower.split_whitespace().collect();
+/// Record a successful query for autocomplete unless the query log is
+/// disabled, and the search in the query stats log unless that is off.
+fn log_query(state: &AppState, sample: QuerySample) {
+    if state.config.query_log {
+        if let Err(e) = state.store.record_query(&sample.query, sample.result_count) {
+            debug!("Failed to record query: {}", e);
+        }
+    }
+    if let Err(e) = state.store.record_search(sample) {
+        debug!("Failed to record query stats: {}", e);
+    }
+}
 
-    results
-        .iter()
-        .map(|hit| {
-            let mut boosted = hit.clone();
-            if let Some(enriched) = &hit.enriched_text {
-                let enriched_lower = enriched.to_lowercase();
-                // Check if any query term appears in the enriched entities/topics
-                let has_entity_match = query_terms
-                    .iter()
-                    .any(|term| term.len() > 2 && enriched_lower.contains(term));
-                if has_entity_match {
-                    boosted.score += 0.15;
-                }
-            }
-            boosted
-        })
-        .collect()
+fn top_score(scores: impl Iterator<Item = f64>) -> Option<f32> {
+    scores.reduce(f64::max).map(|score| score as f32)
+}
+
+/// Diagnostics kept with a slow or zero-hit search in query stats capture
+/// mode: the request's size and level alongside the search's own.
+fn sample_diagnostics(top_k: usize, level: Option<i32>, diagnostics: Option<&SearchDiagnostics>) -> serde_json::Value {
+    let mut value = diagnostics
+        .and_then(|d| serde_json::to_value(d).ok())
+        .unwrap_or_else(|| serde_json::json!({}));
+    if let Some(object) = value.as_object_mut() {
+        object.insert("topK".to_string(), top_k.into());
+        object.insert("level".to_string(), serde_json::json!(level));
+    }
+    value
 }
 
-/// Deduplicate search results by document: keep only the best-scoring chunk per parent document.
-fn dedup_by_document(mut results: Vec<SearchHit>, top_k: usize) -> Vec<SearchHit> {
-    // Sort by score descending
-    results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SuggestQuery {
+    /// Typed prefix.
+    #[serde(default)]
+    q: String,
+    /// At most 10.
+    limit: Option<usize>,
+}
 
-    let mut seen_docs: HashMap<i64, bool> = HashMap::new();
-    let mut deduped = Vec::new();
+#[derive(Serialize, ToSchema)]
+struct SuggestResponse {
+    query: String,
+    suggestions: Vec<Suggestion>,
+    total: usize,
+}
 
-    for hit in results {
-        if seen_docs.contains_key(&hit.doc_id) {
-            continue;
-        }
-        seen_docs.insert(hit.doc_id, true);
-        deduped.push(hit);
-        if deduped.len() >= top_k {
-            break;
-        }
-    }
+/// Most completions returned by `GET /suggest`.
+const MAX_SUGGESTIONS: usize = 10;
 
-    deduped
+/// GET /api/vector-store/suggest?q=prefix — completions from past queries and the FTS vocabulary.
+#[utoipa::path(get, path = "/vector-store/suggest", tag = "vector-store", responses((status = 200, body = SuggestResponse)))]
+async fn suggest(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SuggestQuery>,
+) -> ApiResult<Json<SuggestResponse>> {
+    let limit = params.limit.unwrap_or(MAX_SUGGESTIONS).clamp(1, MAX_SUGGESTIONS);
+    let prefix = params.q.clone();
+    let suggestions = state.db(move |store| store.suggest(&prefix, limit)).await?;
+    Ok(Json(SuggestResponse {
+        query: params.q,
+        total: suggestions.len(),
+        suggestions,
+    }))
 }
 
-/// Heuristic passage extraction: find a window around query term matches.
-fn extract_passage(text: &str, query: &str) -> String {
-    let lower_text = text.to_lowercase();
-    let terms: Vec<&str> = query.split_whitespace().collect();
+/// Largest `passage_window` honoured; whole chunks are rarely longer.
+const MAX_PASSAGE_WINDOW: usize = 2000;
 
-    // Find first matching term position
-    let mut best_pos = None;
-    for term in &terms {
-        if let Some(pos) = lower_text.find(&term.to_lowercase()) {
-            best_pos = Some(pos);
-            break;
+/// The search post-processing settings with a request's overrides,
+/// rejected with 400 when out of range.
+fn checked_post_processing(
+    state: &AppState,
+    mmr_lambda: Option<f64>,
+    max_per_doc: Option<usize>,
+) -> ApiResult<SearchPostProcessing> {
+    let mut settings = state.store.search_post_processing();
+    if let Some(lambda) = mmr_lambda {
+        if !(0.0..=1.0).contains(&lambda) {
+            return Err(ApiError::bad_request("mmr_lambda must be between 0 and 1"));
         }
+        settings.mmr_lambda = lambda;
     }
+    if let Some(max) = max_per_doc {
+        if max == 0 {
+            return Err(ApiError::bad_request("max_per_doc must be at least 1"));
+        }
+        settings.max_chunks_per_doc = Some(max);
+    }
+    Ok(settings)
+}
 
-    let pos = best_pos.unwrap_or(0);
-    let window = 200; // chars on each side
-    let start = pos.saturating_sub(window);
-    let end = (pos + window).min(text.len());
+/// Embeddings of the hits' chunks for an MMR selection at `lambda`; none
+/// when it ranks by relevance alone or no embedder is loaded, so texts are
+/// compared instead.
+fn mmr_embeddings(state: &AppState, hits: &[SearchHit], lambda: f64) -> HashMap<i64, Array1<f32>> {
+    if lambda < 1.0 && state.embedder.is_available() {
+        let ids: Vec<i64> = hits.iter().map(|h| h.chunk_id).collect();
+        state.store.get_chunk_embeddings(&ids).unwrap_or_default()
+    } else {
+        HashMap::new()
+    }
+}
 
-    // Expand to word boundaries
-    let start = text[..start]
-        .rfind(' ')
-        .map(|p| p + 1)
-        .unwrap_or(start);
-    let end = text[end..]
-        .find(' ')
-        .map(|p| end + p)
-        .unwrap_or(end);
+/// Pick `top_k` of the fused hits by Maximal Marginal Relevance.
+pub(crate) fn diversify(state: &AppState, hits: Vec<SearchHit>, diversity: Diversity, top_k: usize) -> Vec<SearchHit> {
+    let embeddings = mmr_embeddings(state, &hits, diversity.lambda);
+    mmr_select(hits, &embeddings, diversity, top_k)
+}
 
-    let mut passage = text[start..end].to_string();
-    if start > 0 {
-        passage = format!("...{}", passage);
-    }
-    if end < text.len() {
-        passage = format!("{}...", passage);
-    }
-    passage
+/// Boost and pick `top_k` of the fused hits for `query` per `settings`;
+/// search results and chat context both go through here.
+pub(crate) fn post_process_hits(
+    state: &AppState,
+    hits: Vec<SearchHit>,
+    query: &str,
+    settings: &SearchPostProcessing,
+    top_k: usize,
+) -> Vec<SearchHit> {
+    let embeddings = mmr_embeddings(state, &hits, settings.mmr_lambda);
+    post_process(hits, query, settings, &embeddings, top_k)
+}
+
+/// GET /api/vector-store/search/settings — how search hits are re-ranked.
+#[utoipa::path(get, path = "/vector-store/search/settings", tag = "vector-store", responses((status = 200, body = SearchPostProcessing)))]
+async fn get_search_settings(State(state): State<Arc<AppState>>) -> Json<SearchPostProcessing> {
+    Json(state.store.search_post_processing())
+}
+
+/// PUT /api/vector-store/search/settings — replace the settings, saved to
+/// `data/search-settings.json`. Fields left out get their defaults.
+#[utoipa::path(
+    put,
+    path = "/vector-store/search/settings",
+    tag = "vector-store",
+    request_body = SearchPostProcessing,
+    responses((status = 200, body = SearchPostProcessing), (status = 400, description = "Setting out of range"))
+)]
+async fn put_search_settings(
+    State(state): State<Arc<AppState>>,
+    Json(settings): Json<SearchPostProcessing>,
+) -> ApiResult<Json<SearchPostProcessing>> {
+    settings.validate().map_err(ApiError::bad_request)?;
+    state
+        .blocking(move |state| {
+            settings
+                .save(&state.config.data_paths.search_settings_file)
+                .map_err(|e| ApiError::internal(format!("Failed to save search settings: {}", e)))?;
+            state.store.set_search_post_processing(settings);
+            Ok(Json(settings))
+        })
+        .await
 }
 
 // ---------------------------------------------------------------
 // Topics (Phase 1 stubs — full implementation in Phase 2/3)
 // ---------------------------------------------------------------
 
-async fn get_topics(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
-    // Scan all document metadata for topics
-    let docs = state.store.get_all_documents(false).unwrap_or_default();
-    let mut topic_counts: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
-
-    for doc in &docs {
-        if let Some(metadata) = &doc.metadata {
-            if let Some(topics) = metadata.get("topics").and_then(|t| t.as_array()) {
-                for topic in topics {
-                    if let Some(t) = topic.as_str() {
-                        *topic_counts.entry(t.to_string()).or_insert(0) += 1;
-                    }
-                }
-            }
-        }
-    }
+/// A topic and its document count. `topic`/`count` for existing clients,
+/// `name`/`document_count` for the frontend's AllTopicsResult.
+#[derive(Serialize, ToSchema)]
+struct TopicCount {
+    topic: String,
+    count: i64,
+    name: String,
+    document_count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct TopicsResponse {
+    success: bool,
+    total_unique_topics: usize,
+    topics: Vec<TopicCount>,
+}
 
-    let topics: Vec<serde_json::Value> = topic_counts
+/// All topics with their document counts.
+#[utoipa::path(get, path = "/vector-store/topics", tag = "topics", responses((status = 200, body = TopicsResponse)))]
+async fn get_topics(State(state): State<Arc<AppState>>) -> ApiResult<Json<TopicsResponse>> {
+    let counts = state.db(|store| store.list_topics()).await?;
+
+    let topics: Vec<TopicCount> = counts
         .into_iter()
-        .map(|(topic, count)| serde_json::json!({ "topic": topic, "count": count }))
+        .map(|(topic, count)| TopicCount {
+            name: topic.clone(),
+            topic,
+            count,
+            document_count: count,
+        })
         .collect();
 
-    Json(serde_json::json!({ "topics": topics }))
+    Ok(Json(TopicsResponse {
+        success: true,
+        total_unique_topics: topics.len(),
+        topics,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TopicDocumentsQuery {
+    /// 1-based page number (default 1).
+    page: Option<usize>,
+    /// Documents per page (default 50, at most 500).
+    page_size: Option<usize>,
 }
 
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct TopicDocumentsResponse {
+    topic: String,
+    documents: Vec<Document>,
+    total: i64,
+    page: usize,
+    page_size: usize,
+    total_pages: i64,
+}
+
+/// Documents tagged with a topic.
+#[utoipa::path(get, path = "/vector-store/topics/{topic}/documents", tag = "topics", responses((status = 200, body = TopicDocumentsResponse)))]
 async fn get_documents_by_topic(
     State(state): State<Arc<AppState>>,
     Path(topic): Path<String>,
-) -> Json<serde_json::Value> {
-    let docs = state.store.get_all_documents(false).unwrap_or_default();
-    let filtered: Vec<&mindsage_store::Document> = docs
-        .iter()
-        .filter(|doc| {
-            doc.metadata
-                .as_ref()
-                .and_then(|m| m.get("topics"))
-                .and_then(|t| t.as_array())
-                .map(|topics| {
-                    topics
-                        .iter()
-                        .any(|t| t.as_str() == Some(topic.as_str()))
-                })
-                .unwrap_or(false)
-        })
-        .collect();
+    Query(params): Query<TopicDocumentsQuery>,
+) -> ApiResult<Json<TopicDocumentsResponse>> {
+    let page = params.page.unwrap_or(1).max(1);
+    let page_size = params.page_size.unwrap_or(50).clamp(1, 500);
+    let lookup = topic.clone();
+    let (docs, total) = state
+        .db(move |store| store.get_documents_by_topic_paginated(&lookup, page, page_size))
+        .await?;
+
+    Ok(Json(TopicDocumentsResponse {
+        topic,
+        documents: docs,
+        total,
+        page,
+        page_size,
+        total_pages: total_pages(total, page_size),
+    }))
+}
 
-    Json(serde_json::json!({
-        "topic": topic,
-        "documents": filtered,
-        "total": filtered.len(),
+/// Top entities reported by `GET /topics/{topic}/stats`.
+const TOPIC_STATS_ENTITIES: usize = 10;
+
+#[derive(Serialize, ToSchema)]
+struct TopicStatsResponse {
+    stats: TopicStats,
+}
+
+/// GET /api/vector-store/topics/:topic/stats — counts, date range, top entities.
+#[utoipa::path(
+    get,
+    path = "/vector-store/topics/{topic}/stats",
+    tag = "topics",
+    responses(
+        (status = 200, body = TopicStatsResponse),
+        (status = 404, description = "Topic not found", body = ErrorBody),
+    )
+)]
+async fn get_topic_stats(
+    State(state): State<Arc<AppState>>,
+    Path(topic): Path<String>,
+) -> ApiResult<Json<TopicStatsResponse>> {
+    let stats = state
+        .db(move |store| store.topic_stats(&topic, TOPIC_STATS_ENTITIES))
+        .await?
+        .ok_or_else(|| ApiError::not_found("Topic not found"))?;
+    Ok(Json(TopicStatsResponse { stats }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CooccurrenceQuery {
+    /// Number of pairs (default 50, at most 500).
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CooccurrenceResponse {
+    pairs: Vec<TopicPair>,
+    total: usize,
+}
+
+/// GET /api/vector-store/topics/cooccurrence — topic pairs sharing the most documents.
+#[utoipa::path(get, path = "/vector-store/topics/cooccurrence", tag = "topics", responses((status = 200, body = CooccurrenceResponse)))]
+async fn get_topic_cooccurrence(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CooccurrenceQuery>,
+) -> ApiResult<Json<CooccurrenceResponse>> {
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let pairs = state.db(move |store| store.topic_cooccurrence(limit)).await?;
+    Ok(Json(CooccurrenceResponse {
+        total: pairs.len(),
+        pairs,
     }))
 }
 
+#[derive(Serialize, ToSchema)]
+struct DocumentTopicsResponse {
+    /// `metadata.topics` of the document, `[]` if unset.
+    #[schema(value_type = Vec<String>)]
+    topics: serde_json::Value,
+    doc_id: i64,
+}
+
+/// Topics of one document.
+#[utoipa::path(
+    get,
+    path = "/vector-store/documents/{id}/topics",
+    tag = "topics",
+    responses(
+        (status = 200, body = DocumentTopicsResponse),
+        (status = 404, description = "Document not found", body = ErrorBody),
+    )
+)]
 async fn get_document_topics(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
-) -> impl IntoResponse {
-    match state.store.get_document(id) {
-        Ok(Some(doc)) => {
-            let topics = doc
-                .metadata
-                .as_ref()
-                .and_then(|m| m.get("topics"))
-                .cloned()
-                .unwrap_or(serde_json::json!([]));
-            (StatusCode::OK, Json(serde_json::json!({ "topics": topics, "doc_id": id })))
-        }
-        Ok(None) => (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Document not found" })),
-        ),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        ),
-    }
+) -> ApiResult<Json<DocumentTopicsResponse>> {
+    let doc = state
+        .db(move |store| store.get_document(id))
+        .await?
+        .ok_or_else(|| ApiError::not_found("Document not found"))?;
+    let topics = doc
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get("topics"))
+        .cloned()
+        .unwrap_or(serde_json::json!([]));
+    Ok(Json(DocumentTopicsResponse { topics, doc_id: id }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct UpdateTopicsRequest {
     topics: Vec<String>,
 }
 
+#[derive(Serialize, ToSchema)]
+struct UpdateTopicsResponse {
+    updated: bool,
+    topics: Vec<String>,
+}
+
+/// Replace the topics of one document.
+#[utoipa::path(
+    put,
+    path = "/vector-store/documents/{id}/topics",
+    tag = "topics",
+    responses(
+        (status = 200, body = UpdateTopicsResponse),
+        (status = 404, description = "Document not found", body = ErrorBody),
+    )
+)]
 async fn update_document_topics(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
     Json(req): Json<UpdateTopicsRequest>,
-) -> impl IntoResponse {
+) -> ApiResult<Json<UpdateTopicsResponse>> {
     let updates = serde_json::json!({ "topics": req.topics });
-    match state.store.update_document_metadata(id, &updates) {
-        Ok(true) => (
-            StatusCode::OK,
-            Json(serde_json::json!({ "updated": true, "topics": req.topics })),
-        ),
-        Ok(false) => (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Document not found" })),
-        ),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        ),
+    if !state.db(move |store| store.update_document_metadata(id, &updates)).await? {
+        return Err(ApiError::not_found("Document not found"));
     }
+    Ok(Json(UpdateTopicsResponse {
+        updated: true,
+        topics: req.topics,
+    }))
+}
+
+/// Most documents accepted by `POST /topics/generate`.
+const MAX_TOPIC_BATCH: usize = 50;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GenerateTopicsQuery {
+    #[serde(default)]
+    mode: TopicMode,
+    /// Replace PII with tokens before the document is sent to the LLM.
+    #[serde(default)]
+    anonymize: bool,
 }
 
+/// POST /api/vector-store/documents/:id/topics/generate?mode=heuristic|llm&anonymize=true
+#[utoipa::path(
+    post,
+    path = "/vector-store/documents/{id}/topics/generate",
+    tag = "topics",
+    responses(
+        (status = 200, body = TopicGeneration),
+        (status = 404, description = "Document not found", body = ErrorBody),
+    )
+)]
 async fn generate_topics(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
-) -> impl IntoResponse {
-    // Use heuristic extraction to generate topics
-    let doc = match state.store.get_document(id) {
-        Ok(Some(doc)) => doc,
-        Ok(None) => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(serde_json::json!({ "error": "Document not found" })),
-            );
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            );
-        }
-    };
+    Query(params): Query<GenerateTopicsQuery>,
+) -> ApiResult<Json<TopicGeneration>> {
+    let generated = generate_document_topics(&state, id, params.mode, params.anonymize)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Document not found"))?;
+    Ok(Json(generated))
+}
 
-    let source = doc
-        .metadata
-        .as_ref()
-        .and_then(|m| m.get("source"))
-        .and_then(|s| s.as_str());
-    let filename = doc
-        .metadata
-        .as_ref()
-        .and_then(|m| m.get("filename"))
-        .and_then(|s| s.as_str());
+#[derive(Deserialize, ToSchema)]
+struct BatchGenerateTopicsRequest {
+    doc_ids: Vec<i64>,
+    #[serde(default)]
+    mode: TopicMode,
+    #[serde(default)]
+    anonymize: bool,
+}
 
-    let result = mindsage_ingest::extract_all(&doc.text, source, filename);
+#[derive(Serialize, ToSchema)]
+struct BatchGenerateTopicsResponse {
+    results: Vec<TopicGeneration>,
+    total: usize,
+    /// Results produced by heuristics after LLM mode was requested.
+    fallbacks: usize,
+    not_found: Vec<i64>,
+}
 
-    // Update document metadata with topics
-    let updates = serde_json::json!({
-        "topics": result.topics,
-        "primary_topic": result.primary_topic,
-        "extraction_method": "heuristic",
-    });
-    let _ = state.store.update_document_metadata(id, &updates);
+/// POST /api/vector-store/topics/generate — generate topics for several documents.
+#[utoipa::path(post, path = "/vector-store/topics/generate", tag = "topics", responses((status = 200, body = BatchGenerateTopicsResponse)))]
+async fn batch_generate_topics(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BatchGenerateTopicsRequest>,
+) -> ApiResult<Json<BatchGenerateTopicsResponse>> {
+    if req.doc_ids.is_empty() {
+        return Err(ApiError::bad_request("doc_ids is required"));
+    }
+    if req.doc_ids.len() > MAX_TOPIC_BATCH {
+        return Err(ApiError::bad_request(format!(
+            "At most {} documents per batch",
+            MAX_TOPIC_BATCH
+        )));
+    }
 
-    // Also enrich chunks
-    if let Ok(chunks) = state.store.get_chunks_for_document(id) {
-        for chunk in &chunks {
-            if chunk.enriched_text.is_some() {
-                continue;
-            }
-            let chunk_result = mindsage_ingest::extract_all(&chunk.text, source, filename);
-            let enriched = mindsage_ingest::build_enriched_text(&chunk_result);
-            if !enriched.is_empty() {
-                let _ = state.store.update_chunk_enriched_text(chunk.id, &enriched);
-            }
+    let mut results = Vec::with_capacity(req.doc_ids.len());
+    let mut not_found = Vec::new();
+    for id in req.doc_ids {
+        match generate_document_topics(&state, id, req.mode, req.anonymize).await? {
+            Some(generated) => results.push(generated),
+            None => not_found.push(id),
         }
     }
 
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({
-            "doc_id": id,
-            "topics": result.topics,
-            "primary_topic": result.primary_topic,
-            "method": "heuristic",
-        })),
-    )
+    let fallbacks = results.iter().filter(|r| r.fallback).count();
+    Ok(Json(BatchGenerateTopicsResponse {
+        total: results.len(),
+        results,
+        fallbacks,
+        not_found,
+    }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct SearchWithTopicRequest {
     query: String,
     topic: String,
@@ -747,76 +1837,1072 @@ struct SearchWithTopicRequest {
     top_k: usize,
 }
 
+#[derive(Serialize, ToSchema)]
+struct TopicSearchResponse {
+    results: Vec<SearchHit>,
+    total: usize,
+    query: String,
+    topic: String,
+}
+
+/// Search restricted to documents tagged with a topic.
+#[utoipa::path(post, path = "/vector-store/search/with-topic", tag = "vector-store", params(TraceQuery), responses((status = 200, body = TopicSearchResponse)))]
 async fn search_with_topic(
     State(state): State<Arc<AppState>>,
+    Query(params): Query<TraceQuery>,
     Json(req): Json<SearchWithTopicRequest>,
-) -> Json<serde_json::Value> {
+) -> ApiResult<Json<serde_json::Value>> {
+    let trace = params.trace;
+    state
+        .blocking(move |state| request_trace::traced(trace, || run_search_with_topic(state, req)))
+        .await
+}
+
+fn run_search_with_topic(state: &AppState, req: SearchWithTopicRequest) -> ApiResult<Json<TopicSearchResponse>> {
+    let started = Instant::now();
     // Hybrid or BM25 search, then filter by topic
     let search_results = if state.embedder.is_available() {
-        if let Some(emb_result) = state.embedder.embed(&req.query) {
+        if let Some(emb_result) = state.embedder.embed_query(&req.query) {
             state.store.hybrid_search(
                 &req.query,
                 &emb_result.embedding,
-                1,
+                None,
                 req.top_k * 3,
                 req.top_k * 3,
                 60,
             )
         } else {
-            state.store.bm25_search(&req.query, 1, req.top_k * 3)
+            state.store.bm25_search(&req.query, None, req.top_k * 3)
         }
     } else {
-        state.store.bm25_search(&req.query, 1, req.top_k * 3)
+        state.store.bm25_search(&req.query, None, req.top_k * 3)
     };
-    match search_results {
-        Ok(results) => {
-            let filtered: Vec<&mindsage_store::SearchHit> = results
-                .iter()
-                .filter(|hit| {
-                    hit.metadata
-                        .as_ref()
-                        .and_then(|m| m.get("topics"))
-                        .and_then(|t| t.as_array())
-                        .map(|topics| {
-                            topics.iter().any(|t| t.as_str() == Some(req.topic.as_str()))
-                        })
-                        .unwrap_or(false)
-                })
-                .take(req.top_k)
-                .collect();
-
-            Json(serde_json::json!({
-                "results": filtered,
-                "total": filtered.len(),
-                "query": req.query,
-                "topic": req.topic,
-            }))
-        }
-        Err(e) => Json(serde_json::json!({ "error": e.to_string() })),
+    let results = search_results?;
+    let filtered: Vec<SearchHit> = results
+        .into_iter()
+        .filter(|hit| {
+            hit.metadata
+                .as_ref()
+                .and_then(|m| m.get("topics"))
+                .and_then(|t| t.as_array())
+                .map(|topics| topics.iter().any(|t| t.as_str() == Some(req.topic.as_str())))
+                .unwrap_or(false)
+        })
+        .collect();
+    let filtered = diversify(state, filtered, Diversity::RELEVANCE, req.top_k);
+
+    log_query(
+        state,
+        QuerySample {
+            query: req.query.clone(),
+            search_type: "topic".to_string(),
+            result_count: filtered.len(),
+            top_score: top_score(filtered.iter().map(|hit| hit.score)),
+            latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+            diagnostics: Some(serde_json::json!({ "topK": req.top_k, "topic": req.topic })),
+        },
+    );
+
+    Ok(Json(TopicSearchResponse {
+        total: filtered.len(),
+        results: filtered,
+        query: req.query,
+        topic: req.topic,
+    }))
+}
+
+// ---------------------------------------------------------------
+// Tags
+// ---------------------------------------------------------------
+
+#[derive(Serialize, ToSchema)]
+struct TagCount {
+    tag: String,
+    count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct TagsResponse {
+    tags: Vec<TagCount>,
+    total: usize,
+}
+
+/// GET /api/vector-store/tags — every tag with its document count.
+#[utoipa::path(get, path = "/vector-store/tags", tag = "tags", responses((status = 200, body = TagsResponse)))]
+async fn get_tags(State(state): State<Arc<AppState>>) -> ApiResult<Json<TagsResponse>> {
+    let tags: Vec<TagCount> = state
+        .db(|store| store.list_tags())
+        .await?
+        .into_iter()
+        .map(|(tag, count)| TagCount { tag, count })
+        .collect();
+    Ok(Json(TagsResponse { total: tags.len(), tags }))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct TagDocumentsResponse {
+    tag: String,
+    documents: Vec<Document>,
+    total: i64,
+    page: usize,
+    page_size: usize,
+    total_pages: i64,
+}
+
+/// GET /api/vector-store/tags/:tag/documents — documents with a tag, newest first.
+#[utoipa::path(
+    get,
+    path = "/vector-store/tags/{tag}/documents",
+    tag = "tags",
+    params(TopicDocumentsQuery),
+    responses((status = 200, body = TagDocumentsResponse))
+)]
+async fn get_documents_by_tag(
+    State(state): State<Arc<AppState>>,
+    Path(tag): Path<String>,
+    Query(params): Query<TopicDocumentsQuery>,
+) -> ApiResult<Json<TagDocumentsResponse>> {
+    let tag = parse_tag(&tag)?;
+    let page = params.page.unwrap_or(1).max(1);
+    let page_size = params.page_size.unwrap_or(50).clamp(1, 500);
+    let lookup = tag.clone();
+    let (documents, total) = state
+        .db(move |store| store.list_documents_by_tag(&lookup, page, page_size))
+        .await?;
+    Ok(Json(TagDocumentsResponse {
+        tag,
+        documents,
+        total,
+        page,
+        page_size,
+        total_pages: total_pages(total, page_size),
+    }))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct DocumentTagResponse {
+    doc_id: i64,
+    tag: String,
+    /// Whether the request changed anything.
+    changed: bool,
+    /// The document's tags afterwards.
+    tags: Vec<String>,
+}
+
+fn parse_tag(raw: &str) -> ApiResult<String> {
+    normalize_tag(raw).ok_or_else(|| {
+        ApiError::bad_request(format!(
+            "Tags are 1-{} characters without whitespace",
+            MAX_TAG_CHARS
+        ))
+    })
+}
+
+/// PUT /api/vector-store/documents/:id/tags/:tag — tag a document. Tags are
+/// lowercased and kept apart from `metadata`, so metadata updates keep them.
+#[utoipa::path(
+    put,
+    path = "/vector-store/documents/{id}/tags/{tag}",
+    tag = "tags",
+    responses(
+        (status = 200, body = DocumentTagResponse),
+        (status = 400, description = "Invalid tag", body = ErrorBody),
+        (status = 404, description = "Document not found", body = ErrorBody),
+    )
+)]
+async fn add_document_tag(
+    State(state): State<Arc<AppState>>,
+    Path((id, tag)): Path<(i64, String)>,
+) -> ApiResult<Json<DocumentTagResponse>> {
+    let tag = parse_tag(&tag)?;
+    let added = tag.clone();
+    let (changed, tags) = state
+        .db(move |store| -> ApiResult<_> { Ok((store.add_tag(id, &added)?, store.get_tags(id)?)) })
+        .await?;
+    Ok(Json(DocumentTagResponse { doc_id: id, tag, changed, tags }))
+}
+
+/// DELETE /api/vector-store/documents/:id/tags/:tag — untag a document.
+#[utoipa::path(
+    delete,
+    path = "/vector-store/documents/{id}/tags/{tag}",
+    tag = "tags",
+    responses(
+        (status = 200, body = DocumentTagResponse),
+        (status = 400, description = "Invalid tag", body = ErrorBody),
+        (status = 404, description = "Document not found", body = ErrorBody),
+    )
+)]
+async fn remove_document_tag(
+    State(state): State<Arc<AppState>>,
+    Path((id, tag)): Path<(i64, String)>,
+) -> ApiResult<Json<DocumentTagResponse>> {
+    let tag = parse_tag(&tag)?;
+    let removed = tag.clone();
+    let (changed, tags) = state
+        .db(move |store| -> ApiResult<_> {
+            if store.get_document(id)?.is_none() {
+                return Err(ApiError::not_found("Document not found"));
+            }
+            Ok((store.remove_tag(id, &removed)?, store.get_tags(id)?))
+        })
+        .await?;
+    Ok(Json(DocumentTagResponse { doc_id: id, tag, changed, tags }))
+}
+
+// ---------------------------------------------------------------
+// Collections
+// ---------------------------------------------------------------
+
+/// Most document ids added to or removed from a collection per request.
+const MAX_COLLECTION_BATCH: usize = 10_000;
+
+#[derive(Serialize, ToSchema)]
+struct CollectionsResponse {
+    collections: Vec<Collection>,
+    total: usize,
+}
+
+/// GET /api/vector-store/collections — every collection with its document
+/// count, by name.
+#[utoipa::path(get, path = "/vector-store/collections", tag = "collections", responses((status = 200, body = CollectionsResponse)))]
+async fn list_collections(State(state): State<Arc<AppState>>) -> ApiResult<Json<CollectionsResponse>> {
+    let collections = state.db(|store| store.list_collections()).await?;
+    Ok(Json(CollectionsResponse { total: collections.len(), collections }))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateCollectionRequest {
+    name: String,
+}
+
+/// POST /api/vector-store/collections — create an empty collection.
+#[utoipa::path(
+    post,
+    path = "/vector-store/collections",
+    tag = "collections",
+    request_body = CreateCollectionRequest,
+    responses(
+        (status = 201, body = Collection),
+        (status = 400, description = "Empty or too long name", body = ErrorBody),
+        (status = 409, description = "A collection has that name", body = ErrorBody),
+    )
+)]
+async fn create_collection(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateCollectionRequest>,
+) -> ApiResult<(StatusCode, Json<Collection>)> {
+    let name = req.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_COLLECTION_NAME_CHARS {
+        return Err(ApiError::bad_request(format!(
+            "Collection names are 1-{} characters",
+            MAX_COLLECTION_NAME_CHARS
+        )));
+    }
+    let created = state.db(move |store| store.create_collection(&name)).await?;
+    let collection = created.ok_or_else(|| {
+        ApiError::new(StatusCode::CONFLICT, "collection_exists", "A collection with this name already exists")
+    })?;
+    Ok((StatusCode::CREATED, Json(collection)))
+}
+
+/// DELETE /api/vector-store/collections/:id — delete a collection. Its
+/// documents are kept.
+#[utoipa::path(
+    delete,
+    path = "/vector-store/collections/{id}",
+    tag = "collections",
+    responses(
+        (status = 200, body = Object),
+        (status = 404, description = "Collection not found", body = ErrorBody),
+    )
+)]
+async fn delete_collection(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<serde_json::Value>> {
+    if !state.db(move |store| store.delete_collection(id)).await? {
+        return Err(collection_not_found());
+    }
+    Ok(Json(serde_json::json!({ "success": true, "id": id })))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct CollectionDocumentsResponse {
+    collection: Collection,
+    documents: Vec<Document>,
+    total: i64,
+    page: usize,
+    page_size: usize,
+    total_pages: i64,
+}
+
+/// GET /api/vector-store/collections/:id/documents — documents in a
+/// collection, newest first.
+#[utoipa::path(
+    get,
+    path = "/vector-store/collections/{id}/documents",
+    tag = "collections",
+    params(TopicDocumentsQuery),
+    responses(
+        (status = 200, body = CollectionDocumentsResponse),
+        (status = 404, description = "Collection not found", body = ErrorBody),
+    )
+)]
+async fn get_collection_documents(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(params): Query<TopicDocumentsQuery>,
+) -> ApiResult<Json<CollectionDocumentsResponse>> {
+    let page = params.page.unwrap_or(1).max(1);
+    let page_size = params.page_size.unwrap_or(50).clamp(1, 500);
+    let (collection, (documents, total)) = state
+        .db(move |store| -> ApiResult<_> {
+            let collection = store.get_collection(id)?.ok_or_else(collection_not_found)?;
+            Ok((collection, store.list_documents_in_collection(id, page, page_size, false)?))
+        })
+        .await?;
+    Ok(Json(CollectionDocumentsResponse {
+        collection,
+        documents,
+        total,
+        page,
+        page_size,
+        total_pages: total_pages(total, page_size),
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct CollectionDocumentsRequest {
+    doc_ids: Vec<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CollectionMembershipResponse {
+    /// The collection afterwards.
+    collection: Collection,
+    /// Documents added or removed; ids already in (or not in) the
+    /// collection and unknown documents are not counted.
+    changed: usize,
+}
+
+fn checked_doc_ids(req: &CollectionDocumentsRequest) -> ApiResult<()> {
+    if req.doc_ids.len() > MAX_COLLECTION_BATCH {
+        return Err(ApiError::bad_request(format!(
+            "At most {} documents per request",
+            MAX_COLLECTION_BATCH
+        )));
     }
+    Ok(())
+}
+
+/// POST /api/vector-store/collections/:id/documents — add documents to a
+/// collection, in one transaction.
+#[utoipa::path(
+    post,
+    path = "/vector-store/collections/{id}/documents",
+    tag = "collections",
+    request_body = CollectionDocumentsRequest,
+    responses(
+        (status = 200, body = CollectionMembershipResponse),
+        (status = 400, description = "Too many document ids", body = ErrorBody),
+        (status = 404, description = "Collection not found", body = ErrorBody),
+    )
+)]
+async fn add_collection_documents(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(req): Json<CollectionDocumentsRequest>,
+) -> ApiResult<Json<CollectionMembershipResponse>> {
+    checked_doc_ids(&req)?;
+    let (changed, collection) = state
+        .db(move |store| -> ApiResult<_> {
+            let changed = store.add_to_collection(id, &req.doc_ids)?;
+            Ok((changed, store.get_collection(id)?.ok_or_else(collection_not_found)?))
+        })
+        .await?;
+    Ok(Json(CollectionMembershipResponse { collection, changed }))
+}
+
+/// DELETE /api/vector-store/collections/:id/documents — remove documents
+/// from a collection. The documents themselves are kept.
+#[utoipa::path(
+    delete,
+    path = "/vector-store/collections/{id}/documents",
+    tag = "collections",
+    request_body = CollectionDocumentsRequest,
+    responses(
+        (status = 200, body = CollectionMembershipResponse),
+        (status = 400, description = "Too many document ids", body = ErrorBody),
+        (status = 404, description = "Collection not found", body = ErrorBody),
+    )
+)]
+async fn remove_collection_documents(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(req): Json<CollectionDocumentsRequest>,
+) -> ApiResult<Json<CollectionMembershipResponse>> {
+    checked_doc_ids(&req)?;
+    let (changed, collection) = state
+        .db(move |store| -> ApiResult<_> {
+            let changed = store.remove_from_collection(id, &req.doc_ids)?;
+            Ok((changed, store.get_collection(id)?.ok_or_else(collection_not_found)?))
+        })
+        .await?;
+    Ok(Json(CollectionMembershipResponse { collection, changed }))
 }
 
 // ---------------------------------------------------------------
 // Knowledge Graph (Phase 1 stubs)
 // ---------------------------------------------------------------
 
-async fn get_graph(State(_state): State<Arc<AppState>>) -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "nodes": [],
-        "edges": [],
-        "stats": {
-            "nodeCount": 0,
-            "edgeCount": 0,
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct GraphStats {
+    node_count: usize,
+    edge_count: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+struct GraphResponse {
+    nodes: Vec<serde_json::Value>,
+    edges: Vec<serde_json::Value>,
+    stats: GraphStats,
+}
+
+/// Knowledge graph (not implemented yet: always empty).
+#[utoipa::path(post, path = "/vector-store/graph", tag = "vector-store", responses((status = 200, body = GraphResponse)))]
+async fn get_graph(State(_state): State<Arc<AppState>>) -> Json<GraphResponse> {
+    Json(GraphResponse {
+        nodes: Vec::new(),
+        edges: Vec::new(),
+        stats: GraphStats {
+            node_count: 0,
+            edge_count: 0,
         },
-    }))
+    })
 }
 
+/// Knowledge graph node (not implemented yet: always 404).
+#[utoipa::path(get, path = "/vector-store/graph/node/{node_id}", tag = "vector-store", responses((status = 404, body = ErrorBody)))]
 async fn get_graph_node(
     State(_state): State<Arc<AppState>>,
     Path(_node_id): Path<String>,
-) -> impl IntoResponse {
-    (
-        StatusCode::NOT_FOUND,
-        Json(serde_json::json!({ "error": "Graph not yet implemented" })),
-    )
+) -> ApiError {
+    ApiError::not_found("Graph not yet implemented")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mindsage_core::MindSageConfig;
+    use mindsage_store::SearchSyntax;
+    use tempfile::TempDir;
+
+    use crate::test_support::test_state;
+
+    fn batch(value: serde_json::Value) -> Json<BatchAddRequest> {
+        Json(serde_json::from_value(value).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_atomic_batch_commits_or_rolls_back() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        state
+            .store
+            .add_document("already here", AddDocumentOptions { content_hash: Some("dup".into()), ..Default::default() })
+            .unwrap();
+        let documents = serde_json::json!([
+            {"text": "first new note"},
+            {"text": "already here", "content_hash": "dup"},
+            {"text": "second new note"},
+        ]);
+
+        let (status, Json(body)) = batch_add_documents(
+            State(state.clone()),
+            batch(serde_json::json!({"documents": documents, "atomic": true, "skip_duplicates": false})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let json = serde_json::to_value(&body).unwrap();
+        assert_eq!(json["transaction"]["outcome"], "rolled_back");
+        assert_eq!(json["transaction"]["failedIndex"], 1);
+        assert_eq!(json["added"], 0);
+        assert_eq!(state.store.count_documents().unwrap(), 1);
+
+        let (status, Json(body)) = batch_add_documents(
+            State(state.clone()),
+            batch(serde_json::json!({"documents": documents, "atomic": true})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let json = serde_json::to_value(&body).unwrap();
+        assert_eq!(json["transaction"]["outcome"], "committed");
+        assert_eq!((json["added"].as_u64(), json["duplicates"].as_u64()), (Some(2), Some(1)));
+        assert_eq!(state.store.count_documents().unwrap(), 3);
+        let id = body.results[0].id;
+        assert!(!state.store.get_chunks_for_document(id).unwrap().is_empty());
+    }
+
+    fn add(text: &str, hash: &str, on_duplicate: Option<OnDuplicate>) -> Json<AddDocumentRequest> {
+        Json(AddDocumentRequest {
+            content_hash: Some(hash.to_string()),
+            on_duplicate,
+            ..AddDocumentRequest::new(text)
+        })
+    }
+
+    async fn add_status(state: &Arc<AppState>, request: Json<AddDocumentRequest>) -> (StatusCode, serde_json::Value) {
+        match add_document(State(state.clone()), request).await {
+            Ok(response) => {
+                let response = response.into_response();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice(&body).unwrap())
+            }
+            Err(e) => (e.status, serde_json::Value::Null),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_document_duplicate_policies() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+
+        let (status, first) = add_status(&state, add("a note", "h1", None)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, _) = add_status(&state, add("a note", "h1", Some(OnDuplicate::Error))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _) = add_status(&state, add("a note", "h1", None)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, existing) = add_status(&state, add("a note", "h1", Some(OnDuplicate::ReturnExisting))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(existing["id"], first["id"]);
+        assert_eq!(existing["status"], "exists");
+        assert_eq!(state.store.count_documents().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_document_hashes_since_and_by_hash() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        for (hash, created_at) in [("old", 1_000), ("new", 9_000)] {
+            state
+                .store
+                .add_document(
+                    hash,
+                    AddDocumentOptions { content_hash: Some(hash.into()), created_at: Some(created_at), ..Default::default() },
+                )
+                .unwrap();
+        }
+        let hashes = |since, format| {
+            list_document_hashes(State(state.clone()), Query(DocumentHashesQuery { since, format }))
+        };
+
+        let Json(all) = hashes(None, None).await.unwrap();
+        assert_eq!(all.total, 2);
+        let Json(recent) = hashes(Some(5_000), None).await.unwrap();
+        let recent_hashes = recent.hashes.as_ref().unwrap();
+        assert_eq!(recent_hashes.len(), 1);
+        assert_eq!(recent_hashes[0].content_hash, "new");
+        assert!(recent.may_contain("new") && !recent.may_contain("old"));
+
+        let Json(bloom) = hashes(None, Some(HashFormat::Bloom)).await.unwrap();
+        assert!(bloom.hashes.is_none());
+        assert!(bloom.may_contain("old") && bloom.may_contain("new"));
+
+        let Json(doc) = get_document_by_hash(State(state.clone()), Path("old".to_string())).await.unwrap();
+        assert_eq!(doc.text, "old");
+        let missing = get_document_by_hash(State(state.clone()), Path("nope".to_string())).await.unwrap_err();
+        assert_eq!(missing.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_raw_document_text_download() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let add = |text: &str, metadata: Option<serde_json::Value>| {
+            state.store.add_document(text, AddDocumentOptions { metadata, ..Default::default() }).unwrap()
+        };
+        let from_file = add("Quarterly report text", Some(serde_json::json!({"filename": "q3 report.pdf"})));
+        let from_chat = add("user: hi", Some(serde_json::json!({"source": "chatgpt", "title": "Trip/plans?"})));
+        let bare = add("just text", None);
+
+        let response = get_document_raw(State(state.clone()), Path(from_file)).await.unwrap().into_response();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain; charset=utf-8");
+        let disposition = response.headers()[header::CONTENT_DISPOSITION].to_str().unwrap().to_string();
+        assert!(disposition.contains("filename=\"q3 report.txt\""), "{}", disposition);
+        let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+        assert_eq!(&body[..], b"Quarterly report text");
+
+        let response = get_document_raw(State(state.clone()), Path(from_chat)).await.unwrap().into_response();
+        assert!(response.headers()[header::CONTENT_DISPOSITION].to_str().unwrap().contains("filename=\"Trip_plans_.txt\""));
+        let response = get_document_raw(State(state.clone()), Path(bare)).await.unwrap().into_response();
+        let expected = format!("filename=\"document-{}.txt\"", bare);
+        assert!(response.headers()[header::CONTENT_DISPOSITION].to_str().unwrap().contains(&expected));
+
+        let missing = get_document_raw(State(state.clone()), Path(9999)).await.err().unwrap();
+        assert_eq!(missing.status, StatusCode::NOT_FOUND);
+    }
+
+    fn list_query(page_size: Option<usize>, cursor: Option<String>) -> Query<ListDocumentsQuery> {
+        Query(ListDocumentsQuery {
+            page: None,
+            page_size,
+            ascending: Some(true),
+            cursor,
+            collection_id: None,
+        })
+    }
+
+    async fn ndjson_lines(response: Response) -> Vec<serde_json::Value> {
+        assert_eq!(response.headers()[header::CONTENT_TYPE], ndjson::NDJSON_CONTENT_TYPE);
+        let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+        std::str::from_utf8(&body).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_document_listing_cursor_and_ndjson() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let ids: Vec<i64> = (0..5)
+            .map(|i| {
+                let options = AddDocumentOptions { created_at: Some(1000 + i), ..Default::default() };
+                state.store.add_document(&format!("note {}", i), options).unwrap()
+            })
+            .collect();
+        state.store.add_tag(ids[0], "inbox").unwrap();
+
+        // JSON pages chain through nextCursor
+        let page = |cursor: Option<String>| {
+            let state = state.clone();
+            async move {
+                let response = list_documents(State(state), HeaderMap::new(), list_query(Some(2), cursor)).await.unwrap();
+                let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+                serde_json::from_slice::<DocumentListResponse>(&body).unwrap()
+            }
+        };
+        let first = page(None).await;
+        let second = page(first.next_cursor.clone()).await;
+        let third = page(second.next_cursor.clone()).await;
+        let listed: Vec<i64> = [first, second, third.clone()].iter().flat_map(|p| p.documents.iter().map(|d| d.id)).collect();
+        assert_eq!(listed, ids);
+        assert_eq!(third.next_cursor, None);
+        assert_eq!(third.total, 5);
+
+        let bad = list_documents(State(state.clone()), HeaderMap::new(), list_query(None, Some("x".into()))).await;
+        assert_eq!(bad.err().unwrap().status, StatusCode::BAD_REQUEST);
+
+        // NDJSON streams every document, or page_size of them, after the cursor
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/x-ndjson"));
+        let response = list_documents(State(state.clone()), headers.clone(), list_query(None, None)).await.unwrap();
+        let lines = ndjson_lines(response).await;
+        assert_eq!(lines.iter().map(|d| d["id"].as_i64().unwrap()).collect::<Vec<_>>(), ids);
+        let cursor = format!("{}.{}", 1001, ids[1]);
+        let response = list_documents(State(state.clone()), headers, list_query(Some(2), Some(cursor))).await.unwrap();
+        let lines = ndjson_lines(response).await;
+        assert_eq!(lines.iter().map(|d| d["id"].as_i64().unwrap()).collect::<Vec<_>>(), &ids[2..4]);
+
+        // The export carries each document with its chunks
+        let response = export_ndjson(State(state.clone())).await;
+        assert!(response.headers()[header::CONTENT_DISPOSITION].to_str().unwrap().contains("mindsage-export.ndjson"));
+        let lines = ndjson_lines(response).await;
+        assert_eq!(lines.len(), 5);
+        let exported: ExportedDocument = serde_json::from_value(lines[0].clone()).unwrap();
+        assert_eq!(exported.document.id, ids[0]);
+        assert_eq!(exported.document.text, "note 0");
+        assert_eq!(exported.tags, ["inbox"]);
+        assert!(lines[1].get("tags").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_debug_reports_dimension_mismatches() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let err = state.store.add_chunk_embedding(1, &ndarray::Array1::zeros(768)).unwrap_err();
+        let api_err = ApiError::from(err);
+        assert_eq!(api_err.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(api_err.body()["details"]["actual"], 768);
+
+        let Json(debug) = get_debug(State(state.clone())).await;
+        assert_eq!(debug["store"]["dimension_mismatches"], 1);
+        assert_eq!(debug["embedder"]["batches"], 0);
+        assert_eq!(debug["embedder"]["truncationRate"], 0.0);
+        assert_eq!(debug["models"]["passage"]["modelId"], "noop");
+        assert!(debug["models"]["query"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_date_facets_by_month() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        // 2019-12-31T23:00:00Z twice, 2020-02-10T00:00:00Z
+        for created_at in [1_577_833_200_000i64, 1_577_833_200_000, 1_581_292_800_000] {
+            state
+                .store
+                .add_document("note", AddDocumentOptions { created_at: Some(created_at), ..Default::default() })
+                .unwrap();
+        }
+        let facets = |tz_offset_minutes| {
+            get_date_facets(State(state.clone()), Query(DateFacetsQuery { tz_offset_minutes }))
+        };
+
+        let Json(utc) = facets(None).await.unwrap();
+        assert_eq!(utc.total, 3);
+        let months: Vec<(&str, i64)> = utc.facets.iter().map(|f| (f.month.as_str(), f.count)).collect();
+        assert_eq!(months, vec![("2019-12", 2), ("2020-02", 1)]);
+
+        // In Berlin (UTC+1) New Year's Eve 23:00 UTC is already January
+        let Json(berlin) = facets(Some(60)).await.unwrap();
+        assert_eq!(berlin.facets[0].month, "2020-01");
+        assert_eq!(berlin.tz_offset_minutes, 60);
+
+        let err = facets(Some(15 * 60)).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_enhanced_search_filters_by_heading() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let setup = "Mount the boiler on a solid wall and connect the gas line before filling. ".repeat(25);
+        let repair = "When the pressure drops, bleed the radiators and check the relief valve. ".repeat(25);
+        let text = format!("# Boiler manual\n\n## Setup\n\n{}\n\n## Repair\n\n{}", setup, repair);
+        add_status(&state, Json(AddDocumentRequest::new(text))).await;
+
+        let search = |filter: serde_json::Value| {
+            let mut req = EnhancedSearchRequest::new("boiler radiators valve wall");
+            req.chunk_filter = serde_json::from_value(filter).unwrap();
+            req.max_per_doc = Some(1);
+            run_enhanced_search(&state, req)
+        };
+
+        let Json(response) = search(serde_json::json!({"heading_path": "Repair"})).unwrap();
+        assert_eq!(response.results.len(), 1);
+        let hit = &response.results[0];
+        assert_eq!(hit.breadcrumb.as_deref(), Some("Boiler manual › Repair"));
+        assert!(hit.text.contains("relief valve"));
+
+        let Json(response) = search(serde_json::json!({"heading_path": "Setup"})).unwrap();
+        assert_eq!(response.results[0].breadcrumb.as_deref(), Some("Boiler manual › Setup"));
+        let Json(response) = search(serde_json::json!({"heading_path": "Warranty"})).unwrap();
+        assert!(response.results.is_empty());
+
+        let bad = search(serde_json::json!({"heading_path": ["Setup"]})).unwrap_err();
+        assert_eq!(bad.status, StatusCode::BAD_REQUEST);
+
+        // Without a per-document cap every paragraph under the heading can be
+        // returned; across all levels the section would stand in for them
+        let mut req = EnhancedSearchRequest::new("boiler radiators valve wall");
+        req.chunk_filter = serde_json::from_value(serde_json::json!({"heading_path": "Repair"})).unwrap();
+        req.level = Some(1);
+        let Json(response) = run_enhanced_search(&state, req.clone()).unwrap();
+        assert!(response.results.len() > 1);
+        assert!(response.results.iter().all(|r| r.breadcrumb.as_deref() == Some("Boiler manual › Repair")));
+        req.mmr_lambda = Some(1.5);
+        assert_eq!(run_enhanced_search(&state, req).unwrap_err().status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_enhanced_search_merges_overlapping_hits() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let text = "Ferries stopped for a week when the harbour froze. Ferries resumed once the harbour ice broke up.";
+        let doc_id = state.store.add_document(text, Default::default()).unwrap();
+        // A chunk over the whole text and, from an older chunker, one over its first sentence
+        let first = text.find(" Ferries").unwrap();
+        let whole = state
+            .store
+            .add_chunk(doc_id, text, 0, 1, None, Some(0), Some(text.len() as i32), None, None, None)
+            .unwrap();
+        let sentence = state
+            .store
+            .add_chunk(doc_id, &text[..first], 1, 1, None, Some(0), Some(first as i32), None, None, None)
+            .unwrap();
+
+        let mut req = EnhancedSearchRequest::new("ferries harbour");
+        req.mmr_lambda = Some(1.0);
+        let Json(response) = run_enhanced_search(&state, req).unwrap();
+        assert_eq!(response.results.len(), 1);
+        assert_eq!(response.results[0].chunk_id, whole);
+        assert_eq!(response.results[0].merged_from, [sentence]);
+    }
+
+    #[tokio::test]
+    async fn test_search_covers_every_chunk_level_by_default() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        // A document from an older import path: one section, no paragraphs
+        let old = state.store.add_document("Lighthouse keeper's log", Default::default()).unwrap();
+        state
+            .store
+            .add_chunk(old, "The lighthouse keeper's log for the winter storms", 0, 0, None, None, None, None, None, None)
+            .unwrap();
+        // A section with its own paragraph, both matching
+        let text = format!("# Storms\n\n{}", "Winter storms flooded the lighthouse stairs. ".repeat(30));
+        add_status(&state, Json(AddDocumentRequest::new(text))).await;
+
+        let search = |level: Option<i32>| {
+            let mut req = SearchRequest::new("lighthouse storms");
+            req.level = level;
+            req.mmr_lambda = Some(1.0);
+            run_search(&state, req).unwrap().0
+        };
+
+        let response = search(None);
+        assert!(response.results.iter().any(|r| r.doc_id == old));
+        // Never a section next to one of its own paragraphs
+        let ids: std::collections::HashSet<i64> = response.results.iter().map(|r| r.chunk_id).collect();
+        for result in &response.results {
+            let chunk = state.store.get_chunk(result.chunk_id).unwrap().unwrap();
+            assert!(chunk.parent_chunk_id.map_or(true, |p| !ids.contains(&p)));
+        }
+
+        let response = search(Some(1));
+        assert!(response.results.iter().all(|r| r.doc_id != old));
+        assert!(!response.results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_collection_scopes_search_and_listing() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let mut ids = Vec::new();
+        for text in ["Sourdough starter feeding schedule", "Sourdough crumb and oven spring", "Sourdough pricing at the market"] {
+            let (_, body) = add_status(&state, Json(AddDocumentRequest::new(text))).await;
+            ids.push(body["id"].as_i64().unwrap());
+        }
+
+        let create = |name: &str| {
+            let state = state.clone();
+            let req = CreateCollectionRequest { name: name.to_string() };
+            async move { create_collection(State(state), Json(req)).await }
+        };
+        let (status, Json(baking)) = create("  Baking ").await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(baking.name, "Baking");
+        assert_eq!(create("Baking").await.err().unwrap().status, StatusCode::CONFLICT);
+        assert_eq!(create(" ").await.err().unwrap().status, StatusCode::BAD_REQUEST);
+
+        let req = CollectionDocumentsRequest { doc_ids: vec![ids[0], ids[1], 9999] };
+        let Json(added) = add_collection_documents(State(state.clone()), Path(baking.id), Json(req)).await.unwrap();
+        assert_eq!((added.changed, added.collection.document_count), (2, 2));
+
+        let search = |collection_id: Option<i64>| {
+            let mut req = SearchRequest::new("sourdough");
+            req.collection_id = collection_id;
+            run_search(&state, req)
+        };
+        let mut hits: Vec<i64> = search(Some(baking.id)).unwrap().0.results.iter().map(|r| r.doc_id).collect();
+        hits.sort();
+        assert_eq!(hits, [ids[0], ids[1]]);
+        assert_eq!(search(None).unwrap().0.results.len(), 3);
+        assert_eq!(search(Some(9999)).err().unwrap().status, StatusCode::NOT_FOUND);
+
+        let mut query = list_query(None, None);
+        query.collection_id = Some(baking.id);
+        let response = list_documents(State(state.clone()), HeaderMap::new(), query).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), 1 << 20).await.unwrap();
+        let listed = serde_json::from_slice::<DocumentListResponse>(&body).unwrap();
+        assert_eq!(listed.documents.iter().map(|d| d.id).collect::<Vec<_>>(), [ids[0], ids[1]]);
+        assert_eq!(listed.total, 2);
+
+        let req = CollectionDocumentsRequest { doc_ids: vec![ids[0]] };
+        let Json(removed) = remove_collection_documents(State(state.clone()), Path(baking.id), Json(req)).await.unwrap();
+        assert_eq!(removed.collection.document_count, 1);
+
+        // Deleting the collection keeps its documents
+        let Json(deleted) = delete_collection(State(state.clone()), Path(baking.id)).await.unwrap();
+        assert_eq!(deleted["success"], true);
+        assert_eq!(state.store.count_documents().unwrap(), 3);
+        let missing = get_collection_documents(State(state.clone()), Path(baking.id), Query(TopicDocumentsQuery { page: None, page_size: None }));
+        assert_eq!(missing.await.err().unwrap().status, StatusCode::NOT_FOUND);
+        let Json(remaining) = list_collections(State(state.clone())).await.unwrap();
+        assert_eq!(remaining.total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_search_query_syntax() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        for (text, source, filename, lang) in [
+            ("Budget review for the spring offsite", "notes", "offsite-plan.md", "en"),
+            ("Budget review draft for the offsite", "notes", "offsite-draft.md", "en"),
+            ("Revisão do orçamento budget do offsite", "notes", "offsite-pt.md", "pt"),
+            ("Budget review article about offsites", "browser", "article.html", "en"),
+        ] {
+            let mut req = AddDocumentRequest::new(text);
+            req.metadata = Some(serde_json::json!({"source": source, "filename": filename, "lang": lang}));
+            add_status(&state, Json(req)).await;
+        }
+        let search = |query: &str, syntax: Option<SearchSyntax>| {
+            let mut req = SearchRequest::new(query);
+            req.syntax = syntax;
+            run_search(&state, req).unwrap().0
+        };
+
+        let response = search("source:notes lang:EN filename:offsite budget -draft", Some(SearchSyntax::Query));
+        let texts: Vec<&str> = response.results.iter().map(|r| r.text.as_str()).collect();
+        assert_eq!(texts, ["Budget review for the spring offsite"]);
+        assert_eq!(response.query, "source:notes lang:EN filename:offsite budget -draft");
+        assert!(response.diagnostics.is_none());
+
+        // Created today, so a past `before:` scope excludes everything
+        assert!(search("before:2020 budget", Some(SearchSyntax::Query)).results.is_empty());
+        assert_eq!(search("after:2020 budget", Some(SearchSyntax::Query)).results.len(), 4);
+
+        // The default reads the same text as plain words
+        assert_eq!(search("source:notes budget -draft", None).results.len(), 4);
+
+        // Syntax errors search the whole string as text, with a warning
+        let response = search("budget after:someday", Some(SearchSyntax::Query));
+        assert_eq!(response.results.len(), 4);
+        let warnings = response.diagnostics.unwrap().warnings;
+        assert!(warnings[0].contains("after:someday"), "{:?}", warnings);
+    }
+
+    #[tokio::test]
+    async fn test_tag_routes_and_tag_scoped_search() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let mut ids = Vec::new();
+        for text in ["Boiler service invoice", "Boiler manual", "Garden invoice"] {
+            let (_, body) = add_status(&state, Json(AddDocumentRequest::new(text))).await;
+            ids.push(body["id"].as_i64().unwrap());
+        }
+        let tag = |id: i64, tag: &str| add_document_tag(State(state.clone()), Path((id, tag.to_string())));
+        let Json(tagged) = tag(ids[0], " Receipts ").await.unwrap();
+        assert_eq!((tagged.tag.as_str(), tagged.changed), ("receipts", true));
+        assert!(!tag(ids[0], "receipts").await.unwrap().changed);
+        assert!(tag(ids[2], "receipts").await.unwrap().changed);
+        assert!(tag(ids[1], "manuals").await.unwrap().changed);
+        assert_eq!(tag(ids[0], "two words").await.unwrap_err().status, StatusCode::BAD_REQUEST);
+        assert_eq!(tag(999, "receipts").await.unwrap_err().status, StatusCode::NOT_FOUND);
+
+        let Json(tags) = get_tags(State(state.clone())).await.unwrap();
+        let counts: Vec<(&str, i64)> = tags.tags.iter().map(|t| (t.tag.as_str(), t.count)).collect();
+        assert_eq!(counts, [("receipts", 2), ("manuals", 1)]);
+
+        let search = |query: &str| {
+            let mut req = SearchRequest::new(query);
+            req.syntax = Some(SearchSyntax::Query);
+            let response = run_search(&state, req).unwrap().0;
+            response.results.into_iter().map(|r| r.text).collect::<Vec<_>>()
+        };
+        assert_eq!(search("tag:receipts boiler"), ["Boiler service invoice"]);
+        assert_eq!(search("tag:RECEIPTS tag:manuals boiler").len(), 2);
+        assert!(search("tag:manuals invoice").is_empty());
+
+        let untag = remove_document_tag(State(state.clone()), Path((ids[0], "receipts".to_string())));
+        let Json(untagged) = untag.await.unwrap();
+        assert!(untagged.changed && untagged.tags.is_empty());
+        assert!(search("tag:receipts boiler").is_empty());
+        let query = Query(TopicDocumentsQuery { page: None, page_size: None });
+        let Json(listed) = get_documents_by_tag(State(state.clone()), Path("receipts".to_string()), query).await.unwrap();
+        assert_eq!(listed.documents.iter().map(|d| d.id).collect::<Vec<_>>(), [ids[2]]);
+    }
+
+    #[tokio::test]
+    async fn test_hostile_queries_never_fail_the_search_routes() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        add_status(&state, Json(AddDocumentRequest::new("Rust text is focused on memory safety"))).await;
+        let corpus = [
+            "\"\" OR text: NEAR",
+            "text: NEAR(rust safety, 2)",
+            "{text enriched_text}:rust*",
+            "rust AND OR NOT (",
+            "\u{201c}memory safety\u{201d} -\u{00ab}draft\u{00bb}",
+            "\u{201c}unclosed",
+            "\"unclosed",
+            "rust\u{0}safety\u{7}",
+            "^* - + : \\ ' ` ;",
+            "source: tag: after:",
+            "-\"\" -",
+            "\u{202e}\u{200b}\u{feff}",
+            &"safety ".repeat(500),
+            "",
+        ];
+        for query in corpus {
+            for syntax in [None, Some(SearchSyntax::Query)] {
+                let mut req = SearchRequest::new(query);
+                req.syntax = syntax;
+                if let Err(e) = run_search(&state, req) {
+                    assert!(e.status.is_client_error(), "{:?}: {:?}", query, e.status);
+                }
+            }
+            if let Err(e) = run_enhanced_search(&state, EnhancedSearchRequest::new(query)) {
+                assert!(e.status.is_client_error(), "{:?}: {:?}", query, e.status);
+            }
+            let topic = SearchWithTopicRequest { query: query.to_string(), topic: "rust".to_string(), top_k: 5 };
+            assert!(run_search_with_topic(&state, topic).is_ok(), "{:?}", query);
+            let prefix = Query(SuggestQuery { q: query.to_string(), limit: None });
+            assert!(suggest(State(state.clone()), prefix).await.is_ok(), "{:?}", query);
+        }
+
+        let mut req = SearchRequest::new("\u{201c}memory safety\u{201d} text:");
+        req.syntax = Some(SearchSyntax::Query);
+        assert_eq!(run_search(&state, req).unwrap().0.results.len(), 1);
+    }
+
+    #[test]
+    fn test_transcript_hits_carry_time_range() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let path = dir.path().join("standup.vtt");
+        std::fs::write(
+            &path,
+            "WEBVTT\n\n00:00:05.000 --> 00:00:09.000\n<v Ana>Release notes are drafted.\n\n\
+             00:03:10.000 --> 00:03:15.500\n<v Ben>The invoice backlog needs triage.\n",
+        )
+        .unwrap();
+        mindsage_ingest::Ingester::new(&state.store).ingest_file(&path).unwrap().unwrap();
+
+        let Json(response) = run_search(&state, SearchRequest::new("invoice backlog")).unwrap();
+        let hit = &response.results[0];
+        assert_eq!(hit.text, "Ben: The invoice backlog needs triage.");
+        assert_eq!(hit.time_range, Some(TimeRange { start_ms: 190_000, end_ms: 195_500 }));
+        assert_eq!(hit.metadata.as_ref().unwrap()["speaker"], "Ben");
+    }
+
+    #[tokio::test]
+    async fn test_search_settings_are_validated_and_saved() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        assert_eq!(get_search_settings(State(state.clone())).await.0, SearchPostProcessing::default());
+
+        let bad = SearchPostProcessing {
+            max_chunks_per_doc: Some(0),
+            ..Default::default()
+        };
+        let err = put_search_settings(State(state.clone()), Json(bad)).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        let settings = SearchPostProcessing {
+            entity_boost: 0.3,
+            min_term_length: 4,
+            max_chunks_per_doc: None,
+            mmr_lambda: 1.0,
+        };
+        let Json(saved) = put_search_settings(State(state.clone()), Json(settings)).await.unwrap();
+        assert_eq!(saved, settings);
+        assert_eq!(state.store.search_post_processing(), settings);
+        // Requests still override the MMR settings
+        let overridden = checked_post_processing(&state, Some(0.5), Some(2)).unwrap();
+        assert_eq!((overridden.mmr_lambda, overridden.max_chunks_per_doc), (0.5, Some(2)));
+        assert_eq!(overridden.entity_boost, 0.3);
+
+        let reloaded = MindSageConfig::from_env(dir.path()).unwrap();
+        assert_eq!(reloaded.search_post_processing, settings);
+    }
 }
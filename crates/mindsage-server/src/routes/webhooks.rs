@@ -0,0 +1,199 @@
+//! Webhook configuration routes — list and replace endpoints, send a test
+//! event (see `webhooks.rs`).
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use mindsage_core::events::EventCategory;
+use mindsage_core::{Event, Secret, WebhookEndpoint};
+use serde::Deserialize;
+use utoipa::OpenApi;
+
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/config/webhooks", get(get_webhooks).put(put_webhooks))
+        .route("/config/webhooks/{id}/test", post(test_webhook))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_webhooks,
+    put_webhooks,
+    test_webhook,
+))]
+pub struct WebhooksApi;
+
+/// Endpoint as returned to clients: the secret is never sent back.
+fn endpoint_view(endpoint: &WebhookEndpoint) -> serde_json::Value {
+    serde_json::json!({
+        "id": endpoint.id,
+        "url": endpoint.url,
+        "events": endpoint.events,
+        "enabled": endpoint.enabled,
+        "hasSecret": endpoint.secret.is_some(),
+    })
+}
+
+fn webhooks_response(state: &AppState) -> Json<serde_json::Value> {
+    let endpoints: Vec<serde_json::Value> = state.webhooks.list().iter().map(endpoint_view).collect();
+    Json(serde_json::json!({ "webhooks": endpoints }))
+}
+
+/// GET /api/config/webhooks — configured endpoints.
+#[utoipa::path(get, path = "/config/webhooks", tag = "webhooks", responses((status = 200, body = Object)))]
+async fn get_webhooks(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    webhooks_response(&state)
+}
+
+#[derive(Deserialize)]
+struct EndpointUpdate {
+    #[serde(default)]
+    id: Option<String>,
+    url: String,
+    /// Omitted keeps the endpoint's current secret; empty removes it.
+    #[serde(default)]
+    secret: Option<Secret>,
+    #[serde(default)]
+    events: Vec<EventCategory>,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct WebhooksUpdate {
+    webhooks: Vec<EndpointUpdate>,
+}
+
+/// PUT /api/config/webhooks — replace all endpoints. Endpoints without an
+/// id get a new one.
+#[utoipa::path(
+    put,
+    path = "/config/webhooks",
+    tag = "webhooks",
+    request_body = Object,
+    responses((status = 200, body = Object))
+)]
+async fn put_webhooks(
+    State(state): State<Arc<AppState>>,
+    Json(update): Json<WebhooksUpdate>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let mut endpoints = Vec::with_capacity(update.webhooks.len());
+    for item in update.webhooks {
+        let url = item.url.trim();
+        match reqwest::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+            _ => return Err(ApiError::bad_request(format!("Invalid webhook URL: {}", url))),
+        }
+        let id = item
+            .id
+            .filter(|id| !id.trim().is_empty())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        if endpoints.iter().any(|e: &WebhookEndpoint| e.id == id) {
+            return Err(ApiError::bad_request(format!("Duplicate webhook id: {}", id)));
+        }
+        let secret = match item.secret {
+            Some(secret) if secret.expose().is_empty() => None,
+            Some(secret) => Some(secret),
+            None => state.webhooks.get(&id).and_then(|e| e.secret),
+        };
+        endpoints.push(WebhookEndpoint {
+            id,
+            url: url.to_string(),
+            secret,
+            events: item.events,
+            enabled: item.enabled,
+        });
+    }
+    state
+        .webhooks
+        .replace(endpoints)
+        .map_err(|e| ApiError::internal(format!("Failed to save webhooks: {}", e)))?;
+    Ok(webhooks_response(&state))
+}
+
+/// POST /api/config/webhooks/{id}/test — send a sample `indexing.job` event
+/// once and report the result.
+#[utoipa::path(post, path = "/config/webhooks/{id}/test", tag = "webhooks", responses((status = 200, body = Object)))]
+async fn test_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let endpoint = state
+        .webhooks
+        .get(&id)
+        .ok_or_else(|| ApiError::not_found(format!("Webhook '{}' not found", id)))?;
+    let sample = Event::IndexingJob {
+        job_id: "webhook-test".to_string(),
+        filename: "webhook-test.txt".to_string(),
+        status: "completed".to_string(),
+        document_id: None,
+        error: None,
+    };
+    let delivery = state.webhooks.send(&endpoint, &sample).await;
+    Ok(Json(serde_json::json!({
+        "success": delivery.delivered,
+        "delivery": delivery,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::test_support::test_state;
+
+    fn update(value: serde_json::Value) -> Json<WebhooksUpdate> {
+        Json(serde_json::from_value(value).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_put_keeps_secrets_and_persists() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+
+        let Json(body) = put_webhooks(
+            State(state.clone()),
+            update(serde_json::json!({"webhooks": [
+                {"id": "ha", "url": "http://192.168.1.5:8123/hook", "secret": "key-1", "events": ["indexing", "connector"]}
+            ]})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body["webhooks"][0]["hasSecret"], true);
+        assert!(body["webhooks"][0].get("secret").is_none());
+
+        // Omitting the secret keeps it; an id-less endpoint gets one
+        let Json(body) = put_webhooks(
+            State(state.clone()),
+            update(serde_json::json!({"webhooks": [
+                {"id": "ha", "url": "http://192.168.1.5:8123/hook", "enabled": false},
+                {"url": "https://example.org/notify"}
+            ]})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body["webhooks"].as_array().unwrap().len(), 2);
+        let saved = mindsage_core::load_webhooks(&state.config.data_paths.webhooks_file);
+        assert_eq!(saved[0].secret.as_ref().map(|s| s.expose()), Some("key-1"));
+        assert!(!saved[0].enabled && saved[0].events.is_empty());
+        assert!(!saved[1].id.is_empty());
+
+        let bad = put_webhooks(State(state.clone()), update(serde_json::json!({"webhooks": [{"url": "ftp://x"}]})))
+            .await
+            .unwrap_err();
+        assert_eq!(bad.status, axum::http::StatusCode::BAD_REQUEST);
+        let missing = test_webhook(State(state.clone()), Path("nope".to_string())).await.unwrap_err();
+        assert_eq!(missing.status, axum::http::StatusCode::NOT_FOUND);
+    }
+}
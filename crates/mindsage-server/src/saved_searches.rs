@@ -0,0 +1,190 @@
+//! Saved-search checks — re-run every saved search after a document is
+//! indexed and notify subscribers about new matches.
+
+use std::collections::HashMap;
+
+use mindsage_core::{Event, Result};
+use mindsage_store::{SavedSearch, SearchHit};
+use tracing::{error, info};
+
+use crate::state::AppState;
+
+/// Run all saved searches, record their hits, and publish a
+/// `search.match` event for each search with new matches.
+pub fn check_saved_searches(state: &AppState) {
+    let searches = match state.store.list_saved_searches() {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to list saved searches: {}", e);
+            return;
+        }
+    };
+
+    for search in searches {
+        let hits = match run_saved_search(state, &search) {
+            Ok(h) => h,
+            Err(e) => {
+                error!("Saved search {} failed: {}", search.id, e);
+                continue;
+            }
+        };
+
+        match state.store.record_saved_search_hits(search.id, &hits) {
+            Ok(new_ids) if !new_ids.is_empty() => {
+                info!(
+                    "Saved search '{}' has {} new match(es)",
+                    search.name,
+                    new_ids.len()
+                );
+                state.events.publish(Event::SavedSearchMatch {
+                    search_id: search.id,
+                    name: search.name.clone(),
+                    new_matches: new_ids.len(),
+                    chunk_ids: new_ids,
+                });
+            }
+            Ok(_) => {}
+            Err(e) => error!("Failed to record saved search {} hits: {}", search.id, e),
+        }
+    }
+}
+
+/// Execute a saved search: hybrid when an embedder is available, else BM25,
+/// then apply the metadata filters and keep the top-k.
+fn run_saved_search(state: &AppState, search: &SavedSearch) -> Result<Vec<SearchHit>> {
+    let fetch_k = search.top_k * 4;
+    let hits = match state
+        .embedder
+        .is_available()
+        .then(|| state.embedder.embed_query(&search.query))
+        .flatten()
+    {
+        Some(emb_result) => state
+            .store
+            .hybrid_search(&search.query, &emb_result.embedding, Some(1), fetch_k, fetch_k, 60)
+            .or_else(|_| state.store.bm25_search(&search.query, Some(1), fetch_k))?,
+        None => state.store.bm25_search(&search.query, Some(1), fetch_k)?,
+    };
+
+    let filters = match search.filters.as_ref().and_then(|f| f.as_object()) {
+        Some(f) if !f.is_empty() => f,
+        _ => return Ok(hits.into_iter().take(search.top_k).collect()),
+    };
+
+    // Filters match against document metadata; cache lookups per document.
+    let mut doc_matches: HashMap<i64, bool> = HashMap::new();
+    let mut kept = Vec::with_capacity(search.top_k);
+    for hit in hits {
+        let matches = match doc_matches.get(&hit.doc_id) {
+            Some(m) => *m,
+            None => {
+                let metadata = state
+                    .store
+                    .get_document(hit.doc_id)?
+                    .and_then(|d| d.metadata)
+                    .unwrap_or(serde_json::Value::Null);
+                let m = filters.iter().all(|(key, value)| metadata.get(key) == Some(value));
+                doc_matches.insert(hit.doc_id, m);
+                m
+            }
+        };
+        if matches {
+            kept.push(hit);
+            if kept.len() >= search.top_k {
+                break;
+            }
+        }
+    }
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mindsage_ingest::Ingester;
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    fn ingest(state: &AppState, dir: &TempDir, name: &str, text: &str) -> i64 {
+        let path = dir.path().join(name);
+        std::fs::write(&path, text).unwrap();
+        Ingester::new(&state.store).ingest_file(&path).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_ingest_after_saving_emits_match_event() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+        ingest(&state, &dir, "groceries.md", "Buy milk, eggs and bread on the way home.");
+
+        let saved = state
+            .store
+            .create_saved_search("phoenix", "project phoenix", None, 10)
+            .unwrap();
+        let mut rx = state.events.subscribe();
+
+        // Nothing matches yet
+        check_saved_searches(&state);
+        assert!(rx.try_recv().is_err());
+
+        let doc_id = ingest(
+            &state,
+            &dir,
+            "phoenix.md",
+            "Project Phoenix kickoff: the phoenix migration starts next sprint.",
+        );
+        check_saved_searches(&state);
+
+        let frame: serde_json::Value = serde_json::from_str(&rx.try_recv().unwrap().to_frame()).unwrap();
+        assert_eq!(frame["type"], "search.match");
+        assert_eq!(frame["searchId"], saved.id);
+        assert_eq!(frame["newMatches"], 1);
+
+        let matches = state.store.get_saved_search_matches(saved.id, true).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].doc_id, doc_id);
+
+        // Re-checking reports nothing new
+        check_saved_searches(&state);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_filters_restrict_to_document_metadata() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+        let saved = state
+            .store
+            .create_saved_search(
+                "invoices",
+                "invoice 2024",
+                Some(&serde_json::json!({"source": "gmail"})),
+                10,
+            )
+            .unwrap();
+
+        for source in ["gmail", "drive"] {
+            let doc = state
+                .store
+                .add_document(
+                    "Invoice 2024 for hosting",
+                    mindsage_store::AddDocumentOptions {
+                        metadata: Some(serde_json::json!({"source": source})),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            state
+                .store
+                .add_chunk(doc, "Invoice 2024 for hosting", 0, 1, None, None, None, None, None, None)
+                .unwrap();
+        }
+
+        let hits = run_saved_search(&state, &saved).unwrap();
+        assert_eq!(hits.len(), 1);
+        let doc = state.store.get_document(hits[0].doc_id).unwrap().unwrap();
+        assert_eq!(doc.metadata.unwrap()["source"], "gmail");
+    }
+}
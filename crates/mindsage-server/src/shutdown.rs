@@ -0,0 +1,172 @@
+//! Graceful shutdown.
+//!
+//! On SIGTERM or SIGINT the server stops accepting connections, the
+//! indexing worker finishes the file it is working on and stops, and the
+//! jobs still queued are saved to `data/indexing-queue.json` so the next
+//! start picks them up. Browser and connector state is flushed and the
+//! SQLite write-ahead log is checkpointed. Whatever is still running when
+//! `MINDSAGE_SHUTDOWN_TIMEOUT` runs out is abandoned; a job cut off that way
+//! is saved with the queue and indexed again on the next start.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::indexing;
+use crate::profiles::Profiles;
+use crate::state::AppState;
+
+/// Process-wide shutdown flag that tasks can poll or wait on.
+pub struct Shutdown {
+    tx: watch::Sender<bool>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self {
+            tx: watch::channel(false).0,
+        }
+    }
+}
+
+impl Shutdown {
+    /// Ask every task to stop. Idempotent.
+    pub fn trigger(&self) {
+        self.tx.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    /// Resolve once shutdown has been triggered.
+    pub async fn wait(&self) {
+        let mut rx = self.tx.subscribe();
+        let _ = rx.wait_for(|triggered| *triggered).await;
+    }
+}
+
+/// Trigger shutdown when the process receives SIGINT (Ctrl-C) or SIGTERM.
+pub fn listen_for_signals(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let ctrl_c = async {
+            let _ = tokio::signal::ctrl_c().await;
+        };
+        #[cfg(unix)]
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut signal) => {
+                    signal.recv().await;
+                }
+                Err(e) => {
+                    warn!("Cannot listen for SIGTERM: {}", e);
+                    std::future::pending::<()>().await;
+                }
+            }
+        };
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = ctrl_c => info!("Received SIGINT, shutting down"),
+            _ = terminate => info!("Received SIGTERM, shutting down"),
+        }
+        state.shutdown.trigger();
+    });
+}
+
+/// Stop every open profile, wait for the HTTP server and the indexing
+/// workers to finish, then save each profile's queue, flush its state and
+/// checkpoint its database. Call after shutdown has been triggered; returns
+/// within the configured timeout plus the time the final flush takes.
+pub async fn drain(profiles: &Profiles, server: JoinHandle<std::io::Result<()>>) {
+    let timeout = Duration::from_secs(profiles.default_state().config.shutdown_timeout_secs);
+    let deadline = Instant::now() + timeout;
+    profiles.trigger_shutdown();
+
+    let (server, workers) = tokio::join!(
+        tokio::time::timeout_at(deadline, server),
+        tokio::time::timeout_at(deadline, futures::future::join_all(profiles.take_workers()))
+    );
+    match server {
+        Ok(Ok(Err(e))) => warn!("HTTP server stopped with an error: {}", e),
+        Err(_) => warn!("Connections still open after {}s; closing them", timeout.as_secs()),
+        _ => {}
+    }
+    if workers.is_err() {
+        warn!("Indexing still running after {}s; unfinished jobs will be re-queued on the next start", timeout.as_secs());
+    }
+
+    for state in profiles.loaded_states() {
+        finish(&state);
+    }
+    info!("Shutdown complete");
+}
+
+/// Persist everything of one profile that must survive the restart. Also
+/// used on its own when the server fails. Nothing is written in read-only mode.
+pub fn finish(state: &AppState) {
+    if state.config.read_only {
+        return;
+    }
+    match indexing::save_pending_queue(state) {
+        Ok(0) => {}
+        Ok(saved) => info!("Saved {} queued indexing jobs of profile '{}' for the next start", saved, state.profile),
+        Err(e) => warn!("Failed to save the indexing queue of profile '{}': {}", state.profile, e),
+    }
+    state.browser_manager.flush();
+    state.connector_manager.flush();
+    if let Err(e) = state.store.checkpoint() {
+        warn!("WAL checkpoint failed for profile '{}': {}", state.profile, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    #[tokio::test]
+    async fn test_shutdown_flag_wakes_waiters() {
+        let shutdown = Arc::new(Shutdown::default());
+        assert!(!shutdown.is_triggered());
+        let waiter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.wait().await }
+        });
+        shutdown.trigger();
+        shutdown.trigger();
+        tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        assert!(shutdown.is_triggered());
+        // Waiting after the trigger returns immediately
+        tokio::time::timeout(Duration::from_secs(1), shutdown.wait()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_drain_stops_worker_and_saves_unstarted_jobs() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).config(|c| c.shutdown_timeout_secs = 2).build());
+        let file = state.config.data_paths.imports.join("notes.txt");
+        std::fs::write(&file, "Queued before shutdown").unwrap();
+
+        // Shut down before the worker takes the job
+        state.shutdown.trigger();
+        let profiles = Profiles::start(state.clone());
+        state.queue_indexing(file.to_string_lossy().to_string(), "notes.txt".to_string());
+        let server = tokio::spawn(async { Ok(()) });
+        drain(&profiles, server).await;
+
+        let saved: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&state.config.data_paths.indexing_queue_file).unwrap())
+                .unwrap();
+        assert_eq!(saved[0]["filename"], "notes.txt");
+        assert_eq!(state.store.count_documents().unwrap(), 0);
+    }
+}
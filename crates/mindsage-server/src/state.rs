@@ -5,97 +5,183 @@ use std::sync::Arc;
 use mindsage_browser::BrowserManager;
 use mindsage_chat::LLMConfig;
 use mindsage_connectors::ConnectorManager;
-use mindsage_core::MindSageConfig;
+use mindsage_core::{DeviceCapabilities, Event, EventBus, MindSageConfig};
 use mindsage_infer::EmbedderBackend;
-use mindsage_localsend::LocalSendServer;
+use mindsage_localsend::{DeviceRegistry, LocalSendServer, TrustLevel};
+use mindsage_protocol::anonymization::AnonymizationSessions;
 use mindsage_protocol::consent::ConsentManager;
 use mindsage_protocol::pii::PiiDetector;
 use mindsage_runtime::Orchestrator;
-use mindsage_store::SqliteStore;
+use mindsage_store::embedding::QuantScheme;
+use mindsage_store::{FtsWeights, IndexedFile, SqliteStore};
 use parking_lot::RwLock;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use tokio::sync::mpsc;
+use tracing::{error, info, warn};
 
-/// Indexing job status.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct IndexingJob {
-    pub id: String,
-    pub filename: String,
-    pub file_path: String,
-    pub status: IndexingStatus,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub document_id: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
-    pub queued_at: i64,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub started_at: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub completed_at: Option<i64>,
-}
+use crate::audit::AuditLog;
+use crate::backfill_offsets::BackfillProgress;
+use crate::normalize_metadata::NormalizeProgress;
+use crate::bulk::BulkOps;
+use crate::catchup::CatchupTracker;
+use crate::health::BackgroundHealth;
+use crate::jobs::JobTracker;
+use crate::quota::DiskUsage;
+use crate::rate_limit::RateLimiter;
+use crate::reembed::ReembedProgress;
+use crate::reextract::ReextractProgress;
+use crate::shutdown::Shutdown;
+use crate::topic_generation::LlmTopicBudget;
+use crate::uploads::UploadSessions;
+use crate::watcher::ImportWatcher;
+use crate::webhooks::Webhooks;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-pub enum IndexingStatus {
-    Queued,
-    Processing,
-    Completed,
-    Failed,
-}
+pub use mindsage_api_types::{IndexingJob, IndexingStatus};
 
-/// Indexed file tracking record.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Record in the legacy `.indexed-files.json`, as written by the Python
+/// backend (camelCase) or by earlier versions of this server (snake_case).
+/// Imported into the store's `indexed_files` table on startup.
+#[derive(Debug, Clone, Deserialize)]
 pub struct IndexedFileRecord {
-    pub filename: String,
+    #[serde(default, alias = "filePath")]
     pub file_path: String,
+    #[serde(default, alias = "indexedAt")]
     pub indexed_at: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, alias = "documentId")]
     pub document_id: Option<i64>,
+    #[serde(default)]
     pub size: u64,
+    #[serde(default)]
     pub modified: String,
 }
 
 /// Shared application state accessible from all route handlers.
 pub struct AppState {
+    /// Profile this state serves (`default` for the data directory itself).
+    pub profile: String,
     pub config: MindSageConfig,
     pub store: SqliteStore,
     pub embedder: Arc<dyn EmbedderBackend>,
-    pub llm_config: RwLock<LLMConfig>,
+    /// Shared by all profiles.
+    pub llm_config: Arc<RwLock<LLMConfig>>,
     pub browser_manager: BrowserManager,
     pub localsend_server: LocalSendServer,
     pub connector_manager: ConnectorManager,
     pub pii_detector: PiiDetector,
+    /// PII token maps of anonymized chat sessions, by `sessionId`.
+    pub anonymization: AnonymizationSessions,
     pub consent_manager: ConsentManager,
+    pub audit: AuditLog,
     pub orchestrator: Orchestrator,
+    pub events: EventBus,
+    pub rate_limiter: RateLimiter,
+    pub bulk: BulkOps,
+    pub reembed: JobTracker<ReembedProgress>,
+    /// Re-extraction of chunks enriched by an older extractor version.
+    pub reextract: JobTracker<ReextractProgress>,
+    /// Backfill of char offsets for chunks stored without them.
+    pub offset_backfill: JobTracker<BackfillProgress>,
+    /// Normalization of metadata written before the current rules.
+    pub metadata_normalization: JobTracker<NormalizeProgress>,
+    pub topic_llm_budget: LlmTopicBudget,
+    /// Cached sizes of the data areas, for their disk quotas.
+    pub disk: DiskUsage,
+    /// Resumable uploads in `data/upload-sessions/`.
+    pub uploads: UploadSessions,
+    /// Watcher on `data/imports/`; idle unless `watch_imports` is set.
+    pub import_watcher: ImportWatcher,
+    /// Outbound webhook endpoints and delivery.
+    pub webhooks: Webhooks,
+    /// Set on SIGTERM/SIGINT; background tasks stop when it fires.
+    pub shutdown: Shutdown,
+    /// Last runs and errors of the background tasks.
+    pub health: BackgroundHealth,
+    /// Progress of the passes over the ingest journal's leftover steps.
+    pub catchup: CatchupTracker,
     pub indexing_jobs: RwLock<HashMap<String, IndexingJob>>,
     pub indexing_tx: mpsc::UnboundedSender<IndexingRequest>,
     indexing_rx: parking_lot::Mutex<Option<mpsc::UnboundedReceiver<IndexingRequest>>>,
-    pub indexed_files: RwLock<HashMap<String, IndexedFileRecord>>,
 }
 
 /// A request to index a file.
+#[derive(Debug, Clone)]
 pub struct IndexingRequest {
     pub job_id: String,
     pub file_path: String,
     pub filename: String,
+    /// Automatic retries after transient failures so far.
+    pub retries: u32,
 }
 
 impl AppState {
     pub fn new(config: MindSageConfig, store: SqliteStore, embedder: Arc<dyn EmbedderBackend>) -> Self {
         let (tx, rx) = mpsc::unbounded_channel();
+        let events = EventBus::default();
+        let rate_limiter = RateLimiter::new(config.rate_limit.clone());
 
-        // Load indexed files from disk
-        let indexed_files = Self::load_indexed_files(&config.data_paths.indexed_files);
+        // New embeddings are tagged with, and search is limited to, this model
+        store.set_embedding_model(embedder.model_id());
+        // Embeddings from before models were tracked are this model's
+        if embedder.is_available() {
+            match store.adopt_unknown_embeddings(embedder.model_id()) {
+                Ok(adopted) if adopted > 0 => info!("Tagged {} untracked embeddings as {}", adopted, embedder.model_id()),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to tag untracked embeddings: {}", e),
+            }
+        }
+        store.set_quant_scheme(if config.block_quantization {
+            QuantScheme::BlockInt8
+        } else {
+            QuantScheme::Int8
+        });
+        store.apply_tier(DeviceCapabilities::discover().tier);
+        let (text, enriched) = config.fts_weights;
+        store.set_fts_weights(FtsWeights { text, enriched });
+        store.set_text_stale_weight(config.text_stale_weight);
+        store.set_search_post_processing(config.search_post_processing);
+        store.set_query_stats_config(config.query_stats);
+
+        // Move indexed-file state from the legacy JSON file into the store
+        if !config.read_only {
+            import_legacy_indexed_files(&store, &config.data_paths.indexed_files);
+            match store.reconcile_indexed_files() {
+                Ok(cleared) if cleared > 0 => info!("Cleared {} indexed files whose document was deleted", cleared),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to reconcile indexed files: {}", e),
+            }
+        }
 
         // Load LLM config
         let llm_config_path = config.data_paths.llm_config_file.clone();
         let llm_config = LLMConfig::load(&llm_config_path);
 
         // Initialize browser manager
-        let browser_manager = BrowserManager::new(&config.data_paths.browser_connector);
+        let browser_manager = if config.read_only {
+            BrowserManager::open_read_only(&config.data_paths.browser_connector)
+        } else {
+            BrowserManager::new(&config.data_paths.browser_connector)
+        }
+        .with_events(events.clone());
 
         // Initialize LocalSend server
-        let localsend_server = LocalSendServer::new(&config.data_paths.uploads, "MindSage");
+        let mut localsend_server = LocalSendServer::new(&config.data_paths.uploads, "MindSage")
+            .with_text_notes(config.localsend_text_notes)
+            .with_port(config.localsend_port)
+            .with_device_registry(DeviceRegistry::open(
+                &config.data_paths.localsend_devices_file,
+                TrustLevel::parse(&config.localsend_default_trust).unwrap_or_else(|| {
+                    warn!(
+                        "Unknown MINDSAGE_LOCALSEND_DEFAULT_TRUST '{}', using 'ask'",
+                        config.localsend_default_trust
+                    );
+                    TrustLevel::Ask
+                }),
+            ));
+        if config.localsend_https && !config.read_only {
+            if let Err(e) = localsend_server.enable_tls(&config.data_paths.localsend) {
+                warn!("LocalSend HTTPS unavailable, serving plain HTTP: {}", e);
+            }
+        }
 
         // Initialize connector manager
         let connector_manager = ConnectorManager::new(
@@ -106,23 +192,103 @@ impl AppState {
         // Initialize privacy and runtime
         let pii_detector = PiiDetector::new();
         let consent_manager = ConsentManager::new();
-        let orchestrator = Orchestrator::new();
+        let mut audit = AuditLog::new(&config.data_paths.audit_log, config.audit_prompts);
+        if config.read_only {
+            audit = audit.unsaved();
+        }
+        let orchestrator = Orchestrator::new().with_events(events.clone());
+        let webhooks = Webhooks::new(
+            config.webhooks.clone(),
+            config.data_paths.webhooks_file.clone(),
+            config.data_paths.webhooks_dead_letter.clone(),
+        );
+
+        let uploads = UploadSessions::new(config.data_paths.upload_sessions.clone());
 
         Self {
+            profile: mindsage_core::DEFAULT_PROFILE.to_string(),
             config,
             store,
             embedder,
-            llm_config: RwLock::new(llm_config),
+            llm_config: Arc::new(RwLock::new(llm_config)),
             browser_manager,
             localsend_server,
             connector_manager,
             pii_detector,
+            anonymization: AnonymizationSessions::new(),
             consent_manager,
+            audit,
             orchestrator,
+            events,
+            rate_limiter,
+            bulk: BulkOps::default(),
+            uploads,
+            reembed: JobTracker::default(),
+            reextract: JobTracker::default(),
+            offset_backfill: JobTracker::default(),
+            metadata_normalization: JobTracker::default(),
+            topic_llm_budget: LlmTopicBudget::default(),
+            disk: DiskUsage::default(),
+            import_watcher: ImportWatcher::default(),
+            webhooks,
+            shutdown: Shutdown::default(),
+            health: BackgroundHealth::default(),
+            catchup: CatchupTracker::default(),
             indexing_jobs: RwLock::new(HashMap::new()),
             indexing_tx: tx,
             indexing_rx: parking_lot::Mutex::new(Some(rx)),
-            indexed_files: RwLock::new(indexed_files),
+        }
+    }
+
+    /// State for a non-default profile, sharing the default profile's
+    /// embedder and LLM configuration.
+    pub fn for_profile(name: &str, config: MindSageConfig, store: SqliteStore, default: &AppState) -> Self {
+        let mut state = Self::new(config, store, default.embedder.clone());
+        state.profile = name.to_string();
+        state.llm_config = default.llm_config.clone();
+        state
+    }
+
+    /// Open the store of `config`'s data directory; read-only in read-only mode.
+    pub fn open_store(config: &MindSageConfig) -> mindsage_core::Result<SqliteStore> {
+        let encryption = mindsage_store::crypto::EncryptionConfig::from_env()?;
+        if config.read_only {
+            SqliteStore::open_read_only(&config.data_paths.vectordb, config.embedding_dim, encryption)
+        } else {
+            SqliteStore::open_with_encryption(&config.data_paths.vectordb, config.embedding_dim, encryption)
+        }
+    }
+
+    /// Run `f` against the store on tokio's blocking pool. Store methods
+    /// take the connection mutex and run SQL synchronously; called inline
+    /// from a handler, a slow query would hold a runtime worker and stall
+    /// every request scheduled on it. The call runs to completion even if
+    /// the awaiting request is dropped, so a write is never abandoned
+    /// halfway. A panic in `f` is resumed in the caller.
+    pub async fn db<T, F>(self: &Arc<Self>, f: F) -> T
+    where
+        F: FnOnce(&SqliteStore) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.blocking(move |state| f(&state.store)).await
+    }
+
+    /// Like `db`, for work that needs more of the state than the store
+    /// (ingesting, embedding, building RAG context).
+    pub async fn blocking<T, F>(self: &Arc<Self>, f: F) -> T
+    where
+        F: FnOnce(&AppState) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let state = self.clone();
+        // Keep the request's span, so store logs carry its request id
+        let span = tracing::Span::current();
+        match tokio::task::spawn_blocking(move || span.in_scope(|| f(&state))).await {
+            Ok(value) => value,
+            Err(e) => match e.try_into_panic() {
+                Ok(panic) => std::panic::resume_unwind(panic),
+                Err(e) => panic!("store task did not complete: {}", e),
+            },
         }
     }
 
@@ -131,61 +297,298 @@ impl AppState {
         self.indexing_rx.lock().take()
     }
 
-    fn load_indexed_files(
-        path: &std::path::Path,
-    ) -> HashMap<String, IndexedFileRecord> {
-        match std::fs::read_to_string(path) {
-            Ok(data) => serde_json::from_str(&data).unwrap_or_default(),
-            Err(_) => HashMap::new(),
+    /// Queue `file_path` for the indexing worker. Returns the job id.
+    pub fn queue_indexing(&self, file_path: String, filename: String) -> String {
+        let job_id = uuid::Uuid::new_v4().to_string();
+        let job = IndexingJob {
+            id: job_id.clone(),
+            filename: filename.clone(),
+            file_path: file_path.clone(),
+            status: IndexingStatus::Queued,
+            document_id: None,
+            error: None,
+            queued_at: chrono::Utc::now().timestamp_millis(),
+            started_at: None,
+            completed_at: None,
+            attempts: 0,
+            next_attempt_at: None,
+        };
+        self.indexing_jobs.write().insert(job_id.clone(), job);
+        self.publish_job(&job_id);
+
+        let _ = self.indexing_tx.send(IndexingRequest {
+            job_id: job_id.clone(),
+            file_path,
+            filename,
+            retries: 0,
+        });
+        job_id
+    }
+
+    /// Queue a failed job again with its original file, keeping its id and
+    /// attempt count. Returns the queued job, or `None` when the job is
+    /// unknown or has not failed.
+    pub fn requeue_indexing(&self, job_id: &str) -> Option<IndexingJob> {
+        let job = {
+            let mut jobs = self.indexing_jobs.write();
+            let job = jobs.get_mut(job_id).filter(|j| j.status == IndexingStatus::Failed)?;
+            job.status = IndexingStatus::Queued;
+            job.error = None;
+            job.document_id = None;
+            job.started_at = None;
+            job.completed_at = None;
+            job.next_attempt_at = None;
+            job.clone()
+        };
+        self.publish_job(job_id);
+
+        let _ = self.indexing_tx.send(IndexingRequest {
+            job_id: job.id.clone(),
+            file_path: job.file_path.clone(),
+            filename: job.filename.clone(),
+            retries: 0,
+        });
+        Some(job)
+    }
+
+    /// Whether a job for `file_path` is queued or being processed.
+    pub fn has_pending_job(&self, file_path: &str) -> bool {
+        self.indexing_jobs.read().values().any(|j| {
+            j.file_path == file_path && matches!(j.status, IndexingStatus::Queued | IndexingStatus::Processing)
+        })
+    }
+
+    /// Publish the current state of an indexing job to event subscribers.
+    pub fn publish_job(&self, job_id: &str) {
+        let event = match self.indexing_jobs.read().get(job_id) {
+            Some(job) => Event::IndexingJob {
+                job_id: job.id.clone(),
+                filename: job.filename.clone(),
+                status: job.status.as_str().to_string(),
+                document_id: job.document_id,
+                error: job.error.clone(),
+            },
+            None => return,
+        };
+        self.events.publish(event);
+    }
+
+    /// Whether `file_path` was indexed and has not changed (same mtime and
+    /// size) since.
+    pub fn is_file_indexed(&self, file_path: &str) -> bool {
+        let Ok(Some(record)) = self.store.get_indexed_file(file_path) else {
+            return false;
+        };
+        match std::fs::metadata(file_path) {
+            Ok(meta) => record.mtime == file_mtime_millis(&meta) && record.size == meta.len() as i64,
+            Err(_) => false,
         }
     }
 
-    pub fn save_indexed_files(&self) {
-        let indexed = self.indexed_files.read();
-        if let Ok(data) = serde_json::to_string_pretty(&*indexed) {
-            let _ = std::fs::write(&self.config.data_paths.indexed_files, data);
+    pub fn mark_file_indexed(&self, file_path: &str, document_id: Option<i64>) {
+        let Ok(meta) = std::fs::metadata(file_path) else {
+            return;
+        };
+        let content_hash = document_id
+            .and_then(|id| self.store.get_document(id).ok().flatten())
+            .and_then(|d| d.content_hash);
+        let record = IndexedFile {
+            path: file_path.to_string(),
+            mtime: file_mtime_millis(&meta),
+            size: meta.len() as i64,
+            content_hash,
+            doc_id: document_id,
+            indexed_at: chrono::Utc::now().timestamp_millis(),
+        };
+        if let Err(e) = self.store.upsert_indexed_file(&record) {
+            error!("Failed to record indexed file: {}", e);
         }
     }
+}
 
-    pub fn is_file_indexed(&self, file_path: &str) -> bool {
-        let indexed = self.indexed_files.read();
-        if let Some(record) = indexed.get(file_path) {
-            if let Ok(meta) = std::fs::metadata(file_path) {
-                if let Ok(modified) = meta.modified() {
-                    let modified_str = chrono::DateTime::<chrono::Utc>::from(modified)
-                        .to_rfc3339();
-                    return record.modified == modified_str;
-                }
+fn file_mtime_millis(meta: &std::fs::Metadata) -> Option<i64> {
+    meta.modified()
+        .ok()
+        .map(|m| chrono::DateTime::<chrono::Utc>::from(m).timestamp_millis())
+}
+
+/// Import a legacy `.indexed-files.json` into the store in one transaction,
+/// then rename it to `.indexed-files.json.imported`. A file that cannot be
+/// parsed (e.g. truncated by a crash) is left in place and nothing is imported.
+fn import_legacy_indexed_files(store: &SqliteStore, path: &std::path::Path) {
+    let Ok(data) = std::fs::read_to_string(path) else {
+        return;
+    };
+    let records: HashMap<String, IndexedFileRecord> = match serde_json::from_str(&data) {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("Cannot import {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let parse_millis = |s: &str| {
+        chrono::DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|t| t.timestamp_millis())
+    };
+    let files: Vec<IndexedFile> = records
+        .into_iter()
+        .map(|(key, record)| IndexedFile {
+            path: if record.file_path.is_empty() { key } else { record.file_path },
+            mtime: parse_millis(&record.modified),
+            size: record.size as i64,
+            content_hash: None,
+            doc_id: record.document_id,
+            indexed_at: parse_millis(&record.indexed_at).unwrap_or(0),
+        })
+        .collect();
+    match store.import_indexed_files(&files) {
+        Ok(imported) => {
+            info!("Imported {} indexed file records from {}", imported, path.display());
+            let mut done = path.as_os_str().to_owned();
+            done.push(".imported");
+            if let Err(e) = std::fs::rename(path, &done) {
+                warn!("Failed to rename {}: {}", path.display(), e);
             }
         }
-        false
+        Err(e) => warn!("Failed to import {}: {}", path.display(), e),
     }
+}
 
-    pub fn mark_file_indexed(&self, file_path: &str, document_id: Option<i64>) {
-        if let Ok(meta) = std::fs::metadata(file_path) {
-            let modified_str = meta
-                .modified()
-                .ok()
-                .map(|m| chrono::DateTime::<chrono::Utc>::from(m).to_rfc3339())
-                .unwrap_or_default();
-
-            let record = IndexedFileRecord {
-                filename: std::path::Path::new(file_path)
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("")
-                    .to_string(),
-                file_path: file_path.to_string(),
-                indexed_at: chrono::Utc::now().to_rfc3339(),
-                document_id,
-                size: meta.len(),
-                modified: modified_str,
-            };
-
-            self.indexed_files
-                .write()
-                .insert(file_path.to_string(), record);
-            self.save_indexed_files();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::test_support::TestState;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_store_load_leaves_other_routes_responsive() {
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use mindsage_store::NewDocument;
+        use std::time::{Duration, Instant};
+        use tower::ServiceExt;
+
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).config(|c| c.rate_limit.enabled = false).build());
+        state.store.add_document("Notes on the sourdough starter", Default::default()).unwrap();
+        let app = crate::routes::build_app(Arc::new(crate::profiles::Profiles::start(state.clone())));
+
+        // One slow write holds the store for its whole transaction...
+        let docs: Vec<NewDocument> = (0..20_000)
+            .map(|i| NewDocument {
+                text: format!("Imported entry number {} about bread", i),
+                options: Default::default(),
+                chunks: Vec::new(),
+            })
+            .collect();
+        let write_started = Instant::now();
+        let writer = {
+            let state = state.clone();
+            tokio::spawn(async move {
+                state.db(move |store| store.add_documents_transactional(&docs, false)).await.unwrap();
+                write_started.elapsed()
+            })
+        };
+        // The test's thread is not a runtime worker, and a blocked runtime
+        // would not fire a tokio timer
+        std::thread::sleep(Duration::from_millis(20));
+
+        // ...while more searches than runtime workers wait behind it
+        let searches: Vec<_> = (0..32)
+            .map(|_| {
+                let request = Request::post("/api/vector-store/search")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"query": "sourdough"}"#))
+                    .unwrap();
+                tokio::spawn(app.clone().oneshot(request))
+            })
+            .collect();
+        std::thread::sleep(Duration::from_millis(20));
+
+        let started = Instant::now();
+        let probe = tokio::spawn(async move {
+            let request = Request::get("/api/health/ready").body(Body::empty()).unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            (response.status(), started.elapsed())
+        });
+        let (status, answered_in) = probe.await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+
+        let write_took = writer.await.unwrap();
+        for search in searches {
+            assert_eq!(search.await.unwrap().unwrap().status(), StatusCode::OK);
+        }
+        // Only meaningful when the write outlasted the probe by a margin
+        if write_took > Duration::from_millis(200) {
+            assert!(answered_in < write_took / 2, "{:?} behind a {:?} write", answered_in, write_took);
+        }
+    }
+
+    #[test]
+    fn test_imports_legacy_indexed_files_json() {
+        let dir = TempDir::new().unwrap();
+        let file_path = dir.path().join("notes.txt");
+        std::fs::write(&file_path, "hello").unwrap();
+        let file_path = file_path.to_string_lossy().to_string();
+        let modified = chrono::DateTime::<chrono::Utc>::from(std::fs::metadata(&file_path).unwrap().modified().unwrap());
+
+        // Python backend format
+        let legacy = serde_json::json!({
+            &file_path: {
+                "filename": "notes.txt",
+                "filePath": &file_path,
+                "indexedAt": "2026-01-01T00:00:00Z",
+                "size": 5,
+                "modified": modified.to_rfc3339(),
+            },
+            "/app/data/imports/gone.txt": {
+                "filePath": "/app/data/imports/gone.txt",
+                "indexedAt": "2026-01-01T00:00:00Z",
+                "size": 1,
+                "modified": "2026-01-01T00:00:00Z",
+            }
+        });
+        std::fs::write(dir.path().join(".indexed-files.json"), legacy.to_string()).unwrap();
+
+        let state = TestState::new(&dir).build();
+        assert_eq!(state.store.count_indexed_files().unwrap(), 2);
+        assert!(state.is_file_indexed(&file_path));
+        assert!(!state.is_file_indexed("/app/data/imports/gone.txt"));
+        assert!(!dir.path().join(".indexed-files.json").exists());
+        assert!(dir.path().join(".indexed-files.json.imported").exists());
+
+        // Changing the file clears its indexed state
+        std::fs::write(&file_path, "hello, world").unwrap();
+        assert!(!state.is_file_indexed(&file_path));
+    }
+
+    #[test]
+    fn test_truncated_legacy_file_leaves_store_intact() {
+        let dir = TempDir::new().unwrap();
+        let file_path = dir.path().join("report.md");
+        std::fs::write(&file_path, "# report").unwrap();
+        let file_path = file_path.to_string_lossy().to_string();
+        {
+            let state = TestState::new(&dir).build();
+            let doc_id = state.store.add_document("# report", Default::default()).unwrap();
+            state.mark_file_indexed(&file_path, Some(doc_id));
         }
+
+        // A JSON file cut off mid-write is ignored, not half-imported
+        std::fs::write(dir.path().join(".indexed-files.json"), r#"{"/x/a.txt": {"filePath": "/x/a.txt", "si"#).unwrap();
+        let state = TestState::new(&dir).build();
+        assert_eq!(state.store.count_indexed_files().unwrap(), 1);
+        assert!(state.is_file_indexed(&file_path));
+        assert!(dir.path().join(".indexed-files.json").exists());
+
+        // Deleting the document clears the indexed flag on the next reconcile
+        let doc_id = state.store.get_indexed_file(&file_path).unwrap().unwrap().doc_id.unwrap();
+        state.store.delete_document(doc_id).unwrap();
+        drop(state);
+        let state = TestState::new(&dir).build();
+        assert!(!state.is_file_indexed(&file_path));
     }
 }
@@ -0,0 +1,82 @@
+//! Shared fixtures for the server's tests and the scenario suite: an
+//! `AppState` over a temporary data directory, with the variants tests
+//! need set on a builder.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use mindsage_core::{DiskQuotas, MindSageConfig};
+use mindsage_infer::{EmbedderBackend, NoopEmbedder};
+
+use crate::state::AppState;
+
+pub use mindsage_infer::test_support::WordEmbedder;
+
+/// Builder of an `AppState` over a data directory: the config read from
+/// the environment, a fresh store and a `NoopEmbedder` unless set.
+pub struct TestState {
+    config: MindSageConfig,
+    embedder: Option<Arc<dyn EmbedderBackend>>,
+}
+
+impl TestState {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            config: MindSageConfig::from_env(dir.as_ref()).unwrap(),
+            embedder: None,
+        }
+    }
+
+    /// Serve the LocalSend protocol port over TLS.
+    pub fn https(mut self, https: bool) -> Self {
+        self.config.localsend_https = https;
+        self
+    }
+
+    pub fn quotas(mut self, quotas: DiskQuotas) -> Self {
+        self.config.disk_quotas = quotas;
+        self
+    }
+
+    /// Save text shared over LocalSend as notes rather than files.
+    pub fn text_notes(mut self, text_notes: bool) -> Self {
+        self.config.localsend_text_notes = text_notes;
+        self
+    }
+
+    /// Trust level of LocalSend senders seen for the first time.
+    pub fn default_trust(mut self, trust: &str) -> Self {
+        self.config.localsend_default_trust = trust.to_string();
+        self
+    }
+
+    pub fn embedder(mut self, embedder: impl EmbedderBackend + 'static) -> Self {
+        self.embedder = Some(Arc::new(embedder));
+        self
+    }
+
+    /// Embed with `WordEmbedder`, so vector search finds texts sharing words.
+    pub fn word_embedder(self) -> Self {
+        let dim = self.config.embedding_dim;
+        self.embedder(WordEmbedder::new(dim))
+    }
+
+    /// Any other change to the config.
+    pub fn config(mut self, f: impl FnOnce(&mut MindSageConfig)) -> Self {
+        f(&mut self.config);
+        self
+    }
+
+    pub fn build(self) -> AppState {
+        let store = AppState::open_store(&self.config).unwrap();
+        let embedder = self
+            .embedder
+            .unwrap_or_else(|| Arc::new(NoopEmbedder::new(self.config.embedding_dim)));
+        AppState::new(self.config, store, embedder)
+    }
+}
+
+/// The default state over `dir`.
+pub fn test_state(dir: impl AsRef<Path>) -> Arc<AppState> {
+    Arc::new(TestState::new(dir).build())
+}
@@ -0,0 +1,370 @@
+//! Document topic generation — heuristic extraction, or an LLM pass that
+//! falls back to heuristics when it cannot be used.
+//!
+//! LLM calls are limited to `LLMConfig::topic_llm_daily_cap` documents per
+//! UTC day. The counter lives in memory and restarts with the server.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use mindsage_chat::providers;
+use mindsage_chat::topics::{self, LLM_TOPIC_MAX_TOKENS, LLM_TOPIC_TIMEOUT};
+use mindsage_chat::ChatMessage;
+use mindsage_core::Result;
+use mindsage_protocol::anonymization::SharedSession;
+use mindsage_store::{Document, SqliteStore};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::audit::{AuditPurpose, AuditRequest};
+use crate::routes::chat::anonymize_messages;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TopicMode {
+    #[default]
+    Heuristic,
+    Llm,
+}
+
+/// Documents sent to the LLM for topics on the current UTC day.
+#[derive(Default)]
+pub struct LlmTopicBudget {
+    /// (day number, documents used)
+    usage: Mutex<(i64, u32)>,
+}
+
+impl LlmTopicBudget {
+    /// Count one document against `cap` for `day`; false once the cap is hit.
+    pub fn try_take(&self, day: i64, cap: u32) -> bool {
+        let mut usage = self.usage.lock();
+        if usage.0 != day {
+            *usage = (day, 0);
+        }
+        if usage.1 >= cap {
+            return false;
+        }
+        usage.1 += 1;
+        true
+    }
+}
+
+/// Outcome of generating topics for one document.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TopicGeneration {
+    pub doc_id: i64,
+    pub topics: Vec<String>,
+    pub primary_topic: String,
+    /// "llm" or "heuristic" — the method that produced `topics`.
+    pub method: &'static str,
+    /// True when LLM mode was requested but heuristics were used.
+    pub fallback: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_reason: Option<String>,
+}
+
+/// Generate topics for a document and merge them into its metadata. With
+/// `anonymize`, PII in the document is replaced with tokens before it is
+/// sent to the LLM. Returns `None` when the document does not exist.
+pub async fn generate_document_topics(
+    state: &Arc<AppState>,
+    doc_id: i64,
+    mode: TopicMode,
+    anonymize: bool,
+) -> Result<Option<TopicGeneration>> {
+    let resolved = match mode {
+        TopicMode::Llm => state.llm_config.read().resolve_provider(),
+        TopicMode::Heuristic => None,
+    };
+    let openai_base_url = state.llm_config.read().openai_base_url.clone();
+    let complete = resolved.map(|(provider, model, api_key)| {
+        move |messages: Vec<ChatMessage>, anonymized: bool| async move {
+            state
+                .audit
+                .record(AuditRequest {
+                    purpose: AuditPurpose::Topics,
+                    provider,
+                    model: &model,
+                    messages: &messages,
+                    chunk_ids: Vec::new(),
+                    max_tokens: LLM_TOPIC_MAX_TOKENS,
+                    anonymized,
+                })
+                .map_err(|e| format!("Failed to write audit entry: {}", e))?;
+            let client = reqwest::Client::new();
+            let stream = providers::stream_llm(
+                &client, provider, messages,
+                &model, &api_key,
+                0.0, LLM_TOPIC_MAX_TOKENS,
+                openai_base_url.as_deref(), None,
+            );
+            topics::collect_completion(stream).await
+        }
+    });
+    generate_with(state, doc_id, mode, anonymize, complete).await
+}
+
+/// Topic generation with the provider call supplied by the caller;
+/// `complete` is `None` when no provider is configured. It is given the
+/// messages to send and whether they were anonymized.
+async fn generate_with<F, Fut>(
+    state: &Arc<AppState>,
+    doc_id: i64,
+    mode: TopicMode,
+    anonymize: bool,
+    complete: Option<F>,
+) -> Result<Option<TopicGeneration>>
+where
+    F: FnOnce(Vec<ChatMessage>, bool) -> Fut,
+    Fut: Future<Output = std::result::Result<String, String>>,
+{
+    // Tokens in the reply are restored before the topics are parsed
+    let session = anonymize.then(SharedSession::default);
+    let complete = complete.map(|complete| {
+        let session = session.clone();
+        move |mut messages: Vec<ChatMessage>| {
+            let anonymized = anonymize_messages(&state.pii_detector, session.as_ref(), &mut messages);
+            async move {
+                let reply = complete(messages, anonymized).await?;
+                Ok(match &session {
+                    Some(session) => session.lock().deanonymize(&reply),
+                    None => reply,
+                })
+            }
+        }
+    });
+
+    let Some(doc) = state.db(move |store| store.get_document(doc_id)).await? else {
+        return Ok(None);
+    };
+
+    let mut llm_result = None;
+    let mut fallback_reason = None;
+    if mode == TopicMode::Llm {
+        match complete {
+            None => fallback_reason = Some("No LLM provider configured".to_string()),
+            Some(complete) => {
+                let cap = state.llm_config.read().topic_llm_daily_cap;
+                let day = chrono::Utc::now().timestamp().div_euclid(86_400);
+                if !state.topic_llm_budget.try_take(day, cap) {
+                    fallback_reason =
+                        Some(format!("Daily LLM topic cap of {} documents reached", cap));
+                } else {
+                    match topics::generate_llm_topics(&doc.text, complete, LLM_TOPIC_TIMEOUT).await {
+                        Ok(t) => llm_result = Some(t),
+                        Err(e) => {
+                            warn!("LLM topic generation for document {} failed: {}", doc_id, e);
+                            fallback_reason = Some(e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let (topics, primary_topic, method) = state
+        .db(move |store| store_topics(store, &doc, llm_result))
+        .await?;
+
+    Ok(Some(TopicGeneration {
+        doc_id,
+        topics,
+        primary_topic,
+        method,
+        fallback: fallback_reason.is_some(),
+        fallback_reason,
+    }))
+}
+
+/// Merge the LLM's topics, or heuristic ones without them, into the
+/// document's metadata and enrich its chunks. Returns the topics, primary
+/// topic and method.
+fn store_topics(
+    store: &SqliteStore,
+    doc: &Document,
+    llm_result: Option<topics::LlmTopics>,
+) -> Result<(Vec<String>, String, &'static str)> {
+    let doc_id = doc.id;
+    let source = doc
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get("source"))
+        .and_then(|s| s.as_str());
+    let filename = doc
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get("filename"))
+        .and_then(|s| s.as_str());
+
+    let (topics, primary_topic, method) = match llm_result {
+        Some(t) => (t.topics, t.primary_topic, "llm"),
+        None => {
+            let result = mindsage_ingest::extract_all(&doc.text, source, filename);
+            (result.topics, result.primary_topic, "heuristic")
+        }
+    };
+
+    // Update document metadata with topics
+    let updates = serde_json::json!({
+        "topics": topics,
+        "primary_topic": primary_topic,
+        "extraction_method": method,
+    });
+    store.update_document_metadata(doc_id, &updates)?;
+
+    // Also enrich chunks
+    if let Ok(chunks) = store.get_chunks_for_document(doc_id) {
+        for chunk in &chunks {
+            if chunk.enriched_text.is_some() {
+                continue;
+            }
+            let chunk_result = mindsage_ingest::extract_all(&chunk.text, source, filename);
+            let enriched = mindsage_ingest::build_enriched_text(&chunk_result);
+            if !enriched.is_empty() {
+                let _ = store.update_chunk_enriched_text(
+                    chunk.id,
+                    &enriched,
+                    mindsage_ingest::CURRENT_EXTRACTION_VERSION,
+                );
+            }
+        }
+    }
+    Ok((topics, primary_topic, method))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use mindsage_store::AddDocumentOptions;
+    use tempfile::TempDir;
+
+    use crate::test_support::test_state;
+
+    fn add_doc(state: &AppState) -> i64 {
+        state
+            .store
+            .add_document(
+                "We migrated the billing service from Python to Rust and cut p99 latency in half.",
+                AddDocumentOptions::default(),
+            )
+            .unwrap()
+    }
+
+    type Reply = std::future::Ready<std::result::Result<String, String>>;
+
+    /// A mocked provider that answers with `text`.
+    fn reply(text: &'static str) -> Option<impl FnOnce(Vec<ChatMessage>, bool) -> Reply> {
+        Some(move |_, _| std::future::ready(Ok(text.to_string())))
+    }
+
+    #[tokio::test]
+    async fn test_llm_topics_merged_into_metadata() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let doc_id = add_doc(&state);
+
+        let generated = generate_with(
+            &state,
+            doc_id,
+            TopicMode::Llm,
+            false,
+            reply(r#"{"topics": ["Rust", "Billing", "Performance"], "primary_topic": "rust"}"#),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(generated.method, "llm");
+        assert!(!generated.fallback);
+        assert_eq!(generated.topics, vec!["rust", "billing", "performance"]);
+
+        let metadata = state.store.get_document(doc_id).unwrap().unwrap().metadata.unwrap();
+        assert_eq!(metadata["extraction_method"], "llm");
+        assert_eq!(metadata["primary_topic"], "rust");
+        let topics: Vec<String> = state.store.list_topics().unwrap().into_iter().map(|(t, _)| t).collect();
+        assert!(topics.contains(&"billing".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_llm_reply_falls_back_to_heuristics() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let doc_id = add_doc(&state);
+
+        let generated = generate_with(&state, doc_id, TopicMode::Llm, false, reply("topics: rust, billing"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(generated.method, "heuristic");
+        assert!(generated.fallback);
+        assert!(generated.fallback_reason.is_some());
+
+        let metadata = state.store.get_document(doc_id).unwrap().unwrap().metadata.unwrap();
+        assert_eq!(metadata["extraction_method"], "heuristic");
+    }
+
+    #[tokio::test]
+    async fn test_daily_cap_and_missing_provider_fall_back() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let doc_id = add_doc(&state);
+        state.llm_config.write().topic_llm_daily_cap = 1;
+
+        let valid = r#"{"topics": ["rust", "billing", "latency"], "primary_topic": "rust"}"#;
+        let first = generate_with(&state, doc_id, TopicMode::Llm, false, reply(valid)).await.unwrap().unwrap();
+        assert_eq!(first.method, "llm");
+        let second = generate_with(&state, doc_id, TopicMode::Llm, false, reply(valid)).await.unwrap().unwrap();
+        assert_eq!(second.method, "heuristic");
+        assert!(second.fallback_reason.unwrap().contains("cap"));
+
+        let none = generate_with(&state, doc_id, TopicMode::Llm, false, None::<fn(Vec<ChatMessage>, bool) -> Reply>)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(none.fallback_reason.as_deref(), Some("No LLM provider configured"));
+        assert!(generate_with(&state, 9999, TopicMode::Heuristic, false, reply(valid)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_anonymized_topics_send_tokens_and_restore_them() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir);
+        let doc_id = state
+            .store
+            .add_document("Ask alice@example.com about the billing migration.", AddDocumentOptions::default())
+            .unwrap();
+
+        let sent = Arc::new(parking_lot::Mutex::new(None));
+        let complete = {
+            let sent = sent.clone();
+            move |messages: Vec<ChatMessage>, anonymized: bool| {
+                let token = messages[1].content.split_whitespace().find(|w| w.starts_with("<PII:")).map(str::to_string);
+                *sent.lock() = Some((messages, anonymized));
+                let topic = token.unwrap_or_default();
+                let reply = format!(r#"{{"topics": ["billing", "migration", "{}"], "primary_topic": "billing"}}"#, topic);
+                std::future::ready(Ok(reply))
+            }
+        };
+        let generated = generate_with(&state, doc_id, TopicMode::Llm, true, Some(complete)).await.unwrap().unwrap();
+
+        let (messages, anonymized) = sent.lock().take().unwrap();
+        assert!(anonymized);
+        assert!(messages.iter().all(|m| !m.content.contains("alice@example.com")));
+        // The token the model echoed is restored before normalization
+        assert_eq!(generated.topics, vec!["billing", "migration", "alice example.com"]);
+    }
+
+    #[test]
+    fn test_budget_resets_each_day() {
+        let budget = LlmTopicBudget::default();
+        assert!(budget.try_take(10, 2));
+        assert!(budget.try_take(10, 2));
+        assert!(!budget.try_take(10, 2));
+        assert!(budget.try_take(11, 2));
+        assert!(!budget.try_take(11, 0));
+    }
+}
@@ -0,0 +1,468 @@
+//! Resumable uploads — a file sent as numbered chunks over as many requests
+//! as it takes, each checked against its SHA-256, so a dropped connection
+//! resumes from the chunks already received instead of from zero.
+//!
+//! A session lives in `data/upload-sessions/<id>/`: `session.json` records
+//! the chunks received and `data.part` holds them at their offsets, so
+//! chunks may arrive in any order, more than once, and across restarts.
+//! Sessions without a new chunk for `SESSION_TTL` are removed.
+
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::error::ApiError;
+use crate::state::AppState;
+use mindsage_api_types::{ByteRange, UploadSession};
+
+/// Chunk size when the client does not ask for one.
+pub const DEFAULT_CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+/// Smallest and largest chunk size a client may ask for.
+pub const MIN_CHUNK_SIZE: u64 = 64 * 1024;
+pub const MAX_CHUNK_SIZE: u64 = 32 * 1024 * 1024;
+/// Idle time after which an incomplete upload is removed.
+pub const SESSION_TTL: Duration = Duration::from_secs(24 * 3600);
+/// How often expired sessions are looked for.
+const GC_INTERVAL: Duration = Duration::from_secs(3600);
+
+const SESSION_FILE: &str = "session.json";
+const DATA_FILE: &str = "data.part";
+
+/// Why an upload request was refused.
+#[derive(Debug)]
+pub enum UploadError {
+    NotFound,
+    /// Bad session parameters.
+    Invalid(String),
+    ChunkOutOfRange { index: u64, total: u64 },
+    WrongLength { index: u64, expected: u64, actual: u64 },
+    ChecksumMismatch { index: u64 },
+    Incomplete { missing: Vec<u64> },
+    FileChecksumMismatch,
+    Io(std::io::Error),
+}
+
+impl From<std::io::Error> for UploadError {
+    fn from(e: std::io::Error) -> Self {
+        UploadError::Io(e)
+    }
+}
+
+impl From<UploadError> for ApiError {
+    fn from(e: UploadError) -> Self {
+        match e {
+            UploadError::NotFound => ApiError::not_found("Upload not found"),
+            UploadError::Invalid(message) => ApiError::bad_request(message),
+            UploadError::ChunkOutOfRange { index, total } => {
+                ApiError::bad_request(format!("Chunk {} is out of range (0..{})", index, total))
+            }
+            UploadError::WrongLength { index, expected, actual } => ApiError::bad_request(format!(
+                "Chunk {} must be {} bytes, got {}",
+                index, expected, actual
+            )),
+            UploadError::ChecksumMismatch { index } => ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "checksum_mismatch",
+                format!("Chunk {} does not match its SHA-256; send it again", index),
+            ),
+            UploadError::Incomplete { missing } => ApiError::new(
+                StatusCode::CONFLICT,
+                "upload_incomplete",
+                format!("{} chunks are missing", missing.len()),
+            )
+            .with_details(serde_json::json!({ "missingChunks": missing })),
+            UploadError::FileChecksumMismatch => ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "checksum_mismatch",
+                "The assembled file does not match its SHA-256",
+            ),
+            UploadError::Io(e) => mindsage_core::Error::Io(e).into(),
+        }
+    }
+}
+
+pub type UploadResult<T> = std::result::Result<T, UploadError>;
+
+/// `session.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionFile {
+    id: String,
+    filename: String,
+    size: u64,
+    chunk_size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+    received: BTreeSet<u64>,
+}
+
+impl SessionFile {
+    fn total_chunks(&self) -> u64 {
+        self.size.div_ceil(self.chunk_size).max(1)
+    }
+
+    /// Length of chunk `index`.
+    fn chunk_len(&self, index: u64) -> u64 {
+        self.size.saturating_sub(index * self.chunk_size).min(self.chunk_size)
+    }
+
+    fn to_session(&self) -> UploadSession {
+        let mut ranges: Vec<ByteRange> = Vec::new();
+        for &index in &self.received {
+            let start = index * self.chunk_size;
+            let end = start + self.chunk_len(index);
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => ranges.push(ByteRange { start, end }),
+            }
+        }
+        UploadSession {
+            upload_id: self.id.clone(),
+            filename: self.filename.clone(),
+            size: self.size,
+            chunk_size: self.chunk_size,
+            total_chunks: self.total_chunks(),
+            received_bytes: ranges.iter().map(|r| r.end - r.start).sum(),
+            received_ranges: ranges,
+            missing_chunks: (0..self.total_chunks()).filter(|i| !self.received.contains(i)).collect(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            expires_at: self.updated_at + SESSION_TTL.as_millis() as i64,
+        }
+    }
+}
+
+/// A finished upload, ready to be moved into place.
+pub struct FinishedUpload {
+    pub filename: String,
+    pub size: u64,
+    /// The assembled file, inside the session directory.
+    pub data: PathBuf,
+}
+
+/// Resumable upload sessions of one profile.
+pub struct UploadSessions {
+    dir: PathBuf,
+    /// Serializes session updates; chunk writes are short and sessions few.
+    lock: Mutex<()>,
+}
+
+impl UploadSessions {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Start an upload of `size` bytes named `filename` (already sanitized).
+    pub fn create(
+        &self,
+        filename: &str,
+        size: u64,
+        chunk_size: Option<u64>,
+        sha256: Option<String>,
+    ) -> UploadResult<UploadSession> {
+        if filename.is_empty() {
+            return Err(UploadError::Invalid("filename is required".to_string()));
+        }
+        if let Some(hash) = &sha256 {
+            if !is_sha256_hex(hash) {
+                return Err(UploadError::Invalid("sha256 must be 64 hex digits".to_string()));
+            }
+        }
+        let now = now_millis();
+        let session = SessionFile {
+            id: uuid::Uuid::new_v4().to_string(),
+            filename: filename.to_string(),
+            size,
+            chunk_size: chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE),
+            sha256: sha256.map(|h| h.to_ascii_lowercase()),
+            created_at: now,
+            updated_at: now,
+            received: BTreeSet::new(),
+        };
+        let dir = self.dir.join(&session.id);
+        std::fs::create_dir_all(&dir)?;
+        File::create(dir.join(DATA_FILE))?.set_len(size)?;
+        let _guard = self.lock.lock();
+        save(&dir, &session)?;
+        Ok(session.to_session())
+    }
+
+    pub fn status(&self, id: &str) -> UploadResult<UploadSession> {
+        let _guard = self.lock.lock();
+        Ok(self.load(id)?.to_session())
+    }
+
+    /// Store chunk `index` after checking its length and `sha256` (hex).
+    /// A chunk already received is written again, so a retry after a lost
+    /// response is harmless.
+    pub fn write_chunk(&self, id: &str, index: u64, bytes: &[u8], sha256: &str) -> UploadResult<UploadSession> {
+        let _guard = self.lock.lock();
+        let mut session = self.load(id)?;
+        let total = session.total_chunks();
+        if index >= total {
+            return Err(UploadError::ChunkOutOfRange { index, total });
+        }
+        let expected = session.chunk_len(index);
+        if bytes.len() as u64 != expected {
+            return Err(UploadError::WrongLength {
+                index,
+                expected,
+                actual: bytes.len() as u64,
+            });
+        }
+        if !hex::encode(Sha256::digest(bytes)).eq_ignore_ascii_case(sha256.trim()) {
+            return Err(UploadError::ChecksumMismatch { index });
+        }
+
+        let dir = self.dir.join(&session.id);
+        let mut file = OpenOptions::new().write(true).open(dir.join(DATA_FILE))?;
+        file.seek(SeekFrom::Start(index * session.chunk_size))?;
+        file.write_all(bytes)?;
+        file.sync_data()?;
+
+        session.received.insert(index);
+        session.updated_at = now_millis();
+        save(&dir, &session)?;
+        Ok(session.to_session())
+    }
+
+    /// Check that every chunk arrived and the whole file matches its
+    /// SHA-256, if one was given. The session stays until `remove`.
+    pub fn finish(&self, id: &str) -> UploadResult<FinishedUpload> {
+        let _guard = self.lock.lock();
+        let session = self.load(id)?;
+        let status = session.to_session();
+        if !status.is_complete() {
+            return Err(UploadError::Incomplete {
+                missing: status.missing_chunks,
+            });
+        }
+        let data = self.dir.join(&session.id).join(DATA_FILE);
+        if let Some(expected) = &session.sha256 {
+            let mut hasher = Sha256::new();
+            std::io::copy(&mut File::open(&data)?, &mut hasher)?;
+            if hex::encode(hasher.finalize()) != *expected {
+                return Err(UploadError::FileChecksumMismatch);
+            }
+        }
+        Ok(FinishedUpload {
+            filename: session.filename,
+            size: session.size,
+            data,
+        })
+    }
+
+    /// Delete a session and its data. False when there was none.
+    pub fn remove(&self, id: &str) -> UploadResult<bool> {
+        let _guard = self.lock.lock();
+        let Some(dir) = self.session_dir(id) else {
+            return Ok(false);
+        };
+        match std::fs::remove_dir_all(dir) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Remove sessions idle since before `now - SESSION_TTL`, and directories
+    /// without a readable session. Returns how many were removed.
+    pub fn collect_garbage(&self, now: i64) -> usize {
+        let _guard = self.lock.lock();
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return 0;
+        };
+        let cutoff = now - SESSION_TTL.as_millis() as i64;
+        let mut removed = 0;
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let expired = match read_session(&path) {
+                Ok(session) => session.updated_at < cutoff,
+                // A crash between creating the directory and the session file
+                Err(_) => modified_millis(&path).map_or(true, |m| m < cutoff),
+            };
+            if expired {
+                match std::fs::remove_dir_all(&path) {
+                    Ok(()) => removed += 1,
+                    Err(e) => warn!("Failed to remove expired upload {}: {}", path.display(), e),
+                }
+            }
+        }
+        removed
+    }
+
+    /// The directory of session `id`, if `id` is a session id (a UUID).
+    fn session_dir(&self, id: &str) -> Option<PathBuf> {
+        uuid::Uuid::parse_str(id).ok().map(|id| self.dir.join(id.to_string()))
+    }
+
+    fn load(&self, id: &str) -> UploadResult<SessionFile> {
+        let dir = self.session_dir(id).ok_or(UploadError::NotFound)?;
+        read_session(&dir).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => UploadError::NotFound,
+            _ => UploadError::Io(e),
+        })
+    }
+}
+
+fn read_session(dir: &Path) -> std::io::Result<SessionFile> {
+    let bytes = std::fs::read(dir.join(SESSION_FILE))?;
+    serde_json::from_slice(&bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Write `session.json` through a temporary file, so a crash never leaves
+/// it half written.
+fn save(dir: &Path, session: &SessionFile) -> std::io::Result<()> {
+    let tmp = dir.join(format!("{}.tmp", SESSION_FILE));
+    std::fs::write(&tmp, serde_json::to_vec(session)?)?;
+    std::fs::rename(tmp, dir.join(SESSION_FILE))
+}
+
+fn is_sha256_hex(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn modified_millis(path: &Path) -> Option<i64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(std::time::UNIX_EPOCH).ok()?.as_millis() as i64)
+}
+
+fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+/// Remove expired upload sessions now and every `GC_INTERVAL` until
+/// shutdown.
+pub fn start_upload_gc(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(GC_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = state.shutdown.wait() => break,
+                _ = interval.tick() => {}
+            }
+            let gc_state = state.clone();
+            let removed = tokio::task::spawn_blocking(move || gc_state.uploads.collect_garbage(now_millis()))
+                .await
+                .unwrap_or(0);
+            if removed > 0 {
+                info!("Removed {} expired upload sessions", removed);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha(bytes: &[u8]) -> String {
+        hex::encode(Sha256::digest(bytes))
+    }
+
+    #[test]
+    fn test_chunks_out_of_order_and_repeated() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let sessions = UploadSessions::new(dir.path());
+        let content: Vec<u8> = (0..(MIN_CHUNK_SIZE * 2 + 100)).map(|i| (i % 251) as u8).collect();
+        let chunk = |i: u64| {
+            let start = (i * MIN_CHUNK_SIZE) as usize;
+            &content[start..(start + MIN_CHUNK_SIZE as usize).min(content.len())]
+        };
+        let session = sessions
+            .create("export.zip", content.len() as u64, Some(1), Some(sha(&content)))
+            .unwrap();
+        assert_eq!((session.chunk_size, session.total_chunks), (MIN_CHUNK_SIZE, 3));
+        let id = session.upload_id;
+
+        let status = sessions.write_chunk(&id, 2, chunk(2), &sha(chunk(2))).unwrap();
+        assert_eq!(status.received_ranges, [ByteRange { start: MIN_CHUNK_SIZE * 2, end: content.len() as u64 }]);
+        assert_eq!(status.missing_chunks, [0, 1]);
+        assert!(matches!(sessions.finish(&id), Err(UploadError::Incomplete { missing }) if missing == [0, 1]));
+
+        sessions.write_chunk(&id, 0, chunk(0), &sha(chunk(0))).unwrap();
+        let status = sessions.write_chunk(&id, 0, chunk(0), &sha(chunk(0)).to_uppercase()).unwrap();
+        assert_eq!(status.received_ranges.len(), 2);
+        assert_eq!(status.received_bytes, MIN_CHUNK_SIZE + 100);
+
+        // Bad chunks are refused without being recorded
+        assert!(matches!(
+            sessions.write_chunk(&id, 1, chunk(1), &sha(b"other")),
+            Err(UploadError::ChecksumMismatch { index: 1 })
+        ));
+        assert!(matches!(sessions.write_chunk(&id, 1, chunk(2), &sha(chunk(2))), Err(UploadError::WrongLength { .. })));
+        assert!(matches!(sessions.write_chunk(&id, 3, chunk(2), &sha(chunk(2))), Err(UploadError::ChunkOutOfRange { .. })));
+        assert_eq!(sessions.status(&id).unwrap().missing_chunks, [1]);
+
+        // A new instance (a restart) resumes the same session
+        let sessions = UploadSessions::new(dir.path());
+        let status = sessions.write_chunk(&id, 1, chunk(1), &sha(chunk(1))).unwrap();
+        assert_eq!(status.received_ranges, [ByteRange { start: 0, end: content.len() as u64 }]);
+        let finished = sessions.finish(&id).unwrap();
+        assert_eq!(std::fs::read(&finished.data).unwrap(), content);
+        assert!(sessions.remove(&id).unwrap());
+        assert!(matches!(sessions.status(&id), Err(UploadError::NotFound)));
+    }
+
+    #[test]
+    fn test_whole_file_checksum_and_ids() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let sessions = UploadSessions::new(dir.path());
+        let id = sessions.create("a.txt", 3, None, Some(sha(b"abc"))).unwrap().upload_id;
+        sessions.write_chunk(&id, 0, b"abd", &sha(b"abd")).unwrap();
+        assert!(matches!(sessions.finish(&id), Err(UploadError::FileChecksumMismatch)));
+
+        assert!(matches!(sessions.create("a.txt", 3, None, Some("xyz".into())), Err(UploadError::Invalid(_))));
+        for bad in ["../etc", "", "not-a-uuid"] {
+            assert!(matches!(sessions.status(bad), Err(UploadError::NotFound)));
+            assert!(!sessions.remove(bad).unwrap());
+        }
+
+        // An empty file is one empty chunk
+        let empty = sessions.create("empty.txt", 0, None, None).unwrap();
+        assert_eq!(empty.total_chunks, 1);
+        sessions.write_chunk(&empty.upload_id, 0, b"", &sha(b"")).unwrap();
+        assert!(sessions.finish(&empty.upload_id).is_ok());
+    }
+
+    #[test]
+    fn test_idle_sessions_are_collected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let sessions = UploadSessions::new(dir.path());
+        let old = sessions.create("old.bin", 10, None, None).unwrap();
+        let fresh = sessions.create("fresh.bin", 10, None, None).unwrap();
+        let day = SESSION_TTL.as_millis() as i64;
+
+        assert_eq!(sessions.collect_garbage(now_millis()), 0);
+        // Only chunks keep a session alive: push the fresh one's activity forward
+        sessions.write_chunk(&fresh.upload_id, 0, &[0; 10], &sha(&[0; 10])).unwrap();
+        let later = fresh.updated_at + day + 1;
+        let mut session = sessions.load(&fresh.upload_id).unwrap();
+        session.updated_at = later;
+        save(&dir.path().join(&fresh.upload_id), &session).unwrap();
+
+        assert_eq!(sessions.collect_garbage(later), 1);
+        assert!(matches!(sessions.status(&old.upload_id), Err(UploadError::NotFound)));
+        assert!(sessions.status(&fresh.upload_id).is_ok());
+    }
+}
@@ -0,0 +1,347 @@
+//! Drop-in indexing: a watcher on `data/imports/`.
+//!
+//! With `MINDSAGE_WATCH_IMPORTS=on`, files copied into the imports folder are
+//! queued for indexing without an import call. Filesystem events are
+//! debounced, so a file that is still being written is queued once it has
+//! been quiet for `WATCH_DEBOUNCE`. A scan on startup picks up files added
+//! while the server was down. If the OS watcher cannot be set up (e.g. the
+//! inotify watch limit is reached) or stops, the folder is rescanned every
+//! `WATCH_POLL_INTERVAL` instead. A removed file's document is deleted only
+//! with `MINDSAGE_WATCH_IMPORTS_DELETE=on`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use mindsage_core::redact;
+use notify::{EventKind, RecursiveMode, Watcher};
+use parking_lot::RwLock;
+use tracing::{error, info, warn};
+
+use crate::state::AppState;
+
+/// Quiet time after the last event on a file before it is queued.
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+/// Rescan interval when filesystem events are unavailable.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+pub use mindsage_api_types::{WatchMode, WatcherStatus};
+
+#[derive(Default)]
+pub struct ImportWatcher {
+    status: RwLock<WatcherStatus>,
+}
+
+impl ImportWatcher {
+    pub fn status(&self) -> WatcherStatus {
+        self.status.read().clone()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut WatcherStatus)) {
+        f(&mut self.status.write());
+    }
+}
+
+/// Start watching the imports folder if `watch_imports` is enabled.
+pub fn start_import_watcher(state: Arc<AppState>) {
+    if !state.config.watch_imports {
+        return;
+    }
+    let spawned = std::thread::Builder::new()
+        .name("imports-watcher".into())
+        .spawn(move || run_watcher(&state, WATCH_POLL_INTERVAL));
+    if let Err(e) = spawned {
+        error!("Failed to start imports watcher: {}", e);
+    }
+}
+
+/// Scan once, then follow filesystem events, falling back to polling every
+/// `poll_interval`. Never returns.
+fn run_watcher(state: &AppState, poll_interval: Duration) {
+    let dir = state.config.data_paths.imports.clone();
+    state
+        .import_watcher
+        .update(|s| s.delete_on_remove = state.config.watch_imports_delete);
+
+    // Files added while the server was down
+    scan_imports(state);
+
+    let (tx, rx) = mpsc::channel();
+    let watcher = notify::recommended_watcher(tx).and_then(|mut w| {
+        w.watch(&dir, RecursiveMode::NonRecursive)?;
+        Ok(w)
+    });
+    let warning = match watcher {
+        Ok(_watcher) => {
+            state.import_watcher.update(|s| s.mode = WatchMode::Events);
+            info!("Watching {} for new files", dir.display());
+            follow_events(state, &rx)
+        }
+        Err(e) => e.to_string(),
+    };
+
+    warn!(
+        "Imports watcher unavailable ({}); rescanning every {}s",
+        warning,
+        poll_interval.as_secs()
+    );
+    state.import_watcher.update(|s| {
+        s.mode = WatchMode::Polling;
+        s.warning = Some(warning);
+    });
+    loop {
+        std::thread::sleep(poll_interval);
+        scan_imports(state);
+    }
+}
+
+/// Handle debounced events until the watcher reports an error or stops.
+/// Returns the reason.
+fn follow_events(state: &AppState, rx: &mpsc::Receiver<notify::Result<notify::Event>>) -> String {
+    let mut pending = Debouncer::default();
+    loop {
+        match rx.recv_timeout(WATCH_DEBOUNCE) {
+            // Opens and reads (including the indexer's own) change nothing
+            Ok(Ok(event)) if matches!(event.kind, EventKind::Access(_)) => {}
+            Ok(Ok(event)) => {
+                let now = Instant::now();
+                for path in event.paths {
+                    pending.touch(path, now);
+                }
+            }
+            Ok(Err(e)) => return e.to_string(),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return "watcher stopped".to_string(),
+        }
+        for path in pending.ready(Instant::now(), WATCH_DEBOUNCE) {
+            handle_path(state, &path);
+        }
+    }
+}
+
+/// Queue new or changed files in the imports folder and, with
+/// `watch_imports_delete`, drop documents whose file is gone. Returns the
+/// number of files queued.
+pub fn scan_imports(state: &AppState) -> usize {
+    let dir = &state.config.data_paths.imports;
+    let mut queued = 0;
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            if handle_path(state, &entry.path()) {
+                queued += 1;
+            }
+        }
+    }
+
+    if state.config.watch_imports_delete {
+        let prefix = format!("{}{}", dir.display(), std::path::MAIN_SEPARATOR);
+        match state.store.get_indexed_files_under(&prefix) {
+            Ok(files) => {
+                for file in files.iter().filter(|f| !Path::new(&f.path).exists()) {
+                    handle_removed(state, &file.path);
+                }
+            }
+            Err(e) => error!("Failed to list indexed imports: {}", e),
+        }
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    state.import_watcher.update(|s| s.last_scan_at = Some(now));
+    queued
+}
+
+/// React to a change at `path`. Returns whether a file was queued.
+fn handle_path(state: &AppState, path: &Path) -> bool {
+    let dir = &state.config.data_paths.imports;
+    // Rebuild the path from the configured folder so it matches the paths
+    // recorded by uploads and imports, however the OS reported it
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    if !is_candidate(name) || path == dir.as_path() {
+        return false;
+    }
+    let path = dir.join(name);
+    let file_path = path.to_string_lossy().to_string();
+
+    match std::fs::metadata(&path) {
+        Ok(meta) if meta.is_file() => {
+            if state.is_file_indexed(&file_path) || state.has_pending_job(&file_path) {
+                return false;
+            }
+            state.queue_indexing(file_path, name.to_string());
+            state.import_watcher.update(|s| s.files_queued += 1);
+            info!("Queued {} from imports", redact(name));
+            true
+        }
+        Ok(_) => false,
+        Err(_) => {
+            if state.config.watch_imports_delete {
+                handle_removed(state, &file_path);
+            }
+            false
+        }
+    }
+}
+
+/// Delete the document indexed from a removed file and forget the file.
+fn handle_removed(state: &AppState, file_path: &str) {
+    let Ok(Some(record)) = state.store.get_indexed_file(file_path) else {
+        return;
+    };
+    if let Some(doc_id) = record.doc_id {
+        if let Err(e) = state.store.delete_document(doc_id) {
+            error!("Failed to delete document {} for removed file: {}", doc_id, e);
+            return;
+        }
+    }
+    if let Err(e) = state.store.delete_indexed_file(file_path) {
+        error!("Failed to forget removed file: {}", e);
+        return;
+    }
+    state.import_watcher.update(|s| s.files_removed += 1);
+    info!("Removed {} and its document", redact(file_path));
+}
+
+/// Skip hidden files and the partial files editors and browsers write.
+fn is_candidate(name: &str) -> bool {
+    !name.starts_with('.')
+        && !name.ends_with('~')
+        && ![".tmp", ".part", ".crdownload", ".swp"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+}
+
+/// Paths that have seen events, released once they have been quiet long enough.
+#[derive(Default)]
+struct Debouncer {
+    last_event: HashMap<PathBuf, Instant>,
+}
+
+impl Debouncer {
+    fn touch(&mut self, path: PathBuf, now: Instant) {
+        self.last_event.insert(path, now);
+    }
+
+    fn ready(&mut self, now: Instant, quiet: Duration) -> Vec<PathBuf> {
+        let ready: Vec<PathBuf> = self
+            .last_event
+            .iter()
+            .filter(|(_, at)| now.duration_since(**at) >= quiet)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &ready {
+            self.last_event.remove(path);
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use crate::state::IndexingStatus;
+    use crate::test_support::TestState;
+
+    /// Stand in for the indexing worker: complete every job and record its file.
+    fn complete_jobs(state: &AppState) {
+        let paths: Vec<String> = state
+            .indexing_jobs
+            .write()
+            .values_mut()
+            .map(|j| {
+                j.status = IndexingStatus::Completed;
+                j.file_path.clone()
+            })
+            .collect();
+        for path in paths {
+            let doc_id = state.store.add_document(&path, Default::default()).unwrap();
+            state.mark_file_indexed(&path, Some(doc_id));
+        }
+    }
+
+    #[test]
+    fn test_scan_queues_new_and_changed_files() {
+        let dir = TempDir::new().unwrap();
+        let state = TestState::new(&dir).build();
+        let imports = state.config.data_paths.imports.clone();
+        std::fs::write(imports.join("a.txt"), "first note").unwrap();
+        std::fs::write(imports.join("b.md"), "# second").unwrap();
+        std::fs::write(imports.join(".hidden"), "skip").unwrap();
+        std::fs::write(imports.join("movie.mp4.part"), "skip").unwrap();
+
+        assert_eq!(scan_imports(&state), 2);
+        // Already queued
+        assert_eq!(scan_imports(&state), 0);
+
+        complete_jobs(&state);
+        assert_eq!(scan_imports(&state), 0);
+
+        std::fs::write(imports.join("a.txt"), "first note, edited").unwrap();
+        assert_eq!(scan_imports(&state), 1);
+        let status = state.import_watcher.status();
+        assert_eq!(status.files_queued, 3);
+        assert!(status.last_scan_at.is_some());
+    }
+
+    #[test]
+    fn test_removed_file_deletes_document_only_when_enabled() {
+        for delete_on_remove in [false, true] {
+            let dir = TempDir::new().unwrap();
+            let state = TestState::new(&dir).config(|c| c.watch_imports_delete = delete_on_remove).build();
+            let path = state.config.data_paths.imports.join("notes.txt");
+            std::fs::write(&path, "meeting notes").unwrap();
+            scan_imports(&state);
+            complete_jobs(&state);
+            let file_path = path.to_string_lossy().to_string();
+            let doc_id = state.store.get_indexed_file(&file_path).unwrap().unwrap().doc_id.unwrap();
+
+            std::fs::remove_file(&path).unwrap();
+            scan_imports(&state);
+            let doc = state.store.get_document(doc_id).unwrap();
+            assert_eq!(doc.is_none(), delete_on_remove);
+            assert_eq!(state.store.get_indexed_file(&file_path).unwrap().is_none(), delete_on_remove);
+            assert_eq!(state.import_watcher.status().files_removed, delete_on_remove as usize);
+        }
+    }
+
+    #[test]
+    fn test_watcher_picks_up_dropped_file() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).build());
+        let imports = state.config.data_paths.imports.clone();
+        std::fs::write(imports.join("before.txt"), "added while down").unwrap();
+
+        let watcher_state = state.clone();
+        std::thread::spawn(move || run_watcher(&watcher_state, Duration::from_millis(200)));
+
+        // Events or, where they are unavailable, polling find the new file
+        let dropped = imports.join("dropped.txt");
+        std::thread::sleep(Duration::from_millis(300));
+        std::fs::write(&dropped, "dropped in").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(15);
+        while !state.has_pending_job(&dropped.to_string_lossy()) {
+            assert!(Instant::now() < deadline, "dropped file was not queued");
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert!(state.has_pending_job(&imports.join("before.txt").to_string_lossy()));
+        assert_ne!(state.import_watcher.status().mode, WatchMode::Off);
+    }
+
+    #[test]
+    fn test_debouncer_waits_for_quiet() {
+        let mut debouncer = Debouncer::default();
+        let start = Instant::now();
+        let quiet = Duration::from_secs(2);
+        debouncer.touch(PathBuf::from("a"), start);
+        debouncer.touch(PathBuf::from("a"), start + Duration::from_secs(1));
+        assert!(debouncer.ready(start + Duration::from_secs(2), quiet).is_empty());
+        assert_eq!(debouncer.ready(start + Duration::from_secs(3), quiet), vec![PathBuf::from("a")]);
+        assert!(debouncer.ready(start + Duration::from_secs(9), quiet).is_empty());
+    }
+}
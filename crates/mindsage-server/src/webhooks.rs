@@ -0,0 +1,405 @@
+//! Outbound webhooks — POST bus events to configured URLs.
+//!
+//! The dispatcher subscribes to the profile's event bus and sends each
+//! notable event to every enabled endpoint whose filter accepts its
+//! category. The body is the same JSON frame `/api/events` clients receive.
+//! With a secret, `X-MindSage-Signature: sha256=<hex>` carries the
+//! HMAC-SHA256 of the body. Failed deliveries (network errors and non-2xx
+//! responses) are retried with exponential backoff; after the last attempt
+//! the delivery is appended to `data/webhooks-dead-letter.jsonl`.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use mindsage_core::{Event, WebhookEndpoint};
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use crate::state::AppState;
+
+pub const SIGNATURE_HEADER: &str = "x-mindsage-signature";
+pub const EVENT_HEADER: &str = "x-mindsage-event";
+pub const DELIVERY_HEADER: &str = "x-mindsage-delivery";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often and how long a failed delivery is retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Attempts including the first.
+    pub max_attempts: u32,
+    /// Wait before the second attempt; doubled for each further one.
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Outcome of delivering one event to one endpoint.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    pub delivered: bool,
+    pub attempts: u32,
+    /// HTTP status of the last response, if any arrived.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A delivery that failed every attempt, as written to the dead-letter log.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeadLetter<'a> {
+    failed_at: String,
+    endpoint_id: &'a str,
+    url: &'a str,
+    attempts: u32,
+    error: &'a str,
+    event: serde_json::Value,
+}
+
+/// Configured endpoints and the HTTP client that delivers to them.
+pub struct Webhooks {
+    endpoints: RwLock<Vec<WebhookEndpoint>>,
+    path: PathBuf,
+    dead_letter_path: PathBuf,
+    dead_letter_lock: Mutex<()>,
+    retry: RetryPolicy,
+    client: reqwest::Client,
+}
+
+impl Webhooks {
+    pub fn new(endpoints: Vec<WebhookEndpoint>, path: PathBuf, dead_letter_path: PathBuf) -> Self {
+        Self {
+            endpoints: RwLock::new(endpoints),
+            path,
+            dead_letter_path,
+            dead_letter_lock: Mutex::new(()),
+            retry: RetryPolicy::default(),
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn list(&self) -> Vec<WebhookEndpoint> {
+        self.endpoints.read().clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<WebhookEndpoint> {
+        self.endpoints.read().iter().find(|e| e.id == id).cloned()
+    }
+
+    /// Replace all endpoints and save them to `data/webhooks.json`.
+    pub fn replace(&self, endpoints: Vec<WebhookEndpoint>) -> std::io::Result<()> {
+        let data = serde_json::to_vec_pretty(&endpoints)?;
+        let mut tmp_name = self.path.as_os_str().to_owned();
+        tmp_name.push(".tmp");
+        let tmp = PathBuf::from(tmp_name);
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, &self.path)?;
+        *self.endpoints.write() = endpoints;
+        Ok(())
+    }
+
+    /// Send `event` once, without retrying or dead-lettering.
+    pub async fn send(&self, endpoint: &WebhookEndpoint, event: &Event) -> Delivery {
+        let body = event.to_frame();
+        let delivery_id = uuid::Uuid::new_v4().to_string();
+        self.attempt(endpoint, event, &body, &delivery_id, 1).await
+    }
+
+    /// Send `event` with retries; a delivery that never succeeds goes to the
+    /// dead-letter log.
+    pub async fn deliver(&self, endpoint: &WebhookEndpoint, event: &Event) -> Delivery {
+        let body = event.to_frame();
+        let delivery_id = uuid::Uuid::new_v4().to_string();
+        let max_attempts = self.retry.max_attempts.max(1);
+        let mut delay = self.retry.base_delay;
+        let mut attempt = 1;
+        loop {
+            let delivery = self.attempt(endpoint, event, &body, &delivery_id, attempt).await;
+            if delivery.delivered {
+                return delivery;
+            }
+            if attempt >= max_attempts {
+                let error = delivery.error.as_deref().unwrap_or("delivery failed");
+                warn!("Webhook {} failed after {} attempts: {}", endpoint.id, attempt, error);
+                self.dead_letter(endpoint, &body, attempt, error);
+                return delivery;
+            }
+            debug!("Webhook {} attempt {} failed, retrying in {:?}", endpoint.id, attempt, delay);
+            tokio::time::sleep(delay).await;
+            delay = delay.saturating_mul(2);
+            attempt += 1;
+        }
+    }
+
+    async fn attempt(
+        &self,
+        endpoint: &WebhookEndpoint,
+        event: &Event,
+        body: &str,
+        delivery_id: &str,
+        attempt: u32,
+    ) -> Delivery {
+        let event_type = serde_json::to_value(event)
+            .ok()
+            .and_then(|v| v.get("type").and_then(|t| t.as_str()).map(str::to_string))
+            .unwrap_or_default();
+        let mut request = self
+            .client
+            .post(&endpoint.url)
+            .header("content-type", "application/json")
+            .header(EVENT_HEADER, event_type)
+            .header(DELIVERY_HEADER, delivery_id)
+            .body(body.to_string());
+        if let Some(secret) = &endpoint.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret.expose(), body.as_bytes()));
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_success() => Delivery {
+                delivered: true,
+                attempts: attempt,
+                status: Some(response.status().as_u16()),
+                error: None,
+            },
+            Ok(response) => Delivery {
+                delivered: false,
+                attempts: attempt,
+                status: Some(response.status().as_u16()),
+                error: Some(format!("HTTP {}", response.status())),
+            },
+            Err(e) => Delivery {
+                delivered: false,
+                attempts: attempt,
+                status: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+
+    fn dead_letter(&self, endpoint: &WebhookEndpoint, body: &str, attempts: u32, error: &str) {
+        let entry = DeadLetter {
+            failed_at: chrono::Utc::now().to_rfc3339(),
+            endpoint_id: &endpoint.id,
+            url: &endpoint.url,
+            attempts,
+            error,
+            event: serde_json::from_str(body).unwrap_or_default(),
+        };
+        let Ok(line) = serde_json::to_string(&entry) else {
+            return;
+        };
+        let _guard = self.dead_letter_lock.lock();
+        let result = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.dead_letter_path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        if let Err(e) = result {
+            warn!("Failed to write webhook dead letter: {}", e);
+        }
+    }
+}
+
+/// `sha256=<hex>` HMAC of `body` keyed with `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Whether an event is worth a webhook call. Intermediate states (queued or
+/// processing jobs, catch-up, distill and connector import progress) stay
+/// on the WebSocket bus.
+fn is_notable(event: &Event) -> bool {
+    match event {
+        Event::IndexingJob { status, .. } => matches!(status.as_str(), "completed" | "failed"),
+        Event::DistillProgress { done, .. } => *done,
+        Event::IndexingCatchup { finished, .. } => *finished,
+        Event::ConnectorProgress { .. } => false,
+        Event::LocalSendSession { state, .. } => matches!(state.as_str(), "pending" | "finished" | "declined" | "cancelled"),
+        _ => true,
+    }
+}
+
+/// Deliver the profile's notable events to its webhooks until shutdown.
+pub fn start_webhook_dispatcher(state: Arc<AppState>) {
+    let mut rx = state.events.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                _ = state.shutdown.wait() => break,
+                event = rx.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Webhook dispatcher lagged, dropped {} events", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            };
+            if !is_notable(&event) {
+                continue;
+            }
+            let category = event.category();
+            for endpoint in state.webhooks.list().into_iter().filter(|e| e.accepts(category)) {
+                let state = state.clone();
+                let event = event.clone();
+                tokio::spawn(async move {
+                    state.webhooks.deliver(&endpoint, &event).await;
+                });
+            }
+        }
+        info!("Webhook dispatcher stopped");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use mindsage_core::events::EventCategory;
+
+    /// Local receiver that fails the first `failures` requests.
+    #[derive(Clone, Default)]
+    struct Receiver {
+        failures: usize,
+        seen: Arc<AtomicUsize>,
+        requests: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+    }
+
+    async fn receive(State(receiver): State<Receiver>, headers: HeaderMap, body: String) -> StatusCode {
+        let n = receiver.seen.fetch_add(1, Ordering::SeqCst);
+        receiver.requests.lock().push((headers, body));
+        if n < receiver.failures {
+            StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            StatusCode::OK
+        }
+    }
+
+    async fn start_receiver(failures: usize) -> (Receiver, String) {
+        let receiver = Receiver {
+            failures,
+            ..Default::default()
+        };
+        let app = Router::new().route("/hook", post(receive)).with_state(receiver.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (receiver, format!("http://{}/hook", addr))
+    }
+
+    fn endpoint(url: &str, secret: Option<&str>) -> WebhookEndpoint {
+        WebhookEndpoint {
+            id: "home".to_string(),
+            url: url.to_string(),
+            secret: secret.map(Into::into),
+            events: vec![EventCategory::Indexing],
+            enabled: true,
+        }
+    }
+
+    fn completed_job() -> Event {
+        Event::IndexingJob {
+            job_id: "job-1".into(),
+            filename: "notes.md".into(),
+            status: "completed".into(),
+            document_id: Some(3),
+            error: None,
+        }
+    }
+
+    fn webhooks(dir: &tempfile::TempDir) -> Webhooks {
+        Webhooks::new(Vec::new(), dir.path().join("webhooks.json"), dir.path().join("dead.jsonl")).with_retry(
+            RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(10),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_delivery_is_signed_and_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let (receiver, url) = start_receiver(2).await;
+        let webhooks = webhooks(&dir);
+
+        let delivery = webhooks.deliver(&endpoint(&url, Some("s3cret")), &completed_job()).await;
+        assert!(delivery.delivered);
+        assert_eq!((delivery.attempts, delivery.status), (3, Some(200)));
+
+        let requests = receiver.requests.lock();
+        assert_eq!(requests.len(), 3);
+        let (headers, body) = &requests[2];
+        assert_eq!(headers[SIGNATURE_HEADER], sign("s3cret", body.as_bytes()).as_str());
+        assert_eq!(headers[EVENT_HEADER], "indexing.job");
+        // Retries of one delivery share its id
+        assert_eq!(headers[DELIVERY_HEADER], requests[0].0[DELIVERY_HEADER]);
+        let frame: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!((frame["type"].as_str(), frame["jobId"].as_str()), (Some("indexing.job"), Some("job-1")));
+        assert!(!dir.path().join("dead.jsonl").exists());
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_delivery_is_dead_lettered() {
+        let dir = tempfile::tempdir().unwrap();
+        let (receiver, url) = start_receiver(usize::MAX).await;
+        let webhooks = webhooks(&dir);
+
+        let delivery = webhooks.deliver(&endpoint(&url, None), &completed_job()).await;
+        assert!(!delivery.delivered);
+        assert_eq!((delivery.attempts, delivery.status), (3, Some(503)));
+        assert!(receiver.requests.lock()[0].0.get(SIGNATURE_HEADER).is_none());
+
+        let log = std::fs::read_to_string(dir.path().join("dead.jsonl")).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(log.trim()).unwrap();
+        assert_eq!((entry["endpointId"].as_str(), entry["attempts"].as_u64()), (Some("home"), Some(3)));
+        assert_eq!(entry["event"]["jobId"], "job-1");
+    }
+
+    #[test]
+    fn test_only_final_states_are_notable() {
+        let job = |status: &str| Event::IndexingJob {
+            job_id: "j".into(),
+            filename: "f".into(),
+            status: status.into(),
+            document_id: None,
+            error: None,
+        };
+        assert!(!is_notable(&job("queued")));
+        assert!(!is_notable(&job("processing")));
+        assert!(is_notable(&job("failed")));
+        assert!(endpoint("http://x", None).accepts(EventCategory::Indexing));
+        assert!(!endpoint("http://x", None).accepts(EventCategory::Capture));
+    }
+}
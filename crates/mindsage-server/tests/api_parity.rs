@@ -363,3 +363,54 @@ fn test_llm_config_shape() {
     assert!(config["anthropicModel"].is_string());
     assert!(config["groqModel"].is_string());
 }
+
+/// Verify the error envelope shape. Every non-2xx response carries
+/// `code`/`message`/`status`, plus the legacy `error` string the
+/// frontend checks.
+#[test]
+fn test_error_envelope_shape() {
+    let error_json = serde_json::json!({
+        "code": "duplicate_content",
+        "message": "Duplicate content",
+        "status": 409,
+        "error": "Duplicate content",
+        "details": { "content_hash": "abc123" },
+    });
+
+    assert!(error_json["code"].is_string());
+    assert!(error_json["message"].is_string());
+    assert!(error_json["status"].is_number());
+    assert_eq!(error_json["error"], error_json["message"]);
+    assert!(error_json["details"].is_object());
+}
+
+/// Verify the paginated document chunks response shape.
+#[test]
+fn test_document_chunks_page_shape() {
+    let page = serde_json::json!({
+        "docId": 7,
+        "chunks": [
+            { "id": 1, "doc_id": 7, "chunk_index": 0, "level": 1, "created_at": 0 },
+        ],
+        "total": 120,
+        "page": 1,
+        "pageSize": 50,
+        "totalPages": 3,
+    });
+
+    assert!(page["docId"].is_number());
+    assert!(page["chunks"].is_array());
+    assert!(page["total"].is_number());
+    assert!(page["pageSize"].is_number());
+    assert!(page["totalPages"].is_number());
+    // omit_text=true drops text fields
+    assert!(page["chunks"][0].get("text").is_none());
+
+    let document = serde_json::json!({
+        "document": { "id": 7 },
+        "chunkSummary": { "total": 120, "byLevel": { "0": 8, "1": 112 } },
+    });
+    assert!(document["chunkSummary"]["total"].is_number());
+    assert!(document["chunkSummary"]["byLevel"].is_object());
+    assert!(document.get("chunks").is_none());
+}
@@ -0,0 +1,47 @@
+//! Streaming chat answered by the mock provider, with RAG context.
+
+use serde_json::json;
+
+use crate::harness::{Harness, MockLlm};
+use mindsage_server::audit::AuditQuery;
+
+#[tokio::test]
+async fn test_streaming_chat_sends_rag_context() {
+    let server = Harness::new();
+    server
+        .upload(&[
+            ("ferry.md", "# Ferry\n\nThe island ferry leaves the harbor at ten every morning."),
+            ("bread.md", "# Bread\n\nSourdough needs a long cold proof in the fridge."),
+        ])
+        .await;
+    server.wait_for_indexing().await;
+    let llm = MockLlm::start(&["The ferry ", "leaves at ", "ten."]).await;
+    server.use_llm(&llm);
+
+    let events = server
+        .post_sse("/api/chat/stream", json!({"message": "When does the island ferry leave?", "topK": 1}))
+        .await;
+    let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(types.first(), Some(&"context"));
+    assert_eq!(types.last(), Some(&"done"));
+    let context = events[0]["context"].as_array().unwrap();
+    assert_eq!(context.len(), 1);
+    assert!(context[0]["excerpt"].as_str().unwrap().contains("island ferry"), "{:?}", context);
+    let answer: String = events.iter().filter_map(|e| e["content"].as_str()).collect();
+    assert_eq!(answer, "The ferry leaves at ten.");
+
+    // The provider got the context in the system prompt and the question last
+    let requests = llm.requests();
+    assert_eq!(requests.len(), 1);
+    let messages = requests[0]["messages"].as_array().unwrap();
+    let system = messages[0]["content"].as_str().unwrap();
+    assert_eq!(messages[0]["role"], "system");
+    assert!(system.contains("leaves the harbor at ten"));
+    assert!(!system.contains("Sourdough"));
+    assert_eq!(messages.last().unwrap()["content"], "When does the island ferry leave?");
+    assert_eq!(requests[0]["stream"], true);
+
+    let (entries, total) = server.state.audit.list(&AuditQuery::default()).unwrap();
+    assert_eq!(total, 1);
+    assert!(!entries[0].chunk_ids.is_empty());
+}
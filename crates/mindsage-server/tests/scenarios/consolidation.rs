@@ -0,0 +1,39 @@
+//! Deleting documents, then consolidating.
+
+use serde_json::json;
+
+use crate::harness::Harness;
+
+#[tokio::test]
+async fn test_consolidation_after_deletes() {
+    let server = Harness::new();
+    let texts = [
+        "Ferry timetable: the first boat leaves at seven.",
+        "Ferry timetable: the last boat returns at nine.",
+        "Library opening hours change in August.",
+    ];
+    let mut ids = Vec::new();
+    for text in texts {
+        let added = server.post_ok("/api/vector-store/documents", json!({"text": text})).await;
+        ids.push(added["id"].as_i64().unwrap());
+    }
+    server.wait_for_indexing().await;
+
+    let (status, _) = server.delete(&format!("/api/vector-store/documents/{}", ids[0])).await;
+    assert!(status.is_success());
+    let report = server
+        .blocking(|state| state.orchestrator.consolidate(&state.store))
+        .await;
+    assert_eq!(report.duplicates_removed, 0);
+
+    let (_, sources) = server.get("/api/stats/sources").await;
+    assert_eq!(sources["consolidations"].as_array().unwrap().len(), 1);
+    let (_, stats) = server.get("/api/stats").await;
+    assert_eq!(stats["documents"], 2);
+    assert_eq!(stats["embeddings"], stats["paragraphChunks"]);
+
+    let (hits, search_type) = server.search("ferry boat timetable").await;
+    assert_eq!(search_type, "hybrid");
+    assert!(!hits.contains(&ids[0]));
+    assert_eq!(hits[0], ids[1]);
+}
@@ -0,0 +1,248 @@
+//! The pieces a scenario runs against: the full router over a temporary
+//! data directory, a deterministic embedder and a mock LLM provider.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{Method, Request, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::{Json, Router};
+use mindsage_api_types::IndexingStatus;
+use mindsage_core::{MindSageConfig, Secret};
+use parking_lot::Mutex;
+use serde_json::Value;
+use tempfile::TempDir;
+use tokio::sync::broadcast::error::RecvError;
+use tower::ServiceExt;
+
+use mindsage_server::indexing;
+use mindsage_server::profiles::Profiles;
+use mindsage_server::state::AppState;
+use mindsage_server::test_support::TestState;
+
+/// How long `wait_for_indexing` waits for the next indexing event before
+/// failing the scenario; a backstop against hangs, not a poll interval.
+const EVENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Request bodies a `MockLlm` received.
+type Recorded = Arc<Mutex<Vec<Value>>>;
+
+/// An OpenAI-compatible chat completions server on an ephemeral port. It
+/// streams a fixed answer and keeps every request body it received.
+pub struct MockLlm {
+    pub base_url: String,
+    requests: Recorded,
+}
+
+impl MockLlm {
+    /// Serve `tokens` as the streamed answer to every request.
+    pub async fn start(tokens: &[&str]) -> Self {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let tokens: Arc<Vec<String>> = Arc::new(tokens.iter().map(|t| t.to_string()).collect());
+        let app = Router::new()
+            .route("/v1/chat/completions", post(complete))
+            .with_state((requests.clone(), tokens));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Self {
+            base_url: format!("http://{}/v1", addr),
+            requests,
+        }
+    }
+
+    /// Request bodies received so far, oldest first.
+    pub fn requests(&self) -> Vec<Value> {
+        self.requests.lock().clone()
+    }
+}
+
+async fn complete(
+    State((requests, tokens)): State<(Recorded, Arc<Vec<String>>)>,
+    Json(body): Json<Value>,
+) -> impl IntoResponse {
+    requests.lock().push(body);
+    let mut sse: String = tokens
+        .iter()
+        .map(|t| format!("data: {}\n\n", serde_json::json!({"choices": [{"delta": {"content": t}}]})))
+        .collect();
+    sse.push_str("data: [DONE]\n\n");
+    ([("content-type", "text/event-stream")], sse)
+}
+
+/// A server over a temporary data directory, called in-process.
+pub struct Harness {
+    pub state: Arc<AppState>,
+    app: Router,
+    _dir: TempDir,
+}
+
+impl Harness {
+    /// Default config, `WordEmbedder`, no LLM provider.
+    pub fn new() -> Self {
+        Self::with_config(|_| {})
+    }
+
+    /// Like `new`, with the config adjusted by `configure` first.
+    pub fn with_config(configure: impl FnOnce(&mut MindSageConfig)) -> Self {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(TestState::new(&dir).config(configure).word_embedder().build());
+        {
+            // Keys from the environment must not send scenario chats anywhere
+            let mut llm = state.llm_config.write();
+            llm.openai_api_key = None;
+            llm.anthropic_api_key = None;
+            llm.groq_api_key = None;
+        }
+        let app = mindsage_server::routes::build_app(Arc::new(Profiles::start(state.clone())));
+        Self { state, app, _dir: dir }
+    }
+
+    /// Answer chats with `llm`, as OpenAI.
+    pub fn use_llm(&self, llm: &MockLlm) {
+        let mut config = self.state.llm_config.write();
+        config.preferred_provider = "openai".into();
+        config.openai_api_key = Some(Secret::new("test-key"));
+        config.openai_base_url = Some(llm.base_url.clone());
+    }
+
+    /// Send a request; the body is returned as JSON, `Null` when empty or
+    /// not JSON.
+    pub async fn send(&self, request: Request<Body>) -> (StatusCode, Value) {
+        let (status, bytes) = self.send_raw(request).await;
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    /// Send a request and read the whole body.
+    pub async fn send_raw(&self, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = self.app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, bytes.to_vec())
+    }
+
+    pub async fn get(&self, uri: &str) -> (StatusCode, Value) {
+        self.send(Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+
+    pub async fn delete(&self, uri: &str) -> (StatusCode, Value) {
+        self.send(Request::delete(uri).body(Body::empty()).unwrap()).await
+    }
+
+    pub async fn post(&self, uri: &str, body: Value) -> (StatusCode, Value) {
+        self.send(json_request(Method::POST, uri, &body)).await
+    }
+
+    /// `POST` raw bytes.
+    pub async fn post_bytes(&self, uri: &str, body: impl Into<Body>) -> (StatusCode, Value) {
+        self.send(Request::post(uri).body(body.into()).unwrap()).await
+    }
+
+    /// `post`, failing the scenario unless the answer is 2xx.
+    pub async fn post_ok(&self, uri: &str, body: Value) -> Value {
+        let (status, response) = self.post(uri, body).await;
+        assert!(status.is_success(), "POST {}: {} {}", uri, status, response);
+        response
+    }
+
+    /// Upload files through `POST /api/files/upload` (multipart).
+    pub async fn upload(&self, files: &[(&str, &str)]) -> Value {
+        let boundary = "scenario-boundary";
+        let mut body = String::new();
+        for (name, content) in files {
+            body.push_str(&format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n{content}\r\n"
+            ));
+        }
+        body.push_str(&format!("--{boundary}--\r\n"));
+        let request = Request::post("/api/files/upload")
+            .header("content-type", format!("multipart/form-data; boundary={boundary}"))
+            .body(Body::from(body))
+            .unwrap();
+        let (status, response) = self.send(request).await;
+        assert_eq!(status, StatusCode::OK, "{}", response);
+        response
+    }
+
+    /// `POST` a streaming endpoint and parse its `data:` events, up to
+    /// `[DONE]`.
+    pub async fn post_sse(&self, uri: &str, body: Value) -> Vec<Value> {
+        let (status, bytes) = self.send_raw(json_request(Method::POST, uri, &body)).await;
+        assert_eq!(status, StatusCode::OK);
+        String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: ").or_else(|| l.strip_prefix("data:")))
+            .take_while(|d| d.trim() != "[DONE]")
+            .map(|d| serde_json::from_str(d).unwrap())
+            .collect()
+    }
+
+    /// Search, returning the document ids of the hits in order and the
+    /// search type (`hybrid` or `bm25`).
+    pub async fn search(&self, query: &str) -> (Vec<i64>, String) {
+        let response = self
+            .post_ok("/api/vector-store/search", serde_json::json!({"query": query}))
+            .await;
+        let ids = response["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["doc_id"].as_i64().unwrap())
+            .collect();
+        (ids, response["search_type"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// Wait until the indexing queue is drained and every paragraph chunk
+    /// has an embedding. Queued jobs are followed through their
+    /// `indexing.job` events; once none is left, the chunks the ingest
+    /// journal lists are embedded right here. A catch-up pass already
+    /// running takes those over instead, and its `indexing.catchup` event
+    /// ends the wait. Subscribing first means no event is missed.
+    pub async fn wait_for_indexing(&self) {
+        let mut events = self.state.events.subscribe();
+        loop {
+            let busy = self
+                .state
+                .indexing_jobs
+                .read()
+                .values()
+                .any(|job| matches!(job.status, IndexingStatus::Queued | IndexingStatus::Processing));
+            if !busy {
+                self.blocking(indexing::embed_pending_chunks).await;
+                let embedded = self.state.store.get_chunks_without_embedding(1).is_ok_and(|c| c.is_empty());
+                if embedded {
+                    return;
+                }
+            }
+            match tokio::time::timeout(EVENT_TIMEOUT, events.recv()).await {
+                Ok(Ok(_)) | Ok(Err(RecvError::Lagged(_))) => {}
+                Ok(Err(RecvError::Closed)) => panic!("event bus closed while waiting for indexing"),
+                Err(_) => panic!("no indexing progress in {:?}", EVENT_TIMEOUT),
+            }
+        }
+    }
+
+    /// Run `f` on a blocking thread with the state, as handlers do.
+    pub fn blocking<T, F>(&self, f: F) -> impl Future<Output = T> + '_
+    where
+        F: FnOnce(&AppState) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.state.blocking(f)
+    }
+}
+
+fn json_request(method: Method, uri: &str, body: &Value) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
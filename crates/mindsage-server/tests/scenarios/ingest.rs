@@ -0,0 +1,85 @@
+//! Uploads and batch adds, through to search.
+
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::harness::Harness;
+
+#[tokio::test]
+async fn test_uploaded_files_become_searchable() {
+    let server = Harness::new();
+    let uploaded = server
+        .upload(&[
+            ("harbor.md", "# Harbor\n\nTide tables for the harbor mouth, updated weekly."),
+            ("bread.txt", "Sourdough bread needs a long cold proof in the fridge."),
+        ])
+        .await;
+    assert_eq!((uploaded["uploaded"].as_u64(), uploaded["errors"].as_u64()), (Some(2), Some(0)));
+    server.wait_for_indexing().await;
+
+    let (_, status) = server.get("/api/indexing/status").await;
+    assert_eq!((status["completed"].as_u64(), status["failed"].as_u64()), (Some(2), Some(0)));
+    let (_, stats) = server.get("/api/stats").await;
+    assert_eq!(stats["documents"], 2);
+    assert_eq!(stats["embeddings"], stats["paragraphChunks"]);
+
+    let (hits, search_type) = server.search("harbor tide tables").await;
+    assert_eq!(search_type, "hybrid");
+    let (_, doc) = server.get(&format!("/api/vector-store/documents/{}", hits[0])).await;
+    assert_eq!(doc["document"]["metadata"]["filename"], "harbor.md");
+    let (hits, _) = server.search("cold proof").await;
+    let (_, doc) = server.get(&format!("/api/vector-store/documents/{}", hits[0])).await;
+    assert_eq!(doc["document"]["metadata"]["filename"], "bread.txt");
+}
+
+#[tokio::test]
+async fn test_batch_add_skips_duplicates() {
+    let server = Harness::new();
+    server
+        .post_ok("/api/vector-store/documents", json!({"text": "The allotment water tap is fixed."}))
+        .await;
+
+    let batch = server
+        .post_ok(
+            "/api/vector-store/documents/batch",
+            json!({"documents": [
+                {"text": "Seed swap at the allotment on Saturday."},
+                {"text": "The allotment water tap is fixed."},
+                {"text": "Seed swap at the allotment on Saturday."},
+                {"text": "Compost needs turning every two weeks."},
+            ]}),
+        )
+        .await;
+    assert_eq!((batch["added"].as_u64(), batch["duplicates"].as_u64()), (Some(2), Some(2)));
+
+    // An atomic batch with a duplicate adds nothing
+    let (status, atomic) = server
+        .post(
+            "/api/vector-store/documents/batch",
+            json!({"atomic": true, "skip_duplicates": false, "documents": [
+                {"text": "Netting for the brassicas."},
+                {"text": "Compost needs turning every two weeks."},
+            ]}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", atomic);
+    assert_eq!(atomic["added"], 0);
+
+    // ... and one without adds and embeds everything
+    let atomic = server
+        .post_ok(
+            "/api/vector-store/documents/batch",
+            json!({"atomic": true, "documents": [
+                {"text": "Netting for the brassicas."},
+                {"text": "Compost needs turning every two weeks."},
+            ]}),
+        )
+        .await;
+    assert_eq!((atomic["added"].as_u64(), atomic["duplicates"].as_u64()), (Some(1), Some(1)));
+    server.wait_for_indexing().await;
+
+    let (_, stats) = server.get("/api/stats").await;
+    assert_eq!(stats["documents"], 4);
+    let (hits, _) = server.search("brassicas netting").await;
+    assert_eq!(hits.first().copied(), atomic["results"][0]["id"].as_i64());
+}
@@ -0,0 +1,17 @@
+//! End-to-end scenarios: the full router over a temporary data directory,
+//! driven through HTTP requests the way a client would, across the module
+//! boundaries unit tests stop at (upload → index → embed → search → chat).
+//!
+//! `harness::Harness` builds the server with a deterministic bag-of-words
+//! embedder (`test_support::WordEmbedder`), so vector and hybrid search run
+//! as they do with a real model, and `harness::MockLlm` answers chats as an
+//! OpenAI-compatible provider on an ephemeral port. A new scenario needs a
+//! `Harness::new()` and requests; `wait_for_indexing` drives the
+//! background indexing and embedding to completion.
+
+mod harness;
+
+mod chat;
+mod consolidation;
+mod ingest;
+mod sources;
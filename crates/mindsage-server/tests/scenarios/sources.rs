@@ -0,0 +1,93 @@
+//! Browser captures and LocalSend transfers, through to search.
+
+use serde_json::json;
+
+use crate::harness::Harness;
+
+fn capture(messages: &[(&str, &str, &str)]) -> serde_json::Value {
+    let messages: Vec<_> = messages
+        .iter()
+        .map(|(id, role, content)| {
+            json!({
+                "id": id, "conversationId": "conv", "role": role, "content": content,
+                "timestamp": "2025-01-01T00:00:00Z", "site": "claude",
+            })
+        })
+        .collect();
+    json!({
+        "site": "claude",
+        "conversationId": "conv",
+        "conversationUrl": "https://claude.ai/chat/conv",
+        "title": "Garden planning",
+        "messages": messages,
+        "fullConversation": true,
+    })
+}
+
+#[tokio::test]
+async fn test_browser_capture_then_reindex() {
+    let server = Harness::new();
+    let first = capture(&[
+        ("u1", "user", "Which tomatoes suit a cold greenhouse?"),
+        ("a1", "assistant", "Try Sungold and Stupice; both ripen early."),
+    ]);
+    let captured = server.post_ok("/api/browser-connector/capture", first).await;
+    assert_eq!(captured["newMessages"], 2);
+
+    let reindexed = server.post_ok("/api/browser-connector/reindex", json!({})).await;
+    assert_eq!((reindexed["total"].as_u64(), reindexed["indexed"].as_u64()), (Some(1), Some(1)));
+    server.wait_for_indexing().await;
+    let (hits, search_type) = server.search("Stupice tomatoes").await;
+    assert_eq!(search_type, "hybrid");
+    assert_eq!(hits.len(), 1);
+
+    // A later capture grows the conversation; reindexing keeps one document
+    let second = capture(&[
+        ("u1", "user", "Which tomatoes suit a cold greenhouse?"),
+        ("a1", "assistant", "Try Sungold and Stupice; both ripen early."),
+        ("u2", "user", "And a blight-resistant variety for outdoors?"),
+    ]);
+    let captured = server.post_ok("/api/browser-connector/capture", second).await;
+    assert_eq!(captured["newMessages"], 1);
+    let reindexed = server.post_ok("/api/browser-connector/reindex", json!({})).await;
+    assert_eq!(reindexed["indexed"], 1);
+    server.wait_for_indexing().await;
+
+    let (_, stats) = server.get("/api/stats").await;
+    assert_eq!(stats["documents"], 1);
+    let (hits, _) = server.search("blight-resistant outdoors").await;
+    let (_, doc) = server.get(&format!("/api/vector-store/documents/{}", hits[0])).await;
+    assert!(doc["document"]["text"].as_str().unwrap().contains("blight-resistant"));
+}
+
+#[tokio::test]
+async fn test_localsend_transfer_is_imported() {
+    let server = Harness::with_config(|config| config.localsend_default_trust = "trusted".to_string());
+    let text = "# Boiler\n\nThe boiler service is due in March; call the engineer.";
+
+    let prepared = server
+        .post_ok(
+            "/api/localsend/v2/prepare-upload",
+            json!({
+                "info": {"alias": "Pixel 8", "version": "2.0", "deviceType": "mobile", "fingerprint": "phone"},
+                "files": {"f1": {"id": "f1", "fileName": "boiler.md", "size": text.len(), "fileType": "text/markdown"}},
+            }),
+        )
+        .await;
+    let session = prepared["sessionId"].as_str().unwrap();
+    let token = prepared["files"]["f1"].as_str().unwrap();
+    let uri = format!("/api/localsend/v2/upload?sessionId={}&fileId=f1&token={}", session, token);
+    let (status, uploaded) = server.post_bytes(&uri, text.to_string()).await;
+    assert!(status.is_success(), "{}", uploaded);
+    let finished = server
+        .post_ok(&format!("/api/localsend/v2/finish?sessionId={}", session), json!({}))
+        .await;
+    assert_eq!(finished["filesReceived"], 1);
+    server.wait_for_indexing().await;
+
+    let (hits, _) = server.search("boiler service engineer").await;
+    let (_, doc) = server.get(&format!("/api/vector-store/documents/{}", hits[0])).await;
+    assert_eq!(doc["document"]["metadata"]["filename"], "boiler.md");
+    let (_, history) = server.get("/api/localsend/history").await;
+    assert!(history.to_string().contains("Pixel 8"));
+}
@@ -0,0 +1,516 @@
+//! Approximate nearest neighbor search — an IVF (inverted file) index over
+//! the normalized embedding matrix.
+//!
+//! Rows are clustered with spherical k-means; a query scores the list
+//! centroids and then only the rows in the `nprobe` closest lists. The
+//! index is built off the query path once the matrix passes a
+//! tier-dependent row count and saved to a sidecar file in the vectordb
+//! directory. Lists are keyed
+//! by chunk id on disk, so the index survives matrix reloads: rows added
+//! since the build are assigned to their nearest list, and rows that have
+//! been deleted are counted until consolidation rebuilds the index.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use mindsage_core::CapabilityTier;
+use ndarray::{Array2, ArrayView1, ArrayView2};
+
+use crate::matrix::VectorRows;
+
+/// Sidecar file name, next to `mindsage.db`.
+pub const ANN_INDEX_FILE: &str = "ann-ivf.bin";
+/// Fraction of indexed rows deleted since the build that triggers a rebuild.
+pub const REBUILD_DELETED_FRACTION: f64 = 0.2;
+/// Format marker and version of the sidecar file.
+const MAGIC: &[u8; 8] = b"MSIVF\0\0\x01";
+/// Fewest and most lists; the count otherwise tracks sqrt(rows).
+const MIN_LISTS: usize = 16;
+const MAX_LISTS: usize = 4096;
+/// Training sample size per list for k-means.
+const TRAINING_ROWS_PER_LIST: usize = 64;
+const KMEANS_ITERATIONS: usize = 8;
+/// Rows scored against the centroids per matrix multiply.
+const ASSIGN_BLOCK_ROWS: usize = 4096;
+
+/// When to use the index and how much of it to scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnnConfig {
+    pub enabled: bool,
+    /// Matrix rows at which vector search switches from brute force to the index.
+    pub threshold: usize,
+    /// Lists scanned per query; higher trades latency for recall.
+    pub nprobe: usize,
+}
+
+impl AnnConfig {
+    /// Brute force is fast enough on stronger hardware for longer, so the
+    /// threshold grows with the tier.
+    pub fn for_tier(tier: CapabilityTier) -> Self {
+        let threshold = match tier {
+            CapabilityTier::Base => 50_000,
+            CapabilityTier::Enhanced => 100_000,
+            CapabilityTier::Advanced => 200_000,
+            CapabilityTier::Full => 500_000,
+        };
+        Self {
+            enabled: true,
+            threshold,
+            nprobe: 16,
+        }
+    }
+}
+
+impl Default for AnnConfig {
+    fn default() -> Self {
+        Self::for_tier(CapabilityTier::Base)
+    }
+}
+
+/// IVF index whose lists hold row numbers of the embedding matrix.
+#[derive(Debug, Clone)]
+pub struct IvfIndex {
+    /// Embedding model the centroids were trained on.
+    model_id: String,
+    /// Normalized list centroids, shape (nlist, dim).
+    centroids: Array2<f32>,
+    lists: Vec<Vec<u32>>,
+    /// Rows indexed at build time.
+    built_rows: usize,
+    /// Indexed rows deleted since the build.
+    deleted: usize,
+}
+
+impl IvfIndex {
+    /// Cluster the rows of a normalized matrix.
+    pub fn build(matrix: &VectorRows, model_id: &str) -> Self {
+        let n = matrix.nrows();
+        let nlist = ((n as f64).sqrt() as usize).clamp(MIN_LISTS, MAX_LISTS).min(n.max(1));
+
+        // Train on an evenly strided sample, seeded with evenly spaced rows
+        let stride = (n / (nlist * TRAINING_ROWS_PER_LIST)).max(1);
+        let picks: Vec<usize> = (0..n).step_by(stride).collect();
+        let mut sample = Array2::zeros((picks.len(), matrix.dim()));
+        for (mut row, &pick) in sample.rows_mut().into_iter().zip(&picks) {
+            row.assign(&matrix.row(pick));
+        }
+        let mut centroids = Array2::zeros((nlist, matrix.dim()));
+        for i in 0..nlist {
+            centroids.row_mut(i).assign(&sample.row(i * sample.nrows() / nlist));
+        }
+
+        for _ in 0..KMEANS_ITERATIONS {
+            let assignment = nearest_lists(sample.view(), centroids.view());
+            let mut sums = Array2::<f32>::zeros(centroids.raw_dim());
+            for (row, &list) in sample.rows().into_iter().zip(&assignment) {
+                let mut sum = sums.row_mut(list);
+                sum += &row;
+            }
+            for (mut centroid, sum) in centroids.rows_mut().into_iter().zip(sums.rows()) {
+                let norm = sum.dot(&sum).sqrt();
+                // An empty list keeps its previous centroid
+                if norm > 1e-9 {
+                    centroid.assign(&(&sum / norm));
+                }
+            }
+        }
+
+        // Assign every row, scoring a block of rows per matrix multiply
+        let mut lists = vec![Vec::new(); nlist];
+        for start in (0..n).step_by(ASSIGN_BLOCK_ROWS) {
+            let block = matrix.block(start, (start + ASSIGN_BLOCK_ROWS).min(n));
+            for (offset, list) in nearest_lists(block.view(), centroids.view()).into_iter().enumerate() {
+                lists[list].push((start + offset) as u32);
+            }
+        }
+        Self {
+            model_id: model_id.to_string(),
+            centroids,
+            lists,
+            built_rows: n,
+            deleted: 0,
+        }
+    }
+
+    pub fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    pub fn nlist(&self) -> usize {
+        self.lists.len()
+    }
+
+    /// Rows currently in the index.
+    pub fn len(&self) -> usize {
+        self.lists.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Share of the rows indexed at build time that have since been deleted.
+    pub fn deleted_fraction(&self) -> f64 {
+        if self.built_rows == 0 {
+            return 0.0;
+        }
+        self.deleted as f64 / self.built_rows as f64
+    }
+
+    /// Index a row appended to the matrix.
+    pub fn add(&mut self, row: usize, embedding: ArrayView1<f32>) {
+        let list = argmax(self.centroids.dot(&embedding).view());
+        self.lists[list].push(row as u32);
+    }
+
+    /// Move a row whose embedding was replaced to the list now nearest it.
+    pub fn reassign(&mut self, row: usize, embedding: ArrayView1<f32>) {
+        for list in &mut self.lists {
+            list.retain(|&r| r as usize != row);
+        }
+        self.add(row, embedding);
+    }
+
+    /// Top `top_k` (row, similarity) pairs for a normalized query among the
+    /// rows `keep` accepts, scanning the `nprobe` lists whose centroids are
+    /// closest to it. Rows are filtered before the cut, so a filter does
+    /// not shrink the result below `top_k` while enough rows match.
+    pub fn search(
+        &self,
+        matrix: &VectorRows,
+        query: ArrayView1<f32>,
+        top_k: usize,
+        nprobe: usize,
+        keep: impl Fn(usize) -> bool,
+    ) -> Vec<(usize, f32)> {
+        let centroid_sims = self.centroids.dot(&query);
+        let mut probe: Vec<(usize, f32)> = centroid_sims.iter().copied().enumerate().collect();
+        probe.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        probe.truncate(nprobe.max(1));
+
+        let mut scored: Vec<(usize, f32)> = probe
+            .iter()
+            .flat_map(|&(list, _)| self.lists[list].iter())
+            .map(|&row| row as usize)
+            .filter(|&row| keep(row))
+            .map(|row| (row, matrix.row_dot(row, query)))
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored.truncate(top_k);
+        scored
+    }
+
+    /// Carry the index over to a reloaded matrix: `old_chunk_ids` names the
+    /// rows the lists refer to, `chunk_ids` the rows of `matrix`.
+    pub fn remap(&self, old_chunk_ids: &[i64], chunk_ids: &[i64], matrix: &VectorRows) -> Self {
+        let chunk_lists = self
+            .lists
+            .iter()
+            .map(|list| list.iter().filter_map(|&row| old_chunk_ids.get(row as usize).copied()).collect())
+            .collect();
+        Self::from_chunk_lists(
+            &self.model_id,
+            self.centroids.clone(),
+            chunk_lists,
+            self.built_rows,
+            self.deleted,
+            chunk_ids,
+            matrix,
+        )
+    }
+
+    /// Write the index with lists keyed by chunk id. Written to a temporary
+    /// file first so a crash never leaves a truncated index behind.
+    pub fn save(&self, path: &Path, chunk_ids: &[i64]) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(self.centroids.len() * 4 + self.len() * 8 + 64);
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&(self.model_id.len() as u32).to_le_bytes());
+        buf.extend_from_slice(self.model_id.as_bytes());
+        buf.extend_from_slice(&(self.centroids.ncols() as u32).to_le_bytes());
+        buf.extend_from_slice(&(self.nlist() as u32).to_le_bytes());
+        buf.extend_from_slice(&(self.built_rows as u64).to_le_bytes());
+        buf.extend_from_slice(&(self.deleted as u64).to_le_bytes());
+        for v in self.centroids.iter() {
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        for list in &self.lists {
+            buf.extend_from_slice(&(list.len() as u32).to_le_bytes());
+            for &row in list {
+                buf.extend_from_slice(&chunk_ids[row as usize].to_le_bytes());
+            }
+        }
+
+        let tmp = path.with_extension("tmp");
+        std::fs::File::create(&tmp)?.write_all(&buf)?;
+        std::fs::rename(&tmp, path)
+    }
+
+    /// Read an index saved by [`IvfIndex::save`] and attach it to `matrix`.
+    /// Returns `None` when the file is missing or was trained on another
+    /// model or dimension.
+    pub fn load(
+        path: &Path,
+        model_id: &str,
+        chunk_ids: &[i64],
+        matrix: &VectorRows,
+    ) -> io::Result<Option<Self>> {
+        let mut file = match std::fs::File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        let mut reader = Reader { buf: &buf, pos: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not an IVF index file"));
+        }
+        let model_len = reader.u32()? as usize;
+        let stored_model = String::from_utf8_lossy(reader.take(model_len)?).into_owned();
+        let dim = reader.u32()? as usize;
+        let nlist = reader.u32()? as usize;
+        if stored_model != model_id || dim != matrix.dim() {
+            return Ok(None);
+        }
+        let built_rows = reader.u64()? as usize;
+        let deleted = reader.u64()? as usize;
+
+        let values = reader
+            .take(nlist * dim * 4)?
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        let centroids =
+            Array2::from_shape_vec((nlist, dim), values).map_err(|e| invalid(&e.to_string()))?;
+        let mut chunk_lists = Vec::with_capacity(nlist);
+        for _ in 0..nlist {
+            let len = reader.u32()? as usize;
+            let ids = reader
+                .take(len * 8)?
+                .chunks_exact(8)
+                .map(|b| i64::from_le_bytes(b.try_into().unwrap_or_default()))
+                .collect();
+            chunk_lists.push(ids);
+        }
+
+        Ok(Some(Self::from_chunk_lists(
+            model_id, centroids, chunk_lists, built_rows, deleted, chunk_ids, matrix,
+        )))
+    }
+
+    /// Resolve lists of chunk ids to matrix rows. Ids no longer in the
+    /// matrix count as deleted; rows missing from every list are assigned
+    /// to their nearest list.
+    fn from_chunk_lists(
+        model_id: &str,
+        centroids: Array2<f32>,
+        chunk_lists: Vec<Vec<i64>>,
+        built_rows: usize,
+        mut deleted: usize,
+        chunk_ids: &[i64],
+        matrix: &VectorRows,
+    ) -> Self {
+        let row_of: HashMap<i64, usize> = chunk_ids.iter().enumerate().map(|(row, &id)| (id, row)).collect();
+        let mut indexed = vec![false; chunk_ids.len()];
+        let mut lists: Vec<Vec<u32>> = chunk_lists
+            .into_iter()
+            .map(|ids| {
+                ids.into_iter()
+                    .filter_map(|id| match row_of.get(&id) {
+                        Some(&row) if !indexed[row] => {
+                            indexed[row] = true;
+                            Some(row as u32)
+                        }
+                        Some(_) => None,
+                        None => {
+                            deleted += 1;
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for (row, _) in indexed.iter().enumerate().filter(|(_, &done)| !done) {
+            let list = argmax(centroids.dot(&matrix.row(row)).view());
+            lists[list].push(row as u32);
+        }
+        Self {
+            model_id: model_id.to_string(),
+            centroids,
+            lists,
+            built_rows,
+            deleted: deleted.min(built_rows),
+        }
+    }
+}
+
+/// Closest centroid for every row.
+fn nearest_lists(rows: ArrayView2<f32>, centroids: ArrayView2<f32>) -> Vec<usize> {
+    let sims = rows.dot(&centroids.t());
+    sims.rows().into_iter().map(argmax).collect()
+}
+
+fn argmax(values: ArrayView1<f32>) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .fold((0, f32::NEG_INFINITY), |best, (i, &v)| if v > best.1 { (i, v) } else { best })
+        .0
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Corrupt ANN index: {}", msg))
+}
+
+/// Bounds-checked little-endian reader over the sidecar bytes.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.buf.len());
+        let end = end.ok_or_else(|| invalid("unexpected end of file"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let b = self.take(8)?;
+        Ok(u64::from_le_bytes(b.try_into().unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::MatrixMode;
+    use ndarray::Array1;
+    use tempfile::TempDir;
+
+    fn normalized(v: ArrayView1<f32>) -> Array1<f32> {
+        &v / v.dot(&v).sqrt()
+    }
+
+    /// Normalized vectors scattered around `clusters` random centers,
+    /// the way topical embeddings group together.
+    fn clustered_vectors(rows: usize, clusters: usize, dim: usize) -> Vec<Array1<f32>> {
+        let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f32 / (1u64 << 53) as f32 * 2.0 - 1.0
+        };
+        let centers: Vec<Array1<f32>> = (0..clusters)
+            .map(|_| Array1::from_iter((0..dim).map(|_| next())))
+            .collect();
+        (0..rows)
+            .map(|i| {
+                let noisy = &centers[i % clusters] + &Array1::from_iter((0..dim).map(|_| next() * 0.6));
+                normalized(noisy.view())
+            })
+            .collect()
+    }
+
+    fn to_rows<'a>(vectors: impl IntoIterator<Item = &'a Array1<f32>>, mode: MatrixMode, dim: usize) -> VectorRows {
+        let mut rows = VectorRows::new(mode, dim);
+        for v in vectors {
+            rows.push(v.view());
+        }
+        rows
+    }
+
+    fn brute_force(matrix: &VectorRows, query: ArrayView1<f32>, k: usize) -> Vec<usize> {
+        let mut scored: Vec<(usize, f32)> = matrix.dot(query).iter().copied().enumerate().collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        scored.into_iter().take(k).map(|(i, _)| i).collect()
+    }
+
+    fn recall_at_k(index: &IvfIndex, matrix: &VectorRows, k: usize, nprobe: usize) -> f64 {
+        let queries = (0..matrix.nrows()).step_by(37);
+        let mut found = 0;
+        let mut total = 0;
+        for q in queries {
+            let query = matrix.row(q);
+            let exact = brute_force(matrix, query.view(), k);
+            let approx: Vec<usize> = index
+                .search(matrix, query.view(), k, nprobe, |_| true)
+                .into_iter()
+                .map(|(r, _)| r)
+                .collect();
+            found += exact.iter().filter(|r| approx.contains(r)).count();
+            total += k;
+        }
+        found as f64 / total as f64
+    }
+
+    #[test]
+    fn test_recall_against_brute_force() {
+        let vectors = clustered_vectors(1500, 30, 64);
+        for mode in [MatrixMode::Float, MatrixMode::Quantized] {
+            let matrix = to_rows(&vectors, mode, 64);
+            let index = IvfIndex::build(&matrix, "model");
+            assert_eq!(index.len(), 1500);
+            assert_eq!(index.nlist(), 38);
+
+            let recall = recall_at_k(&index, &matrix, 10, 8);
+            assert!(recall >= 0.9, "{:?} recall@10 = {}", mode, recall);
+            // Scanning every list is exact
+            assert_eq!(recall_at_k(&index, &matrix, 10, index.nlist()), 1.0);
+        }
+    }
+
+    #[test]
+    fn test_save_load_remaps_rows() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(ANN_INDEX_FILE);
+        let vectors = clustered_vectors(500, 10, 32);
+        let matrix = to_rows(&vectors, MatrixMode::Float, 32);
+        let chunk_ids: Vec<i64> = (1000..1500).collect();
+        let index = IvfIndex::build(&matrix, "model");
+        index.save(&path, &chunk_ids).unwrap();
+
+        // Another model's index is ignored
+        assert!(IvfIndex::load(&path, "other", &chunk_ids, &matrix).unwrap().is_none());
+
+        // Drop the first 100 chunks and append one new row
+        let kept = to_rows(vectors[100..].iter().chain([&vectors[0]]), MatrixMode::Float, 32);
+        let mut kept_ids: Vec<i64> = chunk_ids[100..].to_vec();
+        kept_ids.push(9999);
+
+        let loaded = IvfIndex::load(&path, "model", &kept_ids, &kept).unwrap().unwrap();
+        assert_eq!(loaded.len(), 401);
+        assert!((loaded.deleted_fraction() - 0.2).abs() < 1e-9);
+
+        let hits = loaded.search(&kept, vectors[0].view(), 1, loaded.nlist(), |_| true);
+        assert_eq!(hits[0].0, 400);
+
+        // Filtered rows are skipped before the top-k cut
+        let even = loaded.search(&kept, vectors[0].view(), 10, 1, |row| row % 2 == 0);
+        assert_eq!(even.len(), 10);
+        assert!(even.iter().all(|&(row, _)| row % 2 == 0));
+    }
+
+    #[test]
+    fn test_corrupt_file_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(ANN_INDEX_FILE);
+        let matrix = to_rows(&clustered_vectors(100, 4, 8), MatrixMode::Float, 8);
+        let ids: Vec<i64> = (0..100).collect();
+        IvfIndex::build(&matrix, "m").save(&path, &ids).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(IvfIndex::load(&path, "m", &ids, &matrix).is_err());
+    }
+}
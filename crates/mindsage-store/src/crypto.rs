@@ -0,0 +1,412 @@
+//! Optional field-level encryption of document and chunk text at rest.
+//!
+//! With a key configured, `documents.text`, `chunks.text` and
+//! `chunks.enriched_text` are stored as AES-256-GCM ciphertext with a fresh
+//! 96-bit nonce per value: `enc1:<key id>:<hex(nonce || ciphertext)>`.
+//! Metadata, embeddings and content hashes stay in the clear.
+//!
+//! FTS5 cannot index ciphertext, so full-text search runs over a separate
+//! representation in `chunks.search_text`: each word is replaced by a keyed
+//! hash and the tokens are sorted, keeping term frequencies for BM25 but not
+//! word order or the words themselves. Someone holding the database but not
+//! the key can still see how often a (hashed) term repeats. `EncryptedSearch::
+//! Disabled` stores no tokens at all and leaves search to vectors only.
+//!
+//! A key is either 64 hex characters, used as they are, or a passphrase
+//! stretched with Argon2id over a random salt kept in the database. Text
+//! written before salts were stored is under the passphrase's SHA-256; that
+//! key stays readable until `reencrypt` moves the text to the salted one.
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use mindsage_core::{Error, Result};
+
+/// Hex-encoded 32-byte key, or a passphrase stretched to one.
+pub const ENCRYPTION_KEY_ENV: &str = "MINDSAGE_ENCRYPTION_KEY";
+/// `env` (default) reads the active key from `MINDSAGE_ENCRYPTION_KEY`,
+/// `keyring` from the OS keyring (builds with the `keyring` feature).
+pub const KEY_SOURCE_ENV: &str = "MINDSAGE_ENCRYPTION_KEY_SOURCE";
+/// Keyring service and user the active key is stored under.
+pub const KEYRING_SERVICE: &str = "mindsage";
+pub const KEYRING_USER: &str = "encryption-key";
+/// Comma-separated keys still accepted for reading during key rotation.
+pub const PREVIOUS_KEYS_ENV: &str = "MINDSAGE_ENCRYPTION_PREVIOUS_KEYS";
+/// `off` keeps no search tokens: vector-only search.
+pub const ENCRYPTED_SEARCH_ENV: &str = "MINDSAGE_ENCRYPTED_SEARCH";
+
+const CIPHERTEXT_PREFIX: &str = "enc1:";
+const NONCE_LEN: usize = 12;
+/// Hex characters kept from each search token hash (64 bits).
+const SEARCH_TOKEN_LEN: usize = 16;
+/// Bytes of the salt passphrases are stretched with.
+const SALT_LEN: usize = 16;
+
+/// How full-text search works over encrypted text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptedSearch {
+    /// FTS indexes sorted keyed-hash tokens of each word.
+    #[default]
+    HashedTokens,
+    /// No full-text index; BM25 returns nothing and hybrid search is vector-only.
+    Disabled,
+}
+
+/// One AES-256-GCM key with its search-token key.
+pub struct FieldCipher {
+    key_id: String,
+    cipher: Aes256Gcm,
+    token_key: [u8; 32],
+}
+
+impl FieldCipher {
+    /// Build from 64 hex characters, or from a passphrase stretched with
+    /// Argon2id over `salt`. Without a salt the passphrase is hashed with
+    /// SHA-256, as before salts were stored.
+    fn new(key_material: &str, salt: Option<&[u8]>) -> Self {
+        let key: [u8; 32] = match (raw_key(key_material), salt) {
+            (Some(key), _) => key,
+            (None, Some(salt)) => {
+                let mut key = [0u8; 32];
+                Argon2::default()
+                    .hash_password_into(key_material.as_bytes(), salt, &mut key)
+                    .expect("Argon2id accepts a 16-byte salt and a 32-byte output");
+                key
+            }
+            (None, None) => Sha256::digest(key_material.as_bytes()).into(),
+        };
+        let derive = |label: &[u8]| -> [u8; 32] {
+            let mut hasher = Sha256::new();
+            hasher.update(label);
+            hasher.update(key);
+            hasher.finalize().into()
+        };
+        Self {
+            key_id: hex::encode(&derive(b"mindsage-key-id")[..4]),
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+            token_key: derive(b"mindsage-search-token"),
+        }
+    }
+
+    /// Short fingerprint stored with each ciphertext; not secret.
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn encrypt(&self, plaintext: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .expect("AES-GCM encryption of an in-memory buffer cannot fail");
+        let mut payload = nonce.to_vec();
+        payload.extend(ciphertext);
+        format!("{}{}:{}", CIPHERTEXT_PREFIX, self.key_id, hex::encode(payload))
+    }
+
+    fn decrypt(&self, payload_hex: &str) -> Result<String> {
+        let payload = hex::decode(payload_hex)
+            .ok()
+            .filter(|p| p.len() > NONCE_LEN)
+            .ok_or_else(|| Error::Encryption("Malformed ciphertext".into()))?;
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(&payload[..NONCE_LEN]), &payload[NONCE_LEN..])
+            .map_err(|_| Error::Encryption(format!("Decryption with key {} failed", self.key_id)))?;
+        String::from_utf8(plaintext).map_err(|e| Error::Encryption(e.to_string()))
+    }
+
+    fn token(&self, word: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.token_key);
+        hasher.update(word.as_bytes());
+        let mut token = hex::encode(hasher.finalize());
+        token.truncate(SEARCH_TOKEN_LEN);
+        token
+    }
+}
+
+/// Key material and search mode, as configured. `unlock` derives the keys
+/// once the database's salt is known.
+pub struct EncryptionConfig {
+    key: String,
+    previous_keys: Vec<String>,
+    search: EncryptedSearch,
+}
+
+impl EncryptionConfig {
+    pub fn new(key_material: &str) -> Self {
+        Self {
+            key: key_material.to_string(),
+            previous_keys: Vec::new(),
+            search: EncryptedSearch::default(),
+        }
+    }
+
+    /// Also accept values encrypted with an older key.
+    pub fn with_previous_key(mut self, key_material: &str) -> Self {
+        self.previous_keys.push(key_material.to_string());
+        self
+    }
+
+    pub fn with_search(mut self, search: EncryptedSearch) -> Self {
+        self.search = search;
+        self
+    }
+
+    /// Configuration from `MINDSAGE_ENCRYPTION_KEY` (or the OS keyring) and
+    /// friends; `None` when no key is set.
+    pub fn from_env() -> Result<Option<Self>> {
+        let key = match std::env::var(KEY_SOURCE_ENV).unwrap_or_default().trim() {
+            "" | "env" => std::env::var(ENCRYPTION_KEY_ENV).ok().filter(|k| !k.trim().is_empty()),
+            "keyring" => Some(keyring_key()?),
+            other => {
+                return Err(Error::Encryption(format!(
+                    "Unknown {} '{}'; expected env or keyring",
+                    KEY_SOURCE_ENV, other
+                )))
+            }
+        };
+        let Some(key) = key else {
+            return Ok(None);
+        };
+        let mut config = Self::new(&key);
+        if let Ok(previous) = std::env::var(PREVIOUS_KEYS_ENV) {
+            for material in previous.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+                config = config.with_previous_key(material);
+            }
+        }
+        if std::env::var(ENCRYPTED_SEARCH_ENV)
+            .map(|v| matches!(v.to_lowercase().as_str(), "0" | "off" | "false" | "disabled"))
+            .unwrap_or(false)
+        {
+            config.search = EncryptedSearch::Disabled;
+        }
+        Ok(Some(config))
+    }
+
+    /// Derive the keys with the database's salt. The unsalted form of each
+    /// passphrase is kept for reading text written before salts were stored;
+    /// without a salt (a read-only database from then) it is the active key.
+    pub fn unlock(&self, salt: Option<&[u8]>) -> EncryptionKeys {
+        let key = FieldCipher::new(&self.key, salt);
+        let mut previous_keys: Vec<FieldCipher> = Vec::new();
+        for material in std::iter::once(&self.key).chain(&self.previous_keys) {
+            let mut ciphers = vec![FieldCipher::new(material, salt)];
+            if salt.is_some() && raw_key(material).is_none() {
+                ciphers.push(FieldCipher::new(material, None));
+            }
+            for cipher in ciphers {
+                if cipher.key_id != key.key_id && previous_keys.iter().all(|k| k.key_id != cipher.key_id) {
+                    previous_keys.push(cipher);
+                }
+            }
+        }
+        EncryptionKeys {
+            key,
+            previous_keys,
+            search: self.search,
+        }
+    }
+}
+
+/// A random salt for stretching passphrases, stored once per database.
+pub fn new_salt() -> Vec<u8> {
+    let mut salt = vec![0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// The key of 64 hex characters, used without stretching.
+fn raw_key(key_material: &str) -> Option<[u8; 32]> {
+    hex::decode(key_material.trim()).ok()?.try_into().ok()
+}
+
+/// The active key stored in the OS keyring.
+#[cfg(feature = "keyring")]
+fn keyring_key() -> Result<String> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| Error::Encryption(format!("Cannot read the encryption key from the OS keyring: {}", e)))
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_key() -> Result<String> {
+    Err(Error::Encryption(format!(
+        "{}=keyring needs a build with the keyring feature",
+        KEY_SOURCE_ENV
+    )))
+}
+
+/// Store `key_material` in the OS keyring as the active key.
+#[cfg(feature = "keyring")]
+pub fn store_key_in_keyring(key_material: &str) -> Result<()> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .and_then(|entry| entry.set_password(key_material))
+        .map_err(|e| Error::Encryption(format!("Cannot store the encryption key in the OS keyring: {}", e)))
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn store_key_in_keyring(_key_material: &str) -> Result<()> {
+    Err(Error::Encryption("Storing the key in the OS keyring needs a build with the keyring feature".into()))
+}
+
+/// The active key, keys kept for reading during rotation, and search mode.
+pub struct EncryptionKeys {
+    key: FieldCipher,
+    previous_keys: Vec<FieldCipher>,
+    search: EncryptedSearch,
+}
+
+impl EncryptionKeys {
+    pub fn key_id(&self) -> &str {
+        self.key.key_id()
+    }
+
+    pub fn search(&self) -> EncryptedSearch {
+        self.search
+    }
+
+    /// Prefix of values encrypted with the active key, for SQL `LIKE`.
+    pub(crate) fn current_prefix(&self) -> String {
+        format!("{}{}:", CIPHERTEXT_PREFIX, self.key.key_id)
+    }
+
+    /// Encrypt with the active key.
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        self.key.encrypt(plaintext)
+    }
+
+    /// Decrypt a stored value with whichever configured key produced it.
+    /// Values without the ciphertext prefix (written before encryption was
+    /// enabled) are returned as they are.
+    pub fn decrypt(&self, stored: &str) -> Result<String> {
+        let Some(rest) = stored.strip_prefix(CIPHERTEXT_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let (key_id, payload) = rest
+            .split_once(':')
+            .ok_or_else(|| Error::Encryption("Malformed ciphertext".into()))?;
+        std::iter::once(&self.key)
+            .chain(&self.previous_keys)
+            .find(|k| k.key_id == key_id)
+            .ok_or_else(|| Error::Encryption(format!("No configured key matches key id {}", key_id)))?
+            .decrypt(payload)
+    }
+
+    /// Whether a stored value is plaintext or under a key other than the active one.
+    pub fn needs_reencrypt(&self, stored: &str) -> bool {
+        !stored.starts_with(&self.current_prefix())
+    }
+
+    /// FTS representation of `text`: sorted keyed-hash tokens, or nothing
+    /// when encrypted search is disabled.
+    pub fn search_text(&self, text: &str) -> String {
+        if self.search == EncryptedSearch::Disabled {
+            return String::new();
+        }
+        let mut tokens: Vec<String> = words(text).map(|w| self.key.token(&w)).collect();
+        tokens.sort_unstable();
+        tokens.join(" ")
+    }
+
+    /// Search tokens for a query's words under the active and previous keys,
+    /// so rows not yet re-encrypted still match during rotation.
+    pub fn query_tokens(&self, query: &str) -> Vec<String> {
+        if self.search == EncryptedSearch::Disabled {
+            return Vec::new();
+        }
+        let mut tokens = Vec::new();
+        for word in words(query) {
+            for key in std::iter::once(&self.key).chain(&self.previous_keys) {
+                let token = key.token(&word);
+                if !tokens.contains(&token) {
+                    tokens.push(token);
+                }
+            }
+        }
+        tokens
+    }
+}
+
+/// Lowercased alphanumeric words.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SALT: &[u8] = b"0123456789abcdef";
+
+    #[test]
+    fn test_round_trip_with_fresh_nonces() {
+        let config = EncryptionConfig::new("correct horse battery staple").unlock(Some(SALT));
+        let a = config.encrypt("Meeting notes: budget review");
+        let b = config.encrypt("Meeting notes: budget review");
+        assert_ne!(a, b);
+        assert!(!a.contains("budget"));
+        assert_eq!(config.decrypt(&a).unwrap(), "Meeting notes: budget review");
+        assert_eq!(config.decrypt("plain legacy text").unwrap(), "plain legacy text");
+        assert!(config.needs_reencrypt("plain legacy text"));
+        assert!(!config.needs_reencrypt(&a));
+
+        let hex_key = "00".repeat(32);
+        let raw = EncryptionConfig::new(&hex_key);
+        assert_eq!(raw.unlock(Some(SALT)).key_id(), raw.unlock(None).key_id());
+    }
+
+    #[test]
+    fn test_wrong_key_and_rotation() {
+        let old = EncryptionConfig::new("old key").unlock(Some(SALT));
+        let stored = old.encrypt("secret");
+
+        let wrong = EncryptionConfig::new("other key").unlock(Some(SALT));
+        assert!(matches!(wrong.decrypt(&stored), Err(Error::Encryption(_))));
+
+        let rotated = EncryptionConfig::new("new key").with_previous_key("old key").unlock(Some(SALT));
+        assert_eq!(rotated.decrypt(&stored).unwrap(), "secret");
+        assert!(rotated.needs_reencrypt(&stored));
+        // Both keys, salted and not
+        assert_eq!(rotated.query_tokens("secret").len(), 4);
+    }
+
+    #[test]
+    fn test_passphrase_is_salted_and_unsalted_text_stays_readable() {
+        let config = EncryptionConfig::new("passphrase");
+        let unsalted = config.unlock(None);
+        let salted = config.unlock(Some(SALT));
+        let other_salt = config.unlock(Some(b"fedcba9876543210"));
+        assert_ne!(salted.key_id(), unsalted.key_id());
+        assert_ne!(salted.key_id(), other_salt.key_id());
+
+        // Text from before salts were stored decrypts, and is due for re-encryption
+        let legacy = unsalted.encrypt("old note");
+        assert_eq!(salted.decrypt(&legacy).unwrap(), "old note");
+        assert!(salted.needs_reencrypt(&legacy));
+        assert!(other_salt.decrypt(&salted.encrypt("new note")).is_err());
+    }
+
+    #[test]
+    fn test_search_tokens_hide_words_and_order() {
+        let config = EncryptionConfig::new("k").unlock(Some(SALT));
+        let tokens = config.search_text("Rust rust, async!");
+        assert!(!tokens.contains("rust"));
+        let parts: Vec<&str> = tokens.split(' ').collect();
+        assert_eq!(parts.len(), 3);
+        assert!(parts.windows(2).all(|w| w[0] <= w[1]));
+        assert!(parts.contains(&config.query_tokens("RUST")[0].as_str()));
+
+        let disabled = EncryptionConfig::new("k").with_search(EncryptedSearch::Disabled).unlock(Some(SALT));
+        assert_eq!(disabled.search_text("Rust"), "");
+        assert!(disabled.query_tokens("Rust").is_empty());
+    }
+}
@@ -0,0 +1,227 @@
+//! Result diversity — Maximal Marginal Relevance over fused search hits.
+//!
+//! Hits are picked one at a time by `λ · relevance − (1 − λ) · redundancy`,
+//! where relevance is the hit's score scaled to the best score and
+//! redundancy its highest similarity to a hit already picked: cosine of the
+//! chunk embeddings when both have one, token Jaccard of the texts
+//! otherwise. `λ = 1` keeps the relevance order. A section and one of its
+//! own paragraphs are never both picked: searches over every chunk level
+//! would otherwise return the same text twice.
+
+use std::collections::{HashMap, HashSet};
+
+use ndarray::Array1;
+
+use crate::types::SearchHit;
+
+/// How results are diversified.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Diversity {
+    /// Relevance weight in `[0, 1]`; lower values favour variety.
+    pub lambda: f64,
+    /// Most hits kept from one document.
+    pub max_per_doc: Option<usize>,
+}
+
+impl Diversity {
+    /// Pure relevance order, nothing capped.
+    pub const RELEVANCE: Self = Self {
+        lambda: 1.0,
+        max_per_doc: None,
+    };
+
+    pub fn new(lambda: f64, max_per_doc: Option<usize>) -> Self {
+        Self {
+            lambda: lambda.clamp(0.0, 1.0),
+            max_per_doc,
+        }
+    }
+}
+
+/// Select up to `top_k` of `hits` by MMR. `embeddings` maps chunk ids to
+/// their embeddings and may be empty. Selected hits keep their scores and
+/// are returned in selection order; a hit whose parent or child chunk was
+/// picked before it is dropped.
+pub fn mmr_select(
+    hits: Vec<SearchHit>,
+    embeddings: &HashMap<i64, Array1<f32>>,
+    diversity: Diversity,
+    top_k: usize,
+) -> Vec<SearchHit> {
+    let max_score = hits.iter().map(|h| h.score).fold(f64::MIN, f64::max);
+    let relevance: Vec<f64> = hits
+        .iter()
+        .map(|h| if max_score > 0.0 { h.score / max_score } else { 0.0 })
+        .collect();
+    let tokens: Vec<HashSet<String>> = hits.iter().map(|h| token_set(&h.text)).collect();
+
+    let mut remaining: Vec<usize> = (0..hits.len()).collect();
+    // Highest similarity of each candidate to the selected hits
+    let mut redundancy = vec![0.0f64; hits.len()];
+    let mut per_doc: HashMap<i64, usize> = HashMap::new();
+    // Chunks picked and the parents of chunks picked
+    let mut picked_ids: HashSet<i64> = HashSet::new();
+    let mut picked_parents: HashSet<i64> = HashSet::new();
+    let mut selected: Vec<usize> = Vec::new();
+
+    while selected.len() < top_k {
+        if let Some(cap) = diversity.max_per_doc {
+            remaining.retain(|&i| per_doc.get(&hits[i].doc_id).copied().unwrap_or(0) < cap);
+        }
+        remaining.retain(|&i| {
+            !picked_parents.contains(&hits[i].chunk_id)
+                && !hits[i].parent_chunk_id.is_some_and(|p| picked_ids.contains(&p))
+        });
+        // Ties go to the earlier (higher-ranked) hit
+        let best = remaining.iter().enumerate().fold(None::<(usize, f64)>, |best, (pos, &i)| {
+            let score = diversity.lambda * relevance[i] - (1.0 - diversity.lambda) * redundancy[i];
+            match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((pos, score)),
+            }
+        });
+        let Some((pos, _)) = best else { break };
+        let picked = remaining.remove(pos);
+        *per_doc.entry(hits[picked].doc_id).or_insert(0) += 1;
+        picked_ids.insert(hits[picked].chunk_id);
+        picked_parents.extend(hits[picked].parent_chunk_id);
+        selected.push(picked);
+
+        if diversity.lambda < 1.0 {
+            for &i in &remaining {
+                let sim = similarity(&hits[i], &hits[picked], &tokens[i], &tokens[picked], embeddings);
+                redundancy[i] = redundancy[i].max(sim);
+            }
+        }
+    }
+
+    let mut slots: Vec<Option<SearchHit>> = hits.into_iter().map(Some).collect();
+    selected.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+fn similarity(
+    a: &SearchHit,
+    b: &SearchHit,
+    a_tokens: &HashSet<String>,
+    b_tokens: &HashSet<String>,
+    embeddings: &HashMap<i64, Array1<f32>>,
+) -> f64 {
+    if let (Some(ea), Some(eb)) = (embeddings.get(&a.chunk_id), embeddings.get(&b.chunk_id)) {
+        let norms = ea.dot(ea).sqrt() * eb.dot(eb).sqrt();
+        if ea.len() == eb.len() && norms > 1e-9 {
+            return (ea.dot(eb) / norms) as f64;
+        }
+    }
+    let union = a_tokens.union(b_tokens).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a_tokens.intersection(b_tokens).count() as f64 / union as f64
+}
+
+fn token_set(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(chunk_id: i64, doc_id: i64, text: &str, score: f64) -> SearchHit {
+        SearchHit {
+            chunk_id,
+            doc_id,
+            text: text.to_string(),
+            score,
+            level: 1,
+            metadata: None,
+            enriched_text: None,
+            parent_chunk_id: None,
+            chunk_index: chunk_id as i32,
+            char_start: None,
+            char_end: None,
+        }
+    }
+
+    /// Four near-copies of one paragraph, then two distinct chunks.
+    fn redundant_hits() -> Vec<SearchHit> {
+        let copy = "the heat pump defrost cycle runs every ninety minutes in winter";
+        vec![
+            hit(1, 1, copy, 1.0),
+            hit(2, 1, &format!("{} mode", copy), 0.98),
+            hit(3, 2, &format!("{} weather", copy), 0.96),
+            hit(4, 1, &format!("so {}", copy), 0.95),
+            hit(5, 3, "reset the heat pump by holding the defrost button", 0.80),
+            hit(6, 4, "winter tariffs make the heat pump cheaper at night", 0.70),
+        ]
+    }
+
+    fn distinct_texts(hits: &[SearchHit]) -> usize {
+        hits.iter().filter(|h| h.chunk_id >= 5).count()
+    }
+
+    #[test]
+    fn test_lower_lambda_gives_more_diverse_results() {
+        let embeddings = HashMap::new();
+        let pick = |lambda| mmr_select(redundant_hits(), &embeddings, Diversity::new(lambda, None), 3);
+
+        let relevance = pick(1.0);
+        let ids: Vec<i64> = relevance.iter().map(|h| h.chunk_id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+
+        let counts: Vec<usize> = [1.0, 0.7, 0.3].iter().map(|&l| distinct_texts(&pick(l))).collect();
+        assert!(counts.windows(2).all(|w| w[0] <= w[1]), "{:?}", counts);
+        assert_eq!(counts[0], 0);
+        assert_eq!(counts[2], 2);
+        // The best hit always comes first
+        assert_eq!(pick(0.3)[0].chunk_id, 1);
+    }
+
+    #[test]
+    fn test_max_per_doc_and_embedding_similarity() {
+        // One hit per document reproduces the old per-document dedup
+        let capped = mmr_select(redundant_hits(), &HashMap::new(), Diversity::new(1.0, Some(1)), 10);
+        let docs: Vec<i64> = capped.iter().map(|h| h.doc_id).collect();
+        assert_eq!(docs, vec![1, 2, 3, 4]);
+
+        // Embeddings take precedence over text overlap: chunks 1 and 5
+        // point the same way, so 5 is redundant once 1 is picked
+        let mut embeddings = HashMap::new();
+        embeddings.insert(1, Array1::from(vec![1.0f32, 0.0]));
+        embeddings.insert(5, Array1::from(vec![1.0f32, 0.0]));
+        embeddings.insert(6, Array1::from(vec![0.0f32, 1.0]));
+        let hits = vec![
+            hit(1, 1, "alpha", 1.0),
+            hit(5, 2, "beta", 0.9),
+            hit(6, 3, "gamma", 0.8),
+        ];
+        let picked = mmr_select(hits, &embeddings, Diversity::new(0.5, None), 2);
+        let ids: Vec<i64> = picked.iter().map(|h| h.chunk_id).collect();
+        assert_eq!(ids, vec![1, 6]);
+    }
+
+    #[test]
+    fn test_section_and_own_paragraph_are_not_both_picked() {
+        // Section 10 holds paragraph 11; paragraph 12 is in another section
+        let mut section = hit(10, 1, "garden plan: water the tomatoes daily", 1.0);
+        section.level = 0;
+        let mut own = hit(11, 1, "water the tomatoes daily", 0.9);
+        own.parent_chunk_id = Some(10);
+        let mut other = hit(12, 1, "tomatoes need stakes", 0.8);
+        other.parent_chunk_id = Some(20);
+        let picked = mmr_select(vec![section, own, other], &HashMap::new(), Diversity::RELEVANCE, 3);
+        let ids: Vec<i64> = picked.iter().map(|h| h.chunk_id).collect();
+        assert_eq!(ids, vec![10, 12]);
+
+        // The other way round when the paragraph ranks first
+        let mut own = hit(11, 1, "water the tomatoes daily", 1.0);
+        own.parent_chunk_id = Some(10);
+        let mut section = hit(10, 1, "garden plan: water the tomatoes daily", 0.9);
+        section.level = 0;
+        let picked = mmr_select(vec![own, section], &HashMap::new(), Diversity::RELEVANCE, 3);
+        assert_eq!(picked.iter().map(|h| h.chunk_id).collect::<Vec<_>>(), vec![11]);
+    }
+}
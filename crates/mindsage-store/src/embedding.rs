@@ -1,7 +1,80 @@
-//! int8 quantization/dequantization — matches Python's quantize_uint8/dequantize_uint8.
+//! int8 quantization/dequantization.
+//!
+//! Three stored formats, selected by `chunk_embeddings.quant_version`:
+//! - `Legacy` (0): uint8 with one scale/offset per vector — matches Python's
+//!   quantize_uint8/dequantize_uint8. Rows written before versioning.
+//! - `Int8` (1): symmetric int8 with one scale per vector; the `offset_val`
+//!   column holds the original L2 norm.
+//! - `BlockInt8` (2): symmetric int8 with one f32 scale per block of
+//!   [`QUANT_BLOCK_SIZE`] dimensions stored ahead of the values in the blob;
+//!   `offset_val` holds the original L2 norm.
+//!
+//! The versioned formats rescale dequantized vectors to the stored norm.
 
 use ndarray::Array1;
 
+/// Dimensions sharing one scale in the `BlockInt8` format.
+pub const QUANT_BLOCK_SIZE: usize = 32;
+
+/// Stored quantization format of an embedding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuantScheme {
+    Legacy,
+    #[default]
+    Int8,
+    BlockInt8,
+}
+
+impl QuantScheme {
+    /// Value stored in `chunk_embeddings.quant_version`.
+    pub fn version(self) -> i64 {
+        match self {
+            QuantScheme::Legacy => 0,
+            QuantScheme::Int8 => 1,
+            QuantScheme::BlockInt8 => 2,
+        }
+    }
+
+    /// Blob length of a `dim`-dimensional embedding in this format.
+    pub fn encoded_len(self, dim: usize) -> usize {
+        match self {
+            QuantScheme::Legacy | QuantScheme::Int8 => dim,
+            QuantScheme::BlockInt8 => dim + 4 * dim.div_ceil(QUANT_BLOCK_SIZE),
+        }
+    }
+
+    pub fn from_version(version: i64) -> Option<Self> {
+        match version {
+            0 => Some(QuantScheme::Legacy),
+            1 => Some(QuantScheme::Int8),
+            2 => Some(QuantScheme::BlockInt8),
+            _ => None,
+        }
+    }
+}
+
+/// Quantize with `scheme`. Returns (bytes, scale, offset) as stored in the
+/// `embedding`, `scale` and `offset_val` columns.
+pub fn quantize(embedding: &Array1<f32>, scheme: QuantScheme) -> (Vec<u8>, f32, f32) {
+    match scheme {
+        QuantScheme::Legacy => quantize_uint8(embedding),
+        QuantScheme::Int8 => quantize_int8(embedding),
+        QuantScheme::BlockInt8 => {
+            let (bytes, norm) = quantize_block_int8(embedding);
+            (bytes, 0.0, norm)
+        }
+    }
+}
+
+/// Decode a stored embedding written by [`quantize`] with `scheme`.
+pub fn dequantize(bytes: &[u8], scale: f32, offset: f32, scheme: QuantScheme) -> Array1<f32> {
+    match scheme {
+        QuantScheme::Legacy => dequantize_uint8(bytes, scale, offset),
+        QuantScheme::Int8 => dequantize_int8(bytes, scale, offset),
+        QuantScheme::BlockInt8 => dequantize_block_int8(bytes, offset),
+    }
+}
+
 /// Quantize a float32 embedding to uint8 bytes with scale and offset.
 ///
 /// Maps [min, max] → [0, 255] linearly.
@@ -35,6 +108,89 @@ pub fn dequantize_uint8(bytes: &[u8], scale: f32, offset: f32) -> Array1<f32> {
     Array1::from_iter(bytes.iter().map(|&b| b as f32 * scale + offset))
 }
 
+/// Symmetric int8 quantization: `round(v / scale)` with
+/// `scale = max|v| / 127`. Returns (bytes, scale, norm).
+pub fn quantize_int8(embedding: &Array1<f32>) -> (Vec<u8>, f32, f32) {
+    let norm = embedding.dot(embedding).sqrt();
+    let max_abs = embedding.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+    if max_abs < 1e-12 {
+        return (vec![0u8; embedding.len()], 0.0, norm);
+    }
+    let scale = max_abs / 127.0;
+    let bytes = embedding.iter().map(|&v| to_i8_byte(v / scale)).collect();
+    (bytes, scale, norm)
+}
+
+/// Decode [`quantize_int8`] output and rescale it to `norm`.
+pub fn dequantize_int8(bytes: &[u8], scale: f32, norm: f32) -> Array1<f32> {
+    let values = Array1::from_iter(bytes.iter().map(|&b| b as i8 as f32 * scale));
+    rescale_to_norm(values, norm)
+}
+
+/// Symmetric int8 quantization with one scale per [`QUANT_BLOCK_SIZE`]
+/// dimensions. The blob is the block scales (f32 LE) followed by the
+/// values. Returns (bytes, norm).
+pub fn quantize_block_int8(embedding: &Array1<f32>) -> (Vec<u8>, f32) {
+    let norm = embedding.dot(embedding).sqrt();
+    let values = embedding.as_slice().map(|s| s.to_vec()).unwrap_or_else(|| embedding.to_vec());
+    let mut scales = Vec::with_capacity(values.len().div_ceil(QUANT_BLOCK_SIZE) * 4);
+    let mut quantized = Vec::with_capacity(values.len());
+    for block in values.chunks(QUANT_BLOCK_SIZE) {
+        let max_abs = block.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+        let scale = if max_abs < 1e-12 { 0.0 } else { max_abs / 127.0 };
+        scales.extend_from_slice(&scale.to_le_bytes());
+        quantized.extend(block.iter().map(|&v| if scale == 0.0 { 0 } else { to_i8_byte(v / scale) }));
+    }
+    scales.extend(quantized);
+    (scales, norm)
+}
+
+/// Decode [`quantize_block_int8`] output and rescale it to `norm`.
+pub fn dequantize_block_int8(bytes: &[u8], norm: f32) -> Array1<f32> {
+    // len = 4 * blocks + dim, and blocks = ceil(dim / BLOCK), so
+    // blocks = ceil(len / (4 + BLOCK))
+    let blocks = bytes.len().div_ceil(4 + QUANT_BLOCK_SIZE);
+    let (scale_bytes, values) = bytes.split_at((blocks * 4).min(bytes.len()));
+    let scales: Vec<f32> = scale_bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    let decoded = Array1::from_iter(
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| b as i8 as f32 * scales.get(i / QUANT_BLOCK_SIZE).copied().unwrap_or(0.0)),
+    );
+    rescale_to_norm(decoded, norm)
+}
+
+fn to_i8_byte(v: f32) -> u8 {
+    v.round().clamp(-127.0, 127.0) as i8 as u8
+}
+
+/// Scale `values` to L2 norm `norm` (unchanged when either is ~0).
+fn rescale_to_norm(values: Array1<f32>, norm: f32) -> Array1<f32> {
+    let current = values.dot(&values).sqrt();
+    if current < 1e-12 || norm < 1e-12 {
+        return values;
+    }
+    values * (norm / current)
+}
+
+/// Encode a float32 vector as little-endian bytes (used for document centroids).
+pub fn f32_to_bytes(vector: &Array1<f32>) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Decode little-endian float32 bytes written by [`f32_to_bytes`].
+pub fn bytes_to_f32(bytes: &[u8]) -> Array1<f32> {
+    Array1::from_iter(
+        bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -51,6 +207,84 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_f32_bytes_roundtrip() {
+        let original = array![0.25, -1.5, 3.0];
+        assert_eq!(bytes_to_f32(&f32_to_bytes(&original)), original);
+    }
+
+    /// Deterministic pseudo-random vectors (xorshift), roughly like
+    /// sentence embeddings: mixed signs, a few larger components.
+    fn random_vectors(count: usize, dim: usize) -> Vec<Array1<f32>> {
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f32 / (1u64 << 53) as f32 * 2.0 - 1.0
+        };
+        (0..count)
+            .map(|_| {
+                Array1::from_iter((0..dim).map(|i| {
+                    let v = next();
+                    if i % 37 == 0 { v * 4.0 } else { v * 0.2 }
+                }))
+            })
+            .collect()
+    }
+
+    fn cosine(a: &Array1<f32>, b: &Array1<f32>) -> f32 {
+        a.dot(b) / (a.dot(a).sqrt() * b.dot(b).sqrt())
+    }
+
+    /// Largest |cos(q, v) - cos(q, dequantized v)| over all pairs.
+    fn max_cosine_error(scheme: QuantScheme) -> f32 {
+        let vectors = random_vectors(64, 384);
+        let queries = random_vectors(16, 384);
+        let mut max_err = 0.0f32;
+        for v in &vectors {
+            let (bytes, scale, offset) = quantize(v, scheme);
+            let restored = dequantize(&bytes, scale, offset, scheme);
+            for q in &queries {
+                max_err = max_err.max((cosine(q, v) - cosine(q, &restored)).abs());
+            }
+        }
+        max_err
+    }
+
+    #[test]
+    fn test_cosine_error_against_float() {
+        let legacy = max_cosine_error(QuantScheme::Legacy);
+        let int8 = max_cosine_error(QuantScheme::Int8);
+        let block = max_cosine_error(QuantScheme::BlockInt8);
+        assert!(int8 < 0.01, "int8 max cosine error {}", int8);
+        assert!(block < 0.005, "block int8 max cosine error {}", block);
+        assert!(block < legacy.min(int8), "block {} vs legacy {} / int8 {}", block, legacy, int8);
+    }
+
+    #[test]
+    fn test_versioned_schemes_restore_norm() {
+        let v: Array1<f32> = array![3.0, -4.0, 0.5, 0.0, 12.0];
+        let norm = v.dot(&v).sqrt();
+        for scheme in [QuantScheme::Int8, QuantScheme::BlockInt8] {
+            let (bytes, scale, offset) = quantize(&v, scheme);
+            let restored = dequantize(&bytes, scale, offset, scheme);
+            assert_eq!(restored.len(), v.len());
+            assert!((restored.dot(&restored).sqrt() - norm).abs() < 1e-4);
+            assert_eq!(QuantScheme::from_version(scheme.version()), Some(scheme));
+        }
+    }
+
+    #[test]
+    fn test_block_layout_for_partial_blocks() {
+        for dim in [1usize, 31, 32, 33, 384, 385] {
+            let v = Array1::from_iter((0..dim).map(|i| (i as f32 * 0.37).sin()));
+            let (bytes, norm) = quantize_block_int8(&v);
+            assert_eq!(bytes.len(), dim + 4 * dim.div_ceil(QUANT_BLOCK_SIZE));
+            assert_eq!(dequantize_block_int8(&bytes, norm).len(), dim);
+        }
+    }
+
     #[test]
     fn test_constant_vector() {
         let original = array![0.5, 0.5, 0.5];
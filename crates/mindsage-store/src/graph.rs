@@ -3,7 +3,6 @@
 
 use petgraph::graph::DiGraph;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 /// A node in the knowledge graph.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,14 +27,12 @@ pub struct GraphEdge {
 /// In-memory knowledge graph built from document metadata.
 pub struct GraphBackend {
     graph: DiGraph<GraphNode, GraphEdge>,
-    node_index: HashMap<String, petgraph::graph::NodeIndex>,
 }
 
 impl GraphBackend {
     pub fn new() -> Self {
         Self {
             graph: DiGraph::new(),
-            node_index: HashMap::new(),
         }
     }
 
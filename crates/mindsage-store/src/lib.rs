@@ -1,10 +1,18 @@
 //! MindSage Store — SQLite FTS5 + int8 vector search + knowledge graph.
 
+pub mod ann;
+pub mod crypto;
+pub mod diversity;
 pub mod embedding;
 pub mod graph;
+pub mod matrix;
+pub mod metadata;
+pub mod postprocess;
 pub mod schema;
 pub mod sqlite;
 pub mod types;
 
-pub use sqlite::SqliteStore;
+pub use diversity::{mmr_select, Diversity};
+pub use postprocess::{boost_query_terms, post_process};
+pub use sqlite::{hash_query, timed_stage, DocumentIter, SqliteStore};
 pub use types::*;
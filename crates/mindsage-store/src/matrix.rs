@@ -0,0 +1,294 @@
+//! In-memory storage for the normalized embedding matrix.
+//!
+//! Float mode keeps f32 rows (N × dim × 4 bytes). Quantized mode keeps each
+//! row as symmetric int8 with one f32 scale — a quarter of the size — and
+//! dequantizes on the fly while scoring. It is the Base tier default, where
+//! a 200k-chunk float matrix alone would exceed the memory budget. Rows are
+//! unit-normalized before they are stored, so one scale per row loses little.
+
+use mindsage_core::CapabilityTier;
+use ndarray::{Array1, Array2, ArrayView1, ArrayView2};
+use serde::{Deserialize, Serialize};
+
+/// How the embedding matrix is held in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum MatrixMode {
+    #[default]
+    Float,
+    Quantized,
+}
+
+impl MatrixMode {
+    pub fn for_tier(tier: CapabilityTier) -> Self {
+        match tier {
+            CapabilityTier::Base => MatrixMode::Quantized,
+            _ => MatrixMode::Float,
+        }
+    }
+}
+
+/// Row-major matrix of normalized embeddings in either mode.
+#[derive(Debug, Clone)]
+pub struct VectorRows {
+    dim: usize,
+    storage: Storage,
+}
+
+#[derive(Debug, Clone)]
+enum Storage {
+    Float(Vec<f32>),
+    Quantized { values: Vec<i8>, scales: Vec<f32> },
+}
+
+impl VectorRows {
+    pub fn new(mode: MatrixMode, dim: usize) -> Self {
+        let storage = match mode {
+            MatrixMode::Float => Storage::Float(Vec::new()),
+            MatrixMode::Quantized => Storage::Quantized {
+                values: Vec::new(),
+                scales: Vec::new(),
+            },
+        };
+        Self { dim, storage }
+    }
+
+    pub fn mode(&self) -> MatrixMode {
+        match self.storage {
+            Storage::Float(_) => MatrixMode::Float,
+            Storage::Quantized { .. } => MatrixMode::Quantized,
+        }
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn nrows(&self) -> usize {
+        match &self.storage {
+            Storage::Float(values) => values.len() / self.dim.max(1),
+            Storage::Quantized { scales, .. } => scales.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nrows() == 0
+    }
+
+    /// Heap bytes held by the rows.
+    pub fn memory_bytes(&self) -> usize {
+        match &self.storage {
+            Storage::Float(values) => values.len() * 4,
+            Storage::Quantized { values, scales } => values.len() + scales.len() * 4,
+        }
+    }
+
+    /// Append a row; it must already be normalized and `dim` long.
+    pub fn push(&mut self, row: ArrayView1<f32>) {
+        match &mut self.storage {
+            Storage::Float(values) => values.extend(row.iter()),
+            Storage::Quantized { values, scales } => {
+                let scale = row_scale(row);
+                values.extend(row.iter().map(|&v| quantize_value(v, scale)));
+                scales.push(scale);
+            }
+        }
+    }
+
+    /// Overwrite row `i`; the new row must be normalized and `dim` long.
+    pub fn set_row(&mut self, i: usize, row: ArrayView1<f32>) {
+        let range = i * self.dim..(i + 1) * self.dim;
+        match &mut self.storage {
+            Storage::Float(values) => {
+                for (slot, v) in values[range].iter_mut().zip(row.iter()) {
+                    *slot = *v;
+                }
+            }
+            Storage::Quantized { values, scales } => {
+                let scale = row_scale(row);
+                for (slot, &v) in values[range].iter_mut().zip(row.iter()) {
+                    *slot = quantize_value(v, scale);
+                }
+                scales[i] = scale;
+            }
+        }
+    }
+
+    /// Keep the rows `i` where `keep[i]` is true, in order.
+    pub fn retain(&mut self, keep: &[bool]) {
+        let dim = self.dim.max(1);
+        match &mut self.storage {
+            Storage::Float(values) => retain_rows(values, dim, keep),
+            Storage::Quantized { values, scales } => {
+                retain_rows(values, dim, keep);
+                retain_rows(scales, 1, keep);
+            }
+        }
+    }
+
+    /// Row `i` as floats (dequantized in quantized mode).
+    pub fn row(&self, i: usize) -> Array1<f32> {
+        let range = i * self.dim..(i + 1) * self.dim;
+        match &self.storage {
+            Storage::Float(values) => Array1::from(values[range].to_vec()),
+            Storage::Quantized { values, scales } => {
+                values[range].iter().map(|&v| v as f32 * scales[i]).collect()
+            }
+        }
+    }
+
+    /// Dot product of row `i` with `query`.
+    pub fn row_dot(&self, i: usize, query: ArrayView1<f32>) -> f32 {
+        let range = i * self.dim..(i + 1) * self.dim;
+        match &self.storage {
+            Storage::Float(values) => ArrayView1::from(&values[range]).dot(&query),
+            Storage::Quantized { values, scales } => {
+                let sum: f32 = values[range].iter().zip(query.iter()).map(|(&v, q)| v as f32 * q).sum();
+                sum * scales[i]
+            }
+        }
+    }
+
+    /// Dot product of every row with `query`: (N, dim) @ (dim,) → (N,).
+    pub fn dot(&self, query: ArrayView1<f32>) -> Array1<f32> {
+        self.dot_rows(0, self.nrows(), query)
+    }
+
+    /// Dot product of rows `start..end` with `query`. Float rows go through
+    /// ndarray's matrix-vector product; int8 rows are dequantized one at a
+    /// time.
+    pub fn dot_rows(&self, start: usize, end: usize, query: ArrayView1<f32>) -> Array1<f32> {
+        match &self.storage {
+            Storage::Float(values) => {
+                ArrayView2::from_shape((end - start, self.dim), &values[start * self.dim..end * self.dim])
+                    .expect("float storage holds whole rows")
+                    .dot(&query)
+            }
+            Storage::Quantized { .. } => (start..end).map(|i| self.row_dot(i, query)).collect(),
+        }
+    }
+
+    /// Rows `start..end` as a float matrix.
+    pub fn block(&self, start: usize, end: usize) -> Array2<f32> {
+        let mut block = Array2::zeros((end - start, self.dim));
+        for (r, mut row) in block.rows_mut().into_iter().enumerate() {
+            row.assign(&self.row(start + r));
+        }
+        block
+    }
+
+    /// Copy of the rows in another mode.
+    pub fn to_mode(&self, mode: MatrixMode) -> Self {
+        if mode == self.mode() {
+            return self.clone();
+        }
+        let mut converted = Self::new(mode, self.dim);
+        for i in 0..self.nrows() {
+            converted.push(self.row(i).view());
+        }
+        converted
+    }
+}
+
+/// Keep the `width`-long rows of `values` where `keep` is true.
+fn retain_rows<T>(values: &mut Vec<T>, width: usize, keep: &[bool]) {
+    let mut i = 0;
+    values.retain(|_| {
+        i += 1;
+        keep[(i - 1) / width]
+    });
+}
+
+/// Int8 scale of a row: its largest magnitude maps to 127.
+fn row_scale(row: ArrayView1<f32>) -> f32 {
+    row.iter().fold(0.0f32, |m, v| m.max(v.abs())) / 127.0
+}
+
+fn quantize_value(v: f32, scale: f32) -> i8 {
+    let inv = if scale > 0.0 { 1.0 / scale } else { 0.0 };
+    (v * inv).round().clamp(-127.0, 127.0) as i8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_rows(count: usize, dim: usize, seed: u64) -> Vec<Array1<f32>> {
+        let mut state = seed;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f32 / (1u64 << 53) as f32 * 2.0 - 1.0
+        };
+        (0..count)
+            .map(|_| {
+                let v: Array1<f32> = (0..dim).map(|_| next()).collect();
+                let norm = v.dot(&v).sqrt();
+                v / norm
+            })
+            .collect()
+    }
+
+    fn top_k(scores: &Array1<f32>, k: usize) -> Vec<usize> {
+        let mut indexed: Vec<(usize, f32)> = scores.iter().copied().enumerate().collect();
+        indexed.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        indexed.into_iter().take(k).map(|(i, _)| i).collect()
+    }
+
+    #[test]
+    fn test_quantized_top_k_overlaps_float() {
+        let rows = random_rows(1000, 384, 0x9E37_79B9_7F4A_7C15);
+        let mut float = VectorRows::new(MatrixMode::Float, 384);
+        for row in &rows {
+            float.push(row.view());
+        }
+        let quantized = float.to_mode(MatrixMode::Quantized);
+        assert_eq!(quantized.nrows(), 1000);
+        assert!(quantized.memory_bytes() * 3 < float.memory_bytes());
+
+        let queries = random_rows(30, 384, 0xD1B5_4A32_D192_ED03);
+        let mut overlap = 0;
+        for query in &queries {
+            let exact = top_k(&float.dot(query.view()), 10);
+            let approx = top_k(&quantized.dot(query.view()), 10);
+            overlap += exact.iter().filter(|i| approx.contains(i)).count();
+        }
+        let ratio = overlap as f64 / (queries.len() * 10) as f64;
+        assert!(ratio >= 0.9, "top-10 overlap = {}", ratio);
+    }
+
+    #[test]
+    fn test_row_access_and_round_trip() {
+        let rows = random_rows(3, 16, 42);
+        let mut quantized = VectorRows::new(MatrixMode::Quantized, 16);
+        for row in &rows {
+            quantized.push(row.view());
+        }
+        for (i, row) in rows.iter().enumerate() {
+            assert!((quantized.row_dot(i, row.view()) - 1.0).abs() < 0.01);
+        }
+        for rows in [&quantized, &quantized.to_mode(MatrixMode::Float)] {
+            let scores = rows.dot_rows(1, 3, rows.row(0).view());
+            assert_eq!(scores.len(), 2);
+            assert!((scores[1] - rows.row_dot(2, rows.row(0).view())).abs() < 1e-6);
+        }
+        let block = quantized.block(1, 3);
+        assert_eq!(block.nrows(), 2);
+        assert_eq!(block.row(0), quantized.row(1));
+
+        let mut float = quantized.to_mode(MatrixMode::Float);
+        assert_eq!(float.mode(), MatrixMode::Float);
+        assert_eq!(float.row(2), quantized.row(2));
+
+        let last = quantized.row(2);
+        quantized.retain(&[true, false, true]);
+        float.retain(&[false, false, true]);
+        assert_eq!(quantized.nrows(), 2);
+        assert_eq!(quantized.row(1), last);
+        assert_eq!(float.nrows(), 1);
+        assert_eq!(float.row(0), last);
+        assert_eq!(VectorRows::new(MatrixMode::Float, 16).nrows(), 0);
+    }
+}
@@ -0,0 +1,260 @@
+//! Document metadata normalization — one shape for the keys every
+//! consumer reads.
+//!
+//! Metadata stays freeform JSON, but the known keys are canonicalized on
+//! write: a key matching one case-insensitively is renamed to it
+//! (`Source` → `source`), `topics` is always an array of strings, and
+//! numeric strings in timestamp fields become numbers. Keys the store
+//! manages itself cannot be written by callers. Each document records the
+//! `METADATA_SCHEMA_VERSION` its metadata was normalized to, so rows
+//! written before can be found and brought up to date in batches.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use mindsage_core::{Error, Result};
+
+/// Version of the rules below. Documents record the version their
+/// metadata was normalized to; 0 means never.
+pub const METADATA_SCHEMA_VERSION: i64 = 1;
+
+/// Keys with a fixed spelling, matched case-insensitively.
+pub const CANONICAL_KEYS: &[&str] = &[
+    "source",
+    "topics",
+    "primary_topic",
+    "title",
+    "filename",
+    "url",
+    "author",
+    "type",
+    "timestamp",
+    "start_ms",
+    "end_ms",
+    "speaker",
+    "speakers",
+];
+
+/// Keys holding epoch timestamps (seconds or milliseconds).
+pub const TIMESTAMP_KEYS: &[&str] = &["timestamp", "start_ms", "end_ms", "metadata_updated_at"];
+
+/// Keys only the store writes.
+pub const RESERVED_KEYS: &[&str] = &["metadata_updated_at"];
+
+/// One rewrite made by `normalize_metadata`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum MetadataChange {
+    /// A key spelled differently from its canonical name was renamed.
+    RenamedKey { from: String, to: String },
+    /// A variant spelling was dropped because the canonical key was
+    /// already present.
+    DroppedDuplicateKey { key: String },
+    /// A string `topics` became a one-element array (empty when blank).
+    TopicsToArray,
+    /// Entries of `topics` that were not non-empty strings were dropped.
+    DroppedTopics { count: usize },
+    /// A numeric string in a timestamp field became a number.
+    NumericTimestamp { key: String },
+}
+
+impl MetadataChange {
+    /// Name of the rule that made the change, for per-rule counts.
+    pub fn rule(&self) -> &'static str {
+        match self {
+            Self::RenamedKey { .. } => "renamed_key",
+            Self::DroppedDuplicateKey { .. } => "dropped_duplicate_key",
+            Self::TopicsToArray => "topics_to_array",
+            Self::DroppedTopics { .. } => "dropped_topics",
+            Self::NumericTimestamp { .. } => "numeric_timestamp",
+        }
+    }
+}
+
+/// What one batch of `SqliteStore::normalize_stale_metadata` did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataBatch {
+    /// Id of the last document scanned; `None` when there were none left.
+    pub last_id: Option<i64>,
+    pub scanned: usize,
+    /// Documents whose metadata was rewritten, with the changes.
+    pub changed: Vec<(i64, Vec<MetadataChange>)>,
+    /// Documents whose metadata is not a JSON object, left unchanged.
+    pub unreadable: Vec<i64>,
+}
+
+/// Reject metadata a caller may not write: anything but an object (or
+/// null), and reserved keys in any spelling.
+pub fn validate_metadata(value: &Value) -> Result<()> {
+    let map = match value {
+        Value::Null => return Ok(()),
+        Value::Object(map) => map,
+        other => {
+            return Err(Error::InvalidMetadata(format!(
+                "metadata must be a JSON object, not {}",
+                json_type(other)
+            )))
+        }
+    };
+    if let Some(key) = map.keys().find(|k| RESERVED_KEYS.iter().any(|r| r.eq_ignore_ascii_case(k))) {
+        return Err(Error::InvalidMetadata(format!("\"{}\" is managed by the store and cannot be set", key)));
+    }
+    Ok(())
+}
+
+/// Bring `map` to the canonical shape in place, returning what changed.
+/// Unknown keys are left alone; applying it twice changes nothing.
+pub fn normalize_metadata(map: &mut Map<String, Value>) -> Vec<MetadataChange> {
+    let mut changes = Vec::new();
+
+    let variants: Vec<(String, &str)> = map
+        .keys()
+        .filter_map(|key| {
+            let canonical = CANONICAL_KEYS
+                .iter()
+                .chain(TIMESTAMP_KEYS)
+                .find(|c| c.eq_ignore_ascii_case(key) && *c != key)?;
+            Some((key.clone(), *canonical))
+        })
+        .collect();
+    for (key, canonical) in variants {
+        let value = map.remove(&key).unwrap_or(Value::Null);
+        if map.contains_key(canonical) {
+            changes.push(MetadataChange::DroppedDuplicateKey { key });
+        } else {
+            map.insert(canonical.to_string(), value);
+            changes.push(MetadataChange::RenamedKey {
+                from: key,
+                to: canonical.to_string(),
+            });
+        }
+    }
+
+    if let Some(topics) = map.get_mut("topics") {
+        if let Value::String(topic) = topics {
+            let topic = topic.trim();
+            *topics = if topic.is_empty() { Value::Array(Vec::new()) } else { Value::Array(vec![topic.into()]) };
+            changes.push(MetadataChange::TopicsToArray);
+        }
+        if let Value::Array(entries) = topics {
+            let before = entries.len();
+            entries.retain(|t| t.as_str().is_some_and(|s| !s.trim().is_empty()));
+            if entries.len() < before {
+                changes.push(MetadataChange::DroppedTopics {
+                    count: before - entries.len(),
+                });
+            }
+        }
+    }
+
+    for key in TIMESTAMP_KEYS {
+        let Some(Value::String(text)) = map.get(*key) else {
+            continue;
+        };
+        let number = text
+            .trim()
+            .parse::<i64>()
+            .map(Value::from)
+            .ok()
+            .or_else(|| text.trim().parse::<f64>().ok().and_then(|f| serde_json::Number::from_f64(f).map(Value::Number)));
+        if let Some(number) = number {
+            map.insert(key.to_string(), number);
+            changes.push(MetadataChange::NumericTimestamp { key: key.to_string() });
+        }
+    }
+    changes
+}
+
+/// Validate and normalize caller-supplied metadata for a new document.
+/// `None` for absent or null metadata.
+pub fn prepare_metadata(value: Option<&Value>) -> Result<Option<Map<String, Value>>> {
+    let Some(value) = value.filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    validate_metadata(value)?;
+    let mut map = value.as_object().cloned().unwrap_or_default();
+    normalize_metadata(&mut map);
+    Ok(Some(map))
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn normalized(value: Value) -> (Value, Vec<MetadataChange>) {
+        let mut map = value.as_object().cloned().unwrap();
+        let changes = normalize_metadata(&mut map);
+        (Value::Object(map), changes)
+    }
+
+    #[test]
+    fn test_known_keys_are_renamed_case_insensitively() {
+        let (value, changes) = normalized(json!({"Source": "upload", "TITLE": "Notes", "storedPath": "/x"}));
+        assert_eq!(value, json!({"source": "upload", "title": "Notes", "storedPath": "/x"}));
+        assert_eq!(changes.len(), 2);
+        assert!(changes.contains(&MetadataChange::RenamedKey {
+            from: "Source".into(),
+            to: "source".into()
+        }));
+
+        // The canonical spelling wins over a variant
+        let (value, changes) = normalized(json!({"source": "browser", "Source": "upload"}));
+        assert_eq!(value, json!({"source": "browser"}));
+        assert_eq!(changes, vec![MetadataChange::DroppedDuplicateKey { key: "Source".into() }]);
+    }
+
+    #[test]
+    fn test_topics_become_an_array_of_strings() {
+        let (value, changes) = normalized(json!({"topics": " rust "}));
+        assert_eq!(value, json!({"topics": ["rust"]}));
+        assert_eq!(changes, vec![MetadataChange::TopicsToArray]);
+
+        let (value, _) = normalized(json!({"Topics": ""}));
+        assert_eq!(value, json!({"topics": []}));
+
+        let (value, changes) = normalized(json!({"topics": ["rust", 3, null, " ", "tokio"]}));
+        assert_eq!(value, json!({"topics": ["rust", "tokio"]}));
+        assert_eq!(changes, vec![MetadataChange::DroppedTopics { count: 3 }]);
+    }
+
+    #[test]
+    fn test_numeric_timestamp_strings_become_numbers() {
+        let (value, changes) = normalized(json!({
+            "timestamp": "1700000000", "start_ms": " 1500.5", "end_ms": "2025-01-01T00:00:00Z", "created": "17"
+        }));
+        assert_eq!(value, json!({
+            "timestamp": 1_700_000_000, "start_ms": 1500.5, "end_ms": "2025-01-01T00:00:00Z", "created": "17"
+        }));
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|c| c.rule() == "numeric_timestamp"));
+
+        // Normalizing again changes nothing
+        let mut map = value.as_object().cloned().unwrap();
+        assert!(normalize_metadata(&mut map).is_empty());
+    }
+
+    #[test]
+    fn test_reserved_keys_and_non_objects_are_rejected() {
+        assert!(validate_metadata(&json!({"source": "upload"})).is_ok());
+        assert!(validate_metadata(&Value::Null).is_ok());
+        for bad in [json!({"metadata_updated_at": 1}), json!({"Metadata_Updated_At": "9"}), json!(["a"]), json!("x")] {
+            assert!(matches!(validate_metadata(&bad), Err(Error::InvalidMetadata(_))), "{}", bad);
+        }
+        assert_eq!(prepare_metadata(Some(&Value::Null)).unwrap(), None);
+        let prepared = prepare_metadata(Some(&json!({"Topics": "rust"}))).unwrap().unwrap();
+        assert_eq!(Value::Object(prepared), json!({"topics": ["rust"]}));
+    }
+}
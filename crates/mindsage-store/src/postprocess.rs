@@ -0,0 +1,121 @@
+//! Search post-processing — the re-ranking every consumer of search hits
+//! applies after retrieval.
+//!
+//! Hits containing the query's words get a boost proportional to the share
+//! of words they contain, scaled to the best hit's score so it means the
+//! same for BM25, vector and fused scores. The boosted hits are then picked
+//! by MMR (see `diversity`) with the per-document cap. Search routes, chat
+//! context and the resolver all go through `post_process`, so the same
+//! query and settings give the same order everywhere.
+
+use std::collections::HashMap;
+
+use mindsage_core::SearchPostProcessing;
+use ndarray::Array1;
+
+use crate::diversity::{mmr_select, Diversity};
+use crate::types::SearchHit;
+
+/// Words of `query` that count for the boost: lowercased, at least
+/// `min_term_length` characters, each once.
+fn boost_terms(query: &str, min_term_length: usize) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for word in query.split(|c: char| !c.is_alphanumeric()) {
+        let word = word.to_lowercase();
+        if word.chars().count() >= min_term_length && !terms.contains(&word) {
+            terms.push(word);
+        }
+    }
+    terms
+}
+
+/// Boost `hits` by the share of query terms their text contains, then
+/// re-sort by score. The sort is stable, so unboosted ties keep the
+/// retrieval order.
+pub fn boost_query_terms(hits: &mut [SearchHit], query: &str, settings: &SearchPostProcessing) {
+    let terms = boost_terms(query, settings.min_term_length);
+    let top = hits.iter().map(|h| h.score).fold(0.0f64, f64::max);
+    if terms.is_empty() || settings.entity_boost <= 0.0 || top <= 0.0 {
+        return;
+    }
+    for hit in hits.iter_mut() {
+        let text = hit.text.to_lowercase();
+        let matched = terms.iter().filter(|t| text.contains(t.as_str())).count();
+        hit.score += settings.entity_boost * top * (matched as f64 / terms.len() as f64);
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+}
+
+/// Boost `hits` for `query`, then select up to `top_k` by MMR under the
+/// per-document cap. `embeddings` maps chunk ids to their embeddings and
+/// may be empty (texts are compared instead).
+pub fn post_process(
+    mut hits: Vec<SearchHit>,
+    query: &str,
+    settings: &SearchPostProcessing,
+    embeddings: &HashMap<i64, Array1<f32>>,
+    top_k: usize,
+) -> Vec<SearchHit> {
+    boost_query_terms(&mut hits, query, settings);
+    let diversity = Diversity::new(settings.mmr_lambda, settings.max_chunks_per_doc);
+    mmr_select(hits, embeddings, diversity, top_k)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(chunk_id: i64, doc_id: i64, text: &str, score: f64) -> SearchHit {
+        SearchHit {
+            chunk_id,
+            doc_id,
+            text: text.to_string(),
+            score,
+            level: 1,
+            metadata: None,
+            enriched_text: None,
+            parent_chunk_id: None,
+            chunk_index: chunk_id as i32,
+            char_start: None,
+            char_end: None,
+        }
+    }
+
+    #[test]
+    fn test_boost_follows_term_coverage() {
+        let settings = SearchPostProcessing {
+            entity_boost: 0.5,
+            ..Default::default()
+        };
+        let mut hits = vec![
+            hit(1, 1, "nothing relevant here", 1.0),
+            hit(2, 2, "the rust compiler", 0.9),
+            hit(3, 3, "rust borrow checker in the compiler", 0.8),
+        ];
+        // "in" is shorter than the minimum term length and does not count
+        boost_query_terms(&mut hits, "Rust compiler in", &settings);
+        let order: Vec<i64> = hits.iter().map(|h| h.chunk_id).collect();
+        assert_eq!(order, vec![2, 3, 1]);
+        assert!((hits[0].score - 1.4).abs() < 1e-9);
+
+        let off = SearchPostProcessing {
+            entity_boost: 0.0,
+            ..Default::default()
+        };
+        boost_query_terms(&mut hits, "nothing", &off);
+        assert_eq!(hits[2].chunk_id, 1);
+    }
+
+    #[test]
+    fn test_cap_per_document() {
+        let settings = SearchPostProcessing {
+            max_chunks_per_doc: Some(1),
+            mmr_lambda: 1.0,
+            ..Default::default()
+        };
+        let hits = vec![hit(1, 1, "alpha one", 1.0), hit(2, 1, "alpha two", 0.9), hit(3, 2, "beta", 0.5)];
+        let picked = post_process(hits, "alpha", &settings, &HashMap::new(), 10);
+        let docs: Vec<i64> = picked.iter().map(|h| h.doc_id).collect();
+        assert_eq!(docs, vec![1, 2]);
+    }
+}
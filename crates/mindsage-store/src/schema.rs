@@ -8,7 +8,10 @@ CREATE TABLE IF NOT EXISTS documents (
     metadata_json TEXT,
     content_hash TEXT UNIQUE,
     created_at INTEGER NOT NULL,
-    updated_at INTEGER
+    updated_at INTEGER,
+    external_source TEXT,
+    external_id TEXT,
+    metadata_schema INTEGER NOT NULL DEFAULT 0
 );
 
 CREATE TABLE IF NOT EXISTS chunks (
@@ -22,47 +25,351 @@ CREATE TABLE IF NOT EXISTS chunks (
     char_end INTEGER,
     level INTEGER DEFAULT 0,
     metadata_json TEXT,
-    created_at INTEGER NOT NULL
+    created_at INTEGER NOT NULL,
+    search_text TEXT,
+    search_enriched TEXT,
+    extraction_version INTEGER NOT NULL DEFAULT 0
 );
 
 CREATE INDEX IF NOT EXISTS idx_chunks_doc_id ON chunks(doc_id);
 CREATE INDEX IF NOT EXISTS idx_chunks_level ON chunks(level);
 CREATE INDEX IF NOT EXISTS idx_chunks_parent ON chunks(parent_chunk_id);
 CREATE INDEX IF NOT EXISTS idx_documents_hash ON documents(content_hash);
+CREATE INDEX IF NOT EXISTS idx_documents_created ON documents(created_at, id);
 
 CREATE TABLE IF NOT EXISTS chunk_embeddings (
     chunk_id INTEGER PRIMARY KEY REFERENCES chunks(id) ON DELETE CASCADE,
     embedding BLOB NOT NULL,
     scale REAL NOT NULL,
-    offset_val REAL NOT NULL
+    offset_val REAL NOT NULL,
+    model_id TEXT NOT NULL DEFAULT 'unknown',
+    quant_version INTEGER NOT NULL DEFAULT 0,
+    text_stale INTEGER NOT NULL DEFAULT 0
+);
+"#;
+
+/// Columns added after the first release, as (table, column, definition).
+/// Missing columns are added on open; the defaults describe existing rows.
+pub const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("chunk_embeddings", "model_id", "TEXT NOT NULL DEFAULT 'unknown'"),
+    ("chunk_embeddings", "quant_version", "INTEGER NOT NULL DEFAULT 0"),
+    ("chunk_embeddings", "text_stale", "INTEGER NOT NULL DEFAULT 0"),
+    ("chunks", "search_text", "TEXT"),
+    ("chunks", "search_enriched", "TEXT"),
+    ("chunks", "extraction_version", "INTEGER NOT NULL DEFAULT 0"),
+    ("documents", "external_source", "TEXT"),
+    ("documents", "external_id", "TEXT"),
+    ("documents", "metadata_schema", "INTEGER NOT NULL DEFAULT 0"),
+];
+
+/// Rebuild of a `chunk_embeddings` whose foreign key does not cascade
+/// from `chunks`, as in databases created by the Python backend; SQLite
+/// cannot alter a foreign key. Embeddings whose chunk is already gone are
+/// dropped. Runs after `ADDED_COLUMNS`, in a transaction with foreign keys
+/// off.
+pub const CHUNK_EMBEDDINGS_REBUILD_SQL: &str = r#"
+CREATE TABLE chunk_embeddings_rebuilt (
+    chunk_id INTEGER PRIMARY KEY REFERENCES chunks(id) ON DELETE CASCADE,
+    embedding BLOB NOT NULL,
+    scale REAL NOT NULL,
+    offset_val REAL NOT NULL,
+    model_id TEXT NOT NULL DEFAULT 'unknown',
+    quant_version INTEGER NOT NULL DEFAULT 0,
+    text_stale INTEGER NOT NULL DEFAULT 0
+);
+INSERT INTO chunk_embeddings_rebuilt
+    (chunk_id, embedding, scale, offset_val, model_id, quant_version, text_stale)
+SELECT chunk_id, embedding, scale, offset_val, model_id, quant_version, text_stale
+FROM chunk_embeddings WHERE chunk_id IN (SELECT id FROM chunks);
+DROP TABLE chunk_embeddings;
+ALTER TABLE chunk_embeddings_rebuilt RENAME TO chunk_embeddings;
+"#;
+
+/// Index on the embedding model, created after `chunk_embeddings.model_id`
+/// has been added to databases that predate it.
+pub const EMBEDDING_MODEL_INDEX_SQL: &str = r#"
+CREATE INDEX IF NOT EXISTS idx_chunk_embeddings_model ON chunk_embeddings(model_id);
+"#;
+
+/// One document per external id within a source, created after the
+/// columns have been added to databases that predate them.
+pub const EXTERNAL_ID_INDEX_SQL: &str = r#"
+CREATE UNIQUE INDEX IF NOT EXISTS idx_documents_external
+    ON documents(external_source, external_id) WHERE external_id IS NOT NULL;
+"#;
+
+/// Cached per-document centroid embeddings (mean of normalized chunk
+/// embeddings, re-normalized) for document-level similarity.
+pub const CENTROID_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS doc_centroids (
+    doc_id INTEGER PRIMARY KEY REFERENCES documents(id) ON DELETE CASCADE,
+    centroid BLOB NOT NULL,
+    chunk_count INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+"#;
+
+/// Saved searches and the chunk ids each one has already reported.
+/// `chunk_watermark` is the highest chunk id that existed at the last
+/// check; only unseen hits above it count as new matches.
+pub const SAVED_SEARCH_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS saved_searches (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    query TEXT NOT NULL,
+    filters_json TEXT,
+    top_k INTEGER NOT NULL,
+    new_matches INTEGER NOT NULL DEFAULT 0,
+    chunk_watermark INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL,
+    last_checked_at INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS saved_search_seen (
+    search_id INTEGER NOT NULL REFERENCES saved_searches(id) ON DELETE CASCADE,
+    chunk_id INTEGER NOT NULL,
+    doc_id INTEGER NOT NULL,
+    is_new INTEGER NOT NULL DEFAULT 0,
+    seen_at INTEGER NOT NULL,
+    PRIMARY KEY (search_id, chunk_id)
+);
+"#;
+
+/// Document topics, mirrored from `metadata.topics` whenever document
+/// metadata is written, so topic listings and stats are SQL aggregates.
+pub const TOPIC_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS doc_topics (
+    topic TEXT NOT NULL,
+    doc_id INTEGER NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    PRIMARY KEY (topic, doc_id)
+) WITHOUT ROWID;
+
+CREATE INDEX IF NOT EXISTS idx_doc_topics_doc ON doc_topics(doc_id);
+"#;
+
+/// User-assigned tags. Unlike topics they are never generated or replaced
+/// by enrichment, and unlike metadata they have their own index.
+pub const TAG_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS doc_tags (
+    tag TEXT NOT NULL,
+    doc_id INTEGER NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    PRIMARY KEY (tag, doc_id)
+) WITHOUT ROWID;
+
+CREATE INDEX IF NOT EXISTS idx_doc_tags_doc ON doc_tags(doc_id);
+"#;
+
+/// Collections (notebooks) grouping documents, to scope search and chat.
+/// Deleting a collection removes its memberships, never its documents.
+pub const COLLECTION_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS collections (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    created_at INTEGER NOT NULL
 );
+
+CREATE TABLE IF NOT EXISTS doc_collections (
+    collection_id INTEGER NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+    doc_id INTEGER NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    PRIMARY KEY (collection_id, doc_id)
+) WITHOUT ROWID;
+
+CREATE INDEX IF NOT EXISTS idx_doc_collections_doc ON doc_collections(doc_id);
 "#;
 
-/// FTS5 virtual table for full-text search.
+/// FTS5 virtual table for full-text search. `remove_diacritics 2` folds
+/// accents on any letter, precomposed or combining, so `Zürich`,
+/// `Zu\u{308}rich` and `zurich` index as the same term.
 pub const FTS_SCHEMA_SQL: &str = r#"
 CREATE VIRTUAL TABLE IF NOT EXISTS chunks_fts USING fts5(
     text, enriched_text,
     content='chunks', content_rowid='id',
-    tokenize='porter unicode61'
+    tokenize='porter unicode61 remove_diacritics 2'
+);
+"#;
+
+/// Tokenizer option whose absence marks a `chunks_fts` from before accent
+/// folding covered combining marks.
+pub const FTS_TOKENIZER_MARKER: &str = "remove_diacritics 2";
+
+/// Refill a recreated `chunks_fts` from `chunks`, indexing the same
+/// columns as the triggers (search tokens for encrypted chunks).
+pub const FTS_REFILL_SQL: &str = r#"
+INSERT INTO chunks_fts(rowid, text, enriched_text)
+SELECT id, COALESCE(search_text, text), COALESCE(search_enriched, enriched_text, '') FROM chunks;
+"#;
+
+/// Autocomplete sources: an fts5vocab view of `chunk_words` (with per-term
+/// document frequency) and a log of successful queries. Both are
+/// range-scanned by prefix on their primary keys.
+///
+/// `chunk_words` indexes chunk text with `unicode61` alone, so its
+/// vocabulary is whole words rather than `chunks_fts`'s porter stems.
+/// Encrypted chunks (`enc1:` ciphertext) index nothing; their vocabulary
+/// is not offered.
+pub const SUGGEST_SCHEMA_SQL: &str = r#"
+CREATE VIRTUAL TABLE IF NOT EXISTS chunk_words USING fts5(
+    text,
+    content='chunks', content_rowid='id',
+    tokenize='unicode61 remove_diacritics 0'
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS chunk_words_vocab USING fts5vocab(chunk_words, row);
+
+CREATE TRIGGER IF NOT EXISTS chunk_words_ai AFTER INSERT ON chunks BEGIN
+    INSERT INTO chunk_words(rowid, text)
+    VALUES (new.id, CASE WHEN substr(new.text, 1, 5) = 'enc1:' THEN '' ELSE new.text END);
+END;
+
+CREATE TRIGGER IF NOT EXISTS chunk_words_ad AFTER DELETE ON chunks BEGIN
+    INSERT INTO chunk_words(chunk_words, rowid, text)
+    VALUES ('delete', old.id, CASE WHEN substr(old.text, 1, 5) = 'enc1:' THEN '' ELSE old.text END);
+END;
+
+CREATE TRIGGER IF NOT EXISTS chunk_words_au AFTER UPDATE OF text ON chunks BEGIN
+    INSERT INTO chunk_words(chunk_words, rowid, text)
+    VALUES ('delete', old.id, CASE WHEN substr(old.text, 1, 5) = 'enc1:' THEN '' ELSE old.text END);
+    INSERT INTO chunk_words(rowid, text)
+    VALUES (new.id, CASE WHEN substr(new.text, 1, 5) = 'enc1:' THEN '' ELSE new.text END);
+END;
+
+CREATE TABLE IF NOT EXISTS query_log (
+    query TEXT PRIMARY KEY,
+    hit_count INTEGER NOT NULL,
+    use_count INTEGER NOT NULL DEFAULT 1,
+    last_used_at INTEGER NOT NULL
 );
+
+CREATE INDEX IF NOT EXISTS idx_query_log_last_used ON query_log(last_used_at);
+"#;
+
+/// Fill a new `chunk_words` from `chunks`, as its triggers would have.
+pub const CHUNK_WORDS_REFILL_SQL: &str = r#"
+INSERT INTO chunk_words(rowid, text)
+SELECT id, CASE WHEN substr(text, 1, 5) = 'enc1:' THEN '' ELSE text END FROM chunks;
 "#;
 
-/// Triggers to keep FTS index in sync with chunks table.
+/// Triggers to keep FTS index in sync with chunks table. Encrypted chunks
+/// index their `search_text`/`search_enriched` tokens instead of the text.
+/// Created after `ADDED_COLUMNS`; databases with the older triggers that
+/// indexed `text` directly get them replaced.
 pub const FTS_TRIGGERS_SQL: &str = r#"
 CREATE TRIGGER IF NOT EXISTS chunks_ai AFTER INSERT ON chunks BEGIN
     INSERT INTO chunks_fts(rowid, text, enriched_text)
-    VALUES (new.id, new.text, COALESCE(new.enriched_text, ''));
+    VALUES (new.id, COALESCE(new.search_text, new.text),
+            COALESCE(new.search_enriched, new.enriched_text, ''));
 END;
 
 CREATE TRIGGER IF NOT EXISTS chunks_ad AFTER DELETE ON chunks BEGIN
     INSERT INTO chunks_fts(chunks_fts, rowid, text, enriched_text)
-    VALUES ('delete', old.id, old.text, COALESCE(old.enriched_text, ''));
+    VALUES ('delete', old.id, COALESCE(old.search_text, old.text),
+            COALESCE(old.search_enriched, old.enriched_text, ''));
 END;
 
 CREATE TRIGGER IF NOT EXISTS chunks_au AFTER UPDATE ON chunks BEGIN
     INSERT INTO chunks_fts(chunks_fts, rowid, text, enriched_text)
-    VALUES ('delete', old.id, old.text, COALESCE(old.enriched_text, ''));
+    VALUES ('delete', old.id, COALESCE(old.search_text, old.text),
+            COALESCE(old.search_enriched, old.enriched_text, ''));
     INSERT INTO chunks_fts(rowid, text, enriched_text)
-    VALUES (new.id, new.text, COALESCE(new.enriched_text, ''));
+    VALUES (new.id, COALESCE(new.search_text, new.text),
+            COALESCE(new.search_enriched, new.enriched_text, ''));
 END;
 "#;
+
+/// Triggers dropped when they predate the search-token columns.
+pub const FTS_TRIGGER_NAMES: &[&str] = &["chunks_ai", "chunks_ad", "chunks_au"];
+
+/// Files under uploads/ and imports/ that have been indexed, keyed by path.
+/// `mtime` (Unix ms) and `size` decide whether the file changed since.
+pub const INDEXED_FILES_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS indexed_files (
+    path TEXT PRIMARY KEY,
+    mtime INTEGER,
+    size INTEGER NOT NULL,
+    content_hash TEXT,
+    doc_id INTEGER,
+    indexed_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_indexed_files_doc ON indexed_files(doc_id);
+"#;
+
+/// Ingest journal: steps still owed to a document after its chunks were
+/// written (`embed`, `enrich`). Rows are inserted in the transaction that
+/// writes the chunks and deleted when the step finishes, so whatever a
+/// crash interrupted is still listed on the next start.
+pub const PENDING_WORK_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS pending_work (
+    doc_id INTEGER NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    step TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (doc_id, step)
+);
+
+CREATE INDEX IF NOT EXISTS idx_pending_work_step ON pending_work(step, doc_id);
+"#;
+
+/// Where the last catch-up pass over the ingest journal got to, per step:
+/// the last document it reached and how many it went through. Kept until
+/// the pass reaches the end of the journal, so a restart resumes after
+/// `after_doc_id` instead of starting over.
+pub const CATCHUP_CHECKPOINT_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS catchup_checkpoints (
+    step TEXT PRIMARY KEY,
+    after_doc_id INTEGER NOT NULL,
+    done INTEGER NOT NULL,
+    started_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+"#;
+
+/// Connector items held for review before they become documents. Keyed by
+/// connector and source item, so re-importing an export replaces what is
+/// still staged instead of adding it twice. `text_length` is in characters
+/// (the text may be encrypted); `staged_at` is Unix ms.
+pub const STAGED_ITEMS_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS staged_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    connector_id TEXT NOT NULL,
+    source_item_id TEXT NOT NULL,
+    item_type TEXT NOT NULL,
+    text TEXT NOT NULL,
+    text_length INTEGER NOT NULL,
+    metadata_json TEXT,
+    proposed_topics_json TEXT,
+    external_id TEXT,
+    created_at INTEGER,
+    staged_at INTEGER NOT NULL,
+    UNIQUE (connector_id, source_item_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_staged_items_staged_at ON staged_items(staged_at);
+"#;
+
+/// One row per search while the query stats log is on: the normalized
+/// query or its SHA-256 (`hashed`), result count, top score and latency.
+/// `diagnostics_json` is kept for slow and zero-hit searches in capture
+/// mode. Rows past the retention period are trimmed on write.
+pub const QUERY_STATS_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS query_stats (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    query TEXT NOT NULL,
+    hashed INTEGER NOT NULL DEFAULT 0,
+    search_type TEXT NOT NULL,
+    result_count INTEGER NOT NULL,
+    top_score REAL,
+    latency_ms REAL NOT NULL,
+    created_at INTEGER NOT NULL,
+    diagnostics_json TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_query_stats_created ON query_stats(created_at);
+"#;
+
+/// Store-wide settings as key/value pairs (e.g. the encryption key check).
+pub const SETTINGS_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS store_settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+"#;
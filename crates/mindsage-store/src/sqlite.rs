@@ -3,18 +3,59 @@
 //! Port of Python's `sqlite_store.py`. Same schema, same search algorithms.
 //! Targets <200ms total search latency on Jetson Orin Nano.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
-
-use ndarray::{Array1, Array2, Axis};
-use parking_lot::Mutex;
-use rusqlite::{params, Connection, OptionalExtension};
-use tracing::{debug, info};
-
-use crate::embedding::{dequantize_uint8, quantize_uint8};
-use crate::schema::{FTS_SCHEMA_SQL, FTS_TRIGGERS_SQL, SCHEMA_SQL};
+use std::time::{Duration, Instant};
+
+use ndarray::Array1;
+use parking_lot::{Mutex, RwLock};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, instrument, warn};
+
+use crate::ann::{AnnConfig, IvfIndex, ANN_INDEX_FILE, REBUILD_DELETED_FRACTION};
+use crate::crypto::{EncryptionConfig, EncryptionKeys};
+use crate::embedding::{bytes_to_f32, dequantize, f32_to_bytes, quantize, QuantScheme};
+use crate::matrix::{MatrixMode, VectorRows};
+use crate::metadata::{self, MetadataBatch, METADATA_SCHEMA_VERSION};
+use crate::schema::{
+    ADDED_COLUMNS, CENTROID_SCHEMA_SQL, CHUNK_EMBEDDINGS_REBUILD_SQL, CHUNK_WORDS_REFILL_SQL, COLLECTION_SCHEMA_SQL, EMBEDDING_MODEL_INDEX_SQL, EXTERNAL_ID_INDEX_SQL, FTS_REFILL_SQL, FTS_SCHEMA_SQL,
+    FTS_TOKENIZER_MARKER, FTS_TRIGGERS_SQL, FTS_TRIGGER_NAMES, INDEXED_FILES_SCHEMA_SQL, PENDING_WORK_SCHEMA_SQL, CATCHUP_CHECKPOINT_SCHEMA_SQL, SAVED_SEARCH_SCHEMA_SQL, SCHEMA_SQL, SETTINGS_SCHEMA_SQL, STAGED_ITEMS_SCHEMA_SQL,
+    QUERY_STATS_SCHEMA_SQL, SUGGEST_SCHEMA_SQL, TAG_SCHEMA_SQL, TOPIC_SCHEMA_SQL,
+};
 use crate::types::*;
-use mindsage_core::{Error, Result};
+use mindsage_core::paths::normalize_path;
+use mindsage_core::{CapabilityTier, Error, QueryStatsConfig, QueryStatsMode, Result, SearchPostProcessing};
+
+/// Most words of a query sent to FTS5; longer queries are cut, since
+/// every word adds an OR branch to evaluate.
+const MAX_FTS_TOKENS: usize = 32;
+/// Most chunks scanned for entities by `topic_stats`.
+const TOPIC_ENTITY_SCAN_LIMIT: i64 = 5000;
+/// Rows written per transaction by bulk document operations.
+const BULK_BATCH_SIZE: usize = 500;
+/// Most queries kept in `query_log`; the least recently used are pruned.
+const QUERY_LOG_MAX: i64 = 1000;
+/// `store_settings` key holding a value encrypted with the database key.
+const KEY_CHECK_SETTING: &str = "encryption_key_check";
+/// Plaintext of the key check value.
+const KEY_CHECK_PLAINTEXT: &str = "mindsage";
+/// `store_settings` key holding the hex salt passphrases are stretched with.
+const SALT_SETTING: &str = "encryption_salt";
+/// Longest query recorded in `query_log`.
+const QUERY_LOG_MAX_CHARS: usize = 200;
+/// Most searches kept in `query_stats`, whatever the retention period.
+const QUERY_STATS_MAX: i64 = 100_000;
+/// Searches recorded between two trims of `query_stats`.
+const QUERY_STATS_TRIM_EVERY: u64 = 1000;
+/// Model id of embeddings stored before models were tracked.
+const UNKNOWN_EMBEDDING_MODEL: &str = "unknown";
+/// Smallest share of a full vector scan worth running when the latency
+/// budget cannot cover all of it; below this the stage is skipped.
+const MIN_TRUNCATED_VECTOR_SHARE: f64 = 0.25;
+/// How long a computed per-source breakdown is reused.
+const SOURCE_BREAKDOWN_TTL: Duration = Duration::from_secs(30);
 
 /// SQLite store with FTS5 full-text search and int8 vector search.
 pub struct SqliteStore {
@@ -23,15 +64,59 @@ pub struct SqliteStore {
     embedding_dim: usize,
     /// Pre-loaded normalized embedding matrix for vector search: (N, dim) float32.
     embedding_matrix: Mutex<EmbeddingMatrix>,
+    /// Model id recorded with new embeddings; only embeddings from this
+    /// model are loaded into the matrix.
+    embedding_model: RwLock<String>,
+    /// Quantization format for newly written embeddings.
+    quant_scheme: RwLock<QuantScheme>,
+    /// When vector search switches to the ANN index.
+    ann_config: RwLock<AnnConfig>,
+    /// Held while an ANN index is being built, so builds never overlap.
+    ann_build: Mutex<()>,
+    /// BM25 weights of the `text` and `enriched_text` FTS columns.
+    fts_weights: RwLock<FtsWeights>,
+    /// Boost, MMR and per-document cap applied to search hits.
+    search_post_processing: RwLock<SearchPostProcessing>,
+    /// Factor applied to the vector score of embeddings whose chunk text
+    /// changed after embedding; 1.0 leaves them as they are.
+    text_stale_weight: RwLock<f32>,
+    /// Whether and how searches are recorded in `query_stats`.
+    query_stats_config: RwLock<QueryStatsConfig>,
+    /// Searches recorded since the store was opened, to trim every
+    /// `QUERY_STATS_TRIM_EVERY` of them.
+    query_stats_recorded: std::sync::atomic::AtomicU64,
+    /// Field encryption of document and chunk text, when a key is configured.
+    encryption: Option<EncryptionKeys>,
+    /// Opened with `open_read_only`: SQLite refuses every write, and nothing
+    /// is written next to the database either.
+    read_only: bool,
+    /// Moving average of vector search cost per matrix row, in nanoseconds,
+    /// used to plan budgeted searches.
+    vector_ns_per_row: Mutex<Option<f64>>,
+    /// Rows that failed to map since the store was opened.
+    corrupt_rows: std::sync::atomic::AtomicU64,
+    /// Embeddings of the wrong length seen since the store was opened.
+    dimension_mismatches: std::sync::atomic::AtomicU64,
+    /// Last per-source breakdown and when it was computed.
+    source_breakdown: Mutex<Option<(Instant, Vec<SourceStats>)>>,
+    /// Chunk lookup queries issued, for query-count tests.
+    #[cfg(test)]
+    chunk_queries: std::sync::atomic::AtomicUsize,
 }
 
 struct EmbeddingMatrix {
-    /// Normalized embeddings, shape (N, dim).
-    matrix: Array2<f32>,
+    /// Normalized embeddings, shape (N, dim), as float or int8 rows.
+    matrix: VectorRows,
     /// Chunk IDs corresponding to each row.
     chunk_ids: Vec<i64>,
+    /// Chunk level of each row.
+    levels: Vec<i32>,
+    /// Whether each row's chunk text changed after it was embedded.
+    text_stale: Vec<bool>,
     /// Whether the matrix needs reloading.
     dirty: bool,
+    /// ANN index over the matrix rows, once the store is large enough.
+    ann: Option<IvfIndex>,
 }
 
 impl SqliteStore {
@@ -39,1089 +124,6942 @@ impl SqliteStore {
     ///
     /// `db_dir` is the directory (e.g., `data/vectordb/`). The file will be `db_dir/mindsage.db`.
     pub fn open(db_dir: impl AsRef<Path>, embedding_dim: usize) -> Result<Self> {
+        Self::open_with_encryption(db_dir, embedding_dim, None)
+    }
+
+    /// Open the store with text encrypted at rest under `encryption`.
+    ///
+    /// Fails with `Error::Encryption` when the database is encrypted and no
+    /// key, or a key it was not encrypted with, is given.
+    pub fn open_with_encryption(
+        db_dir: impl AsRef<Path>,
+        embedding_dim: usize,
+        encryption: Option<EncryptionConfig>,
+    ) -> Result<Self> {
         let db_dir = db_dir.as_ref();
         std::fs::create_dir_all(db_dir).map_err(|e| Error::Storage(e.to_string()))?;
         let db_path = db_dir.join("mindsage.db");
 
         let conn = Self::create_connection(&db_path)?;
         Self::init_schema(&conn)?;
+        Self::migrate_schema(&conn)?;
+        let encryption = Self::unlock_encryption(&conn, encryption.as_ref(), false)?;
+
+        let store = Self::from_connection(conn, db_path, embedding_dim, encryption, false);
+
+        // One-time backfill of doc_topics for databases predating it
+        let backfilled = store.backfill_doc_topics()?;
+        if backfilled > 0 {
+            info!("Backfilled {} document topics", backfilled);
+        }
+
+        store.finish_open()?;
+        Ok(store)
+    }
+
+    /// Open an existing store without writing to it: the connection is
+    /// opened with `SQLITE_OPEN_READ_ONLY` and `query_only`, so every write
+    /// fails with `Error::ReadOnly`, and the schema is neither created nor
+    /// migrated. Fails when there is no database or its schema is older
+    /// than this version's.
+    pub fn open_read_only(
+        db_dir: impl AsRef<Path>,
+        embedding_dim: usize,
+        encryption: Option<EncryptionConfig>,
+    ) -> Result<Self> {
+        let db_path = db_dir.as_ref().join("mindsage.db");
+        if !db_path.is_file() {
+            return Err(Error::Storage(format!("No database at {}", db_path.display())));
+        }
+        let conn = Connection::open_with_flags(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(db_error)?;
+        conn.execute_batch(
+            "PRAGMA query_only = ON;
+             PRAGMA foreign_keys = ON;
+             PRAGMA cache_size = -65536;",
+        )
+        .map_err(db_error)?;
+        Self::check_schema_current(&conn)?;
+
+        // Without a stored key check the database is plaintext, and a key
+        // check cannot be recorded
+        let has_key_check = conn
+            .query_row("SELECT 1 FROM store_settings WHERE key = ?1", params![KEY_CHECK_SETTING], |_| Ok(()))
+            .optional()
+            .map_err(db_error)?
+            .is_some();
+        let encryption = if has_key_check {
+            Self::unlock_encryption(&conn, encryption.as_ref(), true)?
+        } else {
+            if encryption.is_some() {
+                warn!("Database is not encrypted; ignoring the encryption key in read-only mode");
+            }
+            None
+        };
+
+        let store = Self::from_connection(conn, db_path, embedding_dim, encryption, true);
+        store.finish_open()?;
+        Ok(store)
+    }
+
+    /// Whether the store was opened with `open_read_only`.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
 
-        let store = Self {
+    fn from_connection(
+        conn: Connection,
+        db_path: PathBuf,
+        embedding_dim: usize,
+        encryption: Option<EncryptionKeys>,
+        read_only: bool,
+    ) -> Self {
+        Self {
             conn: Mutex::new(conn),
             db_path,
             embedding_dim,
             embedding_matrix: Mutex::new(EmbeddingMatrix {
-                matrix: Array2::zeros((0, embedding_dim)),
+                matrix: VectorRows::new(MatrixMode::default(), embedding_dim),
                 chunk_ids: Vec::new(),
+                levels: Vec::new(),
+                text_stale: Vec::new(),
                 dirty: true,
+                ann: None,
             }),
-        };
+            embedding_model: RwLock::new(UNKNOWN_EMBEDDING_MODEL.to_string()),
+            quant_scheme: RwLock::new(QuantScheme::default()),
+            ann_config: RwLock::new(AnnConfig::default()),
+            ann_build: Mutex::new(()),
+            fts_weights: RwLock::new(FtsWeights::default()),
+            search_post_processing: RwLock::new(SearchPostProcessing::default()),
+            text_stale_weight: RwLock::new(1.0),
+            query_stats_config: RwLock::new(QueryStatsConfig::default()),
+            query_stats_recorded: Default::default(),
+            encryption,
+            read_only,
+            vector_ns_per_row: Mutex::new(None),
+            corrupt_rows: Default::default(),
+            dimension_mismatches: Default::default(),
+            source_breakdown: Mutex::new(None),
+            #[cfg(test)]
+            chunk_queries: Default::default(),
+        }
+    }
 
-        // Load embedding matrix
-        store.load_embedding_matrix()?;
+    /// Load the embedding matrix and log what was opened.
+    fn finish_open(&self) -> Result<()> {
+        self.load_embedding_matrix()?;
 
-        let doc_count = store.count_documents()?;
-        let chunk_count = store.count_chunks(None)?;
+        let doc_count = self.count_documents()?;
+        let chunk_count = self.count_chunks(None)?;
         info!(
-            "SqliteStore initialized: {} documents, {} chunks, dim={}, path={}",
+            "SqliteStore initialized: {} documents, {} chunks, dim={}, path={}{}",
             doc_count,
             chunk_count,
-            embedding_dim,
-            store.db_path.display()
+            self.embedding_dim,
+            self.db_path.display(),
+            if self.read_only { " (read-only)" } else { "" }
         );
-
-        Ok(store)
+        Ok(())
     }
 
     fn create_connection(db_path: &Path) -> Result<Connection> {
         let conn = Connection::open(db_path)
-            .map_err(|e| Error::Database(e.to_string()))?;
+            .map_err(db_error)?;
         conn.execute_batch(
             "PRAGMA journal_mode = WAL;
              PRAGMA foreign_keys = ON;
              PRAGMA cache_size = -65536;
              PRAGMA synchronous = NORMAL;",
         )
-        .map_err(|e| Error::Database(e.to_string()))?;
+        .map_err(db_error)?;
         Ok(conn)
     }
 
     fn init_schema(conn: &Connection) -> Result<()> {
-        let full_schema = format!("{}\n{}\n{}", SCHEMA_SQL, FTS_SCHEMA_SQL, FTS_TRIGGERS_SQL);
+        let had_journal = conn
+            .query_row("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'pending_work'", [], |_| Ok(()))
+            .optional()
+            .map_err(db_error)?
+            .is_some();
+        let had_words = conn
+            .query_row("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'chunk_words'", [], |_| Ok(()))
+            .optional()
+            .map_err(db_error)?
+            .is_some();
+        let full_schema = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}",
+            SCHEMA_SQL,
+            FTS_SCHEMA_SQL,
+            CENTROID_SCHEMA_SQL,
+            SAVED_SEARCH_SCHEMA_SQL,
+            SUGGEST_SCHEMA_SQL,
+            QUERY_STATS_SCHEMA_SQL,
+            TOPIC_SCHEMA_SQL,
+            TAG_SCHEMA_SQL,
+            COLLECTION_SCHEMA_SQL,
+            SETTINGS_SCHEMA_SQL,
+            INDEXED_FILES_SCHEMA_SQL,
+            PENDING_WORK_SCHEMA_SQL,
+            CATCHUP_CHECKPOINT_SCHEMA_SQL,
+            STAGED_ITEMS_SCHEMA_SQL
+        );
         conn.execute_batch(&full_schema)
             .map_err(|e| Error::Database(format!("Schema init failed: {}", e)))?;
+        if !had_journal {
+            Self::seed_pending_work(conn)?;
+        }
+        if !had_words {
+            conn.execute_batch(CHUNK_WORDS_REFILL_SQL)
+                .map_err(|e| Error::Database(format!("Migration failed: {}", e)))?;
+            let indexed = conn.changes();
+            if indexed > 0 {
+                info!("Indexed the words of {} chunks for autocomplete", indexed);
+            }
+        }
         Ok(())
     }
 
-    // ---------------------------------------------------------------
-    // Document CRUD
-    // ---------------------------------------------------------------
+    /// Fill a new ingest journal from what databases without one had to
+    /// infer: paragraph chunks without an embedding or an extraction.
+    fn seed_pending_work(conn: &Connection) -> Result<()> {
+        let seeded = conn
+            .execute(
+                "INSERT OR IGNORE INTO pending_work (doc_id, step, created_at) \
+                 SELECT DISTINCT c.doc_id, 'embed', ?1 FROM chunks c \
+                 LEFT JOIN chunk_embeddings ce ON ce.chunk_id = c.id \
+                 WHERE c.level = 1 AND ce.chunk_id IS NULL \
+                 UNION ALL \
+                 SELECT DISTINCT doc_id, 'enrich', ?1 FROM chunks WHERE level = 1 AND enriched_text IS NULL",
+                params![now_millis()],
+            )
+            .map_err(|e| Error::Database(format!("Migration failed: {}", e)))?;
+        if seeded > 0 {
+            info!("Recorded {} pending embedding and extraction steps in the ingest journal", seeded);
+        }
+        Ok(())
+    }
 
-    /// Insert a document. Returns the new document ID.
-    pub fn add_document(&self, text: &str, opts: AddDocumentOptions) -> Result<i64> {
-        let now = opts.created_at.unwrap_or_else(|| {
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as i64
-        });
-        let meta_json = opts.metadata.as_ref().map(|m| serde_json::to_string(m).unwrap());
+    /// Add columns introduced after a database was created.
+    fn migrate_schema(conn: &Connection) -> Result<()> {
+        for (table, column, definition) in ADDED_COLUMNS {
+            if conn.prepare(&format!("SELECT {} FROM {} LIMIT 0", column, table)).is_ok() {
+                continue;
+            }
+            conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .map_err(|e| Error::Database(format!("Migration failed: {}", e)))?;
+            info!("Added column {}.{}", table, column);
+        }
+        Self::cascade_chunk_embeddings(conn)?;
+        conn.execute_batch(EMBEDDING_MODEL_INDEX_SQL)
+            .map_err(|e| Error::Database(format!("Migration failed: {}", e)))?;
+        conn.execute_batch(EXTERNAL_ID_INDEX_SQL)
+            .map_err(|e| Error::Database(format!("Migration failed: {}", e)))?;
+
+        // FTS triggers from before the search-token columns index `text` directly
+        let trigger_sql: Option<String> = conn
+            .query_row("SELECT sql FROM sqlite_master WHERE type = 'trigger' AND name = 'chunks_ai'", [], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(db_error)?;
+        if trigger_sql.is_some_and(|sql| !sql.contains("search_text")) {
+            for name in FTS_TRIGGER_NAMES {
+                conn.execute_batch(&format!("DROP TRIGGER IF EXISTS {}", name))
+                    .map_err(|e| Error::Database(format!("Migration failed: {}", e)))?;
+            }
+            info!("Replaced FTS triggers to index search tokens");
+        }
+        conn.execute_batch(FTS_TRIGGERS_SQL)
+            .map_err(|e| Error::Database(format!("Migration failed: {}", e)))?;
 
-        let conn = self.conn.lock();
-        let id = conn
-            .prepare_cached(
-                "INSERT INTO documents (text, metadata_json, content_hash, created_at) VALUES (?1, ?2, ?3, ?4)",
+        // Autocomplete reads unstemmed words from `chunk_words` now
+        conn.execute_batch("DROP TABLE IF EXISTS chunks_vocab")
+            .map_err(|e| Error::Database(format!("Migration failed: {}", e)))?;
+
+        // An FTS table with the older tokenizer settings is rebuilt
+        let fts_sql: Option<String> = conn
+            .query_row("SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'chunks_fts'", [], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(db_error)?;
+        if fts_sql.is_some_and(|sql| !sql.contains(FTS_TOKENIZER_MARKER)) {
+            conn.execute_batch(&format!(
+                "BEGIN;\nDROP TABLE chunks_fts;\n{}\n{}\nCOMMIT;",
+                FTS_SCHEMA_SQL, FTS_REFILL_SQL
+            ))
+            .map_err(|e| Error::Database(format!("Migration failed: {}", e)))?;
+            info!("Rebuilt the full-text index with accent folding");
+        }
+        Ok(())
+    }
+
+    /// Rebuild `chunk_embeddings` when deleting a chunk does not delete its
+    /// embedding, as in databases created by the Python backend.
+    fn cascade_chunk_embeddings(conn: &Connection) -> Result<()> {
+        let cascades: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_foreign_key_list('chunk_embeddings') \
+                 WHERE \"table\" = 'chunks' AND on_delete = 'CASCADE'",
+                [],
+                |row| row.get(0),
             )
-            .map_err(|e| Error::Database(e.to_string()))?
-            .insert(params![text, meta_json, opts.content_hash, now])
-            .map_err(|e| {
-                if e.to_string().contains("UNIQUE constraint") {
-                    Error::DuplicateContent(opts.content_hash.unwrap_or_default())
-                } else {
-                    Error::Database(e.to_string())
-                }
-            })?;
-        Ok(id)
+            .map_err(db_error)?;
+        if cascades > 0 {
+            return Ok(());
+        }
+        let count = || -> Result<i64> {
+            conn.query_row("SELECT COUNT(*) FROM chunk_embeddings", [], |row| row.get(0))
+                .map_err(db_error)
+        };
+        let before = count()?;
+        // Foreign keys cannot be switched off inside a transaction
+        conn.execute_batch("PRAGMA foreign_keys = OFF")
+            .map_err(db_error)?;
+        let rebuilt = conn.execute_batch(&format!("BEGIN;\n{}\nCOMMIT;", CHUNK_EMBEDDINGS_REBUILD_SQL));
+        if rebuilt.is_err() {
+            let _ = conn.execute_batch("ROLLBACK");
+        }
+        conn.execute_batch("PRAGMA foreign_keys = ON")
+            .map_err(db_error)?;
+        rebuilt.map_err(|e| Error::Database(format!("Migration failed: {}", e)))?;
+        let dropped = before - count()?;
+        info!(
+            "Rebuilt chunk_embeddings to cascade chunk deletes ({} embeddings without a chunk dropped)",
+            dropped
+        );
+        Ok(())
     }
 
-    /// Find a document by content hash.
-    pub fn find_document_by_hash(&self, content_hash: &str) -> Result<Option<Document>> {
-        let conn = self.conn.lock();
-        let row = conn
-            .prepare_cached("SELECT * FROM documents WHERE content_hash = ?1")
-            .map_err(|e| Error::Database(e.to_string()))?
-            .query_row(params![content_hash], |row| Ok(Self::row_to_document(row)))
-            .optional()
-            .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(row)
+    /// Check that `conn` has every table and column of the current schema,
+    /// by building that schema in memory and comparing the two.
+    fn check_schema_current(conn: &Connection) -> Result<()> {
+        let current = Connection::open_in_memory().map_err(db_error)?;
+        Self::init_schema(&current)?;
+        Self::migrate_schema(&current)?;
+        let tables: Vec<String> = current
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")
+            .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+            .map_err(db_error)?;
+        let columns = |conn: &Connection, table: &str| -> rusqlite::Result<HashSet<String>> {
+            let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+            let names = stmt.query_map(params![table], |row| row.get(0))?.collect();
+            names
+        };
+
+        let mut missing = Vec::new();
+        for table in &tables {
+            let have = columns(conn, table).map_err(db_error)?;
+            if have.is_empty() {
+                missing.push(table.clone());
+                continue;
+            }
+            let mut want: Vec<String> = columns(&current, table).map_err(db_error)?.into_iter().collect();
+            want.sort();
+            missing.extend(want.into_iter().filter(|c| !have.contains(c)).map(|c| format!("{}.{}", table, c)));
+        }
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Database(format!(
+                "Database schema is older than this version (missing {}); open it once without read-only mode to migrate it",
+                missing.join(", ")
+            )))
+        }
     }
 
-    /// Get a document by ID.
-    pub fn get_document(&self, doc_id: i64) -> Result<Option<Document>> {
-        let conn = self.conn.lock();
-        let row = conn
-            .prepare_cached("SELECT * FROM documents WHERE id = ?1")
-            .map_err(|e| Error::Database(e.to_string()))?
-            .query_row(params![doc_id], |row| Ok(Self::row_to_document(row)))
-            .optional()
-            .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(row)
+    /// Derive the keys of `encryption` with the database's salt and verify
+    /// them against the stored key check. When encryption is first enabled
+    /// this records the salt and key check, and rewrites the database so
+    /// text deleted before then does not linger in free pages or the WAL.
+    fn unlock_encryption(
+        conn: &Connection,
+        encryption: Option<&EncryptionConfig>,
+        read_only: bool,
+    ) -> Result<Option<EncryptionKeys>> {
+        let setting = |key: &str| -> Result<Option<String>> {
+            conn.query_row("SELECT value FROM store_settings WHERE key = ?1", params![key], |row| row.get(0))
+                .optional()
+                .map_err(db_error)
+        };
+        let check = setting(KEY_CHECK_SETTING)?;
+        let Some(config) = encryption else {
+            return match check {
+                None => Ok(None),
+                Some(_) => Err(Error::Encryption(format!(
+                    "Database is encrypted; set {} to open it",
+                    crate::crypto::ENCRYPTION_KEY_ENV
+                ))),
+            };
+        };
+
+        let salt = match setting(SALT_SETTING)? {
+            Some(salt) => {
+                Some(hex::decode(salt).map_err(|_| Error::Encryption("Malformed encryption salt".into()))?)
+            }
+            // Databases encrypted before salts were stored get one now;
+            // their text stays readable under the unsalted key
+            None if !read_only => {
+                let salt = crate::crypto::new_salt();
+                conn.execute(
+                    "INSERT INTO store_settings (key, value) VALUES (?1, ?2)",
+                    params![SALT_SETTING, hex::encode(&salt)],
+                )
+                .map_err(db_error)?;
+                Some(salt)
+            }
+            None => None,
+        };
+        let keys = config.unlock(salt.as_deref());
+        if !read_only {
+            // Zero deleted content instead of leaving it in free pages
+            conn.execute_batch("PRAGMA secure_delete = ON").map_err(db_error)?;
+        }
+
+        match check {
+            Some(check) => match keys.decrypt(&check) {
+                Ok(plain) if plain == KEY_CHECK_PLAINTEXT => {
+                    if keys.needs_reencrypt(&check) {
+                        warn!(
+                            "Stored text is under a previous key; run `mindsage reencrypt` to move it to key {}",
+                            keys.key_id()
+                        );
+                    }
+                    Ok(Some(keys))
+                }
+                _ => Err(Error::Encryption(
+                    "Encryption key does not match the key this database was encrypted with".into(),
+                )),
+            },
+            None => {
+                conn.execute(
+                    "INSERT INTO store_settings (key, value) VALUES (?1, ?2)",
+                    params![KEY_CHECK_SETTING, keys.encrypt(KEY_CHECK_PLAINTEXT)],
+                )
+                .map_err(db_error)?;
+                purge_free_pages(conn)?;
+                info!("Encryption enabled with key {}", keys.key_id());
+                Ok(Some(keys))
+            }
+        }
     }
 
-    /// Delete a document and its chunks (cascade).
-    pub fn delete_document(&self, doc_id: i64) -> Result<bool> {
-        let conn = self.conn.lock();
-        let count = conn
-            .execute("DELETE FROM documents WHERE id = ?1", params![doc_id])
-            .map_err(|e| Error::Database(e.to_string()))?;
-        if count > 0 {
-            drop(conn);
+    /// Set the model id of the active embedder. Embeddings from any other
+    /// model are excluded from vector search until re-embedded.
+    pub fn set_embedding_model(&self, model_id: &str) {
+        let mut current = self.embedding_model.write();
+        if *current != model_id {
+            *current = model_id.to_string();
             self.embedding_matrix.lock().dirty = true;
-            Ok(true)
-        } else {
-            Ok(false)
         }
     }
 
-    /// Update (merge) metadata on a document.
-    pub fn update_document_metadata(
-        &self,
-        doc_id: i64,
-        updates: &serde_json::Value,
-    ) -> Result<bool> {
-        let conn = self.conn.lock();
+    /// Model id of the active embedder.
+    pub fn embedding_model(&self) -> String {
+        self.embedding_model.read().clone()
+    }
 
-        let existing_json: Option<String> = conn
-            .prepare_cached("SELECT metadata_json FROM documents WHERE id = ?1")
-            .map_err(|e| Error::Database(e.to_string()))?
-            .query_row(params![doc_id], |row| row.get(0))
-            .optional()
-            .map_err(|e| Error::Database(e.to_string()))?
-            .flatten();
+    /// Tag embeddings stored before models were tracked (model `unknown`)
+    /// with `model_id`, when their length fits the store's dimension, so an
+    /// upgraded database keeps its vectors searchable. Callers pass the id
+    /// of a loaded embedder; rows of another dimension stay `unknown` and
+    /// are re-embedded. Returns how many rows were adopted.
+    #[instrument(level = "debug", skip_all)]
+    pub fn adopt_unknown_embeddings(&self, model_id: &str) -> Result<usize> {
+        if self.read_only || model_id == UNKNOWN_EMBEDDING_MODEL {
+            return Ok(0);
+        }
+        let adopted = {
+            let conn = self.conn.lock();
+            conn.execute(
+                "UPDATE chunk_embeddings SET model_id = ?1 \
+                 WHERE model_id = ?2 AND ((quant_version IN (?3, ?4) AND length(embedding) = ?5) \
+                 OR (quant_version = ?6 AND length(embedding) = ?7))",
+                params![
+                    model_id,
+                    UNKNOWN_EMBEDDING_MODEL,
+                    QuantScheme::Legacy.version(),
+                    QuantScheme::Int8.version(),
+                    QuantScheme::Int8.encoded_len(self.embedding_dim) as i64,
+                    QuantScheme::BlockInt8.version(),
+                    QuantScheme::BlockInt8.encoded_len(self.embedding_dim) as i64,
+                ],
+            )
+            .map_err(db_error)?
+        };
+        if adopted > 0 {
+            self.embedding_matrix.lock().dirty = true;
+        }
+        Ok(adopted)
+    }
 
-        let mut existing: serde_json::Map<String, serde_json::Value> = existing_json
-            .as_deref()
-            .and_then(|s| serde_json::from_str(s).ok())
-            .unwrap_or_default();
+    /// Configure when vector search uses the ANN index.
+    pub fn set_ann_config(&self, config: AnnConfig) {
+        *self.ann_config.write() = config;
+    }
 
-        if let serde_json::Value::Object(map) = updates {
-            for (k, v) in map {
-                existing.insert(k.clone(), v.clone());
+    /// Switch how the embedding matrix is held in memory; safe to call at
+    /// any time, e.g. when the device tier is re-evaluated. Float to
+    /// quantized converts the loaded rows in place; quantized to float
+    /// reloads them from the database so no precision is lost.
+    pub fn set_matrix_mode(&self, mode: MatrixMode) {
+        let mut mat = self.embedding_matrix.lock();
+        if mat.matrix.mode() == mode {
+            return;
+        }
+        match mode {
+            MatrixMode::Quantized => mat.matrix = mat.matrix.to_mode(mode),
+            MatrixMode::Float => {
+                // Keep chunk_ids so the ANN index carries over on reload
+                mat.matrix = VectorRows::new(mode, self.embedding_dim);
+                mat.dirty = true;
             }
         }
+        info!("Embedding matrix mode set to {:?}", mode);
+    }
 
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as i64;
-        existing.insert(
-            "metadata_updated_at".to_string(),
-            serde_json::Value::Number(now.into()),
-        );
+    /// Apply the tier-dependent vector search settings: ANN threshold and
+    /// matrix memory mode.
+    pub fn apply_tier(&self, tier: CapabilityTier) {
+        self.set_ann_config(AnnConfig::for_tier(tier));
+        self.set_matrix_mode(MatrixMode::for_tier(tier));
+    }
 
-        let new_json = serde_json::to_string(&existing).unwrap();
-        let count = conn
-            .execute(
-                "UPDATE documents SET metadata_json = ?1, updated_at = ?2 WHERE id = ?3",
-                params![new_json, now, doc_id],
-            )
-            .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(count > 0)
+    /// Set the quantization format for newly written embeddings. Existing
+    /// rows keep decoding with their own format until requantized.
+    pub fn set_quant_scheme(&self, scheme: QuantScheme) {
+        *self.quant_scheme.write() = scheme;
     }
 
-    /// Count total documents.
-    pub fn count_documents(&self) -> Result<i64> {
-        let conn = self.conn.lock();
-        let count: i64 = conn
-            .query_row("SELECT COUNT(*) FROM documents", [], |row| row.get(0))
-            .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(count)
+    /// Set the BM25 column weights used by full-text search. Invalid
+    /// weights (negative, NaN, or both zero) are ignored with a warning.
+    pub fn set_fts_weights(&self, weights: FtsWeights) {
+        if !weights.is_valid() {
+            warn!("Ignoring invalid FTS weights {:?}", weights);
+            return;
+        }
+        *self.fts_weights.write() = weights;
     }
 
-    /// Get documents with pagination. Returns (docs, total_count).
-    pub fn get_documents_paginated(
-        &self,
-        page: usize,
-        page_size: usize,
-        ascending: bool,
-    ) -> Result<(Vec<Document>, i64)> {
-        let total = self.count_documents()?;
-        let order = if ascending { "ASC" } else { "DESC" };
-        let offset = (page.saturating_sub(1)) * page_size;
+    /// BM25 column weights used by full-text search.
+    pub fn fts_weights(&self) -> FtsWeights {
+        *self.fts_weights.read()
+    }
 
-        let conn = self.conn.lock();
-        let sql = format!(
-            "SELECT * FROM documents ORDER BY created_at {} LIMIT ?1 OFFSET ?2",
-            order
-        );
-        let mut stmt = conn.prepare_cached(&sql).map_err(|e| Error::Database(e.to_string()))?;
-        let rows = stmt
-            .query_map(params![page_size as i64, offset as i64], |row| {
-                Ok(Self::row_to_document(row))
-            })
-            .map_err(|e| Error::Database(e.to_string()))?;
+    /// Set how search hits are post-processed (see `postprocess`). Invalid
+    /// settings are ignored with a warning.
+    pub fn set_search_post_processing(&self, settings: SearchPostProcessing) {
+        if let Err(e) = settings.validate() {
+            warn!("Ignoring invalid search post-processing settings: {}", e);
+            return;
+        }
+        *self.search_post_processing.write() = settings;
+    }
 
-        let docs: Vec<Document> = rows.filter_map(|r| r.ok()).collect();
-        Ok((docs, total))
+    /// How search hits are post-processed.
+    pub fn search_post_processing(&self) -> SearchPostProcessing {
+        *self.search_post_processing.read()
     }
 
-    /// Get all documents.
-    pub fn get_all_documents(&self, ascending: bool) -> Result<Vec<Document>> {
-        let order = if ascending { "ASC" } else { "DESC" };
-        let conn = self.conn.lock();
-        let sql = format!("SELECT * FROM documents ORDER BY created_at {}", order);
-        let mut stmt = conn.prepare(&sql).map_err(|e| Error::Database(e.to_string()))?;
-        let rows = stmt
-            .query_map([], |row| Ok(Self::row_to_document(row)))
-            .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(rows.filter_map(|r| r.ok()).collect())
+    /// Set the factor (0 to 1) applied to the vector score of embeddings
+    /// whose chunk text changed after embedding, until they are re-embedded.
+    /// Weights outside that range are ignored with a warning.
+    pub fn set_text_stale_weight(&self, weight: f32) {
+        if !(0.0..=1.0).contains(&weight) {
+            warn!("Ignoring invalid stale embedding weight {}", weight);
+            return;
+        }
+        *self.text_stale_weight.write() = weight;
     }
 
     // ---------------------------------------------------------------
-    // Chunk CRUD
+    // Document CRUD
     // ---------------------------------------------------------------
 
-    /// Insert a chunk. Returns the new chunk ID.
-    #[allow(clippy::too_many_arguments)]
-    pub fn add_chunk(
-        &self,
-        doc_id: i64,
-        text: &str,
-        chunk_index: i32,
-        level: i32,
-        parent_chunk_id: Option<i64>,
-        char_start: Option<i32>,
-        char_end: Option<i32>,
-        enriched_text: Option<&str>,
-        metadata: Option<&serde_json::Value>,
-        created_at: Option<i64>,
-    ) -> Result<i64> {
-        let now = created_at.unwrap_or_else(|| {
+    /// Insert a document. Returns the new document ID.
+    #[instrument(level = "debug", skip_all)]
+    pub fn add_document(&self, text: &str, opts: AddDocumentOptions) -> Result<i64> {
+        let now = opts.created_at.unwrap_or_else(|| {
             std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_millis() as i64
         });
-        let meta_json = metadata.map(|m| serde_json::to_string(m).unwrap());
+        let meta_json = new_metadata_json(opts.metadata.as_ref())?;
+        let stored_text = self.seal(text);
 
         let conn = self.conn.lock();
-        let id = conn
-            .prepare_cached(
-                "INSERT INTO chunks (doc_id, parent_chunk_id, text, enriched_text, \
-                 chunk_index, char_start, char_end, level, metadata_json, created_at) \
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            )
-            .map_err(|e| Error::Database(e.to_string()))?
-            .insert(params![
-                doc_id,
-                parent_chunk_id,
-                text,
-                enriched_text,
-                chunk_index,
-                char_start,
-                char_end,
-                level,
-                meta_json,
-                now,
-            ])
-            .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(id)
+        insert_document(&conn, &stored_text, meta_json.as_deref(), &opts, now)
     }
 
-    /// Store a quantized embedding for a chunk.
-    pub fn add_chunk_embedding(&self, chunk_id: i64, embedding: &Array1<f32>) -> Result<()> {
-        let (q_bytes, scale, offset) = quantize_uint8(embedding);
-        let conn = self.conn.lock();
-        conn.execute(
-            "INSERT OR REPLACE INTO chunk_embeddings (chunk_id, embedding, scale, offset_val) \
-             VALUES (?1, ?2, ?3, ?4)",
-            params![chunk_id, q_bytes, scale, offset],
-        )
-        .map_err(|e| Error::Database(e.to_string()))?;
-        drop(conn);
-        self.embedding_matrix.lock().dirty = true;
-        Ok(())
+    /// Insert documents with their chunks in one transaction. The first
+    /// hard error rolls back every document of the batch. A duplicate
+    /// content hash is such an error unless `skip_duplicates` is set, in
+    /// which case that document is skipped and the batch continues.
+    /// Chunks are stored without embeddings.
+    #[instrument(level = "debug", skip_all)]
+    pub fn add_documents_transactional(
+        &self,
+        docs: &[NewDocument],
+        skip_duplicates: bool,
+    ) -> std::result::Result<Vec<BatchItemOutcome>, BatchRollback> {
+        let whole = |e: rusqlite::Error| BatchRollback {
+            index: None,
+            error: db_error(e),
+        };
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(whole)?;
+        let mut outcomes = Vec::with_capacity(docs.len());
+        for (index, doc) in docs.iter().enumerate() {
+            // Returning early drops `tx`, which rolls the batch back
+            match self.insert_new_document(&tx, doc) {
+                Ok(doc_id) => outcomes.push(BatchItemOutcome::Added { doc_id }),
+                Err(Error::DuplicateContent(_)) if skip_duplicates => {
+                    outcomes.push(BatchItemOutcome::SkippedDuplicate)
+                }
+                Err(error) => {
+                    return Err(BatchRollback {
+                        index: Some(index),
+                        error,
+                    })
+                }
+            }
+        }
+        tx.commit().map_err(whole)?;
+        Ok(outcomes)
     }
 
-    /// Append a single embedding to the in-memory matrix without full reload.
-    pub fn append_to_matrix(&self, chunk_id: i64, embedding: &Array1<f32>) -> Result<()> {
-        self.ensure_matrix_loaded()?;
+    /// Insert one document of a transactional batch and its chunks.
+    fn insert_new_document(&self, conn: &Connection, doc: &NewDocument) -> Result<i64> {
+        self.insert_document_streaming(conn, &doc.text, &doc.options, doc.chunks.iter().map(Ok))
+    }
 
-        let norm = embedding.dot(embedding).sqrt();
-        if norm < 1e-9 {
-            return Ok(());
-        }
-        let normalized = embedding / norm;
+    fn insert_document_streaming<C: std::borrow::Borrow<NewChunk>>(
+        &self,
+        conn: &Connection,
+        text: &str,
+        options: &AddDocumentOptions,
+        chunks: impl IntoIterator<Item = Result<C>>,
+    ) -> Result<i64> {
+        let now = options.created_at.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64
+        });
+        let meta_json = new_metadata_json(options.metadata.as_ref())?;
+        let doc_id = insert_document(conn, &self.seal(text), meta_json.as_deref(), options, now)?;
 
-        let mut mat = self.embedding_matrix.lock();
-        if mat.matrix.nrows() == 0 {
-            mat.matrix = normalized.insert_axis(ndarray::Axis(0)).to_owned();
-        } else {
-            mat.matrix
-                .push(Axis(0), normalized.view())
-                .map_err(|e| Error::Internal(format!("Matrix append failed: {}", e)))?;
+        self.insert_chunk_tree(conn, doc_id, chunks, now)?;
+        Ok(doc_id)
+    }
+
+    /// Insert planned chunks of `doc_id`, linking paragraphs to their
+    /// sections, and journal the steps that follow chunking. Chunks are
+    /// inserted as `chunks` yields them; the first error stops it.
+    fn insert_chunk_tree<C: std::borrow::Borrow<NewChunk>>(
+        &self,
+        conn: &Connection,
+        doc_id: i64,
+        chunks: impl IntoIterator<Item = Result<C>>,
+        now: i64,
+    ) -> Result<()> {
+        let mut section_ids: HashMap<i32, i64> = HashMap::new();
+        let mut inserted = false;
+        for chunk in chunks {
+            let chunk = chunk?;
+            let chunk = chunk.borrow();
+            let parent_id = chunk.parent_index.and_then(|pi| section_ids.get(&pi).copied());
+            let chunk_id = self.insert_chunk(
+                conn,
+                doc_id,
+                &chunk.text,
+                chunk.chunk_index,
+                chunk.level,
+                parent_id,
+                chunk.char_start,
+                chunk.char_end,
+                None,
+                chunk.metadata.as_ref(),
+                now,
+            )?;
+            if chunk.level == 0 {
+                section_ids.insert(chunk.chunk_index, chunk_id);
+            }
+            inserted = true;
+        }
+        if inserted {
+            record_pending_work(conn, doc_id, now)?;
         }
-        mat.chunk_ids.push(chunk_id);
-        mat.dirty = false;
         Ok(())
     }
 
-    /// Get all chunks for a document.
-    pub fn get_chunks_for_document(&self, doc_id: i64) -> Result<Vec<Chunk>> {
-        let conn = self.conn.lock();
-        let mut stmt = conn
-            .prepare_cached("SELECT * FROM chunks WHERE doc_id = ?1 ORDER BY chunk_index")
-            .map_err(|e| Error::Database(e.to_string()))?;
-        let rows = stmt
-            .query_map(params![doc_id], |row| Ok(Self::row_to_chunk(row)))
-            .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(rows.filter_map(|r| r.ok()).collect())
+    /// Add planned chunks to an existing document that has none, in one
+    /// transaction with their journal entries.
+    #[instrument(level = "debug", skip_all)]
+    pub fn add_document_chunks(&self, doc_id: i64, chunks: &[NewChunk]) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        self.insert_chunk_tree(&tx, doc_id, chunks.iter().map(Ok), now_millis())?;
+        tx.commit().map_err(db_error)
     }
 
-    /// Get a chunk by ID.
-    pub fn get_chunk(&self, chunk_id: i64) -> Result<Option<Chunk>> {
+    /// Insert a document with its chunks and its ingest journal entries in
+    /// one transaction: a crash leaves either all of it or none.
+    #[instrument(level = "debug", skip_all)]
+    pub fn add_document_with_chunks(&self, doc: &NewDocument) -> Result<i64> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        let doc_id = self.insert_new_document(&tx, doc)?;
+        tx.commit().map_err(db_error)?;
+        Ok(doc_id)
+    }
+
+    /// `add_document_with_chunks` with chunks made while they are inserted,
+    /// e.g. by a streaming chunker, so they are not all held at once. An
+    /// error from `chunks` rolls the whole document back.
+    #[instrument(level = "debug", skip_all)]
+    pub fn add_document_with_chunk_stream(
+        &self,
+        text: &str,
+        options: &AddDocumentOptions,
+        chunks: impl IntoIterator<Item = Result<NewChunk>>,
+    ) -> Result<i64> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        let doc_id = self.insert_document_streaming(&tx, text, options, chunks)?;
+        tx.commit().map_err(db_error)?;
+        Ok(doc_id)
+    }
+
+    /// Find a document by content hash.
+    #[instrument(level = "debug", skip_all)]
+    pub fn find_document_by_hash(&self, content_hash: &str) -> Result<Option<Document>> {
         let conn = self.conn.lock();
         let row = conn
-            .prepare_cached("SELECT * FROM chunks WHERE id = ?1")
-            .map_err(|e| Error::Database(e.to_string()))?
-            .query_row(params![chunk_id], |row| Ok(Self::row_to_chunk(row)))
+            .prepare_cached("SELECT * FROM documents WHERE content_hash = ?1")
+            .map_err(db_error)?
+            .query_row(params![content_hash], |row| self.row_to_document(row))
             .optional()
-            .map_err(|e| Error::Database(e.to_string()))?;
+            .map_err(db_error)?;
         Ok(row)
     }
 
-    /// Get the section-level parent of a paragraph chunk.
-    pub fn get_parent_chunk(&self, chunk_id: i64) -> Result<Option<Chunk>> {
-        let chunk = match self.get_chunk(chunk_id)? {
-            Some(c) => c,
-            None => return Ok(None),
-        };
-        match chunk.parent_chunk_id {
-            Some(pid) => self.get_chunk(pid),
-            None => Ok(None),
-        }
-    }
-
-    /// Get sibling chunks (same parent) for context expansion.
-    pub fn get_sibling_chunks(&self, chunk_id: i64) -> Result<Vec<Chunk>> {
-        let chunk = match self.get_chunk(chunk_id)? {
-            Some(c) => c,
-            None => return Ok(Vec::new()),
-        };
-        let parent_id = match chunk.parent_chunk_id {
-            Some(pid) => pid,
-            None => return Ok(Vec::new()),
-        };
+    /// Find the document with `external_id` in `source`.
+    #[instrument(level = "debug", skip_all)]
+    pub fn find_document_by_external_id(&self, source: &str, external_id: &str) -> Result<Option<Document>> {
         let conn = self.conn.lock();
-        let mut stmt = conn
-            .prepare_cached(
-                "SELECT * FROM chunks WHERE parent_chunk_id = ?1 ORDER BY chunk_index",
-            )
-            .map_err(|e| Error::Database(e.to_string()))?;
-        let rows = stmt
-            .query_map(params![parent_id], |row| Ok(Self::row_to_chunk(row)))
-            .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(rows.filter_map(|r| r.ok()).collect())
+        let row = conn
+            .prepare_cached("SELECT * FROM documents WHERE external_source = ?1 AND external_id = ?2")
+            .map_err(db_error)?
+            .query_row(params![source, external_id], |row| self.row_to_document(row))
+            .optional()
+            .map_err(db_error)?;
+        Ok(row)
     }
 
-    /// Update enriched_text for a chunk (triggers FTS re-index via trigger).
-    pub fn update_chunk_enriched_text(&self, chunk_id: i64, enriched_text: &str) -> Result<bool> {
-        let conn = self.conn.lock();
-        let count = conn
+    /// Record `external_id` in `source` on an existing document that has
+    /// none, e.g. one indexed before connectors passed ids. Returns false
+    /// when the document does not exist or already has an external id.
+    #[instrument(level = "debug", skip_all)]
+    pub fn set_external_id(&self, doc_id: i64, source: &str, external_id: &str) -> Result<bool> {
+        let count = self
+            .conn
+            .lock()
             .execute(
-                "UPDATE chunks SET enriched_text = ?1 WHERE id = ?2",
-                params![enriched_text, chunk_id],
+                "UPDATE documents SET external_source = ?1, external_id = ?2 WHERE id = ?3 AND external_id IS NULL",
+                params![source, external_id, doc_id],
             )
-            .map_err(|e| Error::Database(e.to_string()))?;
+            .map_err(db_error)?;
         Ok(count > 0)
     }
 
-    /// Count chunks, optionally filtered by level.
-    pub fn count_chunks(&self, level: Option<i32>) -> Result<i64> {
-        let conn = self.conn.lock();
-        let count: i64 = match level {
-            Some(l) => conn
-                .query_row(
-                    "SELECT COUNT(*) FROM chunks WHERE level = ?1",
-                    params![l],
-                    |row| row.get(0),
-                )
-                .map_err(|e| Error::Database(e.to_string()))?,
-            None => conn
-                .query_row("SELECT COUNT(*) FROM chunks", [], |row| row.get(0))
-                .map_err(|e| Error::Database(e.to_string()))?,
+    /// Insert `doc` as the document with `external_id` in `source`, or, when
+    /// one exists, replace its text, content hash and chunks (with their
+    /// embeddings) like `replace_document_chunks`, merging `doc`'s metadata
+    /// into the stored metadata. The document keeps its id and creation
+    /// time. A document whose content hash already matches is left alone.
+    /// One transaction either way.
+    #[instrument(level = "debug", skip_all)]
+    pub fn upsert_document_by_external_id(
+        &self,
+        source: &str,
+        external_id: &str,
+        doc: &NewDocument,
+    ) -> Result<UpsertOutcome> {
+        let content_hash = doc.options.content_hash.as_deref();
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        let existing: Option<(i64, Option<String>, Option<String>)> = tx
+            .prepare_cached(
+                "SELECT id, content_hash, metadata_json FROM documents WHERE external_source = ?1 AND external_id = ?2",
+            )
+            .map_err(db_error)?
+            .query_row(params![source, external_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .optional()
+            .map_err(db_error)?;
+
+        let Some((doc_id, stored_hash, stored_metadata)) = existing else {
+            let mut doc = doc.clone();
+            doc.options.source = Some(source.to_string());
+            doc.options.external_id = Some(external_id.to_string());
+            let doc_id = self.insert_new_document(&tx, &doc)?;
+            tx.commit().map_err(db_error)?;
+            return Ok(UpsertOutcome::Inserted(doc_id));
         };
-        Ok(count)
+        if content_hash.is_some() && stored_hash.as_deref() == content_hash {
+            return Ok(UpsertOutcome::Unchanged(doc_id));
+        }
+
+        let now = now_millis();
+        let meta_json = match &doc.options.metadata {
+            Some(metadata) => Some(merge_metadata(stored_metadata.as_deref(), metadata, now)?),
+            None => stored_metadata,
+        };
+        tx.execute(
+            "UPDATE documents SET text = ?1, content_hash = ?2, metadata_json = ?3, updated_at = ?4, \
+             metadata_schema = ?5 WHERE id = ?6",
+            params![self.seal(&doc.text), content_hash, meta_json, now, METADATA_SCHEMA_VERSION, doc_id],
+        )
+        .map_err(|e| {
+            if e.to_string().contains("UNIQUE constraint") {
+                Error::DuplicateContent(content_hash.unwrap_or_default().to_string())
+            } else {
+                db_error(e)
+            }
+        })?;
+        sync_doc_topics(&tx, doc_id, meta_json.as_deref())?;
+        tx.execute("DELETE FROM chunks WHERE doc_id = ?1", params![doc_id])
+            .map_err(db_error)?;
+        tx.execute("DELETE FROM doc_centroids WHERE doc_id = ?1", params![doc_id])
+            .map_err(db_error)?;
+        self.insert_chunk_tree(&tx, doc_id, doc.chunks.iter().map(Ok), now)?;
+        tx.commit().map_err(db_error)?;
+        drop(conn);
+        self.embedding_matrix.lock().dirty = true;
+        Ok(UpsertOutcome::Updated(doc_id))
     }
 
-    /// Get chunks that haven't been enriched yet (for pending extraction).
-    pub fn get_chunks_without_enrichment(&self, limit: usize) -> Result<Vec<Chunk>> {
+    /// Content hashes of documents changed at or after `since` (all
+    /// documents when `None`), oldest change first.
+    #[instrument(level = "debug", skip_all)]
+    pub fn list_content_hashes(&self, since: Option<i64>) -> Result<Vec<DocumentHash>> {
         let conn = self.conn.lock();
         let mut stmt = conn
             .prepare_cached(
-                "SELECT * FROM chunks WHERE enriched_text IS NULL AND level = 1 \
-                 ORDER BY created_at ASC LIMIT ?1",
+                "SELECT content_hash, id, COALESCE(updated_at, created_at) AS changed_at FROM documents \
+                 WHERE content_hash IS NOT NULL AND COALESCE(updated_at, created_at) >= ?1 \
+                 ORDER BY changed_at, id",
             )
-            .map_err(|e| Error::Database(e.to_string()))?;
+            .map_err(db_error)?;
         let rows = stmt
-            .query_map(params![limit as i64], |row| Ok(Self::row_to_chunk(row)))
-            .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(rows.filter_map(|r| r.ok()).collect())
+            .query_map(params![since.unwrap_or(i64::MIN)], |row| {
+                Ok(DocumentHash {
+                    content_hash: row.get(0)?,
+                    id: row.get(1)?,
+                    changed_at: row.get(2)?,
+                })
+            })
+            .map_err(db_error)?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(db_error)?;
+        Ok(rows)
     }
 
-    /// Get level=1 chunks that have no embedding stored yet.
-    pub fn get_chunks_without_embedding(&self, limit: usize) -> Result<Vec<Chunk>> {
+    /// Document counts per creation month (`YYYY-MM`), oldest first, with
+    /// months taken in a zone `tz_offset_minutes` east of UTC.
+    #[instrument(level = "debug", skip_all)]
+    pub fn document_month_counts(&self, tz_offset_minutes: i32) -> Result<Vec<DateFacet>> {
         let conn = self.conn.lock();
         let mut stmt = conn
             .prepare_cached(
-                "SELECT c.* FROM chunks c \
-                 LEFT JOIN chunk_embeddings ce ON c.id = ce.chunk_id \
-                 WHERE ce.chunk_id IS NULL AND c.level = 1 \
-                 ORDER BY c.created_at ASC LIMIT ?1",
+                "SELECT strftime('%Y-%m', created_at / 1000 + ?1, 'unixepoch') AS month, COUNT(*) \
+                 FROM documents GROUP BY month ORDER BY month",
             )
-            .map_err(|e| Error::Database(e.to_string()))?;
+            .map_err(db_error)?;
         let rows = stmt
-            .query_map(params![limit as i64], |row| Ok(Self::row_to_chunk(row)))
-            .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(rows.filter_map(|r| r.ok()).collect())
+            .query_map(params![tz_offset_minutes as i64 * 60], |row| {
+                Ok(DateFacet {
+                    month: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })
+            .map_err(db_error)?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(db_error)?;
+        Ok(rows)
     }
 
-    /// Get surrounding paragraph chunks from the same document for context.
-    pub fn get_surrounding_chunks(&self, chunk_id: i64, window: i32) -> Result<Vec<Chunk>> {
-        let chunk = match self.get_chunk(chunk_id)? {
-            Some(c) => c,
-            None => return Ok(Vec::new()),
-        };
+    /// Documents, chunks, embeddings and bytes stored per source, largest
+    /// first. The breakdown scans every table, so it is reused for
+    /// `SOURCE_BREAKDOWN_TTL` after being computed.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_source_breakdown(&self) -> Result<Vec<SourceStats>> {
+        let mut cached = self.source_breakdown.lock();
+        if let Some((at, sources)) = cached.as_ref() {
+            if at.elapsed() < SOURCE_BREAKDOWN_TTL {
+                return Ok(sources.clone());
+            }
+        }
+        let sources = self.compute_source_breakdown()?;
+        *cached = Some((Instant::now(), sources.clone()));
+        Ok(sources)
+    }
+
+    fn compute_source_breakdown(&self) -> Result<Vec<SourceStats>> {
         let conn = self.conn.lock();
         let mut stmt = conn
             .prepare_cached(
-                "SELECT * FROM chunks WHERE doc_id = ?1 AND level = ?2 \
-                 AND chunk_index BETWEEN ?3 AND ?4 ORDER BY chunk_index",
+                "SELECT COALESCE(NULLIF(CAST(CASE WHEN json_valid(d.metadata_json) \
+                   THEN json_extract(d.metadata_json, '$.source') END AS TEXT), ''), 'unknown') AS source, \
+                   COUNT(*), COALESCE(SUM(c.chunks), 0), COALESCE(SUM(c.embeddings), 0), \
+                   SUM(LENGTH(CAST(d.text AS BLOB))) + COALESCE(SUM(c.text_bytes), 0) AS text_bytes, \
+                   COALESCE(SUM(c.embedding_bytes), 0) \
+                 FROM documents d LEFT JOIN ( \
+                   SELECT ch.doc_id, COUNT(*) AS chunks, COUNT(e.chunk_id) AS embeddings, \
+                     SUM(LENGTH(CAST(ch.text AS BLOB)) + COALESCE(LENGTH(CAST(ch.enriched_text AS BLOB)), 0)) AS text_bytes, \
+                     COALESCE(SUM(LENGTH(e.embedding)), 0) AS embedding_bytes \
+                   FROM chunks ch LEFT JOIN chunk_embeddings e ON e.chunk_id = ch.id GROUP BY ch.doc_id \
+                 ) c ON c.doc_id = d.id \
+                 GROUP BY source ORDER BY text_bytes DESC, source",
             )
-            .map_err(|e| Error::Database(e.to_string()))?;
+            .map_err(db_error)?;
         let rows = stmt
-            .query_map(
-                params![
-                    chunk.doc_id,
-                    chunk.level,
-                    chunk.chunk_index - window,
-                    chunk.chunk_index + window
-                ],
-                |row| Ok(Self::row_to_chunk(row)),
-            )
-            .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(rows.filter_map(|r| r.ok()).collect())
+            .query_map([], |row| {
+                Ok(SourceStats {
+                    source: row.get(0)?,
+                    documents: row.get(1)?,
+                    chunks: row.get(2)?,
+                    embeddings: row.get(3)?,
+                    text_bytes: row.get(4)?,
+                    embedding_bytes: row.get(5)?,
+                })
+            })
+            .map_err(db_error)?;
+        self.collect_rows(rows)
     }
 
-    // ---------------------------------------------------------------
-    // BM25 Search (FTS5)
-    // ---------------------------------------------------------------
-
-    /// Full-text search using FTS5 BM25 ranking.
-    pub fn bm25_search(&self, query: &str, level: i32, top_k: usize) -> Result<Vec<SearchHit>> {
-        let fts_query = Self::sanitize_fts_query(query);
-        if fts_query.is_empty() {
-            return Ok(Vec::new());
-        }
-
+    /// Get a document by ID.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_document(&self, doc_id: i64) -> Result<Option<Document>> {
         let conn = self.conn.lock();
-        let sql = "SELECT c.*, chunks_fts.rank AS bm25_score \
-                   FROM chunks_fts \
-                   JOIN chunks c ON c.id = chunks_fts.rowid \
-                   WHERE chunks_fts MATCH ?1 \
-                     AND c.level = ?2 \
-                   ORDER BY chunks_fts.rank \
-                   LIMIT ?3";
+        let row = conn
+            .prepare_cached("SELECT * FROM documents WHERE id = ?1")
+            .map_err(db_error)?
+            .query_row(params![doc_id], |row| self.row_to_document(row))
+            .optional()
+            .map_err(db_error)?;
+        Ok(row)
+    }
 
-        let mut stmt = conn.prepare_cached(sql).map_err(|e| Error::Database(e.to_string()))?;
-        let rows = stmt
-            .query_map(params![fts_query, level, top_k as i64], |row| {
-                let bm25_score: f64 = row.get("bm25_score").unwrap_or(0.0);
-                Ok(SearchHit {
-                    chunk_id: row.get("id")?,
-                    doc_id: row.get("doc_id")?,
-                    text: row.get("text")?,
-                    score: -bm25_score, // FTS5 rank is negative; negate for positive
-                    level: row.get("level")?,
-                    metadata: row
-                        .get::<_, Option<String>>("metadata_json")?
-                        .and_then(|s| serde_json::from_str(&s).ok()),
-                    enriched_text: row.get("enriched_text")?,
-                    parent_chunk_id: row.get("parent_chunk_id")?,
-                    chunk_index: row.get("chunk_index")?,
-                    char_start: row.get("char_start")?,
-                    char_end: row.get("char_end")?,
-                })
-            })
-            .map_err(|e| Error::Database(e.to_string()))?;
+    /// Delete a document and its chunks, whose embeddings go with them
+    /// (cascade). The chunks leave the embedding matrix at once.
+    #[instrument(level = "debug", skip_all)]
+    pub fn delete_document(&self, doc_id: i64) -> Result<bool> {
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(db_error)?;
+        let (deleted, chunk_ids) = Self::delete_document_rows(&tx, doc_id)?;
+        tx.commit().map_err(db_error)?;
+        drop(conn);
+        self.remove_from_matrix(&chunk_ids);
+        Ok(deleted)
+    }
+
+    /// Delete a document's chunks, then the document. Returns whether the
+    /// document existed and the ids of the deleted chunks.
+    fn delete_document_rows(conn: &Connection, doc_id: i64) -> Result<(bool, Vec<i64>)> {
+        let chunk_ids = conn
+            .prepare_cached("DELETE FROM chunks WHERE doc_id = ?1 RETURNING id")
+            .map_err(db_error)?
+            .query_map(params![doc_id], |row| row.get(0))
+            .map_err(db_error)?
+            .collect::<std::result::Result<Vec<i64>, _>>()
+            .map_err(db_error)?;
+        let count = conn
+            .prepare_cached("DELETE FROM documents WHERE id = ?1")
+            .map_err(db_error)?
+            .execute(params![doc_id])
+            .map_err(db_error)?;
+        Ok((count > 0, chunk_ids))
+    }
 
-        Ok(rows.filter_map(|r| r.ok()).collect())
+    /// Replace a document's text and content hash and delete its chunks
+    /// (with their embeddings), so it can be chunked again. Metadata is kept.
+    /// Returns false when the document does not exist.
+    #[instrument(level = "debug", skip_all)]
+    pub fn replace_document_text(&self, doc_id: i64, text: &str, content_hash: Option<&str>) -> Result<bool> {
+        self.replace_document_chunks(doc_id, text, content_hash, &[])
     }
 
-    /// Sanitize a user query for FTS5 MATCH syntax.
-    /// Wraps each token in double quotes and joins with OR.
-    fn sanitize_fts_query(query: &str) -> String {
-        let tokens: Vec<String> = query
-            .split_whitespace()
-            .filter(|t| !t.is_empty())
-            .map(|t| format!("\"{}\"", t.replace('"', "")))
-            .collect();
-        if tokens.is_empty() {
-            return String::new();
+    /// `replace_document_text` that also writes the new `chunks` and their
+    /// ingest journal entries, all in one transaction.
+    #[instrument(level = "debug", skip_all)]
+    pub fn replace_document_chunks(
+        &self,
+        doc_id: i64,
+        text: &str,
+        content_hash: Option<&str>,
+        chunks: &[NewChunk],
+    ) -> Result<bool> {
+        let stored_text = self.seal(text);
+        let now = now_millis();
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(db_error)?;
+        let count = tx
+            .execute(
+                "UPDATE documents SET text = ?1, content_hash = ?2, updated_at = ?3 WHERE id = ?4",
+                params![stored_text, content_hash, now, doc_id],
+            )
+            .map_err(|e| {
+                if e.to_string().contains("UNIQUE constraint") {
+                    Error::DuplicateContent(content_hash.unwrap_or_default().to_string())
+                } else {
+                    db_error(e)
+                }
+            })?;
+        if count == 0 {
+            return Ok(false);
         }
-        tokens.join(" OR ")
+        tx.execute("DELETE FROM chunks WHERE doc_id = ?1", params![doc_id])
+            .map_err(db_error)?;
+        tx.execute("DELETE FROM doc_centroids WHERE doc_id = ?1", params![doc_id])
+            .map_err(db_error)?;
+        self.insert_chunk_tree(&tx, doc_id, chunks.iter().map(Ok), now)?;
+        tx.commit().map_err(db_error)?;
+        drop(conn);
+        self.embedding_matrix.lock().dirty = true;
+        Ok(true)
     }
 
     // ---------------------------------------------------------------
-    // Vector Search
+    // Ingest journal
     // ---------------------------------------------------------------
 
-    /// Load and normalize all chunk embeddings into a matrix for fast search.
-    fn load_embedding_matrix(&self) -> Result<()> {
-        let mut chunk_ids = Vec::new();
-        let mut embeddings: Vec<Array1<f32>> = Vec::new();
-
-        {
-            let conn = self.conn.lock();
-            let mut stmt = conn
-                .prepare(
-                    "SELECT ce.chunk_id, ce.embedding, ce.scale, ce.offset_val \
-                     FROM chunk_embeddings ce \
-                     JOIN chunks c ON c.id = ce.chunk_id \
-                     WHERE c.level = 1",
-                )
-                .map_err(|e| Error::Database(e.to_string()))?;
-
-            let rows = stmt
-                .query_map([], |row| {
-                    let chunk_id: i64 = row.get(0)?;
-                    let blob: Vec<u8> = row.get(1)?;
-                    let scale: f64 = row.get(2)?;
-                    let offset: f64 = row.get(3)?;
-                    Ok((chunk_id, blob, scale as f32, offset as f32))
-                })
-                .map_err(|e| Error::Database(e.to_string()))?;
+    /// Documents owing `step`, in id order after `after_doc_id`.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_pending_work(&self, step: PendingStep, after_doc_id: i64, limit: usize) -> Result<Vec<i64>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached("SELECT doc_id FROM pending_work WHERE step = ?1 AND doc_id > ?2 ORDER BY doc_id LIMIT ?3")
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![step.as_str(), after_doc_id, limit as i64], |row| row.get(0))
+            .map_err(db_error)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(db_error)
+    }
 
-            for row in rows {
-                let (cid, blob, scale, offset) = row.map_err(|e| Error::Database(e.to_string()))?;
-                let emb = dequantize_uint8(&blob, scale, offset);
-                chunk_ids.push(cid);
-                embeddings.push(emb);
-            }
-        } // conn and stmt dropped here
+    /// Remove `step` of `doc_id` from the journal once it is done.
+    pub fn complete_pending_work(&self, doc_id: i64, step: PendingStep) -> Result<()> {
+        self.conn
+            .lock()
+            .prepare_cached("DELETE FROM pending_work WHERE doc_id = ?1 AND step = ?2")
+            .map_err(db_error)?
+            .execute(params![doc_id, step.as_str()])
+            .map_err(db_error)?;
+        Ok(())
+    }
 
-        let mut mat = self.embedding_matrix.lock();
-        if embeddings.is_empty() {
-            mat.matrix = Array2::zeros((0, self.embedding_dim));
-            mat.chunk_ids = Vec::new();
-            mat.dirty = false;
-            return Ok(());
-        }
+    /// Number of documents owing `step`.
+    pub fn count_pending_work(&self, step: PendingStep) -> Result<i64> {
+        self.conn
+            .lock()
+            .prepare_cached("SELECT COUNT(*) FROM pending_work WHERE step = ?1")
+            .map_err(db_error)?
+            .query_row(params![step.as_str()], |row| row.get(0))
+            .map_err(db_error)
+    }
 
-        // Stack into matrix and normalize rows
-        let n = embeddings.len();
-        let dim = self.embedding_dim;
-        let mut matrix = Array2::zeros((n, dim));
-        for (i, emb) in embeddings.iter().enumerate() {
-            matrix.row_mut(i).assign(emb);
-        }
+    /// Number of documents owing `step` after `after_doc_id`.
+    pub fn count_pending_work_after(&self, step: PendingStep, after_doc_id: i64) -> Result<i64> {
+        self.conn
+            .lock()
+            .prepare_cached("SELECT COUNT(*) FROM pending_work WHERE step = ?1 AND doc_id > ?2")
+            .map_err(db_error)?
+            .query_row(params![step.as_str(), after_doc_id], |row| row.get(0))
+            .map_err(db_error)
+    }
 
-        // Normalize rows for cosine similarity via dot product
-        for mut row in matrix.rows_mut() {
-            let norm = row.dot(&row).sqrt();
-            if norm > 1e-9 {
-                row /= norm;
-            }
-        }
+    /// Where the interrupted catch-up pass over `step` stopped, if any.
+    pub fn get_catchup_checkpoint(&self, step: PendingStep) -> Result<Option<CatchupCheckpoint>> {
+        self.conn
+            .lock()
+            .prepare_cached("SELECT after_doc_id, done, started_at FROM catchup_checkpoints WHERE step = ?1")
+            .map_err(db_error)?
+            .query_row(params![step.as_str()], |row| {
+                Ok(CatchupCheckpoint {
+                    step,
+                    after_doc_id: row.get(0)?,
+                    done: row.get::<_, i64>(1)? as usize,
+                    started_at: row.get(2)?,
+                })
+            })
+            .optional()
+            .map_err(db_error)
+    }
 
-        mat.matrix = matrix;
-        mat.chunk_ids = chunk_ids;
-        mat.dirty = false;
-        debug!("Loaded {} embeddings into matrix", n);
+    /// Record how far the catch-up pass over `checkpoint.step` got.
+    pub fn save_catchup_checkpoint(&self, checkpoint: &CatchupCheckpoint) -> Result<()> {
+        self.conn
+            .lock()
+            .prepare_cached(
+                "INSERT INTO catchup_checkpoints (step, after_doc_id, done, started_at, updated_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5) \
+                 ON CONFLICT(step) DO UPDATE SET after_doc_id = excluded.after_doc_id, done = excluded.done, \
+                 started_at = excluded.started_at, updated_at = excluded.updated_at",
+            )
+            .map_err(db_error)?
+            .execute(params![
+                checkpoint.step.as_str(),
+                checkpoint.after_doc_id,
+                checkpoint.done as i64,
+                checkpoint.started_at,
+                now_millis()
+            ])
+            .map_err(db_error)?;
         Ok(())
     }
 
-    fn ensure_matrix_loaded(&self) -> Result<()> {
-        if self.embedding_matrix.lock().dirty {
-            self.load_embedding_matrix()?;
-        }
+    /// Forget the checkpoint of `step` once a pass reached the end.
+    pub fn clear_catchup_checkpoint(&self, step: PendingStep) -> Result<()> {
+        self.conn
+            .lock()
+            .prepare_cached("DELETE FROM catchup_checkpoints WHERE step = ?1")
+            .map_err(db_error)?
+            .execute(params![step.as_str()])
+            .map_err(db_error)?;
         Ok(())
     }
 
-    /// Cosine similarity search using pre-loaded normalized matrix.
-    pub fn vector_search(
+    /// Documents without chunks, in id order after `after_id`:
+    /// ingests torn between the document insert and its chunks by versions
+    /// that did not write them in one transaction.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_documents_without_chunks(&self, after_id: i64, limit: usize) -> Result<Vec<Document>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT * FROM documents d WHERE d.id > ?1 \
+                 AND NOT EXISTS (SELECT 1 FROM chunks c WHERE c.doc_id = d.id) \
+                 ORDER BY d.id LIMIT ?2",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![after_id, limit as i64], |row| self.row_to_document(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Update (merge) metadata on a document. The patch is validated and
+    /// the result normalized (see `metadata`).
+    #[instrument(level = "debug", skip_all)]
+    pub fn update_document_metadata(
         &self,
-        query_embedding: &Array1<f32>,
-        _level: i32,
-        top_k: usize,
-    ) -> Result<Vec<SearchHit>> {
-        self.ensure_matrix_loaded()?;
+        doc_id: i64,
+        updates: &serde_json::Value,
+    ) -> Result<bool> {
+        let conn = self.conn.lock();
 
-        let mat = self.embedding_matrix.lock();
-        if mat.matrix.nrows() == 0 {
-            return Ok(Vec::new());
-        }
+        let existing_json: Option<String> = conn
+            .prepare_cached("SELECT metadata_json FROM documents WHERE id = ?1")
+            .map_err(db_error)?
+            .query_row(params![doc_id], |row| row.get(0))
+            .optional()
+            .map_err(db_error)?
+            .flatten();
 
-        // Normalize query
-        let q_norm = query_embedding.dot(query_embedding).sqrt();
-        if q_norm < 1e-9 {
+        let now = now_millis();
+        let new_json = merge_metadata(existing_json.as_deref(), updates, now)?;
+        let count = conn
+            .execute(
+                "UPDATE documents SET metadata_json = ?1, updated_at = ?2, metadata_schema = ?3 WHERE id = ?4",
+                params![new_json, now, METADATA_SCHEMA_VERSION, doc_id],
+            )
+            .map_err(db_error)?;
+        if count > 0 {
+            sync_doc_topics(&conn, doc_id, Some(&new_json))?;
+        }
+        Ok(count > 0)
+    }
+
+    /// IDs of the documents matching a selector, ascending.
+    #[instrument(level = "debug", skip_all)]
+    pub fn select_documents(&self, selector: &DocumentSelector) -> Result<Vec<i64>> {
+        if selector.is_empty() {
             return Ok(Vec::new());
         }
-        let q = query_embedding / q_norm;
+        let ids_json = (!selector.ids.is_empty()).then(|| serde_json::json!(selector.ids).to_string());
+        let hash_prefix = selector.content_hash_prefix.as_deref().filter(|p| !p.is_empty());
+        let metadata_key = selector.has_metadata.as_deref().filter(|k| !k.is_empty());
 
-        // Matrix multiply: (N, dim) @ (dim,) → (N,)
-        let similarities = mat.matrix.dot(&q);
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT id FROM documents \
+                 WHERE (?1 IS NULL OR id IN (SELECT value FROM json_each(?1))) \
+                   AND (?2 IS NULL OR CASE WHEN json_valid(metadata_json) \
+                        THEN json_extract(metadata_json, '$.source') END = ?2) \
+                   AND (?3 IS NULL OR EXISTS (SELECT 1 FROM json_each( \
+                        CASE WHEN json_valid(metadata_json) THEN metadata_json END, '$.topics') \
+                        WHERE value = ?3)) \
+                   AND (?4 IS NULL OR created_at >= ?4) \
+                   AND (?5 IS NULL OR created_at < ?5) \
+                   AND (?6 IS NULL OR substr(content_hash, 1, length(?6)) = ?6) \
+                   AND (?7 IS NULL OR CASE WHEN json_valid(metadata_json) \
+                        THEN json_type(metadata_json, '$.\"' || ?7 || '\"') END IS NOT NULL) \
+                 ORDER BY id",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(
+                params![
+                    ids_json,
+                    selector.source,
+                    selector.topic,
+                    selector.created_after,
+                    selector.created_before,
+                    hash_prefix,
+                    metadata_key
+                ],
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
 
-        // Get top-k indices
-        let k = top_k.min(similarities.len());
-        let mut indexed: Vec<(usize, f32)> = similarities
-            .iter()
-            .enumerate()
-            .map(|(i, &s)| (i, s))
-            .collect();
-        indexed.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        indexed.truncate(k);
+    /// Delete documents with their chunks and embeddings in transactions
+    /// of `BULK_BATCH_SIZE`. The lock is released between batches, and each
+    /// batch's chunks leave the embedding matrix when it commits.
+    /// `on_batch` receives the number of IDs processed by each batch.
+    #[instrument(level = "debug", skip_all)]
+    pub fn bulk_delete_documents(&self, ids: &[i64], mut on_batch: impl FnMut(usize)) -> Result<usize> {
+        let mut deleted = 0;
+        for batch in ids.chunks(BULK_BATCH_SIZE) {
+            let mut conn = self.conn.lock();
+            let tx = conn
+                .transaction()
+                .map_err(db_error)?;
+            let mut chunk_ids = Vec::new();
+            for &id in batch {
+                let (existed, chunks) = Self::delete_document_rows(&tx, id)?;
+                deleted += existed as usize;
+                chunk_ids.extend(chunks);
+            }
+            tx.commit().map_err(db_error)?;
+            drop(conn);
+            self.remove_from_matrix(&chunk_ids);
+            on_batch(batch.len());
+        }
+        Ok(deleted)
+    }
 
-        let top_chunk_ids: Vec<(i64, f64)> = indexed
-            .iter()
-            .map(|&(i, s)| (mat.chunk_ids[i], s as f64))
-            .collect();
-        drop(mat);
+    /// Merge a metadata patch into many documents, in transactions of
+    /// `BULK_BATCH_SIZE`. Same merge semantics as `update_document_metadata`.
+    #[instrument(level = "debug", skip_all)]
+    pub fn bulk_update_document_metadata(
+        &self,
+        ids: &[i64],
+        updates: &serde_json::Value,
+        mut on_batch: impl FnMut(usize),
+    ) -> Result<usize> {
+        metadata::validate_metadata(updates)?;
+        let now = now_millis();
+        let mut updated = 0;
+        for batch in ids.chunks(BULK_BATCH_SIZE) {
+            let mut conn = self.conn.lock();
+            let tx = conn
+                .transaction()
+                .map_err(db_error)?;
+            {
+                let mut select = tx
+                    .prepare_cached("SELECT metadata_json FROM documents WHERE id = ?1")
+                    .map_err(db_error)?;
+                let mut update = tx
+                    .prepare_cached(
+                        "UPDATE documents SET metadata_json = ?1, updated_at = ?2, metadata_schema = ?3 WHERE id = ?4",
+                    )
+                    .map_err(db_error)?;
+                for id in batch {
+                    let existing: Option<Option<String>> = select
+                        .query_row(params![id], |row| row.get(0))
+                        .optional()
+                        .map_err(db_error)?;
+                    let Some(existing) = existing else { continue };
+                    let new_json = merge_metadata(existing.as_deref(), updates, now)?;
+                    updated += update
+                        .execute(params![new_json, now, METADATA_SCHEMA_VERSION, id])
+                        .map_err(db_error)?;
+                    sync_doc_topics(&tx, *id, Some(&new_json))?;
+                }
+            }
+            tx.commit().map_err(db_error)?;
+            drop(conn);
+            on_batch(batch.len());
+        }
+        Ok(updated)
+    }
 
-        // Fetch chunk data for top hits
-        let mut results = Vec::with_capacity(k);
-        for (cid, score) in top_chunk_ids {
-            if let Some(chunk) = self.get_chunk(cid)? {
-                results.push(SearchHit {
-                    chunk_id: chunk.id,
-                    doc_id: chunk.doc_id,
-                    text: chunk.text,
-                    score,
-                    level: chunk.level,
-                    metadata: chunk.metadata,
-                    enriched_text: chunk.enriched_text,
-                    parent_chunk_id: chunk.parent_chunk_id,
-                    chunk_index: chunk.chunk_index,
-                    char_start: chunk.char_start,
-                    char_end: chunk.char_end,
-                });
+    /// Count documents whose metadata was normalized by an older version
+    /// of the rules, or never.
+    #[instrument(level = "debug", skip_all)]
+    pub fn count_documents_with_stale_metadata(&self) -> Result<i64> {
+        let conn = self.conn.lock();
+        conn.query_row(
+            "SELECT COUNT(*) FROM documents WHERE metadata_schema < ?1",
+            params![METADATA_SCHEMA_VERSION],
+            |row| row.get(0),
+        )
+        .map_err(db_error)
+    }
+
+    /// Normalize the metadata of up to `limit` documents with stale
+    /// metadata after `after_id`, in id order and one transaction, and
+    /// record the current schema version on each. Rows whose metadata is
+    /// not a JSON object are left as they are and listed. Neither
+    /// `updated_at` nor `metadata_updated_at` changes.
+    #[instrument(level = "debug", skip_all)]
+    pub fn normalize_stale_metadata(&self, after_id: i64, limit: usize) -> Result<MetadataBatch> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        let rows: Vec<(i64, Option<String>)> = tx
+            .prepare_cached(
+                "SELECT id, metadata_json FROM documents WHERE id > ?1 AND metadata_schema < ?2 ORDER BY id LIMIT ?3",
+            )
+            .map_err(db_error)?
+            .query_map(params![after_id, METADATA_SCHEMA_VERSION, limit as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .and_then(|rows| rows.collect())
+            .map_err(db_error)?;
+
+        let mut batch = MetadataBatch::default();
+        for (id, json) in rows {
+            batch.last_id = Some(id);
+            batch.scanned += 1;
+            let parsed = json.as_deref().map(serde_json::from_str::<serde_json::Value>);
+            let new_json = match parsed {
+                None => None,
+                Some(Ok(serde_json::Value::Object(mut map))) => {
+                    let changes = metadata::normalize_metadata(&mut map);
+                    (!changes.is_empty()).then(|| {
+                        batch.changed.push((id, changes));
+                        serde_json::to_string(&map).unwrap()
+                    })
+                }
+                Some(_) => {
+                    batch.unreadable.push(id);
+                    None
+                }
+            };
+            if let Some(new_json) = &new_json {
+                tx.execute(
+                    "UPDATE documents SET metadata_json = ?1 WHERE id = ?2",
+                    params![new_json, id],
+                )
+                .map_err(db_error)?;
+                sync_doc_topics(&tx, id, Some(new_json))?;
+            }
+            tx.execute(
+                "UPDATE documents SET metadata_schema = ?1 WHERE id = ?2",
+                params![METADATA_SCHEMA_VERSION, id],
+            )
+            .map_err(db_error)?;
+        }
+        tx.commit().map_err(db_error)?;
+        Ok(batch)
+    }
+
+    /// Count total documents.
+    #[instrument(level = "debug", skip_all)]
+    pub fn count_documents(&self) -> Result<i64> {
+        let conn = self.conn.lock();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM documents", [], |row| row.get(0))
+            .map_err(db_error)?;
+        Ok(count)
+    }
+
+    /// Get documents with pagination. Returns (docs, total_count). Deep
+    /// pages pay for the skipped rows; `get_documents_after` does not.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_documents_paginated(
+        &self,
+        page: usize,
+        page_size: usize,
+        ascending: bool,
+    ) -> Result<(Vec<Document>, i64)> {
+        let total = self.count_documents()?;
+        let order = if ascending { "ASC" } else { "DESC" };
+        let offset = (page.saturating_sub(1)) * page_size;
+
+        let conn = self.conn.lock();
+        let sql = format!(
+            "SELECT * FROM documents ORDER BY created_at {0}, id {0} LIMIT ?1 OFFSET ?2",
+            order
+        );
+        let mut stmt = conn.prepare_cached(&sql).map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![page_size as i64, offset as i64], |row| {
+                self.row_to_document(row)
+            })
+            .map_err(db_error)?;
+
+        let docs: Vec<Document> = self.collect_rows(rows)?;
+        Ok((docs, total))
+    }
+
+    /// Up to `limit` documents following `cursor` (from the start when
+    /// `None`) in `(created_at, id)` order, oldest first when `ascending`.
+    /// Seeks through the `(created_at, id)` index, so every page costs the
+    /// same, and documents added meanwhile never shift the pages.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_documents_after(
+        &self,
+        cursor: Option<DocumentCursor>,
+        limit: usize,
+        ascending: bool,
+    ) -> Result<Vec<Document>> {
+        let sql = match (cursor.is_some(), ascending) {
+            (false, true) => "SELECT * FROM documents ORDER BY created_at, id LIMIT ?3",
+            (false, false) => "SELECT * FROM documents ORDER BY created_at DESC, id DESC LIMIT ?3",
+            (true, true) => {
+                "SELECT * FROM documents WHERE (created_at, id) > (?1, ?2) ORDER BY created_at, id LIMIT ?3"
+            }
+            (true, false) => {
+                "SELECT * FROM documents WHERE (created_at, id) < (?1, ?2) \
+                 ORDER BY created_at DESC, id DESC LIMIT ?3"
+            }
+        };
+        let (created_at, id) = cursor.map_or((0, 0), |c| (c.created_at, c.id));
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare_cached(sql).map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![created_at, id, limit as i64], |row| self.row_to_document(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Iterate over all documents in `(created_at, id)` order, loading
+    /// `batch_size` at a time. The database is not locked between batches;
+    /// documents added during the iteration are seen when they sort after
+    /// the current position, and no document is seen twice.
+    pub fn iter_documents(&self, ascending: bool, batch_size: usize) -> DocumentIter<'_> {
+        DocumentIter {
+            store: self,
+            ascending,
+            batch_size: batch_size.max(1),
+            cursor: None,
+            batch: std::collections::VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Get all documents.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_all_documents(&self, ascending: bool) -> Result<Vec<Document>> {
+        let order = if ascending { "ASC" } else { "DESC" };
+        let conn = self.conn.lock();
+        let sql = format!("SELECT * FROM documents ORDER BY created_at {}", order);
+        let mut stmt = conn.prepare(&sql).map_err(db_error)?;
+        let rows = stmt
+            .query_map([], |row| self.row_to_document(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    // ---------------------------------------------------------------
+    // Chunk CRUD
+    // ---------------------------------------------------------------
+
+    /// Insert a chunk. Returns the new chunk ID.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(level = "debug", skip_all)]
+    pub fn add_chunk(
+        &self,
+        doc_id: i64,
+        text: &str,
+        chunk_index: i32,
+        level: i32,
+        parent_chunk_id: Option<i64>,
+        char_start: Option<i32>,
+        char_end: Option<i32>,
+        enriched_text: Option<&str>,
+        metadata: Option<&serde_json::Value>,
+        created_at: Option<i64>,
+    ) -> Result<i64> {
+        let now = created_at.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64
+        });
+        let conn = self.conn.lock();
+        self.insert_chunk(
+            &conn,
+            doc_id,
+            text,
+            chunk_index,
+            level,
+            parent_chunk_id,
+            char_start,
+            char_end,
+            enriched_text,
+            metadata,
+            now,
+        )
+    }
+
+    /// Insert a chunk row on `conn` (sealing the text when encrypted).
+    #[allow(clippy::too_many_arguments)]
+    fn insert_chunk(
+        &self,
+        conn: &Connection,
+        doc_id: i64,
+        text: &str,
+        chunk_index: i32,
+        level: i32,
+        parent_chunk_id: Option<i64>,
+        char_start: Option<i32>,
+        char_end: Option<i32>,
+        enriched_text: Option<&str>,
+        metadata: Option<&serde_json::Value>,
+        now: i64,
+    ) -> Result<i64> {
+        let meta_json = metadata.map(|m| serde_json::to_string(m).unwrap());
+        let stored_text = self.seal(text);
+        let stored_enriched = enriched_text.map(|e| self.seal(e));
+        let search_text = self.encryption.as_ref().map(|c| c.search_text(text));
+        let search_enriched = self.encryption.as_ref().map(|c| c.search_text(enriched_text.unwrap_or("")));
+
+        let id = conn
+            .prepare_cached(
+                "INSERT INTO chunks (doc_id, parent_chunk_id, text, enriched_text, \
+                 chunk_index, char_start, char_end, level, metadata_json, created_at, \
+                 search_text, search_enriched) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            )
+            .map_err(db_error)?
+            .insert(params![
+                doc_id,
+                parent_chunk_id,
+                stored_text,
+                stored_enriched,
+                chunk_index,
+                char_start,
+                char_end,
+                level,
+                meta_json,
+                now,
+                search_text,
+                search_enriched,
+            ])
+            .map_err(db_error)?;
+        Ok(id)
+    }
+
+    /// Store a quantized embedding for a chunk, tagged with the active model.
+    #[instrument(level = "debug", skip_all)]
+    pub fn add_chunk_embedding(&self, chunk_id: i64, embedding: &Array1<f32>) -> Result<()> {
+        self.check_dimension(embedding)?;
+        let scheme = *self.quant_scheme.read();
+        let (q_bytes, scale, offset) = quantize(embedding, scheme);
+        let model_id = self.embedding_model();
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT OR REPLACE INTO chunk_embeddings \
+             (chunk_id, embedding, scale, offset_val, model_id, quant_version) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![chunk_id, q_bytes, scale, offset, model_id, scheme.version()],
+        )
+        .map_err(db_error)?;
+        // The owning document's centroid is stale; it is rebuilt lazily.
+        conn.execute(
+            "DELETE FROM doc_centroids WHERE doc_id = (SELECT doc_id FROM chunks WHERE id = ?1)",
+            params![chunk_id],
+        )
+        .map_err(db_error)?;
+        drop(conn);
+        self.embedding_matrix.lock().dirty = true;
+        Ok(())
+    }
+
+    /// Add a single embedding of a chunk at `level` to the in-memory
+    /// matrix without full reload. A chunk already in the matrix, such as
+    /// one re-embedded after a text edit, has its row replaced.
+    #[instrument(level = "debug", skip_all)]
+    pub fn append_to_matrix(&self, chunk_id: i64, level: i32, embedding: &Array1<f32>) -> Result<()> {
+        self.check_dimension(embedding)?;
+        self.ensure_matrix_loaded()?;
+
+        let norm = embedding.dot(embedding).sqrt();
+        if norm < 1e-9 {
+            return Ok(());
+        }
+        let normalized = embedding / norm;
+
+        let mut mat = self.embedding_matrix.lock();
+        if let Some(row) = mat.chunk_ids.iter().rposition(|&id| id == chunk_id) {
+            mat.matrix.set_row(row, normalized.view());
+            if let Some(index) = mat.ann.as_mut() {
+                index.reassign(row, normalized.view());
             }
+            mat.levels[row] = level;
+            mat.text_stale[row] = false;
+            mat.dirty = false;
+            return Ok(());
+        }
+        let row = mat.matrix.nrows();
+        if let Some(index) = mat.ann.as_mut() {
+            index.add(row, normalized.view());
+        }
+        mat.matrix.push(normalized.view());
+        mat.chunk_ids.push(chunk_id);
+        mat.levels.push(level);
+        mat.text_stale.push(false);
+        mat.dirty = false;
+        Ok(())
+    }
+
+    /// Drop the rows of deleted chunks from a loaded matrix in place, so a
+    /// search never scores them; the ANN index counts them as deleted. A
+    /// matrix waiting for a reload is left to it.
+    fn remove_from_matrix(&self, chunk_ids: &[i64]) {
+        if chunk_ids.is_empty() {
+            return;
+        }
+        let removed: HashSet<i64> = chunk_ids.iter().copied().collect();
+        let mut guard = self.embedding_matrix.lock();
+        let mat = &mut *guard;
+        if mat.dirty {
+            return;
+        }
+        let keep: Vec<bool> = mat.chunk_ids.iter().map(|id| !removed.contains(id)).collect();
+        if keep.iter().all(|&k| k) {
+            return;
+        }
+        let old_chunk_ids = std::mem::take(&mut mat.chunk_ids);
+        let kept = |i: &usize| keep[*i];
+        mat.chunk_ids = (0..keep.len()).filter(kept).map(|i| old_chunk_ids[i]).collect();
+        mat.levels = (0..keep.len()).filter(kept).map(|i| mat.levels[i]).collect();
+        mat.text_stale = (0..keep.len()).filter(kept).map(|i| mat.text_stale[i]).collect();
+        mat.matrix.retain(&keep);
+        if let Some(index) = mat.ann.take() {
+            mat.ann = Some(index.remap(&old_chunk_ids, &mat.chunk_ids, &mat.matrix));
+        }
+    }
+
+    /// Fail with `DimensionMismatch` (and count it) unless `embedding` has
+    /// the store's dimension.
+    fn check_dimension(&self, embedding: &Array1<f32>) -> Result<()> {
+        if embedding.len() == self.embedding_dim {
+            return Ok(());
+        }
+        self.dimension_mismatches.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Err(Error::DimensionMismatch {
+            expected: self.embedding_dim,
+            actual: embedding.len(),
+        })
+    }
+
+    /// Get all chunks for a document.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_chunks_for_document(&self, doc_id: i64) -> Result<Vec<Chunk>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached("SELECT * FROM chunks WHERE doc_id = ?1 ORDER BY chunk_index")
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![doc_id], |row| self.row_to_chunk(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Get a page of chunks for a document, optionally filtered by level.
+    /// Returns (chunks, total_count) where the count honours the level filter.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_chunks_for_document_paginated(
+        &self,
+        doc_id: i64,
+        page: usize,
+        page_size: usize,
+        level: Option<i32>,
+    ) -> Result<(Vec<Chunk>, i64)> {
+        let offset = (page.saturating_sub(1)) * page_size;
+
+        let conn = self.conn.lock();
+        let total: i64 = conn
+            .prepare_cached(
+                "SELECT COUNT(*) FROM chunks WHERE doc_id = ?1 AND (?2 IS NULL OR level = ?2)",
+            )
+            .map_err(db_error)?
+            .query_row(params![doc_id, level], |row| row.get(0))
+            .map_err(db_error)?;
+
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT * FROM chunks WHERE doc_id = ?1 AND (?2 IS NULL OR level = ?2) \
+                 ORDER BY chunk_index, level, id LIMIT ?3 OFFSET ?4",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(
+                params![doc_id, level, page_size as i64, offset as i64],
+                |row| self.row_to_chunk(row),
+            )
+            .map_err(db_error)?;
+
+        let chunks: Vec<Chunk> = self.collect_rows(rows)?;
+        Ok((chunks, total))
+    }
+
+    /// Count a document's chunks per level. Returns (level, count) pairs ordered by level.
+    #[instrument(level = "debug", skip_all)]
+    pub fn count_chunks_by_level(&self, doc_id: i64) -> Result<Vec<(i32, i64)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT level, COUNT(*) FROM chunks WHERE doc_id = ?1 GROUP BY level ORDER BY level",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![doc_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Get a chunk by ID.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_chunk(&self, chunk_id: i64) -> Result<Option<Chunk>> {
+        #[cfg(test)]
+        self.chunk_queries.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let conn = self.conn.lock();
+        let row = conn
+            .prepare_cached("SELECT * FROM chunks WHERE id = ?1")
+            .map_err(db_error)?
+            .query_row(params![chunk_id], |row| self.row_to_chunk(row))
+            .optional()
+            .map_err(db_error)?;
+        Ok(row)
+    }
+
+    /// Get several chunks in one query, one per entry of `chunk_ids` and in
+    /// that order, so a repeated id is returned again. Missing ids are
+    /// skipped.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_chunks_by_ids(&self, chunk_ids: &[i64]) -> Result<Vec<Chunk>> {
+        if chunk_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        #[cfg(test)]
+        self.chunk_queries.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let ids_json = serde_json::json!(chunk_ids).to_string();
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached("SELECT * FROM chunks WHERE id IN (SELECT value FROM json_each(?1))")
+            .map_err(db_error)?;
+        let by_id: HashMap<i64, Chunk> = stmt
+            .query_map(params![ids_json], |row| self.row_to_chunk(row))
+            .map_err(db_error)?
+            .map(|r| r.map(|c| (c.id, c)))
+            .map(|r| r.map_err(|e| self.row_error(e)))
+            .collect::<Result<_>>()?;
+        Ok(chunk_ids.iter().filter_map(|id| by_id.get(id).cloned()).collect())
+    }
+
+    /// Get the section-level parent of a paragraph chunk.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_parent_chunk(&self, chunk_id: i64) -> Result<Option<Chunk>> {
+        let chunk = match self.get_chunk(chunk_id)? {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        match chunk.parent_chunk_id {
+            Some(pid) => self.get_chunk(pid),
+            None => Ok(None),
+        }
+    }
+
+    /// Get sibling chunks (same parent) for context expansion.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_sibling_chunks(&self, chunk_id: i64) -> Result<Vec<Chunk>> {
+        let chunk = match self.get_chunk(chunk_id)? {
+            Some(c) => c,
+            None => return Ok(Vec::new()),
+        };
+        let parent_id = match chunk.parent_chunk_id {
+            Some(pid) => pid,
+            None => return Ok(Vec::new()),
+        };
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT * FROM chunks WHERE parent_chunk_id = ?1 ORDER BY chunk_index",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![parent_id], |row| self.row_to_chunk(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Update enriched_text for a chunk (triggers FTS re-index via trigger),
+    /// recording the extractor version that produced it.
+    #[instrument(level = "debug", skip_all)]
+    pub fn update_chunk_enriched_text(
+        &self,
+        chunk_id: i64,
+        enriched_text: &str,
+        extraction_version: u32,
+    ) -> Result<bool> {
+        let stored = self.seal(enriched_text);
+        let search_enriched = self.encryption.as_ref().map(|c| c.search_text(enriched_text));
+        let conn = self.conn.lock();
+        let count = conn
+            .execute(
+                "UPDATE chunks SET enriched_text = ?1, search_enriched = ?2, extraction_version = ?3 \
+                 WHERE id = ?4",
+                params![stored, search_enriched, extraction_version, chunk_id],
+            )
+            .map_err(db_error)?;
+        Ok(count > 0)
+    }
+
+    /// Replace a chunk's text (FTS is re-indexed by trigger). Its stored
+    /// embedding still describes the old text, so it is marked
+    /// `text_stale` until the chunk is re-embedded. Returns false when the
+    /// chunk does not exist.
+    #[instrument(level = "debug", skip_all)]
+    pub fn update_chunk_text(&self, chunk_id: i64, text: &str) -> Result<bool> {
+        let Some(chunk) = self.get_chunk(chunk_id)? else {
+            return Ok(false);
+        };
+        if chunk.text == text {
+            return Ok(true);
+        }
+        let stored = self.seal(text);
+        let search_text = self.encryption.as_ref().map(|c| c.search_text(text));
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        tx.execute(
+            "UPDATE chunks SET text = ?1, search_text = ?2 WHERE id = ?3",
+            params![stored, search_text, chunk_id],
+        )
+        .map_err(db_error)?;
+        let stale = tx
+            .execute("UPDATE chunk_embeddings SET text_stale = 1 WHERE chunk_id = ?1", params![chunk_id])
+            .map_err(db_error)?;
+        tx.commit().map_err(db_error)?;
+        drop(conn);
+        if stale > 0 {
+            self.embedding_matrix.lock().dirty = true;
+        }
+        Ok(true)
+    }
+
+    /// Count chunks, optionally filtered by level.
+    #[instrument(level = "debug", skip_all)]
+    pub fn count_chunks(&self, level: Option<i32>) -> Result<i64> {
+        let conn = self.conn.lock();
+        let count: i64 = match level {
+            Some(l) => conn
+                .query_row(
+                    "SELECT COUNT(*) FROM chunks WHERE level = ?1",
+                    params![l],
+                    |row| row.get(0),
+                )
+                .map_err(db_error)?,
+            None => conn
+                .query_row("SELECT COUNT(*) FROM chunks", [], |row| row.get(0))
+                .map_err(db_error)?,
+        };
+        Ok(count)
+    }
+
+    /// Get chunks that haven't been enriched yet (for pending extraction).
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_chunks_without_enrichment(&self, limit: usize) -> Result<Vec<Chunk>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT * FROM chunks WHERE enriched_text IS NULL AND level = 1 \
+                 ORDER BY created_at ASC LIMIT ?1",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![limit as i64], |row| self.row_to_chunk(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Get enriched level=1 chunks extracted by a version older than
+    /// `min_version`, in id order after `after_id`.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_chunks_with_outdated_extraction(
+        &self,
+        min_version: u32,
+        after_id: i64,
+        limit: usize,
+    ) -> Result<Vec<Chunk>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT * FROM chunks \
+                 WHERE enriched_text IS NOT NULL AND extraction_version < ?1 AND level = 1 AND id > ?2 \
+                 ORDER BY id ASC LIMIT ?3",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![min_version, after_id, limit as i64], |row| self.row_to_chunk(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Count enriched level=1 chunks extracted by a version older than `min_version`.
+    #[instrument(level = "debug", skip_all)]
+    pub fn count_outdated_extractions(&self, min_version: u32) -> Result<i64> {
+        let conn = self.conn.lock();
+        conn.query_row(
+            "SELECT COUNT(*) FROM chunks \
+             WHERE enriched_text IS NOT NULL AND extraction_version < ?1 AND level = 1",
+            params![min_version],
+            |row| row.get(0),
+        )
+        .map_err(db_error)
+    }
+
+    /// Get chunks without char offsets (stored before offsets were
+    /// recorded), in id order after `after_id`.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_chunks_without_offsets(&self, after_id: i64, limit: usize) -> Result<Vec<Chunk>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT * FROM chunks WHERE (char_start IS NULL OR char_end IS NULL) AND id > ?1 \
+                 ORDER BY id ASC LIMIT ?2",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![after_id, limit as i64], |row| self.row_to_chunk(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Count chunks without char offsets.
+    #[instrument(level = "debug", skip_all)]
+    pub fn count_chunks_without_offsets(&self) -> Result<i64> {
+        let conn = self.conn.lock();
+        conn.query_row(
+            "SELECT COUNT(*) FROM chunks WHERE char_start IS NULL OR char_end IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(db_error)
+    }
+
+    /// Record where a chunk's text lies in its document. False when the
+    /// chunk does not exist.
+    #[instrument(level = "debug", skip_all)]
+    pub fn set_chunk_offsets(&self, chunk_id: i64, char_start: i32, char_end: i32) -> Result<bool> {
+        let conn = self.conn.lock();
+        let count = conn
+            .execute(
+                "UPDATE chunks SET char_start = ?1, char_end = ?2 WHERE id = ?3",
+                params![char_start, char_end, chunk_id],
+            )
+            .map_err(db_error)?;
+        Ok(count > 0)
+    }
+
+    /// Whether every chunk of a document has char offsets.
+    #[instrument(level = "debug", skip_all)]
+    pub fn document_offsets_complete(&self, doc_id: i64) -> Result<bool> {
+        let conn = self.conn.lock();
+        let missing: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM chunks WHERE doc_id = ?1 AND (char_start IS NULL OR char_end IS NULL))",
+                params![doc_id],
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+        Ok(!missing)
+    }
+
+    /// Get level=1 chunks that have no embedding stored yet.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_chunks_without_embedding(&self, limit: usize) -> Result<Vec<Chunk>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT c.* FROM chunks c \
+                 LEFT JOIN chunk_embeddings ce ON c.id = ce.chunk_id \
+                 WHERE ce.chunk_id IS NULL AND c.level = 1 \
+                 ORDER BY c.created_at ASC LIMIT ?1",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![limit as i64], |row| self.row_to_chunk(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Get level=1 chunks whose embedding came from a model other than the
+    /// active one, in id order after `after_id`.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_chunks_with_stale_embedding(&self, after_id: i64, limit: usize) -> Result<Vec<Chunk>> {
+        let model_id = self.embedding_model();
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT c.* FROM chunks c \
+                 JOIN chunk_embeddings ce ON c.id = ce.chunk_id \
+                 WHERE ce.model_id != ?1 AND c.level = 1 AND c.id > ?2 \
+                 ORDER BY c.id ASC LIMIT ?3",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![model_id, after_id, limit as i64], |row| {
+                self.row_to_chunk(row)
+            })
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Count level=1 embeddings produced by a model other than the active one.
+    #[instrument(level = "debug", skip_all)]
+    pub fn count_stale_embeddings(&self) -> Result<i64> {
+        let model_id = self.embedding_model();
+        let conn = self.conn.lock();
+        conn.query_row(
+            "SELECT COUNT(*) FROM chunk_embeddings ce \
+             JOIN chunks c ON c.id = ce.chunk_id \
+             WHERE ce.model_id != ?1 AND c.level = 1",
+            params![model_id],
+            |row| row.get(0),
+        )
+        .map_err(db_error)
+    }
+
+    /// Get level=1 chunks whose text changed after they were embedded, in
+    /// id order after `after_id`.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_chunks_with_text_stale_embedding(&self, after_id: i64, limit: usize) -> Result<Vec<Chunk>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT c.* FROM chunks c \
+                 JOIN chunk_embeddings ce ON c.id = ce.chunk_id \
+                 WHERE ce.text_stale = 1 AND c.level = 1 AND c.id > ?1 \
+                 ORDER BY c.id ASC LIMIT ?2",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![after_id, limit as i64], |row| self.row_to_chunk(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Get level=1 chunks that need an embedding: those whose text changed
+    /// after they were embedded first, then those never embedded.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_chunks_to_embed(&self, limit: usize) -> Result<Vec<Chunk>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT c.* FROM chunks c \
+                 LEFT JOIN chunk_embeddings ce ON c.id = ce.chunk_id \
+                 WHERE (ce.chunk_id IS NULL OR ce.text_stale = 1) AND c.level = 1 \
+                 ORDER BY ce.chunk_id IS NULL, c.created_at ASC, c.id ASC LIMIT ?1",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![limit as i64], |row| self.row_to_chunk(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Rewrite embeddings stored in another quantization format with the
+    /// active one, in batches. Precision already lost by the old format is
+    /// not recovered; re-embedding does that. Rows in a format this build
+    /// cannot decode are left as they are. Returns rows rewritten.
+    #[instrument(level = "debug", skip_all)]
+    pub fn requantize_embeddings(&self) -> Result<usize> {
+        let scheme = *self.quant_scheme.read();
+        let mut total = 0;
+        let mut last_id = 0;
+        loop {
+            let mut conn = self.conn.lock();
+            let tx = conn.transaction().map_err(db_error)?;
+            let rows: Vec<(i64, Vec<u8>, f64, f64, i64)> = {
+                let mut stmt = tx
+                    .prepare_cached(
+                        "SELECT chunk_id, embedding, scale, offset_val, quant_version \
+                         FROM chunk_embeddings WHERE quant_version != ?1 AND chunk_id > ?2 \
+                         ORDER BY chunk_id LIMIT ?3",
+                    )
+                    .map_err(db_error)?;
+                let rows = stmt
+                    .query_map(params![scheme.version(), last_id, BULK_BATCH_SIZE as i64], |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+                    })
+                    .map_err(db_error)?;
+                self.collect_rows(rows)?
+            };
+
+            let mut rewritten = 0;
+            for (chunk_id, blob, scale, offset, version) in &rows {
+                let Some(emb) = decode_embedding(blob, *scale, *offset, *version) else {
+                    continue;
+                };
+                let (q_bytes, scale, offset) = quantize(&emb, scheme);
+                tx.execute(
+                    "UPDATE chunk_embeddings \
+                     SET embedding = ?2, scale = ?3, offset_val = ?4, quant_version = ?5 \
+                     WHERE chunk_id = ?1",
+                    params![chunk_id, q_bytes, scale, offset, scheme.version()],
+                )
+                .map_err(db_error)?;
+                rewritten += 1;
+            }
+            tx.commit().map_err(db_error)?;
+            total += rewritten;
+
+            // Page past undecodable rows instead of selecting them again
+            match rows.last() {
+                Some((chunk_id, ..)) if rows.len() == BULK_BATCH_SIZE => last_id = *chunk_id,
+                _ => break,
+            }
+        }
+        if total > 0 {
+            self.embedding_matrix.lock().dirty = true;
+        }
+        Ok(total)
+    }
+
+    /// Get the chunk with up to `window` chunks of the same level before and
+    /// after it in its document, in document order. Sections and paragraphs
+    /// share one `chunk_index` sequence, so neighbours are counted rather
+    /// than taken from an index range.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_surrounding_chunks(&self, chunk_id: i64, window: i32) -> Result<Vec<Chunk>> {
+        let chunk = match self.get_chunk(chunk_id)? {
+            Some(c) => c,
+            None => return Ok(Vec::new()),
+        };
+        let window = window.max(0);
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT * FROM ( \
+                     SELECT * FROM (SELECT * FROM chunks WHERE doc_id = ?1 AND level = ?2 AND chunk_index < ?3 \
+                                    ORDER BY chunk_index DESC LIMIT ?4) \
+                     UNION ALL SELECT * FROM chunks WHERE id = ?5 \
+                     UNION ALL \
+                     SELECT * FROM (SELECT * FROM chunks WHERE doc_id = ?1 AND level = ?2 AND chunk_index > ?3 \
+                                    ORDER BY chunk_index LIMIT ?4) \
+                 ) ORDER BY chunk_index",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(
+                params![chunk.doc_id, chunk.level, chunk.chunk_index, window, chunk.id],
+                |row| self.row_to_chunk(row),
+            )
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    // ---------------------------------------------------------------
+    // BM25 Search (FTS5)
+    // ---------------------------------------------------------------
+
+    /// Full-text search using FTS5 BM25 ranking over chunks of `level`, or
+    /// of every level when `None`.
+    pub fn bm25_search(&self, query: &str, level: Option<i32>, top_k: usize) -> Result<Vec<SearchHit>> {
+        self.bm25_search_filtered(query, level, top_k, None, None)
+    }
+
+    /// `bm25_search` restricted to chunks matching `filter` and `filters`,
+    /// applied in the same query. Traced as `bm25_search` either way.
+    #[instrument(name = "bm25_search", level = "debug", skip_all, fields(chunk_level = ?level, top_k = top_k))]
+    pub fn bm25_search_filtered(
+        &self,
+        query: &str,
+        level: Option<i32>,
+        top_k: usize,
+        filter: Option<&ChunkFilter>,
+        filters: Option<&SearchFilters>,
+    ) -> Result<Vec<SearchHit>> {
+        let fts_query = match &self.encryption {
+            // Encrypted chunks are indexed by search tokens, not words
+            Some(config) => config
+                .query_tokens(query)
+                .iter()
+                .map(|t| format!("\"{}\"", t))
+                .collect::<Vec<_>>()
+                .join(" OR "),
+            None => Self::sanitize_fts_query(query),
+        };
+        if fts_query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let weights = self.fts_weights();
+        let (filter_sql, mut values) = chunk_filter_clause(filter, 6)?;
+        let (scope_sql, mut scope_values) = self.search_filter_clause(filters, 6 + values.len());
+        let conn = self.conn.lock();
+        // Explicit bm25() instead of the `rank` column, whose weights are the
+        // table's (equal) defaults
+        let sql = format!(
+            "SELECT c.*, bm25(chunks_fts, ?4, ?5) AS bm25_score \
+             FROM chunks_fts \
+             JOIN chunks c ON c.id = chunks_fts.rowid \
+             WHERE chunks_fts MATCH ?1 \
+               AND (?2 IS NULL OR c.level = ?2){}{} \
+             ORDER BY bm25_score \
+             LIMIT ?3",
+            filter_sql, scope_sql
+        );
+        let mut bound = vec![
+            SqlValue::Text(fts_query),
+            level.map_or(SqlValue::Null, |l| SqlValue::Integer(l as i64)),
+            SqlValue::Integer(top_k as i64),
+            SqlValue::Real(weights.text),
+            SqlValue::Real(weights.enriched),
+        ];
+        bound.append(&mut values);
+        bound.append(&mut scope_values);
+
+        let mut stmt = conn.prepare_cached(&sql).map_err(db_error)?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(bound), |row| {
+                let bm25_score: f64 = row.get("bm25_score").unwrap_or(0.0);
+                Ok(SearchHit {
+                    chunk_id: row.get("id")?,
+                    doc_id: row.get("doc_id")?,
+                    text: self.open_field(row.get("text")?)?,
+                    score: -bm25_score, // FTS5 rank is negative; negate for positive
+                    level: row.get("level")?,
+                    metadata: row
+                        .get::<_, Option<String>>("metadata_json")?
+                        .and_then(|s| serde_json::from_str(&s).ok()),
+                    enriched_text: row
+                        .get::<_, Option<String>>("enriched_text")?
+                        .map(|e| self.open_field(e))
+                        .transpose()?,
+                    parent_chunk_id: row.get("parent_chunk_id")?,
+                    chunk_index: row.get("chunk_index")?,
+                    char_start: row.get("char_start")?,
+                    char_end: row.get("char_end")?,
+                })
+            })
+            .map_err(db_error)?;
+
+        // FTS5 evaluates MATCH on the first step, so a rejected expression
+        // surfaces as the first row's error
+        let mut hits = Vec::new();
+        for row in rows {
+            match row {
+                Ok(hit) => hits.push(hit),
+                Err(e) if is_fts_query_error(&e) => {
+                    warn!("FTS5 rejected the keyword query, returning no BM25 hits: {}", e);
+                    return Ok(Vec::new());
+                }
+                Err(e) => return Err(self.row_error(e)),
+            }
+        }
+        Ok(hits)
+    }
+
+    /// Sanitize a user query for FTS5 MATCH syntax: each whitespace-separated
+    /// token becomes a quoted phrase of its words (`e-mail` → `"e mail"`),
+    /// joined with OR, up to `MAX_FTS_TOKENS` words in all.
+    fn sanitize_fts_query(query: &str) -> String {
+        let mut budget = MAX_FTS_TOKENS;
+        let mut terms = Vec::new();
+        for token in query.split_whitespace() {
+            let words: Vec<&str> = fts_words(token).take(budget).collect();
+            if words.is_empty() {
+                continue;
+            }
+            budget -= words.len();
+            terms.push(format!("\"{}\"", words.join(" ")));
+            if budget == 0 {
+                break;
+            }
+        }
+        terms.join(" OR ")
+    }
+
+    /// FTS5 expression matching `text` as a phrase; encrypted chunks are
+    /// indexed by search tokens, so there it matches all of the tokens.
+    fn fts_phrase(&self, text: &str) -> String {
+        match &self.encryption {
+            Some(config) => config
+                .query_tokens(text)
+                .iter()
+                .map(|t| format!("\"{}\"", t))
+                .collect::<Vec<_>>()
+                .join(" AND "),
+            None => {
+                let words: Vec<&str> = fts_words(text).take(MAX_FTS_TOKENS).collect();
+                if words.is_empty() {
+                    String::new()
+                } else {
+                    format!("\"{}\"", words.join(" "))
+                }
+            }
+        }
+    }
+
+    /// `AND` conditions for `filters` on chunks aliased `c`, with their
+    /// parameters numbered from `first_param`.
+    fn search_filter_clause(&self, filters: Option<&SearchFilters>, first_param: usize) -> (String, Vec<SqlValue>) {
+        let Some(filters) = filters else {
+            return (String::new(), Vec::new());
+        };
+        let mut sql = String::new();
+        let mut values: Vec<SqlValue> = Vec::new();
+        let param = |values: &mut Vec<SqlValue>, value: SqlValue| {
+            values.push(value);
+            format!("?{}", first_param + values.len() - 1)
+        };
+        let lowered = |list: &[String]| list.iter().map(|v| SqlValue::Text(v.to_lowercase())).collect::<Vec<_>>();
+
+        if filters.scopes_documents() {
+            let mut conditions = Vec::new();
+            let field = |key: &str| {
+                format!(
+                    "LOWER(json_extract(CASE WHEN json_valid(d.metadata_json) THEN d.metadata_json END, '$.{}'))",
+                    key
+                )
+            };
+            for (key, list) in [("source", &filters.sources), ("lang", &filters.langs)] {
+                if !list.is_empty() {
+                    let placeholders: Vec<String> = lowered(list).into_iter().map(|v| param(&mut values, v)).collect();
+                    conditions.push(format!("{} IN ({})", field(key), placeholders.join(", ")));
+                }
+            }
+            if !filters.topics.is_empty() {
+                let placeholders: Vec<String> =
+                    lowered(&filters.topics).into_iter().map(|v| param(&mut values, v)).collect();
+                conditions.push(format!(
+                    "EXISTS (SELECT 1 FROM doc_topics t WHERE t.doc_id = d.id AND LOWER(t.topic) IN ({}))",
+                    placeholders.join(", ")
+                ));
+            }
+            if !filters.tags.is_empty() {
+                // Stored tags are already lowercase (`normalize_tag`)
+                let placeholders: Vec<String> =
+                    lowered(&filters.tags).into_iter().map(|v| param(&mut values, v)).collect();
+                conditions.push(format!(
+                    "EXISTS (SELECT 1 FROM doc_tags g WHERE g.doc_id = d.id AND g.tag IN ({}))",
+                    placeholders.join(", ")
+                ));
+            }
+            if !filters.collections.is_empty() {
+                let placeholders: Vec<String> = filters
+                    .collections
+                    .iter()
+                    .map(|&id| param(&mut values, SqlValue::Integer(id)))
+                    .collect();
+                conditions.push(format!(
+                    "EXISTS (SELECT 1 FROM doc_collections m WHERE m.doc_id = d.id AND m.collection_id IN ({}))",
+                    placeholders.join(", ")
+                ));
+            }
+            if !filters.filenames.is_empty() {
+                let likes: Vec<String> = filters
+                    .filenames
+                    .iter()
+                    .map(|name| {
+                        let pattern = format!("%{}%", escape_like(&name.to_lowercase()));
+                        format!("{} LIKE {} ESCAPE '\\'", field("filename"), param(&mut values, SqlValue::Text(pattern)))
+                    })
+                    .collect();
+                conditions.push(format!("({})", likes.join(" OR ")));
+            }
+            if let Some(after) = filters.created_after {
+                conditions.push(format!("d.created_at >= {}", param(&mut values, SqlValue::Integer(after))));
+            }
+            if let Some(before) = filters.created_before {
+                conditions.push(format!("d.created_at < {}", param(&mut values, SqlValue::Integer(before))));
+            }
+            sql.push_str(&format!(
+                " AND c.doc_id IN (SELECT d.id FROM documents d WHERE {})",
+                conditions.join(" AND ")
+            ));
+        }
+
+        for phrase in &filters.phrases {
+            let expr = self.fts_phrase(phrase);
+            if !expr.is_empty() {
+                sql.push_str(&format!(
+                    " AND c.id IN (SELECT rowid FROM chunks_fts WHERE chunks_fts MATCH {})",
+                    param(&mut values, SqlValue::Text(expr))
+                ));
+            }
+        }
+        let excluded: Vec<String> = filters
+            .excluded
+            .iter()
+            .map(|e| self.fts_phrase(e))
+            .filter(|e| !e.is_empty())
+            .map(|e| format!("({})", e))
+            .collect();
+        if !excluded.is_empty() {
+            sql.push_str(&format!(
+                " AND c.id NOT IN (SELECT rowid FROM chunks_fts WHERE chunks_fts MATCH {})",
+                param(&mut values, SqlValue::Text(excluded.join(" OR ")))
+            ));
+        }
+        (sql, values)
+    }
+
+    // ---------------------------------------------------------------
+    // Vector Search
+    // ---------------------------------------------------------------
+
+    /// Load and normalize the active model's chunk embeddings into a matrix
+    /// for fast search.
+    fn load_embedding_matrix(&self) -> Result<()> {
+        let mut chunk_ids = Vec::new();
+        let mut levels = Vec::new();
+        let mut text_stale = Vec::new();
+        let mode = self.embedding_matrix.lock().matrix.mode();
+        let mut matrix = VectorRows::new(mode, self.embedding_dim);
+        let model_id = self.embedding_model();
+
+        {
+            let conn = self.conn.lock();
+            let mut stmt = conn
+                .prepare(
+                    "SELECT ce.chunk_id, ce.embedding, ce.scale, ce.offset_val, ce.quant_version, c.level, \
+                     ce.text_stale \
+                     FROM chunk_embeddings ce \
+                     JOIN chunks c ON c.id = ce.chunk_id \
+                     WHERE ce.model_id = ?1",
+                )
+                .map_err(db_error)?;
+
+            let rows = stmt
+                .query_map(params![model_id], |row| {
+                    let chunk_id: i64 = row.get(0)?;
+                    let blob: Vec<u8> = row.get(1)?;
+                    let scale: f64 = row.get(2)?;
+                    let offset: f64 = row.get(3)?;
+                    let version: i64 = row.get(4)?;
+                    let level: i32 = row.get(5)?;
+                    let stale: bool = row.get(6)?;
+                    Ok((chunk_id, level, stale, decode_embedding(&blob, scale, offset, version)))
+                })
+                .map_err(db_error)?;
+
+            for row in rows {
+                let (cid, level, stale, emb) = row.map_err(db_error)?;
+                match emb {
+                    Some(mut emb) if emb.len() == self.embedding_dim => {
+                        // Normalize rows for cosine similarity via dot product
+                        let norm = emb.dot(&emb).sqrt();
+                        if norm > 1e-9 {
+                            emb /= norm;
+                        }
+                        chunk_ids.push(cid);
+                        levels.push(level);
+                        text_stale.push(stale);
+                        matrix.push(emb.view());
+                    }
+                    _ => debug!("Skipping undecodable embedding for chunk {}", cid),
+                }
+            }
+        } // conn and stmt dropped here
+
+        let mut mat = self.embedding_matrix.lock();
+        if matrix.is_empty() {
+            mat.matrix = matrix;
+            mat.chunk_ids = Vec::new();
+            mat.levels = Vec::new();
+            mat.text_stale = Vec::new();
+            mat.ann = None;
+            mat.dirty = false;
+            return Ok(());
+        }
+
+        let n = matrix.nrows();
+        let previous = mat.ann.take();
+        mat.ann = self.attach_ann_index(previous, &mat.chunk_ids, &matrix, &chunk_ids, &model_id);
+        mat.matrix = matrix;
+        mat.chunk_ids = chunk_ids;
+        mat.levels = levels;
+        mat.text_stale = text_stale;
+        mat.dirty = false;
+        debug!("Loaded {} embeddings into matrix", n);
+        Ok(())
+    }
+
+    fn ann_index_path(&self) -> PathBuf {
+        self.db_path.with_file_name(ANN_INDEX_FILE)
+    }
+
+    /// Carry the ANN index over to a freshly loaded matrix, falling back to
+    /// the sidecar file when none is in memory.
+    fn attach_ann_index(
+        &self,
+        previous: Option<IvfIndex>,
+        old_chunk_ids: &[i64],
+        matrix: &VectorRows,
+        chunk_ids: &[i64],
+        model_id: &str,
+    ) -> Option<IvfIndex> {
+        if !self.ann_config.read().enabled {
+            return None;
+        }
+        match previous {
+            Some(index) if index.model_id() == model_id => {
+                Some(index.remap(old_chunk_ids, chunk_ids, matrix))
+            }
+            _ => match IvfIndex::load(&self.ann_index_path(), model_id, chunk_ids, matrix) {
+                Ok(index) => index,
+                Err(e) => {
+                    warn!("Ignoring ANN index file: {}", e);
+                    None
+                }
+            },
+        }
+    }
+
+    /// Build the ANN index over a copy of the matrix and save it next to
+    /// the database. The k-means pass runs without the matrix lock, so
+    /// searches carry on meanwhile; the index is then attached to the rows
+    /// present by the time it is done. Returns false when another build is
+    /// already running.
+    fn build_ann_index(&self) -> bool {
+        let Some(_building) = self.ann_build.try_lock() else {
+            return false;
+        };
+        let (matrix, chunk_ids) = {
+            let mat = self.embedding_matrix.lock();
+            (mat.matrix.clone(), mat.chunk_ids.clone())
+        };
+        let start = std::time::Instant::now();
+        let index = IvfIndex::build(&matrix, &self.embedding_model());
+        if self.read_only {
+            debug!("Read-only store: ANN index kept in memory only");
+        } else if let Err(e) = index.save(&self.ann_index_path(), &chunk_ids) {
+            warn!("Failed to save ANN index: {}", e);
+        }
+        info!(
+            "Built ANN index: {} rows in {} lists ({}ms)",
+            index.len(),
+            index.nlist(),
+            start.elapsed().as_millis()
+        );
+
+        let mut guard = self.embedding_matrix.lock();
+        let mat = &mut *guard;
+        mat.ann = Some(index.remap(&chunk_ids, &mat.chunk_ids, &mat.matrix));
+        true
+    }
+
+    /// Keep the ANN index in step with the store: build it once the matrix
+    /// reaches the threshold, rebuild it once deletions pass
+    /// `REBUILD_DELETED_FRACTION`, and drop it when the store has shrunk to
+    /// half the threshold. Called after indexing and by consolidation, never
+    /// on the query path; searches stay brute force until the index is
+    /// ready. Returns whether an index was built.
+    #[instrument(level = "debug", skip_all)]
+    pub fn maintain_ann_index(&self) -> Result<bool> {
+        self.ensure_matrix_loaded()?;
+        let config = *self.ann_config.read();
+        let build = {
+            let mut mat = self.embedding_matrix.lock();
+            if !config.enabled || mat.matrix.nrows() < config.threshold / 2 {
+                if mat.ann.take().is_some() && !self.read_only {
+                    let _ = std::fs::remove_file(self.ann_index_path());
+                }
+                return Ok(false);
+            }
+            match &mat.ann {
+                Some(index) => index.deleted_fraction() > REBUILD_DELETED_FRACTION,
+                None => mat.matrix.nrows() >= config.threshold,
+            }
+        };
+        Ok(build && self.build_ann_index())
+    }
+
+    fn ensure_matrix_loaded(&self) -> Result<()> {
+        if self.embedding_matrix.lock().dirty {
+            self.load_embedding_matrix()?;
+        }
+        Ok(())
+    }
+
+    /// Cosine similarity search using pre-loaded normalized matrix.
+    #[instrument(level = "debug", skip_all, fields(chunk_level = ?level, top_k = top_k))]
+    pub fn vector_search(
+        &self,
+        query_embedding: &Array1<f32>,
+        level: Option<i32>,
+        top_k: usize,
+    ) -> Result<Vec<SearchHit>> {
+        self.vector_search_limited(query_embedding, level, top_k, None, None)
+    }
+
+    /// Ids of the chunks at `level` (any when `None`) matching `filter`
+    /// and `filters`.
+    fn filtered_chunk_ids(
+        &self,
+        level: Option<i32>,
+        filter: Option<&ChunkFilter>,
+        filters: Option<&SearchFilters>,
+    ) -> Result<HashSet<i64>> {
+        let (filter_sql, mut values) = chunk_filter_clause(filter, 2)?;
+        let (scope_sql, mut scope_values) = self.search_filter_clause(filters, 2 + values.len());
+        let mut bound = vec![level.map_or(SqlValue::Null, |l| SqlValue::Integer(l as i64))];
+        bound.append(&mut values);
+        bound.append(&mut scope_values);
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(&format!(
+                "SELECT c.id FROM chunks c WHERE (?1 IS NULL OR c.level = ?1){}{}",
+                filter_sql, scope_sql
+            ))
+            .map_err(db_error)?;
+        let ids = stmt
+            .query_map(rusqlite::params_from_iter(bound), |row| row.get(0))
+            .map_err(db_error)?
+            .collect::<std::result::Result<HashSet<i64>, _>>()
+            .map_err(db_error)?;
+        Ok(ids)
+    }
+
+    /// Vector search that scores at most `row_limit` rows: the newest rows
+    /// on the brute-force path, or a proportionally smaller `nprobe` on the
+    /// ANN path. With `allowed`, only those chunks are scored, by brute
+    /// force. Rows of another level than `level` are skipped.
+    fn vector_search_limited(
+        &self,
+        query_embedding: &Array1<f32>,
+        level: Option<i32>,
+        top_k: usize,
+        row_limit: Option<usize>,
+        allowed: Option<&HashSet<i64>>,
+    ) -> Result<Vec<SearchHit>> {
+        // A query from a different model cannot be scored against the matrix
+        if let Err(e) = self.check_dimension(query_embedding) {
+            warn!("Vector search skipped: {}", e);
+            return Ok(Vec::new());
+        }
+        self.ensure_matrix_loaded()?;
+
+        let mat = self.embedding_matrix.lock();
+        if mat.matrix.nrows() == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Normalize query
+        let q_norm = query_embedding.dot(query_embedding).sqrt();
+        if q_norm < 1e-9 {
+            return Ok(Vec::new());
+        }
+        let q = query_embedding / q_norm;
+        let k = top_k.min(mat.matrix.nrows());
+
+        // Large stores go through the ANN index once `maintain_ann_index`
+        // has built it; until then they are scanned in full
+        let config = *self.ann_config.read();
+        let use_ann = allowed.is_none() && config.enabled && mat.matrix.nrows() >= config.threshold;
+
+        let rows = mat.matrix.nrows();
+        let limit = row_limit.unwrap_or(rows).min(rows);
+        let at_level = |i: usize| level.map_or(true, |l| mat.levels[i] == l);
+        let stale_weight = *self.text_stale_weight.read();
+        let weigh = |indexed: &mut Vec<(usize, f32)>| {
+            if stale_weight < 1.0 {
+                for (i, score) in indexed.iter_mut() {
+                    if mat.text_stale[*i] {
+                        *score *= stale_weight;
+                    }
+                }
+            }
+            indexed.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        };
+        let indexed = match mat.ann.as_ref().filter(|_| use_ann) {
+            Some(index) => {
+                let nprobe = (config.nprobe * limit / rows).max(1);
+                let mut indexed = index.search(&mat.matrix, q.view(), k, nprobe, at_level);
+                weigh(&mut indexed);
+                indexed
+            }
+            None => {
+                // Allowed chunks are scored row by row; otherwise the newest
+                // `limit` rows in one product: (limit, dim) @ (dim,) → (limit,)
+                let start = rows - limit;
+                let mut indexed: Vec<(usize, f32)> = if let Some(allowed) = allowed {
+                    (start..rows)
+                        .filter(|&i| allowed.contains(&mat.chunk_ids[i]))
+                        .map(|i| (i, mat.matrix.row_dot(i, q.view())))
+                        .collect()
+                } else {
+                    let scores = mat.matrix.dot_rows(start, rows, q.view());
+                    (start..rows).zip(scores).filter(|&(i, _)| at_level(i)).collect()
+                };
+                weigh(&mut indexed);
+                indexed.truncate(k);
+                indexed
+            }
+        };
+
+        let top_chunk_ids: Vec<(i64, f64)> = indexed
+            .iter()
+            .map(|&(i, s)| (mat.chunk_ids[i], s as f64))
+            .collect();
+        drop(mat);
+
+        // Fetch chunk data for top hits in one query
+        let ids: Vec<i64> = top_chunk_ids.iter().map(|&(cid, _)| cid).collect();
+        let mut chunks: HashMap<i64, Chunk> = self
+            .get_chunks_by_ids(&ids)?
+            .into_iter()
+            .map(|c| (c.id, c))
+            .collect();
+        let mut results = Vec::with_capacity(k);
+        for (cid, score) in top_chunk_ids {
+            if let Some(chunk) = chunks.remove(&cid) {
+                results.push(SearchHit {
+                    chunk_id: chunk.id,
+                    doc_id: chunk.doc_id,
+                    text: chunk.text,
+                    score,
+                    level: chunk.level,
+                    metadata: chunk.metadata,
+                    enriched_text: chunk.enriched_text,
+                    parent_chunk_id: chunk.parent_chunk_id,
+                    chunk_index: chunk.chunk_index,
+                    char_start: chunk.char_start,
+                    char_end: chunk.char_end,
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    // ---------------------------------------------------------------
+    // Reciprocal Rank Fusion
+    // ---------------------------------------------------------------
+
+    /// Fuse BM25 and vector search results using Reciprocal Rank Fusion.
+    /// RRF score = sum(1 / (k + rank)) across result lists.
+    pub fn reciprocal_rank_fusion(
+        bm25_results: &[SearchHit],
+        vector_results: &[SearchHit],
+        k: usize,
+    ) -> Vec<SearchHit> {
+        let mut rrf_scores: HashMap<i64, f64> = HashMap::new();
+        let mut chunk_map: HashMap<i64, &SearchHit> = HashMap::new();
+
+        for (rank, hit) in bm25_results.iter().enumerate() {
+            *rrf_scores.entry(hit.chunk_id).or_insert(0.0) +=
+                1.0 / (k as f64 + rank as f64 + 1.0);
+            chunk_map.entry(hit.chunk_id).or_insert(hit);
+        }
+
+        for (rank, hit) in vector_results.iter().enumerate() {
+            *rrf_scores.entry(hit.chunk_id).or_insert(0.0) +=
+                1.0 / (k as f64 + rank as f64 + 1.0);
+            chunk_map.entry(hit.chunk_id).or_insert(hit);
+        }
+
+        let mut sorted: Vec<(i64, f64)> = rrf_scores.into_iter().collect();
+        sorted.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        sorted
+            .into_iter()
+            .filter_map(|(cid, score)| {
+                chunk_map.get(&cid).map(|hit| SearchHit {
+                    chunk_id: hit.chunk_id,
+                    doc_id: hit.doc_id,
+                    text: hit.text.clone(),
+                    score,
+                    level: hit.level,
+                    metadata: hit.metadata.clone(),
+                    enriched_text: hit.enriched_text.clone(),
+                    parent_chunk_id: hit.parent_chunk_id,
+                    chunk_index: hit.chunk_index,
+                    char_start: hit.char_start,
+                    char_end: hit.char_end,
+                })
+            })
+            .collect()
+    }
+
+    // ---------------------------------------------------------------
+    // Hybrid Search (BM25 + Vector → RRF)
+    // ---------------------------------------------------------------
+
+    /// `hybrid_search` under a latency budget. Each stage is timed in its
+    /// own tracing span. When the time left after BM25 will not cover a
+    /// full vector scan (estimated from earlier scans), the vector stage
+    /// scores only the rows that fit, or is skipped when less than
+    /// `MIN_TRUNCATED_VECTOR_SHARE` of the scan fits. Degradations are
+    /// reported in the diagnostics. A `filter` is pushed into both stages.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(level = "debug", skip_all, fields(chunk_level = ?level, bm25_top_k = bm25_top_k, vector_top_k = vector_top_k))]
+    pub fn hybrid_search_within(
+        &self,
+        query: &str,
+        query_embedding: &Array1<f32>,
+        level: Option<i32>,
+        bm25_top_k: usize,
+        vector_top_k: usize,
+        rrf_k: usize,
+        budget: Duration,
+        filter: Option<&ChunkFilter>,
+        filters: Option<&SearchFilters>,
+    ) -> Result<(Vec<SearchHit>, SearchDiagnostics)> {
+        let start = Instant::now();
+        let mut diagnostics = SearchDiagnostics {
+            budget_ms: Some(budget.as_millis() as u64),
+            ..Default::default()
+        };
+
+        let bm25_hits = timed_stage(SearchStage::Bm25, &mut diagnostics, || {
+            self.bm25_search_filtered(query, level, bm25_top_k, filter, filters)
+        })?;
+        let filters = filters.filter(|f| !f.is_empty());
+        let allowed = if filter.is_some() || filters.is_some() {
+            Some(self.filtered_chunk_ids(level, filter, filters)?)
+        } else {
+            None
+        };
+
+        self.ensure_matrix_loaded()?;
+        let rows = self.embedding_matrix.lock().matrix.nrows();
+        let remaining = budget.saturating_sub(start.elapsed());
+        let estimate = self
+            .vector_ns_per_row
+            .lock()
+            .map(|ns| Duration::from_nanos((ns * rows as f64) as u64));
+        let share = match estimate {
+            _ if remaining.is_zero() => 0.0,
+            Some(estimate) if estimate > remaining => remaining.as_secs_f64() / estimate.as_secs_f64(),
+            _ => 1.0,
+        };
+
+        let vector_hits = if let Err(e) = self.check_dimension(query_embedding) {
+            diagnostics.degradations.push(Degradation::VectorDimensionMismatch);
+            diagnostics.vector_rows_scanned = Some(0);
+            warn!("Vector stage skipped: {}", e);
+            Vec::new()
+        } else if share < MIN_TRUNCATED_VECTOR_SHARE {
+            diagnostics.degradations.push(Degradation::VectorSkipped);
+            diagnostics.vector_rows_scanned = Some(0);
+            debug!("Vector stage skipped: {}ms of budget left", remaining.as_millis());
+            Vec::new()
+        } else {
+            let row_limit = (share < 1.0).then_some((rows as f64 * share) as usize);
+            if row_limit.is_some() {
+                diagnostics.degradations.push(Degradation::VectorTruncated);
+            }
+            diagnostics.vector_rows_scanned = Some(row_limit.unwrap_or(rows));
+            let vector_start = Instant::now();
+            let hits = timed_stage(SearchStage::Vector, &mut diagnostics, || {
+                self.vector_search_limited(query_embedding, level, vector_top_k, row_limit, allowed.as_ref())
+            })?;
+            if row_limit.is_none() && allowed.is_none() && rows > 0 {
+                self.record_vector_cost(vector_start.elapsed(), rows);
+            }
+            hits
+        };
+
+        let fused = timed_stage(SearchStage::Fusion, &mut diagnostics, || {
+            Self::reciprocal_rank_fusion(&bm25_hits, &vector_hits, rrf_k)
+        });
+
+        let elapsed = start.elapsed();
+        diagnostics.elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        diagnostics.over_budget = elapsed > budget;
+        Ok((fused, diagnostics))
+    }
+
+    /// Fold a full vector scan into the per-row cost average.
+    fn record_vector_cost(&self, elapsed: Duration, rows: usize) {
+        let sample = elapsed.as_nanos() as f64 / rows as f64;
+        let mut cost = self.vector_ns_per_row.lock();
+        *cost = Some(match *cost {
+            Some(avg) => avg * 0.8 + sample * 0.2,
+            None => sample,
+        });
+    }
+
+    /// Combined BM25 + vector search with RRF fusion.
+    #[instrument(level = "debug", skip_all, fields(chunk_level = ?level, bm25_top_k = bm25_top_k, vector_top_k = vector_top_k))]
+    pub fn hybrid_search(
+        &self,
+        query: &str,
+        query_embedding: &Array1<f32>,
+        level: Option<i32>,
+        bm25_top_k: usize,
+        vector_top_k: usize,
+        rrf_k: usize,
+    ) -> Result<Vec<SearchHit>> {
+        self.hybrid_search_filtered(query, query_embedding, level, bm25_top_k, vector_top_k, rrf_k, None)
+    }
+
+    /// `hybrid_search` within the documents and text scopes of `filters`,
+    /// pushed into both stages.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(level = "debug", skip_all, fields(chunk_level = ?level, bm25_top_k = bm25_top_k, vector_top_k = vector_top_k))]
+    pub fn hybrid_search_filtered(
+        &self,
+        query: &str,
+        query_embedding: &Array1<f32>,
+        level: Option<i32>,
+        bm25_top_k: usize,
+        vector_top_k: usize,
+        rrf_k: usize,
+        filters: Option<&SearchFilters>,
+    ) -> Result<Vec<SearchHit>> {
+        let bm25_hits = self.bm25_search_filtered(query, level, bm25_top_k, None, filters)?;
+        let vector_hits = match filters {
+            Some(filters) => {
+                let allowed = self.filtered_chunk_ids(level, None, Some(filters))?;
+                self.vector_search_limited(query_embedding, level, vector_top_k, None, Some(&allowed))?
+            }
+            None => self.vector_search(query_embedding, level, vector_top_k)?,
+        };
+        Ok(Self::reciprocal_rank_fusion(&bm25_hits, &vector_hits, rrf_k))
+    }
+
+    // ---------------------------------------------------------------
+    // Document Similarity
+    // ---------------------------------------------------------------
+
+    /// Embeddings of the given chunks from the active model, by chunk id.
+    /// Chunks without one are left out.
+    pub fn get_chunk_embeddings(&self, chunk_ids: &[i64]) -> Result<HashMap<i64, Array1<f32>>> {
+        if chunk_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let model_id = self.embedding_model();
+        let ids_json = serde_json::json!(chunk_ids).to_string();
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT chunk_id, embedding, scale, offset_val, quant_version FROM chunk_embeddings \
+                 WHERE chunk_id IN (SELECT value FROM json_each(?1)) AND model_id = ?2",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![ids_json, model_id], |row| {
+                let chunk_id: i64 = row.get(0)?;
+                let blob: Vec<u8> = row.get(1)?;
+                let scale: f64 = row.get(2)?;
+                let offset: f64 = row.get(3)?;
+                let version: i64 = row.get(4)?;
+                Ok(decode_embedding(&blob, scale, offset, version).map(|emb| (chunk_id, emb)))
+            })
+            .map_err(db_error)?;
+        Ok(self.collect_rows::<Vec<_>, _>(rows)?.into_iter().flatten().collect())
+    }
+
+    /// Compute the centroid of a document's paragraph embeddings from the
+    /// active model: the mean of the normalized embeddings, re-normalized.
+    /// Returns None when the document has no such embeddings.
+    fn compute_doc_centroid(&self, doc_id: i64) -> Result<Option<(Array1<f32>, usize)>> {
+        let model_id = self.embedding_model();
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT ce.embedding, ce.scale, ce.offset_val, ce.quant_version \
+                 FROM chunk_embeddings ce \
+                 JOIN chunks c ON c.id = ce.chunk_id \
+                 WHERE c.doc_id = ?1 AND c.level = 1 AND ce.model_id = ?2",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![doc_id, model_id], |row| {
+                let blob: Vec<u8> = row.get(0)?;
+                let scale: f64 = row.get(1)?;
+                let offset: f64 = row.get(2)?;
+                let version: i64 = row.get(3)?;
+                Ok(decode_embedding(&blob, scale, offset, version))
+            })
+            .map_err(db_error)?;
+
+        let mut sum = Array1::<f32>::zeros(self.embedding_dim);
+        let mut count = 0usize;
+        for emb in self.collect_rows::<Vec<_>, _>(rows)?.into_iter().flatten() {
+            let norm = emb.dot(&emb).sqrt();
+            if norm < 1e-9 || emb.len() != self.embedding_dim {
+                continue;
+            }
+            sum += &(emb / norm);
+            count += 1;
+        }
+
+        let norm = sum.dot(&sum).sqrt();
+        if count == 0 || norm < 1e-9 {
+            return Ok(None);
+        }
+        Ok(Some((sum / norm, count)))
+    }
+
+    /// Compute and cache centroids for every document that has embeddings
+    /// but no cached centroid, and drop centroids of deleted documents.
+    /// Returns the number of centroids (re)built.
+    #[instrument(level = "debug", skip_all)]
+    pub fn refresh_doc_centroids(&self) -> Result<usize> {
+        let model_id = self.embedding_model();
+        let stale: Vec<i64> = {
+            let conn = self.conn.lock();
+            conn.execute(
+                "DELETE FROM doc_centroids WHERE doc_id NOT IN (SELECT id FROM documents)",
+                [],
+            )
+            .map_err(db_error)?;
+            let mut stmt = conn
+                .prepare_cached(
+                    "SELECT DISTINCT c.doc_id FROM chunks c \
+                     JOIN chunk_embeddings ce ON ce.chunk_id = c.id \
+                     LEFT JOIN doc_centroids dc ON dc.doc_id = c.doc_id \
+                     WHERE dc.doc_id IS NULL AND c.level = 1 AND ce.model_id = ?1",
+                )
+                .map_err(db_error)?;
+            let rows = stmt
+                .query_map(params![model_id], |row| row.get(0))
+                .map_err(db_error)?;
+            self.collect_rows(rows)?
+        };
+
+        let mut refreshed = 0;
+        for doc_id in stale {
+            if self.refresh_doc_centroid(doc_id)?.is_some() {
+                refreshed += 1;
+            }
+        }
+        if refreshed > 0 {
+            debug!("Refreshed {} document centroids", refreshed);
+        }
+        Ok(refreshed)
+    }
+
+    /// Compute and cache the centroid of one document, e.g. after its
+    /// chunks were embedded. Returns it, or None when the document has no
+    /// embeddings from the active model.
+    #[instrument(level = "debug", skip_all)]
+    pub fn refresh_doc_centroid(&self, doc_id: i64) -> Result<Option<Array1<f32>>> {
+        let Some((centroid, count)) = self.compute_doc_centroid(doc_id)? else {
+            return Ok(None);
+        };
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT OR REPLACE INTO doc_centroids (doc_id, centroid, chunk_count, updated_at) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![doc_id, f32_to_bytes(&centroid), count as i64, now_millis()],
+        )
+        .map_err(db_error)?;
+        Ok(Some(centroid))
+    }
+
+    /// Find the documents most similar to `doc_id`, excluding itself.
+    ///
+    /// Uses centroid-vs-centroid cosine similarity over the cached
+    /// centroids, which consolidation and embedding keep up to date; only
+    /// the queried document's is computed when missing (and cached unless the
+    /// store is read-only). Documents without
+    /// embeddings fall back to a BM25 search over their most frequent terms.
+    #[instrument(level = "debug", skip_all)]
+    pub fn find_similar_documents(&self, doc_id: i64, top_k: usize) -> Result<Vec<SimilarDocument>> {
+        let doc = self
+            .get_document(doc_id)?
+            .ok_or_else(|| Error::NotFound(format!("Document {}", doc_id)))?;
+
+        let centroids: Vec<(i64, Array1<f32>)> = {
+            let conn = self.conn.lock();
+            let mut stmt = conn
+                .prepare_cached("SELECT doc_id, centroid FROM doc_centroids")
+                .map_err(db_error)?;
+            let rows = stmt
+                .query_map([], |row| {
+                    let id: i64 = row.get(0)?;
+                    let blob: Vec<u8> = row.get(1)?;
+                    Ok((id, bytes_to_f32(&blob)))
+                })
+                .map_err(db_error)?;
+            self.collect_rows(rows)?
+        };
+
+        let cached = centroids
+            .iter()
+            .find(|(id, _)| *id == doc_id)
+            .map(|(_, c)| c.clone());
+        let query = match cached {
+            Some(q) => Some(q),
+            // A read-only store computes it without caching it
+            None if self.read_only => self.compute_doc_centroid(doc_id)?.map(|(centroid, _)| centroid),
+            None => self.refresh_doc_centroid(doc_id)?,
+        };
+        let query = match query {
+            Some(q) => q,
+            None => return self.similar_documents_by_terms(doc_id, &doc.text, top_k),
+        };
+
+        let mut scored: Vec<(i64, f64)> = centroids
+            .iter()
+            .filter(|(id, c)| *id != doc_id && c.len() == query.len())
+            .map(|(id, c)| (*id, c.dot(&query) as f64))
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored.truncate(top_k);
+
+        let mut results = Vec::with_capacity(scored.len());
+        for (id, score) in scored {
+            if let Some(other) = self.get_document(id)? {
+                results.push(SimilarDocument {
+                    doc_id: id,
+                    score,
+                    method: "centroid".to_string(),
+                    metadata: other.metadata,
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    /// BM25 fallback for [`find_similar_documents`](Self::find_similar_documents):
+    /// search for the document's most frequent terms and rank other documents
+    /// by their best-matching chunk.
+    fn similar_documents_by_terms(&self, doc_id: i64, text: &str, top_k: usize) -> Result<Vec<SimilarDocument>> {
+        const TOP_TERMS: usize = 12;
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| w.chars().count() > 3)
+        {
+            *counts.entry(word.to_lowercase()).or_insert(0) += 1;
+        }
+        let mut terms: Vec<(String, usize)> = counts.into_iter().collect();
+        terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let query = terms
+            .into_iter()
+            .take(TOP_TERMS)
+            .map(|(t, _)| t)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let hits = self.bm25_search(&query, Some(1), top_k.saturating_mul(10).max(20))?;
+        let mut best: Vec<(i64, f64)> = Vec::new();
+        for hit in hits.into_iter().filter(|h| h.doc_id != doc_id) {
+            match best.iter_mut().find(|(id, _)| *id == hit.doc_id) {
+                Some(entry) => entry.1 = entry.1.max(hit.score),
+                None => best.push((hit.doc_id, hit.score)),
+            }
+        }
+        best.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        best.truncate(top_k);
+
+        let mut results = Vec::with_capacity(best.len());
+        for (id, score) in best {
+            if let Some(other) = self.get_document(id)? {
+                results.push(SimilarDocument {
+                    doc_id: id,
+                    score,
+                    method: "bm25".to_string(),
+                    metadata: other.metadata,
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    // ---------------------------------------------------------------
+    // Context Expansion
+    // ---------------------------------------------------------------
+
+    /// Get the section-level parent text for context around a chunk.
+    #[instrument(level = "debug", skip_all)]
+    pub fn expand_to_parent_context(&self, chunk_id: i64) -> Result<Option<String>> {
+        Ok(self.get_parent_chunk(chunk_id)?.map(|c| c.text))
+    }
+
+    // ---------------------------------------------------------------
+    // Indexed files
+    // ---------------------------------------------------------------
+
+    /// Record (or replace) the indexed state of a file. Paths of the
+    /// indexed-file methods are stored and matched in their canonical form
+    /// (`paths::normalize_path`), so either separator finds the record.
+    #[instrument(level = "debug", skip_all)]
+    pub fn upsert_indexed_file(&self, file: &IndexedFile) -> Result<()> {
+        let path = normalize_path(&file.path);
+        let conn = self.conn.lock();
+        conn.prepare_cached(
+            "INSERT OR REPLACE INTO indexed_files (path, mtime, size, content_hash, doc_id, indexed_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )
+        .map_err(db_error)?
+        .execute(params![path, file.mtime, file.size, file.content_hash, file.doc_id, file.indexed_at])
+        .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// The indexed state recorded for `path`, if any.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_indexed_file(&self, path: &str) -> Result<Option<IndexedFile>> {
+        let conn = self.conn.lock();
+        let row = conn
+            .prepare_cached("SELECT * FROM indexed_files WHERE path = ?1")
+            .map_err(db_error)?
+            .query_row(params![normalize_path(path)], Self::row_to_indexed_file)
+            .optional()
+            .map_err(db_error)?;
+        Ok(row)
+    }
+
+    /// Insert records that are not present yet, in one transaction: either
+    /// all of them are imported or none. Returns the number inserted.
+    #[instrument(level = "debug", skip_all)]
+    pub fn import_indexed_files(&self, files: &[IndexedFile]) -> Result<usize> {
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(db_error)?;
+        let mut inserted = 0;
+        {
+            let mut stmt = tx
+                .prepare_cached(
+                    "INSERT OR IGNORE INTO indexed_files \
+                     (path, mtime, size, content_hash, doc_id, indexed_at) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )
+                .map_err(db_error)?;
+            for file in files {
+                inserted += stmt
+                    .execute(params![normalize_path(&file.path), file.mtime, file.size, file.content_hash, file.doc_id, file.indexed_at])
+                    .map_err(db_error)?;
+            }
+        }
+        tx.commit().map_err(db_error)?;
+        Ok(inserted)
+    }
+
+    /// Forget files whose document has since been deleted, so they show as
+    /// not indexed and can be imported again. Returns the number cleared.
+    #[instrument(level = "debug", skip_all)]
+    pub fn reconcile_indexed_files(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "DELETE FROM indexed_files \
+             WHERE doc_id IS NOT NULL AND doc_id NOT IN (SELECT id FROM documents)",
+            [],
+        )
+        .map_err(db_error)
+    }
+
+    /// Indexed files whose path starts with `dir`.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_indexed_files_under(&self, dir: &str) -> Result<Vec<IndexedFile>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached("SELECT * FROM indexed_files WHERE substr(path, 1, length(?1)) = ?1 ORDER BY path")
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![normalize_path(dir)], Self::row_to_indexed_file)
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Forget the indexed state of `path`. Returns whether a record existed.
+    #[instrument(level = "debug", skip_all)]
+    pub fn delete_indexed_file(&self, path: &str) -> Result<bool> {
+        let conn = self.conn.lock();
+        let deleted = conn
+            .execute("DELETE FROM indexed_files WHERE path = ?1", params![normalize_path(path)])
+            .map_err(db_error)?;
+        Ok(deleted > 0)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub fn count_indexed_files(&self) -> Result<i64> {
+        let conn = self.conn.lock();
+        conn.query_row("SELECT COUNT(*) FROM indexed_files", [], |row| row.get(0))
+            .map_err(db_error)
+    }
+
+    fn row_to_indexed_file(row: &rusqlite::Row<'_>) -> rusqlite::Result<IndexedFile> {
+        Ok(IndexedFile {
+            path: row.get("path")?,
+            mtime: row.get("mtime")?,
+            size: row.get("size")?,
+            content_hash: row.get("content_hash")?,
+            doc_id: row.get("doc_id")?,
+            indexed_at: row.get("indexed_at")?,
+        })
+    }
+
+    // ---------------------------------------------------------------
+    // Stats
+    // ---------------------------------------------------------------
+
+    /// Bytes the in-memory embedding matrix holds, without the queries
+    /// `get_stats` runs.
+    pub fn matrix_memory_bytes(&self) -> usize {
+        self.embedding_matrix.lock().matrix.memory_bytes()
+    }
+
+    /// Get store statistics.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_stats(&self) -> Result<StoreStats> {
+        let doc_count = self.count_documents()?;
+        let chunk_count = self.count_chunks(None)?;
+        let para_count = self.count_chunks(Some(1))?;
+        let section_count = self.count_chunks(Some(0))?;
+
+        let conn = self.conn.lock();
+        let emb_count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM chunk_embeddings", [], |row| {
+                row.get(0)
+            })
+            .map_err(db_error)?;
+        let embeddings_by_model: BTreeMap<String, i64> = {
+            let mut stmt = conn
+                .prepare_cached("SELECT model_id, COUNT(*) FROM chunk_embeddings GROUP BY model_id")
+                .map_err(db_error)?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(db_error)?;
+            self.collect_rows(rows)?
+        };
+        let text_stale_embeddings: i64 = conn
+            .query_row("SELECT COUNT(*) FROM chunk_embeddings WHERE text_stale = 1", [], |row| row.get(0))
+            .map_err(db_error)?;
+        let encryption = match &self.encryption {
+            Some(config) => {
+                let prefix = format!("{}%", config.current_prefix());
+                let rows_pending: i64 = conn
+                    .query_row(
+                        "SELECT (SELECT COUNT(*) FROM documents WHERE text NOT LIKE ?1) + \
+                         (SELECT COUNT(*) FROM chunks WHERE text NOT LIKE ?1 \
+                          OR (enriched_text IS NOT NULL AND enriched_text NOT LIKE ?1))",
+                        params![prefix],
+                        |row| row.get(0),
+                    )
+                    .map_err(db_error)?;
+                Some(EncryptionStats {
+                    key_id: config.key_id().to_string(),
+                    search: config.search(),
+                    rows_pending,
+                })
+            }
+            None => None,
+        };
+        drop(conn);
+
+        let db_size = std::fs::metadata(&self.db_path)
+            .map(|m| m.len())
+            .unwrap_or(0);
+
+        let mat = self.embedding_matrix.lock();
+        let matrix_rows = mat.matrix.nrows();
+        let matrix_loaded = matrix_rows > 0;
+        let matrix_mode = mat.matrix.mode();
+        let matrix_bytes = mat.matrix.memory_bytes();
+
+        Ok(StoreStats {
+            total_documents: doc_count,
+            total_chunks: chunk_count,
+            paragraph_chunks: para_count,
+            section_chunks: section_count,
+            embeddings_stored: emb_count,
+            embeddings_by_model,
+            text_stale_embeddings,
+            embedding_model: self.embedding_model(),
+            embedding_dimension: self.embedding_dim,
+            db_path: self.db_path.to_string_lossy().to_string(),
+            db_size_mb: db_size as f64 / (1024.0 * 1024.0),
+            matrix_loaded,
+            matrix_rows,
+            matrix_mode,
+            matrix_bytes,
+            encryption,
+            corrupt_rows: self.corrupt_rows.load(std::sync::atomic::Ordering::Relaxed),
+            dimension_mismatches: self.dimension_mismatches.load(std::sync::atomic::Ordering::Relaxed),
+        })
+    }
+
+    /// Copy the write-ahead log into the database file and truncate it.
+    /// Run at shutdown so a large `-wal` file is not left behind.
+    #[instrument(level = "debug", skip_all)]
+    pub fn checkpoint(&self) -> Result<()> {
+        let conn = self.conn.lock();
+        let busy: i64 = conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))
+            .map_err(db_error)?;
+        if busy != 0 {
+            return Err(Error::Database("WAL checkpoint blocked by another connection".to_string()));
+        }
+        Ok(())
+    }
+
+    // ---------------------------------------------------------------
+    // Encryption
+    // ---------------------------------------------------------------
+
+    /// Encrypt plaintext rows and re-encrypt rows under a previous key with
+    /// the active key, in transactions of `BULK_BATCH_SIZE`. Search tokens
+    /// are rewritten with them. The lock is released between batches;
+    /// `on_batch` receives the number of rows each batch rewrote.
+    #[instrument(level = "debug", skip_all)]
+    pub fn reencrypt(&self, mut on_batch: impl FnMut(usize)) -> Result<usize> {
+        let Some(config) = &self.encryption else {
+            return Err(Error::Encryption("No encryption key configured".into()));
+        };
+        let prefix = format!("{}%", config.current_prefix());
+        let open = |stored: String| config.decrypt(&stored);
+        let mut rewritten = 0;
+
+        loop {
+            let mut conn = self.conn.lock();
+            let tx = conn
+                .transaction()
+                .map_err(db_error)?;
+            let docs: Vec<(i64, String)> = {
+                let mut stmt = tx
+                    .prepare_cached("SELECT id, text FROM documents WHERE text NOT LIKE ?1 LIMIT ?2")
+                    .map_err(db_error)?;
+                let rows = stmt
+                    .query_map(params![prefix, BULK_BATCH_SIZE as i64], |row| {
+                        Ok((row.get(0)?, row.get(1)?))
+                    })
+                    .map_err(db_error)?;
+                self.collect_rows(rows)?
+            };
+            for (id, text) in &docs {
+                tx.execute(
+                    "UPDATE documents SET text = ?1 WHERE id = ?2",
+                    params![config.encrypt(&open(text.clone())?), id],
+                )
+                .map_err(db_error)?;
+            }
+
+            let remaining = BULK_BATCH_SIZE - docs.len();
+            let chunks: Vec<(i64, String, Option<String>)> = {
+                let mut stmt = tx
+                    .prepare_cached(
+                        "SELECT id, text, enriched_text FROM chunks \
+                         WHERE text NOT LIKE ?1 \
+                            OR (enriched_text IS NOT NULL AND enriched_text NOT LIKE ?1) \
+                         LIMIT ?2",
+                    )
+                    .map_err(db_error)?;
+                let rows = stmt
+                    .query_map(params![prefix, remaining as i64], |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                    })
+                    .map_err(db_error)?;
+                self.collect_rows(rows)?
+            };
+            for (id, text, enriched) in &chunks {
+                let text = open(text.clone())?;
+                let enriched = enriched.clone().map(open).transpose()?;
+                tx.execute(
+                    "UPDATE chunks SET text = ?1, enriched_text = ?2, search_text = ?3, \
+                     search_enriched = ?4 WHERE id = ?5",
+                    params![
+                        config.encrypt(&text),
+                        enriched.as_deref().map(|e| config.encrypt(e)),
+                        config.search_text(&text),
+                        config.search_text(enriched.as_deref().unwrap_or("")),
+                        id
+                    ],
+                )
+                .map_err(db_error)?;
+            }
+
+            let batch = docs.len() + chunks.len();
+            if batch == 0 {
+                // Later opens must use the active key
+                tx.execute(
+                    "UPDATE store_settings SET value = ?1 WHERE key = ?2",
+                    params![config.encrypt(KEY_CHECK_PLAINTEXT), KEY_CHECK_SETTING],
+                )
+                .map_err(db_error)?;
+                tx.commit().map_err(db_error)?;
+                break;
+            }
+            tx.commit().map_err(db_error)?;
+            drop(conn);
+            rewritten += batch;
+            on_batch(batch);
+        }
+        // The old values are gone from the tables, not yet from the file
+        purge_free_pages(&self.conn.lock())?;
+
+        info!("Re-encrypted {} rows with key {}", rewritten, config.key_id());
+        Ok(rewritten)
+    }
+
+    // ---------------------------------------------------------------
+    // Topics
+    // ---------------------------------------------------------------
+
+    /// Populate `doc_topics` from document metadata when the table is empty.
+    /// Returns the number of (topic, document) rows inserted.
+    #[instrument(level = "debug", skip_all)]
+    pub fn backfill_doc_topics(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+        let existing: i64 = conn
+            .query_row("SELECT COUNT(*) FROM doc_topics", [], |row| row.get(0))
+            .map_err(db_error)?;
+        if existing > 0 {
+            return Ok(0);
+        }
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO doc_topics (topic, doc_id) \
+                 SELECT t.value, d.id FROM documents d, \
+                      json_each(CASE WHEN json_valid(d.metadata_json) \
+                          AND json_type(d.metadata_json, '$.topics') = 'array' \
+                          THEN d.metadata_json END, '$.topics') t \
+                 WHERE t.type = 'text'",
+                [],
+            )
+            .map_err(db_error)?;
+        Ok(inserted)
+    }
+
+    /// All topics with their document counts, most common first.
+    #[instrument(level = "debug", skip_all)]
+    pub fn list_topics(&self) -> Result<Vec<(String, i64)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT topic, COUNT(*) AS n FROM doc_topics GROUP BY topic ORDER BY n DESC, topic ASC",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Documents tagged with a topic, newest first. Returns (docs, total_count).
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_documents_by_topic_paginated(
+        &self,
+        topic: &str,
+        page: usize,
+        page_size: usize,
+    ) -> Result<(Vec<Document>, i64)> {
+        let offset = page.saturating_sub(1) * page_size;
+        let conn = self.conn.lock();
+        let total: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM doc_topics WHERE topic = ?1",
+                params![topic],
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT d.* FROM doc_topics t JOIN documents d ON d.id = t.doc_id \
+                 WHERE t.topic = ?1 ORDER BY d.created_at DESC, d.id DESC LIMIT ?2 OFFSET ?3",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![topic, page_size as i64, offset as i64], |row| {
+                self.row_to_document(row)
+            })
+            .map_err(db_error)?;
+        Ok((self.collect_rows(rows)?, total))
+    }
+
+    /// Document/chunk counts, date range, and top entities for a topic.
+    /// Returns None when no document carries the topic.
+    ///
+    /// Entities are read from the `entities:` section of chunk
+    /// `enriched_text` (see `mindsage_ingest::build_enriched_text`).
+    #[instrument(level = "debug", skip_all)]
+    pub fn topic_stats(&self, topic: &str, top_entities: usize) -> Result<Option<TopicStats>> {
+        let conn = self.conn.lock();
+        let (doc_count, first, last): (i64, Option<i64>, Option<i64>) = conn
+            .query_row(
+                "SELECT COUNT(*), MIN(d.created_at), MAX(d.created_at) \
+                 FROM doc_topics t JOIN documents d ON d.id = t.doc_id WHERE t.topic = ?1",
+                params![topic],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(db_error)?;
+        if doc_count == 0 {
+            return Ok(None);
+        }
+        let chunk_count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM doc_topics t JOIN chunks c ON c.doc_id = t.doc_id WHERE t.topic = ?1",
+                params![topic],
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT c.enriched_text FROM doc_topics t JOIN chunks c ON c.doc_id = t.doc_id \
+                 WHERE t.topic = ?1 AND c.enriched_text IS NOT NULL LIMIT ?2",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![topic, TOPIC_ENTITY_SCAN_LIMIT], |row| row.get::<_, String>(0))
+            .map_err(db_error)?;
+
+        let mut counts: HashMap<String, i64> = HashMap::new();
+        for stored in self.collect_rows::<Vec<_>, _>(rows)? {
+            let enriched = self.open_field(stored).map_err(|e| self.row_error(e))?;
+            let Some(section) = enriched
+                .split(" | ")
+                .find_map(|part| part.strip_prefix("entities: "))
+            else {
+                continue;
+            };
+            let mut seen: Vec<&str> = Vec::new();
+            for entity in section.split_whitespace() {
+                if !seen.contains(&entity) {
+                    seen.push(entity);
+                    *counts.entry(entity.to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+        let mut entities: Vec<EntityCount> = counts
+            .into_iter()
+            .map(|(entity, count)| EntityCount { entity, count })
+            .collect();
+        entities.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.entity.cmp(&b.entity)));
+        entities.truncate(top_entities);
+
+        Ok(Some(TopicStats {
+            topic: topic.to_string(),
+            doc_count,
+            chunk_count,
+            first_created_at: first,
+            last_created_at: last,
+            top_entities: entities,
+        }))
+    }
+
+    /// Topic pairs that share the most documents.
+    #[instrument(level = "debug", skip_all)]
+    pub fn topic_cooccurrence(&self, limit: usize) -> Result<Vec<TopicPair>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT a.topic, b.topic, COUNT(*) AS n \
+                 FROM doc_topics a JOIN doc_topics b ON a.doc_id = b.doc_id AND a.topic < b.topic \
+                 GROUP BY a.topic, b.topic ORDER BY n DESC, a.topic, b.topic LIMIT ?1",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![limit as i64], |row| {
+                Ok(TopicPair {
+                    a: row.get(0)?,
+                    b: row.get(1)?,
+                    count: row.get(2)?,
+                })
+            })
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    // ---------------------------------------------------------------
+    // Tags
+    // ---------------------------------------------------------------
+
+    /// Tag a document. `tag` must come from `normalize_tag`. Returns false
+    /// when the document already had the tag.
+    #[instrument(level = "debug", skip_all)]
+    pub fn add_tag(&self, doc_id: i64, tag: &str) -> Result<bool> {
+        let conn = self.conn.lock();
+        let exists = conn
+            .query_row("SELECT 1 FROM documents WHERE id = ?1", params![doc_id], |_| Ok(()))
+            .optional()
+            .map_err(db_error)?
+            .is_some();
+        if !exists {
+            return Err(Error::NotFound(format!("Document {} not found", doc_id)));
+        }
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO doc_tags (tag, doc_id) VALUES (?1, ?2)",
+                params![tag, doc_id],
+            )
+            .map_err(db_error)?;
+        Ok(inserted > 0)
+    }
+
+    /// Untag a document. Returns false when it did not have the tag.
+    #[instrument(level = "debug", skip_all)]
+    pub fn remove_tag(&self, doc_id: i64, tag: &str) -> Result<bool> {
+        let removed = self
+            .conn
+            .lock()
+            .execute("DELETE FROM doc_tags WHERE tag = ?1 AND doc_id = ?2", params![tag, doc_id])
+            .map_err(db_error)?;
+        Ok(removed > 0)
+    }
+
+    /// A document's tags, alphabetically.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_tags(&self, doc_id: i64) -> Result<Vec<String>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached("SELECT tag FROM doc_tags WHERE doc_id = ?1 ORDER BY tag")
+            .map_err(db_error)?;
+        let rows = stmt.query_map(params![doc_id], |row| row.get(0)).map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// All tags with their document counts, most common first.
+    #[instrument(level = "debug", skip_all)]
+    pub fn list_tags(&self) -> Result<Vec<(String, i64)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached("SELECT tag, COUNT(*) AS n FROM doc_tags GROUP BY tag ORDER BY n DESC, tag ASC")
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Documents with a tag, newest first. Returns (docs, total_count).
+    #[instrument(level = "debug", skip_all)]
+    pub fn list_documents_by_tag(&self, tag: &str, page: usize, page_size: usize) -> Result<(Vec<Document>, i64)> {
+        let offset = page.saturating_sub(1) * page_size;
+        let conn = self.conn.lock();
+        let total: i64 = conn
+            .query_row("SELECT COUNT(*) FROM doc_tags WHERE tag = ?1", params![tag], |row| row.get(0))
+            .map_err(db_error)?;
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT d.* FROM doc_tags g JOIN documents d ON d.id = g.doc_id \
+                 WHERE g.tag = ?1 ORDER BY d.created_at DESC, d.id DESC LIMIT ?2 OFFSET ?3",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![tag, page_size as i64, offset as i64], |row| {
+                self.row_to_document(row)
+            })
+            .map_err(db_error)?;
+        Ok((self.collect_rows(rows)?, total))
+    }
+
+    // ---------------------------------------------------------------
+    // Collections
+    // ---------------------------------------------------------------
+
+    /// Create an empty collection. Returns `None` when one already has the
+    /// name.
+    #[instrument(level = "debug", skip_all)]
+    pub fn create_collection(&self, name: &str) -> Result<Option<Collection>> {
+        let now = now_millis();
+        let conn = self.conn.lock();
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO collections (name, created_at) VALUES (?1, ?2)",
+                params![name, now],
+            )
+            .map_err(db_error)?;
+        Ok((inserted > 0).then(|| Collection {
+            id: conn.last_insert_rowid(),
+            name: name.to_string(),
+            created_at: now,
+            document_count: 0,
+        }))
+    }
+
+    /// A collection with its document count.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_collection(&self, id: i64) -> Result<Option<Collection>> {
+        let conn = self.conn.lock();
+        conn.query_row(
+            "SELECT k.id, k.name, k.created_at, \
+             (SELECT COUNT(*) FROM doc_collections m WHERE m.collection_id = k.id) \
+             FROM collections k WHERE k.id = ?1",
+            params![id],
+            row_to_collection,
+        )
+        .optional()
+        .map_err(db_error)
+    }
+
+    /// All collections with their document counts, by name.
+    #[instrument(level = "debug", skip_all)]
+    pub fn list_collections(&self) -> Result<Vec<Collection>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT k.id, k.name, k.created_at, COUNT(m.doc_id) FROM collections k \
+                 LEFT JOIN doc_collections m ON m.collection_id = k.id \
+                 GROUP BY k.id ORDER BY k.name COLLATE NOCASE, k.id",
+            )
+            .map_err(db_error)?;
+        let rows = stmt.query_map([], row_to_collection).map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Delete a collection. Its documents stay; only their membership goes.
+    /// Returns false when there was no such collection.
+    #[instrument(level = "debug", skip_all)]
+    pub fn delete_collection(&self, id: i64) -> Result<bool> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        tx.execute("DELETE FROM doc_collections WHERE collection_id = ?1", params![id])
+            .map_err(db_error)?;
+        let deleted = tx
+            .execute("DELETE FROM collections WHERE id = ?1", params![id])
+            .map_err(db_error)?;
+        tx.commit().map_err(db_error)?;
+        Ok(deleted > 0)
+    }
+
+    /// Add documents to a collection in one transaction. Ids of documents
+    /// that do not exist are skipped. Returns how many were added, not
+    /// counting documents already in the collection.
+    #[instrument(level = "debug", skip_all, fields(documents = doc_ids.len()))]
+    pub fn add_to_collection(&self, collection_id: i64, doc_ids: &[i64]) -> Result<usize> {
+        let mut conn = self.conn.lock();
+        self.require_collection(&conn, collection_id)?;
+        let tx = conn.transaction().map_err(db_error)?;
+        let mut added = 0;
+        {
+            let mut stmt = tx
+                .prepare_cached(
+                    "INSERT OR IGNORE INTO doc_collections (collection_id, doc_id) \
+                     SELECT ?1, id FROM documents WHERE id = ?2",
+                )
+                .map_err(db_error)?;
+            for &doc_id in doc_ids {
+                added += stmt.execute(params![collection_id, doc_id]).map_err(db_error)?;
+            }
+        }
+        tx.commit().map_err(db_error)?;
+        Ok(added)
+    }
+
+    /// Remove documents from a collection. Returns how many were in it.
+    #[instrument(level = "debug", skip_all, fields(documents = doc_ids.len()))]
+    pub fn remove_from_collection(&self, collection_id: i64, doc_ids: &[i64]) -> Result<usize> {
+        let mut conn = self.conn.lock();
+        self.require_collection(&conn, collection_id)?;
+        let tx = conn.transaction().map_err(db_error)?;
+        let mut removed = 0;
+        {
+            let mut stmt = tx
+                .prepare_cached("DELETE FROM doc_collections WHERE collection_id = ?1 AND doc_id = ?2")
+                .map_err(db_error)?;
+            for &doc_id in doc_ids {
+                removed += stmt.execute(params![collection_id, doc_id]).map_err(db_error)?;
+            }
+        }
+        tx.commit().map_err(db_error)?;
+        Ok(removed)
+    }
+
+    /// Ids of the collections a document is in.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_document_collections(&self, doc_id: i64) -> Result<Vec<i64>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached("SELECT collection_id FROM doc_collections WHERE doc_id = ?1 ORDER BY collection_id")
+            .map_err(db_error)?;
+        let rows = stmt.query_map(params![doc_id], |row| row.get(0)).map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Documents in a collection, newest first unless `ascending`. Returns
+    /// (docs, total_count).
+    #[instrument(level = "debug", skip_all)]
+    pub fn list_documents_in_collection(
+        &self,
+        collection_id: i64,
+        page: usize,
+        page_size: usize,
+        ascending: bool,
+    ) -> Result<(Vec<Document>, i64)> {
+        let offset = page.saturating_sub(1) * page_size;
+        let order = if ascending { "ASC" } else { "DESC" };
+        let conn = self.conn.lock();
+        let total: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM doc_collections WHERE collection_id = ?1",
+                params![collection_id],
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+        let mut stmt = conn
+            .prepare_cached(&format!(
+                "SELECT d.* FROM doc_collections m JOIN documents d ON d.id = m.doc_id \
+                 WHERE m.collection_id = ?1 ORDER BY d.created_at {order}, d.id {order} LIMIT ?2 OFFSET ?3",
+            ))
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![collection_id, page_size as i64, offset as i64], |row| {
+                self.row_to_document(row)
+            })
+            .map_err(db_error)?;
+        Ok((self.collect_rows(rows)?, total))
+    }
+
+    fn require_collection(&self, conn: &Connection, id: i64) -> Result<()> {
+        conn.query_row("SELECT 1 FROM collections WHERE id = ?1", params![id], |_| Ok(()))
+            .optional()
+            .map_err(db_error)?
+            .ok_or_else(|| Error::NotFound(format!("Collection {} not found", id)))
+    }
+
+    // ---------------------------------------------------------------
+    // Staged Imports
+    // ---------------------------------------------------------------
+
+    /// Stage connector items for review. An item already staged under the
+    /// same source item id is replaced. Returns how many were written.
+    #[instrument(level = "debug", skip_all, fields(items = items.len()))]
+    pub fn stage_items(&self, connector_id: &str, items: &[NewStagedItem]) -> Result<usize> {
+        let now = now_millis();
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        {
+            let mut stmt = tx
+                .prepare_cached(
+                    "INSERT INTO staged_items (connector_id, source_item_id, item_type, text, text_length, \
+                     metadata_json, proposed_topics_json, external_id, created_at, staged_at) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10) \
+                     ON CONFLICT (connector_id, source_item_id) DO UPDATE SET \
+                     item_type = excluded.item_type, text = excluded.text, text_length = excluded.text_length, \
+                     metadata_json = excluded.metadata_json, proposed_topics_json = excluded.proposed_topics_json, \
+                     external_id = excluded.external_id, created_at = excluded.created_at, staged_at = excluded.staged_at",
+                )
+                .map_err(db_error)?;
+            for item in items {
+                stmt.execute(params![
+                    connector_id,
+                    item.source_item_id,
+                    item.item_type,
+                    self.seal(&item.text),
+                    item.text.chars().count() as i64,
+                    serde_json::to_string(&item.metadata)?,
+                    serde_json::to_string(&item.proposed_topics)?,
+                    item.external_id,
+                    item.created_at,
+                    now,
+                ])
+                .map_err(db_error)?;
+            }
+        }
+        tx.commit().map_err(db_error)?;
+        Ok(items.len())
+    }
+
+    /// A page of a connector's staged items matching `filter`, oldest
+    /// first, with the number of matching items.
+    #[instrument(level = "debug", skip_all)]
+    pub fn list_staged_items(
+        &self,
+        connector_id: &str,
+        filter: &StagedFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<StagedItem>, i64)> {
+        let (clause, mut values) = staged_filter_clause(connector_id, filter);
+        let conn = self.conn.lock();
+        let total: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM staged_items WHERE {}", clause),
+                rusqlite::params_from_iter(values.iter()),
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+        values.push(SqlValue::Integer(limit as i64));
+        values.push(SqlValue::Integer(offset as i64));
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT * FROM staged_items WHERE {} ORDER BY id LIMIT ?{} OFFSET ?{}",
+                clause,
+                values.len() - 1,
+                values.len()
+            ))
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(values.iter()), |row| self.row_to_staged_item(row))
+            .map_err(db_error)?;
+        Ok((self.collect_rows(rows)?, total))
+    }
+
+    /// All of a connector's staged items matching `filter`, oldest first.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_staged_items(&self, connector_id: &str, filter: &StagedFilter) -> Result<Vec<StagedItem>> {
+        let (clause, values) = staged_filter_clause(connector_id, filter);
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare(&format!("SELECT * FROM staged_items WHERE {} ORDER BY id", clause))
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(values.iter()), |row| self.row_to_staged_item(row))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Delete a connector's staged items matching `filter`. Returns how
+    /// many were deleted.
+    #[instrument(level = "debug", skip_all)]
+    pub fn delete_staged_items(&self, connector_id: &str, filter: &StagedFilter) -> Result<usize> {
+        let (clause, values) = staged_filter_clause(connector_id, filter);
+        let conn = self.conn.lock();
+        conn.execute(
+            &format!("DELETE FROM staged_items WHERE {}", clause),
+            rusqlite::params_from_iter(values.iter()),
+        )
+        .map_err(db_error)
+    }
+
+    /// Delete staged items of any connector staged before `before` (Unix
+    /// ms). Returns how many expired.
+    #[instrument(level = "debug", skip_all)]
+    pub fn expire_staged_items(&self, before: i64) -> Result<usize> {
+        let conn = self.conn.lock();
+        let expired = conn
+            .execute("DELETE FROM staged_items WHERE staged_at < ?1", params![before])
+            .map_err(db_error)?;
+        if expired > 0 {
+            debug!(expired, "Expired staged items");
+        }
+        Ok(expired)
+    }
+
+    fn row_to_staged_item(&self, row: &rusqlite::Row<'_>) -> rusqlite::Result<StagedItem> {
+        let proposed_topics = json_column(row, "proposed_topics_json")?
+            .and_then(|topics| serde_json::from_value(topics).ok())
+            .unwrap_or_default();
+        Ok(StagedItem {
+            id: row.get("id")?,
+            connector_id: row.get("connector_id")?,
+            source_item_id: row.get("source_item_id")?,
+            item_type: row.get("item_type")?,
+            text: self.open_field(row.get("text")?)?,
+            text_length: row.get("text_length")?,
+            metadata: json_column(row, "metadata_json")?.unwrap_or_default(),
+            proposed_topics,
+            external_id: row.get("external_id")?,
+            created_at: row.get("created_at")?,
+            staged_at: row.get("staged_at")?,
+        })
+    }
+
+    // ---------------------------------------------------------------
+    // Forgetting
+    // ---------------------------------------------------------------
+
+    /// Ids of documents mentioning `term`, ignoring case: in their text or
+    /// metadata, or in a chunk's text or enriched text. Every row is read
+    /// (and decrypted) in batches rather than looked up in an index, so a
+    /// mention the full-text index misses is found too.
+    #[instrument(level = "debug", skip_all)]
+    pub fn find_documents_mentioning(&self, term: &str) -> Result<Vec<i64>> {
+        let needle = term.trim().to_lowercase();
+        if needle.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mentions = |text: &str| text.to_lowercase().contains(&needle);
+        let mut found = std::collections::BTreeSet::new();
+
+        for doc in self.iter_documents(true, BULK_BATCH_SIZE) {
+            let doc = doc?;
+            if mentions(&doc.text) || doc.metadata.as_ref().is_some_and(|m| mentions(&m.to_string())) {
+                found.insert(doc.id);
+            }
+        }
+
+        let mut after = 0i64;
+        loop {
+            let batch: Vec<(i64, i64, String, Option<String>)> = {
+                let conn = self.conn.lock();
+                let mut stmt = conn
+                    .prepare_cached("SELECT id, doc_id, text, enriched_text FROM chunks WHERE id > ?1 ORDER BY id LIMIT ?2")
+                    .map_err(db_error)?;
+                let rows = stmt
+                    .query_map(params![after, BULK_BATCH_SIZE as i64], |row| {
+                        let enriched: Option<String> = row.get(3)?;
+                        Ok((
+                            row.get(0)?,
+                            row.get(1)?,
+                            self.open_field(row.get(2)?)?,
+                            enriched.map(|e| self.open_field(e)).transpose()?,
+                        ))
+                    })
+                    .map_err(db_error)?;
+                self.collect_rows(rows)?
+            };
+            let Some(&(last, ..)) = batch.last() else {
+                break;
+            };
+            after = last;
+            for (_, doc_id, text, enriched) in batch {
+                if mentions(&text) || enriched.as_deref().is_some_and(mentions) {
+                    found.insert(doc_id);
+                }
+            }
+        }
+        Ok(found.into_iter().collect())
+    }
+
+    /// The chunks, embeddings and topic links that deleting `doc_ids`
+    /// removes with them.
+    #[instrument(level = "debug", skip_all, fields(documents = doc_ids.len()))]
+    pub fn document_footprint(&self, doc_ids: &[i64]) -> Result<DocumentFootprint> {
+        let conn = self.conn.lock();
+        let mut footprint = DocumentFootprint::default();
+        let mut chunks = conn
+            .prepare_cached("SELECT id FROM chunks WHERE doc_id = ?1 ORDER BY id")
+            .map_err(db_error)?;
+        let mut embeddings = conn
+            .prepare_cached(
+                "SELECT COUNT(*) FROM chunk_embeddings WHERE chunk_id IN (SELECT id FROM chunks WHERE doc_id = ?1)",
+            )
+            .map_err(db_error)?;
+        let mut topics = conn
+            .prepare_cached("SELECT COUNT(*) FROM doc_topics WHERE doc_id = ?1")
+            .map_err(db_error)?;
+        for &doc_id in doc_ids {
+            let ids = chunks.query_map(params![doc_id], |row| row.get(0)).map_err(db_error)?;
+            footprint.chunk_ids.extend(self.collect_rows::<Vec<i64>, _>(ids)?);
+            let count = |stmt: &mut rusqlite::CachedStatement<'_>| -> Result<usize> {
+                stmt.query_row(params![doc_id], |row| row.get::<_, i64>(0))
+                    .map(|n| n as usize)
+                    .map_err(db_error)
+            };
+            footprint.embeddings += count(&mut embeddings)?;
+            footprint.topic_links += count(&mut topics)?;
+        }
+        Ok(footprint)
+    }
+
+    /// Delete the logged search queries, saved searches and staged
+    /// connector items that mention `term`, ignoring case. Hashed query
+    /// stats rows only match the whole term. With `dry_run` they are only
+    /// counted.
+    #[instrument(level = "debug", skip_all)]
+    pub fn forget_mentions(&self, term: &str, dry_run: bool) -> Result<MentionCounts> {
+        let needle = term.trim().to_lowercase();
+        if needle.is_empty() {
+            return Ok(MentionCounts::default());
+        }
+        let mentions = |text: &str| text.to_lowercase().contains(&needle);
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+
+        let queries: Vec<String> = {
+            let mut stmt = tx.prepare("SELECT query FROM query_log").map_err(db_error)?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0)).map_err(db_error)?;
+            self.collect_rows::<Vec<String>, _>(rows)?.into_iter().filter(|q| mentions(q)).collect()
+        };
+        let term_hash = hash_query(term);
+        let query_stats: Vec<i64> = {
+            let mut stmt = tx.prepare("SELECT id, query, hashed FROM query_stats").map_err(db_error)?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, bool>(2)?)))
+                .map_err(db_error)?;
+            self.collect_rows::<Vec<_>, _>(rows)?
+                .into_iter()
+                .filter(|(_, query, hashed)| if *hashed { *query == term_hash } else { mentions(query) })
+                .map(|(id, ..)| id)
+                .collect()
+        };
+        let saved_searches: Vec<i64> = {
+            let mut stmt = tx.prepare("SELECT id, name, query FROM saved_searches").map_err(db_error)?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))
+                .map_err(db_error)?;
+            self.collect_rows::<Vec<_>, _>(rows)?
+                .into_iter()
+                .filter(|(_, name, query)| mentions(name) || mentions(query))
+                .map(|(id, ..)| id)
+                .collect()
+        };
+        let staged_items: Vec<i64> = {
+            let mut stmt = tx.prepare("SELECT id, text, metadata_json FROM staged_items").map_err(db_error)?;
+            let rows = stmt
+                .query_map([], |row| {
+                    let metadata: Option<String> = row.get(2)?;
+                    Ok((row.get::<_, i64>(0)?, self.open_field(row.get(1)?)?, metadata))
+                })
+                .map_err(db_error)?;
+            self.collect_rows::<Vec<_>, _>(rows)?
+                .into_iter()
+                .filter(|(_, text, metadata)| mentions(text) || metadata.as_deref().is_some_and(mentions))
+                .map(|(id, ..)| id)
+                .collect()
+        };
+
+        if !dry_run {
+            for query in &queries {
+                tx.execute("DELETE FROM query_log WHERE query = ?1", params![query]).map_err(db_error)?;
+            }
+            for id in &query_stats {
+                tx.execute("DELETE FROM query_stats WHERE id = ?1", params![id]).map_err(db_error)?;
+            }
+            for id in &saved_searches {
+                tx.execute("DELETE FROM saved_searches WHERE id = ?1", params![id]).map_err(db_error)?;
+            }
+            for id in &staged_items {
+                tx.execute("DELETE FROM staged_items WHERE id = ?1", params![id]).map_err(db_error)?;
+            }
+            tx.commit().map_err(db_error)?;
+        }
+        Ok(MentionCounts {
+            search_queries: queries.len() + query_stats.len(),
+            saved_searches: saved_searches.len(),
+            staged_items: staged_items.len(),
+        })
+    }
+
+    /// Indexed-file records of the documents `doc_ids`.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_indexed_files_of(&self, doc_ids: &[i64]) -> Result<Vec<IndexedFile>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached("SELECT * FROM indexed_files WHERE doc_id = ?1 ORDER BY path")
+            .map_err(db_error)?;
+        let mut files = Vec::new();
+        for &doc_id in doc_ids {
+            let rows = stmt.query_map(params![doc_id], Self::row_to_indexed_file).map_err(db_error)?;
+            files.extend(self.collect_rows::<Vec<_>, _>(rows)?);
+        }
+        Ok(files)
+    }
+
+    // ---------------------------------------------------------------
+    // Saved Searches
+    // ---------------------------------------------------------------
+
+    /// Persist a saved search. Chunks that already exist never count as new.
+    #[instrument(level = "debug", skip_all)]
+    pub fn create_saved_search(
+        &self,
+        name: &str,
+        query: &str,
+        filters: Option<&serde_json::Value>,
+        top_k: usize,
+    ) -> Result<SavedSearch> {
+        let now = now_millis();
+        let filters_json = filters.map(|f| f.to_string());
+        let conn = self.conn.lock();
+        let watermark: i64 = conn
+            .query_row("SELECT COALESCE(MAX(id), 0) FROM chunks", [], |row| row.get(0))
+            .map_err(db_error)?;
+        let id = conn
+            .prepare_cached(
+                "INSERT INTO saved_searches (name, query, filters_json, top_k, chunk_watermark, created_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )
+            .map_err(db_error)?
+            .insert(params![name, query, filters_json, top_k as i64, watermark, now])
+            .map_err(db_error)?;
+        Ok(SavedSearch {
+            id,
+            name: name.to_string(),
+            query: query.to_string(),
+            filters: filters.cloned(),
+            top_k,
+            new_matches: 0,
+            created_at: now,
+            last_checked_at: None,
+        })
+    }
+
+    /// All saved searches, oldest first.
+    #[instrument(level = "debug", skip_all)]
+    pub fn list_saved_searches(&self) -> Result<Vec<SavedSearch>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached("SELECT * FROM saved_searches ORDER BY id")
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map([], |row| Ok(Self::row_to_saved_search(row)))
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Get a saved search by ID.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_saved_search(&self, search_id: i64) -> Result<Option<SavedSearch>> {
+        let conn = self.conn.lock();
+        let row = conn
+            .prepare_cached("SELECT * FROM saved_searches WHERE id = ?1")
+            .map_err(db_error)?
+            .query_row(params![search_id], |row| Ok(Self::row_to_saved_search(row)))
+            .optional()
+            .map_err(db_error)?;
+        Ok(row)
+    }
+
+    /// Delete a saved search and its seen-chunk state (cascade).
+    #[instrument(level = "debug", skip_all)]
+    pub fn delete_saved_search(&self, search_id: i64) -> Result<bool> {
+        let conn = self.conn.lock();
+        let count = conn
+            .execute("DELETE FROM saved_searches WHERE id = ?1", params![search_id])
+            .map_err(db_error)?;
+        Ok(count > 0)
+    }
+
+    /// Record the hits of a saved-search run and return the chunk IDs that
+    /// are new matches.
+    ///
+    /// A hit is new only if this search never reported it and its chunk was
+    /// created after the previous check. The second condition keeps older
+    /// chunks that drift into the top-k (e.g. after a matching document is
+    /// deleted) from being reported as new.
+    #[instrument(level = "debug", skip_all)]
+    pub fn record_saved_search_hits(&self, search_id: i64, hits: &[SearchHit]) -> Result<Vec<i64>> {
+        let now = now_millis();
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(db_error)?;
+
+        let watermark: i64 = tx
+            .query_row(
+                "SELECT chunk_watermark FROM saved_searches WHERE id = ?1",
+                params![search_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error)?
+            .ok_or_else(|| Error::NotFound(format!("saved search {}", search_id)))?;
+
+        let mut new_ids = Vec::new();
+        {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT OR IGNORE INTO saved_search_seen (search_id, chunk_id, doc_id, is_new, seen_at) \
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )
+                .map_err(db_error)?;
+            for hit in hits {
+                let is_new = hit.chunk_id > watermark;
+                let inserted = insert
+                    .execute(params![search_id, hit.chunk_id, hit.doc_id, is_new, now])
+                    .map_err(db_error)?;
+                if inserted > 0 && is_new {
+                    new_ids.push(hit.chunk_id);
+                }
+            }
+        }
+
+        tx.execute(
+            "UPDATE saved_searches SET new_matches = new_matches + ?2, \
+             chunk_watermark = (SELECT COALESCE(MAX(id), 0) FROM chunks), last_checked_at = ?3 \
+             WHERE id = ?1",
+            params![search_id, new_ids.len() as i64, now],
+        )
+        .map_err(db_error)?;
+        tx.commit().map_err(db_error)?;
+
+        Ok(new_ids)
+    }
+
+    /// Chunks a saved search has reported, newest first. Chunks of deleted
+    /// documents are omitted.
+    #[instrument(level = "debug", skip_all)]
+    pub fn get_saved_search_matches(&self, search_id: i64, new_only: bool) -> Result<Vec<SavedSearchMatch>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT s.chunk_id, s.doc_id, c.text, s.is_new, s.seen_at \
+                 FROM saved_search_seen s \
+                 JOIN chunks c ON c.id = s.chunk_id \
+                 WHERE s.search_id = ?1 AND (?2 = 0 OR s.is_new = 1) \
+                 ORDER BY s.seen_at DESC, s.chunk_id DESC",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![search_id, new_only], |row| {
+                Ok(SavedSearchMatch {
+                    chunk_id: row.get(0)?,
+                    doc_id: row.get(1)?,
+                    text: self.open_field(row.get(2)?)?,
+                    is_new: row.get(3)?,
+                    seen_at: row.get(4)?,
+                })
+            })
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Mark all of a saved search's matches as seen and reset its counter.
+    #[instrument(level = "debug", skip_all)]
+    pub fn acknowledge_saved_search(&self, search_id: i64) -> Result<bool> {
+        let conn = self.conn.lock();
+        let count = conn
+            .execute(
+                "UPDATE saved_searches SET new_matches = 0 WHERE id = ?1",
+                params![search_id],
+            )
+            .map_err(db_error)?;
+        conn.execute(
+            "UPDATE saved_search_seen SET is_new = 0 WHERE search_id = ?1",
+            params![search_id],
+        )
+        .map_err(db_error)?;
+        Ok(count > 0)
+    }
+
+    // ---------------------------------------------------------------
+    // Autocomplete
+    // ---------------------------------------------------------------
+
+    /// Record a successful search query for suggestions. Queries are
+    /// lowercased and whitespace-collapsed; empty results are not recorded.
+    #[instrument(level = "debug", skip_all)]
+    pub fn record_query(&self, query: &str, hit_count: usize) -> Result<()> {
+        let normalized = normalize_query(query);
+        if normalized.is_empty() || hit_count == 0 || normalized.chars().count() > QUERY_LOG_MAX_CHARS {
+            return Ok(());
+        }
+
+        let conn = self.conn.lock();
+        conn.prepare_cached(
+            "INSERT INTO query_log (query, hit_count, use_count, last_used_at) VALUES (?1, ?2, 1, ?3) \
+             ON CONFLICT(query) DO UPDATE SET hit_count = ?2, use_count = use_count + 1, last_used_at = ?3",
+        )
+        .map_err(db_error)?
+        .execute(params![normalized, hit_count as i64, now_millis()])
+        .map_err(db_error)?;
+
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM query_log", [], |row| row.get(0))
+            .map_err(db_error)?;
+        if count > QUERY_LOG_MAX {
+            conn.execute(
+                "DELETE FROM query_log WHERE query IN \
+                 (SELECT query FROM query_log ORDER BY last_used_at ASC LIMIT ?1)",
+                params![count - QUERY_LOG_MAX],
+            )
+            .map_err(db_error)?;
+        }
+        Ok(())
+    }
+
+    /// Complete the search box input: past queries that extend the whole
+    /// input first, then indexed terms completing its last token (ranked by
+    /// document frequency). The typed prefix before the last token is kept.
+    ///
+    /// Vocabulary terms are the index's porter-stemmed tokens.
+    #[instrument(level = "debug", skip_all)]
+    pub fn suggest(&self, input: &str, limit: usize) -> Result<Vec<Suggestion>> {
+        let normalized = normalize_query(input);
+        if normalized.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Split off the token being typed; a trailing space means none.
+        let (head, last) = match input.char_indices().rev().find(|(_, c)| c.is_whitespace()) {
+            Some((i, c)) => (&input[..i + c.len_utf8()], &input[i + c.len_utf8()..]),
+            None => ("", input),
+        };
+        let history_prefix = if last.is_empty() {
+            format!("{} ", normalized)
+        } else {
+            normalized
+        };
+
+        let conn = self.conn.lock();
+        let mut suggestions: Vec<Suggestion> = Vec::new();
+
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT query, use_count FROM query_log \
+                 WHERE query >= ?1 AND query < ?2 AND query != ?3 \
+                 ORDER BY use_count DESC, last_used_at DESC LIMIT ?4",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(
+                params![
+                    history_prefix,
+                    prefix_upper_bound(&history_prefix),
+                    history_prefix.trim_end(),
+                    limit as i64
+                ],
+                |row| {
+                    Ok(Suggestion {
+                        text: row.get(0)?,
+                        source: "history".to_string(),
+                        score: row.get(1)?,
+                    })
+                },
+            )
+            .map_err(db_error)?;
+        suggestions.extend(self.collect_rows::<Vec<_>, _>(rows)?);
+
+        // The vocabulary of an encrypted store is search-token hashes
+        let token = last.to_lowercase();
+        if !token.is_empty() && self.encryption.is_none() {
+            let mut stmt = conn
+                .prepare_cached(
+                    "SELECT term, doc FROM chunk_words_vocab \
+                     WHERE term >= ?1 AND term < ?2 AND term != ?1 \
+                     ORDER BY doc DESC, term ASC LIMIT ?3",
+                )
+                .map_err(db_error)?;
+            let rows = stmt
+                .query_map(params![token, prefix_upper_bound(&token), limit as i64], |row| {
+                    let term: String = row.get(0)?;
+                    Ok(Suggestion {
+                        text: format!("{}{}", head, term),
+                        source: "vocab".to_string(),
+                        score: row.get(1)?,
+                    })
+                })
+                .map_err(db_error)?;
+            for suggestion in self.collect_rows::<Vec<_>, _>(rows)? {
+                let text = suggestion.text.to_lowercase();
+                if !suggestions.iter().any(|s| s.text.to_lowercase() == text) {
+                    suggestions.push(suggestion);
+                }
+            }
+        }
+
+        suggestions.truncate(limit);
+        Ok(suggestions)
+    }
+
+    // ---------------------------------------------------------------
+    // Query Stats
+    // ---------------------------------------------------------------
+
+    /// Set whether and how searches are recorded in the query stats log.
+    pub fn set_query_stats_config(&self, config: QueryStatsConfig) {
+        *self.query_stats_config.write() = config;
+    }
+
+    /// Whether and how searches are recorded in the query stats log.
+    pub fn query_stats_config(&self) -> QueryStatsConfig {
+        *self.query_stats_config.read()
+    }
+
+    /// Record a search in the query stats log, unless the log is off. The
+    /// query is normalized like autocomplete's, and stored as its SHA-256
+    /// in hashed mode. Diagnostics are kept only in capture mode, for
+    /// searches slower than the threshold or without results. The log is
+    /// trimmed on the first search recorded and every
+    /// `QUERY_STATS_TRIM_EVERY` after it, and whenever it is read.
+    #[instrument(level = "debug", skip_all)]
+    pub fn record_search(&self, sample: QuerySample) -> Result<()> {
+        let config = self.query_stats_config();
+        let normalized = normalize_query(&sample.query);
+        if config.mode == QueryStatsMode::Off || normalized.is_empty() {
+            return Ok(());
+        }
+        let hashed = config.mode == QueryStatsMode::Hashed;
+        let query = if hashed {
+            hash_query(&normalized)
+        } else {
+            normalized.chars().take(QUERY_LOG_MAX_CHARS).collect()
+        };
+        let captured = config.capture && (sample.latency_ms >= config.slow_ms as f64 || sample.result_count == 0);
+        let diagnostics = sample.diagnostics.filter(|_| captured).map(|mut diagnostics| {
+            // Warnings may quote the query
+            if let (true, Some(object)) = (hashed, diagnostics.as_object_mut()) {
+                object.remove("warnings");
+            }
+            diagnostics.to_string()
+        });
+
+        {
+            let conn = self.conn.lock();
+            conn.prepare_cached(
+                "INSERT INTO query_stats \
+                 (query, hashed, search_type, result_count, top_score, latency_ms, created_at, diagnostics_json) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )
+            .map_err(db_error)?
+            .execute(params![
+                query,
+                hashed,
+                sample.search_type,
+                sample.result_count as i64,
+                sample.top_score.map(f64::from),
+                sample.latency_ms,
+                now_millis(),
+                diagnostics
+            ])
+            .map_err(db_error)?;
+        }
+        let recorded = self.query_stats_recorded.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if recorded % QUERY_STATS_TRIM_EVERY == 0 {
+            self.trim_query_stats()?;
+        }
+        Ok(())
+    }
+
+    /// Delete query stats rows older than the retention period, and the
+    /// oldest beyond `QUERY_STATS_MAX`. Returns how many were deleted.
+    #[instrument(level = "debug", skip_all)]
+    pub fn trim_query_stats(&self) -> Result<usize> {
+        if self.read_only {
+            return Ok(0);
+        }
+        let retention_days = self.query_stats_config().retention_days;
+        let cutoff = now_millis() - i64::from(retention_days) * 86_400_000;
+        let conn = self.conn.lock();
+        let expired = conn
+            .prepare_cached("DELETE FROM query_stats WHERE created_at < ?1")
+            .map_err(db_error)?
+            .execute(params![cutoff])
+            .map_err(db_error)?;
+        let excess = conn
+            .prepare_cached("DELETE FROM query_stats WHERE id <= (SELECT MAX(id) FROM query_stats) - ?1")
+            .map_err(db_error)?
+            .execute(params![QUERY_STATS_MAX])
+            .map_err(db_error)?;
+        Ok(expired + excess)
+    }
+
+    /// Aggregate the searches recorded since `since` (Unix millis): counts,
+    /// zero-result rate, latency percentiles and the `top` most frequent
+    /// queries, overall and among those that found nothing.
+    #[instrument(level = "debug", skip_all)]
+    pub fn query_stats(&self, since: i64, top: usize) -> Result<QueryStats> {
+        self.trim_query_stats()?;
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached("SELECT search_type, result_count, latency_ms FROM query_stats WHERE created_at >= ?1")
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![since], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, f64>(2)?))
+            })
+            .map_err(db_error)?;
+        let rows: Vec<(String, i64, f64)> = self.collect_rows(rows)?;
+
+        let mut stats = QueryStats {
+            since,
+            searches: rows.len(),
+            ..Default::default()
+        };
+        let mut latencies = Vec::with_capacity(rows.len());
+        for (search_type, result_count, latency_ms) in rows {
+            *stats.by_search_type.entry(search_type).or_default() += 1;
+            stats.zero_results += usize::from(result_count == 0);
+            latencies.push(latency_ms);
+        }
+        if stats.searches > 0 {
+            stats.zero_result_rate = stats.zero_results as f64 / stats.searches as f64;
+        }
+        latencies.sort_by(f64::total_cmp);
+        stats.latency_ms = LatencyPercentiles {
+            p50: percentile(&latencies, 0.5),
+            p90: percentile(&latencies, 0.9),
+            p99: percentile(&latencies, 0.99),
+            max: latencies.last().copied().unwrap_or(0.0),
+        };
+
+        let top_queries = |having: &str| -> Result<Vec<QueryCount>> {
+            let sql = format!(
+                "SELECT query, hashed, COUNT(*) AS n, SUM(result_count = 0) AS zero, AVG(result_count) \
+                 FROM query_stats WHERE created_at >= ?1 GROUP BY query, hashed {} \
+                 ORDER BY {} DESC, MAX(created_at) DESC LIMIT ?2",
+                having,
+                if having.is_empty() { "n" } else { "zero" }
+            );
+            let mut stmt = conn.prepare_cached(&sql).map_err(db_error)?;
+            let rows = stmt
+                .query_map(params![since, top as i64], |row| {
+                    Ok(QueryCount {
+                        query: row.get(0)?,
+                        hashed: row.get(1)?,
+                        count: row.get::<_, i64>(2)? as usize,
+                        zero_results: row.get::<_, i64>(3)? as usize,
+                        avg_results: row.get(4)?,
+                    })
+                })
+                .map_err(db_error)?;
+            self.collect_rows(rows)
+        };
+        stats.top_queries = top_queries("")?;
+        stats.zero_result_queries = top_queries("HAVING zero > 0")?;
+        Ok(stats)
+    }
+
+    /// The latest searches kept with diagnostics (slow or zero-hit searches
+    /// in capture mode), newest first.
+    #[instrument(level = "debug", skip_all)]
+    pub fn captured_searches(&self, limit: usize) -> Result<Vec<LoggedQuery>> {
+        self.trim_query_stats()?;
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT id, query, hashed, search_type, result_count, top_score, latency_ms, created_at, \
+                 diagnostics_json FROM query_stats WHERE diagnostics_json IS NOT NULL ORDER BY id DESC LIMIT ?1",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![limit as i64], |row| {
+                let diagnostics: Option<String> = row.get(8)?;
+                Ok(LoggedQuery {
+                    id: row.get(0)?,
+                    query: row.get(1)?,
+                    hashed: row.get(2)?,
+                    search_type: row.get(3)?,
+                    result_count: row.get::<_, i64>(4)? as usize,
+                    top_score: row.get(5)?,
+                    latency_ms: row.get(6)?,
+                    created_at: row.get(7)?,
+                    diagnostics: diagnostics.and_then(|d| serde_json::from_str(&d).ok()),
+                })
+            })
+            .map_err(db_error)?;
+        self.collect_rows(rows)
+    }
+
+    /// Delete every row of the query stats log. Returns how many there were.
+    #[instrument(level = "debug", skip_all)]
+    pub fn purge_query_stats(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+        conn.execute("DELETE FROM query_stats", []).map_err(db_error)
+    }
+
+    // ---------------------------------------------------------------
+    // Row Mapping Helpers
+    // ---------------------------------------------------------------
+
+    fn row_to_saved_search(row: &rusqlite::Row<'_>) -> SavedSearch {
+        let top_k: i64 = row.get("top_k").unwrap_or(10);
+        SavedSearch {
+            id: row.get("id").unwrap_or(0),
+            name: row.get("name").unwrap_or_default(),
+            query: row.get("query").unwrap_or_default(),
+            filters: row
+                .get::<_, Option<String>>("filters_json")
+                .ok()
+                .flatten()
+                .and_then(|s| serde_json::from_str(&s).ok()),
+            top_k: top_k.max(1) as usize,
+            new_matches: row.get("new_matches").unwrap_or(0),
+            created_at: row.get("created_at").unwrap_or(0),
+            last_checked_at: row.get("last_checked_at").ok().flatten(),
+        }
+    }
+
+    fn row_to_document(&self, row: &rusqlite::Row<'_>) -> rusqlite::Result<Document> {
+        let map = || {
+            Ok(Document {
+                id: row.get("id")?,
+                text: self.open_field(row.get("text")?)?,
+                metadata: json_column(row, "metadata_json")?,
+                content_hash: row.get("content_hash")?,
+                created_at: row.get("created_at")?,
+                updated_at: row.get("updated_at")?,
+            })
+        };
+        map().map_err(|e| tag_row_error("documents", row, e))
+    }
+
+    fn row_to_chunk(&self, row: &rusqlite::Row<'_>) -> rusqlite::Result<Chunk> {
+        let map = || {
+            let enriched_text: Option<String> = row.get("enriched_text")?;
+            Ok(Chunk {
+                id: row.get("id")?,
+                doc_id: row.get("doc_id")?,
+                parent_chunk_id: row.get("parent_chunk_id")?,
+                text: self.open_field(row.get("text")?)?,
+                enriched_text: enriched_text.map(|e| self.open_field(e)).transpose()?,
+                chunk_index: row.get("chunk_index")?,
+                char_start: row.get("char_start")?,
+                char_end: row.get("char_end")?,
+                level: row.get::<_, Option<i32>>("level")?.unwrap_or(0),
+                metadata: json_column(row, "metadata_json")?,
+                created_at: row.get("created_at")?,
+            })
+        };
+        map().map_err(|e| tag_row_error("chunks", row, e))
+    }
+
+    /// Collect mapped rows, failing on the first row that cannot be read
+    /// instead of leaving it out of the results.
+    fn collect_rows<B, T>(&self, rows: impl Iterator<Item = rusqlite::Result<T>>) -> Result<B>
+    where
+        B: FromIterator<T>,
+    {
+        rows.map(|r| r.map_err(|e| self.row_error(e))).collect()
+    }
+
+    /// Error for a row that failed to map. Conversion failures (bad UTF-8,
+    /// NULL in a required column, malformed JSON, undecryptable text) are
+    /// counted in `StoreStats::corrupt_rows` and logged.
+    fn row_error(&self, e: rusqlite::Error) -> Error {
+        let corrupt = matches!(
+            e,
+            rusqlite::Error::FromSqlConversionFailure(..)
+                | rusqlite::Error::InvalidColumnType(..)
+                | rusqlite::Error::IntegralValueOutOfRange(..)
+                | rusqlite::Error::Utf8Error(..)
+        );
+        if !corrupt {
+            return Error::Database(e.to_string());
+        }
+        self.corrupt_rows.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        warn!("Unreadable row: {}", e);
+        Error::Database(format!("Unreadable row: {}", e))
+    }
+
+    /// Encrypt a text value for storage when encryption is enabled.
+    fn seal(&self, text: &str) -> String {
+        match &self.encryption {
+            Some(config) => config.encrypt(text),
+            None => text.to_string(),
+        }
+    }
+
+    /// Decrypt a stored text value; plaintext passes through.
+    fn open_field(&self, stored: String) -> rusqlite::Result<String> {
+        match &self.encryption {
+            Some(config) => config.decrypt(&stored).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+            }),
+            None => Ok(stored),
+        }
+    }
+
+    // ---------------------------------------------------------------
+    // Consolidation Operations
+    // ---------------------------------------------------------------
+
+    /// Remove chunks whose parent document no longer exists.
+    #[instrument(level = "debug", skip_all)]
+    pub fn prune_orphan_chunks(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+        let count = conn
+            .execute(
+                "DELETE FROM chunks WHERE doc_id NOT IN (SELECT id FROM documents)",
+                [],
+            )
+            .map_err(db_error)?;
+        // Also clean up FTS for orphaned chunks
+        conn.execute(
+            "DELETE FROM chunks_fts WHERE rowid NOT IN (SELECT id FROM chunks)",
+            [],
+        )
+        .map_err(db_error)?;
+        // Clean up orphaned embeddings
+        conn.execute(
+            "DELETE FROM chunk_embeddings WHERE chunk_id NOT IN (SELECT id FROM chunks)",
+            [],
+        )
+        .map_err(db_error)?;
+        Ok(count)
+    }
+
+    /// Remove documents with duplicate content_hash, keeping the newest.
+    #[instrument(level = "debug", skip_all)]
+    pub fn remove_duplicate_documents(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+        let count = conn
+            .execute(
+                "DELETE FROM documents WHERE id NOT IN (
+                    SELECT MAX(id) FROM documents
+                    WHERE content_hash IS NOT NULL
+                    GROUP BY content_hash
+                ) AND content_hash IS NOT NULL
+                AND content_hash IN (
+                    SELECT content_hash FROM documents
+                    WHERE content_hash IS NOT NULL
+                    GROUP BY content_hash
+                    HAVING COUNT(*) > 1
+                )",
+                [],
+            )
+            .map_err(db_error)?;
+        if count > 0 {
+            drop(conn);
+            // Cascade: prune orphaned chunks
+            self.prune_orphan_chunks()?;
+        }
+        Ok(count)
+    }
+
+    /// Give documents that have sections but no paragraph (level=1)
+    /// chunks, as imported by older versions, one paragraph chunk per
+    /// section with the same text and offsets, and journal them for
+    /// embedding and enrichment. Documents that already have a paragraph
+    /// chunk are left alone, so this does nothing once a store is
+    /// reconciled. Returns the number of documents reconciled.
+    #[instrument(level = "debug", skip_all)]
+    pub fn add_missing_paragraph_chunks(&self) -> Result<usize> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        let sections: Vec<Chunk> = {
+            let mut stmt = tx
+                .prepare_cached(
+                    "SELECT * FROM chunks s WHERE s.level = 0 AND NOT EXISTS \
+                     (SELECT 1 FROM chunks p WHERE p.doc_id = s.doc_id AND p.level = 1) \
+                     ORDER BY s.doc_id, s.chunk_index",
+                )
+                .map_err(db_error)?;
+            let rows = stmt.query_map([], |row| self.row_to_chunk(row)).map_err(db_error)?;
+            self.collect_rows(rows)?
+        };
+        if sections.is_empty() {
+            return Ok(0);
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        let mut docs = HashSet::new();
+        for section in &sections {
+            self.insert_chunk(
+                &tx,
+                section.doc_id,
+                &section.text,
+                section.chunk_index,
+                1,
+                Some(section.id),
+                section.char_start,
+                section.char_end,
+                None,
+                section.metadata.as_ref(),
+                now,
+            )?;
+            if docs.insert(section.doc_id) {
+                record_pending_work(&tx, section.doc_id, now)?;
+            }
+        }
+        tx.commit().map_err(db_error)?;
+        Ok(docs.len())
+    }
+
+    /// Evict the oldest N documents by created_at timestamp.
+    #[instrument(level = "debug", skip_all)]
+    pub fn evict_oldest_documents(&self, count: usize) -> Result<usize> {
+        let conn = self.conn.lock();
+        let deleted = conn
+            .execute(
+                "DELETE FROM documents WHERE id IN (
+                    SELECT id FROM documents ORDER BY created_at ASC LIMIT ?1
+                )",
+                [count],
+            )
+            .map_err(db_error)?;
+        if deleted > 0 {
+            drop(conn);
+            self.prune_orphan_chunks()?;
+        }
+        Ok(deleted)
+    }
+}
+
+/// Iterator returned by `SqliteStore::iter_documents`.
+pub struct DocumentIter<'a> {
+    store: &'a SqliteStore,
+    ascending: bool,
+    batch_size: usize,
+    cursor: Option<DocumentCursor>,
+    batch: std::collections::VecDeque<Document>,
+    done: bool,
+}
+
+impl Iterator for DocumentIter<'_> {
+    type Item = Result<Document>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.batch.is_empty() && !self.done {
+            match self.store.get_documents_after(self.cursor, self.batch_size, self.ascending) {
+                Ok(docs) => {
+                    self.done = docs.len() < self.batch_size;
+                    self.cursor = docs.last().map(DocumentCursor::after);
+                    self.batch = docs.into();
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.batch.pop_front().map(Ok)
+    }
+}
+
+/// Rewrite the database without free pages and truncate the WAL, so values
+/// replaced or deleted earlier do not survive in either.
+fn purge_free_pages(conn: &Connection) -> Result<()> {
+    conn.execute_batch("VACUUM").map_err(db_error)?;
+    let busy: i64 = conn
+        .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))
+        .map_err(db_error)?;
+    if busy != 0 {
+        warn!("WAL checkpoint blocked by another connection; old values may remain in the WAL until the next one");
+    }
+    Ok(())
+}
+
+/// Escape `%`, `_` and `\` for a `LIKE ... ESCAPE '\'` pattern.
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// `AND` conditions for a chunk filter on chunks aliased `c`, with their
+/// parameters numbered from `first_param`.
+fn chunk_filter_clause(filter: Option<&ChunkFilter>, first_param: usize) -> Result<(String, Vec<SqlValue>)> {
+    let Some(filter) = filter else {
+        return Ok((String::new(), Vec::new()));
+    };
+    check_chunk_filter(filter).map_err(Error::Search)?;
+    let mut sql = String::new();
+    let mut values = Vec::with_capacity(filter.len() * 2);
+    for (key, value) in filter {
+        let n = first_param + values.len();
+        // json_each yields a scalar field itself, or each element of an array
+        sql.push_str(&format!(
+            " AND EXISTS (SELECT 1 FROM json_each(CASE WHEN json_valid(c.metadata_json) \
+             THEN c.metadata_json END, ?{}) WHERE json_each.value = ?{})",
+            n,
+            n + 1
+        ));
+        values.push(SqlValue::Text(format!("$.{}", key)));
+        values.push(match value {
+            serde_json::Value::Bool(b) => SqlValue::Integer(*b as i64),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => SqlValue::Integer(i),
+                None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::String(s) => SqlValue::Text(s.clone()),
+            _ => SqlValue::Null,
+        });
+    }
+    Ok((sql, values))
+}
+
+/// Insert a document row and its topic rows. A content hash that already
+/// exists is reported as `Error::DuplicateContent`.
+fn insert_document(
+    conn: &Connection,
+    stored_text: &str,
+    meta_json: Option<&str>,
+    opts: &AddDocumentOptions,
+    now: i64,
+) -> Result<i64> {
+    let content_hash = opts.content_hash.as_deref();
+    let id = conn
+        .prepare_cached(
+            "INSERT INTO documents (text, metadata_json, content_hash, created_at, external_source, external_id, \
+             metadata_schema) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .map_err(db_error)?
+        .insert(params![
+            stored_text,
+            meta_json,
+            content_hash,
+            now,
+            opts.external_id.as_ref().and(opts.source.as_deref()),
+            opts.external_id,
+            METADATA_SCHEMA_VERSION
+        ])
+        .map_err(|e| {
+            let message = e.to_string();
+            if message.contains("UNIQUE constraint failed: documents.content_hash") {
+                Error::DuplicateContent(content_hash.unwrap_or_default().to_string())
+            } else if message.contains("UNIQUE constraint") {
+                Error::Database(format!(
+                    "A document with external id {:?} already exists in source {:?}",
+                    opts.external_id.as_deref().unwrap_or_default(),
+                    opts.source.as_deref().unwrap_or_default()
+                ))
+            } else {
+                db_error(e)
+            }
+        })?;
+    sync_doc_topics(conn, id, meta_json)?;
+    Ok(id)
+}
+
+/// Journal every post-chunking step for `doc_id`.
+fn record_pending_work(conn: &Connection, doc_id: i64, now: i64) -> Result<()> {
+    let mut stmt = conn
+        .prepare_cached("INSERT OR IGNORE INTO pending_work (doc_id, step, created_at) VALUES (?1, ?2, ?3)")
+        .map_err(db_error)?;
+    for step in PendingStep::ALL {
+        stmt.execute(params![doc_id, step.as_str(), now]).map_err(db_error)?;
+    }
+    Ok(())
+}
+
+/// Replace a document's `doc_topics` rows with the `topics` array of its
+/// metadata JSON (no rows when absent or not an array of strings).
+fn sync_doc_topics(conn: &Connection, doc_id: i64, metadata_json: Option<&str>) -> Result<()> {
+    conn.prepare_cached("DELETE FROM doc_topics WHERE doc_id = ?1")
+        .map_err(db_error)?
+        .execute(params![doc_id])
+        .map_err(db_error)?;
+
+    let topics: Vec<String> = metadata_json
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+        .and_then(|m| {
+            m.get("topics").and_then(|t| t.as_array()).map(|topics| {
+                topics
+                    .iter()
+                    .filter_map(|t| t.as_str().map(str::to_string))
+                    .collect()
+            })
+        })
+        .unwrap_or_default();
+    if topics.is_empty() {
+        return Ok(());
+    }
+
+    let mut stmt = conn
+        .prepare_cached("INSERT OR IGNORE INTO doc_topics (topic, doc_id) VALUES (?1, ?2)")
+        .map_err(db_error)?;
+    for topic in &topics {
+        stmt.execute(params![topic, doc_id])
+            .map_err(db_error)?;
+    }
+    Ok(())
+}
+
+/// A row that could not be mapped, with the table and rowid it came from.
+#[derive(Debug)]
+struct CorruptRow {
+    table: &'static str,
+    rowid: i64,
+    source: rusqlite::Error,
+}
+
+impl std::fmt::Display for CorruptRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} rowid {}: {}", self.table, self.rowid, self.source)
+    }
+}
+
+impl std::error::Error for CorruptRow {}
+
+/// Map a SQLite error, keeping "database busy/locked" apart as
+/// `Error::Busy` so callers can retry it.
+/// Words of a query as the `unicode61` tokenizer splits them: every
+/// character that is not a letter or digit separates words, so FTS5
+/// operators (`*`, `^`, `-`, `+`, parentheses), column filters (`text:`),
+/// quotation marks of any script and control characters never reach MATCH.
+/// Combining accents stay in their word, where the tokenizer folds them.
+fn fts_words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric() && !is_combining_accent(c)).filter(|w| !w.is_empty())
+}
+
+/// Whether `c` is in one of the blocks of combining diacritical marks.
+fn is_combining_accent(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' | '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}')
+}
+
+/// Whether FTS5 rejected a MATCH expression, as opposed to a database fault.
+fn is_fts_query_error(e: &rusqlite::Error) -> bool {
+    match e {
+        rusqlite::Error::SqliteFailure(_, Some(message)) => {
+            message.starts_with("fts5:") || message.contains("unterminated string") || message.contains("malformed MATCH")
+        }
+        _ => false,
+    }
+}
+
+fn db_error(e: rusqlite::Error) -> Error {
+    match e.sqlite_error_code() {
+        Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked) => Error::Busy(e.to_string()),
+        Some(rusqlite::ErrorCode::ReadOnly) => Error::ReadOnly(e.to_string()),
+        _ => Error::Database(e.to_string()),
+    }
+}
+
+/// Name the offending row in a mapping error, when its id is readable.
+/// The column index `usize::MAX` is rusqlite's "unknown column", which
+/// displays the wrapped error alone.
+fn row_to_collection(row: &rusqlite::Row<'_>) -> rusqlite::Result<Collection> {
+    Ok(Collection {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        created_at: row.get(2)?,
+        document_count: row.get(3)?,
+    })
+}
+
+fn tag_row_error(table: &'static str, row: &rusqlite::Row<'_>, e: rusqlite::Error) -> rusqlite::Error {
+    match row.get::<_, i64>("id") {
+        Ok(rowid) => rusqlite::Error::FromSqlConversionFailure(
+            usize::MAX,
+            rusqlite::types::Type::Null,
+            Box::new(CorruptRow { table, rowid, source: e }),
+        ),
+        Err(_) => e,
+    }
+}
+
+/// A nullable JSON text column; malformed JSON is an error, not `None`.
+fn json_column(row: &rusqlite::Row<'_>, column: &str) -> rusqlite::Result<Option<serde_json::Value>> {
+    let Some(text) = row.get::<_, Option<String>>(column)? else {
+        return Ok(None);
+    };
+    let index = row.as_ref().column_index(column)?;
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+/// Decode a `chunk_embeddings` row; None for an unknown quantization version.
+fn decode_embedding(blob: &[u8], scale: f64, offset: f64, version: i64) -> Option<Array1<f32>> {
+    QuantScheme::from_version(version).map(|scheme| dequantize(blob, scale as f32, offset as f32, scheme))
+}
+
+/// Serialized metadata of a new document, validated and normalized.
+fn new_metadata_json(metadata: Option<&serde_json::Value>) -> Result<Option<String>> {
+    Ok(metadata::prepare_metadata(metadata)?.map(|m| serde_json::to_string(&m).unwrap()))
+}
+
+/// Merge top-level `updates` keys into stored metadata JSON, normalize
+/// the result and stamp `metadata_updated_at`. The patch is normalized
+/// first, so `{"Source": ..}` replaces a stored `source`. Unparseable
+/// existing metadata is replaced.
+fn merge_metadata(existing_json: Option<&str>, updates: &serde_json::Value, now: i64) -> Result<String> {
+    metadata::validate_metadata(updates)?;
+    let mut existing: serde_json::Map<String, serde_json::Value> = existing_json
+        .and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or_default();
+
+    if let serde_json::Value::Object(map) = updates {
+        let mut patch = map.clone();
+        metadata::normalize_metadata(&mut patch);
+        existing.extend(patch);
+    }
+    metadata::normalize_metadata(&mut existing);
+    existing.insert(
+        "metadata_updated_at".to_string(),
+        serde_json::Value::Number(now.into()),
+    );
+    Ok(serde_json::to_string(&existing).unwrap())
+}
+
+/// Lowercase and collapse whitespace so equivalent queries share a log row.
+fn normalize_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// How hashed query stats store a query: the SHA-256 (hex) of its
+/// normalized text, so equal queries can still be counted together.
+pub fn hash_query(query: &str) -> String {
+    hex::encode(Sha256::digest(normalize_query(query).as_bytes()))
+}
+
+/// Run one search stage inside a `search_stage` tracing span and record
+/// its duration in `diagnostics`. Shared with the resolver's stages.
+pub fn timed_stage<T>(stage: SearchStage, diagnostics: &mut SearchDiagnostics, f: impl FnOnce() -> T) -> T {
+    let span = tracing::debug_span!("search_stage", stage = stage.as_str(), elapsed_ms = tracing::field::Empty);
+    let _entered = span.enter();
+    let start = Instant::now();
+    let result = f();
+    let ms = start.elapsed().as_secs_f64() * 1000.0;
+    span.record("elapsed_ms", ms);
+    diagnostics.stages.push(StageTiming { stage, ms });
+    result
+}
+
+/// Nearest-rank percentile `p` (0 to 1) of sorted `values`; 0 when empty.
+fn percentile(values: &[f64], p: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let rank = (p * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+/// Exclusive upper bound for a `>= prefix` range scan over TEXT keys:
+/// U+10FFFF sorts after every character that can follow the prefix.
+fn prefix_upper_bound(prefix: &str) -> String {
+    format!("{}{}", prefix, char::MAX)
+}
+
+fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+/// `WHERE` condition selecting a connector's staged items by `filter`, with
+/// its parameters.
+fn staged_filter_clause(connector_id: &str, filter: &StagedFilter) -> (String, Vec<SqlValue>) {
+    let mut conditions = vec!["connector_id = ?1".to_string()];
+    let mut values = vec![SqlValue::Text(connector_id.to_string())];
+    if let Some(min_length) = filter.min_length {
+        values.push(SqlValue::Integer(min_length as i64));
+        conditions.push(format!("text_length >= ?{}", values.len()));
+    }
+    if let Some(item_type) = &filter.item_type {
+        values.push(SqlValue::Text(item_type.to_lowercase()));
+        conditions.push(format!("LOWER(item_type) = ?{}", values.len()));
+    }
+    if let Some(ids) = &filter.ids {
+        let placeholders: Vec<String> = ids
+            .iter()
+            .map(|&id| {
+                values.push(SqlValue::Integer(id));
+                format!("?{}", values.len())
+            })
+            .collect();
+        conditions.push(format!("id IN ({})", placeholders.join(", ")));
+    }
+    (conditions.join(" AND "), values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::EncryptedSearch;
+    use tempfile::TempDir;
+
+    fn test_store() -> (SqliteStore, TempDir) {
+        let dir = TempDir::new().unwrap();
+        let store = SqliteStore::open(dir.path(), 384).unwrap();
+        (store, dir)
+    }
+
+    #[test]
+    fn test_add_and_get_document() {
+        let (store, _dir) = test_store();
+
+        let doc_id = store
+            .add_document(
+                "Hello world, this is a test document.",
+                AddDocumentOptions {
+                    content_hash: Some("hash123".into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let doc = store.get_document(doc_id).unwrap().unwrap();
+        assert_eq!(doc.text, "Hello world, this is a test document.");
+        assert_eq!(doc.content_hash.as_deref(), Some("hash123"));
+    }
+
+    #[test]
+    fn test_duplicate_content_hash() {
+        let (store, _dir) = test_store();
+
+        store
+            .add_document(
+                "First doc",
+                AddDocumentOptions {
+                    content_hash: Some("dup_hash".into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let result = store.add_document(
+            "Second doc",
+            AddDocumentOptions {
+                content_hash: Some("dup_hash".into()),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(result, Err(Error::DuplicateContent(_))));
+    }
+
+    fn new_document(text: &str, hash: &str) -> NewDocument {
+        NewDocument {
+            text: text.to_string(),
+            options: AddDocumentOptions {
+                content_hash: Some(hash.into()),
+                ..Default::default()
+            },
+            chunks: vec![
+                NewChunk {
+                    text: text.to_string(),
+                    chunk_index: 0,
+                    level: 0,
+                    parent_index: None,
+                    char_start: Some(0),
+                    char_end: Some(text.len() as i32),
+                    metadata: None,
+                },
+                NewChunk {
+                    text: text.to_string(),
+                    chunk_index: 1,
+                    level: 1,
+                    parent_index: Some(0),
+                    char_start: Some(0),
+                    char_end: Some(text.len() as i32),
+                    metadata: None,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_upsert_by_external_id_keeps_one_document() {
+        let (store, _dir) = test_store();
+        let mut first = new_document("Draft agenda: budget review", "v1");
+        first.options.metadata = Some(serde_json::json!({"title": "Agenda", "topics": ["work"]}));
+        let outcome = store.upsert_document_by_external_id("notion", "page-1", &first).unwrap();
+        let UpsertOutcome::Inserted(doc_id) = outcome else { panic!("not inserted: {:?}", outcome) };
+        let old_chunks = store.get_chunks_for_document(doc_id).unwrap();
+        store.complete_pending_work(doc_id, PendingStep::Embed).unwrap();
+
+        // Same content: nothing written
+        assert_eq!(
+            store.upsert_document_by_external_id("notion", "page-1", &first).unwrap(),
+            UpsertOutcome::Unchanged(doc_id)
+        );
+
+        // The page grew: same document, new text and chunks, merged metadata
+        let mut second = new_document("Final agenda: budget review and hiring plan", "v2");
+        second.options.metadata = Some(serde_json::json!({"title": "Agenda (final)"}));
+        assert_eq!(
+            store.upsert_document_by_external_id("notion", "page-1", &second).unwrap(),
+            UpsertOutcome::Updated(doc_id)
+        );
+        assert_eq!(store.count_documents().unwrap(), 1);
+        let doc = store.get_document(doc_id).unwrap().unwrap();
+        assert_eq!(doc.text, "Final agenda: budget review and hiring plan");
+        assert_eq!(doc.content_hash.as_deref(), Some("v2"));
+        let metadata = doc.metadata.unwrap();
+        assert_eq!(metadata["title"], "Agenda (final)");
+        assert_eq!(metadata["topics"][0], "work");
+        let chunks = store.get_chunks_for_document(doc_id).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(|c| c.text.contains("hiring") && !old_chunks.iter().any(|o| o.id == c.id)));
+        assert_eq!(store.get_pending_work(PendingStep::Embed, 0, 10).unwrap(), vec![doc_id]);
+        assert_eq!(store.bm25_search("hiring", Some(1), 10).unwrap()[0].doc_id, doc_id);
+        assert!(store.bm25_search("draft", Some(1), 10).unwrap().is_empty());
+
+        // Ids are scoped by source; a plain add cannot take a used id
+        let other = store
+            .upsert_document_by_external_id("chatgpt", "page-1", &new_document("Other", "v3"))
+            .unwrap();
+        assert!(matches!(other, UpsertOutcome::Inserted(id) if id != doc_id));
+        let taken = AddDocumentOptions {
+            source: Some("notion".into()),
+            external_id: Some("page-1".into()),
+            ..Default::default()
+        };
+        assert!(matches!(store.add_document("Copy", taken), Err(Error::Database(_))));
+
+        // Documents from before external ids can adopt one
+        let legacy = store.add_document("Legacy page", Default::default()).unwrap();
+        assert!(store.set_external_id(legacy, "notion", "page-2").unwrap());
+        assert!(!store.set_external_id(legacy, "notion", "page-3").unwrap());
+        assert_eq!(store.find_document_by_external_id("notion", "page-2").unwrap().unwrap().id, legacy);
+    }
+
+    #[test]
+    fn test_ingest_journal_records_and_clears_steps() {
+        let (store, dir) = test_store();
+        let doc_id = store.add_document_with_chunks(&new_document("journaled", "j1")).unwrap();
+        for step in PendingStep::ALL {
+            assert_eq!(store.get_pending_work(step, 0, 10).unwrap(), vec![doc_id]);
+        }
+        assert!(store.get_pending_work(PendingStep::Embed, doc_id, 10).unwrap().is_empty());
+
+        store.complete_pending_work(doc_id, PendingStep::Embed).unwrap();
+        assert_eq!(store.count_pending_work(PendingStep::Embed).unwrap(), 0);
+        assert_eq!(store.count_pending_work(PendingStep::Enrich).unwrap(), 1);
+
+        // A document without chunks is torn and has nothing journaled
+        let bare = store.add_document("bare", AddDocumentOptions::default()).unwrap();
+        assert_eq!(store.get_documents_without_chunks(0, 10).unwrap().iter().map(|d| d.id).collect::<Vec<_>>(), vec![bare]);
+        store.add_document_chunks(bare, &new_document("bare", "j2").chunks).unwrap();
+        assert!(store.get_documents_without_chunks(0, 10).unwrap().is_empty());
+        assert_eq!(store.get_pending_work(PendingStep::Embed, 0, 10).unwrap(), vec![bare]);
+
+        // Deleting a document drops its entries
+        store.delete_document(bare).unwrap();
+        assert_eq!(store.count_pending_work(PendingStep::Embed).unwrap(), 0);
+
+        // A database from before the journal has it seeded from its chunks
+        store.conn.lock().execute_batch("DROP TABLE pending_work").unwrap();
+        drop(store);
+        let store = SqliteStore::open(dir.path(), 384).unwrap();
+        assert_eq!(store.get_pending_work(PendingStep::Embed, 0, 10).unwrap(), vec![doc_id]);
+        assert_eq!(store.get_pending_work(PendingStep::Enrich, 0, 10).unwrap(), vec![doc_id]);
+    }
+
+    #[test]
+    fn test_chunk_stream_error_rolls_back_the_document() {
+        let (store, _dir) = test_store();
+        let doc = new_document("streamed", "s1");
+        let doc_id = store
+            .add_document_with_chunk_stream(&doc.text, &doc.options, doc.chunks.clone().into_iter().map(Ok))
+            .unwrap();
+        assert_eq!(store.get_chunks_for_document(doc_id).unwrap().len(), 2);
+        assert_eq!(store.get_pending_work(PendingStep::Embed, 0, 10).unwrap(), vec![doc_id]);
+
+        let failing = doc
+            .chunks
+            .into_iter()
+            .map(Ok)
+            .chain(std::iter::once(Err(Error::Database("read failed".into()))));
+        let options = AddDocumentOptions {
+            content_hash: Some("s2".into()),
+            ..doc.options
+        };
+        let result = store.add_document_with_chunk_stream("broken", &options, failing);
+        assert!(matches!(result, Err(Error::Database(e)) if e == "read failed"));
+        assert_eq!(store.count_documents().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_catchup_checkpoint_survives_reopen() {
+        let (store, dir) = test_store();
+        let first = store.add_document_with_chunks(&new_document("first", "c1")).unwrap();
+        let second = store.add_document_with_chunks(&new_document("second", "c2")).unwrap();
+        assert!(store.get_catchup_checkpoint(PendingStep::Embed).unwrap().is_none());
+        assert_eq!(store.count_pending_work_after(PendingStep::Embed, first).unwrap(), 1);
+
+        let checkpoint = CatchupCheckpoint {
+            step: PendingStep::Embed,
+            after_doc_id: first,
+            done: 1,
+            started_at: 1_700_000_000_000,
+        };
+        store.save_catchup_checkpoint(&checkpoint).unwrap();
+        store.save_catchup_checkpoint(&CatchupCheckpoint { after_doc_id: second, done: 2, ..checkpoint.clone() }).unwrap();
+        drop(store);
+
+        let store = SqliteStore::open(dir.path(), 384).unwrap();
+        let saved = store.get_catchup_checkpoint(PendingStep::Embed).unwrap().unwrap();
+        assert_eq!((saved.after_doc_id, saved.done, saved.started_at), (second, 2, checkpoint.started_at));
+        assert!(store.get_catchup_checkpoint(PendingStep::Enrich).unwrap().is_none());
+        store.clear_catchup_checkpoint(PendingStep::Embed).unwrap();
+        assert!(store.get_catchup_checkpoint(PendingStep::Embed).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_document_iteration_is_stable_under_inserts() {
+        let (store, _dir) = test_store();
+        let add = |text: &str, created_at: i64| {
+            store
+                .add_document(text, AddDocumentOptions { created_at: Some(created_at), ..Default::default() })
+                .unwrap()
+        };
+        // Two documents share a timestamp; the id breaks the tie
+        let original: Vec<i64> = [10, 20, 20, 30, 40].iter().enumerate().map(|(i, &t)| add(&format!("doc {}", i), t)).collect();
+
+        let mut iter = store.iter_documents(true, 2);
+        let mut seen: Vec<i64> = iter.by_ref().take(3).map(|d| d.unwrap().id).collect();
+        // Added mid-iteration: one sorts after the position, one before it
+        let late = add("late", 50);
+        let backdated = add("backdated", 5);
+        seen.extend(iter.map(|d| d.unwrap().id));
+
+        let mut expected = original.clone();
+        expected.push(late);
+        assert_eq!(seen, expected);
+        assert!(!seen.contains(&backdated));
+
+        // Keyset pages match offset pages, newest first
+        let mut cursor = None;
+        let mut pages = Vec::new();
+        loop {
+            let page = store.get_documents_after(cursor, 3, false).unwrap();
+            let Some(last) = page.last() else { break };
+            cursor = Some(DocumentCursor::after(last));
+            pages.push(page.iter().map(|d| d.id).collect::<Vec<_>>());
+        }
+        let offset_pages: Vec<Vec<i64>> = (1..=3)
+            .map(|page| store.get_documents_paginated(page, 3, false).unwrap().0.iter().map(|d| d.id).collect())
+            .collect();
+        assert_eq!(pages, offset_pages);
+
+        let cursor = DocumentCursor { created_at: -3, id: 42 };
+        assert_eq!(DocumentCursor::parse(&cursor.to_string()), Some(cursor));
+        assert_eq!(DocumentCursor::parse("20"), None);
+    }
+
+    #[test]
+    fn test_surrounding_chunks_skip_other_levels() {
+        let (store, _dir) = test_store();
+        // Two sections of three paragraphs: indices 0 [1 2 3] 4 [5 6 7]
+        let mut chunks = Vec::new();
+        for section in 0..2 {
+            let section_index = section * 4;
+            chunks.push(NewChunk {
+                text: format!("Section {}", section),
+                chunk_index: section_index,
+                level: 0,
+                parent_index: None,
+                char_start: None,
+                char_end: None,
+                metadata: None,
+            });
+            for p in 1..=3 {
+                chunks.push(NewChunk {
+                    text: format!("Paragraph {}", section_index + p),
+                    chunk_index: section_index + p,
+                    level: 1,
+                    parent_index: Some(section_index),
+                    char_start: None,
+                    char_end: None,
+                    metadata: None,
+                });
+            }
+        }
+        let doc = NewDocument {
+            text: "Sections".to_string(),
+            options: AddDocumentOptions::default(),
+            chunks,
+        };
+        let outcomes = store.add_documents_transactional(&[doc], false).unwrap();
+        let BatchItemOutcome::Added { doc_id } = outcomes[0] else { panic!("not added") };
+        let all = store.get_chunks_for_document(doc_id).unwrap();
+        let id_at = |index: i32| all.iter().find(|c| c.chunk_index == index).unwrap().id;
+        let indices = |chunks: Vec<Chunk>| chunks.iter().map(|c| c.chunk_index).collect::<Vec<_>>();
+
+        // Two paragraphs each way, across the section boundary
+        assert_eq!(indices(store.get_surrounding_chunks(id_at(3), 2).unwrap()), [1, 2, 3, 5, 6]);
+        assert_eq!(indices(store.get_surrounding_chunks(id_at(7), 1).unwrap()), [6, 7]);
+        assert_eq!(indices(store.get_surrounding_chunks(id_at(0), 5).unwrap()), [0, 4]);
+        assert_eq!(indices(store.get_surrounding_chunks(id_at(5), 0).unwrap()), [5]);
+    }
+
+    #[test]
+    fn test_transactional_add_rolls_back_on_duplicate() {
+        let (store, _dir) = test_store();
+        store
+            .add_document("Existing", AddDocumentOptions { content_hash: Some("h2".into()), ..Default::default() })
+            .unwrap();
+
+        let docs = [new_document("One", "h1"), new_document("Two", "h2"), new_document("Three", "h3")];
+        let rollback = store.add_documents_transactional(&docs, false).unwrap_err();
+        assert_eq!(rollback.index, Some(1));
+        assert!(matches!(rollback.error, Error::DuplicateContent(ref h) if h == "h2"));
+
+        // Nothing from the batch survived, including the first document's chunks
+        assert!(store.find_document_by_hash("h1").unwrap().is_none());
+        assert!(store.find_document_by_hash("h3").unwrap().is_none());
+        let stats = store.get_stats().unwrap();
+        assert_eq!((stats.total_documents, stats.total_chunks), (1, 0));
+    }
+
+    #[test]
+    fn test_transactional_add_skips_duplicates() {
+        let (store, _dir) = test_store();
+        store
+            .add_document("Existing", AddDocumentOptions { content_hash: Some("h2".into()), ..Default::default() })
+            .unwrap();
+
+        // A duplicate of a stored document and one within the batch itself
+        let docs = [
+            new_document("One", "h1"),
+            new_document("Two", "h2"),
+            new_document("One again", "h1"),
+        ];
+        let outcomes = store.add_documents_transactional(&docs, true).unwrap();
+        let BatchItemOutcome::Added { doc_id } = outcomes[0] else {
+            panic!("first document not added: {:?}", outcomes);
+        };
+        assert_eq!(&outcomes[1..], &[BatchItemOutcome::SkippedDuplicate, BatchItemOutcome::SkippedDuplicate]);
+
+        let chunks = store.get_chunks_for_document(doc_id).unwrap();
+        assert_eq!(chunks.len(), 2);
+        let section = chunks.iter().find(|c| c.level == 0).unwrap();
+        let paragraph = chunks.iter().find(|c| c.level == 1).unwrap();
+        assert_eq!(paragraph.parent_chunk_id, Some(section.id));
+        assert_eq!(store.get_stats().unwrap().total_documents, 2);
+    }
+
+    #[test]
+    fn test_list_content_hashes_since() {
+        let (store, _dir) = test_store();
+        for (hash, created_at) in [("old", 1_000), ("new", 5_000)] {
+            store
+                .add_document(
+                    hash,
+                    AddDocumentOptions {
+                        content_hash: Some(hash.into()),
+                        created_at: Some(created_at),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+        store.add_document("no hash", AddDocumentOptions::default()).unwrap();
+
+        let all: Vec<String> = store.list_content_hashes(None).unwrap().into_iter().map(|h| h.content_hash).collect();
+        assert_eq!(all, vec!["old", "new"]);
+        let recent = store.list_content_hashes(Some(2_000)).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!((recent[0].content_hash.as_str(), recent[0].changed_at), ("new", 5_000));
+    }
+
+    #[test]
+    fn test_document_month_counts_in_time_zone() {
+        let (store, _dir) = test_store();
+        // 2019-03-31T23:30:00Z, 2019-04-15T12:00:00Z, 2021-01-01T00:00:00Z
+        for created_at in [1_554_075_000_000i64, 1_555_329_600_000, 1_609_459_200_000] {
+            store
+                .add_document(
+                    "dated",
+                    AddDocumentOptions {
+                        created_at: Some(created_at),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+        let months = |offset| -> Vec<(String, i64)> {
+            store
+                .document_month_counts(offset)
+                .unwrap()
+                .into_iter()
+                .map(|f| (f.month, f.count))
+                .collect()
+        };
+        assert_eq!(
+            months(0),
+            vec![("2019-03".into(), 1), ("2019-04".into(), 1), ("2021-01".into(), 1)]
+        );
+        // One hour east of UTC the March document falls in April and
+        // three hours west the New Year one falls in December
+        assert_eq!(months(60)[0], ("2019-04".into(), 2));
+        assert_eq!(months(-180)[2], ("2020-12".into(), 1));
+    }
+
+    #[test]
+    fn test_checkpoint_truncates_wal() {
+        let (store, dir) = test_store();
+        for i in 0..50 {
+            store
+                .add_document(&format!("Document number {}", i), AddDocumentOptions::default())
+                .unwrap();
+        }
+        let wal = dir.path().join("mindsage.db-wal");
+        assert!(std::fs::metadata(&wal).unwrap().len() > 0);
+
+        store.checkpoint().unwrap();
+        assert_eq!(std::fs::metadata(&wal).unwrap().len(), 0);
+        assert_eq!(store.count_documents().unwrap(), 50);
+    }
+
+    #[test]
+    fn test_read_only_store_searches_but_refuses_writes() {
+        let (store, dir) = test_store();
+        let text = "Tide tables for the harbor";
+        let doc_id = store.add_document(text, AddDocumentOptions::default()).unwrap();
+        store.add_chunk(doc_id, text, 0, 1, None, None, None, None, None, None).unwrap();
+        drop(store);
+
+        let store = SqliteStore::open_read_only(dir.path(), 384, None).unwrap();
+        assert!(store.is_read_only());
+        assert_eq!(store.bm25_search("harbor", None, 5).unwrap().len(), 1);
+        let err = store.add_document("Sneaked in", AddDocumentOptions::default()).unwrap_err();
+        assert!(matches!(err, Error::ReadOnly(_)), "{}", err);
+        assert!(matches!(store.delete_document(doc_id), Err(Error::ReadOnly(_))));
+        assert_eq!(store.count_documents().unwrap(), 1);
+
+        // A missing database, or one from before the current schema, is refused
+        let empty = TempDir::new().unwrap();
+        assert!(SqliteStore::open_read_only(empty.path(), 384, None).is_err());
+        Connection::open(empty.path().join("mindsage.db"))
+            .unwrap()
+            .execute_batch("CREATE TABLE documents (id INTEGER PRIMARY KEY, text TEXT)")
+            .unwrap();
+        let err = SqliteStore::open_read_only(empty.path(), 384, None).err().unwrap().to_string();
+        assert!(err.contains("missing") && err.contains("chunks"), "{}", err);
+    }
+
+    #[test]
+    fn test_add_chunk_and_bm25_search() {
+        let (store, _dir) = test_store();
+
+        let doc_id = store
+            .add_document("Rust programming guide", Default::default())
+            .unwrap();
+
+        // Add a searchable paragraph chunk (level=1)
+        store
+            .add_chunk(
+                doc_id,
+                "Rust is a systems programming language focused on safety and performance",
+                0,
+                1,
+                None,
+                Some(0),
+                Some(72),
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        store
+            .add_chunk(
+                doc_id,
+                "Python is great for data science and machine learning applications",
+                1,
+                1,
+                None,
+                Some(72),
+                Some(137),
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        // Search for "rust programming"
+        let results = store.bm25_search("rust programming", Some(1), 10).unwrap();
+        assert!(!results.is_empty());
+        assert!(results[0].text.contains("Rust"));
+    }
+
+    /// Queries that are FTS5 syntax when passed through raw, and whether
+    /// they match "Rust is focused on memory safety" as plain words.
+    const HOSTILE_QUERIES: &[(&str, bool)] = &[
+        ("\"\" OR text: NEAR", false),
+        ("text: rust", true),
+        ("enriched_text:safety", false),
+        ("{text enriched_text}: rust", true),
+        ("NEAR(rust safety, 2)", true),
+        ("rust AND OR NOT", true),
+        ("\u{201c}rust safety\u{201d}", true),
+        ("\u{ff02}memory\u{ff02} \u{00ab}x\u{00bb}", true),
+        ("rust\u{0}safety", false),
+        ("^rust* -safety +(", true),
+        ("\"unclosed", false),
+        (")))", false),
+        ("*", false),
+        ("", false),
+        ("\u{200b}\u{feff}", false),
+    ];
+
+    #[test]
+    fn test_hostile_queries_search_as_words() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Rust guide", Default::default()).unwrap();
+        let id = store
+            .add_chunk(doc_id, "Rust is focused on memory safety", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+        let mut emb = Array1::zeros(384);
+        emb[0] = 1.0;
+        store.add_chunk_embedding(id, &emb).unwrap();
+
+        for &(query, matches) in HOSTILE_QUERIES {
+            let hits = store.bm25_search(query, Some(1), 10).unwrap_or_else(|e| panic!("{:?}: {}", query, e));
+            assert_eq!(!hits.is_empty(), matches, "{:?}", query);
+            let filters = SearchFilters {
+                phrases: vec![query.to_string()],
+                excluded: vec![query.to_string()],
+                ..Default::default()
+            };
+            store
+                .hybrid_search_within(query, &emb, Some(1), 10, 10, 60, Duration::from_secs(5), None, Some(&filters))
+                .unwrap_or_else(|e| panic!("{:?}: {}", query, e));
+        }
+
+        assert_eq!(SqliteStore::sanitize_fts_query("e-mail text:Rust \u{201c}x\u{201d}"), r#""e mail" OR "text Rust" OR "x""#);
+        let long = "word ".repeat(100);
+        assert_eq!(SqliteStore::sanitize_fts_query(&long).matches("word").count(), MAX_FTS_TOKENS);
+
+        // Raw syntax errors are told apart from database faults
+        let conn = store.conn.lock();
+        let mut stmt = conn.prepare("SELECT rowid FROM chunks_fts WHERE chunks_fts MATCH ?1").unwrap();
+        let err = stmt.query_map(["rust AND"], |row| row.get::<_, i64>(0)).unwrap().next().unwrap().unwrap_err();
+        assert!(is_fts_query_error(&err), "{}", err);
+        assert!(!is_fts_query_error(&rusqlite::Error::QueryReturnedNoRows));
+    }
+
+    #[test]
+    fn test_enriched_text_search() {
+        let (store, _dir) = test_store();
+
+        let doc_id = store
+            .add_document("Technical document", Default::default())
+            .unwrap();
+
+        let chunk_id = store
+            .add_chunk(
+                doc_id,
+                "We deployed the new microservice to production on Friday",
+                0,
+                1,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        // Add enriched text (simulating extraction)
+        store
+            .update_chunk_enriched_text(
+                chunk_id,
+                "topics: work technology | entities: microservice | activities: deployed",
+                1,
+            )
+            .unwrap();
+
+        // Search for "microservice" should find it via enriched text
+        let results = store.bm25_search("microservice", Some(1), 10).unwrap();
+        assert!(!results.is_empty());
+        assert!(results[0].enriched_text.is_some());
+    }
+
+    #[test]
+    fn test_fts_weights_favor_prose_over_keyword_lists() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Finance notes", Default::default()).unwrap();
+        let add = |text: &str, enriched: &str, index: i32| {
+            let id = store
+                .add_chunk(doc_id, text, index, 1, None, None, None, None, None, None)
+                .unwrap();
+            store.update_chunk_enriched_text(id, enriched, 1).unwrap();
+            id
+        };
+        let keywords = add(
+            "Agenda for Tuesday: room booking, catering and the guest list.",
+            "topics: finance money bank | keywords: finance bank money finance",
+            0,
+        );
+        let prose = add(
+            "The bank raised its rates, so our household finance plan for next year now \
+             puts more money aside for the mortgage.",
+            "topics: home",
+            1,
+        );
+
+        // Equal weights: the extraction's repeated words win
+        store.set_fts_weights(FtsWeights {
+            text: 1.0,
+            enriched: 1.0,
+        });
+        let hits = store.bm25_search("finance bank money", Some(1), 10).unwrap();
+        assert_eq!(hits[0].chunk_id, keywords);
+
+        // The default weights rank the chunk discussing the topic first
+        store.set_fts_weights(FtsWeights::default());
+        let hits = store.bm25_search("finance bank money", Some(1), 10).unwrap();
+        assert_eq!(hits.iter().map(|h| h.chunk_id).collect::<Vec<_>>(), vec![prose, keywords]);
+
+        // Enriched text still finds chunks whose text lacks the words
+        store.set_fts_weights(FtsWeights {
+            text: 1.0,
+            enriched: 0.0,
+        });
+        assert_eq!(store.bm25_search("finance", Some(1), 10).unwrap().len(), 2);
+
+        // Invalid weights are ignored
+        store.set_fts_weights(FtsWeights {
+            text: -1.0,
+            enriched: f64::NAN,
+        });
+        assert_eq!(store.fts_weights().enriched, 0.0);
+    }
+
+    #[test]
+    fn test_delete_document_cascades() {
+        let (store, _dir) = test_store();
+
+        let doc_id = store
+            .add_document("To be deleted", Default::default())
+            .unwrap();
+
+        store
+            .add_chunk(doc_id, "Chunk text", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+
+        assert_eq!(store.count_chunks(None).unwrap(), 1);
+
+        store.delete_document(doc_id).unwrap();
+
+        assert!(store.get_document(doc_id).unwrap().is_none());
+        assert_eq!(store.count_chunks(None).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_deleted_chunks_leave_the_matrix_at_once() {
+        let (store, _dir) = test_store();
+        let mut docs = Vec::new();
+        for d in 0..3 {
+            let doc_id = store.add_document(&format!("Document {}", d), Default::default()).unwrap();
+            for c in 0..2 {
+                let chunk = store.add_chunk(doc_id, &format!("Chunk {} {}", d, c), c, 1, None, None, None, None, None, None).unwrap();
+                let mut emb = Array1::zeros(384);
+                emb[0] = 1.0 - d as f32 * 0.3;
+                emb[1 + c as usize] = 0.2;
+                store.add_chunk_embedding(chunk, &emb).unwrap();
+            }
+            docs.push(doc_id);
+        }
+        let mut query = Array1::zeros(384);
+        query[0] = 1.0;
+        let hits = store.vector_search(&query, Some(1), 4).unwrap();
+        assert!(hits.iter().any(|h| h.doc_id == docs[0]));
+
+        // The nearest document goes; its embeddings with it, without a reload
+        assert!(store.delete_document(docs[0]).unwrap());
+        {
+            let mat = store.embedding_matrix.lock();
+            assert!(!mat.dirty);
+            assert_eq!(mat.matrix.nrows(), 4);
+            assert_eq!(mat.chunk_ids.len(), 4);
+        }
+        let count: i64 = store
+            .conn
+            .lock()
+            .query_row("SELECT COUNT(*) FROM chunk_embeddings", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 4);
+        let hits = store.vector_search(&query, Some(1), 4).unwrap();
+        assert_eq!(hits.len(), 4);
+        assert!(hits.iter().all(|h| h.doc_id != docs[0]));
+
+        assert_eq!(store.bulk_delete_documents(&docs[1..2], |_| {}).unwrap(), 1);
+        assert_eq!(store.embedding_matrix.lock().matrix.nrows(), 2);
+        let hits = store.vector_search(&query, Some(1), 4).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|h| h.doc_id == docs[2]));
+        assert!(!store.delete_document(docs[0]).unwrap());
+    }
+
+    #[test]
+    fn test_chunk_embeddings_rebuilt_to_cascade() {
+        let dir = TempDir::new().unwrap();
+        let doc_id = {
+            let store = SqliteStore::open(dir.path(), 384).unwrap();
+            let doc_id = store.add_document("Kept", Default::default()).unwrap();
+            let chunk = store.add_chunk(doc_id, "Kept", 0, 1, None, None, None, None, None, None).unwrap();
+            let mut emb = Array1::zeros(384);
+            emb[0] = 1.0;
+            store.add_chunk_embedding(chunk, &emb).unwrap();
+            doc_id
+        };
+        // Put back the foreign key of Python-created databases, with an
+        // embedding left behind by a deleted chunk
+        {
+            let conn = Connection::open(dir.path().join("mindsage.db")).unwrap();
+            conn.execute_batch(
+                "PRAGMA foreign_keys = OFF;
+                 ALTER TABLE chunk_embeddings RENAME TO old_embeddings;
+                 CREATE TABLE chunk_embeddings (chunk_id INTEGER PRIMARY KEY REFERENCES chunks(id), \
+                     embedding BLOB NOT NULL, scale REAL NOT NULL, offset_val REAL NOT NULL, \
+                     model_id TEXT NOT NULL DEFAULT 'unknown', quant_version INTEGER NOT NULL DEFAULT 0, \
+                     text_stale INTEGER NOT NULL DEFAULT 0);
+                 INSERT INTO chunk_embeddings SELECT * FROM old_embeddings;
+                 INSERT INTO chunk_embeddings (chunk_id, embedding, scale, offset_val) \
+                     SELECT 999, embedding, scale, offset_val FROM old_embeddings;
+                 DROP TABLE old_embeddings;",
+            )
+            .unwrap();
+        }
+
+        let store = SqliteStore::open(dir.path(), 384).unwrap();
+        let count = |store: &SqliteStore| -> i64 {
+            store
+                .conn
+                .lock()
+                .query_row("SELECT COUNT(*) FROM chunk_embeddings", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(count(&store), 1);
+        let on_delete: String = store
+            .conn
+            .lock()
+            .query_row("SELECT on_delete FROM pragma_foreign_key_list('chunk_embeddings')", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(on_delete, "CASCADE");
+        let mut query = Array1::zeros(384);
+        query[0] = 1.0;
+        assert_eq!(store.vector_search(&query, None, 5).unwrap().len(), 1);
+
+        store.delete_document(doc_id).unwrap();
+        assert_eq!(count(&store), 0);
+    }
+
+    #[test]
+    fn test_document_metadata_update() {
+        let (store, _dir) = test_store();
+
+        let doc_id = store
+            .add_document(
+                "Test doc",
+                AddDocumentOptions {
+                    metadata: Some(serde_json::json!({"source": "test"})),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let updates = serde_json::json!({"topics": ["programming", "rust"]});
+        store.update_document_metadata(doc_id, &updates).unwrap();
+
+        let doc = store.get_document(doc_id).unwrap().unwrap();
+        let meta = doc.metadata.unwrap();
+        assert_eq!(meta["source"], "test");
+        assert_eq!(meta["topics"][0], "programming");
+    }
+
+    #[test]
+    fn test_metadata_normalized_on_write_and_in_batches() {
+        let (store, _dir) = test_store();
+        let add = |text: &str, metadata: serde_json::Value| {
+            store
+                .add_document(
+                    text,
+                    AddDocumentOptions {
+                        metadata: Some(metadata),
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+        };
+        let written = add("written", serde_json::json!({"Source": "upload", "topics": "rust", "timestamp": "1700000000"}));
+        let meta = store.get_document(written).unwrap().unwrap().metadata.unwrap();
+        assert_eq!(meta, serde_json::json!({"source": "upload", "topics": ["rust"], "timestamp": 1_700_000_000}));
+        assert_eq!(store.list_topics().unwrap(), vec![("rust".to_string(), 1)]);
+
+        // A patch in a variant spelling replaces the canonical key
+        store.update_document_metadata(written, &serde_json::json!({"SOURCE": "notes"})).unwrap();
+        let meta = store.get_document(written).unwrap().unwrap().metadata.unwrap();
+        assert_eq!(meta["source"], "notes");
+        assert!(meta.get("SOURCE").is_none());
+        let reserved = serde_json::json!({"metadata_updated_at": 0});
+        assert!(matches!(store.update_document_metadata(written, &reserved), Err(Error::InvalidMetadata(_))));
+        assert!(matches!(
+            store.bulk_update_document_metadata(&[written], &reserved, |_| {}),
+            Err(Error::InvalidMetadata(_))
+        ));
+        assert!(matches!(
+            store.add_document("x", AddDocumentOptions { metadata: Some(serde_json::json!([1])), ..Default::default() }),
+            Err(Error::InvalidMetadata(_))
+        ));
+        assert_eq!(store.count_documents_with_stale_metadata().unwrap(), 0);
+
+        // Rows written before normalization existed
+        let legacy = add("legacy", serde_json::json!({}));
+        let broken = add("broken", serde_json::json!({}));
+        let plain = add("plain", serde_json::json!({"source": "file"}));
+        {
+            let conn = store.conn.lock();
+            conn.execute(
+                "UPDATE documents SET metadata_json = ?1, metadata_schema = 0 WHERE id = ?2",
+                params![r#"{"Topics":"travel","source":"file","Source":"x"}"#, legacy],
+            )
+            .unwrap();
+            conn.execute("UPDATE documents SET metadata_json = '[1]', metadata_schema = 0 WHERE id = ?1", params![broken])
+                .unwrap();
+            conn.execute("UPDATE documents SET metadata_schema = 0 WHERE id = ?1", params![plain]).unwrap();
+        }
+        assert_eq!(store.count_documents_with_stale_metadata().unwrap(), 3);
+
+        let first = store.normalize_stale_metadata(0, 2).unwrap();
+        assert_eq!((first.last_id, first.scanned), (Some(broken), 2));
+        assert_eq!(first.changed.len(), 1);
+        assert_eq!(first.changed[0].0, legacy);
+        let rules: Vec<&str> = first.changed[0].1.iter().map(|c| c.rule()).collect();
+        assert_eq!(rules, vec!["dropped_duplicate_key", "renamed_key", "topics_to_array"]);
+        assert_eq!(first.unreadable, vec![broken]);
+        let meta = store.get_document(legacy).unwrap().unwrap().metadata.unwrap();
+        assert_eq!(meta, serde_json::json!({"source": "file", "topics": ["travel"]}));
+        assert!(store.list_topics().unwrap().contains(&("travel".to_string(), 1)));
+
+        let second = store.normalize_stale_metadata(broken, 2).unwrap();
+        assert_eq!((second.last_id, second.scanned, second.changed.len()), (Some(plain), 1, 0));
+        assert_eq!(store.normalize_stale_metadata(plain, 2).unwrap(), Default::default());
+        assert_eq!(store.count_documents_with_stale_metadata().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_pagination() {
+        let (store, _dir) = test_store();
+
+        for i in 0..5 {
+            store
+                .add_document(
+                    &format!("Document number {}", i),
+                    AddDocumentOptions {
+                        content_hash: Some(format!("hash_{}", i)),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
         }
-        Ok(results)
-    }
 
-    // ---------------------------------------------------------------
-    // Reciprocal Rank Fusion
-    // ---------------------------------------------------------------
+        let (docs, total) = store.get_documents_paginated(1, 2, false).unwrap();
+        assert_eq!(total, 5);
+        assert_eq!(docs.len(), 2);
 
-    /// Fuse BM25 and vector search results using Reciprocal Rank Fusion.
-    /// RRF score = sum(1 / (k + rank)) across result lists.
-    pub fn reciprocal_rank_fusion(
-        bm25_results: &[SearchHit],
-        vector_results: &[SearchHit],
-        k: usize,
-    ) -> Vec<SearchHit> {
-        let mut rrf_scores: HashMap<i64, f64> = HashMap::new();
-        let mut chunk_map: HashMap<i64, &SearchHit> = HashMap::new();
+        let (docs2, _) = store.get_documents_paginated(2, 2, false).unwrap();
+        assert_eq!(docs2.len(), 2);
+    }
 
-        for (rank, hit) in bm25_results.iter().enumerate() {
-            *rrf_scores.entry(hit.chunk_id).or_insert(0.0) +=
-                1.0 / (k as f64 + rank as f64 + 1.0);
-            chunk_map.entry(hit.chunk_id).or_insert(hit);
-        }
+    #[test]
+    fn test_chunk_pagination_boundaries() {
+        let (store, _dir) = test_store();
 
-        for (rank, hit) in vector_results.iter().enumerate() {
-            *rrf_scores.entry(hit.chunk_id).or_insert(0.0) +=
-                1.0 / (k as f64 + rank as f64 + 1.0);
-            chunk_map.entry(hit.chunk_id).or_insert(hit);
+        let doc_id = store.add_document("Paged doc", Default::default()).unwrap();
+        for i in 0..5 {
+            store
+                .add_chunk(doc_id, &format!("Paragraph {}", i), i, 1, None, None, None, None, None, None)
+                .unwrap();
         }
 
-        let mut sorted: Vec<(i64, f64)> = rrf_scores.into_iter().collect();
-        sorted.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        let (page1, total) = store.get_chunks_for_document_paginated(doc_id, 1, 2, None).unwrap();
+        assert_eq!(total, 5);
+        assert_eq!(page1.len(), 2);
+        assert_eq!(page1[0].text, "Paragraph 0");
 
-        sorted
-            .into_iter()
-            .filter_map(|(cid, score)| {
-                chunk_map.get(&cid).map(|hit| SearchHit {
-                    chunk_id: hit.chunk_id,
-                    doc_id: hit.doc_id,
-                    text: hit.text.clone(),
-                    score,
-                    level: hit.level,
-                    metadata: hit.metadata.clone(),
-                    enriched_text: hit.enriched_text.clone(),
-                    parent_chunk_id: hit.parent_chunk_id,
-                    chunk_index: hit.chunk_index,
-                    char_start: hit.char_start,
-                    char_end: hit.char_end,
-                })
-            })
-            .collect()
-    }
+        // Last page is partial
+        let (page3, _) = store.get_chunks_for_document_paginated(doc_id, 3, 2, None).unwrap();
+        assert_eq!(page3.len(), 1);
+        assert_eq!(page3[0].text, "Paragraph 4");
 
-    // ---------------------------------------------------------------
-    // Hybrid Search (BM25 + Vector → RRF)
-    // ---------------------------------------------------------------
+        // Past the end is empty but still reports the total
+        let (page4, total) = store.get_chunks_for_document_paginated(doc_id, 4, 2, None).unwrap();
+        assert!(page4.is_empty());
+        assert_eq!(total, 5);
 
-    /// Combined BM25 + vector search with RRF fusion.
-    pub fn hybrid_search(
-        &self,
-        query: &str,
-        query_embedding: &Array1<f32>,
-        level: i32,
-        bm25_top_k: usize,
-        vector_top_k: usize,
-        rrf_k: usize,
-    ) -> Result<Vec<SearchHit>> {
-        let bm25_hits = self.bm25_search(query, level, bm25_top_k)?;
-        let vector_hits = self.vector_search(query_embedding, level, vector_top_k)?;
-        Ok(Self::reciprocal_rank_fusion(&bm25_hits, &vector_hits, rrf_k))
+        // Page 0 is treated as page 1
+        let (page0, _) = store.get_chunks_for_document_paginated(doc_id, 0, 2, None).unwrap();
+        assert_eq!(page0[0].id, page1[0].id);
     }
 
-    // ---------------------------------------------------------------
-    // Context Expansion
-    // ---------------------------------------------------------------
+    #[test]
+    fn test_chunk_pagination_level_filter() {
+        let (store, _dir) = test_store();
 
-    /// Get the section-level parent text for context around a chunk.
-    pub fn expand_to_parent_context(&self, chunk_id: i64) -> Result<Option<String>> {
-        Ok(self.get_parent_chunk(chunk_id)?.map(|c| c.text))
-    }
+        let doc_id = store.add_document("Leveled doc", Default::default()).unwrap();
+        let other = store.add_document("Other doc", Default::default()).unwrap();
 
-    // ---------------------------------------------------------------
-    // Stats
-    // ---------------------------------------------------------------
+        let section = store
+            .add_chunk(doc_id, "Section", 0, 0, None, None, None, None, None, None)
+            .unwrap();
+        for i in 0..3 {
+            store
+                .add_chunk(doc_id, &format!("Paragraph {}", i), i, 1, Some(section), None, None, None, None, None)
+                .unwrap();
+        }
+        store
+            .add_chunk(other, "Unrelated", 0, 1, None, None, None, None, None, None)
+            .unwrap();
 
-    /// Get store statistics.
-    pub fn get_stats(&self) -> Result<StoreStats> {
-        let doc_count = self.count_documents()?;
-        let chunk_count = self.count_chunks(None)?;
-        let para_count = self.count_chunks(Some(1))?;
-        let section_count = self.count_chunks(Some(0))?;
+        let (sections, total) = store.get_chunks_for_document_paginated(doc_id, 1, 10, Some(0)).unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(sections[0].id, section);
 
-        let conn = self.conn.lock();
-        let emb_count: i64 = conn
-            .query_row("SELECT COUNT(*) FROM chunk_embeddings", [], |row| {
-                row.get(0)
-            })
-            .map_err(|e| Error::Database(e.to_string()))?;
-        drop(conn);
+        let (paragraphs, total) = store.get_chunks_for_document_paginated(doc_id, 1, 10, Some(1)).unwrap();
+        assert_eq!(total, 3);
+        assert!(paragraphs.iter().all(|c| c.level == 1 && c.doc_id == doc_id));
 
-        let db_size = std::fs::metadata(&self.db_path)
-            .map(|m| m.len())
-            .unwrap_or(0);
+        assert_eq!(store.count_chunks_by_level(doc_id).unwrap(), vec![(0, 1), (1, 3)]);
+    }
 
-        let mat = self.embedding_matrix.lock();
-        let matrix_rows = mat.matrix.nrows();
-        let matrix_loaded = matrix_rows > 0;
+    /// Three documents: two about Rust, one about cooking.
+    fn similarity_fixture(store: &SqliteStore) -> (i64, i64, i64) {
+        let rust_a = store
+            .add_document(
+                "Rust ownership and the borrow checker",
+                AddDocumentOptions {
+                    metadata: Some(serde_json::json!({"title": "Rust A", "source": "notes"})),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let rust_b = store
+            .add_document(
+                "Rust lifetimes and borrow checker errors",
+                AddDocumentOptions {
+                    metadata: Some(serde_json::json!({"title": "Rust B", "source": "notes"})),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let cooking = store
+            .add_document(
+                "Tomato pasta recipe with fresh basil",
+                AddDocumentOptions {
+                    metadata: Some(serde_json::json!({"title": "Pasta"})),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
 
-        Ok(StoreStats {
-            total_documents: doc_count,
-            total_chunks: chunk_count,
-            paragraph_chunks: para_count,
-            section_chunks: section_count,
-            embeddings_stored: emb_count,
-            embedding_dimension: self.embedding_dim,
-            db_path: self.db_path.to_string_lossy().to_string(),
-            db_size_mb: db_size as f64 / (1024.0 * 1024.0),
-            matrix_loaded,
-            matrix_rows,
-        })
+        for (doc_id, text) in [
+            (rust_a, "Rust ownership rules and the borrow checker keep memory safe"),
+            (rust_b, "Fighting the borrow checker over Rust lifetimes"),
+            (cooking, "Simmer tomato sauce and toss the pasta with basil"),
+        ] {
+            store
+                .add_chunk(doc_id, text, 0, 1, None, None, None, None, None, None)
+                .unwrap();
+        }
+        (rust_a, rust_b, cooking)
     }
 
-    // ---------------------------------------------------------------
-    // Row Mapping Helpers
-    // ---------------------------------------------------------------
+    #[test]
+    fn test_find_similar_documents_by_centroid() {
+        let (store, _dir) = test_store();
+        let (rust_a, rust_b, cooking) = similarity_fixture(&store);
 
-    fn row_to_document(row: &rusqlite::Row<'_>) -> Document {
-        Document {
-            id: row.get("id").unwrap_or(0),
-            text: row.get("text").unwrap_or_default(),
-            metadata: row
-                .get::<_, Option<String>>("metadata_json")
-                .ok()
-                .flatten()
-                .and_then(|s| serde_json::from_str(&s).ok()),
-            content_hash: row.get("content_hash").ok().flatten(),
-            created_at: row.get("created_at").unwrap_or(0),
-            updated_at: row.get("updated_at").ok().flatten(),
+        let embed = |axis: usize, bleed: f32| {
+            let mut v = Array1::<f32>::zeros(384);
+            v[axis] = 1.0;
+            v[axis + 1] = bleed;
+            v
+        };
+        for (doc_id, emb) in [
+            (rust_a, embed(0, 0.1)),
+            (rust_b, embed(0, 0.3)),
+            (cooking, embed(5, 0.1)),
+        ] {
+            let chunk = &store.get_chunks_for_document(doc_id).unwrap()[0];
+            store.add_chunk_embedding(chunk.id, &emb).unwrap();
         }
+
+        let rust_a_chunk = store.get_chunks_for_document(rust_a).unwrap()[0].id;
+        let fetched = store.get_chunk_embeddings(&[rust_a_chunk, 9999]).unwrap();
+        assert_eq!(fetched.len(), 1);
+        assert!((fetched[&rust_a_chunk][0] - 1.0).abs() < 0.05);
+
+        // Reads compute only the queried document's centroid
+        assert!(store.find_similar_documents(rust_a, 5).unwrap().is_empty());
+        assert_eq!(store.refresh_doc_centroids().unwrap(), 2);
+
+        let similar = store.find_similar_documents(rust_a, 5).unwrap();
+        assert_eq!(similar.len(), 2);
+        assert_eq!(similar[0].doc_id, rust_b);
+        assert_eq!(similar[0].method, "centroid");
+        assert_eq!(similar[0].metadata.as_ref().unwrap()["title"], "Rust B");
+        assert_eq!(similar[1].doc_id, cooking);
+        assert!(similar[0].score > similar[1].score);
+        assert!(similar.iter().all(|s| s.doc_id != rust_a));
+
+        // Centroids are cached, and invalidated by new embeddings
+        assert_eq!(store.refresh_doc_centroids().unwrap(), 0);
+        let chunk = &store.get_chunks_for_document(cooking).unwrap()[0];
+        store.add_chunk_embedding(chunk.id, &embed(0, 0.2)).unwrap();
+        assert_eq!(store.refresh_doc_centroids().unwrap(), 1);
+        store.add_chunk_embedding(chunk.id, &embed(5, 0.2)).unwrap();
+        assert_eq!(store.find_similar_documents(cooking, 5).unwrap().len(), 2);
+        assert_eq!(store.refresh_doc_centroids().unwrap(), 0);
     }
 
-    fn row_to_chunk(row: &rusqlite::Row<'_>) -> Chunk {
-        Chunk {
-            id: row.get("id").unwrap_or(0),
-            doc_id: row.get("doc_id").unwrap_or(0),
-            parent_chunk_id: row.get("parent_chunk_id").ok().flatten(),
-            text: row.get("text").unwrap_or_default(),
-            enriched_text: row.get("enriched_text").ok().flatten(),
-            chunk_index: row.get("chunk_index").unwrap_or(0),
-            char_start: row.get("char_start").ok().flatten(),
-            char_end: row.get("char_end").ok().flatten(),
-            level: row.get("level").unwrap_or(0),
-            metadata: row
-                .get::<_, Option<String>>("metadata_json")
-                .ok()
-                .flatten()
-                .and_then(|s| serde_json::from_str(&s).ok()),
-            created_at: row.get("created_at").unwrap_or(0),
-        }
+    #[test]
+    fn test_find_similar_documents_bm25_fallback() {
+        let (store, _dir) = test_store();
+        let (rust_a, rust_b, _cooking) = similarity_fixture(&store);
+
+        let similar = store.find_similar_documents(rust_a, 5).unwrap();
+        assert!(!similar.is_empty());
+        assert_eq!(similar[0].doc_id, rust_b);
+        assert_eq!(similar[0].method, "bm25");
+        assert!(similar.iter().all(|s| s.doc_id != rust_a));
+
+        assert!(matches!(
+            store.find_similar_documents(9999, 5),
+            Err(Error::NotFound(_))
+        ));
     }
 
-    // ---------------------------------------------------------------
-    // Consolidation Operations
-    // ---------------------------------------------------------------
+    #[test]
+    fn test_saved_search_new_matches_ignore_deletions() {
+        let (store, _dir) = test_store();
+        let (rust_a, _, _) = similarity_fixture(&store);
 
-    /// Remove chunks whose parent document no longer exists.
-    pub fn prune_orphan_chunks(&self) -> Result<usize> {
-        let conn = self.conn.lock();
-        let count = conn
-            .execute(
-                "DELETE FROM chunks WHERE doc_id NOT IN (SELECT id FROM documents)",
-                [],
-            )
-            .map_err(|e| Error::Database(e.to_string()))?;
-        // Also clean up FTS for orphaned chunks
-        conn.execute(
-            "DELETE FROM chunks_fts WHERE rowid NOT IN (SELECT id FROM chunks)",
-            [],
-        )
-        .map_err(|e| Error::Database(e.to_string()))?;
-        // Clean up orphaned embeddings
-        conn.execute(
-            "DELETE FROM chunk_embeddings WHERE chunk_id NOT IN (SELECT id FROM chunks)",
-            [],
-        )
-        .map_err(|e| Error::Database(e.to_string()))?;
-        Ok(count)
+        // Existing chunks are never reported as new
+        let saved = store.create_saved_search("borrow", "borrow checker", None, 1).unwrap();
+        let hits = store.bm25_search("borrow checker", Some(1), 1).unwrap();
+        assert!(store.record_saved_search_hits(saved.id, &hits).unwrap().is_empty());
+
+        // A chunk indexed after the check is new, and only reported once
+        let doc = store.add_document("Borrow checker notes", AddDocumentOptions::default()).unwrap();
+        let chunk = store
+            .add_chunk(doc, "borrow checker borrow checker borrow checker", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+        let hits = store.bm25_search("borrow checker", Some(1), 1).unwrap();
+        assert_eq!(store.record_saved_search_hits(saved.id, &hits).unwrap(), vec![chunk]);
+        assert!(store.record_saved_search_hits(saved.id, &hits).unwrap().is_empty());
+
+        // Deleting matches lets older chunks into the top-k without counting as new
+        store.delete_document(doc).unwrap();
+        store.delete_document(rust_a).unwrap();
+        let hits = store.bm25_search("borrow checker", Some(1), 1).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(store.record_saved_search_hits(saved.id, &hits).unwrap().is_empty());
+
+        let saved = store.get_saved_search(saved.id).unwrap().unwrap();
+        assert_eq!(saved.new_matches, 1);
+        assert!(saved.last_checked_at.is_some());
+        // The deleted document's match is no longer listed
+        assert!(store.get_saved_search_matches(saved.id, true).unwrap().is_empty());
+
+        assert!(store.acknowledge_saved_search(saved.id).unwrap());
+        assert_eq!(store.get_saved_search(saved.id).unwrap().unwrap().new_matches, 0);
+        assert!(store.delete_saved_search(saved.id).unwrap());
+        assert!(store.list_saved_searches().unwrap().is_empty());
     }
 
-    /// Remove documents with duplicate content_hash, keeping the newest.
-    pub fn remove_duplicate_documents(&self) -> Result<usize> {
-        let conn = self.conn.lock();
-        let count = conn
-            .execute(
-                "DELETE FROM documents WHERE id NOT IN (
-                    SELECT MAX(id) FROM documents
-                    WHERE content_hash IS NOT NULL
-                    GROUP BY content_hash
-                ) AND content_hash IS NOT NULL
-                AND content_hash IN (
-                    SELECT content_hash FROM documents
-                    WHERE content_hash IS NOT NULL
-                    GROUP BY content_hash
-                    HAVING COUNT(*) > 1
-                )",
-                [],
-            )
-            .map_err(|e| Error::Database(e.to_string()))?;
-        if count > 0 {
-            drop(conn);
-            // Cascade: prune orphaned chunks
-            self.prune_orphan_chunks()?;
+    #[test]
+    fn test_suggest_vocab_prefix_and_frequency() {
+        let (store, _dir) = test_store();
+        let doc = store.add_document("vocab", AddDocumentOptions::default()).unwrap();
+        for (i, text) in [
+            "project phoenix kickoff",
+            "project planning for phoenix",
+            "phoenix retro notes",
+            "photo backup",
+        ]
+        .iter()
+        .enumerate()
+        {
+            store
+                .add_chunk(doc, text, i as i32, 1, None, None, None, None, None, None)
+                .unwrap();
         }
-        Ok(count)
+
+        let suggestions = store.suggest("pho", 10).unwrap();
+        let texts: Vec<&str> = suggestions.iter().map(|s| s.text.as_str()).collect();
+        // "phoenix" is in three chunks, "photo" in one
+        assert_eq!(texts, vec!["phoenix", "photo"]);
+        assert_eq!(suggestions[0].score, 3);
+        assert!(suggestions.iter().all(|s| s.source == "vocab"));
+
+        // Multi-word input completes only the last token and keeps the prefix
+        let suggestions = store.suggest("Project pl", 10).unwrap();
+        assert_eq!(suggestions[0].text, "Project planning");
+
+        // Completions are whole words, not index stems
+        let suggestions = store.suggest("kick", 10).unwrap();
+        assert_eq!(suggestions.iter().map(|s| s.text.as_str()).collect::<Vec<_>>(), vec!["kickoff"]);
+
+        assert!(store.suggest("zzz", 10).unwrap().is_empty());
+        assert!(store.suggest("  ", 10).unwrap().is_empty());
     }
 
-    /// Evict the oldest N documents by created_at timestamp.
-    pub fn evict_oldest_documents(&self, count: usize) -> Result<usize> {
-        let conn = self.conn.lock();
-        let deleted = conn
-            .execute(
-                "DELETE FROM documents WHERE id IN (
-                    SELECT id FROM documents ORDER BY created_at ASC LIMIT ?1
-                )",
-                [count],
-            )
-            .map_err(|e| Error::Database(e.to_string()))?;
-        if deleted > 0 {
-            drop(conn);
-            self.prune_orphan_chunks()?;
-        }
-        Ok(deleted)
+    #[test]
+    fn test_suggest_history_before_vocab() {
+        let (store, _dir) = test_store();
+        let doc = store.add_document("vocab", AddDocumentOptions::default()).unwrap();
+        store
+            .add_chunk(doc, "invoice archive", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+
+        store.record_query("Invoice  2024", 3).unwrap();
+        store.record_query("invoice 2024", 2).unwrap();
+        store.record_query("invoice 2023", 1).unwrap();
+        store.record_query("invoice nothing", 0).unwrap();
+
+        let suggestions = store.suggest("invo", 10).unwrap();
+        let texts: Vec<&str> = suggestions.iter().map(|s| s.text.as_str()).collect();
+        // The most used query first; unrecorded zero-hit queries never appear
+        assert_eq!(texts[0], "invoice 2024");
+        assert_eq!(suggestions[0].score, 2);
+        assert_eq!(texts[1], "invoice 2023");
+        assert_eq!(texts[2..], ["invoice"]);
+        assert!(!texts.contains(&"invoice nothing"));
+
+        // A trailing space only completes whole following words from history
+        let suggestions = store.suggest("invoice ", 10).unwrap();
+        assert!(suggestions.iter().all(|s| s.source == "history"));
+        assert_eq!(suggestions.len(), 2);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
+    fn sample(query: &str, result_count: usize, latency_ms: f64) -> QuerySample {
+        QuerySample {
+            query: query.to_string(),
+            search_type: "hybrid".to_string(),
+            result_count,
+            top_score: (result_count > 0).then_some(0.5),
+            latency_ms,
+            diagnostics: Some(serde_json::json!({"topK": 10, "warnings": [format!("slow: {}", query)]})),
+        }
+    }
 
-    fn test_store() -> (SqliteStore, TempDir) {
-        let dir = TempDir::new().unwrap();
-        let store = SqliteStore::open(dir.path(), 384).unwrap();
-        (store, dir)
+    #[test]
+    fn test_query_stats_aggregates() {
+        let (store, _dir) = test_store();
+        store.record_search(sample("tax return", 3, 10.0)).unwrap();
+        assert_eq!(store.query_stats(0, 10).unwrap().searches, 0);
+
+        store.set_query_stats_config(QueryStatsConfig {
+            mode: QueryStatsMode::Plain,
+            capture: true,
+            slow_ms: 500,
+            ..Default::default()
+        });
+        for latency in 1..=8 {
+            store.record_search(sample("Tax  Return", 3, latency as f64 * 10.0)).unwrap();
+        }
+        store.record_search(sample("lost receipt", 0, 20.0)).unwrap();
+        store.record_search(sample("lost receipt", 0, 900.0)).unwrap();
+
+        let stats = store.query_stats(0, 10).unwrap();
+        assert_eq!((stats.searches, stats.zero_results), (10, 2));
+        assert!((stats.zero_result_rate - 0.2).abs() < 1e-9);
+        assert_eq!(stats.latency_ms.p50, 40.0);
+        assert_eq!(stats.latency_ms.p90, 80.0);
+        assert_eq!((stats.latency_ms.p99, stats.latency_ms.max), (900.0, 900.0));
+        assert_eq!(stats.by_search_type.get("hybrid"), Some(&10));
+        assert_eq!(stats.top_queries[0].query, "tax return");
+        assert_eq!((stats.top_queries[0].count, stats.top_queries[0].avg_results), (8, 3.0));
+        assert_eq!(stats.zero_result_queries.len(), 1);
+        assert_eq!(stats.zero_result_queries[0].query, "lost receipt");
+        assert!(store.query_stats(now_millis() + 1, 10).unwrap().top_queries.is_empty());
+
+        // Only the zero-hit and slow searches keep diagnostics
+        let captured = store.captured_searches(10).unwrap();
+        assert_eq!(captured.len(), 2);
+        assert_eq!(captured[0].latency_ms, 900.0);
+        assert_eq!(captured[0].diagnostics.as_ref().unwrap()["topK"], 10);
+
+        assert_eq!(store.purge_query_stats().unwrap(), 10);
+        assert_eq!(store.query_stats(0, 10).unwrap().searches, 0);
     }
 
     #[test]
-    fn test_add_and_get_document() {
+    fn test_hashed_query_stats_store_no_plaintext() {
         let (store, _dir) = test_store();
+        store.set_query_stats_config(QueryStatsConfig {
+            mode: QueryStatsMode::Hashed,
+            capture: true,
+            ..Default::default()
+        });
+        store.record_search(sample("Divorce Lawyer", 0, 20.0)).unwrap();
+        store.record_search(sample("divorce lawyer", 2, 20.0)).unwrap();
+
+        let stats = store.query_stats(0, 10).unwrap();
+        assert_eq!(stats.top_queries.len(), 1);
+        assert_eq!(stats.top_queries[0].query, hash_query("divorce lawyer"));
+        assert!(stats.top_queries[0].hashed);
+        let rows: Vec<String> = {
+            let conn = store.conn.lock();
+            let mut stmt = conn
+                .prepare("SELECT query || ' ' || search_type || ' ' || IFNULL(diagnostics_json, '') FROM query_stats")
+                .unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().map(|r| r.unwrap()).collect()
+        };
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| !row.to_lowercase().contains("divorce")));
+        assert!(rows[0].contains("topK"));
 
-        let doc_id = store
-            .add_document(
-                "Hello world, this is a test document.",
-                AddDocumentOptions {
-                    content_hash: Some("hash123".into()),
-                    ..Default::default()
-                },
+        // Forgetting the whole term finds its hashed rows
+        assert_eq!(store.forget_mentions("Divorce lawyer", false).unwrap().search_queries, 2);
+        assert_eq!(store.query_stats(0, 10).unwrap().searches, 0);
+    }
+
+    #[test]
+    fn test_query_stats_trimmed_periodically() {
+        let (store, _dir) = test_store();
+        store.set_query_stats_config(QueryStatsConfig {
+            mode: QueryStatsMode::Plain,
+            ..Default::default()
+        });
+        let count = || -> i64 {
+            store.conn.lock().query_row("SELECT COUNT(*) FROM query_stats", [], |row| row.get(0)).unwrap()
+        };
+        store.record_search(sample("tax return", 3, 10.0)).unwrap();
+        store
+            .conn
+            .lock()
+            .execute(
+                "INSERT INTO query_stats (query, hashed, search_type, result_count, latency_ms, created_at) \
+                 VALUES ('expired', 0, 'hybrid', 1, 5.0, 0)",
+                [],
             )
             .unwrap();
 
-        let doc = store.get_document(doc_id).unwrap().unwrap();
-        assert_eq!(doc.text, "Hello world, this is a test document.");
-        assert_eq!(doc.content_hash.as_deref(), Some("hash123"));
+        // Recording does not trim on every search
+        store.record_search(sample("tax return", 3, 10.0)).unwrap();
+        assert_eq!(count(), 3);
+
+        // Reading the log does
+        assert_eq!(store.query_stats(0, 10).unwrap().searches, 2);
+        assert_eq!(count(), 2);
     }
 
     #[test]
-    fn test_duplicate_content_hash() {
+    fn test_select_documents_filters() {
         let (store, _dir) = test_store();
+        let add = |text: &str, meta: serde_json::Value, hash: &str, created_at: i64| {
+            store
+                .add_document(
+                    text,
+                    AddDocumentOptions {
+                        metadata: Some(meta),
+                        content_hash: Some(hash.to_string()),
+                        created_at: Some(created_at),
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+        };
+        let a = add("a", serde_json::json!({"source": "gmail", "topics": ["finance"]}), "aa11", 1000);
+        let b = add("b", serde_json::json!({"source": "gmail", "topics": ["travel"]}), "aa22", 2000);
+        let c = add(
+            "c",
+            serde_json::json!({"source": "drive", "topics": ["finance"], "embedding_truncation": {"rate": 0.5}}),
+            "bb33",
+            3000,
+        );
 
-        store
-            .add_document(
-                "First doc",
-                AddDocumentOptions {
-                    content_hash: Some("dup_hash".into()),
-                    ..Default::default()
-                },
-            )
-            .unwrap();
+        let select = |selector: DocumentSelector| store.select_documents(&selector).unwrap();
 
-        let result = store.add_document(
-            "Second doc",
-            AddDocumentOptions {
-                content_hash: Some("dup_hash".into()),
+        assert!(select(DocumentSelector::default()).is_empty());
+        assert_eq!(select(DocumentSelector { source: Some("gmail".into()), ..Default::default() }), vec![a, b]);
+        assert_eq!(select(DocumentSelector { topic: Some("finance".into()), ..Default::default() }), vec![a, c]);
+        assert_eq!(
+            select(DocumentSelector {
+                created_after: Some(2000),
+                created_before: Some(3000),
                 ..Default::default()
-            },
+            }),
+            vec![b]
+        );
+        assert_eq!(
+            select(DocumentSelector { content_hash_prefix: Some("aa".into()), ..Default::default() }),
+            vec![a, b]
+        );
+        assert_eq!(
+            select(DocumentSelector { has_metadata: Some("embedding_truncation".into()), ..Default::default() }),
+            vec![c]
+        );
+        assert!(select(DocumentSelector { has_metadata: Some(String::new()), ..Default::default() }).is_empty());
+        // Criteria are ANDed, including with explicit ids
+        assert_eq!(
+            select(DocumentSelector {
+                ids: vec![a, c, 999],
+                topic: Some("finance".into()),
+                source: Some("drive".into()),
+                ..Default::default()
+            }),
+            vec![c]
         );
-        assert!(matches!(result, Err(Error::DuplicateContent(_))));
     }
 
     #[test]
-    fn test_add_chunk_and_bm25_search() {
+    fn test_bulk_delete_and_update_metadata() {
         let (store, _dir) = test_store();
+        let ids: Vec<i64> = (0..3)
+            .map(|i| {
+                let doc = store
+                    .add_document(&format!("doc {}", i), AddDocumentOptions::default())
+                    .unwrap();
+                store
+                    .add_chunk(doc, "bulk chunk text", 0, 1, None, None, None, None, None, None)
+                    .unwrap();
+                doc
+            })
+            .collect();
 
-        let doc_id = store
-            .add_document("Rust programming guide", Default::default())
+        let mut batches = 0;
+        let updated = store
+            .bulk_update_document_metadata(&ids[..2], &serde_json::json!({"source": "bad-import"}), |_| batches += 1)
             .unwrap();
+        assert_eq!(updated, 2);
+        assert_eq!(batches, 1);
+        let doc = store.get_document(ids[0]).unwrap().unwrap();
+        assert_eq!(doc.metadata.unwrap()["source"], "bad-import");
 
-        // Add a searchable paragraph chunk (level=1)
-        store
-            .add_chunk(
-                doc_id,
-                "Rust is a systems programming language focused on safety and performance",
-                0,
-                1,
-                None,
-                Some(0),
-                Some(72),
-                None,
-                None,
-                None,
-            )
+        let selected = store
+            .select_documents(&DocumentSelector { source: Some("bad-import".into()), ..Default::default() })
             .unwrap();
+        let deleted = store.bulk_delete_documents(&selected, |_| {}).unwrap();
+        assert_eq!(deleted, 2);
+        assert_eq!(store.count_documents().unwrap(), 1);
+        assert_eq!(store.count_chunks(None).unwrap(), 1);
+    }
 
-        store
-            .add_chunk(
-                doc_id,
-                "Python is great for data science and machine learning applications",
-                1,
-                1,
-                None,
-                Some(72),
-                Some(137),
-                None,
-                None,
-                None,
-            )
-            .unwrap();
+    /// Per-topic document counts derived by scanning every document's metadata.
+    fn metadata_topic_counts(store: &SqliteStore) -> Vec<(String, i64)> {
+        let mut counts: HashMap<String, i64> = HashMap::new();
+        for doc in store.get_all_documents(false).unwrap() {
+            if let Some(topics) = doc.metadata.as_ref().and_then(|m| m.get("topics")).and_then(|t| t.as_array()) {
+                let mut seen: Vec<&str> = Vec::new();
+                for topic in topics.iter().filter_map(|t| t.as_str()) {
+                    if !seen.contains(&topic) {
+                        seen.push(topic);
+                        *counts.entry(topic.to_string()).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+        let mut counts: Vec<(String, i64)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
 
-        // Search for "rust programming"
-        let results = store.bm25_search("rust programming", 1, 10).unwrap();
-        assert!(!results.is_empty());
-        assert!(results[0].text.contains("Rust"));
+    #[test]
+    fn test_doc_topics_backfill_matches_metadata() {
+        let (store, dir) = test_store();
+        for (i, meta) in [
+            serde_json::json!({"topics": ["rust", "programming"]}),
+            serde_json::json!({"topics": ["rust"]}),
+            serde_json::json!({"topics": ["cooking", "rust", "rust"]}),
+            serde_json::json!({"topics": "not-an-array"}),
+            serde_json::json!({"source": "file"}),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            store
+                .add_document(
+                    &format!("doc {}", i),
+                    AddDocumentOptions {
+                        metadata: Some(meta),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+        let expected = metadata_topic_counts(&store);
+        assert_eq!(store.list_topics().unwrap(), expected);
+
+        // Simulate a database created before doc_topics existed
+        store.conn.lock().execute("DELETE FROM doc_topics", []).unwrap();
+        assert!(store.list_topics().unwrap().is_empty());
+        drop(store);
+
+        let reopened = SqliteStore::open(dir.path(), 384).unwrap();
+        assert_eq!(reopened.list_topics().unwrap(), expected);
+        assert_eq!(expected[0], ("rust".to_string(), 3));
+        // A second backfill is a no-op
+        assert_eq!(reopened.backfill_doc_topics().unwrap(), 0);
     }
 
     #[test]
-    fn test_enriched_text_search() {
+    fn test_topic_stats_pagination_and_cooccurrence() {
         let (store, _dir) = test_store();
+        let mut ids = Vec::new();
+        for (i, topics) in [vec!["rust", "work"], vec!["rust", "work"], vec!["rust"], vec!["travel"]]
+            .into_iter()
+            .enumerate()
+        {
+            let doc = store
+                .add_document(
+                    &format!("doc {}", i),
+                    AddDocumentOptions {
+                        created_at: Some(1000 * (i as i64 + 1)),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            // Topics arrive later, through a metadata update
+            store
+                .update_document_metadata(doc, &serde_json::json!({ "topics": topics }))
+                .unwrap();
+            store
+                .add_chunk(doc, "chunk", 0, 1, None, None, None, Some("topics: rust | entities: Tokio Axum"), None, None)
+                .unwrap();
+            ids.push(doc);
+        }
 
-        let doc_id = store
-            .add_document("Technical document", Default::default())
-            .unwrap();
+        let stats = store.topic_stats("rust", 5).unwrap().unwrap();
+        assert_eq!(stats.doc_count, 3);
+        assert_eq!(stats.chunk_count, 3);
+        assert_eq!(stats.first_created_at, Some(1000));
+        assert_eq!(stats.last_created_at, Some(3000));
+        assert_eq!(stats.top_entities[0].entity, "Axum");
+        assert_eq!(stats.top_entities[0].count, 3);
+        assert!(store.topic_stats("unknown", 5).unwrap().is_none());
 
-        let chunk_id = store
-            .add_chunk(
-                doc_id,
-                "We deployed the new microservice to production on Friday",
-                0,
-                1,
-                None,
-                None,
-                None,
-                None,
-                None,
-                None,
-            )
-            .unwrap();
+        let (page, total) = store.get_documents_by_topic_paginated("rust", 1, 2).unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(page.iter().map(|d| d.id).collect::<Vec<_>>(), vec![ids[2], ids[1]]);
 
-        // Add enriched text (simulating extraction)
+        let pairs = store.topic_cooccurrence(10).unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].a.as_str(), pairs[0].b.as_str(), pairs[0].count), ("rust", "work", 2));
+
+        // Retagging and deleting keep the table in sync
         store
-            .update_chunk_enriched_text(
-                chunk_id,
-                "topics: work technology | entities: microservice | activities: deployed",
-            )
+            .update_document_metadata(ids[0], &serde_json::json!({"topics": ["travel"]}))
             .unwrap();
-
-        // Search for "microservice" should find it via enriched text
-        let results = store.bm25_search("microservice", 1, 10).unwrap();
-        assert!(!results.is_empty());
-        assert!(results[0].enriched_text.is_some());
+        store.delete_document(ids[1]).unwrap();
+        assert_eq!(store.list_topics().unwrap(), metadata_topic_counts(&store));
+        assert!(store.topic_cooccurrence(10).unwrap().is_empty());
     }
 
     #[test]
-    fn test_delete_document_cascades() {
+    fn test_tags_survive_metadata_updates() {
         let (store, _dir) = test_store();
+        let mut ids = Vec::new();
+        for i in 0..3 {
+            let options = AddDocumentOptions { created_at: Some(1000 * (i + 1)), ..Default::default() };
+            ids.push(store.add_document(&format!("doc {}", i), options).unwrap());
+        }
+        for &id in &ids {
+            assert!(store.add_tag(id, "receipts").unwrap());
+        }
+        assert!(!store.add_tag(ids[0], "receipts").unwrap());
+        assert!(store.add_tag(ids[0], "2024").unwrap());
+        assert!(matches!(store.add_tag(999, "receipts"), Err(Error::NotFound(_))));
 
-        let doc_id = store
-            .add_document("To be deleted", Default::default())
-            .unwrap();
-
+        // Metadata rewrites leave tags alone
         store
-            .add_chunk(doc_id, "Chunk text", 0, 1, None, None, None, None, None, None)
+            .update_document_metadata(ids[0], &serde_json::json!({"topics": ["finance"], "tags": ["ignored"]}))
             .unwrap();
+        assert_eq!(store.get_tags(ids[0]).unwrap(), ["2024", "receipts"]);
+        assert_eq!(
+            store.list_tags().unwrap(),
+            [("receipts".to_string(), 3), ("2024".to_string(), 1)]
+        );
 
-        assert_eq!(store.count_chunks(None).unwrap(), 1);
-
-        store.delete_document(doc_id).unwrap();
+        let (page, total) = store.list_documents_by_tag("receipts", 1, 2).unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(page.iter().map(|d| d.id).collect::<Vec<_>>(), vec![ids[2], ids[1]]);
 
-        assert!(store.get_document(doc_id).unwrap().is_none());
-        assert_eq!(store.count_chunks(None).unwrap(), 0);
+        assert!(store.remove_tag(ids[1], "receipts").unwrap());
+        assert!(!store.remove_tag(ids[1], "receipts").unwrap());
+        store.delete_document(ids[2]).unwrap();
+        assert_eq!(store.list_tags().unwrap(), [("2024".to_string(), 1), ("receipts".to_string(), 1)]);
     }
 
     #[test]
-    fn test_document_metadata_update() {
+    fn test_search_filters_by_tag() {
         let (store, _dir) = test_store();
+        let mut ids = Vec::new();
+        for (i, text) in ["boiler service invoice", "boiler manual", "garden invoice"].into_iter().enumerate() {
+            let doc_id = store.add_document(text, Default::default()).unwrap();
+            let id = store.add_chunk(doc_id, text, 0, 1, None, None, None, None, None, None).unwrap();
+            let mut emb = Array1::zeros(384);
+            emb[0] = 1.0;
+            emb[i + 1] = 0.1;
+            store.add_chunk_embedding(id, &emb).unwrap();
+            if text.contains("invoice") {
+                store.add_tag(doc_id, "receipts").unwrap();
+            }
+            ids.push(id);
+        }
+        let mut query = Array1::zeros(384);
+        query[0] = 1.0;
 
-        let doc_id = store
-            .add_document(
-                "Test doc",
-                AddDocumentOptions {
-                    metadata: Some(serde_json::json!({"source": "test"})),
-                    ..Default::default()
-                },
-            )
+        let receipts = SearchFilters { tags: vec!["Receipts".into()], ..Default::default() };
+        let bm25: Vec<i64> = store
+            .bm25_search_filtered("boiler", Some(1), 10, None, Some(&receipts))
+            .unwrap()
+            .iter()
+            .map(|h| h.chunk_id)
+            .collect();
+        assert_eq!(bm25, [ids[0]]);
+        let (hybrid, _) = store
+            .hybrid_search_within("invoice", &query, Some(1), 10, 10, 60, Duration::from_secs(5), None, Some(&receipts))
             .unwrap();
+        let mut hybrid: Vec<i64> = hybrid.iter().map(|h| h.chunk_id).collect();
+        hybrid.sort();
+        assert_eq!(hybrid, [ids[0], ids[2]]);
 
-        let updates = serde_json::json!({"topics": ["programming", "rust"]});
-        store.update_document_metadata(doc_id, &updates).unwrap();
-
-        let doc = store.get_document(doc_id).unwrap().unwrap();
-        let meta = doc.metadata.unwrap();
-        assert_eq!(meta["source"], "test");
-        assert_eq!(meta["topics"][0], "programming");
+        let unknown = SearchFilters { tags: vec!["taxes".into()], ..Default::default() };
+        assert!(store.bm25_search_filtered("invoice", Some(1), 10, None, Some(&unknown)).unwrap().is_empty());
     }
 
     #[test]
-    fn test_pagination() {
+    fn test_staged_items_filter_replace_and_expire() {
         let (store, _dir) = test_store();
+        let item = |source_item_id: &str, item_type: &str, text: &str| NewStagedItem {
+            source_item_id: source_item_id.to_string(),
+            item_type: item_type.to_string(),
+            text: text.to_string(),
+            metadata: serde_json::json!({"source": "facebook", "type": item_type}),
+            proposed_topics: vec!["social".to_string()],
+            external_id: None,
+            created_at: Some(1_600_000_000_000),
+        };
+        let items = [item("a", "post", "Harbor festival photos"), item("b", "comment", "ok"), item("c", "comment", "Thanks, see you there")];
+        assert_eq!(store.stage_items("fb", &items).unwrap(), 3);
+        // Staging the same source item again replaces it
+        store.stage_items("fb", &[item("b", "comment", "ok!")]).unwrap();
+        store.stage_items("other", &[item("a", "post", "Not the same connector")]).unwrap();
+
+        let (all, total) = store.list_staged_items("fb", &StagedFilter::default(), 0, 10).unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(all[1].text, "ok!");
+        assert_eq!(all[0].proposed_topics, vec!["social"]);
+        let long_comments = StagedFilter {
+            min_length: Some(5),
+            item_type: Some("Comment".to_string()),
+            ids: None,
+        };
+        let (page, total) = store.list_staged_items("fb", &long_comments, 0, 10).unwrap();
+        assert_eq!((page.len(), total), (1, 1));
+        assert_eq!(page[0].source_item_id, "c");
+        let (page, total) = store.list_staged_items("fb", &StagedFilter::default(), 2, 2).unwrap();
+        assert_eq!((page.len(), total), (1, 3));
+
+        let by_id = StagedFilter {
+            ids: Some(vec![all[0].id, all[1].id]),
+            ..Default::default()
+        };
+        assert_eq!(store.get_staged_items("fb", &by_id).unwrap().len(), 2);
+        assert_eq!(store.delete_staged_items("fb", &by_id).unwrap(), 2);
+        assert_eq!(store.expire_staged_items(0).unwrap(), 0);
+        assert_eq!(store.expire_staged_items(now_millis() + 1).unwrap(), 2);
+        assert!(store.get_staged_items("other", &StagedFilter::default()).unwrap().is_empty());
+    }
 
-        for i in 0..5 {
-            store
-                .add_document(
-                    &format!("Document number {}", i),
-                    AddDocumentOptions {
-                        content_hash: Some(format!("hash_{}", i)),
-                        ..Default::default()
-                    },
-                )
-                .unwrap();
+    #[test]
+    fn test_collections_scope_search() {
+        let (store, _dir) = test_store();
+        let mut docs = Vec::new();
+        for (i, text) in ["quarterly budget review", "budget for the garden shed", "holiday budget"].into_iter().enumerate() {
+            let doc_id = store.add_document(text, Default::default()).unwrap();
+            let id = store.add_chunk(doc_id, text, 0, 1, None, None, None, None, None, None).unwrap();
+            let mut emb = Array1::zeros(384);
+            emb[0] = 1.0;
+            emb[i + 1] = 0.1;
+            store.add_chunk_embedding(id, &emb).unwrap();
+            docs.push(doc_id);
         }
-
-        let (docs, total) = store.get_documents_paginated(1, 2, false).unwrap();
-        assert_eq!(total, 5);
-        assert_eq!(docs.len(), 2);
-
-        let (docs2, _) = store.get_documents_paginated(2, 2, false).unwrap();
-        assert_eq!(docs2.len(), 2);
+        let work = store.create_collection("Work").unwrap().unwrap();
+        let personal = store.create_collection("Personal").unwrap().unwrap();
+        assert!(store.create_collection("Work").unwrap().is_none());
+        assert_eq!(store.add_to_collection(work.id, &[docs[0], 999]).unwrap(), 1);
+        assert_eq!(store.add_to_collection(personal.id, &[docs[1], docs[2], docs[1]]).unwrap(), 2);
+        assert!(matches!(store.add_to_collection(999, &[docs[0]]), Err(Error::NotFound(_))));
+
+        let names: Vec<(String, i64)> =
+            store.list_collections().unwrap().into_iter().map(|c| (c.name, c.document_count)).collect();
+        assert_eq!(names, [("Personal".to_string(), 2), ("Work".to_string(), 1)]);
+
+        // Both stages only see documents of the selected collection
+        let mut query = Array1::zeros(384);
+        query[0] = 1.0;
+        let scoped = SearchFilters { collections: vec![work.id], ..Default::default() };
+        let hits = store.hybrid_search_filtered("budget", &query, None, 10, 10, 60, Some(&scoped)).unwrap();
+        let found: HashSet<i64> = hits.iter().map(|h| h.doc_id).collect();
+        assert_eq!(found, HashSet::from([docs[0]]));
+        let hits = store.bm25_search_filtered("budget", None, 10, None, Some(&scoped)).unwrap();
+        assert_eq!(hits.len(), 1);
+        let (listed, total) = store.list_documents_in_collection(personal.id, 1, 10, true).unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(listed.iter().map(|d| d.id).collect::<Vec<_>>(), [docs[1], docs[2]]);
+
+        // Removing and deleting only drop memberships
+        assert_eq!(store.remove_from_collection(personal.id, &[docs[2], docs[0]]).unwrap(), 1);
+        assert_eq!(store.get_document_collections(docs[1]).unwrap(), [personal.id]);
+        assert!(store.delete_collection(personal.id).unwrap());
+        assert!(!store.delete_collection(personal.id).unwrap());
+        assert_eq!(store.count_documents().unwrap(), 3);
+        assert!(store.get_document_collections(docs[1]).unwrap().is_empty());
+        assert_eq!(store.get_collection(work.id).unwrap().unwrap().document_count, 1);
     }
 
     #[test]
@@ -1136,26 +7074,163 @@ mod tests {
         let c1 = store
             .add_chunk(doc_id, "Unenriched chunk", 0, 1, None, None, None, None, None, None)
             .unwrap();
-
-        // Chunk with enrichment
-        store
-            .add_chunk(
-                doc_id,
-                "Enriched chunk",
-                1,
-                1,
-                None,
-                None,
-                None,
-                Some("topics: test"),
-                None,
-                None,
-            )
+
+        // Chunk with enrichment
+        store
+            .add_chunk(
+                doc_id,
+                "Enriched chunk",
+                1,
+                1,
+                None,
+                None,
+                None,
+                Some("topics: test"),
+                None,
+                None,
+            )
+            .unwrap();
+
+        let pending = store.get_chunks_without_enrichment(10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, c1);
+    }
+
+    #[test]
+    fn test_outdated_extraction_by_version() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Test", Default::default()).unwrap();
+        let ids: Vec<i64> = (0..3)
+            .map(|i| {
+                store
+                    .add_chunk(doc_id, "Deployed the service", i, 1, None, None, None, None, None, None)
+                    .unwrap()
+            })
+            .collect();
+        store.update_chunk_enriched_text(ids[0], "topics: work", 1).unwrap();
+        store.update_chunk_enriched_text(ids[1], "topics: work", 2).unwrap();
+
+        assert_eq!(store.count_outdated_extractions(2).unwrap(), 1);
+        let outdated = store.get_chunks_with_outdated_extraction(3, 0, 10).unwrap();
+        assert_eq!(outdated.iter().map(|c| c.id).collect::<Vec<_>>(), vec![ids[0], ids[1]]);
+        assert_eq!(store.get_chunks_with_outdated_extraction(3, ids[0], 10).unwrap().len(), 1);
+
+        // Re-extraction goes through the update trigger
+        store.update_chunk_enriched_text(ids[0], "topics: kubernetes", 3).unwrap();
+        assert_eq!(store.count_outdated_extractions(3).unwrap(), 1);
+        assert_eq!(store.bm25_search("kubernetes", Some(1), 10).unwrap()[0].chunk_id, ids[0]);
+    }
+
+    #[test]
+    fn test_any_level_finds_section_only_documents() {
+        let (store, _dir) = test_store();
+        // Imported by an older path: sections only, one of them embedded
+        let doc_id = store.add_document("Harbour notes", Default::default()).unwrap();
+        let section = store
+            .add_chunk(doc_id, "The harbour master keeps the mooring list", 0, 0, None, None, None, None, None, None)
+            .unwrap();
+        let emb = Array1::from_iter((0..384).map(|i| (i % 7) as f32));
+        store.add_chunk_embedding(section, &emb).unwrap();
+        let other_doc = store.add_document("Paragraphs", Default::default()).unwrap();
+        let paragraph = store
+            .add_chunk(other_doc, "Mooring fees rise in spring", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+
+        assert!(store.bm25_search("harbour", Some(1), 10).unwrap().is_empty());
+        assert!(store.vector_search(&emb, Some(1), 5).unwrap().is_empty());
+
+        let hits = store.bm25_search("mooring", None, 10).unwrap();
+        let mut ids: Vec<i64> = hits.iter().map(|h| h.chunk_id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![section, paragraph]);
+        let hits = store.vector_search(&emb, None, 5).unwrap();
+        assert_eq!((hits[0].chunk_id, hits[0].level), (section, 0));
+        let hits = store.hybrid_search("harbour", &emb, None, 10, 10, 60).unwrap();
+        assert_eq!(hits[0].chunk_id, section);
+
+        // Appended rows carry their level too
+        let late = store.add_chunk(other_doc, "Late section", 1, 0, None, None, None, None, None, None).unwrap();
+        store.add_chunk_embedding(late, &emb).unwrap();
+        store.append_to_matrix(late, 0, &emb).unwrap();
+        assert_eq!(store.vector_search(&emb, Some(0), 5).unwrap().len(), 2);
+        assert!(store.vector_search(&emb, Some(1), 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_edited_chunk_is_flagged_and_reembedded_in_place() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Ferry notes", Default::default()).unwrap();
+        let edited = store
+            .add_chunk(doc_id, "The ferry leaves at nine", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+        let other = store
+            .add_chunk(doc_id, "Tickets are sold on board", 1, 1, None, None, None, None, None, None)
+            .unwrap();
+        let unembedded = store
+            .add_chunk(doc_id, "Bikes ride free", 2, 1, None, None, None, None, None, None)
             .unwrap();
+        let old = Array1::from_iter((0..384).map(|i| if i == 0 { 1.0 } else { 0.0 }));
+        let new = Array1::from_iter((0..384).map(|i| if i == 1 { 1.0 } else { 0.0 }));
+        for (id, emb) in [(edited, &old), (other, &new)] {
+            store.add_chunk_embedding(id, emb).unwrap();
+            store.append_to_matrix(id, 1, emb).unwrap();
+        }
 
-        let pending = store.get_chunks_without_enrichment(10).unwrap();
-        assert_eq!(pending.len(), 1);
-        assert_eq!(pending[0].id, c1);
+        // Editing the text flags the embedding; an unchanged text does not
+        assert!(store.update_chunk_text(other, "Tickets are sold on board").unwrap());
+        assert!(store.update_chunk_text(edited, "The ferry leaves at ten").unwrap());
+        assert!(!store.update_chunk_text(9999, "Nothing").unwrap());
+        assert_eq!(store.get_stats().unwrap().text_stale_embeddings, 1);
+        let stale = store.get_chunks_with_text_stale_embedding(0, 10).unwrap();
+        assert_eq!(stale.iter().map(|c| c.id).collect::<Vec<_>>(), vec![edited]);
+        assert_eq!(stale[0].text, "The ferry leaves at ten");
+        assert_eq!(store.bm25_search("ten", None, 5).unwrap()[0].chunk_id, edited);
+        let to_embed: Vec<i64> = store.get_chunks_to_embed(10).unwrap().iter().map(|c| c.id).collect();
+        assert_eq!(to_embed, vec![edited, unembedded]);
+
+        // Down-weighting applies to the stale row only
+        assert!((store.vector_search(&old, None, 1).unwrap()[0].score - 1.0).abs() < 1e-3);
+        store.set_text_stale_weight(0.5);
+        let hits = store.vector_search(&old, None, 1).unwrap();
+        assert_eq!(hits[0].chunk_id, edited);
+        assert!((hits[0].score - 0.5).abs() < 1e-3);
+        assert!((store.vector_search(&new, None, 1).unwrap()[0].score - 1.0).abs() < 1e-3);
+
+        // Re-embedding clears the flag and replaces the matrix row
+        let rows = store.get_stats().unwrap().matrix_rows;
+        store.add_chunk_embedding(edited, &new).unwrap();
+        store.append_to_matrix(edited, 1, &new).unwrap();
+        let stats = store.get_stats().unwrap();
+        assert_eq!((stats.text_stale_embeddings, stats.matrix_rows), (0, rows));
+        let hits = store.vector_search(&new, None, 5).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|h| (h.score - 1.0).abs() < 1e-3));
+        assert_eq!(store.get_chunks_to_embed(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_chunks_without_offsets() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Old text. New text.", Default::default()).unwrap();
+        let old = store.add_chunk(doc_id, "Old text.", 0, 1, None, None, None, None, None, None).unwrap();
+        store.add_chunk(doc_id, "New text.", 1, 1, None, Some(10), Some(19), None, None, None).unwrap();
+        let other = store.add_document("Other", Default::default()).unwrap();
+        store.add_chunk(other, "Other", 0, 1, None, Some(0), Some(5), None, None, None).unwrap();
+
+        assert_eq!(store.count_chunks_without_offsets().unwrap(), 1);
+        let missing = store.get_chunks_without_offsets(0, 10).unwrap();
+        assert_eq!(missing.iter().map(|c| c.id).collect::<Vec<_>>(), vec![old]);
+        assert!(store.get_chunks_without_offsets(old, 10).unwrap().is_empty());
+        assert!(!store.document_offsets_complete(doc_id).unwrap());
+        assert!(store.document_offsets_complete(other).unwrap());
+
+        assert!(store.set_chunk_offsets(old, 0, 9).unwrap());
+        assert!(!store.set_chunk_offsets(9999, 0, 1).unwrap());
+        assert_eq!(store.count_chunks_without_offsets().unwrap(), 0);
+        assert!(store.document_offsets_complete(doc_id).unwrap());
+        let chunk = store.get_chunk(old).unwrap().unwrap();
+        assert_eq!((chunk.char_start, chunk.char_end), (Some(0), Some(9)));
+        assert_eq!(store.bm25_search("old", Some(1), 10).unwrap()[0].chunk_id, old);
     }
 
     #[test]
@@ -1184,6 +7259,48 @@ mod tests {
         assert_eq!(stats.embedding_dimension, 384);
     }
 
+    #[test]
+    fn test_source_breakdown_aggregates_per_source() {
+        let (store, _dir) = test_store();
+        let add = |text: &str, metadata: Option<serde_json::Value>| {
+            store
+                .add_document(text, AddDocumentOptions { metadata, ..Default::default() })
+                .unwrap()
+        };
+        let fb_post = add("post", Some(serde_json::json!({"source": "facebook"})));
+        let fb_thread = add("thread", Some(serde_json::json!({"source": "facebook"})));
+        add("chat", Some(serde_json::json!({"source": "chatgpt"})));
+        add("loose", None);
+        add("empty", Some(serde_json::json!({"source": ""})));
+
+        let c1 = store.add_chunk(fb_post, "one", 0, 1, None, None, None, Some("ab"), None, None).unwrap();
+        store.add_chunk(fb_thread, "two", 0, 1, None, None, None, None, None, None).unwrap();
+        store.add_chunk_embedding(c1, &Array1::from_elem(384, 0.5)).unwrap();
+
+        let sources = store.get_source_breakdown().unwrap();
+        let by_source: HashMap<&str, &SourceStats> = sources.iter().map(|s| (s.source.as_str(), s)).collect();
+        assert_eq!(by_source.len(), 3);
+
+        let facebook = by_source["facebook"];
+        assert_eq!((facebook.documents, facebook.chunks, facebook.embeddings), (2, 2, 1));
+        // "post" + "thread" + "one" + "ab" + "two"
+        assert_eq!(facebook.text_bytes, 4 + 6 + 3 + 2 + 3);
+        assert!(facebook.embedding_bytes > 0);
+        assert_eq!(sources[0].source, "facebook");
+
+        let chatgpt = by_source["chatgpt"];
+        assert_eq!((chatgpt.documents, chatgpt.chunks, chatgpt.text_bytes), (1, 0, 4));
+        // No source and an empty one both count as unknown
+        assert_eq!(by_source["unknown"].documents, 2);
+        assert_eq!(by_source["unknown"].embedding_bytes, 0);
+
+        // Reused until the TTL runs out
+        add("more chat", Some(serde_json::json!({"source": "chatgpt"})));
+        assert_eq!(store.get_source_breakdown().unwrap(), sources);
+        let fresh = store.compute_source_breakdown().unwrap();
+        assert_eq!(fresh.iter().find(|s| s.source == "chatgpt").unwrap().documents, 2);
+    }
+
     #[test]
     fn test_vector_search_with_embeddings() {
         let (store, _dir) = test_store();
@@ -1212,17 +7329,821 @@ mod tests {
         store.add_chunk_embedding(c2, &emb2).unwrap();
 
         // Reload matrix
-        store.append_to_matrix(c1, &emb1).unwrap();
-        store.append_to_matrix(c2, &emb2).unwrap();
+        store.append_to_matrix(c1, 1, &emb1).unwrap();
+        store.append_to_matrix(c2, 1, &emb2).unwrap();
 
         // Query similar to emb1
         let mut query = Array1::zeros(384);
         query[0] = 1.0;
         query[1] = 0.3;
 
-        let results = store.vector_search(&query, 1, 5).unwrap();
+        let results = store.vector_search(&query, Some(1), 5).unwrap();
         assert!(!results.is_empty());
         // First result should be chunk 1 (more similar to query)
         assert_eq!(results[0].chunk_id, c1);
     }
+
+    #[test]
+    fn test_wrong_dimension_embeddings_are_rejected() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Dimensions", Default::default()).unwrap();
+        let chunk = store
+            .add_chunk(doc_id, "heat pump defrost cycle", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+
+        // Write side: a 768-dim vector against a 384-dim store
+        let wide = Array1::from_elem(768, 0.1f32);
+        for result in [store.add_chunk_embedding(chunk, &wide), store.append_to_matrix(chunk, 1, &wide)] {
+            match result {
+                Err(Error::DimensionMismatch { expected, actual }) => assert_eq!((expected, actual), (384, 768)),
+                other => panic!("expected a dimension mismatch, got {:?}", other),
+            }
+        }
+        assert_eq!(store.get_stats().unwrap().embeddings_stored, 0);
+
+        let mut emb = Array1::zeros(384);
+        emb[0] = 1.0;
+        store.add_chunk_embedding(chunk, &emb).unwrap();
+        store.append_to_matrix(chunk, 1, &emb).unwrap();
+
+        // Query side: no panic, no vector hits, BM25 still answers
+        assert!(store.vector_search(&wide, Some(1), 5).unwrap().is_empty());
+        let hits = store.hybrid_search("defrost", &wide, Some(1), 10, 10, 60).unwrap();
+        assert_eq!(hits.len(), 1);
+        let (hits, diagnostics) = store
+            .hybrid_search_within("defrost", &wide, Some(1), 10, 10, 60, Duration::from_secs(5), None, None)
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(diagnostics.degradations, vec![Degradation::VectorDimensionMismatch]);
+
+        assert_eq!(store.get_stats().unwrap().dimension_mismatches, 5);
+        assert_eq!(store.vector_search(&emb, Some(1), 5).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_embedding_model_switch_excludes_stale_vectors() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Model switch", Default::default()).unwrap();
+        let c1 = store
+            .add_chunk(doc_id, "Embedded by model A", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+        let c2 = store
+            .add_chunk(doc_id, "Embedded by model B", 1, 1, None, None, None, None, None, None)
+            .unwrap();
+        let mut emb = Array1::zeros(384);
+        emb[0] = 1.0;
+
+        store.set_embedding_model("model-a");
+        store.add_chunk_embedding(c1, &emb).unwrap();
+        assert_eq!(store.vector_search(&emb, Some(1), 5).unwrap().len(), 1);
+
+        store.set_embedding_model("model-b");
+        assert!(store.vector_search(&emb, Some(1), 5).unwrap().is_empty());
+        assert_eq!(store.count_stale_embeddings().unwrap(), 1);
+        let stale = store.get_chunks_with_stale_embedding(0, 10).unwrap();
+        assert_eq!(stale.iter().map(|c| c.id).collect::<Vec<_>>(), vec![c1]);
+        assert!(store.get_chunks_with_stale_embedding(c1, 10).unwrap().is_empty());
+
+        store.add_chunk_embedding(c2, &emb).unwrap();
+        let hits = store.vector_search(&emb, Some(1), 5).unwrap();
+        assert_eq!(hits.iter().map(|h| h.chunk_id).collect::<Vec<_>>(), vec![c2]);
+
+        let stats = store.get_stats().unwrap();
+        assert_eq!(stats.embedding_model, "model-b");
+        assert_eq!(stats.embeddings_by_model.get("model-a"), Some(&1));
+        assert_eq!(stats.embeddings_by_model.get("model-b"), Some(&1));
+    }
+
+    #[test]
+    fn test_legacy_embeddings_migrate_to_unknown_model() {
+        let dir = TempDir::new().unwrap();
+        {
+            let conn = Connection::open(dir.path().join("mindsage.db")).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE documents (id INTEGER PRIMARY KEY AUTOINCREMENT, text TEXT NOT NULL, \
+                     metadata_json TEXT, content_hash TEXT UNIQUE, created_at INTEGER NOT NULL, updated_at INTEGER);
+                 CREATE TABLE chunks (id INTEGER PRIMARY KEY AUTOINCREMENT, doc_id INTEGER NOT NULL, \
+                     parent_chunk_id INTEGER, text TEXT NOT NULL, enriched_text TEXT, chunk_index INTEGER NOT NULL, \
+                     char_start INTEGER, char_end INTEGER, level INTEGER DEFAULT 0, metadata_json TEXT, \
+                     created_at INTEGER NOT NULL);
+                 CREATE TABLE chunk_embeddings (chunk_id INTEGER PRIMARY KEY, embedding BLOB NOT NULL, \
+                     scale REAL NOT NULL, offset_val REAL NOT NULL);
+                 INSERT INTO documents (id, text, created_at) VALUES (1, 'legacy', 0);
+                 INSERT INTO chunks (id, doc_id, text, chunk_index, level, created_at) VALUES (1, 1, 'legacy', 0, 1, 0);
+                 INSERT INTO chunk_embeddings VALUES (1, randomblob(384), 1.0, 0.0);",
+            )
+            .unwrap();
+        }
+
+        let store = SqliteStore::open(dir.path(), 384).unwrap();
+        let stats = store.get_stats().unwrap();
+        assert_eq!(stats.embeddings_by_model.get("unknown"), Some(&1));
+
+        store.set_embedding_model("all-MiniLM-L6-v2:90405214");
+        assert_eq!(store.count_stale_embeddings().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_legacy_embeddings_adopted_by_loaded_model() {
+        let dir = TempDir::new().unwrap();
+        {
+            let conn = Connection::open(dir.path().join("mindsage.db")).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE documents (id INTEGER PRIMARY KEY AUTOINCREMENT, text TEXT NOT NULL, \
+                     metadata_json TEXT, content_hash TEXT UNIQUE, created_at INTEGER NOT NULL, updated_at INTEGER);
+                 CREATE TABLE chunks (id INTEGER PRIMARY KEY AUTOINCREMENT, doc_id INTEGER NOT NULL, \
+                     parent_chunk_id INTEGER, text TEXT NOT NULL, enriched_text TEXT, chunk_index INTEGER NOT NULL, \
+                     char_start INTEGER, char_end INTEGER, level INTEGER DEFAULT 0, metadata_json TEXT, \
+                     created_at INTEGER NOT NULL);
+                 CREATE TABLE chunk_embeddings (chunk_id INTEGER PRIMARY KEY, embedding BLOB NOT NULL, \
+                     scale REAL NOT NULL, offset_val REAL NOT NULL);
+                 INSERT INTO documents (id, text, created_at) VALUES (1, 'legacy', 0);
+                 INSERT INTO chunks (id, doc_id, text, chunk_index, level, created_at) VALUES (1, 1, 'legacy', 0, 1, 0);
+                 INSERT INTO chunks (id, doc_id, text, chunk_index, level, created_at) VALUES (2, 1, 'other', 1, 1, 0);
+                 INSERT INTO chunk_embeddings VALUES (1, randomblob(384), 1.0, 0.0);
+                 INSERT INTO chunk_embeddings VALUES (2, randomblob(768), 1.0, 0.0);",
+            )
+            .unwrap();
+        }
+
+        let store = SqliteStore::open(dir.path(), 384).unwrap();
+        store.set_embedding_model("all-MiniLM-L6-v2:90405214");
+        let query = Array1::from_elem(384, 1.0);
+        assert!(store.vector_search(&query, Some(1), 5).unwrap().is_empty());
+
+        assert_eq!(store.adopt_unknown_embeddings("all-MiniLM-L6-v2:90405214").unwrap(), 1);
+        let hits = store.vector_search(&query, Some(1), 5).unwrap();
+        assert_eq!(hits.iter().map(|h| h.chunk_id).collect::<Vec<_>>(), vec![1]);
+        // The row of another dimension is left for re-embedding
+        assert_eq!(store.count_stale_embeddings().unwrap(), 1);
+        assert_eq!(store.adopt_unknown_embeddings("all-MiniLM-L6-v2:90405214").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_fts_rebuilt_with_accent_folding() {
+        let dir = TempDir::new().unwrap();
+        {
+            let store = SqliteStore::open(dir.path(), 384).unwrap();
+            for (i, text) in ["Weather in Zürich", "Trains from Zu\u{308}rich", "Bern"].into_iter().enumerate() {
+                let doc_id = store.add_document(text, Default::default()).unwrap();
+                store.add_chunk(doc_id, text, i as i32, 1, None, None, None, None, None, None).unwrap();
+            }
+        }
+        // Put back the tokenizer of older databases
+        {
+            let conn = Connection::open(dir.path().join("mindsage.db")).unwrap();
+            conn.execute_batch(
+                "DROP TABLE chunks_fts;
+                 CREATE VIRTUAL TABLE chunks_fts USING fts5(text, enriched_text, content='chunks', content_rowid='id', tokenize='porter unicode61');
+                 CREATE VIRTUAL TABLE chunks_vocab USING fts5vocab(chunks_fts, row);
+                 DROP TRIGGER chunk_words_ai; DROP TRIGGER chunk_words_ad; DROP TRIGGER chunk_words_au;
+                 DROP TABLE chunk_words_vocab; DROP TABLE chunk_words;",
+            )
+            .unwrap();
+            conn.execute_batch(FTS_REFILL_SQL).unwrap();
+        }
+
+        let store = SqliteStore::open(dir.path(), 384).unwrap();
+        let sql: String = store
+            .conn
+            .lock()
+            .query_row("SELECT sql FROM sqlite_master WHERE name = 'chunks_fts'", [], |row| row.get(0))
+            .unwrap();
+        assert!(sql.contains(FTS_TOKENIZER_MARKER));
+        for query in ["zurich", "Zürich", "ZU\u{308}RICH"] {
+            assert_eq!(store.bm25_search(query, Some(1), 10).unwrap().len(), 2, "{}", query);
+        }
+        assert_eq!(store.bm25_search("bern", Some(1), 10).unwrap().len(), 1);
+
+        // Autocomplete words are indexed for the existing chunks
+        let suggestions = store.suggest("we", 10).unwrap();
+        assert_eq!(suggestions.iter().map(|s| s.text.as_str()).collect::<Vec<_>>(), vec!["weather"]);
+        let exists = |name: &str| {
+            store
+                .conn
+                .lock()
+                .query_row("SELECT 1 FROM sqlite_master WHERE name = ?1", [name], |_| Ok(()))
+                .optional()
+                .unwrap()
+                .is_some()
+        };
+        assert!(!exists("chunks_vocab"));
+        assert!(exists("chunk_words_ai"));
+    }
+
+    #[test]
+    fn test_mixed_quant_versions_search_and_requantize() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Quantization", Default::default()).unwrap();
+        let mut ids = Vec::new();
+        for (i, scheme) in [QuantScheme::Legacy, QuantScheme::Int8, QuantScheme::BlockInt8].into_iter().enumerate() {
+            let id = store
+                .add_chunk(doc_id, &format!("chunk {}", i), i as i32, 1, None, None, None, None, None, None)
+                .unwrap();
+            let mut emb = Array1::zeros(384);
+            emb[i] = 1.0;
+            emb[100] = 0.1;
+            store.set_quant_scheme(scheme);
+            store.add_chunk_embedding(id, &emb).unwrap();
+            ids.push(id);
+        }
+
+        // Each row decodes with its own format
+        for (i, id) in ids.iter().enumerate() {
+            let mut query = Array1::zeros(384);
+            query[i] = 1.0;
+            let hits = store.vector_search(&query, Some(1), 1).unwrap();
+            assert_eq!(hits[0].chunk_id, *id);
+            assert!(hits[0].score > 0.98);
+        }
+
+        store.set_quant_scheme(QuantScheme::BlockInt8);
+        assert_eq!(store.requantize_embeddings().unwrap(), 2);
+        assert_eq!(store.requantize_embeddings().unwrap(), 0);
+        let versions: Vec<i64> = {
+            let conn = store.conn.lock();
+            let mut stmt = conn.prepare("SELECT DISTINCT quant_version FROM chunk_embeddings").unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().filter_map(|r| r.ok()).collect()
+        };
+        assert_eq!(versions, vec![QuantScheme::BlockInt8.version()]);
+
+        let mut query = Array1::zeros(384);
+        query[0] = 1.0;
+        assert_eq!(store.vector_search(&query, Some(1), 1).unwrap()[0].chunk_id, ids[0]);
+    }
+
+    #[test]
+    fn test_requantize_pages_past_undecodable_rows() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Quantization", Default::default()).unwrap();
+        store.set_quant_scheme(QuantScheme::Legacy);
+        let mut emb = Array1::zeros(384);
+        emb[0] = 1.0;
+        let mut last = 0;
+        for i in 0..=BULK_BATCH_SIZE {
+            last = store
+                .add_chunk(doc_id, &format!("chunk {}", i), i as i32, 1, None, None, None, None, None, None)
+                .unwrap();
+            store.add_chunk_embedding(last, &emb).unwrap();
+        }
+        // A full batch in a format from a newer build comes first
+        store
+            .conn
+            .lock()
+            .execute("UPDATE chunk_embeddings SET quant_version = 99 WHERE chunk_id < ?1", params![last])
+            .unwrap();
+
+        store.set_quant_scheme(QuantScheme::BlockInt8);
+        assert_eq!(store.requantize_embeddings().unwrap(), 1);
+        let version: i64 = store
+            .conn
+            .lock()
+            .query_row("SELECT quant_version FROM chunk_embeddings WHERE chunk_id = ?1", params![last], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(version, QuantScheme::BlockInt8.version());
+    }
+
+    #[test]
+    fn test_ann_index_matches_brute_force_and_rebuilds_after_deletes() {
+        let (store, dir) = test_store();
+        let docs = [
+            store.add_document("first batch", Default::default()).unwrap(),
+            store.add_document("second batch", Default::default()).unwrap(),
+        ];
+        // Thirty topics with per-chunk noise, so neighbours are not tied
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut noise = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        };
+        let mut embeddings = Vec::new();
+        for i in 0..300 {
+            let doc_id = docs[(i >= 100) as usize];
+            let id = store
+                .add_chunk(doc_id, &format!("chunk {}", i), i as i32, 1, None, None, None, None, None, None)
+                .unwrap();
+            let mut emb = Array1::from_iter((0..384).map(|_| noise() * 0.1));
+            emb[i % 30] += 1.0;
+            store.add_chunk_embedding(id, &emb).unwrap();
+            embeddings.push((id, emb));
+        }
+
+        let top_ids = |query: &Array1<f32>| -> Vec<i64> {
+            store.vector_search(query, Some(1), 5).unwrap().iter().map(|h| h.chunk_id).collect()
+        };
+        store.set_ann_config(AnnConfig { enabled: false, threshold: 200, nprobe: 4 });
+        let exact: Vec<Vec<i64>> = embeddings.iter().step_by(11).map(|(_, e)| top_ids(e)).collect();
+
+        // Searches never build the index; they scan in full until it is built
+        store.set_ann_config(AnnConfig { enabled: true, threshold: 200, nprobe: 4 });
+        assert_eq!(top_ids(&embeddings[0].1), exact[0]);
+        assert!(!dir.path().join(ANN_INDEX_FILE).exists());
+        assert!(store.maintain_ann_index().unwrap());
+        assert!(dir.path().join(ANN_INDEX_FILE).exists());
+        let approx: Vec<Vec<i64>> = embeddings.iter().step_by(11).map(|(_, e)| top_ids(e)).collect();
+        let found: usize = exact
+            .iter()
+            .zip(&approx)
+            .map(|(e, a)| e.iter().filter(|id| a.contains(id)).count())
+            .sum();
+        let recall = found as f64 / (exact.len() * 5) as f64;
+        assert!(recall >= 0.9, "recall@5 = {}", recall);
+        for ((id, _), hits) in embeddings.iter().step_by(11).zip(&approx) {
+            assert_eq!(hits[0], *id);
+        }
+
+        // A third of the indexed rows deleted: consolidation rebuilds once
+        assert!(!store.maintain_ann_index().unwrap());
+        store.delete_document(docs[0]).unwrap();
+        assert!(store.maintain_ann_index().unwrap());
+        assert!(!store.maintain_ann_index().unwrap());
+        let (id, emb) = &embeddings[150];
+        assert_eq!(top_ids(emb)[0], *id);
+    }
+
+    #[test]
+    fn test_ann_search_fills_top_k_at_one_level() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("levels", Default::default()).unwrap();
+        // Thirty topics; half of each topic's chunks are sections
+        let mut sections = Vec::new();
+        for i in 0..300 {
+            let level = if (i / 30) % 2 == 0 { 0 } else { 1 };
+            let id = store
+                .add_chunk(doc_id, &format!("chunk {}", i), i as i32, level, None, None, None, None, None, None)
+                .unwrap();
+            let mut emb = Array1::from_elem(384, 0.001 * (i / 30) as f32);
+            emb[i % 30] += 1.0;
+            store.add_chunk_embedding(id, &emb).unwrap();
+            if level == 0 {
+                sections.push(id);
+            }
+        }
+        store.set_ann_config(AnnConfig { enabled: true, threshold: 200, nprobe: 4 });
+        assert!(store.maintain_ann_index().unwrap());
+
+        let mut query = Array1::zeros(384);
+        query[7] = 1.0;
+        let hits = store.vector_search(&query, Some(0), 5).unwrap();
+        assert_eq!(hits.len(), 5);
+        assert!(hits.iter().all(|h| h.level == 0 && sections.contains(&h.chunk_id)));
+    }
+
+    #[test]
+    fn test_matrix_mode_switch_is_transparent() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Modes", Default::default()).unwrap();
+        let mut embeddings = Vec::new();
+        for i in 0..20 {
+            let id = store
+                .add_chunk(doc_id, &format!("chunk {}", i), i, 1, None, None, None, None, None, None)
+                .unwrap();
+            let mut emb = Array1::from_elem(384, 0.01);
+            emb[i as usize] = 1.0;
+            store.add_chunk_embedding(id, &emb).unwrap();
+            embeddings.push((id, emb));
+        }
+        let float_bytes = {
+            store.vector_search(&embeddings[0].1, Some(1), 1).unwrap();
+            store.get_stats().unwrap().matrix_bytes
+        };
+
+        store.set_matrix_mode(MatrixMode::Quantized);
+        let stats = store.get_stats().unwrap();
+        assert_eq!(stats.matrix_mode, MatrixMode::Quantized);
+        assert_eq!(stats.matrix_rows, 20);
+        assert!(stats.matrix_bytes * 3 < float_bytes);
+        for (id, emb) in &embeddings {
+            assert_eq!(store.vector_search(emb, Some(1), 1).unwrap()[0].chunk_id, *id);
+        }
+
+        // New rows are appended in the active mode
+        let id = store.add_chunk(doc_id, "late", 20, 1, None, None, None, None, None, None).unwrap();
+        let mut emb = Array1::zeros(384);
+        emb[300] = 1.0;
+        store.append_to_matrix(id, 1, &emb).unwrap();
+        assert_eq!(store.vector_search(&emb, Some(1), 1).unwrap()[0].chunk_id, id);
+
+        store.set_matrix_mode(MatrixMode::Float);
+        let hit = &store.vector_search(&embeddings[3].1, Some(1), 1).unwrap()[0];
+        assert_eq!(hit.chunk_id, embeddings[3].0);
+        assert!((hit.score - 1.0).abs() < 1e-3);
+        assert_eq!(store.get_stats().unwrap().matrix_mode, MatrixMode::Float);
+    }
+
+    #[test]
+    fn test_hybrid_search_degrades_to_fit_budget() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Budget", Default::default()).unwrap();
+        let mut query = Array1::zeros(384);
+        for i in 0..40 {
+            let id = store
+                .add_chunk(doc_id, &format!("budget chunk {}", i), i, 1, None, None, None, None, None, None)
+                .unwrap();
+            let mut emb = Array1::zeros(384);
+            emb[i as usize] = 1.0;
+            store.add_chunk_embedding(id, &emb).unwrap();
+            if i == 0 {
+                query = emb;
+            }
+        }
+        let search = |budget_ms: u64| {
+            store
+                .hybrid_search_within("budget", &query, Some(1), 10, 10, 60, Duration::from_millis(budget_ms), None, None)
+                .unwrap()
+        };
+
+        // With no cost estimate yet the full scan runs, and is measured
+        let (_, diagnostics) = search(1000);
+        assert!(diagnostics.degradations.is_empty());
+        assert_eq!(diagnostics.vector_rows_scanned, Some(40));
+        let stages: Vec<SearchStage> = diagnostics.stages.iter().map(|s| s.stage).collect();
+        assert_eq!(stages, vec![SearchStage::Bm25, SearchStage::Vector, SearchStage::Fusion]);
+        assert!(store.vector_ns_per_row.lock().is_some());
+
+        // A full scan estimated at 40 × 25ms = 1s: about half fits in 500ms
+        *store.vector_ns_per_row.lock() = Some(25e6);
+        let (hits, diagnostics) = search(500);
+        assert_eq!(diagnostics.degradations, vec![Degradation::VectorTruncated]);
+        let scanned = diagnostics.vector_rows_scanned.unwrap();
+        assert!((18..=20).contains(&scanned), "scanned {}", scanned);
+        assert!(!hits.is_empty());
+
+        // Nothing left of the budget: vector is skipped, keyword hits remain
+        let (hits, diagnostics) = search(0);
+        assert_eq!(diagnostics.degradations, vec![Degradation::VectorSkipped]);
+        assert_eq!(diagnostics.vector_rows_scanned, Some(0));
+        assert!(diagnostics.over_budget);
+        assert_eq!(hits.len(), 10);
+    }
+
+    #[test]
+    fn test_search_filters_on_chunk_metadata() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Manual", Default::default()).unwrap();
+        let sections = [
+            (["Manual", "Setup"], "install the heater and check the valve"),
+            (["Manual", "Repair"], "replace the valve when the heater leaks"),
+        ];
+        let mut ids = Vec::new();
+        for (i, (path, text)) in sections.iter().enumerate() {
+            let metadata = serde_json::json!({"heading_path": path, "page": i + 1});
+            let id = store
+                .add_chunk(doc_id, text, i as i32, 1, None, None, None, None, Some(&metadata), None)
+                .unwrap();
+            let mut emb = Array1::zeros(384);
+            emb[0] = 1.0;
+            emb[i + 1] = 0.1;
+            store.add_chunk_embedding(id, &emb).unwrap();
+            ids.push(id);
+        }
+        let filter = |key: &str, value: serde_json::Value| ChunkFilter::from([(key.to_string(), value)]);
+
+        // Array fields match any element, scalars match themselves
+        let setup = filter("heading_path", serde_json::json!("Setup"));
+        let hits = store.bm25_search_filtered("valve heater", Some(1), 10, Some(&setup), None).unwrap();
+        assert_eq!(hits.iter().map(|h| h.chunk_id).collect::<Vec<_>>(), vec![ids[0]]);
+        assert_eq!(hits[0].metadata.as_ref().unwrap()["heading_path"][1], "Setup");
+        let page_two = filter("page", serde_json::json!(2));
+        let hits = store.bm25_search_filtered("valve", Some(1), 10, Some(&page_two), None).unwrap();
+        assert_eq!(hits.iter().map(|h| h.chunk_id).collect::<Vec<_>>(), vec![ids[1]]);
+        let shared = filter("heading_path", serde_json::json!("Manual"));
+        assert_eq!(store.bm25_search_filtered("valve", Some(1), 10, Some(&shared), None).unwrap().len(), 2);
+
+        // The vector stage only scores matching chunks
+        let mut query = Array1::zeros(384);
+        query[0] = 1.0;
+        query[1] = 0.1;
+        let repair = filter("heading_path", serde_json::json!("Repair"));
+        let (hits, _) = store
+            .hybrid_search_within("valve", &query, Some(1), 10, 10, 60, Duration::from_secs(5), Some(&repair), None)
+            .unwrap();
+        assert_eq!(hits.iter().map(|h| h.chunk_id).collect::<Vec<_>>(), vec![ids[1]]);
+
+        let bad = filter("heading path", serde_json::json!("x"));
+        assert!(matches!(store.bm25_search_filtered("valve", Some(1), 10, Some(&bad), None), Err(Error::Search(_))));
+    }
+
+    #[test]
+    fn test_search_filters_scope_both_stages() {
+        let (store, _dir) = test_store();
+        let mut ids = Vec::new();
+        for (i, (text, source, filename, created_at)) in [
+            ("heater valve notes 50%_off", "Notes", "Heater_manual.md", 1_000),
+            ("heater valve draft", "notes", "heater-draft.md", 2_000),
+            ("heater valve clipping", "browser", "heater.html", 3_000),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let metadata = serde_json::json!({"source": source, "filename": filename, "topics": ["Home"]});
+            let options = AddDocumentOptions { metadata: Some(metadata), created_at: Some(created_at), ..Default::default() };
+            let doc_id = store.add_document(text, options).unwrap();
+            let id = store.add_chunk(doc_id, text, 0, 1, None, None, None, None, None, None).unwrap();
+            let mut emb = Array1::zeros(384);
+            emb[0] = 1.0;
+            emb[i + 1] = 0.1;
+            store.add_chunk_embedding(id, &emb).unwrap();
+            ids.push(id);
+        }
+        let mut query = Array1::zeros(384);
+        query[0] = 1.0;
+        let search = |text: &str, filters: SearchFilters| {
+            let (mut bm25, mut hybrid): (Vec<i64>, Vec<i64>) = (
+                store.bm25_search_filtered(text, Some(1), 10, None, Some(&filters)).unwrap().iter().map(|h| h.chunk_id).collect(),
+                store
+                    .hybrid_search_within(text, &query, Some(1), 10, 10, 60, Duration::from_secs(5), None, Some(&filters))
+                    .unwrap()
+                    .0
+                    .iter()
+                    .map(|h| h.chunk_id)
+                    .collect(),
+            );
+            bm25.sort();
+            hybrid.sort();
+            assert_eq!(bm25, hybrid, "{:?}", filters);
+            bm25
+        };
+
+        let notes = SearchFilters { sources: vec!["NOTES".into()], ..Default::default() };
+        assert_eq!(search("heater", notes.clone()), ids[..2]);
+        let without_draft = SearchFilters { excluded: vec!["draft".into(), "no such phrase".into()], ..notes };
+        assert_eq!(search("heater", without_draft), ids[..1]);
+        let topic = SearchFilters { topics: vec!["home".into()], created_after: Some(2_000), ..Default::default() };
+        assert_eq!(search("heater", topic), ids[1..]);
+        let before = SearchFilters { created_before: Some(2_000), ..Default::default() };
+        assert_eq!(search("valve", before), ids[..1]);
+        let phrase = SearchFilters { phrases: vec!["valve draft".into()], ..Default::default() };
+        assert_eq!(search("heater", phrase), ids[1..2]);
+        let wrong_order = SearchFilters { phrases: vec!["draft valve".into()], ..Default::default() };
+        assert!(search("heater", wrong_order).is_empty());
+
+        // Filenames match by substring; LIKE wildcards are literal
+        let filename = SearchFilters { filenames: vec!["r_m".into(), "HTML".into()], ..Default::default() };
+        assert_eq!(search("heater", filename), [ids[0], ids[2]]);
+        let wildcard = SearchFilters { filenames: vec!["%".into()], ..Default::default() };
+        assert!(search("heater", wildcard).is_empty());
+    }
+
+    #[test]
+    fn test_vector_search_fetches_hits_in_one_query() {
+        use std::sync::atomic::Ordering;
+
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Batch", Default::default()).unwrap();
+        let mut ids = Vec::new();
+        for i in 0..12 {
+            let id = store
+                .add_chunk(doc_id, &format!("batch chunk {}", i), i, 1, None, None, None, None, None, None)
+                .unwrap();
+            let mut emb = Array1::zeros(384);
+            emb[0] = 1.0;
+            emb[i as usize + 1] = 0.1 * i as f32;
+            store.add_chunk_embedding(id, &emb).unwrap();
+            ids.push(id);
+        }
+
+        // Same chunks, same order as the per-id lookups; missing ids
+        // skipped, repeated ids returned again
+        let mut requested = ids.clone();
+        requested.reverse();
+        requested.insert(3, 99_999);
+        requested.push(ids[0]);
+        let batched = store.get_chunks_by_ids(&requested).unwrap();
+        let single: Vec<Chunk> = requested.iter().filter_map(|&id| store.get_chunk(id).unwrap()).collect();
+        assert_eq!(batched.len(), 13);
+        for (a, b) in batched.iter().zip(&single) {
+            assert_eq!((a.id, &a.text, a.chunk_index), (b.id, &b.text, b.chunk_index));
+        }
+        assert!(store.get_chunks_by_ids(&[]).unwrap().is_empty());
+
+        let mut query = Array1::zeros(384);
+        query[0] = 1.0;
+        store.vector_search(&query, Some(1), 10).unwrap();
+        store.chunk_queries.store(0, Ordering::Relaxed);
+        let hits = store.vector_search(&query, Some(1), 10).unwrap();
+        assert_eq!(hits.len(), 10);
+        assert_eq!(store.chunk_queries.load(Ordering::Relaxed), 1);
+        for hit in &hits {
+            assert_eq!(store.get_chunk(hit.chunk_id).unwrap().unwrap().text, hit.text);
+        }
+    }
+
+    fn encrypted_store(dir: &TempDir, config: EncryptionConfig) -> SqliteStore {
+        SqliteStore::open_with_encryption(dir.path(), 384, Some(config)).unwrap()
+    }
+
+    #[test]
+    fn test_encrypted_round_trip_and_token_search() {
+        let dir = TempDir::new().unwrap();
+        let store = encrypted_store(&dir, EncryptionConfig::new("test key"));
+        let doc_id = store
+            .add_document("Quarterly budget review notes", AddDocumentOptions::default())
+            .unwrap();
+        let chunk_id = store
+            .add_chunk(doc_id, "Quarterly budget review notes", 0, 1, None, None, None, Some("Finance: budget"), None, None)
+            .unwrap();
+
+        let raw: Vec<String> = {
+            let conn = store.conn.lock();
+            let doc: String = conn
+                .query_row("SELECT text FROM documents WHERE id = ?1", params![doc_id], |r| r.get(0))
+                .unwrap();
+            let (text, enriched): (String, String) = conn
+                .query_row("SELECT text, enriched_text FROM chunks WHERE id = ?1", params![chunk_id], |r| {
+                    Ok((r.get(0)?, r.get(1)?))
+                })
+                .unwrap();
+            vec![doc, text, enriched]
+        };
+        assert!(raw.iter().all(|v| v.starts_with("enc1:") && !v.to_lowercase().contains("budget")));
+
+        assert_eq!(store.get_document(doc_id).unwrap().unwrap().text, "Quarterly budget review notes");
+        let chunk = store.get_chunk(chunk_id).unwrap().unwrap();
+        assert_eq!(chunk.enriched_text.as_deref(), Some("Finance: budget"));
+
+        let hits = store.bm25_search("budget", Some(1), 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].text, "Quarterly budget review notes");
+        assert!(store.bm25_search("holiday", Some(1), 10).unwrap().is_empty());
+        assert_eq!(store.get_stats().unwrap().encryption.unwrap().rows_pending, 0);
+        drop(store);
+
+        let vector_only = EncryptionConfig::new("test key").with_search(EncryptedSearch::Disabled);
+        let store = encrypted_store(&dir, vector_only);
+        assert!(store.bm25_search("budget", Some(1), 10).unwrap().is_empty());
+        assert_eq!(store.get_chunk(chunk_id).unwrap().unwrap().text, "Quarterly budget review notes");
+    }
+
+    #[test]
+    fn test_open_encrypted_store_needs_matching_key() {
+        let dir = TempDir::new().unwrap();
+        drop(encrypted_store(&dir, EncryptionConfig::new("right key")));
+
+        let missing = SqliteStore::open(dir.path(), 384);
+        assert!(matches!(missing, Err(Error::Encryption(_))));
+        let wrong = SqliteStore::open_with_encryption(dir.path(), 384, Some(EncryptionConfig::new("wrong key")));
+        assert!(matches!(wrong, Err(Error::Encryption(_))));
+        let store = SqliteStore::open_with_encryption(dir.path(), 384, Some(EncryptionConfig::new("right key"))).unwrap();
+        let conn = store.conn.lock();
+        let secure_delete: i64 = conn.query_row("PRAGMA secure_delete", [], |r| r.get(0)).unwrap();
+        assert_eq!(secure_delete, 1);
+        let salt: String = conn
+            .query_row("SELECT value FROM store_settings WHERE key = ?1", params![SALT_SETTING], |r| r.get(0))
+            .unwrap();
+        assert_eq!(hex::decode(salt).unwrap().len(), 16);
+    }
+
+    #[test]
+    fn test_unsalted_database_moves_to_salted_key() {
+        let dir = TempDir::new().unwrap();
+        let store = SqliteStore::open(dir.path(), 384).unwrap();
+        let doc_id = store.add_document("pending", AddDocumentOptions::default()).unwrap();
+        drop(store);
+
+        // As written before salts were stored: the passphrase's SHA-256
+        let unsalted = EncryptionConfig::new("passphrase").unlock(None);
+        let conn = Connection::open(dir.path().join("mindsage.db")).unwrap();
+        conn.execute(
+            "UPDATE documents SET text = ?1 WHERE id = ?2",
+            params![unsalted.encrypt("travel plans"), doc_id],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO store_settings (key, value) VALUES (?1, ?2)",
+            params![KEY_CHECK_SETTING, unsalted.encrypt(KEY_CHECK_PLAINTEXT)],
+        )
+        .unwrap();
+        drop(conn);
+
+        let store = encrypted_store(&dir, EncryptionConfig::new("passphrase"));
+        assert_eq!(store.get_document(doc_id).unwrap().unwrap().text, "travel plans");
+        let stats = store.get_stats().unwrap().encryption.unwrap();
+        assert_ne!(stats.key_id, unsalted.key_id());
+        assert_eq!(stats.rows_pending, 1);
+        assert_eq!(store.reencrypt(|_| {}).unwrap(), 1);
+        drop(store);
+
+        let store = encrypted_store(&dir, EncryptionConfig::new("passphrase"));
+        assert_eq!(store.get_stats().unwrap().encryption.unwrap().rows_pending, 0);
+        assert_eq!(store.get_document(doc_id).unwrap().unwrap().text, "travel plans");
+    }
+
+    #[test]
+    fn test_reencrypt_migrates_plaintext_and_rotates_keys() {
+        let dir = TempDir::new().unwrap();
+        let store = SqliteStore::open(dir.path(), 384).unwrap();
+        let doc_id = store.add_document("plain note about gardening", AddDocumentOptions::default()).unwrap();
+        store
+            .add_chunk(doc_id, "plain note about gardening", 0, 1, None, None, None, None, None, None)
+            .unwrap();
+        drop(store);
+
+        let store = encrypted_store(&dir, EncryptionConfig::new("first"));
+        assert_eq!(store.get_stats().unwrap().encryption.unwrap().rows_pending, 2);
+        assert_eq!(store.reencrypt(|_| {}).unwrap(), 2);
+        assert_eq!(store.bm25_search("gardening", Some(1), 10).unwrap().len(), 1);
+        drop(store);
+
+        let rotated = EncryptionConfig::new("second").with_previous_key("first");
+        let store = encrypted_store(&dir, rotated);
+        assert_eq!(store.bm25_search("gardening", Some(1), 10).unwrap().len(), 1);
+        assert_eq!(store.reencrypt(|_| {}).unwrap(), 2);
+        assert_eq!(store.get_stats().unwrap().encryption.unwrap().rows_pending, 0);
+        drop(store);
+
+        // The old key is no longer needed, or accepted
+        let store = encrypted_store(&dir, EncryptionConfig::new("second"));
+        assert_eq!(store.get_document(doc_id).unwrap().unwrap().text, "plain note about gardening");
+        drop(store);
+        let old = SqliteStore::open_with_encryption(dir.path(), 384, Some(EncryptionConfig::new("first")));
+        assert!(matches!(old, Err(Error::Encryption(_))));
+    }
+
+    #[test]
+    fn test_indexed_files_import_and_reconcile() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("kept", Default::default()).unwrap();
+        let deleted_id = store.add_document("deleted", Default::default()).unwrap();
+        let file = |path: &str, doc_id: Option<i64>| IndexedFile {
+            path: path.to_string(),
+            mtime: Some(1_700_000_000_000),
+            size: 10,
+            content_hash: None,
+            doc_id,
+            indexed_at: 1_700_000_000_500,
+        };
+
+        store.upsert_indexed_file(&file("/data/imports/a.txt", Some(doc_id))).unwrap();
+        let imported = store
+            .import_indexed_files(&[
+                file("/data/imports/a.txt", Some(999)),
+                file("/data/imports/b.txt", Some(deleted_id)),
+            ])
+            .unwrap();
+        // Existing records win over the import
+        assert_eq!(imported, 1);
+        assert_eq!(store.get_indexed_file("/data/imports/a.txt").unwrap().unwrap().doc_id, Some(doc_id));
+
+        store.delete_document(deleted_id).unwrap();
+        assert_eq!(store.reconcile_indexed_files().unwrap(), 1);
+        assert!(store.get_indexed_file("/data/imports/b.txt").unwrap().is_none());
+        assert_eq!(store.count_indexed_files().unwrap(), 1);
+
+        store.upsert_indexed_file(&file("/data/uploads/c.txt", None)).unwrap();
+        let under: Vec<String> = store
+            .get_indexed_files_under("/data/imports/")
+            .unwrap()
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        assert_eq!(under, vec!["/data/imports/a.txt"]);
+        assert!(store.delete_indexed_file("/data/imports/a.txt").unwrap());
+        assert!(!store.delete_indexed_file("/data/imports/a.txt").unwrap());
+
+        // Windows paths match whichever separator they were recorded with
+        store.upsert_indexed_file(&file("c:\\data\\imports\\d.txt", None)).unwrap();
+        let record = store.get_indexed_file("C:/data/imports/d.txt").unwrap().unwrap();
+        assert_eq!(record.path, "C:/data/imports/d.txt");
+        assert_eq!(store.get_indexed_files_under("C:\\data\\imports\\").unwrap().len(), 1);
+        assert!(store.delete_indexed_file("C:\\data\\imports\\d.txt").unwrap());
+    }
+
+    #[test]
+    fn test_corrupt_rows_are_reported_not_dropped() {
+        let (store, _dir) = test_store();
+        let doc_id = store.add_document("Boiler manual", AddDocumentOptions::default()).unwrap();
+        store.add_chunk(doc_id, "Bleed the radiators", 0, 1, None, None, None, None, None, None).unwrap();
+        let bad_chunk = store.add_chunk(doc_id, "Check the valve", 1, 1, None, None, None, None, None, None).unwrap();
+        assert_eq!(store.get_chunks_for_document(doc_id).unwrap().len(), 2);
+
+        // Malformed metadata on one chunk fails the query and names the row
+        store
+            .conn
+            .lock()
+            .execute("UPDATE chunks SET metadata_json = '{broken' WHERE id = ?1", params![bad_chunk])
+            .unwrap();
+        let err = store.get_chunks_for_document(doc_id).unwrap_err().to_string();
+        assert!(err.contains(&format!("chunks rowid {}", bad_chunk)), "{}", err);
+        assert_eq!(store.get_stats().unwrap().corrupt_rows, 1);
+
+        // So does invalid UTF-8 or a NULL where a value is required
+        store
+            .conn
+            .lock()
+            .execute_batch(&format!(
+                "UPDATE chunks SET metadata_json = NULL, text = CAST(X'FF00' AS TEXT) WHERE id = {bad_chunk};
+                 UPDATE documents SET metadata_json = '[1,' WHERE id = {doc_id};"
+            ))
+            .unwrap();
+        assert!(store.get_chunks_for_document(doc_id).is_err());
+        let err = store.get_all_documents(true).unwrap_err().to_string();
+        assert!(err.contains(&format!("documents rowid {}", doc_id)), "{}", err);
+        assert!(store.get_document(doc_id).is_err());
+        assert_eq!(store.get_stats().unwrap().corrupt_rows, 3);
+    }
 }
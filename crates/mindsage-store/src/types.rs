@@ -1,72 +1,435 @@
 //! Data types for documents, chunks, and search results.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-/// A document row from the database.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Document {
-    pub id: i64,
-    pub text: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub metadata: Option<serde_json::Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub content_hash: Option<String>,
-    pub created_at: i64,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub updated_at: Option<i64>,
-}
+// Rows and diagnostics that are also API wire types
+pub use mindsage_api_types::{
+    Chunk, ChunkFilter, DateFacet, Degradation, Document, DocumentHash, SearchDiagnostics, SearchStage, SearchSyntax,
+    StageTiming,
+};
 
-impl Document {
-    /// Parse metadata JSON into a map.
-    pub fn metadata_map(&self) -> HashMap<String, serde_json::Value> {
-        match &self.metadata {
-            Some(v) => serde_json::from_value(v.clone()).unwrap_or_default(),
-            None => HashMap::new(),
-        }
-    }
-}
+use crate::crypto::EncryptedSearch;
+use crate::matrix::MatrixMode;
 
-/// A chunk row from the database.
+/// Intermediate search result before fusion.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Chunk {
-    pub id: i64,
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SearchHit {
+    pub chunk_id: i64,
     pub doc_id: i64,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub parent_chunk_id: Option<i64>,
     pub text: String,
+    pub score: f64,
+    pub level: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enriched_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_chunk_id: Option<i64>,
     pub chunk_index: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub char_start: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub char_end: Option<i32>,
-    pub level: i32,
+}
+
+/// A document ranked by similarity to another document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SimilarDocument {
+    pub doc_id: i64,
+    pub score: f64,
+    /// "centroid" (embedding cosine) or "bm25" (top-term fallback).
+    pub method: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<serde_json::Value>,
+}
+
+/// Indexed state of a file under uploads/ or imports/.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedFile {
+    pub path: String,
+    /// Modification time when indexed, Unix milliseconds.
+    pub mtime: Option<i64>,
+    pub size: i64,
+    pub content_hash: Option<String>,
+    pub doc_id: Option<i64>,
+    /// Unix milliseconds.
+    pub indexed_at: i64,
+}
+
+/// A persisted search re-run after new documents are indexed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: String,
+    pub query: String,
+    /// Metadata equality filters applied to hits (`{"source": "gmail"}`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filters: Option<serde_json::Value>,
+    pub top_k: usize,
+    /// Unacknowledged new matches since the last acknowledgement.
+    pub new_matches: i64,
     pub created_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_checked_at: Option<i64>,
 }
 
-/// Intermediate search result before fusion.
+/// A chunk reported by a saved search.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SearchHit {
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SavedSearchMatch {
     pub chunk_id: i64,
     pub doc_id: i64,
     pub text: String,
-    pub score: f64,
-    pub level: i32,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub metadata: Option<serde_json::Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub enriched_text: Option<String>,
+    pub is_new: bool,
+    pub seen_at: i64,
+}
+
+/// An autocomplete suggestion for the search box.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Suggestion {
+    /// The full completed input (typed prefix + completed last token).
+    pub text: String,
+    /// "history" (a past query) or "vocab" (an indexed term).
+    pub source: String,
+    /// Use count for history, document frequency for vocab.
+    pub score: i64,
+}
+
+/// Selects documents for bulk operations. Criteria are ANDed; a selector
+/// without any criteria matches nothing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentSelector {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ids: Vec<i64>,
+    /// Exact `metadata.source`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Member of the `metadata.topics` array.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// `created_at >= created_after` (epoch ms).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<i64>,
+    /// `created_at < created_before` (epoch ms).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash_prefix: Option<String>,
+    /// Top-level `metadata` key that is present, e.g.
+    /// `embedding_truncation`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_metadata: Option<String>,
+}
+
+impl DocumentSelector {
+    /// Whether no criteria are set.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+            && self.source.is_none()
+            && self.topic.is_none()
+            && self.created_after.is_none()
+            && self.created_before.is_none()
+            && self.content_hash_prefix.as_deref().map_or(true, str::is_empty)
+            && self.has_metadata.as_deref().map_or(true, str::is_empty)
+    }
+}
+
+/// Longest tag, in characters.
+pub const MAX_TAG_CHARS: usize = 64;
+
+/// Normalize a tag: trimmed and lowercased. Returns `None` when it is empty,
+/// longer than [`MAX_TAG_CHARS`], or contains whitespace or control
+/// characters, so a tag is always one query-language token.
+pub fn normalize_tag(raw: &str) -> Option<String> {
+    let tag = raw.trim().to_lowercase();
+    if tag.is_empty()
+        || tag.chars().count() > MAX_TAG_CHARS
+        || tag.chars().any(|c| c.is_whitespace() || c.is_control())
+    {
+        return None;
+    }
+    Some(tag)
+}
+
+/// Longest collection name, in characters.
+pub const MAX_COLLECTION_NAME_CHARS: usize = 100;
+
+/// A named group of documents (a notebook) that scopes search and chat.
+/// A document can be in any number of collections.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+    pub created_at: i64,
+    pub document_count: i64,
+}
+
+/// A connector item waiting in the staging table for approval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct StagedItem {
+    pub id: i64,
+    pub connector_id: String,
+    /// Id of the item in its export (external id or export file name).
+    pub source_item_id: String,
+    /// `metadata.type` (post, comment, ...), or the source when it has none.
+    pub item_type: String,
+    pub text: String,
+    pub text_length: i64,
+    #[cfg_attr(feature = "openapi", schema(value_type = Object))]
+    pub metadata: serde_json::Value,
+    pub proposed_topics: Vec<String>,
+    pub external_id: Option<String>,
+    /// When the item was written, from the export (Unix ms).
+    pub created_at: Option<i64>,
+    pub staged_at: i64,
+}
+
+/// An item to stage; see [`StagedItem`].
+#[derive(Debug, Clone)]
+pub struct NewStagedItem {
+    pub source_item_id: String,
+    pub item_type: String,
+    pub text: String,
+    pub metadata: serde_json::Value,
+    pub proposed_topics: Vec<String>,
+    pub external_id: Option<String>,
+    pub created_at: Option<i64>,
+}
+
+/// Selects staged items of a connector. Empty fields match everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StagedFilter {
+    /// Only items with at least this many characters.
+    pub min_length: Option<usize>,
+    /// Only items of this type, ignoring case.
+    pub item_type: Option<String>,
+    /// Only these ids; `None` leaves ids unrestricted.
+    pub ids: Option<Vec<i64>>,
+}
+
+/// What deleting a set of documents takes with it, for a forget preview.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentFootprint {
+    /// Chunks of the documents, section and paragraph.
+    pub chunk_ids: Vec<i64>,
+    pub embeddings: usize,
+    /// `doc_topics` rows: the documents' edges in the topic graph.
+    pub topic_links: usize,
+}
+
+/// One search, to be recorded in the query stats log.
+#[derive(Debug, Clone, Default)]
+pub struct QuerySample {
+    pub query: String,
+    /// How it ran, e.g. `hybrid`, `bm25`, `enhanced_hybrid`, `topic`
+    /// or `resolve_keyword`.
+    pub search_type: String,
+    pub result_count: usize,
+    pub top_score: Option<f32>,
+    pub latency_ms: f64,
+    /// Kept only for slow or zero-hit searches in capture mode. Must not
+    /// hold the query text, except in `warnings`, which hashed mode drops.
+    pub diagnostics: Option<serde_json::Value>,
+}
+
+/// A search recorded in the query stats log.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LoggedQuery {
+    pub id: i64,
+    /// The normalized query, or its SHA-256 (hex) when `hashed`.
+    pub query: String,
+    pub hashed: bool,
+    pub search_type: String,
+    pub result_count: usize,
+    pub top_score: Option<f64>,
+    pub latency_ms: f64,
+    /// Unix millis.
+    pub created_at: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub parent_chunk_id: Option<i64>,
-    pub chunk_index: i32,
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub diagnostics: Option<serde_json::Value>,
+}
+
+/// How often one query was searched.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct QueryCount {
+    pub query: String,
+    pub hashed: bool,
+    pub count: usize,
+    /// Searches of it that found nothing.
+    pub zero_results: usize,
+    pub avg_results: f64,
+}
+
+/// Nearest-rank latency percentiles, in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LatencyPercentiles {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+/// Aggregates of the query stats log since a time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct QueryStats {
+    /// Unix millis.
+    pub since: i64,
+    pub searches: usize,
+    pub zero_results: usize,
+    /// `zero_results / searches`, 0 without searches.
+    pub zero_result_rate: f64,
+    pub latency_ms: LatencyPercentiles,
+    pub by_search_type: BTreeMap<String, usize>,
+    /// Most searched queries, most often first.
+    pub top_queries: Vec<QueryCount>,
+    /// Queries that found nothing, most often first.
+    pub zero_result_queries: Vec<QueryCount>,
+}
+
+/// Rows outside the documents that mention a forgotten term.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MentionCounts {
+    /// Logged search queries (autocomplete suggestions and query stats).
+    pub search_queries: usize,
+    pub saved_searches: usize,
+    /// Connector items waiting in staging.
+    pub staged_items: usize,
+}
+
+/// Document and text scopes of a search, from the query language. Values of
+/// one field are alternatives; different fields must all match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchFilters {
+    /// `metadata.source`, ignoring case.
+    pub sources: Vec<String>,
+    /// Member of the document's topics, ignoring case.
+    pub topics: Vec<String>,
+    /// Member of the document's tags, ignoring case.
+    pub tags: Vec<String>,
+    /// Id of one of the document's collections.
+    pub collections: Vec<i64>,
+    /// Substring of `metadata.filename`, ignoring case.
+    pub filenames: Vec<String>,
+    /// `metadata.lang`, ignoring case.
+    pub langs: Vec<String>,
+    /// `created_at >= created_after` (epoch ms).
+    pub created_after: Option<i64>,
+    /// `created_at < created_before` (epoch ms).
+    pub created_before: Option<i64>,
+    /// Phrases every hit must contain.
+    pub phrases: Vec<String>,
+    /// Words or phrases no hit may contain.
+    pub excluded: Vec<String>,
+}
+
+impl SearchFilters {
+    /// Whether no scope is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether any document-level scope is set.
+    pub(crate) fn scopes_documents(&self) -> bool {
+        !(self.sources.is_empty()
+            && self.topics.is_empty()
+            && self.tags.is_empty()
+            && self.collections.is_empty()
+            && self.filenames.is_empty()
+            && self.langs.is_empty())
+            || self.created_after.is_some()
+            || self.created_before.is_some()
+    }
+}
+
+/// BM25 column weights of the full-text index: matches in a chunk's text
+/// count `text` times, matches in its extracted `enriched_text` (topics,
+/// entities, keywords) count `enriched` times.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FtsWeights {
+    pub text: f64,
+    pub enriched: f64,
+}
+
+impl FtsWeights {
+    /// Whether both weights are finite and non-negative, and one is positive.
+    pub fn is_valid(&self) -> bool {
+        let valid = |w: f64| w.is_finite() && w >= 0.0;
+        valid(self.text) && valid(self.enriched) && self.text + self.enriched > 0.0
+    }
+}
+
+impl Default for FtsWeights {
+    /// Prose outranks a keyword list repeating the query's words.
+    fn default() -> Self {
+        let (text, enriched) = mindsage_core::config::DEFAULT_FTS_WEIGHTS;
+        Self { text, enriched }
+    }
+}
+
+/// Aggregate statistics for one topic.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TopicStats {
+    pub topic: String,
+    pub doc_count: i64,
+    pub chunk_count: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub char_start: Option<i32>,
+    pub first_created_at: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub char_end: Option<i32>,
+    pub last_created_at: Option<i64>,
+    /// Most frequent extracted entities across the topic's chunks.
+    pub top_entities: Vec<EntityCount>,
+}
+
+/// An entity and the number of chunks mentioning it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EntityCount {
+    pub entity: String,
+    pub count: i64,
+}
+
+/// Two topics and the number of documents tagged with both.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TopicPair {
+    pub a: String,
+    pub b: String,
+    pub count: i64,
+}
+
+/// Storage taken by the documents of one source (`metadata.source`;
+/// `unknown` when absent).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SourceStats {
+    pub source: String,
+    pub documents: i64,
+    pub chunks: i64,
+    pub embeddings: i64,
+    /// Bytes of document, chunk and enriched chunk text.
+    pub text_bytes: i64,
+    /// Bytes of stored embedding vectors.
+    pub embedding_bytes: i64,
 }
 
 /// Store-level statistics.
@@ -77,11 +440,104 @@ pub struct StoreStats {
     pub paragraph_chunks: i64,
     pub section_chunks: i64,
     pub embeddings_stored: i64,
+    /// Stored embeddings per producing model id.
+    pub embeddings_by_model: BTreeMap<String, i64>,
+    /// Embeddings whose chunk text changed after embedding, waiting to be
+    /// re-embedded.
+    pub text_stale_embeddings: i64,
+    /// Model id of the active embedder; other models' embeddings are not searched.
+    pub embedding_model: String,
     pub embedding_dimension: usize,
     pub db_path: String,
     pub db_size_mb: f64,
     pub matrix_loaded: bool,
     pub matrix_rows: usize,
+    /// Whether the in-memory matrix holds float or int8 rows.
+    pub matrix_mode: MatrixMode,
+    /// Heap bytes held by the in-memory matrix.
+    pub matrix_bytes: usize,
+    /// Field encryption state; `None` when text is stored in the clear.
+    pub encryption: Option<EncryptionStats>,
+    /// Rows that could not be read (malformed JSON, NULL or invalid UTF-8
+    /// in a column, undecryptable text) since the store was opened.
+    pub corrupt_rows: u64,
+    /// Embeddings rejected, and query embeddings ignored, for not having
+    /// the store's dimension since the store was opened.
+    pub dimension_mismatches: u64,
+}
+
+/// Field encryption state of the store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionStats {
+    /// Fingerprint of the active key.
+    pub key_id: String,
+    pub search: EncryptedSearch,
+    /// Documents and chunks still in plaintext or under an older key.
+    pub rows_pending: i64,
+}
+
+/// Position in the documents ordered by `(created_at, id)`, for keyset
+/// pagination. Written as `<created_at>.<id>` in URLs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DocumentCursor {
+    pub created_at: i64,
+    pub id: i64,
+}
+
+impl DocumentCursor {
+    /// The cursor just past `doc`.
+    pub fn after(doc: &Document) -> Self {
+        Self {
+            created_at: doc.created_at,
+            id: doc.id,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let (created_at, id) = value.rsplit_once('.')?;
+        Some(Self {
+            created_at: created_at.parse().ok()?,
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+impl std::fmt::Display for DocumentCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.created_at, self.id)
+    }
+}
+
+/// A step of the ingest journal (`pending_work`) still owed to a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PendingStep {
+    /// Embed its paragraph chunks.
+    Embed,
+    /// Run heuristic extraction on its chunks.
+    Enrich,
+}
+
+impl PendingStep {
+    pub const ALL: [PendingStep; 2] = [PendingStep::Embed, PendingStep::Enrich];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Embed => "embed",
+            Self::Enrich => "enrich",
+        }
+    }
+}
+
+/// How far a catch-up pass over the ingest journal got through `step`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatchupCheckpoint {
+    pub step: PendingStep,
+    /// Last document the pass reached; it goes on after this one.
+    pub after_doc_id: i64,
+    /// Documents the pass went through, whether the step succeeded or not.
+    pub done: usize,
+    /// Unix ms.
+    pub started_at: i64,
 }
 
 /// Options for adding a document.
@@ -90,4 +546,88 @@ pub struct AddDocumentOptions {
     pub metadata: Option<serde_json::Value>,
     pub content_hash: Option<String>,
     pub created_at: Option<i64>,
+    /// Namespace of `external_id`, e.g. `chatgpt` or a connector name.
+    pub source: Option<String>,
+    /// Stable id of the item at its source, unique within `source`. Set, it
+    /// lets `upsert_document_by_external_id` find the document again after
+    /// its content (and so its content hash) changed.
+    pub external_id: Option<String>,
+}
+
+/// A chunk inserted together with its document by
+/// `add_documents_transactional`.
+#[derive(Debug, Clone)]
+pub struct NewChunk {
+    pub text: String,
+    pub chunk_index: i32,
+    pub level: i32,
+    /// `chunk_index` of the section chunk (level 0) of the same document
+    /// this chunk belongs to.
+    pub parent_index: Option<i32>,
+    pub char_start: Option<i32>,
+    pub char_end: Option<i32>,
+    /// Stored as `chunks.metadata_json` (heading path, page, speaker).
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// A document and its chunks for `add_documents_transactional`.
+#[derive(Debug, Clone)]
+pub struct NewDocument {
+    pub text: String,
+    pub options: AddDocumentOptions,
+    pub chunks: Vec<NewChunk>,
+}
+
+/// What `upsert_document_by_external_id` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsertOutcome {
+    /// No document had the external id; a new one was added.
+    Inserted(i64),
+    /// The document's text, metadata and chunks were replaced.
+    Updated(i64),
+    /// The document already had this content hash; nothing was written.
+    Unchanged(i64),
+}
+
+impl UpsertOutcome {
+    pub fn doc_id(&self) -> i64 {
+        match *self {
+            UpsertOutcome::Inserted(id) | UpsertOutcome::Updated(id) | UpsertOutcome::Unchanged(id) => id,
+        }
+    }
+}
+
+/// What happened to one document of a committed transactional batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchItemOutcome {
+    Added { doc_id: i64 },
+    SkippedDuplicate,
+}
+
+/// Why a transactional batch was rolled back.
+#[derive(Debug)]
+pub struct BatchRollback {
+    /// Position of the document that failed; `None` when the transaction
+    /// itself could not be opened or committed.
+    pub index: Option<usize>,
+    pub error: mindsage_core::Error,
+}
+
+/// Check that a filter can be pushed down: keys are dotted paths of
+/// letters, digits and underscores, values are strings, numbers or
+/// booleans.
+pub fn check_chunk_filter(filter: &ChunkFilter) -> std::result::Result<(), String> {
+    for (key, value) in filter {
+        let valid_key = !key.is_empty()
+            && key
+                .split('.')
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        if !valid_key {
+            return Err(format!("Invalid chunk filter key: {:?}", key));
+        }
+        if !(value.is_string() || value.is_number() || value.is_boolean()) {
+            return Err(format!("Chunk filter value for {:?} must be a string, number or boolean", key));
+        }
+    }
+    Ok(())
 }